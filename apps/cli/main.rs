@@ -0,0 +1,508 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process::exit;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use clap::{Parser, Subcommand};
+use serde_json::{json, Map, Value as Json};
+
+use boson::{
+    Id,
+    DID_PREFIX,
+    PeerInfo,
+    Value,
+    CryptoIdentity,
+    signature::{self, KeyPair, PrivateKey},
+    did::{Credential, Vouch, Card},
+    messaging::InviteTicket,
+};
+
+#[derive(Parser, Debug)]
+#[command(name = "boson-cli")]
+#[command(version = "1.0")]
+#[command(about = "Boson keys, ids and offline DID tools", long_about = None)]
+struct Options {
+    /// Print machine-readable JSON
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate a new key pair
+    Keygen {
+        /// Write the private key to FILE
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Print the id and DID of a private key, an id or a DID
+    Id {
+        /// The key, id or DID file, stdin when omitted
+        input: Option<String>,
+    },
+
+    /// Sign a file with a private key
+    Sign {
+        /// The private key file
+        #[arg(short, long, value_name = "FILE")]
+        key: String,
+
+        /// The file to sign, stdin when omitted
+        input: Option<String>,
+    },
+
+    /// Verify the signature of a file
+    Verify {
+        /// The id or DID of the signer
+        #[arg(long, value_name = "ID")]
+        signer: String,
+
+        /// The signature, in Base58 or 0x prefixed hex
+        #[arg(long, value_name = "SIG")]
+        signature: String,
+
+        /// The signed file, stdin when omitted
+        input: Option<String>,
+    },
+
+    /// Issue and verify credentials
+    #[command(subcommand)]
+    Credential(CredentialCommand),
+
+    /// Inspect invite tickets
+    #[command(subcommand)]
+    Ticket(TicketCommand),
+
+    /// Inspect values
+    #[command(subcommand)]
+    Value(ValueCommand),
+
+    /// Inspect peer records
+    #[command(subcommand)]
+    Peer(PeerCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum CredentialCommand {
+    /// Issue a credential signed by a private key
+    Issue {
+        /// The private key file of the issuer
+        #[arg(short, long, value_name = "FILE")]
+        key: String,
+
+        /// The credential id
+        #[arg(long)]
+        id: String,
+
+        /// The id or DID of the subject, the issuer when omitted
+        #[arg(long, value_name = "ID")]
+        subject: Option<String>,
+
+        /// A credential type, may be repeated
+        #[arg(long = "type", value_name = "TYPE")]
+        types: Vec<String>,
+
+        #[arg(long)]
+        name: Option<String>,
+
+        #[arg(long)]
+        description: Option<String>,
+
+        /// A claim as NAME=VALUE, VALUE is taken as JSON when it parses
+        #[arg(long = "claim", value_name = "NAME=VALUE", required = true)]
+        claims: Vec<String>,
+
+        /// Days the credential stays valid, forever when omitted
+        #[arg(long, value_name = "DAYS")]
+        valid_days: Option<u64>,
+
+        /// Write the credential to FILE
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+
+    /// Verify a credential, vouch or card
+    Verify {
+        /// The JSON document, stdin when omitted
+        input: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TicketCommand {
+    /// Decode an invite ticket in hex or Base58
+    Decode {
+        /// The ticket file, stdin when omitted
+        input: Option<String>,
+
+        /// Check the ticket was issued to this id or DID
+        #[arg(long, value_name = "ID")]
+        invitee: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ValueCommand {
+    /// Decode a value in Base58, hex or raw CBOR
+    Decode {
+        /// The value file, stdin when omitted
+        input: Option<String>,
+
+        /// The private key file of the recipient, to decrypt the data
+        #[arg(short, long, value_name = "FILE")]
+        key: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum PeerCommand {
+    /// Decode a peer record in Base58, hex or raw CBOR
+    Decode {
+        /// The peer file, stdin when omitted
+        input: Option<String>,
+    },
+}
+
+// The fields of a report, printed in order or as a JSON object.
+type Report = Vec<(&'static str, Json)>;
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {message}");
+    exit(1)
+}
+
+fn print_report(report: Report, as_json: bool) {
+    if as_json {
+        let object: Map<String, Json> = report.into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&object).unwrap());
+        return;
+    }
+
+    for (key, value) in report {
+        match value {
+            Json::Null => continue,
+            Json::String(v) => println!("{key:<14}: {v}"),
+            v => println!("{key:<14}: {v}"),
+        }
+    }
+}
+
+fn read_input(path: Option<&str>) -> Vec<u8> {
+    match path {
+        None | Some("-") => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data).unwrap_or_else(|e| {
+                fail(format!("Reading stdin failed: {e}"))
+            });
+            data
+        },
+        Some(path) => fs::read(path).unwrap_or_else(|e| {
+            fail(format!("Reading {path} failed: {e}"))
+        }),
+    }
+}
+
+fn read_text(path: Option<&str>) -> String {
+    String::from_utf8(read_input(path)).unwrap_or_else(|_| {
+        fail("Input is not UTF-8 text")
+    }).trim().to_string()
+}
+
+fn write_output(path: &str, data: &[u8]) {
+    fs::write(path, data).unwrap_or_else(|e| {
+        fail(format!("Writing {path} failed: {e}"))
+    })
+}
+
+fn read_keypair(path: &str) -> KeyPair {
+    let text = read_text(Some(path));
+    PrivateKey::try_from(text.as_str())
+        .map(KeyPair::from)
+        .unwrap_or_else(|e| fail(format!("Invalid private key in {path}: {e}")))
+}
+
+fn parse_id(input: &str) -> boson::Result<Id> {
+    match input.starts_with(DID_PREFIX) {
+        true => Id::try_from_did_string(input),
+        false => Id::try_from(input),
+    }
+}
+
+// Text in 0x prefixed or bare hex, or Base58, falls back to raw bytes.
+fn decode_text(input: &[u8]) -> Vec<u8> {
+    let Some(text) = std::str::from_utf8(input).ok().map(|v| v.trim()) else {
+        return input.to_vec();
+    };
+    if let Some(hex) = text.strip_prefix("0x") {
+        return hex::decode(hex).unwrap_or_else(|e| fail(format!("Invalid hex input: {e}")));
+    }
+    if text.len().is_multiple_of(2) && text.bytes().all(|b| b.is_ascii_hexdigit()) {
+        if let Ok(bytes) = hex::decode(text) {
+            return bytes;
+        }
+    }
+    bs58::decode(text).into_vec().unwrap_or_else(|_| input.to_vec())
+}
+
+fn decode_signature(input: &str) -> Vec<u8> {
+    let sig = match input.strip_prefix("0x") {
+        Some(hex) => hex::decode(hex).ok(),
+        None => bs58::decode(input).into_vec().ok(),
+    };
+    sig.unwrap_or_else(|| fail("Invalid signature encoding"))
+}
+
+fn secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn id_report(id: &Id) -> Report {
+    vec![
+        ("id",          json!(id.to_base58())),
+        ("did",         json!(id.to_did_string())),
+        ("hex",         json!(id.to_hexstr())),
+    ]
+}
+
+fn keygen(output: Option<String>) -> Report {
+    let keypair = KeyPair::random();
+    let id = Id::from(keypair.public_key());
+    let sk = keypair.private_key().to_hexstr();
+
+    let mut report = id_report(&id);
+    report.push(("publicKey", json!(keypair.public_key().to_string())));
+    match output {
+        Some(path) => {
+            write_output(&path, sk.as_bytes());
+            report.push(("privateKeyFile", json!(path)));
+        },
+        None => report.push(("privateKey", json!(sk))),
+    }
+    report
+}
+
+fn id(input: Option<String>) -> Report {
+    let text = read_text(input.as_deref());
+    if let Ok(id) = parse_id(&text) {
+        return id_report(&id);
+    }
+
+    let sk = PrivateKey::try_from(text.as_str()).unwrap_or_else(|_| {
+        fail("Input is not a private key, id or DID")
+    });
+    let keypair = KeyPair::from(sk);
+    let mut report = id_report(&Id::from(keypair.public_key()));
+    report.push(("publicKey", json!(keypair.public_key().to_string())));
+    report
+}
+
+fn sign(key: String, input: Option<String>) -> Report {
+    let keypair = read_keypair(&key);
+    let data = read_input(input.as_deref());
+    let sig = signature::sign_into(&data, keypair.private_key())
+        .unwrap_or_else(|e| fail(format!("Signing failed: {e}")));
+
+    vec![
+        ("signer",      json!(Id::from(keypair.public_key()).to_base58())),
+        ("signature",   json!(bs58::encode(sig).into_string())),
+    ]
+}
+
+fn verify(signer: String, sig: String, input: Option<String>) -> (Report, bool) {
+    let signer = parse_id(&signer).unwrap_or_else(|e| fail(format!("Invalid signer: {e}")));
+    let sig = decode_signature(&sig);
+    let data = read_input(input.as_deref());
+    let valid = signature::verify(&data, &sig, &signer.to_signature_key()).unwrap_or(false);
+
+    (vec![
+        ("signer",      json!(signer.to_base58())),
+        ("valid",       json!(valid)),
+    ], valid)
+}
+
+fn claim_of(input: &str) -> (String, Json) {
+    let Some((name, value)) = input.split_once('=') else {
+        fail(format!("Invalid claim {input}, expected NAME=VALUE"))
+    };
+    let value = serde_json::from_str(value).unwrap_or_else(|_| json!(value));
+    (name.to_string(), value)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn issue_credential(
+    key: String,
+    id: String,
+    subject: Option<String>,
+    types: Vec<String>,
+    name: Option<String>,
+    description: Option<String>,
+    claims: Vec<String>,
+    valid_days: Option<u64>,
+    output: Option<String>,
+) {
+    let issuer = CryptoIdentity::from(read_keypair(&key));
+    let mut builder = Credential::builder(issuer);
+    builder.with_id(&id).with_types(types.iter().map(|v| v.as_str()).collect());
+    if let Some(subject) = subject {
+        builder.with_subject(parse_id(&subject).unwrap_or_else(|e| {
+            fail(format!("Invalid subject: {e}"))
+        }));
+    }
+    if let Some(name) = name.as_deref() {
+        builder.with_name(name);
+    }
+    if let Some(description) = description.as_deref() {
+        builder.with_description(description);
+    }
+    for (name, value) in claims.iter().map(|v| claim_of(v)) {
+        builder.with_claim(&name, value);
+    }
+    if let Some(days) = valid_days {
+        builder.with_valid_until(SystemTime::now() + Duration::from_secs(days * 24 * 60 * 60));
+    }
+
+    let credential = builder.build()
+        .unwrap_or_else(|e| fail(format!("Issuing credential failed: {e}")));
+    let json = credential.to_string();
+    match output {
+        Some(path) => write_output(&path, json.as_bytes()),
+        None => println!("{json}"),
+    }
+}
+
+fn verify_credential(input: Option<String>) -> (Report, bool) {
+    let text = read_text(input.as_deref());
+    let (mut report, result) = if let Ok(cred) = Credential::try_from(text.as_str()) {
+        (vec![
+            ("type",        json!("credential")),
+            ("id",          json!(cred.id())),
+            ("issuer",      json!(cred.issuer().to_base58())),
+            ("subject",     json!(cred.subject().id().to_base58())),
+            ("genuine",     json!(cred.is_genuine())),
+        ], cred.validate())
+    } else if let Ok(vouch) = Vouch::try_from(text.as_str()) {
+        (vec![
+            ("type",        json!("vouch")),
+            ("id",          json!(vouch.id())),
+            ("holder",      json!(vouch.holder().to_base58())),
+            ("credentials", json!(vouch.credentials().len())),
+            ("genuine",     json!(vouch.is_genuine())),
+        ], vouch.validate())
+    } else if let Ok(card) = Card::try_from(text.as_str()) {
+        (vec![
+            ("type",        json!("card")),
+            ("id",          json!(card.id().to_base58())),
+            ("credentials", json!(card.credentials().len())),
+            ("services",    json!(card.services().len())),
+            ("genuine",     json!(card.is_genuine())),
+        ], card.validate())
+    } else {
+        fail("Input is not a credential, vouch or card")
+    };
+
+    let valid = result.is_ok();
+    report.push(("valid", json!(valid)));
+    report.push(("error", result.err().map(|e| json!(e.to_string())).unwrap_or(Json::Null)));
+    (report, valid)
+}
+
+fn decode_ticket(input: Option<String>, invitee: Option<String>) -> Report {
+    let text = read_text(input.as_deref());
+    let ticket = text.parse::<InviteTicket>()
+        .unwrap_or_else(|e| fail(format!("Invalid invite ticket: {e}")));
+    let invitee = invitee.map(|v| parse_id(&v).unwrap_or_else(|e| {
+        fail(format!("Invalid invitee: {e}"))
+    }));
+
+    // Bearer tickets are signed for any invitee.
+    let valid = match (ticket.is_bearer_ticket(), invitee.as_ref()) {
+        (true, _) => json!(ticket.is_valid(&Id::max())),
+        (false, Some(invitee)) => json!(ticket.is_valid(invitee)),
+        (false, None) => Json::Null,
+    };
+    vec![
+        ("channel",     json!(ticket.channel_id().to_base58())),
+        ("inviter",     json!(ticket.inviter().to_base58())),
+        ("bearer",      json!(ticket.is_bearer_ticket())),
+        ("expiration",  json!(secs(ticket.expiration()))),
+        ("expired",     json!(ticket.is_expired())),
+        ("sessionKey",  json!(ticket.session_key().is_some())),
+        ("valid",       valid),
+    ]
+}
+
+fn decode_value(input: Option<String>, key: Option<String>) -> Report {
+    let value = Value::try_from(decode_text(&read_input(input.as_deref())).as_slice())
+        .unwrap_or_else(|e| fail(format!("Invalid value: {e}")));
+    let plain = key.map(|path| {
+        value.decrypt(&read_keypair(&path))
+            .unwrap_or_else(|e| fail(format!("Decrypting value failed: {e}")))
+    });
+
+    let opt_id = |v: Option<&Id>| v.map(|v| json!(v.to_base58())).unwrap_or(Json::Null);
+    vec![
+        ("id",          json!(value.id().to_base58())),
+        ("publicKey",   opt_id(value.public_key())),
+        ("recipient",   opt_id(value.recipient())),
+        ("nonce",       value.nonce().map(|v| json!(hex::encode(v.as_bytes()))).unwrap_or(Json::Null)),
+        ("sequence",    json!(value.sequence_number())),
+        ("signature",   value.signature().map(|v| json!(hex::encode(v))).unwrap_or(Json::Null)),
+        ("mutable",     json!(value.is_mutable())),
+        ("encrypted",   json!(value.is_encrypted())),
+        ("valid",       json!(value.is_valid())),
+        ("data",        json!(hex::encode(value.data()))),
+        ("plain",       plain.as_ref().map(|v| json!(hex::encode(v))).unwrap_or(Json::Null)),
+        ("text",        plain.as_deref().or((!value.is_encrypted()).then(|| value.data()))
+            .and_then(|v| std::str::from_utf8(v).ok())
+            .map(|v| json!(v))
+            .unwrap_or(Json::Null)),
+    ]
+}
+
+fn decode_peer(input: Option<String>) -> Report {
+    let peer = PeerInfo::try_from(decode_text(&read_input(input.as_deref())).as_slice())
+        .unwrap_or_else(|e| fail(format!("Invalid peer: {e}")));
+
+    vec![
+        ("id",          json!(peer.id().to_base58())),
+        ("endpoint",    json!(peer.endpoint())),
+        ("fingerprint", json!(peer.fingerprint())),
+        ("sequence",    json!(peer.sequence_number())),
+        ("nodeId",      peer.nodeid().map(|v| json!(v.to_base58())).unwrap_or(Json::Null)),
+        ("announced",   peer.announced_time().map(|v| json!(secs(v))).unwrap_or(Json::Null)),
+        ("extra",       peer.extra_data().map(|v| json!(hex::encode(v))).unwrap_or(Json::Null)),
+        ("authenticated", json!(peer.is_authenticated())),
+        ("valid",       json!(peer.is_valid())),
+    ]
+}
+
+fn main() {
+    let opts = Options::parse();
+    let (report, ok) = match opts.command {
+        Command::Keygen { output } => (keygen(output), true),
+        Command::Id { input } => (id(input), true),
+        Command::Sign { key, input } => (sign(key, input), true),
+        Command::Verify { signer, signature, input } => verify(signer, signature, input),
+        Command::Credential(CredentialCommand::Issue {
+            key, id, subject, types, name, description, claims, valid_days, output
+        }) => {
+            issue_credential(key, id, subject, types, name, description, claims, valid_days, output);
+            return;
+        },
+        Command::Credential(CredentialCommand::Verify { input }) => verify_credential(input),
+        Command::Ticket(TicketCommand::Decode { input, invitee }) => (decode_ticket(input, invitee), true),
+        Command::Value(ValueCommand::Decode { input, key }) => (decode_value(input, key), true),
+        Command::Peer(PeerCommand::Decode { input }) => (decode_peer(input), true),
+    };
+
+    print_report(report, opts.json);
+    let _ = io::stdout().flush();
+    if !ok {
+        exit(1);
+    }
+}
@@ -41,6 +41,18 @@ pub(crate) fn channel_cli() -> Command {
                 .about("Retrieve channel information")
                 .arg(arg!(<ID> "The channel id to retrieve information for"))
         )
+        .subcommand(
+            Command::new("approve")
+                .about("Approve a request to join a channel")
+                .arg(arg!(<ID> "The channel id the request is for"))
+                .arg(arg!(<USER> "The id of the user asking to join"))
+        )
+        .subcommand(
+            Command::new("deny")
+                .about("Deny a request to join a channel")
+                .arg(arg!(<ID> "The channel id the request is for"))
+                .arg(arg!(<USER> "The id of the user asking to join"))
+        )
         .subcommand(
             Command::new("ticket")
                 .about("Create a ticket")
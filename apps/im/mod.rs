@@ -118,6 +118,29 @@ async fn execute_command(matches: ArgMatches, client: &Arc<Mutex<MessagingClient
                 });
             }
 
+            Some((decision @ ("approve" | "deny"), m)) => {
+                let id = m.get_one::<String>("ID").unwrap();
+                let Ok(channel_id) = Id::try_from(id.as_str()) else {
+                    println!("Error: invalid channel id: {}", id);
+                    return;
+                };
+                let user = m.get_one::<String>("USER").unwrap();
+                let Ok(user_id) = Id::try_from(user.as_str()) else {
+                    println!("Error: invalid user id: {}", user);
+                    return;
+                };
+
+                let approve = decision == "approve";
+                _ = client.lock().unwrap().approve_join(&channel_id, &user_id, approve).await.map_err(|e| {
+                    println!("Error deciding on join request: {}", e);
+                }).map(|_| {
+                    match approve {
+                        true => println!("Approved {} to join channel {}", user_id, channel_id),
+                        false => println!("Denied {} to join channel {}", user_id, channel_id),
+                    }
+                });
+            }
+
             Some(("ticket", m)) => {
                 let id = m.get_one::<String>("ID").unwrap();
                 let Ok(channel_id) = Id::try_from(id.as_str()) else {
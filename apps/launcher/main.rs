@@ -1,17 +1,25 @@
-use std::sync::{Arc, Mutex};
+use std::path::PathBuf;
 use std::time::Duration;
-use std::thread;
 use std::process::exit;
 use clap::Parser;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::LocalSet,
+};
 
 use boson::{
-    dht::Node,
-    configuration as cfg,
-    ActiveProxyClient as ActiveProxy,
+    Id,
+    signature,
+    dht::{Node, NodeConfiguration, KnownNetwork},
+    activeproxy::{
+        VersionRange,
+        client::ActiveProxyOptions,
+        supervisor::{ProxyService, Supervisor, SupervisorOptions},
+    },
 };
 
 #[derive(Parser, Debug)]
-#[command(name = "Laucnher")]
+#[command(name = "Launcher")]
 #[command(version = "1.0")]
 #[command(about = "Boson launcher service", long_about = None)]
 struct Options {
@@ -19,14 +27,6 @@ struct Options {
     #[arg(short, long, value_name = "FILE")]
     config: String,
 
-    /// IPv4 address used for listening.
-    #[arg(short = '4', long, value_name = "IPv4")]
-    addr4: Option<String>,
-
-    /// IPv6 address used for listening.
-    #[arg(short = '6', long, value_name = "IPv6")]
-    addr6: Option<String>,
-
     /// The directory for storing node data
     #[arg(short, long, value_name = "PATH")]
     storage: Option<String>,
@@ -35,50 +35,127 @@ struct Options {
     #[arg(short, long, default_value_t = 39011)]
     port: u16,
 
+    /// Consecutive ActiveProxy failures tolerated before giving up
+    #[arg(long, default_value_t = 10)]
+    max_failures: u32,
+
+    /// Upper bound of the restart backoff in seconds
+    #[arg(long, default_value_t = 300)]
+    max_backoff: u64,
+
     /// Run this program in daemon mode
     #[arg(short='D', long)]
     daemonize: bool
 }
 
-fn main() {
-    let opts = Options::parse();
-    let cfg  = cfg::Builder::new()
-        .load(&opts.config).map_err(|e| {
-            println!("Error loading configuration: {}", e);
+fn load_json(path: &str) -> serde_json::Value {
+    let raw = std::fs::read_to_string(path).unwrap_or_else(|e| {
+        println!("Error loading configuration: {e}");
+        exit(-1)
+    });
+    serde_json::from_str(&raw).unwrap_or_else(|e| {
+        println!("Error parsing configuration: {e}");
+        exit(-1)
+    })
+}
+
+fn proxy_options(json: &serde_json::Value, data_dir: &str) -> ActiveProxyOptions {
+    let str_of = |v: &serde_json::Value, key: &str| v.get(key).and_then(|v| v.as_str()).map(|v| v.to_string());
+    let ap = json.get("activeproxy").unwrap_or_else(|| {
+        println!("Missing activeproxy configuration");
+        exit(-1)
+    });
+
+    let user_sk = str_of(&json["user"], "privateKey")
+        .and_then(|v| signature::PrivateKey::try_from(v.as_str()).ok())
+        .unwrap_or_else(|| {
+            println!("Missing or invalid user private key");
             exit(-1)
-        }).unwrap()
-        .with_data_dir(
-            opts.storage.as_deref().unwrap_or("~/.boson")
-        ).build().map_err(|e| {
-            println!("Error building configuration: {}", e);
+        });
+    let network = str_of(json, "network").map(|v| v.parse::<KnownNetwork>().unwrap_or_else(|e| {
+        println!("network: {e}");
+        exit(-1)
+    }));
+    let server_peerid = match str_of(ap, "serverPeerId") {
+        Some(v) => Id::try_from(v.as_str()).unwrap_or_else(|e| {
+            println!("activeproxy.serverPeerId: invalid peer id '{v}': {e}");
             exit(-1)
-        }).unwrap();
+        }),
+        None => network.and_then(|v| v.activeproxy_peer_id()).unwrap_or_else(|| {
+            println!("activeproxy.serverPeerId: missing, and no network with a well-known one");
+            exit(-1)
+        }),
+    };
+    let peer_keypair = str_of(ap, "peerPrivateKey").map(|v| {
+        signature::PrivateKey::try_from(v.as_str()).unwrap_or_else(|e| {
+            println!("activeproxy.peerPrivateKey: invalid private key: {e}");
+            exit(-1)
+        })
+    }).map(signature::KeyPair::from);
+    let allowed_clients = ap.get("allowedClients")
+        .and_then(|v| v.as_array())
+        .map(|v| v.iter().map(|v| {
+            v.as_str().and_then(|v| Id::try_from(v).ok()).unwrap_or_else(|| {
+                println!("Invalid allowed client id: {v}");
+                exit(-1)
+            })
+        }).collect())
+        .unwrap_or_default();
 
-    let result = Node::new(&cfg);
-    if let Err(e) = result {
-        panic!("Creating Node instance error: {e}")
+    ActiveProxyOptions {
+        cached_dir: PathBuf::from(data_dir).join("activeproxy.cache"),
+        server_peerid,
+        user_keypair: signature::KeyPair::from(user_sk),
+        peer_keypair,
+        upstream_host: str_of(ap, "upstreamHost").unwrap_or("127.0.0.1".into()),
+        upstream_port: ap.get("upstreamPort").and_then(|v| v.as_u64()).unwrap_or(8080) as u16,
+        upstream_domain: str_of(ap, "domainName"),
+        allowed_clients,
+        remote_dns: ap.get("remoteDns").and_then(|v| v.as_bool()).unwrap_or(true),
+        protocol_versions: VersionRange::default(),
     }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let opts = Options::parse();
+    let json = load_json(&opts.config);
 
-    let node = Arc::new(Mutex::new(result.unwrap()));
-    let _ = node.lock()
-        .unwrap()
-        .start();
+    let data_dir = opts.storage.clone()
+        .or_else(|| json.get("dataDir").and_then(|v| v.as_str()).map(|v| v.to_string()))
+        .unwrap_or("~/.boson".into());
+    let private_key = json["user"]["privateKey"].as_str().unwrap_or_default().to_string();
+    let yaml = format!(
+        "ipv4: true\nport: {}\nprivateKey: \"{}\"\ndataDir: {}\ndatabaseUri: jdbc:sqlite:storage.db\n",
+        opts.port,
+        private_key,
+        data_dir,
+    );
 
-    thread::sleep(Duration::from_secs(2));
+    let service = ProxyService::new(
+        move || Node::new(Box::new(NodeConfiguration::from(&yaml)?)),
+        proxy_options(&json, &data_dir),
+    );
 
-    let result = ActiveProxy::new(node.clone(), &cfg);
-    if let Err(e) = result {
-        panic!("Creating ActiveProxy client error: {e}")
-    }
+    let mut supervisor = Supervisor::new(service, SupervisorOptions {
+        max_failures: opts.max_failures,
+        max_backoff: Duration::from_secs(opts.max_backoff),
+        ..Default::default()
+    });
 
-    let ap = result.unwrap();
-    match ap.start() {
-        Ok(_) => {},
-        Err(e) => panic!("{e}")
-    }
+    let handle = supervisor.shutdown_handle();
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = sigterm.recv() => {},
+            _ = tokio::signal::ctrl_c() => {},
+        }
+        handle.shutdown();
+    });
+
+    let exit_code = LocalSet::new().run_until(async {
+        supervisor.run().await.exit_code()
+    }).await;
 
-    thread::sleep(Duration::from_secs(60*100));
-    let _ = node.lock()
-        .unwrap()
-        .stop();
+    exit(exit_code);
 }
@@ -3,7 +3,7 @@ use tokio::{
     task::LocalSet,
     time::Duration,
 };
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use boson::{
     Id,
@@ -12,6 +12,7 @@ use boson::{
         NodeConfig,
         NodeConfiguration
     },
+    crawler::Crawler,
 };
 
 #[derive(Parser, Debug)]
@@ -23,6 +24,31 @@ struct Options {
 
     #[arg(short='S', long)]
     simulate: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Crawl the DHT and estimate the size of the network
+    Crawl {
+        /// Requests per second across all nodes
+        #[arg(short, long, default_value_t = 20)]
+        rate: u32,
+
+        /// The time the crawl may take, in seconds
+        #[arg(short, long, default_value_t = 300)]
+        timeout: u64,
+
+        /// Requests awaiting an answer at a time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Milliseconds between two requests to the same node
+        #[arg(long, default_value_t = 500)]
+        politeness: u64,
+    },
 }
 
 #[tokio::main(flavor = "current_thread")]
@@ -47,6 +73,25 @@ async fn main() {
             let _ = node.bootstrap_one(&bootstrap_nodes[0]).await;
         }
 
+        if let Some(Command::Crawl { rate, timeout, concurrency, politeness }) = opts.command {
+            for bootstrap in bootstrap_nodes.iter() {
+                let _ = node.bootstrap_one(bootstrap).await;
+            }
+
+            println!("Crawling the DHT for at most {} seconds ...", timeout);
+            let crawler = Crawler::new(&node)
+                .with_rate(rate)
+                .with_concurrency(concurrency)
+                .with_politeness(Duration::from_millis(politeness));
+            match crawler.run(Duration::from_secs(timeout)).await {
+                Ok(report) => print!("{}", report),
+                Err(e) => println!("error: {}", e),
+            }
+
+            let _ = node.stop().await;
+            return;
+        }
+
         thread::sleep(Duration::from_secs(10*60));
 
         let target: Id = "HZXXs9LTfNQjrDKvvexRhuMk8TTJhYCfrHwaj3jUzuhZ".try_into().unwrap();
@@ -0,0 +1,64 @@
+// Decrypting a burst of messages from a few senders, deriving the crypto
+// context for every message against looking it up in the crypto cache.
+//
+//   cargo bench --bench crypto_cache
+
+use std::time::{Duration, Instant};
+
+use boson::{
+    Identity,
+    CryptoIdentity,
+    dht::CryptoCache,
+};
+
+const MESSAGES: usize = 1000;
+const SENDERS: usize = 10;
+const ROUNDS: u32 = 10;
+
+fn burst(me: &CryptoIdentity) -> Vec<(CryptoIdentity, Vec<u8>)> {
+    let senders: Vec<_> = (0..SENDERS).map(|_| CryptoIdentity::new()).collect();
+    let mut contexts: Vec<_> = senders.iter()
+        .map(|s| s.create_crypto_context(me.id()).unwrap())
+        .collect();
+
+    (0..MESSAGES).map(|i| {
+        let n = i % SENDERS;
+        let cipher = contexts[n].encrypt_into(format!("message {i}").as_bytes()).unwrap();
+        (senders[n].clone(), cipher)
+    }).collect()
+}
+
+fn measure(name: &str, mut run: impl FnMut()) {
+    run(); // warm up
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let started = Instant::now();
+        run();
+        best = best.min(started.elapsed());
+    }
+    println!("{name:<12} {MESSAGES} messages in {best:>12.3?}, {:>10.3?}/message",
+        best / MESSAGES as u32);
+}
+
+fn main() {
+    let me = CryptoIdentity::new();
+    let messages = burst(&me);
+
+    measure("per-message", || {
+        for (sender, cipher) in messages.iter() {
+            let ctx = me.create_crypto_context(sender.id()).unwrap();
+            ctx.decrypt_into(cipher).unwrap();
+        }
+    });
+
+    let cache = CryptoCache::new(256, Duration::from_secs(30 * 60));
+    measure("cached", || {
+        for (sender, cipher) in messages.iter() {
+            let ctx = cache.context(sender.id(), me.encryption_keypair());
+            ctx.lock().unwrap().decrypt_into(cipher).unwrap();
+        }
+    });
+
+    let stats = cache.stats();
+    println!("cache: {} entries, {} hits, {} misses", stats.entries(), stats.hits(), stats.misses());
+}
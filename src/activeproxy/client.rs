@@ -4,7 +4,7 @@ use std::path::{Path, PathBuf};
 use std::io::{Read, Write};
 use std::fs::File;
 
-use tokio::runtime::Runtime;
+use tokio::{runtime, task::LocalSet, sync::oneshot};
 use rand::seq::SliceRandom;
 use log::{error, warn, info, debug};
 
@@ -15,14 +15,18 @@ use crate::{
     signature,
     Result,
     core::errors::{ArgumentError, StateError},
+    core::endpoint::socket_addr_of,
     dht::Node,
 };
 
 use super::{
-    managed::ManagedFields,
+    managed::{ManagedFields, ManagedCmd, ManagedSender},
     worker::{self, ManagedWorker},
+    local_proxy::{self, ExitSession, LocalProxyHandle},
+    version::VersionRange,
 };
 
+#[derive(Clone)]
 pub struct ActiveProxyOptions {
     pub cached_dir: PathBuf,
     pub server_peerid: Id,
@@ -31,6 +35,56 @@ pub struct ActiveProxyOptions {
     pub upstream_host: String,
     pub upstream_port: u16,
     pub upstream_domain: Option<String>,
+    /// Clients that have to prove their identity before being relayed to
+    /// the upstream. Empty to relay everyone.
+    pub allowed_clients: Vec<Id>,
+    /// Whether the exit peer resolves the domain names asked for through
+    /// a local proxy, they are resolved locally otherwise.
+    pub remote_dns: bool,
+    /// The packet protocol versions offered to the relay, which picks one
+    /// or refuses the client.
+    pub protocol_versions: VersionRange,
+}
+
+// A snapshot of the worker state: the connections to the server, how many
+// of them relay a client at the time, and how many the server allows.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyStatistics {
+    pub(crate) connections      : usize,
+    pub(crate) inflights        : usize,
+    pub(crate) capacity         : usize,
+    pub(crate) server_failures  : i32,
+    pub(crate) relay_port       : Option<u16>,
+    pub(crate) session_version  : Option<u8>,
+}
+
+impl ProxyStatistics {
+    pub fn connections(&self) -> usize {
+        self.connections
+    }
+
+    pub fn inflights(&self) -> usize {
+        self.inflights
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn server_failures(&self) -> i32 {
+        self.server_failures
+    }
+
+    /// The port the server relays the clients on, once authenticated.
+    pub fn relay_port(&self) -> Option<u16> {
+        self.relay_port
+    }
+
+    /// The protocol version negotiated with the relay, once a connection
+    /// opened.
+    pub fn session_version(&self) -> Option<u8> {
+        self.session_version
+    }
 }
 
 pub struct ProxyClient {
@@ -47,9 +101,14 @@ pub struct ProxyClient {
     upstream_addr:      SocketAddr,
     upstream_domain:    Option<String>,
 
-    managed:            Arc<Mutex<ManagedFields>>,
-    worker:             Arc<Mutex<ManagedWorker>>,
-    quit:               Arc<Mutex<bool>>,
+    user_keypair:       signature::KeyPair,
+    peer_keypair:       Option<signature::KeyPair>,
+    allowed_clients:    Vec<Id>,
+    remote_dns:         bool,
+    protocol_versions:  VersionRange,
+
+    // Set while the worker runs, it owns the managed state.
+    worker:             Mutex<Option<ManagedSender>>,
 }
 
 impl ProxyClient {
@@ -66,24 +125,7 @@ impl ProxyClient {
                 ArgumentError::new("Network error!")
             })?;
 
-        let managed = {
-            let mut fields = ManagedFields::new(&options.user_keypair);
-            fields.peer_keypair  = options.peer_keypair;
-            fields.upstream_addr = Some(upstream_addr.clone());
-            fields.upstream_name = Some(upstream_name.clone());
-            fields.peer_domain   = options.upstream_domain.clone();
-
-            Arc::new(Mutex::new(fields))
-        };
-
         let peerid = options.server_peerid.clone();
-        let worker = Arc::new(Mutex::new(ManagedWorker::new(
-            options.cached_dir.clone(),
-            node.clone(),
-            managed.clone(),
-            peerid.clone(),
-        )));
-
         Ok(Self {
             node,
             cached_dir: options.cached_dir,
@@ -98,9 +140,13 @@ impl ProxyClient {
             upstream_addr:  upstream_addr,
             upstream_domain:    options.upstream_domain,
 
-            managed,
-            worker,
-            quit:           Arc::new(Mutex::new(false)),
+            user_keypair:   options.user_keypair,
+            peer_keypair:   options.peer_keypair,
+            allowed_clients: options.allowed_clients,
+            remote_dns:     options.remote_dns,
+            protocol_versions: options.protocol_versions,
+
+            worker:         Mutex::new(None),
         })
     }
 
@@ -148,15 +194,21 @@ impl ProxyClient {
         self.remote_node.as_ref().map(|v|v.lock().unwrap().clone())
     }
 
+    // Blocks the calling thread until stopped. When the node was created with
+    // a runtime the worker runs on it, call this from outside an async
+    // context then, e.g. within spawn_blocking.
     pub fn start(&self) -> Result<()> {
         let result = load_peer(self.cached_path(), self.remote_peerid()).or_else(||{
             if self.cached_path().exists() {
                 _ = std::fs::remove_file(self.cached_path());
             }
 
-            Runtime::new().unwrap().block_on(async {
-                lookup_peer(self.node(), self.remote_peerid()).await
-            }).map(|v| {
+            let lookup = lookup_peer(self.node(), self.remote_peerid());
+            let result = match self.node.runtime() {
+                Some(handle) => handle.block_on(lookup),
+                None => runtime::Runtime::new().unwrap().block_on(lookup),
+            };
+            result.map(|v| {
                 _ = save_peer(self.cached_path(), v.clone());
                 v
             })
@@ -172,33 +224,94 @@ impl ProxyClient {
             return Err(StateError::new(format!("No available nodes hosting peerid {} found", self.remote_peerid)));
         };
 
-        let remote_addr = peer.endpoint().to_socket_addrs().ok().and_then(|mut addrs| addrs.next())
+        let remote_addr = socket_addr_of(peer.endpoint())
             .unwrap_or_else(|| SocketAddr::new(node.ip(), 0));
         info!("ActiveProxy found the peer serivce {} on server {}.", peer.id(), remote_addr);
 
-        if let Ok(mut managed) = self.managed.lock() {
-            managed.remote_peer = Some(Arc::new(Mutex::new(peer)));
-            managed.remote_node = Some(Arc::new(Mutex::new(node)));
-            managed.remote_addr = Some(remote_addr);
-            managed.remote_name = Some(remote_addr.to_string());
+        let mut fields = ManagedFields::new(&self.user_keypair);
+        fields.peer_keypair  = self.peer_keypair.clone();
+        fields.upstream_addr = Some(self.upstream_addr);
+        fields.upstream_name = Some(self.upstream_endpoint.clone());
+        fields.peer_domain   = self.upstream_domain.clone();
+        fields.allowed_clients = self.allowed_clients.iter().cloned().collect();
+        fields.versions      = self.protocol_versions;
+        fields.clock         = self.node.clock();
+
+        fields.remote_peer = Some(peer);
+        fields.remote_node = Some(node);
+        fields.remote_addr = Some(remote_addr);
+        fields.remote_name = Some(remote_addr.to_string());
+
+        let worker = ManagedWorker::new(
+            self.cached_dir.clone(),
+            self.node.clone(),
+            fields,
+            self.remote_peerid,
+        );
+        *self.worker.lock().unwrap() = Some(worker.sender());
+
+        // The worker spawns its connections as local tasks.
+        let local = LocalSet::new();
+        let run = local.run_until(worker::run_loop(worker));
+        let result = match self.node.runtime() {
+            Some(handle) => handle.block_on(run),
+            None => {
+                let rt = runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                rt.block_on(run)
+            }
+        };
+
+        *self.worker.lock().unwrap() = None;
+        result
+    }
+
+    pub fn stop(&self) {
+        if let Some(worker) = self.worker.lock().unwrap().as_ref() {
+            _ = worker.send(ManagedCmd::Stop);
         }
+    }
+
+    /// Listens on `listen` for SOCKS5 clients and tunnels their CONNECT
+    /// requests out through the exit peer `exit_peer`, which opens the
+    /// outbound connections. The proxy runs on the current runtime until
+    /// the returned handle is stopped or dropped.
+    pub async fn start_local_proxy(&self, listen: SocketAddr, exit_peer: &Id) -> Result<LocalProxyHandle> {
+        let Some((peer, _)) = lookup_peer(self.node(), exit_peer).await else {
+            error!("No available nodes hosting exit peer ID {} were found.", exit_peer);
+            return Err(StateError::new(format!("No available nodes hosting exit peerid {} found", exit_peer)));
+        };
 
-        let rt = tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .build()
-            .unwrap();
+        // The exit takes the tunnels on the endpoint it announces.
+        let Some(exit_addr) = socket_addr_of(peer.endpoint()) else {
+            return Err(StateError::new(format!("Exit peer {} announces no reachable endpoint", exit_peer)));
+        };
+        info!("ActiveProxy found the exit peer {} on server {}.", peer.id(), exit_addr);
 
-        let worker = self.worker.clone();
-        let quit = self.quit.clone();
-        rt.block_on(async {
-            _ = worker::run_loop(worker, quit).await
-        });
+        let session = ExitSession::new(&self.user_keypair, *exit_peer, exit_addr, self.remote_dns);
+        local_proxy::start(listen, session).await
+    }
 
-        Ok(())
+    /// The protocol version negotiated with the relay, for the features
+    /// depending on one to check. None until a connection opened.
+    pub async fn session_version(&self) -> Option<u8> {
+        self.statistics().await.ok()?.session_version()
     }
 
-    pub fn stop(&self) {
-        // TODO:
+    /// Returns the state of the running worker, an error if it is not
+    /// started.
+    pub async fn statistics(&self) -> Result<ProxyStatistics> {
+        let (tx, rx) = oneshot::channel();
+        let sent = self.worker.lock().unwrap().as_ref()
+            .map(|worker| worker.send(ManagedCmd::Statistics(tx)).is_ok())
+            .unwrap_or(false);
+
+        match sent {
+            true => rx.await.map_err(|_| StateError::new("ActiveProxy worker is stopped")),
+            false => Err(StateError::new("ActiveProxy worker is not running")),
+        }
     }
 }
 
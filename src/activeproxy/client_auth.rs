@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    Id,
+    Result,
+    signature,
+    Signature,
+    random_bytes,
+    core::errors::{PermissionError, ProtocolError},
+};
+
+const MAGIC: &[u8] = b"BAP1";
+const NONCE_BYTES: usize = 32;
+
+pub(crate) const CHALLENGE_BYTES: usize = MAGIC.len() + NONCE_BYTES;
+pub(crate) const RESPONSE_BYTES: usize = Id::BYTES + Signature::BYTES;
+
+fn signing_data(nonce: &[u8], clientid: &Id) -> Vec<u8> {
+    let mut data = Vec::with_capacity(CHALLENGE_BYTES + Id::BYTES);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(nonce);
+    data.extend_from_slice(clientid.as_bytes());
+    data
+}
+
+/*
+ * The challenge sent to the connecting client when the service has an
+ * allow-list of clients, before the upstream is opened:
+ *   - magic[4 bytes]
+ *   - nonce[32 bytes]
+ *
+ * The client answers with:
+ *   - clientId[32 bytes]
+ *   - signature[64 bytes] over magic|nonce|clientId
+ */
+pub(crate) struct ClientChallenge {
+    nonce: Vec<u8>,
+    received: Vec<u8>,
+}
+
+pub(crate) enum Verdict {
+    Pending,
+    Authorized(Id, Vec<u8>),    // the client id and the data following the response.
+    Refused(crate::Error),
+}
+
+impl ClientChallenge {
+    pub(crate) fn new() -> Self {
+        Self {
+            nonce: random_bytes(NONCE_BYTES),
+            received: Vec::with_capacity(RESPONSE_BYTES),
+        }
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CHALLENGE_BYTES);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.nonce);
+        bytes
+    }
+
+    /// Collects the response from the client data, which may arrive in
+    /// several pieces, and checks it once complete.
+    pub(crate) fn feed(&mut self, data: &[u8], allowed: &HashSet<Id>) -> Verdict {
+        self.received.extend_from_slice(data);
+        if self.received.len() < RESPONSE_BYTES {
+            return Verdict::Pending;
+        }
+
+        let rest = self.received.split_off(RESPONSE_BYTES);
+        match self.verify(&self.received, allowed) {
+            Ok(clientid) => Verdict::Authorized(clientid, rest),
+            Err(e) => Verdict::Refused(e),
+        }
+    }
+
+    pub(crate) fn verify(&self, response: &[u8], allowed: &HashSet<Id>) -> Result<Id> {
+        if response.len() != RESPONSE_BYTES {
+            return Err(ProtocolError::new("Invalid client authentication response"));
+        }
+
+        let clientid = Id::from_bytes(response[..Id::BYTES].try_into().unwrap());
+        if !allowed.contains(&clientid) {
+            return Err(PermissionError::new(format!("Client {} is not allowed", clientid)));
+        }
+
+        let data = signing_data(&self.nonce, &clientid);
+        match signature::verify(&data, &response[Id::BYTES..], &clientid.to_signature_key()) {
+            Ok(true) => Ok(clientid),
+            _ => Err(PermissionError::new(format!("Client {} failed the challenge", clientid))),
+        }
+    }
+}
+
+/// Signs the challenge from a service with an allow-list of clients.
+pub(crate) fn respond(challenge: &[u8], keypair: &signature::KeyPair) -> Result<Vec<u8>> {
+    if challenge.len() != CHALLENGE_BYTES || &challenge[..MAGIC.len()] != MAGIC {
+        return Err(ProtocolError::new("Invalid client authentication challenge"));
+    }
+
+    let clientid = Id::from(keypair.public_key());
+    let data = signing_data(&challenge[MAGIC.len()..], &clientid);
+
+    let mut response = Vec::with_capacity(RESPONSE_BYTES);
+    response.extend_from_slice(clientid.as_bytes());
+    response.extend_from_slice(&signature::sign_into(&data, keypair.private_key())?);
+    Ok(response)
+}
+
+/// Proves the identity of `keypair` to a service behind the active proxy
+/// that only accepts the clients on its allow-list. Must be called on a
+/// freshly connected stream to the relay port, before any other data.
+pub async fn authenticate<S>(stream: &mut S, keypair: &signature::KeyPair) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin
+{
+    let mut challenge = vec![0u8; CHALLENGE_BYTES];
+    stream.read_exact(&mut challenge).await?;
+
+    let response = respond(&challenge, keypair)?;
+    stream.write_all(&response).await?;
+    stream.flush().await?;
+    Ok(())
+}
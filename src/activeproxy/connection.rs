@@ -2,6 +2,7 @@ use std::mem;
 use std::fmt;
 use std::str;
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicI32, Ordering};
 use std::time::SystemTime;
 use std::net::{
     SocketAddr,
@@ -19,24 +20,18 @@ use tokio::net::{
     TcpStream,
     TcpSocket
 };
+use tokio::sync::oneshot;
 use log::{warn, error,info, debug, trace};
 
 use crate::{
-    elapsed_ms,
-    srv_endp,
-    srv_addr,
-    srv_peer,
-    ups_endp,
-    ups_addr,
-    enbox,
     unwrap,
     random_bytes,
     Id,
+    Clock,
     Result,
     cryptobox, CryptoBox,
     signature,
     Signature,
-    PeerBuilder,
     Identity,
     CryptoContext,
     core::errors::{PermissionError, ProtocolError, StateError},
@@ -46,9 +41,11 @@ use crate::activeproxy::{
     random_padding,
     random_timeshift,
     random_boolean,
-    managed::ManagedFields,
+    managed::{ManagedCmd, ManagedSender, Endpoints},
+    client_auth::{ClientChallenge, Verdict},
     packet::{Packet, AttachType, AuthType, ConnType, DisconnType, DataType, PingType},
     state::State,
+    version::{self, IncompatibleVersionError},
 };
 
 // packet size (2bytes) + packet type(1bytes)
@@ -56,14 +53,13 @@ const PACKET_HEADER_BYTES: usize = mem::size_of::<u16>() + mem::size_of::<u8>();
 const KEEPALIVE_INTERVAL:   u128 = 60000;      // 60 seconds
 const MAX_KEEP_ALIVE_RETRY: u128 = 3;
 
-static mut NEXT_CONNID: i32 = 0;
+static NEXT_CONNID: AtomicI32 = AtomicI32::new(0);
 fn next_connection_id() -> i32 {
-    unsafe {
-        NEXT_CONNID += 1;
-        if NEXT_CONNID == 0 {
-            NEXT_CONNID += 1;
+    loop {
+        let id = NEXT_CONNID.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        if id != 0 {
+            return id;
         }
-        NEXT_CONNID
     }
 }
 
@@ -72,8 +68,12 @@ pub(crate) struct ProxyConnection {
     state:              State,
     keepalive:          SystemTime,
     disconnect_confirms: i32,
+    clock:              Arc<dyn Clock>,
 
-    inners:             Arc<Mutex<ManagedFields>>,
+    // Reports to the worker owning the managed state, which never waits on
+    // the connection in turn.
+    managed:            ManagedSender,
+    endpoints:          Endpoints,
 
     relay_reader:       Option<ReadHalf<TcpStream>>,
     relay_writer:       Option<WriteHalf<TcpStream>>,
@@ -83,16 +83,13 @@ pub(crate) struct ProxyConnection {
 
     stickybuf:          Option<Vec<u8>>,
 
+    // The pending challenge to the client, when the service only relays
+    // the clients on its allow-list.
+    client_challenge:   Option<ClientChallenge>,
+
     deviceid:           Id,
     signature_keypair:  signature::KeyPair,
     crypto_context:     Mutex<CryptoContext>,
-
-    authorized_cb:      Box<dyn Fn(&ProxyConnection, &cryptobox::PublicKey, u16, bool) + Send + Sync>,
-    opened_cb:          Box<dyn Fn(&ProxyConnection) + Send + Sync>,
-    open_failed_cb:     Box<dyn Fn(&ProxyConnection) + Send + Sync>,
-    closed_cb:          Box<dyn Fn(&ProxyConnection) + Send + Sync>,
-    busy_cb:            Box<dyn Fn(&ProxyConnection) + Send + Sync>,
-    idle_cb:            Box<dyn Fn(&ProxyConnection) + Send + Sync>,
 }
 
 impl Identity for ProxyConnection {
@@ -135,16 +132,23 @@ impl Identity for ProxyConnection {
 }
 
 impl ProxyConnection {
-    pub(crate) fn new(inners: Arc<Mutex<ManagedFields>>, keypair: &signature::KeyPair) -> Self {
+    pub(crate) fn new(managed: ManagedSender, endpoints: Endpoints, keypair: &signature::KeyPair) -> Self {
         let encryption_keypair = cryptobox::KeyPair::from(keypair);
+        let clock = endpoints.clock.clone();
+        let crypto_context = CryptoContext::from_private_key(
+            endpoints.remote_peerid,
+            encryption_keypair.private_key()
+        );
 
-        let mut connection = Self {
-            inners:             inners.clone(),
+        let connection = Self {
+            managed,
+            endpoints,
 
             conn_id:            next_connection_id(),
             state:              State::Initializing,
-            keepalive:          SystemTime::now(),
+            keepalive:          clock.now(),
             disconnect_confirms: 0,
+            clock,
 
             relay_reader:       None,
             relay_writer:       None,
@@ -153,90 +157,32 @@ impl ProxyConnection {
 
             stickybuf:          Some(Vec::with_capacity(4*1024)),
 
+            client_challenge:   None,
+
             deviceid:           Id::from(keypair.public_key()),
             signature_keypair:  keypair.clone(),
-            crypto_context:     Mutex::new(CryptoContext::from_private_key(
-                srv_peer!(inners).lock().unwrap().id().clone(),
-                encryption_keypair.private_key()
-            )),
-
-            authorized_cb:      Box::new(|_,_,_,_|{}),
-            opened_cb:          Box::new(|_|{}),
-            open_failed_cb:     Box::new(|_|{}),
-            closed_cb:          Box::new(|_|{}),
-            busy_cb:            Box::new(|_|{}),
-            idle_cb:            Box::new(|_|{}),
+            crypto_context:     Mutex::new(crypto_context),
         };
 
-        connection.authorized_cb = Box::new(move |conn, pk, port, domain_enabled| {
-            let mut inners = conn.inners.lock().unwrap();
-
-            inners.relay_port = Some(port);
-            inners.cryptobox  = cryptobox::CryptoBox::try_from((pk, inners.session_keypair.private_key())).ok();
-            inners.domain_enabled = domain_enabled;
-
-            if inners.peer_keypair.is_none() {
-                return;
-            }
-
-            let endpoint = format!("{}:{}", unwrap!(inners.remote_addr).ip(), port);
-            let mut builder = PeerBuilder::new(&endpoint)
-                .with_fingerprint(0)
-                .with_sequence_number(0);
-            if let Some(keypair) = inners.peer_keypair.as_ref() {
-                builder = builder.with_key(keypair.clone());
-            }
-
-            if let Ok(peer) = builder.build() {
-                info!("-**- ActiveProxy: peer server endpoint: {} -**-", peer.endpoint());
-                inners.peer = Some(peer);
-            }
-        });
-
-        connection.opened_cb = Box::new(move |conn| {
-            let mut inners = conn.inners.lock().unwrap();
-            inners.server_failures = 0;
-            inners.reconnect_delay = 0;
-        });
-
-        connection.open_failed_cb = Box::new(move|conn| {
-            let mut inners = conn.inners.lock().unwrap();
-            let failures = inners.server_failures;
-
-            inners.server_failures = failures + 1;
-            if inners.reconnect_delay < 64 {
-                inners.reconnect_delay = (1 << failures) * 1000;
-            }
-        });
-
-        connection.closed_cb = Box::new(move |conn| {
-            conn.inners.lock().unwrap().connections -= 1;
-        });
-
-        connection.busy_cb = Box::new(move |conn| {
-            let mut inners = conn.inners.lock().unwrap();
-            inners.inflights += 1;
-            inners.last_idle_check = SystemTime::UNIX_EPOCH;
-        });
-
-        connection.idle_cb = Box::new(move |conn| {
-            let mut inners = conn.inners.lock().unwrap();
-            inners.inflights -= 1;
-            if inners.inflights == 0 {
-                inners.last_idle_check = SystemTime::now();
-            }
-        });
-
         info!("Connection {} is created.", connection.id());
         connection
     }
 
-    pub(crate) fn cid(&self) -> i32 {
-        self.conn_id
+    // Creates a connection with the endpoints queried from the worker.
+    pub(crate) async fn open(managed: ManagedSender, keypair: &signature::KeyPair) -> Result<Self> {
+        let (tx, rx) = oneshot::channel();
+        managed.send(ManagedCmd::QueryEndpoints(tx))
+            .map_err(|_| StateError::new("ActiveProxy worker is stopped"))?;
+
+        match rx.await {
+            Ok(Some(endpoints)) => Ok(Self::new(managed, endpoints, keypair)),
+            Ok(None) => Err(StateError::new("ActiveProxy server peer is unknown")),
+            Err(_) => Err(StateError::new("ActiveProxy worker is stopped")),
+        }
     }
 
-    pub(crate) fn inners(&self) -> Arc<Mutex<ManagedFields>> {
-        self.inners.clone()
+    pub(crate) fn cid(&self) -> i32 {
+        self.conn_id
     }
 
     pub(crate) fn take_relay_reader(&mut self) -> Option<ReadHalf<TcpStream>> {
@@ -267,28 +213,51 @@ impl ProxyConnection {
         true
     }
 
-    fn on_authorized(&mut self, pk: &cryptobox::PublicKey, port: u16, domain_enabled: bool) {
-        (self.authorized_cb)(self, pk, port, domain_enabled);
+    // The worker is gone once stopped, the events are dropped then.
+    fn report(&self, cmd: ManagedCmd) {
+        _ = self.managed.send(cmd);
+    }
+
+    fn on_authorized(&mut self, pk: cryptobox::PublicKey, port: u16, domain_enabled: bool, capacity: usize) {
+        self.endpoints.cryptobox = CryptoBox::try_from((&pk, self.endpoints.session_keypair.private_key())).ok();
+        self.report(ManagedCmd::Authorized { pk, port, domain_enabled, capacity });
+    }
+
+    fn on_opened(&mut self, version: u8) {
+        self.report(ManagedCmd::Opened { version });
     }
 
-    fn on_opened(&mut self) {
-        (self.opened_cb)(self);
+    // The relay shares no version with this client, which the worker stops
+    // on rather than reconnecting.
+    fn on_incompatible(&mut self, e: Box<IncompatibleVersionError>) -> crate::Error {
+        error!("Connection {} is incompatible with server {}: {e}", self.cid(), self.endpoints.remote_name);
+        self.report(ManagedCmd::Incompatible {
+            relay: e.relay_versions(),
+            client: e.client_versions(),
+        });
+        e
+    }
+
+    // The version the relay chose in the trailer of its ACK.
+    fn negotiate(&mut self, trailer: &[u8]) -> Result<u8> {
+        version::negotiate(self.endpoints.versions, trailer)
+            .map_err(|e| self.on_incompatible(e))
     }
 
     fn on_closed(&mut self) {
-        (self.closed_cb)(self);
+        self.report(ManagedCmd::ConnectionClosed);
     }
 
     fn on_open_failed(&mut self) {
-        (self.open_failed_cb)(self)
+        self.report(ManagedCmd::OpenFailed);
     }
 
     fn on_busy(&mut self) {
-        (self.busy_cb)(self);
+        self.report(ManagedCmd::Busy);
     }
 
     fn on_idle(&mut self) {
-        (self.idle_cb)(self);
+        self.report(ManagedCmd::Idle);
     }
 
     pub(crate) async fn close(&mut self) -> Result<()> {
@@ -339,26 +308,33 @@ impl ProxyConnection {
         Ok(())
     }
 
-    async fn open_upstream(&mut self) -> Result<()> {
-        debug!("Connection {} connecting to upstream {}...", self.cid(), ups_endp!(self.inners));
+    async fn connect_upstream(&mut self) -> Result<()> {
+        debug!("Connection {} connecting to upstream {}...", self.cid(), self.endpoints.upstream_name);
 
-        let raddr = ups_addr!(self.inners).clone();
+        let raddr = self.endpoints.upstream_addr;
         let socket = TcpSocket::new_v4()?;  // TODO: ip v4 addr?;
         let result = socket.connect(raddr).await;
         match result {
             Ok(stream) => {
-                info!("Connection {} has connected to upstream {}", self.cid(), ups_endp!(self.inners));
+                info!("Connection {} has connected to upstream {}", self.cid(), self.endpoints.upstream_name);
                 let (reader, writer) = split(stream);
                 self.upstream_reader = Some(reader);
                 self.upstream_writer = Some(writer);
+                Ok(())
             },
             Err(e) => {
-                error!("Connection {} connect to upstream {} failed: {}", self.cid(), ups_endp!(self.inners), e);
-                self.close_upstream2().await?;
-                self.state = State::Idling;
-                self.on_idle();
+                error!("Connection {} connect to upstream {} failed: {}", self.cid(), self.endpoints.upstream_name, e);
+                Err(e.into())
             }
-        };
+        }
+    }
+
+    async fn open_upstream(&mut self) -> Result<()> {
+        if self.connect_upstream().await.is_err() {
+            self.close_upstream2().await?;
+            self.state = State::Idling;
+            self.on_idle();
+        }
 
         if self.upstream_reader.is_some() {
             self.send_connect_response(true).await
@@ -367,6 +343,44 @@ impl ProxyConnection {
         }
     }
 
+    // Accepts the relayed connection without opening the upstream, which
+    // waits until the client answers the challenge.
+    async fn challenge_client(&mut self) -> Result<()> {
+        debug!("Connection {} challenging the client before opening upstream", self.cid());
+
+        let challenge = ClientChallenge::new();
+        let bytes = challenge.to_bytes();
+        self.client_challenge = Some(challenge);
+
+        self.send_connect_response(true).await?;
+        self.send_data(&bytes).await
+    }
+
+    async fn on_client_response(&mut self, data: &[u8]) -> Result<()> {
+        let verdict = self.client_challenge.as_mut().unwrap().feed(data, &self.endpoints.allowed_clients);
+
+        match verdict {
+            Verdict::Pending => Ok(()),
+            Verdict::Authorized(clientid, rest) => {
+                self.client_challenge = None;
+                info!("Connection {} authorized client {}", self.cid(), clientid);
+
+                if self.connect_upstream().await.is_err() {
+                    return self.close_upstream().await;
+                }
+                match rest.is_empty() {
+                    true => Ok(()),
+                    false => self.send_upstream(&rest).await,
+                }
+            },
+            Verdict::Refused(e) => {
+                self.client_challenge = None;
+                warn!("Connection {} refused the client: {e}", self.cid());
+                self.close_upstream().await
+            }
+        }
+    }
+
     async fn close_upstream2(&mut self) -> Result<()> {
         if  self.state == State::Closed ||
             self.state == State::Idling {
@@ -385,7 +399,7 @@ impl ProxyConnection {
             }).await
         }
 
-        info!("Connection {} closed upstream {}", self.cid(), ups_endp!(self.inners));
+        info!("Connection {} closed upstream {}", self.cid(), self.endpoints.upstream_name);
         Ok(())
     }
 
@@ -395,7 +409,7 @@ impl ProxyConnection {
             return Ok(())
         }
 
-        info!("Connection {} closing upstream {}", self.cid(), ups_endp!(self.inners));
+        info!("Connection {} closing upstream {}", self.cid(), self.endpoints.upstream_name);
 
         self.state = State::Disconnecting;
 
@@ -410,7 +424,7 @@ impl ProxyConnection {
             return Ok(())
         }
 
-        if elapsed_ms!(self.keepalive) > MAX_KEEP_ALIVE_RETRY * KEEPALIVE_INTERVAL {
+        if self.clock.elapsed_ms(self.keepalive) > MAX_KEEP_ALIVE_RETRY * KEEPALIVE_INTERVAL {
             warn!("Connection {} is dead and should be obsolete.", self.cid());
             return Err(StateError::new(format!("Connection {} is dead", self.cid())));
         }
@@ -418,21 +432,21 @@ impl ProxyConnection {
         // keepalive check.
         let random_shift = random_timeshift() as u128; // max  10 seconds;
         if self.state == State::Idling &&
-            elapsed_ms!(self.keepalive) >= KEEPALIVE_INTERVAL - random_shift {
+            self.clock.elapsed_ms(self.keepalive) >= KEEPALIVE_INTERVAL - random_shift {
             return self.send_ping_request().await;
         }
         return Ok(())
     }
 
     pub(crate) async fn connect_server(&mut self) -> Result<()> {
-        info!("Connection {} is connecting to the server {}...", self.cid(), srv_endp!(self.inners));
+        info!("Connection {} is connecting to the server {}...", self.cid(), self.endpoints.remote_name);
 
-        let raddr = srv_addr!(self.inners).clone();
+        let raddr = self.endpoints.remote_addr;
         let socket = TcpSocket::new_v4()?;  // TODO: ip v4 addr?;
         let result = socket.connect(raddr).await;
         match result {
             Ok(stream) => {
-                info!("Connection {} has connected to server {}", self.cid(), srv_endp!(self.inners));
+                info!("Connection {} has connected to server {}", self.cid(), self.endpoints.remote_name);
 
                 let (reader, writer) = split(stream);
                 self.relay_reader = Some(reader);
@@ -440,14 +454,14 @@ impl ProxyConnection {
                 Ok(())
             },
             Err(e) => {
-                error!("Connection {} connect to server {} failed: {}", self.cid(), srv_endp!(self.inners), e);
+                error!("Connection {} connect to server {} failed: {}", self.cid(), self.endpoints.remote_name, e);
                 Err(e.into())
             }
         }
     }
 
     pub(crate) async fn on_relay_data(&mut self, input: &[u8]) -> Result<()> {
-        self.keepalive = SystemTime::now();
+        self.keepalive = self.clock.now();
 
         let mut pos = 0;
         let mut remain = input.len();
@@ -526,18 +540,18 @@ impl ProxyConnection {
 
         let packet = result.unwrap();
         debug!("Connection {} got packet from server {}: type={}, ack={}, size={}",
-            self.cid(), srv_endp!(self.inners), packet, packet.ack(), input.len());
+            self.cid(), self.endpoints.remote_name, packet, packet.ack(), input.len());
 
         if matches!(packet, Packet::Error(_)) {
             let len = input.len() - PACKET_HEADER_BYTES;
             let mut plain = vec![0u8; len];
-            _ = enbox!(self.inners).decrypt(
+            _ = unwrap!(self.endpoints.cryptobox).decrypt(
                 &input[PACKET_HEADER_BYTES..],
                 &mut plain[..]
             ).map_err(|e| {
                 error!("Connection {} decrypt packet from server {} error {e}",
                     self.cid(),
-                    srv_endp!(self.inners)
+                    self.endpoints.remote_name
                 ); e
             })?;
 
@@ -550,7 +564,7 @@ impl ProxyConnection {
             let errstr = str::from_utf8(data).unwrap().to_string();
 
             error!("Connection {} got ERR response from the server {}, error:{}:{}",
-                self.cid(), srv_endp!(self.inners), ecode, errstr);
+                self.cid(), self.endpoints.remote_name, ecode, errstr);
 
             return Err(ProtocolError::new("Packet error"));
         }
@@ -568,6 +582,7 @@ impl ProxyConnection {
             Packet::Data(_)         => self.on_data_request(input).await,
             Packet::Disconnect(_)   => self.on_disconnect_request(input).await,
             Packet::DisconnectAck(_)=> self.on_disconnect_response(input),
+            Packet::Incompatible(_) => self.on_incompatible_response(input),
             _ => {
                 error!("INTERNAL ERROR: Connection {} got wrong {} packet in {} state", self.cid(), packet, self.state);
                 Err(ProtocolError::new(format!("Wrong expected packet {} received", packet)))
@@ -584,7 +599,7 @@ impl ProxyConnection {
         if input.len() < 32 || input.len() > 256 {
             error!("Connection {} got invalid challenge from server {}, expected range {}:{}, acutal length:{}!",
                 self.cid(),
-                srv_endp!(self.inners),
+                self.endpoints.remote_name,
                 32,
                 256,
 
@@ -596,10 +611,10 @@ impl ProxyConnection {
         // Sign the challenge, send auth or attach with siguature
         let sig = self.sign_into(input)?;
         // TODO: device signature
-        if self.inners.lock().unwrap().is_authenticated() {
+        if self.endpoints.cryptobox.is_some() {
             self.send_attach_request(&sig).await
         } else {
-            let user_sig = signature::sign_into(input, self.inners().lock().unwrap().keypair.private_key()).unwrap();
+            let user_sig = signature::sign_into(input, self.endpoints.keypair.private_key()).unwrap();
             self.send_authenticate_request(&user_sig, &sig).await
         }
     }
@@ -610,6 +625,8 @@ impl ProxyConnection {
     *   - sessionPk[server]
     *   - port[uint16]
     *   - domainEnabled[uint8]
+    * - plain
+    *   - version trailer, none from relays speaking v1 only
     */
     const AUTH_ACK_SIZE: usize = PACKET_HEADER_BYTES    // header.
         + cryptobox::Nonce::BYTES                       // nonce.
@@ -623,7 +640,7 @@ impl ProxyConnection {
         if input.len() < Self::AUTH_ACK_SIZE {
             error!("Connection {} got invalid AUTH ACK from server {}, expected minimum length {}, actual found: {}",
                 self.cid(),
-                srv_endp!(self.inners),
+                self.endpoints.remote_name,
                 Self::AUTH_ACK_SIZE,
                 input.len()
             );
@@ -633,7 +650,7 @@ impl ProxyConnection {
         let plain_len = Self::AUTH_ACK_SIZE - PACKET_HEADER_BYTES - CryptoBox::MAC_BYTES - cryptobox::Nonce::BYTES;
         let mut plain = vec![0u8; plain_len];
 
-        let peerid = srv_peer!(self.inners).lock().unwrap().id().clone();
+        let peerid = self.endpoints.remote_peerid.clone();
         self.decrypt(
             &peerid,
             &input[PACKET_HEADER_BYTES..Self::AUTH_ACK_SIZE],
@@ -641,7 +658,7 @@ impl ProxyConnection {
         ).map_err(|e| {
             error!("Connection {} decrypt AUTH ACK from server {} error {e}.",
                 self.cid(),
-                srv_endp!(self.inners())
+                self.endpoints.remote_name
             ); e
         })?;
 
@@ -663,35 +680,51 @@ impl ProxyConnection {
             plain[pos..end].try_into().unwrap()
         ) as usize;
 
-        self.inners.lock().unwrap().capacity = max_connections;
-
         pos = end;
         let domain_enabled = input[pos] != 0;           // extract flag whether domain enabled or not.
 
-        self.on_authorized(&server_pk, port, domain_enabled);
+        let version = self.negotiate(&input[Self::AUTH_ACK_SIZE..])?;
+        self.on_authorized(server_pk, port, domain_enabled, max_connections);
 
         self.state = State::Idling;
-        self.on_opened();
-        info!("Connection {} opened.", self.cid());
+        self.on_opened(version);
+        info!("Connection {} opened with protocol v{version}.", self.cid());
         Ok(())
     }
 
     /*
-     * No Payload.
+     * ATTACHACK packet payload:
+     * - plain
+     *   - version trailer, none from relays speaking v1 only
      */
-    fn on_attach_reponse(&mut self, _input: &[u8]) -> Result<()> {
-        debug!("Connection {} got ATTACH ACK from server {}", self.cid(), srv_endp!(self.inners));
+    fn on_attach_reponse(&mut self, input: &[u8]) -> Result<()> {
+        debug!("Connection {} got ATTACH ACK from server {}", self.cid(), self.endpoints.remote_name);
+        let version = self.negotiate(&input[PACKET_HEADER_BYTES..])?;
         self.state = State::Idling;
-        self.on_opened();
-        info!("Connection {} opened.", self.cid());
+        self.on_opened(version);
+        info!("Connection {} opened with protocol v{version}.", self.cid());
         Ok(())
     }
 
+    /*
+     * INCOMPATIBLE packet payload:
+     * - plain
+     *   - minVersion[uint8]
+     *   - maxVersion[uint8]
+     */
+    fn on_incompatible_response(&mut self, input: &[u8]) -> Result<()> {
+        let Some(relay) = version::decode_refusal(&input[PACKET_HEADER_BYTES..]) else {
+            error!("Connection {} got invalid INCOMPATIBLE from server {}", self.cid(), self.endpoints.remote_name);
+            return Err(ProtocolError::new("Invalid INCOMPATIBLE packet"));
+        };
+        Err(self.on_incompatible(IncompatibleVersionError::new(relay, self.endpoints.versions)))
+    }
+
     /*
      * No Payload.
      */
     fn on_ping_response(&mut self, _input: &[u8]) -> Result<()> {
-        debug!("Connection {} got PING ACK from server {}", self.cid(), srv_endp!(self.inners));
+        debug!("Connection {} got PING ACK from server {}", self.cid(), self.endpoints.remote_name);
         // ignore the random padding payload.
         // keep-alive time stamp already update when we got the server data.
         // so nothing to do here.
@@ -716,27 +749,27 @@ impl ProxyConnection {
         if input.len() < Self::CONNECT_REQ_SIZE {
             error!("Connection {} got invalid CONNECT request from server {}, expected length: {}, acutal length:{}",
                 self.cid(),
-                srv_endp!(self.inners),
+                self.endpoints.remote_name,
                 Self::CONNECT_REQ_SIZE,
                 input.len()
             );
             return Err(ProtocolError::new("Invalid CONNECT packet"));
         }
 
-        debug!("Connection {} got CONNECT from server {}", self.cid(), srv_endp!(self.inners));
+        debug!("Connection {} got CONNECT from server {}", self.cid(), self.endpoints.remote_name);
         self.state = State::Relaying;
         self.on_busy();
 
         let plain_len = Self::CONNECT_REQ_SIZE - PACKET_HEADER_BYTES - CryptoBox::MAC_BYTES - cryptobox::Nonce::BYTES;
         let mut plain = vec![0u8; plain_len];
 
-        let _ = enbox!(self.inners).decrypt(
+        let _ = unwrap!(self.endpoints.cryptobox).decrypt(
             &input[PACKET_HEADER_BYTES..Self::CONNECT_REQ_SIZE],
             &mut plain[..]
         ).map_err(|e| {
             error!("Connection {} decrypt CONNECT request packet from server {} error: {e}",
                 self.cid(),
-                srv_endp!(self.inners)
+                self.endpoints.remote_name
             ); e
         })?;
 
@@ -763,7 +796,10 @@ impl ProxyConnection {
         let port = u16::from_be_bytes(input[pos..end].try_into().unwrap());
         let addr = SocketAddr::new(ip, port);
 
-        if self.allow(&addr) {
+        let restricted = !self.endpoints.allowed_clients.is_empty();
+        if self.allow(&addr) && restricted {
+            self.challenge_client().await
+        } else if self.allow(&addr) {
             self.open_upstream().await
         } else {
             self.send_connect_response(false).await?;
@@ -779,25 +815,38 @@ impl ProxyConnection {
      *   - data
      */
     async fn on_data_request(&mut self, input: &[u8]) -> Result<()> {
-        debug!("Connection {} got DATA({}) from server {}", self.cid(), input.len(), srv_endp!(self.inners));
+        debug!("Connection {} got DATA({}) from server {}", self.cid(), input.len(), self.endpoints.remote_name);
 
         let plain_len = input.len() - PACKET_HEADER_BYTES - CryptoBox::MAC_BYTES - cryptobox::Nonce::BYTES;
         let mut data = Box::new(vec![0u8; plain_len]);
 
-        _ = enbox!(self.inners).decrypt(
+        _ = unwrap!(self.endpoints.cryptobox).decrypt(
             &input[PACKET_HEADER_BYTES..],
             &mut data[..]
         ).map_err(|e| {
             error!("Connection {} decrypt CONNECT request packet from server {} error: {e}",
                 self.cid(),
-                srv_endp!(self.inners)
+                self.endpoints.remote_name
             ); e
         })?;
 
+        if self.client_challenge.is_some() {
+            return self.on_client_response(&data).await;
+        }
+        self.send_upstream(&data).await
+    }
+
+    async fn send_upstream(&mut self, data: &[u8]) -> Result<()> {
+        if self.upstream_writer.is_none() {
+            // The upstream is closed or was never opened for a refused client.
+            debug!("Connection {} dropped {} bytes data without upstream", self.cid(), data.len());
+            return Ok(())
+        }
+
         trace!("Connection {} sending {} bytes data to upstream {}",
             self.cid(),
             data.len(),
-            ups_endp!(self.inners)
+            self.endpoints.upstream_name
         );
 
         let mut written = 0;
@@ -807,7 +856,7 @@ impl ProxyConnection {
                 Err(e) => {
                     error!("Connection {} send DATA to upstream {} error: {e}",
                         self.cid(),
-                        srv_endp!(self.inners)
+                        self.endpoints.remote_name
                     );
                     return Err(e.into())
                 }
@@ -818,7 +867,7 @@ impl ProxyConnection {
         debug!("Connection {} sended DATA (len:{}) to upstream {}.",
             self.cid(),
             data.len(),
-            ups_endp!(self.inners)
+            self.endpoints.upstream_name
         );
 
         Ok(())
@@ -828,8 +877,9 @@ impl ProxyConnection {
      * No payload
      */
     async fn on_disconnect_request(&mut self, _input: &[u8]) -> Result<()> {
-        debug!("Connection {} got DISCONNECT from server {}", self.cid(), srv_endp!(self.inners));
+        debug!("Connection {} got DISCONNECT from server {}", self.cid(), self.endpoints.remote_name);
 
+        self.client_challenge = None;
         _ = self.close_upstream();
         _ = self.send_disconnect_response().await?;
 
@@ -846,7 +896,7 @@ impl ProxyConnection {
     * No payload
     */
     fn on_disconnect_response(&mut self, _input: &[u8]) -> Result<()> {
-        debug!("Connection {} got DISCONNECT_ACK from server {}", self.cid(), srv_endp!(self.inners));
+        debug!("Connection {} got DISCONNECT_ACK from server {}", self.cid(), self.endpoints.remote_name);
 
         self.disconnect_confirms += 1;
         if self.disconnect_confirms == 2 {
@@ -866,7 +916,7 @@ impl ProxyConnection {
     *     - connectionNonce
     *     - signature[challenge]
     *   - plain
-    *     - padding
+    *     - version trailer
     */
     async fn send_attach_request(&mut self, dev_sig: &[u8]) -> Result<()> {
         assert!(dev_sig.len() == Signature::BYTES);
@@ -880,21 +930,24 @@ impl ProxyConnection {
         let mut plain:Vec<u8> = Vec::with_capacity(len);
         plain.extend_from_slice(dev_sig);           // signature of challenge.
 
+        let offer = version::encode_offer(self.endpoints.versions);
         let len = PACKET_HEADER_BYTES
             + Id::BYTES                          // plain device id
             + cryptobox::Nonce::BYTES  + cryptobox::CryptoBox::MAC_BYTES // encryption padding of nonce + MAC
-            + plain.len();
+            + plain.len()
+            + offer.len();                       // versions offered
 
         let mut payload =vec![0u8;len];
         payload[PACKET_HEADER_BYTES..PACKET_HEADER_BYTES + Id::BYTES].copy_from_slice(self.deviceid.as_bytes());
         self.encrypt(
-            srv_peer!(self.inners).lock().unwrap().id(),
+            &self.endpoints.remote_peerid,
             &plain,
-            &mut payload[PACKET_HEADER_BYTES + Id::BYTES..]
+            &mut payload[PACKET_HEADER_BYTES + Id::BYTES..len - offer.len()]
         ).map_err(|e| {
             error!("Connection {} failed to encrypt attach request: {e}", self.cid());
             e
         })?;
+        payload[len - offer.len()..].copy_from_slice(&offer);
 
         self.send_relay_packet(
             Packet::Attach(AttachType),
@@ -912,6 +965,8 @@ impl ProxyConnection {
         mem::size_of::<8>()                 // domain size.
         Signature::BYTES                    // signature of challenge from user client.
         Signature::BYTES                    // signature of challenge from device node.
+        version trailer                     // versions offered, plain.
+        padding
     */
     async fn send_authenticate_request(&mut self, user_sig: &[u8], dev_sig: &[u8]) -> Result<()> {
         assert!(user_sig.len() == Signature::BYTES);
@@ -923,7 +978,7 @@ impl ProxyConnection {
 
         self.state = State::Authenticating;
 
-        //let domain_len = self.inners.lock().unwrap().peer_domain.as_ref().map_or(0, |v|v.len());
+        //let domain_len = self.endpoints.peer_domain.as_ref().map_or(0, |v|v.len());
         let len = Id::BYTES                      // client user id.
             + cryptobox::PublicKey::BYTES       // client public key.
             + mem::size_of::<u8>()              // the value to domain length.
@@ -931,16 +986,18 @@ impl ProxyConnection {
             + Signature::BYTES;                 // signature of challenge from device node.
 
         let mut plain = Vec::with_capacity(len);
-        plain.extend_from_slice(self.inners().lock().unwrap().userid.as_bytes()); // userid
-        plain.extend_from_slice(self.inners().lock().unwrap().session_keypair.public_key().as_bytes()); // client session public key
+        plain.extend_from_slice(self.endpoints.userid.as_bytes()); // userid
+        plain.extend_from_slice(self.endpoints.session_keypair.public_key().as_bytes()); // client session public key
         plain.extend_from_slice(&[false as u8]);        // boolean for domain DNS
         plain.extend_from_slice(user_sig);              // signature of challenge.
         plain.extend_from_slice(dev_sig);               // signature of challenge.
 
+        let offer = version::encode_offer(self.endpoints.versions);
         let mut len = PACKET_HEADER_BYTES               // packet header.
             + Id::BYTES                                  // plain device id
             + cryptobox::Nonce::BYTES  + cryptobox::CryptoBox::MAC_BYTES // encryption padding of nonce + MAC
-            + plain.len();                              // encyption payload
+            + plain.len()                               // encyption payload
+            + offer.len();                              // versions offered
 
         let mut padding_sz = random_padding() as usize;
         if padding_sz == 0 {
@@ -951,13 +1008,14 @@ impl ProxyConnection {
         let mut payload =vec![0u8;len];
         payload[PACKET_HEADER_BYTES..PACKET_HEADER_BYTES + Id::BYTES].copy_from_slice(self.deviceid.as_bytes());
         self.encrypt(
-            srv_peer!(self.inners).lock().unwrap().id(),
+            &self.endpoints.remote_peerid,
             &plain,
-            &mut payload[PACKET_HEADER_BYTES + Id::BYTES..]
+            &mut payload[PACKET_HEADER_BYTES + Id::BYTES..len - padding_sz - offer.len()]
         ).map_err(|e| {
             error!("Connection {} failed to encrypt authentication request: {e}", self.cid());
             e
         })?;
+        payload[len - padding_sz - offer.len()..len - padding_sz].copy_from_slice(&offer);
 
         if padding_sz > 0 {
             let padding = random_bytes(padding_sz);     // padding
@@ -1102,7 +1160,7 @@ impl ProxyConnection {
                     error!("Connection {} failed to send {} to server {} with error: {e}",
                         self.cid(),
                         pkt,
-                        srv_endp!(self.inners)
+                        self.endpoints.remote_name
                     );
                     return Err(e.into())
                 }
@@ -1114,12 +1172,16 @@ impl ProxyConnection {
             self.cid(),
             pkt,
             input.len(),
-            srv_endp!(self.inners)
+            self.endpoints.remote_name
         );
         Ok(())
     }
 
     pub(crate) async fn on_upstream_data(&mut self, input: &[u8]) -> Result<()> {
+        self.send_data(input).await
+    }
+
+    async fn send_data(&mut self, input: &[u8]) -> Result<()> {
         let len = PACKET_HEADER_BYTES
             + cryptobox::Nonce::BYTES  + cryptobox::CryptoBox::MAC_BYTES // encryption padding of nonce + MAC
             + input.len();
@@ -1127,14 +1189,14 @@ impl ProxyConnection {
         let mut payload = vec![0u8; len];
         let nonce = cryptobox::Nonce::random();
 
-        _ = enbox!(self.inners).encrypt(
+        _ = unwrap!(self.endpoints.cryptobox).encrypt(
             &input[..],
             &mut payload[PACKET_HEADER_BYTES..],
             &nonce
         ).map_err(|e| {
             error!("Connection {} encrypt DATA packet to server {} error: {e}",
                 self.cid(),
-                srv_endp!(self.inners)
+                self.endpoints.remote_name
             ); e
         })?;
 
@@ -0,0 +1,726 @@
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, tcp::{OwnedReadHalf, OwnedWriteHalf}};
+use tokio::sync::watch;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::{self, Instant};
+use log::{info, debug, warn};
+
+use crate::{
+    random_bytes,
+    Id,
+    Result,
+    CryptoContext,
+    signature,
+    cryptobox::{self, CryptoBox, Nonce},
+    core::errors::{NetworkError, ProtocolError},
+};
+
+use super::{
+    random_padding,
+    packet::{Packet, AttachType, AuthType, ConnType, DisconnType, DataType},
+};
+
+// packet size (2bytes) + packet type(1bytes)
+const PACKET_HEADER_BYTES: usize = mem::size_of::<u16>() + mem::size_of::<u8>();
+
+// Bytes read from a SOCKS client into one DATA packet. The tunnel writes
+// each packet out before reading on, so a slow side holds back the other
+// one of its own connection only.
+const MAX_DATA_CHUNK: usize = 16 * 1024;
+
+const HANDSHAKE_TIMEOUT:    Duration = Duration::from_secs(10);
+const DISCONNECT_TIMEOUT:   Duration = Duration::from_secs(5);
+
+const SOCKS_VERSION:        u8 = 0x05;
+const SOCKS_NO_AUTH:        u8 = 0x00;
+const SOCKS_NO_METHODS:     u8 = 0xFF;
+const SOCKS_CMD_CONNECT:    u8 = 0x01;
+
+const ATYP_IPV4:            u8 = 0x01;
+const ATYP_DOMAIN:          u8 = 0x03;
+const ATYP_IPV6:            u8 = 0x04;
+
+const REPLY_SUCCEEDED:              u8 = 0x00;
+const REPLY_GENERAL_FAILURE:        u8 = 0x01;
+const REPLY_HOST_UNREACHABLE:       u8 = 0x04;
+const REPLY_CONNECTION_REFUSED:     u8 = 0x05;
+const REPLY_COMMAND_NOT_SUPPORTED:  u8 = 0x07;
+const REPLY_ADDRESS_NOT_SUPPORTED:  u8 = 0x08;
+
+// The destination a SOCKS client asks for, a domain name is resolved by
+// the exit unless the proxy resolves it locally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Target {
+    Addr(SocketAddr),
+    Domain(String, u16),
+}
+
+impl Target {
+    /*
+     * The address as SOCKS5 encodes it, and as the CONNECT-TO request
+     * carries it to the exit:
+     * - atyp[uint8]: 1 for IPv4, 3 for a domain name, 4 for IPv6
+     * - addr: 4 bytes, length[uint8] and name, or 16 bytes
+     * - port[uint16]
+     */
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32);
+        match self {
+            Target::Addr(SocketAddr::V4(addr)) => {
+                bytes.push(ATYP_IPV4);
+                bytes.extend_from_slice(&addr.ip().octets());
+            },
+            Target::Addr(SocketAddr::V6(addr)) => {
+                bytes.push(ATYP_IPV6);
+                bytes.extend_from_slice(&addr.ip().octets());
+            },
+            Target::Domain(name, _) => {
+                bytes.push(ATYP_DOMAIN);
+                bytes.push(name.len() as u8);
+                bytes.extend_from_slice(name.as_bytes());
+            },
+        }
+        bytes.extend_from_slice(&self.port().to_be_bytes());
+        bytes
+    }
+
+    pub(crate) fn from_bytes(input: &[u8]) -> Result<Self> {
+        let Some(len) = Self::encoded_len(input) else {
+            return Err(ProtocolError::new("Unsupported address type"));
+        };
+        if input.len() != len {
+            return Err(ProtocolError::new(format!("Invalid address length {}", input.len())));
+        }
+
+        let port = u16::from_be_bytes(input[len - 2..].try_into().unwrap());
+        let target = match input[0] {
+            ATYP_IPV4 => {
+                let octets: [u8; 4] = input[1..5].try_into().unwrap();
+                Target::Addr(SocketAddr::new(IpAddr::V4(Ipv4Addr::from(octets)), port))
+            },
+            ATYP_IPV6 => {
+                let octets: [u8; 16] = input[1..17].try_into().unwrap();
+                Target::Addr(SocketAddr::new(IpAddr::V6(Ipv6Addr::from(octets)), port))
+            },
+            _ => {
+                let name = std::str::from_utf8(&input[2..len - 2])
+                    .map_err(|_| ProtocolError::new("Invalid domain name"))?;
+                if name.is_empty() {
+                    return Err(ProtocolError::new("Empty domain name"));
+                }
+                Target::Domain(name.to_string(), port)
+            },
+        };
+        Ok(target)
+    }
+
+    // The length of the encoded address, known from its first one or two
+    // bytes, None for an unknown address type.
+    fn encoded_len(input: &[u8]) -> Option<usize> {
+        let port = mem::size_of::<u16>();
+        match *input.first()? {
+            ATYP_IPV4   => Some(1 + 4 + port),
+            ATYP_IPV6   => Some(1 + 16 + port),
+            ATYP_DOMAIN => Some(1 + 1 + *input.get(1)? as usize + port),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        match self {
+            Target::Addr(addr) => addr.port(),
+            Target::Domain(_, port) => *port,
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Addr(addr) => write!(f, "{}", addr),
+            Target::Domain(name, port) => write!(f, "{}:{}", name, port),
+        }
+    }
+}
+
+// Reads the SOCKS5 greeting and request of a client. Only clients offering
+// no authentication and asking to CONNECT are served, the others are told
+// before the error is returned.
+pub(crate) async fn socks5_handshake<S>(stream: &mut S) -> Result<Target>
+where S: AsyncRead + AsyncWrite + Unpin {
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head).await?;
+    if head[0] != SOCKS_VERSION {
+        return Err(ProtocolError::new(format!("Unsupported SOCKS version {}", head[0])));
+    }
+
+    let mut methods = vec![0u8; head[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&SOCKS_NO_AUTH) {
+        stream.write_all(&[SOCKS_VERSION, SOCKS_NO_METHODS]).await?;
+        return Err(ProtocolError::new("No supported SOCKS authentication method"));
+    }
+    stream.write_all(&[SOCKS_VERSION, SOCKS_NO_AUTH]).await?;
+
+    // ver, cmd, rsv, then the address up to its length.
+    let mut request = [0u8; 5];
+    stream.read_exact(&mut request).await?;
+    if request[0] != SOCKS_VERSION {
+        return Err(ProtocolError::new(format!("Unsupported SOCKS version {}", request[0])));
+    }
+
+    let Some(len) = Target::encoded_len(&request[3..]) else {
+        socks5_reply(stream, REPLY_ADDRESS_NOT_SUPPORTED).await?;
+        return Err(ProtocolError::new(format!("Unsupported SOCKS address type {}", request[3])));
+    };
+    let mut addr = vec![0u8; len];
+    addr[..2].copy_from_slice(&request[3..]);
+    stream.read_exact(&mut addr[2..]).await?;
+
+    if request[1] != SOCKS_CMD_CONNECT {
+        socks5_reply(stream, REPLY_COMMAND_NOT_SUPPORTED).await?;
+        return Err(ProtocolError::new(format!("Unsupported SOCKS command {}", request[1])));
+    }
+
+    match Target::from_bytes(&addr).map_err(|e| e.to_string()) {
+        Ok(target) => Ok(target),
+        Err(e) => {
+            socks5_reply(stream, REPLY_ADDRESS_NOT_SUPPORTED).await?;
+            Err(ProtocolError::new(e))
+        }
+    }
+}
+
+// The bound address is left unspecified, the clients have no use for it.
+async fn socks5_reply<S>(stream: &mut S, code: u8) -> io::Result<()>
+where S: AsyncWrite + Unpin {
+    stream.write_all(&[SOCKS_VERSION, code, 0, ATYP_IPV4, 0, 0, 0, 0, 0, 0]).await
+}
+
+// The exit peer and the keys to authenticate with, shared by the tunnels
+// of a local proxy.
+pub(crate) struct ExitSession {
+    exit_peerid:        Id,
+    exit_addr:          SocketAddr,
+
+    userid:             Id,
+    keypair:            signature::KeyPair,
+    session_keypair:    cryptobox::KeyPair,
+    device_keypair:     signature::KeyPair,
+
+    remote_dns:         bool,
+
+    // Set once a tunnel authenticated, the later ones attach to the session.
+    cryptobox:          Mutex<Option<CryptoBox>>,
+}
+
+impl ExitSession {
+    pub(crate) fn new(keypair: &signature::KeyPair, exit_peerid: Id, exit_addr: SocketAddr, remote_dns: bool) -> Self {
+        Self {
+            exit_peerid,
+            exit_addr,
+
+            userid:             Id::from(keypair.public_key()),
+            keypair:            keypair.clone(),
+            session_keypair:    cryptobox::KeyPair::from(keypair),
+            device_keypair:     signature::KeyPair::random(),
+
+            remote_dns,
+            cryptobox:          Mutex::new(None),
+        }
+    }
+
+    fn deviceid(&self) -> Id {
+        Id::from(self.device_keypair.public_key())
+    }
+
+    fn crypto_context(&self) -> CryptoContext {
+        let encryption_keypair = cryptobox::KeyPair::from(&self.device_keypair);
+        CryptoContext::from_private_key(self.exit_peerid, encryption_keypair.private_key())
+    }
+
+    // Domain names go to the exit as they are, unless resolved here.
+    async fn resolve(&self, target: Target) -> io::Result<Target> {
+        let Target::Domain(name, port) = &target else {
+            return Ok(target);
+        };
+        if self.remote_dns {
+            return Ok(target);
+        }
+
+        tokio::net::lookup_host((name.as_str(), *port)).await?
+            .next()
+            .map(Target::Addr)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("No address for {}", name)))
+    }
+}
+
+// Splits the stream from the exit into packets, keeping the bytes read
+// ahead across cancelled reads.
+struct PacketReader {
+    reader:     OwnedReadHalf,
+    buf:        Vec<u8>,
+    chunk:      Vec<u8>,
+}
+
+impl PacketReader {
+    fn new(reader: OwnedReadHalf) -> Self {
+        Self {
+            reader,
+            buf:    Vec::with_capacity(4 * 1024),
+            chunk:  vec![0u8; MAX_DATA_CHUNK],
+        }
+    }
+
+    // The next packet with its header, None once the exit closed. Gives an
+    // io error, the output of a select over it has to stay Send.
+    async fn next(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if self.buf.len() >= mem::size_of::<u16>() {
+                let len = u16::from_be_bytes(self.buf[..2].try_into().unwrap()) as usize;
+                if len < PACKET_HEADER_BYTES {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Invalid packet size {}", len)));
+                }
+                if self.buf.len() >= len {
+                    return Ok(Some(self.buf.drain(..len).collect()));
+                }
+            }
+
+            let len = self.reader.read(&mut self.chunk).await?;
+            if len == 0 {
+                return Ok(None);
+            }
+            self.buf.extend_from_slice(&self.chunk[..len]);
+        }
+    }
+
+    async fn expect(&mut self) -> Result<(Packet, Vec<u8>)> {
+        let Some(mut input) = self.next().await? else {
+            return Err(NetworkError::new("Exit closed the connection"));
+        };
+        let packet = Packet::from(input[mem::size_of::<u16>()])?;
+        Ok((packet, input.split_off(PACKET_HEADER_BYTES)))
+    }
+}
+
+// A connection to the exit, authenticated or attached to the session.
+struct ExitLink {
+    reader:     PacketReader,
+    writer:     OwnedWriteHalf,
+    cryptobox:  CryptoBox,
+}
+
+impl ExitLink {
+    async fn open(session: &ExitSession) -> Result<Self> {
+        let stream = TcpStream::connect(session.exit_addr).await?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = PacketReader::new(reader);
+
+        /*
+         * Challenge packet
+         * - plain
+         *   - Random challenge bytes.
+         */
+        let Some(challenge) = reader.next().await? else {
+            return Err(NetworkError::new("Exit closed the connection before the challenge"));
+        };
+        let challenge = &challenge[mem::size_of::<u16>()..];
+        if challenge.len() < 32 || challenge.len() > 256 {
+            return Err(ProtocolError::new(format!("Invalid challenge length {}", challenge.len())));
+        }
+
+        let dev_sig = signature::sign_into(challenge, session.device_keypair.private_key())?;
+        let mut context = session.crypto_context();
+        let attached = session.cryptobox.lock().unwrap().clone();
+
+        let cryptobox = match attached {
+            Some(cryptobox) => {
+                /*
+                 * ATTACH packet:
+                 *   - plain
+                 *     - clientNodeId
+                 *   - encrypted
+                 *     - signature[challenge]
+                 */
+                let mut payload = session.deviceid().as_bytes().to_vec();
+                payload.extend_from_slice(&context.encrypt_into(&dev_sig)?);
+                write_packet(&mut writer, Packet::Attach(AttachType), &payload).await?;
+
+                match reader.expect().await? {
+                    (Packet::AttachAck(_), _) => cryptobox,
+                    (packet, _) => return Err(ProtocolError::new(format!("Exit answered ATTACH with {}", packet))),
+                }
+            },
+            None => {
+                /*
+                 * AUTH packet:
+                 *   - plain
+                 *     - clientNodeId
+                 *   - encrypted
+                 *     - userId, sessionPk[client], domainEnabled[uint8]
+                 *     - signature[challenge] of the user and of the device
+                 *   - plain
+                 *     - padding
+                 */
+                let user_sig = signature::sign_into(challenge, session.keypair.private_key())?;
+                let mut plain = Vec::with_capacity(Id::BYTES + cryptobox::PublicKey::BYTES + 1 + 2 * user_sig.len());
+                plain.extend_from_slice(session.userid.as_bytes());
+                plain.extend_from_slice(session.session_keypair.public_key().as_bytes());
+                plain.push(false as u8);
+                plain.extend_from_slice(&user_sig);
+                plain.extend_from_slice(&dev_sig);
+
+                let mut payload = session.deviceid().as_bytes().to_vec();
+                payload.extend_from_slice(&context.encrypt_into(&plain)?);
+                payload.extend_from_slice(&padding());
+                write_packet(&mut writer, Packet::Auth(AuthType), &payload).await?;
+
+                /*
+                 * AUTHACK packet payload:
+                 * - encrypted
+                 *   - sessionPk[server]
+                 *   - port[uint16]
+                 *   - maxConnections[uint16]
+                 *   - domainEnabled[uint8]
+                 */
+                let input = match reader.expect().await? {
+                    (Packet::AuthAck(_), input) => input,
+                    (packet, _) => return Err(ProtocolError::new(format!("Exit answered AUTH with {}", packet))),
+                };
+                let cipher_len = Nonce::BYTES + CryptoBox::MAC_BYTES + cryptobox::PublicKey::BYTES
+                    + 2 * mem::size_of::<u16>() + mem::size_of::<u8>();
+                if input.len() < cipher_len {
+                    return Err(ProtocolError::new("Invalid AUTH ACK packet"));
+                }
+
+                let plain = context.decrypt_into(&input[..cipher_len])?;
+                let server_pk = cryptobox::PublicKey::try_from(&plain[..cryptobox::PublicKey::BYTES])?;
+                let cryptobox = CryptoBox::try_from((&server_pk, session.session_keypair.private_key()))?;
+                *session.cryptobox.lock().unwrap() = Some(cryptobox.clone());
+                cryptobox
+            },
+        };
+
+        Ok(Self { reader, writer, cryptobox })
+    }
+
+    /*
+     * CONNECT packet from the client, the CONNECT-TO request:
+     * - encrypted
+     *   - the target address, see Target::to_bytes
+     *
+     * CONNECTACK packet payload:
+     * - plain
+     *   - success[uint8]
+     *   - padding
+     */
+    async fn connect(&mut self, target: &Target) -> Result<bool> {
+        let payload = self.cryptobox.encrypt_into(&target.to_bytes(), &Nonce::random())?;
+        write_packet(&mut self.writer, Packet::Connect(ConnType), &payload).await?;
+
+        match self.reader.expect().await? {
+            (Packet::ConnectAck(_), input) if !input.is_empty() => Ok(input[0] & 0x01 != 0),
+            (Packet::Error(_), input) => Err(ProtocolError::new(self.error_message(&input))),
+            (packet, _) => Err(ProtocolError::new(format!("Exit answered CONNECT with {}", packet))),
+        }
+    }
+
+    /*
+     * ERROR packet payload:
+     * - encrypted
+     *   - code[uint16]
+     *   - message
+     */
+    fn error_message(&self, input: &[u8]) -> String {
+        if input.len() < Nonce::BYTES + CryptoBox::MAC_BYTES {
+            return "Exit error".into();
+        }
+        match self.cryptobox.decrypt_into(input) {
+            Ok(plain) if plain.len() >= mem::size_of::<u16>() => format!("Exit error {}: {}",
+                u16::from_be_bytes(plain[..2].try_into().unwrap()),
+                String::from_utf8_lossy(&plain[2..])
+            ),
+            _ => "Exit error".into(),
+        }
+    }
+}
+
+fn padding() -> Vec<u8> {
+    random_bytes((random_padding() as usize).max(1))
+}
+
+async fn write_packet(writer: &mut OwnedWriteHalf, packet: Packet, payload: &[u8]) -> io::Result<()> {
+    let len = PACKET_HEADER_BYTES + payload.len();
+    let mut input = Vec::with_capacity(len);
+    input.extend_from_slice(&(len as u16).to_be_bytes());
+    input.push(packet.value());
+    input.extend_from_slice(payload);
+    writer.write_all(&input).await
+}
+
+#[derive(Default)]
+struct Counters {
+    tunnels:        AtomicU64,
+    active:         AtomicUsize,
+    failures:       AtomicU64,
+    bytes_sent:     AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+// Tunnels of a local proxy since it started: those opened for the SOCKS
+// clients, still open and failed before or while relaying, and the bytes
+// sent to and received from the exit for the clients.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LocalProxyStatistics {
+    pub(crate) tunnels          : u64,
+    pub(crate) active           : usize,
+    pub(crate) failures         : u64,
+    pub(crate) bytes_sent       : u64,
+    pub(crate) bytes_received   : u64,
+}
+
+impl LocalProxyStatistics {
+    pub fn tunnels(&self) -> u64 {
+        self.tunnels
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+}
+
+/// A running local proxy, stopped with [`LocalProxyHandle::stop`] or once
+/// dropped.
+pub struct LocalProxyHandle {
+    addr:           SocketAddr,
+    exit_peerid:    Id,
+    counters:       Arc<Counters>,
+    stop:           watch::Sender<bool>,
+    task:           Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LocalProxyHandle {
+    /// The address the SOCKS5 listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn exit_peerid(&self) -> &Id {
+        &self.exit_peerid
+    }
+
+    pub fn statistics(&self) -> LocalProxyStatistics {
+        LocalProxyStatistics {
+            tunnels:        self.counters.tunnels.load(Ordering::Relaxed),
+            active:         self.counters.active.load(Ordering::Relaxed),
+            failures:       self.counters.failures.load(Ordering::Relaxed),
+            bytes_sent:     self.counters.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.counters.bytes_received.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stops accepting SOCKS clients and disconnects the open tunnels,
+    /// returns once they are closed.
+    pub async fn stop(&self) {
+        _ = self.stop.send(true);
+        let task = self.task.lock().unwrap().take();
+        if let Some(task) = task {
+            _ = task.await;
+        }
+    }
+}
+
+// Binds the SOCKS5 listener and serves it on the current runtime, each
+// client through a connection of its own to the exit.
+pub(crate) async fn start(listen: SocketAddr, session: ExitSession) -> Result<LocalProxyHandle> {
+    let listener = TcpListener::bind(listen).await?;
+    let addr = listener.local_addr()?;
+    let exit_peerid = session.exit_peerid;
+    info!("ActiveProxy local proxy listening on {} through exit {} at {}", addr, exit_peerid, session.exit_addr);
+
+    let (stop, stopped) = watch::channel(false);
+    let counters = Arc::new(Counters::default());
+    let task = tokio::spawn(accept_loop(listener, Arc::new(session), stopped, counters.clone()));
+
+    Ok(LocalProxyHandle {
+        addr,
+        exit_peerid,
+        counters,
+        stop,
+        task: Mutex::new(Some(task)),
+    })
+}
+
+async fn accept_loop(listener: TcpListener,
+    session: Arc<ExitSession>,
+    mut stopped: watch::Receiver<bool>,
+    counters: Arc<Counters>
+) {
+    let mut tunnels = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, from)) => {
+                    debug!("ActiveProxy local proxy accepted SOCKS client {}", from);
+                    tunnels.spawn(run_tunnel(session.clone(), stream, stopped.clone(), counters.clone()));
+                },
+                Err(e) => warn!("ActiveProxy local proxy failed to accept: {e}"),
+            },
+            Some(_) = tunnels.join_next(), if !tunnels.is_empty() => {},
+            _ = stopped.changed() => break,
+        }
+    }
+
+    // The tunnels see the stop as well and disconnect.
+    drop(listener);
+    while tunnels.join_next().await.is_some() {}
+    info!("ActiveProxy local proxy stopped");
+}
+
+async fn run_tunnel(session: Arc<ExitSession>,
+    stream: TcpStream,
+    stopped: watch::Receiver<bool>,
+    counters: Arc<Counters>
+) {
+    counters.tunnels.fetch_add(1, Ordering::Relaxed);
+    counters.active.fetch_add(1, Ordering::Relaxed);
+
+    if let Err(e) = tunnel(&session, stream, stopped, &counters).await {
+        counters.failures.fetch_add(1, Ordering::Relaxed);
+        warn!("ActiveProxy local proxy tunnel failed: {e}");
+    }
+    counters.active.fetch_sub(1, Ordering::Relaxed);
+}
+
+async fn tunnel(session: &ExitSession,
+    mut stream: TcpStream,
+    stopped: watch::Receiver<bool>,
+    counters: &Counters
+) -> Result<()> {
+    let target = time::timeout(HANDSHAKE_TIMEOUT, socks5_handshake(&mut stream)).await
+        .map_err(|_| ProtocolError::new("SOCKS handshake timed out"))??;
+
+    let target = match session.resolve(target).await {
+        Ok(target) => target,
+        Err(e) => {
+            socks5_reply(&mut stream, REPLY_HOST_UNREACHABLE).await?;
+            return Err(e.into());
+        }
+    };
+
+    let opened = ExitLink::open(session).await.map_err(|e| e.to_string());
+    let mut link = match opened {
+        Ok(link) => link,
+        Err(e) => {
+            socks5_reply(&mut stream, REPLY_GENERAL_FAILURE).await?;
+            return Err(NetworkError::new(format!("Opening the tunnel to {} failed: {e}", target)));
+        }
+    };
+
+    let connected = link.connect(&target).await.map_err(|e| e.to_string());
+    match connected {
+        Ok(true) => socks5_reply(&mut stream, REPLY_SUCCEEDED).await?,
+        Ok(false) => {
+            socks5_reply(&mut stream, REPLY_CONNECTION_REFUSED).await?;
+            return Err(NetworkError::new(format!("Exit failed to connect to {}", target)));
+        },
+        Err(e) => {
+            socks5_reply(&mut stream, REPLY_GENERAL_FAILURE).await?;
+            return Err(NetworkError::new(format!("Connecting to {} failed: {e}", target)));
+        },
+    }
+
+    debug!("ActiveProxy local proxy tunnel to {} is open", target);
+    relay(link, stream, stopped, counters).await?;
+    debug!("ActiveProxy local proxy tunnel to {} is closed", target);
+    Ok(())
+}
+
+// Shuttles the data between the SOCKS client and the exit until either
+// disconnects. The client closing its side ends the tunnel, there is no
+// half-close over the exit connection.
+async fn relay(link: ExitLink,
+    stream: TcpStream,
+    mut stopped: watch::Receiver<bool>,
+    counters: &Counters
+) -> Result<()> {
+    let ExitLink { mut reader, mut writer, cryptobox } = link;
+    let (mut client_reader, mut client_writer) = stream.into_split();
+    let mut data = vec![0u8; MAX_DATA_CHUNK];
+    let mut disconnecting: Option<Instant> = None;
+
+    loop {
+        let deadline = disconnecting.unwrap_or_else(Instant::now);
+        tokio::select! {
+            read = client_reader.read(&mut data), if disconnecting.is_none() => match read {
+                Ok(len) if len > 0 => {
+                    let payload = cryptobox.encrypt_into(&data[..len], &Nonce::random())?;
+                    write_packet(&mut writer, Packet::Data(DataType), &payload).await?;
+                    counters.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+                },
+                _ => {
+                    write_packet(&mut writer, Packet::Disconnect(DisconnType), &padding()).await?;
+                    disconnecting = Some(Instant::now() + DISCONNECT_TIMEOUT);
+                },
+            },
+            input = reader.next() => {
+                let Some(mut input) = input? else {
+                    break;
+                };
+                let packet = Packet::from(input[mem::size_of::<u16>()])?;
+                let input = input.split_off(PACKET_HEADER_BYTES);
+                match packet {
+                    Packet::Data(_) => {
+                        if input.len() < Nonce::BYTES + CryptoBox::MAC_BYTES {
+                            return Err(ProtocolError::new("Invalid DATA packet from the exit"));
+                        }
+                        let plain = cryptobox.decrypt_into(&input)?;
+                        client_writer.write_all(&plain).await?;
+                        counters.bytes_received.fetch_add(plain.len() as u64, Ordering::Relaxed);
+                    },
+                    Packet::Disconnect(_) => {
+                        write_packet(&mut writer, Packet::DisconnectAck(DisconnType), &padding()).await?;
+                        break;
+                    },
+                    Packet::DisconnectAck(_) if disconnecting.is_some() => break,
+                    Packet::PingAck(_) => {},
+                    packet => {
+                        return Err(ProtocolError::new(format!("Unexpected {} packet from the exit", packet)));
+                    },
+                }
+            },
+            _ = stopped.changed(), if disconnecting.is_none() => {
+                write_packet(&mut writer, Packet::Disconnect(DisconnType), &padding()).await?;
+                disconnecting = Some(Instant::now() + DISCONNECT_TIMEOUT);
+            },
+            _ = time::sleep_until(deadline), if disconnecting.is_some() => {
+                warn!("ActiveProxy local proxy tunnel got no DISCONNECT ACK from the exit");
+                break;
+            },
+        }
+    }
+
+    _ = client_writer.shutdown().await;
+    _ = writer.shutdown().await;
+    Ok(())
+}
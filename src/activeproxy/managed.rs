@@ -1,76 +1,74 @@
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::time::SystemTime;
+use tokio::sync::{mpsc, oneshot};
+use log::info;
 
 use crate::{
     PeerInfo,
+    PeerBuilder,
     NodeInfo,
     cryptobox,
     signature,
     Id,
+    Clock,
+    SystemClock,
 };
 
-#[macro_export]
-macro_rules! srv_endp {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().remote_name).as_str()
-    };
-}
-
-#[macro_export]
-macro_rules! srv_addr {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().remote_addr)
-    };
-}
-
-#[macro_export]
-macro_rules! srv_nodeid {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().remote_node).lock().unwrap().id()
-    };
-}
+use super::{
+    client::ProxyStatistics,
+    version::VersionRange,
+};
 
-#[macro_export]
-macro_rules! srv_peer {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().remote_peer)
-    };
+// Messages to the worker task owning the managed state. The connections
+// report their progress with them, the client asks for its statistics and
+// to stop. Nothing waits on a connection while handling them.
+pub(crate) enum ManagedCmd {
+    QueryEndpoints(oneshot::Sender<Option<Endpoints>>),
+    Authorized {
+        pk:             cryptobox::PublicKey,
+        port:           u16,
+        domain_enabled: bool,
+        capacity:       usize,
+    },
+    Opened {
+        version:        u8,
+    },
+    OpenFailed,
+    // Ends the worker, the relay would refuse every connection alike.
+    Incompatible {
+        relay:          VersionRange,
+        client:         VersionRange,
+    },
+    Busy,
+    Idle,
+    ConnectionClosed,
+    Statistics(oneshot::Sender<ProxyStatistics>),
+    Stop,
 }
 
-#[macro_export]
-macro_rules! ups_endp {
-    ($managed:expr) => {
-       unwrap!($managed.lock().unwrap().upstream_name).as_str()
-    };
-}
+pub(crate) type ManagedSender = mpsc::UnboundedSender<ManagedCmd>;
 
-#[macro_export]
-macro_rules! ups_addr {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().upstream_addr)
-    };
-}
+// The part of the managed state a connection works with, copied to it
+// when it is opened.
+#[derive(Clone)]
+pub(crate) struct Endpoints {
+    pub(crate) userid:          Id,
+    pub(crate) keypair:         signature::KeyPair,
+    pub(crate) session_keypair: cryptobox::KeyPair,
+    pub(crate) cryptobox:       Option<cryptobox::CryptoBox>,
 
-#[macro_export]
-macro_rules! ups_peer {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().peer)
-    };
-}
+    pub(crate) remote_peerid:   Id,
+    pub(crate) remote_addr:     SocketAddr,
+    pub(crate) remote_name:     String,
 
-#[macro_export]
-macro_rules! session_keypair {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().session_keypair)
-    };
-}
+    pub(crate) upstream_addr:   SocketAddr,
+    pub(crate) upstream_name:   String,
 
-#[macro_export]
-macro_rules! enbox {
-    ($managed:expr) => {
-        unwrap!($managed.lock().unwrap().cryptobox)
-    };
+    pub(crate) allowed_clients: HashSet<Id>,
+    pub(crate) versions:        VersionRange,
+    pub(crate) clock:           Arc<dyn Clock>,
 }
 
 pub(crate) struct ManagedFields {
@@ -79,8 +77,8 @@ pub(crate) struct ManagedFields {
     pub(crate) session_keypair:     cryptobox::KeyPair,
     pub(crate) cryptobox:           Option<cryptobox::CryptoBox>,
 
-    pub(crate) remote_peer:         Option<Arc<Mutex<PeerInfo>>>,
-    pub(crate) remote_node:         Option<Arc<Mutex<NodeInfo>>>,
+    pub(crate) remote_peer:         Option<PeerInfo>,
+    pub(crate) remote_node:         Option<NodeInfo>,
     pub(crate) remote_addr:         Option<SocketAddr>,
     pub(crate) remote_name:         Option<String>,
 
@@ -93,6 +91,13 @@ pub(crate) struct ManagedFields {
     pub(crate) peer_domain:         Option<String>,
     pub(crate) peer:                Option<PeerInfo>,
 
+    // Empty for the open mode, where every client is relayed to the upstream.
+    pub(crate) allowed_clients:     HashSet<Id>,
+
+    pub(crate) versions:            VersionRange,
+    // Negotiated by the last connection opened.
+    pub(crate) session_version:     Option<u8>,
+
     pub(crate) server_failures:     i32,
     pub(crate) reconnect_delay:     u128,
 
@@ -104,6 +109,8 @@ pub(crate) struct ManagedFields {
     pub(crate) last_announce_peer:  SystemTime,
     pub(crate) last_save_peer:      SystemTime,
 
+    pub(crate) clock:               Arc<dyn Clock>,
+
     //pub(crate) last_health_check:   SystemTime,
     //pub(crate) last_reconnect:      SystemTime
 }
@@ -130,6 +137,11 @@ impl ManagedFields {
             peer:               None,
             relay_port:         None,
 
+            allowed_clients:    HashSet::new(),
+
+            versions:           VersionRange::default(),
+            session_version:    None,
+
             server_failures:    0,
             reconnect_delay:    0,
 
@@ -139,7 +151,9 @@ impl ManagedFields {
 
             last_idle_check:    SystemTime::UNIX_EPOCH,
             last_announce_peer: SystemTime::UNIX_EPOCH,
-            last_save_peer:     SystemTime::UNIX_EPOCH
+            last_save_peer:     SystemTime::UNIX_EPOCH,
+
+            clock:              Arc::new(SystemClock),
         }
     }
 
@@ -185,4 +199,100 @@ impl ManagedFields {
 
         false   // TODO: refine the conditions later.
     }
+
+    // None until the server peer was set.
+    pub(crate) fn endpoints(&self) -> Option<Endpoints> {
+        Some(Endpoints {
+            userid:             self.userid,
+            keypair:            self.keypair.clone(),
+            session_keypair:    self.session_keypair.clone(),
+            cryptobox:          self.cryptobox.clone(),
+
+            remote_peerid:      self.remote_peer.as_ref().map(|peer| *peer.id())?,
+            remote_addr:        self.remote_addr?,
+            remote_name:        self.remote_name.clone()?,
+
+            upstream_addr:      self.upstream_addr?,
+            upstream_name:      self.upstream_name.clone()?,
+
+            allowed_clients:    self.allowed_clients.clone(),
+            versions:           self.versions,
+            clock:              self.clock.clone(),
+        })
+    }
+
+    pub(crate) fn statistics(&self) -> ProxyStatistics {
+        ProxyStatistics {
+            connections:        self.connections,
+            inflights:          self.inflights,
+            capacity:           self.capacity,
+            server_failures:    self.server_failures,
+            relay_port:         self.relay_port,
+            session_version:    self.session_version,
+        }
+    }
+
+    // Applies a message other than Stop, which ends the worker loop instead.
+    pub(crate) fn handle(&mut self, cmd: ManagedCmd) {
+        match cmd {
+            ManagedCmd::QueryEndpoints(reply) => {
+                _ = reply.send(self.endpoints());
+            },
+            ManagedCmd::Authorized { pk, port, domain_enabled, capacity } => {
+                self.on_authorized(&pk, port, domain_enabled, capacity);
+            },
+            ManagedCmd::Opened { version } => {
+                self.session_version = Some(version);
+                self.server_failures = 0;
+                self.reconnect_delay = 0;
+            },
+            ManagedCmd::OpenFailed => {
+                let failures = self.server_failures;
+                self.server_failures = failures + 1;
+                if self.reconnect_delay < 64 {
+                    self.reconnect_delay = (1 << failures) * 1000;
+                }
+            },
+            ManagedCmd::Busy => {
+                self.inflights += 1;
+                self.last_idle_check = SystemTime::UNIX_EPOCH;
+            },
+            ManagedCmd::Idle => {
+                self.inflights -= 1;
+                if self.inflights == 0 {
+                    self.last_idle_check = self.clock.now();
+                }
+            },
+            ManagedCmd::ConnectionClosed => {
+                self.connections -= 1;
+            },
+            ManagedCmd::Statistics(reply) => {
+                _ = reply.send(self.statistics());
+            },
+            ManagedCmd::Incompatible { .. } | ManagedCmd::Stop => {},
+        }
+    }
+
+    fn on_authorized(&mut self, pk: &cryptobox::PublicKey, port: u16, domain_enabled: bool, capacity: usize) {
+        self.relay_port = Some(port);
+        self.cryptobox  = cryptobox::CryptoBox::try_from((pk, self.session_keypair.private_key())).ok();
+        self.domain_enabled = domain_enabled;
+        self.capacity   = capacity;
+
+        let (Some(keypair), Some(addr)) = (self.peer_keypair.as_ref(), self.remote_addr) else {
+            return;
+        };
+
+        let endpoint = format!("tcp://{}", SocketAddr::new(addr.ip(), port));
+        let result = PeerBuilder::new(&endpoint)
+            .with_fingerprint(0)
+            .with_sequence_number(0)
+            .with_key(keypair.clone())
+            .build();
+
+        if let Ok(peer) = result {
+            info!("-**- ActiveProxy: peer server endpoint: {} -**-", peer.endpoint());
+            self.peer = Some(peer);
+        }
+    }
 }
@@ -3,29 +3,37 @@ mod state;
 mod connection;
 mod managed;
 mod worker;
+mod client_auth;
+mod local_proxy;
+mod version;
 pub mod client;
+pub mod supervisor;
 
 #[cfg(test)]
 mod unitests {
     mod test_activeproxy;
+    mod test_supervisor;
+    mod test_client_auth;
+    mod test_worker;
+    mod test_local_proxy;
+    mod test_version;
 }
 
 pub use {
     client::ProxyClient as ActiveProxyClient,
+    client_auth::authenticate,
+    local_proxy::{LocalProxyHandle, LocalProxyStatistics},
+    version::{VersionRange, IncompatibleVersionError},
 };
 
 pub(crate)
 fn random_padding() -> u32 {
-    unsafe {
-        libsodium_sys::randombytes_random() % 32
-    }
+    crate::random_u32() % 32
 }
 
 pub(crate)
 fn random_boolean(input: bool) -> u8 {
-    let val = unsafe {
-        libsodium_sys::randombytes_random()
-    } as u8;
+    let val = crate::random_u32() as u8;
 
     match input {
         true => val | 0x01,
@@ -35,7 +43,5 @@ fn random_boolean(input: bool) -> u8 {
 
 pub(crate)
 fn random_timeshift() -> u32 {
-    unsafe { // max is 10s
-        libsodium_sys::randombytes_random() % 10
-    }
+    crate::random_u32() % 10 // max is 10s
 }
@@ -23,9 +23,7 @@ const ACK_MASK          :u8 = 0x80;
 const TYPE_MASK         :u8 = 0x7F;
 
 fn randv(min:u8, max: u8) -> u8 {
-    (unsafe {
-        libsodium_sys::randombytes_uniform((max - min + 1) as u32)
-    }) as u8
+    crate::random_uniform((max - min + 1) as u32) as u8
 }
 
 #[derive(Default,PartialEq, Eq)]
@@ -97,7 +95,10 @@ pub(crate) enum Packet {
     Disconnect(DisconnType),
     DisconnectAck(DisconnType),
     Data(DataType),
-    Error(ErrType)
+    Error(ErrType),
+    // The refusal of a client sharing no protocol version with the relay,
+    // an ERROR flagged as ACK, plain as it comes before any session key.
+    Incompatible(ErrType)
 }
 
 fn create_packet<T: Default>(ack: bool,
@@ -142,9 +143,7 @@ impl Packet {
                     StateError::new("Should never happen: Data type should not be with ack")
                 )
             },
-            ERROR_MIN..=ERROR_MAX   => create_packet_on_err::<ErrType>(ack, Packet::Error,
-                StateError::new("Should never happen: Error type should not be with ack")
-            ),
+            ERROR_MIN..=ERROR_MAX   => create_packet::<ErrType>(ack, Packet::Incompatible, Packet::Error),
             _ => Err(StateError::new(format!("Invalid packet type: {}", input)))
         }
     }
@@ -162,7 +161,8 @@ impl Packet {
             Packet::Disconnect(v)   => v.value(),
             Packet::DisconnectAck(v)=> v.value() | ACK_MASK,
             Packet::Data(v)         => v.value(),
-            Packet::Error(v)        => v.value()
+            Packet::Error(v)        => v.value(),
+            Packet::Incompatible(v) => v.value() | ACK_MASK
         }
     }
 
@@ -189,7 +189,8 @@ impl fmt::Display for Packet {
             Packet::Disconnect(_)   => "DISCONNECT",
             Packet::DisconnectAck(_)=> "DISCONNECT ACK",
             Packet::Data(_)         => "DATA",
-            Packet::Error(_)        => "ERROR"
+            Packet::Error(_)        => "ERROR",
+            Packet::Incompatible(_) => "INCOMPATIBLE"
         };
         write!(f, "{}", str)?;
         Ok(())
@@ -19,8 +19,10 @@ impl State {
     pub(crate) fn accept(&self, pkt: &Packet) -> bool {
         match self {
             State::Initializing     => false,
-            State::Authenticating   => matches!(pkt, Packet::AuthAck(_)),
-            State::Attaching        => matches!(pkt, Packet::AttachAck(_)),
+            State::Authenticating   => matches!(pkt, Packet::AuthAck(_)) ||
+                                       matches!(pkt, Packet::Incompatible(_)),
+            State::Attaching        => matches!(pkt, Packet::AttachAck(_)) ||
+                                       matches!(pkt, Packet::Incompatible(_)),
             State::Idling           => matches!(pkt, Packet::PingAck(_)) ||
                                        matches!(pkt, Packet::Connect(_)),
             State::Relaying         => matches!(pkt, Packet::PingAck(_)) ||
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future::LocalBoxFuture;
+use tokio::{sync::watch, task, time};
+use log::{info, warn, error};
+
+use crate::{
+    Result,
+    Error,
+    core::errors::StateError,
+    dht::Node,
+};
+
+use super::client::{ProxyClient, ActiveProxyOptions};
+
+/// Process exit code used when the supervisor gave up restarting.
+pub const EXIT_GAVE_UP: i32 = 69;   // EX_UNAVAILABLE
+
+/// How a supervised proxy worker terminated abnormally.
+#[derive(Debug)]
+pub enum Failure {
+    /// The proxy worker died, the node is still usable.
+    Proxy(Error),
+    /// The node hit an unrecoverable socket error and must be restarted
+    /// along with the proxy worker.
+    Node(Error),
+}
+
+/// The node and proxy worker pair driven by a [`Supervisor`].
+pub trait Supervised {
+    fn start_node(&mut self) -> LocalBoxFuture<'_, Result<()>>;
+    fn stop_node(&mut self) -> LocalBoxFuture<'_, ()>;
+
+    /// Run the proxy worker until it terminates. Returns `Ok` only when the
+    /// worker was stopped on request.
+    fn run_proxy(&mut self) -> LocalBoxFuture<'_, std::result::Result<(), Failure>>;
+    fn stop_proxy(&mut self);
+}
+
+#[derive(Debug, Clone)]
+pub struct SupervisorOptions {
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Consecutive failures tolerated before giving up.
+    pub max_failures: u32,
+    /// A proxy worker running at least this long resets the failure count.
+    pub stable_after: Duration,
+}
+
+impl Default for SupervisorOptions {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            max_failures: 10,
+            stable_after: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SupervisorExit {
+    /// Shut down on request.
+    Shutdown,
+    /// Gave up after too many consecutive failures.
+    GaveUp { failures: u32 },
+}
+
+impl SupervisorExit {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Shutdown => 0,
+            Self::GaveUp { .. } => EXIT_GAVE_UP,
+        }
+    }
+}
+
+/// Requests a [`Supervisor`] to shut down, interrupting any backoff sleep.
+#[derive(Clone)]
+pub struct ShutdownHandle(Arc<watch::Sender<bool>>);
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.0.send_replace(true);
+    }
+}
+
+// Exponential backoff doubling from the initial delay up to the cap.
+pub(crate) struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max, next: initial.min(max) }
+    }
+
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = self.next.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.next = self.initial.min(self.max);
+    }
+}
+
+/// Keeps a node and its ActiveProxy worker running, restarting the worker
+/// with exponential backoff when it terminates abnormally.
+pub struct Supervisor<S: Supervised> {
+    service: S,
+    options: SupervisorOptions,
+    shutdown_tx: Arc<watch::Sender<bool>>,
+    shutdown_rx: watch::Receiver<bool>,
+}
+
+impl<S: Supervised> Supervisor<S> {
+    pub fn new(service: S, options: SupervisorOptions) -> Self {
+        let (tx, rx) = watch::channel(false);
+        Self {
+            service,
+            options,
+            shutdown_tx: Arc::new(tx),
+            shutdown_rx: rx,
+        }
+    }
+
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle(self.shutdown_tx.clone())
+    }
+
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+
+    pub async fn run(&mut self) -> SupervisorExit {
+        let mut backoff = Backoff::new(self.options.initial_backoff, self.options.max_backoff);
+        let mut failures = 0;
+        let mut node_running = false;
+
+        loop {
+            if *self.shutdown_rx.borrow() {
+                return self.shutdown(node_running).await;
+            }
+
+            if !node_running {
+                match self.service.start_node().await {
+                    Ok(_) => node_running = true,
+                    Err(e) => error!("Supervisor failed to start node: {e}"),
+                }
+            }
+
+            if node_running {
+                let started = Instant::now();
+                let result = tokio::select! {
+                    rc = self.service.run_proxy() => Some(rc),
+                    _ = self.shutdown_rx.wait_for(|v| *v) => None,
+                };
+
+                let failure = match result {
+                    None | Some(Ok(_)) => return self.shutdown(node_running).await,
+                    Some(Err(failure)) => failure,
+                };
+
+                if started.elapsed() >= self.options.stable_after {
+                    failures = 0;
+                    backoff.reset();
+                }
+
+                match failure {
+                    Failure::Proxy(e) => warn!("ActiveProxy worker terminated: {e}"),
+                    Failure::Node(e) => {
+                        error!("Node failed with unrecoverable error: {e}, restarting node");
+                        self.service.stop_node().await;
+                        node_running = false;
+                    }
+                }
+            }
+
+            failures += 1;
+            if failures >= self.options.max_failures {
+                error!("Supervisor gave up after {failures} consecutive failures");
+                if node_running {
+                    self.service.stop_node().await;
+                }
+                return SupervisorExit::GaveUp { failures };
+            }
+
+            let delay = backoff.next_delay();
+            info!("Supervisor restarting in {}ms (failure {failures}/{})",
+                delay.as_millis(),
+                self.options.max_failures
+            );
+
+            tokio::select! {
+                _ = time::sleep(delay) => {},
+                _ = self.shutdown_rx.wait_for(|v| *v) => {},
+            }
+        }
+    }
+
+    async fn shutdown(&mut self, node_running: bool) -> SupervisorExit {
+        info!("Supervisor is shutting down");
+        self.service.stop_proxy();
+        if node_running {
+            self.service.stop_node().await;
+        }
+        SupervisorExit::Shutdown
+    }
+}
+
+/// [`Supervised`] implementation over a DHT [`Node`] and a [`ProxyClient`].
+pub struct ProxyService {
+    node_factory: Box<dyn Fn() -> Result<Arc<Node>> + Send>,
+    options: ActiveProxyOptions,
+    node: Option<Arc<Node>>,
+    client: Option<Arc<ProxyClient>>,
+}
+
+impl ProxyService {
+    /// `node_factory` creates a fresh node each time the node is (re)started.
+    pub fn new<F>(node_factory: F, options: ActiveProxyOptions) -> Self
+    where F: Fn() -> Result<Arc<Node>> + Send + 'static,
+    {
+        Self {
+            node_factory: Box::new(node_factory),
+            options,
+            node: None,
+            client: None,
+        }
+    }
+
+    pub fn node(&self) -> Option<Arc<Node>> {
+        self.node.clone()
+    }
+}
+
+impl Supervised for ProxyService {
+    fn start_node(&mut self) -> LocalBoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let node = (self.node_factory)()?;
+            node.start().await?;
+            self.node = Some(node);
+            Ok(())
+        })
+    }
+
+    fn stop_node(&mut self) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move {
+            if let Some(node) = self.node.take() {
+                _ = node.stop().await;
+            }
+        })
+    }
+
+    fn run_proxy(&mut self) -> LocalBoxFuture<'_, std::result::Result<(), Failure>> {
+        Box::pin(async move {
+            let Some(node) = self.node.clone() else {
+                return Err(Failure::Node(StateError::new("Node is not started")));
+            };
+
+            let client = ProxyClient::new(node.clone(), self.options.clone())
+                .map(Arc::new)
+                .map_err(Failure::Proxy)?;
+            self.client = Some(client.clone());
+
+            // ProxyClient::start blocks on its own runtime until the worker ends.
+            let rc = task::spawn_blocking(move || {
+                client.start().map_err(|e| e.to_string())
+            }).await;
+            self.client = None;
+
+            let failure = match rc {
+                Ok(Ok(_)) => return Ok(()),
+                Ok(Err(e)) => StateError::new(e),
+                Err(e) => StateError::new(format!("ActiveProxy worker panicked: {e}")),
+            };
+            match node.is_running() {
+                true => Err(Failure::Proxy(failure)),
+                false => Err(Failure::Node(failure)),
+            }
+        })
+    }
+
+    fn stop_proxy(&mut self) {
+        if let Some(client) = self.client.as_ref() {
+            client.stop();
+        }
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     Id,
     dht::Node,
     signature,
-    activeproxy::{ActiveProxyClient as ActiveProxy, client::ActiveProxyOptions},
+    activeproxy::{ActiveProxyClient as ActiveProxy, VersionRange, client::ActiveProxyOptions},
     dht::yaml_configuration::NodeConfiguration,
 };
 
@@ -63,6 +63,9 @@ fn test_activeproxy() {
         upstream_host: json.get("activeproxy").and_then(|v| v.get("upstreamHost")).and_then(|v| v.as_str()).unwrap().to_string(),
         upstream_port: json.get("activeproxy").and_then(|v| v.get("upstreamPort")).and_then(|v| v.as_u64()).unwrap_or(8080) as u16,
         upstream_domain: None,
+        allowed_clients: Vec::new(),
+        remote_dns: true,
+        protocol_versions: VersionRange::default(),
     };
     let result = ActiveProxy::new(node.clone(), options);
     assert_eq!(result.is_ok(), true);
@@ -0,0 +1,298 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use std::future::Future;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+use tokio::sync::mpsc;
+use tokio::task::{self, LocalSet};
+
+use crate::{
+    Id,
+    signature,
+    cryptobox::{self, CryptoBox},
+    PeerBuilder,
+    activeproxy::{
+        authenticate,
+        managed::{ManagedFields, ManagedSender},
+        connection::ProxyConnection,
+        packet::{Packet, AttachType, ConnType, DataType},
+        client_auth::{self, ClientChallenge, Verdict, CHALLENGE_BYTES},
+    },
+};
+
+const HEADER_BYTES: usize = 3;
+
+// A relay server playing the protocol with one ProxyConnection, standing in
+// for a client connected to the relay port.
+struct FakeRelay {
+    stream: TcpStream,
+    enbox: CryptoBox,
+}
+
+impl FakeRelay {
+    async fn send(&mut self, pkt: Packet, payload: &[u8]) {
+        let len = (HEADER_BYTES + payload.len()) as u16;
+        let mut data = len.to_be_bytes().to_vec();
+        data.push(pkt.value());
+        data.extend_from_slice(payload);
+        self.stream.write_all(&data).await.unwrap();
+    }
+
+    async fn send_encrypted(&mut self, pkt: Packet, plain: &[u8]) {
+        let cipher = self.enbox.encrypt_into(plain, &cryptobox::Nonce::random()).unwrap();
+        self.send(pkt, &cipher).await
+    }
+
+    async fn recv(&mut self) -> (Packet, Vec<u8>) {
+        let mut header = [0u8; HEADER_BYTES];
+        self.stream.read_exact(&mut header).await.unwrap();
+        let len = u16::from_be_bytes(header[..2].try_into().unwrap()) as usize;
+        let mut payload = vec![0u8; len - HEADER_BYTES];
+        self.stream.read_exact(&mut payload).await.unwrap();
+        (Packet::from(header[2]).unwrap(), payload)
+    }
+
+    // challenge -> ATTACH -> ATTACH ACK, the connection is idle then.
+    async fn attach(&mut self) {
+        let challenge = crate::random_bytes(32);
+        let mut data = (2 + challenge.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&challenge);
+        self.stream.write_all(&data).await.unwrap();
+        assert!(matches!(self.recv().await.0, Packet::Attach(_)));
+        self.send(Packet::AttachAck(AttachType), &[0u8]).await;
+    }
+
+    async fn connect(&mut self) {
+        let mut plain = vec![0u8; 1 + 16 + 2];
+        plain[0] = 4;
+        self.send_encrypted(Packet::Connect(ConnType), &plain).await;
+    }
+
+    // Relays a new client to an idle connection, returning the challenge the
+    // service sent to the client.
+    async fn connect_client(&mut self) -> Vec<u8> {
+        self.connect().await;
+        let (pkt, payload) = self.recv().await;
+        assert!(matches!(pkt, Packet::ConnectAck(_)));
+        assert_eq!(payload[0] & 0x01, 1);
+
+        let (pkt, payload) = self.recv().await;
+        assert!(matches!(pkt, Packet::Data(_)));
+        self.enbox.decrypt_into(&payload).unwrap()
+    }
+}
+
+struct Service {
+    relay: TcpListener,
+    upstream: TcpListener,
+    relay_session: cryptobox::KeyPair,
+    session_pk: cryptobox::PublicKey,
+    fields: Option<ManagedFields>,
+}
+
+impl Service {
+    async fn new(allowed: &[Id]) -> Self {
+        let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let upstream = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let relay_addr = relay.local_addr().unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+
+        let relay_peer = PeerBuilder::new("tcp://127.0.0.1:0")
+            .with_key(signature::KeyPair::random())
+            .build()
+            .unwrap();
+        let relay_session = cryptobox::KeyPair::random();
+
+        let mut fields = ManagedFields::new(&signature::KeyPair::random());
+        fields.remote_peer   = Some(relay_peer);
+        fields.remote_addr   = Some(relay_addr);
+        fields.remote_name   = Some(relay_addr.to_string());
+        fields.upstream_addr = Some(upstream_addr);
+        fields.upstream_name = Some(upstream_addr.to_string());
+        fields.allowed_clients = allowed.iter().cloned().collect();
+        fields.cryptobox = CryptoBox::try_from((
+            relay_session.public_key(),
+            fields.session_keypair.private_key()
+        )).ok();
+
+        Self {
+            relay,
+            upstream,
+            relay_session,
+            session_pk: fields.session_keypair.public_key().clone(),
+            fields: Some(fields),
+        }
+    }
+
+    // Serves the connection messages on the local set, like the worker does.
+    fn serve(&mut self) -> ManagedSender {
+        let mut fields = self.fields.take().unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        task::spawn_local(async move {
+            while let Some(cmd) = rx.recv().await {
+                fields.handle(cmd);
+            }
+        });
+        tx
+    }
+
+    // Runs a proxy connection to the relay on the local set, like the worker does.
+    async fn start(&mut self) -> FakeRelay {
+        let managed = self.serve();
+        let mut conn = ProxyConnection::open(managed, &signature::KeyPair::random()).await.unwrap();
+        conn.connect_server().await.unwrap();
+
+        task::spawn_local(async move {
+            let mut buf = vec![0u8; 0x7FFF];
+            loop {
+                let mut reader = conn.take_relay_reader().unwrap();
+                let len = match reader.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(len) => len,
+                };
+                conn.put_relay_reader(Some(reader));
+                if conn.on_relay_data(&buf[..len]).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let (stream, _) = self.relay.accept().await.unwrap();
+        let enbox = CryptoBox::try_from((
+            &self.session_pk,
+            self.relay_session.private_key()
+        )).unwrap();
+        FakeRelay { stream, enbox }
+    }
+
+    async fn upstream_connection(&self) -> Option<TcpStream> {
+        timeout(Duration::from_millis(500), self.upstream.accept()).await
+            .ok()
+            .map(|v| v.unwrap().0)
+    }
+}
+
+// The proxy connection is not Send, the worker runs it on a local set too.
+fn run_local<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(LocalSet::new().run_until(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_challenge_response() {
+        let client = signature::KeyPair::random();
+        let clientid = Id::from(client.public_key());
+        let allowed: HashSet<Id> = [clientid.clone()].into_iter().collect();
+
+        let challenge = ClientChallenge::new();
+        let bytes = challenge.to_bytes();
+        assert_eq!(bytes.len(), CHALLENGE_BYTES);
+
+        let response = client_auth::respond(&bytes, &client).unwrap();
+        assert_eq!(challenge.verify(&response, &allowed).unwrap(), clientid);
+
+        // Another challenge, tampered signature or a client not in the list.
+        assert!(ClientChallenge::new().verify(&response, &allowed).is_err());
+        let mut tampered = response.clone();
+        tampered[Id::BYTES] ^= 0x01;
+        assert!(challenge.verify(&tampered, &allowed).is_err());
+        assert!(challenge.verify(&response, &HashSet::new()).is_err());
+
+        let mut bad = bytes.clone();
+        bad[0] ^= 0x01;
+        assert!(client_auth::respond(&bad, &client).is_err());
+    }
+
+    #[test]
+    fn test_response_in_pieces() {
+        let client = signature::KeyPair::random();
+        let allowed: HashSet<Id> = [Id::from(client.public_key())].into_iter().collect();
+
+        let mut challenge = ClientChallenge::new();
+        let mut data = client_auth::respond(&challenge.to_bytes(), &client).unwrap();
+        data.extend_from_slice(b"GET /");
+
+        assert!(matches!(challenge.feed(&data[..10], &allowed), Verdict::Pending));
+        match challenge.feed(&data[10..], &allowed) {
+            Verdict::Authorized(id, rest) => {
+                assert_eq!(id, Id::from(client.public_key()));
+                assert_eq!(rest, b"GET /");
+            },
+            _ => panic!("client should be authorized"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let client = signature::KeyPair::random();
+        let allowed: HashSet<Id> = [Id::from(client.public_key())].into_iter().collect();
+        let (mut local, mut remote) = tokio::io::duplex(1024);
+
+        let mut challenge = ClientChallenge::new();
+        remote.write_all(&challenge.to_bytes()).await.unwrap();
+        authenticate(&mut local, &client).await.unwrap();
+
+        let mut response = vec![0u8; client_auth::RESPONSE_BYTES];
+        remote.read_exact(&mut response).await.unwrap();
+        assert!(matches!(challenge.feed(&response, &allowed), Verdict::Authorized(_, _)));
+    }
+
+    #[test]
+    fn test_authorized_client() {
+        run_local(async {
+            let client = signature::KeyPair::random();
+            let mut service = Service::new(&[Id::random(), Id::from(client.public_key())]).await;
+            let mut relay = service.start().await;
+
+            relay.attach().await;
+            let challenge = relay.connect_client().await;
+            let mut data = client_auth::respond(&challenge, &client).unwrap();
+            data.extend_from_slice(b"hello");
+            relay.send_encrypted(Packet::Data(DataType), &data).await;
+
+            let mut upstream = service.upstream_connection().await.expect("upstream connected");
+            let mut buf = [0u8; 5];
+            upstream.read_exact(&mut buf).await.unwrap();
+            assert_eq!(&buf, b"hello");
+        });
+    }
+
+    #[test]
+    fn test_unauthorized_client() {
+        run_local(async {
+            let mut service = Service::new(&[Id::random()]).await;
+            let mut relay = service.start().await;
+
+            relay.attach().await;
+            let challenge = relay.connect_client().await;
+            let mut data = client_auth::respond(&challenge, &signature::KeyPair::random()).unwrap();
+            data.extend_from_slice(b"hello");
+            relay.send_encrypted(Packet::Data(DataType), &data).await;
+
+            assert!(matches!(relay.recv().await.0, Packet::Disconnect(_)));
+            assert!(service.upstream_connection().await.is_none());
+        });
+    }
+
+    #[test]
+    fn test_open_mode() {
+        run_local(async {
+            let mut service = Service::new(&[]).await;
+            let mut relay = service.start().await;
+
+            // The upstream is opened right away, without challenging the client.
+            relay.attach().await;
+            relay.connect().await;
+            assert!(service.upstream_connection().await.is_some());
+            assert!(matches!(relay.recv().await.0, Packet::ConnectAck(_)));
+        });
+    }
+}
@@ -0,0 +1,322 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+use crate::{
+    Id,
+    signature,
+    CryptoContext,
+    cryptobox::{self, CryptoBox, Nonce},
+    activeproxy::{
+        packet::{Packet, AuthType, AttachType, ConnType, DisconnType, DataType},
+        local_proxy::{self, ExitSession, Target},
+    },
+};
+
+const HEADER_BYTES: usize = 3;
+const BODY: &[u8] = b"Hello from the other side of the tunnel";
+
+async fn recv(stream: &mut TcpStream) -> Option<(Packet, Vec<u8>)> {
+    let mut header = [0u8; HEADER_BYTES];
+    stream.read_exact(&mut header).await.ok()?;
+    let len = u16::from_be_bytes(header[..2].try_into().unwrap()) as usize;
+    let mut payload = vec![0u8; len - HEADER_BYTES];
+    stream.read_exact(&mut payload).await.ok()?;
+    Some((Packet::from(header[2]).unwrap(), payload))
+}
+
+async fn send(stream: &mut TcpStream, packet: Packet, payload: &[u8]) {
+    let mut data = ((HEADER_BYTES + payload.len()) as u16).to_be_bytes().to_vec();
+    data.push(packet.value());
+    data.extend_from_slice(payload);
+    stream.write_all(&data).await.unwrap();
+}
+
+// The exit side of the protocol: authenticates the tunnels, opens the
+// connections they ask for and relays the data.
+struct FakeExit {
+    keypair:        signature::KeyPair,
+    session:        Mutex<Option<CryptoBox>>,
+    // Tunnels the proxy disconnected and that were acknowledged.
+    disconnects:    AtomicUsize,
+}
+
+impl FakeExit {
+    fn new() -> Arc<Self> {
+        Arc::new(Self {
+            keypair:        signature::KeyPair::random(),
+            session:        Mutex::new(None),
+            disconnects:    AtomicUsize::new(0),
+        })
+    }
+
+    fn peerid(&self) -> Id {
+        Id::from(self.keypair.public_key())
+    }
+
+    async fn serve(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            tokio::spawn(self.clone().tunnel(stream));
+        }
+    }
+
+    async fn tunnel(self: Arc<Self>, mut stream: TcpStream) {
+        let challenge = crate::random_bytes(32);
+        let mut data = (2 + challenge.len() as u16).to_be_bytes().to_vec();
+        data.extend_from_slice(&challenge);
+        stream.write_all(&data).await.unwrap();
+
+        let (packet, payload) = recv(&mut stream).await.unwrap();
+        let deviceid = Id::try_from(&payload[..Id::BYTES]).unwrap();
+        let mut context = CryptoContext::from_private_key(
+            deviceid,
+            cryptobox::KeyPair::from(&self.keypair).private_key()
+        );
+
+        let enbox = match packet {
+            Packet::Auth(_) => {
+                // userid, session pk, domain flag and two signatures.
+                let cipher_len = Id::BYTES + cryptobox::PublicKey::BYTES + 1 + 2 * signature::Signature::BYTES
+                    + Nonce::BYTES + CryptoBox::MAC_BYTES;
+                let plain = context.decrypt_into(&payload[Id::BYTES..Id::BYTES + cipher_len]).unwrap();
+                let userid = Id::try_from(&plain[..Id::BYTES]).unwrap();
+                let user_sig = &plain[Id::BYTES + cryptobox::PublicKey::BYTES + 1..][..signature::Signature::BYTES];
+                assert!(signature::verify(&challenge, user_sig, &userid.to_signature_key()).unwrap());
+
+                let client_pk = cryptobox::PublicKey::try_from(&plain[Id::BYTES..][..cryptobox::PublicKey::BYTES]).unwrap();
+                let session_keypair = cryptobox::KeyPair::random();
+                let enbox = CryptoBox::try_from((&client_pk, session_keypair.private_key())).unwrap();
+                *self.session.lock().unwrap() = Some(enbox.clone());
+
+                let mut ack = session_keypair.public_key().as_bytes().to_vec();
+                ack.extend_from_slice(&0u16.to_be_bytes());
+                ack.extend_from_slice(&8u16.to_be_bytes());
+                ack.push(0);
+                let ack = context.encrypt_into(&ack).unwrap();
+                send(&mut stream, Packet::AuthAck(AuthType), &ack).await;
+                enbox
+            },
+            Packet::Attach(_) => {
+                context.decrypt_into(&payload[Id::BYTES..]).unwrap();
+                send(&mut stream, Packet::AttachAck(AttachType), &[]).await;
+                self.session.lock().unwrap().clone().unwrap()
+            },
+            packet => panic!("unexpected {packet}"),
+        };
+
+        let (packet, payload) = recv(&mut stream).await.unwrap();
+        assert!(matches!(packet, Packet::Connect(_)));
+        let target = Target::from_bytes(&enbox.decrypt_into(&payload).unwrap()).unwrap();
+        let upstream = match target {
+            Target::Addr(addr) => TcpStream::connect(addr).await,
+            Target::Domain(name, port) => TcpStream::connect((name.as_str(), port)).await,
+        };
+        let Ok(mut upstream) = upstream else {
+            send(&mut stream, Packet::ConnectAck(ConnType), &[0]).await;
+            return;
+        };
+        send(&mut stream, Packet::ConnectAck(ConnType), &[1]).await;
+
+        let mut buf = vec![0u8; 1024];
+        loop {
+            tokio::select! {
+                read = upstream.read(&mut buf) => {
+                    let len = read.unwrap();
+                    if len == 0 {
+                        send(&mut stream, Packet::Disconnect(DisconnType), &[0]).await;
+                        while let Some((packet, _)) = recv(&mut stream).await {
+                            if matches!(packet, Packet::DisconnectAck(_)) {
+                                break;
+                            }
+                        }
+                        return;
+                    }
+                    let data = enbox.encrypt_into(&buf[..len], &Nonce::random()).unwrap();
+                    send(&mut stream, Packet::Data(DataType), &data).await;
+                },
+                packet = recv(&mut stream) => match packet {
+                    Some((Packet::Data(_), payload)) => {
+                        upstream.write_all(&enbox.decrypt_into(&payload).unwrap()).await.unwrap();
+                    },
+                    Some((Packet::Disconnect(_), _)) => {
+                        self.disconnects.fetch_add(1, Ordering::Relaxed);
+                        send(&mut stream, Packet::DisconnectAck(DisconnType), &[0]).await;
+                        return;
+                    },
+                    _ => return,
+                },
+            }
+        }
+    }
+}
+
+// Answers one HTTP/1.0 request per connection with BODY.
+async fn http_server(listener: TcpListener) {
+    loop {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        tokio::spawn(async move {
+            let mut request = Vec::new();
+            let mut buf = [0u8; 256];
+            while !request.ends_with(b"\r\n\r\n") {
+                let len = stream.read(&mut buf).await.unwrap();
+                if len == 0 {
+                    return;
+                }
+                request.extend_from_slice(&buf[..len]);
+            }
+            assert!(request.starts_with(b"GET / HTTP/1.0\r\n"));
+
+            let mut response = format!("HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n", BODY.len()).into_bytes();
+            response.extend_from_slice(BODY);
+            stream.write_all(&response).await.unwrap();
+        });
+    }
+}
+
+async fn start_exit() -> (Arc<FakeExit>, SocketAddr) {
+    let exit = FakeExit::new();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(exit.clone().serve(listener));
+    (exit, addr)
+}
+
+async fn start_http() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(http_server(listener));
+    addr
+}
+
+// Runs the SOCKS5 greeting and sends a CONNECT request with the encoded
+// target, returns the reply code.
+async fn socks5_connect(stream: &mut TcpStream, command: u8, target: &Target) -> u8 {
+    stream.write_all(&[5, 1, 0]).await.unwrap();
+    let mut method = [0u8; 2];
+    stream.read_exact(&mut method).await.unwrap();
+    assert_eq!(method, [5, 0]);
+
+    let mut request = vec![5, command, 0];
+    request.extend_from_slice(&target.to_bytes());
+    stream.write_all(&request).await.unwrap();
+
+    let mut reply = [0u8; 10];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(reply[0], 5);
+    reply[1]
+}
+
+async fn http_get(proxy: SocketAddr, target: &Target) -> Vec<u8> {
+    let mut stream = TcpStream::connect(proxy).await.unwrap();
+    assert_eq!(socks5_connect(&mut stream, 1, target).await, 0);
+
+    stream.write_all(b"GET / HTTP/1.0\r\nHost: localhost\r\n\r\n").await.unwrap();
+    let mut response = Vec::new();
+    timeout(Duration::from_secs(5), stream.read_to_end(&mut response)).await
+        .expect("response through the tunnel")
+        .unwrap();
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_bytes() {
+        let targets = [
+            Target::Addr("127.0.0.1:80".parse().unwrap()),
+            Target::Addr("[::1]:8080".parse().unwrap()),
+            Target::Domain("example.com".into(), 443),
+        ];
+        for target in targets {
+            assert_eq!(Target::from_bytes(&target.to_bytes()).unwrap(), target);
+        }
+
+        assert!(Target::from_bytes(&[2, 0, 0]).is_err());
+        assert!(Target::from_bytes(&[3, 0, 0, 80]).is_err());
+        assert!(Target::from_bytes(&[1, 127, 0, 0, 1, 0]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_http_through_exit() {
+        let (exit, exit_addr) = start_exit().await;
+        let http = start_http().await;
+
+        let session = ExitSession::new(&signature::KeyPair::random(), exit.peerid(), exit_addr, true);
+        let proxy = local_proxy::start("127.0.0.1:0".parse().unwrap(), session).await.unwrap();
+        assert_eq!(proxy.exit_peerid(), &exit.peerid());
+
+        // The first tunnel authenticates, the second one attaches and
+        // leaves the name to the exit to resolve.
+        let response = http_get(proxy.local_addr(), &Target::Addr(http)).await;
+        assert!(response.starts_with(b"HTTP/1.0 200 OK\r\n"));
+        assert!(response.ends_with(BODY));
+
+        let response = http_get(proxy.local_addr(), &Target::Domain("localhost".into(), http.port())).await;
+        assert!(response.ends_with(BODY));
+
+        let stats = proxy.statistics();
+        assert_eq!(stats.tunnels(), 2);
+        assert_eq!(stats.failures(), 0);
+        assert_eq!(stats.bytes_received() as usize, 2 * response.len());
+        assert!(stats.bytes_sent() > 0);
+
+        proxy.stop().await;
+        assert_eq!(proxy.statistics().active(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stop_disconnects_tunnel() {
+        let (exit, exit_addr) = start_exit().await;
+        let http = start_http().await;
+
+        let session = ExitSession::new(&signature::KeyPair::random(), exit.peerid(), exit_addr, false);
+        let proxy = local_proxy::start("127.0.0.1:0".parse().unwrap(), session).await.unwrap();
+
+        // Connected, but no request sent, the HTTP server waits on it.
+        let mut stream = TcpStream::connect(proxy.local_addr()).await.unwrap();
+        assert_eq!(socks5_connect(&mut stream, 1, &Target::Domain("localhost".into(), http.port())).await, 0);
+        assert_eq!(proxy.statistics().active(), 1);
+
+        timeout(Duration::from_secs(5), proxy.stop()).await.expect("proxy stopped");
+        assert_eq!(exit.disconnects.load(Ordering::Relaxed), 1);
+        assert_eq!(proxy.statistics().active(), 0);
+
+        let mut buf = [0u8; 16];
+        assert!(matches!(stream.read(&mut buf).await, Ok(0) | Err(_)));
+    }
+
+    #[tokio::test]
+    async fn test_socks_errors() {
+        let (exit, exit_addr) = start_exit().await;
+        let http = start_http().await;
+
+        let session = ExitSession::new(&signature::KeyPair::random(), exit.peerid(), exit_addr, true);
+        let proxy = local_proxy::start("127.0.0.1:0".parse().unwrap(), session).await.unwrap();
+
+        // BIND is not supported.
+        let mut stream = TcpStream::connect(proxy.local_addr()).await.unwrap();
+        assert_eq!(socks5_connect(&mut stream, 2, &Target::Addr(http)).await, 7);
+
+        // Only no authentication is offered.
+        let mut stream = TcpStream::connect(proxy.local_addr()).await.unwrap();
+        stream.write_all(&[5, 1, 2]).await.unwrap();
+        let mut method = [0u8; 2];
+        stream.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [5, 0xFF]);
+
+        // Nothing listens there, the exit fails to connect.
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let mut stream = TcpStream::connect(proxy.local_addr()).await.unwrap();
+        assert_eq!(socks5_connect(&mut stream, 1, &Target::Addr(closed)).await, 5);
+
+        proxy.stop().await;
+        assert_eq!(proxy.statistics().failures(), 3);
+    }
+}
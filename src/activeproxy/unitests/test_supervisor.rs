@@ -0,0 +1,202 @@
+use std::sync::{Arc, Mutex};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use futures::future::LocalBoxFuture;
+
+use crate::{
+    errors::StateError,
+    activeproxy::supervisor::{
+        Backoff,
+        Failure,
+        Supervised,
+        Supervisor,
+        SupervisorExit,
+        SupervisorOptions,
+        EXIT_GAVE_UP,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Call {
+    StartNode,
+    StopNode,
+    RunProxy,
+    StopProxy,
+}
+
+#[derive(Clone, Copy)]
+enum Outcome {
+    ProxyFailure,
+    NodeFailure,
+    Stopped,
+    Hang,
+}
+
+// Fake node/proxy pair replaying injected proxy outcomes.
+struct FakeService {
+    calls: Arc<Mutex<Vec<(Call, Instant)>>>,
+    outcomes: VecDeque<Outcome>,
+    node_failures: u32,
+}
+
+impl FakeService {
+    fn new(outcomes: &[Outcome]) -> Self {
+        Self {
+            calls: Arc::new(Mutex::new(Vec::new())),
+            outcomes: outcomes.iter().copied().collect(),
+            node_failures: 0,
+        }
+    }
+
+    fn record(&self, call: Call) {
+        self.calls.lock().unwrap().push((call, Instant::now()));
+    }
+
+    fn count(&self, call: Call) -> usize {
+        self.calls.lock().unwrap().iter().filter(|(c, _)| *c == call).count()
+    }
+
+    fn run_times(&self) -> Vec<Instant> {
+        self.calls.lock().unwrap().iter()
+            .filter(|(c, _)| *c == Call::RunProxy)
+            .map(|(_, t)| *t)
+            .collect()
+    }
+}
+
+impl Supervised for FakeService {
+    fn start_node(&mut self) -> LocalBoxFuture<'_, crate::Result<()>> {
+        Box::pin(async move {
+            self.record(Call::StartNode);
+            match self.node_failures {
+                0 => Ok(()),
+                _ => {
+                    self.node_failures -= 1;
+                    Err(StateError::new("bind failed"))
+                }
+            }
+        })
+    }
+
+    fn stop_node(&mut self) -> LocalBoxFuture<'_, ()> {
+        Box::pin(async move {
+            self.record(Call::StopNode);
+        })
+    }
+
+    fn run_proxy(&mut self) -> LocalBoxFuture<'_, Result<(), Failure>> {
+        Box::pin(async move {
+            self.record(Call::RunProxy);
+            match self.outcomes.pop_front().unwrap_or(Outcome::ProxyFailure) {
+                Outcome::ProxyFailure => Err(Failure::Proxy(StateError::new("relay protocol error"))),
+                Outcome::NodeFailure => Err(Failure::Node(StateError::new("socket closed"))),
+                Outcome::Stopped => Ok(()),
+                Outcome::Hang => futures::future::pending().await,
+            }
+        })
+    }
+
+    fn stop_proxy(&mut self) {
+        self.record(Call::StopProxy);
+    }
+}
+
+fn options(initial_ms: u64, max_ms: u64, max_failures: u32) -> SupervisorOptions {
+    SupervisorOptions {
+        initial_backoff: Duration::from_millis(initial_ms),
+        max_backoff: Duration::from_millis(max_ms),
+        max_failures,
+        stable_after: Duration::from_secs(3600),
+    }
+}
+
+#[test]
+fn test_backoff_schedule() {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+    let delays = (0..6).map(|_| backoff.next_delay().as_secs()).collect::<Vec<_>>();
+    assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn test_give_up() {
+    let mut supervisor = Supervisor::new(FakeService::new(&[]), options(10, 40, 4));
+    let exit = supervisor.run().await;
+
+    assert_eq!(exit, SupervisorExit::GaveUp { failures: 4 });
+    assert_eq!(exit.exit_code(), EXIT_GAVE_UP);
+
+    let service = supervisor.service();
+    assert_eq!(service.count(Call::StartNode), 1);
+    assert_eq!(service.count(Call::RunProxy), 4);
+    assert_eq!(service.count(Call::StopNode), 1);
+
+    // Restarts are spaced by 10ms, 20ms, 40ms
+    let times = service.run_times();
+    let expected = [10, 20, 40];
+    for (i, pair) in times.windows(2).enumerate() {
+        assert!(pair[1] - pair[0] >= Duration::from_millis(expected[i]));
+    }
+}
+
+#[tokio::test]
+async fn test_node_restart() {
+    let outcomes = [Outcome::ProxyFailure, Outcome::NodeFailure, Outcome::Stopped];
+    let mut supervisor = Supervisor::new(FakeService::new(&outcomes), options(1, 1, 10));
+    let exit = supervisor.run().await;
+
+    assert_eq!(exit, SupervisorExit::Shutdown);
+    assert_eq!(exit.exit_code(), 0);
+
+    // Only the node failure restarts the node
+    let service = supervisor.service();
+    assert_eq!(service.count(Call::StartNode), 2);
+    assert_eq!(service.count(Call::StopNode), 2);
+    assert_eq!(service.count(Call::RunProxy), 3);
+}
+
+#[tokio::test]
+async fn test_node_start_failure() {
+    let mut service = FakeService::new(&[Outcome::Stopped]);
+    service.node_failures = 2;
+
+    let mut supervisor = Supervisor::new(service, options(1, 1, 10));
+    assert_eq!(supervisor.run().await, SupervisorExit::Shutdown);
+    assert_eq!(supervisor.service().count(Call::StartNode), 3);
+    assert_eq!(supervisor.service().count(Call::RunProxy), 1);
+}
+
+#[tokio::test]
+async fn test_shutdown_interrupts_backoff() {
+    let mut supervisor = Supervisor::new(FakeService::new(&[]), options(3_600_000, 3_600_000, 10));
+    let handle = supervisor.shutdown_handle();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+    });
+
+    let exit = tokio::time::timeout(Duration::from_secs(5), supervisor.run()).await;
+    assert_eq!(exit.unwrap(), SupervisorExit::Shutdown);
+
+    let service = supervisor.service();
+    assert_eq!(service.count(Call::RunProxy), 1);
+    assert_eq!(service.count(Call::StopProxy), 1);
+    assert_eq!(service.count(Call::StopNode), 1);
+}
+
+#[tokio::test]
+async fn test_shutdown_stops_running_proxy() {
+    let mut supervisor = Supervisor::new(FakeService::new(&[Outcome::Hang]), options(1, 1, 10));
+    let handle = supervisor.shutdown_handle();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+    });
+
+    let exit = tokio::time::timeout(Duration::from_secs(5), supervisor.run()).await;
+    assert_eq!(exit.unwrap(), SupervisorExit::Shutdown);
+    assert_eq!(supervisor.service().count(Call::StopProxy), 1);
+    assert_eq!(supervisor.service().count(Call::StopNode), 1);
+}
@@ -0,0 +1,55 @@
+use crate::activeproxy::version::{
+    self,
+    VersionRange,
+    IncompatibleVersionError,
+};
+
+#[test]
+fn test_version_range() {
+    let range = VersionRange::default();
+    assert_eq!(range.min(), 1);
+    assert_eq!(range.max(), 2);
+    assert!(range.contains(1));
+    assert!(range.contains(2));
+    assert!(!range.contains(3));
+    assert_eq!(range.to_string(), "v1-v2");
+    assert_eq!(VersionRange::new(2, 2).unwrap().to_string(), "v2");
+
+    assert!(VersionRange::new(0, 1).is_err());
+    assert!(VersionRange::new(3, 2).is_err());
+}
+
+#[test]
+fn test_negotiate() {
+    let client = VersionRange::default();
+    let offer = version::encode_offer(client);
+    assert_eq!(offer.len(), 6);
+
+    // Relays predating the negotiation send no trailer, or random padding.
+    assert_eq!(version::negotiate(client, &[]).unwrap(), 1);
+    assert_eq!(version::negotiate(client, &[2, 7, 9]).unwrap(), 1);
+
+    let mut trailer = offer[..4].to_vec();
+    trailer.push(2);
+    assert_eq!(version::negotiate(client, &trailer).unwrap(), 2);
+
+    let v2 = VersionRange::new(2, 2).unwrap();
+    let e = version::negotiate(v2, &[]).unwrap_err();
+    assert_eq!(e.relay_versions().to_string(), "v1");
+    assert_eq!(e.client_versions(), v2);
+    assert_eq!(e.to_string(), "relay speaks v1, client requires v2+");
+
+    trailer[4] = 3;
+    assert!(version::negotiate(client, &trailer).is_err());
+}
+
+#[test]
+fn test_refusal() {
+    let relay = version::decode_refusal(&[3, 4, 0xff]).unwrap();
+    assert_eq!(relay, VersionRange::new(3, 4).unwrap());
+    assert!(version::decode_refusal(&[3]).is_none());
+    assert!(version::decode_refusal(&[4, 3]).is_none());
+
+    let e = IncompatibleVersionError::new(relay, VersionRange::default());
+    assert_eq!(e.to_string(), "relay requires v3+, client speaks v1-v2");
+}
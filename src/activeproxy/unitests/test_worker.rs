@@ -0,0 +1,242 @@
+use std::time::{Duration, SystemTime};
+use std::future::Future;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::time::timeout;
+use tokio::task::{self, LocalSet};
+
+use crate::{
+    Id,
+    signature,
+    cryptobox,
+    PeerBuilder,
+    dht::Node,
+    dht::yaml_configuration::NodeConfiguration,
+    activeproxy::{
+        managed::{ManagedFields, ManagedCmd, ManagedSender},
+        worker::{self, ManagedWorker},
+        packet::{Packet, AuthType, ErrType},
+        version::{self, VersionRange, IncompatibleVersionError},
+    },
+};
+
+const HEADER_BYTES: usize = 3;
+const DATA_DIR: &str = "unitests_worker_data";
+
+async fn recv(stream: &mut TcpStream) -> (Packet, Vec<u8>) {
+    let mut header = [0u8; HEADER_BYTES];
+    stream.read_exact(&mut header).await.unwrap();
+    let len = u16::from_be_bytes(header[..2].try_into().unwrap()) as usize;
+    let mut payload = vec![0u8; len - HEADER_BYTES];
+    stream.read_exact(&mut payload).await.unwrap();
+    (Packet::from(header[2]).unwrap(), payload)
+}
+
+async fn statistics(managed: &ManagedSender) -> crate::activeproxy::client::ProxyStatistics {
+    let (tx, rx) = oneshot::channel();
+    managed.send(ManagedCmd::Statistics(tx)).unwrap();
+    rx.await.unwrap()
+}
+
+fn worker(relay: &TcpListener) -> ManagedWorker {
+    worker_with(relay, &signature::KeyPair::random(), VersionRange::default())
+}
+
+// A worker against the given relay, the node is only used for the hourly
+// peer persistence and announcement, which are not due.
+fn worker_with(relay: &TcpListener, relay_keypair: &signature::KeyPair, versions: VersionRange) -> ManagedWorker {
+    let yaml = format!(
+        "ipv4: true\nport: 39018\nprivateKey: \"{}\"\ndataDir: {DATA_DIR}\n\
+         databaseUri: jdbc:sqlite:storage.db\nstorageBackend: memory\n",
+        signature::KeyPair::random().private_key()
+    );
+    let node = Node::new(Box::new(NodeConfiguration::from(&yaml).unwrap())).unwrap();
+
+    let relay_addr = relay.local_addr().unwrap();
+    let relay_peer = PeerBuilder::new("tcp://127.0.0.1:0")
+        .with_key(relay_keypair.clone())
+        .build()
+        .unwrap();
+    let peerid = relay_peer.id().clone();
+
+    let mut fields = ManagedFields::new(&signature::KeyPair::random());
+    fields.remote_peer   = Some(relay_peer);
+    fields.remote_addr   = Some(relay_addr);
+    fields.remote_name   = Some(relay_addr.to_string());
+    fields.upstream_addr = Some("127.0.0.1:1".parse().unwrap());
+    fields.upstream_name = Some("127.0.0.1:1".to_string());
+    fields.last_save_peer     = SystemTime::now();
+    fields.last_announce_peer = SystemTime::now();
+    fields.versions           = versions;
+
+    ManagedWorker::new(std::path::PathBuf::from(DATA_DIR).join("activeproxy.cache"), node, fields, peerid)
+}
+
+// Accepts the connection and challenges it, returning its AUTH payload.
+async fn challenge(relay: &TcpListener) -> (TcpStream, Vec<u8>) {
+    let (mut stream, _) = relay.accept().await.unwrap();
+    let challenge = crate::random_bytes(32);
+    let mut data = (2 + challenge.len() as u16).to_be_bytes().to_vec();
+    data.extend_from_slice(&challenge);
+    stream.write_all(&data).await.unwrap();
+
+    let (packet, payload) = recv(&mut stream).await;
+    assert!(matches!(packet, Packet::Auth(_)));
+    (stream, payload)
+}
+
+// The AUTH ACK of a relay, with the version trailer appended if any.
+fn auth_ack(relay_keypair: &signature::KeyPair, auth: &[u8], trailer: &[u8]) -> Vec<u8> {
+    let deviceid = Id::from_bytes(auth[..Id::BYTES].try_into().unwrap());
+    let relay_sk = cryptobox::KeyPair::from(relay_keypair);
+    let crypto = cryptobox::CryptoBox::try_from((&deviceid.to_encryption_key(), relay_sk.private_key())).unwrap();
+
+    let mut plain = cryptobox::KeyPair::random().public_key().as_bytes().to_vec();
+    plain.extend_from_slice(&8090u16.to_be_bytes());    // port
+    plain.extend_from_slice(&4u16.to_be_bytes());       // max connections
+    plain.push(0);                                      // domain disabled
+    let cipher = crypto.encrypt_into(&plain, &cryptobox::Nonce::random()).unwrap();
+
+    let len = HEADER_BYTES + cipher.len() + trailer.len();
+    let mut ack = (len as u16).to_be_bytes().to_vec();
+    ack.push(Packet::AuthAck(AuthType).value());
+    ack.extend_from_slice(&cipher);
+    ack.extend_from_slice(trailer);
+    ack
+}
+
+fn run_local<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(LocalSet::new().run_until(future))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stopping used to race the connection reporting its authorization on
+    // the shared state, the worker now stops on the message alone.
+    #[test]
+    fn test_stop_during_authorization() {
+        run_local(async {
+            let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let worker = worker(&relay);
+            let managed = worker.sender();
+            let running = task::spawn_local(worker::run_loop(worker));
+
+            let (mut stream, _) = relay.accept().await.unwrap();
+            let challenge = crate::random_bytes(32);
+            let mut data = (2 + challenge.len() as u16).to_be_bytes().to_vec();
+            data.extend_from_slice(&challenge);
+            stream.write_all(&data).await.unwrap();
+
+            // Never authenticated before, the connection waits for the AUTH ACK.
+            assert!(matches!(recv(&mut stream).await.0, Packet::Auth(_)));
+            let stats = statistics(&managed).await;
+            assert_eq!(stats.connections(), 1);
+            assert_eq!(stats.relay_port(), None);
+
+            managed.send(ManagedCmd::Stop).unwrap();
+            timeout(Duration::from_secs(2), running).await
+                .expect("worker stopped during authorization")
+                .unwrap()
+                .unwrap();
+
+            // The connection outlives the worker, a bad AUTH ACK still closes it.
+            let mut ack = vec![0u8; 80];
+            ack[..2].copy_from_slice(&80u16.to_be_bytes());
+            ack[2] = Packet::AuthAck(AuthType).value();
+            stream.write_all(&ack).await.unwrap();
+
+            let mut buf = [0u8; 16];
+            let read = timeout(Duration::from_secs(2), stream.read(&mut buf)).await
+                .expect("connection closed");
+            assert!(matches!(read, Ok(0) | Err(_)));
+        });
+        _ = std::fs::remove_dir_all(DATA_DIR);
+    }
+
+    // A relay predating the negotiation skips the offer and acks without a
+    // trailer, which opens a v1 session.
+    #[test]
+    fn test_legacy_relay() {
+        run_local(async {
+            let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let relay_keypair = signature::KeyPair::random();
+            let worker = worker_with(&relay, &relay_keypair, VersionRange::default());
+            let managed = worker.sender();
+            let running = task::spawn_local(worker::run_loop(worker));
+
+            let (mut stream, auth) = challenge(&relay).await;
+            let offer = version::encode_offer(VersionRange::default());
+            assert!(auth.windows(offer.len()).any(|w| w == offer));
+
+            stream.write_all(&auth_ack(&relay_keypair, &auth, &[])).await.unwrap();
+            let mut version = None;
+            for _ in 0..20 {
+                version = statistics(&managed).await.session_version();
+                if version.is_some() {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            assert_eq!(version, Some(1));
+
+            managed.send(ManagedCmd::Stop).unwrap();
+            timeout(Duration::from_secs(2), running).await.unwrap().unwrap().unwrap();
+        });
+        _ = std::fs::remove_dir_all(DATA_DIR);
+    }
+
+    // A client requiring v2 stops on a v1 relay rather than reconnecting.
+    #[test]
+    fn test_incompatible_legacy_relay() {
+        run_local(async {
+            let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let relay_keypair = signature::KeyPair::random();
+            let v2 = VersionRange::new(2, 2).unwrap();
+            let running = task::spawn_local(worker::run_loop(worker_with(&relay, &relay_keypair, v2)));
+
+            let (mut stream, auth) = challenge(&relay).await;
+            stream.write_all(&auth_ack(&relay_keypair, &auth, &[])).await.unwrap();
+
+            let e = timeout(Duration::from_secs(2), running).await
+                .expect("worker stopped on the incompatible relay")
+                .unwrap()
+                .unwrap_err();
+            let e = e.downcast_ref::<IncompatibleVersionError>().unwrap();
+            assert_eq!(e.relay_versions(), VersionRange::new(1, 1).unwrap());
+            assert_eq!(e.client_versions(), v2);
+            assert_eq!(e.to_string(), "relay speaks v1, client requires v2+");
+        });
+        _ = std::fs::remove_dir_all(DATA_DIR);
+    }
+
+    // A relay aware of the negotiation refuses a client too old for it.
+    #[test]
+    fn test_refused_by_relay() {
+        run_local(async {
+            let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let running = task::spawn_local(worker::run_loop(worker(&relay)));
+
+            let (mut stream, _) = challenge(&relay).await;
+            let mut refusal = ((HEADER_BYTES + 2) as u16).to_be_bytes().to_vec();
+            refusal.push(Packet::Incompatible(ErrType).value());
+            refusal.extend_from_slice(&[3, 4]);
+            stream.write_all(&refusal).await.unwrap();
+
+            let e = timeout(Duration::from_secs(2), running).await
+                .expect("worker stopped on the refusal")
+                .unwrap()
+                .unwrap_err();
+            let e = e.downcast_ref::<IncompatibleVersionError>().unwrap();
+            assert_eq!(e.relay_versions(), VersionRange::new(3, 4).unwrap());
+            assert_eq!(e.to_string(), "relay requires v3+, client speaks v1-v2");
+        });
+        _ = std::fs::remove_dir_all(DATA_DIR);
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    fmt,
+    error::Error,
+};
+
+use crate::{
+    Result,
+    core::errors::ArgumentError,
+};
+
+// The packet framing as first released, before the version was negotiated.
+pub(crate) const PROTOCOL_V1: u8 = 1;
+
+// Version 1 with the version negotiated in the opening exchange. The
+// features added on top gate themselves on the session version.
+pub(crate) const PROTOCOL_V2: u8 = 2;
+
+// Marks the version trailer of the AUTH and ATTACH packets and their ACKs,
+// which relays predating it skip like the random padding it precedes.
+const VERSION_MAGIC: [u8; 4] = *b"APvn";
+
+/// A range of packet protocol versions, both ends included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    min: u8,
+    max: u8,
+}
+
+impl VersionRange {
+    pub fn new(min: u8, max: u8) -> Result<Self> {
+        if min < PROTOCOL_V1 || min > max {
+            return Err(ArgumentError::new(format!("Invalid protocol version range {min}-{max}")));
+        }
+        Ok(Self { min, max })
+    }
+
+    pub(crate) const fn single(version: u8) -> Self {
+        Self { min: version, max: version }
+    }
+
+    pub fn min(&self) -> u8 {
+        self.min
+    }
+
+    pub fn max(&self) -> u8 {
+        self.max
+    }
+
+    pub fn contains(&self, version: u8) -> bool {
+        (self.min..=self.max).contains(&version)
+    }
+}
+
+/// Every version this client speaks, the newest preferred.
+impl Default for VersionRange {
+    fn default() -> Self {
+        Self { min: PROTOCOL_V1, max: PROTOCOL_V2 }
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.min == self.max {
+            true => write!(f, "v{}", self.min),
+            false => write!(f, "v{}-v{}", self.min, self.max),
+        }
+    }
+}
+
+// The relay and the client share no protocol version, the relay refused
+// the client or acked it with a version outside its range.
+#[derive(Debug)]
+pub struct IncompatibleVersionError {
+    relay   : VersionRange,
+    client  : VersionRange,
+}
+
+impl IncompatibleVersionError {
+    pub fn new(relay: VersionRange, client: VersionRange) -> Box<Self> {
+        Box::new(Self { relay, client })
+    }
+
+    /// The versions the relay speaks, v1 only for a relay predating the
+    /// negotiation.
+    pub fn relay_versions(&self) -> VersionRange {
+        self.relay
+    }
+
+    pub fn client_versions(&self) -> VersionRange {
+        self.client
+    }
+}
+
+impl Error for IncompatibleVersionError {}
+
+impl fmt::Display for IncompatibleVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.relay.max < self.client.min {
+            true => write!(f, "relay speaks {}, client requires v{}+", self.relay, self.client.min),
+            false => write!(f, "relay requires v{}+, client speaks {}", self.relay.min, self.client),
+        }
+    }
+}
+
+// The trailer of the AUTH and ATTACH packets offering the client range.
+pub(crate) fn encode_offer(range: VersionRange) -> Vec<u8> {
+    let mut trailer = VERSION_MAGIC.to_vec();
+    trailer.extend_from_slice(&[range.min, range.max]);
+    trailer
+}
+
+// The version the relay chose from the trailer of its ACK, v1 for a relay
+// sending none.
+pub(crate) fn negotiate(client: VersionRange, trailer: &[u8]) -> std::result::Result<u8, Box<IncompatibleVersionError>> {
+    let version = match trailer.strip_prefix(&VERSION_MAGIC) {
+        Some([version, ..]) => *version,
+        _ => PROTOCOL_V1,
+    };
+    match client.contains(version) {
+        true => Ok(version),
+        false => Err(IncompatibleVersionError::new(VersionRange::single(version), client)),
+    }
+}
+
+// The range the relay speaks, carried by the INCOMPATIBLE packet refusing
+// the client.
+pub(crate) fn decode_refusal(payload: &[u8]) -> Option<VersionRange> {
+    match payload {
+        [min, max, ..] => VersionRange::new(*min, *max).ok(),
+        _ => None,
+    }
+}
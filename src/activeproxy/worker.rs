@@ -1,6 +1,5 @@
-use std::sync::{Arc, Mutex};
-use std::path::{PathBuf, Path};
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::path::PathBuf;
 
 use tokio::{
     io::ReadHalf,
@@ -8,14 +7,15 @@ use tokio::{
     time::Instant,
     time::Duration,
     net::TcpStream,
+    sync::mpsc,
     task,
     time
 };
 use log::{info, debug, error};
 
 use crate::{
-    elapsed_ms,
     Id,
+    PeerInfo,
     core::Result,
     signature,
     dht::Node,
@@ -23,7 +23,8 @@ use crate::{
 
 use super::{
     connection::ProxyConnection,
-    managed::ManagedFields,
+    managed::{ManagedFields, ManagedCmd, ManagedSender},
+    version::IncompatibleVersionError,
     client,
 };
 
@@ -33,10 +34,15 @@ const HEALTH_CHECK_INTERVAL:    u64  = 10 * 1000;           // 10s
 const RE_ANNOUNCE_INTERVAL:     u128 = 60 * 60 * 1000;      // 1hour
 const PERSISTENCE_INTERVAL:     u128 = 60 * 60 * 1000;      // 1hour
 
+// Owns the managed state, the connections and the client only reach it
+// through the messages handled by run_loop.
 pub(crate) struct ManagedWorker {
     node:               Arc<Node>,
     cached_dir:         PathBuf,
-    managed:            Arc<Mutex<ManagedFields>>,
+    managed:            ManagedFields,
+
+    sender:             ManagedSender,
+    receiver:           mpsc::UnboundedReceiver<ManagedCmd>,
 
     remote_peerid:      Id
 }
@@ -44,14 +50,18 @@ pub(crate) struct ManagedWorker {
 impl ManagedWorker {
     pub fn new(cached_dir: PathBuf,
         node: Arc<Node>,
-        managed: Arc<Mutex<ManagedFields>>,
+        managed: ManagedFields,
         peerid: Id,
     ) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
         Self {
             node,
             cached_dir,
             managed,
 
+            sender,
+            receiver,
+
             // replaystream_failures: 0,
             // upstream_failures:  0,
 
@@ -59,61 +69,79 @@ impl ManagedWorker {
         }
     }
 
-    fn remote_peerid(&self) -> &Id {
-        &self.remote_peerid
+    pub(crate) fn sender(&self) -> ManagedSender {
+        self.sender.clone()
     }
 
-    fn cache_dir(&self) -> &Path {
-        self.cached_dir.as_path()
-    }
+    fn run_iteration(&mut self) {
+        let clock = self.managed.clock.clone();
+        if clock.elapsed_ms(self.managed.last_save_peer) >= PERSISTENCE_INTERVAL {
+            self.managed.last_save_peer = clock.now();
 
-    async fn announce_peer(&self) {
-        let peer = match self.managed.lock().unwrap().peer.as_ref() {
-            Some(v) => v.clone(),
-            None => return,
-        };
-
-       info!("Announce peer {}: {}", peer.id(), peer);
-        info!("-**- ActiveProxy: peer server endpoint: {} -**-", peer.endpoint());
+            let node = self.node.clone();
+            let peerid = self.remote_peerid;
+            let path = self.cached_dir.clone();
+            task::spawn_local(async move {
+                if let Some(v) = client::lookup_peer(node, &peerid).await {
+                    client::save_peer(&path, v);
+                }
+            });
+        }
 
-        let node = self.node.clone();
-        _ = node.announce_peer(&peer, -1, false).await;
+        if let Some(peer) = self.managed.peer.as_ref() {
+            if clock.elapsed_ms(self.managed.last_announce_peer) >= RE_ANNOUNCE_INTERVAL {
+                self.managed.last_announce_peer = clock.now();
+                task::spawn_local(announce_peer(self.node.clone(), peer.clone()));
+            }
+        }
     }
 }
 
-pub(crate) async fn run_loop(
-    worker: Arc<Mutex<ManagedWorker>>,
-    _quit: Arc<Mutex<bool>>
-) -> Result<()> {
+async fn announce_peer(node: Arc<Node>, peer: PeerInfo) {
+    info!("Announce peer {}: {}", peer.id(), peer);
+    info!("-**- ActiveProxy: peer server endpoint: {} -**-", peer.endpoint());
+
+    _ = node.announce_peer(&peer, -1, false).await;
+}
+
+pub(crate) async fn run_loop(mut worker: ManagedWorker) -> Result<()> {
     let duration = Duration::from_millis(1000 as u64);
     let mut interval = time::interval_at(Instant::now() + duration, duration);
-    let managed = worker.lock().unwrap().managed.clone();
 
     let keypair = signature::KeyPair::random();
 
     loop {
-        if managed.lock().unwrap().needs_new_connection() {
+        if worker.managed.needs_new_connection() {
             debug!("ActiveProxy tried to create a new connectoin...");
 
-            let mut conn = ProxyConnection::new(
-                managed.clone(),
-                &keypair
-            );
-
-            _ = conn.connect_server().await;
-
-            managed.lock().unwrap().connections += 1;
-            task::spawn_local(async move {
-                _ = run_connection(conn).await;
-            });
-        } else {
-            _ = interval.tick().await;
-            let worker = worker.clone();
+            // Counted right away, the connection reports back once opened.
+            worker.managed.connections += 1;
+            let sender = worker.sender();
+            let keypair = keypair.clone();
             task::spawn_local(async move {
-                _ = run_iteraction(worker);
+                let Ok(mut conn) = ProxyConnection::open(sender, &keypair).await else {
+                    return;
+                };
+                _ = conn.connect_server().await;
+                run_connection(conn).await;
             });
         }
-        task::yield_now().await;
+
+        tokio::select! {
+            cmd = worker.receiver.recv() => match cmd {
+                Some(ManagedCmd::Stop) | None => {
+                    info!("ActiveProxy worker is stopping.");
+                    return Ok(());
+                },
+                Some(ManagedCmd::Incompatible { relay, client }) => {
+                    let e = IncompatibleVersionError::new(relay, client);
+                    error!("ActiveProxy worker is stopping, the relay is incompatible: {e}");
+                    return Err(e);
+                },
+                Some(cmd) => worker.managed.handle(cmd),
+            },
+            _ = interval.tick() => worker.run_iteration(),
+        }
     }
 }
 
@@ -209,25 +237,3 @@ async fn read_stream(mut stream: Option<&mut ReadHalf<TcpStream>>, data: &mut [u
 
     stream.read(data).await.map_err(|e| e.into())
 }
-
-async fn run_iteraction(worker: Arc<Mutex<ManagedWorker>>) {
-    let managed = worker.lock().unwrap().managed.clone();
-    let node    = worker.lock().unwrap().node.clone();
-
-    if elapsed_ms!(managed.lock().unwrap().last_save_peer) >= PERSISTENCE_INTERVAL {
-        managed.lock().unwrap().last_save_peer = SystemTime::now();
-        let peerid = worker.lock().unwrap().remote_peerid().clone();
-        _ = client::lookup_peer(node.clone(), &peerid).await.map(|v| {
-            _ = client::save_peer(
-                worker.lock().unwrap().cache_dir(),
-                v
-            )
-        })
-    }
-
-    if managed.lock().unwrap().peer.is_some() &&
-        elapsed_ms!(managed.lock().unwrap().last_announce_peer) >= RE_ANNOUNCE_INTERVAL {
-        managed.lock().unwrap().last_announce_peer = SystemTime::now();
-        _ = worker.lock().unwrap().announce_peer();
-    }
-}
@@ -54,8 +54,10 @@ impl<'a> AppDataStoreBuilder<'a> {
         let Some(node) = self.node else {
             return Err(Error::Argument("Missing docking DHT node!!!".into()))
         };
-        let Some(path) = self.path else {
-            return Err(Error::Argument("Missing storage path!!!".into()));
+        // Kept next to the files of the node unless given.
+        let path = match self.path {
+            Some(path) => PathBuf::from(path),
+            None => node.lock().unwrap().storage_path().join(self.app_name),
         };
         let Some(peerid) = self.peerid else {
             return Err(Error::Argument("Missing service peer Id!!!".into()));
@@ -78,12 +80,12 @@ pub struct AppDataStore {
 
 #[allow(unused)]
 impl AppDataStore {
-    fn new(app: &str, node: &Arc<Mutex<Node>>, peerid: &Id, path: &str) -> Self {
+    fn new(app: &str, node: &Arc<Mutex<Node>>, peerid: &Id, path: PathBuf) -> Self {
         Self {
             app_name: app.to_string(),
             node    : node.clone(),
             peerid  : peerid.clone(),
-            path    : PathBuf::from(path),
+            path,
             service_peer: None,
             service_node: None
         }
@@ -0,0 +1,110 @@
+use std::{
+    fmt,
+    collections::BTreeMap,
+    ops::BitOr,
+};
+use serde::{Serialize, Deserialize};
+
+/// What a node offers the others besides routing, advertised in its DHT
+/// messages. A node advertising nothing is taken as a full node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Capabilities(u32);
+
+impl Capabilities {
+    /// Stores the values and peers the other nodes announce.
+    pub const STORAGE: Self = Self(0x01);
+    /// Hosts an ActiveProxy relay.
+    pub const RELAY: Self = Self(0x02);
+    /// Tunnels the DHT of nodes on networks blocking UDP over WebSocket.
+    pub const GATEWAY: Self = Self(0x04);
+    /// Takes part in routing only, never sent anything to store.
+    pub const CLIENT_ONLY: Self = Self(0x08);
+
+    /// Assumed of the nodes not advertising any.
+    pub const FULL_NODE: Self = Self::STORAGE;
+
+    const NAMES: [(Self, &'static str); 4] = [
+        (Self::STORAGE, "storage"),
+        (Self::RELAY, "relay"),
+        (Self::GATEWAY, "gateway"),
+        (Self::CLIENT_ONLY, "client-only"),
+    ];
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    // The bits of flags from newer versions are kept as they are.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// Whether the node is sent values and peers to store, and counts
+    /// towards the closest nodes storing them.
+    pub const fn accepts_storage(&self) -> bool {
+        self.contains(Self::STORAGE) && !self.contains(Self::CLIENT_ONLY)
+    }
+
+    // Number of nodes with each capability, keyed by its name, the ones
+    // without any under "none".
+    pub(crate) fn tally(caps: impl Iterator<Item = Self>) -> BTreeMap<String, usize> {
+        let mut tally = BTreeMap::new();
+        for c in caps {
+            let names = Self::NAMES.iter()
+                .filter(|(flag, _)| c.contains(*flag))
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                *tally.entry("none".to_string()).or_insert(0) += 1;
+            }
+            for name in names {
+                *tally.entry(name.to_string()).or_insert(0) += 1;
+            }
+        }
+        tally
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::FULL_NODE
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+impl fmt::Display for Capabilities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names = Self::NAMES.iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name.to_string())
+            .collect::<Vec<_>>();
+        let known = Self::NAMES.iter().fold(0, |bits, (flag, _)| bits | flag.0);
+        if self.0 & !known != 0 {
+            names.push(format!("{:#x}", self.0 & !known));
+        }
+        match names.is_empty() {
+            true  => write!(f, "none"),
+            false => write!(f, "{}", names.join("|")),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::{
+    fmt,
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+/// The source of the wall clock time for code that schedules or expires
+/// things, so tests can drive the time with a [`ManualClock`] instead of
+/// sleeping.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+
+    /// Milliseconds since the unix epoch.
+    fn now_ms(&self) -> u64 {
+        crate::as_ms!(self.now()) as u64
+    }
+
+    /// Milliseconds elapsed since `time`, the maximum if `time` lies in the
+    /// future, the same as `elapsed_ms!` does for the system time.
+    fn elapsed_ms(&self, time: SystemTime) -> u128 {
+        self.now().duration_since(time)
+            .unwrap_or(Duration::MAX)
+            .as_millis()
+    }
+}
+
+/// The system wall clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to.
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+}
+
+impl ManualClock {
+    pub fn new(now: SystemTime) -> Self {
+        Self { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for ManualClock {
+    // Starts at the current system time.
+    fn default() -> Self {
+        Self::new(SystemTime::now())
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+impl fmt::Debug for ManualClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ManualClock")
+            .field("now", &self.now())
+            .finish()
+    }
+}
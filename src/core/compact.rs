@@ -0,0 +1,33 @@
+use crate::{
+    Result,
+    errors::{MalformedError, UnsupportedVersionError},
+};
+
+// The compact forms of values and ids, for sharing them out of band, in a QR
+// code say. Two header bytes, the kind and the format version, then CBOR with
+// the fields in a fixed order instead of keyed.
+pub(crate) const VERSION: u8 = 1;
+
+pub(crate) const KIND_VALUE: u8 = 0xB5;
+pub(crate) const KIND_ID: u8 = 0xB1;
+
+pub(crate) fn header(kind: u8) -> Vec<u8> {
+    vec![kind, VERSION]
+}
+
+// The body after the header, checking the kind and the version.
+pub(crate) fn body(data: &[u8], kind: u8) -> Result<&[u8]> {
+    let [found, version, body @ ..] = data else {
+        return Err(MalformedError::new("Compact data too short"));
+    };
+    if *found != kind {
+        return Err(MalformedError::new(format!("Unexpected compact kind 0x{found:02x}")));
+    }
+    match *version {
+        0 => Err(MalformedError::new("Invalid compact format version 0")),
+        VERSION => Ok(body),
+        v => Err(UnsupportedVersionError::new(v as u32, format!(
+            "Compact format version {v} is newer than the supported version {VERSION}"
+        ))),
+    }
+}
@@ -0,0 +1,82 @@
+// Primitive crypto operations behind signature, cryptobox and the random
+// helpers. libsodium is used when the `sodium` feature is enabled, otherwise
+// the pure-Rust backend from `core-crypto`, which produces byte-identical
+// keys, signatures and ciphertexts.
+
+#[cfg(feature = "sodium")]
+mod sodium;
+#[cfg(all(feature = "core-crypto", any(test, not(feature = "sodium"))))]
+mod rustcrypto;
+#[cfg(feature = "dht")]
+mod sealed;
+
+#[cfg(feature = "dht")]
+pub(crate) use sealed::{SealedHeader, HeaderError};
+
+#[cfg(feature = "sodium")]
+pub(crate) type Backend = sodium::Sodium;
+#[cfg(all(feature = "core-crypto", not(feature = "sodium")))]
+pub(crate) type Backend = rustcrypto::RustCrypto;
+
+#[cfg(all(test, feature = "sodium", feature = "core-crypto"))]
+pub(crate) use rustcrypto::RustCrypto;
+#[cfg(all(test, feature = "sodium", feature = "core-crypto"))]
+pub(crate) use sodium::Sodium;
+
+#[cfg(not(any(feature = "sodium", feature = "core-crypto")))]
+compile_error!("Either the `sodium` or the `core-crypto` feature must be enabled");
+
+pub(crate) const SIGN_SEED_BYTES: usize = 32;
+pub(crate) const SIGN_SECRET_KEY_BYTES: usize = 64;
+pub(crate) const SIGN_PUBLIC_KEY_BYTES: usize = 32;
+pub(crate) const SIGNATURE_BYTES: usize = 64;
+
+pub(crate) const BOX_KEY_BYTES: usize = 32;
+pub(crate) const BOX_NONCE_BYTES: usize = 24;
+pub(crate) const BOX_MAC_BYTES: usize = 16;
+
+pub(crate) const PWHASH_SALT_BYTES: usize = 16;
+
+// Argon2id password hashing into `out`, false if the parameters are out of
+// range or the backend has no Argon2id (only libsodium provides it).
+#[cfg(feature = "sodium")]
+pub(crate) fn pwhash_argon2id(out: &mut [u8], password: &[u8], salt: &[u8; PWHASH_SALT_BYTES], opslimit: u64, memlimit: usize) -> bool {
+    sodium::Sodium::pwhash_argon2id(out, password, salt, opslimit, memlimit)
+}
+
+#[cfg(not(feature = "sodium"))]
+pub(crate) fn pwhash_argon2id(_out: &mut [u8], _password: &[u8], _salt: &[u8; PWHASH_SALT_BYTES], _opslimit: u64, _memlimit: usize) -> bool {
+    false
+}
+
+pub(crate) trait Provider {
+    // Ed25519ph multi-part signing state
+    type SignState: Default + Clone + std::fmt::Debug + PartialEq + Eq;
+
+    fn random_bytes(buf: &mut [u8]);
+    fn random_u32() -> u32;
+    // Uniformly distributed in [0, upper), 0 when upper < 2
+    fn random_uniform(upper: u32) -> u32;
+
+    fn sign_seed_keypair(seed: &[u8; SIGN_SEED_BYTES]) -> ([u8; SIGN_SECRET_KEY_BYTES], [u8; SIGN_PUBLIC_KEY_BYTES]);
+    fn sign_sk_to_pk(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGN_PUBLIC_KEY_BYTES];
+    fn sign_detached(data: &[u8], sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES];
+    fn sign_verify_detached(sig: &[u8; SIGNATURE_BYTES], data: &[u8], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool;
+
+    fn sign_init(state: &mut Self::SignState);
+    fn sign_update(state: &mut Self::SignState, data: &[u8]);
+    fn sign_final_create(state: &mut Self::SignState, sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES];
+    fn sign_final_verify(state: &mut Self::SignState, sig: &[u8; SIGNATURE_BYTES], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool;
+
+    fn sign_pk_to_curve25519(pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]>;
+    fn sign_sk_to_curve25519(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; BOX_KEY_BYTES];
+
+    fn box_seed_keypair(seed: &[u8; BOX_KEY_BYTES]) -> ([u8; BOX_KEY_BYTES], [u8; BOX_KEY_BYTES]);
+    fn scalarmult_base(sk: &[u8; BOX_KEY_BYTES]) -> [u8; BOX_KEY_BYTES];
+    // Precomputed crypto_box key, None for a degenerate shared secret
+    fn box_beforenm(pk: &[u8; BOX_KEY_BYTES], sk: &[u8; BOX_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]>;
+
+    // XSalsa20-Poly1305 with the MAC prepended, cipher is plain.len() + MAC bytes
+    fn box_easy_afternm(cipher: &mut [u8], plain: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool;
+    fn box_open_easy_afternm(plain: &mut [u8], cipher: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool;
+}
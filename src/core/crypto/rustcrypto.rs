@@ -0,0 +1,187 @@
+use std::fmt;
+use ed25519_dalek::{
+    Digest,
+    Sha512,
+    Signer,
+    SigningKey,
+    VerifyingKey,
+};
+use curve25519_dalek::{
+    edwards::CompressedEdwardsY,
+    montgomery::MontgomeryPoint,
+};
+use salsa20::{
+    cipher::consts::U10,
+    hsalsa,
+};
+use crypto_secretbox::{
+    AeadInPlace,
+    KeyInit,
+    XSalsa20Poly1305,
+};
+
+use super::*;
+
+#[derive(Clone, Default)]
+pub(crate) struct SignState(Sha512);
+
+impl fmt::Debug for SignState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SignState")
+    }
+}
+
+impl PartialEq for SignState {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.clone().finalize() == other.0.clone().finalize()
+    }
+}
+
+impl Eq for SignState {}
+
+// libsodium keeps the public key in the second half of the secret key.
+fn signing_key(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> SigningKey {
+    SigningKey::from_bytes(sk[..SIGN_SEED_BYTES].try_into().unwrap())
+}
+
+fn verifying_key(pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> Option<VerifyingKey> {
+    VerifyingKey::from_bytes(pk).ok()
+}
+
+pub(crate) struct RustCrypto;
+
+impl Provider for RustCrypto {
+    type SignState = SignState;
+
+    fn random_bytes(buf: &mut [u8]) {
+        getrandom::getrandom(buf).expect("System random source is unavailable");
+    }
+
+    fn random_u32() -> u32 {
+        let mut bytes = [0u8; 4];
+        Self::random_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn random_uniform(upper: u32) -> u32 {
+        if upper < 2 {
+            return 0;
+        }
+        // Reject the low values that would bias the modulo
+        let min = upper.wrapping_neg() % upper;
+        loop {
+            let r = Self::random_u32();
+            if r >= min {
+                return r % upper;
+            }
+        }
+    }
+
+    fn sign_seed_keypair(seed: &[u8; SIGN_SEED_BYTES]) -> ([u8; SIGN_SECRET_KEY_BYTES], [u8; SIGN_PUBLIC_KEY_BYTES]) {
+        let key = SigningKey::from_bytes(seed);
+        (key.to_keypair_bytes(), key.verifying_key().to_bytes())
+    }
+
+    fn sign_sk_to_pk(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGN_PUBLIC_KEY_BYTES] {
+        sk[SIGN_SEED_BYTES..].try_into().unwrap()
+    }
+
+    fn sign_detached(data: &[u8], sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES] {
+        signing_key(sk).sign(data).to_bytes()
+    }
+
+    fn sign_verify_detached(sig: &[u8; SIGNATURE_BYTES], data: &[u8], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool {
+        let sig = ed25519_dalek::Signature::from_bytes(sig);
+        verifying_key(pk).is_some_and(|pk| pk.verify_strict(data, &sig).is_ok())
+    }
+
+    fn sign_init(state: &mut SignState) {
+        state.0 = Sha512::new();
+    }
+
+    fn sign_update(state: &mut SignState, data: &[u8]) {
+        state.0.update(data);
+    }
+
+    fn sign_final_create(state: &mut SignState, sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES] {
+        let prehashed = std::mem::take(&mut state.0);
+        signing_key(sk).sign_prehashed(prehashed, None)
+            .expect("Ed25519ph without context always succeeds")
+            .to_bytes()
+    }
+
+    fn sign_final_verify(state: &mut SignState, sig: &[u8; SIGNATURE_BYTES], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool {
+        let prehashed = std::mem::take(&mut state.0);
+        let sig = ed25519_dalek::Signature::from_bytes(sig);
+        verifying_key(pk).is_some_and(|pk| {
+            pk.verify_prehashed_strict(prehashed, None, &sig).is_ok()
+        })
+    }
+
+    fn sign_pk_to_curve25519(pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]> {
+        let point = CompressedEdwardsY(*pk).decompress()?;
+        if point.is_small_order() || !point.is_torsion_free() {
+            return None;
+        }
+        Some(point.to_montgomery().to_bytes())
+    }
+
+    fn sign_sk_to_curve25519(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; BOX_KEY_BYTES] {
+        let mut x25519 = signing_key(sk).to_scalar_bytes();
+        x25519[0] &= 248;
+        x25519[31] &= 127;
+        x25519[31] |= 64;
+        x25519
+    }
+
+    fn box_seed_keypair(seed: &[u8; BOX_KEY_BYTES]) -> ([u8; BOX_KEY_BYTES], [u8; BOX_KEY_BYTES]) {
+        let hash = Sha512::digest(seed);
+        let sk: [u8; BOX_KEY_BYTES] = hash[..BOX_KEY_BYTES].try_into().unwrap();
+        let pk = Self::scalarmult_base(&sk);
+        (sk, pk)
+    }
+
+    fn scalarmult_base(sk: &[u8; BOX_KEY_BYTES]) -> [u8; BOX_KEY_BYTES] {
+        MontgomeryPoint::mul_base_clamped(*sk).to_bytes()
+    }
+
+    fn box_beforenm(pk: &[u8; BOX_KEY_BYTES], sk: &[u8; BOX_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]> {
+        let shared = MontgomeryPoint(*pk).mul_clamped(*sk).to_bytes();
+        if shared.iter().all(|b| *b == 0) {
+            return None;
+        }
+        let key = hsalsa::<U10>(&shared.into(), &[0u8; 16].into());
+        Some(key.into())
+    }
+
+    fn box_easy_afternm(cipher: &mut [u8], plain: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool {
+        assert_eq!(cipher.len(), plain.len() + BOX_MAC_BYTES);
+        let (mac, body) = cipher.split_at_mut(BOX_MAC_BYTES);
+        body.copy_from_slice(plain);
+
+        let secretbox = XSalsa20Poly1305::new(key.into());
+        match secretbox.encrypt_in_place_detached(nonce.into(), b"", body) {
+            Ok(tag) => {
+                mac.copy_from_slice(&tag);
+                true
+            },
+            Err(_) => false,
+        }
+    }
+
+    fn box_open_easy_afternm(plain: &mut [u8], cipher: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool {
+        if cipher.len() < BOX_MAC_BYTES {
+            return false;
+        }
+        assert_eq!(plain.len(), cipher.len() - BOX_MAC_BYTES);
+        let (mac, body) = cipher.split_at(BOX_MAC_BYTES);
+        plain.copy_from_slice(body);
+
+        let secretbox = XSalsa20Poly1305::new(key.into());
+        let ok = secretbox.decrypt_in_place_detached(nonce.into(), b"", plain, mac.into()).is_ok();
+        if !ok {
+            plain.fill(0);
+        }
+        ok
+    }
+}
@@ -0,0 +1,87 @@
+use super::{pwhash_argon2id, PWHASH_SALT_BYTES};
+
+// The header of the blobs sealed under a key derived from a password with
+// Argon2id: magic || version || opslimit || memlimit (KiB) || salt. The
+// cost is recorded so it can be raised without breaking older blobs. What
+// follows the header, the sealed data with its nonce, is up to the blob.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SealedHeader {
+    pub(crate) opslimit     : u32,
+    pub(crate) memlimit_kib : u32,
+    pub(crate) salt         : [u8; PWHASH_SALT_BYTES],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum HeaderError {
+    // Too short, or not the expected magic.
+    Incomplete,
+    UnsupportedVersion(u8),
+    // A cost out of the accepted bounds.
+    UnsupportedCost,
+}
+
+impl SealedHeader {
+    pub(crate) const MAGIC_BYTES: usize = 4;
+    pub(crate) const BYTES: usize = Self::MAGIC_BYTES + 1 + 4 + 4 + PWHASH_SALT_BYTES;
+
+    // Argon2id cost of new blobs.
+    const OPSLIMIT      : u32 = 3;
+    const MEMLIMIT_KIB  : u32 = 64 * 1024;
+    // A crafted header must not make the reader allocate unbounded memory.
+    const MAX_OPSLIMIT      : u32 = 16;
+    const MAX_MEMLIMIT_KIB  : u32 = 1024 * 1024;
+
+    // The header of a new blob, with a random salt.
+    pub(crate) fn new() -> Self {
+        Self {
+            opslimit    : Self::OPSLIMIT,
+            memlimit_kib: Self::MEMLIMIT_KIB,
+            salt        : crate::random_array::<PWHASH_SALT_BYTES>(),
+        }
+    }
+
+    pub(crate) fn write(&self, magic: &[u8; Self::MAGIC_BYTES], version: u8, out: &mut Vec<u8>) {
+        out.extend_from_slice(magic);
+        out.push(version);
+        out.extend_from_slice(&self.opslimit.to_be_bytes());
+        out.extend_from_slice(&self.memlimit_kib.to_be_bytes());
+        out.extend_from_slice(&self.salt);
+    }
+
+    // The header at the start of `data`, which must carry the magic and
+    // the version given.
+    pub(crate) fn parse(data: &[u8], magic: &[u8; Self::MAGIC_BYTES], version: u8) -> Result<Self, HeaderError> {
+        if data.len() < Self::BYTES || !data.starts_with(magic) {
+            return Err(HeaderError::Incomplete);
+        }
+        let found = data[Self::MAGIC_BYTES];
+        if found != version {
+            return Err(HeaderError::UnsupportedVersion(found));
+        }
+
+        let field = |pos: usize| u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap());
+        let header = Self {
+            opslimit    : field(Self::MAGIC_BYTES + 1),
+            memlimit_kib: field(Self::MAGIC_BYTES + 5),
+            salt        : data[Self::MAGIC_BYTES + 9..Self::BYTES].try_into().unwrap(),
+        };
+        if header.opslimit == 0 ||
+            header.opslimit > Self::MAX_OPSLIMIT ||
+            header.memlimit_kib > Self::MAX_MEMLIMIT_KIB {
+            return Err(HeaderError::UnsupportedCost);
+        }
+        Ok(header)
+    }
+
+    // Derives the key of the blob from the password into `out`, false if
+    // the backend has no Argon2id.
+    pub(crate) fn derive(&self, password: &str, out: &mut [u8]) -> bool {
+        pwhash_argon2id(
+            out,
+            password.as_bytes(),
+            &self.salt,
+            self.opslimit as u64,
+            self.memlimit_kib as usize * 1024
+        )
+    }
+}
@@ -0,0 +1,287 @@
+use std::mem;
+use static_assertions::const_assert;
+use libsodium_sys::{
+    crypto_box_BEFORENMBYTES,
+    crypto_box_MACBYTES,
+    crypto_box_NONCEBYTES,
+    crypto_box_SEEDBYTES,
+    crypto_box_beforenm,
+    crypto_box_easy_afternm,
+    crypto_box_open_easy_afternm,
+    crypto_box_seed_keypair,
+    crypto_pwhash,
+    crypto_pwhash_ALG_ARGON2ID13,
+    crypto_pwhash_SALTBYTES,
+    crypto_scalarmult_base,
+    crypto_sign_BYTES,
+    crypto_sign_PUBLICKEYBYTES,
+    crypto_sign_SECRETKEYBYTES,
+    crypto_sign_SEEDBYTES,
+    crypto_sign_detached,
+    crypto_sign_ed25519_pk_to_curve25519,
+    crypto_sign_ed25519_sk_to_curve25519,
+    crypto_sign_ed25519_sk_to_pk,
+    crypto_sign_final_create,
+    crypto_sign_final_verify,
+    crypto_sign_init,
+    crypto_sign_seed_keypair,
+    crypto_sign_state,
+    crypto_sign_update,
+    crypto_sign_verify_detached,
+    randombytes_buf,
+    randombytes_random,
+    randombytes_uniform,
+};
+
+use crate::{
+    as_uchar_ptr,
+    as_uchar_ptr_mut,
+};
+use super::*;
+
+const_assert!(SIGN_SECRET_KEY_BYTES == crypto_sign_SECRETKEYBYTES as usize);
+const_assert!(SIGN_PUBLIC_KEY_BYTES == crypto_sign_PUBLICKEYBYTES as usize);
+const_assert!(SIGN_SEED_BYTES == crypto_sign_SEEDBYTES as usize);
+const_assert!(SIGNATURE_BYTES == crypto_sign_BYTES as usize);
+const_assert!(BOX_KEY_BYTES == crypto_box_BEFORENMBYTES as usize);
+const_assert!(BOX_KEY_BYTES == crypto_box_SEEDBYTES as usize);
+const_assert!(BOX_NONCE_BYTES == crypto_box_NONCEBYTES as usize);
+const_assert!(BOX_MAC_BYTES == crypto_box_MACBYTES as usize);
+const_assert!(PWHASH_SALT_BYTES == crypto_pwhash_SALTBYTES as usize);
+
+#[repr(transparent)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SignState([u8; mem::size_of::<crypto_sign_state>()]);
+
+impl Default for SignState {
+    fn default() -> Self {
+        Self([0u8; mem::size_of::<crypto_sign_state>()])
+    }
+}
+
+impl SignState {
+    fn as_mut_ptr(&mut self) -> *mut crypto_sign_state {
+        &mut self.0 as *mut _ as *mut crypto_sign_state
+    }
+}
+
+pub(crate) struct Sodium;
+
+impl Provider for Sodium {
+    type SignState = SignState;
+
+    fn random_bytes(buf: &mut [u8]) {
+        unsafe {
+            randombytes_buf(
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len()
+            );
+        }
+    }
+
+    fn random_u32() -> u32 {
+        unsafe { randombytes_random() }
+    }
+
+    fn random_uniform(upper: u32) -> u32 {
+        unsafe { randombytes_uniform(upper) }
+    }
+
+    fn sign_seed_keypair(seed: &[u8; SIGN_SEED_BYTES]) -> ([u8; SIGN_SECRET_KEY_BYTES], [u8; SIGN_PUBLIC_KEY_BYTES]) {
+        let mut sk = [0u8; SIGN_SECRET_KEY_BYTES];
+        let mut pk = [0u8; SIGN_PUBLIC_KEY_BYTES];
+        unsafe { // Always success
+            crypto_sign_seed_keypair(
+                as_uchar_ptr_mut!(pk),
+                as_uchar_ptr_mut!(sk),
+                as_uchar_ptr!(seed),
+            );
+        }
+        (sk, pk)
+    }
+
+    fn sign_sk_to_pk(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGN_PUBLIC_KEY_BYTES] {
+        let mut pk = [0u8; SIGN_PUBLIC_KEY_BYTES];
+        unsafe { // Always success
+            crypto_sign_ed25519_sk_to_pk(
+                as_uchar_ptr_mut!(pk),
+                as_uchar_ptr!(sk)
+            );
+        }
+        pk
+    }
+
+    fn sign_detached(data: &[u8], sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES] {
+        let mut sig = [0u8; SIGNATURE_BYTES];
+        unsafe { // Always success
+            crypto_sign_detached(
+                as_uchar_ptr_mut!(sig),
+                std::ptr::null_mut(),
+                as_uchar_ptr!(data),
+                data.len() as libc::c_ulonglong,
+                as_uchar_ptr!(sk),
+            );
+        }
+        sig
+    }
+
+    fn sign_verify_detached(sig: &[u8; SIGNATURE_BYTES], data: &[u8], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool {
+        let rc = unsafe {
+            crypto_sign_verify_detached(
+                as_uchar_ptr!(sig),
+                as_uchar_ptr!(data),
+                data.len() as libc::c_ulonglong,
+                as_uchar_ptr!(pk),
+            )
+        };
+        rc == 0
+    }
+
+    fn sign_init(state: &mut SignState) {
+        unsafe { // Always success
+            crypto_sign_init(state.as_mut_ptr());
+        }
+    }
+
+    fn sign_update(state: &mut SignState, data: &[u8]) {
+        unsafe { // Always success
+            crypto_sign_update(
+                state.as_mut_ptr(),
+                as_uchar_ptr!(data),
+                data.len() as libc::c_ulonglong
+            );
+        }
+    }
+
+    fn sign_final_create(state: &mut SignState, sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; SIGNATURE_BYTES] {
+        let mut sig = [0u8; SIGNATURE_BYTES];
+        unsafe { // Always success
+            crypto_sign_final_create(
+                state.as_mut_ptr(),
+                as_uchar_ptr_mut!(sig),
+                std::ptr::null_mut(),
+                as_uchar_ptr!(sk),
+            );
+        }
+        sig
+    }
+
+    fn sign_final_verify(state: &mut SignState, sig: &[u8; SIGNATURE_BYTES], pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> bool {
+        let rc = unsafe {
+            crypto_sign_final_verify(
+                state.as_mut_ptr(),
+                as_uchar_ptr!(sig),
+                as_uchar_ptr!(pk)
+            )
+        };
+        rc == 0
+    }
+
+    fn sign_pk_to_curve25519(pk: &[u8; SIGN_PUBLIC_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]> {
+        let mut x25519 = [0u8; BOX_KEY_BYTES];
+        let rc = unsafe {
+            crypto_sign_ed25519_pk_to_curve25519(
+                as_uchar_ptr_mut!(x25519),
+                as_uchar_ptr!(pk),
+            )
+        };
+        (rc == 0).then_some(x25519)
+    }
+
+    fn sign_sk_to_curve25519(sk: &[u8; SIGN_SECRET_KEY_BYTES]) -> [u8; BOX_KEY_BYTES] {
+        let mut x25519 = [0u8; BOX_KEY_BYTES];
+        unsafe { // Always success
+            crypto_sign_ed25519_sk_to_curve25519(
+                as_uchar_ptr_mut!(x25519),
+                as_uchar_ptr!(sk),
+            );
+        }
+        x25519
+    }
+
+    fn box_seed_keypair(seed: &[u8; BOX_KEY_BYTES]) -> ([u8; BOX_KEY_BYTES], [u8; BOX_KEY_BYTES]) {
+        let mut sk = [0u8; BOX_KEY_BYTES];
+        let mut pk = [0u8; BOX_KEY_BYTES];
+        unsafe { // Always success
+            crypto_box_seed_keypair(
+                as_uchar_ptr_mut!(pk),
+                as_uchar_ptr_mut!(sk),
+                as_uchar_ptr!(seed),
+            );
+        }
+        (sk, pk)
+    }
+
+    fn scalarmult_base(sk: &[u8; BOX_KEY_BYTES]) -> [u8; BOX_KEY_BYTES] {
+        let mut pk = [0u8; BOX_KEY_BYTES];
+        unsafe {
+            crypto_scalarmult_base(
+                as_uchar_ptr_mut!(pk),
+                as_uchar_ptr!(sk)
+            );
+        }
+        pk
+    }
+
+    fn box_beforenm(pk: &[u8; BOX_KEY_BYTES], sk: &[u8; BOX_KEY_BYTES]) -> Option<[u8; BOX_KEY_BYTES]> {
+        let mut key = [0u8; BOX_KEY_BYTES];
+        let rc = unsafe {
+            crypto_box_beforenm(
+                as_uchar_ptr_mut!(key),
+                as_uchar_ptr!(pk),
+                as_uchar_ptr!(sk),
+            )
+        };
+        (rc == 0).then_some(key)
+    }
+
+    fn box_easy_afternm(cipher: &mut [u8], plain: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool {
+        assert_eq!(cipher.len(), plain.len() + BOX_MAC_BYTES);
+        let rc = unsafe {
+            crypto_box_easy_afternm(
+                as_uchar_ptr_mut!(cipher),
+                as_uchar_ptr!(plain),
+                plain.len() as libc::c_ulonglong,
+                as_uchar_ptr!(nonce),
+                as_uchar_ptr!(key),
+            )
+        };
+        rc == 0
+    }
+
+    fn box_open_easy_afternm(plain: &mut [u8], cipher: &[u8], nonce: &[u8; BOX_NONCE_BYTES], key: &[u8; BOX_KEY_BYTES]) -> bool {
+        if cipher.len() < BOX_MAC_BYTES {
+            return false;
+        }
+        assert_eq!(plain.len(), cipher.len() - BOX_MAC_BYTES);
+        let rc = unsafe {
+            crypto_box_open_easy_afternm(
+                as_uchar_ptr_mut!(plain),
+                as_uchar_ptr!(cipher),
+                cipher.len() as libc::c_ulonglong,
+                as_uchar_ptr!(nonce),
+                as_uchar_ptr!(key),
+            )
+        };
+        rc == 0
+    }
+}
+
+impl Sodium {
+    // Argon2id v1.3, outside of Provider as the pure-Rust backend has none.
+    pub(crate) fn pwhash_argon2id(out: &mut [u8], password: &[u8], salt: &[u8; PWHASH_SALT_BYTES], opslimit: u64, memlimit: usize) -> bool {
+        let rc = unsafe {
+            crypto_pwhash(
+                as_uchar_ptr_mut!(out),
+                out.len() as libc::c_ulonglong,
+                password.as_ptr() as *const libc::c_char,
+                password.len() as libc::c_ulonglong,
+                as_uchar_ptr!(salt),
+                opslimit as libc::c_ulonglong,
+                memlimit,
+                crypto_pwhash_ALG_ARGON2ID13 as libc::c_int,
+            )
+        };
+        rc == 0
+    }
+}
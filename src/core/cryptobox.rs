@@ -1,42 +1,19 @@
 use std::fmt;
 use static_assertions::const_assert;
-use libsodium_sys::{
-    crypto_box_BEFORENMBYTES,
-    crypto_box_MACBYTES,
-    crypto_box_NONCEBYTES,
-    crypto_box_PUBLICKEYBYTES,
-    crypto_box_SECRETKEYBYTES,
-    crypto_box_SEEDBYTES,
-    crypto_box_beforenm,
-    crypto_box_easy,
-    crypto_box_easy_afternm,
-    crypto_box_keypair,
-    crypto_box_open_easy,
-    crypto_box_open_easy_afternm,
-    crypto_box_seed_keypair,
-    crypto_scalarmult_base,
-    crypto_sign_ed25519_pk_to_curve25519,
-    crypto_sign_ed25519_sk_to_curve25519,
-    sodium_increment,
-};
-
-use crate::{
-    as_uchar_ptr,
-    as_uchar_ptr_mut,
-};
 
 use super::{
     signature,
     Error, Result,
+    crypto::{self, Backend, Provider},
     errors::{ArgumentError, CryptoError},
 };
 
-const_assert!(PrivateKey::BYTES == crypto_box_SECRETKEYBYTES as usize);
-const_assert!(PublicKey::BYTES == crypto_box_PUBLICKEYBYTES as usize);
-const_assert!(Nonce::BYTES == crypto_box_NONCEBYTES as usize);
-const_assert!(KeyPair::SEED_BYTES == crypto_box_SEEDBYTES as usize);
-const_assert!(CryptoBox::SYMMETRIC_KEY_BYTES == crypto_box_BEFORENMBYTES as usize);
-const_assert!(CryptoBox::MAC_BYTES == crypto_box_MACBYTES as usize);
+const_assert!(PrivateKey::BYTES == crypto::BOX_KEY_BYTES);
+const_assert!(PublicKey::BYTES == crypto::BOX_KEY_BYTES);
+const_assert!(Nonce::BYTES == crypto::BOX_NONCE_BYTES);
+const_assert!(KeyPair::SEED_BYTES == crypto::BOX_KEY_BYTES);
+const_assert!(CryptoBox::SYMMETRIC_KEY_BYTES == crypto::BOX_KEY_BYTES);
+const_assert!(CryptoBox::MAC_BYTES == crypto::BOX_MAC_BYTES);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PrivateKey([u8; Self::BYTES]);
@@ -74,20 +51,9 @@ impl TryFrom<&[u8]> for PrivateKey {
 impl TryFrom<&signature::PrivateKey> for PrivateKey {
     type Error = Error;
     fn try_from(sk: &signature::PrivateKey) -> Result<Self> {
-        let mut bytes = [0u8; Self::BYTES];
-        let rc = unsafe {
-            crypto_sign_ed25519_sk_to_curve25519(
-                as_uchar_ptr_mut!(bytes),
-                as_uchar_ptr!(sk.as_bytes()),
-            )
-        };
-
-        if rc != 0 {
-            return Err(CryptoError::new(format!(
-                "converts Ed25519 key to x25519 key failed."
-            )))
-        }
-        Ok(Self(bytes))
+        Ok(Self(Backend::sign_sk_to_curve25519(
+            sk.as_bytes().try_into().unwrap()
+        )))
     }
 }
 
@@ -109,7 +75,7 @@ impl fmt::Display for PrivateKey {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct PublicKey(
     pub(crate) [u8; Self::BYTES]
 );
@@ -147,19 +113,11 @@ impl TryFrom<&[u8]> for PublicKey {
 impl TryFrom<&signature::PublicKey> for PublicKey {
     type Error = Error;
     fn try_from(pk: &signature::PublicKey) -> Result<Self> {
-        let mut bytes = [0u8; Self::BYTES];
-        let rc = unsafe {
-            crypto_sign_ed25519_pk_to_curve25519(
-                as_uchar_ptr_mut!(bytes),
-                as_uchar_ptr!(pk.as_bytes()),
-            )
-        };
-
-        if rc != 0 {
+        let Some(bytes) = Backend::sign_pk_to_curve25519(&pk.0) else {
             return Err(CryptoError::new(format!(
                 "converts Ed25519 key to x25519 key failed."
             )))
-        }
+        };
         Ok(Self(bytes))
     }
 }
@@ -193,12 +151,13 @@ impl Nonce {
         Nonce(crate::random_array::<{ Self::BYTES }>())
     }
 
+    // Little-endian increment, same as sodium_increment()
     pub fn increment(&mut self) -> &Self {
-        unsafe {
-            sodium_increment(
-                as_uchar_ptr_mut!(self.0),
-                Self::BYTES
-            )
+        let mut carry = 1u16;
+        for b in self.0.iter_mut() {
+            carry += *b as u16;
+            *b = carry as u8;
+            carry >>= 8;
         }
         self
     }
@@ -256,33 +215,14 @@ impl KeyPair {
     pub const SEED_BYTES: usize = 32;
 
     pub fn new() -> Self {
-        let mut pk = [0u8; PublicKey::BYTES];
-        let mut sk = [0u8; PrivateKey::BYTES];
-
-        unsafe {
-            crypto_box_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk)
-            );
-        }
-
+        let sk = crate::random_array::<{ PrivateKey::BYTES }>();
+        let pk = Backend::scalarmult_base(&sk);
         Self(PrivateKey(sk), PublicKey(pk))
     }
 
     pub fn random() -> Self {
         let seed = crate::random_array::<{ Self::SEED_BYTES }>();
-
-        let mut sk = [0u8; PrivateKey::BYTES];
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe { // Always success
-            crypto_box_seed_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk),
-                as_uchar_ptr!(seed),
-            );
-        }
-
+        let (sk, pk) = Backend::box_seed_keypair(&seed);
         Self(PrivateKey(sk), PublicKey(pk))
     }
 
@@ -295,16 +235,7 @@ impl KeyPair {
             )));
         }
 
-        let mut pk = [0u8; PublicKey::BYTES];
-        let mut sk = [0u8; PrivateKey::BYTES];
-        unsafe {
-            crypto_box_seed_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk),
-                as_uchar_ptr!(seed),
-            );
-        }
-
+        let (sk, pk) = Backend::box_seed_keypair(seed.try_into().unwrap());
         Ok(Self(PrivateKey(sk), PublicKey(pk)))
     }
 
@@ -341,14 +272,7 @@ impl TryFrom<&[u8]> for KeyPair {
             )));
         }
 
-        let mut pk = [0u8; PublicKey::BYTES];
-        unsafe {
-            crypto_scalarmult_base(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr!(sk)
-            );
-        }
-
+        let pk = Backend::scalarmult_base(sk.try_into().unwrap());
         Ok(Self(
             PrivateKey::try_from(sk).unwrap(),
             PublicKey(pk)
@@ -358,15 +282,7 @@ impl TryFrom<&[u8]> for KeyPair {
 
 impl From<&PrivateKey> for KeyPair {
     fn from(sk: &PrivateKey) -> Self {
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe {
-            crypto_scalarmult_base(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr!(sk.as_bytes())
-            );
-        }
-
+        let pk = Backend::scalarmult_base(&sk.0);
         KeyPair(
             sk.clone(),
             PublicKey(pk)
@@ -376,14 +292,9 @@ impl From<&PrivateKey> for KeyPair {
 
 impl From<&signature::KeyPair> for KeyPair {
     fn from(kp: &signature::KeyPair) -> Self {
-        let mut x25519 = [0u8; PrivateKey::BYTES];
-
-        unsafe {
-            crypto_sign_ed25519_sk_to_curve25519(
-                as_uchar_ptr_mut!(x25519),
-                as_uchar_ptr!(kp.private_key().as_bytes()),
-            );
-        }
+        let x25519 = Backend::sign_sk_to_curve25519(
+            kp.private_key().as_bytes().try_into().unwrap()
+        );
         Self::try_from(x25519.as_slice()).unwrap()
     }
 }
@@ -413,6 +324,11 @@ impl CryptoBox {
     pub const SYMMETRIC_KEY_BYTES: usize = 32;
     pub const MAC_BYTES: usize = 16;
 
+    // Wraps an already derived symmetric key instead of a key agreement result.
+    pub(crate) const fn from_symmetric_key(key: [u8; Self::SYMMETRIC_KEY_BYTES]) -> Self {
+        Self(key)
+    }
+
     pub const fn size(&self) -> usize {
         Self::SYMMETRIC_KEY_BYTES
     }
@@ -436,17 +352,14 @@ impl CryptoBox {
         }
 
         cipher[..Nonce::BYTES].copy_from_slice(nonce.as_bytes());
-        let rc = unsafe {
-            crypto_box_easy_afternm(
-                as_uchar_ptr_mut!(cipher[Nonce::BYTES..expected_len]),
-                as_uchar_ptr!(plain),
-                plain.len() as libc::c_ulonglong,
-                as_uchar_ptr!(cipher[..Nonce::BYTES]),
-                as_uchar_ptr!(self.0),
-            )
-        };
-
-        match rc == 0 {
+        let rc = Backend::box_easy_afternm(
+            &mut cipher[Nonce::BYTES..expected_len],
+            plain,
+            &nonce.0,
+            &self.0,
+        );
+
+        match rc {
             true => Ok(expected_len),
             false => return Err(CryptoError::new(format!("Data encryption failed")))
         }
@@ -469,19 +382,15 @@ impl CryptoBox {
             return Err(ArgumentError::new(format!("The input buffer is insufficient.")));
         }
 
-        let cipher_len = cipher.len() - Nonce::BYTES;
         //  Extract the nonce from the cipher text
-        let rc = unsafe {
-            crypto_box_open_easy_afternm(
-                as_uchar_ptr_mut!(plain[.. expected_len]),
-                as_uchar_ptr!(cipher[Nonce::BYTES..]),
-                cipher_len as libc::c_ulonglong,
-                as_uchar_ptr!(cipher[..Nonce::BYTES]),
-                as_uchar_ptr!(self.0),
-            )
-        };
-
-        match rc == 0 {
+        let rc = Backend::box_open_easy_afternm(
+            &mut plain[..expected_len],
+            &cipher[Nonce::BYTES..],
+            cipher[..Nonce::BYTES].try_into().unwrap(),
+            &self.0,
+        );
+
+        match rc {
             true => Ok(expected_len),
             false => return Err(CryptoError::new(format!("Data decryption failed")))
         }
@@ -498,20 +407,11 @@ impl CryptoBox {
 impl TryFrom<(&PublicKey, &PrivateKey)> for CryptoBox {
     type Error = Error;
     fn try_from(kp: (&PublicKey, &PrivateKey)) -> Result<Self> {
-        let mut key = [0u8; Self::SYMMETRIC_KEY_BYTES];
-        let rc = unsafe {
-            crypto_box_beforenm(
-                as_uchar_ptr_mut!(key),
-                as_uchar_ptr!(kp.0.as_bytes()),
-                as_uchar_ptr!(kp.1.as_bytes()),
-            )
-        };
-        if rc != 0 {
+        let Some(key) = Backend::box_beforenm(&kp.0.0, &kp.1.0) else {
             return Err(CryptoError::new(format!(
                 "Compute symmetric key failed, wrong public key or private key"
             )));
-        }
-
+        };
         Ok(CryptoBox(key))
     }
 }
@@ -533,21 +433,10 @@ pub fn encrypt(plain: &[u8],
         return Err(ArgumentError::new(format!("The input buffer is insufficient.")));
     }
 
-    cipher[..Nonce::BYTES].copy_from_slice(nonce.as_bytes());
-    let rc = unsafe {
-        crypto_box_easy(
-            as_uchar_ptr_mut!(cipher[Nonce::BYTES.. expected_len]),
-            as_uchar_ptr!(plain),
-            plain.len() as libc::c_ulonglong,
-            as_uchar_ptr!(cipher[..Nonce::BYTES]),
-            as_uchar_ptr!(pk.as_bytes()),
-            as_uchar_ptr!(sk.as_bytes()),
-        )
+    let Some(key) = Backend::box_beforenm(&pk.0, &sk.0) else {
+        return Err(CryptoError::new(format!("Data encryption failed")));
     };
-    match rc == 0 {
-        true => Ok(expected_len),
-        false => return Err(CryptoError::new(format!("Data encryption failed")))
-    }
+    CryptoBox(key).encrypt(plain, cipher, nonce)
 }
 
 pub fn encrypt_into(plain: &[u8],
@@ -569,23 +458,10 @@ pub fn decrypt(cipher: &[u8],
         return Err(ArgumentError::new(format!("The input buffer is insufficient.")));
     }
 
-    let cipher_len = cipher.len() - Nonce::BYTES;
-    //  Extract the nonce from the cipher text
-    let rc = unsafe {
-        crypto_box_open_easy(
-            as_uchar_ptr_mut!(plain[..expected_len]),
-            as_uchar_ptr!(cipher[Nonce::BYTES..]),
-            cipher_len as libc::c_ulonglong,
-            as_uchar_ptr!(cipher[..Nonce::BYTES]),
-            as_uchar_ptr!(pk.as_bytes()),
-            as_uchar_ptr!(sk.as_bytes()),
-        )
+    let Some(key) = Backend::box_beforenm(&pk.0, &sk.0) else {
+        return Err(CryptoError::new(format!("Data decryption failed")));
     };
-
-    match rc == 0 {
-        true => Ok(expected_len),
-        false => return Err(CryptoError::new(format!("Data decryption failed")))
-    }
+    CryptoBox(key).decrypt(cipher, plain)
 }
 
 pub fn decrypt_into(cipher: &[u8],
@@ -0,0 +1,453 @@
+//! Application-facing serde representation of [`PeerInfo`], [`NodeInfo`]
+//! and [`Value`].
+//!
+//! The `Serialize`/`Deserialize` impls on the types themselves are the DHT
+//! wire encoding and must not change. [`Document`] wraps those types with a
+//! stable, self-describing map layout meant to be embedded directly in
+//! application protocols:
+//!
+//! | Type       | Fields                                                                                  |
+//! |------------|-----------------------------------------------------------------------------------------|
+//! | `PeerInfo` | `id`, `nonce`, `seq`, `nodeId`?, `nodeSig`?, `sig`, `fingerprint`, `endpoint`, `extra`?, `privateKey`? |
+//! | `NodeInfo` | `id`, `host`, `port`, `version`                                                         |
+//! | `Value`    | `publicKey`?, `recipient`?, `nonce`?, `sig`?, `seq`, `data`, `countersigner`?, `countersig`?, `privateKey`? |
+//!
+//! Ids are base58 strings and byte fields are url-safe base64 strings in
+//! human-readable formats (JSON); both are raw byte strings in binary
+//! formats (CBOR). Optional fields (`?`) are omitted when absent.
+//!
+//! Private keys are never written by `Document<T>`; convert it with
+//! [`Document::with_private`] to opt in. Deserialized peers and values are
+//! re-verified and rejected if their signatures do not check out.
+
+use std::{
+    fmt,
+    marker::PhantomData,
+    net::{IpAddr, SocketAddr},
+};
+use serde::{
+    Serialize,
+    Deserialize,
+    Serializer,
+    Deserializer,
+    de::Error as _,
+};
+
+use super::{
+    Id,
+    NodeInfo,
+    PeerInfo,
+    Value,
+    cryptobox::Nonce,
+    signature::PrivateKey,
+    peer_info::PeerFields,
+};
+
+/// Type-state marker: private keys are left out.
+#[derive(Debug, Clone, Copy)]
+pub struct Public;
+
+/// Type-state marker: private keys are included when present.
+#[derive(Debug, Clone, Copy)]
+pub struct WithPrivate;
+
+mod sealed {
+    pub trait Sealed {}
+    impl Sealed for super::Public {}
+    impl Sealed for super::WithPrivate {}
+}
+
+/// Private key policy of a [`Document`].
+pub trait KeyPolicy: sealed::Sealed {
+    #[doc(hidden)]
+    const PRIVATE: bool;
+}
+
+impl KeyPolicy for Public {
+    const PRIVATE: bool = false;
+}
+
+impl KeyPolicy for WithPrivate {
+    const PRIVATE: bool = true;
+}
+
+/// Wraps a [`PeerInfo`], [`NodeInfo`] or [`Value`] for its documented
+/// application-level serde representation.
+#[derive(Debug, Clone)]
+pub struct Document<T, K: KeyPolicy = Public> {
+    inner: T,
+    _policy: PhantomData<K>,
+}
+
+impl<T> Document<T, Public> {
+    pub fn new(inner: T) -> Self {
+        Self { inner, _policy: PhantomData }
+    }
+
+    /// Include private keys when serializing, and restore them when
+    /// deserializing.
+    pub fn with_private(self) -> Document<T, WithPrivate> {
+        Document { inner: self.inner, _policy: PhantomData }
+    }
+}
+
+impl<T, K: KeyPolicy> Document<T, K> {
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> From<T> for Document<T, Public> {
+    fn from(inner: T) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl<T: fmt::Display, K: KeyPolicy> fmt::Display for Document<T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+mod bytes {
+    use std::fmt;
+    use serde::{Deserialize, Deserializer, Serializer, de::{self, Error, Visitor, SeqAccess}};
+    use base64::{engine::general_purpose, Engine as _};
+
+    struct BytesVisitor;
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a byte string")
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+            while let Some(b) = seq.next_element::<u8>()? {
+                v.push(b);
+            }
+            Ok(v)
+        }
+    }
+
+    pub fn serialize<S>(bytes: &[u8], se: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        match se.is_human_readable() {
+            true  => se.serialize_str(&general_purpose::URL_SAFE_NO_PAD.encode(bytes)),
+            false => se.serialize_bytes(bytes),
+        }
+    }
+
+    pub fn deserialize<'de, D>(de: D) -> Result<Vec<u8>, D::Error>
+    where D: Deserializer<'de>,
+    {
+        match de.is_human_readable() {
+            true => {
+                let s = String::deserialize(de)?;
+                general_purpose::URL_SAFE_NO_PAD.decode(&s).map_err(D::Error::custom)
+            },
+            false => de.deserialize_byte_buf(BytesVisitor),
+        }
+    }
+}
+
+mod option_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(serde::Serialize)]
+    struct Ref<'a>(#[serde(with = "super::bytes")] &'a [u8]);
+
+    #[derive(serde::Deserialize)]
+    struct Owned(#[serde(with = "super::bytes")] Vec<u8>);
+
+    pub fn serialize<S, T>(bytes: &Option<T>, se: S) -> Result<S::Ok, S::Error>
+    where S: Serializer, T: AsRef<[u8]>,
+    {
+        match bytes {
+            Some(v) => se.serialize_some(&Ref(v.as_ref())),
+            None => se.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(de: D) -> Result<Option<Vec<u8>>, D::Error>
+    where D: Deserializer<'de>,
+    {
+        Option::<Owned>::deserialize(de).map(|v| v.map(|v| v.0))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerRef<'a> {
+    id: &'a Id,
+    #[serde(with = "bytes")]
+    nonce: &'a [u8],
+    seq: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    node_id: Option<&'a Id>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    node_sig: Option<&'a [u8]>,
+    #[serde(with = "bytes")]
+    sig: &'a [u8],
+    fingerprint: u64,
+    endpoint: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    extra: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "is_default_weight")]
+    weight: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    announced: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    private_key: Option<&'a [u8]>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerOwned {
+    id: Id,
+    #[serde(with = "bytes")]
+    nonce: Vec<u8>,
+    #[serde(default)]
+    seq: i32,
+    #[serde(default)]
+    node_id: Option<Id>,
+    #[serde(default, with = "option_bytes")]
+    node_sig: Option<Vec<u8>>,
+    #[serde(with = "bytes")]
+    sig: Vec<u8>,
+    #[serde(default)]
+    fingerprint: u64,
+    endpoint: String,
+    #[serde(default, with = "option_bytes")]
+    extra: Option<Vec<u8>>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default = "default_weight")]
+    weight: u8,
+    #[serde(default)]
+    announced: Option<u64>,
+    #[serde(default, with = "option_bytes")]
+    private_key: Option<Vec<u8>>,
+}
+
+fn is_default_weight(weight: &u8) -> bool {
+    *weight == PeerInfo::DEFAULT_WEIGHT
+}
+
+fn default_weight() -> u8 {
+    PeerInfo::DEFAULT_WEIGHT
+}
+
+impl<K: KeyPolicy> Serialize for Document<PeerInfo, K> {
+    fn serialize<S>(&self, se: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        let peer = &self.inner;
+        PeerRef {
+            id: peer.id(),
+            nonce: peer.nonce(),
+            seq: peer.sequence_number(),
+            node_id: peer.nodeid(),
+            node_sig: peer.node_signature(),
+            sig: peer.signature(),
+            fingerprint: peer.fingerprint(),
+            endpoint: peer.endpoint(),
+            extra: peer.extra_data(),
+            tags: peer.tags(),
+            weight: peer.weight(),
+            announced: peer.announced(),
+            private_key: match K::PRIVATE {
+                true  => peer.private_key().map(|sk| sk.as_bytes()),
+                false => None,
+            },
+        }.serialize(se)
+    }
+}
+
+impl<'de, K: KeyPolicy> Deserialize<'de> for Document<PeerInfo, K> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>,
+    {
+        let v = PeerOwned::deserialize(de)?;
+        let mut peer = PeerInfo::packed(
+            v.id,
+            v.nonce,
+            v.seq,
+            v.node_id,
+            v.node_sig,
+            v.sig,
+            v.fingerprint,
+            v.endpoint,
+            PeerFields {
+                extra: v.extra,
+                tags: v.tags,
+                weight: v.weight,
+                announced: v.announced,
+            },
+        );
+
+        if !peer.is_valid() {
+            return Err(D::Error::custom("Invalid peer info signature"));
+        }
+
+        if K::PRIVATE {
+            if let Some(sk) = v.private_key {
+                let sk = PrivateKey::try_from(sk.as_slice()).map_err(D::Error::custom)?;
+                peer.set_private_key(sk).map_err(D::Error::custom)?;
+            }
+        }
+
+        Ok(Self { inner: peer, _policy: PhantomData })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeFields {
+    id: Id,
+    host: IpAddr,
+    port: u16,
+    #[serde(default)]
+    version: i32,
+}
+
+impl Serialize for Document<NodeInfo, Public> {
+    fn serialize<S>(&self, se: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        let node = &self.inner;
+        NodeFields {
+            id: *node.id(),
+            host: node.ip(),
+            port: node.port(),
+            version: node.version(),
+        }.serialize(se)
+    }
+}
+
+impl<'de> Deserialize<'de> for Document<NodeInfo, Public> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>,
+    {
+        let v = NodeFields::deserialize(de)?;
+        let mut node = NodeInfo::new(v.id, SocketAddr::new(v.host, v.port));
+        node.set_version(v.version);
+        Ok(Self::new(node))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ValueRef<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    public_key: Option<&'a Id>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recipient: Option<&'a Id>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    nonce: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    sig: Option<&'a [u8]>,
+    seq: i32,
+    #[serde(with = "bytes")]
+    data: &'a [u8],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    countersigner: Option<&'a Id>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    countersig: Option<&'a [u8]>,
+    #[serde(skip_serializing_if = "Option::is_none", with = "option_bytes")]
+    private_key: Option<&'a [u8]>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ValueOwned {
+    #[serde(default)]
+    public_key: Option<Id>,
+    #[serde(default)]
+    recipient: Option<Id>,
+    #[serde(default, with = "option_bytes")]
+    nonce: Option<Vec<u8>>,
+    #[serde(default, with = "option_bytes")]
+    sig: Option<Vec<u8>>,
+    #[serde(default)]
+    seq: i32,
+    #[serde(with = "bytes")]
+    data: Vec<u8>,
+    #[serde(default)]
+    countersigner: Option<Id>,
+    #[serde(default, with = "option_bytes")]
+    countersig: Option<Vec<u8>>,
+    #[serde(default, with = "option_bytes")]
+    private_key: Option<Vec<u8>>,
+}
+
+impl<K: KeyPolicy> Serialize for Document<Value, K> {
+    fn serialize<S>(&self, se: S) -> Result<S::Ok, S::Error>
+    where S: Serializer,
+    {
+        let value = &self.inner;
+        ValueRef {
+            public_key: value.public_key(),
+            recipient: value.recipient(),
+            nonce: value.nonce().map(|n| n.as_bytes()),
+            sig: value.signature(),
+            seq: value.sequence_number(),
+            data: value.data(),
+            countersigner: value.countersigner(),
+            countersig: value.countersignature(),
+            private_key: match K::PRIVATE {
+                true  => value.private_key().map(|sk| sk.as_bytes()),
+                false => None,
+            },
+        }.serialize(se)
+    }
+}
+
+impl<'de, K: KeyPolicy> Deserialize<'de> for Document<Value, K> {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de>,
+    {
+        let v = ValueOwned::deserialize(de)?;
+        let nonce = match v.nonce {
+            Some(n) => Some(Nonce::try_from(n.as_slice()).map_err(D::Error::custom)?),
+            None => None,
+        };
+
+        let mut value = Value::packed(
+            v.public_key,
+            v.recipient,
+            nonce,
+            v.sig,
+            v.data,
+            v.seq,
+        ).with_countersignature(v.countersigner, v.countersig);
+
+        if !value.is_valid() {
+            return Err(D::Error::custom("Invalid value signature"));
+        }
+
+        if K::PRIVATE {
+            if let Some(sk) = v.private_key {
+                let sk = PrivateKey::try_from(sk.as_slice()).map_err(D::Error::custom)?;
+                value.set_private_key(sk).map_err(D::Error::custom)?;
+            }
+        }
+
+        Ok(Self { inner: value, _policy: PhantomData })
+    }
+}
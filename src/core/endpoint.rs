@@ -0,0 +1,179 @@
+use std::fmt;
+use std::str::FromStr;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(feature = "activeproxy")]
+use std::net::ToSocketAddrs;
+use url::Url;
+
+use crate::errors::{Error, Result, ArgumentError};
+
+// Longest endpoint accepted, several peers have to fit in one datagram.
+pub const MAX_ENDPOINT_LENGTH: usize = 512;
+
+const ALLOWED_SCHEMES: [&str; 6] = ["http", "https", "tcp", "ssl", "ws", "wss"];
+
+// How peer endpoints received from the network are screened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EndpointPolicy {
+    // Messages carrying an invalid or non-normalized endpoint are rejected,
+    // including the bare host:port endpoints of older nodes.
+    Reject,
+    // Peers with an invalid endpoint are dropped, the rest is kept. The bare
+    // host:port endpoints of older nodes are valid tcp endpoints.
+    #[default]
+    Sanitize,
+    // Endpoints are not checked.
+    Accept,
+}
+
+impl FromStr for EndpointPolicy {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        match input.to_ascii_lowercase().as_str() {
+            "reject"    => Ok(EndpointPolicy::Reject),
+            "sanitize"  => Ok(EndpointPolicy::Sanitize),
+            "accept"    => Ok(EndpointPolicy::Accept),
+            _ => Err(ArgumentError::new(format!("Unsupported endpoint policy: {input}"))),
+        }
+    }
+}
+
+impl fmt::Display for EndpointPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            EndpointPolicy::Reject      => "reject",
+            EndpointPolicy::Sanitize    => "sanitize",
+            EndpointPolicy::Accept      => "accept",
+        })
+    }
+}
+
+impl EndpointPolicy {
+    // Whether a received endpoint may pass under this policy.
+    pub(crate) fn admits(&self, endpoint: &str) -> bool {
+        match self {
+            EndpointPolicy::Accept      => true,
+            EndpointPolicy::Sanitize    => normalize_endpoint(endpoint).is_ok(),
+            EndpointPolicy::Reject      => normalize_endpoint(endpoint)
+                .is_ok_and(|normalized| normalized == endpoint),
+        }
+    }
+}
+
+// Validates a peer endpoint and returns its normalized form: lowercase scheme
+// and host, no default port and no bare root path. The bare host:port
+// endpoints announced by older nodes are taken as tcp endpoints.
+pub(crate) fn normalize_endpoint(input: &str) -> Result<String> {
+    let input = input.trim();
+    if is_legacy_endpoint(input) {
+        return normalize_endpoint(&format!("tcp://{input}"));
+    }
+    if input.len() > MAX_ENDPOINT_LENGTH {
+        return Err(ArgumentError::new(format!(
+            "Endpoint too long: {} bytes, at most {}", input.len(), MAX_ENDPOINT_LENGTH
+        )));
+    }
+    if input.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(ArgumentError::new("Endpoint contains whitespace or control characters"));
+    }
+
+    let url = Url::parse(input).map_err(|e| {
+        ArgumentError::new(format!("Invalid endpoint {input}: {e}"))
+    })?;
+    if !ALLOWED_SCHEMES.contains(&url.scheme()) {
+        return Err(ArgumentError::new(format!("Unsupported endpoint scheme: {}", url.scheme())));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(ArgumentError::new("Endpoint must not embed credentials"));
+    }
+    let host = match url.host_str() {
+        Some(host) if !host.is_empty() => host.to_lowercase(),
+        _ => return Err(ArgumentError::new(format!("Endpoint {input} has no host"))),
+    };
+
+    let mut normalized = format!("{}://{}", url.scheme(), host);
+    if let Some(port) = url.port() {
+        normalized.push_str(&format!(":{port}"));
+    }
+    if url.path() != "/" {
+        normalized.push_str(url.path());
+    }
+    if let Some(query) = url.query() {
+        normalized.push_str(&format!("?{query}"));
+    }
+    if let Some(fragment) = url.fragment() {
+        normalized.push_str(&format!("#{fragment}"));
+    }
+    Ok(normalized)
+}
+
+// A host:port endpoint without a scheme, as older nodes announce them.
+fn is_legacy_endpoint(input: &str) -> bool {
+    if input.contains("://") {
+        return false;
+    }
+    input.parse::<SocketAddr>().is_ok() || input.rsplit_once(':').is_some_and(|(host, port)| {
+        !host.is_empty() && port.parse::<u16>().is_ok() &&
+            host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+    })
+}
+
+// The socket address a tcp endpoint resolves to, of the legacy host:port
+// form or not. None for an invalid endpoint or one without a port.
+#[cfg(feature = "activeproxy")]
+pub(crate) fn socket_addr_of(endpoint: &str) -> Option<SocketAddr> {
+    let url = Url::parse(&normalize_endpoint(endpoint).ok()?).ok()?;
+    let host = url.host_str()?.trim_start_matches('[').trim_end_matches(']');
+    (host, url.port_or_known_default()?).to_socket_addrs().ok()?.next()
+}
+
+// Whether the rest of the network can't reach the address: private, shared
+// (carrier-grade NAT), link-local, unique local, loopback or unspecified.
+pub(crate) fn is_unroutable(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => v4.is_private() || v4.is_link_local() || v4.is_loopback()
+            || v4.is_unspecified()
+            || (v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64),
+        IpAddr::V6(v6) => v6.is_loopback() || v6.is_unspecified()
+            || (v6.segments()[0] & 0xfe00) == 0xfc00
+            || (v6.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+// The endpoint of a peer as seen from where its announcement came from.
+// When the endpoint names an unroutable address, as announced from behind
+// a NAT, and the announcement came from another address of the family, that
+// address stands in for the host, the port and the rest kept. The source is
+// an address of the NAT, or a private one when the nodes share a network.
+// None when the endpoint is fine as it is or nothing better is known.
+pub(crate) fn observed_endpoint(endpoint: &str, source: IpAddr) -> Option<String> {
+    let url = Url::parse(endpoint).ok()?;
+    let host = url.host_str()?;
+    let ip = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>().ok()?;
+    if !is_unroutable(&ip) || ip == source || ip.is_ipv4() != source.is_ipv4() {
+        return None;
+    }
+    if source.is_unspecified() {
+        return None;
+    }
+
+    let host = match source {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => format!("[{v6}]"),
+    };
+    let mut observed = format!("{}://{}", url.scheme(), host);
+    if let Some(port) = url.port() {
+        observed.push_str(&format!(":{port}"));
+    }
+    if url.path() != "/" {
+        observed.push_str(url.path());
+    }
+    if let Some(query) = url.query() {
+        observed.push_str(&format!("?{query}"));
+    }
+    if let Some(fragment) = url.fragment() {
+        observed.push_str(&format!("#{fragment}"));
+    }
+    Some(observed)
+}
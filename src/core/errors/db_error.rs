@@ -20,12 +20,14 @@ impl Error for DBError {
      }
 }
 
+#[cfg(feature = "dht")]
 impl From<diesel::result::Error> for Box<DBError> {
     fn from(err: diesel::result::Error) -> Box<DBError> {
         DBError::new(format!("SQlite excutation error: {}", err))
     }
 }
 
+#[cfg(feature = "dht")]
 impl From<diesel::ConnectionError> for Box<DBError> {
     fn from(err: diesel::ConnectionError) -> Box<DBError> {
         DBError::new(format!("SQLite connection error: {}", err))
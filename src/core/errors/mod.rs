@@ -11,6 +11,8 @@ pub mod db_error;
 pub mod before_valid_period;
 pub mod expired_error;
 pub mod malformed;
+pub mod unsupported_version;
+pub mod unsupported_protocol;
 
 pub type Error = Box<dyn std::error::Error>;
 pub type Result<T> = std::result::Result<T, Error>;
@@ -29,4 +31,6 @@ pub use {
     before_valid_period::BeforeValidPeriodError,
     expired_error::ExpiredError,
     malformed::MalformedError,
+    unsupported_version::UnsupportedVersionError,
+    unsupported_protocol::UnsupportedProtocolError,
 };
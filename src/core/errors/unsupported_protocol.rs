@@ -0,0 +1,33 @@
+use std::{
+    fmt,
+    error::Error
+};
+
+// An address using a protocol that has no counterpart in boson.
+#[derive(Debug)]
+pub struct UnsupportedProtocolError {
+    protocol: String,
+    message: String
+}
+
+impl UnsupportedProtocolError {
+    pub fn new(protocol: impl Into<String>, message: impl Into<String>) -> Box<Self> {
+        Box::new(Self { protocol: protocol.into(), message: message.into() })
+    }
+
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+}
+
+impl Error for UnsupportedProtocolError {
+    fn description(&self) -> &str {
+        &self.message
+     }
+}
+
+impl fmt::Display for UnsupportedProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UnsupportedProtocolError: {}", self.message)
+     }
+}
@@ -0,0 +1,33 @@
+use std::{
+    fmt,
+    error::Error
+};
+
+// Data in a format version newer than this build reads.
+#[derive(Debug)]
+pub struct UnsupportedVersionError {
+    version: u32,
+    message: String
+}
+
+impl UnsupportedVersionError {
+    pub fn new(version: u32, message: impl Into<String>) -> Box<Self> {
+        Box::new(Self { version, message: message.into() })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+impl Error for UnsupportedVersionError {
+    fn description(&self) -> &str {
+        &self.message
+     }
+}
+
+impl fmt::Display for UnsupportedVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UnsupportedVersionError: {}", self.message)
+     }
+}
@@ -6,6 +6,8 @@ use std::{
     result::Result as SResult
 };
 use bs58;
+use sha2::{Digest, Sha256};
+use unicode_normalization::UnicodeNormalization;
 use serde::{
     Serialize,Deserialize,
     ser::Serializer,
@@ -17,11 +19,15 @@ use crate::{
     signature,
     Error,
     Result,
-    errors::ArgumentError,
+    errors::{ArgumentError, MalformedError},
+    core::compact,
 };
 
 pub const DID_PREFIX: &str = "did:boson:";
 
+// Domain separator of Id::derive, versioned with the derivation.
+const DERIVE_DOMAIN: &[u8] = b"boson:id:derive:v1\0";
+
 #[derive(Debug, Copy, Clone, Default, PartialOrd, PartialEq, Ord, Eq, Hash)]
 pub struct Id(
     [u8; Id::BYTES]
@@ -38,7 +44,7 @@ impl Id {
         Id(crate::random_array::<{ Self::BYTES }>())
     }
 
-    pub fn from_bytes(input: [u8; Id::BYTES]) -> Self {
+    pub const fn from_bytes(input: [u8; Id::BYTES]) -> Self {
         Id(input)
     }
 
@@ -77,12 +83,33 @@ impl Id {
             .with_alphabet(bs58::Alphabet::DEFAULT)
             .onto(&mut bytes[..])
             .map_err(|e| {
-                println!(">>> e: {}, input:{}", e, input);
                 ArgumentError::new(format!("Invalid base58 format string: {e}"))
         })?;
         Ok(Id(bytes))
     }
 
+    pub fn try_from_did_string(input: &str) -> Result<Self> {
+        let Some(input) = input.strip_prefix(DID_PREFIX) else {
+            return Err(ArgumentError::new(format!("DID strings must have a '{DID_PREFIX}' prefix.")));
+        };
+        Self::try_from_base58(input)
+    }
+
+    /// Derives the id of a string key within a namespace, such as the id of
+    /// an application, so that applications agree on the id of a key.
+    ///
+    /// The id is the SHA-256 digest of the domain separator
+    /// `"boson:id:derive:v1\0"`, the 32 bytes of the namespace and the UTF-8
+    /// bytes of the NFC normalized key. The derivation is a compatibility
+    /// guarantee: it never changes, a new scheme would get a new separator.
+    pub fn derive(namespace: &Id, key: &str) -> Self {
+        let mut sha256 = Sha256::new();
+        sha256.update(DERIVE_DOMAIN);
+        sha256.update(namespace.as_bytes());
+        sha256.update(key.nfc().collect::<String>().as_bytes());
+        Id::try_from_bytes(sha256.finalize().as_slice()).unwrap()
+    }
+
     //  Creates an id with the specified bit set to 1.
     pub fn try_from_bit_at(index: usize) -> Result<Self> {
         if index >= Id::BITS {
@@ -172,6 +199,24 @@ impl Id {
         self.0.as_slice()
     }
 
+    /// The id in the compact form shared with compact values, the two byte
+    /// header and the raw id.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut data = compact::header(compact::KIND_ID);
+        data.extend_from_slice(self.as_bytes());
+        data
+    }
+
+    /// Decodes an id from its compact form. Fails with an
+    /// UnsupportedVersionError for a newer format version.
+    pub fn from_compact_bytes(data: &[u8]) -> Result<Self> {
+        let body = compact::body(data, compact::KIND_ID)?;
+        let bytes = <[u8; Id::BYTES]>::try_from(body).map_err(|_|
+            MalformedError::new(format!("Invalid compact id length {}", body.len()))
+        )?;
+        Ok(Id(bytes))
+    }
+
     #[allow(unused)]
     pub(crate) fn update(&mut self, cb: impl Fn(&mut [u8])) {
         cb(self.0.as_mut_slice());
@@ -181,8 +226,11 @@ impl Id {
     Order::Less     : means a is closer to the target than b.
     Order::Greater  : means a is farther from the target than b.
     Order::Equal    : means a and b are equidistant from the target.
+
+    This is the XOR-distance ordering used by the routing table and lookups,
+    equivalent to comparing self.distance(a) with self.distance(b).
      */
-    pub(crate) fn three_way_compare(&self, a: &Self, b: &Self) -> Ordering {
+    pub fn three_way_compare(&self, a: &Self, b: &Self) -> Ordering {
         let mut mmi = Id::BYTES;
         for i in 0..Id::BYTES {
             if a.0[i] != b.0[i] {
@@ -204,6 +252,61 @@ impl Id {
         a.distance(b)
     }
 
+    // Bits are indexed from the most significant bit, as in try_from_bit_at.
+    // Panics if the index is out of bounds.
+    pub fn bit_at(&self, index: usize) -> bool {
+        assert!(index < Id::BITS, "Bit index {index} out of bounds");
+        self.0[index / 8] & (0x80 >> (index % 8)) != 0
+    }
+
+    pub fn set_bit(&mut self, index: usize, value: bool) {
+        assert!(index < Id::BITS, "Bit index {index} out of bounds");
+        let mask = 0x80 >> (index % 8);
+        match value {
+            true  => self.0[index / 8] |= mask,
+            false => self.0[index / 8] &= !mask,
+        }
+    }
+
+    // Number of leading zero bits, Id::BITS for the zero id.
+    pub fn leading_zeros(&self) -> usize {
+        match self.0.iter().position(|b| *b != 0) {
+            Some(i) => i * 8 + self.0[i].leading_zeros() as usize,
+            None => Id::BITS,
+        }
+    }
+
+    // Length of the common bit prefix, i.e. the leading zeros of the distance.
+    pub fn common_prefix_len(&self, other: &Id) -> usize {
+        self.distance(other).leading_zeros()
+    }
+
+    // The next id in keyspace order, wrapping from MAX_ID to MIN_ID.
+    pub fn add_one(&self) -> Id {
+        let mut bytes = self.0;
+        for b in bytes.iter_mut().rev() {
+            let (v, carry) = b.overflowing_add(1);
+            *b = v;
+            if !carry {
+                break;
+            }
+        }
+        Id(bytes)
+    }
+
+    // The previous id in keyspace order, wrapping from MIN_ID to MAX_ID.
+    pub fn sub_one(&self) -> Id {
+        let mut bytes = self.0;
+        for b in bytes.iter_mut().rev() {
+            let (v, borrow) = b.overflowing_sub(1);
+            *b = v;
+            if !borrow {
+                break;
+            }
+        }
+        Id(bytes)
+    }
+
     pub(crate) fn bits_equal(a: &Id, b: &Id, depth: i32) -> bool {
         if depth == -1 {
             return true;
@@ -1,17 +1,32 @@
 use std::hash::{Hash, Hasher};
 use super::Network;
 
+// Where a lookup result was taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultSource {
+    // A fresh entry of the local routing table.
+    Cache,
+    // The hinted node answered a ping.
+    Hint,
+    // A network lookup.
+    Lookup,
+}
+
 #[derive(Debug, Clone)]
 pub struct JointResult<T> {
     v4: Option<T>,
     v6: Option<T>,
+    src4: Option<ResultSource>,
+    src6: Option<ResultSource>,
 }
 
 impl<T> JointResult<T> {
     pub(crate) fn new() -> Self {
         Self {
             v4: None,
-            v6: None
+            v6: None,
+            src4: None,
+            src6: None,
         }
     }
 
@@ -42,12 +57,28 @@ impl<T> JointResult<T> {
         self.v4.is_some() || self.v6.is_some()
     }
 
+    // Where the value of the network came from, None if it was not recorded.
+    pub fn source(&self, network: Network) -> Option<ResultSource> {
+        match network {
+            Network::IPv4 => self.src4,
+            Network::IPv6 => self.src6,
+        }
+    }
+
     pub(crate) fn set_value(&mut self, network: Network, value: T) {
         match network {
             Network::IPv4 => self.v4 = Some(value),
             Network::IPv6 => self.v6 = Some(value)
         }
     }
+
+    pub(crate) fn set_value_from(&mut self, network: Network, value: T, source: ResultSource) {
+        match network {
+            Network::IPv4 => self.src4 = Some(source),
+            Network::IPv6 => self.src6 = Some(source),
+        }
+        self.set_value(network, value);
+    }
 }
 
 impl Hash for JointResult<()> {
@@ -1,7 +1,10 @@
 pub(crate) mod logger;
 pub(crate) mod version;
+pub(crate) mod crypto;
+pub(crate) mod compact;
 
 pub mod config;
+pub mod clock;
 pub mod id;
 pub mod joint_result;
 pub mod network;
@@ -9,10 +12,16 @@ pub mod identity;
 pub mod crypto_context;
 pub mod signature;
 pub mod cryptobox;
+pub mod capabilities;
 pub mod node_info;
 pub mod peer_info;
+pub mod endpoint;
 pub mod value;
+pub mod name_record;
+pub mod document;
 pub mod errors;
+#[cfg(feature = "multiaddr")]
+pub mod multiaddr;
 
 pub use crate::core::{
     id::{Id, DID_PREFIX},
@@ -24,7 +33,8 @@ pub use crate::core::{
     signature::Signature,
     cryptobox::CryptoBox,
 
-    joint_result::JointResult,
+    joint_result::{JointResult, ResultSource},
+    clock::{Clock, SystemClock, ManualClock},
     network::Network,
     config::{
         Config,
@@ -33,23 +43,38 @@ pub use crate::core::{
         ActiveProxyConfig,
         MessagingConfig,
     },
+    capabilities::Capabilities,
     node_info::NodeInfo,
     peer_info::{PeerInfo, PeerBuilder},
+    endpoint::EndpointPolicy,
     value::{Value, ImmutableBuilder, SignedBuilder, EncryptedBuilder},
+    name_record::{NameRecord, NameClaim, NAME_NAMESPACE},
+    document::Document,
 };
 
 #[cfg(test)]
 mod unitests {
     mod test_id;
     mod test_logger;
+    mod test_clock;
     mod test_version;
     mod test_value;
+    mod test_name_record;
+    mod test_capabilities;
     mod test_node_info;
     mod test_peer_info;
+    mod test_endpoint;
     mod test_crypto_identity;
     mod test_crypto_context;
+    #[cfg(feature = "dht")]
+    mod test_sealed_header;
+    #[cfg(feature = "multiaddr")]
+    mod test_multiaddr;
+    #[cfg(all(feature = "sodium", feature = "core-crypto"))]
+    mod test_crypto_provider;
 }
 
+#[cfg(feature = "sodium")]
 #[macro_export]
 macro_rules! as_uchar_ptr {
     ($val:expr) => {{
@@ -57,6 +82,7 @@ macro_rules! as_uchar_ptr {
     }};
 }
 
+#[cfg(feature = "sodium")]
 #[macro_export]
 macro_rules! as_uchar_ptr_mut {
     ($val:expr) => {{
@@ -0,0 +1,509 @@
+use std::{
+    fmt,
+    str::FromStr,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+};
+use url::{Host, Url};
+
+use crate::{
+    Id,
+    NodeInfo,
+    PeerInfo,
+    Result,
+    errors::{
+        ArgumentError,
+        MalformedError,
+        NetworkError,
+        UnsupportedProtocolError,
+    },
+};
+
+// The multicodec codes of the protocols boson addresses are made of.
+const IP4:          u32 = 0x04;
+const TCP:          u32 = 0x06;
+const IP6:          u32 = 0x29;
+const DNS:          u32 = 0x35;
+const DNS4:         u32 = 0x36;
+const DNS6:         u32 = 0x37;
+const UDP:          u32 = 0x0111;
+const HTTPS:        u32 = 0x01bb;
+const TLS:          u32 = 0x01c0;
+const WS:           u32 = 0x01dd;
+const WSS:          u32 = 0x01de;
+const HTTP:         u32 = 0x01e0;
+const HTTP_PATH:    u32 = 0x01e1;
+
+/// The code of the `/boson/<base58 id>` component, taken from the private
+/// use range of the multicodec table.
+pub const BOSON: u32 = 0x30_b050;
+
+/// A component of a multiaddr, limited to the protocols boson nodes and
+/// peers are reached with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Ip4(Ipv4Addr),
+    Ip6(Ipv6Addr),
+    Dns(String),
+    Dns4(String),
+    Dns6(String),
+    Tcp(u16),
+    Udp(u16),
+    Tls,
+    Http,
+    Https,
+    Ws,
+    Wss,
+    /// The path of an http address, percent-encoded in the text form.
+    HttpPath(String),
+    Boson(Id),
+}
+
+impl Protocol {
+    pub fn code(&self) -> u32 {
+        match self {
+            Protocol::Ip4(_)        => IP4,
+            Protocol::Ip6(_)        => IP6,
+            Protocol::Dns(_)        => DNS,
+            Protocol::Dns4(_)       => DNS4,
+            Protocol::Dns6(_)       => DNS6,
+            Protocol::Tcp(_)        => TCP,
+            Protocol::Udp(_)        => UDP,
+            Protocol::Tls           => TLS,
+            Protocol::Http          => HTTP,
+            Protocol::Https         => HTTPS,
+            Protocol::Ws            => WS,
+            Protocol::Wss           => WSS,
+            Protocol::HttpPath(_)   => HTTP_PATH,
+            Protocol::Boson(_)      => BOSON,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Ip4(_)        => "ip4",
+            Protocol::Ip6(_)        => "ip6",
+            Protocol::Dns(_)        => "dns",
+            Protocol::Dns4(_)       => "dns4",
+            Protocol::Dns6(_)       => "dns6",
+            Protocol::Tcp(_)        => "tcp",
+            Protocol::Udp(_)        => "udp",
+            Protocol::Tls           => "tls",
+            Protocol::Http          => "http",
+            Protocol::Https         => "https",
+            Protocol::Ws            => "ws",
+            Protocol::Wss           => "wss",
+            Protocol::HttpPath(_)   => "http-path",
+            Protocol::Boson(_)      => "boson",
+        }
+    }
+
+    // Parses the component named `name`, taking its value from `parts`.
+    fn parse<'a>(name: &str, parts: &mut impl Iterator<Item = &'a str>) -> Result<Self> {
+        let mut value = || parts.next()
+            .filter(|v| !v.is_empty())
+            .ok_or_else(|| MalformedError::new(format!("Missing the value of /{name}")));
+
+        let port = |v: &str| v.parse::<u16>()
+            .map_err(|_| MalformedError::new(format!("Invalid port {v}")));
+
+        let protocol = match name {
+            "ip4"   => Protocol::Ip4(value()?.parse().map_err(|_| MalformedError::new("Invalid ip4 address"))?),
+            "ip6"   => Protocol::Ip6(value()?.parse().map_err(|_| MalformedError::new("Invalid ip6 address"))?),
+            "dns"   => Protocol::Dns(value()?.to_string()),
+            "dns4"  => Protocol::Dns4(value()?.to_string()),
+            "dns6"  => Protocol::Dns6(value()?.to_string()),
+            "tcp"   => Protocol::Tcp(port(value()?)?),
+            "udp"   => Protocol::Udp(port(value()?)?),
+            "tls"   => Protocol::Tls,
+            "http"  => Protocol::Http,
+            "https" => Protocol::Https,
+            "ws"    => Protocol::Ws,
+            "wss"   => Protocol::Wss,
+            "http-path" => Protocol::HttpPath(percent_decode(value()?)?),
+            "boson" => Protocol::Boson(Id::try_from_base58(value()?)?),
+            _ => return Err(UnsupportedProtocolError::new(name, format!("Unsupported multiaddr protocol /{name}"))),
+        };
+        Ok(protocol)
+    }
+
+    fn write_bytes(&self, output: &mut Vec<u8>) {
+        write_varint(output, self.code() as u64);
+        match self {
+            Protocol::Ip4(ip) => output.extend_from_slice(&ip.octets()),
+            Protocol::Ip6(ip) => output.extend_from_slice(&ip.octets()),
+            Protocol::Tcp(port) | Protocol::Udp(port) => output.extend_from_slice(&port.to_be_bytes()),
+            Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) | Protocol::HttpPath(name) => {
+                write_varint(output, name.len() as u64);
+                output.extend_from_slice(name.as_bytes());
+            },
+            Protocol::Boson(id) => {
+                write_varint(output, Id::BYTES as u64);
+                output.extend_from_slice(id.as_bytes());
+            },
+            Protocol::Tls | Protocol::Http | Protocol::Https | Protocol::Ws | Protocol::Wss => {},
+        }
+    }
+
+    fn read_bytes(input: &mut &[u8]) -> Result<Self> {
+        let code = read_varint(input)?;
+        let protocol = match u32::try_from(code).unwrap_or(u32::MAX) {
+            IP4 => Protocol::Ip4(<[u8; 4]>::try_from(take(input, 4)?).unwrap().into()),
+            IP6 => Protocol::Ip6(<[u8; 16]>::try_from(take(input, 16)?).unwrap().into()),
+            TCP => Protocol::Tcp(u16::from_be_bytes(take(input, 2)?.try_into().unwrap())),
+            UDP => Protocol::Udp(u16::from_be_bytes(take(input, 2)?.try_into().unwrap())),
+            DNS => Protocol::Dns(read_string(input)?),
+            DNS4 => Protocol::Dns4(read_string(input)?),
+            DNS6 => Protocol::Dns6(read_string(input)?),
+            HTTP_PATH => Protocol::HttpPath(read_string(input)?),
+            TLS => Protocol::Tls,
+            HTTP => Protocol::Http,
+            HTTPS => Protocol::Https,
+            WS => Protocol::Ws,
+            WSS => Protocol::Wss,
+            BOSON => {
+                let len = read_varint(input)? as usize;
+                Protocol::Boson(Id::try_from_bytes(take(input, len)?)?)
+            },
+            _ => return Err(UnsupportedProtocolError::new(
+                format!("0x{code:x}"),
+                format!("Unsupported multiaddr protocol code 0x{code:x}")
+            )),
+        };
+        Ok(protocol)
+    }
+}
+
+impl fmt::Display for Protocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "/{}", self.name())?;
+        match self {
+            Protocol::Ip4(ip) => write!(f, "/{ip}"),
+            Protocol::Ip6(ip) => write!(f, "/{ip}"),
+            Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) => write!(f, "/{name}"),
+            Protocol::Tcp(port) | Protocol::Udp(port) => write!(f, "/{port}"),
+            Protocol::HttpPath(path) => write!(f, "/{}", percent_encode(path)),
+            Protocol::Boson(id) => write!(f, "/{}", id.to_base58()),
+            Protocol::Tls | Protocol::Http | Protocol::Https | Protocol::Ws | Protocol::Wss => Ok(()),
+        }
+    }
+}
+
+/// A self-describing network address, such as
+/// `/ip4/203.0.113.7/udp/39001/boson/<base58 id>`, in the text or the
+/// binary form other multiaddr implementations read.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Multiaddr(Vec<Protocol>);
+
+impl Multiaddr {
+    pub fn empty() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn with(mut self, protocol: Protocol) -> Self {
+        self.0.push(protocol);
+        self
+    }
+
+    pub fn push(&mut self, protocol: Protocol) {
+        self.0.push(protocol);
+    }
+
+    pub fn protocols(&self) -> &[Protocol] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(64);
+        self.0.iter().for_each(|p| p.write_bytes(&mut output));
+        output
+    }
+
+    pub fn try_from_bytes(mut input: &[u8]) -> Result<Self> {
+        let mut protocols = Vec::new();
+        while !input.is_empty() {
+            protocols.push(Protocol::read_bytes(&mut input)?);
+        }
+        Ok(Self(protocols))
+    }
+}
+
+impl FromStr for Multiaddr {
+    type Err = crate::Error;
+
+    fn from_str(input: &str) -> Result<Self> {
+        let Some(body) = input.strip_prefix('/') else {
+            return Err(MalformedError::new(format!("Multiaddr {input} does not start with /")));
+        };
+
+        let body = body.strip_suffix('/').unwrap_or(body);
+        let mut parts = body.split('/');
+        let mut protocols = Vec::new();
+        while let Some(name) = parts.next() {
+            if name.is_empty() {
+                return Err(MalformedError::new(format!("Empty protocol in multiaddr {input}")));
+            }
+            protocols.push(Protocol::parse(name, &mut parts)?);
+        }
+        Ok(Self(protocols))
+    }
+}
+
+impl fmt::Display for Multiaddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|p| write!(f, "{p}"))
+    }
+}
+
+impl NodeInfo {
+    /// The node as `/ip4/<ip>/udp/<port>/boson/<id>`, `/ip6/` for an IPv6
+    /// node. The version is not carried.
+    pub fn to_multiaddr(&self) -> Multiaddr {
+        let host = match self.ip() {
+            IpAddr::V4(ip) => Protocol::Ip4(ip),
+            IpAddr::V6(ip) => Protocol::Ip6(ip),
+        };
+        Multiaddr(vec![host, Protocol::Udp(self.port()), Protocol::Boson(*self.id())])
+    }
+
+    /// The node a multiaddr made by [`NodeInfo::to_multiaddr`] stands for.
+    /// A DNS name in place of the ip is only taken with `resolve` set, the
+    /// first address it resolves to is used then.
+    pub fn try_from_multiaddr(addr: &Multiaddr, resolve: bool) -> Result<Self> {
+        let [host, transport, Protocol::Boson(id)] = addr.protocols() else {
+            let unsupported = |p: &Protocol| !matches!(p,
+                Protocol::Ip4(_) | Protocol::Ip6(_) | Protocol::Dns(_) | Protocol::Dns4(_) |
+                Protocol::Dns6(_) | Protocol::Udp(_) | Protocol::Boson(_)
+            );
+            return Err(unsupported_in(addr, unsupported).unwrap_or_else(|| MalformedError::new(format!(
+                "Multiaddr {addr} is not of a node, /<ip>/<address>/udp/<port>/boson/<id> expected"
+            ))));
+        };
+
+        let Protocol::Udp(port) = transport else {
+            return Err(UnsupportedProtocolError::new(transport.name(), format!(
+                "Boson nodes are reached over udp, not /{}", transport.name()
+            )));
+        };
+
+        let ip = match host {
+            Protocol::Ip4(ip) => IpAddr::V4(*ip),
+            Protocol::Ip6(ip) => IpAddr::V6(*ip),
+            Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) if !resolve => {
+                return Err(UnsupportedProtocolError::new(host.name(), format!(
+                    "The name {name} has to be resolved for a node address"
+                )));
+            },
+            Protocol::Dns(name)  => resolve_name(name, *port, |_| true)?,
+            Protocol::Dns4(name) => resolve_name(name, *port, IpAddr::is_ipv4)?,
+            Protocol::Dns6(name) => resolve_name(name, *port, IpAddr::is_ipv6)?,
+            other => {
+                return Err(MalformedError::new(format!("Expected an ip or dns address, not /{}", other.name())));
+            },
+        };
+        Ok(NodeInfo::new(*id, SocketAddr::new(ip, *port)))
+    }
+}
+
+impl PeerInfo {
+    /// The endpoint of the peer as a multiaddr followed by
+    /// `/boson/<peer id>`: the host and `/tcp/<port>`, then `/tls` for ssl,
+    /// https and wss endpoints, `/http` or `/ws` for web ones, and the path
+    /// as a trailing `/http-path` when there is one. Endpoints with a query
+    /// or a fragment have no lossless form and give an error.
+    pub fn to_multiaddr(&self) -> Result<Multiaddr> {
+        let mut addr = endpoint_to_multiaddr(self.endpoint())?;
+        addr.push(Protocol::Boson(*self.id()));
+        Ok(addr)
+    }
+
+    /// The peer id and the endpoint a multiaddr made by
+    /// [`PeerInfo::to_multiaddr`] carries. The signed peer itself is looked
+    /// up by the id.
+    pub fn parse_multiaddr(addr: &Multiaddr) -> Result<(Id, String)> {
+        let [endpoint @ .., Protocol::Boson(id)] = addr.protocols() else {
+            return Err(MalformedError::new(format!("Multiaddr {addr} does not end with /boson/<id>")));
+        };
+        Ok((*id, multiaddr_to_endpoint(endpoint)?))
+    }
+}
+
+fn endpoint_to_multiaddr(endpoint: &str) -> Result<Multiaddr> {
+    let url = Url::parse(endpoint).map_err(|e| ArgumentError::new(format!("Invalid endpoint {endpoint}: {e}")))?;
+    if url.query().is_some() || url.fragment().is_some() || !url.username().is_empty() {
+        return Err(ArgumentError::new(format!("Endpoint {endpoint} has no multiaddr form")));
+    }
+
+    let host = match url.host() {
+        Some(Host::Ipv4(ip)) => Protocol::Ip4(ip),
+        Some(Host::Ipv6(ip)) => Protocol::Ip6(ip),
+        // Hosts of tcp and ssl URLs stay opaque, ip4 addresses among them.
+        Some(Host::Domain(name)) => match name.parse::<Ipv4Addr>() {
+            Ok(ip) => Protocol::Ip4(ip),
+            Err(_) if !name.is_empty() => Protocol::Dns(name.to_string()),
+            Err(_) => return Err(ArgumentError::new(format!("Endpoint {endpoint} has no host"))),
+        },
+        _ => return Err(ArgumentError::new(format!("Endpoint {endpoint} has no host"))),
+    };
+    let Some(port) = url.port_or_known_default() else {
+        return Err(ArgumentError::new(format!("Endpoint {endpoint} has no port")));
+    };
+
+    let mut addr = Multiaddr(vec![host, Protocol::Tcp(port)]);
+    match url.scheme() {
+        "tcp"   => {},
+        "ssl"   => addr.push(Protocol::Tls),
+        "http"  => addr.push(Protocol::Http),
+        "https" => addr.0.extend([Protocol::Tls, Protocol::Http]),
+        "ws"    => addr.push(Protocol::Ws),
+        "wss"   => addr.0.extend([Protocol::Tls, Protocol::Ws]),
+        scheme  => return Err(UnsupportedProtocolError::new(scheme, format!(
+            "Endpoint scheme {scheme} has no multiaddr form"
+        ))),
+    }
+
+    // The path as the endpoint is normalized, without a bare root.
+    let path = url.path();
+    if !path.is_empty() && path != "/" {
+        if matches!(url.scheme(), "tcp" | "ssl") {
+            return Err(ArgumentError::new(format!("Endpoint {endpoint} has a path")));
+        }
+        addr.push(Protocol::HttpPath(path.to_string()));
+    }
+    Ok(addr)
+}
+
+fn multiaddr_to_endpoint(protocols: &[Protocol]) -> Result<String> {
+    let (host, port, rest) = match protocols {
+        [host, Protocol::Tcp(port), rest @ ..] => (host, *port, rest),
+        _ => {
+            let addr = Multiaddr(protocols.to_vec());
+            return Err(unsupported_in(&addr, |p| matches!(p, Protocol::Udp(_))).unwrap_or_else(|| MalformedError::new(format!(
+                "Multiaddr {addr} has no endpoint form, /<host>/tcp/<port> expected"
+            ))));
+        },
+    };
+
+    let (scheme, path) = match rest {
+        [] => ("tcp", None),
+        [Protocol::Tls] => ("ssl", None),
+        [Protocol::Http, path @ ..] => ("http", Some(path)),
+        [Protocol::Tls, Protocol::Http, path @ ..] => ("https", Some(path)),
+        [Protocol::Ws, path @ ..] => ("ws", Some(path)),
+        [Protocol::Tls, Protocol::Ws, path @ ..] => ("wss", Some(path)),
+        _ => {
+            let addr = Multiaddr(protocols.to_vec());
+            return Err(unsupported_in(&addr, |p| matches!(p, Protocol::Https | Protocol::Wss)).unwrap_or_else(|| MalformedError::new(format!(
+                "Multiaddr {addr} has no endpoint form"
+            ))));
+        },
+    };
+
+    let host = match host {
+        Protocol::Ip4(ip) => ip.to_string(),
+        Protocol::Ip6(ip) => format!("[{ip}]"),
+        Protocol::Dns(name) | Protocol::Dns4(name) | Protocol::Dns6(name) => name.clone(),
+        other => return Err(MalformedError::new(format!("Expected an ip or dns address, not /{}", other.name()))),
+    };
+
+    let path = match path {
+        None | Some([]) => "",
+        Some([Protocol::HttpPath(path)]) if path.starts_with('/') => path.as_str(),
+        Some(_) => return Err(MalformedError::new(format!("Expected a single /http-path after /{scheme}"))),
+    };
+
+    // Normalized as endpoints are, without the default port.
+    let mut endpoint = format!("{scheme}://{host}:{port}{path}");
+    if let Ok(url) = Url::parse(&endpoint) {
+        if url.port().is_none() {
+            endpoint = format!("{scheme}://{host}{path}");
+        }
+    }
+    Ok(endpoint)
+}
+
+// The first protocol of the address boson has no use for in its place.
+fn unsupported_in(addr: &Multiaddr, unsupported: fn(&Protocol) -> bool) -> Option<crate::Error> {
+    addr.protocols().iter()
+        .find(|p| unsupported(p))
+        .map(|p| UnsupportedProtocolError::new(p.name(), format!("Unsupported /{} in {addr}", p.name())) as crate::Error)
+}
+
+fn resolve_name(name: &str, port: u16, family: fn(&IpAddr) -> bool) -> Result<IpAddr> {
+    (name, port).to_socket_addrs()
+        .map_err(|e| NetworkError::new(format!("Resolving {name} failed: {e}")))?
+        .map(|addr| addr.ip())
+        .find(family)
+        .ok_or_else(|| NetworkError::new(format!("No usable address for {name}")) as crate::Error)
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_varint(input: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..63).step_by(7) {
+        let [byte, rest @ ..] = *input else {
+            return Err(MalformedError::new("Truncated multiaddr"));
+        };
+        *input = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(MalformedError::new("Varint too long in multiaddr"))
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if input.len() < len {
+        return Err(MalformedError::new("Truncated multiaddr"));
+    }
+    let (value, rest) = input.split_at(len);
+    *input = rest;
+    Ok(value)
+}
+
+fn read_string(input: &mut &[u8]) -> Result<String> {
+    let len = read_varint(input)? as usize;
+    let value = take(input, len)?;
+    Ok(String::from_utf8(value.to_vec()).map_err(|_| MalformedError::new("Invalid utf8 in multiaddr"))?)
+}
+
+fn percent_encode(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for b in input.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => output.push(b as char),
+            _ => output.push_str(&format!("%{b:02X}")),
+        }
+    }
+    output
+}
+
+fn percent_decode(input: &str) -> Result<String> {
+    let bytes = input.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'%' {
+            output.push(bytes[i]);
+            i += 1;
+            continue;
+        }
+        let byte = input.get(i + 1..i + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| MalformedError::new(format!("Invalid percent-encoding in {input}")))?;
+        output.push(byte);
+        i += 3;
+    }
+    Ok(String::from_utf8(output).map_err(|_| MalformedError::new(format!("Invalid utf8 in {input}")))?)
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+use unicode_normalization::UnicodeNormalization;
+
+use super::{
+    Id,
+    Value,
+    SignedBuilder,
+    signature,
+    signature::KeyPair,
+    Error,
+    Result,
+    errors::ArgumentError,
+};
+
+/// The namespace the names are keys of. The claims on a name are kept in
+/// the value of the key, see [`name_id`].
+pub const NAME_NAMESPACE: Id = Id::from_bytes(*b"boson:name:namespace:v1\0\0\0\0\0\0\0\0\0");
+
+// Prefixes the signed digest of a name record.
+const NAME_RECORD_TAG: &[u8] = b"boson-name-record";
+
+/// Normalizes a name the way it is claimed and resolved: NFKC, lower case,
+/// with the whitespace trimmed and each run of it turned into one space.
+/// Names differing only in case, width or spacing are thus the same name.
+/// Fails for empty names, control characters, or names longer than
+/// [`NameRecord::MAX_NAME_BYTES`] once normalized.
+pub fn normalize_name(name: &str) -> Result<String> {
+    let folded = name.nfkc().collect::<String>().to_lowercase();
+    let normalized = folded.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .nfc()
+        .collect::<String>();
+
+    if normalized.is_empty() {
+        return Err(ArgumentError::new("Name cannot be empty"));
+    }
+    if normalized.chars().any(char::is_control) {
+        return Err(ArgumentError::new("Name cannot contain control characters"));
+    }
+    if normalized.len() > NameRecord::MAX_NAME_BYTES {
+        return Err(ArgumentError::new(format!(
+            "Name is {} bytes long, more than {}", normalized.len(), NameRecord::MAX_NAME_BYTES
+        )));
+    }
+    Ok(normalized)
+}
+
+/// The id of the value holding the claims on a name: the value of the
+/// normalized name as a key of [`NAME_NAMESPACE`], signed with the key pair
+/// seeded with [`Id::derive`] of them, see [`Value::id_for_key`].
+pub fn name_id(name: &str) -> Result<Id> {
+    Ok(Value::id_for_key(&NAME_NAMESPACE, &normalize_name(name)?))
+}
+
+/// A claim of its owner binding a name to a target id, signed by the owner.
+///
+/// Anyone may claim any name, so a name may have several claims. The value
+/// holding them is writable by whoever knows the name, but the nodes storing
+/// it merge the claims of each update into those they hold, see
+/// [`merge_claims`]: claims can neither be forged nor dropped by others, they
+/// go away once expired. Which claim to trust is left to the application.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NameRecord {
+    #[serde(rename = "n")]
+    name    : String,
+    #[serde(rename = "o")]
+    owner   : Id,
+    #[serde(rename = "t")]
+    target  : Id,
+    #[serde(rename = "s")]
+    seq     : i32,
+    #[serde(rename = "e")]
+    expires : u64,
+    #[serde(rename = "sig")]
+    #[serde_as(as = "Bytes")]
+    sig     : Vec<u8>,
+}
+
+impl NameRecord {
+    pub const MAX_NAME_BYTES: usize = 64;
+    /// Claims held in the value of a name at most.
+    pub const MAX_CLAIMS: usize = 16;
+    pub const DEFAULT_LIFETIME: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+    /// Signs the claim of the key pair on the normalized name, valid until
+    /// `expires` in milliseconds since the Unix epoch.
+    pub fn new(name: &str, target: &Id, seq: i32, expires: u64, keypair: &KeyPair) -> Result<Self> {
+        if seq < 0 {
+            return Err(ArgumentError::new(format!("Invalid sequence number: {seq}")));
+        }
+        let mut record = Self {
+            name    : normalize_name(name)?,
+            owner   : Id::from(keypair.public_key()),
+            target  : *target,
+            seq,
+            expires,
+            sig     : Vec::new(),
+        };
+        record.sig = signature::sign_into(record.digest().as_slice(), keypair.private_key())?;
+        Ok(record)
+    }
+
+    /// The normalized name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn owner(&self) -> &Id {
+        &self.owner
+    }
+
+    pub fn target(&self) -> &Id {
+        &self.target
+    }
+
+    /// Bumped by the owner each time the name is claimed again.
+    pub fn sequence_number(&self) -> i32 {
+        self.seq
+    }
+
+    /// Milliseconds since the Unix epoch the claim is valid until.
+    pub fn expires(&self) -> u64 {
+        self.expires
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.sig
+    }
+
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires <= now_ms
+    }
+
+    /// Whether the name is normalized and the record signed by its owner.
+    pub fn is_valid(&self) -> bool {
+        if self.sig.len() != signature::Signature::BYTES || self.seq < 0 {
+            return false;
+        }
+        if normalize_name(&self.name).ok().as_deref() != Some(self.name.as_str()) {
+            return false;
+        }
+        signature::verify(
+            self.digest().as_slice(),
+            self.sig.as_slice(),
+            &self.owner.to_signature_key()
+        ).unwrap_or(false)
+    }
+
+    fn digest(&self) -> Vec<u8> {
+        let mut sha = Sha256::new();
+        sha.update(NAME_RECORD_TAG);
+        sha.update((self.name.len() as u8).to_be_bytes());
+        sha.update(self.name.as_bytes());
+        sha.update(self.owner.as_bytes());
+        sha.update(self.target.as_bytes());
+        sha.update(self.seq.to_be_bytes());
+        sha.update(self.expires.to_be_bytes());
+        sha.finalize().to_vec()
+    }
+}
+
+/// A claim on a name as resolved by
+/// [`Node::resolve_name`](crate::dht::Node::resolve_name): signed by its
+/// owner and unexpired at the time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameClaim {
+    record: NameRecord,
+}
+
+impl NameClaim {
+    pub fn owner(&self) -> &Id {
+        self.record.owner()
+    }
+
+    pub fn target(&self) -> &Id {
+        self.record.target()
+    }
+
+    pub fn sequence_number(&self) -> i32 {
+        self.record.sequence_number()
+    }
+
+    pub fn expires(&self) -> u64 {
+        self.record.expires()
+    }
+
+    pub fn record(&self) -> &NameRecord {
+        &self.record
+    }
+}
+
+impl From<NameClaim> for NameRecord {
+    fn from(claim: NameClaim) -> Self {
+        claim.record
+    }
+}
+
+// The claims of the value of a name, in the order they were first made.
+pub(crate) fn encode_claims(claims: &[NameRecord]) -> Result<Vec<u8>> {
+    serde_cbor::to_vec(&claims).map_err(|e| ArgumentError::new(format!(
+        "Encoding the name claims failed: {e}"
+    )) as Error)
+}
+
+// The valid claims on the normalized name held by the value data, unexpired
+// at `now_ms`, one per owner. Data not holding claims has none.
+pub(crate) fn decode_claims(name: &str, data: &[u8], now_ms: u64) -> Vec<NameRecord> {
+    let records = serde_cbor::from_slice::<Vec<NameRecord>>(data).unwrap_or_default();
+    let mut claims: Vec<NameRecord> = Vec::new();
+    for record in records {
+        if record.name != name || record.is_expired(now_ms) || !record.is_valid() {
+            continue;
+        }
+        if claims.iter().all(|c| c.owner != record.owner) {
+            claims.push(record);
+        }
+    }
+    claims
+}
+
+pub(crate) fn resolve_claims(name: &str, data: &[u8], now_ms: u64) -> Vec<NameClaim> {
+    decode_claims(name, data, now_ms).into_iter()
+        .map(|record| NameClaim { record })
+        .collect()
+}
+
+// The value of a name as a node holding `existing` stores it once `value`
+// is stored there: the claims of both, the newest of each owner, the ones
+// held first kept in place and the expired ones dropped, signed again for
+// the name. Whoever updates the value can thus add claims but drop none of
+// the others, and concurrent updates don't lose each other's claims. None
+// for the values of anything but a name with unexpired claims.
+pub(crate) fn merge_claims(existing: Option<&Value>, value: &Value, now_ms: u64) -> Option<Value> {
+    let id = value.id();
+    let name = [existing, Some(value)].into_iter()
+        .flatten()
+        .flat_map(|v| serde_cbor::from_slice::<Vec<NameRecord>>(v.data()).unwrap_or_default())
+        .map(|record| record.name)
+        .find(|name| Value::id_for_key(&NAME_NAMESPACE, name) == id)?;
+
+    let mut claims = existing
+        .map(|v| decode_claims(&name, v.data(), now_ms))
+        .unwrap_or_default();
+    for record in decode_claims(&name, value.data(), now_ms) {
+        match claims.iter().position(|c| c.owner == record.owner) {
+            Some(i) if claims[i].seq < record.seq => claims[i] = record,
+            Some(_) => {},
+            None if claims.len() < NameRecord::MAX_CLAIMS => claims.push(record),
+            None => {},
+        }
+    }
+    if claims.is_empty() {
+        return None;
+    }
+
+    let data = encode_claims(&claims).ok()?;
+    let seq = existing.map_or(0, |v| v.sequence_number()).max(value.sequence_number());
+    let mut builder = SignedBuilder::world_writable(&NAME_NAMESPACE, &name, &data);
+    builder.with_sequence_number(seq);
+    if let Some(nonce) = value.nonce() {
+        builder.with_nonce(nonce);
+    }
+    builder.build().ok()
+}
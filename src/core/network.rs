@@ -1,7 +1,7 @@
 use std::fmt;
 use std::net::SocketAddr;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Network {
     IPv4 = 4,
@@ -9,6 +9,13 @@ pub enum Network {
 }
 
 impl Network {
+    pub fn of(addr: &SocketAddr) -> Self {
+        match addr.is_ipv4() {
+            true  => Network::IPv4,
+            false => Network::IPv6,
+        }
+    }
+
     pub fn is_ipv4(&self) -> bool {
         self == &Network::IPv4
     }
@@ -27,10 +34,7 @@ impl Network {
 
 impl From<&SocketAddr> for Network {
     fn from(input: &SocketAddr) -> Self {
-        match input.is_ipv4() {
-            true  => Network::IPv4,
-            false => Network::IPv6,
-        }
+        Network::of(input)
     }
 }
 
@@ -21,6 +21,7 @@ use super::{
     Id,
     version,
     Network,
+    Capabilities,
 };
 
 #[derive(Debug, Clone)]
@@ -28,17 +29,22 @@ pub struct NodeInfo {
     id: Id,
     addr: SocketAddr,
     ver: i32,
+    caps: Option<Capabilities>,
 }
 
 impl NodeInfo {
     pub fn new(id: Id, addr: SocketAddr) -> Self {
-        Self {id, addr, ver: 0}
+        Self {id, addr, ver: 0, caps: None}
     }
 
     pub fn set_version(&mut self, ver: i32) {
         self.ver = ver;
     }
 
+    pub fn set_capabilities(&mut self, caps: Capabilities) {
+        self.caps = Some(caps);
+    }
+
     pub const fn ip(&self) -> IpAddr {
         self.addr.ip()
     }
@@ -67,8 +73,23 @@ impl NodeInfo {
         self.ver
     }
 
+    // What the node advertised it offers, a full node when it did not.
+    pub fn capabilities(&self) -> Capabilities {
+        self.caps.unwrap_or(Capabilities::FULL_NODE)
+    }
+
+    pub(crate) const fn advertised_capabilities(&self) -> Option<Capabilities> {
+        self.caps
+    }
+
+    // The software and version the node runs, such as "MK/4".
+    pub fn normalized_version(&self) -> String {
+        version::normalized_version(self.ver)
+    }
+
+    #[deprecated(note = "use normalized_version")]
     pub fn format_version(&self) -> String {
-        version::format_version(self.ver)
+        self.normalized_version()
     }
 
     pub fn is_ipv4(&self) -> bool {
@@ -170,7 +191,8 @@ impl<'de> Deserialize<'de> for NodeInfo {
                 Ok(NodeInfo {
                     id,
                     addr: SocketAddr::new(ip, port),
-                    ver: 0
+                    ver: 0,
+                    caps: None,
                 })
             }
         }
@@ -2,6 +2,7 @@ use std::fmt;
 use std::result::Result as SResult;
 use std::hash::{Hash, Hasher};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use serde::{
     Serialize, Deserialize, Serializer, Deserializer,
     ser::SerializeTuple,
@@ -15,9 +16,12 @@ use super::{
     Id,
     Identity,
     signature,
+    Error,
     Result,
-    errors::StateError,
+    Network,
+    errors::{ArgumentError, StateError},
     signature::{KeyPair, PrivateKey},
+    endpoint::normalize_endpoint,
 };
 
 pub struct PeerBuilder {
@@ -28,6 +32,9 @@ pub struct PeerBuilder {
     fingerprint: u64,
     endpoint: String,
     extra: Option<Vec<u8>>,
+    tags: Vec<String>,
+    weight: u8,
+    announced: Option<SystemTime>,
 }
 
 impl PeerBuilder {
@@ -40,6 +47,9 @@ impl PeerBuilder {
             fingerprint: 0,
             endpoint: endpoint.nfc().collect::<String>(),
             extra: None,
+            tags: Vec::new(),
+            weight: PeerInfo::DEFAULT_WEIGHT,
+            announced: None,
         }
     }
 
@@ -53,6 +63,27 @@ impl PeerBuilder {
         self
     }
 
+    /// Capability tags of the service, searchable at lookup time with
+    /// [`Node::find_peer_tagged`](crate::dht::Node::find_peer_tagged).
+    /// Their meaning is up to the application, duplicates are dropped.
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        for tag in tags {
+            let tag = tag.nfc().collect::<String>();
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+        self
+    }
+
+    /// The share of the load this instance of the service takes, relative
+    /// to the other peers announced under the same id, as picked by
+    /// [`PeerSelector`](crate::dht::PeerSelector). 0 takes none.
+    pub fn with_weight(mut self, weight: u8) -> Self {
+        self.weight = weight;
+        self
+    }
+
     pub fn with_node(mut self, node: Arc<Mutex<dyn Identity>>) -> Self {
         self.node = Some(node);
         self
@@ -68,6 +99,12 @@ impl PeerBuilder {
         self
     }
 
+    /// The time the announcement is signed at, the current time if unset.
+    pub fn with_announced_time(mut self, time: SystemTime) -> Self {
+        self.announced = Some(time);
+        self
+    }
+
     pub fn with_key(mut self, kp: KeyPair) -> Self {
         self.keypair = Some(kp);
         self
@@ -91,7 +128,7 @@ impl PeerBuilder {
                     nonce.len(), PeerInfo::NONCE_BYTES)));
             }
         }
-
+        PeerInfo::check_tags(&self.tags)?;
 
         PeerInfo::new(
             self.keypair.as_ref(),
@@ -99,12 +136,39 @@ impl PeerBuilder {
             self.nonce.as_ref().map(|v| v.as_slice()),
             self.seq,
             self.fingerprint,
-            self.endpoint,
-            self.extra
+            normalize_endpoint(&self.endpoint)?,
+            PeerFields {
+                extra: self.extra,
+                tags: self.tags,
+                weight: self.weight,
+                announced: Some(crate::as_ms!(self.announced.unwrap_or_else(SystemTime::now)) as u64),
+            }
         )
     }
 }
 
+// The optional signed fields of an announcement, passed by name rather than
+// as a row of look-alike arguments.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PeerFields {
+    pub(crate) extra: Option<Vec<u8>>,
+    pub(crate) tags: Vec<String>,
+    pub(crate) weight: u8,
+    // Milliseconds since the epoch, on the publisher's clock.
+    pub(crate) announced: Option<u64>,
+}
+
+impl Default for PeerFields {
+    fn default() -> Self {
+        Self {
+            extra: None,
+            tags: Vec::new(),
+            weight: PeerInfo::DEFAULT_WEIGHT,
+            announced: None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PeerInfo {
     pk: Id,
@@ -119,10 +183,30 @@ pub struct PeerInfo {
     fingerprint: u64,
     endpoint: String,
     extra: Option<Vec<u8>>,
+    tags: Vec<String>,
+    weight: u8,
+    // Milliseconds since the epoch, on the publisher's clock.
+    announced: Option<u64>,
+    // Where the storage node saw the announcement come from, not signed.
+    observed: Option<String>,
 }
 
+// Prefixes the signed digest of announcements carrying their time, so the
+// time can't be stripped to pass one off as an announcement without it.
+const ANNOUNCED_TAG: &[u8] = b"boson-peer-announced";
+
+// Prefixes the tags in the signed digest, announcements without any sign
+// the same digest as older versions.
+const TAGS_TAG: &[u8] = b"boson-peer-tags";
+
+// Prefixes the weight in the signed digest, left out for the default one.
+const WEIGHT_TAG: &[u8] = b"boson-peer-weight";
+
 impl PeerInfo {
     pub const NONCE_BYTES: usize = 24;
+    pub const MAX_TAGS: usize = 8;
+    pub const MAX_TAG_BYTES: usize = 32;
+    pub const DEFAULT_WEIGHT: u8 = 1;
 
     fn new(
         keypair_opt: Option<&KeyPair>,
@@ -131,7 +215,7 @@ impl PeerInfo {
         seq: i32,
         fingerprint: u64,
         endpoint: String,
-        extra: Option<Vec<u8>>,
+        fields: PeerFields,
     ) -> Result<Self> {
         let kp = match keypair_opt {
             Some(k) => k.clone(),
@@ -146,11 +230,7 @@ impl PeerInfo {
                 }
                 v.to_vec()
             },
-            None => {
-                let mut v = vec![0u8; Self::NONCE_BYTES];
-                rand::fill(&mut v);
-                v
-            }
+            None => crate::random_bytes(Self::NONCE_BYTES)
         };
 
         let mut nodeid: Option<Id> = None;
@@ -180,7 +260,11 @@ impl PeerInfo {
             node_sig,
             fingerprint,
             endpoint,
-            extra,
+            extra: fields.extra,
+            tags: fields.tags,
+            weight: fields.weight,
+            announced: fields.announced,
+            observed: None,
             sig: Vec::new(),
         };
 
@@ -201,7 +285,7 @@ impl PeerInfo {
         sig: Vec<u8>,
         fingerprint: u64,
         endpoint: String,
-        extra: Option<Vec<u8>>,
+        fields: PeerFields,
     ) -> Self {
         Self {
             pk,
@@ -213,10 +297,20 @@ impl PeerInfo {
             sig,
             fingerprint,
             endpoint,
-            extra,
+            extra: fields.extra,
+            tags: fields.tags,
+            weight: fields.weight,
+            announced: fields.announced,
+            observed: None,
         }
     }
 
+    // The endpoint as observed by the node the peer was found on.
+    pub(crate) fn with_observed(mut self, observed: Option<String>) -> Self {
+        self.observed = observed;
+        self
+    }
+
     pub fn id(&self) -> &Id {
         &self.pk
     }
@@ -261,6 +355,28 @@ impl PeerInfo {
         self.endpoint.as_str()
     }
 
+    /// The endpoint the peer is reachable at as seen by the node it was
+    /// found on, when the signed [`endpoint`](Self::endpoint) names an
+    /// unroutable address, such as that of a network behind a NAT. It is not
+    /// covered by the signature of the peer, so it is only as trustworthy as
+    /// that node.
+    pub fn observed_endpoint(&self) -> Option<&str> {
+        self.observed.as_deref()
+    }
+
+    /// The network of the announced endpoint, `None` if its host is a name,
+    /// which may resolve to either.
+    pub fn network(&self) -> Option<Network> {
+        let url = url::Url::parse(&self.endpoint).ok()?;
+        match url.host()? {
+            url::Host::Ipv4(_) => Some(Network::IPv4),
+            url::Host::Ipv6(_) => Some(Network::IPv6),
+            // The host of a tcp or ssl endpoint is opaque to the parser.
+            url::Host::Domain(host) => host.parse::<std::net::Ipv4Addr>().ok()
+                .map(|_| Network::IPv4),
+        }
+    }
+
     pub fn has_extra(&self) -> bool {
         self.extra.as_ref().map(|v| !v.is_empty()).unwrap_or(false)
     }
@@ -269,6 +385,79 @@ impl PeerInfo {
         self.extra.as_deref()
     }
 
+    pub fn tags(&self) -> &[String] {
+        self.tags.as_slice()
+    }
+
+    /// Whether the peer was announced with all the tags, in any order.
+    pub fn has_tags(&self, required: &[&str]) -> bool {
+        required.iter().all(|tag| self.tags.iter().any(|v| v == tag))
+    }
+
+    pub fn weight(&self) -> u8 {
+        self.weight
+    }
+
+    // At most MAX_TAGS of them, none empty or longer than MAX_TAG_BYTES.
+    pub(crate) fn check_tags(tags: &[String]) -> Result<()> {
+        if tags.len() > Self::MAX_TAGS {
+            return Err(StateError::new(format!("Too many tags {}, at most {}",
+                tags.len(), Self::MAX_TAGS)));
+        }
+        if let Some(tag) = tags.iter().find(|v| v.is_empty() || v.len() > Self::MAX_TAG_BYTES) {
+            return Err(StateError::new(format!("Invalid tag '{tag}', must be 1 to {} bytes",
+                Self::MAX_TAG_BYTES)));
+        }
+        Ok(())
+    }
+
+    /// When the publisher signed the announcement, on its own clock. None
+    /// for announcements of older versions, which can be replayed at any
+    /// time.
+    pub fn announced_time(&self) -> Option<SystemTime> {
+        self.announced.map(|v| SystemTime::UNIX_EPOCH + Duration::from_millis(v))
+    }
+
+    pub(crate) fn announced(&self) -> Option<u64> {
+        self.announced
+    }
+
+    /// The version of the announcement, a newer one replaces the older ones
+    /// of the same peer: the sequence number, then the time it was signed
+    /// at, zero for announcements without one. Both are signed.
+    pub fn announcement_version(&self) -> (i32, u64) {
+        (self.seq, self.announced.unwrap_or(0))
+    }
+
+    // The same announcement signed again at `now`, to be re-announced
+    // without being taken for a replay.
+    pub(crate) fn restamped(&self, now: u64) -> Result<Self> {
+        self.signed_at(Some(now))
+    }
+
+    // The same announcement signed without its time, as older versions do.
+    pub(crate) fn untimed(&self) -> Result<Self> {
+        self.signed_at(None)
+    }
+
+    fn signed_at(&self, announced: Option<u64>) -> Result<Self> {
+        let Some(sk) = self.sk.as_ref() else {
+            return Err(StateError::new("Not the owner of the peer info"));
+        };
+        let mut peer = self.clone();
+        peer.announced = announced;
+        peer.sig = signature::sign_into(peer.digest().as_slice(), sk)?;
+        Ok(peer)
+    }
+
+    pub(crate) fn set_private_key(&mut self, sk: PrivateKey) -> Result<()> {
+        if Id::from(KeyPair::from(&sk).public_key()) != self.pk {
+            return Err(StateError::new("Private key does not match the peer id"));
+        }
+        self.sk = Some(sk);
+        Ok(())
+    }
+
     pub fn without_private_key(&self) -> Self {
         if self.sk.is_none() {
             return self.clone();
@@ -290,7 +479,7 @@ impl PeerInfo {
             return Err(StateError::new("Invalid endpoint"));
         }
 
-        let endpoint_nfc = endpoint.nfc().collect::<String>();
+        let endpoint_nfc = normalize_endpoint(&endpoint.nfc().collect::<String>())?;
         let extra_bytes = extra.filter(|v| !v.is_empty());
 
         if endpoint_nfc == self.endpoint &&
@@ -321,7 +510,12 @@ impl PeerInfo {
             sequence_number,
             self.fingerprint,
             endpoint_nfc,
-            extra_bytes
+            PeerFields {
+                extra: extra_bytes,
+                tags: self.tags.clone(),
+                weight: self.weight,
+                announced: Some(crate::as_ms!(SystemTime::now()) as u64),
+            }
         )
     }
 
@@ -332,6 +526,9 @@ impl PeerInfo {
         if self.nonce.len() != Self::NONCE_BYTES {
             return false;
         }
+        if Self::check_tags(&self.tags).is_err() {
+            return false;
+        }
 
         if let Some(nodeid) = self.nodeid.as_ref() {
             if self.node_sig.is_none() {
@@ -343,11 +540,13 @@ impl PeerInfo {
             sha.update(self.nonce.as_slice());
             let digest = sha.finalize().to_vec();
 
-            return signature::verify(
+            if !signature::verify(
                 digest.as_slice(),
                 self.node_sig.as_ref().unwrap().as_slice(),
                 &nodeid.to_signature_key()
-            ).is_ok()
+            ).unwrap_or(false) {
+                return false;
+            }
         } else if self.node_sig.is_some() {
             return false;
         }
@@ -356,11 +555,15 @@ impl PeerInfo {
             self.digest().as_slice(),
             self.sig.as_slice(),
             &self.pk.to_signature_key()
-        ).is_ok()
+        ).unwrap_or(false)
     }
 
     fn digest(&self) -> Vec<u8> {
         let mut sha = Sha256::new();
+        if let Some(announced) = self.announced {
+            sha.update(ANNOUNCED_TAG);
+            sha.update(announced.to_be_bytes().as_ref());
+        }
         sha.update(self.pk.as_bytes());
         sha.update(self.nonce.as_slice());
         sha.update(self.seq.to_be_bytes().as_ref());
@@ -373,6 +576,17 @@ impl PeerInfo {
         if let Some(extra) = self.extra.as_ref() {
             sha.update(extra.as_slice());
         }
+        if !self.tags.is_empty() {
+            sha.update(TAGS_TAG);
+            for tag in self.tags.iter() {
+                sha.update([tag.len() as u8]);
+                sha.update(tag.as_bytes());
+            }
+        }
+        if self.weight != Self::DEFAULT_WEIGHT {
+            sha.update(WEIGHT_TAG);
+            sha.update([self.weight]);
+        }
         sha.finalize().to_vec()
     }
 }
@@ -395,6 +609,9 @@ impl Hash for PeerInfo {
         if let Some(v) = self.extra.as_ref() {
             v.hash(state);
         }
+        self.tags.hash(state);
+        self.weight.hash(state);
+        self.announced.hash(state);
     }
 }
 
@@ -414,6 +631,18 @@ impl fmt::Display for PeerInfo {
         if let Some(node_sig) = self.node_sig.as_ref() {
             write!(f, ",nodeSig:{}", hex::encode(node_sig))?;
         }
+        if !self.tags.is_empty() {
+            write!(f, ",tags:{}", self.tags.join("|"))?;
+        }
+        if self.weight != Self::DEFAULT_WEIGHT {
+            write!(f, ",weight:{}", self.weight)?;
+        }
+        if let Some(announced) = self.announced {
+            write!(f, ",at:{}", announced)?;
+        }
+        if let Some(observed) = self.observed.as_ref() {
+            write!(f, ",observed:{}", observed)?;
+        }
         write!(f, ",sig:{}", hex::encode(&self.sig))?;
         Ok(())
     }
@@ -426,7 +655,18 @@ impl Serialize for PeerInfo {
     {
         let seq = (self.seq != 0).then_some(self.seq);
         let fingerprint = (self.fingerprint != 0).then_some(self.fingerprint);
-        let mut s = ser.serialize_tuple(9)?;
+        // The time, the tags, the weight and the observed endpoint go last,
+        // so announcements without them keep the elements older versions
+        // expect.
+        let weighted = self.weight != Self::DEFAULT_WEIGHT;
+        let len = match (self.announced.is_some(), self.tags.is_empty()) {
+            _ if self.observed.is_some() => 13,
+            _ if weighted => 12,
+            (_, false) => 11,
+            (true, true) => 10,
+            (false, true) => 9,
+        };
+        let mut s = ser.serialize_tuple(len)?;
         s.serialize_element(&self.pk)?;
         s.serialize_element(&self.nonce)?;
         s.serialize_element(&seq)?;
@@ -436,6 +676,19 @@ impl Serialize for PeerInfo {
         s.serialize_element(&fingerprint)?;
         s.serialize_element(&self.endpoint)?;
         s.serialize_element(&self.extra)?;
+        if len >= 12 {
+            s.serialize_element(&self.announced)?;
+            s.serialize_element(&self.tags)?;
+            s.serialize_element(&self.weight)?;
+            if let Some(observed) = self.observed.as_ref() {
+                s.serialize_element(observed)?;
+            }
+        } else if !self.tags.is_empty() {
+            s.serialize_element(&self.announced)?;
+            s.serialize_element(&self.tags)?;
+        } else if let Some(announced) = self.announced.as_ref() {
+            s.serialize_element(announced)?;
+        }
         s.end()
     }
 }
@@ -478,11 +731,39 @@ impl<'de> Deserialize<'de> for PeerInfo {
                     .ok_or_else(|| de::Error::invalid_length(7, &"9 elements"))?;
                 let extra = seq.next_element::<Option<Vec<u8>>>()?
                     .flatten();
+                let announced = seq.next_element::<Option<u64>>()?
+                    .flatten();
+                let tags = seq.next_element::<Option<Vec<String>>>()?
+                    .flatten()
+                    .unwrap_or_default();
+                let weight = seq.next_element::<Option<u8>>()?
+                    .flatten()
+                    .unwrap_or(PeerInfo::DEFAULT_WEIGHT);
+                let observed = seq.next_element::<Option<String>>()?
+                    .flatten();
+                let fields = PeerFields { extra, tags, weight, announced };
                 Ok(PeerInfo::packed(
-                    pk, nonce, seqno, nodeid, node_sig, sig, fingerprint, endpoint, extra
-                ))
+                    pk, nonce, seqno, nodeid, node_sig, sig, fingerprint, endpoint, fields
+                ).with_observed(observed))
             }
         }
-        des.deserialize_tuple(9, PeerVisitor)
+        des.deserialize_tuple(13, PeerVisitor)
+    }
+}
+
+// Decodes a peer from its CBOR form, as stored and sent around.
+impl TryFrom<&[u8]> for PeerInfo {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(data).map_err(|e| {
+            ArgumentError::new(format!("Failed to parse PeerInfo from bytes: {}", e)).into()
+        })
+    }
+}
+
+impl From<&PeerInfo> for Vec<u8> {
+    fn from(peer: &PeerInfo) -> Self {
+        serde_cbor::to_vec(peer).unwrap()
     }
 }
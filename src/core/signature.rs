@@ -1,43 +1,22 @@
-use std::{fmt, mem};
+use std::fmt;
 use std::str::FromStr;
 use static_assertions::const_assert;
 use bs58::decode;
 use hex::FromHexError;
 
-use libsodium_sys::{
-    crypto_sign_BYTES,
-    crypto_sign_PUBLICKEYBYTES,
-    crypto_sign_SECRETKEYBYTES,
-    crypto_sign_SEEDBYTES,
-    crypto_sign_detached,
-    crypto_sign_ed25519_sk_to_pk,
-    crypto_sign_final_create,
-    crypto_sign_final_verify,
-    crypto_sign_init,
-    crypto_sign_keypair,
-    crypto_sign_seed_keypair,
-    crypto_sign_state,
-    crypto_sign_update,
-    crypto_sign_verify_detached,
-    randombytes_buf,
-};
-
-use crate::{
-    as_uchar_ptr,
-    as_uchar_ptr_mut
-};
 use super::{
     Error, Result,
+    crypto::{self, Backend, Provider},
     errors::{
         ArgumentError,
         CryptoError,
     }
 };
 
-const_assert!(PrivateKey::BYTES == crypto_sign_SECRETKEYBYTES as usize);
-const_assert!(PublicKey::BYTES == crypto_sign_PUBLICKEYBYTES as usize);
-const_assert!(KeyPair::SEED_BYTES == crypto_sign_SEEDBYTES as usize);
-const_assert!(Signature::BYTES == crypto_sign_BYTES as usize);
+const_assert!(PrivateKey::BYTES == crypto::SIGN_SECRET_KEY_BYTES);
+const_assert!(PublicKey::BYTES == crypto::SIGN_PUBLIC_KEY_BYTES);
+const_assert!(KeyPair::SEED_BYTES == crypto::SIGN_SEED_BYTES);
+const_assert!(Signature::BYTES == crypto::SIGNATURE_BYTES);
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PrivateKey([u8; Self::BYTES]);
@@ -66,15 +45,7 @@ impl PrivateKey {
             )));
         }
 
-        unsafe { // Always success
-            crypto_sign_detached(
-                as_uchar_ptr_mut!(signature),
-                std::ptr::null_mut(),
-                as_uchar_ptr!(data),
-                data.len() as libc::c_ulonglong,
-                as_uchar_ptr!(self.0),
-            )
-        };
+        signature.copy_from_slice(&Backend::sign_detached(data, &self.0));
         Ok(Signature::BYTES)
     }
 
@@ -217,15 +188,11 @@ impl PublicKey {
             )));
         }
 
-        let rc = unsafe {
-            crypto_sign_verify_detached(
-                as_uchar_ptr!(signature),
-                as_uchar_ptr!(data),
-                data.len() as libc::c_ulonglong,
-                as_uchar_ptr!(self.0),
-            )
-        };
-        Ok(rc == 0)
+        Ok(Backend::sign_verify_detached(
+            signature.try_into().unwrap(),
+            data,
+            &self.0
+        ))
     }
 }
 
@@ -255,39 +222,12 @@ impl KeyPair {
     pub const SEED_BYTES: usize = 32;
 
     pub fn new() -> Self {
-        let mut sk = [0u8; PrivateKey::BYTES];
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe { // Always success
-            crypto_sign_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk)
-            );
-        }
-
-        KeyPair(PrivateKey(sk), PublicKey(pk))
+        Self::random()
     }
 
     pub fn random() -> Self {
-        let mut seed = [0u8; KeyPair::SEED_BYTES];
-        unsafe {
-            randombytes_buf(
-                seed.as_mut_ptr() as *mut libc::c_void,
-                KeyPair::SEED_BYTES
-            );
-        }
-
-        let mut sk = [0u8; PrivateKey::BYTES];
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe { // Always success
-            crypto_sign_seed_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk),
-                as_uchar_ptr!(seed),
-            );
-        }
-
+        let seed = crate::random_array::<{ KeyPair::SEED_BYTES }>();
+        let (sk, pk) = Backend::sign_seed_keypair(&seed);
         KeyPair(PrivateKey(sk), PublicKey(pk))
     }
 
@@ -300,17 +240,7 @@ impl KeyPair {
             )));
         }
 
-        let mut sk = [0u8; PrivateKey::BYTES];
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe {// Always success
-            crypto_sign_seed_keypair(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr_mut!(sk),
-                as_uchar_ptr!(seed),
-            );
-        }
-
+        let (sk, pk) = Backend::sign_seed_keypair(seed.try_into().unwrap());
         Ok(KeyPair(PrivateKey(sk), PublicKey(pk)))
     }
 
@@ -348,14 +278,7 @@ impl TryFrom<&[u8]> for KeyPair {
             )));
         }
 
-        let mut pk = [0u8; PublicKey::BYTES];
-        unsafe { // Always success
-            crypto_sign_ed25519_sk_to_pk(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr!(sk)
-            );
-        }
-
+        let pk = Backend::sign_sk_to_pk(sk.try_into().unwrap());
         Ok(KeyPair(
             PrivateKey::try_from(sk).unwrap(),
             PublicKey(pk)
@@ -365,14 +288,7 @@ impl TryFrom<&[u8]> for KeyPair {
 
 impl From<&PrivateKey> for KeyPair {
     fn from(sk: &PrivateKey) -> Self {
-        let mut pk = [0u8; PublicKey::BYTES];
-
-        unsafe { // Always success
-            crypto_sign_ed25519_sk_to_pk(
-                as_uchar_ptr_mut!(pk),
-                as_uchar_ptr!(sk.as_bytes())
-            );
-        }
+        let pk = Backend::sign_sk_to_pk(&sk.0);
         KeyPair(sk.clone(), PublicKey(pk))
     }
 }
@@ -410,13 +326,9 @@ impl AsRef<PublicKey> for KeyPair {
     }
 }
 
-#[repr(transparent)]
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct SignState([u8; std::mem::size_of::<crypto_sign_state>()]);
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Signature {
-    state: SignState,
+    state: <Backend as Provider>::SignState,
 }
 
 impl Signature {
@@ -424,32 +336,17 @@ impl Signature {
 
     pub fn new() -> Self {
         Self {
-            state: SignState([0u8; std::mem::size_of::<crypto_sign_state>()])
+            state: Default::default()
         }
     }
 
     pub fn reset(&mut self) -> &mut Self {
-        assert!(
-            mem::size_of::<SignState>() >= mem::size_of::<crypto_sign_state>(),
-            "Inappropriate signature state size."
-        );
-
-        let s = &mut self.state.0 as *mut _ as *mut crypto_sign_state;
-        unsafe { // Always success
-            crypto_sign_init(s);
-        }
+        Backend::sign_init(&mut self.state);
         self
     }
 
     pub fn update(&mut self, part: &[u8]) -> &mut Self {
-        let s = &mut self.state.0 as *mut _ as *mut crypto_sign_state;
-        unsafe { // Always success
-            crypto_sign_update(
-                s,
-                as_uchar_ptr!(part),
-                part.len() as libc::c_ulonglong
-            );
-        }
+        Backend::sign_update(&mut self.state, part);
         self
     }
 
@@ -462,15 +359,7 @@ impl Signature {
             )));
         }
 
-        let s = &mut self.state.0 as *mut _ as *mut crypto_sign_state;
-        unsafe { // Always success
-            crypto_sign_final_create(
-                s,
-                as_uchar_ptr_mut!(signature),
-                std::ptr::null_mut(),
-                as_uchar_ptr!(sk.as_bytes()),
-            );
-        }
+        signature.copy_from_slice(&Backend::sign_final_create(&mut self.state, &sk.0));
         Ok(Signature::BYTES)
     }
 
@@ -489,16 +378,11 @@ impl Signature {
             )));
         }
 
-        let s = &mut self.state.0 as *mut _ as *mut crypto_sign_state;
-        let rc = unsafe {
-            crypto_sign_final_verify(
-                s,
-                as_uchar_ptr!(signature),
-                as_uchar_ptr!(pk.as_bytes())
-            )
-        };
-
-        Ok(rc == 0)
+        Ok(Backend::sign_final_verify(
+            &mut self.state,
+            signature.try_into().unwrap(),
+            &pk.0
+        ))
     }
 }
 
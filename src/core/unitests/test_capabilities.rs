@@ -0,0 +1,63 @@
+use std::net::SocketAddr;
+use crate::{
+    Id,
+    NodeInfo,
+    Capabilities,
+};
+
+#[test]
+fn test_flags() {
+    let caps = Capabilities::STORAGE | Capabilities::RELAY;
+    assert!(caps.contains(Capabilities::STORAGE));
+    assert!(caps.contains(Capabilities::RELAY));
+    assert!(!caps.contains(Capabilities::GATEWAY));
+    assert!(caps.accepts_storage());
+    assert_eq!(caps.bits(), 0x03);
+    assert_eq!(Capabilities::from_bits(0x03), caps);
+
+    assert!(!Capabilities::CLIENT_ONLY.accepts_storage());
+    assert!(!(Capabilities::STORAGE | Capabilities::CLIENT_ONLY).accepts_storage());
+    assert!(!Capabilities::empty().accepts_storage());
+    assert!(Capabilities::default().accepts_storage());
+}
+
+#[test]
+fn test_display() {
+    assert_eq!((Capabilities::STORAGE | Capabilities::GATEWAY).to_string(), "storage|gateway");
+    assert_eq!(Capabilities::CLIENT_ONLY.to_string(), "client-only");
+    assert_eq!(Capabilities::empty().to_string(), "none");
+    // Flags from newer versions are kept.
+    assert_eq!(Capabilities::from_bits(0x41).to_string(), "storage|0x40");
+}
+
+#[test]
+fn test_tally() {
+    let tally = Capabilities::tally([
+        Capabilities::STORAGE,
+        Capabilities::STORAGE | Capabilities::RELAY,
+        Capabilities::CLIENT_ONLY,
+        Capabilities::empty(),
+    ].into_iter());
+    assert_eq!(tally.len(), 4);
+    assert_eq!(tally["storage"], 2);
+    assert_eq!(tally["relay"], 1);
+    assert_eq!(tally["client-only"], 1);
+    assert_eq!(tally["none"], 1);
+}
+
+#[test]
+fn test_node_info() {
+    let addr: SocketAddr = "192.168.1.2:39001".parse().unwrap();
+    let mut ni = NodeInfo::new(Id::random(), addr);
+    assert_eq!(ni.capabilities(), Capabilities::FULL_NODE);
+    assert_eq!(ni.advertised_capabilities(), None);
+
+    ni.set_capabilities(Capabilities::CLIENT_ONLY);
+    assert_eq!(ni.capabilities(), Capabilities::CLIENT_ONLY);
+
+    // Not part of the wire form, as the version.
+    let bytes = serde_cbor::to_vec(&ni).unwrap();
+    let decoded: NodeInfo = serde_cbor::from_slice(&bytes).unwrap();
+    assert_eq!(decoded, ni);
+    assert_eq!(decoded.advertised_capabilities(), None);
+}
@@ -0,0 +1,34 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::core::clock::{Clock, SystemClock, ManualClock};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manual_clock() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = ManualClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now_ms(), 1_700_000_000_000);
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), start + Duration::from_millis(1500));
+        assert_eq!(clock.elapsed_ms(start), 1500);
+
+        // A time in the future counts as long gone, like elapsed_ms! does.
+        let later = clock.now() + Duration::from_secs(1);
+        assert_eq!(clock.elapsed_ms(later), Duration::MAX.as_millis());
+
+        clock.set(start);
+        assert_eq!(clock.elapsed_ms(start), 0);
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let before = SystemTime::now();
+        let now = SystemClock.now();
+        assert!(now >= before);
+        assert!(SystemClock.elapsed_ms(UNIX_EPOCH) >= crate::as_ms!(before));
+    }
+}
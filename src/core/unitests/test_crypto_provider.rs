@@ -0,0 +1,123 @@
+use crate::core::crypto::{
+    Provider,
+    RustCrypto,
+    Sodium,
+    BOX_MAC_BYTES,
+};
+/*
+ The pure-Rust backend must be byte-compatible with libsodium:
+ - Ed25519 keys and signatures, single and multi-part
+ - Ed25519 to X25519 key conversion
+ - crypto_box keys and ciphertexts
+ */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> [u8; 32] {
+        crate::random_array::<32>()
+    }
+
+    #[test]
+    fn test_sign() {
+        let seed = seed();
+        let (sk, pk) = Sodium::sign_seed_keypair(&seed);
+        assert_eq!(RustCrypto::sign_seed_keypair(&seed), (sk, pk));
+        assert_eq!(RustCrypto::sign_sk_to_pk(&sk), pk);
+
+        let data = crate::random_bytes(1024);
+        let sig = Sodium::sign_detached(&data, &sk);
+        assert_eq!(RustCrypto::sign_detached(&data, &sk), sig);
+        assert!(RustCrypto::sign_verify_detached(&sig, &data, &pk));
+
+        let mut tampered = sig;
+        tampered[0] ^= 0x01;
+        assert!(!Sodium::sign_verify_detached(&tampered, &data, &pk));
+        assert!(!RustCrypto::sign_verify_detached(&tampered, &data, &pk));
+    }
+
+    #[test]
+    fn test_sign_multipart() {
+        let (sk, pk) = Sodium::sign_seed_keypair(&seed());
+        let parts = [crate::random_bytes(100), crate::random_bytes(33), Vec::new()];
+
+        let mut s1 = <Sodium as Provider>::SignState::default();
+        let mut s2 = <RustCrypto as Provider>::SignState::default();
+        Sodium::sign_init(&mut s1);
+        RustCrypto::sign_init(&mut s2);
+        for part in parts.iter() {
+            Sodium::sign_update(&mut s1, part);
+            RustCrypto::sign_update(&mut s2, part);
+        }
+        let sig = Sodium::sign_final_create(&mut s1, &sk);
+        assert_eq!(RustCrypto::sign_final_create(&mut s2, &sk), sig);
+
+        RustCrypto::sign_init(&mut s2);
+        parts.iter().for_each(|part| RustCrypto::sign_update(&mut s2, part));
+        assert!(RustCrypto::sign_final_verify(&mut s2, &sig, &pk));
+
+        // Multi-part signatures differ from single-part ones
+        let data = parts.concat();
+        RustCrypto::sign_init(&mut s2);
+        RustCrypto::sign_update(&mut s2, &data);
+        assert!(!RustCrypto::sign_final_verify(&mut s2, &Sodium::sign_detached(&data, &sk), &pk));
+    }
+
+    #[test]
+    fn test_key_conversion() {
+        let (sk, pk) = Sodium::sign_seed_keypair(&seed());
+        assert_eq!(RustCrypto::sign_sk_to_curve25519(&sk), Sodium::sign_sk_to_curve25519(&sk));
+        assert_eq!(RustCrypto::sign_pk_to_curve25519(&pk), Sodium::sign_pk_to_curve25519(&pk));
+        assert!(RustCrypto::sign_pk_to_curve25519(&pk).is_some());
+
+        // Small order point
+        let small = [0u8; 32];
+        assert_eq!(Sodium::sign_pk_to_curve25519(&small), None);
+        assert_eq!(RustCrypto::sign_pk_to_curve25519(&small), None);
+
+        let x25519 = RustCrypto::sign_sk_to_curve25519(&sk);
+        assert_eq!(RustCrypto::scalarmult_base(&x25519), RustCrypto::sign_pk_to_curve25519(&pk).unwrap());
+    }
+
+    #[test]
+    fn test_box() {
+        let seed1 = seed();
+        let (sk1, pk1) = Sodium::box_seed_keypair(&seed1);
+        let (sk2, pk2) = RustCrypto::box_seed_keypair(&seed());
+        assert_eq!(RustCrypto::box_seed_keypair(&seed1), (sk1, pk1));
+        assert_eq!(Sodium::scalarmult_base(&sk2), pk2);
+
+        let key = Sodium::box_beforenm(&pk2, &sk1).unwrap();
+        assert_eq!(RustCrypto::box_beforenm(&pk2, &sk1), Some(key));
+        assert_eq!(RustCrypto::box_beforenm(&pk1, &sk2), Some(key));
+        assert_eq!(RustCrypto::box_beforenm(&[0u8; 32], &sk1), None);
+
+        let nonce = crate::random_array::<24>();
+        let plain = crate::random_bytes(1000);
+        let mut c1 = vec![0u8; plain.len() + BOX_MAC_BYTES];
+        let mut c2 = vec![0u8; plain.len() + BOX_MAC_BYTES];
+        assert!(Sodium::box_easy_afternm(&mut c1, &plain, &nonce, &key));
+        assert!(RustCrypto::box_easy_afternm(&mut c2, &plain, &nonce, &key));
+        assert_eq!(c1, c2);
+
+        let mut decrypted = vec![0u8; plain.len()];
+        assert!(RustCrypto::box_open_easy_afternm(&mut decrypted, &c1, &nonce, &key));
+        assert_eq!(decrypted, plain);
+
+        c1[BOX_MAC_BYTES] ^= 0x01;
+        assert!(!Sodium::box_open_easy_afternm(&mut decrypted, &c1, &nonce, &key));
+        assert!(!RustCrypto::box_open_easy_afternm(&mut decrypted, &c1, &nonce, &key));
+    }
+
+    #[test]
+    fn test_random() {
+        assert_eq!(RustCrypto::random_uniform(0), 0);
+        assert_eq!(RustCrypto::random_uniform(1), 0);
+        assert!((0..1000).all(|_| RustCrypto::random_uniform(7) < 7));
+
+        let mut buf = [0u8; 64];
+        RustCrypto::random_bytes(&mut buf);
+        assert_ne!(buf, [0u8; 64]);
+    }
+}
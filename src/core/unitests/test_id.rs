@@ -1,5 +1,8 @@
 use std::cmp::Ordering;
-use crate::core::Id;
+use crate::core::{
+    Id,
+    errors::{MalformedError, UnsupportedVersionError},
+};
 
 #[cfg(test)]
 mod tests {
@@ -79,4 +82,137 @@ mod tests {
         assert_eq!(id_str, id2_str);
         assert_eq!(id, des);
     }
+
+    #[test]
+    fn test_bit_round_trip() {
+        for _ in 0..32 {
+            let id = Id::random();
+            let mut rebuilt = Id::zero();
+            for i in 0..Id::BITS {
+                rebuilt.set_bit(i, id.bit_at(i));
+            }
+            assert_eq!(rebuilt, id);
+
+            let i = (id.as_bytes()[0] as usize) % Id::BITS;
+            let mut flipped = id;
+            flipped.set_bit(i, !id.bit_at(i));
+            assert_ne!(flipped, id);
+            assert_eq!(flipped.common_prefix_len(&id), i);
+        }
+
+        for i in 0..Id::BITS {
+            let id = Id::try_from_bit_at(i).unwrap();
+            assert!(id.bit_at(i));
+            assert_eq!(id.leading_zeros(), i);
+        }
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        let id = Id::random();
+        assert_eq!(id.common_prefix_len(&id), Id::BITS);
+        assert_eq!(Id::MIN_ID.common_prefix_len(&Id::MAX_ID), 0);
+
+        for _ in 0..256 {
+            let a = Id::random();
+            let b = Id::random();
+            let n = a.common_prefix_len(&b);
+            assert_eq!(n, a.distance(&b).leading_zeros());
+            assert_eq!(n, b.common_prefix_len(&a));
+            assert!(n == Id::BITS || a.bit_at(n) != b.bit_at(n));
+            assert!((0..n).all(|i| a.bit_at(i) == b.bit_at(i)));
+            if n > 0 {
+                assert!(Id::bits_equal(&a, &b, n as i32 - 1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_sub_one() {
+        assert_eq!(Id::MAX_ID.add_one(), Id::MIN_ID);
+        assert_eq!(Id::MIN_ID.sub_one(), Id::MAX_ID);
+        assert_eq!(Id::zero().add_one(), Id::try_from_bit_at(Id::BITS - 1).unwrap());
+
+        let id = Id::try_from("0x00000000000000000000000000000000000000000000000000000000000000ff").unwrap();
+        assert_eq!(id.add_one().to_hexstr(), "0x0000000000000000000000000000000000000000000000000000000000000100");
+        assert_eq!(id.add_one().sub_one(), id);
+
+        for _ in 0..64 {
+            let id = Id::random();
+            assert!(id.add_one() > id || id == Id::MAX_ID);
+            assert_eq!(id.add_one().sub_one(), id);
+            assert_eq!(id.sub_one().add_one(), id);
+        }
+    }
+
+    #[test]
+    fn test_three_way_compare_matches_distance() {
+        for _ in 0..1024 {
+            let target = Id::random();
+            let a = Id::random();
+            let b = Id::random();
+            assert_eq!(
+                target.three_way_compare(&a, &b),
+                target.distance(&a).cmp(&target.distance(&b))
+            );
+            assert_eq!(target.three_way_compare(&a, &b), target.three_way_compare(&b, &a).reverse());
+        }
+    }
+
+    #[test]
+    fn test_three_way_compare_transitive() {
+        for _ in 0..1024 {
+            let target = Id::random();
+            let mut ids = [Id::random(), Id::random(), Id::random()];
+            ids.sort_by(|a, b| target.three_way_compare(a, b));
+
+            let [a, b, c] = ids;
+            assert_ne!(target.three_way_compare(&a, &b), Ordering::Greater);
+            assert_ne!(target.three_way_compare(&b, &c), Ordering::Greater);
+            assert_ne!(target.three_way_compare(&a, &c), Ordering::Greater);
+
+            // A closer id never shares a shorter prefix with the target
+            assert!(target.common_prefix_len(&a) >= target.common_prefix_len(&c));
+        }
+    }
+
+    #[test]
+    fn test_derive() {
+        let zero = Id::zero();
+        let ns = Id::try_from("0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+
+        // Fixed vectors, the derivation must never change.
+        assert_eq!(Id::derive(&zero, "").to_hexstr(),
+            "0xb17c3c5f4f595794b3333c0a62a57beb442fab5ec1d2a500a363a85266941386");
+        assert_eq!(Id::derive(&zero, "profile").to_hexstr(),
+            "0x76ebf0b536764139d0eb79977932376220868c8b68208811f3a3e38b1784759b");
+        assert_eq!(Id::derive(&ns, "profile").to_hexstr(),
+            "0xdf6c7f87b932cfd76b3fe84d2e95ddd43722060bf74e978ae3105de9216318a3");
+        assert_eq!(Id::derive(&ns, "caf\u{e9}").to_hexstr(),
+            "0xc91a02e482b43ea14b71535deaddad008e7b9ff67df1aa9be5b48d82a90ca707");
+
+        // The composed and decomposed forms of a key are the same key.
+        assert_eq!(Id::derive(&ns, "cafe\u{301}"), Id::derive(&ns, "caf\u{e9}"));
+    }
+
+    #[test]
+    fn test_compact() {
+        let id = Id::random();
+        let compact = id.to_compact_bytes();
+        assert_eq!(compact.len(), 2 + Id::BYTES);
+        assert_eq!(Id::from_compact_bytes(&compact).unwrap(), id);
+
+        assert!(Id::from_compact_bytes(&compact[..compact.len() - 1]).is_err());
+        assert!(Id::from_compact_bytes(&compact[..1]).is_err());
+
+        let mut newer = compact.clone();
+        newer[1] += 1;
+        let err = Id::from_compact_bytes(&newer).unwrap_err();
+        assert!(err.downcast_ref::<UnsupportedVersionError>().is_some());
+
+        let mut other = compact.clone();
+        other[0] ^= 0xff;
+        let err = Id::from_compact_bytes(&other).unwrap_err();
+        assert!(err.downcast_ref::<MalformedError>().is_some());
+    }
 }
@@ -0,0 +1,153 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use crate::core::{
+    Id,
+    NodeInfo,
+    PeerBuilder,
+    errors::UnsupportedProtocolError,
+    multiaddr::{Multiaddr, Protocol},
+};
+
+fn unsupported(result: crate::Result<impl std::fmt::Debug>) -> String {
+    let err = result.expect_err("an unsupported protocol");
+    err.downcast_ref::<UnsupportedProtocolError>()
+        .unwrap_or_else(|| panic!("unexpected error {err}"))
+        .protocol()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_node_v4() {
+        let id = Id::random();
+        let node = NodeInfo::new(id, "203.0.113.7:39001".parse().unwrap());
+
+        let addr = node.to_multiaddr();
+        assert_eq!(addr.to_string(), format!("/ip4/203.0.113.7/udp/39001/boson/{}", id.to_base58()));
+        assert_eq!(NodeInfo::try_from_multiaddr(&addr, false).unwrap(), node);
+
+        let parsed = Multiaddr::from_str(&addr.to_string()).unwrap();
+        assert_eq!(parsed, addr);
+        assert_eq!(Multiaddr::try_from_bytes(&addr.to_bytes()).unwrap(), addr);
+
+        // A trailing slash is taken as well.
+        let parsed = Multiaddr::from_str(&format!("{addr}/")).unwrap();
+        assert_eq!(parsed, addr);
+    }
+
+    #[test]
+    fn test_node_v6() {
+        let id = Id::random();
+        let node = NodeInfo::new(id, "[2001:db8::1]:39001".parse().unwrap());
+
+        let addr = node.to_multiaddr();
+        assert_eq!(addr.to_string(), format!("/ip6/2001:db8::1/udp/39001/boson/{}", id.to_base58()));
+        assert_eq!(NodeInfo::try_from_multiaddr(&addr, false).unwrap(), node);
+        assert_eq!(Multiaddr::from_str(&addr.to_string()).unwrap(), addr);
+        assert_eq!(Multiaddr::try_from_bytes(&addr.to_bytes()).unwrap(), addr);
+    }
+
+    #[test]
+    fn test_node_binary() {
+        let id = Id::random();
+        let addr = NodeInfo::new(id, "127.0.0.1:4001".parse().unwrap()).to_multiaddr();
+
+        // ip4 and the address, udp as a two byte varint and the port, then
+        // the boson code and the length of the id.
+        let bytes = addr.to_bytes();
+        assert_eq!(&bytes[..14], &[0x04, 127, 0, 0, 1, 0x91, 0x02, 0x0f, 0xa1, 0xd0, 0xe0, 0xc2, 0x01, 32]);
+        assert_eq!(&bytes[bytes.len() - Id::BYTES..], id.as_bytes());
+
+        assert!(Multiaddr::try_from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert_eq!(unsupported(Multiaddr::try_from_bytes(&[0xa5, 0x03, 0x00])), "0x1a5");
+    }
+
+    #[test]
+    fn test_node_dns() {
+        let id = Id::random();
+        let addr = Multiaddr::from_str(&format!("/dns4/localhost/udp/39001/boson/{}", id.to_base58())).unwrap();
+
+        assert_eq!(unsupported(NodeInfo::try_from_multiaddr(&addr, false)), "dns4");
+
+        let node = NodeInfo::try_from_multiaddr(&addr, true).unwrap();
+        assert_eq!(node.id(), &id);
+        assert_eq!(node.socket_addr(), &"127.0.0.1:39001".parse::<SocketAddr>().unwrap());
+    }
+
+    #[test]
+    fn test_node_rejections() {
+        let id = Id::random().to_base58();
+
+        let tcp = Multiaddr::from_str(&format!("/ip4/127.0.0.1/tcp/39001/boson/{id}")).unwrap();
+        assert_eq!(unsupported(NodeInfo::try_from_multiaddr(&tcp, false)), "tcp");
+
+        let tcp = Multiaddr::from_str("/ip4/127.0.0.1/tcp/39001").unwrap();
+        assert_eq!(unsupported(NodeInfo::try_from_multiaddr(&tcp, false)), "tcp");
+
+        // Without the id.
+        let bare = Multiaddr::from_str("/ip4/127.0.0.1/udp/39001").unwrap();
+        assert!(NodeInfo::try_from_multiaddr(&bare, false).is_err());
+
+        assert_eq!(unsupported(Multiaddr::from_str("/ip4/127.0.0.1/quic-v1")), "quic-v1");
+        assert!(Multiaddr::from_str("ip4/127.0.0.1").is_err());
+        assert!(Multiaddr::from_str("/ip4/127.0.0.256").is_err());
+        assert!(Multiaddr::from_str("/ip4/127.0.0.1/udp").is_err());
+        assert!(Multiaddr::from_str("/ip4/127.0.0.1/udp/65536").is_err());
+        assert!(Multiaddr::from_str("/boson/notbase58!").is_err());
+    }
+
+    #[test]
+    fn test_peer() {
+        let endpoints = [
+            "tcp://203.0.113.7:8080",
+            "ssl://[2001:db8::1]:9000",
+            "http://example.com",
+            "http://203.0.113.7:8080/peers",
+            "https://example.com:8443/api/v1/peers%20list",
+            "ws://example.com/socket",
+            "wss://example.com",
+        ];
+
+        for endpoint in endpoints {
+            let peer = PeerBuilder::new(endpoint).build().unwrap();
+            let addr = peer.to_multiaddr().unwrap();
+            assert!(matches!(addr.protocols().last(), Some(Protocol::Boson(id)) if id == peer.id()));
+
+            let parsed = Multiaddr::from_str(&addr.to_string()).unwrap();
+            assert_eq!(parsed, addr);
+            assert_eq!(Multiaddr::try_from_bytes(&addr.to_bytes()).unwrap(), addr);
+
+            let (id, parsed) = crate::PeerInfo::parse_multiaddr(&addr).unwrap();
+            assert_eq!(&id, peer.id());
+            assert_eq!(parsed, endpoint);
+        }
+
+        let peer = PeerBuilder::new("https://example.com:8443/api").build().unwrap();
+        assert_eq!(peer.to_multiaddr().unwrap().to_string(),
+            format!("/dns/example.com/tcp/8443/tls/http/http-path/%2Fapi/boson/{}", peer.id().to_base58())
+        );
+    }
+
+    #[test]
+    fn test_peer_rejections() {
+        let no_port = PeerBuilder::new("tcp://example.com").build().unwrap();
+        assert!(no_port.to_multiaddr().is_err());
+
+        let query = PeerBuilder::new("http://example.com/api?q=1").build().unwrap();
+        assert!(query.to_multiaddr().is_err());
+
+        let id = Id::random().to_base58();
+        let udp = Multiaddr::from_str(&format!("/ip4/127.0.0.1/udp/80/boson/{id}")).unwrap();
+        assert_eq!(unsupported(crate::PeerInfo::parse_multiaddr(&udp)), "udp");
+
+        let https = Multiaddr::from_str(&format!("/dns/example.com/tcp/443/https/boson/{id}")).unwrap();
+        assert_eq!(unsupported(crate::PeerInfo::parse_multiaddr(&https)), "https");
+
+        let no_id = Multiaddr::from_str("/ip4/127.0.0.1/tcp/80").unwrap();
+        assert!(crate::PeerInfo::parse_multiaddr(&no_id).is_err());
+    }
+}
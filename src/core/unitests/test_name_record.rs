@@ -0,0 +1,198 @@
+use serde_cbor::Value as CborValue;
+
+use crate::core::{
+    Id,
+    Value,
+    SignedBuilder,
+    signature::KeyPair,
+    name_record::{
+        self,
+        NameRecord,
+        NAME_NAMESPACE,
+        normalize_name,
+        name_id,
+    },
+};
+
+const HOUR: u64 = 60 * 60 * 1000;
+
+fn keypair(seed: u8) -> KeyPair {
+    KeyPair::try_from_seed(&[seed; KeyPair::SEED_BYTES]).unwrap()
+}
+
+// The record with one field replaced as encoded, its signature kept.
+fn tampered(record: &NameRecord, key: &str, field: CborValue) -> NameRecord {
+    let mut value: CborValue = serde_cbor::value::to_value(record).unwrap();
+    if let CborValue::Map(map) = &mut value {
+        map.insert(CborValue::Text(key.to_string()), field);
+    }
+    serde_cbor::value::from_value(value).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize_name("alice").unwrap(), "alice");
+        assert_eq!(normalize_name("  Alice \t Smith\n").unwrap(), "alice smith");
+        assert_eq!(normalize_name("ALICE").unwrap(), "alice");
+        // Full width forms and compatibility ligatures fold to the plain ones.
+        assert_eq!(normalize_name("\u{ff21}lice").unwrap(), "alice");
+        assert_eq!(normalize_name("\u{fb01}ne").unwrap(), "fine");
+        // Composed and decomposed forms are the same name.
+        assert_eq!(normalize_name("Cafe\u{301}").unwrap(), normalize_name("caf\u{e9}").unwrap());
+        assert_eq!(normalize_name("\u{d6}ZIL").unwrap(), "\u{f6}zil");
+
+        assert!(normalize_name("").is_err());
+        assert!(normalize_name(" \t\n").is_err());
+        assert!(normalize_name("al\u{7}ice").is_err());
+        assert!(normalize_name(&"a".repeat(NameRecord::MAX_NAME_BYTES)).is_ok());
+        assert!(normalize_name(&"a".repeat(NameRecord::MAX_NAME_BYTES + 1)).is_err());
+        // Counted once normalized, four bytes each here.
+        assert!(normalize_name(&"\u{1f600}".repeat(16)).is_ok());
+        assert!(normalize_name(&"\u{1f600}".repeat(17)).is_err());
+    }
+
+    #[test]
+    fn test_derivation() {
+        // Fixed vectors, the ids of names must never change.
+        assert_eq!(NAME_NAMESPACE.to_hexstr(),
+            "0x626f736f6e3a6e616d653a6e616d6573706163653a7631000000000000000000");
+        assert_eq!(name_id("alice").unwrap().to_hexstr(),
+            "0xa73ced206a9fd9b5a40f60a6a8301aa06481ee7c2e7a3a044a68e5d5f09d3940");
+        assert_eq!(name_id("alice smith").unwrap().to_hexstr(),
+            "0x1367e8c640f5b796838984d3cbe06bce448c7d0dd0918af78dee5963f79eb998");
+
+        assert_eq!(name_id(" ALICE ").unwrap(), name_id("alice").unwrap());
+        assert_eq!(name_id("alice").unwrap(), Value::id_for_key(&NAME_NAMESPACE, "alice"));
+        assert_eq!(Value::world_writable_keypair(&NAME_NAMESPACE, "alice").public_key(),
+            KeyPair::try_from_seed(Id::derive(&NAME_NAMESPACE, "alice").as_bytes()).unwrap().public_key());
+        assert_ne!(name_id("alice").unwrap(), name_id("alice smith").unwrap());
+    }
+
+    #[test]
+    fn test_verify() {
+        let owner = keypair(1);
+        let target = Id::random();
+        let record = NameRecord::new(" Alice ", &target, 0, 10 * HOUR, &owner).unwrap();
+        assert_eq!(record.name(), "alice");
+        assert_eq!(record.owner(), &Id::from(owner.public_key()));
+        assert_eq!(record.target(), &target);
+        assert_eq!(record.sequence_number(), 0);
+        assert!(record.is_valid());
+
+        // Serialized as kept in the value of the name.
+        let data = serde_cbor::to_vec(&record).unwrap();
+        let decoded: NameRecord = serde_cbor::from_slice(&data).unwrap();
+        assert_eq!(decoded, record);
+        assert!(decoded.is_valid());
+
+        let bytes = |id: &Id| CborValue::Bytes(id.as_bytes().to_vec());
+        assert!(!tampered(&record, "n", CborValue::Text("bob".into())).is_valid());
+        assert!(!tampered(&record, "n", CborValue::Text("Alice".into())).is_valid());
+        assert!(!tampered(&record, "t", bytes(&Id::random())).is_valid());
+        assert!(!tampered(&record, "o", bytes(&Id::from(keypair(2).public_key()))).is_valid());
+        assert!(!tampered(&record, "s", CborValue::Integer(1)).is_valid());
+        assert!(!tampered(&record, "e", CborValue::Integer(11 * HOUR as i128)).is_valid());
+        assert!(!tampered(&record, "sig", CborValue::Bytes(vec![0; 64])).is_valid());
+        assert!(tampered(&record, "s", CborValue::Integer(0)).is_valid());
+
+        assert!(NameRecord::new("alice", &target, -1, 10 * HOUR, &owner).is_err());
+        assert!(NameRecord::new("", &target, 0, 10 * HOUR, &owner).is_err());
+    }
+
+    #[test]
+    fn test_claims() {
+        let target = Id::random();
+        let first = NameRecord::new("alice", &target, 0, 10 * HOUR, &keypair(1)).unwrap();
+        let second = NameRecord::new("alice", &Id::random(), 3, 20 * HOUR, &keypair(2)).unwrap();
+        let again = NameRecord::new("alice", &target, 1, 30 * HOUR, &keypair(1)).unwrap();
+        let other = NameRecord::new("bob", &target, 0, 10 * HOUR, &keypair(3)).unwrap();
+        let forged = tampered(
+            &NameRecord::new("alice", &target, 0, 10 * HOUR, &keypair(4)).unwrap(),
+            "t", CborValue::Bytes(Id::random().as_bytes().to_vec())
+        );
+
+        let data = name_record::encode_claims(&[
+            first.clone(), other, forged, second.clone(), again.clone()
+        ]).unwrap();
+
+        // In the order first made, the later claims of an owner left out.
+        let claims = name_record::decode_claims("alice", &data, HOUR);
+        assert_eq!(claims, vec![first.clone(), second.clone()]);
+        let resolved = name_record::resolve_claims("alice", &data, HOUR);
+        assert_eq!(resolved.len(), 2);
+        assert_eq!(resolved[0].owner(), first.owner());
+        assert_eq!(resolved[0].target(), &target);
+        assert_eq!(resolved[1].sequence_number(), 3);
+        assert_eq!(resolved[1].record(), &second);
+
+        // Expired claims are dropped, the next one of the owner counts then.
+        assert!(first.is_expired(10 * HOUR));
+        assert!(!first.is_expired(10 * HOUR - 1));
+        assert_eq!(name_record::decode_claims("alice", &data, 15 * HOUR), vec![second, again.clone()]);
+        assert_eq!(name_record::decode_claims("alice", &data, 20 * HOUR), vec![again]);
+        assert!(name_record::decode_claims("alice", &data, 30 * HOUR).is_empty());
+
+        // Data of some other value holds no claims.
+        assert!(name_record::decode_claims("alice", b"not claims", HOUR).is_empty());
+        assert!(name_record::decode_claims("alice", &[], HOUR).is_empty());
+    }
+
+    #[test]
+    fn test_merge_claims() {
+        let name_value = |claims: &[NameRecord], seq: i32| SignedBuilder::world_writable(
+            &NAME_NAMESPACE, "alice", &name_record::encode_claims(claims).unwrap()
+        ).with_sequence_number(seq).build().unwrap();
+        let owners = |value: &Value| name_record::decode_claims("alice", value.data(), HOUR)
+            .iter()
+            .map(|c| *c.owner())
+            .collect::<Vec<_>>();
+        let (alice, bob, mallory) = (keypair(1), keypair(2), keypair(3));
+        let claim = |kp: &KeyPair, seq: i32| NameRecord::new("alice", &Id::random(), seq, 10 * HOUR, kp).unwrap();
+
+        let held = name_record::merge_claims(None, &name_value(&[claim(&alice, 0)], 0), HOUR).unwrap();
+        assert!(held.is_valid());
+        assert_eq!(held.id(), name_id("alice").unwrap());
+        assert_eq!(owners(&held), vec![Id::from(alice.public_key())]);
+
+        // Registered concurrently, each from the claims found before.
+        let held = name_record::merge_claims(Some(&held), &name_value(&[claim(&bob, 0)], 0), HOUR).unwrap();
+        assert!(held.is_valid());
+        assert_eq!(owners(&held), vec![Id::from(alice.public_key()), Id::from(bob.public_key())]);
+
+        // A third key can add its own claim, not drop the others nor replace them.
+        let wiped = SignedBuilder::world_writable(&NAME_NAMESPACE, "alice", b"wiped")
+            .with_sequence_number(10)
+            .build()
+            .unwrap();
+        let merged = name_record::merge_claims(Some(&held), &wiped, HOUR).unwrap();
+        assert_eq!(merged.sequence_number(), 10);
+        assert_eq!(merged.data(), held.data());
+
+        let forged = tampered(&claim(&mallory, 5), "o", CborValue::Bytes(Id::from(alice.public_key()).as_bytes().to_vec()));
+        let merged = name_record::merge_claims(Some(&held), &name_value(&[claim(&mallory, 0), forged], 11), HOUR).unwrap();
+        assert!(merged.is_valid());
+        assert_eq!(owners(&merged), vec![
+            Id::from(alice.public_key()), Id::from(bob.public_key()), Id::from(mallory.public_key())
+        ]);
+        assert_eq!(name_record::decode_claims("alice", merged.data(), HOUR)[0],
+            name_record::decode_claims("alice", held.data(), HOUR)[0]);
+
+        // Only the owners bump their claims, an older one is ignored.
+        let bumped = claim(&alice, 1);
+        let merged = name_record::merge_claims(Some(&held), &name_value(&[bumped.clone()], 1), HOUR).unwrap();
+        assert_eq!(name_record::decode_claims("alice", merged.data(), HOUR)[0], bumped);
+        let again = name_record::merge_claims(Some(&merged), &held, HOUR).unwrap();
+        assert_eq!(again.data(), merged.data());
+
+        // Expired claims go away, the values of anything else are not merged.
+        assert!(name_record::merge_claims(Some(&held), &wiped, 10 * HOUR).is_none());
+        assert!(name_record::merge_claims(None, &wiped, HOUR).is_none());
+        let other = SignedBuilder::new(&name_record::encode_claims(&[claim(&alice, 0)]).unwrap()).build().unwrap();
+        assert!(name_record::merge_claims(None, &other, HOUR).is_none());
+        assert!(name_record::merge_claims(Some(&other), &other, HOUR).is_none());
+    }
+}
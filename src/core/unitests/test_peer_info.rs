@@ -1,11 +1,14 @@
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
 use crate::core::{
     Id,
     signature,
     CryptoIdentity,
     PeerInfo,
     PeerBuilder,
+    Network,
     signature::KeyPair,
+    peer_info::PeerFields,
 };
 
 #[cfg(test)]
@@ -50,7 +53,12 @@ mod tests {
             sig.clone(),
             fingerprint,
             endpoint.clone(),
-            extra.clone()
+            PeerFields {
+                extra: extra.clone(),
+                tags: vec!["v2".to_string()],
+                weight: 4,
+                announced: Some(1_700_000_000_000),
+            }
         );
 
         assert_eq!(peer.id(), &pk);
@@ -62,6 +70,9 @@ mod tests {
         assert_eq!(peer.fingerprint(), fingerprint);
         assert_eq!(peer.endpoint(), endpoint);
         assert_eq!(peer.extra_data(), extra.as_deref());
+        assert_eq!(peer.tags(), ["v2"]);
+        assert_eq!(peer.weight(), 4);
+        assert_eq!(peer.announced(), Some(1_700_000_000_000));
 
         assert!(!peer.has_private_key());
     }
@@ -99,8 +110,7 @@ mod tests {
         let node_identity = CryptoIdentity::from(node_kp);
         let node = Arc::new(Mutex::new(node_identity));
         let peer_kp = signature::KeyPair::random();
-        let mut nonce = vec![0u8; PeerInfo::NONCE_BYTES];
-        rand::fill(&mut nonce);
+        let nonce = crate::random_bytes(PeerInfo::NONCE_BYTES);
         let rc = PeerBuilder::new(endpoint)
             .with_key(peer_kp.clone())
             .with_nonce(&nonce)
@@ -122,4 +132,197 @@ mod tests {
         assert_eq!(peer.endpoint(), des.endpoint());
         assert_eq!(peer.fingerprint(), des.fingerprint());
     }
+
+    #[test]
+    fn test_announced_time() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_000);
+        let peer = PeerBuilder::new("tcp://10.0.0.1:9000")
+            .with_announced_time(time)
+            .build()
+            .unwrap();
+        assert_eq!(peer.announced_time(), Some(time));
+        assert!(peer.is_valid());
+
+        // The time is signed, neither moving nor stripping it passes.
+        let ser = serde_cbor::to_vec(&peer).unwrap();
+        let mut value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 10);
+        value[9] = serde_cbor::Value::Integer(1_700_000_000_001);
+        let moved: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert!(!moved.is_valid());
+        value.pop();
+        let stripped: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert_eq!(stripped.announced_time(), None);
+        assert!(!stripped.is_valid());
+
+        let restamped = peer.restamped(1_800_000_000_000).unwrap();
+        assert!(restamped.is_valid());
+        assert_eq!(restamped.announced(), Some(1_800_000_000_000));
+        assert_eq!(restamped.sequence_number(), peer.sequence_number());
+        assert_ne!(restamped.signature(), peer.signature());
+        assert!(peer.without_private_key().restamped(1_800_000_000_000).is_err());
+
+        // Updates are signed at the time they are made.
+        let updated = peer.update("tcp://10.0.0.2:9000", None, None).unwrap();
+        assert!(updated.announced_time().unwrap() > time);
+    }
+
+    #[test]
+    fn test_serde_untimed() {
+        let peer = PeerBuilder::new("tcp://10.0.0.1:9000")
+            .with_extra(b"extra")
+            .build()
+            .unwrap()
+            .untimed()
+            .unwrap();
+        assert!(peer.is_valid());
+
+        // Announcements of older versions keep their 9 elements.
+        let ser = serde_cbor::to_vec(&peer).unwrap();
+        let value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 9);
+
+        let des: PeerInfo = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(des.announced_time(), None);
+        assert_eq!(des, peer.without_private_key());
+        assert!(des.is_valid());
+    }
+
+    #[test]
+    fn test_tags() {
+        let peer = PeerBuilder::new("tcp://10.0.0.1:9000")
+            .with_tags(&["proto/2", "tls", "tls"])
+            .build()
+            .unwrap();
+        assert_eq!(peer.tags(), ["proto/2", "tls"]);
+        assert!(peer.has_tags(&["tls", "proto/2"]));
+        assert!(peer.has_tags(&[]));
+        assert!(!peer.has_tags(&["tls", "proto/3"]));
+        assert!(peer.is_valid());
+
+        // The tags go last and are signed, neither changing nor stripping them passes.
+        let ser = serde_cbor::to_vec(&peer).unwrap();
+        let mut value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 11);
+        let des: PeerInfo = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(des, peer.without_private_key());
+        assert!(des.is_valid());
+
+        value[10] = serde_cbor::Value::Array(vec![serde_cbor::Value::Text("tls".into())]);
+        let changed: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert!(!changed.is_valid());
+        value.pop();
+        let stripped: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert!(stripped.tags().is_empty());
+        assert!(!stripped.is_valid());
+
+        // Tags without the time keep their place.
+        let untimed = peer.untimed().unwrap();
+        let des: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&untimed).unwrap()).unwrap();
+        assert_eq!(des.announced_time(), None);
+        assert_eq!(des.tags(), peer.tags());
+        assert!(des.is_valid());
+
+        // Kept through updates.
+        let updated = peer.update("tcp://10.0.0.2:9000", None, None).unwrap();
+        assert_eq!(updated.tags(), peer.tags());
+        assert!(updated.is_valid());
+    }
+
+    #[test]
+    fn test_weight() {
+        let peer = PeerBuilder::new("tcp://10.0.0.1:9000").build().unwrap();
+        assert_eq!(peer.weight(), PeerInfo::DEFAULT_WEIGHT);
+        let ser = serde_cbor::to_vec(&peer).unwrap();
+        let value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 10);
+
+        // The weight goes last and is signed.
+        let peer = PeerBuilder::new("tcp://10.0.0.1:9000")
+            .with_weight(4)
+            .build()
+            .unwrap();
+        assert_eq!(peer.weight(), 4);
+        assert!(peer.is_valid());
+
+        let ser = serde_cbor::to_vec(&peer).unwrap();
+        let mut value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 12);
+        let des: PeerInfo = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(des, peer.without_private_key());
+        assert!(des.is_valid());
+
+        value[11] = serde_cbor::Value::Integer(8);
+        let changed: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert_eq!(changed.weight(), 8);
+        assert!(!changed.is_valid());
+        value.pop();
+        let stripped: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+        assert_eq!(stripped.weight(), PeerInfo::DEFAULT_WEIGHT);
+        assert!(!stripped.is_valid());
+
+        // Kept through updates, along with the tags.
+        let tagged = PeerBuilder::new("tcp://10.0.0.1:9000")
+            .with_tags(&["tls"])
+            .with_weight(0)
+            .build()
+            .unwrap();
+        let updated = tagged.update("tcp://10.0.0.2:9000", None, None).unwrap();
+        assert_eq!(updated.weight(), 0);
+        assert_eq!(updated.tags(), ["tls"]);
+        let des: PeerInfo = serde_cbor::from_slice(&serde_cbor::to_vec(&updated).unwrap()).unwrap();
+        assert!(des.is_valid());
+    }
+
+    #[test]
+    fn test_observed() {
+        let peer = PeerBuilder::new("tcp://192.168.1.5:9000")
+            .with_tags(&["tls"])
+            .build()
+            .unwrap();
+        assert_eq!(peer.observed_endpoint(), None);
+
+        // Goes after the signed fields, the signature still holds.
+        let observed = peer.clone().with_observed(Some("tcp://203.0.113.7:9000".to_string()));
+        let ser = serde_cbor::to_vec(&observed).unwrap();
+        let value: Vec<serde_cbor::Value> = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(value.len(), 13);
+        let des: PeerInfo = serde_cbor::from_slice(&ser).unwrap();
+        assert_eq!(des.observed_endpoint(), Some("tcp://203.0.113.7:9000"));
+        assert_eq!(des.endpoint(), "tcp://192.168.1.5:9000");
+        assert_eq!(des.tags(), ["tls"]);
+        assert!(des.is_valid());
+
+        let stripped = des.with_observed(None);
+        assert_eq!(stripped, peer.without_private_key());
+        assert!(stripped.is_valid());
+    }
+
+    #[test]
+    fn test_tags_bounded() {
+        let tags: Vec<String> = (0..=PeerInfo::MAX_TAGS).map(|i| format!("t{i}")).collect();
+        let tags: Vec<&str> = tags.iter().map(|v| v.as_str()).collect();
+        assert!(PeerBuilder::new("tcp://10.0.0.1:9000").with_tags(&tags[..PeerInfo::MAX_TAGS]).build().is_ok());
+        assert!(PeerBuilder::new("tcp://10.0.0.1:9000").with_tags(&tags).build().is_err());
+
+        let long = "x".repeat(PeerInfo::MAX_TAG_BYTES + 1);
+        assert!(PeerBuilder::new("tcp://10.0.0.1:9000").with_tags(&[&long[1..]]).build().is_ok());
+        assert!(PeerBuilder::new("tcp://10.0.0.1:9000").with_tags(&[&long]).build().is_err());
+        assert!(PeerBuilder::new("tcp://10.0.0.1:9000").with_tags(&[""]).build().is_err());
+    }
+
+    #[test]
+    fn test_network() {
+        for (endpoint, network) in [
+            ("tcp://203.0.113.7:9000", Some(Network::IPv4)),
+            ("https://203.0.113.7:8443", Some(Network::IPv4)),
+            ("ssl://[2001:db8::1]:9000", Some(Network::IPv6)),
+            ("http://[2001:db8::1]:8080/peers", Some(Network::IPv6)),
+            ("wss://example.com/socket", None),
+            ("tcp://example.com:9000", None),
+        ] {
+            let peer = PeerBuilder::new(endpoint).build().unwrap();
+            assert_eq!(peer.network(), network, "{endpoint}");
+        }
+    }
 }
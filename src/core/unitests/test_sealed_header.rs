@@ -0,0 +1,39 @@
+use crate::core::crypto::{SealedHeader, HeaderError};
+
+const MAGIC: &[u8; SealedHeader::MAGIC_BYTES] = b"TEST";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let header = SealedHeader::new();
+        let mut data = Vec::new();
+        header.write(MAGIC, 1, &mut data);
+        assert_eq!(data.len(), SealedHeader::BYTES);
+
+        // Whatever follows the header is left to the blob
+        data.extend_from_slice(b"sealed");
+        assert_eq!(SealedHeader::parse(&data, MAGIC, 1), Ok(header));
+    }
+
+    #[test]
+    fn test_rejected() {
+        let mut data = Vec::new();
+        SealedHeader::new().write(MAGIC, 1, &mut data);
+
+        assert_eq!(SealedHeader::parse(&data[..SealedHeader::BYTES - 1], MAGIC, 1), Err(HeaderError::Incomplete));
+        assert_eq!(SealedHeader::parse(&data, b"NOPE", 1), Err(HeaderError::Incomplete));
+        assert_eq!(SealedHeader::parse(&data, MAGIC, 2), Err(HeaderError::UnsupportedVersion(1)));
+
+        // A crafted cost must not be taken
+        let mut costly = data.clone();
+        costly[5..9].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(SealedHeader::parse(&costly, MAGIC, 1), Err(HeaderError::UnsupportedCost));
+
+        let mut costly = data.clone();
+        costly[9..13].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert_eq!(SealedHeader::parse(&costly, MAGIC, 1), Err(HeaderError::UnsupportedCost));
+    }
+}
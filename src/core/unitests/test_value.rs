@@ -5,9 +5,32 @@ use crate::core::{
     SignedBuilder,
     EncryptedBuilder,
     signature,
-    cryptobox
+    cryptobox,
+    errors::{MalformedError, UnsupportedVersionError},
 };
 
+// Roughly what a contact card shared by QR code holds.
+fn contact_card() -> Vec<u8> {
+    let mut card = br#"{"name":"Alice Liddell","avatar":"https://example.com/a.png","#.to_vec();
+    card.extend_from_slice(br#""home":"did:boson:4833af415161cbd0a3ef83aa59a55fbadc9bd520a886a8ca214a3d09b6676cb8","#);
+    card.extend_from_slice(br#""note":"met at the conference"}"#);
+    card
+}
+
+// Flips, drops, inserts or overwrites a byte at random.
+fn mutate(data: &[u8]) -> Vec<u8> {
+    let mut data = data.to_vec();
+    let [pick, bit, byte, pos @ ..] = crate::random_array::<11>();
+    let at = u64::from_le_bytes(pos) as usize % data.len();
+    match pick % 4 {
+        0 => data[at] ^= 1 << (bit % 8),
+        1 => data.truncate(at),
+        2 => data.insert(at, byte),
+        _ => data[at] = byte,
+    }
+    data
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,4 +164,236 @@ mod tests {
         assert_eq!(des.sequence_number(), 55);
         assert_eq!(des.data(), val.data());
     }
+
+    #[test]
+    fn test_reencrypt_for() {
+        let data = crate::random_bytes(32);
+        let kp = signature::KeyPair::random();
+        let rec1 = signature::KeyPair::random();
+        let rec2 = signature::KeyPair::random();
+        let rec1_id: Id = rec1.public_key().into();
+        let rec2_id: Id = rec2.public_key().into();
+        let owner_pk = Id::from(kp.public_key()).to_encryption_key();
+
+        let v1 = EncryptedBuilder::new(&data, &rec1_id)
+            .with_keypair(&kp)
+            .with_sequence_number(7)
+            .build()
+            .unwrap();
+        let v2 = v1.reencrypt_for(&kp, &rec2_id).unwrap();
+
+        assert_eq!(v2.id(), v1.id());
+        assert_eq!(v2.public_key(), v1.public_key());
+        assert_eq!(v2.recipient(), Some(&rec2_id));
+        assert_eq!(v2.sequence_number(), 8);
+        assert_ne!(v2.nonce(), v1.nonce());
+        assert!(v2.is_valid());
+
+        let sk1 = cryptobox::PrivateKey::try_from(rec1.private_key()).unwrap();
+        let sk2 = cryptobox::PrivateKey::try_from(rec2.private_key()).unwrap();
+        assert_eq!(cryptobox::decrypt_into(v1.data(), &owner_pk, &sk1).unwrap(), data);
+        assert_eq!(cryptobox::decrypt_into(v2.data(), &owner_pk, &sk2).unwrap(), data);
+        assert!(cryptobox::decrypt_into(v2.data(), &owner_pk, &sk1).is_err());
+
+        // Only the owner keypair can re-address the value
+        let other = signature::KeyPair::random();
+        assert!(v1.reencrypt_for(&other, &rec2_id).is_err());
+        assert!(v1.reencrypt_for(&rec1, &rec2_id).is_err());
+
+        // The value must be encrypted and decryptable by its owner
+        let signed = SignedBuilder::new(&data).with_keypair(&kp).build().unwrap();
+        assert!(signed.reencrypt_for(&kp, &rec2_id).is_err());
+
+        let mut data = v1.data().to_vec();
+        data[0] ^= 0xff;
+        let tampered = Value::packed(
+            v1.public_key().cloned(),
+            v1.recipient().cloned(),
+            v1.nonce().cloned(),
+            v1.signature().map(|sig| sig.to_vec()),
+            data,
+            v1.sequence_number()
+        );
+        assert!(tampered.reencrypt_for(&kp, &rec2_id).is_err());
+    }
+
+    #[test]
+    fn test_world_writable() {
+        let ns = Id::try_from("0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+        let data = crate::random_bytes(32);
+
+        let val = SignedBuilder::world_writable(&ns, "profile", &data)
+            .with_sequence_number(3)
+            .build()
+            .unwrap();
+        assert_eq!(val.is_signed(), true);
+        assert_eq!(val.is_valid(), true);
+        assert_eq!(val.id(), Value::id_for_key(&ns, "profile"));
+        assert_eq!(val.public_key().unwrap().to_hexstr(),
+            "0x02f4e145251ae801c5869208e6bad6711ab8e1aa801ea8d456072a5047d74e57");
+        assert_eq!(val.id().to_hexstr(),
+            "0x5b254d6f0c5c8e35ba291be6e163e05aff996d908e19f3de26e45181e52d612b");
+
+        // Updated by anyone knowing the key.
+        let update = SignedBuilder::world_writable(&ns, "profile", b"updated")
+            .with_sequence_number(4)
+            .build()
+            .unwrap();
+        assert_eq!(update.id(), val.id());
+        assert_ne!(Value::id_for_key(&ns, "other"), val.id());
+    }
+
+    #[test]
+    fn test_countersign() {
+        let data = crate::random_bytes(32);
+        let service = signature::KeyPair::random();
+        let service_id = Id::from(service.public_key());
+        let val = SignedBuilder::new(&data)
+            .with_countersigner_slot(&service_id)
+            .with_sequence_number(2)
+            .build()
+            .unwrap();
+        assert_eq!(val.countersigner(), Some(&service_id));
+        assert_eq!(val.is_countersigned(), false);
+        assert_eq!(val.verify_countersignature(&service_id), false);
+
+        // Only the reserved service fills the slot.
+        assert!(val.countersign(&signature::KeyPair::random()).is_err());
+
+        let signed = val.countersign(&service).unwrap();
+        assert_eq!(signed.is_countersigned(), true);
+        assert_eq!(signed.is_valid(), true);
+        assert_eq!(signed.signature(), val.signature());
+        assert_eq!(signed.verify_countersignature(&service_id), true);
+        assert_eq!(signed.verify_countersignature(&Id::random()), false);
+
+        let ser = serde_cbor::to_vec(&signed).expect("Failed to serialize value");
+        let des: Value = serde_cbor::from_slice(&ser).expect("Failed to deserialize value");
+        assert_eq!(des.countersigner(), Some(&service_id));
+        assert_eq!(des.countersignature(), signed.countersignature());
+        assert_eq!(des.verify_countersignature(&service_id), true);
+
+        // Covers the owner signature and the payload.
+        let mut cs = signed.countersignature().unwrap().to_vec();
+        cs[0] ^= 0x01;
+        let tampered = des.clone().with_countersignature(Some(service_id), Some(cs));
+        assert_eq!(tampered.verify_countersignature(&service_id), false);
+
+        let other = SignedBuilder::new(&data)
+            .with_sequence_number(2)
+            .build()
+            .unwrap()
+            .with_countersignature(Some(service_id), signed.countersignature().map(|s| s.to_vec()));
+        assert_eq!(other.verify_countersignature(&service_id), false);
+    }
+
+    #[test]
+    fn test_countersign_without_slot() {
+        let service = signature::KeyPair::random();
+        let val = SignedBuilder::new(b"data").build().unwrap();
+        assert_eq!(val.countersigner(), None);
+
+        let signed = val.countersign(&service).unwrap();
+        assert_eq!(signed.verify_countersignature(&Id::from(service.public_key())), true);
+
+        // Immutable values carry no owner signature to cover.
+        let immutable = ValueBuilder::new(b"data").build().unwrap();
+        assert!(immutable.countersign(&service).is_err());
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let card = contact_card();
+        let owner = signature::KeyPair::random();
+        let service = signature::KeyPair::random();
+        let recipient = signature::KeyPair::random();
+        let recipient_id = Id::from(recipient.public_key());
+
+        let immutable = ValueBuilder::new(&card).build().unwrap();
+        let signed = SignedBuilder::new(&card)
+            .with_keypair(&owner)
+            .with_sequence_number(7)
+            .build()
+            .unwrap();
+        let countersigned = signed.countersign(&service).unwrap();
+        let encrypted = EncryptedBuilder::new(&card, &recipient_id)
+            .with_keypair(&owner)
+            .build()
+            .unwrap();
+
+        for value in [&immutable, &signed, &countersigned, &encrypted] {
+            let compact = value.to_compact_bytes();
+            // Fits a QR code with room to spare.
+            assert!(compact.len() < 500, "{} bytes", compact.len());
+            assert!(compact.len() < Vec::<u8>::from(value).len());
+
+            let decoded = Value::from_compact_bytes(&compact).unwrap();
+            assert_eq!(decoded.id(), value.id());
+            assert_eq!(decoded.sequence_number(), value.sequence_number());
+            assert_eq!(decoded.data(), value.data());
+            assert_eq!(decoded.private_key(), None);
+            assert_eq!(decoded.is_valid(), true);
+        }
+
+        let decoded = Value::from_compact_bytes(&countersigned.to_compact_bytes()).unwrap();
+        assert_eq!(decoded.verify_countersignature(&Id::from(service.public_key())), true);
+        let decoded = Value::from_compact_bytes(&encrypted.to_compact_bytes()).unwrap();
+        assert_eq!(decoded.decrypt(&recipient).unwrap(), card);
+    }
+
+    #[test]
+    fn test_compact_version() {
+        let value = SignedBuilder::new(b"data").build().unwrap();
+        let mut compact = value.to_compact_bytes();
+
+        compact[1] = 2;
+        let err = Value::from_compact_bytes(&compact).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnsupportedVersionError>().map(|e| e.version()), Some(2));
+
+        compact[1] = 0;
+        let err = Value::from_compact_bytes(&compact).unwrap_err();
+        assert!(err.downcast_ref::<MalformedError>().is_some());
+
+        // A compact id is no value.
+        let err = Value::from_compact_bytes(&Id::random().to_compact_bytes()).unwrap_err();
+        assert!(err.downcast_ref::<MalformedError>().is_some());
+    }
+
+    #[test]
+    fn test_compact_tampered() {
+        let value = SignedBuilder::new(b"data").build().unwrap();
+        let compact = value.to_compact_bytes();
+
+        // The last byte is the last byte of the data, the signature fails.
+        let mut tampered = compact.clone();
+        *tampered.last_mut().unwrap() ^= 0x01;
+        assert_eq!(Value::from_compact_bytes(&tampered).unwrap().is_valid(), false);
+
+        assert!(Value::from_compact_bytes(&compact[..compact.len() - 1]).is_err());
+        assert!(Value::from_compact_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_compact_mutated() {
+        let recipient = Id::from(signature::KeyPair::random().public_key());
+        let service = signature::KeyPair::random();
+        let values = [
+            ValueBuilder::new(b"data").build().unwrap(),
+            SignedBuilder::new(b"data").with_sequence_number(3).build().unwrap()
+                .countersign(&service).unwrap(),
+            EncryptedBuilder::new(b"data", &recipient).build().unwrap(),
+        ];
+
+        for value in values.iter() {
+            let compact = value.to_compact_bytes();
+            for _ in 0..5000 {
+                // Never panics, whatever it decodes to holds together.
+                if let Ok(decoded) = Value::from_compact_bytes(&mutate(&compact)) {
+                    let _ = decoded.is_valid();
+                    let _ = decoded.to_string();
+                    assert_eq!(Value::from_compact_bytes(&decoded.to_compact_bytes()).unwrap(), decoded);
+                }
+            }
+        }
+    }
 }
@@ -1,5 +1,12 @@
 use crate::core::version;
 
+// The versions of the Rust (Meerkat) and Java (Orca) nodes.
+const KNOWN: [(&str, i32, &str); 3] = [
+    ("MK", 4, "MK/4"),
+    ("MK", 0x0102, "MK/258"),
+    ("OR", 8, "OR/8"),
+];
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -7,27 +14,127 @@ mod tests {
     #[test]
     fn test_def_version() {
         let ver = version::ver();
-        let ver_str = version::format_version(ver);
-        assert_eq!(ver_str, "MK/1");
+        let ver_str = version::normalized_version(ver);
+        assert_eq!(ver_str, "MK/7");
+    }
+
+    #[test]
+    fn test_supports_peer_tags() {
+        assert!(version::supports_peer_tags(version::ver()));
+        assert!(version::supports_peer_tags(version::build("MK", 3)));
+        assert!(!version::supports_peer_tags(version::build("MK", 1)));
+        assert!(!version::supports_peer_tags(version::build("OR", 2)));
+        assert!(!version::supports_peer_tags(0));
+    }
+
+    #[test]
+    fn test_supports_countersignature() {
+        assert!(version::supports_countersignature(version::ver()));
+        assert!(!version::supports_countersignature(version::build("MK", 2)));
+        assert!(!version::supports_countersignature(version::build("OR", 3)));
+        assert!(!version::supports_countersignature(0));
+    }
+
+    #[test]
+    fn test_supports_peer_weight() {
+        assert!(version::supports_peer_weight(version::ver()));
+        assert!(!version::supports_peer_weight(version::build("MK", 3)));
+        assert!(!version::supports_peer_weight(version::build("OR", 4)));
+        assert!(!version::supports_peer_weight(0));
+    }
+
+    #[test]
+    fn test_supports_peer_network() {
+        assert!(version::supports_peer_network(version::ver()));
+        assert!(!version::supports_peer_network(version::build("MK", 4)));
+        assert!(!version::supports_peer_network(version::build("OR", 5)));
+        assert!(!version::supports_peer_network(0));
+    }
+
+    #[test]
+    fn test_supports_peer_announced() {
+        assert!(version::supports_peer_announced(version::ver()));
+        assert!(version::supports_peer_announced(version::build("MK", 2)));
+        assert!(!version::supports_peer_announced(version::build("MK", 1)));
+        assert!(!version::supports_peer_announced(version::build("OR", 2)));
+        assert!(!version::supports_peer_announced(0));
+    }
+
+    #[test]
+    fn test_supports_peer_observed() {
+        assert!(version::supports_peer_observed(version::ver()));
+        assert!(!version::supports_peer_observed(version::build("MK", 5)));
+        assert!(!version::supports_peer_observed(version::build("OR", 6)));
+        assert!(!version::supports_peer_observed(0));
+    }
+
+    #[test]
+    fn test_supports_capabilities() {
+        assert!(version::supports_capabilities(version::ver()));
+        assert!(!version::supports_capabilities(version::build("MK", 6)));
+        assert!(!version::supports_capabilities(version::build("OR", 7)));
+        assert!(!version::supports_capabilities(0));
     }
 
     #[test]
     fn test_mk_version() {
         let ver = version::build("MK", 5);
-        let ver_str = version::format_version(ver);
+        let ver_str = version::normalized_version(ver);
         assert_eq!(ver_str, "MK/5");
     }
 
     #[test]
     fn test_or_version() {
         let ver = version::build("OR", 8);
-        let ver_str = version::format_version(ver);
+        let ver_str = version::normalized_version(ver);
         assert_eq!(ver_str, "OR/8");
     }
 
     #[test]
     fn test_na_version() {
-        let ver_str = version::format_version(0);
+        let ver_str = version::normalized_version(0);
         assert_eq!(ver_str, "N/A");
     }
+
+    #[test]
+    fn test_known_versions() {
+        for (tag, number, expected) in KNOWN {
+            assert_eq!(version::normalized_version(version::build(tag, number)), expected);
+        }
+    }
+
+    #[test]
+    fn test_unknown_version() {
+        assert_eq!(version::normalized_version(0x01FF0004), "unknown(01ff0004)");
+        assert_eq!(version::normalized_version(-1), "unknown(ffffffff)");
+        assert_eq!(version::normalized_version(0x4D000001), "unknown(4d000001)");
+    }
+
+    #[test]
+    fn test_random_versions() {
+        for _ in 0..10_000 {
+            let ver = i32::from_le_bytes(crate::random_array());
+            let normalized = version::normalized_version(ver);
+            // Stable for the same field
+            assert_eq!(normalized, version::normalized_version(ver));
+            assert!(normalized.is_ascii() && !normalized.is_empty(), "{normalized}");
+            assert!(normalized == "N/A" ||
+                normalized.starts_with("unknown(") ||
+                normalized.split_once('/').is_some_and(|(tag, _)| tag.len() == 2)
+            );
+        }
+    }
+
+    #[test]
+    fn test_tally() {
+        let tally = version::tally([
+            version::build("MK", 4),
+            version::build("MK", 4),
+            version::build("OR", 8),
+            0,
+        ].into_iter());
+        assert_eq!(tally.get("MK/4"), Some(&2));
+        assert_eq!(tally.get("OR/8"), Some(&1));
+        assert_eq!(tally.get("N/A"), Some(&1));
+    }
 }
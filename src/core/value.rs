@@ -8,16 +8,30 @@ use serde::{
     de::{self, Visitor, MapAccess}
 };
 
+use serde_cbor::Value as CborValue;
+
 use super::{
     Id,
+    compact,
     cryptobox,
     signature,
-    signature::{KeyPair, PrivateKey},
+    signature::{KeyPair, PrivateKey, Signature},
     cryptobox::Nonce,
+    Error,
     Result,
-    errors::ArgumentError
+    errors::{ArgumentError, CryptoError, MalformedError}
 };
 
+// The fields present in the compact form, in their order there. The data
+// always comes last.
+const COMPACT_KEY           : u8 = 0x01;
+const COMPACT_RECIPIENT     : u8 = 0x02;
+const COMPACT_NONCE         : u8 = 0x04;
+const COMPACT_SIGNATURE     : u8 = 0x08;
+const COMPACT_COUNTERSIGNER : u8 = 0x10;
+const COMPACT_COUNTERSIG    : u8 = 0x20;
+const COMPACT_SEQ           : u8 = 0x40;
+
 #[derive(Clone)]
 pub struct ImmutableBuilder<'a> {
     data: &'a [u8],
@@ -39,7 +53,9 @@ impl<'a> ImmutableBuilder<'a> {
 #[derive(Clone)]
 pub struct SignedBuilder<'a> {
     keypair: Option<&'a KeyPair>,
+    derived: Option<KeyPair>,
     nonce: Option<&'a Nonce>,
+    countersigner: Option<&'a Id>,
 
     data: &'a [u8],
     seq: i32,
@@ -50,11 +66,25 @@ impl<'a> SignedBuilder<'a> {
         Self {
             data,
             keypair: None,
+            derived: None,
             nonce: None,
+            countersigner: None,
             seq: 0,
         }
     }
 
+    /// A world-writable value stored under the id of `key` in `namespace`,
+    /// see [`Value::id_for_key`]. It is signed with
+    /// [`Value::world_writable_keypair`], which anyone knowing the namespace
+    /// and the key can compute: whoever knows them can replace the value,
+    /// nothing ties it to who stored it first. Sign with a key pair of your
+    /// own for a value only you can update.
+    pub fn world_writable(namespace: &Id, key: &str, data: &'a [u8]) -> Self {
+        let mut builder = Self::new(data);
+        builder.derived = Some(Value::world_writable_keypair(namespace, key));
+        builder
+    }
+
     pub fn with_keypair(&mut self, keypair: &'a KeyPair) -> &mut Self {
         self.keypair = Some(keypair);
         self
@@ -70,6 +100,13 @@ impl<'a> SignedBuilder<'a> {
         self
     }
 
+    /// Reserves the counter-signature of the value for `service`, only its
+    /// key pair can then [`Value::countersign`] the built value.
+    pub fn with_countersigner_slot(&mut self, service: &'a Id) -> &mut Self {
+        self.countersigner = Some(service);
+        self
+    }
+
     pub fn build(&self) -> Result<Value> {
         if self.data.is_empty() {
             return Err(ArgumentError::new("Value data cannot be empty"));
@@ -131,6 +168,8 @@ pub struct Value {
     sig: Option<Vec<u8>>,
     data: Vec<u8>,
     seq: i32,
+    countersigner: Option<Id>,
+    countersig: Option<Vec<u8>>,
 }
 
 impl Value {
@@ -145,13 +184,15 @@ impl Value {
             sig: None,
             data: b.data.to_vec(),
             seq: 0,
+            countersigner: None,
+            countersig: None,
         }
     }
 
     fn signed(b: &SignedBuilder) -> Result<Value> {
         assert!(!b.data.is_empty());
 
-        let kp = match b.keypair.as_ref() {
+        let kp = match b.keypair.or(b.derived.as_ref()) {
             Some(v) => v,
             None => &KeyPair::random()
         };
@@ -162,7 +203,9 @@ impl Value {
             nonce: Some(b.nonce.map_or(Nonce::random(), |v|v.clone())),
             sig: None,
             data: b.data.to_vec(),
-            seq: b.seq
+            seq: b.seq,
+            countersigner: b.countersigner.cloned(),
+            countersig: None,
         };
 
         // sign data.
@@ -191,18 +234,20 @@ impl Value {
             sk: Some(kp.to_private_key()),
             recipient: Some(b.rec.clone()),
             nonce: Some(b.nonce.map_or(Nonce::random(), |v|v.clone())),
-            data: b.data.to_vec(),
+            data: Vec::new(),
             sig: None,
             seq: b.seq,
+            countersigner: None,
+            countersig: None,
         };
 
         let encryption_sk = cryptobox::PrivateKey::try_from(
             value.sk.as_ref().unwrap()
         )?;
 
-        // encrypt data.
+        // encrypt data, the plaintext is never copied into the value.
         value.data = cryptobox::encrypt_into(
-            value.data.as_ref(),
+            b.data,
             value.nonce.as_ref().unwrap(),
             &value.recipient.as_ref().unwrap().to_encryption_key(),
             &encryption_sk,
@@ -233,7 +278,116 @@ impl Value {
             sig,
             data,
             seq,
+            countersigner: None,
+            countersig: None,
+        }
+    }
+
+    // The counter-signature travels next to the owner-signed fields, it is
+    // attached once they are unpacked.
+    pub(crate) fn with_countersignature(mut self, signer: Option<Id>, sig: Option<Vec<u8>>) -> Self {
+        self.countersigner = signer;
+        self.countersig = sig;
+        self
+    }
+
+    /// Counter-signs the value with the key pair of a service approving it,
+    /// over the owner signature and the payload it covers. The value must
+    /// be signed by its owner and, if it has a countersigner slot, the key
+    /// pair must be the reserved one. Any previous counter-signature is
+    /// replaced.
+    pub fn countersign(&self, keypair: &KeyPair) -> Result<Value> {
+        if !self.is_signed() {
+            return Err(ArgumentError::new("Value is not signed by its owner"));
+        }
+        if !self.is_valid() {
+            return Err(ArgumentError::new("Value owner signature is invalid"));
         }
+        let signer = Id::from(keypair.public_key());
+        if let Some(slot) = self.countersigner.as_ref() {
+            if slot != &signer {
+                return Err(ArgumentError::new("Keypair is not the countersigner of the value"));
+            }
+        }
+
+        let sig = signature::sign_into(
+            self.serialize_countersignature_data().as_slice(),
+            keypair.private_key()
+        )?;
+        let mut value = self.clone();
+        value.countersigner = Some(signer);
+        value.countersig = Some(sig);
+        Ok(value)
+    }
+
+    /// Whether the value is validly signed by its owner and counter-signed
+    /// by `expected_signer`.
+    pub fn verify_countersignature(&self, expected_signer: &Id) -> bool {
+        let (Some(signer), Some(sig)) = (self.countersigner.as_ref(), self.countersig.as_ref()) else {
+            return false;
+        };
+        if signer != expected_signer || !self.is_signed() || !self.is_valid() {
+            return false;
+        }
+
+        signature::verify(
+            self.serialize_countersignature_data().as_slice(),
+            sig.as_slice(),
+            &signer.to_signature_key(),
+        ).unwrap_or(false)
+    }
+
+    // Re-addresses an encrypted value to a new recipient under the next
+    // sequence number. The plaintext only lives inside this call and is
+    // wiped before returning.
+    pub fn reencrypt_for(&self, owner: &KeyPair, new_recipient: &Id) -> Result<Value> {
+        let Some(recipient) = self.recipient.as_ref() else {
+            return Err(ArgumentError::new("Value is not encrypted"));
+        };
+        if self.pk.as_ref() != Some(&Id::from(owner.public_key())) {
+            return Err(ArgumentError::new("Keypair is not the owner of the value"));
+        }
+        let Some(seq) = self.seq.checked_add(1) else {
+            return Err(ArgumentError::new("Value sequence number is exhausted"));
+        };
+        if self.data.len() < cryptobox::CryptoBox::MAC_BYTES + Nonce::BYTES {
+            return Err(CryptoError::new("Value data is too short to be decrypted"));
+        }
+
+        let encryption_sk = cryptobox::PrivateKey::try_from(owner.private_key())?;
+        let mut plain = cryptobox::decrypt_into(
+            self.data.as_slice(),
+            &recipient.to_encryption_key(),
+            &encryption_sk,
+        ).map_err(|e| CryptoError::new(format!("Value can not be decrypted by its owner: {e}")))?;
+
+        let result = EncryptedBuilder::new(&plain, new_recipient)
+            .with_keypair(owner)
+            .with_sequence_number(seq)
+            .build();
+        plain.fill(0);
+        result
+    }
+
+    /// Decrypts the data of an encrypted value with the key pair of its
+    /// recipient.
+    pub fn decrypt(&self, recipient: &KeyPair) -> Result<Vec<u8>> {
+        let (Some(pk), Some(rec)) = (self.pk.as_ref(), self.recipient.as_ref()) else {
+            return Err(ArgumentError::new("Value is not encrypted"));
+        };
+        if rec != &Id::from(recipient.public_key()) {
+            return Err(ArgumentError::new("Keypair is not the recipient of the value"));
+        }
+        if self.data.len() < cryptobox::CryptoBox::MAC_BYTES + Nonce::BYTES {
+            return Err(CryptoError::new("Value data is too short to be decrypted"));
+        }
+
+        let encryption_sk = cryptobox::PrivateKey::try_from(recipient.private_key())?;
+        cryptobox::decrypt_into(
+            self.data.as_slice(),
+            &pk.to_encryption_key(),
+            &encryption_sk,
+        ).map_err(|e| CryptoError::new(format!("Value can not be decrypted by its recipient: {e}")).into())
     }
 
     pub fn id(&self) -> Id {
@@ -249,6 +403,24 @@ impl Value {
         }).unwrap()
     }
 
+    /// The key pair signing the world-writable value of `key` in
+    /// `namespace`, seeded with [`Id::derive`] of them. Stable like the
+    /// derivation itself, and no secret to anyone knowing them.
+    pub fn world_writable_keypair(namespace: &Id, key: &str) -> KeyPair {
+        KeyPair::try_from_seed(Id::derive(namespace, key).as_bytes()).unwrap()
+    }
+
+    /// The id of the value of `key` in `namespace`, as built by
+    /// [`SignedBuilder::world_writable`].
+    pub fn id_for_key(namespace: &Id, key: &str) -> Id {
+        let pk = Id::from(Self::world_writable_keypair(namespace, key).public_key());
+        Id::try_from({
+            let mut sha256 = Sha256::new();
+            sha256.update(pk.as_bytes());
+            sha256.finalize().as_slice()
+        }).unwrap()
+    }
+
     pub const fn public_key(&self) -> Option<&Id> {
         self.pk.as_ref()
     }
@@ -265,6 +437,15 @@ impl Value {
         self.sk.as_ref()
     }
 
+    pub(crate) fn set_private_key(&mut self, sk: PrivateKey) -> Result<()> {
+        let pk = Id::from(KeyPair::from(&sk).public_key());
+        if self.pk.as_ref() != Some(&pk) {
+            return Err(ArgumentError::new("Private key does not match the value public key"));
+        }
+        self.sk = Some(sk);
+        Ok(())
+    }
+
     pub const fn sequence_number(&self) -> i32 {
         self.seq
     }
@@ -281,9 +462,22 @@ impl Value {
         self.data.as_slice()
     }
 
+    pub const fn countersigner(&self) -> Option<&Id> {
+        self.countersigner.as_ref()
+    }
+
+    pub fn countersignature(&self) -> Option<&[u8]> {
+        self.countersig.as_deref()
+    }
+
+    pub const fn is_countersigned(&self) -> bool {
+        self.countersig.is_some()
+    }
+
     pub fn size(&self) -> usize {
         self.data.len() +
-            self.sig.as_ref().map_or(0, |s|s.len())
+            self.sig.as_ref().map_or(0, |s|s.len()) +
+            self.countersig.as_ref().map_or(0, |s|s.len())
     }
 
     pub const fn is_encrypted(&self) -> bool {
@@ -315,7 +509,111 @@ impl Value {
             self.serialize_signature_data().as_slice(),
             self.sig.as_ref().unwrap().as_slice(),
             &self.pk.as_ref().unwrap().to_signature_key(),
-        ).is_ok()
+        ).unwrap_or(false)
+    }
+
+    /// Encodes the value into its compact form, for sharing it out of band
+    /// like in a QR code: a two byte header, then a CBOR array of the fields
+    /// present, in a fixed order behind a bitmap of them. The private key is
+    /// left out, as in the CBOR form.
+    pub fn to_compact_bytes(&self) -> Vec<u8> {
+        let mut flags = 0u8;
+        let mut fields = vec![CborValue::Null];
+        let mut push = |flag: u8, bytes: Option<&[u8]>| {
+            if let Some(bytes) = bytes {
+                flags |= flag;
+                fields.push(CborValue::Bytes(bytes.to_vec()));
+            }
+        };
+
+        push(COMPACT_KEY, self.pk.as_ref().map(|pk| pk.as_bytes()));
+        push(COMPACT_RECIPIENT, self.recipient.as_ref().map(|rec| rec.as_bytes()));
+        push(COMPACT_NONCE, self.nonce.as_ref().map(|n| n.as_bytes()));
+        push(COMPACT_SIGNATURE, self.sig.as_deref());
+        push(COMPACT_COUNTERSIGNER, self.countersigner.as_ref().map(|csk| csk.as_bytes()));
+        push(COMPACT_COUNTERSIG, self.countersig.as_deref());
+        if self.seq != 0 {
+            flags |= COMPACT_SEQ;
+            fields.push(CborValue::Integer(self.seq as i128));
+        }
+        fields.push(CborValue::Bytes(self.data.clone()));
+        fields[0] = CborValue::Integer(flags as i128);
+
+        let mut data = compact::header(compact::KIND_VALUE);
+        serde_cbor::to_writer(&mut data, &CborValue::Array(fields)).unwrap();
+        data
+    }
+
+    /// Decodes a value from its compact form. Fails with an
+    /// UnsupportedVersionError for a newer format version and a
+    /// MalformedError for anything else unexpected. The signatures are not
+    /// checked here, see [`Value::is_valid`].
+    pub fn from_compact_bytes(data: &[u8]) -> Result<Value> {
+        let malformed = |what: &str| -> Error {
+            MalformedError::new(format!("Invalid compact value: {what}"))
+        };
+
+        let body = compact::body(data, compact::KIND_VALUE)?;
+        let fields = match serde_cbor::from_slice::<CborValue>(body) {
+            Ok(CborValue::Array(fields)) => fields,
+            Ok(_) => return Err(malformed("not an array")),
+            Err(e) => return Err(malformed(&e.to_string())),
+        };
+
+        let mut fields = fields.into_iter();
+        let flags = match fields.next() {
+            Some(CborValue::Integer(flags)) if (0..=0x7f).contains(&flags) => flags as u8,
+            _ => return Err(malformed("invalid field flags")),
+        };
+        let mut take = |flag: u8, len: usize, name: &str| -> Result<Option<Vec<u8>>> {
+            if flags & flag == 0 {
+                return Ok(None);
+            }
+            match fields.next() {
+                Some(CborValue::Bytes(bytes)) if bytes.len() == len => Ok(Some(bytes)),
+                _ => Err(malformed(&format!("invalid {name}"))),
+            }
+        };
+
+        let pk = take(COMPACT_KEY, Id::BYTES, "public key")?;
+        let recipient = take(COMPACT_RECIPIENT, Id::BYTES, "recipient")?;
+        let nonce = take(COMPACT_NONCE, Nonce::BYTES, "nonce")?;
+        let sig = take(COMPACT_SIGNATURE, Signature::BYTES, "signature")?;
+        let countersigner = take(COMPACT_COUNTERSIGNER, Id::BYTES, "countersigner")?;
+        let countersig = take(COMPACT_COUNTERSIG, Signature::BYTES, "countersignature")?;
+
+        let seq = match flags & COMPACT_SEQ != 0 {
+            true => match fields.next() {
+                Some(CborValue::Integer(seq)) => i32::try_from(seq)
+                    .map_err(|_| malformed("sequence number out of range"))?,
+                _ => return Err(malformed("invalid sequence number")),
+            },
+            false => 0,
+        };
+        let data = match fields.next() {
+            Some(CborValue::Bytes(data)) if !data.is_empty() => data,
+            _ => return Err(malformed("invalid data")),
+        };
+        if fields.next().is_some() {
+            return Err(malformed("trailing fields"));
+        }
+
+        // Everything but the data belongs to a mutable value, which comes
+        // with its nonce and signature.
+        let mutable = flags & (COMPACT_KEY | COMPACT_NONCE | COMPACT_SIGNATURE);
+        if flags & !COMPACT_SEQ != 0 && mutable != (COMPACT_KEY | COMPACT_NONCE | COMPACT_SIGNATURE) {
+            return Err(malformed("incomplete mutable value"));
+        }
+
+        let id = |bytes: Vec<u8>| Id::try_from(bytes.as_slice());
+        Ok(Value::packed(
+            pk.map(id).transpose()?,
+            recipient.map(id).transpose()?,
+            nonce.map(|n| Nonce::try_from(n.as_slice())).transpose()?,
+            sig,
+            data,
+            seq,
+        ).with_countersignature(countersigner.map(id).transpose()?, countersig))
     }
 
     pub(crate) fn serialize_signature_data(&self) -> Vec<u8> {
@@ -332,6 +630,13 @@ impl Value {
         sha256.update(self.data.as_slice());
         sha256.finalize().to_vec()
     }
+
+    // sig2 = sign(owner_sig || payload)
+    fn serialize_countersignature_data(&self) -> Vec<u8> {
+        let mut data = self.sig.clone().unwrap_or_default();
+        data.extend_from_slice(self.serialize_signature_data().as_slice());
+        data
+    }
 }
 
 impl fmt::Display for Value {
@@ -356,6 +661,12 @@ impl fmt::Display for Value {
                 hex::encode(self.sig.as_ref().unwrap())
             )?;
         }
+        if let Some(signer) = self.countersigner.as_ref() {
+            write!(f, ",countersigner:{}", signer)?;
+        }
+        if let Some(sig) = self.countersig.as_ref() {
+            write!(f, ",countersig:{}", hex::encode(sig))?;
+        }
         write!(f,
             ", seq:{}, data:{}",
             self.seq,
@@ -369,6 +680,23 @@ pub fn value_id(value: &Value) -> Id {
     value.id()
 }
 
+// Decodes a value from its CBOR form, as stored and sent around.
+impl TryFrom<&[u8]> for Value {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(data).map_err(|e| {
+            ArgumentError::new(format!("Failed to parse Value from bytes: {}", e)).into()
+        })
+    }
+}
+
+impl From<&Value> for Vec<u8> {
+    fn from(value: &Value) -> Self {
+        serde_cbor::to_vec(value).unwrap()
+    }
+}
+
 impl Serialize for Value {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -379,6 +707,8 @@ impl Serialize for Value {
         if self.recipient.is_some() { len += 1; }
         if self.nonce.is_some() { len += 1; }
         if self.sig.is_some() { len += 1; }
+        if self.countersigner.is_some() { len += 1; }
+        if self.countersig.is_some() { len += 1; }
 
         let mut state = serializer.serialize_struct("Value", len)?;
 
@@ -394,6 +724,12 @@ impl Serialize for Value {
         if let Some(s) = &self.sig {
              state.serialize_field("s", s)?;
         }
+        if let Some(csk) = &self.countersigner {
+            state.serialize_field("csk", csk)?;
+        }
+        if let Some(cs) = &self.countersig {
+            state.serialize_field("cs", cs)?;
+        }
 
         state.serialize_field("seq", &self.seq)?;
         state.serialize_field("v", &self.data)?;
@@ -413,6 +749,8 @@ impl<'de> Deserialize<'de> for Value {
             Recipient,      // "rec"
             Nonce,          // "n"
             Signature,      // "s"
+            Countersigner,  // "csk"
+            Countersig,     // "cs"
             SequenceNumber, // "seq"
             Data,           // "v"
         }
@@ -428,9 +766,11 @@ impl<'de> Deserialize<'de> for Value {
                     "rec"   => Ok(Field::Recipient),
                     "n"     => Ok(Field::Nonce),
                     "s"     => Ok(Field::Signature),
+                    "csk"   => Ok(Field::Countersigner),
+                    "cs"    => Ok(Field::Countersig),
                     "seq"   => Ok(Field::SequenceNumber),
                     "v"     => Ok(Field::Data),
-                    _ => Err(de::Error::unknown_field(&key, &["k", "rec", "n", "s", "seq", "v"])),
+                    _ => Err(de::Error::unknown_field(&key, &["k", "rec", "n", "s", "csk", "cs", "seq", "v"])),
                 }
             }
         }
@@ -452,6 +792,8 @@ impl<'de> Deserialize<'de> for Value {
                 let mut recipient: Option<Id> = None;
                 let mut raw_nonce: Option<Vec<u8>> = None;
                 let mut sig: Option<Vec<u8>> = None;
+                let mut countersigner: Option<Id> = None;
+                let mut countersig: Option<Vec<u8>> = None;
                 let mut seq: i32 = 0;
                 let mut data: Option<Vec<u8>> = None;
 
@@ -461,6 +803,8 @@ impl<'de> Deserialize<'de> for Value {
                         Field::Recipient        => recipient = Some(map.next_value()?),
                         Field::Nonce            => raw_nonce = Some(map.next_value()?),
                         Field::Signature        => sig = Some(map.next_value()?),
+                        Field::Countersigner    => countersigner = Some(map.next_value()?),
+                        Field::Countersig       => countersig = Some(map.next_value()?),
                         Field::SequenceNumber   => seq = map.next_value()?,
                         Field::Data             => data = Some(map.next_value()?),
                     }
@@ -478,7 +822,8 @@ impl<'de> Deserialize<'de> for Value {
                 };
 
                 let data = data.ok_or_else(|| de::Error::missing_field("v"))?;
-                Ok(Value::packed(pk, recipient, nonce, sig, data, seq))
+                Ok(Value::packed(pk, recipient, nonce, sig, data, seq)
+                    .with_countersignature(countersigner, countersig))
             }
         }
         deserializer.deserialize_map(ValueVisitor)
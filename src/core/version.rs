@@ -1,8 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use once_cell::sync::Lazy;
 
 pub(crate) const NODE_TAG_NAME: &str = "MK";
-pub(crate) const NODE_VERSION: i32 = 1;
+pub(crate) const NODE_VERSION: i32 = 7;
+
+// The first version filtering peers by their tags when asked to.
+const PEER_TAGS_VERSION: i32 = 2;
+// The first version decoding the announcement time of peers.
+const PEER_ANNOUNCED_VERSION: i32 = 2;
+// The first version decoding counter-signed values in responses.
+const COUNTERSIGNATURE_VERSION: i32 = 3;
+// The first version decoding weighted peers.
+const PEER_WEIGHT_VERSION: i32 = 4;
+// The first version filtering peers by the network of their endpoint.
+const PEER_NETWORK_VERSION: i32 = 5;
+// The first version decoding the observed endpoint of a peer.
+const PEER_OBSERVED_VERSION: i32 = 6;
+// The first version decoding the capabilities a node advertises.
+const CAPABILITIES_VERSION: i32 = 7;
 
 #[allow(unused)]
 static NAMES: Lazy<HashMap<String, String>> = Lazy::new(|| {
@@ -16,6 +31,53 @@ pub(crate) fn ver() -> i32 {
     build(NODE_TAG_NAME, NODE_VERSION)
 }
 
+// Whether a node of the version filters peers by tags and can decode
+// tagged peers. Other implementations are assumed not to.
+pub(crate) fn supports_peer_tags(ver: i32) -> bool {
+    is_at_least(ver, PEER_TAGS_VERSION)
+}
+
+// Whether a node of the version can decode the announcement time of a
+// peer, older ones reject the whole message carrying one.
+pub(crate) fn supports_peer_announced(ver: i32) -> bool {
+    is_at_least(ver, PEER_ANNOUNCED_VERSION)
+}
+
+// Whether a node of the version accepts the counter-signature of a value
+// in a find value response, older ones reject the unknown fields.
+pub(crate) fn supports_countersignature(ver: i32) -> bool {
+    is_at_least(ver, COUNTERSIGNATURE_VERSION)
+}
+
+// Whether a node of the version can decode the weight of a peer, older
+// ones lose the whole response carrying one.
+pub(crate) fn supports_peer_weight(ver: i32) -> bool {
+    is_at_least(ver, PEER_WEIGHT_VERSION)
+}
+
+// Whether a node of the version filters peers by the network of their
+// endpoint, older ones reject the request asking for it.
+pub(crate) fn supports_peer_network(ver: i32) -> bool {
+    is_at_least(ver, PEER_NETWORK_VERSION)
+}
+
+// Whether a node of the version can decode the observed endpoint of a
+// peer, older ones lose the whole response carrying one.
+pub(crate) fn supports_peer_observed(ver: i32) -> bool {
+    is_at_least(ver, PEER_OBSERVED_VERSION)
+}
+
+// Whether a node of the version accepts the capabilities in a message,
+// older ones reject the whole message carrying them.
+pub(crate) fn supports_capabilities(ver: i32) -> bool {
+    is_at_least(ver, CAPABILITIES_VERSION)
+}
+
+fn is_at_least(ver: i32, number: i32) -> bool {
+    let name = ((ver as u32) >> 16) as u16;
+    name.to_be_bytes() == NODE_TAG_NAME.as_bytes() && (ver & 0x0000FFFF) >= number
+}
+
 // Build a version from the software name and version number.
 pub(crate) fn build(short_name: &str, ver: i32) -> i32 {
     let bytes = short_name.as_bytes();
@@ -23,23 +85,27 @@ pub(crate) fn build(short_name: &str, ver: i32) -> i32 {
     (bytes[1] as u32) << 16 | (ver as u32) & 0x000000FF) as i32
 }
 
-pub(crate) fn format_version(ver: i32) -> String {
-    let ver = ver as u32;
+// The readable form of a version field, "MK/4" for the software tag and
+// version number it was built from. Fields not following that layout, as
+// sent by some non-conforming clients, show as "unknown(<hex>)".
+pub(crate) fn normalized_version(ver: i32) -> String {
     if ver == 0 {
         return String::from("N/A");
     }
 
-    let mut bytes = vec![0u8; 2];
-    bytes[0] = (ver >> 24) as u8;
-    bytes[1] = ((ver & 0x00FF0000) >> 16) as u8;
-
-    let n: String = bytes.iter().map(|&b| b as char).collect();
-    let v = (ver & 0x0000FFFF).to_string();
+    let bytes = (ver as u32).to_be_bytes();
+    let tag = &bytes[..2];
+    match tag.iter().all(|b| b.is_ascii_alphanumeric()) {
+        true  => format!("{}{}/{}", tag[0] as char, tag[1] as char, ver as u32 & 0x0000FFFF),
+        false => format!("unknown({})", hex::encode(bytes)),
+    }
+}
 
-    let mut str = String::new();
-    //str.push_str(NAMES.get(&n).map_or(NAMES.get("MK"), |v|Some(v)).unwrap());
-    str.push_str(n.as_str());
-    str.push_str("/");
-    str.push_str(&v);
-    str
+// Number of nodes running each normalized version.
+pub(crate) fn tally(versions: impl Iterator<Item = i32>) -> BTreeMap<String, usize> {
+    let mut tally = BTreeMap::new();
+    for ver in versions {
+        *tally.entry(normalized_version(ver)).or_insert(0) += 1;
+    }
+    tally
 }
@@ -0,0 +1,237 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+use crate::{
+    Id,
+    NodeInfo,
+    Capabilities,
+    core::version,
+};
+
+/// A node found by the crawl, with the round trip time of its fastest
+/// answer if it answered at all.
+#[derive(Debug, Clone)]
+pub struct CrawledNode {
+    node    : NodeInfo,
+    rtt     : Option<Duration>,
+}
+
+impl CrawledNode {
+    pub(crate) fn new(node: NodeInfo, rtt: Option<Duration>) -> Self {
+        Self { node, rtt }
+    }
+
+    /// The node as it answered, with its version, or as others referred to
+    /// it, without one.
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
+    }
+
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    pub fn is_responsive(&self) -> bool {
+        self.rtt.is_some()
+    }
+}
+
+/// What a [`Crawler`](super::Crawler) found out about the network.
+#[derive(Debug, Clone)]
+pub struct CrawlReport {
+    nodes           : Vec<CrawledNode>,
+    unique_nodes    : usize,
+    queried         : usize,
+    requests        : usize,
+    versions        : BTreeMap<String, usize>,
+    capabilities    : BTreeMap<String, usize>,
+    addresses       : BTreeMap<IpAddr, usize>,
+    birthday        : Option<f64>,
+    densest_prefix  : Option<f64>,
+    elapsed         : Duration,
+}
+
+impl CrawlReport {
+    // `references` counts how often each node was returned in the answers,
+    // `queried` the nodes asked at least once.
+    pub(crate) fn new(
+        nodes: Vec<CrawledNode>,
+        references: &HashMap<Id, usize>,
+        queried: usize,
+        requests: usize,
+        elapsed: Duration
+    ) -> Self {
+        let ids = nodes.iter()
+            .map(|n| *n.node.id())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let versions = version::tally(nodes.iter()
+            .filter(|n| n.is_responsive())
+            .map(|n| n.node.version())
+        );
+
+        let capabilities = Capabilities::tally(nodes.iter()
+            .filter(|n| n.is_responsive())
+            .map(|n| n.node.capabilities())
+        );
+
+        let mut addresses = BTreeMap::new();
+        for n in nodes.iter() {
+            *addresses.entry(slash8(n.node.ip())).or_insert(0) += 1;
+        }
+
+        Self {
+            unique_nodes    : ids.len(),
+            birthday        : birthday_estimate(references.values().copied()),
+            densest_prefix  : densest_prefix_estimate(&ids, DENSEST_PREFIX_ZONE),
+            nodes,
+            queried,
+            requests,
+            versions,
+            capabilities,
+            addresses,
+            elapsed,
+        }
+    }
+
+    /// Every node found, ordered by id, a node on both networks shows up
+    /// once for each.
+    pub fn nodes(&self) -> &[CrawledNode] {
+        &self.nodes
+    }
+
+    /// The distinct node ids found.
+    pub fn unique_nodes(&self) -> usize {
+        self.unique_nodes
+    }
+
+    /// The nodes asked, and those of them that answered.
+    pub fn queried(&self) -> usize {
+        self.queried
+    }
+
+    pub fn responsive(&self) -> usize {
+        self.nodes.iter().filter(|n| n.is_responsive()).count()
+    }
+
+    /// The share of the nodes asked that answered, 0 if none was asked.
+    pub fn reachability(&self) -> f64 {
+        match self.queried {
+            0 => 0.0,
+            n => self.responsive() as f64 / n as f64,
+        }
+    }
+
+    /// The find node requests sent.
+    pub fn requests(&self) -> usize {
+        self.requests
+    }
+
+    /// The answering nodes by their normalized version, such as "MK/5".
+    pub fn versions(&self) -> &BTreeMap<String, usize> {
+        &self.versions
+    }
+
+    /// The answering nodes by the capabilities they advertised, such as
+    /// "storage" or "client-only", those not advertising any as full nodes.
+    pub fn capabilities(&self) -> &BTreeMap<String, usize> {
+        &self.capabilities
+    }
+
+    /// The nodes found by the /8 network of their address.
+    pub fn address_distribution(&self) -> &BTreeMap<IpAddr, usize> {
+        &self.addresses
+    }
+
+    /// The network size told by how often the same nodes came back in the
+    /// answers, taking them as random samples: n samples out of N nodes
+    /// hold n(n-1)/2N pairs of the same node. None without any repeat.
+    pub fn birthday_estimate(&self) -> Option<f64> {
+        self.birthday
+    }
+
+    /// The network size told by the densest prefix of the ids found, taken
+    /// to be crawled completely, scaled up to the whole keyspace.
+    pub fn densest_prefix_estimate(&self) -> Option<f64> {
+        self.densest_prefix
+    }
+
+    /// The mean of the estimates, never fewer than the nodes found.
+    pub fn estimated_size(&self) -> usize {
+        let estimates = [self.birthday, self.densest_prefix]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        let mean = match estimates.len() {
+            0 => 0.0,
+            n => estimates.iter().sum::<f64>() / n as f64,
+        };
+        (mean.round() as usize).max(self.unique_nodes)
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+impl fmt::Display for CrawlReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Crawled in {:?} with {} requests", self.elapsed, self.requests)?;
+        writeln!(f, "Nodes found: {}, estimated network size: {}", self.unique_nodes, self.estimated_size())?;
+        writeln!(f, "Responsive: {} of {} queried ({:.1}%)",
+            self.responsive(), self.queried, self.reachability() * 100.0)?;
+        writeln!(f, "Versions:")?;
+        for (ver, count) in self.versions.iter() {
+            writeln!(f, "  {:<12} {}", ver, count)?;
+        }
+        writeln!(f, "Capabilities:")?;
+        for (cap, count) in self.capabilities.iter() {
+            writeln!(f, "  {:<12} {}", cap, count)?;
+        }
+        writeln!(f, "Addresses:")?;
+        for (net, count) in self.addresses.iter() {
+            writeln!(f, "  {:<12} {}", format!("{}/8", net), count)?;
+        }
+        Ok(())
+    }
+}
+
+// The nodes each prefix holds on average for the densest prefix estimate,
+// large enough that the densest one is not far off by chance.
+pub(crate) const DENSEST_PREFIX_ZONE: usize = 64;
+
+fn slash8(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => Ipv4Addr::new(v4.octets()[0], 0, 0, 0).into(),
+        IpAddr::V6(v6) => Ipv6Addr::from((v6.octets()[0] as u128) << 120).into(),
+    }
+}
+
+// `counts` tells how often each node was sampled.
+pub(crate) fn birthday_estimate(counts: impl Iterator<Item = usize>) -> Option<f64> {
+    let (samples, pairs) = counts.fold((0.0, 0.0), |(n, c), count| {
+        let count = count as f64;
+        (n + count, c + count * (count - 1.0) / 2.0)
+    });
+    (pairs > 0.0).then(|| samples * (samples - 1.0) / (2.0 * pairs))
+}
+
+// The ids are split by the deepest prefix that still leaves `zone` of them
+// to each part on average, the part holding the most is scaled up.
+pub(crate) fn densest_prefix_estimate(ids: &[Id], zone: usize) -> Option<f64> {
+    if ids.is_empty() || zone == 0 {
+        return None;
+    }
+
+    let bits = (ids.len() / zone).max(1).ilog2() as usize;
+    let mut zones = HashMap::new();
+    for id in ids {
+        let prefix = (0..bits).fold(0u64, |acc, i| acc << 1 | id.bit_at(i) as u64);
+        *zones.entry(prefix).or_insert(0usize) += 1;
+    }
+    zones.values().max().map(|&densest| densest as f64 * (1u64 << bits) as f64)
+}
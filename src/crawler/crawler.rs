@@ -0,0 +1,298 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::time::{Instant, sleep_until};
+use log::{debug, info};
+
+use crate::{
+    Id,
+    Network,
+    NodeInfo,
+    Result,
+    errors::{ArgumentError, StateError},
+    dht::{Node, Prefix},
+};
+
+use super::crawl_report::{CrawlReport, CrawledNode};
+
+// Requests sent per second by default, across all nodes.
+const DEFAULT_RATE: u32 = 20;
+// Time between two requests to the same node by default.
+const DEFAULT_POLITENESS: Duration = Duration::from_millis(500);
+// Requests awaiting an answer at a time.
+const DEFAULT_CONCURRENCY: usize = 8;
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+// The buckets of each node asked for, beyond the one of the node itself.
+const DEFAULT_SWEEP_DEPTH: usize = 4;
+// Routing table entries to start from without seeds.
+const SEED_COUNT: usize = 128;
+
+type NodeKey = (Id, Network);
+
+/// Maps the DHT from a running node by asking every node it finds for the
+/// nodes it knows, over a sweep of the keyspace around its id.
+///
+/// The crawl only sends find node requests, nothing is stored or announced.
+/// It keeps to a global request rate and waits a politeness delay between
+/// two requests to the same node.
+///
+/// ```ignore
+/// let report = Crawler::new(&node)
+///     .with_rate(50)
+///     .run(Duration::from_secs(60)).await?;
+/// println!("{}", report);
+/// ```
+pub struct Crawler {
+    node            : Arc<Node>,
+    seeds           : Vec<NodeInfo>,
+    rate            : u32,
+    politeness      : Duration,
+    concurrency     : usize,
+    query_timeout   : Duration,
+    sweep_depth     : usize,
+}
+
+impl Crawler {
+    pub fn new(node: &Arc<Node>) -> Self {
+        Self {
+            node            : node.clone(),
+            seeds           : Vec::new(),
+            rate            : DEFAULT_RATE,
+            politeness      : DEFAULT_POLITENESS,
+            concurrency     : DEFAULT_CONCURRENCY,
+            query_timeout   : DEFAULT_QUERY_TIMEOUT,
+            sweep_depth     : DEFAULT_SWEEP_DEPTH,
+        }
+    }
+
+    /// Starts from these nodes instead of the routing table of the node.
+    pub fn with_seeds(mut self, seeds: &[NodeInfo]) -> Self {
+        self.seeds = seeds.to_vec();
+        self
+    }
+
+    /// Requests per second across all nodes, at least one.
+    pub fn with_rate(mut self, rate: u32) -> Self {
+        self.rate = rate.max(1);
+        self
+    }
+
+    pub fn with_politeness(mut self, delay: Duration) -> Self {
+        self.politeness = delay;
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn with_query_timeout(mut self, timeout: Duration) -> Self {
+        self.query_timeout = timeout;
+        self
+    }
+
+    /// How many buckets of each node to ask for: the one at each of the
+    /// first `depth` bits the id of the node differs at, besides the nodes
+    /// closest to it.
+    pub fn with_sweep_depth(mut self, depth: usize) -> Self {
+        self.sweep_depth = depth.min(Id::BITS - 1);
+        self
+    }
+
+    /// Crawls until every node found was asked, or `timeout` passed. The
+    /// report covers what was found by then either way.
+    pub async fn run(&self, timeout: Duration) -> Result<CrawlReport> {
+        if !self.node.is_running() {
+            return Err(StateError::new("Node is not running"));
+        }
+
+        let seeds = match self.seeds.is_empty() {
+            true => self.node.closest_nodes(self.node.id(), SEED_COUNT, None, false).await?,
+            false => self.seeds.clone(),
+        };
+        if seeds.is_empty() {
+            return Err(ArgumentError::new("No nodes to start the crawl from"));
+        }
+
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let interval = Duration::from_secs(1) / self.rate;
+
+        let mut crawl = Crawl::new(*self.node.id(), self.politeness);
+        for seed in seeds {
+            crawl.found(seed, started, self);
+        }
+
+        let mut inflight = FuturesUnordered::new();
+        let mut next_slot = started;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                debug!("Crawl stopped at the deadline, {} requests left", crawl.pending() + inflight.len());
+                break;
+            }
+
+            while inflight.len() < self.concurrency && now >= next_slot {
+                let Some((node, target)) = crawl.next_due(now) else {
+                    break;
+                };
+                let node_ref = self.node.clone();
+                let timeout = self.query_timeout;
+                inflight.push(async move {
+                    let result = node_ref.query_node(&node, &target, timeout).await;
+                    (node, result)
+                });
+                crawl.requests += 1;
+                next_slot = next_slot.max(now) + interval;
+            }
+
+            if inflight.is_empty() && crawl.pending() == 0 {
+                break;
+            }
+
+            let wake = match crawl.next_time() {
+                Some(due) if inflight.len() < self.concurrency => due.max(next_slot).min(deadline),
+                _ => deadline,
+            };
+            tokio::select! {
+                Some((node, result)) = inflight.next(), if !inflight.is_empty() => {
+                    match result {
+                        Ok(answer) => {
+                            crawl.responded(answer.node().clone(), answer.rtt());
+                            let now = Instant::now();
+                            for found in answer.into_nodes() {
+                                crawl.referred(found.id());
+                                crawl.found(found, now, self);
+                            }
+                        },
+                        Err(e) => debug!("Crawling {} failed: {}", node, e),
+                    }
+                },
+                _ = sleep_until(wake) => {},
+            }
+        }
+
+        let report = crawl.into_report(started.elapsed());
+        info!("Crawl found {} nodes in {:?}, {} of {} queried responded",
+            report.unique_nodes(), report.elapsed(), report.responsive(), report.queried());
+        Ok(report)
+    }
+
+    // The lookup ids sweeping the keyspace around the node: its own id, and
+    // a random id in the bucket at each of the first bits.
+    fn sweep_targets(&self, id: &Id) -> Vec<Id> {
+        let mut targets = vec![*id];
+        for bit in 0..self.sweep_depth {
+            let mut flipped = *id;
+            flipped.set_bit(bit, !id.bit_at(bit));
+            targets.push(Prefix::from(&flipped, bit as i32).random_id());
+        }
+        targets
+    }
+}
+
+// The state of a crawl in progress.
+struct Crawl {
+    local       : Id,
+    politeness  : Duration,
+    nodes       : HashMap<NodeKey, NodeInfo>,
+    rtts        : HashMap<NodeKey, Duration>,
+    references  : HashMap<Id, usize>,
+    queried     : HashSet<NodeKey>,
+    last_sent   : HashMap<NodeKey, Instant>,
+    // The requests not sent yet, by the time they are due at.
+    schedule    : BTreeMap<(Instant, u64), (NodeInfo, Id)>,
+    seq         : u64,
+    requests    : usize,
+}
+
+impl Crawl {
+    fn new(local: Id, politeness: Duration) -> Self {
+        Self {
+            local,
+            politeness,
+            nodes       : HashMap::new(),
+            rtts        : HashMap::new(),
+            references  : HashMap::new(),
+            queried     : HashSet::new(),
+            last_sent   : HashMap::new(),
+            schedule    : BTreeMap::new(),
+            seq         : 0,
+            requests    : 0,
+        }
+    }
+
+    // Schedules the sweep of a node seen for the first time, its requests
+    // spaced by the politeness delay.
+    fn found(&mut self, node: NodeInfo, now: Instant, crawler: &Crawler) {
+        let key = (*node.id(), node.network());
+        if self.nodes.contains_key(&key) {
+            return;
+        }
+        self.nodes.insert(key, node.clone());
+        if node.id() == &self.local {
+            return;
+        }
+
+        for (i, target) in crawler.sweep_targets(node.id()).into_iter().enumerate() {
+            self.seq += 1;
+            let due = now + self.politeness * i as u32;
+            self.schedule.insert((due, self.seq), (node.clone(), target));
+        }
+    }
+
+    fn referred(&mut self, id: &Id) {
+        *self.references.entry(*id).or_insert(0) += 1;
+    }
+
+    fn responded(&mut self, node: NodeInfo, rtt: Duration) {
+        let key = (*node.id(), node.network());
+        self.rtts.entry(key)
+            .and_modify(|v| *v = (*v).min(rtt))
+            .or_insert(rtt);
+        // Keeps the version it answered with.
+        self.nodes.insert(key, node);
+    }
+
+    // The next request due, one held back by the politeness delay of its
+    // node is put off until the delay passed.
+    fn next_due(&mut self, now: Instant) -> Option<(NodeInfo, Id)> {
+        loop {
+            let entry = self.schedule.first_entry()?;
+            if entry.key().0 > now {
+                return None;
+            }
+            let (node, target) = entry.remove();
+            let key = (*node.id(), node.network());
+            if let Some(last) = self.last_sent.get(&key) {
+                if now < *last + self.politeness {
+                    self.seq += 1;
+                    self.schedule.insert((*last + self.politeness, self.seq), (node, target));
+                    continue;
+                }
+            }
+            self.last_sent.insert(key, now);
+            self.queried.insert(key);
+            return Some((node, target));
+        }
+    }
+
+    fn next_time(&self) -> Option<Instant> {
+        self.schedule.first_key_value().map(|((due, _), _)| *due)
+    }
+
+    fn pending(&self) -> usize {
+        self.schedule.len()
+    }
+
+    fn into_report(self, elapsed: Duration) -> CrawlReport {
+        let mut nodes = self.nodes.into_iter()
+            .map(|(key, node)| CrawledNode::new(node, self.rtts.get(&key).copied()))
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|n| (*n.node().id(), n.node().network() == Network::IPv6));
+
+        CrawlReport::new(nodes, &self.references, self.queried.len(), self.requests, elapsed)
+    }
+}
@@ -0,0 +1,13 @@
+#[allow(clippy::module_inception)]
+mod crawler;
+mod crawl_report;
+
+#[cfg(test)]
+mod unitests {
+    mod test_crawl_report;
+}
+
+pub use {
+    crawler::Crawler,
+    crawl_report::{CrawlReport, CrawledNode},
+};
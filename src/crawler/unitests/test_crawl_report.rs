@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::{
+    Id,
+    NodeInfo,
+    Capabilities,
+    crawler::{
+        CrawledNode,
+        CrawlReport,
+        crawl_report::{birthday_estimate, densest_prefix_estimate, DENSEST_PREFIX_ZONE},
+    },
+};
+
+fn random_ids(count: usize) -> Vec<Id> {
+    (0..count).map(|_| Id::random()).collect()
+}
+
+fn node_at(addr: &str) -> NodeInfo {
+    NodeInfo::new(Id::random(), addr.parse::<SocketAddr>().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_birthday_estimate() {
+        // Nothing came back twice, there is nothing to tell from.
+        assert_eq!(birthday_estimate([1, 1, 1].into_iter()), None);
+        assert_eq!(birthday_estimate(std::iter::empty()), None);
+
+        // 3000 random samples out of 1000 nodes.
+        let mut counts = HashMap::new();
+        for _ in 0..3000 {
+            *counts.entry(rand::random::<u32>() % 1000).or_insert(0usize) += 1;
+        }
+        let estimate = birthday_estimate(counts.into_values()).unwrap();
+        assert!((800.0..1250.0).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn test_densest_prefix_estimate() {
+        assert_eq!(densest_prefix_estimate(&[], DENSEST_PREFIX_ZONE), None);
+
+        // Too few to split, the nodes found are all there is.
+        let ids = random_ids(40);
+        assert_eq!(densest_prefix_estimate(&ids, DENSEST_PREFIX_ZONE), Some(40.0));
+
+        let ids = random_ids(4096);
+        let estimate = densest_prefix_estimate(&ids, DENSEST_PREFIX_ZONE).unwrap();
+        assert!((4096.0..6600.0).contains(&estimate), "{estimate}");
+
+        // The upper half of the keyspace crawled completely, a quarter of
+        // the lower one: the complete part tells the size.
+        let mut kept = 0;
+        let partial = ids.into_iter().filter(|id| {
+            kept += 1;
+            id.bit_at(0) || kept % 4 == 0
+        }).collect::<Vec<_>>();
+        assert!(partial.len() < 3000);
+        let estimate = densest_prefix_estimate(&partial, DENSEST_PREFIX_ZONE).unwrap();
+        assert!((3600.0..6200.0).contains(&estimate), "{estimate}");
+    }
+
+    #[test]
+    fn test_report() {
+        let a = node_at("10.0.0.1:39001");
+        let b = node_at("10.8.0.2:39001");
+        let c = node_at("192.168.1.3:39001");
+        let mut answered = a.clone();
+        answered.set_version(crate::core::version::ver());
+        answered.set_capabilities(Capabilities::CLIENT_ONLY | Capabilities::RELAY);
+
+        let nodes = vec![
+            CrawledNode::new(answered, Some(Duration::from_millis(12))),
+            CrawledNode::new(b.clone(), Some(Duration::from_millis(30))),
+            CrawledNode::new(c.clone(), None),
+        ];
+        let references = HashMap::from([(*a.id(), 3), (*b.id(), 2), (*c.id(), 1)]);
+        let report = CrawlReport::new(nodes, &references, 3, 9, Duration::from_secs(2));
+
+        assert_eq!(report.unique_nodes(), 3);
+        assert_eq!(report.responsive(), 2);
+        assert!((report.reachability() - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.requests(), 9);
+
+        // Only the nodes that answered count, by the version they answered with.
+        let versions = report.versions().iter().collect::<Vec<_>>();
+        assert_eq!(versions.len(), 2, "{versions:?}");
+        assert_eq!(versions.iter().map(|(_, n)| **n).sum::<usize>(), 2);
+
+        // The others taken as full nodes.
+        let capabilities = report.capabilities();
+        assert_eq!(capabilities.len(), 3, "{capabilities:?}");
+        assert_eq!(capabilities.get("client-only"), Some(&1));
+        assert_eq!(capabilities.get("relay"), Some(&1));
+        assert_eq!(capabilities.get("storage"), Some(&1));
+        assert!(report.to_string().contains("client-only"));
+
+        let addresses = report.address_distribution();
+        assert_eq!(addresses.get(&"10.0.0.0".parse().unwrap()), Some(&2));
+        assert_eq!(addresses.get(&"192.0.0.0".parse().unwrap()), Some(&1));
+
+        // 6 samples, 4 pairs of the same node: 6 * 5 / 8.
+        assert_eq!(report.birthday_estimate(), Some(3.75));
+        assert_eq!(report.densest_prefix_estimate(), Some(3.0));
+        assert_eq!(report.estimated_size(), 3);
+        assert!(report.to_string().contains("10.0.0.0/8"));
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use crate::PeerInfo;
+use crate::dht::node_config::DEFAULT_ANNOUNCEMENT_SKEW;
+
+/// What becomes of an announcement received from another node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    Accepted,
+    // Accepted, but without a time it may well be a replay.
+    Untimed,
+    Rejected(&'static str),
+}
+
+// How far the signed time of an announcement may be off the local clock,
+// either way, before it is taken for a replay of a stale record.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AnnouncementPolicy {
+    skew    : Duration,
+    required: bool,
+}
+
+impl Default for AnnouncementPolicy {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(DEFAULT_ANNOUNCEMENT_SKEW), false)
+    }
+}
+
+impl AnnouncementPolicy {
+    // Announcements without a time, from older versions, are only accepted
+    // unless `required`.
+    pub(crate) fn new(skew: Duration, required: bool) -> Self {
+        Self { skew, required }
+    }
+
+    pub(crate) fn check(&self, peer: &PeerInfo, now: u64) -> Verdict {
+        let Some(announced) = peer.announced() else {
+            return match self.required {
+                true => Verdict::Rejected("Announcement time is missing"),
+                false => Verdict::Untimed,
+            };
+        };
+
+        let skew = self.skew.as_millis() as u64;
+        if announced < now.saturating_sub(skew) {
+            Verdict::Rejected("Announcement is stale")
+        } else if announced > now.saturating_add(skew) {
+            Verdict::Rejected("Announcement time is in the future")
+        } else {
+            Verdict::Accepted
+        }
+    }
+}
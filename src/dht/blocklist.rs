@@ -0,0 +1,336 @@
+use std::{
+    fmt,
+    fs,
+    collections::HashMap,
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use serde::{Deserialize, Serialize};
+use log::{info, warn};
+
+use crate::{
+    Id,
+    Clock,
+    Error,
+    errors::{Result, IOError},
+    dht::stats::BlocklistStats,
+};
+
+pub(crate) const BLOCKLIST_FILE: &str = "blocklist";
+
+/// What a block applies to: every packet from an address, or every packet
+/// and routing table entry of a node id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockTarget {
+    Address(IpAddr),
+    Id(Id),
+}
+
+impl fmt::Display for BlockTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(ip) => write!(f, "address {ip}"),
+            Self::Id(id) => write!(f, "node {id}"),
+        }
+    }
+}
+
+/// Why a target got blocked: by the application, or by the node after
+/// repeated invalid tokens or invalid values from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BlockReason {
+    Manual,
+    InvalidToken,
+    InvalidValue,
+}
+
+impl fmt::Display for BlockReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Manual => "manual",
+            Self::InvalidToken => "invalid tokens",
+            Self::InvalidValue => "invalid values",
+        })
+    }
+}
+
+/// A blocked address or node id, with the time it was blocked and the time
+/// the block ends, if ever.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockEntry {
+    #[serde(rename = "t")]
+    target      : BlockTarget,
+    #[serde(rename = "r")]
+    reason      : BlockReason,
+    #[serde(rename = "s")]
+    since_ms    : u64,
+    #[serde(rename = "e", default, skip_serializing_if = "Option::is_none")]
+    expires_ms  : Option<u64>,
+}
+
+impl BlockEntry {
+    pub fn target(&self) -> &BlockTarget {
+        &self.target
+    }
+
+    pub fn reason(&self) -> BlockReason {
+        self.reason
+    }
+
+    pub fn since(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.since_ms)
+    }
+
+    // None for a block lasting until removed.
+    pub fn expires(&self) -> Option<SystemTime> {
+        self.expires_ms.map(|ms| UNIX_EPOCH + Duration::from_millis(ms))
+    }
+
+    fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_ms.is_some_and(|ms| ms <= now_ms)
+    }
+}
+
+// How many invalid tokens or invalid values a node sends within the strike
+// window before it is blocked, 0 never blocks, and for how long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct StrikePolicy {
+    pub(crate) token_strikes    : u32,
+    pub(crate) value_strikes    : u32,
+    pub(crate) duration         : Duration,
+}
+
+struct Strikes {
+    count   : u32,
+    first_ms: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries : HashMap<BlockTarget, BlockEntry>,
+    strikes : HashMap<(Id, BlockReason), Strikes>,
+    stats   : BlocklistStats,
+}
+
+impl Inner {
+    fn is_blocked(&mut self, target: &BlockTarget, now_ms: u64) -> bool {
+        match self.entries.get(target) {
+            Some(entry) if entry.is_expired(now_ms) => {
+                self.entries.remove(target);
+                false
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+// Addresses and node ids whose packets are dropped as received and which
+// never get into the routing tables, shared by the node and both DHT
+// instances. Changes are saved to the instance directory right away, an
+// expired block is just dropped once met.
+pub(crate) struct Blocklist {
+    clock   : Arc<dyn Clock>,
+    policy  : StrikePolicy,
+    path    : Option<PathBuf>,
+    inner   : Mutex<Inner>,
+}
+
+impl Blocklist {
+    // Strikes further apart than this start the count over.
+    pub(crate) const STRIKE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+    const MAX_STRUCK: usize = 4096;
+
+    pub(crate) fn new(clock: Arc<dyn Clock>, policy: StrikePolicy) -> Self {
+        Self {
+            clock,
+            policy,
+            path    : None,
+            inner   : Mutex::new(Inner::default()),
+        }
+    }
+
+    // The blocks saved at the path, a missing or unreadable file starts
+    // with none.
+    pub(crate) fn open(path: PathBuf, clock: Arc<dyn Clock>, policy: StrikePolicy) -> Self {
+        let mut blocklist = Self::new(clock, policy);
+        match Self::load(&path) {
+            Ok(entries) => {
+                let now_ms = blocklist.clock.now_ms();
+                let mut inner = blocklist.inner.lock().unwrap();
+                for entry in entries.into_iter().filter(|e| !e.is_expired(now_ms)) {
+                    inner.entries.insert(entry.target, entry);
+                }
+                if !inner.entries.is_empty() {
+                    info!("Loaded {} blocked addresses and node ids from {}",
+                        inner.entries.len(), path.display());
+                }
+            },
+            Err(e) => warn!("Loading blocklist from {} error: {e}", path.display()),
+        }
+        blocklist.path = Some(path);
+        blocklist
+    }
+
+    fn load(path: &Path) -> Result<Vec<BlockEntry>> {
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let bytes = fs::read(path).map_err(|e| -> Error {
+            IOError::new(format!("Reading {} error: {e}", path.display()))
+        })?;
+        Ok(serde_cbor::from_slice(&bytes)?)
+    }
+
+    fn save(&self, inner: &Inner) {
+        let Some(path) = self.path.as_ref() else {
+            return;
+        };
+        let entries = inner.entries.values().collect::<Vec<_>>();
+        let result = serde_cbor::to_vec(&entries).map_err(Error::from).and_then(|bytes| {
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, bytes)
+                .and_then(|_| fs::rename(&tmp_path, path))
+                .map_err(|e| -> Error {
+                    IOError::new(format!("Writing {} error: {e}", path.display()))
+                })
+        });
+        if let Err(e) = result {
+            warn!("Saving blocklist error: {e}");
+        }
+    }
+
+    pub(crate) fn block(&self, target: BlockTarget, reason: BlockReason, duration: Option<Duration>) -> BlockEntry {
+        let now_ms = self.clock.now_ms();
+        let entry = BlockEntry {
+            target,
+            reason,
+            since_ms    : now_ms,
+            expires_ms  : duration.map(|d| now_ms.saturating_add(d.as_millis() as u64)),
+        };
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.insert(target, entry.clone());
+        if let BlockTarget::Id(id) = target {
+            inner.strikes.retain(|(struck, _), _| *struck != id);
+        }
+        self.save(&inner);
+        entry
+    }
+
+    pub(crate) fn unblock(&self, target: &BlockTarget) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let removed = inner.entries.remove(target).is_some();
+        if removed {
+            self.save(&inner);
+        }
+        removed
+    }
+
+    // Longest standing first.
+    pub(crate) fn entries(&self) -> Vec<BlockEntry> {
+        let now_ms = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.retain(|_, entry| !entry.is_expired(now_ms));
+
+        let mut entries = inner.entries.values().cloned().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| entry.since_ms);
+        entries
+    }
+
+    // Whether a packet from the address is dropped, counted when it is.
+    pub(crate) fn drops_addr(&self, ip: &IpAddr) -> bool {
+        let now_ms = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        let blocked = inner.is_blocked(&BlockTarget::Address(*ip), now_ms);
+        if blocked {
+            inner.stats.dropped_by_address += 1;
+        }
+        blocked
+    }
+
+    // Whether a packet from the node id is dropped, counted when it is.
+    pub(crate) fn drops_id(&self, id: &Id) -> bool {
+        let now_ms = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        let blocked = inner.is_blocked(&BlockTarget::Id(*id), now_ms);
+        if blocked {
+            inner.stats.dropped_by_id += 1;
+        }
+        blocked
+    }
+
+    // Blocked by its id or its address.
+    pub(crate) fn blocks_node(&self, id: &Id, ip: &IpAddr) -> bool {
+        let now_ms = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        inner.is_blocked(&BlockTarget::Id(*id), now_ms) ||
+            inner.is_blocked(&BlockTarget::Address(*ip), now_ms)
+    }
+
+    // Whether the node is kept out of the routing table, counted when it is.
+    pub(crate) fn refuses_entry(&self, id: &Id, ip: &IpAddr) -> bool {
+        let blocked = self.blocks_node(id, ip);
+        if blocked {
+            self.inner.lock().unwrap().stats.refused_entries += 1;
+        }
+        blocked
+    }
+
+    pub(crate) fn on_evicted(&self, count: usize) {
+        self.inner.lock().unwrap().stats.evicted_entries += count as u64;
+    }
+
+    // Counts an invalid token or value from the node, returns whether it
+    // got the node blocked for the policy duration.
+    pub(crate) fn strike(&self, id: &Id, reason: BlockReason) -> bool {
+        let threshold = match reason {
+            BlockReason::InvalidToken => self.policy.token_strikes,
+            BlockReason::InvalidValue => self.policy.value_strikes,
+            BlockReason::Manual => return false,
+        };
+        if threshold == 0 {
+            return false;
+        }
+
+        let now_ms = self.clock.now_ms();
+        let window = Self::STRIKE_WINDOW.as_millis() as u64;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.strikes.len() >= Self::MAX_STRUCK {
+            inner.strikes.retain(|_, s| now_ms.saturating_sub(s.first_ms) <= window);
+        }
+        let strikes = inner.strikes.entry((*id, reason)).or_insert(Strikes {
+            count: 0,
+            first_ms: now_ms,
+        });
+        if now_ms.saturating_sub(strikes.first_ms) > window {
+            *strikes = Strikes { count: 0, first_ms: now_ms };
+        }
+        strikes.count += 1;
+        if strikes.count < threshold {
+            return false;
+        }
+
+        inner.strikes.retain(|(struck, _), _| struck != id);
+        inner.stats.auto_blocks += 1;
+        drop(inner);
+
+        warn!("Blocking node {id} for {}s after {threshold} {reason}",
+            self.policy.duration.as_secs());
+        self.block(BlockTarget::Id(*id), reason, Some(self.policy.duration));
+        true
+    }
+
+    pub(crate) fn stats(&self) -> BlocklistStats {
+        let now_ms = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.retain(|_, entry| !entry.is_expired(now_ms));
+        BlocklistStats {
+            entries: inner.entries.len() as u64,
+            ..inner.stats
+        }
+    }
+}
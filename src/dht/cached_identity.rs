@@ -1,5 +1,4 @@
 use std::sync::{Arc, Mutex};
-use moka::sync::Cache;
 
 use crate::{
     Id,
@@ -8,37 +7,35 @@ use crate::{
         Result,
         CryptoIdentity,
         CryptoContext,
-    }
+    },
+    dht::crypto_cache::CryptoCache,
 };
 
-const CONTEXT_CACHE_CAPACITY: u64 = 10;
-
 pub(crate) struct CachedIdentity {
     id      : Id,
     identity: Arc<CryptoIdentity>,
-    cache   : Cache<Id, Arc<Mutex<CryptoContext>>>,
+    cache   : CryptoCache,
 }
 
 impl CachedIdentity {
-    pub(crate) fn new(identity: CryptoIdentity) -> Self {
+    pub(crate) fn new(identity: CryptoIdentity, cache: CryptoCache) -> Self {
         Self {
             id: identity.id().clone(),
             identity: Arc::new(identity),
-            cache: Cache::new(CONTEXT_CACHE_CAPACITY),
+            cache,
         }
     }
 
     pub(crate) fn clear_cache(&self) {
-        self.cache.invalidate_all();
+        self.cache.clear();
+    }
+
+    pub(crate) fn cache(&self) -> &CryptoCache {
+        &self.cache
     }
 
     pub(crate) fn context(&self, key: &Id) -> Arc<Mutex<CryptoContext>> {
-        self.cache.get_with(key.clone(), || {
-            Arc::new(Mutex::new(CryptoContext::from_private_key(
-                key.clone(),
-                self.identity.encryption_keypair().private_key(),
-            )))
-        })
+        self.cache.context(key, self.identity.encryption_keypair())
     }
 
     pub(crate) fn identity(&self) -> Arc<CryptoIdentity> {
@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicI64, Ordering},
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::{Id, Clock};
+
+// How the estimated skew moved against the threshold, told once per move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SkewChange {
+    // The estimate, in milliseconds, went beyond the threshold.
+    Exceeded(i64),
+    // Back within the threshold.
+    Recovered,
+}
+
+// Estimates how far the local clock is off the clocks of other nodes, from
+// the time they tell in lookup responses. The latest offset of each node is
+// kept and the median of them taken, so a few nodes with wrong clocks do
+// not move the estimate. It is shared by the DHT instances of a node.
+pub(crate) struct ClockSkew {
+    threshold   : u64,  // milliseconds, 0 never warns nor compensates
+    compensate  : bool,
+    compensation: AtomicI64,
+    samples     : Mutex<Samples>,
+}
+
+#[derive(Default)]
+struct Samples {
+    offsets     : HashMap<Id, i64>,
+    order       : VecDeque<Id>,
+    exceeded    : bool,
+}
+
+impl ClockSkew {
+    // Nodes kept, the oldest reporting is dropped beyond them.
+    pub(crate) const MAX_NODES: usize = 64;
+    // Nodes needed to agree before the skew counts.
+    pub(crate) const MIN_NODES: usize = 2;
+
+    pub(crate) fn new(threshold: Duration, compensate: bool) -> Self {
+        Self {
+            threshold   : threshold.as_millis() as u64,
+            compensate,
+            compensation: AtomicI64::new(0),
+            samples     : Mutex::new(Samples::default()),
+        }
+    }
+
+    // Records the time `remote` (seconds since the epoch) told by `node`
+    // when the local clock read `now` (milliseconds since the epoch).
+    pub(crate) fn record(&self, node: &Id, remote: u64, now: u64) -> Option<SkewChange> {
+        // The remote time is cut to seconds, the middle of it is closest.
+        let offset = (remote as i64).saturating_mul(1000).saturating_add(500)
+            .saturating_sub(now as i64);
+
+        let mut samples = self.samples.lock().unwrap();
+        if samples.offsets.insert(*node, offset).is_none() {
+            samples.order.push_back(*node);
+            if samples.order.len() > Self::MAX_NODES {
+                if let Some(oldest) = samples.order.pop_front() {
+                    samples.offsets.remove(&oldest);
+                }
+            }
+        }
+
+        let estimate = match samples.offsets.len() >= Self::MIN_NODES {
+            true  => median(samples.offsets.values().copied().collect()),
+            false => 0,
+        };
+        let exceeded = self.threshold > 0 && estimate.unsigned_abs() > self.threshold;
+        self.compensation.store(
+            if exceeded && self.compensate { estimate } else { 0 },
+            Ordering::Relaxed
+        );
+
+        match (exceeded, samples.exceeded) {
+            (true, false) => {
+                samples.exceeded = true;
+                Some(SkewChange::Exceeded(estimate))
+            },
+            (false, true) => {
+                samples.exceeded = false;
+                Some(SkewChange::Recovered)
+            },
+            _ => None,
+        }
+    }
+
+    // Milliseconds the clocks of the other nodes are ahead of the local
+    // one, behind if negative. None until enough of them told their time.
+    pub(crate) fn estimate(&self) -> Option<i64> {
+        let samples = self.samples.lock().unwrap();
+        (samples.offsets.len() >= Self::MIN_NODES)
+            .then(|| median(samples.offsets.values().copied().collect()))
+    }
+
+    pub(crate) fn nodes(&self) -> usize {
+        self.samples.lock().unwrap().offsets.len()
+    }
+
+    // Milliseconds added to the local clock when compensating, only once
+    // the estimate is beyond the threshold.
+    pub(crate) fn compensation(&self) -> i64 {
+        self.compensation.load(Ordering::Relaxed)
+    }
+}
+
+// Of the two middle offsets of an even count the one closer to the local
+// clock, a lone outlier never tips it.
+fn median(mut offsets: Vec<i64>) -> i64 {
+    offsets.sort_unstable();
+    let mid = offsets.len() / 2;
+    match offsets.len() % 2 {
+        1 => offsets[mid],
+        _ => match offsets[mid - 1].unsigned_abs() < offsets[mid].unsigned_abs() {
+            true  => offsets[mid - 1],
+            false => offsets[mid],
+        },
+    }
+}
+
+// The local clock moved by the compensation of the skew, for the windows
+// of the tokens and announcements the node generates.
+pub(crate) struct CompensatedClock {
+    clock   : Arc<dyn Clock>,
+    skew    : Arc<ClockSkew>,
+}
+
+impl CompensatedClock {
+    pub(crate) fn new(clock: Arc<dyn Clock>, skew: Arc<ClockSkew>) -> Self {
+        Self { clock, skew }
+    }
+}
+
+impl Clock for CompensatedClock {
+    fn now(&self) -> SystemTime {
+        let now = self.clock.now();
+        let offset = self.skew.compensation();
+        match offset >= 0 {
+            true  => now + Duration::from_millis(offset as u64),
+            false => now - Duration::from_millis(offset.unsigned_abs()),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use moka::{
+    notification::RemovalCause,
+    sync::Cache,
+};
+
+use crate::{
+    Id,
+    CryptoContext,
+    cryptobox::{KeyPair, PublicKey},
+    dht::stats::CryptoCacheStats,
+};
+
+#[derive(Default)]
+struct Counters {
+    hits            : AtomicU64,
+    misses          : AtomicU64,
+    evictions       : AtomicU64,
+    invalidations   : AtomicU64,
+}
+
+// Crypto contexts shared by the node and the messaging client, so the key
+// agreement with a remote id is done once rather than for every message.
+// A context is kept for the local key it was derived from, another key of
+// the same id (a new session key of a contact) never gets a stale one.
+// Clones share the same cache.
+#[derive(Clone)]
+pub struct CryptoCache {
+    cache   : Cache<(Id, PublicKey), Arc<Mutex<CryptoContext>>>,
+    counters: Arc<Counters>,
+}
+
+impl CryptoCache {
+    // Contexts kept at most, the least used ones are evicted beyond it, each
+    // for `ttl` at most after derived, Duration::ZERO keeps them until evicted.
+    pub fn new(capacity: u64, ttl: Duration) -> Self {
+        let counters = Arc::new(Counters::default());
+        let removed = counters.clone();
+        let mut builder = Cache::builder()
+            .max_capacity(capacity)
+            .support_invalidation_closures()
+            .eviction_listener(move |_, _, cause| {
+                let counter = match cause {
+                    RemovalCause::Size | RemovalCause::Expired => &removed.evictions,
+                    RemovalCause::Explicit => &removed.invalidations,
+                    RemovalCause::Replaced => return,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        if !ttl.is_zero() {
+            builder = builder.time_to_live(ttl);
+        }
+
+        Self {
+            cache: builder.build(),
+            counters,
+        }
+    }
+
+    // The context to encrypt to and decrypt from `peer` with the private key
+    // of `keypair`, derived on the first use.
+    pub fn context(&self, peer: &Id, keypair: &KeyPair) -> Arc<Mutex<CryptoContext>> {
+        let entry = self.cache.entry((*peer, keypair.to_public_key()))
+            .or_insert_with(|| Arc::new(Mutex::new(
+                CryptoContext::from_private_key(*peer, keypair.private_key())
+            )));
+
+        let counter = match entry.is_fresh() {
+            true  => &self.counters.misses,
+            false => &self.counters.hits,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        entry.into_value()
+    }
+
+    // Drops the contexts with `peer`, whatever local key they were derived
+    // from, once the session key with it changed or it was removed.
+    pub fn invalidate(&self, peer: &Id) {
+        let peer = *peer;
+        if let Err(e) = self.cache.invalidate_entries_if(move |(id, _), _| *id == peer) {
+            log::warn!("Invalidating crypto contexts of {peer} error: {e}, dropping all");
+            self.cache.invalidate_all();
+        }
+    }
+
+    pub fn clear(&self) {
+        self.cache.invalidate_all();
+    }
+
+    pub fn stats(&self) -> CryptoCacheStats {
+        // Evictions are counted when the cache gets to them.
+        self.cache.run_pending_tasks();
+        CryptoCacheStats {
+            entries         : self.cache.entry_count(),
+            hits            : self.counters.hits.load(Ordering::Relaxed),
+            misses          : self.counters.misses.load(Ordering::Relaxed),
+            evictions       : self.counters.evictions.load(Ordering::Relaxed),
+            invalidations   : self.counters.invalidations.load(Ordering::Relaxed),
+        }
+    }
+}
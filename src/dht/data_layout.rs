@@ -0,0 +1,142 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use log::info;
+
+use crate::{
+    Id,
+    Network,
+    Error,
+    errors::{Result, IOError},
+    dht::{
+        stats::STATS_JOURNAL_FILE,
+        blocklist::BLOCKLIST_FILE,
+    },
+};
+
+const ID_FILE: &str = "id";
+
+// Where the files of one node go, `<data_dir>/<instance>/`, so nodes sharing
+// a data directory keep out of each other's way. All the paths of the node
+// are resolved here.
+#[derive(Debug, Clone)]
+pub(crate) struct DataLayout {
+    data_dir: PathBuf,
+    root    : PathBuf,
+}
+
+impl DataLayout {
+    // Characters of the base58 node id naming the instance by default.
+    pub(crate) const SHORT_ID_LEN: usize = 8;
+
+    pub(crate) fn new(data_dir: &Path, instance: &str) -> Self {
+        Self {
+            data_dir: data_dir.to_path_buf(),
+            root    : data_dir.join(instance),
+        }
+    }
+
+    // The instance named in the config, or after the node id.
+    pub(crate) fn for_node(data_dir: &Path, instance: Option<&str>, id: &Id) -> Self {
+        match instance {
+            Some(name) => Self::new(data_dir, name),
+            None => Self::new(data_dir, &Self::short_id(id)),
+        }
+    }
+
+    pub(crate) fn short_id(id: &Id) -> String {
+        id.to_base58().chars().take(Self::SHORT_ID_LEN).collect()
+    }
+
+    // Names a directory of its own under the data directory.
+    pub(crate) fn is_valid_instance(name: &str) -> bool {
+        !name.is_empty() && name != "." && name != ".." &&
+            !name.contains(['/', '\\'])
+    }
+
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // An absolute database path is taken as it is.
+    pub(crate) fn database(&self, name: &str) -> PathBuf {
+        self.root.join(name)
+    }
+
+    pub(crate) fn routing_cache(&self, network: Network) -> PathBuf {
+        self.root.join(match network {
+            Network::IPv4 => "routing4.cache",
+            Network::IPv6 => "routing6.cache",
+        })
+    }
+
+    pub(crate) fn id_file(&self) -> PathBuf {
+        self.root.join(ID_FILE)
+    }
+
+    pub(crate) fn stats_journal(&self) -> PathBuf {
+        self.root.join(STATS_JOURNAL_FILE)
+    }
+
+    pub(crate) fn blocklist(&self) -> PathBuf {
+        self.root.join(BLOCKLIST_FILE)
+    }
+
+    pub(crate) fn create(&self) -> Result<()> {
+        fs::create_dir_all(&self.root).map_err(|e| -> Error {
+            IOError::new(format!("Creating instance directory {} error: {e}", self.root.display()))
+        })
+    }
+
+    // Moves the files of the flat layout of earlier versions, right in the
+    // data directory, into the instance directory. Only done when the id
+    // cached there is of this node, they belong to another node sharing the
+    // directory otherwise, and never over a file already moved. The id file
+    // goes last so an interrupted move is picked up on the next start.
+    pub(crate) fn migrate_legacy(&self, id: &Id, database: &str) -> Result<Vec<PathBuf>> {
+        let legacy_id = self.data_dir.join(ID_FILE);
+        match fs::read_to_string(&legacy_id) {
+            Ok(cached) if cached.trim() == id.to_base58() => {},
+            _ => return Ok(Vec::new()),
+        }
+
+        let mut moves: Vec<(String, PathBuf)> = Vec::new();
+        if !Path::new(database).is_absolute() {
+            for suffix in ["", "-wal", "-shm", "-journal"] {
+                let name = format!("{database}{suffix}");
+                moves.push((name.clone(), self.root.join(name)));
+            }
+        }
+        moves.push(("dht4.cache".into(), self.routing_cache(Network::IPv4)));
+        moves.push(("dht6.cache".into(), self.routing_cache(Network::IPv6)));
+
+        let entries = fs::read_dir(&self.data_dir).map_err(|e| IOError::new(format!(
+            "Reading data directory {} error: {e}", self.data_dir.display()
+        )))?;
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with(STATS_JOURNAL_FILE) {
+                moves.push((name.clone(), self.root.join(name)));
+            }
+        }
+        moves.push((ID_FILE.into(), self.id_file()));
+
+        let mut moved = Vec::new();
+        for (name, to) in moves {
+            let from = self.data_dir.join(&name);
+            if !from.is_file() || to.exists() {
+                continue;
+            }
+            fs::rename(&from, &to).map_err(|e| IOError::new(format!(
+                "Moving {} to {} error: {e}", from.display(), to.display()
+            )))?;
+            moved.push(to);
+        }
+
+        if !moved.is_empty() {
+            info!("Moved {} files of the flat data layout into {}", moved.len(), self.root.display());
+        }
+        Ok(moved)
+    }
+}
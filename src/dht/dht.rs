@@ -1,6 +1,6 @@
 use std::{
     net::SocketAddr,
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime},
     path::PathBuf,
     future::Future,
     rc::{Rc, Weak},
@@ -17,10 +17,14 @@ use log::{trace, debug, info, warn, error};
 
 use crate::{
     Id, Network,
+    Clock, SystemClock,
     NodeInfo, PeerInfo, Value,
     Identity,
+    Capabilities,
+    EndpointPolicy,
     crypto_identity::CryptoIdentity,
-    errors::Result
+    core::{version, endpoint, name_record},
+    errors::{Result, NetworkError, ProtocolError}
 };
 use crate::dht::{
     utils::{is_any_unicast, is_bogon},
@@ -29,8 +33,25 @@ use crate::dht::{
     promise::Promise,
     handler::{Handler, LocalHandler as AsyncHandler,},
     token_manager::TokenManager,
+    token_cache::TokenCache,
     lookup_option::LookupOption,
+    lookup_result::{ValueResult, PeerResult, LookupOutcome},
+    lookup_progress::{ProgressReporter, ProgressSender},
+    node_query::NodeQuery,
+    eligible_peers::EligiblePeers,
+    node_event::{EventLog, NodeEventKind},
+    stats::{DhtStats, Concurrency, CommandQueue},
     dht_verticle::VerticleOptions,
+    node::ExtensionHandler,
+    hole_punch::{self, DirectConnections, PunchResult},
+    announcement::{AnnouncementPolicy, Verdict},
+    clock_skew::{ClockSkew, SkewChange},
+    blocklist::{Blocklist, BlockReason},
+    socket_event::SocketErrors,
+    siblings::Siblings,
+    session_ids::SessionIds,
+    storage_event::{StorageEvent, StorageEvents, DEFAULT_STORAGE_EVENT_CAPACITY},
+    node_config::DEFAULT_CLOCK_SKEW_THRESHOLD,
     timer_client::LocalTimerClient as TimerClient,
     storage::data_storage::DataStorage,
     suspicious_node_detector::SuspiciousNodeDetector,
@@ -38,35 +59,49 @@ use crate::dht::{
         Reachability,
         RpcCall, rpccall::State as CallState,
         rpc_server::RpcServer,
+        socket_health::{SocketEvent, SocketHealthOptions},
+        send_shaper::SendShaperOptions,
         listener::Listener as CallListener
     },
     msg::{
         Message,
         LookupRequest, LookupResponse,
+        Rendezvous,
         msg::{self, Kind, Method, Body},
+        error::{GENERIC_ERROR, METHOD_UNKNOWN, PROTOCOL_ERROR},
     },
     routing::{
-        routing_table::RoutingTable,
+        routing_table::{RoutingTable, RoutingStrategy},
         KClosestNodes,
         KBucketEntry,
         KBucket,
         Prefix,
     },
     task::{
-        task::{State, Task},
+        task::{State, Task, TaskId},
         task_manager::TaskManager,
         task_listener::TaskListener,
+        lookup_coalescer::{LookupCoalescer, Joined, Waiter},
         LookupTask,
         NodeLookupTask,
         PeerLookupTask,
         ValueLookupTask,
         PeerAnnounceTask,
         ValueAnnounceTask,
-        PingRefreshTask
+        PingRefreshTask,
+        ClosestSet,
+        CandidateNode,
     }
 };
+#[cfg(feature = "testing")]
+use crate::testing::{LossyTransport, SimTransport};
+#[cfg(feature = "wsgateway")]
+use url::Url;
 
-    pub(crate) struct DHT {
+    type ValueLookupKey = (Id, i32, bool, Option<u64>);
+pub(crate) type ValueLookupWaiter = Waiter<ValueLookupKey>;
+
+pub(crate) struct DHT {
     identity            : Arc<CryptoIdentity>,
     network             : Network,
     host                : String,
@@ -78,11 +113,13 @@ use crate::dht::{
 
     storage             : Arc<Mutex<dyn DataStorage>>,
     tokenman            : Arc<TokenManager>,
+    clock               : Arc<dyn Clock>,
 
     task_man            : Rc<TaskManager>,
 
     persist_file        : Option<PathBuf>,
     rt                  : Option<Rc<RefCell<RoutingTable>>>,
+    routing_strategy    : RoutingStrategy,
 
     bootstrap_nodes     : Vec<NodeInfo>,
     bootstrap_ids       : Vec<Id>,
@@ -91,12 +128,49 @@ use crate::dht::{
 
     last_maintenance    : SystemTime,
     maintenance_tasks   : Rc<RefCell<HashSet<Prefix>>>,
+    refresh_lookups     : Rc<RefCell<HashSet<Prefix>>>,
+    bucket_refresh_interval: u64,
+
+    // Value lookups by target, expected sequence number and whether done on
+    // the first eligible value.
+    value_lookups       : Rc<RefCell<LookupCoalescer<ValueLookupKey, LookupOutcome<Option<ValueResult>>>>>,
+    // Write tokens of the closest nodes to the targets announced.
+    token_cache         : Rc<RefCell<TokenCache>>,
 
     timer_client        : Rc<TimerClient>,
 
     rpc_server          : Option<Rc<RefCell<RpcServer>>>,
 
     suspicious_detector : Option<Rc<RefCell<dyn SuspiciousNodeDetector>>>,
+    events              : EventLog,
+    socket_health       : Option<SocketHealthOptions>,
+    send_shaper         : Option<SendShaperOptions>,
+    receive_sockets     : usize,
+    #[cfg(feature = "testing")]
+    transport           : Option<Arc<LossyTransport>>,
+    #[cfg(feature = "testing")]
+    sim                 : Option<Arc<SimTransport>>,
+    #[cfg(feature = "wsgateway")]
+    ws_gateway          : Option<Url>,
+    extension_handler   : Arc<Mutex<Option<ExtensionHandler>>>,
+    direct_connections  : Arc<Mutex<DirectConnections>>,
+    endpoint_policy     : EndpointPolicy,
+    announcement_policy : AnnouncementPolicy,
+    required_countersigner: Option<Id>,
+    // The nodes whose values are stored at any distance from this node.
+    reject_far_stores   : bool,
+    pin_allowlist       : HashSet<Id>,
+    clock_skew          : Arc<ClockSkew>,
+    blocklist           : Option<Arc<Blocklist>>,
+    socket_errors       : Option<Arc<SocketErrors>>,
+    // The other instance of a dual-stack node, none when single-stack.
+    siblings            : Option<Arc<Siblings>>,
+    storage_events      : Arc<StorageEvents>,
+    prefer_low_rtt      : bool,
+    capabilities        : Capabilities,
+    // The identities the lookups of the application are sent from in
+    // privacy mode.
+    sessions            : Option<Rc<RefCell<SessionIds>>>,
     pub(crate) weak     : std::rc::Weak<RefCell<Self>>,
 }
 
@@ -107,6 +181,10 @@ impl DHT {
     const ROUTING_TABLE_MAINTENANCE_INTERVAL: u128 = 4 * 60 * 1000; // 4 minutes
     const RANDOM_LOOKUP_INTERVAL: u64 = 10 * 60 * 1000;             // 10 minutes
     const RANDOM_PING_INTERVAL  : u64 = 10 * 1000;                  // 10 seconds
+    const SIBLING_SYNC_INTERVAL : u64 = 5 * 1000;                   // 5 seconds
+    const PING_TIMEOUT          : u64 = 2 * 1000;                   // 2 seconds
+
+    const MAX_CONCURRENT_BUCKET_REFRESHES: usize = 2;
 
     const BOOTSTRAP_IF_LESS_THAN_X_ENTRIES: usize = 30;
     const USE_BOOTSTRAP_NODES_IF_LESS_THAN_X_ENTRIES: usize = 8;
@@ -121,7 +199,7 @@ impl DHT {
         assert!(options.identity.is_some());
         assert!(options.storage.is_some());
         assert!(options.token_man.is_some());
-        assert!(options.data_dir.is_some());
+        assert!(options.layout.is_some());
         assert!(options.listener.is_some());
 
         let identity = options.identity.as_ref().unwrap().clone();
@@ -130,6 +208,10 @@ impl DHT {
         let listener = options.listener.as_ref().unwrap().clone();
         let bootstrap_nodes = options.bootstrap_nodes.as_ref().map(|nodes| nodes.to_vec())
             .unwrap_or_else(Vec::new);
+        let events = options.event_log.as_ref()
+            .map(|log| log.with_network(network))
+            .unwrap_or_else(|| EventLog::new(0));
+        let clock = options.clock.clone().unwrap_or_else(|| Arc::new(SystemClock));
 
         Ok( Self {
             identity,
@@ -141,9 +223,11 @@ impl DHT {
             listener,
             storage,
             tokenman,
-            task_man            : Rc::new(TaskManager::new()),
+            clock               : clock.clone(),
+            task_man            : TaskManager::new(options.concurrency),
 
             rt                  : None,
+            routing_strategy    : options.routing_strategy,
             persist_file,
 
             bootstrap_nodes,
@@ -151,15 +235,54 @@ impl DHT {
             last_bootstrap      : SystemTime::UNIX_EPOCH,
             last_maintenance    : SystemTime::UNIX_EPOCH,
             maintenance_tasks   : Rc::new(RefCell::new(HashSet::new())),
+            refresh_lookups     : Rc::new(RefCell::new(HashSet::new())),
+            bucket_refresh_interval: options.bucket_refresh_interval,
+            value_lookups       : Rc::new(RefCell::new(LookupCoalescer::new(options.lookup_cache_ttl, clock.clone()))),
+            token_cache         : Rc::new(RefCell::new(TokenCache::new(clock))),
             bootstrapping       : AtomicBool::new(false),
             timer_client,
             suspicious_detector : None,
             rpc_server          : None,
+            events,
+            socket_health       : options.socket_health,
+            send_shaper         : options.send_shaper,
+            receive_sockets     : options.receive_sockets.unwrap_or(1),
+            #[cfg(feature = "testing")]
+            transport           : options.transport,
+            #[cfg(feature = "testing")]
+            sim                 : options.sim,
+            #[cfg(feature = "wsgateway")]
+            ws_gateway          : options.ws_gateway,
+            extension_handler   : options.extension_handler.unwrap_or_default(),
+            direct_connections  : options.direct_connections.unwrap_or_default(),
+            endpoint_policy     : options.endpoint_policy,
+            announcement_policy : options.announcement_policy,
+            required_countersigner: options.required_countersigner,
+            reject_far_stores   : options.reject_far_stores,
+            pin_allowlist       : options.pin_allowlist.into_iter().collect(),
+            clock_skew          : options.clock_skew.unwrap_or_else(||
+                Arc::new(ClockSkew::new(Duration::from_secs(DEFAULT_CLOCK_SKEW_THRESHOLD), false))
+            ),
+            blocklist           : options.blocklist,
+            socket_errors       : options.socket_errors,
+            siblings            : options.siblings.inspect(|siblings| siblings.attach(network)),
+            storage_events      : options.storage_events.unwrap_or_else(||
+                Arc::new(StorageEvents::new(DEFAULT_STORAGE_EVENT_CAPACITY))
+            ),
+            prefer_low_rtt      : options.prefer_low_rtt,
+            capabilities        : options.capabilities,
+            sessions            : options.privacy.map(|rotation|
+                Rc::new(RefCell::new(SessionIds::new(rotation)))
+            ),
 
             weak                : Weak::new(), // will be set later
         })
     }
 
+    pub(crate) fn prefer_low_rtt(&self) -> bool {
+        self.prefer_low_rtt
+    }
+
     pub(crate) fn network(&self) -> Network {
         self.network
     }
@@ -178,6 +301,45 @@ impl DHT {
         self.rt.as_ref().expect("RT not initialized").clone()
     }
 
+    pub(crate) fn token_cache(&self) -> Rc<RefCell<TokenCache>> {
+        self.token_cache.clone()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn events(&self) -> &EventLog {
+        &self.events
+    }
+
+    pub(crate) fn stats(&self) -> DhtStats {
+        let rs = self.rpc_server.as_ref().map(|rs| rs.borrow());
+        DhtStats {
+            network         : self.network,
+            routing_entries : self.rt.as_ref().map_or(0, |rt| rt.borrow().number_of_entries()),
+            versions        : self.rt.as_ref().map(|rt| version::tally(
+                rt.borrow().buckets().iter()
+                    .flat_map(|bucket| bucket.borrow().entries())
+                    .map(|entry| entry.version())
+            )).unwrap_or_default(),
+            reachable       : rs.as_ref().is_some_and(|rs| rs.is_reachable()),
+            addr            : rs.as_ref().and_then(|rs| rs.local_addr()),
+            counters        : rs.as_ref().map(|rs| rs.counters()).unwrap_or_default(),
+            active_tasks    : self.task_man.active(),
+            concurrency     : Concurrency {
+                running_tasks   : self.task_man.running(),
+                peak_tasks      : self.task_man.peak_running(),
+                queued_tasks    : self.task_man.queued(),
+                inflight_calls  : rs.as_ref().map_or(0, |rs| rs.inflight_calls()),
+                peak_calls      : rs.as_ref().map_or(0, |rs| rs.peak_inflight_calls()),
+                deferred_calls  : rs.as_ref().map_or(0, |rs| rs.deferred_calls()),
+            },
+            // Filled in by the verticle, which owns the command queue.
+            commands        : CommandQueue::default(),
+            value_lookups   : self.value_lookups.borrow().started_count(),
+            token_cache     : self.token_cache.borrow().stats(),
+            receive         : rs.as_ref().and_then(|rs| rs.receive_stats()),
+        }
+    }
+
     pub(crate) fn dht(&self) -> Rc<RefCell<Self>> {
         self.weak.upgrade().expect("DHT instance is dropped")
     }
@@ -212,7 +374,7 @@ impl DHT {
         task.with_listener(TaskListener::default().ended_fn(
             move |_| promise.complete(Ok(()))
         ));
-        self.task_man.add(task);
+        self.task_man.add_maintenance(task);
     }
 
     fn fill_buckets(&self, promise: Promise<()>) {
@@ -238,7 +400,7 @@ impl DHT {
             task.with_listener(TaskListener::default().ended_fn(
                 move |_| promise.complete(Ok(()))
             ));
-            self.task_man.add(task);
+            self.task_man.add_maintenance(task);
             unordered.push(future);
         }
 
@@ -288,7 +450,7 @@ impl DHT {
                     TaskListener::default().ended_fn(move |_| {
                         maintenance_tasks.borrow_mut().remove(&prefix_to_remove);
                 }));
-                dht.borrow().task_man.add(task);
+                dht.borrow().task_man.add_maintenance(task);
             }
         }
     }
@@ -303,7 +465,7 @@ impl DHT {
             self.dht(), Id::random(), false,
         ));
         task.with_name("Periodic: random node lookup".into());
-        self.task_man.add(task);
+        self.task_man.add_maintenance(task);
     }
 
     pub(crate) fn random_ping(&self) {
@@ -323,40 +485,97 @@ impl DHT {
         let _ = self.send_call(call);
     }
 
+    // Blocks set by the application since the last update, those set on
+    // strikes evict right away.
+    fn evict_blocked(&self) {
+        let evicted = self.rt().borrow_mut().evict_blocked();
+        if evicted > 0 {
+            info!("Evicted {evicted} blocked nodes from the routing table");
+        }
+    }
+
+    // An invalid token or value from the node, which gets it blocked and
+    // out of the routing table after too many of them.
+    fn strike(&self, id: &Id, reason: BlockReason) {
+        let Some(blocklist) = self.blocklist.as_ref() else {
+            return;
+        };
+        if !blocklist.strike(id, reason) {
+            return;
+        }
+        self.events.record(NodeEventKind::NodeBlocked { id: *id, reason });
+        if self.rt.as_ref().is_some_and(|rt| rt.borrow_mut().remove(id).is_some()) {
+            blocklist.on_evicted(1);
+        }
+    }
+
     fn routing_table_maintenance(&mut self) {
-        if crate::elapsed_ms!(self.last_maintenance) <
+        if self.clock.elapsed_ms(self.last_maintenance) <
                 Self::ROUTING_TABLE_MAINTENANCE_INTERVAL {
             return;
         }
 
         debug!("Routing table maintenance ...");
-        self.last_maintenance = SystemTime::now();
+        self.last_maintenance = self.clock.now();
+
+        // Idle buckets are only refreshed while the server is reachable, and
+        // at most a few at a time to not flood the network after a long sleep.
+        let max_refreshes = match self.rs().borrow().is_reachable() {
+            true  => Self::MAX_CONCURRENT_BUCKET_REFRESHES
+                .saturating_sub(self.refresh_lookups.borrow().len()),
+            false => 0,
+        };
 
         let dht = self.dht();
         let ids = self.bootstrap_ids.clone();
+        let lookup_dht = self.dht();
         let _ = self.rt().borrow_mut().maintenance(
             ids.as_slice(),
+            self.bucket_refresh_interval as u128 * 1000,
+            max_refreshes,
             Handler::new(move |bucket: &Rc<RefCell<KBucket>>| {
                 let prefix = bucket.borrow().prefix().clone();
                 Self::try_ping_maintenance(dht.clone(), bucket.clone(), false, false, false,
                         format!("Routing table maintenance: refreshing bucket {}", prefix)
                     );
+            }),
+            Handler::new(move |prefix: &Prefix| {
+                lookup_dht.borrow().refresh_bucket(*prefix);
             })
         );
     }
 
+    // Looks up a random id in the range of an idle bucket.
+    fn refresh_bucket(&self, prefix: Prefix) {
+        let refresh_lookups = self.refresh_lookups.clone();
+        if !refresh_lookups.borrow_mut().insert(prefix) {
+            return;
+        }
+
+        let mut task = Box::new(NodeLookupTask::new(
+            self.dht(), prefix.random_id(), false
+        ));
+        task.with_name(format!("Routing table maintenance: refreshing idle bucket {}", prefix));
+        task.with_listener(TaskListener::default().ended_fn(move |_| {
+            refresh_lookups.borrow_mut().remove(&prefix);
+        }));
+        self.task_man.add_maintenance(task);
+    }
+
     async fn update(&mut self) {
         let bootstrap_nodes = {
             if !self.is_running {
                 return;
             }
+            self.evict_blocked();
+            self.routing_table_maintenance();
 
             let rt = self.rt();
             let borrowed_rt = rt.borrow();
 
             let entry_sz = borrowed_rt.number_of_entries();
             if entry_sz >= Self::BOOTSTRAP_IF_LESS_THAN_X_ENTRIES &&
-                crate::elapsed_ms!(self.last_bootstrap) <= Self::SELF_LOOKUP_INTERVAL {
+                self.clock.elapsed_ms(self.last_bootstrap) <= Self::SELF_LOOKUP_INTERVAL {
                 return;
             }
 
@@ -368,7 +587,6 @@ impl DHT {
         };
 
         debug!("Periodic: DHT/{} update...", self.network());
-        self.routing_table_maintenance();
 
         let dht = self.dht();
         let _ = task::spawn_local(async move {
@@ -397,6 +615,33 @@ impl DHT {
         }
     }
 
+    fn on_socket_event(&mut self, event: SocketEvent) {
+        match event {
+            SocketEvent::Failed(_) => {
+                self.set_status(ConnectionStatus::Disconnected);
+            },
+            SocketEvent::Rebound(addr) => {
+                self.host = addr.ip().to_string();
+                self.port = addr.port();
+                self.set_status(ConnectionStatus::Connecting);
+
+                // Bootstrap right away, regardless of the last attempt
+                self.last_bootstrap = SystemTime::UNIX_EPOCH;
+                let dht = self.dht();
+                let rt  = self.rt();
+                let nodes = self.bootstrap_nodes.clone();
+                task::spawn_local(async move {
+                    Self::do_bootstrap(dht.clone(), nodes).await;
+                    if rt.borrow().number_of_entries() > 0 {
+                        dht.borrow_mut().set_status(ConnectionStatus::Connected);
+                    } else {
+                        dht.borrow_mut().set_status(ConnectionStatus::Disconnected);
+                    }
+                });
+            }
+        }
+    }
+
     pub(crate) async fn start0(&mut self) -> Result<()> {
         if self.is_running {
             return Ok(());
@@ -409,7 +654,10 @@ impl DHT {
             self.port
         );
 
-        let mut rt = RoutingTable::new(self.id().clone());
+        let mut rt = RoutingTable::with_strategy(self.id().clone(), self.routing_strategy);
+        if let Some(blocklist) = self.blocklist.as_ref() {
+            rt.set_blocklist(blocklist.clone());
+        }
         if let Some(ref path) = self.persist_file {
             let file = path.display();
             let suc_cb = |_| debug!("Loaded routing table from {}.", file);
@@ -420,6 +668,7 @@ impl DHT {
                 .map(suc_cb)
                 .map_err(err_cb);
         };
+        rt.set_event_log(self.events.clone());
         self.rt = Some(Rc::new(RefCell::new(rt)));
 
         // initialize RPC server
@@ -429,6 +678,46 @@ impl DHT {
             self.timer_client.clone(),
             self.suspicious_detector.clone()
         );
+        rs.set_event_log(self.events.clone());
+        if let Some(blocklist) = self.blocklist.as_ref() {
+            rs.set_blocklist(blocklist.clone());
+        }
+        if let Some(errors) = self.socket_errors.clone() {
+            rs.set_socket_errors(errors);
+        }
+        if let Some(options) = self.socket_health {
+            rs.set_socket_health(options);
+        }
+        if let Some(options) = self.send_shaper {
+            rs.set_send_shaper(options);
+        }
+        rs.set_receive_sockets(self.receive_sockets);
+        #[cfg(feature = "testing")]
+        if let Some(transport) = self.transport.clone() {
+            rs.set_transport(transport);
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sim) = self.sim.clone() {
+            rs.set_sim(sim);
+        }
+        #[cfg(feature = "wsgateway")]
+        if let Some(url) = self.ws_gateway.clone() {
+            rs.set_ws_gateway(url);
+        }
+        rs.set_max_inflight_calls(self.task_man.limits().max_inflight_calls);
+        rs.set_endpoint_policy(self.endpoint_policy);
+        if let Some(sessions) = self.sessions.clone() {
+            rs.set_session_ids(sessions);
+        }
+        rs.set_capabilities(self.capabilities, self.rt());
+
+        let dht = self.dht();
+        rs.socket_handler(AsyncHandler::new(move |event: SocketEvent| {
+            let dht = dht.clone();
+            Box::pin(async move {
+                dht.borrow_mut().on_socket_event(event);
+            })
+        }));
 
         let dht = self.dht();
         rs.message_handler(AsyncHandler::new(move |msg: Rc<Message>| {
@@ -447,9 +736,11 @@ impl DHT {
         }));
 
         let rt = self.rt();
+        let events = self.events.clone();
         rs.calltimeout_handler(Handler::new({
             let rt = rt.clone();
             move |nodeid: &Id| {
+                events.record(NodeEventKind::CallTimeout { id: *nodeid });
                 rt.borrow_mut().on_timeout(&nodeid);
             }
         }));
@@ -512,7 +803,7 @@ impl DHT {
             task.with_listener(TaskListener::default().ended_fn(move |_| {
                 task_promise.complete(Ok(()));
             }));
-            task_man.add(task);
+            task_man.add_maintenance(task);
             unordered.push(task_future);
         }
 
@@ -581,6 +872,10 @@ impl DHT {
             sd.borrow_mut().purge();
         }
 
+        if let Some(siblings) = self.siblings.as_ref() {
+            siblings.detach(self.network);
+        }
+
         info!("Stopped DHT/{}:{} on {}:{}.",
             self.network, self.id(), self.host, self.port);
     }
@@ -608,6 +903,20 @@ impl DHT {
             })
         )?;
 
+        if self.siblings.is_some() {
+            let dht = self.dht();
+            let _ = self.timer_client.add_timer(
+                Self::SIBLING_SYNC_INTERVAL,
+                Some(Self::SIBLING_SYNC_INTERVAL),
+                AsyncHandler::new(move |_| {
+                    let dht = dht.clone();
+                    Box::pin(async move {
+                        dht.borrow_mut().sync_sibling();
+                    })
+                })
+            )?;
+        }
+
         let dht = self.dht();
         let _ = self.timer_client.add_timer(
             Self::RANDOM_PING_INTERVAL,
@@ -655,6 +964,16 @@ impl DHT {
         }
     }
 
+    // A node answered a lookup with a forged value, which counts against
+    // it in the routing table until it gets replaced.
+    pub(crate) fn on_forged_value(&self, from: &Id, target: &Id) {
+        self.events.record(NodeEventKind::ValueRejected { from: *from, target: *target });
+        if let Some(rt) = self.rt.as_ref() {
+            rt.borrow().on_strike(from);
+        }
+        self.strike(from, BlockReason::InvalidValue);
+    }
+
     fn suspicious_last_known_id(&self, addr: SocketAddr) -> Option<Id> {
         self.suspicious_detector.as_ref().and_then(|detector| {
             detector.borrow_mut().last_known_id(&addr).cloned()
@@ -775,10 +1094,15 @@ impl DHT {
 
         let mut new_entry = KBucketEntry::new(remote_id, remote_addr);
         new_entry.set_ver(msg.ver());
+        if let Some(caps) = msg.capabilities() {
+            new_entry.set_capabilities(caps);
+        }
 
-        if let Some(_call) = call_opt {
-            new_entry.on_responded(0); // TOOD: RTT.
-            new_entry.update_last_sent(_call.borrow().sent_time().unwrap());
+        if let Some(call) = call_opt {
+            let call = call.borrow();
+            // a sub-millisecond round trip still counts as a sample
+            new_entry.on_responded(call.rtt().map_or(0, |rtt| rtt.max(1)));
+            new_entry.update_last_sent(call.sent_time().unwrap());
         }
 
         self.rt().borrow_mut().put(new_entry.clone());
@@ -794,9 +1118,18 @@ impl DHT {
         }
     }
 
-    fn send_err(&mut self, method: Method, code: i32, str: &str) {
-        let msg = msg::error_msg(method, 0, code, str.into());
-        // TODO: set remote id and addr
+    // Sends the response, with our capabilities when the requester decodes them.
+    fn send_rsp(&self, req: &Message, mut rsp: Message) {
+        if version::supports_capabilities(req.ver()) {
+            rsp.set_capabilities(self.capabilities);
+        }
+        self.send_msg(rsp);
+    }
+
+    fn send_err(&mut self, req: &Message, code: i32, str: &str) {
+        let mut msg = msg::error_msg(req.method(), req.txid(), code, str.into());
+        msg.set_remote(*req.remote_id(), *req.remote_addr());
+        msg.set_nodeid(*self.id());
         self.send_msg(msg);
     }
 
@@ -834,6 +1167,11 @@ impl DHT {
             Kind::Response => self.on_response(&msg),
         };
 
+        // A lookup from a session id in privacy mode, the requester is gone
+        // once the lookup ends, nothing to route to.
+        if msg.is_transient() {
+            return;
+        }
         self.received(msg);
     }
 
@@ -846,11 +1184,41 @@ impl DHT {
             Method::FindPeer    => self.on_find_peer(msg),
             Method::StoreValue  => self.on_store_value(msg),
             Method::AnnouncePeer=> self.on_announce_peer(msg),
+            Method::Extension   => self.on_extension(msg),
+            Method::Rendezvous  => self.on_rendezvous(msg),
             _                   => self.on_unknown_req(msg),
         }
     }
 
-    fn on_response(&mut self, _msg: &Message) {}
+    fn on_response(&mut self, msg: &Message) {
+        let Some(remote) = msg.time() else {
+            return;
+        };
+        // Only the times of the nodes really called count.
+        let Some(call) = msg.associated_call() else {
+            return;
+        };
+        if call.borrow().nodeid_mismatched() || call.borrow().addr_mismatched() {
+            return;
+        }
+
+        match self.clock_skew.record(msg.nodeid(), remote, self.clock.now_ms()) {
+            Some(SkewChange::Exceeded(offset)) => {
+                let nodes = self.clock_skew.nodes();
+                warn!("!!! The local clock is off by {}s from the clocks of {} other nodes, \
+                    check the time settings of this host !!!", offset / 1000, nodes);
+                self.events.record(NodeEventKind::ClockSkewDetected {
+                    offset_secs: offset / 1000,
+                    nodes
+                });
+            },
+            Some(SkewChange::Recovered) => {
+                info!("The local clock is back in line with other nodes");
+                self.events.record(NodeEventKind::ClockSkewRecovered);
+            },
+            None => {},
+        }
+    }
 
     fn on_error(&mut self, msg: &Message) {
         let Some(Body::Error(err)) = msg.body() else {
@@ -867,12 +1235,120 @@ impl DHT {
     }
 
     fn on_unknown_req(&mut self, msg: &Message) {
-        warn!("Received unknown request {} from {}@{}, txid {}, ignoring it",
+        warn!("Received unknown request {} from {}@{}, txid {}",
             msg.method(),
             msg.remote_id(),
             msg.remote_addr(),
             msg.txid()
         );
+        self.send_err(msg, METHOD_UNKNOWN, "Method not supported");
+    }
+
+    fn on_extension(&mut self, req: &Message) {
+        let Some(Body::ExtensionRequest(body)) = req.body() else {
+            return;
+        };
+
+        let sender = NodeInfo::new(*req.remote_id(), *req.remote_addr());
+        let handled = self.extension_handler.lock().unwrap().as_ref()
+            .map(|handler| handler(&sender, body.data()));
+
+        let Some(result) = handled else {
+            debug!("No extension handler, rejecting extension request from {}", sender);
+            self.send_err(req, METHOD_UNKNOWN, "Method not supported");
+            return;
+        };
+
+        let Some(data) = result else {
+            self.send_err(req, GENERIC_ERROR, "Extension request rejected");
+            return;
+        };
+
+        let rsp = {
+            let mut msg = msg::extension_response(req.txid(), data);
+            msg.set_remote(*req.remote_id(), *req.remote_addr());
+            msg.set_nodeid(*self.id());
+            msg
+        };
+        self.send_rsp(req, rsp);
+    }
+
+    // Answers a direct connection attempt with the probe port and addresses
+    // of a fresh socket, then probes the requester from it. Refused unless
+    // the application takes direct connections.
+    fn on_rendezvous(&mut self, req: &Message) {
+        let Some(Body::RendezvousRequest(body)) = req.body() else {
+            return;
+        };
+
+        let direct = self.direct_connections.clone();
+        let pattern = {
+            let mut locked = direct.lock().unwrap();
+            if locked.handler.is_none() {
+                drop(locked);
+                debug!("Direct connections not accepted, rejecting rendezvous from {}", req.remote_id());
+                self.send_err(req, METHOD_UNKNOWN, "Method not supported");
+                return;
+            }
+            if locked.incoming >= hole_punch::MAX_INCOMING_ATTEMPTS {
+                drop(locked);
+                self.send_err(req, GENERIC_ERROR, "Too many direct connection attempts");
+                return;
+            }
+            locked.incoming += 1;
+            locked.pattern.clone()
+        };
+
+        let ip = self.ni().ip();
+        let socket = std::net::UdpSocket::bind(SocketAddr::new(ip, 0)).and_then(|socket| {
+            socket.set_nonblocking(true)?;
+            tokio::net::UdpSocket::from_std(socket)
+        });
+        let (socket, port) = match socket.and_then(|v| v.local_addr().map(|addr| (v, addr.port()))) {
+            Ok(v) => v,
+            Err(e) => {
+                direct.lock().unwrap().incoming -= 1;
+                warn!("Binding a probe socket failed: {e}");
+                self.send_err(req, GENERIC_ERROR, "Direct connection unavailable");
+                return;
+            }
+        };
+
+        let rsp = {
+            let body = Rendezvous::new(body.session(), port, &hole_punch::local_hosts(ip));
+            let mut msg = msg::rendezvous_response(req.txid(), body);
+            msg.set_remote(*req.remote_id(), *req.remote_addr());
+            msg.set_nodeid(*self.id());
+            msg
+        };
+        self.send_rsp(req, rsp);
+
+        let peer = *req.remote_id();
+        let session = body.session();
+        let candidates = hole_punch::candidates(
+            &body.hosts(),
+            Some(req.remote_addr().ip()),
+            body.port(),
+            &pattern,
+            ip.is_ipv4()
+        );
+        task::spawn_local(async move {
+            let result = hole_punch::punch(&socket, session, &candidates, &pattern).await
+                .and_then(|(remote, rtt)| PunchResult::new(peer, socket, remote, rtt));
+            let handler = {
+                let mut locked = direct.lock().unwrap();
+                locked.incoming -= 1;
+                locked.handler.clone()
+            };
+            match (result, handler) {
+                (Ok(result), Some(handler)) => {
+                    info!("Direct connection set up by {}", result);
+                    handler(result);
+                },
+                (Ok(_), None) => debug!("Direct connections no longer accepted, dropped the one with {peer}"),
+                (Err(e), _) => debug!("Direct connection attempt from {peer} failed: {e}"),
+            }
+        });
     }
 
     fn on_ping(&mut self, req: &Message) {
@@ -888,32 +1364,112 @@ impl DHT {
             msg.set_nodeid(*self.id());
             msg
         };
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
     }
 
+    // Nodes returned in find_node/find_value responses, the faster ones
+    // preferred among equally distant nodes when enabled.
     fn fill_closest_nodes(&self, target: Id) -> Vec<NodeInfo> {
         let mut kns = KClosestNodes::new(
             &self.rt().borrow(),
             target,
             KBucket::MAX_ENTRIES
         );
+        kns.set_prefer_low_rtt(self.prefer_low_rtt);
         kns.fill();
         kns.into()
     }
 
+    fn sibling_network(&self) -> Network {
+        match self.network {
+            Network::IPv4 => Network::IPv6,
+            Network::IPv6 => Network::IPv4,
+        }
+    }
+
+    // Nodes of `network` for the responses, those of the other family come
+    // from the sibling instance of a dual-stack node, none without it.
+    fn closest_nodes_of(&self, network: Network, target: Id) -> Option<Vec<NodeInfo>> {
+        if network == self.network {
+            return Some(self.fill_closest_nodes(target));
+        }
+        self.siblings.as_ref()?.closest(network, &target, KBucket::MAX_ENTRIES)
+    }
+
+    // The families to ask for in find_node requests, the one of the sibling
+    // only while it is short of contacts.
+    fn wants(&self) -> (bool, bool) {
+        let sibling = self.sibling_network();
+        let want_sibling = self.siblings.as_ref()
+            .is_some_and(|siblings| siblings.wants_contacts(sibling));
+        match self.network {
+            Network::IPv4 => (true, want_sibling),
+            Network::IPv6 => (want_sibling, true),
+        }
+    }
+
+    fn publish_to_sibling(&self) {
+        let Some(siblings) = self.siblings.as_ref() else {
+            return;
+        };
+        let nodes = self.rt().borrow().buckets().iter()
+            .flat_map(|bucket| bucket.borrow().entries())
+            .filter(|entry| entry.eligible_for_nodes_list())
+            .map(|entry| entry.into())
+            .collect();
+        siblings.publish(self.network, nodes);
+    }
+
+    // Publishes the routing table for the sibling and bootstraps from the
+    // contacts it handed over while the routing table is still small.
+    fn sync_sibling(&mut self) {
+        let Some(siblings) = self.siblings.clone() else {
+            return;
+        };
+        if !self.is_running {
+            return;
+        }
+
+        self.publish_to_sibling();
+        let nodes = siblings.take_handed_over(self.network);
+        if nodes.is_empty() ||
+            self.rt().borrow().number_of_entries() >= Self::USE_BOOTSTRAP_NODES_IF_LESS_THAN_X_ENTRIES {
+            return;
+        }
+
+        debug!("DHT/{} bootstrapping from {} contacts of the sibling", self.network, nodes.len());
+        self.last_bootstrap = SystemTime::UNIX_EPOCH;
+        let dht = self.dht();
+        task::spawn_local(async move {
+            Self::do_bootstrap(dht, nodes).await;
+        });
+    }
+
+    pub(crate) fn closest_nodes(&self, target: Id, count: usize, include_self: bool) -> Vec<NodeInfo> {
+        let mut kns = KClosestNodes::new(
+            &self.rt().borrow(),
+            target,
+            count
+        );
+        kns.fill();
+        if include_self {
+            kns.add_self(*self.ni().socket_addr());
+        }
+        kns.into()
+    }
+
     fn on_find_node(&mut self, req: &Message) {
         let Some(Body::FindNodeRequest(body)) = req.body() else {
             return;
         };
 
-        let network= self.network();
         let target = body.target().clone();
-        let nodes4 = match body.want4() && network.is_ipv4() {
-            true  => Some(self.fill_closest_nodes(target)),
+        let nodes4 = match body.want4() {
+            true  => self.closest_nodes_of(Network::IPv4, target),
             false => None
         };
-        let nodes6 = match body.want6() && network.is_ipv6() {
-            true  => Some(self.fill_closest_nodes(target)),
+        let nodes6 = match body.want6() {
+            true  => self.closest_nodes_of(Network::IPv6, target),
             false => None
         };
         let token  = match body.want_token() {
@@ -925,11 +1481,14 @@ impl DHT {
         let rsp = {
             let txid = req.txid();
             let mut msg = msg::find_node_response(txid, nodes4, nodes6, token);
+            if body.want_time() {
+                msg.set_time(self.clock.now_ms() / 1000);
+            }
             msg.set_remote(*req.remote_id(), *req.remote_addr());
             msg.set_nodeid(*self.id());
             msg
         };
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
     }
 
     fn on_find_value(&mut self, req: &Message) {
@@ -942,6 +1501,7 @@ impl DHT {
             Ok(v) => v,
             Err(e) => {
                 warn!("Retrieve value for {} error: {}", body.target(), e);
+                self.events.record(NodeEventKind::StorageError { op: "get_value" });
                 return;
             }
         };
@@ -955,26 +1515,36 @@ impl DHT {
         }
 
         let txid = req.txid();
-        let mut rsp = if let Some(value) = value {
-            msg::find_value_response(txid, value)
+        let mut rsp = if let Some(mut value) = value {
+            // Older nodes reject the unknown fields of the counter-signature.
+            if !version::supports_countersignature(req.ver()) {
+                value = value.with_countersignature(None, None);
+            }
+            let age = match body.want_age() {
+                true  => self.record_age(self.storage.lock().unwrap().get_value_updated(body.target())),
+                false => None
+            };
+            msg::find_value_response(txid, value, age)
         } else {
-            let network = self.network();
             let target = body.target().clone();
-            let nodes4 = match body.want4() && network.is_ipv4() {
-                true  => Some(self.fill_closest_nodes(target)),
+            let nodes4 = match body.want4() {
+                true  => self.closest_nodes_of(Network::IPv4, target),
                 false => None
             };
-            let nodes6 = match body.want6() && network.is_ipv6() {
-                true  => Some(self.fill_closest_nodes(target)),
+            let nodes6 = match body.want6() {
+                true  => self.closest_nodes_of(Network::IPv6, target),
                 false => None
             };
             msg::find_value_response_with_nodes(txid, nodes4, nodes6)
         };
+        if body.want_time() {
+            rsp.set_time(self.clock.now_ms() / 1000);
+        }
 
         rsp.set_remote(*req.remote_id(), *req.remote_addr());
         rsp.set_nodeid(*self.id());
 
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
     }
 
     fn on_store_value(&mut self, req: &Message) {
@@ -992,10 +1562,31 @@ impl DHT {
 
         if !is_valid {
             warn!("Invalid token for store value request from {}", remote_addr);
+            self.events.record(NodeEventKind::TokenRejected {
+                from: remote_addr,
+                target: value_id
+            });
+            self.send_err(req, PROTOCOL_ERROR, "Invalid token");
+            self.strike(req.nodeid(), BlockReason::InvalidToken);
             return;
         }
         if !value.is_valid() {
             warn!("Invalid value for store value request from {}", remote_addr);
+            self.strike(req.nodeid(), BlockReason::InvalidValue);
+            return;
+        }
+        if let Some(signer) = self.required_countersigner.as_ref() {
+            if !value.verify_countersignature(signer) {
+                warn!("Rejecting value {}: missing or invalid counter-signature from {}", value_id, remote_addr);
+                self.send_err(req, 300,
+                    "Value is not counter-signed by the required countersigner");
+                return;
+            }
+        }
+        if self.reject_far_stores && !self.pin_allowlist.contains(req.nodeid()) && self.is_far_from(&value_id) {
+            warn!("Rejecting value {}: too far from this node, stored by {}", value_id, remote_addr);
+            self.send_err(req, 300,
+                "Value is not close to this node");
             return;
         }
 
@@ -1004,26 +1595,30 @@ impl DHT {
             Ok(v) => v,
             Err(e) => {
                 warn!("Retrieve existing value {} error: {}", value_id, e);
+                self.events.record(NodeEventKind::StorageError { op: "get_value" });
                 return;
             }
         };
 
-        if let Some(existing) = local_value {
+        let previous = local_value.as_ref().map(|v| v.sequence_number());
+        // The claims on a name are merged into those held, never replaced.
+        let merged = name_record::merge_claims(local_value.as_ref(), value, self.clock.now_ms());
+        if let Some(existing) = local_value.filter(|_| merged.is_none()) {
             if existing.is_mutable() != value.is_mutable() {
                 warn!("Rejecting value {}: cannot replace mismatched mutable/immutable", value_id);
-                self.send_err(Method::StoreValue, 300,
+                self.send_err(req, 300,
                     "Cannot replace mismatched mutable/immutable value");
                 return;
             }
             if value.sequence_number() < existing.sequence_number() {
                 warn!("Rejecting value {}: sequence number {} is less than existing {}", value_id, value.sequence_number(), existing.sequence_number());
-                self.send_err(Method::StoreValue, 300,
+                self.send_err(req, 300,
                     "Sequence number is less than existing value");
                 return;
             }
             if body.expected_seq() >= 0 && existing.sequence_number() > body.expected_seq() {
                 warn!("Rejecting value {}: existing sequence number {} is greater than expected {}", value_id, existing.sequence_number(), body.expected_seq());
-                self.send_err(Method::StoreValue, 300,
+                self.send_err(req, 300,
                     "Existing sequence number is greater than expected");
                 return;
             }
@@ -1035,7 +1630,14 @@ impl DHT {
             }
         }
 
-        _ = self.storage.lock().unwrap().put_value(value.clone(), false);
+        let value = merged.as_ref().unwrap_or(value);
+        match self.storage.lock().unwrap().put_value(value.clone(), false) {
+            Ok(_) => self.storage_events.emit(StorageEvent::value_stored(value, previous, false)),
+            Err(e) => {
+                warn!("Store value {} error: {}", value_id, e);
+                self.events.record(NodeEventKind::StorageError { op: "put_value" });
+            }
+        }
 
         let rsp = {
             let mut msg = msg::store_value_response(req.txid());
@@ -1044,7 +1646,19 @@ impl DHT {
             msg
         };
 
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
+    }
+
+    // Whether a full bucket of verified nodes is closer to the target than
+    // this node, a lookup for it would have ended on them. Measured against
+    // the bucket size storers pick their store set by, not the configured
+    // bucket capacity of this node, which may be smaller.
+    fn is_far_from(&self, target: &Id) -> bool {
+        // Only verified entries are handed out as the closest ones.
+        let closest = self.closest_nodes(*target, KBucket::MAX_ENTRIES, false);
+        closest.len() >= KBucket::MAX_ENTRIES && closest.iter().all(|ni|
+            target.three_way_compare(ni.id(), self.id()) == std::cmp::Ordering::Less
+        )
     }
 
     fn on_find_peer(&mut self, req: &Message) {
@@ -1052,38 +1666,73 @@ impl DHT {
             return;
         };
 
+        // All of them when filtering, the first ones stored may not match.
+        let tags = body.tags();
+        let network = body.network();
+        let limit = match tags.is_empty() && network.is_none() {
+            true => body.expected_count(),
+            false => i32::MAX,
+        };
         let result = self.storage.lock().unwrap().get_peers_with_expected_seq(
-            body.target(), body.expected_seq(), body.expected_count()
+            body.target(), body.expected_seq(), limit
         );
-        let peers = match result {
+        let mut peers = match result {
             Ok(v) => v,
             Err(e) => {
                 warn!("Retrieve peers for {} error: {}", body.target(), e);
+                self.events.record(NodeEventKind::StorageError { op: "get_peers" });
                 return;
             }
         };
+        if !tags.is_empty() || network.is_some() {
+            peers.retain(|p| tags.iter().all(|tag| p.tags().contains(tag)));
+            peers.retain(|p| EligiblePeers::in_network(p, network));
+            peers.truncate(body.expected_count().max(0) as usize);
+        }
+        let peers = EligiblePeers::decodable_by(peers, req.ver());
 
         let txid = req.txid();
         let mut rsp = if peers.is_empty() {
-            let network= self.network();
             let target = body.target().clone();
-            let nodes4 = match body.want4() && network.is_ipv4() {
-                true  => Some(self.fill_closest_nodes(target)),
+            let nodes4 = match body.want4() {
+                true  => self.closest_nodes_of(Network::IPv4, target),
                 false => None
             };
-            let nodes6 = match body.want6() && network.is_ipv6() {
-                true  => Some(self.fill_closest_nodes(target)),
+            let nodes6 = match body.want6() {
+                true  => self.closest_nodes_of(Network::IPv6, target),
                 false => None
             };
             msg::find_peer_response_with_nodes(txid, nodes4, nodes6)
         } else {
-            msg::find_peer_response(txid, peers)
+            let ages = body.want_age().then(|| {
+                let storage = self.storage.lock().unwrap();
+                peers.iter().map(|p| {
+                    self.record_age(storage.get_peer_updated(p.id(), p.fingerprint()))
+                        .unwrap_or(0)
+                }).collect()
+            });
+            msg::find_peer_response(txid, peers, ages)
         };
+        if body.want_time() {
+            rsp.set_time(self.clock.now_ms() / 1000);
+        }
 
         rsp.set_remote(*req.remote_id(), *req.remote_addr());
         rsp.set_nodeid(*self.id());
 
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
+    }
+
+    // Seconds since a record was stored or refreshed, going by the updated
+    // time kept in storage. Relative, so clock skew between nodes doesn't matter.
+    fn record_age(&self, updated: Result<Option<u64>>) -> Option<u64> {
+        match updated {
+            Ok(v) => v.map(|t| self.clock.now_ms().saturating_sub(t) / 1000),
+            Err(e) => {
+                warn!("Retrieve record time error: {}", e);
+                None
+            }
+        }
     }
 
     fn on_announce_peer(&mut self, req: &Message) {
@@ -1099,12 +1748,39 @@ impl DHT {
 
         if !is_valid {
             warn!("Invalid token for announce peer request from {}", remote_addr);
+            self.events.record(NodeEventKind::TokenRejected {
+                from: remote_addr,
+                target: *peer.id()
+            });
+            self.send_err(req, PROTOCOL_ERROR, "Invalid token");
+            self.strike(req.nodeid(), BlockReason::InvalidToken);
             return;
         }
         if !peer.is_valid() {
             warn!("Invalid peer for announce peer request from {}", remote_addr);
+            self.strike(req.nodeid(), BlockReason::InvalidValue);
             return;
         }
+        let now = self.clock.now_ms().saturating_add_signed(self.clock_skew.compensation());
+        match self.announcement_policy.check(peer, now) {
+            Verdict::Accepted => {},
+            Verdict::Untimed => {
+                debug!("Peer {} announced by {} without announcement time", peer.id(), remote_addr);
+                self.events.record(NodeEventKind::AnnouncementUntimed {
+                    from: remote_addr,
+                    target: *peer.id()
+                });
+            },
+            Verdict::Rejected(reason) => {
+                warn!("Rejecting peer {} announced by {}: {}", peer.id(), remote_addr, reason);
+                self.events.record(NodeEventKind::AnnouncementRejected {
+                    from: remote_addr,
+                    target: *peer.id()
+                });
+                self.send_err(req, PROTOCOL_ERROR, reason);
+                return;
+            },
+        }
 
         let result = self.storage.lock().unwrap().get_peer(
             peer.id(), peer.fingerprint()
@@ -1113,6 +1789,7 @@ impl DHT {
             Ok(v) => v,
             Err(e) => {
                 warn!("Retrieve existing peer {} error: {}", peer.id(), e);
+                self.events.record(NodeEventKind::StorageError { op: "get_peer" });
                 return;
             }
         };
@@ -1120,14 +1797,14 @@ impl DHT {
         if let Some(existing) = local_peers {
             if peer.sequence_number() < existing.sequence_number() {
                 warn!("Rejecting peer {}: sequence number {} is less than existing {}", peer.id(), peer.sequence_number(), existing.sequence_number());
-                self.send_err(Method::AnnouncePeer, 300,
+                self.send_err(req, 300,
                     "Sequence number is less than existing value");
                 return;
             }
 
             if body.expected_seq() >= 0 && existing.sequence_number() > body.expected_seq() {
                 warn!("Rejecting peer {}: existing sequence number {} is greater than expected {}", peer.id(), existing.sequence_number(), body.expected_seq());
-                self.send_err(Method::AnnouncePeer, 300,
+                self.send_err(req, 300,
                     "Existing sequence number is greater than expected");
                 return;
             }
@@ -1140,16 +1817,33 @@ impl DHT {
             }
         }
 
-        let _ = self.storage.lock().unwrap().put_peer(peer.clone(), false);
+        // Only the origin of a peer announces it from where it's reachable,
+        // nodes announcing it on behalf of the origin don't tell.
+        let origin = peer.nodeid().is_none_or(|id| id == req.nodeid());
+        let observed = origin.then(|| {
+            endpoint::observed_endpoint(peer.endpoint(), remote_addr.ip())
+        }).flatten();
+        if let Some(observed) = observed.as_ref() {
+            debug!("Peer {} announced with unroutable endpoint {}, observed at {}", peer.id(), peer.endpoint(), observed);
+        }
+
+        let stored = peer.clone().with_observed(observed.clone());
+        match self.storage.lock().unwrap().put_peer(stored.clone(), false) {
+            Ok(_) => self.storage_events.emit(StorageEvent::peer_announced(&stored, *req.remote_id(), false)),
+            Err(e) => {
+                warn!("Store peer {} error: {}", peer.id(), e);
+                self.events.record(NodeEventKind::StorageError { op: "put_peer" });
+            }
+        }
 
         let rsp = {
-            let mut msg = msg::announce_peer_response(req.txid());
+            let mut msg = msg::announce_peer_response(req.txid(), observed);
             msg.set_remote(*req.remote_id(), *req.remote_addr());
             msg.set_nodeid(*self.id());
             msg
         };
 
-        self.send_msg(rsp);
+        self.send_rsp(req, rsp);
     }
 
     pub(crate) async fn bootstrap(
@@ -1183,6 +1877,8 @@ impl DHT {
         let unordered = FuturesUnordered::new();
 
         let network = self.network();
+        let sibling = self.sibling_network();
+        let (want4, want6) = self.wants();
 
         for item in nodes {
             if item.id() == self.id() {
@@ -1190,13 +1886,14 @@ impl DHT {
             }
             let msg = msg::find_node_request(
                 Id::random(),
-                network.is_ipv4(),
-                network.is_ipv6(),
+                want4,
+                want6,
                 Some(true)
             );
 
             let mut call = RpcCall::new(item, msg);
             let (promise, future) = Promise::<Vec<NodeInfo>>::pair();
+            let siblings = self.siblings.clone();
 
             let listener = CallListener::new(move |_call, _, cur| {
                 if cur.is_final() {
@@ -1216,6 +1913,9 @@ impl DHT {
                             return;
                         };
                         nodes = body.nodes(network).map(|v| v.to_vec());
+                        if let (Some(siblings), Some(others)) = (siblings.as_ref(), body.nodes(sibling)) {
+                            siblings.hand_over(sibling, others);
+                        }
                     }
 
                     promise.complete(Ok(
@@ -1234,14 +1934,18 @@ impl DHT {
     }
 
     async fn do_bootstrap(dht: Rc<RefCell<DHT>>, nodes: Vec<NodeInfo>) {
-        if crate::elapsed_ms!(dht.borrow().last_bootstrap) <
-                Self::BOOTSTRAP_MIN_INTERVAL as u128 {
+        let elapsed = {
+            let dht = dht.borrow();
+            dht.clock.elapsed_ms(dht.last_bootstrap)
+        };
+        if elapsed < Self::BOOTSTRAP_MIN_INTERVAL as u128 {
             return;
         }
 
         let rt = dht.borrow().rt();
         if nodes.is_empty() && rt.borrow().is_empty() {
             warn!("no bootstrap nodes provided and routing table is empty.");
+            dht.borrow().events.record(NodeEventKind::BootstrapFailed { nodes: 0 });
             return;
         }
 
@@ -1254,10 +1958,18 @@ impl DHT {
         }
 
         debug!("DHT/{}:{} bootstrapping ...", network, self_id);
+        let total = nodes.len();
+        dht.borrow().events.record(NodeEventKind::BootstrapStarted { nodes: total });
+
         let mut unordered = dht.borrow_mut().find_closest_nodes(nodes);
         let mut nodes = Vec::new();
+        let mut responded = 0;
         while let Some(result) = unordered.next().await {
+            // timed out calls complete with an empty list as well
             if let Ok(item) = result {
+                if !item.is_empty() {
+                    responded += 1;
+                }
                 nodes.extend(item);
             }
         }
@@ -1297,7 +2009,14 @@ impl DHT {
 
         let mut borrowd_dht = dht.borrow_mut();
         borrowd_dht.bootstrapping.store(false, Ordering::Relaxed);
-        borrowd_dht.last_bootstrap = SystemTime::now();
+        borrowd_dht.last_bootstrap = borrowd_dht.clock.now();
+        borrowd_dht.publish_to_sibling();
+
+        let entries = borrowd_dht.rt().borrow().number_of_entries();
+        match responded == 0 && entries == 0 {
+            true  => borrowd_dht.events.record(NodeEventKind::BootstrapFailed { nodes: total }),
+            false => borrowd_dht.events.record(NodeEventKind::BootstrapCompleted { responded, entries }),
+        }
 
         info!("DHT {}:{} bootstrapping finished", network, self_id);
     }
@@ -1327,6 +2046,7 @@ impl DHT {
     pub(crate) fn find_node(&self,
         target: Id,
         option: LookupOption,
+        progress: Option<ProgressSender>,
         promise: Promise<Option<NodeInfo>>
     ) {
         let node: Option<NodeInfo> = self.rt().borrow().bucket_entry(&target).map(|v| v.into());
@@ -1346,48 +2066,161 @@ impl DHT {
         ));
         task.with_name(format!("Lookup node: {target}"));
         task.with_want_target(true);
+        self.with_session(task.as_mut());
+        self.with_progress(task.as_mut(), progress);
+        let rt = self.rt();
         task.with_listener(
             TaskListener::default().ended_fn(
                 move |t: &dyn Task| {
                     let task = t.as_any()
                         .downcast_ref::<NodeLookupTask>().unwrap();
-                    promise.complete(Ok(task.result()));
+                    task.finish_progress();
+                    // Nodes lists carry no version, the routing table may know it.
+                    let result = task.result().map(|mut ni| {
+                        if ni.version() == 0 {
+                            if let Some(entry) = rt.borrow().bucket_entry(ni.id()) {
+                                ni.set_version(entry.version());
+                                ni.set_capabilities(entry.capabilities());
+                            }
+                        }
+                        ni
+                    });
+                    promise.complete(Ok(result));
             })
         );
         self.task_man.add(task);
     }
 
+    // Sends the lookup of the application from a session id in privacy mode.
+    fn with_session(&self, task: &mut dyn Task) {
+        if let Some(sessions) = self.sessions.as_ref() {
+            task.with_session(sessions.borrow_mut().next(Instant::now()));
+        }
+    }
+
+    // Reports the progress of the lookup of the application, if asked to.
+    fn with_progress(&self, task: &mut impl LookupTask, progress: Option<ProgressSender>) {
+        if let Some(sender) = progress {
+            task.data_mut().with_progress(ProgressReporter::new(self.network, sender));
+        }
+    }
+
+    // The routing table entry of the target if it is good enough to skip
+    // a lookup under the given option.
+    pub(crate) fn cached_node(&self, target: &Id, option: LookupOption) -> Option<NodeInfo> {
+        let entry = self.rt().borrow().bucket_entry(target)?;
+        match option {
+            LookupOption::Local | LookupOption::Conservative => Some(entry.into()),
+            LookupOption::Optimistic | LookupOption::Arbitrary => entry.is_fresh().then(|| entry.into()),
+        }
+    }
+
+    // Pings a node, resolves to whether it answered with the expected id.
+    // A node with another id can not decrypt the ping, so a stale target
+    // only shows as a timeout, which is kept short.
+    pub(crate) fn ping(&self, target: NodeInfo, promise: Promise<bool>) {
+        let mut call = RpcCall::new(target, msg::ping_request());
+        call.set_timeout(Self::PING_TIMEOUT);
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let alive = match cur {
+                CallState::Responded => !call.nodeid_mismatched(),
+                CallState::Err | CallState::Timeout => false,
+                _ => return,
+            };
+            promise.complete(Ok(alive));
+        }));
+        self.send_call(call);
+    }
+
+    // Joins the lookup in flight for the same value if there is one, those
+    // with a deadline only join the ones ending at the same time. Returns
+    // None if the promise was completed from a recent result.
     pub(crate) fn find_value(
         &self,
         value_id: Id,
         expected_seq: i32,
         option: LookupOption,
-        promise: Promise<Option<Value>>
-    ) {
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>,
+        promise: Promise<LookupOutcome<Option<ValueResult>>>
+    ) -> Option<ValueLookupWaiter> {
+        let done_on_eligible = option != LookupOption::Conservative;
+        let deadline = timeout.map(|v| self.clock.now_ms() + v.as_millis() as u64);
+        let key = (value_id, expected_seq, done_on_eligible, deadline);
+
+        let joined = self.value_lookups.borrow_mut().join(&key, promise);
+        let waiter = match joined {
+            Joined::Cached => return None,
+            Joined::Attached(waiter) => return Some(waiter),
+            Joined::New(waiter) => waiter,
+        };
+
         let mut task = Box::new(ValueLookupTask::new(
             self.dht(),
             value_id,
             expected_seq,
-            option != LookupOption::Conservative
+            done_on_eligible
         ));
         task.with_name(format!("Lookup value: {value_id}"));
+        self.with_session(task.as_mut());
+        // Only the caller starting the lookup sees its progress.
+        self.with_progress(task.as_mut(), progress);
         task.with_listener(
-            TaskListener::default().ended_fn(
+            TaskListener::default().ended_fn({
+                let lookups = self.value_lookups.clone();
                 move |t: &dyn Task| {
                     let task = t.as_any()
                         .downcast_ref::<ValueLookupTask>().unwrap();
-                    promise.complete(Ok(task.result()));
-            })
+                    task.finish_progress();
+                    let completed = task.task_state() == State::Completed;
+                    let outcome = LookupOutcome::new(task.result(), completed);
+                    lookups.borrow_mut().complete(&key, outcome, completed);
+            }})
         );
 
+        let taskid = task.task_id();
+        self.value_lookups.borrow_mut().started(&key, taskid);
         self.task_man.add(task);
+        self.set_deadline(taskid, timeout);
+        Some(waiter)
+    }
+
+    // Cancels the lookup task once the timeout passes, its ended listener
+    // hands out the best result found so far. Nothing happens if the task
+    // has ended by then.
+    fn set_deadline(&self, taskid: TaskId, timeout: Option<Duration>) {
+        let Some(timeout) = timeout else {
+            return;
+        };
+        let task_man = self.task_man.clone();
+        let result = self.timer_client.add_timer((timeout.as_millis() as u64).max(1), None,
+            AsyncHandler::new(move |_| {
+                if task_man.cancel(taskid) {
+                    debug!("Lookup task #{taskid} ended on its deadline");
+                }
+                Box::pin(async {})
+            })
+        );
+        if let Err(e) = result {
+            error!("Failed to set lookup deadline timer: {e}");
+        }
+    }
+
+    // The caller of a value lookup is gone, the lookup is canceled if
+    // nobody else waits for it.
+    pub(crate) fn leave_value_lookup(&self, waiter: &ValueLookupWaiter) {
+        let taskid = self.value_lookups.borrow_mut().leave(waiter);
+        if let Some(taskid) = taskid {
+            debug!("Value lookup task #{taskid} canceled without waiters");
+            self.task_man.cancel(taskid);
+        }
     }
 
     pub(crate) fn store_value(
         &self,
         value: Value,
         expected_seq: i32,
-        promise: Promise::<()>
+        promise: Promise::<usize>
     ) {
         let valueid = value.id();
         let mut nested = Box::new(ValueAnnounceTask::new(
@@ -1395,11 +2228,21 @@ impl DHT {
         ));
         nested.with_name(format!("Store value:{valueid}"));
         nested.with_listener(
-            TaskListener::default().ended_fn(
-                move |_| promise.complete(Ok(()))
-            )
+            TaskListener::default().ended_fn(move |t: &dyn Task| {
+                let acks = t.as_any()
+                    .downcast_ref::<ValueAnnounceTask>()
+                    .map_or(0, |task| task.acks());
+                promise.complete(Ok(acks))
+            })
         );
 
+        // Stored again while the tokens of the closest nodes are valid.
+        if let Some(closest) = self.cached_closest(&valueid) {
+            nested.with_cached(closest);
+            self.task_man.add(nested);
+            return;
+        }
+
         let task_man = self.task_man.clone();
         // Lookup task to find the closest nodes to the valueid, and
         // then nested announce task to announce the value to those nodes.
@@ -1442,39 +2285,65 @@ impl DHT {
         task_man.add(task);
     }
 
+    // The nodes with a token cached for the target, none unless one of the
+    // tokens is fresh.
+    fn cached_closest(&self, target: &Id) -> Option<ClosestSet> {
+        let nodes = self.token_cache.borrow_mut().lookup(target)?;
+        let mut closest = ClosestSet::new(*target, KBucket::MAX_ENTRIES);
+        for (ni, token) in nodes {
+            let mut cn: CandidateNode = ni.into();
+            cn.set_token(token);
+            closest.add(Rc::new(RefCell::new(cn)));
+        }
+        Some(closest)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn find_peer(
         &self,
         peerid: Id,
         expected_seq: i32,
         expected_count: usize,
+        tags: Vec<String>,
+        network: Option<Network>,
         option: LookupOption,
-        promise: Promise::<Vec<PeerInfo>>
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>,
+        promise: Promise::<LookupOutcome<Vec<PeerResult>>>
     ) {
         let mut task = Box::new(PeerLookupTask::new(
             self.dht(),
             peerid,
             expected_seq,
             expected_count,
+            tags,
+            network,
             option != LookupOption::Conservative
         ));
         task.with_name(format!("Lookup peer: {}", peerid));
+        self.with_session(task.as_mut());
+        self.with_progress(task.as_mut(), progress);
         task.with_listener({
             TaskListener::default().ended_fn(
                 move |t: &dyn Task| {
                     let task = t.as_any()
                         .downcast_ref::<PeerLookupTask>().unwrap();
-                    promise.complete(Ok(task.result()));
+                    task.finish_progress();
+                    let completed = task.task_state() == State::Completed;
+                    promise.complete(Ok(LookupOutcome::new(task.result(), completed)));
             })
         });
 
+        let taskid = task.task_id();
         self.task_man.add(task);
+        self.set_deadline(taskid, timeout);
     }
 
     pub(crate) fn announce_peer(
         &self,
         peer: PeerInfo,
         expected_seq: i32,
-        promise: Promise::<()>
+        promise: Promise::<Option<String>>
     ) {
         // Announce task to announce the peer to the closest nodes found
         // by the lookup task.
@@ -1482,11 +2351,32 @@ impl DHT {
             self.dht(), peer.clone(), expected_seq,
         ));
         nested.with_name(format!("Announce peer: {}", peer.id()));
-        nested.with_listener(
-            TaskListener::default().ended_fn(
-                move |_| promise.complete(Ok(()))
-            )
-        );
+        nested.with_listener({
+            let events = self.events.clone();
+            TaskListener::default().ended_fn(move |t: &dyn Task| {
+                let task = t.as_any()
+                    .downcast_ref::<PeerAnnounceTask>().unwrap();
+                // Consumers get the observed endpoint from the nodes, still
+                // the peer is better announced with a routable one.
+                let observed = task.observed_consensus();
+                if let Some(observed) = observed.as_ref() {
+                    warn!("Peer {} announced with unroutable endpoint {}, observed at {}",
+                        task.peer().id(), task.peer().endpoint(), observed);
+                    events.record(NodeEventKind::EndpointUnroutable {
+                        peer: *task.peer().id(),
+                        observed: observed.clone()
+                    });
+                }
+                promise.complete(Ok(observed))
+            })
+        });
+
+        // Announced again while the tokens of the closest nodes are valid.
+        if let Some(closest) = self.cached_closest(peer.id()) {
+            nested.with_cached(closest);
+            self.task_man.add(nested);
+            return;
+        }
 
         let task_man = self.task_man.clone();
         // Lookup task to find the closest nodes to the targetid.
@@ -1529,4 +2419,153 @@ impl DHT {
 
         task_man.add(task);
     }
+
+    pub(crate) fn send_extension(
+        &self,
+        target: NodeInfo,
+        data: Vec<u8>,
+        promise: Promise<Vec<u8>>
+    ) {
+        let mut call = RpcCall::new(target, msg::extension_request(data));
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let rsp = call.rsp();
+            let result: Result<Vec<u8>> = match cur {
+                CallState::Responded => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::ExtensionResponse(body)) => Ok(body.data().to_vec()),
+                    _ => Err(ProtocolError::new("Invalid extension response")),
+                },
+                CallState::Err => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::Error(err)) => Err(ProtocolError::new(format!(
+                        "Extension request failed with error {}: {}", err.code(), err.description()
+                    ))),
+                    _ => Err(NetworkError::new("Extension request failed")),
+                },
+                CallState::Timeout => Err(NetworkError::new("Extension request timed out")),
+                _ => return,
+            };
+            promise.complete(result);
+        }));
+        self.send_call(call);
+    }
+
+    // Asks the target alone for the nodes it knows closest to the lookup id,
+    // nothing is stored or announced.
+    pub(crate) fn query_node(
+        &self,
+        target: NodeInfo,
+        lookup_id: Id,
+        promise: Promise<NodeQuery>
+    ) {
+        let want4 = self.network == Network::IPv4;
+        let req = msg::find_node_request(lookup_id, want4, !want4, None);
+        let network = self.network;
+        let mut call = RpcCall::new(target.clone(), req);
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let rsp = call.rsp();
+            let result: Result<NodeQuery> = match cur {
+                CallState::Responded if call.nodeid_mismatched() => Err(ProtocolError::new(
+                    format!("Node {} answered with another id", target)
+                )),
+                CallState::Responded => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::FindNodeResponse(body)) => {
+                        let mut node = target.clone();
+                        node.set_version(rsp.as_ref().unwrap().ver());
+                        if let Some(caps) = rsp.as_ref().unwrap().capabilities() {
+                            node.set_capabilities(caps);
+                        }
+                        let rtt = Duration::from_millis(call.rtt().unwrap_or(0));
+                        let nodes = body.nodes(network).map(|v| v.to_vec()).unwrap_or_default();
+                        Ok(NodeQuery::new(node, rtt, nodes))
+                    },
+                    _ => Err(ProtocolError::new("Invalid find node response")),
+                },
+                CallState::Err => Err(NetworkError::new("Find node request failed")),
+                CallState::Timeout => Err(NetworkError::new("Find node request timed out")),
+                _ => return,
+            };
+            promise.complete(result);
+        }));
+        self.send_call(call);
+    }
+
+    // Asks the target for the token to store a value with the given id.
+    pub(crate) fn request_token(
+        &self,
+        target: NodeInfo,
+        value_id: Id,
+        promise: Promise<i32>
+    ) {
+        let want4 = self.network == Network::IPv4;
+        let req = msg::find_node_request(value_id, want4, !want4, Some(true));
+        let mut call = RpcCall::new(target, req);
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let rsp = call.rsp();
+            let result: Result<i32> = match cur {
+                CallState::Responded => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::FindNodeResponse(body)) if body.token() != 0 => Ok(body.token()),
+                    _ => Err(ProtocolError::new("No token in the find node response")),
+                },
+                CallState::Err => Err(NetworkError::new("Token request failed")),
+                CallState::Timeout => Err(NetworkError::new("Token request timed out")),
+                _ => return,
+            };
+            promise.complete(result);
+        }));
+        self.send_call(call);
+    }
+
+    // Stores the value on the target alone, with a token it handed out.
+    pub(crate) fn send_store_value(
+        &self,
+        target: NodeInfo,
+        value: Value,
+        token: i32,
+        promise: Promise<()>
+    ) {
+        let mut call = RpcCall::new(target, msg::store_value_request(value, token, -1));
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let rsp = call.rsp();
+            let result: Result<()> = match cur {
+                CallState::Responded => Ok(()),
+                CallState::Err => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::Error(err)) => Err(ProtocolError::new(format!(
+                        "Store value refused with error {}: {}", err.code(), err.description()
+                    ))),
+                    _ => Err(NetworkError::new("Store value request failed")),
+                },
+                CallState::Timeout => Err(NetworkError::new("Store value request timed out")),
+                _ => return,
+            };
+            promise.complete(result);
+        }));
+        self.send_call(call);
+    }
+
+    pub(crate) fn send_rendezvous(
+        &self,
+        target: NodeInfo,
+        rendezvous: Rendezvous,
+        promise: Promise<Rendezvous>
+    ) {
+        let mut call = RpcCall::new(target, msg::rendezvous_request(rendezvous));
+        call.set_listener(CallListener::new(move |call, _, cur| {
+            let rsp = call.rsp();
+            let result: Result<Rendezvous> = match cur {
+                CallState::Responded => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::RendezvousResponse(body)) => Ok(body.clone()),
+                    _ => Err(ProtocolError::new("Invalid rendezvous response")),
+                },
+                CallState::Err => match rsp.as_ref().and_then(|m| m.body()) {
+                    Some(Body::Error(err)) => Err(ProtocolError::new(format!(
+                        "Rendezvous refused with error {}: {}", err.code(), err.description()
+                    ))),
+                    _ => Err(NetworkError::new("Rendezvous request failed")),
+                },
+                CallState::Timeout => Err(NetworkError::new("Rendezvous request timed out")),
+                _ => return,
+            };
+            promise.complete(result);
+        }));
+        self.send_call(call);
+    }
 }
@@ -2,11 +2,11 @@ use std::{
     rc::Rc,
     pin::Pin,
     cell::RefCell,
-    path::PathBuf,
     result::Result as StdResult,
-    sync::{mpsc as std_mpsc, Arc, Mutex},
+    sync::{Arc, Mutex},
     thread::JoinHandle,
     future::Future,
+    time::Duration,
 };
 use futures::{
     stream::{FuturesUnordered, StreamExt },
@@ -14,14 +14,17 @@ use futures::{
 };
 use log::{error, info};
 use tokio::{
-    runtime,
+    runtime::Handle,
     sync::{mpsc,oneshot},
 };
 
 use crate::{
+    Clock,
     CryptoIdentity,
+    EndpointPolicy,
     Id, Network, NodeInfo,
-    PeerInfo, Value,
+    Capabilities,
+    PeerInfo, Value, ResultSource,
     Result,
     errors::StateError
 };
@@ -29,16 +32,46 @@ use crate::dht::{
     ConnectionStatusListener,
     dht::DHT,
     lookup_option::LookupOption,
+    lookup_result::{ValueResult, PeerResult, LookupOutcome},
+    lookup_progress::ProgressSender,
+    node_query::NodeQuery,
+    node::ExtensionHandler,
+    hole_punch::DirectConnections,
+    announcement::AnnouncementPolicy,
+    clock_skew::ClockSkew,
+    blocklist::Blocklist,
+    socket_event::SocketErrors,
+    siblings::Siblings,
+    storage_event::StorageEvents,
+    msg::Rendezvous,
+    node_event::EventLog,
+    node_config::DEFAULT_COMMAND_QUEUE_SIZE,
     promise::Promise,
+    stats::{DhtStats, CommandQueue},
+    routing::kbucket::BucketInfo,
     storage::data_storage::DataStorage,
     timer_client::{LocalTimerClient as TimerClient, LocalTimerCmd as TimerCmd},
     timer_manager::LocalTimerManager as TimerManager,
+    timer_verticle,
     token_manager::TokenManager,
-    rpc::rpc_server::RpcServer,
+    data_layout::DataLayout,
+    task::task_manager::ConcurrencyLimits,
+    routing::{Prefix, routing_table::RoutingStrategy},
+    rpc::{
+        rpc_server::RpcServer,
+        socket_health::SocketHealthOptions,
+        send_shaper::SendShaperOptions,
+    },
 };
 
-const CHANNEL_REQ_CLOSED: &str = "verticle request channel closed";
-const CHANNEL_RSP_CLOSED: &str = "verticle response channel closed";
+#[cfg(feature = "testing")]
+use crate::testing::{LossyTransport, SimTransport};
+#[cfg(feature = "wsgateway")]
+use url::Url;
+
+// Queued commands and those still in progress once the verticle stops
+// complete with this error.
+const NODE_STOPPED: &str = "node stopped";
 
 enum Cmd {
     Bootstrap {
@@ -48,31 +81,84 @@ enum Cmd {
     FindNode {
         target: Id,
         option: LookupOption,
+        progress: Option<ProgressSender>,
         complete: oneshot::Sender<CmdResult<Option<NodeInfo>>>,
     },
+    FindNodeWithHint {
+        target: Id,
+        hint: Option<NodeInfo>,
+        option: LookupOption,
+        complete: oneshot::Sender<CmdResult<Option<(NodeInfo, ResultSource)>>>,
+    },
     FindValue {
         target: Id,
         expected_seq: i32,
         option: LookupOption,
-        complete: oneshot::Sender<CmdResult<Option<Value>>>,
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>,
+        complete: oneshot::Sender<CmdResult<LookupOutcome<Option<ValueResult>>>>,
     },
     StoreValue {
         value: Value,
         expected_seq: i32,
-        complete: oneshot::Sender<CmdResult<()>>,
+        complete: oneshot::Sender<CmdResult<usize>>,
     },
     FindPeer {
         target: Id,
         expected_seq: i32,
         expected_count: usize,
+        tags: Vec<String>,
+        network: Option<Network>,
         option: LookupOption,
-        complete: oneshot::Sender<CmdResult<Vec<PeerInfo>>>,
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>,
+        complete: oneshot::Sender<CmdResult<LookupOutcome<Vec<PeerResult>>>>,
     },
     AnnouncePeer {
         peer: PeerInfo,
         expected_seq: i32,
+        complete: oneshot::Sender<CmdResult<Option<String>>>,
+    },
+    SendExtension {
+        target: NodeInfo,
+        data: Vec<u8>,
+        complete: oneshot::Sender<CmdResult<Vec<u8>>>,
+    },
+    PinValue {
+        target: NodeInfo,
+        value: Value,
         complete: oneshot::Sender<CmdResult<()>>,
     },
+    Rendezvous {
+        target: NodeInfo,
+        rendezvous: Rendezvous,
+        complete: oneshot::Sender<CmdResult<Rendezvous>>,
+    },
+    PingNode {
+        target: Id,
+        complete: oneshot::Sender<CmdResult<bool>>,
+    },
+    QueryNode {
+        target: NodeInfo,
+        lookup_id: Id,
+        complete: oneshot::Sender<CmdResult<NodeQuery>>,
+    },
+    ClosestNodes {
+        target: Id,
+        count: usize,
+        include_self: bool,
+        complete: oneshot::Sender<CmdResult<Vec<NodeInfo>>>,
+    },
+    Stats {
+        complete: oneshot::Sender<CmdResult<DhtStats>>,
+    },
+    RoutingTable {
+        complete: oneshot::Sender<CmdResult<Vec<BucketInfo>>>,
+    },
+    RoutingEntries {
+        prefix: Prefix,
+        complete: oneshot::Sender<CmdResult<usize>>,
+    },
     Start {
         complete: oneshot::Sender<CmdResult<()>>,
     },
@@ -81,139 +167,273 @@ enum Cmd {
     },
 }
 
+impl Cmd {
+    fn kind(&self) -> &'static str {
+        match self {
+            Cmd::Bootstrap { .. }         => "bootstrap",
+            Cmd::FindNode { .. }          => "findNode",
+            Cmd::FindNodeWithHint { .. }  => "findNodeWithHint",
+            Cmd::FindValue { .. }         => "findValue",
+            Cmd::StoreValue { .. }        => "storeValue",
+            Cmd::FindPeer { .. }          => "findPeer",
+            Cmd::AnnouncePeer { .. }      => "announcePeer",
+            Cmd::SendExtension { .. }     => "sendExtension",
+            Cmd::PinValue { .. }          => "pinValue",
+            Cmd::Rendezvous { .. }        => "rendezvous",
+            Cmd::PingNode { .. }          => "pingNode",
+            Cmd::QueryNode { .. }         => "queryNode",
+            Cmd::ClosestNodes { .. }      => "closestNodes",
+            Cmd::Stats { .. }             => "stats",
+            Cmd::RoutingTable { .. }      => "routingTable",
+            Cmd::RoutingEntries { .. }    => "routingEntries",
+            Cmd::Start { .. }             => "start",
+            Cmd::StopAll { .. }           => "stopAll",
+        }
+    }
+
+    fn reject(self, msg: &str) {
+        let msg = msg.to_string();
+        match self {
+            Cmd::Bootstrap { complete, .. }         => _ = complete.send(Err(msg)),
+            Cmd::FindNode { complete, .. }          => _ = complete.send(Err(msg)),
+            Cmd::FindNodeWithHint { complete, .. }  => _ = complete.send(Err(msg)),
+            Cmd::FindValue { complete, .. }         => _ = complete.send(Err(msg)),
+            Cmd::StoreValue { complete, .. }        => _ = complete.send(Err(msg)),
+            Cmd::FindPeer { complete, .. }          => _ = complete.send(Err(msg)),
+            Cmd::AnnouncePeer { complete, .. }      => _ = complete.send(Err(msg)),
+            Cmd::SendExtension { complete, .. }     => _ = complete.send(Err(msg)),
+            Cmd::PinValue { complete, .. }          => _ = complete.send(Err(msg)),
+            Cmd::Rendezvous { complete, .. }        => _ = complete.send(Err(msg)),
+            Cmd::PingNode { complete, .. }          => _ = complete.send(Err(msg)),
+            Cmd::QueryNode { complete, .. }         => _ = complete.send(Err(msg)),
+            Cmd::ClosestNodes { complete, .. }      => _ = complete.send(Err(msg)),
+            Cmd::Stats { complete }                 => _ = complete.send(Err(msg)),
+            Cmd::RoutingTable { complete }          => _ = complete.send(Err(msg)),
+            Cmd::RoutingEntries { complete, .. }    => _ = complete.send(Err(msg)),
+            Cmd::Start { complete }                 => _ = complete.send(Err(msg)),
+            Cmd::StopAll { complete }               => _ = complete.send(Err(msg)),
+        }
+    }
+}
+
 pub(crate) struct VerticleClient {
     ni          : NodeInfo,
-    command_tx  : mpsc::UnboundedSender<Cmd>,
-    handle      : Option<JoinHandle<()>>,
+    command_tx  : mpsc::Sender<Cmd>,
+    handle      : Mutex<Option<JoinHandle<()>>>,
 }
 type CmdResult<T> = StdResult<T, String>;
 
+// Waits for room in the command queue, then for the command to complete.
+async fn call<T>(
+    command_tx: &mpsc::Sender<Cmd>,
+    cmd: impl FnOnce(oneshot::Sender<CmdResult<T>>) -> Cmd
+) -> Result<T> {
+    let (tx, rx) = oneshot::channel();
+    if command_tx.send(cmd(tx)).await.is_err() {
+        return Err(StateError::new(NODE_STOPPED));
+    }
+    match rx.await {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(msg)) => Err(StateError::new(msg)),
+        Err(_) => Err(StateError::new(NODE_STOPPED)),
+    }
+}
+
 impl VerticleClient {
     pub(crate) fn ni(&self) -> NodeInfo {
         self.ni.clone()
     }
 
-    async fn rx_result<T>(
-        &self,
-        rx: oneshot::Receiver<CmdResult<T>>,
-    ) -> Result<T> {
-        match rx.await {
-            Ok(Ok(v)) => Ok(v),
-            Ok(Err(msg)) => Err(StateError::new(msg)),
-            Err(_) => Err(StateError::new(CHANNEL_RSP_CLOSED)),
-        }
-    }
-
     pub(crate) async fn bootstrap(
         &self,
         nodes: Vec<NodeInfo>
     ) -> Result<()> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(
-            Cmd::Bootstrap { nodes, complete: tx }
-        ).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+        call(&self.command_tx, |complete|
+            Cmd::Bootstrap { nodes, complete }
+        ).await
     }
 
     pub(crate) async fn find_node(
         &self,
         target: Id,
-        option: LookupOption
+        option: LookupOption,
+        progress: Option<ProgressSender>
     ) -> Result<Option<NodeInfo>> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(
-            Cmd::FindNode { target, option, complete: tx }
-        ).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+        call(&self.command_tx, |complete|
+            Cmd::FindNode { target, option, progress, complete }
+        ).await
+    }
+
+    pub(crate) async fn find_node_with_hint(
+        &self,
+        target: Id,
+        hint: Option<NodeInfo>,
+        option: LookupOption
+    ) -> Result<Option<(NodeInfo, ResultSource)>> {
+        call(&self.command_tx, |complete|
+            Cmd::FindNodeWithHint { target, hint, option, complete }
+        ).await
     }
 
     pub(crate) async fn find_value(
         &self,
         target: Id,
         expected_seq: i32,
-        option: LookupOption
-    ) -> Result<Option<Value>> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(Cmd::FindValue {
+        option: LookupOption,
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>
+    ) -> Result<LookupOutcome<Option<ValueResult>>> {
+        call(&self.command_tx, |complete| Cmd::FindValue {
             target,
             expected_seq,
             option,
-            complete: tx,
-        }).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+            timeout,
+            progress,
+            complete,
+        }).await
     }
 
+    // Returns the number of nodes that stored the value.
     pub(crate) async fn store_value(
         &self,
         value: Value,
         expected_seq: i32
-    ) -> Result<()> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(
-            Cmd::StoreValue { value, expected_seq, complete: tx }
-        ).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+    ) -> Result<usize> {
+        call(&self.command_tx, |complete|
+            Cmd::StoreValue { value, expected_seq, complete }
+        ).await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn find_peer(
         &self,
         target: Id,
         expected_seq: i32,
         expected_count: usize,
-        option: LookupOption
-    ) -> Result<Vec<PeerInfo>> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(Cmd::FindPeer {
+        tags: Vec<String>,
+        network: Option<Network>,
+        option: LookupOption,
+        timeout: Option<Duration>,
+        progress: Option<ProgressSender>
+    ) -> Result<LookupOutcome<Vec<PeerResult>>> {
+        call(&self.command_tx, |complete| Cmd::FindPeer {
             target,
             expected_seq,
             expected_count,
+            tags,
+            network,
             option,
-            complete: tx,
-        }).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+            timeout,
+            progress,
+            complete,
+        }).await
     }
 
+    // Resolves to the endpoint the nodes agree to have observed the peer
+    // at, when its own is unroutable.
     pub(crate) async fn announce_peer(
         &self,
         peer: PeerInfo,
         expected_seq: i32,
+    ) -> Result<Option<String>> {
+        call(&self.command_tx, |complete|
+            Cmd::AnnouncePeer { peer, expected_seq, complete }
+        ).await
+    }
+
+    pub(crate) async fn send_extension(
+        &self,
+        target: NodeInfo,
+        data: Vec<u8>
+    ) -> Result<Vec<u8>> {
+        call(&self.command_tx, |complete|
+            Cmd::SendExtension { target, data, complete }
+        ).await
+    }
+
+    pub(crate) async fn pin_value(
+        &self,
+        target: NodeInfo,
+        value: Value
     ) -> Result<()> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(
-            Cmd::AnnouncePeer { peer, expected_seq, complete: tx }
-        ).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
-        }
-        self.rx_result(rx).await
+        call(&self.command_tx, |complete|
+            Cmd::PinValue { target, value, complete }
+        ).await
+    }
+
+    pub(crate) async fn rendezvous(
+        &self,
+        target: NodeInfo,
+        rendezvous: Rendezvous
+    ) -> Result<Rendezvous> {
+        call(&self.command_tx, |complete|
+            Cmd::Rendezvous { target, rendezvous, complete }
+        ).await
+    }
+
+    // Whether the node answers a ping, at its address in the routing table
+    // or found by a lookup.
+    pub(crate) async fn ping_node(&self, target: Id) -> Result<bool> {
+        call(&self.command_tx, |complete|
+            Cmd::PingNode { target, complete }
+        ).await
+    }
+
+    pub(crate) async fn query_node(
+        &self,
+        target: NodeInfo,
+        lookup_id: Id
+    ) -> Result<NodeQuery> {
+        call(&self.command_tx, |complete|
+            Cmd::QueryNode { target, lookup_id, complete }
+        ).await
+    }
+
+    pub(crate) async fn closest_nodes(
+        &self,
+        target: Id,
+        count: usize,
+        include_self: bool
+    ) -> Result<Vec<NodeInfo>> {
+        call(&self.command_tx, |complete| Cmd::ClosestNodes {
+            target,
+            count,
+            include_self,
+            complete,
+        }).await
+    }
+
+    pub(crate) async fn routing_table(&self) -> Result<Vec<BucketInfo>> {
+        call(&self.command_tx, |complete| Cmd::RoutingTable { complete }).await
+    }
+
+    pub(crate) async fn routing_entries(&self, prefix: Prefix) -> Result<usize> {
+        call(&self.command_tx, |complete| Cmd::RoutingEntries { prefix, complete }).await
     }
 
-    async fn start(&mut self) -> Result<()> {
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(Cmd::Start { complete: tx }).is_err() {
-            return Err(StateError::new(CHANNEL_REQ_CLOSED));
+    // The returned future does not borrow the client, so a sampling in
+    // flight never keeps the node from stopping.
+    pub(crate) fn stats(&self) -> impl Future<Output = Result<DhtStats>> + 'static {
+        let command_tx = self.command_tx.clone();
+        async move {
+            call(&command_tx, |complete| Cmd::Stats { complete }).await
         }
-        self.rx_result(rx).await
     }
 
-    pub(crate) async fn stop(&mut self) {
+    async fn start(&self) -> Result<()> {
+        call(&self.command_tx, |complete| Cmd::Start { complete }).await
+    }
+
+    // Commands still queued or in progress complete with NODE_STOPPED, as do
+    // the ones issued afterwards.
+    pub(crate) async fn stop(&self) {
         info!("Stopping DHT verticle");
-        let (tx, rx) = oneshot::channel();
-        if self.command_tx.send(Cmd::StopAll { complete: tx }).is_ok() {
-            let _ = rx.await;
-        }
+        let _ = call(&self.command_tx, |complete| Cmd::StopAll { complete }).await;
 
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
+        // Never block the caller's runtime threads on the join.
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            let _ = tokio::task::spawn_blocking(move || handle.join()).await;
         }
         info!("DHT verticle stopped");
     }
@@ -225,8 +445,44 @@ pub(crate) struct VerticleOptions {
     pub(crate) storage      : Option<Arc<Mutex<dyn DataStorage>>>,
     pub(crate) token_man    : Option<Arc<TokenManager>>,
     pub(crate) listener     : Option<Arc<dyn ConnectionStatusListener>>,
-    pub(crate) data_dir     : Option<PathBuf>,
+    pub(crate) layout       : Option<DataLayout>,
     pub(crate) bootstrap_nodes  : Option<Vec<NodeInfo>>,
+    pub(crate) event_log    : Option<EventLog>,
+    pub(crate) socket_health: Option<SocketHealthOptions>,
+    pub(crate) send_shaper  : Option<SendShaperOptions>,
+    pub(crate) extension_handler: Option<Arc<Mutex<Option<ExtensionHandler>>>>,
+    pub(crate) direct_connections: Option<Arc<Mutex<DirectConnections>>>,
+    pub(crate) endpoint_policy: EndpointPolicy,
+    pub(crate) announcement_policy: AnnouncementPolicy,
+    pub(crate) required_countersigner: Option<Id>,
+    pub(crate) reject_far_stores: bool,
+    pub(crate) pin_allowlist: Vec<Id>,
+    pub(crate) clock_skew   : Option<Arc<ClockSkew>>,
+    pub(crate) blocklist    : Option<Arc<Blocklist>>,
+    pub(crate) socket_errors: Option<Arc<SocketErrors>>,
+    pub(crate) siblings     : Option<Arc<Siblings>>,
+    pub(crate) storage_events: Option<Arc<StorageEvents>>,
+    pub(crate) prefer_low_rtt: bool,
+    pub(crate) capabilities : Capabilities,
+    pub(crate) bucket_refresh_interval: u64,
+    pub(crate) lookup_cache_ttl: u64,
+    // The rotation of the session ids in privacy mode, none when off.
+    pub(crate) privacy      : Option<Duration>,
+    pub(crate) concurrency: ConcurrencyLimits,
+    pub(crate) routing_strategy: RoutingStrategy,
+    pub(crate) command_queue_size: Option<usize>,
+    pub(crate) receive_sockets: Option<usize>,
+    pub(crate) runtime      : Option<Handle>,
+    pub(crate) clock        : Option<Arc<dyn Clock>>,
+    #[cfg(feature = "testing")]
+    pub(crate) transport    : Option<Arc<LossyTransport>>,
+    // Runs the verticle on the caller's LocalSet, see Node::with_sim.
+    #[cfg(feature = "testing")]
+    pub(crate) sim          : Option<Arc<SimTransport>>,
+    // The WebSocket gateway the packets are tunneled through, see
+    // NodeConfig::ws_gateway.
+    #[cfg(feature = "wsgateway")]
+    pub(crate) ws_gateway   : Option<Url>,
 }
 
 impl VerticleOptions {
@@ -250,8 +506,13 @@ impl VerticleOptions {
         self
     }
 
-    pub(crate) fn with_datadir(mut self, datadir: PathBuf) -> Self {
-        self.data_dir = Some(datadir);
+    pub(crate) fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    pub(crate) fn with_layout(mut self, layout: DataLayout) -> Self {
+        self.layout = Some(layout);
         self
     }
 
@@ -259,14 +520,158 @@ impl VerticleOptions {
         self.listener = Some(listener);
         self
     }
+
+    pub(crate) fn with_event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    pub(crate) fn with_socket_health(mut self, options: SocketHealthOptions) -> Self {
+        self.socket_health = Some(options);
+        self
+    }
+
+    pub(crate) fn with_send_shaper(mut self, options: SendShaperOptions) -> Self {
+        self.send_shaper = Some(options);
+        self
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn with_transport(mut self, transport: Option<Arc<LossyTransport>>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn with_sim(mut self, sim: Option<Arc<SimTransport>>) -> Self {
+        self.sim = sim;
+        self
+    }
+
+    #[cfg(feature = "wsgateway")]
+    pub(crate) fn with_ws_gateway(mut self, url: Option<Url>) -> Self {
+        self.ws_gateway = url;
+        self
+    }
+
+    pub(crate) fn with_endpoint_policy(mut self, policy: EndpointPolicy) -> Self {
+        self.endpoint_policy = policy;
+        self
+    }
+
+    pub(crate) fn with_announcement_policy(mut self, policy: AnnouncementPolicy) -> Self {
+        self.announcement_policy = policy;
+        self
+    }
+
+    pub(crate) fn with_required_countersigner(mut self, signer: Option<Id>) -> Self {
+        self.required_countersigner = signer;
+        self
+    }
+
+    pub(crate) fn with_reject_far_stores(mut self, reject: bool) -> Self {
+        self.reject_far_stores = reject;
+        self
+    }
+
+    pub(crate) fn with_pin_allowlist(mut self, allowlist: Vec<Id>) -> Self {
+        self.pin_allowlist = allowlist;
+        self
+    }
+
+    pub(crate) fn with_clock_skew(mut self, skew: Arc<ClockSkew>) -> Self {
+        self.clock_skew = Some(skew);
+        self
+    }
+
+    pub(crate) fn with_blocklist(mut self, blocklist: Arc<Blocklist>) -> Self {
+        self.blocklist = Some(blocklist);
+        self
+    }
+
+    pub(crate) fn with_socket_errors(mut self, errors: Arc<SocketErrors>) -> Self {
+        self.socket_errors = Some(errors);
+        self
+    }
+
+    pub(crate) fn with_siblings(mut self, siblings: Option<Arc<Siblings>>) -> Self {
+        self.siblings = siblings;
+        self
+    }
+
+    pub(crate) fn with_storage_events(mut self, events: Arc<StorageEvents>) -> Self {
+        self.storage_events = Some(events);
+        self
+    }
+
+    pub(crate) fn with_prefer_low_rtt(mut self, enabled: bool) -> Self {
+        self.prefer_low_rtt = enabled;
+        self
+    }
+
+    pub(crate) fn with_capabilities(mut self, caps: Capabilities) -> Self {
+        self.capabilities = caps;
+        self
+    }
+
+    pub(crate) fn with_bucket_refresh_interval(mut self, interval: u64) -> Self {
+        self.bucket_refresh_interval = interval;
+        self
+    }
+
+    pub(crate) fn with_lookup_cache_ttl(mut self, ttl: u64) -> Self {
+        self.lookup_cache_ttl = ttl;
+        self
+    }
+
+    pub(crate) fn with_privacy(mut self, rotation: Option<Duration>) -> Self {
+        self.privacy = rotation;
+        self
+    }
+
+    pub(crate) fn with_concurrency_limits(mut self, limits: ConcurrencyLimits) -> Self {
+        self.concurrency = limits;
+        self
+    }
+
+    pub(crate) fn with_routing_strategy(mut self, strategy: RoutingStrategy) -> Self {
+        self.routing_strategy = strategy;
+        self
+    }
+
+    pub(crate) fn with_command_queue_size(mut self, size: usize) -> Self {
+        self.command_queue_size = Some(size);
+        self
+    }
+
+    pub(crate) fn with_receive_sockets(mut self, count: usize) -> Self {
+        self.receive_sockets = Some(count);
+        self
+    }
+
+    pub(crate) fn with_runtime(mut self, runtime: Option<Handle>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    pub(crate) fn with_extension_handler(mut self, handler: Arc<Mutex<Option<ExtensionHandler>>>) -> Self {
+        self.extension_handler = Some(handler);
+        self
+    }
+
+    pub(crate) fn with_direct_connections(mut self, direct: Arc<Mutex<DirectConnections>>) -> Self {
+        self.direct_connections = Some(direct);
+        self
+    }
 }
 
 pub(crate) struct Verticle {
     dht             : Rc<RefCell<DHT>>,
     timer_manager   : TimerManager,
 
-    cmd_rx          : mpsc::UnboundedReceiver<Cmd>,
+    cmd_rx          : mpsc::Receiver<Cmd>,
     tmr_rx          : mpsc::UnboundedReceiver<TimerCmd>,
+    commands        : CommandQueue,
 
     quit            : bool,
 }
@@ -277,15 +682,11 @@ impl Verticle {
         network: Network,
         host: String,
         port: u16,
-        cmd_rx: mpsc::UnboundedReceiver<Cmd>
+        cmd_rx: mpsc::Receiver<Cmd>
     ) -> Result<Verticle> {
-        let persist_file = options.data_dir.as_ref().map(|dir| {
-            let filename = match network {
-                Network::IPv4 => "dht4.cache",
-                Network::IPv6 => "dht6.cache",
-            };
-            dir.join(filename)
-        });
+        let persist_file = options.layout.as_ref().map(|layout|
+            layout.routing_cache(network)
+        );
 
         let (tmr_tx, tmr_rx) = mpsc::unbounded_channel::<TimerCmd>();
         let timer_client = Rc::new(TimerClient::new(tmr_tx));
@@ -298,6 +699,10 @@ impl Verticle {
         Ok(Self {
             dht,
             timer_manager,
+            commands: CommandQueue {
+                capacity: cmd_rx.max_capacity(),
+                ..Default::default()
+            },
             cmd_rx,
             tmr_rx,
             quit: false,
@@ -312,6 +717,14 @@ impl Verticle {
         self.dht.borrow().ni()
     }
 
+    // Counts the command just taken off the queue, the room it left may
+    // already be taken by a caller waiting for it.
+    fn record_cmd(&mut self, cmd: &Cmd) {
+        let depth = self.cmd_rx.len();
+        self.commands.peak_depth = self.commands.peak_depth.max(depth);
+        *self.commands.counts.entry(cmd.kind()).or_default() += 1;
+    }
+
     fn handle_dht_cmd(
         &mut self,
         cmd: Cmd,
@@ -334,30 +747,70 @@ impl Verticle {
             Cmd::FindNode {
                 target,
                 option,
+                progress,
                 complete,
             } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
                     let (promise, future) = Promise::<Option<NodeInfo>>::pair();
-                    dht.borrow().find_node(target, option, promise);
+                    dht.borrow().find_node(target, option, progress, promise);
                     let _ = complete.send(
                         future.await.map_err(|e| format!("{e}"))
                     );
                 }.boxed_local());
             }
+            Cmd::FindNodeWithHint {
+                target,
+                hint,
+                option,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let result = async {
+                        let cached = dht.borrow().cached_node(&target, option);
+                        if let Some(ni) = cached {
+                            return Ok(Some((ni, ResultSource::Cache)));
+                        }
+
+                        // A hint is only worth a ping if a lookup is allowed anyway.
+                        if let Some(hint) = hint.filter(|_| option != LookupOption::Local) {
+                            let (promise, future) = Promise::<bool>::pair();
+                            dht.borrow().ping(hint.clone(), promise);
+                            if future.await.unwrap_or(false) {
+                                return Ok(Some((hint, ResultSource::Hint)));
+                            }
+                        }
+
+                        let (promise, future) = Promise::<Option<NodeInfo>>::pair();
+                        dht.borrow().find_node(target, option, None, promise);
+                        future.await.map(|ni| ni.map(|ni| (ni, ResultSource::Lookup)))
+                    }.await;
+                    let _ = complete.send(result.map_err(|e| format!("{e}")));
+                }.boxed_local());
+            }
             Cmd::FindValue {
                 target,
                 expected_seq,
                 option,
-                complete,
+                timeout,
+                progress,
+                mut complete,
             } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
-                    let (promise, future) = Promise::<Option<Value>>::pair();
-                    dht.borrow().find_value(target, expected_seq, option, promise);
-                    let _ = complete.send(
-                        future.await.map_err(|e| format!("{e}"))
-                    );
+                    let (promise, future) = Promise::<LookupOutcome<Option<ValueResult>>>::pair();
+                    let waiter = dht.borrow().find_value(target, expected_seq, option, timeout, progress, promise);
+                    tokio::select! {
+                        result = future => {
+                            let _ = complete.send(result.map_err(|e| format!("{e}")));
+                        }
+                        _ = complete.closed() => {
+                            if let Some(waiter) = waiter {
+                                dht.borrow().leave_value_lookup(&waiter);
+                            }
+                        }
+                    }
                 }.boxed_local());
             }
             Cmd::StoreValue {
@@ -367,7 +820,7 @@ impl Verticle {
             } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
-                    let (promise, future) = Promise::<()>::pair();
+                    let (promise, future) = Promise::<usize>::pair();
                     dht.borrow().store_value(value, expected_seq, promise);
                     let _ = complete.send(
                         future.await.map_err(|e| format!("{e}"))
@@ -378,13 +831,17 @@ impl Verticle {
                 target,
                 expected_seq,
                 expected_count,
+                tags,
+                network,
                 option,
+                timeout,
+                progress,
                 complete,
             } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
-                    let (promise, future) = Promise::<Vec<PeerInfo>>::pair();
-                    dht.borrow().find_peer(target, expected_seq, expected_count, option, promise);
+                    let (promise, future) = Promise::<LookupOutcome<Vec<PeerResult>>>::pair();
+                    dht.borrow().find_peer(target, expected_seq, expected_count, tags, network, option, timeout, progress, promise);
                     let _ = complete.send(
                         future.await.map_err(|e| format!("{e}"))
                     );
@@ -397,13 +854,119 @@ impl Verticle {
             } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
-                    let (promise, future) = Promise::<()>::pair();
+                    let (promise, future) = Promise::<Option<String>>::pair();
                     dht.borrow().announce_peer(peer, expected_seq, promise);
                     let _ = complete.send(
                         future.await.map_err(|e| format!("{e}"))
                     );
                 }.boxed_local());
             }
+            Cmd::SendExtension {
+                target,
+                data,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let (promise, future) = Promise::<Vec<u8>>::pair();
+                    dht.borrow().send_extension(target, data, promise);
+                    let _ = complete.send(
+                        future.await.map_err(|e| format!("{e}"))
+                    );
+                }.boxed_local());
+            }
+            Cmd::QueryNode {
+                target,
+                lookup_id,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let (promise, future) = Promise::<NodeQuery>::pair();
+                    dht.borrow().query_node(target, lookup_id, promise);
+                    let _ = complete.send(
+                        future.await.map_err(|e| format!("{e}"))
+                    );
+                }.boxed_local());
+            }
+            Cmd::PinValue {
+                target,
+                value,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let (promise, future) = Promise::<i32>::pair();
+                    dht.borrow().request_token(target.clone(), value.id(), promise);
+                    let token = match future.await {
+                        Ok(token) => token,
+                        Err(e) => {
+                            let _ = complete.send(Err(format!("{e}")));
+                            return;
+                        }
+                    };
+
+                    let (promise, future) = Promise::<()>::pair();
+                    dht.borrow().send_store_value(target, value, token, promise);
+                    let _ = complete.send(
+                        future.await.map_err(|e| format!("{e}"))
+                    );
+                }.boxed_local());
+            }
+            Cmd::Rendezvous {
+                target,
+                rendezvous,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let (promise, future) = Promise::<Rendezvous>::pair();
+                    dht.borrow().send_rendezvous(target, rendezvous, promise);
+                    let _ = complete.send(
+                        future.await.map_err(|e| format!("{e}"))
+                    );
+                }.boxed_local());
+            }
+            Cmd::PingNode {
+                target,
+                complete,
+            } => {
+                let dht = self.dht.clone();
+                pending.push(async move {
+                    let result = async {
+                        let (promise, future) = Promise::<Option<NodeInfo>>::pair();
+                        dht.borrow().find_node(target, LookupOption::Conservative, None, promise);
+                        let Some(ni) = future.await? else {
+                            return Ok(false);
+                        };
+                        let (promise, future) = Promise::<bool>::pair();
+                        dht.borrow().ping(ni, promise);
+                        future.await
+                    }.await;
+                    let _ = complete.send(result.map_err(|e| format!("{e}")));
+                }.boxed_local());
+            }
+            Cmd::ClosestNodes {
+                target,
+                count,
+                include_self,
+                complete,
+            } => {
+                let nodes = self.dht.borrow().closest_nodes(target, count, include_self);
+                let _ = complete.send(Ok(nodes));
+            }
+            Cmd::Stats { complete } => {
+                let mut stats = self.dht.borrow().stats();
+                stats.commands = self.commands.clone();
+                stats.commands.depth = self.cmd_rx.len();
+                let _ = complete.send(Ok(stats));
+            }
+            Cmd::RoutingTable { complete } => {
+                let _ = complete.send(Ok(self.dht.borrow().rt().borrow().snapshot()));
+            }
+            Cmd::RoutingEntries { prefix, complete } => {
+                let _ = complete.send(Ok(self.dht.borrow().rt().borrow().entries_within(&prefix)));
+            }
             Cmd::Start { complete } => {
                 let dht = self.dht.clone();
                 pending.push(async move {
@@ -442,11 +1005,17 @@ impl Verticle {
         let mut pendings = FuturesUnordered::<Pin<Box<dyn Future<Output=()>>>>::new();
 
         let cloned_server = self.dht.borrow().rs();
-        let socket = match cloned_server.borrow().rx_tokio_socket() {
-            Ok(socket) => socket,
-            Err(e) => {
-                error!("Failed to get rx socket: {e}");
-                return;
+        // In multi-socket mode the receive threads read the sockets and
+        // queue the packets to this thread.
+        let mut inbound = cloned_server.borrow_mut().take_inbound();
+        let mut socket = match inbound.is_some() {
+            true => None,
+            false => match cloned_server.borrow().rx_tokio_socket() {
+                Ok(socket) => Some(socket),
+                Err(e) => {
+                    error!("Failed to get rx socket: {e}");
+                    return;
+                }
             }
         };
 
@@ -459,12 +1028,13 @@ impl Verticle {
         loop {
             tokio::select! {
                 Some(cmd) = self.cmd_rx.recv() => {
+                    self.record_cmd(&cmd);
                     self.handle_dht_cmd(cmd, &mut pendings);
                 }
                 Some(cmd) = self.tmr_rx.recv() => {
                     self.handle_timer_cmd(cmd);
                 }
-                packet = socket.recv_from(&mut buf) => {
+                packet = async { socket.as_ref().unwrap().recv_from(&mut buf).await }, if socket.is_some() => {
                     match packet {
                         Ok((len, from)) => {
                             let rs = self.dht.borrow().rs();
@@ -472,11 +1042,16 @@ impl Verticle {
                         }
                         Err(e) => {
                             error!("Receiving data error: {e}");
+                            self.dht.borrow().rs().borrow().receive_failed(&e);
                             continue;
                         }
                     }
 
                 },
+                Some(packet) = async { inbound.as_mut().unwrap().recv().await }, if inbound.is_some() => {
+                    let rs = self.dht.borrow().rs();
+                    RpcServer::handle_inbound(rs, packet).await;
+                },
                 Some(timer_id) = self.timer_manager.next_expired(), if !self.timer_manager.is_idle() => {
                     self.timer_manager.fire_expired(timer_id).await;
                 }
@@ -486,8 +1061,22 @@ impl Verticle {
             if self.quit {
                 break;
             }
+
+            if cloned_server.borrow().rebind_pending() {
+                // Release our clone so the port can be bound again
+                drop(socket.take());
+                RpcServer::rebind(cloned_server.clone()).await;
+                socket = cloned_server.borrow().rx_tokio_socket().ok();
+            }
         }
 
+        // Callers waiting for room in the queue are turned away as well.
+        self.cmd_rx.close();
+        while let Ok(cmd) = self.cmd_rx.try_recv() {
+            cmd.reject(NODE_STOPPED);
+        }
+        drop(pendings);
+
         self.timer_manager.stop_all();
         self.dht.borrow_mut().stop().await;
         info!("DHT verticle exited run_loop");
@@ -495,49 +1084,80 @@ impl Verticle {
 }
 
 type StartupResult = StdResult<NodeInfo, String>;
+
+// Creates and starts the DHT, then serves the commands until it stops.
+async fn run(
+    options: VerticleOptions,
+    network: Network,
+    host: String,
+    port: u16,
+    command_rx: mpsc::Receiver<Cmd>,
+    startup_tx: oneshot::Sender<StartupResult>,
+) {
+    let result = Verticle::new(options, network, host, port, command_rx);
+    let mut vert = match result {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = startup_tx.send(Err(format!("{e}")));
+            return;
+        }
+    };
+
+    let result = vert.start0().await;
+    match result {
+        Ok(()) => {
+            let _ = startup_tx.send(Ok(vert.ni()));
+        }
+        Err(e) => {
+            let _ = startup_tx.send(Err(format!("{e}")));
+            return;
+        }
+    }
+    vert.run_loop().await;
+}
+
 pub(crate) async fn deploy(
     options: VerticleOptions,
     network: Network,
     host: String,
     port: u16,
 ) -> Result<VerticleClient> {
-    let (command_tx, command_rx) = mpsc::unbounded_channel::<Cmd>();
-    let (startup_tx, startup_rx) = std_mpsc::sync_channel::<StartupResult>(1);
-
-    let handle = std::thread::spawn(move || {
-        let rt = runtime::Builder::new_current_thread()
-            .enable_time()
-            .enable_io()
-            .build()
-            .expect("dht verticle runtime should build");
-
-        let local = tokio::task::LocalSet::new();
-        rt.block_on(local.run_until(async move {
-                let result = Verticle::new(options, network, host, port, command_rx);
-                let mut vert = match result {
-                    Ok(v) => v,
-                    Err(e) => {
-                        let _ = startup_tx.send(Err(format!("{e}")));
-                        return;
-                    }
-                };
+    let queue_size = options.command_queue_size.unwrap_or(DEFAULT_COMMAND_QUEUE_SIZE);
+    let (command_tx, command_rx) = mpsc::channel::<Cmd>(queue_size);
+    let (startup_tx, startup_rx) = oneshot::channel::<StartupResult>();
 
-                let result = vert.start0().await;
-                match result {
-                    Ok(()) => {
-                        let _ = startup_tx.send(Ok(vert.ni()));
-                    }
-                    Err(e) => {
-                        let _ = startup_tx.send(Err(format!("{e}")));
-                        return;
-                    }
-                }
-                vert.run_loop().await;
-            }));
-    });
+    // A simulated node shares the thread, and the virtual time, of the
+    // simulation.
+    #[cfg(feature = "testing")]
+    let local = options.sim.is_some();
+    #[cfg(not(feature = "testing"))]
+    let local = false;
+
+    let handle = match local {
+        true => {
+            tokio::task::spawn_local(run(options, network, host, port, command_rx, startup_tx));
+            None
+        },
+        false => {
+            let name = match network {
+                Network::IPv4 => "boson-dht4",
+                Network::IPv6 => "boson-dht6",
+            };
+            let runtime = options.runtime.clone();
+            let result = std::thread::Builder::new().name(name.into()).spawn(move || {
+                let local = tokio::task::LocalSet::new();
+                timer_verticle::block_on(runtime, local.run_until(
+                    run(options, network, host, port, command_rx, startup_tx)
+                ));
+            });
+            Some(result.map_err(|e| {
+                StateError::new(format!("Spawning DHT verticle thread error: {e}"))
+            })?)
+        }
+    };
 
-    let mut vert = match startup_rx.recv() {
-        Ok(Ok(ni)) => VerticleClient {ni, command_tx, handle: Some(handle)},
+    let vert = match startup_rx.await {
+        Ok(Ok(ni)) => VerticleClient {ni, command_tx, handle: Mutex::new(handle)},
         Ok(Err(msg)) => return Err(StateError::new(msg)),
         Err(_) => return Err(StateError::new("dht verticle startup channel closed")),
     };
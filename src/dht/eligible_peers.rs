@@ -3,13 +3,20 @@ use std::{
     collections::HashMap,
 };
 
-use crate::{Id, PeerInfo};
+use crate::{Id, Network, PeerInfo};
+use crate::core::version;
+use crate::dht::lookup_result::{Origins, PeerResult};
 
 pub(crate) struct EligiblePeers {
     target  : Id,
     expected_seq    : i32,
     expected_count  : usize,
-    peers   : HashMap<(Id, u64), PeerInfo>,
+    tags    : Vec<String>,
+    network : Option<Network>,
+    peers   : HashMap<(Id, u64), (PeerInfo, Origins)>,
+    // What each responder returning the peer reported it observed at, the
+    // responders don't sign it.
+    observed: HashMap<(Id, u64), HashMap<Id, Option<String>>>,
     latest  : bool,
 }
 
@@ -19,11 +26,38 @@ impl EligiblePeers {
             target,
             expected_seq,
             expected_count,
+            tags: Vec::new(),
+            network: None,
             peers: HashMap::new(),
+            observed: HashMap::new(),
             latest: false,
         }
     }
 
+    // Only the peers with all the tags are kept, the others are skipped
+    // without taking the whole response for ineligible, responders not
+    // filtering by tags return them too.
+    pub(crate) fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub(crate) fn tags(&self) -> &[String] {
+        self.tags.as_slice()
+    }
+
+    // Only the peers with an endpoint of the network are kept, skipped as
+    // the ones without the tags. Endpoints named by a host name are kept,
+    // they may resolve to either network.
+    pub(crate) fn with_network(mut self, network: Option<Network>) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub(crate) fn network(&self) -> Option<Network> {
+        self.network
+    }
+
     pub(crate) fn expected_seq(&self) -> i32 {
         self.expected_seq
     }
@@ -41,22 +75,40 @@ impl EligiblePeers {
             self.peers.len() >= self.expected_count
     }
 
-    pub(crate) fn add(&mut self, peers: Vec<PeerInfo>, latest: bool) -> bool {
-        for peer in &peers {
+    // Keeps where each peer came from too, merged with the origins of the
+    // same peer from elsewhere. The observed endpoints are kept aside as
+    // votes of the responders, see observed_consensus().
+    pub(crate) fn add(&mut self, peers: Vec<(PeerInfo, Origins)>, latest: bool) -> bool {
+        for (peer, _) in &peers {
             if !self.is_peer_eligible(peer) {
                 return false;
             }
         }
 
-        for peer in peers {
+        for (peer, origins) in peers {
+            if !self.tags.iter().all(|tag| peer.tags().contains(tag)) ||
+                !Self::in_network(&peer, self.network) {
+                continue;
+            }
             let key = (peer.id().clone(), peer.fingerprint());
+            let observed = peer.observed_endpoint().map(|v| v.to_string());
+            let votes = origins.sources().iter()
+                .map(|source| (*source, observed.clone()))
+                .collect::<HashMap<_, _>>();
+            let peer = peer.with_observed(None);
+
             if let Some(existing) = self.peers.get_mut(&key) {
-                if existing.sequence_number() < peer.sequence_number() {
-                    *existing = peer;
+                if existing.0.announcement_version() < peer.announcement_version() {
+                    *existing = (peer, origins);
+                    self.observed.insert(key, votes);
                     self.latest = latest;
+                } else if existing.0.announcement_version() == peer.announcement_version() {
+                    existing.1.merge(origins);
+                    self.observed.entry(key).or_default().extend(votes);
                 }
             } else {
-                self.peers.insert(key, peer);
+                self.peers.insert(key, (peer, origins));
+                self.observed.insert(key, votes);
                 self.latest = latest;
             }
         }
@@ -72,18 +124,67 @@ impl EligiblePeers {
             return;
         }
 
-        let mut all: Vec<PeerInfo> = self.peers.values().cloned().collect();
-        all.sort_by(|l, r| self.peer_order(l, r));
+        let mut all: Vec<(PeerInfo, Origins)> = self.peers.values().cloned().collect();
+        all.sort_by(|l, r| self.peer_order(&l.0, &r.0));
         all.truncate(self.expected_count);
 
         self.peers = all
             .into_iter()
-            .map(|p| ((p.id().clone(), p.fingerprint()), p))
+            .map(|v| ((v.0.id().clone(), v.0.fingerprint()), v))
             .collect();
+        self.observed.retain(|key, _| self.peers.contains_key(key));
     }
 
     pub(crate) fn peers(&self) -> Vec<PeerInfo> {
-        self.peers.values().cloned().collect()
+        self.peers.iter()
+            .map(|(key, (peer, _))| peer.clone().with_observed(self.observed_consensus(key)))
+            .collect()
+    }
+
+    pub(crate) fn results(&self) -> Vec<PeerResult> {
+        self.peers.iter()
+            .map(|(key, (peer, origins))| PeerResult::new(
+                peer.clone().with_observed(self.observed_consensus(key)),
+                origins.clone()
+            ))
+            .collect()
+    }
+
+    // The endpoint the peer was observed at by most of the responders
+    // returning it, none without a majority. Unlike the announcement, the
+    // observed endpoint is not signed, so a responder can't override what
+    // the others returning the peer report.
+    fn observed_consensus(&self, key: &(Id, u64)) -> Option<String> {
+        let responders = self.observed.get(key)?;
+        let mut votes = HashMap::<&str, usize>::new();
+        for endpoint in responders.values().flatten() {
+            *votes.entry(endpoint.as_str()).or_default() += 1;
+        }
+        votes.into_iter()
+            .find(|(_, count)| count * 2 > responders.len())
+            .map(|(endpoint, _)| endpoint.to_string())
+    }
+
+    pub(crate) fn in_network(peer: &PeerInfo, network: Option<Network>) -> bool {
+        match (network, peer.network()) {
+            (Some(wanted), Some(network)) => wanted == network,
+            _ => true,
+        }
+    }
+
+    // The peers as a node of the version can decode them, those it can't
+    // are left out as the whole response would be lost on it. The time,
+    // tags and weight are signed, only the observed endpoint is dropped.
+    pub(crate) fn decodable_by(peers: Vec<PeerInfo>, ver: i32) -> Vec<PeerInfo> {
+        peers.into_iter()
+            .filter(|p| version::supports_peer_announced(ver) || p.announced().is_none())
+            .filter(|p| version::supports_peer_tags(ver) || p.tags().is_empty())
+            .filter(|p| version::supports_peer_weight(ver) || p.weight() == PeerInfo::DEFAULT_WEIGHT)
+            .map(|p| match version::supports_peer_observed(ver) {
+                true => p,
+                false => p.with_observed(None),
+            })
+            .collect()
     }
 
     fn is_peer_eligible(&self, peer: &PeerInfo) -> bool {
@@ -1,10 +1,40 @@
 
-use crate::{Id, Value};
+use crate::{Id, Value, CryptoBox};
+use crate::core::cryptobox::Nonce;
+use crate::dht::lookup_result::{Origins, ValueResult};
+
+// Whether the value is the genuine value of `target`, the reason if not.
+// An immutable value hashes to the target and carries no signature, a
+// mutable one hashes its public key to the target and is signed by it, and
+// an encrypted one, signed over its recipient and nonce, holds at least a
+// sealed box.
+pub(crate) fn verify(target: &Id, value: &Value) -> Result<(), &'static str> {
+    if value.data().is_empty() {
+        return Err("empty data");
+    }
+    if value.id() != *target {
+        return Err("not the value of the target");
+    }
+    if !value.is_mutable() {
+        return match value.is_signed() || value.is_encrypted() || value.nonce().is_some() {
+            true => Err("immutable value with mutable fields"),
+            false => Ok(()),
+        };
+    }
+    if value.is_encrypted() && value.data().len() < CryptoBox::MAC_BYTES + Nonce::BYTES {
+        return Err("encrypted data too short");
+    }
+    match value.is_valid() {
+        true => Ok(()),
+        false => Err("invalid signature"),
+    }
+}
 
 pub(crate) struct EligibleValue {
     target  : Id,
     expected_seq: i32,
     value   : Option<Value>,
+    origins : Origins,
     latest  : bool,
 }
 
@@ -14,6 +44,7 @@ impl EligibleValue {
             target,
             expected_seq,
             value   : None,
+            origins : Origins::default(),
             latest  : false
         }
     }
@@ -26,17 +57,23 @@ impl EligibleValue {
         self.value.is_none()
     }
 
-    pub(crate) fn update(&mut self, value: Value, latest: bool) -> bool {
-        if value.id() != self.target
+    // Keeps where the value came from too, merged with the origins of the
+    // same value from elsewhere.
+    pub(crate) fn update(&mut self, value: Value, latest: bool, origins: Origins) -> bool {
+        if verify(&self.target, &value).is_err()
             || (self.expected_seq >= 0 && value.sequence_number() < self.expected_seq)
-            || !value.is_valid()
         {
             return false;
         }
 
-        if self.value.as_ref().map_or(true, |v| value.sequence_number() > v.sequence_number()) {
-            self.value = Some(value);
-            self.latest = latest;
+        match self.value.as_ref().map(|v| v.sequence_number()) {
+            Some(seq) if value.sequence_number() < seq => {},
+            Some(seq) if value.sequence_number() == seq => self.origins.merge(origins),
+            _ => {
+                self.value = Some(value);
+                self.origins = origins;
+                self.latest = latest;
+            }
         }
         true
     }
@@ -48,4 +85,8 @@ impl EligibleValue {
     pub(crate) fn value(&self) -> Option<Value> {
         self.value.as_ref().map(|v| v.clone())
     }
+
+    pub(crate) fn result(&self) -> Option<ValueResult> {
+        self.value.as_ref().map(|v| ValueResult::new(v.clone(), self.origins.clone()))
+    }
 }
@@ -0,0 +1,51 @@
+use futures::stream::{self, Stream};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+pub(crate) const DEFAULT_EVENT_STREAM_CAPACITY: usize = 64;
+
+/// An item of the event streams of the node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamItem<T> {
+    Event(T),
+    /// The stream fell behind and missed this many events, the oldest
+    /// ones were dropped rather than holding up the node.
+    Lagged(u64),
+}
+
+/// The life cycle of the node, see [`Node::status_events`](crate::dht::Node::status_events).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeStatusEvent {
+    Started,
+    Stopped,
+}
+
+// Feeds the events to all the streams subscribed to it. Sending never
+// waits on a stream, each one has a bounded backlog of its own.
+pub(crate) struct EventBroadcast<T> {
+    sender: broadcast::Sender<T>,
+}
+
+impl<T: Clone + Send + 'static> EventBroadcast<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            sender: broadcast::channel(capacity).0,
+        }
+    }
+
+    // Dropped when nothing is subscribed.
+    pub(crate) fn emit(&self, event: T) {
+        _ = self.sender.send(event);
+    }
+
+    // Dropping the stream unsubscribes it.
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = StreamItem<T>> + Send + 'static {
+        stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            let item = match receiver.recv().await {
+                Ok(event) => StreamItem::Event(event),
+                Err(RecvError::Lagged(missed)) => StreamItem::Lagged(missed),
+                Err(RecvError::Closed) => return None,
+            };
+            Some((item, receiver))
+        })
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    fmt,
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use log::{debug, trace};
+use tokio::net::UdpSocket;
+
+use crate::{
+    Id,
+    errors::{Result, NetworkError},
+};
+use crate::dht::utils;
+
+const PROBE_MAGIC: &[u8; 4] = b"BSPN";
+const PROBE_BYTES: usize = PROBE_MAGIC.len() + 1 + 8 + 2;
+
+// Incoming attempts probed at the same time, each one binds a socket and
+// sends probes on behalf of the requester.
+pub(crate) const MAX_INCOMING_ATTEMPTS: usize = 4;
+
+// Candidate addresses probed per attempt.
+pub(crate) const MAX_CANDIDATES: usize = 16;
+
+const KIND_PROBE: u8 = 0;
+const KIND_ACK  : u8 = 1;
+
+/// Invoked with every direct connection a remote node set up with this
+/// node. Incoming rendezvous requests are refused while no handler is set.
+pub type DirectConnectionHandler = Box<dyn Fn(PunchResult) + Send + Sync>;
+
+/// How the probes of a direct connection attempt are sent, to be tuned
+/// for the NATs in the way.
+///
+/// Every round sends one probe to each candidate address of the remote
+/// node. The candidates are the ports the remote node reported, shifted by
+/// each of the port offsets, on every address it reported plus the address
+/// the DHT sees it at. Offsets other than 0 guess the mappings of NATs that
+/// allocate ports sequentially.
+#[derive(Debug, Clone)]
+pub struct ProbePattern {
+    rounds      : u32,
+    interval    : Duration,
+    port_offsets: Vec<i32>,
+}
+
+impl Default for ProbePattern {
+    fn default() -> Self {
+        Self {
+            rounds      : 10,
+            interval    : Duration::from_millis(200),
+            port_offsets: vec![0],
+        }
+    }
+}
+
+impl ProbePattern {
+    pub fn with_rounds(mut self, rounds: u32) -> Self {
+        self.rounds = rounds.max(1);
+        self
+    }
+
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval.max(Duration::from_millis(10));
+        self
+    }
+
+    pub fn with_port_offsets(mut self, offsets: &[i32]) -> Self {
+        self.port_offsets = match offsets.is_empty() {
+            true => vec![0],
+            false => offsets.to_vec(),
+        };
+        self
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn port_offsets(&self) -> &[i32] {
+        &self.port_offsets
+    }
+
+    // How long an attempt waits for the remote probes, a grace of two
+    // intervals past the last round.
+    pub(crate) fn duration(&self) -> Duration {
+        self.interval * (self.rounds + 2)
+    }
+}
+
+/// A direct UDP path to a remote node, verified in both directions.
+///
+/// The socket is the one the probes were exchanged on, the NAT mappings
+/// opened by the probes belong to it. Packets sent on it to
+/// [`remote_addr`](Self::remote_addr) reach the remote node directly.
+#[derive(Debug)]
+pub struct PunchResult {
+    peer    : Id,
+    socket  : std::net::UdpSocket,
+    remote  : SocketAddr,
+    rtt     : Duration,
+}
+
+impl PunchResult {
+    pub(crate) fn new(peer: Id, socket: UdpSocket, remote: SocketAddr, rtt: Duration) -> Result<Self> {
+        Ok(Self {
+            peer,
+            socket: socket.into_std()?,
+            remote,
+            rtt,
+        })
+    }
+
+    /// The remote node.
+    pub fn peer(&self) -> &Id {
+        &self.peer
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// The address the remote node answered the probes from.
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote
+    }
+
+    /// The round trip time of the first answered probe.
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+
+    /// The socket to talk to the remote node on. It is in non-blocking mode,
+    /// ready to be handed to an async runtime.
+    pub fn into_socket(self) -> std::net::UdpSocket {
+        self.socket
+    }
+}
+
+impl fmt::Display for PunchResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "udp {} <-> {}@{}",
+            self.socket.local_addr().map(|v| v.to_string()).unwrap_or_default(),
+            self.peer,
+            self.remote
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Probe {
+    Probe(u16),
+    Ack(u16),
+}
+
+impl Probe {
+    pub(crate) fn encode(&self, session: u64) -> [u8; PROBE_BYTES] {
+        let (kind, seq) = match *self {
+            Probe::Probe(seq) => (KIND_PROBE, seq),
+            Probe::Ack(seq) => (KIND_ACK, seq),
+        };
+
+        let mut data = [0u8; PROBE_BYTES];
+        data[..4].copy_from_slice(PROBE_MAGIC);
+        data[4] = kind;
+        data[5..13].copy_from_slice(&session.to_be_bytes());
+        data[13..].copy_from_slice(&seq.to_be_bytes());
+        data
+    }
+
+    // None for anything but a probe of the given session.
+    pub(crate) fn decode(data: &[u8], session: u64) -> Option<Self> {
+        if data.len() != PROBE_BYTES || &data[..4] != PROBE_MAGIC {
+            return None;
+        }
+        if u64::from_be_bytes(data[5..13].try_into().unwrap()) != session {
+            return None;
+        }
+        let seq = u16::from_be_bytes(data[13..].try_into().unwrap());
+        match data[4] {
+            KIND_PROBE => Some(Probe::Probe(seq)),
+            KIND_ACK => Some(Probe::Ack(seq)),
+            _ => None,
+        }
+    }
+}
+
+// The addresses a node can be reached at besides loopback, the bound
+// address itself unless the socket is bound to all interfaces.
+pub(crate) fn local_hosts(bound: IpAddr) -> Vec<IpAddr> {
+    if !bound.is_unspecified() {
+        return vec![bound];
+    }
+    utils::local_addrs().unwrap_or_default().into_iter()
+        .filter(|ip| !ip.is_loopback() && ip.is_ipv4() == bound.is_ipv4())
+        .collect()
+}
+
+// The addresses to probe: every reported or observed host of the remote
+// node, of the local socket family, combined with the port offsets.
+pub(crate) fn candidates(
+    hosts: &[IpAddr],
+    observed: Option<IpAddr>,
+    port: u16,
+    pattern: &ProbePattern,
+    ipv4: bool
+) -> Vec<SocketAddr> {
+    let mut result = Vec::new();
+    for ip in hosts.iter().copied().chain(observed) {
+        if ip.is_ipv4() != ipv4 || ip.is_unspecified() {
+            continue;
+        }
+        for offset in pattern.port_offsets() {
+            let Ok(port) = u16::try_from(port as i32 + offset) else {
+                continue;
+            };
+            let addr = SocketAddr::new(ip, port);
+            if port > 0 && !result.contains(&addr) && result.len() < MAX_CANDIDATES {
+                result.push(addr);
+            }
+        }
+    }
+    result
+}
+
+// Exchanges probes with the remote node until both directions are verified:
+// one of our probes was acknowledged, and one of the remote probes reached
+// us and was acknowledged in turn. Probes are answered from the address
+// they came from, which is what a NAT in the way allows through. Returns
+// the remote address that acknowledged and the round trip time.
+pub(crate) async fn punch(
+    socket: &UdpSocket,
+    session: u64,
+    candidates: &[SocketAddr],
+    pattern: &ProbePattern
+) -> Result<(SocketAddr, Duration)> {
+    if candidates.is_empty() {
+        return Err(NetworkError::new("No candidate address to probe"));
+    }
+
+    let deadline = tokio::time::Instant::now() + pattern.duration();
+    let mut ticker = tokio::time::interval(pattern.interval());
+    let mut sent = HashMap::<u16, Instant>::new();
+    let mut acked = None;
+    let mut heard = false;
+    let mut buf = [0u8; 64];
+    let mut round = 0u32;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if round < pattern.rounds() => {
+                let seq = round as u16;
+                sent.insert(seq, Instant::now());
+                let probe = Probe::Probe(seq).encode(session);
+                for addr in candidates {
+                    if let Err(e) = socket.send_to(&probe, addr).await {
+                        trace!("Sending probe to {addr} failed: {e}");
+                    }
+                }
+                round += 1;
+            },
+            result = socket.recv_from(&mut buf) => {
+                let (len, from) = match result {
+                    Ok(v) => v,
+                    // ICMP unreachable from a candidate nobody listens on.
+                    Err(e) => {
+                        trace!("Receiving probe failed: {e}");
+                        continue;
+                    }
+                };
+                match Probe::decode(&buf[..len], session) {
+                    Some(Probe::Probe(seq)) => {
+                        let _ = socket.send_to(&Probe::Ack(seq).encode(session), from).await;
+                        heard = true;
+                    },
+                    Some(Probe::Ack(seq)) => {
+                        if acked.is_none() {
+                            if let Some(time) = sent.get(&seq) {
+                                acked = Some((from, time.elapsed()));
+                            }
+                        }
+                    },
+                    None => trace!("Ignored stray datagram from {from}"),
+                }
+            },
+            _ = tokio::time::sleep_until(deadline) => {
+                break;
+            },
+        }
+
+        if let (true, Some(result)) = (heard, acked) {
+            debug!("Probes verified with {} after {} rounds", result.0, round);
+            return Ok(result);
+        }
+    }
+
+    Err(NetworkError::new(match (heard, acked) {
+        (false, None)   => "No probe went through in either direction",
+        (true, None)    => "Remote probes arrived, none of ours was acknowledged",
+        _               => "Our probes were acknowledged, no remote probe arrived",
+    }))
+}
+
+// The connection handler and the probe pattern, shared by the node with
+// its DHTs, and the count of incoming attempts in progress.
+#[derive(Default)]
+pub(crate) struct DirectConnections {
+    pub(crate) handler  : Option<Arc<DirectConnectionHandler>>,
+    pub(crate) pattern  : ProbePattern,
+    pub(crate) incoming : usize,
+}
@@ -0,0 +1,145 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+};
+use log::info;
+
+use crate::{
+    signature,
+    cryptobox::{CryptoBox, Nonce},
+    core::crypto::{SealedHeader, HeaderError},
+    errors::{Result, IOError, ArgumentError, CryptoError},
+};
+
+// The node key kept in a file of its own, either as the text of the private
+// key as in node.yaml (legacy, and the default without a passphrase), or
+// sealed under a passphrase: a sealed header || secretbox(private key), the
+// secretbox output carrying its nonce.
+const MAGIC: &[u8; SealedHeader::MAGIC_BYTES] = b"BSNK";
+const FORMAT_VERSION: u8 = 1;
+
+pub(crate) fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive(passphrase: &str, header: &SealedHeader) -> Result<CryptoBox> {
+    let mut key = [0u8; CryptoBox::SYMMETRIC_KEY_BYTES];
+    if !header.derive(passphrase, &mut key) {
+        return Err(CryptoError::new("Deriving the node key file key failed, Argon2id needs the sodium backend"));
+    }
+    let cipher = CryptoBox::from_symmetric_key(key);
+    key.fill(0);
+    Ok(cipher)
+}
+
+pub(crate) fn seal(sk: &signature::PrivateKey, passphrase: &str) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        return Err(ArgumentError::new("Node key passphrase must not be empty"));
+    }
+
+    let header = SealedHeader::new();
+    let sealed = derive(passphrase, &header)?
+        .encrypt_into(sk.as_bytes(), &Nonce::random())?;
+
+    let mut data = Vec::with_capacity(SealedHeader::BYTES + sealed.len());
+    header.write(MAGIC, FORMAT_VERSION, &mut data);
+    data.extend_from_slice(&sealed);
+    Ok(data)
+}
+
+pub(crate) fn open(data: &[u8], passphrase: &str) -> Result<signature::PrivateKey> {
+    let header = SealedHeader::parse(data, MAGIC, FORMAT_VERSION).map_err(|e| match e {
+        HeaderError::Incomplete => ArgumentError::new("Node key file is incomplete"),
+        HeaderError::UnsupportedVersion(v) => ArgumentError::new(format!(
+            "Unsupported node key file version {v}"
+        )),
+        HeaderError::UnsupportedCost => ArgumentError::new("Unsupported node key file key parameters"),
+    })?;
+    let sealed = &data[SealedHeader::BYTES..];
+
+    // The secretbox MAC fails alike on a wrong passphrase and on a damaged
+    // file, the passphrase being far more likely.
+    let mut plain = derive(passphrase, &header)?
+        .decrypt_into(sealed)
+        .map_err(|_| CryptoError::new("Wrong passphrase for the node key file") as crate::Error)?;
+    let sk = signature::PrivateKey::try_from(plain.as_slice());
+    plain.fill(0);
+    sk
+}
+
+// The private key in the file at `path`, sealed or not.
+pub(crate) fn read(path: &Path, passphrase: Option<&str>) -> Result<signature::PrivateKey> {
+    read_file(path, passphrase).map(|(sk, _)| sk)
+}
+
+// The private key and whether the file was sealed.
+fn read_file(path: &Path, passphrase: Option<&str>) -> Result<(signature::PrivateKey, bool)> {
+    let mut data = fs::read(path).map_err(|e| IOError::new(
+        format!("Reading node key file {} error: {e}", path.display())))?;
+
+    let encrypted = is_encrypted(&data);
+    let sk = match (encrypted, passphrase) {
+        (true, Some(passphrase)) => open(&data, passphrase),
+        (true, None) => Err(ArgumentError::new(format!(
+            "Node key file {} is encrypted, a key passphrase is required", path.display())) as crate::Error),
+        (false, _) => match std::str::from_utf8(&data) {
+            Ok(text) => signature::PrivateKey::try_from(text.trim()),
+            Err(_) => Err(ArgumentError::new(format!("Invalid node key file {}", path.display())) as crate::Error),
+        },
+    };
+    data.fill(0);
+    sk.map(|sk| (sk, encrypted))
+}
+
+// Writes the private key to `path`, sealed under the passphrase if given. The
+// file is replaced in one step, so an interrupted write never loses the key.
+pub(crate) fn write(path: &Path, sk: &signature::PrivateKey, passphrase: Option<&str>) -> Result<()> {
+    let mut data = match passphrase {
+        Some(passphrase) => seal(sk, passphrase)?,
+        None => sk.to_string().into_bytes(),
+    };
+
+    let tmp = path.with_extension("tmp");
+    let written = (|| {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    data.fill(0);
+
+    written.map_err(|e| {
+        _ = fs::remove_file(&tmp);
+        IOError::new(format!("Writing node key file {} error: {e}", path.display())) as crate::Error
+    })
+}
+
+// The node key kept at `path`: a new key on the first start, and a legacy
+// plaintext file sealed in place once a passphrase is configured.
+pub(crate) fn load_or_create(path: &Path, passphrase: Option<&str>) -> Result<signature::PrivateKey> {
+    if !path.exists() {
+        if let Some(dir) = path.parent().filter(|v| !v.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| IOError::new(
+                format!("Creating node key directory {} error: {e}", dir.display())))?;
+        }
+        let sk = signature::KeyPair::random().to_private_key();
+        write(path, &sk, passphrase)?;
+        info!("Created node key file {}", path.display());
+        return Ok(sk);
+    }
+
+    let (sk, encrypted) = read_file(path, passphrase)?;
+    if let (Some(passphrase), false) = (passphrase, encrypted) {
+        write(path, &sk, Some(passphrase))?;
+        info!("Encrypted the plaintext node key file {}", path.display());
+    }
+    Ok(sk)
+}
@@ -1,4 +1,4 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LookupOption {
@@ -18,3 +18,27 @@ impl fmt::Display for LookupOption {
         })
     }
 }
+
+/// A lookup option with a deadline for the lookup. Once it passes, the
+/// lookup ends with the best result found so far, flagged as not completed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LookupOptionsEx {
+    pub option  : Option<LookupOption>,
+    pub timeout : Option<Duration>,
+}
+
+impl LookupOptionsEx {
+    pub fn new(option: Option<LookupOption>, timeout: Option<Duration>) -> Self {
+        Self { option, timeout }
+    }
+
+    pub fn with_option(mut self, option: LookupOption) -> Self {
+        self.option = Some(option);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
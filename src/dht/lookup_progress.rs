@@ -0,0 +1,170 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::core::Network;
+
+// Snapshots are sent at most this often, unless the lookup got closer to
+// its target.
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// Snapshots the caller has not taken yet, further ones are dropped.
+pub(crate) const PROGRESS_QUEUE_SIZE: usize = 16;
+
+pub(crate) type ProgressSender = mpsc::Sender<LookupProgress>;
+
+/// A snapshot of a lookup in progress, handed to the callback of
+/// [`Node::find_value_with_progress`](crate::dht::Node::find_value_with_progress)
+/// and its siblings.
+///
+/// Each DHT network runs a lookup of its own, their snapshots are told apart
+/// by [`network`](Self::network). The counts of one network never decrease.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupProgress {
+    network     : Network,
+    queried     : usize,
+    responded   : usize,
+    timed_out   : usize,
+    failed      : usize,
+    best_distance: Option<usize>,
+    candidates  : usize,
+    elapsed     : Duration,
+    finished    : bool,
+}
+
+impl LookupProgress {
+    pub(crate) fn new(network: Network) -> Self {
+        Self {
+            network,
+            queried     : 0,
+            responded   : 0,
+            timed_out   : 0,
+            failed      : 0,
+            best_distance: None,
+            candidates  : 0,
+            elapsed     : Duration::ZERO,
+            finished    : false,
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Requests sent to the nodes, a node asked again after a timeout counts
+    /// twice.
+    pub fn queried(&self) -> usize {
+        self.queried
+    }
+
+    pub fn responded(&self) -> usize {
+        self.responded
+    }
+
+    pub fn timed_out(&self) -> usize {
+        self.timed_out
+    }
+
+    /// Requests that failed other than by a timeout, an error response
+    /// among them.
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// The distance of the closest responding node to the target, as the
+    /// leading zero bits of their XOR distance: the higher the closer. None
+    /// before the first response.
+    pub fn best_distance(&self) -> Option<usize> {
+        self.best_distance
+    }
+
+    /// The nodes known to the lookup but not queried yet.
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Whether the lookup ended, the last snapshot of a network.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub(crate) fn with_counts(mut self, queried: usize, responded: usize, timed_out: usize, failed: usize) -> Self {
+        self.queried = queried;
+        self.responded = responded;
+        self.timed_out = timed_out;
+        self.failed = failed;
+        self
+    }
+
+    pub(crate) fn with_best_distance(mut self, best_distance: Option<usize>) -> Self {
+        self.best_distance = best_distance;
+        self
+    }
+
+    pub(crate) fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    pub(crate) fn with_elapsed(mut self, elapsed: Duration) -> Self {
+        self.elapsed = elapsed;
+        self
+    }
+
+    pub(crate) fn into_finished(mut self) -> Self {
+        self.finished = true;
+        self.candidates = 0;
+        self
+    }
+}
+
+// Hands the snapshots of a lookup task from the DHT thread to the caller.
+// Sending never waits, a snapshot finding the queue full is dropped.
+pub(crate) struct ProgressReporter {
+    network     : Network,
+    sender      : ProgressSender,
+    started     : Instant,
+    last_sent   : Cell<Option<Instant>>,
+    last_best   : Cell<Option<usize>>,
+}
+
+impl ProgressReporter {
+    pub(crate) fn new(network: Network, sender: ProgressSender) -> Self {
+        Self {
+            network,
+            sender,
+            started     : Instant::now(),
+            last_sent   : Cell::new(None),
+            last_best   : Cell::new(None),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> LookupProgress {
+        LookupProgress::new(self.network)
+            .with_elapsed(self.started.elapsed())
+    }
+
+    // Sent if the interval passed since the last one, or the lookup got
+    // closer to the target.
+    pub(crate) fn update(&self, progress: LookupProgress) {
+        let now = Instant::now();
+        let closer = progress.best_distance > self.last_best.get();
+        let due = self.last_sent.get()
+            .is_none_or(|last| now.duration_since(last) >= PROGRESS_INTERVAL);
+        if !closer && !due {
+            return;
+        }
+
+        self.last_best.set(progress.best_distance);
+        self.last_sent.set(Some(now));
+        _ = self.sender.try_send(progress);
+    }
+
+    pub(crate) fn finish(&self, progress: LookupProgress) {
+        _ = self.sender.try_send(progress.into_finished());
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::Duration;
+use crate::{Id, Value, PeerInfo};
+
+// The nodes a record was received from, and the youngest age in seconds
+// they reported for it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Origins {
+    min_age : Option<u64>,
+    sources : Vec<Id>,
+}
+
+impl Origins {
+    pub(crate) fn new(source: Id, age: Option<u64>) -> Self {
+        Self {
+            min_age : age,
+            sources : vec![source],
+        }
+    }
+
+    pub(crate) fn sources(&self) -> &[Id] {
+        &self.sources
+    }
+
+    pub(crate) fn merge(&mut self, other: Origins) {
+        for source in other.sources {
+            if !self.sources.contains(&source) {
+                self.sources.push(source);
+            }
+        }
+        self.min_age = match (self.min_age, other.min_age) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// A value found by a lookup, with what the nodes returning it reported.
+#[derive(Debug, Clone)]
+pub struct ValueResult {
+    value   : Value,
+    min_age : Option<Duration>,
+    sources : Vec<Id>,
+}
+
+impl ValueResult {
+    pub(crate) fn new(value: Value, origins: Origins) -> Self {
+        Self {
+            value,
+            min_age : origins.min_age.map(Duration::from_secs),
+            sources : origins.sources,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (Value, Origins) {
+        let min_age = self.min_age.map(|v| v.as_secs());
+        (self.value, Origins { min_age, sources: self.sources })
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    pub fn into_value(self) -> Value {
+        self.value
+    }
+
+    /// The youngest age reported for the value, the time since one of the
+    /// sources stored or refreshed it. `None` if no source reported an age,
+    /// like nodes running older versions.
+    pub fn min_age(&self) -> Option<Duration> {
+        self.min_age
+    }
+
+    /// The nodes the value was received from, the local node included
+    /// when the value was found in its storage.
+    pub fn sources(&self) -> &[Id] {
+        &self.sources
+    }
+}
+
+/// A peer found by a lookup, with what the nodes returning it reported.
+#[derive(Debug, Clone)]
+pub struct PeerResult {
+    peer    : PeerInfo,
+    min_age : Option<Duration>,
+    sources : Vec<Id>,
+}
+
+impl PeerResult {
+    pub(crate) fn new(peer: PeerInfo, origins: Origins) -> Self {
+        Self {
+            peer,
+            min_age : origins.min_age.map(Duration::from_secs),
+            sources : origins.sources,
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (PeerInfo, Origins) {
+        let min_age = self.min_age.map(|v| v.as_secs());
+        (self.peer, Origins { min_age, sources: self.sources })
+    }
+
+    pub fn peer(&self) -> &PeerInfo {
+        &self.peer
+    }
+
+    pub fn into_peer(self) -> PeerInfo {
+        self.peer
+    }
+
+    /// The youngest age reported for the peer, see [`ValueResult::min_age`].
+    pub fn min_age(&self) -> Option<Duration> {
+        self.min_age
+    }
+
+    /// The nodes the peer was received from.
+    pub fn sources(&self) -> &[Id] {
+        &self.sources
+    }
+}
+
+/// The result of a lookup, and whether the lookup ran to its end or was
+/// cut short by its deadline with the best result found by then.
+#[derive(Debug, Clone)]
+pub struct LookupOutcome<T> {
+    result      : T,
+    completed   : bool,
+}
+
+impl<T> LookupOutcome<T> {
+    pub(crate) fn new(result: T, completed: bool) -> Self {
+        Self { result, completed }
+    }
+
+    pub fn result(&self) -> &T {
+        &self.result
+    }
+
+    pub fn into_result(self) -> T {
+        self.result
+    }
+
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+}
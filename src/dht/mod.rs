@@ -11,13 +11,17 @@ mod msg {
     pub(crate) mod find_value_req;
     pub(crate) mod find_value_rsp;
     pub(crate) mod announce_peer_req;
+    pub(crate) mod announce_peer_rsp;
     pub(crate) mod store_value_req;
+    pub(crate) mod extension;
+    pub(crate) mod rendezvous;
 
     #[cfg(test)]
     mod unitests {
         mod test_find_node_req;
         mod test_find_node_rsp;
         mod test_announce_peer_req;
+        mod test_announce_peer_rsp;
         mod test_find_peer_req;
         mod test_find_peer_rsp;
         mod test_find_value_req;
@@ -25,6 +29,8 @@ mod msg {
         mod test_msg;
         mod test_store_value_req;
         mod test_error;
+        mod test_extension;
+        mod test_rendezvous;
     }
 
     pub(crate) use {
@@ -37,7 +43,10 @@ mod msg {
         find_value_req::FindValueRequest,
         find_value_rsp::FindValueResponse,
         announce_peer_req::AnnouncePeerRequest,
+        announce_peer_rsp::AnnouncePeerResponse,
         store_value_req::StoreValueRequest,
+        extension::Extension,
+        rendezvous::Rendezvous,
         error::Error as ErrorBody,
         msg::{Message, Body},
     };
@@ -49,6 +58,7 @@ mod task {
     pub(crate) mod candidate_node;
 
     pub(crate) mod task_manager;
+    pub(crate) mod lookup_coalescer;
     pub(crate) mod task;
     pub(crate) mod task_listener;
     pub(crate) mod lookup_task;
@@ -71,6 +81,8 @@ mod task {
         mod test_peer_announce;
         mod test_value_lookup;
         mod test_value_announce;
+        mod test_lookup_coalescer;
+        mod test_task_manager;
     }
 
     pub(crate) use {
@@ -112,11 +124,16 @@ mod routing {
     };
 }
 
-mod rpc {
+pub(crate) mod rpc {
     pub(crate) mod listener;
     pub(crate) mod rpccall;
     pub(crate) mod rpc_server;
     pub(crate) mod rpc_target;
+    pub(crate) mod socket_health;
+    pub(crate) mod send_shaper;
+    pub(crate) mod receivers;
+    #[cfg(feature = "wsgateway")]
+    pub(crate) mod ws_gateway;
 
     pub(crate) use {
         rpccall::RpcCall,
@@ -125,7 +142,7 @@ mod rpc {
     };
 }
 
-mod storage;
+pub(crate) mod storage;
 mod cached_identity;
 mod dht_verticle;
 mod dht;
@@ -135,6 +152,15 @@ mod eligible_peers;
 mod eligible_value;
 mod suspicious_node_detector;
 mod token_manager;
+mod token_cache;
+mod blocklist;
+mod origin_check;
+mod data_layout;
+mod key_file;
+mod announcement;
+mod clock_skew;
+mod siblings;
+mod session_ids;
 mod timer_client;
 mod timer_manager;
 mod timer_verticle;
@@ -145,15 +171,45 @@ pub mod errors;
 pub mod connection_status_listener;
 pub mod connection_status;
 pub mod lookup_option;
+pub mod lookup_result;
+pub mod lookup_progress;
+pub mod node_query;
+pub mod hole_punch;
+pub mod storage_backend;
+pub mod node_event;
+pub mod storage_event;
+pub mod event_stream;
+pub mod stats;
+pub mod crypto_cache;
+pub mod peer_selector;
+pub mod well_known;
+pub mod socket_event;
+pub mod seed;
 pub mod node;
 
 pub use crate::dht::{
-    node::Node,
-    lookup_option::LookupOption,
+    node::{Node, ExtensionHandler, MAX_EXTENSION_PAYLOAD},
+    lookup_option::{LookupOption, LookupOptionsEx},
+    lookup_result::{ValueResult, PeerResult, LookupOutcome},
+    lookup_progress::LookupProgress,
+    node_query::NodeQuery,
+    hole_punch::{PunchResult, ProbePattern, DirectConnectionHandler},
+    storage_backend::StorageBackend,
+    node_event::{NodeEvent, NodeEventKind},
+    storage_event::{StorageEvent, StorageListener},
+    event_stream::{StreamItem, NodeStatusEvent},
+    storage::data_storage::IntegrityReport,
+    stats::{StatsSample, NetworkSample, Concurrency, CommandQueue, CryptoCacheStats, TokenCacheStats, BlocklistStats, OriginCheckStats, ReceiveStats, SocketErrorStats, KeyspaceStats, KeyspaceReport},
+    socket_event::{SocketEvent, ErrnoClass, SocketErrorHandler},
+    blocklist::{BlockEntry, BlockTarget, BlockReason},
+    crypto_cache::CryptoCache,
+    peer_selector::PeerSelector,
+    routing::{kbucket::BucketInfo, prefix::Prefix},
     connection_status::ConnectionStatus,
     connection_status_listener::ConnectionStatusListener,
     node_config::NodeConfig,
     yaml_configuration::NodeConfiguration,
+    well_known::KnownNetwork,
 };
 
 pub(crate) mod utils {
@@ -218,6 +274,12 @@ pub(crate) mod utils {
         addr.port() < 0xFFFF && is_global_unicast(&addr.ip()))
     }
 
+    pub(crate) fn local_addrs() -> Option<Vec<IpAddr>> {
+        get_if_addrs::get_if_addrs().ok().map(|ifs| {
+            ifs.iter().map(|iface| iface.ip()).collect()
+        })
+    }
+
     #[allow(unused)]
     pub(crate) fn local_addr(ipv4: bool) -> Option<IpAddr>{
         let if_addrs = match get_if_addrs::get_if_addrs() {
@@ -241,12 +303,41 @@ pub(crate) mod utils {
 mod unitests {
     mod test_addr;
     mod test_node_configuration;
+    mod test_key_file;
 
     mod test_rpccall;
     mod test_token_manager;
+    mod test_token_cache;
+    mod test_blocklist;
+    mod test_origin_check;
+    #[cfg(target_os = "linux")]
+    mod test_receivers;
+    mod test_data_layout;
     mod test_dht;
     mod test_cached_identity;
+    mod test_node_event;
+    mod test_socket_health;
+    mod test_socket_errors;
+    mod test_send_shaper;
+    #[cfg(feature = "wsgateway")]
+    mod test_ws_gateway;
+    mod test_inflight_calls;
+    mod test_stats;
+    mod test_endpoint_screening;
+    mod test_hole_punch;
+    mod test_announcement;
+    mod test_eligible_value;
+    mod test_clock_skew;
+    mod test_siblings;
+    mod test_storage_event;
+    mod test_event_stream;
+    mod test_lookup_progress;
+    mod test_session_ids;
+    mod test_crypto_cache;
+    mod test_peer_selector;
 
     // storage
     mod test_storage;
+    mod test_migrations;
+    mod test_seed;
 }
@@ -4,6 +4,7 @@ use crate::{
     Id, PeerInfo,
     errors::{Error, Result},
 };
+use crate::core::peer_info::PeerFields;
 use super::utils;
 
 #[derive(Clone)]
@@ -74,6 +75,15 @@ struct SerdeAnnouncePeerRequest {
     #[serde(rename = "ex")]
     #[serde(skip_serializing_if = "crate::is_default")]
     extra: Option<Vec<u8>>,
+    #[serde(rename = "tg")]
+    #[serde(skip_serializing_if = "crate::is_default", default)]
+    tags: Vec<String>,
+    #[serde(rename = "w")]
+    #[serde(skip_serializing_if = "is_default_weight", default = "default_weight")]
+    weight: u8,
+    #[serde(rename = "at")]
+    #[serde(skip_serializing_if = "crate::is_default", default)]
+    announced: Option<u64>,
 }
 
 impl Into<SerdeAnnouncePeerRequest> for AnnouncePeerRequest {
@@ -99,6 +109,9 @@ impl Into<SerdeAnnouncePeerRequest> for AnnouncePeerRequest {
             fingerprint: peer.fingerprint(),
             endpoint: peer.endpoint().to_string(),
             extra   : peer.extra_data().map(|v| v.to_vec()),
+            tags    : peer.tags().to_vec(),
+            weight  : peer.weight(),
+            announced: peer.announced(),
         }
     }
 }
@@ -115,7 +128,12 @@ impl TryFrom<SerdeAnnouncePeerRequest> for AnnouncePeerRequest {
             s.sig,
             s.fingerprint,
             s.endpoint,
-            s.extra
+            PeerFields {
+                extra: s.extra,
+                tags: s.tags,
+                weight: s.weight,
+                announced: s.announced,
+            }
         );
         Ok(AnnouncePeerRequest {
             token: s.token,
@@ -132,3 +150,11 @@ impl fmt::Display for AnnouncePeerRequest {
         write!(f, "{}", json)
     }
 }
+
+fn is_default_weight(weight: &u8) -> bool {
+    *weight == PeerInfo::DEFAULT_WEIGHT
+}
+
+fn default_weight() -> u8 {
+    PeerInfo::DEFAULT_WEIGHT
+}
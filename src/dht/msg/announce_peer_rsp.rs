@@ -0,0 +1,29 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// The endpoint the responder saw an unroutable peer announced from, only
+// sent when the announcing node is the origin of the peer. Older nodes
+// respond to an announcement without a body.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct AnnouncePeerResponse {
+    #[serde(rename = "o", skip_serializing_if = "crate::is_default", default)]
+    observed: Option<String>,
+}
+
+impl AnnouncePeerResponse {
+    pub(crate) fn new(observed: Option<String>) -> Self {
+        Self { observed }
+    }
+
+    pub(crate) fn observed(&self) -> Option<&str> {
+        self.observed.as_deref()
+    }
+}
+
+impl fmt::Display for AnnouncePeerResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let json = serde_json::to_value(self)
+            .map_err(|_| fmt::Error)?;
+        write!(f, "{}", json)
+    }
+}
@@ -1,6 +1,11 @@
 use std::fmt;
 use serde::{Deserialize, Serialize};
 
+// Error codes shared with the BEP-5 KRPC protocol.
+pub(crate) const GENERIC_ERROR  : i32 = 201;
+pub(crate) const PROTOCOL_ERROR : i32 = 203;
+pub(crate) const METHOD_UNKNOWN : i32 = 204;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct Error {
     #[serde(rename = "c")]
@@ -0,0 +1,30 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+
+// Opaque payload of a vendor extension request or response, only the
+// cooperating nodes registering an extension handler understand it.
+#[serde_as]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Extension {
+    #[serde(rename = "d")]
+    #[serde_as(as = "Bytes")]
+    data: Vec<u8>,
+}
+
+impl Extension {
+    pub(crate) fn new(data: Vec<u8>) -> Self {
+        Self { data }
+    }
+
+    pub(crate) fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl fmt::Display for Extension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\"d\":\"{} bytes\"}}", self.data.len())
+    }
+}
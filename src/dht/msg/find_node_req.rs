@@ -6,7 +6,7 @@ use crate::{
     dht::msg::lookup_req::{
         LookupRequest,
         Data as LookupData,
-        WANT4_MASK, WANT6_MASK, WANT_TOKEN_MASK,
+        WANT4_MASK, WANT6_MASK, WANT_TOKEN_MASK, WANT_TIME_MASK, TRANSIENT_MASK,
     }
 };
 
@@ -27,6 +27,16 @@ impl FindNodeRequest {
             data: LookupData::new(target, want4, want6, want_token)
         }
     }
+
+    pub(crate) fn with_want_time(mut self, want_time: bool) -> Self {
+        self.data.set_want_time(want_time);
+        self
+    }
+
+    pub(crate) fn with_transient(mut self, transient: bool) -> Self {
+        self.data.set_transient(transient);
+        self
+    }
 }
 
 impl LookupRequest for FindNodeRequest {
@@ -64,7 +74,8 @@ impl From<SerdeFindNodeRequest> for FindNodeRequest {
             s.want & WANT4_MASK != 0,
             s.want & WANT6_MASK != 0,
             s.want & WANT_TOKEN_MASK != 0
-        )
+        ).with_want_time(s.want & WANT_TIME_MASK != 0)
+         .with_transient(s.want & TRANSIENT_MASK != 0)
     }
 }
 
@@ -31,6 +31,10 @@ impl LookupResponse for FindNodeResponse {
     fn data(&self) -> &LookupData {
         &self.data
     }
+
+    fn data_mut(&mut self) -> &mut LookupData {
+        &mut self.data
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,6 +46,8 @@ struct SerdeFindNodeResponse {
     nodes6: Option<Vec<NodeInfo>>,
     #[serde(rename = "tok")]
     token: i32,
+    #[serde(rename = "now", skip_serializing_if = "crate::is_default", default)]
+    time: Option<u64>,
 }
 
 impl Into<SerdeFindNodeResponse> for FindNodeResponse {
@@ -50,17 +56,20 @@ impl Into<SerdeFindNodeResponse> for FindNodeResponse {
             nodes4: self.nodes4().map(|v| v.to_vec()),
             nodes6: self.nodes6().map(|v| v.to_vec()),
             token: self.token(),
+            time: self.time(),
         }
     }
 }
 
 impl From<SerdeFindNodeResponse> for FindNodeResponse {
     fn from(s: SerdeFindNodeResponse) -> Self {
-        Self::new(
+        let mut rsp = Self::new(
             s.nodes4,
             s.nodes6,
             s.token
-        )
+        );
+        rsp.set_time(s.time);
+        rsp
     }
 }
 
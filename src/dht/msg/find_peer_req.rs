@@ -2,14 +2,16 @@ use std::fmt;
 use serde::{Deserialize, Serialize};
 use crate::{
     Id,
-    errors::{Error, Result},
+    Network,
+    PeerInfo,
+    errors::{Error, Result, ProtocolError},
 };
 use crate::dht::{
     msg::utils,
     msg::lookup_req::{
         LookupRequest,
         Data as LookupData,
-        WANT4_MASK, WANT6_MASK,
+        WANT4_MASK, WANT6_MASK, WANT_AGE_MASK, WANT_TIME_MASK, TRANSIENT_MASK,
     },
 };
 
@@ -20,6 +22,8 @@ pub(crate) struct FindPeerRequest {
     data: LookupData,
     expected_seq: i32,
     expected_count: i32,
+    tags: Vec<String>,
+    network: Option<Network>,
 }
 
 impl FindPeerRequest {
@@ -32,9 +36,48 @@ impl FindPeerRequest {
             data: LookupData::new(target, want4, want6, false),
             expected_seq,
             expected_count,
+            tags: Vec::new(),
+            network: None,
         }
     }
 
+    // Only peers with all the tags are wanted, to be sent to responders
+    // that understand it.
+    pub(crate) fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub(crate) fn tags(&self) -> &[String] {
+        self.tags.as_slice()
+    }
+
+    // Only peers with an endpoint of the network are wanted, to be sent to
+    // responders that understand it.
+    pub(crate) fn with_network(mut self, network: Option<Network>) -> Self {
+        self.network = network;
+        self
+    }
+
+    pub(crate) fn network(&self) -> Option<Network> {
+        self.network
+    }
+
+    pub(crate) fn with_want_age(mut self, want_age: bool) -> Self {
+        self.data.set_want_age(want_age);
+        self
+    }
+
+    pub(crate) fn with_want_time(mut self, want_time: bool) -> Self {
+        self.data.set_want_time(want_time);
+        self
+    }
+
+    pub(crate) fn with_transient(mut self, transient: bool) -> Self {
+        self.data.set_transient(transient);
+        self
+    }
+
     pub(crate) fn expected_seq(&self) -> i32 {
         self.expected_seq
     }
@@ -78,6 +121,12 @@ struct SerdeFindPeerRequest {
         deserialize_with = "utils::deserialize_count"
     )]
     expected_count: i32,
+
+    #[serde(rename = "tg", skip_serializing_if = "crate::is_default", default)]
+    tags: Vec<String>,
+
+    #[serde(rename = "nf", skip_serializing_if = "Option::is_none", default)]
+    network: Option<u8>,
 }
 
 impl Into<SerdeFindPeerRequest> for FindPeerRequest {
@@ -87,6 +136,8 @@ impl Into<SerdeFindPeerRequest> for FindPeerRequest {
             want: self.want(),
             expected_seq: self.expected_seq,
             expected_count: self.expected_count,
+            tags: self.tags,
+            network: self.network.map(|v| v as u8),
         }
     }
 }
@@ -94,13 +145,26 @@ impl Into<SerdeFindPeerRequest> for FindPeerRequest {
 impl TryFrom<SerdeFindPeerRequest> for FindPeerRequest {
     type Error = Error;
     fn try_from(s: SerdeFindPeerRequest) -> Result<Self> {
+        if s.tags.len() > PeerInfo::MAX_TAGS {
+            return Err(ProtocolError::new(format!("at most {} tags in \"tg\"", PeerInfo::MAX_TAGS)));
+        }
+        let network = match s.network {
+            None => None,
+            Some(4) => Some(Network::IPv4),
+            Some(6) => Some(Network::IPv6),
+            Some(v) => return Err(ProtocolError::new(format!("invalid network {v} in \"nf\""))),
+        };
         Ok(FindPeerRequest::new(
             s.target,
             s.want & WANT4_MASK != 0,
             s.want & WANT6_MASK != 0,
             s.expected_seq,
             s.expected_count
-        ))
+        ).with_want_age(s.want & WANT_AGE_MASK != 0)
+         .with_want_time(s.want & WANT_TIME_MASK != 0)
+         .with_transient(s.want & TRANSIENT_MASK != 0)
+         .with_tags(s.tags)
+         .with_network(network))
     }
 }
 
@@ -16,6 +16,7 @@ use crate::{
 pub(crate) struct FindPeerResponse {
     data: Data,
     peers: Option<Vec<PeerInfo>>,
+    ages: Option<Vec<u64>>,
 }
 
 impl FindPeerResponse {
@@ -26,6 +27,7 @@ impl FindPeerResponse {
         Self {
             data: Data::new(nodes4, nodes6, 0),
             peers: None,
+            ages: None,
         }
     }
 
@@ -33,18 +35,48 @@ impl FindPeerResponse {
         Self {
             data: Data::new(None, None, 0),
             peers: Some(peers),
+            ages: None,
         }
     }
 
+    // Seconds since the responder stored or refreshed each of the peers,
+    // in the same order, only sent when asked for.
+    pub(crate) fn with_ages(mut self, ages: Option<Vec<u64>>) -> Self {
+        self.ages = ages;
+        self
+    }
+
     pub(crate) fn peers(&self) -> Option<&[PeerInfo]> {
         self.peers.as_deref()
     }
+
+    pub(crate) fn ages(&self) -> Option<&[u64]> {
+        self.ages.as_deref()
+    }
+
+    pub(crate) fn retain_peers(&mut self, mut f: impl FnMut(&PeerInfo) -> bool) {
+        let Some(peers) = self.peers.as_mut() else {
+            return;
+        };
+        let keep: Vec<bool> = peers.iter().map(&mut f).collect();
+        let mut iter = keep.iter();
+        peers.retain(|_| *iter.next().unwrap());
+
+        if let Some(ages) = self.ages.as_mut() {
+            let mut iter = keep.iter();
+            ages.retain(|_| *iter.next().unwrap());
+        }
+    }
 }
 
 impl LookupResponse for FindPeerResponse {
     fn data(&self) -> &Data {
         &self.data
     }
+
+    fn data_mut(&mut self) -> &mut Data {
+        &mut self.data
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -58,6 +90,10 @@ struct SerdeFindPeerResponse {
     token: i32,
     #[serde(rename = "p", skip_serializing_if = "crate::is_default")]
     peers: Option<Vec<PeerInfo>>,
+    #[serde(rename = "age", skip_serializing_if = "crate::is_default", default)]
+    ages: Option<Vec<u64>>,
+    #[serde(rename = "now", skip_serializing_if = "crate::is_default", default)]
+    time: Option<u64>,
 }
 
 impl Into<SerdeFindPeerResponse> for FindPeerResponse {
@@ -67,6 +103,8 @@ impl Into<SerdeFindPeerResponse> for FindPeerResponse {
             nodes6: self.nodes6().map(|v| v.to_vec()),
             token: self.token(),
             peers: self.peers().map(|v| v.to_vec()),
+            time: self.time(),
+            ages: self.ages,
         }
     }
 }
@@ -88,10 +126,16 @@ impl TryFrom<SerdeFindPeerResponse> for FindPeerResponse {
             return Err(ProtocolError::new("\"p\" cannot be combined with \"n4\" or \"n6\""));
         }
 
-        Ok(match s.peers {
-            Some(peers) => FindPeerResponse::with_peers(peers),
+        if s.ages.as_ref().is_some_and(|v| Some(v.len()) != s.peers.as_ref().map(|p| p.len())) {
+            return Err(ProtocolError::new("\"age\" must have an entry per peer in \"p\""));
+        }
+
+        let mut rsp = match s.peers {
+            Some(peers) => FindPeerResponse::with_peers(peers).with_ages(s.ages),
             _ => FindPeerResponse::with_nodes(s.nodes4, s.nodes6)
-        })
+        };
+        rsp.set_time(s.time);
+        Ok(rsp)
     }
 }
 
@@ -9,7 +9,7 @@ use super::{
     lookup_req::{
         LookupRequest,
         Data as LookupData,
-        WANT4_MASK, WANT6_MASK,
+        WANT4_MASK, WANT6_MASK, WANT_AGE_MASK, WANT_TIME_MASK, TRANSIENT_MASK,
     }
 };
 
@@ -33,6 +33,21 @@ impl FindValueRequest {
         }
     }
 
+    pub(crate) fn with_want_age(mut self, want_age: bool) -> Self {
+        self.data.set_want_age(want_age);
+        self
+    }
+
+    pub(crate) fn with_want_time(mut self, want_time: bool) -> Self {
+        self.data.set_want_time(want_time);
+        self
+    }
+
+    pub(crate) fn with_transient(mut self, transient: bool) -> Self {
+        self.data.set_transient(transient);
+        self
+    }
+
     pub(crate) fn expected_seq(&self) -> i32 {
         self.expected_seq
     }
@@ -82,7 +97,9 @@ impl TryFrom<SerdeFindValueRequest> for FindValueRequest {
             s.want & WANT4_MASK != 0,
             s.want & WANT6_MASK != 0,
             s.expected_seq,
-        ))
+        ).with_want_age(s.want & WANT_AGE_MASK != 0)
+         .with_want_time(s.want & WANT_TIME_MASK != 0)
+         .with_transient(s.want & TRANSIENT_MASK != 0))
     }
 }
 
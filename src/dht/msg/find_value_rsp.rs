@@ -21,6 +21,7 @@ use super::{
 pub(crate) struct FindValueResponse {
     data: Data,
     value: Option<Value>,
+    age: Option<u64>,
 }
 
 impl FindValueResponse {
@@ -31,6 +32,7 @@ impl FindValueResponse {
         Self {
             data: Data::new(nodes4, nodes6, 0),
             value: None,
+            age: None,
         }
     }
 
@@ -38,18 +40,34 @@ impl FindValueResponse {
         Self {
             data: Data::new(None, None, 0),
             value: Some(value),
+            age: None,
         }
     }
 
+    // Seconds since the responder stored or refreshed the value, only
+    // sent when asked for.
+    pub(crate) fn with_age(mut self, age: Option<u64>) -> Self {
+        self.age = age;
+        self
+    }
+
     pub(crate) fn value(&self) -> Option<&Value> {
         self.value.as_ref()
     }
+
+    pub(crate) fn age(&self) -> Option<u64> {
+        self.age
+    }
 }
 
 impl LookupResponse for FindValueResponse {
     fn data(&self) -> &Data {
         &self.data
     }
+
+    fn data_mut(&mut self) -> &mut Data {
+        &mut self.data
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -78,6 +96,14 @@ struct SerdeFindValueResponse {
     sig: Option<Vec<u8>>,
     #[serde(rename = "v", skip_serializing_if = "crate::is_default")]
     value: Option<Vec<u8>>,
+    #[serde(rename = "age", skip_serializing_if = "crate::is_default", default)]
+    age: Option<u64>,
+    #[serde(rename = "csk", skip_serializing_if = "crate::is_default", default)]
+    countersigner: Option<Id>,
+    #[serde(rename = "cs", skip_serializing_if = "crate::is_default", default)]
+    countersig: Option<Vec<u8>>,
+    #[serde(rename = "now", skip_serializing_if = "crate::is_default", default)]
+    time: Option<u64>,
 }
 
 impl Into<SerdeFindValueResponse> for FindValueResponse {
@@ -92,6 +118,10 @@ impl Into<SerdeFindValueResponse> for FindValueResponse {
             expected_seq: self.value.as_ref().map(|v| v.sequence_number()).unwrap_or(-1),
             sig     : self.value.as_ref().and_then(|v| v.signature().map(|s| s.to_vec())),
             value   : self.value.as_ref().map(|v| v.data().to_vec()),
+            age     : self.age,
+            countersigner: self.value.as_ref().and_then(|v| v.countersigner().cloned()),
+            countersig  : self.value.as_ref().and_then(|v| v.countersignature().map(|s| s.to_vec())),
+            time    : self.time(),
         }
     }
 }
@@ -125,14 +155,19 @@ impl TryFrom<SerdeFindValueResponse> for FindValueResponse {
                     .map_err(|_| ProtocolError::new("invalid nonce length"))
             }).transpose()?;
 
-            let value = Value::packed(s.pk, s.rec, nonce, s.sig, data, expected_seq);
+            let value = Value::packed(s.pk, s.rec, nonce, s.sig, data, expected_seq)
+                .with_countersignature(s.countersigner, s.countersig);
             if !value.is_valid() {
                 return Err(ProtocolError::new("invalid value"));
             }
 
-            Ok(Self::with_value(value))
+            let mut rsp = Self::with_value(value).with_age(s.age);
+            rsp.set_time(s.time);
+            Ok(rsp)
         } else {
-            Ok(Self::with_nodes(s.nodes4, s.nodes6))
+            let mut rsp = Self::with_nodes(s.nodes4, s.nodes6);
+            rsp.set_time(s.time);
+            Ok(rsp)
         }
     }
 }
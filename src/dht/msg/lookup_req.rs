@@ -3,6 +3,14 @@ use crate::Id;
 pub(crate) const WANT4_MASK: i32 = 0x01;
 pub(crate) const WANT6_MASK: i32 = 0x02;
 pub(crate) const WANT_TOKEN_MASK: i32 = 0x04;
+// Asks for the age of the returned records, ignored by older nodes.
+pub(crate) const WANT_AGE_MASK: i32 = 0x08;
+// Asks for the current time of the responder, to tell how far the local
+// clock is off. Ignored by older nodes as well.
+pub(crate) const WANT_TIME_MASK: i32 = 0x10;
+// Sent from a throwaway id in privacy mode, the requester is not a node to
+// route to. Older nodes take it for one all the same.
+pub(crate) const TRANSIENT_MASK: i32 = 0x20;
 
 #[derive(Clone)]
 pub(crate) struct Data {
@@ -10,6 +18,9 @@ pub(crate) struct Data {
     want4   : bool,
     want6   : bool,
     want_token: bool,
+    want_age: bool,
+    want_time: bool,
+    transient: bool,
 }
 
 impl Data {
@@ -19,7 +30,19 @@ impl Data {
         want6: bool,
         want_token: bool
     ) -> Self {
-        Self {target, want4, want6, want_token}
+        Self {target, want4, want6, want_token, want_age: false, want_time: false, transient: false}
+    }
+
+    pub(crate) fn set_want_age(&mut self, want_age: bool) {
+        self.want_age = want_age;
+    }
+
+    pub(crate) fn set_want_time(&mut self, want_time: bool) {
+        self.want_time = want_time;
+    }
+
+    pub(crate) fn set_transient(&mut self, transient: bool) {
+        self.transient = transient;
     }
 }
 
@@ -42,9 +65,24 @@ pub(crate) trait LookupRequest {
         self.data().want_token
     }
 
+    fn want_age(&self) -> bool {
+        self.data().want_age
+    }
+
+    fn want_time(&self) -> bool {
+        self.data().want_time
+    }
+
+    fn is_transient(&self) -> bool {
+        self.data().transient
+    }
+
     fn want(&self) -> i32 {
         (if self.want4() { 0x01 } else { 0x00 }) |
         (if self.want6() { 0x02 } else { 0x00 }) |
-        (if self.want_token() { 0x04 } else { 0x00 })
+        (if self.want_token() { 0x04 } else { 0x00 }) |
+        (if self.want_age() { 0x08 } else { 0x00 }) |
+        (if self.want_time() { 0x10 } else { 0x00 }) |
+        (if self.is_transient() { 0x20 } else { 0x00 })
     }
 }
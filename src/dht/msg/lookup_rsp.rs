@@ -5,6 +5,8 @@ pub(crate) struct Data {
     pub(crate) nodes4  : Option<Vec<NodeInfo>>,
     pub(crate) nodes6  : Option<Vec<NodeInfo>>,
     pub(crate) token   : i32,
+    // Seconds since the epoch on the responder, only sent when asked for.
+    pub(crate) time    : Option<u64>,
 }
 
 impl Data {
@@ -13,12 +15,13 @@ impl Data {
         nodes6: Option<Vec<NodeInfo>>,
         token: i32
     ) -> Self {
-        Self { nodes4, nodes6, token }
+        Self { nodes4, nodes6, token, time: None }
     }
 }
 
 pub(crate) trait LookupResponse {
     fn data(&self) -> &Data;
+    fn data_mut(&mut self) -> &mut Data;
 
     fn nodes4(&self) -> Option<&[NodeInfo]> {
         self.data().nodes4.as_deref()
@@ -38,4 +41,12 @@ pub(crate) trait LookupResponse {
     fn token(&self) -> i32 {
         self.data().token
     }
+
+    fn time(&self) -> Option<u64> {
+        self.data().time
+    }
+
+    fn set_time(&mut self, time: Option<u64>) {
+        self.data_mut().time = time;
+    }
 }
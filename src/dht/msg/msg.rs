@@ -11,8 +11,10 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     Id,
+    Network,
     Value,
     NodeInfo,
+    Capabilities,
     PeerInfo,
     errors::{Error, Result, ProtocolError},
     core::version,
@@ -27,7 +29,12 @@ use crate::{
         FindValueRequest,
         FindValueResponse,
         AnnouncePeerRequest,
+        AnnouncePeerResponse,
         StoreValueRequest,
+        Extension,
+        Rendezvous,
+        lookup_req::LookupRequest,
+        lookup_rsp::LookupResponse,
     },
 };
 
@@ -79,12 +86,16 @@ pub(crate) enum Method {
     FindPeer    = 0x04,
     StoreValue  = 0x05,
     FindValue   = 0x06,
+    Rendezvous  = 0x07,
+    // Reserved for vendor extension messages, never used by the DHT itself.
+    Extension   = 0x1F,
 }
 
 impl Method {
     const MASK: i32 = 0x1F;
     pub(crate) fn is_valid(_type: i32) -> bool {
-        (_type & Self::MASK) <= 0x06
+        let method = _type & Self::MASK;
+        method <= 0x07 || method == Method::Extension as i32
     }
 }
 
@@ -99,6 +110,8 @@ impl From<i32> for Method {
             0x04 => Method::FindPeer,
             0x05 => Method::StoreValue,
             0x06 => Method::FindValue,
+            0x07 => Method::Rendezvous,
+            0x1F => Method::Extension,
             _ => panic!("invalid msg method: {}", method)
         }
     }
@@ -114,6 +127,8 @@ impl fmt::Display for Method {
             Method::FindPeer => "find_peer",
             Method::StoreValue => "store_value",
             Method::FindValue => "find_value",
+            Method::Rendezvous => "rendezvous",
+            Method::Extension => "extension",
         })
     }
 }
@@ -128,7 +143,12 @@ pub(crate) enum Body {
     FindValueRequest(FindValueRequest),
     FindValueResponse(FindValueResponse),
     AnnouncePeerRequest(AnnouncePeerRequest),
+    AnnouncePeerResponse(AnnouncePeerResponse),
     StoreValueRequest(StoreValueRequest),
+    ExtensionRequest(Extension),
+    ExtensionResponse(Extension),
+    RendezvousRequest(Rendezvous),
+    RendezvousResponse(Rendezvous),
     Error(ErrorBody),
 }
 
@@ -165,6 +185,14 @@ impl Body {
                 .map(Body::FindValueRequest)
                 .map(Some)
                 .map_err(err_cb)?,
+            Method::Extension => from_value::<Extension>(value)
+                .map(Body::ExtensionRequest)
+                .map(Some)
+                .map_err(err_cb)?,
+            Method::Rendezvous => from_value::<Rendezvous>(value)
+                .map(Body::RendezvousRequest)
+                .map(Some)
+                .map_err(err_cb)?,
             Method::Unknown => return Err(ProtocolError::new("invalid unknown request".to_string())),
         })
     }
@@ -172,7 +200,11 @@ impl Body {
     fn from_rsp(method: Method, value: CborValue) -> Result<Option<Self>> {
         let err_cb = |e| ProtocolError::new(format!("Decoding {} response error: {}", method, e));
         Ok(match method {
-            Method::Ping | Method::AnnouncePeer | Method::StoreValue => None,
+            Method::Ping | Method::StoreValue => None,
+            Method::AnnouncePeer => from_value::<AnnouncePeerResponse>(value)
+                .map(Body::AnnouncePeerResponse)
+                .map(Some)
+                .map_err(err_cb)?,
             Method::FindNode => from_value::<FindNodeResponse>(value)
                 .map(Body::FindNodeResponse)
                 .map(Some)
@@ -185,6 +217,14 @@ impl Body {
                 .map(Body::FindValueResponse)
                 .map(Some)
                 .map_err(err_cb)?,
+            Method::Extension => from_value::<Extension>(value)
+                .map(Body::ExtensionResponse)
+                .map(Some)
+                .map_err(err_cb)?,
+            Method::Rendezvous => from_value::<Rendezvous>(value)
+                .map(Body::RendezvousResponse)
+                .map(Some)
+                .map_err(err_cb)?,
             Method::Unknown => return Err(ProtocolError::new("invalid unknown response".to_string())),
         })
     }
@@ -200,7 +240,12 @@ impl fmt::Display for Body {
             Body::FindValueRequest(body)  => write!(f, "{}", body),
             Body::FindValueResponse(body) => write!(f, "{}", body),
             Body::AnnouncePeerRequest(body) => write!(f, "{}", body),
+            Body::AnnouncePeerResponse(body) => write!(f, "{}", body),
             Body::StoreValueRequest(body) => write!(f, "{}", body),
+            Body::ExtensionRequest(body)  => write!(f, "{}", body),
+            Body::ExtensionResponse(body) => write!(f, "{}", body),
+            Body::RendezvousRequest(body) => write!(f, "{}", body),
+            Body::RendezvousResponse(body)=> write!(f, "{}", body),
             Body::Error(body)             => write!(f, "{}", body),
         }
     }
@@ -226,6 +271,7 @@ pub(crate) struct Message {
     method  : Method,
     txid    : i32,
     ver     : i32,
+    caps    : Option<Capabilities>,   // What the sender offers, only to nodes decoding it.
 
     body    : Option<Body>,
 
@@ -244,6 +290,7 @@ impl Message {
             method,
             txid,
             ver: version::ver(),
+            caps: None,
             body,
             associated_call: None,
             remote_addr: None,
@@ -293,13 +340,68 @@ impl Message {
         self.body.as_ref()
     }
 
+    pub(crate) fn body_mut(&mut self) -> Option<&mut Body> {
+        self.body.as_mut()
+    }
+
     pub(crate) fn ver(&self) -> i32 {
         self.ver
     }
 
+    pub(crate) fn capabilities(&self) -> Option<Capabilities> {
+        self.caps
+    }
+
+    pub(crate) fn set_capabilities(&mut self, caps: Capabilities) {
+        self.caps = Some(caps);
+    }
+
     #[allow(unused)]
     pub(crate) fn readable_version(&self) -> String {
-        version::format_version(self.ver)
+        version::normalized_version(self.ver)
+    }
+
+    // The time of the responder in a lookup response, in seconds since the
+    // epoch, only there when asked for.
+    pub(crate) fn time(&self) -> Option<u64> {
+        match self.body.as_ref()? {
+            Body::FindNodeResponse(body)  => body.time(),
+            Body::FindPeerResponse(body)  => body.time(),
+            Body::FindValueResponse(body) => body.time(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn set_time(&mut self, time: u64) {
+        match self.body.as_mut() {
+            Some(Body::FindNodeResponse(body))  => body.set_time(Some(time)),
+            Some(Body::FindPeerResponse(body))  => body.set_time(Some(time)),
+            Some(Body::FindValueResponse(body)) => body.set_time(Some(time)),
+            _ => {},
+        }
+    }
+
+    // A lookup request sent from a session id in privacy mode.
+    pub(crate) fn is_transient(&self) -> bool {
+        match self.body.as_ref() {
+            Some(Body::FindNodeRequest(body))  => body.is_transient(),
+            Some(Body::FindPeerRequest(body))  => body.is_transient(),
+            Some(Body::FindValueRequest(body)) => body.is_transient(),
+            _ => false,
+        }
+    }
+
+    pub(crate) fn set_transient(&mut self) {
+        let body = match self.body.take() {
+            Some(Body::FindNodeRequest(body))  => Body::FindNodeRequest(body.with_transient(true)),
+            Some(Body::FindPeerRequest(body))  => Body::FindPeerRequest(body.with_transient(true)),
+            Some(Body::FindValueRequest(body)) => Body::FindValueRequest(body.with_transient(true)),
+            other => {
+                self.body = other;
+                return;
+            }
+        };
+        self.body = Some(body);
     }
 
     pub(crate) fn associated_call(&self) -> Option<Rc<RefCell<RpcCall>>> {
@@ -335,6 +437,10 @@ struct SerdeCborMessage {
     #[serde(rename = "v")]
     ver: i32,
 
+    #[serde(rename = "c")]
+    #[serde(skip_serializing_if = "crate::is_default", default)]
+    caps: Option<Capabilities>,
+
     #[serde(rename = "q")]
     #[serde(skip_serializing_if = "crate::is_default")]
     req: Option<CborValue>,
@@ -356,6 +462,8 @@ struct SerdeJsonMessage<'a> {
     txid: i32,
     #[serde(rename = "v", serialize_with = "utils::serialize_ver")]
     ver: i32,
+    #[serde(rename = "c", skip_serializing_if = "crate::is_default")]
+    caps: Option<Capabilities>,
 
     #[serde(rename = "q", skip_serializing_if = "crate::is_default")]
     req: Option<&'a Body>,
@@ -380,6 +488,7 @@ impl Serialize for Message {
             type_: self.composite_type(),
             txid: self.txid,
             ver: self.ver,
+            caps: self.caps,
             req: (self.kind == Kind::Request).then_some(body).flatten(),
             rsp: (self.kind == Kind::Response).then_some(body).flatten(),
             err: (self.kind == Kind::Error).then_some(body).flatten(),
@@ -394,6 +503,7 @@ impl TryFrom<&Message> for SerdeCborMessage {
         let type_ = msg.composite_type();
         let txid = msg.txid;
         let ver  = msg.ver;
+        let caps = msg.caps;
         let body = msg.body();
 
         let req = if msg.kind() == Kind::Request {
@@ -412,7 +522,7 @@ impl TryFrom<&Message> for SerdeCborMessage {
             None
         };
 
-        Ok(Self { type_, txid, ver, req, rsp, err })
+        Ok(Self { type_, txid, ver, caps, req, rsp, err })
     }
 }
 
@@ -456,6 +566,7 @@ impl TryFrom<SerdeCborMessage> for Message {
 
         let mut msg = Message::new(kind, method, s.txid, body);
         msg.ver = s.ver;
+        msg.caps = s.caps;
         Ok(msg)
     }
 }
@@ -477,6 +588,7 @@ pub(crate) fn ping_response(txid: i32) -> Message {
 pub(crate) fn find_node_request(target: Id, want4: bool, want6: bool, want_token: Option<bool>) -> Message {
     let body = Body::FindNodeRequest(
         FindNodeRequest::new(target, want4, want6, want_token.unwrap_or(false))
+            .with_want_time(true)
     );
     Message::new(Kind::Request, Method::FindNode, next_txid(), Some(body))
 }
@@ -488,9 +600,15 @@ pub(crate) fn find_node_response(txid: i32, nodes4: Option<Vec<NodeInfo>>, nodes
     Message::new(Kind::Response, Method::FindNode, txid, Some(body))
 }
 
-pub(crate) fn find_peer_request(target: Id, want4: bool, want6: bool, expected_seq: i32, expected_count: i32) -> Message {
+// Lookups always ask for the age of the records found, and all of them for
+// the time of the responders.
+pub(crate) fn find_peer_request(target: Id, want4: bool, want6: bool, expected_seq: i32, expected_count: i32, tags: Vec<String>, network: Option<Network>) -> Message {
     let body = Body::FindPeerRequest(
         FindPeerRequest::new(target, want4, want6, expected_seq, expected_count)
+            .with_want_age(true)
+            .with_want_time(true)
+            .with_tags(tags)
+            .with_network(network)
     );
     Message::new(Kind::Request, Method::FindPeer, next_txid(), Some(body))
 }
@@ -502,9 +620,9 @@ pub(crate) fn find_peer_response_with_nodes(txid: i32, nodes4: Option<Vec<NodeIn
     Message::new(Kind::Response, Method::FindPeer, txid, Some(body))
 }
 
-pub(crate) fn find_peer_response(txid: i32, peers: Vec<PeerInfo>) -> Message {
+pub(crate) fn find_peer_response(txid: i32, peers: Vec<PeerInfo>, ages: Option<Vec<u64>>) -> Message {
     let body = Body::FindPeerResponse(
-        FindPeerResponse::with_peers(peers)
+        FindPeerResponse::with_peers(peers).with_ages(ages)
     );
     Message::new(Kind::Response, Method::FindPeer, txid, Some(body))
 }
@@ -512,6 +630,8 @@ pub(crate) fn find_peer_response(txid: i32, peers: Vec<PeerInfo>) -> Message {
 pub(crate) fn find_value_request(target: Id, want4: bool, want6: bool, expected_seq: i32) -> Message {
     let body = Body::FindValueRequest(
         FindValueRequest::new(target, want4, want6, expected_seq)
+            .with_want_age(true)
+            .with_want_time(true)
     );
     Message::new(Kind::Request, Method::FindValue, next_txid(),Some(body))
 }
@@ -523,9 +643,9 @@ pub(crate) fn find_value_response_with_nodes(txid: i32, nodes4: Option<Vec<NodeI
     Message::new(Kind::Response, Method::FindValue, txid, Some(body))
 }
 
-pub(crate) fn find_value_response(txid: i32, value: Value) -> Message {
+pub(crate) fn find_value_response(txid: i32, value: Value, age: Option<u64>) -> Message {
     let body = Body::FindValueResponse(
-        FindValueResponse::with_value(value)
+        FindValueResponse::with_value(value).with_age(age)
     );
     Message::new(Kind::Response, Method::FindValue, txid, Some(body))
 }
@@ -548,8 +668,31 @@ pub(crate) fn announce_peer_request(peer: PeerInfo, token: i32, expected_seq: i3
     Message::new(Kind::Request, Method::AnnouncePeer, next_txid(), Some(body))
 }
 
-pub(crate) fn announce_peer_response(txid: i32) -> Message {
-    Message::new(Kind::Response, Method::AnnouncePeer, txid, None)
+pub(crate) fn announce_peer_response(txid: i32, observed: Option<String>) -> Message {
+    let body = observed.map(|observed| Body::AnnouncePeerResponse(
+        AnnouncePeerResponse::new(Some(observed))
+    ));
+    Message::new(Kind::Response, Method::AnnouncePeer, txid, body)
+}
+
+pub(crate) fn extension_request(data: Vec<u8>) -> Message {
+    let body = Body::ExtensionRequest(Extension::new(data));
+    Message::new(Kind::Request, Method::Extension, next_txid(), Some(body))
+}
+
+pub(crate) fn extension_response(txid: i32, data: Vec<u8>) -> Message {
+    let body = Body::ExtensionResponse(Extension::new(data));
+    Message::new(Kind::Response, Method::Extension, txid, Some(body))
+}
+
+pub(crate) fn rendezvous_request(rendezvous: Rendezvous) -> Message {
+    let body = Body::RendezvousRequest(rendezvous);
+    Message::new(Kind::Request, Method::Rendezvous, next_txid(), Some(body))
+}
+
+pub(crate) fn rendezvous_response(txid: i32, rendezvous: Rendezvous) -> Message {
+    let body = Body::RendezvousResponse(rendezvous);
+    Message::new(Kind::Response, Method::Rendezvous, txid, Some(body))
 }
 
 pub(crate) fn error_msg(method: Method, txid: i32, code: i32, description: String) -> Message {
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::net::IpAddr;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+
+// Where a node waits for the hole punching probes of a direct connection
+// attempt: its probe port and the local addresses it can be reached at.
+// The session ties the probes to the attempt, it only travels in the
+// encrypted rendezvous request and response.
+#[serde_as]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Rendezvous {
+    #[serde(rename = "s")]
+    session: u64,
+    #[serde(rename = "p")]
+    port: u16,
+    #[serde(rename = "h")]
+    #[serde_as(as = "Vec<Bytes>")]
+    hosts: Vec<Vec<u8>>,
+}
+
+impl Rendezvous {
+    pub(crate) fn new(session: u64, port: u16, hosts: &[IpAddr]) -> Self {
+        Self {
+            session,
+            port,
+            hosts: hosts.iter().map(|ip| match ip {
+                IpAddr::V4(v4) => v4.octets().to_vec(),
+                IpAddr::V6(v6) => v6.octets().to_vec(),
+            }).collect(),
+        }
+    }
+
+    pub(crate) fn session(&self) -> u64 {
+        self.session
+    }
+
+    pub(crate) fn port(&self) -> u16 {
+        self.port
+    }
+
+    // Addresses of unknown length are skipped.
+    pub(crate) fn hosts(&self) -> Vec<IpAddr> {
+        self.hosts.iter().filter_map(|v| {
+            if let Ok(v4) = <[u8; 4]>::try_from(v.as_slice()) {
+                Some(IpAddr::from(v4))
+            } else if let Ok(v6) = <[u8; 16]>::try_from(v.as_slice()) {
+                Some(IpAddr::from(v6))
+            } else {
+                None
+            }
+        }).collect()
+    }
+}
+
+impl fmt::Display for Rendezvous {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\"p\":{},\"h\":{:?}}}", self.port, self.hosts())
+    }
+}
@@ -61,6 +61,10 @@ struct SerdeStoreValueRequest {
     signature: Option<Vec<u8>>,
     #[serde(rename = "v")]
     data: Vec<u8>,
+    #[serde(rename = "csk", skip_serializing_if = "crate::is_default", default)]
+    countersigner: Option<Id>,
+    #[serde(rename = "cs", skip_serializing_if = "crate::is_default", default)]
+    countersig: Option<Vec<u8>>,
 }
 
 impl Into<SerdeStoreValueRequest> for StoreValueRequest {
@@ -75,6 +79,8 @@ impl Into<SerdeStoreValueRequest> for StoreValueRequest {
             nonce       : value.nonce().map(|n| n.as_bytes().to_vec()),
             signature   : value.signature().map(|v| v.to_vec()),
             data        : value.data().to_vec(),
+            countersigner: value.countersigner().cloned(),
+            countersig  : value.countersignature().map(|v| v.to_vec()),
         }
     }
 }
@@ -99,7 +105,7 @@ impl TryFrom<SerdeStoreValueRequest> for StoreValueRequest {
             s.signature,
             s.data,
             s.seq
-        );
+        ).with_countersignature(s.countersigner, s.countersig);
         // if !value.is_valid() {
         //     return Err(ProtocolError::new("The value is invalid"));
         // }
@@ -1,6 +1,7 @@
 use crate::{
     Id,
     PeerInfo,
+    core::peer_info::PeerFields,
     dht::msg::announce_peer_req::AnnouncePeerRequest,
 };
 
@@ -14,7 +15,12 @@ fn make_peer() -> PeerInfo {
         vec![9; 64],
         123456,
         "127.0.0.1:39001".to_string(),
-        Some(vec![1, 2, 3]),
+        PeerFields {
+            extra: Some(vec![1, 2, 3]),
+            tags: Vec::new(),
+            weight: 3,
+            announced: Some(1_700_000_000_000),
+        },
     )
 }
 
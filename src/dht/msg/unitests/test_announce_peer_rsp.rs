@@ -0,0 +1,45 @@
+use crate::dht::msg::{
+    msg,
+    Message,
+    msg::{Body, Kind, Method},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde_observed() {
+        let msg = msg::announce_peer_response(0x4321, Some("tcp://203.0.113.7:9000".to_string()));
+        assert_eq!(msg.kind() as u8, Kind::Response as u8);
+        assert_eq!(msg.method() as u8, Method::AnnouncePeer as u8);
+
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+
+        assert!(decoded.is_rsp());
+        assert_eq!(decoded.txid(), 0x4321);
+        match decoded.body() {
+            Some(Body::AnnouncePeerResponse(body)) => {
+                assert_eq!(body.observed(), Some("tcp://203.0.113.7:9000"));
+            },
+            _ => panic!("expected an announce peer response body"),
+        }
+    }
+
+    #[test]
+    fn test_serde_plain() {
+        // Without a body, as older nodes respond.
+        let msg = msg::announce_peer_response(0x4321, None);
+        assert!(msg.body().is_none());
+
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+        assert!(decoded.is_rsp());
+        assert!(decoded.body().is_none());
+    }
+}
@@ -0,0 +1,59 @@
+use crate::dht::msg::{
+    msg,
+    Message,
+    msg::{Body, Kind, Method},
+    extension::Extension,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor() {
+        let ext = Extension::new(vec![1, 2, 3, 4]);
+        assert_eq!(ext.data(), &[1, 2, 3, 4]);
+
+        let encoded = serde_cbor::to_vec(&ext)
+            .expect("Serialization failed");
+        let decoded: Extension = serde_cbor::from_slice(&encoded)
+            .expect("Deserialization failed");
+        assert_eq!(decoded.data(), &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_serde_request() {
+        let msg = msg::extension_request(b"vendor payload".to_vec());
+        assert_eq!(msg.kind() as u8, Kind::Request as u8);
+        assert_eq!(msg.method() as u8, Method::Extension as u8);
+
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+
+        assert!(decoded.is_req());
+        assert_eq!(decoded.method() as u8, Method::Extension as u8);
+        match decoded.body() {
+            Some(Body::ExtensionRequest(ext)) => assert_eq!(ext.data(), b"vendor payload"),
+            _ => panic!("expected an extension request body"),
+        }
+    }
+
+    #[test]
+    fn test_serde_response() {
+        let msg = msg::extension_response(0x1234, Vec::new());
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+
+        assert!(decoded.is_rsp());
+        assert_eq!(decoded.txid(), 0x1234);
+        assert_eq!(decoded.method() as u8, Method::Extension as u8);
+        match decoded.body() {
+            Some(Body::ExtensionResponse(ext)) => assert!(ext.data().is_empty()),
+            _ => panic!("expected an extension response body"),
+        }
+    }
+}
@@ -55,4 +55,39 @@ mod tests {
         assert!(!decoded.want_token());
 
     }
+
+    #[test]
+    fn test_serde_want_time() {
+        let req = FindNodeRequest::new(Id::random(), true, false, true);
+        assert!(!req.want_time());
+        assert_eq!(req.want(), 0x05);
+
+        let req = req.with_want_time(true);
+        assert!(req.want_time());
+        assert_eq!(req.want(), 0x15);
+
+        let encoded = serde_cbor::to_vec(&req)
+            .expect("Serialization failed");
+        let decoded = serde_cbor::from_slice::<FindNodeRequest>(&encoded)
+            .expect("Deserialization failed");
+        assert!(decoded.want_time());
+        assert!(decoded.want4());
+        assert!(decoded.want_token());
+    }
+
+    #[test]
+    fn test_serde_transient() {
+        let req = FindNodeRequest::new(Id::random(), true, false, false)
+            .with_transient(true);
+        assert!(req.is_transient());
+        assert_eq!(req.want(), 0x21);
+
+        let encoded = serde_cbor::to_vec(&req)
+            .expect("Serialization failed");
+        let decoded = serde_cbor::from_slice::<FindNodeRequest>(&encoded)
+            .expect("Deserialization failed");
+        assert!(decoded.is_transient());
+        assert!(decoded.want4());
+        assert!(!decoded.want_token());
+    }
 }
@@ -122,4 +122,22 @@ mod tests {
         assert_eq!(nodes4[0], node1);
         assert_eq!(nodes4[1], node2);
     }
+
+    #[test]
+    fn test_serde_time() {
+        let mut rsp = FindNodeResponse::new(Some(vec![make_node_info4()]), None, 0);
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        let decoded = serde_cbor::from_slice::<FindNodeResponse>(&encoded)
+            .expect("Deserialization failed");
+        assert_eq!(decoded.time(), None);
+
+        rsp.set_time(Some(1_700_000_000));
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        let decoded = serde_cbor::from_slice::<FindNodeResponse>(&encoded)
+            .expect("Deserialization failed");
+        assert_eq!(decoded.time(), Some(1_700_000_000));
+        assert_eq!(decoded.nodes4().map(|n| n.len()), Some(1));
+    }
 }
@@ -1,5 +1,7 @@
 use crate::{
     Id,
+    Network,
+    PeerInfo,
     dht::msg::{LookupRequest, FindPeerRequest},
 };
 
@@ -81,4 +83,47 @@ mod tests {
             .expect("missing target field");
         assert!(matches!(target, serde_cbor::Value::Bytes(bytes) if bytes.len() == Id::BYTES));
     }
+
+    #[test]
+    fn test_serde_tags() {
+        let peerid = Id::random();
+        let request = FindPeerRequest::new(peerid, true, false, -1, 2);
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("tg").is_none());
+
+        let request = request.with_tags(vec!["tls".into(), "proto/2".into()]);
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["tg"], serde_json::json!(["tls", "proto/2"]));
+
+        let encoded = serde_cbor::to_vec(&request).unwrap();
+        let decoded: FindPeerRequest = serde_cbor::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.tags(), ["tls", "proto/2"]);
+        assert_eq!(decoded.target(), &peerid);
+
+        let tags = (0..=PeerInfo::MAX_TAGS).map(|i| format!("t{i}")).collect();
+        let encoded = serde_cbor::to_vec(&request.with_tags(tags)).unwrap();
+        assert!(serde_cbor::from_slice::<FindPeerRequest>(&encoded).is_err());
+    }
+
+    #[test]
+    fn test_serde_network() {
+        let peerid = Id::random();
+        let request = FindPeerRequest::new(peerid, true, true, -1, 2);
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("nf").is_none());
+
+        for network in [Network::IPv4, Network::IPv6] {
+            let request = request.clone().with_network(Some(network));
+            let json = serde_json::to_value(&request).unwrap();
+            assert_eq!(json["nf"], network as u8);
+
+            let encoded = serde_cbor::to_vec(&request).unwrap();
+            let decoded: FindPeerRequest = serde_cbor::from_slice(&encoded).unwrap();
+            assert_eq!(decoded.network(), Some(network));
+        }
+
+        let mut json = serde_json::to_value(&request).unwrap();
+        json["nf"] = serde_json::json!(5);
+        assert!(serde_json::from_value::<FindPeerRequest>(json).is_err());
+    }
 }
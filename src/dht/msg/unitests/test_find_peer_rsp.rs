@@ -187,4 +187,26 @@ mod tests {
         assert_eq!(decoded_peer2.signature(), peer2.signature());
         assert_eq!(decoded_peer2.nonce(), peer2.nonce());
     }
+
+    #[test]
+    fn test_serde_with_ages() {
+        let peer1 = make_peer(8080);
+        let peer2 = make_peer(8081);
+        let rsp = FindPeerResponse::with_peers(vec![peer1.clone(), peer2.clone()])
+            .with_ages(Some(vec![5, 3600]));
+
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        let decoded: FindPeerResponse = serde_cbor::from_slice(encoded.as_slice())
+            .expect("Deserialization failed");
+        assert_eq!(decoded.peers().unwrap().len(), 2);
+        assert_eq!(decoded.ages(), Some([5, 3600].as_slice()));
+
+        // An age is needed for every peer.
+        let rsp = FindPeerResponse::with_peers(vec![peer1, peer2])
+            .with_ages(Some(vec![5]));
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        assert!(serde_cbor::from_slice::<FindPeerResponse>(encoded.as_slice()).is_err());
+    }
 }
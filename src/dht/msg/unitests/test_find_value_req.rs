@@ -70,4 +70,23 @@ mod tests {
         assert!(decoded.want6());
         assert!(!decoded.want_token());
     }
+
+    #[test]
+    fn test_serde_want_age() {
+        let msg = FindValueRequest::new(Id::random(), true, false, -1);
+        assert!(!msg.want_age());
+        assert_eq!(msg.want(), 0x01);
+
+        let msg = msg.with_want_age(true);
+        assert!(msg.want_age());
+        assert_eq!(msg.want(), 0x09);
+
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("Serialization failed");
+        let decoded: FindValueRequest = serde_cbor::from_slice(&encoded)
+            .expect("Deserialization failed");
+        assert!(decoded.want_age());
+        assert!(decoded.want4());
+        assert!(!decoded.want6());
+    }
 }
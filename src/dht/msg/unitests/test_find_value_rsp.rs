@@ -2,6 +2,8 @@ use std::net::SocketAddr;
 use crate::{
     Id, Network, NodeInfo,
     Value, core::ImmutableBuilder as ValueBuilder,
+    core::SignedBuilder,
+    signature::KeyPair,
     dht::msg::{
         find_value_rsp::FindValueResponse,
         lookup_rsp::LookupResponse,
@@ -128,4 +130,44 @@ mod tests {
         assert_eq!(decoded.token(), 0);
         assert_eq!(decoded.value().unwrap(), &value);
     }
+
+    #[test]
+    fn test_serde_with_age() {
+        let value = make_value();
+        let rsp = FindValueResponse::with_value(value.clone()).with_age(Some(42));
+        assert_eq!(rsp.age(), Some(42));
+
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        let decoded: FindValueResponse = serde_cbor::from_slice(encoded.as_slice())
+            .expect("Deserialization failed");
+        assert_eq!(decoded.value().unwrap(), &value);
+        assert_eq!(decoded.age(), Some(42));
+
+        // Responses without the age, like the ones from older nodes.
+        let encoded = serde_cbor::to_vec(&FindValueResponse::with_value(value))
+            .expect("Serialization failed");
+        let decoded: FindValueResponse = serde_cbor::from_slice(encoded.as_slice())
+            .expect("Deserialization failed");
+        assert_eq!(decoded.age(), None);
+    }
+
+    #[test]
+    fn test_serde_countersigned() {
+        let service = KeyPair::random();
+        let value = SignedBuilder::new(&[1, 2, 3])
+            .build()
+            .and_then(|v| v.countersign(&service))
+            .expect("Failed to build value");
+        let rsp = FindValueResponse::with_value(value.clone());
+
+        let encoded = serde_cbor::to_vec(&rsp)
+            .expect("Serialization failed");
+        let decoded: FindValueResponse = serde_cbor::from_slice(encoded.as_slice())
+            .expect("Deserialization failed");
+        let decoded = decoded.value().unwrap();
+        assert_eq!(decoded.countersigner(), value.countersigner());
+        assert_eq!(decoded.countersignature(), value.countersignature());
+        assert!(decoded.verify_countersignature(&Id::from(service.public_key())));
+    }
 }
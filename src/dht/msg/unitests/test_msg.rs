@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
 use crate::{
     Id,
+    Capabilities,
     dht::msg::{msg, Message, msg::{Method, Kind}}
 };
 
@@ -51,7 +52,7 @@ mod tests {
     #[test]
     fn test_serde_find_peer_request() {
         let target = Id::random();
-        let message = msg::find_peer_request(target, true, false, -1, 1);
+        let message = msg::find_peer_request(target, true, false, -1, 1, Vec::new(), None);
 
         let json = serde_json::to_value(&message).expect("JSON serialization failed");
         assert_eq!(json["q"]["t"], target.to_base58());
@@ -79,4 +80,26 @@ mod tests {
             .expect("missing target field");
         assert!(matches!(encoded_target, serde_cbor::Value::Bytes(bytes) if bytes.len() == Id::BYTES));
     }
+
+    #[test]
+    fn test_serde_capabilities() {
+        let mut message = msg::ping_request();
+        let encoded = serde_cbor::to_vec(&message).expect("message serialization failed");
+        let value: serde_cbor::Value = serde_cbor::from_slice(&encoded).unwrap();
+        match value {
+            serde_cbor::Value::Map(entries) => assert!(!entries.contains_key(&serde_cbor::Value::Text("c".to_string()))),
+            _ => panic!("expected a CBOR message map"),
+        }
+        let decoded: Message = serde_cbor::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.capabilities(), None);
+
+        let caps = Capabilities::CLIENT_ONLY | Capabilities::GATEWAY;
+        message.set_capabilities(caps);
+        let encoded = serde_cbor::to_vec(&message).expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.capabilities(), Some(caps));
+
+        let json = serde_json::to_value(&message).expect("JSON serialization failed");
+        assert_eq!(json["c"], caps.bits());
+    }
 }
@@ -0,0 +1,94 @@
+use std::net::IpAddr;
+
+use crate::dht::msg::{
+    msg,
+    Message,
+    msg::{Body, Kind, Method},
+    rendezvous::Rendezvous,
+};
+
+fn hosts() -> Vec<IpAddr> {
+    vec![
+        "192.168.1.20".parse().unwrap(),
+        "2001:db8::20".parse().unwrap(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cbor() {
+        let rendezvous = Rendezvous::new(0x0102030405060708, 40123, &hosts());
+        assert_eq!(rendezvous.session(), 0x0102030405060708);
+        assert_eq!(rendezvous.port(), 40123);
+        assert_eq!(rendezvous.hosts(), hosts());
+
+        let encoded = serde_cbor::to_vec(&rendezvous)
+            .expect("Serialization failed");
+        let decoded: Rendezvous = serde_cbor::from_slice(&encoded)
+            .expect("Deserialization failed");
+        assert_eq!(decoded.session(), rendezvous.session());
+        assert_eq!(decoded.port(), 40123);
+        assert_eq!(decoded.hosts(), hosts());
+    }
+
+    #[test]
+    fn test_invalid_hosts() {
+        let value = serde_cbor::Value::Map([
+            ("s", serde_cbor::Value::Integer(7)),
+            ("p", serde_cbor::Value::Integer(9000)),
+            ("h", serde_cbor::Value::Array(vec![
+                serde_cbor::Value::Bytes(vec![10, 0, 0, 1]),
+                serde_cbor::Value::Bytes(vec![1, 2, 3]),
+            ])),
+        ].into_iter().map(|(k, v)| (serde_cbor::Value::Text(k.into()), v)).collect());
+
+        let encoded = serde_cbor::to_vec(&value).unwrap();
+        let decoded: Rendezvous = serde_cbor::from_slice(&encoded).unwrap();
+        assert_eq!(decoded.hosts(), vec!["10.0.0.1".parse::<IpAddr>().unwrap()]);
+    }
+
+    #[test]
+    fn test_serde_request() {
+        let msg = msg::rendezvous_request(Rendezvous::new(42, 5000, &hosts()));
+        assert_eq!(msg.kind() as u8, Kind::Request as u8);
+        assert_eq!(msg.method() as u8, Method::Rendezvous as u8);
+
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+
+        assert!(decoded.is_req());
+        assert_eq!(decoded.method() as u8, Method::Rendezvous as u8);
+        match decoded.body() {
+            Some(Body::RendezvousRequest(body)) => {
+                assert_eq!(body.session(), 42);
+                assert_eq!(body.port(), 5000);
+                assert_eq!(body.hosts(), hosts());
+            },
+            _ => panic!("expected a rendezvous request body"),
+        }
+    }
+
+    #[test]
+    fn test_serde_response() {
+        let msg = msg::rendezvous_response(0x1234, Rendezvous::new(42, 6000, &[]));
+        let encoded = serde_cbor::to_vec(&msg)
+            .expect("message serialization failed");
+        let decoded: Message = serde_cbor::from_slice(&encoded)
+            .expect("message cbor decoding failed");
+
+        assert!(decoded.is_rsp());
+        assert_eq!(decoded.txid(), 0x1234);
+        match decoded.body() {
+            Some(Body::RendezvousResponse(body)) => {
+                assert_eq!(body.port(), 6000);
+                assert!(body.hosts().is_empty());
+            },
+            _ => panic!("expected a rendezvous response body"),
+        }
+    }
+}
@@ -58,4 +58,26 @@ mod tests {
         assert_eq!(decoded.expected_seq(), -1);
         assert_eq!(decoded.value(), &value);
     }
+
+    #[test]
+    fn test_serde_countersigned() {
+        let signer = Id::random();
+        let value = make_value().with_countersignature(Some(signer), Some(vec![5; 64]));
+        let req = StoreValueRequest::new(value.clone(), 42, -1);
+
+        let encoded = serde_cbor::to_vec(&req)
+            .expect("Serialization failed");
+        let decoded: StoreValueRequest = serde_cbor::from_slice(&encoded)
+            .expect("Deserialization failed");
+        assert_eq!(decoded.value(), &value);
+        assert_eq!(decoded.value().countersigner(), Some(&signer));
+
+        // Requests without it encode as before.
+        let plain = serde_cbor::to_vec(&StoreValueRequest::new(make_value(), 42, -1))
+            .expect("Serialization failed");
+        let map: serde_cbor::Value = serde_cbor::from_slice(&plain).unwrap();
+        let serde_cbor::Value::Map(map) = map else { panic!("not a map") };
+        assert!(!map.contains_key(&serde_cbor::Value::Text("cs".into())));
+        assert!(!map.contains_key(&serde_cbor::Value::Text("csk".into())));
+    }
 }
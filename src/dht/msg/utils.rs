@@ -7,7 +7,7 @@ pub(crate) fn serialize_ver<S>(ver: &i32, se: S) -> SResult<S::Ok, S::Error>
 where S: Serializer,
 {
     if se.is_human_readable() {
-        se.serialize_str(&format!("{}", version::format_version(*ver)))
+        se.serialize_str(&format!("{}", version::normalized_version(*ver)))
     } else {
         ver.serialize(se)
     }
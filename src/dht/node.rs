@@ -1,40 +1,65 @@
 use std::{
+    collections::HashMap,
     fs, fs::File,
     io::Write,
+    future::Future,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
     sync::{Arc, Mutex, Weak},
-    time::{Duration, SystemTime}
+    time::Duration
 };
 use futures::{
-    stream::FuturesUnordered,
+    stream::{FuturesUnordered, Stream},
     StreamExt
 };
-use tokio::task;
-use log::{warn, info, debug};
+use unicode_normalization::UnicodeNormalization;
+use tokio::{runtime::Handle, sync::mpsc, task};
+use log::{error, warn, info, debug};
 
 use crate::{
     Id,
     Network,
+    Clock, SystemClock,
     CryptoContext, CryptoIdentity, Identity,
-    NodeInfo, PeerInfo, Value,
+    NodeInfo, PeerInfo, Value, SignedBuilder,
     JointResult,
     core::{logger,version},
-    errors::{Result, ArgumentError, IOError, StateError},
+    name_record::{self, NameRecord, NameClaim, NAME_NAMESPACE},
+    errors::{Error, Result, ArgumentError, IOError, MalformedError, NetworkError, ProtocolError, StateError},
     signature
 };
 use crate::dht::{
     NodeConfig,
     LookupOption,
+    LookupOptionsEx,
+    StorageBackend,
+    lookup_result::{Origins, ValueResult, PeerResult, LookupOutcome},
+    lookup_progress::{LookupProgress, ProgressSender, PROGRESS_QUEUE_SIZE},
+    node_query::NodeQuery,
+    hole_punch::{self, DirectConnections, DirectConnectionHandler, ProbePattern, PunchResult},
+    announcement::AnnouncementPolicy,
+    clock_skew::{ClockSkew, CompensatedClock},
+    blocklist::{Blocklist, BlockEntry, BlockTarget, BlockReason, StrikePolicy},
+    origin_check::OriginChecks,
+    siblings::Siblings,
+    seed,
+    storage_event::{StorageEvent, StorageEvents, StorageListener, DEFAULT_STORAGE_EVENT_CAPACITY},
+    event_stream::{EventBroadcast, StreamItem, NodeStatusEvent, DEFAULT_EVENT_STREAM_CAPACITY},
+    msg::Rendezvous,
+    node_event::{EventLog, NodeEvent, NodeEventKind},
     eligible_value::EligibleValue,
     eligible_peers::EligiblePeers,
     cached_identity::CachedIdentity,
+    crypto_cache::CryptoCache,
+    peer_selector::PeerSelector,
     token_manager::TokenManager,
     handler::AsyncHandler,
     connection_status::ConnectionStatus,
     connection_status_listener::ConnectionStatusListener,
     storage::{
-        data_storage::{self, DataStorage},
+        data_storage::{self, DataStorage, IntegrityReport},
         sqlite_storage::SqliteStorage,
+        memory_storage::MemoryStorage,
     },
     errors::{
         SeqNotExpected,
@@ -44,13 +69,41 @@ use crate::dht::{
     },
     timer_verticle,
     dht_verticle::{self, VerticleClient, VerticleOptions},
+    rpc::{
+        socket_health::SocketHealthOptions,
+        send_shaper::SendShaperOptions,
+    },
+    stats::{StatsJournal, Concurrency, CommandQueue, CryptoCacheStats, TokenCacheStats, BlocklistStats, OriginCheckStats, ReceiveStats, SocketErrorStats, KeyspaceReport},
+    socket_event::{SocketErrors, SocketErrorHandler},
+    data_layout::DataLayout,
+    key_file,
+    routing::{Prefix, kbucket::BucketInfo, routing_table::RoutingStrategy},
+    task::task_manager::ConcurrencyLimits,
+};
+#[cfg(feature = "testing")]
+use {
+    std::sync::OnceLock,
+    crate::testing::{LossyTransport, SimTransport},
 };
+#[cfg(feature = "wsgateway")]
+use crate::dht::rpc::ws_gateway::GatewayListener;
+
+// Invoked on the DHT thread for incoming extension requests, returns the
+// response payload, or None to answer the request with an error.
+pub type ExtensionHandler = Box<dyn Fn(&NodeInfo, &[u8]) -> Option<Vec<u8>> + Send + Sync>;
+
+// Keeps an extension request in a single datagram.
+pub const MAX_EXTENSION_PAYLOAD: usize = 1024;
 
 const MAX_PEER_AGE  : Duration = Duration::from_millis(120 * 60 * 1000); // 2 hours in milliseconds
 const MAX_VALUE_AGE : Duration = Duration::from_millis(120 * 60 * 1000); // 2 hours in milliseconds
 
+// Instances of a service looked up to pick one from.
+const MAX_PEER_CHOICES: usize = 32;
+
 const RE_ANNOUNCE_INTERVAL      : u64 = 5 * 60 * 1000;      // 5 minutes in milliseconds
 const STORAGE_EXPIRE_INTERVAL   : u64 = 10 * 60 * 1000;     // 10 minutes in milliseconds
+const STORE_RETRY_INTERVAL      : u64 = 60 * 1000;          // 1 minute in milliseconds
 
 pub struct Node {
     cfg             : Box<dyn NodeConfig>,
@@ -61,21 +114,93 @@ pub struct Node {
     dht4            : Mutex<Option<Arc<VerticleClient>>>,
     dht6            : Mutex<Option<Arc<VerticleClient>>>,
 
-    data_dir        : PathBuf,
+    layout          : DataLayout,
     database_uri    : PathBuf,
 
     running         : Mutex<bool>,
     listeners       : Arc<Mutex<Vec<Box<dyn ConnectionStatusListener>>>>,
+    status_events   : EventBroadcast<NodeStatusEvent>,
+    connection_events: Arc<EventBroadcast<(Network, ConnectionStatus)>>,
 
     timer_verticle  : Mutex<Option<Arc<timer_verticle::VerticleClient>>>,
 
     storage         : Arc<Mutex<dyn DataStorage>>,
     token_man       : Arc<TokenManager>,
+    clock           : Arc<dyn Clock>,
+    clock_skew      : Arc<ClockSkew>,
+    blocklist       : Arc<Blocklist>,
+    socket_errors   : Arc<SocketErrors>,
+    origin_checks   : OriginChecks,
+    events          : EventLog,
+    storage_events  : Arc<StorageEvents>,
+    extension_handler: Arc<Mutex<Option<ExtensionHandler>>>,
+    direct_connections: Arc<Mutex<DirectConnections>>,
+    stats_journal   : Option<Mutex<StatsJournal>>,
+    // Local values fewer nodes than required stored, with their persistence.
+    unconfirmed     : Mutex<HashMap<Id, bool>>,
+    runtime         : Option<Handle>,
+    #[cfg(feature = "testing")]
+    transport       : OnceLock<Arc<LossyTransport>>,
+    #[cfg(feature = "testing")]
+    sim             : OnceLock<Arc<SimTransport>>,
+    // Relays the packets of the nodes tunneling through this one.
+    #[cfg(feature = "wsgateway")]
+    ws_gateway      : Mutex<Option<GatewayListener>>,
     weak            : Weak<Self>,
 }
 
 impl Node {
     pub fn new(cfg: Box<dyn NodeConfig>) -> Result<Arc<Self>> {
+        Self::create(cfg, None, Arc::new(SystemClock))
+    }
+
+    // Creates a node driven by the caller's multi-thread runtime instead of
+    // runtimes of its own, pass Handle::current() for the ambient runtime.
+    // The DHT instances and the periodic tasks keep their dedicated threads
+    // ("boson-dht4", "boson-dht6" and "boson-timer") as their state is not
+    // Send, but their sockets and timers are registered with this runtime.
+    // The ActiveProxy worker runs on it as well.
+    pub fn with_runtime(cfg: Box<dyn NodeConfig>, runtime: Handle) -> Result<Arc<Self>> {
+        Self::create(cfg, Some(runtime), Arc::new(SystemClock))
+    }
+
+    // Creates a node reading the time from the given clock for the storage
+    // expiry, the token rotation and the periodic DHT maintenance, meant for
+    // tests driving the time with a ManualClock.
+    pub fn with_clock(cfg: Box<dyn NodeConfig>, clock: Arc<dyn Clock>) -> Result<Arc<Self>> {
+        Self::create(cfg, None, clock)
+    }
+
+    // Like with_clock, passing the packets of the node through the fault
+    // injector, for soak tests of the DHT under loss, duplication and delay.
+    #[cfg(feature = "testing")]
+    pub fn with_faults(cfg: Box<dyn NodeConfig>,
+        clock: Arc<dyn Clock>,
+        transport: Arc<LossyTransport>
+    ) -> Result<Arc<Self>> {
+        let node = Self::create(cfg, None, clock)?;
+        let _ = node.transport.set(transport);
+        Ok(node)
+    }
+
+    // Like with_clock, the node sending its packets through the simulated
+    // network instead of a socket. Its DHT and periodic tasks run on the
+    // caller's LocalSet, on the virtual time of the simulation, see
+    // SimNetwork.
+    #[cfg(feature = "testing")]
+    pub(crate) fn with_sim(cfg: Box<dyn NodeConfig>,
+        clock: Arc<dyn Clock>,
+        sim: Arc<SimTransport>
+    ) -> Result<Arc<Self>> {
+        let node = Self::create(cfg, None, clock)?;
+        let _ = node.sim.set(sim);
+        Ok(node)
+    }
+
+    fn create(cfg: Box<dyn NodeConfig>,
+        runtime: Option<Handle>,
+        clock: Arc<dyn Clock>
+    ) -> Result<Arc<Self>> {
         Self::check_config(cfg.as_ref())?;
 
         // Setup logger before any log is generated.
@@ -88,19 +213,27 @@ impl Node {
         #[cfg(feature = "devp")]
         info!("DHT node running in development mode!!!");
 
-        let data_dir = PathBuf::from(cfg.data_dir());
-        let database_uri = data_dir.join(
-            data_storage::database_name(cfg.database_uri())
-        );
-
         let identity = CachedIdentity::new({
             let kp = signature::KeyPair::from(cfg.private_key());
             CryptoIdentity::from(kp)
-        });
+        }, CryptoCache::new(
+            cfg.crypto_cache_capacity(),
+            Duration::from_secs(cfg.crypto_cache_ttl())
+        ));
+
+        let layout = DataLayout::for_node(
+            Path::new(cfg.data_dir()),
+            cfg.instance_name(),
+            identity.id()
+        );
+        layout.create()?;
+        let database_name = data_storage::database_name(cfg.database_uri());
+        layout.migrate_legacy(identity.id(), database_name)?;
+        let database_uri = layout.database(database_name);
 
         // Cache the node id to a file for quick access in the future.
         let bs58 = identity.id().to_base58();
-        let path = data_dir.join("id");
+        let path = layout.id_file();
         File::create(&path).map_err(|e| IOError::new(
                 format!("Creating node id cache file error: {e}")))?
             .write_all(bs58.as_bytes()).map_err(|e| IOError::new(
@@ -108,10 +241,32 @@ impl Node {
 
         info!("The Kad node ID: {}", identity.id());
 
+        let storage: Arc<Mutex<dyn DataStorage>> = match cfg.storage_backend() {
+            StorageBackend::Memory => Arc::new(Mutex::new(MemoryStorage::with_clock(clock.clone()))),
+            StorageBackend::Sqlite => Arc::new(Mutex::new(SqliteStorage::with_clock(clock.clone()))),
+        };
+
+        let events = EventLog::new(cfg.event_log_capacity());
+        let clock_skew = Arc::new(ClockSkew::new(
+            Duration::from_secs(cfg.clock_skew_threshold()),
+            cfg.compensate_clock_skew()
+        ));
+        let blocklist = Arc::new(Blocklist::open(layout.blocklist(), clock.clone(), StrikePolicy {
+            token_strikes   : cfg.block_token_strikes(),
+            value_strikes   : cfg.block_value_strikes(),
+            duration        : Duration::from_secs(cfg.block_duration()),
+        }));
+        let origin_checks = OriginChecks::new(*identity.id(), clock.clone());
+        let stats_journal = (cfg.stats_interval() > 0).then(|| Mutex::new(StatsJournal::new(
+            layout.stats_journal(),
+            cfg.stats_max_file_size(),
+            cfg.stats_max_files()
+        )));
+
         Ok(Arc::new_cyclic(|weak| Self {
             cfg,
             identity,
-            data_dir,
+            layout,
             database_uri,
             lookup_option   : Mutex::new(LookupOption::Conservative),
             dht4            : Mutex::new(None),
@@ -119,11 +274,33 @@ impl Node {
 
             running         : Mutex::new(false),
             listeners       : Arc::new(Mutex::new(Vec::new())),
+            status_events   : EventBroadcast::new(DEFAULT_EVENT_STREAM_CAPACITY),
+            connection_events: Arc::new(EventBroadcast::new(DEFAULT_EVENT_STREAM_CAPACITY)),
 
             timer_verticle  : Mutex::new(None),
 
-            storage         : Arc::new(Mutex::new(SqliteStorage::new())),
-            token_man       : Arc::new(TokenManager::new()),
+            storage,
+            token_man       : Arc::new(TokenManager::new(Arc::new(
+                CompensatedClock::new(clock.clone(), clock_skew.clone())
+            ))),
+            clock,
+            clock_skew,
+            blocklist,
+            socket_errors   : Arc::new(SocketErrors::new()),
+            origin_checks,
+            events,
+            storage_events  : Arc::new(StorageEvents::new(DEFAULT_STORAGE_EVENT_CAPACITY)),
+            extension_handler: Arc::new(Mutex::new(None)),
+            direct_connections: Arc::new(Mutex::new(DirectConnections::default())),
+            stats_journal,
+            unconfirmed     : Mutex::new(HashMap::new()),
+            runtime,
+            #[cfg(feature = "testing")]
+            transport       : OnceLock::new(),
+            #[cfg(feature = "testing")]
+            sim             : OnceLock::new(),
+            #[cfg(feature = "wsgateway")]
+            ws_gateway      : Mutex::new(None),
             weak            : weak.clone(),
         }))
     }
@@ -133,6 +310,12 @@ impl Node {
             return Err(ArgumentError::new(
                 "At least one host/address must be specified"));
         }
+        if cfg.host4().is_none() && cfg.port4() != cfg.port() {
+            return Err(ArgumentError::new("An IPv4 port is configured without an IPv4 address"));
+        }
+        if cfg.host6().is_none() && cfg.port6() != cfg.port() {
+            return Err(ArgumentError::new("An IPv6 port is configured without an IPv6 address"));
+        }
 
         //if cfg.bootstrap_nodes().is_empty() {
         //    return Err(ArgumentError::new(
@@ -142,6 +325,9 @@ impl Node {
         if cfg.data_dir().is_empty() {
             return Err(ArgumentError::new("Data directory cannot be empty"));
         }
+        if !cfg!(feature = "wsgateway") && (cfg.ws_gateway().is_some() || cfg.ws_gateway_listener().is_some()) {
+            return Err(ArgumentError::new("A DHT gateway is configured without the wsgateway feature"));
+        }
 
         let data_dir = cfg.data_dir();
         let path = Path::new(data_dir);
@@ -157,6 +343,20 @@ impl Node {
             })?;
         };
 
+        if let Some(name) = cfg.instance_name() {
+            if !DataLayout::is_valid_instance(name) {
+                return Err(ArgumentError::new(format!("Invalid instance name: {name}")));
+            }
+        }
+
+        if cfg.send_rate() > 0 && cfg.send_rate_interval() == 0 {
+            return Err(ArgumentError::new("Send rate interval cannot be 0 with a send rate set"));
+        }
+
+        if cfg.storage_backend() == StorageBackend::Memory {
+            return Ok(());
+        }
+
         let database_uri = cfg.database_uri();
         if database_uri.is_empty() {
             return Err(ArgumentError::new("Database URI cannot be empty"));
@@ -184,6 +384,13 @@ impl Node {
         }
     }
 
+    // Records a failed storage operation in the event log.
+    fn storage_result<T>(&self, op: &'static str, result: Result<T>) -> Result<T> {
+        result.inspect_err(|_| {
+            self.events.record(NodeEventKind::StorageError { op });
+        })
+    }
+
     #[inline]
     fn timer_verticle(&self) -> Arc<timer_verticle::VerticleClient> {
         self.timer_verticle.lock().unwrap()
@@ -198,7 +405,7 @@ impl Node {
         let mut handles = FuturesUnordered::<task::JoinHandle<()>>::new();
 
         // Re-announce values
-        let before = crate::as_ms!(SystemTime::now()) as u64
+        let before = self.clock.now_ms()
             - MAX_VALUE_AGE.as_millis() as u64
             + RE_ANNOUNCE_INTERVAL * 2;
 
@@ -225,7 +432,7 @@ impl Node {
         }
 
         // Re-announce peers
-        let before_peer = crate::as_ms!(SystemTime::now()) as u64
+        let before_peer = self.clock.now_ms()
             - MAX_PEER_AGE.as_millis() as u64
             + RE_ANNOUNCE_INTERVAL * 2;
 
@@ -259,19 +466,76 @@ impl Node {
         }
     }
 
+    async fn record_stats(&self) {
+        let Some(journal) = self.stats_journal.as_ref() else {
+            return;
+        };
+
+        let dht4 = self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let dht6 = self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let (stats4, stats6) = tokio::join!(
+            async { match dht4 { Some(f) => f.await.ok(), None => None } },
+            async { match dht6 { Some(f) => f.await.ok(), None => None } }
+        );
+
+        let (values, peers) = {
+            let storage = self.storage.lock().unwrap();
+            (storage.count_values().unwrap_or(0), storage.count_peers().unwrap_or(0))
+        };
+
+        let result = journal.lock().unwrap().record(stats4, stats6, values, peers);
+        if let Err(e) = result {
+            warn!("Failed to record stats sample: {e}");
+        }
+    }
+
+    async fn retry_unconfirmed_stores(self: Arc<Self>) {
+        let pending: Vec<(Id, bool)> = self.unconfirmed.lock().unwrap()
+            .iter()
+            .map(|(id, persistent)| (*id, *persistent))
+            .collect();
+
+        let mut handles = FuturesUnordered::<task::JoinHandle<()>>::new();
+        for (value_id, persistent) in pending {
+            let value = self.storage.lock().unwrap().get_value(&value_id).ok().flatten();
+            let Some(value) = value else {
+                // Removed since.
+                self.unconfirmed.lock().unwrap().remove(&value_id);
+                continue;
+            };
+            debug!("Storing value {} to the network again", value_id);
+            let node = self.clone();
+            handles.push(task::spawn_local(async move {
+                if let Err(e) = node.store_value(&value, value.sequence_number(), persistent).await {
+                    warn!("Failed to store value {} again: {}", value_id, e);
+                }
+            }));
+        }
+
+        while let Some(result) = handles.next().await {
+            if let Err(e) = result {
+                warn!("Store retry task panicked: {}", e);
+            }
+        }
+    }
+
     async fn setup_periodic_tasks(&self) -> Result<()> {
         let client  = self.timer_verticle();
 
-        let storage = self.storage.clone();
+        let weak = self.weak.clone();
         let _ = client.add_timer(
             30_000,
             Some(STORAGE_EXPIRE_INTERVAL),
-            AsyncHandler::new(move |_|{
-                    let storage = storage.clone();
-                    Box::pin(async move {
-                        let _ = storage.lock().unwrap().purge();
+            AsyncHandler::new(move |_| {
+                let weak = weak.clone();
+                Box::pin(async move {
+                    if let Some(node) = weak.upgrade() {
+                        node.check_peer_origins().await;
+                        node.expire_storage();
+                    }
                 })
-        }))?;
+            })
+        )?;
 
         let weak = self.weak.clone();
         let _ = client.add_timer(
@@ -288,6 +552,20 @@ impl Node {
             })
         )?;
 
+        let weak = self.weak.clone();
+        let _ = client.add_timer(
+            STORE_RETRY_INTERVAL,
+            Some(STORE_RETRY_INTERVAL),
+            AsyncHandler::new(move |_| {
+                let weak = weak.clone();
+                Box::pin(async move {
+                    if let Some(node) = weak.upgrade() {
+                        node.retry_unconfirmed_stores().await;
+                    }
+                })
+            })
+        )?;
+
         let token_man = self.token_man.clone();
         let _ = client.add_timer(
             TokenManager::TOKEN_TIMEOUT,
@@ -299,34 +577,129 @@ impl Node {
                 })
             })
         )?;
+
+        let interval = self.cfg.storage_maintenance_interval() * 1000;
+        if interval > 0 {
+            let storage = self.storage.clone();
+            let events = self.events.clone();
+            let _ = client.add_timer(
+                interval,
+                Some(interval),
+                AsyncHandler::new(move |_| {
+                    let storage = storage.clone();
+                    let events = events.clone();
+                    Box::pin(async move {
+                        match storage.lock().unwrap().maintain() {
+                            Ok(freed_pages) => events.record(NodeEventKind::StorageMaintained { freed_pages }),
+                            Err(e) => warn!("Storage maintenance failed: {e}"),
+                        }
+                    })
+                })
+            )?;
+        }
+
+        if self.stats_journal.is_some() {
+            let interval = self.cfg.stats_interval() * 1000;
+            let weak = self.weak.clone();
+            let _ = client.add_timer(
+                interval,
+                Some(interval),
+                AsyncHandler::new(move |_| {
+                    let weak = weak.clone();
+                    Box::pin(async move {
+                        if let Some(node) = weak.upgrade() {
+                            node.record_stats().await;
+                        }
+                    })
+                })
+            )?;
+        }
         Ok(())
     }
 
+    #[cfg(feature = "activeproxy")]
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
     pub fn add_listener(&self, listener: Box<dyn ConnectionStatusListener>) {
         self.listeners.lock().unwrap().push(listener);
     }
 
+    // The node being started and stopped from now on. A stream falling
+    // behind gets a Lagged item in place of the events it missed.
+    pub fn status_events(&self) -> impl Stream<Item = StreamItem<NodeStatusEvent>> + Send + 'static {
+        self.status_events.subscribe()
+    }
+
+    // The connection status changes of the DHT on the network from now on,
+    // as told to the ConnectionStatusListeners.
+    pub fn connection_events(&self, network: Network) -> impl Stream<Item = StreamItem<ConnectionStatus>> + Send + 'static {
+        self.connection_events.subscribe().filter_map(move |item| async move {
+            match item {
+                StreamItem::Event((net, status)) if net == network => Some(StreamItem::Event(status)),
+                StreamItem::Event(_) => None,
+                StreamItem::Lagged(missed) => Some(StreamItem::Lagged(missed)),
+            }
+        })
+    }
+
+    // Opens the storage, a database SQLite finds corrupt or failing the quick
+    // integrity check is moved aside and replaced by a fresh one. Any other
+    // error, a newer version or a file it can't access, is passed through
+    // with the database left in place.
+    fn open_storage(&self) -> Result<()> {
+        let db_path = self.database_uri.to_str()
+            .ok_or_else(|| IOError::new("Database path contains invalid UTF-8"))?;
+        let mut locked = self.storage.lock().unwrap();
+
+        let result = locked.open(db_path).and_then(|_| {
+            let report = locked.check_integrity(true)?;
+            match report.is_ok() {
+                true => Ok(()),
+                false => Err(MalformedError::new(report.errors().join("; ")) as Error),
+            }
+        });
+
+        if let Err(e) = result {
+            if self.cfg.storage_backend() != StorageBackend::Sqlite
+                || e.downcast_ref::<MalformedError>().is_none() {
+                return Err(e);
+            }
+            locked.close();
+            let moved_to = data_storage::quarantine(&self.database_uri)?;
+            error!("Database {} is corrupt ({e}), moved to {} and starting with an empty one",
+                self.database_uri.display(), moved_to.display());
+            self.events.record(NodeEventKind::StorageRecovered { moved_to });
+            locked.open(db_path)?;
+        }
+        locked.initialize(MAX_VALUE_AGE, MAX_PEER_AGE)?;
+
+        if let Some(path) = self.cfg.seed_data() {
+            seed::load(Path::new(path), &mut *locked)?;
+        }
+        Ok(())
+    }
+
     pub async fn start(&self) -> Result<()> {
         if self.is_running() {
             return Err(StateError::new("KadNode is already running."));
         };
 
-        {
-            let db_path = self.database_uri.to_str()
-                .ok_or_else(|| IOError::new("Database path contains invalid UTF-8"))?;
-            let mut locked = self.storage.lock().unwrap();
-            locked.open(db_path)?;
-            locked.initialize(MAX_VALUE_AGE, MAX_PEER_AGE)?
-        }
+        self.open_storage()?;
 
-        let options = timer_verticle::VerticleOptions::default();
+        let options = timer_verticle::VerticleOptions::default()
+            .with_runtime(self.runtime.clone());
+        #[cfg(feature = "testing")]
+        let options = options.with_local(self.sim.get().is_some());
         let client = timer_verticle::deploy(options)?;
         *self.timer_verticle.lock().unwrap() = Some(Arc::new(client));
 
         self.setup_periodic_tasks().await?;
 
         let listener = Arc::new(DefaultConnectionStatusListener {
-            listeners: self.listeners.clone()
+            listeners: self.listeners.clone(),
+            events: self.connection_events.clone(),
         });
 
 
@@ -335,19 +708,77 @@ impl Node {
             .with_identity(self.identity.identity())
             .with_storage(self.storage.clone())
             .with_tokenman(self.token_man.clone())
+            .with_clock(self.clock.clone())
             .with_bootstrap(self.cfg.bootstrap_nodes().to_vec())
-            .with_datadir(self.data_dir.clone())
-            .with_listener(listener);
+            .with_layout(self.layout.clone())
+            .with_listener(listener)
+            .with_event_log(self.events.clone())
+            .with_extension_handler(self.extension_handler.clone())
+            .with_direct_connections(self.direct_connections.clone())
+            .with_endpoint_policy(self.cfg.endpoint_policy())
+            .with_announcement_policy(AnnouncementPolicy::new(
+                Duration::from_secs(self.cfg.announcement_skew()),
+                self.cfg.require_announcement_time()
+            ))
+            .with_required_countersigner(self.cfg.required_countersigner().cloned())
+            .with_reject_far_stores(self.cfg.reject_far_stores())
+            .with_pin_allowlist(self.cfg.pin_allowlist().to_vec())
+            .with_clock_skew(self.clock_skew.clone())
+            .with_blocklist(self.blocklist.clone())
+            .with_socket_errors(self.socket_errors.clone())
+            .with_storage_events(self.storage_events.clone())
+            .with_prefer_low_rtt(self.cfg.prefer_low_rtt())
+            .with_capabilities(self.cfg.capabilities())
+            .with_bucket_refresh_interval(self.cfg.bucket_refresh_interval())
+            .with_lookup_cache_ttl(self.cfg.lookup_cache_ttl())
+            .with_privacy(self.cfg.privacy_mode().then(||
+                Duration::from_secs(self.cfg.privacy_rotation())
+            ))
+            .with_runtime(self.runtime.clone())
+            .with_concurrency_limits(ConcurrencyLimits {
+                max_active_tasks    : self.cfg.max_active_tasks(),
+                max_inflight_calls  : self.cfg.max_inflight_calls(),
+                max_task_calls      : self.cfg.max_task_calls(),
+            })
+            .with_command_queue_size(self.cfg.command_queue_size())
+            .with_receive_sockets(self.cfg.receive_sockets())
+            .with_routing_strategy(RoutingStrategy {
+                bucket_capacity     : self.cfg.bucket_capacity(),
+                home_split_levels   : self.cfg.home_split_levels(),
+                max_entries         : self.cfg.max_routing_entries(),
+            })
+            .with_socket_health(SocketHealthOptions {
+                recv_timeout: Duration::from_secs(self.cfg.socket_recv_timeout()),
+                stall_calls : self.cfg.socket_stall_calls(),
+            })
+            .with_send_shaper(SendShaperOptions {
+                rate    : self.cfg.send_rate(),
+                interval: Duration::from_millis(self.cfg.send_rate_interval()),
+                burst   : self.cfg.send_burst(),
+                pacing  : Duration::from_millis(self.cfg.send_pacing()),
+            });
+        #[cfg(feature = "testing")]
+        let options = options
+            .with_transport(self.transport.get().cloned())
+            .with_sim(self.sim.get().cloned());
+        #[cfg(feature = "wsgateway")]
+        let options = options.with_ws_gateway(self.cfg.ws_gateway().cloned());
+
 
+        let addr4 = self.cfg.host4().map(|host| (host, self.cfg.port4()));
+        let addr6 = self.cfg.host6().map(|host| (host, self.cfg.port6()));
 
-        let port  = self.cfg.port();
-        let host4 = self.cfg.host4();
-        let host6 = self.cfg.host6();
+        // A dual-stack node answers the wants of both families from either DHT.
+        let siblings = match addr4.is_some() && addr6.is_some() {
+            true  => Some(Arc::new(Siblings::new())),
+            false => None
+        };
+        let options = options.with_siblings(siblings);
 
-        let cb = async move|host: Option<&str> | {
-            if let Some(host) = host {
+        let cb = async move|network: Network, addr: Option<(&str, u16)> | {
+            if let Some((host, port)) = addr {
                 dht_verticle::deploy(
-                    options.clone(), Network::IPv4, host.into(), port
+                    options.clone(), network, host.into(), port
                 ).await.map(|v| Some(v))
             } else {
                 Ok(None)
@@ -355,8 +786,8 @@ impl Node {
         };
 
         let result = tokio::join!(
-            cb(host4),
-            cb(host6)
+            cb(Network::IPv4, addr4),
+            cb(Network::IPv6, addr6)
         );
 
         match result.0 {
@@ -374,7 +805,16 @@ impl Node {
             _ => {}
         }
 
+        #[cfg(feature = "wsgateway")]
+        if let Some(addr) = self.cfg.ws_gateway_listener() {
+            let listener = GatewayListener::bind(addr).await.map_err(|e| {
+                NetworkError::new(format!("Binding the DHT gateway listener at {addr} failed: {e}"))
+            })?;
+            *self.ws_gateway.lock().unwrap() = Some(listener);
+        }
+
         *self.running.lock().unwrap() = true;
+        self.status_events.emit(NodeStatusEvent::Started);
         info!("Kademlia node started.");
         Ok(())
     }
@@ -385,21 +825,22 @@ impl Node {
             return Ok(());
         }
         *self.running.lock().unwrap() = false;
+        #[cfg(feature = "wsgateway")]
+        drop(self.ws_gateway.lock().unwrap().take());
 
-        // Stop DHT verticles concurrently
+        // Stop DHT verticles concurrently, lookups still in progress may hold
+        // on to the clients and complete with an error.
         let dht4 = self.dht4.lock().unwrap().take();
         let dht6 = self.dht6.lock().unwrap().take();
         tokio::join!(
             async {
                 if let Some(dht) = dht4 {
-                    let mut c = Arc::try_unwrap(dht).ok().unwrap();
-                    let _ = c.stop().await;
+                    dht.stop().await;
                 }
             },
             async {
                 if let Some(dht) = dht6 {
-                    let mut c = Arc::try_unwrap(dht).ok().unwrap();
-                    let _ = c.stop().await;
+                    dht.stop().await;
                 }
             }
         );
@@ -411,16 +852,41 @@ impl Node {
         }
         self.storage.lock().unwrap().close();
 
+        self.status_events.emit(NodeStatusEvent::Stopped);
         info!("Kademlia node stopped.");
         logger::teardown();
 
         Ok(())
     }
 
+    // The runtime given to Node::with_runtime, if any.
+    pub fn runtime(&self) -> Option<&Handle> {
+        self.runtime.as_ref()
+    }
+
     pub fn id(&self) -> &Id {
         self.identity.id()
     }
 
+    // Writes the node key to a key file, sealed under the passphrase if
+    // given, to carry the identity to another machine. The file serves as
+    // the keyFile of a node there, or is read back by import_identity.
+    pub fn export_identity(&self, path: impl AsRef<Path>, passphrase: Option<&str>) -> Result<()> {
+        let identity = self.identity.identity();
+        key_file::write(path.as_ref(), identity.signature_keypair().private_key(), passphrase)
+    }
+
+    // The identity kept in a key file written by export_identity.
+    pub fn import_identity(path: impl AsRef<Path>, passphrase: Option<&str>) -> Result<signature::KeyPair> {
+        key_file::read(path.as_ref(), passphrase).map(signature::KeyPair::from)
+    }
+
+    // The directory of this node under the data directory, named after the
+    // instance, where all its files go.
+    pub fn storage_path(&self) -> &Path {
+        self.layout.root()
+    }
+
     pub fn node_info(&self) -> NodeInfo {
         let dht4 = self.dht4.lock().unwrap().clone();
         let dht6 = self.dht6.lock().unwrap().clone();
@@ -435,8 +901,24 @@ impl Node {
         ni.unwrap()
     }
 
+    // The address the DHT gateway of this node accepts connections at,
+    // None unless it is configured with a ws_gateway_listener.
+    #[cfg(feature = "wsgateway")]
+    pub fn ws_gateway_addr(&self) -> Option<std::net::SocketAddr> {
+        self.ws_gateway.lock().unwrap().as_ref().map(|l| l.local_addr())
+    }
+
+    // The node info advertised by the DHT of the given network.
+    pub fn node_info_of(&self, network: Network) -> Option<NodeInfo> {
+        let dht = match network {
+            Network::IPv4 => self.dht4.lock().unwrap().clone(),
+            Network::IPv6 => self.dht6.lock().unwrap().clone(),
+        };
+        dht.map(|dht| dht.ni())
+    }
+
     pub fn version(&self) -> String {
-        version::format_version(version::ver())
+        version::normalized_version(version::ver())
     }
 
     pub fn set_default_lookup_option(&self, option: LookupOption) {
@@ -486,6 +968,32 @@ impl Node {
         target: &Id,
         lookup_option: Option<LookupOption>
     ) -> Result<JointResult<NodeInfo>>
+    {
+        self.lookup_node(target, lookup_option, None).await
+    }
+
+    /// Like [`find_node`](Self::find_node), calling `on_progress` with
+    /// snapshots of the lookup as it goes, the last one
+    /// [finished](LookupProgress::is_finished). The callback runs on the
+    /// task of the caller, not on the DHT thread.
+    pub async fn find_node_with_progress(
+        &self,
+        target: &Id,
+        lookup_option: Option<LookupOption>,
+        on_progress: impl Fn(LookupProgress) + Send
+    ) -> Result<JointResult<NodeInfo>>
+    {
+        let (tx, rx) = mpsc::channel(PROGRESS_QUEUE_SIZE);
+        let lookup = self.lookup_node(target, lookup_option, Some(tx));
+        self.with_progress(lookup, rx, on_progress).await
+    }
+
+    async fn lookup_node(
+        &self,
+        target: &Id,
+        lookup_option: Option<LookupOption>,
+        progress: Option<ProgressSender>
+    ) -> Result<JointResult<NodeInfo>>
     {
         self.check_running()?;
 
@@ -494,7 +1002,7 @@ impl Node {
             let option  = self.option(lookup_option);
 
             if let Some(dht) = dht {
-                dht.find_node(target, option).await
+                dht.find_node(target, option, progress.clone()).await
             } else {
                 Ok(None)
             }
@@ -515,64 +1023,311 @@ impl Node {
         Ok(joint)
     }
 
+    // Like find_node, with two shortcuts tried before a lookup: a fresh routing
+    // table entry (any entry under Local and Conservative), then a ping to the
+    // hinted node, which must answer with the target id. The result tells
+    // which of them produced the node.
+    pub async fn find_node_with_hint(
+        &self,
+        target: &Id,
+        hint: Option<&NodeInfo>,
+        lookup_option: Option<LookupOption>
+    ) -> Result<JointResult<NodeInfo>>
+    {
+        if let Some(hint) = hint {
+            if hint.id() != target {
+                return Err(ArgumentError::new(format!(
+                    "Hint {} does not match the target {}", hint, target
+                )));
+            }
+        }
+        self.check_running()?;
+
+        let option = self.option(lookup_option);
+        let cb = async move |dht: Option<Arc<VerticleClient>>| {
+            let Some(dht) = dht else {
+                return Ok(None);
+            };
+            let network = dht.ni().network();
+            let hint = hint.filter(|ni| ni.network() == network).cloned();
+            dht.find_node_with_hint(*target, hint, option).await
+        };
+
+        let dht4 = self.dht4.lock().unwrap().clone();
+        let dht6 = self.dht6.lock().unwrap().clone();
+
+        let result = tokio::select!(
+            v = cb(dht4), if dht4.is_some() => (Network::IPv4, v),
+            v = cb(dht6), if dht6.is_some() => (Network::IPv6, v),
+        );
+
+        let mut joint = JointResult::<NodeInfo>::new();
+        if let Some((ni, source)) = result.1? {
+            joint.set_value_from(result.0, ni, source);
+        }
+        Ok(joint)
+    }
+
+    // Nodes from the local routing table closest to the target by XOR distance,
+    // no network lookup is performed.
+    pub async fn closest_nodes(
+        &self,
+        target: &Id,
+        count: usize,
+        network: Option<Network>,
+        include_self: bool
+    ) -> Result<Vec<NodeInfo>> {
+        if count == 0 {
+            return Err(ArgumentError::new("Invalid count: 0, must be larger than 0"));
+        }
+        self.check_running()?;
+
+        let dht4 = self.dht4.lock().unwrap().clone();
+        let dht6 = self.dht6.lock().unwrap().clone();
+
+        let (dht4, dht6) = match network {
+            Some(Network::IPv4) if dht4.is_none() => {
+                return Err(ArgumentError::new("DHT/IPv4 is not enabled"));
+            },
+            Some(Network::IPv6) if dht6.is_none() => {
+                return Err(ArgumentError::new("DHT/IPv6 is not enabled"));
+            },
+            Some(Network::IPv4) => (dht4, None),
+            Some(Network::IPv6) => (None, dht6),
+            None => (dht4, dht6),
+        };
+
+        let cb = async move |dht: Option<Arc<VerticleClient>>| {
+            if let Some(dht) = dht {
+                dht.closest_nodes(*target, count, include_self).await
+            } else {
+                Ok(Vec::new())
+            }
+        };
+
+        let result = tokio::join!(
+            cb(dht4),
+            cb(dht6)
+        );
+
+        let mut nodes = result.0?;
+        nodes.extend(result.1?);
+        nodes.sort_by(|a, b| target.three_way_compare(a.id(), b.id()));
+        nodes.truncate(count);
+        Ok(nodes)
+    }
+
+    /// Looks up the value stored by [`crate::SignedBuilder::world_writable`] under `key`
+    /// in the application `namespace`.
+    pub async fn find_value_by_key(
+        &self,
+        namespace: &Id,
+        key: &str,
+        expected_seq: i32,
+        lookup_option: Option<LookupOption>
+    ) -> Result<Option<Value>>
+    {
+        self.find_value(&Value::id_for_key(namespace, key), expected_seq, lookup_option).await
+    }
+
+    /// Claims `name` for `target` on behalf of the owner of the key pair,
+    /// valid for [`NameRecord::DEFAULT_LIFETIME`]. See
+    /// [`register_name_until`](Self::register_name_until).
+    pub async fn register_name(
+        &self,
+        name: &str,
+        target: &Id,
+        keypair: &signature::KeyPair
+    ) -> Result<NameRecord>
+    {
+        let expires = self.clock.now_ms() + NameRecord::DEFAULT_LIFETIME.as_millis() as u64;
+        self.register_name_until(name, target, keypair, expires).await
+    }
+
+    /// Adds the claim of the key pair owner on `name` to the claims found for
+    /// it, valid until `expires` in milliseconds since the Unix epoch, and
+    /// stores them again. A claim of the owner already there is replaced by
+    /// the new one with its sequence number bumped, keeping its place. The
+    /// expired and invalid claims are dropped meanwhile. The nodes storing
+    /// the claims merge them into those they hold, so the claims of others
+    /// stored meanwhile are kept.
+    pub async fn register_name_until(
+        &self,
+        name: &str,
+        target: &Id,
+        keypair: &signature::KeyPair,
+        expires: u64
+    ) -> Result<NameRecord>
+    {
+        let name = name_record::normalize_name(name)?;
+        let now = self.clock.now_ms();
+        if expires <= now {
+            return Err(ArgumentError::new("The name claim would be expired already"));
+        }
+
+        let current = self.find_value_by_key(&NAME_NAMESPACE, &name, -1, Some(LookupOption::Conservative)).await?;
+        let mut claims = current.as_ref()
+            .map(|v| name_record::decode_claims(&name, v.data(), now))
+            .unwrap_or_default();
+
+        let owner = Id::from(keypair.public_key());
+        let position = claims.iter().position(|c| c.owner() == &owner);
+        let seq = position.map_or(0, |i| claims[i].sequence_number() + 1);
+        let record = NameRecord::new(&name, target, seq, expires, keypair)?;
+        match position {
+            Some(i) => claims[i] = record.clone(),
+            None if claims.len() >= NameRecord::MAX_CLAIMS => {
+                return Err(StateError::new(format!(
+                    "The name '{name}' has {} claims already", claims.len()
+                )));
+            },
+            None => claims.push(record.clone()),
+        }
+
+        let data = name_record::encode_claims(&claims)?;
+        let value = SignedBuilder::world_writable(&NAME_NAMESPACE, &name, &data)
+            .with_sequence_number(current.map_or(0, |v| v.sequence_number() + 1))
+            .build()?;
+        self.store_value(&value, -1, true).await?;
+        Ok(record)
+    }
+
+    /// Looks up the claims on `name`, in the order they were first made.
+    /// Every claim signed by its owner and unexpired is returned, choosing
+    /// between conflicting ones is up to the application.
+    pub async fn resolve_name(&self, name: &str) -> Result<Vec<NameClaim>> {
+        let name = name_record::normalize_name(name)?;
+        let found = self.find_value_by_key(&NAME_NAMESPACE, &name, -1, Some(LookupOption::Conservative)).await?;
+        Ok(found.map(|v| name_record::resolve_claims(&name, v.data(), self.clock.now_ms()))
+            .unwrap_or_default())
+    }
+
     pub async fn find_value(
         &self,
         value_id: &Id,
         expected_seq: i32,
         lookup_option: Option<LookupOption>
     ) -> Result<Option<Value>>
+    {
+        self.find_value_detailed(value_id, expected_seq, lookup_option).await
+            .map(|v| v.map(ValueResult::into_value))
+    }
+
+    /// Like [`find_value`](Self::find_value), also telling how old the value
+    /// is and which nodes returned it.
+    pub async fn find_value_detailed(
+        &self,
+        value_id: &Id,
+        expected_seq: i32,
+        lookup_option: Option<LookupOption>
+    ) -> Result<Option<ValueResult>>
+    {
+        let options = LookupOptionsEx::new(lookup_option, None);
+        self.find_value_ex(value_id, expected_seq, options).await
+            .map(LookupOutcome::into_result)
+    }
+
+    /// Like [`find_value_detailed`](Self::find_value_detailed), ended by
+    /// the timeout of the options if any. A lookup ended that way returns
+    /// the best value found by then, or none, not completed.
+    pub async fn find_value_ex(
+        &self,
+        value_id: &Id,
+        expected_seq: i32,
+        options: LookupOptionsEx
+    ) -> Result<LookupOutcome<Option<ValueResult>>>
+    {
+        self.lookup_value(value_id, expected_seq, options, None).await
+    }
+
+    /// Like [`find_value`](Self::find_value), calling `on_progress` with
+    /// snapshots of the lookup as it goes, the last one
+    /// [finished](LookupProgress::is_finished). The callback runs on the
+    /// task of the caller, not on the DHT thread. A lookup of the same value
+    /// already running is joined, only its last snapshot is seen then.
+    pub async fn find_value_with_progress(
+        &self,
+        value_id: &Id,
+        expected_seq: i32,
+        lookup_option: Option<LookupOption>,
+        on_progress: impl Fn(LookupProgress) + Send
+    ) -> Result<Option<Value>>
+    {
+        let (tx, rx) = mpsc::channel(PROGRESS_QUEUE_SIZE);
+        let options = LookupOptionsEx::new(lookup_option, None);
+        let lookup = self.lookup_value(value_id, expected_seq, options, Some(tx));
+        self.with_progress(lookup, rx, on_progress).await
+            .map(|v| v.into_result().map(ValueResult::into_value))
+    }
+
+    async fn lookup_value(
+        &self,
+        value_id: &Id,
+        expected_seq: i32,
+        options: LookupOptionsEx,
+        progress: Option<ProgressSender>
+    ) -> Result<LookupOutcome<Option<ValueResult>>>
     {
         if expected_seq < -1 {
             return Err(ArgumentError::new(format!(
                 "Invalid expected sequence number: {expected_seq}, must be larger than or equal to -1")));
         }
+        Self::check_timeout(options.timeout)?;
 
         self.check_running()?;
 
         let target  = value_id.clone();
-        let option  = self.option(lookup_option);
+        let option  = self.option(options.option);
+        let timeout = options.timeout;
         let dht4    = self.dht4.lock().unwrap().clone();
         let dht6    = self.dht6.lock().unwrap().clone();
 
         let mut ev = EligibleValue::new(target, expected_seq);
 
-        let value = self.storage.lock().unwrap().get_value(&target)?;
+        let value = self.storage_result("get_value",
+            self.storage.lock().unwrap().get_value(&target)
+        )?;
         if let Some(v) = value {
             let is_mutable = v.is_mutable();
-            ev.update(v, false);
+            let updated = self.storage.lock().unwrap().get_value_updated(&target);
+            ev.update(v, false, self.local_origins(updated));
 
             if !is_mutable {
-                return Ok(ev.value());
+                return Ok(LookupOutcome::new(ev.result(), true));
             }
             if option != LookupOption::Conservative && !ev.is_empty() {
-                return Ok(ev.value());
+                return Ok(LookupOutcome::new(ev.result(), true));
             }
         }
 
         let cb = async move |dht: Option<Arc<VerticleClient>>| {
             if let Some(dht) = dht {
-                dht.find_value(target, expected_seq, option).await
+                dht.find_value(target, expected_seq, option, timeout, progress.clone()).await
             } else {
-                Ok(None)
+                Ok(LookupOutcome::new(None, true))
             }
         };
 
         let rc = tokio::select!(
             v = cb(dht4), if dht4.is_some() => v,
             v = cb(dht6), if dht6.is_some() => v,
-        );
+        )?;
 
-        if let Some(value) = rc? {
-            ev.update(value, true);
+        let completed = rc.completed();
+        if let Some(result) = rc.into_result() {
+            let (value, origins) = result.into_parts();
+            ev.update(value, true, origins);
         }
 
         if !ev.is_empty() && ev.is_latest() {
-            let _ = self.storage.lock().unwrap().put_value(
-                ev.value().unwrap(), false
-            );
+            // Cached like a stored value, the required counter-signature included.
+            let value = ev.value().unwrap();
+            if self.cfg.required_countersigner().is_none_or(|signer| value.verify_countersignature(signer)) {
+                let _ = self.storage.lock().unwrap().put_value(value, false);
+            }
         }
 
-        Ok(ev.value())
+        Ok(LookupOutcome::new(ev.result(), completed))
     }
 
     pub async fn find_peer(
@@ -582,6 +1337,126 @@ impl Node {
         expected_count: usize,
         lookup_option: Option<LookupOption>
     ) -> Result<Vec<PeerInfo>>
+    {
+        self.find_peer_detailed(peer_id, expected_seq, expected_count, lookup_option).await
+            .map(|v| v.into_iter().map(PeerResult::into_peer).collect())
+    }
+
+    /// Like [`find_peer`](Self::find_peer), also telling how old each peer
+    /// is and which nodes returned it.
+    pub async fn find_peer_detailed(
+        &self,
+        peer_id: &Id,
+        expected_seq: i32,
+        expected_count: usize,
+        lookup_option: Option<LookupOption>
+    ) -> Result<Vec<PeerResult>>
+    {
+        let options = LookupOptionsEx::new(lookup_option, None);
+        self.lookup_peers(peer_id, Vec::new(), None, expected_seq, expected_count, options, None).await
+            .map(LookupOutcome::into_result)
+    }
+
+    /// Like [`find_peer_detailed`](Self::find_peer_detailed), ended by the
+    /// timeout of the options if any. A lookup ended that way returns the
+    /// peers found by then, not completed.
+    pub async fn find_peer_ex(
+        &self,
+        peer_id: &Id,
+        expected_seq: i32,
+        expected_count: usize,
+        options: LookupOptionsEx
+    ) -> Result<LookupOutcome<Vec<PeerResult>>>
+    {
+        self.lookup_peers(peer_id, Vec::new(), None, expected_seq, expected_count, options, None).await
+    }
+
+    /// Like [`find_peer`](Self::find_peer), only the peers announced with
+    /// all the tags are returned. Nodes filter them when they can, the
+    /// peers from older nodes are filtered locally.
+    pub async fn find_peer_tagged(
+        &self,
+        peer_id: &Id,
+        required_tags: &[&str],
+        expected_seq: i32,
+        expected_count: usize,
+        lookup_option: Option<LookupOption>
+    ) -> Result<Vec<PeerInfo>>
+    {
+        if required_tags.len() > PeerInfo::MAX_TAGS {
+            return Err(ArgumentError::new(format!(
+                "Invalid tags: {}, at most {} can be required", required_tags.len(), PeerInfo::MAX_TAGS)));
+        }
+        let tags = required_tags.iter().map(|v| v.nfc().collect::<String>()).collect();
+        let options = LookupOptionsEx::new(lookup_option, None);
+        self.lookup_peers(peer_id, tags, None, expected_seq, expected_count, options, None).await
+            .map(|v| v.into_result().into_iter().map(PeerResult::into_peer).collect())
+    }
+
+    /// Like [`find_peer`](Self::find_peer), only the peers announced with
+    /// an endpoint of `network` are returned when given, as told by
+    /// [`PeerInfo::network`]. Peers with an endpoint named by a host name are
+    /// returned either way. Nodes filter them when they can, the peers from
+    /// older nodes are filtered locally.
+    pub async fn find_peer_filtered(
+        &self,
+        peer_id: &Id,
+        expected_seq: i32,
+        expected_count: usize,
+        lookup_option: Option<LookupOption>,
+        network: Option<Network>
+    ) -> Result<Vec<PeerInfo>>
+    {
+        let options = LookupOptionsEx::new(lookup_option, None);
+        self.lookup_peers(peer_id, Vec::new(), network, expected_seq, expected_count, options, None).await
+            .map(|v| v.into_result().into_iter().map(PeerResult::into_peer).collect())
+    }
+
+    /// Like [`find_peer`](Self::find_peer), calling `on_progress` with
+    /// snapshots of the lookup as it goes, the last one
+    /// [finished](LookupProgress::is_finished). The callback runs on the
+    /// task of the caller, not on the DHT thread.
+    pub async fn find_peer_with_progress(
+        &self,
+        peer_id: &Id,
+        expected_seq: i32,
+        expected_count: usize,
+        lookup_option: Option<LookupOption>,
+        on_progress: impl Fn(LookupProgress) + Send
+    ) -> Result<Vec<PeerInfo>>
+    {
+        let (tx, rx) = mpsc::channel(PROGRESS_QUEUE_SIZE);
+        let options = LookupOptionsEx::new(lookup_option, None);
+        let lookup = self.lookup_peers(peer_id, Vec::new(), None, expected_seq, expected_count, options, Some(tx));
+        self.with_progress(lookup, rx, on_progress).await
+            .map(|v| v.into_result().into_iter().map(PeerResult::into_peer).collect())
+    }
+
+    /// Looks up the instances of a service announced under `peer_id` and
+    /// picks one of them at random by their weights, as
+    /// [`PeerSelector::weighted_choice`] does. None if none was found, or
+    /// all of them take no load.
+    pub async fn find_peer_one(
+        &self,
+        peer_id: &Id,
+        lookup_option: Option<LookupOption>
+    ) -> Result<Option<PeerInfo>>
+    {
+        let peers = self.find_peer(peer_id, -1, MAX_PEER_CHOICES, lookup_option).await?;
+        Ok(PeerSelector::weighted_choice(&peers, &mut rand::rng()).cloned())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn lookup_peers(
+        &self,
+        peer_id: &Id,
+        tags: Vec<String>,
+        network: Option<Network>,
+        expected_seq: i32,
+        expected_count: usize,
+        options: LookupOptionsEx,
+        progress: Option<ProgressSender>
+    ) -> Result<LookupOutcome<Vec<PeerResult>>>
     {
         if expected_seq < -1 {
             return Err(ArgumentError::new(format!(
@@ -591,52 +1466,129 @@ impl Node {
             return Err(ArgumentError::new(format!(
                 "Invalid expected count: {expected_count}, must be larger than 0")));
         }
+        Self::check_timeout(options.timeout)?;
         self.check_running()?;
 
         let target  = peer_id.clone();
-        let option  = self.option(lookup_option);
+        let option  = self.option(options.option);
+        let timeout = options.timeout;
         let dht4    = self.dht4.lock().unwrap().clone();
         let dht6    = self.dht6.lock().unwrap().clone();
 
         let mut ep = EligiblePeers::new(
-            target, expected_seq, expected_count);
-
-        let peers = self.storage.lock().unwrap().get_peers_with_expected_seq(
-                &target, expected_seq, expected_count as i32)?;
+            target, expected_seq, expected_count)
+            .with_tags(tags.clone())
+            .with_network(network);
+
+        // All of them when filtering, the first ones stored may not match.
+        let limit = match tags.is_empty() && network.is_none() {
+            true => expected_count as i32,
+            false => i32::MAX,
+        };
+        let peers = self.storage_result("get_peers",
+            self.storage.lock().unwrap().get_peers_with_expected_seq(
+                &target, expected_seq, limit)
+        )?;
 
+        let peers = {
+            let storage = self.storage.lock().unwrap();
+            peers.into_iter().map(|p| {
+                let updated = storage.get_peer_updated(p.id(), p.fingerprint());
+                (p, self.local_origins(updated))
+            }).collect()
+        };
         ep.add(peers, false);
         ep.prune();
 
         if !ep.is_empty() {
             if option == LookupOption::Local {
-                return Ok(ep.peers())
+                return Ok(LookupOutcome::new(ep.results(), true))
             }
             if option  != LookupOption::Conservative &&
                 expected_seq >= 0 && ep.reached_capacity() {
-                return Ok(ep.peers())
+                return Ok(LookupOutcome::new(ep.results(), true))
             }
         }
 
         let cb = async move |dht: Option<Arc<VerticleClient>>| {
             if let Some(dht) = dht {
-                dht.find_peer(target, expected_seq, expected_count, option).await
+                dht.find_peer(target, expected_seq, expected_count, tags.clone(), network, option, timeout, progress.clone()).await
             } else {
-                Ok(Vec::new())
+                Ok(LookupOutcome::new(Vec::new(), true))
             }
         };
 
         let rc = tokio::select!(
             v = cb(dht4), if dht4.is_some() => v,
             v = cb(dht6), if dht6.is_some() => v,
-        );
+        )?;
 
-        ep.add(rc?, true);
+        let completed = rc.completed();
+        ep.add(rc.into_result().into_iter().map(PeerResult::into_parts).collect(), true);
         ep.prune();
 
         if !ep.is_empty() && ep.is_latest() {
             let _ = self.storage.lock().unwrap().put_peers(ep.peers());
         }
-        Ok(ep.peers())
+        Ok(LookupOutcome::new(ep.results(), completed))
+    }
+
+    // Hands the snapshots of the lookup to the callback until it completes.
+    // Each network ends with a finished snapshot, made up from the last one
+    // seen if the task sent none, a lookup answered from the local storage
+    // with an empty one.
+    async fn with_progress<T>(
+        &self,
+        lookup: impl Future<Output = Result<T>>,
+        mut rx: mpsc::Receiver<LookupProgress>,
+        on_progress: impl Fn(LookupProgress)
+    ) -> Result<T>
+    {
+        let mut last: Vec<LookupProgress> = Vec::new();
+        let mut seen = |progress: LookupProgress| {
+            last.retain(|v| v.network() != progress.network());
+            last.push(progress.clone());
+            on_progress(progress);
+        };
+
+        tokio::pin!(lookup);
+        let result = loop {
+            tokio::select! {
+                result = &mut lookup => break result,
+                Some(progress) = rx.recv() => seen(progress),
+            }
+        };
+        while let Ok(progress) = rx.try_recv() {
+            seen(progress);
+        }
+
+        if last.is_empty() {
+            let network = match self.dht4.lock().unwrap().is_some() {
+                true => Network::IPv4,
+                false => Network::IPv6,
+            };
+            last.push(LookupProgress::new(network));
+        }
+        for progress in last.into_iter().filter(|v| !v.is_finished()) {
+            on_progress(progress.into_finished());
+        }
+        result
+    }
+
+    fn check_timeout(timeout: Option<Duration>) -> Result<()> {
+        match timeout {
+            Some(v) if v.is_zero() => Err(ArgumentError::new("Invalid lookup timeout: 0, must be longer than zero")),
+            _ => Ok(()),
+        }
+    }
+
+    // The local node as the source of a stored record, aged from the
+    // updated time kept in storage.
+    fn local_origins(&self, updated: Result<Option<u64>>) -> Origins {
+        let age = updated.ok().flatten().map(|t| {
+            self.clock.now_ms().saturating_sub(t) / 1000
+        });
+        Origins::new(*self.id(), age)
     }
 
     pub async fn store_value(
@@ -649,6 +1601,12 @@ impl Node {
         if !value.is_valid() {
             return Err(ArgumentError::new("The value failed validation."));
         }
+        if let Some(signer) = self.cfg.required_countersigner() {
+            if !value.verify_countersignature(signer) {
+                return Err(ArgumentError::new(format!(
+                    "The value is not counter-signed by the required countersigner {signer}")));
+            }
+        }
         if expected_seq < -1 {
             return Err(ArgumentError::new(format!(
                 "Invalid expected sequence number: {expected_seq}, must be larger than or equal to -1")));
@@ -656,13 +1614,23 @@ impl Node {
         self.check_running()?;
 
         let value_id = value.id();
-        let result = self.storage.lock().unwrap().get_value(&value_id)?;
-        if let Some(ref existing) = result {
+        let result = self.storage_result("get_value",
+            self.storage.lock().unwrap().get_value(&value_id)
+        )?;
+        // The claims on a name are merged into those held, never replaced.
+        let merged = name_record::merge_claims(result.as_ref(), value, self.clock.now_ms());
+        if let Some(existing) = result.as_ref().filter(|_| merged.is_none()) {
             let _  = check_value_validity(existing, value, expected_seq)?;
         };
 
-        // store the value in local node.
-        self.storage.lock().unwrap().put_value(value.clone(), persistent)?;
+        // store the value in local node, others find it here right away.
+        let local = merged.as_ref().unwrap_or(value);
+        self.storage_result("put_value",
+            self.storage.lock().unwrap().put_local_value(local.clone(), persistent)
+        )?;
+        self.storage_events.emit(StorageEvent::value_stored(
+            local, result.map(|v| v.sequence_number()), true
+        ));
 
         // store the value to the network.
         let dht4 = self.dht4.lock().unwrap().clone();
@@ -674,7 +1642,7 @@ impl Node {
             if let Some(dht) = dht {
                 dht.store_value(value, expected_seq).await
             } else {
-                Ok(())
+                Ok(0)
             }
         };
         let result = tokio::join!(
@@ -682,14 +1650,59 @@ impl Node {
             cb(dht6),
         );
 
+        let mut acks = 0;
         for item in [result.0, result.1] {
-            item?;
+            acks += item?;
+        }
+
+        // The local copy stays the authoritative one until enough nodes
+        // stored the value, it is stored again later.
+        if acks < self.cfg.min_store_acks() {
+            warn!("Value {value_id} stored by {acks} nodes only, served locally and stored again later");
+            self.events.record(NodeEventKind::StoreUnconfirmed { value_id, acks });
+            self.unconfirmed.lock().unwrap().insert(value_id, persistent);
+            return Ok(());
         }
 
+        self.unconfirmed.lock().unwrap().remove(&value_id);
         let _ = self.storage.lock().unwrap().update_value_announced_time(&value_id);
         Ok(())
     }
 
+    // Asks the given nodes to store the value however far it is from them,
+    // which they only do when this node is on their pin allowlist. Each node
+    // is sent the store request directly with the token it hands out, the
+    // result tells per node whether it stored the value.
+    pub async fn pin_value_on(
+        &self,
+        value: &Value,
+        nodes: &[NodeInfo]
+    ) -> Result<Vec<(Id, Result<()>)>> {
+        if !value.is_valid() {
+            return Err(ArgumentError::new("The value failed validation."));
+        }
+        if let Some(signer) = self.cfg.required_countersigner() {
+            if !value.verify_countersignature(signer) {
+                return Err(ArgumentError::new(format!(
+                    "The value is not counter-signed by the required countersigner {signer}")));
+            }
+        }
+        self.check_running()?;
+
+        let pins = nodes.iter().map(|node| async move {
+            let dht = match node.network() {
+                Network::IPv4 => self.dht4.lock().unwrap().clone(),
+                Network::IPv6 => self.dht6.lock().unwrap().clone(),
+            };
+            let result = match dht {
+                Some(dht) => dht.pin_value(node.clone(), value.clone()).await,
+                None => Err(StateError::new(format!("No {} DHT to reach {}", node.network(), node))),
+            };
+            (*node.id(), result)
+        });
+        Ok(futures::future::join_all(pins).await)
+    }
+
     pub async fn announce_peer(
         &self,
         peer: &PeerInfo,
@@ -705,8 +1718,21 @@ impl Node {
         }
         self.check_running()?;
 
-        let result = self.storage.lock().unwrap().get_peer(
-            peer.id(), peer.fingerprint()
+        // Other nodes take an announcement signed long ago for a replay,
+        // the peers owned here are signed again before going out.
+        let now = self.clock.now_ms().saturating_add_signed(self.clock_skew.compensation());
+        let restamped;
+        let peer = match peer.announced() {
+            Some(t) if t + RE_ANNOUNCE_INTERVAL > now => peer,
+            _ if peer.has_private_key() => {
+                restamped = peer.restamped(now)?;
+                &restamped
+            },
+            _ => peer,
+        };
+
+        let result = self.storage_result("get_peer",
+            self.storage.lock().unwrap().get_peer(peer.id(), peer.fingerprint())
         )?;
 
         // check the peer validity.
@@ -715,7 +1741,10 @@ impl Node {
         }
 
         // store the new peer locally.
-        self.storage.lock().unwrap().put_peer(peer.clone(), persistent)?;
+        self.storage_result("put_peer",
+            self.storage.lock().unwrap().put_peer(peer.clone(), persistent)
+        )?;
+        self.storage_events.emit(StorageEvent::peer_announced(peer, *self.id(), true));
 
         // announce the peer to the network.
         let dht4 = self.dht4.lock().unwrap().clone();
@@ -727,7 +1756,7 @@ impl Node {
             if let Some(dht) = dht {
                 dht.announce_peer(peer, expected_seq).await
             } else {
-                Ok(())
+                Ok(None)
             }
         };
 
@@ -735,8 +1764,16 @@ impl Node {
             cb(dht4),
             cb(dht6),
         );
+        let mut observed = None;
         for item in [result.0, result.1] {
-            item?;
+            observed = observed.or(item?);
+        }
+
+        // Served from here as well, the same as the other nodes do.
+        if observed.is_some() {
+            self.storage_result("put_peer",
+                self.storage.lock().unwrap().put_peer(peer.clone().with_observed(observed), persistent)
+            )?;
         }
 
         let _ = self.storage.lock().unwrap()
@@ -746,32 +1783,439 @@ impl Node {
 
     pub fn value(&self, value_id: Id) -> Result<Option<Value>> {
         self.check_running()?;
-        crate::locked!(self.storage).get_value(&value_id)
+        let result = crate::locked!(self.storage).get_value(&value_id);
+        self.storage_result("get_value", result)
     }
 
     pub fn remove_value(&self, value_id: Id) -> Result<()> {
         self.check_running()?;
-        crate::locked!(self.storage).remove_value(&value_id)
+        let result = crate::locked!(self.storage).remove_value(&value_id);
+        self.storage_result("remove_value", result)
     }
 
     pub async fn peers(&self, peer_id: Id) -> Result<Vec<PeerInfo>> {
         self.check_running()?;
-        crate::locked!(self.storage).get_peers(&peer_id)
+        let result = crate::locked!(self.storage).get_peers(&peer_id);
+        self.storage_result("get_peers", result)
     }
 
     pub async fn remove_peers(&self, peer_id: Id) -> Result<()> {
         self.check_running()?;
-        crate::locked!(self.storage).remove_peers(&peer_id)
+        let result = crate::locked!(self.storage).remove_peers(&peer_id);
+        self.storage_result("remove_peers", result)
     }
 
     pub async fn peer(&self, peer_id: Id, finger_print: u64) -> Result<Option<PeerInfo>> {
         self.check_running()?;
-        crate::locked!(self.storage).get_peer(&peer_id, finger_print)
+        let result = crate::locked!(self.storage).get_peer(&peer_id, finger_print);
+        self.storage_result("get_peer", result)
     }
 
     pub async fn remove_peer(&self, peer_id: Id, finger_print: u64) -> Result<()> {
         self.check_running()?;
-        crate::locked!(self.storage).remove_peer(&peer_id, finger_print)
+        let result = crate::locked!(self.storage).remove_peer(&peer_id, finger_print);
+        self.storage_result("remove_peer", result)
+    }
+
+    // The journal file the periodic stats samples are appended to,
+    // None if the stats journal is disabled in the configuration.
+    pub fn stats_journal_path(&self) -> Option<PathBuf> {
+        self.stats_journal.as_ref().map(|journal| {
+            journal.lock().unwrap().path().to_path_buf()
+        })
+    }
+
+    // The most recent `limit` node events, oldest first.
+    // Sends a vendor extension request to the target node and returns the raw
+    // payload of its extension response.
+    pub async fn send_extension(&self,
+        target: &NodeInfo,
+        payload: Vec<u8>,
+        timeout: Duration
+    ) -> Result<Vec<u8>> {
+        if payload.len() > MAX_EXTENSION_PAYLOAD {
+            return Err(ArgumentError::new(format!(
+                "Extension payload too large: {} bytes, at most {}", payload.len(), MAX_EXTENSION_PAYLOAD
+            )));
+        }
+        self.check_running()?;
+
+        let dht = match target.network() {
+            Network::IPv4 => self.dht4.lock().unwrap().clone(),
+            Network::IPv6 => self.dht6.lock().unwrap().clone(),
+        };
+        let Some(dht) = dht else {
+            return Err(StateError::new(format!("No {} DHT to reach {}", target.network(), target)));
+        };
+
+        match tokio::time::timeout(timeout, dht.send_extension(target.clone(), payload)).await {
+            Ok(result) => result,
+            Err(_) => Err(NetworkError::new(format!("Extension request to {} timed out", target))),
+        }
+    }
+
+    /// Asks `target` alone for the nodes it knows closest to `lookup_id`,
+    /// without a lookup around it. The answer carries the version the node
+    /// runs and the round trip time of the request. Nothing is stored or
+    /// announced, which makes it fit for surveying the network.
+    pub async fn query_node(&self,
+        target: &NodeInfo,
+        lookup_id: &Id,
+        timeout: Duration
+    ) -> Result<NodeQuery> {
+        if target.id() == self.id() {
+            return Err(ArgumentError::new("Cannot query the local node"));
+        }
+        self.check_running()?;
+
+        let dht = match target.network() {
+            Network::IPv4 => self.dht4.lock().unwrap().clone(),
+            Network::IPv6 => self.dht6.lock().unwrap().clone(),
+        };
+        let Some(dht) = dht else {
+            return Err(StateError::new(format!("No {} DHT to reach {}", target.network(), target)));
+        };
+
+        match tokio::time::timeout(timeout, dht.query_node(target.clone(), *lookup_id)).await {
+            Ok(result) => result,
+            Err(_) => Err(NetworkError::new(format!("Find node request to {} timed out", target))),
+        }
+    }
+
+    pub fn set_extension_handler(&self, handler: ExtensionHandler) {
+        *self.extension_handler.lock().unwrap() = Some(handler);
+    }
+
+    // Sets up a direct UDP path to the target node by hole punching. The
+    // target is found through the DHT, both sides exchange their probe
+    // addresses over a rendezvous request and then probe each other until
+    // the path is verified in both directions. No application data is
+    // carried, the result hands over the socket and the remote address.
+    pub async fn connect_direct(&self,
+        target: &Id,
+        timeout: Duration
+    ) -> Result<PunchResult> {
+        if target == self.id() {
+            return Err(ArgumentError::new("Cannot connect directly to the local node"));
+        }
+        self.check_running()?;
+
+        let started = tokio::time::Instant::now();
+        let deadline = started + timeout;
+        let found = until(deadline, target, "finding the node", self.find_node_with_hint(target, None, None)).await?;
+        let (ni, dht) = [Network::IPv4, Network::IPv6].into_iter().find_map(|network| {
+            let ni = found.value(network)?;
+            let dht = match network {
+                Network::IPv4 => self.dht4.lock().unwrap().clone(),
+                Network::IPv6 => self.dht6.lock().unwrap().clone(),
+            }?;
+            Some((ni.clone(), dht))
+        }).ok_or_else(|| NetworkError::new(format!("Node {} not found", target)))?;
+
+        let ip = dht.ni().ip();
+        let socket = tokio::net::UdpSocket::bind(SocketAddr::new(ip, 0)).await?;
+        let session = u64::from_be_bytes(crate::random_array::<8>());
+        let local = Rendezvous::new(session, socket.local_addr()?.port(), &hole_punch::local_hosts(ip));
+
+        let remote = until(deadline, target, "at the rendezvous", dht.rendezvous(ni.clone(), local)).await?;
+        if remote.session() != session {
+            return Err(ProtocolError::new(format!("Rendezvous session mismatch from {}", ni)));
+        }
+
+        let pattern = self.direct_connections.lock().unwrap().pattern.clone();
+        let candidates = hole_punch::candidates(
+            &remote.hosts(),
+            Some(ni.ip()),
+            remote.port(),
+            &pattern,
+            ip.is_ipv4()
+        );
+        debug!("Probing {} at {:?}", target, candidates);
+
+        let (addr, rtt) = until(deadline, target, "probing", hole_punch::punch(&socket, session, &candidates, &pattern)).await?;
+        let result = PunchResult::new(*target, socket, addr, rtt)?;
+        info!("Direct connection set up in {:?}: {}", started.elapsed(), result);
+        Ok(result)
+    }
+
+    // Accepts direct connections set up by other nodes, the handler is called
+    // with each verified one.
+    pub fn set_direct_connection_handler(&self, handler: DirectConnectionHandler) {
+        self.direct_connections.lock().unwrap().handler = Some(Arc::new(handler));
+    }
+
+    // Calls the handler with the bind, send and receive failures of the DHT
+    // sockets and their rebinds, on the DHT threads. Send and receive
+    // failures beyond 10 a second are only counted. It replaces the handler
+    // set before.
+    pub fn set_socket_error_handler(&self, handler: SocketErrorHandler) {
+        self.socket_errors.set_handler(handler);
+    }
+
+    // How probes are sent in both directions of direct connection attempts.
+    pub fn set_probe_pattern(&self, pattern: ProbePattern) {
+        self.direct_connections.lock().unwrap().pattern = pattern;
+    }
+
+    // Calls the listener with the changes of the stored values and peers,
+    // on a thread of its own. It replaces the listener set before.
+    pub fn set_storage_listener(&self, listener: StorageListener) {
+        self.storage_events.set_listener(listener);
+    }
+
+    // Storage events dropped as the listener fell behind.
+    pub fn dropped_storage_events(&self) -> u64 {
+        self.storage_events.dropped()
+    }
+
+    // Removes the values and peers past their expiry right away instead
+    // of waiting for the periodic cleanup.
+    pub fn expire_storage(&self) {
+        let expired = self.storage.lock().unwrap().purge();
+        for id in expired.values {
+            self.storage_events.emit(StorageEvent::ValueExpired { id });
+        }
+        for (id, fingerprint) in expired.peers {
+            self.storage_events.emit(StorageEvent::PeerExpired { id, fingerprint });
+        }
+
+        let mut storage = self.storage.lock().unwrap();
+        let expired = self.origin_checks.take_expired(|id, fingerprint| {
+            storage.get_peer_updated(id, fingerprint).ok().flatten()
+        });
+        for (id, fingerprint) in expired {
+            if let Err(e) = storage.remove_peer(&id, fingerprint) {
+                warn!("Failed to remove peer {id} of a gone origin: {e}");
+                continue;
+            }
+            debug!("Removed peer {id} early, its origin node is gone");
+            self.storage_events.emit(StorageEvent::PeerExpired { id, fingerprint });
+        }
+    }
+
+    // Pings the nodes that announced the stored peers past half their
+    // lifetime, a few per call. The peers of those that fail the checks
+    // repeatedly are removed by expire_storage once a shortened lifetime
+    // passes, unless the node answers or announces them again.
+    pub async fn check_peer_origins(&self) {
+        let before = self.clock.now_ms().saturating_sub(MAX_PEER_AGE.as_millis() as u64 / 2);
+        let peers = match self.storage.lock().unwrap().get_peers_announced_before(false, before) {
+            Ok(peers) => peers,
+            Err(e) => {
+                warn!("Failed to read the peers to check: {e}");
+                return;
+            }
+        };
+
+        let dht4 = self.dht4.lock().unwrap().clone();
+        let dht6 = self.dht6.lock().unwrap().clone();
+        if dht4.is_none() && dht6.is_none() {
+            return;
+        }
+
+        for origin in self.origin_checks.select(&peers) {
+            let mut alive = false;
+            for dht in [&dht4, &dht6].into_iter().flatten() {
+                if dht.ping_node(origin).await.unwrap_or(false) {
+                    alive = true;
+                    break;
+                }
+            }
+            let keys = peers.iter()
+                .filter(|p| p.nodeid() == Some(&origin))
+                .map(|p| (*p.id(), p.fingerprint()))
+                .collect::<Vec<_>>();
+            let shortened = self.origin_checks.record(&origin, alive, &keys);
+            if shortened > 0 {
+                info!("Origin node {origin} is gone, {shortened} of its peers expire early");
+            }
+        }
+    }
+
+    // Origin nodes of the stored peers checked and found gone, and the
+    // peers given a shortened lifetime and removed for it.
+    pub fn origin_check_stats(&self) -> OriginCheckStats {
+        self.origin_checks.stats()
+    }
+
+    // Runs a full integrity check of the storage, including signature spot
+    // checks on a sample of the stored values and peers.
+    pub fn check_storage_integrity(&self) -> Result<IntegrityReport> {
+        let result = crate::locked!(self.storage).check_integrity(false);
+        self.storage_result("check_integrity", result)
+    }
+
+    // Number of DHT tasks (lookups, announces, pings) queued or running.
+    pub async fn active_tasks(&self) -> usize {
+        let dht4 = self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let dht6 = self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let mut active = 0;
+        for stats in [dht4, dht6].into_iter().flatten() {
+            active += stats.await.map_or(0, |s| s.active_tasks);
+        }
+        active
+    }
+
+    // Running, queued and peak tasks and RPC calls of the DHT of the given
+    // network, None if it is not enabled.
+    pub async fn concurrency(&self, network: Network) -> Option<Concurrency> {
+        let stats = match network {
+            Network::IPv4 => self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats()),
+            Network::IPv6 => self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats()),
+        };
+        stats?.await.ok().map(|s| s.concurrency)
+    }
+
+    // Depth and per-kind counts of the commands queued to the DHT of the
+    // given network, None if it is not enabled.
+    pub async fn command_queue(&self, network: Network) -> Option<CommandQueue> {
+        let stats = match network {
+            Network::IPv4 => self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats()),
+            Network::IPv6 => self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats()),
+        };
+        stats?.await.ok().map(|s| s.commands)
+    }
+
+    // Packets read and dropped on each receive socket of the DHT of the
+    // given network and its receive queue depth, None if it is not enabled
+    // or reads a single socket.
+    pub async fn receive_stats(&self, network: Network) -> Option<ReceiveStats> {
+        let stats = match network {
+            Network::IPv4 => self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats()),
+            Network::IPv6 => self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats()),
+        };
+        stats?.await.ok().and_then(|s| s.receive)
+    }
+
+    // Number of value lookup tasks started, concurrent lookups of the same
+    // value share one and count once.
+    pub async fn value_lookups(&self) -> u64 {
+        let dht4 = self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let dht6 = self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let mut lookups = 0;
+        for stats in [dht4, dht6].into_iter().flatten() {
+            lookups += stats.await.map_or(0, |s| s.value_lookups);
+        }
+        lookups
+    }
+
+    // Write tokens cached for announcing by both DHT instances, and how many
+    // announces found fresh ones or looked up the closest nodes instead.
+    pub async fn token_cache_stats(&self) -> TokenCacheStats {
+        let dht4 = self.dht4.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let dht6 = self.dht6.lock().unwrap().as_ref().map(|dht| dht.stats());
+        let mut total = TokenCacheStats::default();
+        for stats in [dht4, dht6].into_iter().flatten() {
+            if let Ok(stats) = stats.await {
+                total.entries += stats.token_cache.entries;
+                total.hits    += stats.token_cache.hits;
+                total.misses  += stats.token_cache.misses;
+            }
+        }
+        total
+    }
+
+    // Drops every packet from the address, for the duration or until
+    // unblocked, and keeps the nodes at it out of the routing tables. Kept
+    // across restarts.
+    pub fn block_address(&self, ip: IpAddr, duration: Option<Duration>) -> BlockEntry {
+        info!("Blocking address {ip}");
+        self.blocklist.block(BlockTarget::Address(ip), BlockReason::Manual, duration)
+    }
+
+    // Drops every packet from the node id and keeps it out of the routing
+    // tables, those holding it drop it on their next update.
+    pub fn block_id(&self, id: &Id, duration: Option<Duration>) -> BlockEntry {
+        info!("Blocking node {id}");
+        self.blocklist.block(BlockTarget::Id(*id), BlockReason::Manual, duration)
+    }
+
+    // Lifts a block set here or by the node, returns whether there was one.
+    pub fn unblock(&self, target: &BlockTarget) -> bool {
+        self.blocklist.unblock(target)
+    }
+
+    // The blocks in force, the longest standing first.
+    pub fn blocklist(&self) -> Vec<BlockEntry> {
+        self.blocklist.entries()
+    }
+
+    // Packets dropped and routing table entries refused or evicted for the
+    // blocks, and the nodes blocked on their strikes, since the node started.
+    pub fn blocklist_stats(&self) -> BlocklistStats {
+        self.blocklist.stats()
+    }
+
+    // Socket errors of both DHT instances by errno class since the node was
+    // created, and the events the error handler was spared.
+    pub fn socket_error_stats(&self) -> SocketErrorStats {
+        self.socket_errors.stats()
+    }
+
+    // Buckets of the routing table of the given network with their entry
+    // count and last refresh and activity times.
+    pub async fn routing_table_snapshot(&self, network: Network) -> Result<Vec<BucketInfo>> {
+        self.check_running()?;
+
+        let dht = match network {
+            Network::IPv4 => self.dht4.lock().unwrap().clone(),
+            Network::IPv6 => self.dht6.lock().unwrap().clone(),
+        };
+        let Some(dht) = dht else {
+            return Err(ArgumentError::new(format!("DHT/{} is not enabled", network)));
+        };
+        dht.routing_table().await
+    }
+
+    // The values and peers stored under the prefix, their ages and
+    // publishers, and the routing table entries of both networks under it.
+    pub async fn keyspace_report(&self, prefix: &Prefix) -> Result<KeyspaceReport> {
+        self.check_running()?;
+
+        let (first, last) = (prefix.first(), prefix.last());
+        let (values, peers) = {
+            let storage = self.storage.lock().unwrap();
+            let values = storage.value_range_stats(&first, &last);
+            (values, storage.peer_range_stats(&first, &last))
+        };
+        let values = self.storage_result("value_range_stats", values)?;
+        let peers = self.storage_result("peer_range_stats", peers)?;
+
+        let dht4 = self.dht4.lock().unwrap().clone();
+        let dht6 = self.dht6.lock().unwrap().clone();
+        let mut routing_entries = 0;
+        for dht in [dht4, dht6].into_iter().flatten() {
+            routing_entries += dht.routing_entries(*prefix).await?;
+        }
+
+        Ok(KeyspaceReport {
+            prefix  : *prefix.id(),
+            depth   : prefix.depth(),
+            values,
+            peers,
+            routing_entries,
+        })
+    }
+
+    pub fn recent_events(&self, limit: usize) -> Vec<NodeEvent> {
+        self.events.recent(limit)
+    }
+
+    // Milliseconds the clocks of other nodes are ahead of the local clock,
+    // behind if negative, going by the times they tell in lookup responses.
+    // None until enough nodes have told theirs.
+    pub fn estimated_clock_skew(&self) -> Option<i64> {
+        self.clock_skew.estimate()
+    }
+
+    // The crypto contexts cached for the ids the node encrypts to and
+    // decrypts from, shared with the messaging client of the node.
+    pub fn crypto_cache(&self) -> &CryptoCache {
+        self.identity.cache()
+    }
+
+    pub fn crypto_cache_stats(&self) -> CryptoCacheStats {
+        self.identity.cache().stats()
     }
 
     pub fn sign(&self, data: &[u8], signature:&mut [u8]) -> Result<usize> {
@@ -884,7 +2328,8 @@ fn check_peer_validity(old: &PeerInfo, new: &PeerInfo, expected_seq: i32) -> Res
 }
 
 struct DefaultConnectionStatusListener {
-    listeners: Arc<Mutex<Vec<Box<dyn ConnectionStatusListener>>>>
+    listeners: Arc<Mutex<Vec<Box<dyn ConnectionStatusListener>>>>,
+    events: Arc<EventBroadcast<(Network, ConnectionStatus)>>,
 }
 
 impl ConnectionStatusListener for DefaultConnectionStatusListener {
@@ -894,6 +2339,7 @@ impl ConnectionStatusListener for DefaultConnectionStatusListener {
         old_status: ConnectionStatus,
     ) {
         info!("Connection status changed for DHT{{{}}}: {}->{}", network, old_status, new_status);
+        self.events.emit((network, new_status));
         let locked = self.listeners.lock().unwrap();
         for l in locked.iter() {
             l.status_changed(network, new_status, old_status);
@@ -924,3 +2370,18 @@ impl ConnectionStatusListener for DefaultConnectionStatusListener {
 
 unsafe impl Send for Node {}
 unsafe impl Sync for Node {}
+
+// Runs a step of a direct connection attempt, failing it at the deadline.
+async fn until<T>(
+    deadline: tokio::time::Instant,
+    target: &Id,
+    step: &str,
+    future: impl std::future::Future<Output = Result<T>>
+) -> Result<T> {
+    match tokio::time::timeout_at(deadline, future).await {
+        Ok(result) => result,
+        Err(_) => Err(NetworkError::new(format!(
+            "Direct connection to {} timed out {}", target, step
+        ))),
+    }
+}
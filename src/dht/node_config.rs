@@ -1,23 +1,223 @@
+use std::net::{IpAddr, SocketAddr};
 use log::LevelFilter;
+use url::Url;
 
-use crate::{NodeInfo, signature};
+use crate::{Id, NodeInfo, EndpointPolicy, Capabilities, signature};
+use crate::dht::{StorageBackend, node_event::DEFAULT_EVENT_LOG_CAPACITY};
 pub const DEFAULT_DHT_PORT: u16 = 19001;
+pub const DEFAULT_SOCKET_RECV_TIMEOUT: u64 = 120;    // seconds
+pub const DEFAULT_SOCKET_STALL_CALLS: u32 = 8;
+pub const DEFAULT_STATS_MAX_FILE_SIZE: u64 = 1024 * 1024;  // bytes
+pub const DEFAULT_STATS_MAX_FILES: usize = 4;
+pub const DEFAULT_STORAGE_MAINTENANCE_INTERVAL: u64 = 7 * 24 * 60 * 60; // seconds
+pub const DEFAULT_SEND_RATE_INTERVAL: u64 = 1000;   // milliseconds
+pub const DEFAULT_SEND_BURST: u32 = 16;
+pub const DEFAULT_SEND_PACING: u64 = 50;            // milliseconds
+pub const DEFAULT_BUCKET_REFRESH_INTERVAL: u64 = 60 * 60; // seconds
+pub const DEFAULT_ANNOUNCEMENT_SKEW: u64 = 2 * 60 * 60;   // seconds
+pub const DEFAULT_CLOCK_SKEW_THRESHOLD: u64 = 5 * 60;     // seconds
+pub const DEFAULT_CRYPTO_CACHE_CAPACITY: u64 = 256;
+pub const DEFAULT_CRYPTO_CACHE_TTL: u64 = 30 * 60;        // seconds
+pub const DEFAULT_MIN_STORE_ACKS: usize = 1;
+pub const DEFAULT_MAX_ACTIVE_TASKS: usize = 8;
+pub const DEFAULT_MAX_INFLIGHT_CALLS: usize = 64;
+pub const DEFAULT_MAX_TASK_CALLS: usize = 16;
+pub const DEFAULT_BUCKET_CAPACITY: usize = 8;
+pub const DEFAULT_COMMAND_QUEUE_SIZE: usize = 256;
+pub const MAX_RECEIVE_SOCKETS: usize = 64;
+pub const DEFAULT_BLOCK_TOKEN_STRIKES: u32 = 8;
+pub const DEFAULT_BLOCK_VALUE_STRIKES: u32 = 4;
+pub const DEFAULT_BLOCK_DURATION: u64 = 60 * 60;         // seconds
 
 pub trait NodeConfig: Send + Sync {
     fn host4(&self) -> Option<&str>;
     fn host6(&self) -> Option<&str>;
     fn port(&self) -> u16 { DEFAULT_DHT_PORT}
 
+    // Per-network listening ports, both fall back to the shared port.
+    fn port4(&self) -> u16 { self.port() }
+    fn port6(&self) -> u16 { self.port() }
+
+    fn addr4(&self) -> Option<SocketAddr> {
+        self.host4()
+            .and_then(|host| host.parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, self.port4()))
+    }
+
+    fn addr6(&self) -> Option<SocketAddr> {
+        self.host6()
+            .and_then(|host| host.parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, self.port6()))
+    }
+
     fn private_key(&self) -> &signature::PrivateKey;
 
     fn data_dir(&self) -> &str;
     fn database_uri(&self) -> &str;
+
+    // Names the directory under the data directory the files of the node go
+    // to, so nodes can share one. The first characters of the node id when
+    // not given.
+    fn instance_name(&self) -> Option<&str> { None }
+    fn storage_backend(&self) -> StorageBackend { StorageBackend::Sqlite }
+    // A seed file written by seed::pack, its values and peers are stored at
+    // each start, verified and never replacing newer ones.
+    fn seed_data(&self) -> Option<&str> { None }
     fn bootstrap_nodes(&self) -> &[NodeInfo];
 
     fn log_level(&self) -> LevelFilter { LevelFilter::Info }
     fn log_file(&self) -> Option<&str> { None }
 
     fn enable_devp(&self) -> bool { false }
+    fn event_log_capacity(&self) -> usize { DEFAULT_EVENT_LOG_CAPACITY }
+
+    fn socket_recv_timeout(&self) -> u64 { DEFAULT_SOCKET_RECV_TIMEOUT }
+    fn socket_stall_calls(&self) -> u32 { DEFAULT_SOCKET_STALL_CALLS }
+
+    // How peer endpoints received from other nodes are screened.
+    fn endpoint_policy(&self) -> EndpointPolicy { EndpointPolicy::Sanitize }
+
+    // Nodes that must store a value put by the node before it counts as
+    // announced. Below it the node serves the value as the authoritative
+    // copy and stores it again later, 0 never does.
+    fn min_store_acks(&self) -> usize { DEFAULT_MIN_STORE_ACKS }
+
+    // Crypto contexts derived for other ids kept at most, and seconds each
+    // is kept after derived, 0 keeps them until evicted for capacity.
+    fn crypto_cache_capacity(&self) -> u64 { DEFAULT_CRYPTO_CACHE_CAPACITY }
+    fn crypto_cache_ttl(&self) -> u64 { DEFAULT_CRYPTO_CACHE_TTL }
+
+    // Seconds between two stats journal samples, 0 disables the journal.
+    fn stats_interval(&self) -> u64 { 0 }
+    fn stats_max_file_size(&self) -> u64 { DEFAULT_STATS_MAX_FILE_SIZE }
+    fn stats_max_files(&self) -> usize { DEFAULT_STATS_MAX_FILES }
+
+    // Seconds between two storage vacuum/optimize runs, 0 disables them.
+    fn storage_maintenance_interval(&self) -> u64 { DEFAULT_STORAGE_MAINTENANCE_INTERVAL }
+
+    // Outgoing packets allowed per send_rate_interval milliseconds, 0 disables
+    // the shaping. Up to send_burst packets may leave back-to-back, and packets
+    // to the same destination are at least send_pacing milliseconds apart.
+    fn send_rate(&self) -> u32 { 0 }
+    fn send_rate_interval(&self) -> u64 { DEFAULT_SEND_RATE_INTERVAL }
+    fn send_burst(&self) -> u32 { DEFAULT_SEND_BURST }
+    fn send_pacing(&self) -> u64 { DEFAULT_SEND_PACING }
+
+    // Among candidates equally distant from a lookup target (same common
+    // prefix length), query the ones with the lower round-trip time first.
+    // Disable it to keep the pure XOR ordering.
+    fn prefer_low_rtt(&self) -> bool { true }
+
+    // Seconds a routing table bucket may go without any traffic in its range
+    // before a lookup of a random id in it refreshes it, 0 disables it.
+    fn bucket_refresh_interval(&self) -> u64 { DEFAULT_BUCKET_REFRESH_INTERVAL }
+
+    // Milliseconds a value lookup result is reused for the same lookup,
+    // 0 disables it. Concurrent lookups share one task regardless.
+    fn lookup_cache_ttl(&self) -> u64 { 0 }
+
+    // Sends the node, value and peer lookups of the application from
+    // throwaway session ids, so the nodes answering them can not tell what
+    // this node looks up. Announcements and the lookups for them keep the
+    // node id, the tokens being bound to it. The lookups in privacy mode put
+    // nothing into the routing tables of the other nodes, which makes this
+    // node less known to the network, and nodes of older versions still
+    // take the session ids for nodes to route to.
+    fn privacy_mode(&self) -> bool { false }
+    // Seconds a session id is used for new lookups, 0 takes a new one per
+    // lookup.
+    fn privacy_rotation(&self) -> u64 { 0 }
+
+    // Seconds the signed time of an announced peer may be off the local
+    // clock, either way, before the announcement is rejected as a replay.
+    // Announcements of older versions carry no time, they are only accepted
+    // unless the time is required, which suits private networks.
+    fn announcement_skew(&self) -> u64 { DEFAULT_ANNOUNCEMENT_SKEW }
+    fn require_announcement_time(&self) -> bool { false }
+
+    // The service whose counter-signature values must carry to be stored,
+    // whether put locally, sent by other nodes or found by lookups. Meant
+    // for private networks, values are stored as they are when unset.
+    fn required_countersigner(&self) -> Option<&Id> { None }
+
+    // Whether store requests are refused for values a full bucket of
+    // verified nodes is closer to than this node.
+    fn reject_far_stores(&self) -> bool { false }
+
+    // The nodes whose store requests are accepted however far the value is
+    // from this node, so they can have it pin their values. Their requests
+    // still need the token handed out by this node.
+    fn pin_allowlist(&self) -> &[Id] { &[] }
+
+    // Seconds the local clock may be off the clocks other nodes tell in
+    // lookup responses before it is warned about, 0 never warns. When
+    // compensating, the token and announcement windows of the node follow
+    // the others beyond it.
+    fn clock_skew_threshold(&self) -> u64 { DEFAULT_CLOCK_SKEW_THRESHOLD }
+    fn compensate_clock_skew(&self) -> bool { false }
+
+    // Tasks (lookups, announces, routing table maintenance) running at once
+    // per network, the others wait in a queue where the ones requested by
+    // the application go before maintenance. RPC calls awaiting a response
+    // at once per network and per task, calls beyond them are deferred
+    // until others complete.
+    fn max_active_tasks(&self) -> usize { DEFAULT_MAX_ACTIVE_TASKS }
+    fn max_inflight_calls(&self) -> usize { DEFAULT_MAX_INFLIGHT_CALLS }
+    fn max_task_calls(&self) -> usize { DEFAULT_MAX_TASK_CALLS }
+
+    // Shape of the routing table: entries per bucket, how many levels next
+    // to the local id keep splitting when full (0 splits the classic way
+    // only), and the total entries kept (0 for no limit), beyond which the
+    // slowest entries of the most distant buckets go first.
+    fn bucket_capacity(&self) -> usize { DEFAULT_BUCKET_CAPACITY }
+    fn home_split_levels(&self) -> usize { 0 }
+    fn max_routing_entries(&self) -> usize { 0 }
+
+    // Commands from the node waiting for each DHT network to take them,
+    // callers beyond it wait for room in the queue.
+    fn command_queue_size(&self) -> usize { DEFAULT_COMMAND_QUEUE_SIZE }
+
+    // UDP sockets sharing the port of each DHT network through SO_REUSEPORT
+    // (Linux only), each read and decrypted on a thread of its own before
+    // the packets are handed to the DHT. 1 reads the single socket on the
+    // DHT thread.
+    fn receive_sockets(&self) -> usize { 1 }
+
+    // The WebSocket gateway the DHT packets are tunneled through instead of
+    // UDP, for nodes on networks blocking it. Nothing is bound then, the
+    // other nodes see the node at the address the gateway relays from.
+    fn ws_gateway(&self) -> Option<&Url> { None }
+    // Where a full node accepts the connections of such nodes, as their
+    // gateway.
+    fn ws_gateway_listener(&self) -> Option<SocketAddr> { None }
+
+    // A node only taking part in routing, such as one on a phone or behind
+    // a metered link. The other nodes never send it anything to store.
+    fn client_only(&self) -> bool { false }
+    // Whether the node advertises it hosts an ActiveProxy relay.
+    fn relay(&self) -> bool { false }
+
+    // What the node advertises to the other nodes it offers.
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = match self.client_only() {
+            true  => Capabilities::CLIENT_ONLY,
+            false => Capabilities::STORAGE,
+        };
+        if self.relay() {
+            caps = caps | Capabilities::RELAY;
+        }
+        if self.ws_gateway_listener().is_some() {
+            caps = caps | Capabilities::GATEWAY;
+        }
+        caps
+    }
+
+    // Invalid tokens and invalid values (store or announce requests, forged
+    // lookup answers) a node sends within ten minutes before it is blocked
+    // for block_duration seconds, 0 never blocks it for them.
+    fn block_token_strikes(&self) -> u32 { DEFAULT_BLOCK_TOKEN_STRIKES }
+    fn block_value_strikes(&self) -> u32 { DEFAULT_BLOCK_VALUE_STRIKES }
+    fn block_duration(&self) -> u64 { DEFAULT_BLOCK_DURATION }
 
     fn dump(&self);
 }
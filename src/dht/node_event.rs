@@ -0,0 +1,176 @@
+use std::{
+    fmt,
+    io,
+    net::SocketAddr,
+    path::PathBuf,
+    time::SystemTime,
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use crate::{Id, Network};
+use crate::dht::blocklist::BlockReason;
+
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 1024;
+
+// Significant node events kept for post-mortem debugging.
+// Only plain values are stored, formatting happens lazily on read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeEventKind {
+    BootstrapStarted { nodes: usize },
+    BootstrapCompleted { responded: usize, entries: usize },
+    BootstrapFailed { nodes: usize },
+    BucketSplit { depth: i32 },
+    EntryEvicted { id: Id },
+    StorageError { op: &'static str },
+    StorageRecovered { moved_to: PathBuf },
+    StorageMaintained { freed_pages: u64 },
+    TokenRejected { from: SocketAddr, target: Id },
+    AnnouncementRejected { from: SocketAddr, target: Id },
+    AnnouncementUntimed { from: SocketAddr, target: Id },
+    CallTimeout { id: Id },
+    ValueRejected { from: Id, target: Id },
+    NodeBlocked { id: Id, reason: BlockReason },
+    ClockSkewDetected { offset_secs: i64, nodes: usize },
+    ClockSkewRecovered,
+    StoreUnconfirmed { value_id: Id, acks: usize },
+    SocketError { kind: io::ErrorKind },
+    SocketUnhealthy { reason: &'static str },
+    SocketRebound { addr: SocketAddr },
+    EndpointUnroutable { peer: Id, observed: String },
+}
+
+#[derive(Clone)]
+pub struct NodeEvent {
+    timestamp   : SystemTime,
+    network     : Option<Network>,
+    kind        : NodeEventKind,
+}
+
+impl NodeEvent {
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    pub fn network(&self) -> Option<Network> {
+        self.network
+    }
+
+    pub fn kind(&self) -> &NodeEventKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for NodeEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::BootstrapStarted { nodes } =>
+                write!(f, "bootstrap started with {nodes} nodes"),
+            Self::BootstrapCompleted { responded, entries } =>
+                write!(f, "bootstrap completed, {responded} responded, {entries} entries"),
+            Self::BootstrapFailed { nodes } =>
+                write!(f, "bootstrap failed with {nodes} nodes"),
+            Self::BucketSplit { depth } =>
+                write!(f, "bucket split at depth {depth}"),
+            Self::EntryEvicted { id } =>
+                write!(f, "routing entry {id} evicted"),
+            Self::StorageError { op } =>
+                write!(f, "storage error on {op}"),
+            Self::StorageRecovered { moved_to } =>
+                write!(f, "corrupt storage moved to {}, started fresh", moved_to.display()),
+            Self::StorageMaintained { freed_pages } =>
+                write!(f, "storage maintained, {freed_pages} pages freed"),
+            Self::TokenRejected { from, target } =>
+                write!(f, "token rejected from {from} for {target}"),
+            Self::AnnouncementRejected { from, target } =>
+                write!(f, "announcement of {target} from {from} rejected"),
+            Self::AnnouncementUntimed { from, target } =>
+                write!(f, "announcement of {target} from {from} without time"),
+            Self::CallTimeout { id } =>
+                write!(f, "call to {id} timed out"),
+            Self::ValueRejected { from, target } =>
+                write!(f, "forged value of {target} from {from} rejected"),
+            Self::NodeBlocked { id, reason } =>
+                write!(f, "node {id} blocked for {reason}"),
+            Self::ClockSkewDetected { offset_secs, nodes } =>
+                write!(f, "local clock off by {offset_secs}s from {nodes} nodes"),
+            Self::ClockSkewRecovered =>
+                write!(f, "local clock back in line with other nodes"),
+            Self::StoreUnconfirmed { value_id, acks } =>
+                write!(f, "value {value_id} stored by {acks} nodes only, served locally"),
+            Self::SocketError { kind } =>
+                write!(f, "socket error: {kind}"),
+            Self::SocketUnhealthy { reason } =>
+                write!(f, "socket unhealthy: {reason}"),
+            Self::SocketRebound { addr } =>
+                write!(f, "socket rebound to {addr}"),
+            Self::EndpointUnroutable { peer, observed } =>
+                write!(f, "peer {peer} announced with an unroutable endpoint, observed at {observed}"),
+        }
+    }
+}
+
+impl fmt::Display for NodeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ts = self.timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+
+        write!(f, "[{ts}]")?;
+        if let Some(network) = self.network {
+            write!(f, " DHT/{network}")?;
+        }
+        write!(f, " {}", self.kind)
+    }
+}
+
+// Bounded ring buffer of node events shared between the node and its
+// DHT instances, each clone can be tagged with the network it reports.
+#[derive(Clone)]
+pub(crate) struct EventLog {
+    network     : Option<Network>,
+    capacity    : usize,
+    events      : Arc<Mutex<VecDeque<NodeEvent>>>,
+}
+
+impl EventLog {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            network : None,
+            capacity,
+            events  : Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    pub(crate) fn with_network(&self, network: Network) -> Self {
+        Self {
+            network : Some(network),
+            capacity: self.capacity,
+            events  : self.events.clone(),
+        }
+    }
+
+    pub(crate) fn record(&self, kind: NodeEventKind) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(NodeEvent {
+            timestamp   : SystemTime::now(),
+            network     : self.network,
+            kind,
+        });
+    }
+
+    // The most recent `limit` events, oldest first.
+    pub(crate) fn recent(&self, limit: usize) -> Vec<NodeEvent> {
+        let events = self.events.lock().unwrap();
+        let skip = events.len().saturating_sub(limit);
+        events.iter().skip(skip).cloned().collect()
+    }
+}
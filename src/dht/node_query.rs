@@ -0,0 +1,49 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::NodeInfo;
+
+/// The answer of one node to a single find node request, see
+/// [`Node::query_node`](crate::dht::Node::query_node).
+#[derive(Debug, Clone)]
+pub struct NodeQuery {
+    node    : NodeInfo,
+    rtt     : Duration,
+    nodes   : Vec<NodeInfo>,
+}
+
+impl NodeQuery {
+    pub(crate) fn new(node: NodeInfo, rtt: Duration, nodes: Vec<NodeInfo>) -> Self {
+        Self { node, rtt, nodes }
+    }
+
+    /// The node asked, with the version it answered with.
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
+    }
+
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+
+    /// The nodes it knows closest to the target, of the network it was
+    /// asked on.
+    pub fn nodes(&self) -> &[NodeInfo] {
+        &self.nodes
+    }
+
+    pub fn into_nodes(self) -> Vec<NodeInfo> {
+        self.nodes
+    }
+}
+
+impl fmt::Display for NodeQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} [{}] in {:?}: {} nodes",
+            self.node,
+            self.node.normalized_version(),
+            self.rtt,
+            self.nodes.len()
+        )
+    }
+}
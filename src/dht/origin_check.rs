@@ -0,0 +1,150 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    Id,
+    Clock,
+    PeerInfo,
+    dht::stats::OriginCheckStats,
+};
+
+// Origin nodes checked per maintenance round, those checked longest ago
+// first, so a storage node with many peers only sends a few pings.
+pub(crate) const CHECKS_PER_ROUND: usize = 4;
+
+// Failed checks in a row before the peers of an origin age out early.
+pub(crate) const MAX_FAILURES: u32 = 3;
+
+// Lifetime left to the peers of an origin found gone, unless it answers
+// again or announces them anew in the meantime.
+pub(crate) const SHORTENED_AGE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Default)]
+struct Origin {
+    failures    : u32,
+    checked_ms  : u64,
+}
+
+struct Shortened {
+    origin      : Id,
+    since_ms    : u64,
+    deadline_ms : u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    origins     : HashMap<Id, Origin>,
+    shortened   : HashMap<(Id, u64), Shortened>,
+    stats       : OriginCheckStats,
+}
+
+// Tracks the liveness of the nodes that announced the stored peers and
+// shortens the lifetime of the peers whose origin stopped answering.
+pub(crate) struct OriginChecks {
+    local_id    : Id,
+    clock       : Arc<dyn Clock>,
+    inner       : Mutex<Inner>,
+}
+
+impl OriginChecks {
+    pub(crate) fn new(local_id: Id, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            local_id,
+            clock,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    // Origins of the given peers to check this round. Peers without an
+    // origin node and those announced by the local node are left alone,
+    // and origins no longer behind any of the peers are forgotten.
+    pub(crate) fn select(&self, peers: &[PeerInfo]) -> Vec<Id> {
+        let candidates = peers.iter()
+            .filter_map(|p| p.nodeid())
+            .filter(|id| **id != self.local_id)
+            .collect::<HashSet<_>>();
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.origins.retain(|id, _| candidates.contains(id));
+
+        let mut selected = candidates.into_iter()
+            .map(|id| (inner.origins.get(id).map_or(0, |o| o.checked_ms), *id))
+            .collect::<Vec<_>>();
+        selected.sort();
+        selected.truncate(CHECKS_PER_ROUND);
+        selected.into_iter().map(|(_, id)| id).collect()
+    }
+
+    // Records the result of a check on the origin of the given peers.
+    // Returns the number of peers whose lifetime got shortened.
+    pub(crate) fn record(&self, origin: &Id, alive: bool, peers: &[(Id, u64)]) -> usize {
+        let now = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+        inner.stats.checks += 1;
+
+        let entry = inner.origins.entry(*origin).or_default();
+        entry.checked_ms = now;
+        if alive {
+            entry.failures = 0;
+            inner.shortened.retain(|_, s| s.origin != *origin);
+            return 0;
+        }
+
+        entry.failures += 1;
+        let failures = entry.failures;
+        inner.stats.failures += 1;
+        if failures < MAX_FAILURES {
+            return 0;
+        }
+
+        let mut count = 0;
+        for key in peers {
+            if inner.shortened.contains_key(key) {
+                continue;
+            }
+            inner.shortened.insert(*key, Shortened {
+                origin      : *origin,
+                since_ms    : now,
+                deadline_ms : now + SHORTENED_AGE.as_millis() as u64,
+            });
+            count += 1;
+        }
+        inner.stats.shortened_peers += count as u64;
+        count
+    }
+
+    // Peers past their shortened lifetime, to be removed. The updated
+    // function gives the time a peer was last stored, peers stored again
+    // after their lifetime got shortened keep the normal one.
+    pub(crate) fn take_expired<F>(&self, updated: F) -> Vec<(Id, u64)>
+    where F: Fn(&Id, u64) -> Option<u64> {
+        let now = self.clock.now_ms();
+        let mut inner = self.inner.lock().unwrap();
+
+        let mut expired = Vec::new();
+        inner.shortened.retain(|(id, fingerprint), s| {
+            if s.deadline_ms > now {
+                return true;
+            }
+            if let Some(t) = updated(id, *fingerprint) {
+                if t <= s.since_ms {
+                    expired.push((*id, *fingerprint));
+                }
+            }
+            false
+        });
+        inner.stats.expired_peers += expired.len() as u64;
+        expired
+    }
+
+    pub(crate) fn stats(&self) -> OriginCheckStats {
+        let inner = self.inner.lock().unwrap();
+        OriginCheckStats {
+            pending_peers: inner.shortened.len() as u64,
+            ..inner.stats
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use rand::{Rng, RngExt};
+
+use crate::PeerInfo;
+
+/// Spreads the load over the instances of a service announced under the
+/// same peer id, in proportion to the weight each was announced with.
+pub struct PeerSelector;
+
+impl PeerSelector {
+    /// Picks one of the peers at random, each as likely as its weight out
+    /// of the total. Peers of weight 0 are never picked, None if all of
+    /// them are. The peers are taken as found by a lookup, which verified
+    /// them already.
+    pub fn weighted_choice<'a, R: Rng + ?Sized>(peers: &'a [PeerInfo], rng: &mut R) -> Option<&'a PeerInfo> {
+        let total: u32 = peers.iter()
+            .map(|p| p.weight() as u32)
+            .sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut point = rng.random_range(0..total);
+        peers.iter()
+            .find(|p| match point < p.weight() as u32 {
+                true => true,
+                false => {
+                    point -= p.weight() as u32;
+                    false
+                }
+            })
+    }
+}
@@ -1,12 +1,12 @@
 use std::{
     fmt,
+    collections::BTreeMap,
     time::SystemTime
 };
-use libsodium_sys::randombytes_uniform;
 use rbtree::RBTree;
 use log::info;
 
-use crate::Id;
+use crate::{Id, Capabilities, core::version};
 use crate::dht::{
     rpc::Reachability,
     handler::Handler,
@@ -32,25 +32,83 @@ use crate::dht::{
 pub(crate) struct KBucket {
     prefix          : Prefix,
     home_bucket     : bool,
+    capacity        : usize,
     entries         : RBTree<SystemTime, KBucketEntry>,
     last_refreshed  : Option<SystemTime>,
+    // Last time a node in the bucket range was heard from or looked up.
+    last_activity   : SystemTime,
+}
+
+// Point-in-time view of one routing table bucket.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BucketInfo {
+    prefix          : Id,
+    depth           : i32,
+    home_bucket     : bool,
+    entries         : usize,
+    versions        : BTreeMap<String, usize>,
+    capabilities    : BTreeMap<String, usize>,
+    last_refreshed  : Option<SystemTime>,
+    last_activity   : SystemTime,
+}
+
+impl BucketInfo {
+    pub fn prefix(&self) -> &Id {
+        &self.prefix
+    }
+
+    pub fn depth(&self) -> i32 {
+        self.depth
+    }
+
+    pub fn is_home_bucket(&self) -> bool {
+        self.home_bucket
+    }
+
+    pub fn entries(&self) -> usize {
+        self.entries
+    }
+
+    // Number of entries running each version, keyed by its normalized
+    // form such as "MK/4".
+    pub fn versions(&self) -> &BTreeMap<String, usize> {
+        &self.versions
+    }
+
+    // Number of entries offering each capability, keyed by its name such
+    // as "storage" or "client-only".
+    pub fn capabilities(&self) -> &BTreeMap<String, usize> {
+        &self.capabilities
+    }
+
+    // Last ping refresh of the bucket entries.
+    pub fn last_refreshed(&self) -> Option<SystemTime> {
+        self.last_refreshed
+    }
+
+    // Last time the bucket range saw traffic or a refresh lookup.
+    pub fn last_activity(&self) -> SystemTime {
+        self.last_activity
+    }
 }
 
 impl KBucket {
     pub(crate) const MAX_ENTRIES: usize = 8;
     pub(crate) const REFRESH_INTERVAL: u128 = 15 * 60 * 1000;   // 15 minutes in milliseconds
 
-    pub(crate) fn new(prefix: Prefix, home_bucket: bool) -> Self {
+    pub(crate) fn new(prefix: Prefix, home_bucket: bool, capacity: usize) -> Self {
         Self {
             prefix,
             home_bucket,
+            capacity,
             entries         : RBTree::new(),
             last_refreshed  : None,
+            last_activity   : SystemTime::now(),
         }
     }
 
-    pub(crate) fn home_bucket(prefix: Prefix) -> Self {
-        Self::new(prefix, true)
+    pub(crate) fn home_bucket(prefix: Prefix, capacity: usize) -> Self {
+        Self::new(prefix, true, capacity)
     }
 
     pub(crate) fn prefix(&self) -> &Prefix {
@@ -76,7 +134,7 @@ impl KBucket {
     }
 
     pub(crate) fn is_full(&self) -> bool {
-        self.entries.len() >= KBucket::MAX_ENTRIES
+        self.entries.len() >= self.capacity
     }
 
     pub(crate) fn contains(&self, id: &Id) -> bool {
@@ -96,9 +154,7 @@ impl KBucket {
                 .map(|(_, v)| v.clone());
         }
 
-        let pos = unsafe {
-            randombytes_uniform(self.entries.len() as u32)
-        } as usize;
+        let pos = crate::random_uniform(self.entries.len() as u32) as usize;
         self.entries.values().nth(pos).cloned()
     }
 
@@ -106,6 +162,37 @@ impl KBucket {
         self.last_refreshed = Some(SystemTime::now());
     }
 
+    pub(crate) fn last_activity(&self) -> SystemTime {
+        self.last_activity
+    }
+
+    pub(crate) fn set_last_activity(&mut self, time: SystemTime) {
+        self.last_activity = time;
+    }
+
+    pub(crate) fn update_activity_time(&mut self) {
+        self.last_activity = SystemTime::now();
+    }
+
+    // No node in the bucket range was heard from or looked up for longer
+    // than the interval, in milliseconds.
+    pub(crate) fn is_idle(&self, interval: u128) -> bool {
+        crate::elapsed_ms!(self.last_activity) > interval
+    }
+
+    pub(crate) fn info(&self) -> BucketInfo {
+        BucketInfo {
+            prefix          : *self.prefix.id(),
+            depth           : self.prefix.depth(),
+            home_bucket     : self.home_bucket,
+            entries         : self.entries.len(),
+            versions        : version::tally(self.entries.iter().map(|(_, v)| v.version())),
+            capabilities    : Capabilities::tally(self.entries.iter().map(|(_, v)| v.capabilities())),
+            last_refreshed  : self.last_refreshed,
+            last_activity   : self.last_activity,
+        }
+    }
+
     pub(crate) fn needs_refreshing(&self) -> bool {
         let needs_ping = self.entries.iter().any(|(_,v)|v.needs_ping());
         let needs_refresh = self.last_refreshed.map_or(true, |v| {
@@ -122,27 +209,28 @@ impl KBucket {
         self.entries.iter().any(|(_, v)| v.needs_replacement())
     }
 
-    pub(crate) fn put(&mut self, new: KBucketEntry) {
+    // Returns the entry evicted to make room for the new one, if any.
+    pub(crate) fn put(&mut self, new: KBucketEntry) -> Option<KBucketEntry> {
         for (_, v) in self.entries.iter_mut() {
             if v.equals(&new) {
                 v.merge(new);
-                return;
+                return None;
             }
             if v.matches(&new) {
                 info!("New node {} claims same ID or IP as {}, might be impersonation attack or IP change.
                     ignoring until old entry times out", new, v);
-                return;
+                return None;
             }
         }
         if new.is_reachable() {
             // insert to the list if it still has room
-            if self.entries.len() < KBucket::MAX_ENTRIES {
+            if self.entries.len() < self.capacity {
                 self._put_as_main_entry(new);
-                return;
+                return None;
             }
 
             // Try to replace the bad entry
-            self._replace_bad_entry(new);
+            let evicted = self._replace_bad_entry(new);
 
             // When bucket full and new reachable entry arrives, Kademlia(original paper) pings the
 			// oldest/least-recent when full; if unresponsive, replace from cache, else cache the new one.
@@ -150,7 +238,9 @@ impl KBucket {
 			// This will force a refresh to run PingRefreshTask with probe replacement on the current bucket
 			// Assumes PingRefreshTask pings least-recent-seen entries for LRS eviction.
 			self.last_refreshed = None;
+            return evicted;
         }
+        None
     }
 
     fn _put_as_main_entry(&mut self, entry: KBucketEntry) {
@@ -158,18 +248,18 @@ impl KBucket {
         self.entries.insert(created_time, entry);
     }
 
-    fn _replace_bad_entry(&mut self, entry: KBucketEntry) {
+    fn _replace_bad_entry(&mut self, entry: KBucketEntry) -> Option<KBucketEntry> {
         let key = self.entries.iter()
             .find(|(_,v)| v.needs_replacement())
             .map(|(k,_)| k.clone());
 
-        if let Some(ref key) = key {
-            self.entries.remove(key);
-        } else {
-            self.entries.pop_last();
-        }
+        let evicted = match key {
+            Some(ref key) => self.entries.remove(key),
+            None => self.entries.pop_last().map(|(_, v)| v),
+        };
 
         self._put_as_main_entry(entry);
+        evicted
     }
 
     pub(crate) fn on_timeout(&mut self, id: &Id) {
@@ -179,6 +269,13 @@ impl KBucket {
         }
     }
 
+    pub(crate) fn on_strike(&mut self, id: &Id) {
+        let found = self.entries.iter_mut().find(|(_, v)| v.id() == id);
+        if let Some((_, v)) = found {
+            v.on_strike();
+        }
+    }
+
     pub(crate) fn on_request_sent(&mut self, id: &Id) {
         let found = self.entries.iter_mut().find(|(_, v)| v.id() == id);
         if let Some((_, v)) = found {
@@ -193,6 +290,19 @@ impl KBucket {
         }
     }
 
+    // Drops the entry slowest to respond, entries never heard from counting
+    // as the slowest, and the oldest among equally slow ones.
+    pub(crate) fn _evict_slowest(&mut self) -> Option<KBucketEntry> {
+        let mut slowest: Option<(SystemTime, f64)> = None;
+        for (k, v) in self.entries.iter() {
+            let rtt = v.avg_rtt().unwrap_or(f64::INFINITY);
+            if slowest.is_none_or(|(_, max)| rtt > max) {
+                slowest = Some((*k, rtt));
+            }
+        }
+        slowest.and_then(|(k, _)| self.entries.remove(&k))
+    }
+
     pub(crate) fn _remove_bad_entry(&mut self, entry: KBucketEntry, force: bool
     ) -> Option<KBucketEntry> {
         let test_cb = |v: &KBucketEntry| {
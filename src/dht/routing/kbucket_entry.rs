@@ -1,6 +1,6 @@
 use std::{
     fmt,
-    cmp::{min, max},
+    cmp::{min, max, Ordering},
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     time::{Duration, SystemTime}
 };
@@ -8,6 +8,7 @@ use serde::{Serialize, Deserialize};
 use crate::{
     Id,
     NodeInfo,
+    Capabilities,
     core::version,
     dht::rpc::{Reachability, rpc_target::NodeInfoLike}
 };
@@ -28,6 +29,8 @@ pub(crate) struct KBucketEntry {
 
     reachable   : bool,
     failed_reqs : i32,
+    // Forged answers, unlike timeouts not forgiven by the next response.
+    strikes     : i32,
     avg_rtt     : Option<f64>,
 }
 
@@ -48,6 +51,7 @@ impl KBucketEntry {
             last_sent   : SystemTime::UNIX_EPOCH,
             reachable   : false,
             failed_reqs: 0,
+            strikes     : 0,
             avg_rtt     : None,
         }
     }
@@ -56,6 +60,19 @@ impl KBucketEntry {
         self.ni.set_version(ver);
     }
 
+    pub(crate) fn version(&self) -> i32 {
+        self.ni.version()
+    }
+
+    pub(crate) fn set_capabilities(&mut self, caps: Capabilities) {
+        self.ni.set_capabilities(caps);
+    }
+
+    // A full node unless it advertised otherwise.
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        self.ni.capabilities()
+    }
+
     pub(crate) fn id(&self) -> &Id {
         &self.ni.id()
     }
@@ -89,18 +106,33 @@ impl KBucketEntry {
         self.failed_reqs
     }
 
+    #[allow(unused)]
+    pub(crate) const fn strikes(&self) -> i32 {
+        self.strikes
+    }
+
+    const fn failures(&self) -> i32 {
+        self.failed_reqs + self.strikes
+    }
+
+    // Exponentially weighted round-trip time in milliseconds, None until
+    // the node answered one of our requests.
+    pub(crate) const fn avg_rtt(&self) -> Option<f64> {
+        self.avg_rtt
+    }
+
     pub(crate) const fn eligible_for_nodes_list(&self) -> bool {
         // 1 timeout can occasionally happen. should be fine to hand it out
         // as long as we've verified it at least once
-        self.reachable && self.failed_reqs < 3
+        self.reachable && self.failures() < 3
     }
 
     pub(crate) const fn eligible_for_local_lookup(&self) -> bool {
         // allow implicit initial ping during lookups
         // TO~DO: make this work now that we don't keep unverified entries
         // in the main bucket
-        (self.reachable && self.failed_reqs <= 3) ||
-            self.failed_reqs <= 0
+        (self.reachable && self.failures() <= 3) ||
+            self.failures() <= 0
     }
 
     fn backoff(&self) -> u64 {
@@ -148,6 +180,12 @@ impl KBucketEntry {
             || crate::elapsed_ms!(self.last_seen) > Self::OLD_AND_STALE_TIME as u128
     }
 
+    // Recently confirmed alive, good enough to skip a lookup.
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.failed_reqs == 0
+            && crate::elapsed_ms!(self.last_seen) < Self::OLD_AND_STALE_TIME as u128
+    }
+
     pub(crate) fn old_and_stale(&self) -> bool {
         self.failed_reqs > Self::OLD_AND_STALE_FAILURES
             && crate::elapsed_ms!(self.last_seen) > Self::OLD_AND_STALE_TIME as u128
@@ -180,7 +218,7 @@ impl KBucketEntry {
     ///
     pub(crate) fn needs_replacement(&self) -> bool {
         (self.failed_reqs > 1 && !self.is_reachable()) ||
-            self.failed_reqs > Self::MAX_FAILURES ||
+            self.failures() > Self::MAX_FAILURES ||
             self.old_and_stale()
     }
 
@@ -192,6 +230,7 @@ impl KBucketEntry {
         if entry.last_seen >= self.last_seen {
             self.failed_reqs = entry.failed_reqs;
         }
+        self.strikes = self.strikes.max(entry.strikes);
         if entry.is_reachable() {
             self.set_reachable(true);
         }
@@ -199,6 +238,10 @@ impl KBucketEntry {
         if let Some(avg_rtt) = entry.avg_rtt {
             self.update_avg_rtt(avg_rtt);
         }
+        // Kept when the node did not tell them again.
+        if let Some(caps) = entry.ni.advertised_capabilities() {
+            self.ni.set_capabilities(caps);
+        }
 
         self.created    = self.created.min(entry.created);
         self.last_seen  = self.last_seen.max(entry.last_seen);
@@ -227,6 +270,10 @@ impl KBucketEntry {
         self.failed_reqs += 1;
     }
 
+    pub(crate) fn on_strike(&mut self) {
+        self.strikes += 1;
+    }
+
     pub(crate) fn matches(&self, other: &Self) -> bool {
         self.ni.matches(&other.ni)
     }
@@ -243,6 +290,31 @@ impl KBucketEntry {
     }
 }
 
+// Orders two nodes by their XOR distance to the target. With prefer_low_rtt,
+// nodes sharing the same common prefix length with the target are ordered by
+// their RTT estimate first, nodes without a sample after the sampled ones.
+pub(crate) fn distance_order(
+    target: &Id,
+    a: (&Id, Option<f64>),
+    b: (&Id, Option<f64>),
+    prefer_low_rtt: bool
+) -> Ordering {
+    if !prefer_low_rtt {
+        return target.three_way_compare(a.0, b.0);
+    }
+
+    let rtt_order = match (a.1, b.1) {
+        (Some(rtt_a), Some(rtt_b)) => rtt_a.total_cmp(&rtt_b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+
+    target.common_prefix_len(b.0).cmp(&target.common_prefix_len(a.0))
+        .then(rtt_order)
+        .then_with(|| target.three_way_compare(a.0, b.0))
+}
+
 impl Eq for KBucketEntry {}
 impl PartialEq for KBucketEntry {
     fn eq(&self, other: &Self) -> bool {
@@ -300,6 +372,8 @@ struct SerializableKbucketEntry {
     avg_rtt: Option<f64>,
     #[serde(rename="version", skip_serializing_if = "crate::is_default")]
     ver: i32,
+    #[serde(rename="capabilities", skip_serializing_if = "crate::is_default", default)]
+    caps: Option<Capabilities>,
 }
 
 impl Into<SerializableKbucketEntry> for KBucketEntry {
@@ -320,6 +394,7 @@ impl Into<SerializableKbucketEntry> for KBucketEntry {
             // failed_reqs: self.failed_reqs,
             avg_rtt     : self.avg_rtt,
             ver         : self.ni.version(),
+            caps        : self.ni.advertised_capabilities(),
         }
     }
 }
@@ -346,6 +421,9 @@ impl From<SerializableKbucketEntry> for KBucketEntry {
 
         let mut entry = KBucketEntry::new(ser.id, SocketAddr::new(ip, ser.port));
         entry.set_ver(ser.ver);
+        if let Some(caps) = ser.caps {
+            entry.set_capabilities(caps);
+        }
         entry.created = convert_cb(ser.created);
         entry.last_seen = convert_cb(ser.last_seen);
         entry.last_sent = convert_cb(ser.last_sent);
@@ -372,13 +450,19 @@ impl fmt::Display for KBucketEntry {
         if self.failed_reqs > 0 {
             write!(f, "; fail: {}", self.failed_reqs - 0)?;
         }
+        if self.strikes > 0 {
+            write!(f, "; strikes: {}", self.strikes)?;
+        }
         if self.reachable {
             write!(f, "; reachable")?;
         }
+        if let Some(rtt) = self.avg_rtt {
+            write!(f, "; rtt: {:.0}ms", rtt)?;
+        }
         if self.ni.version() != 0 {
             write!(f,
                 "; ver: {}",
-                version::format_version(self.ni.version())
+                version::normalized_version(self.ni.version())
             )?;
         }
         Ok(())
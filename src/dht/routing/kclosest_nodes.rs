@@ -1,7 +1,8 @@
 use std::{
     rc::Rc,
     cell::RefCell,
-    cmp::Ordering
+    cmp::Ordering,
+    net::SocketAddr,
 };
 
 use crate::{Id, NodeInfo};
@@ -9,7 +10,8 @@ use crate::dht::{
     routing::{
         KBucket,
         KBucketEntry,
-        RoutingTable
+        RoutingTable,
+        kbucket_entry::distance_order,
     },
     rpc::rpc_target::NodeInfoLike,
 };
@@ -20,7 +22,8 @@ pub(crate) struct KClosestNodes {
     target  : Id,
     capacity: usize,
     entries : Vec<KBucketEntry>,
-    filter  : Box<dyn Fn(&KBucketEntry) -> bool>
+    filter  : Box<dyn Fn(&KBucketEntry) -> bool>,
+    prefer_low_rtt: bool,
 }
 
 impl KClosestNodes {
@@ -38,6 +41,7 @@ impl KClosestNodes {
             target,
             capacity,
             entries: Vec::with_capacity(capacity + KBucket::MAX_ENTRIES),
+            prefer_low_rtt: false,
         }
     }
 
@@ -76,6 +80,11 @@ impl KClosestNodes {
         });
     }
 
+    // Prefers nodes with a lower RTT among the equally distant ones.
+    pub(crate) fn set_prefer_low_rtt(&mut self, enabled: bool) {
+        self.prefer_low_rtt = enabled;
+    }
+
     pub(crate) fn fill(&mut self) {
         let buckets = self.buckets.clone();
         if buckets.is_empty() {
@@ -143,6 +152,16 @@ impl KClosestNodes {
         self.shave();
     }
 
+    // Puts the local node into the result by its distance to the target,
+    // should be called after fill().
+    pub(crate) fn add_self(&mut self, addr: SocketAddr) {
+        if self.entries.iter().any(|e| e.id() == &self.local_id) {
+            return;
+        }
+        self.entries.push(KBucketEntry::new(self.local_id, addr));
+        self.shave();
+    }
+
     fn add_entries(&mut self, bucket: &Rc<RefCell<KBucket>>) {
         let bucket  = bucket.borrow();
         let entries = bucket.entries();
@@ -154,9 +173,12 @@ impl KClosestNodes {
     }
 
     fn shave(&mut self) {
-        self.entries.sort_by(|e1, e2|
-            self.target.three_way_compare(e1.id(), e2.id())
-        );
+        self.entries.sort_by(|e1, e2| distance_order(
+            &self.target,
+            (e1.id(), e1.avg_rtt()),
+            (e2.id(), e2.avg_rtt()),
+            self.prefer_low_rtt
+        ));
 
         if self.entries.len() <= self.capacity {
             return;
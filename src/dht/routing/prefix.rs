@@ -18,8 +18,10 @@ impl Prefix {
         }
     }
 
-    pub(crate) fn from(src: &Id, depth: i32) -> Self {
-       assert!(depth < Id::BITS as i32);
+    // The prefix made of the first depth + 1 bits of the id, the whole
+    // keyspace for a depth of -1.
+    pub fn from(src: &Id, depth: i32) -> Self {
+       assert!((-1..Id::BITS as i32).contains(&depth));
 
         let mut id = Id::default();
         Id::bits_copy(src, &mut id, depth);
@@ -27,16 +29,15 @@ impl Prefix {
         Self {id, depth }
     }
 
-    pub(crate) const fn id(&self) -> &Id {
+    pub const fn id(&self) -> &Id {
         &self.id
     }
 
-    #[allow(unused)]
-    pub(crate) const fn depth(&self) -> i32 {
+    pub const fn depth(&self) -> i32 {
         self.depth
     }
 
-    pub(crate) fn is_prefix_of(&self, id: &Id) -> bool {
+    pub fn is_prefix_of(&self, id: &Id) -> bool {
         Id::bits_equal(&self.id, id, self.depth)
     }
 
@@ -44,15 +45,13 @@ impl Prefix {
         self.depth < (Id::BITS - 1) as i32
     }
 
-    pub(crate) fn first(&self) -> Id {
+    // The lowest and the highest id under the prefix.
+    pub fn first(&self) -> Id {
         self.id.clone()
     }
 
-    pub(crate) fn last(&self) -> Id {
-        let prefix = Prefix {
-            id: Id::MAX_ID,
-            depth: self.depth,
-        };
+    pub fn last(&self) -> Id {
+        let prefix = Prefix::from(&Id::MAX_ID, self.depth);
         let trailing_bits = prefix.id.distance(&Id::MAX_ID);
         self.id.distance(&trailing_bits)
     }
@@ -6,6 +6,7 @@ use std::{
     fs::{self, File},
     io::{ErrorKind, Error as StdError},
     rc::Rc,
+    sync::Arc,
     cell::RefCell,
 };
 use serde::{Deserialize, Serialize};
@@ -16,10 +17,14 @@ use crate::{Id, Result};
 use crate::dht::{
     handler::Handler,
     rpc::Reachability,
+    blocklist::Blocklist,
+    node_config::DEFAULT_BUCKET_CAPACITY,
+    node_event::{EventLog, NodeEventKind},
     routing:: {
         Prefix,
         KBucket,
         KBucketEntry,
+        kbucket::BucketInfo,
     },
 };
 
@@ -31,27 +36,73 @@ struct SerdeRoutingTable {
     entries: Vec<KBucketEntry>,
 }
 
+// How the routing table grows: the entries each bucket holds, the levels
+// above the home bucket where full buckets split whichever branch the new
+// entry falls in (0 only splits towards the local id), and the entries the
+// whole table keeps (0 for no limit).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RoutingStrategy {
+    pub(crate) bucket_capacity      : usize,
+    pub(crate) home_split_levels    : usize,
+    pub(crate) max_entries          : usize,
+}
+
+impl Default for RoutingStrategy {
+    fn default() -> Self {
+        Self {
+            bucket_capacity     : DEFAULT_BUCKET_CAPACITY,
+            home_split_levels   : 0,
+            max_entries         : 0,
+        }
+    }
+}
+
 pub(crate) struct RoutingTable {
     nodeid  : Id,
     buckets : RBTree<Prefix, Rc<RefCell<KBucket>>>,
+    strategy: RoutingStrategy,
     updated : SystemTime,
     saved   : SystemTime,
+    events  : Option<EventLog>,
+    blocklist: Option<Arc<Blocklist>>,
 }
 
 impl RoutingTable {
     const _MAX_PERSIST_AGE_MILLIS: u64 = 24 * 60 * 60 * 1000;
 
+    #[allow(unused)]
     pub(crate) fn new(nodeid: Id) -> Self {
+        Self::with_strategy(nodeid, RoutingStrategy::default())
+    }
+
+    pub(crate) fn with_strategy(nodeid: Id, strategy: RoutingStrategy) -> Self {
         let prefix = Prefix::new();
-        let bucket = Rc::new(RefCell::new(KBucket::home_bucket(prefix)));
+        let bucket = KBucket::home_bucket(prefix, strategy.bucket_capacity);
         let mut bs = RBTree::new();
-        bs.insert(prefix, bucket);
+        bs.insert(prefix, Rc::new(RefCell::new(bucket)));
 
         Self {
             nodeid  : nodeid,
             buckets : bs,
+            strategy,
             updated : SystemTime::UNIX_EPOCH,
             saved   : SystemTime::UNIX_EPOCH,
+            events  : None,
+            blocklist: None,
+        }
+    }
+
+    pub(crate) fn set_event_log(&mut self, events: EventLog) {
+        self.events = Some(events);
+    }
+
+    pub(crate) fn set_blocklist(&mut self, blocklist: Arc<Blocklist>) {
+        self.blocklist = Some(blocklist);
+    }
+
+    fn record(&self, kind: NodeEventKind) {
+        if let Some(events) = self.events.as_ref() {
+            events.record(kind);
         }
     }
 
@@ -63,6 +114,21 @@ impl RoutingTable {
         p.is_prefix_of(&self.nodeid)
     }
 
+    // Leading bits of the bucket range shared with the local id, the fewer
+    // the more distant the bucket.
+    fn common_bits(&self, p: &Prefix) -> usize {
+        let bits = (p.depth() + 1) as usize;
+        p.id().common_prefix_len(&self.nodeid).min(bits)
+    }
+
+    // The home bucket, or one branching off the path to it within
+    // home_split_levels levels above it.
+    fn is_near_home(&self, p: &Prefix) -> bool {
+        let home = self.bucket(&self.nodeid);
+        let home_bits = (home.borrow().prefix().depth() + 1) as usize;
+        self.common_bits(p) + self.strategy.home_split_levels > home_bits
+    }
+
     pub(crate) fn nodeid(&self) -> &Id {
         &self.nodeid
     }
@@ -82,6 +148,10 @@ impl RoutingTable {
         self.buckets.values().cloned().collect()
     }
 
+    pub(crate) fn snapshot(&self) -> Vec<BucketInfo> {
+        self.buckets.values().map(|v| v.borrow().info()).collect()
+    }
+
     pub(crate) fn bucket_entry(&self, id: &Id) -> Option<KBucketEntry> {
         self.bucket(id).borrow().entry(Some(id))
     }
@@ -95,6 +165,13 @@ impl RoutingTable {
         self.bucket(id).borrow().contains(id)
     }
 
+    // Entries with their ids under the prefix.
+    pub(crate) fn entries_within(&self, prefix: &Prefix) -> usize {
+        self.buckets.values()
+            .map(|v| v.borrow().entries().iter().filter(|e| prefix.is_prefix_of(e.id())).count())
+            .sum()
+    }
+
     pub(crate) fn number_of_entries(&self) -> usize {
         self.buckets.values().map(|v| v.borrow().size()).sum()
     }
@@ -130,17 +207,37 @@ impl RoutingTable {
     }
 
     pub(crate) fn put(&mut self, entry: KBucketEntry) {
+        let id = *entry.id();
         self._put(entry);
+        self.bucket(&id).borrow_mut().update_activity_time();
         self.updated = SystemTime::now();
     }
 
     pub(crate) fn remove(&mut self, id: &Id) -> Option<KBucketEntry> {
         self._remove(id).map(|entry| {
             self.updated = SystemTime::now();
+            self.record(NodeEventKind::EntryEvicted { id: *entry.id() });
             entry
         })
     }
 
+    // Drops the entries of the node ids and addresses blocked since they
+    // got in, returns how many.
+    pub(crate) fn evict_blocked(&mut self) -> usize {
+        let Some(blocklist) = self.blocklist.clone() else {
+            return 0;
+        };
+        let blocked = self.buckets.values()
+            .flat_map(|bucket| bucket.borrow().entries())
+            .filter(|entry| blocklist.blocks_node(entry.id(), &entry.socket_addr().ip()))
+            .map(|entry| *entry.id())
+            .collect::<Vec<_>>();
+
+        let evicted = blocked.iter().filter(|id| self.remove(id).is_some()).count();
+        blocklist.on_evicted(evicted);
+        evicted
+    }
+
     pub(crate) fn on_timeout(&self, id: &Id) {
         self._on_timeout(id)
     }
@@ -149,9 +246,17 @@ impl RoutingTable {
         self._on_request_sent(id)
     }
 
+    // The node answered with forged data.
+    pub(crate) fn on_strike(&self, id: &Id) {
+        self.bucket(id).borrow_mut().on_strike(id);
+    }
+
     #[allow(unused)]
     pub(crate) fn on_responded(&mut self, id: &Id, rtt: u64) {
-        self.bucket(id).borrow_mut().on_responded(id, rtt);
+        let bucket = self.bucket(id);
+        let mut borrowed = bucket.borrow_mut();
+        borrowed.on_responded(id, rtt);
+        borrowed.update_activity_time();
     }
 
     // The bucket has already been removed from the routing table
@@ -162,17 +267,23 @@ impl RoutingTable {
         let lp = prefix.split_branch(false);
         let hp = prefix.split_branch(true);
 
-        let mut low  = KBucket::new(lp, self.is_home_bucket(&lp));
-        let mut high = KBucket::new(hp, self.is_home_bucket(&hp));
+        let capacity = self.strategy.bucket_capacity;
+        let mut low  = KBucket::new(lp, self.is_home_bucket(&lp), capacity);
+        let mut high = KBucket::new(hp, self.is_home_bucket(&hp), capacity);
+        low.set_last_activity(borrowed.last_activity());
+        high.set_last_activity(borrowed.last_activity());
 
         for item in borrowed.entries().iter().cloned() {
             match lp.is_prefix_of(item.id()) {
                 true  => low.put(item),
                 false => high.put(item)
-            }
+            };
         }
+        let depth = prefix.depth();
         drop(borrowed);
 
+        self.record(NodeEventKind::BucketSplit { depth });
+
         self.modify(
             vec![bucket],
             vec![Rc::new(RefCell::new(low)), Rc::new(RefCell::new(high))]
@@ -194,17 +305,26 @@ impl RoutingTable {
     }
 
     fn _put(&mut self, entry: KBucketEntry) {
+        if let Some(blocklist) = self.blocklist.as_ref() {
+            if blocklist.refuses_entry(entry.id(), &entry.socket_addr().ip()) {
+                return;
+            }
+        }
         let entry_id = entry.id();
         let mut bucket = self.bucket(entry_id);
 
-        while Self::needs_split(&bucket, &entry) {
+        while self.needs_split(&bucket, &entry) {
             self._split(bucket);
             bucket = self.bucket(entry_id);
         }
-        bucket.borrow_mut().put(entry);
+        let evicted = bucket.borrow_mut().put(entry);
+        if let Some(evicted) = evicted {
+            self.record(NodeEventKind::EntryEvicted { id: *evicted.id() });
+        }
+        self._trim();
     }
 
-    fn needs_split(bucket: &Rc<RefCell<KBucket>>, entry: &KBucketEntry) -> bool {
+    fn needs_split(&self, bucket: &Rc<RefCell<KBucket>>, entry: &KBucketEntry) -> bool {
         let borrowed = bucket.borrow();
         if !borrowed.prefix().is_splittable() ||
             !borrowed.is_full() ||
@@ -213,11 +333,37 @@ impl RoutingTable {
             return false;
         }
 
+        if self.strategy.home_split_levels > 0 && self.is_near_home(borrowed.prefix()) {
+            return true;
+        }
+
         borrowed.prefix()
             .split_branch(true)
             .is_prefix_of(entry.id())
     }
 
+    // Keeps the table within max_entries, dropping entries from the buckets
+    // most distant from the local id first.
+    fn _trim(&mut self) {
+        if self.strategy.max_entries == 0 {
+            return;
+        }
+        while self.number_of_entries() > self.strategy.max_entries {
+            let farthest = self.buckets.values()
+                .filter(|v| !v.borrow().is_empty())
+                .min_by_key(|v| self.common_bits(v.borrow().prefix()))
+                .cloned();
+
+            let Some(bucket) = farthest else {
+                break;
+            };
+            let evicted = bucket.borrow_mut()._evict_slowest();
+            if let Some(evicted) = evicted {
+                self.record(NodeEventKind::EntryEvicted { id: *evicted.id() });
+            }
+        }
+    }
+
     fn _remove(&self, id: &Id) -> Option<KBucketEntry> {
         let bucket = self.bucket(id);
         let mut borrow_mut = bucket.borrow_mut();
@@ -265,10 +411,10 @@ impl RoutingTable {
             let borrowed_r = r.borrow();
 
             if borrowed_l.prefix().is_sibling_of(borrowed_r.prefix()) {
-                let effective_sz1 = borrowed_l.entries().iter().filter(|e| e.removable_without_replacement()).count();
-                let effective_sz2 = borrowed_r.entries().iter().filter(|e| e.removable_without_replacement()).count();
+                let effective_sz1 = borrowed_l.entries().iter().filter(|e| !e.removable_without_replacement()).count();
+                let effective_sz2 = borrowed_r.entries().iter().filter(|e| !e.removable_without_replacement()).count();
 
-                if effective_sz1 + effective_sz2 <= KBucket::MAX_ENTRIES {
+                if effective_sz1 + effective_sz2 <= self.strategy.bucket_capacity {
                     debug!("Merging buckets {} and {}...",
                         borrowed_l.prefix(),
                         borrowed_r.prefix()
@@ -276,7 +422,10 @@ impl RoutingTable {
 
                     let prefix = borrowed_l.prefix().parent();
                     let is_home_bucket = self.is_home_bucket(&prefix);
-                    let mut new_bucket = KBucket::new(prefix, is_home_bucket);
+                    let mut new_bucket = KBucket::new(prefix, is_home_bucket, self.strategy.bucket_capacity);
+                    new_bucket.set_last_activity(
+                        borrowed_l.last_activity().max(borrowed_r.last_activity())
+                    );
 
                     for entry in borrowed_l.entries().iter().cloned() {
                         new_bucket.put(entry);
@@ -290,17 +439,34 @@ impl RoutingTable {
                         vec![Rc::new(RefCell::new(new_bucket))]
                     );
 
-                    idx -= 2; // Adjust index to re-check after merge
+                    idx = idx.saturating_sub(2); // Adjust index to re-check after merge
                 }
             }
             debug!("Finished merge buckets({})... ", self.buckets.len());
         }
     }
 
+    // Buckets idle for longer than refresh_interval milliseconds, least
+    // recently active first.
+    pub(crate) fn idle_buckets(&self, refresh_interval: u128) -> Vec<Rc<RefCell<KBucket>>> {
+        let mut idle = self.buckets.values()
+            .filter(|v| v.borrow().is_idle(refresh_interval))
+            .cloned()
+            .collect::<Vec<_>>();
+        idle.sort_by_key(|v| v.borrow().last_activity());
+        idle
+    }
+
+    // Besides pinging the buckets that need it, hands up to max_refreshes
+    // buckets idle beyond refresh_interval (milliseconds, 0 disables it) to
+    // the lookup handler, which looks up a random id in their range.
     pub(crate) fn maintenance(
         &mut self,
         bootstrap_ids: &[Id],
-        handler: Handler<Rc<RefCell<KBucket>>>
+        refresh_interval: u128,
+        max_refreshes: usize,
+        handler: Handler<Rc<RefCell<KBucket>>>,
+        lookup_handler: Handler<Prefix>
     ){
         self._merge_buckets();
         self.updated = SystemTime::now();
@@ -325,6 +491,16 @@ impl RoutingTable {
                 handler.cb(&bucket);
             }
         }
+
+        if refresh_interval == 0 || max_refreshes == 0 {
+            return;
+        }
+        for bucket in self.idle_buckets(refresh_interval).into_iter().take(max_refreshes) {
+            let prefix = *bucket.borrow().prefix();
+            bucket.borrow_mut().update_activity_time();
+            log::debug!("Bucket {} is idle, refreshing it with a lookup", prefix);
+            lookup_handler.cb(&prefix);
+        }
     }
 
     pub(crate) fn save(&mut self, path: &Path) -> Result<()> {
@@ -2,7 +2,7 @@ use std::{
     net::SocketAddr,
     time::{Duration, SystemTime},
 };
-use crate::Id;
+use crate::{Id, Capabilities};
 use crate::dht::{
     rpc::{
         rpc_target::Reachability,
@@ -68,8 +68,63 @@ mod tests {
         assert_eq!(first.created_time(), second.created_time());
         assert_eq!(first.last_seen(), second.last_seen());
         assert_eq!(first.last_sent(), second.last_sent());
-        //assert!(first.rtt() < 100);
-        //assert!(first.rtt() > 40);
+        assert_eq!(first.avg_rtt(), Some(40.0));
+
+        // The merged estimate counts as one more sample
+        let mut third = first.clone();
+        third.on_responded(100);
+        first.merge(third);
+        assert!(first.avg_rtt().unwrap() > 40.0);
+        assert!(first.avg_rtt().unwrap() < 100.0);
+    }
+
+    #[test]
+    fn test_strike() {
+        let mut entry = make_entry();
+        entry.on_responded(20);
+        assert!(entry.eligible_for_nodes_list());
+
+        // Unlike timeouts, forged answers outlive the next response
+        for _ in 0..3 {
+            entry.on_strike();
+        }
+        entry.on_responded(20);
+        assert_eq!(entry.failed_reqs(), 0);
+        assert_eq!(entry.strikes(), 3);
+        assert!(!entry.eligible_for_nodes_list());
+        assert!(entry.eligible_for_local_lookup());
+        assert!(!entry.needs_replacement());
+
+        let mut fresh = entry.clone();
+        fresh.on_responded(20);
+        fresh.merge(make_entry());
+        entry.on_strike();
+        fresh.merge(entry.clone());
+        assert_eq!(fresh.strikes(), 4);
+        assert!(!fresh.eligible_for_local_lookup());
+
+        entry.on_strike();
+        entry.on_strike();
+        assert!(entry.needs_replacement());
+        assert!(entry.to_string().contains("; strikes: 6"));
+    }
+
+    #[test]
+    fn test_avg_rtt() {
+        let mut entry = make_entry();
+        assert_eq!(entry.avg_rtt(), None);
+
+        // A response without a round trip time is no sample
+        entry.on_responded(0);
+        assert_eq!(entry.avg_rtt(), None);
+
+        entry.on_responded(20);
+        assert_eq!(entry.avg_rtt(), Some(20.0));
+        entry.on_responded(55);
+        assert!((entry.avg_rtt().unwrap() - 30.5).abs() < 1e-9);
+        entry.on_responded(10);
+        assert!((entry.avg_rtt().unwrap() - 24.35).abs() < 1e-9);
+        assert!(entry.to_string().ends_with("; rtt: 24ms"));
     }
 
     #[test]
@@ -91,7 +146,34 @@ mod tests {
         assert_eq!(decoded.socket_addr(), entry.socket_addr());
         assert_eq!(decoded.failed_reqs(), entry.failed_reqs());
         assert_eq!(decoded.is_reachable(), entry.is_reachable());
-        //assert_eq!(decoded.rtt(), entry.rtt());
+        assert_eq!(decoded.avg_rtt(), entry.avg_rtt());
         assert_eq!(decoded.ni().version(), entry.ni().version());
     }
+
+    #[test]
+    fn test_capabilities() {
+        let mut entry = make_entry();
+        entry.set_ver(1234);
+        entry.update_last_sent(SystemTime::now());
+        entry.on_responded(20);
+        assert_eq!(entry.capabilities(), Capabilities::FULL_NODE);
+
+        // Persisted only when advertised.
+        let decoded: KBucketEntry = serde_cbor::from_slice(&serde_cbor::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(decoded.ni().advertised_capabilities(), None);
+
+        entry.set_capabilities(Capabilities::CLIENT_ONLY);
+        let decoded: KBucketEntry = serde_cbor::from_slice(&serde_cbor::to_vec(&entry).unwrap()).unwrap();
+        assert_eq!(decoded.capabilities(), Capabilities::CLIENT_ONLY);
+
+        // Not forgotten when the node stops telling them.
+        let mut merged = entry.clone();
+        merged.merge(KBucketEntry::new(*entry.id(), *entry.socket_addr()));
+        assert_eq!(merged.capabilities(), Capabilities::CLIENT_ONLY);
+
+        let mut update = KBucketEntry::new(*entry.id(), *entry.socket_addr());
+        update.set_capabilities(Capabilities::STORAGE | Capabilities::RELAY);
+        merged.merge(update);
+        assert_eq!(merged.capabilities(), Capabilities::STORAGE | Capabilities::RELAY);
+    }
 }
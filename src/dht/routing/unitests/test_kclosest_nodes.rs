@@ -30,6 +30,15 @@ fn make_kentry(id: Id, port: u16) -> KBucketEntry {
     entry
 }
 
+fn make_kentry_with_rtt(id: Id, port: u16, rtt: Option<u64>) -> KBucketEntry {
+    let mut entry = KBucketEntry::new(
+        id,
+        format!("127.0.0.1:{port}").parse::<SocketAddr>().unwrap(),
+    );
+    entry.on_responded(rtt.unwrap_or(0));
+    entry
+}
+
 fn make_rt(local_id: Id) -> RoutingTable {
     RoutingTable::new(local_id)
 }
@@ -110,6 +119,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fill_prefers_low_rtt() {
+        // All share a 7 bit prefix with the target, the last one is closer
+        let mut rt = make_rt(Id::zero());
+        rt.put(make_kentry_with_rtt(make_id(0x01, 1), 37000, Some(80)));
+        rt.put(make_kentry_with_rtt(make_id(0x01, 2), 37001, None));
+        rt.put(make_kentry_with_rtt(make_id(0x01, 3), 37002, Some(10)));
+        rt.put(make_kentry_with_rtt(make_id(0x01, 4), 37003, Some(30)));
+        rt.put(make_kentry_with_rtt(make_id(0x03, 1), 37004, Some(500)));
+        let target = make_id(0x03, 0);
+
+        let ids = |prefer_low_rtt: bool| {
+            let mut closest = make_closest(&rt, target, 3);
+            closest.set_prefer_low_rtt(prefer_low_rtt);
+            closest.fill();
+            closest.entries().iter().map(|e| e.id().clone()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(false), vec![make_id(0x03, 1), make_id(0x01, 1), make_id(0x01, 2)]);
+        assert_eq!(ids(true), vec![make_id(0x03, 1), make_id(0x01, 3), make_id(0x01, 4)]);
+    }
+
     #[test]
     fn test_set_filter() {
         let rt = make_split_rt();
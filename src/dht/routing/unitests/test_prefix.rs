@@ -121,6 +121,11 @@ mod tests {
             let prefix = Prefix::from(&id, i);
             let last = prefix.last();
             assert_eq!(prefix.is_prefix_of(&last), true);
+
+            // The bits past the prefix are all set.
+            let bit = (i + 1) as usize;
+            assert_ne!(last.as_bytes()[bit / 8] & (0x80 >> (bit % 8)), 0);
+            assert_eq!(last.as_bytes()[Id::BYTES - 1] & 0x01, 0x01);
         }
     }
 
@@ -1,16 +1,22 @@
 use std::{
+    cmp::Ordering,
     net::SocketAddr,
-    time::SystemTime,
+    time::{Duration, SystemTime},
+    rc::Rc,
+    cell::RefCell,
 };
 
 use crate::{
     Id,
     dht::{
+    handler::Handler,
     rpc::rpc_target::Reachability,
     routing::{
+        prefix::Prefix,
         kbucket::KBucket,
         kbucket_entry::KBucketEntry,
-        routing_table::RoutingTable,
+        routing_table::{RoutingTable, RoutingStrategy},
+        kclosest_nodes::KClosestNodes,
     },
     }
 };
@@ -48,6 +54,22 @@ fn fill_and_split_table() -> (RoutingTable, Id, Id) {
     (rt, make_id(0x00, 1), high_id)
 }
 
+// Ids sharing the first 18 bits with the zero id, differing in the next 6.
+fn clustered_entry(i: u8) -> KBucketEntry {
+    let mut bytes = [0u8; Id::BYTES];
+    bytes[2] = i;
+    bytes[Id::BYTES - 1] = 1;
+    make_reachable_entry(Id::from_bytes(bytes), &format!("127.0.0.1:{}", 32000 + i as u16))
+}
+
+fn populate(strategy: RoutingStrategy, count: u8) -> RoutingTable {
+    let mut rt = RoutingTable::with_strategy(Id::zero(), strategy);
+    for i in 0..count {
+        rt.put(clustered_entry(i));
+    }
+    rt
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -127,6 +149,190 @@ mod tests {
         let responsed = rt.bucket_entry(&id).unwrap();
         assert_eq!(responsed.is_reachable(), true);
         assert_eq!(responsed.failed_reqs(), 0);
-        //assert_eq!(responsed.rtt(), 31);
+        assert!((responsed.avg_rtt().unwrap() - 30.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_closest_nodes_ordering() {
+        let (rt, _, high_id) = fill_and_split_table();
+        let target = make_id(0x00, 5);
+
+        let mut kns = KClosestNodes::new(&rt, target, KBucket::MAX_ENTRIES);
+        kns.fill();
+
+        let entries = kns.entries();
+        assert_eq!(entries.len(), KBucket::MAX_ENTRIES);
+        assert_eq!(entries[0].id(), &target);
+        for pair in entries.windows(2) {
+            assert_eq!(target.three_way_compare(pair[0].id(), pair[1].id()), Ordering::Less);
+        }
+        // the far bucket entry is shaved off by the closer ones.
+        assert!(entries.iter().all(|e| e.id() != &high_id));
+    }
+
+    #[test]
+    fn test_closest_nodes_count() {
+        let (rt, _, _) = fill_and_split_table();
+        let target = make_id(0x80, 1);
+
+        let mut kns = KClosestNodes::new(&rt, target, 3);
+        kns.fill();
+        assert_eq!(kns.size(), 3);
+        assert_eq!(kns.entries()[0].id(), &target);
+
+        let mut kns = KClosestNodes::new(&rt, target, 100);
+        kns.fill();
+        assert_eq!(kns.size(), rt.number_of_entries());
+    }
+
+    #[test]
+    fn test_closest_nodes_include_self() {
+        let (rt, low_id, high_id) = fill_and_split_table();
+        let local_id = rt.nodeid().clone();
+        let addr = "127.0.0.1:39001".parse::<SocketAddr>().unwrap();
+
+        let mut kns = KClosestNodes::new(&rt, local_id, 4);
+        kns.fill();
+        assert!(kns.entries().iter().all(|e| e.id() != &local_id));
+        assert_eq!(kns.entries()[0].id(), &low_id);
+
+        kns.add_self(addr);
+        assert_eq!(kns.size(), 4);
+        assert_eq!(kns.entries()[0].id(), &local_id);
+        assert_eq!(kns.entries()[1].id(), &low_id);
+
+        // farther than the capacity allows, the local node is capped out.
+        let mut kns = KClosestNodes::new(&rt, high_id, 2);
+        kns.fill();
+        kns.add_self(addr);
+        assert_eq!(kns.size(), 2);
+        assert_eq!(kns.entries()[0].id(), &high_id);
+        assert!(kns.entries().iter().all(|e| e.id() != &local_id));
+    }
+
+    // Runs a maintenance pass and returns the prefixes queued for a refresh lookup.
+    fn refresh_pass(rt: &mut RoutingTable, interval: u128, max_refreshes: usize) -> Vec<Prefix> {
+        let queued = Rc::new(RefCell::new(Vec::new()));
+        let cloned = queued.clone();
+        rt.maintenance(&[], interval, max_refreshes,
+            Handler::new(|_| {}),
+            Handler::new(move |prefix: &Prefix| cloned.borrow_mut().push(*prefix))
+        );
+        let queued = queued.borrow().clone();
+        queued
+    }
+
+    #[test]
+    fn test_idle_bucket_refresh() {
+        let (mut rt, low_id, high_id) = fill_and_split_table();
+        assert_eq!(rt.size(), 2);
+
+        std::thread::sleep(Duration::from_millis(300));
+        // Only the high bucket sees traffic
+        let before = SystemTime::now();
+        rt.on_responded(&high_id, 20);
+
+        let queued = refresh_pass(&mut rt, 200, 2);
+        assert_eq!(queued.len(), 1);
+        assert!(queued[0].is_prefix_of(&low_id));
+        assert!(!queued[0].is_prefix_of(&high_id));
+
+        // The refreshed bucket is active again, nothing else is queued
+        let snapshot = rt.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert!(snapshot.iter().all(|b| b.last_activity() >= before));
+        assert!(refresh_pass(&mut rt, 200, 2).is_empty());
+
+        // Disabled refresh never queues a lookup
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(refresh_pass(&mut rt, 0, 2).is_empty());
+    }
+
+    #[test]
+    fn test_idle_bucket_refresh_bounded() {
+        let (mut rt, low_id, _) = fill_and_split_table();
+        std::thread::sleep(Duration::from_millis(300));
+
+        // Both buckets are idle, the least recently active goes first
+        let queued = refresh_pass(&mut rt, 200, 1);
+        assert_eq!(queued.len(), 1);
+        assert!(queued[0].is_prefix_of(&low_id));
+
+        let queued = refresh_pass(&mut rt, 200, 1);
+        assert_eq!(queued.len(), 1);
+        assert!(!queued[0].is_prefix_of(&low_id));
+
+        assert!(refresh_pass(&mut rt, 200, 0).is_empty());
+    }
+
+    #[test]
+    fn test_home_split_levels() {
+        // The classic rule never splits without an entry in the high branch
+        let rt = populate(RoutingStrategy::default(), 64);
+        assert_eq!(rt.size(), 1);
+        assert_eq!(rt.number_of_entries(), KBucket::MAX_ENTRIES);
+
+        let deepened = populate(RoutingStrategy { home_split_levels: 1, ..Default::default() }, 64);
+        assert_eq!(deepened.number_of_entries(), 56);
+
+        let snapshot = deepened.snapshot();
+        let home = snapshot.iter().find(|b| b.is_home_bucket()).unwrap();
+        assert_eq!(snapshot.iter().filter(|b| b.is_home_bucket()).count(), 1);
+        assert_eq!(home.depth(), 20);
+        assert_eq!(home.entries(), KBucket::MAX_ENTRIES);
+        assert_eq!(snapshot.iter().map(|b| b.depth()).max(), Some(20));
+        assert_eq!(snapshot.iter().map(|b| b.entries()).sum::<usize>(), 56);
+
+        // Branches next to the home bucket split as well
+        let wider = populate(RoutingStrategy { home_split_levels: 4, ..Default::default() }, 64);
+        assert!(wider.number_of_entries() > deepened.number_of_entries());
+        assert!(wider.size() > deepened.size());
+    }
+
+    #[test]
+    fn test_bucket_capacity() {
+        let strategy = RoutingStrategy { bucket_capacity: 16, ..Default::default() };
+        let rt = populate(strategy, 64);
+        assert_eq!(rt.size(), 1);
+        assert_eq!(rt.number_of_entries(), 16);
+
+        let strategy = RoutingStrategy { bucket_capacity: 16, home_split_levels: 1, ..Default::default() };
+        let rt = populate(strategy, 64);
+        assert!(rt.snapshot().iter().all(|b| b.entries() <= 16));
+        assert_eq!(rt.number_of_entries(), 64);
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let strategy = RoutingStrategy {
+            home_split_levels: 1,
+            max_entries: 16,
+            ..Default::default()
+        };
+        let mut rt = RoutingTable::with_strategy(Id::zero(), strategy);
+
+        let far = (0..KBucket::MAX_ENTRIES)
+            .map(|i| make_id(0x80, i as u8 + 1))
+            .collect::<Vec<_>>();
+        for (i, id) in far.iter().enumerate() {
+            let mut entry = KBucketEntry::new(*id, format!("127.0.0.1:{}", 31000 + i).parse().unwrap());
+            entry.on_responded(if i == 2 { 500 } else { 20 });
+            rt.put(entry);
+        }
+        for i in 0..9 {
+            rt.put(clustered_entry(i));
+        }
+
+        // The slowest entry of the most distant bucket goes first
+        assert_eq!(rt.number_of_entries(), 16);
+        assert!(!rt.contains(&far[2]));
+        assert_eq!(far.iter().filter(|id| rt.contains(id)).count(), 7);
+
+        for i in 9..64 {
+            rt.put(clustered_entry(i));
+        }
+        assert_eq!(rt.number_of_entries(), 16);
+        assert!(far.iter().all(|id| !rt.contains(id)));
+        assert!(rt.contains(clustered_entry(0).id()));
     }
 }
@@ -0,0 +1,218 @@
+use std::{
+    io,
+    thread::{self, JoinHandle},
+    time::Duration,
+    net::{SocketAddr, UdpSocket as StdUdpSocket},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+};
+use log::{debug, warn};
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::{
+    Id,
+    Identity,
+    CryptoIdentity,
+    dht::{
+        blocklist::Blocklist,
+        socket_event::SocketErrors,
+        stats::ReceiveStats,
+    },
+};
+
+// Packets read by the receive threads waiting for the DHT thread, those
+// arriving beyond it are dropped.
+pub(crate) const RECEIVE_QUEUE_SIZE: usize = 4096;
+
+// How long a receive thread blocks on its socket before checking whether
+// it should stop.
+const READ_TIMEOUT: Duration = Duration::from_millis(200);
+
+// A packet read on a receive thread. Packets encrypted for the node id
+// come decrypted, the others (encrypted for a session id, or malformed)
+// are left to the DHT thread as they arrived.
+pub(crate) struct Inbound {
+    pub(crate) from         : SocketAddr,
+    pub(crate) data         : Vec<u8>,
+    pub(crate) decrypted    : Option<(Id, Vec<u8>)>,
+}
+
+// Binds count UDP sockets to the address with SO_REUSEPORT, the kernel
+// then spreads the incoming packets over them by source. A port of 0 is
+// resolved by the first socket and shared by the others.
+#[cfg(target_os = "linux")]
+pub(crate) fn bind_reuse_port(addr: SocketAddr, count: usize) -> io::Result<Vec<StdUdpSocket>> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let bind = |addr: SocketAddr| -> io::Result<StdUdpSocket> {
+        let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_port(true)?;
+        socket.bind(&addr.into())?;
+        Ok(socket.into())
+    };
+
+    let first = bind(addr)?;
+    let addr = first.local_addr()?;
+    let mut sockets = vec![first];
+    for _ in 1..count {
+        sockets.push(bind(addr)?);
+    }
+    Ok(sockets)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn bind_reuse_port(_: SocketAddr, _: usize) -> io::Result<Vec<StdUdpSocket>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Multiple receive sockets need Linux"))
+}
+
+#[derive(Default)]
+struct Counters {
+    packets : AtomicU64,
+    dropped : AtomicU64,
+}
+
+struct Receiver {
+    identity    : Arc<CryptoIdentity>,
+    blocklist   : Option<Arc<Blocklist>>,
+    errors      : Option<Arc<SocketErrors>>,
+    queue       : mpsc::Sender<Inbound>,
+    stop        : Arc<AtomicBool>,
+    counters    : Arc<Counters>,
+}
+
+impl Receiver {
+    fn run(self, socket: StdUdpSocket) {
+        if let Err(e) = socket.set_read_timeout(Some(READ_TIMEOUT)) {
+            warn!("Failed to set the read timeout of a receive socket: {e}");
+        }
+
+        let mut buf = vec![0u8; 2048];
+        while !self.stop.load(Ordering::Relaxed) {
+            let (len, from) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+                Err(e) => {
+                    warn!("Receiving data error: {e}");
+                    if let Some(errors) = self.errors.as_ref() {
+                        errors.receive_failed(&e);
+                    }
+                    continue;
+                }
+            };
+            self.counters.packets.fetch_add(1, Ordering::Relaxed);
+
+            let Some(inbound) = self.check(&buf[..len], from) else {
+                continue;
+            };
+            match self.queue.try_send(inbound) {
+                Ok(()) => {},
+                Err(TrySendError::Full(_)) => {
+                    self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Closed(_)) => break,
+            }
+        }
+    }
+
+    // Drops the packets of blocked nodes and decrypts those for the node
+    // id, the rest is checked again on the DHT thread.
+    fn check(&self, data: &[u8], from: SocketAddr) -> Option<Inbound> {
+        let blocklist = self.blocklist.as_ref();
+        if blocklist.is_some_and(|b| b.drops_addr(&from.ip())) {
+            debug!("Dropped packet from blocked address {}", from);
+            return None;
+        }
+
+        let mut inbound = Inbound {
+            from,
+            data: data.to_vec(),
+            decrypted: None,
+        };
+        if data.len() <= Id::BYTES {
+            return Some(inbound);
+        }
+        let Ok(from_id) = Id::try_from(&data[..Id::BYTES]) else {
+            return Some(inbound);
+        };
+        if blocklist.is_some_and(|b| b.drops_id(&from_id)) {
+            debug!("Dropped packet from blocked node {}@{}", from_id, from);
+            return None;
+        }
+
+        if let Ok(decrypted) = self.identity.decrypt_into(&from_id, &data[Id::BYTES..]) {
+            inbound.decrypted = Some((from_id, decrypted));
+        }
+        Some(inbound)
+    }
+}
+
+// The threads reading the sockets of a multi-socket RPC server, each
+// handing its packets over a bounded queue to the DHT thread.
+pub(crate) struct Receivers {
+    stop        : Arc<AtomicBool>,
+    threads     : Vec<JoinHandle<()>>,
+    counters    : Vec<Arc<Counters>>,
+    queue       : mpsc::Sender<Inbound>,
+}
+
+impl Receivers {
+    pub(crate) fn spawn(
+        name: &str,
+        sockets: Vec<StdUdpSocket>,
+        identity: Arc<CryptoIdentity>,
+        blocklist: Option<Arc<Blocklist>>,
+        errors: Option<Arc<SocketErrors>>,
+    ) -> io::Result<(Self, mpsc::Receiver<Inbound>)> {
+        let (tx, rx) = mpsc::channel(RECEIVE_QUEUE_SIZE);
+        let mut receivers = Self {
+            stop    : Arc::new(AtomicBool::new(false)),
+            threads : Vec::with_capacity(sockets.len()),
+            counters: Vec::with_capacity(sockets.len()),
+            queue   : tx,
+        };
+
+        for (i, socket) in sockets.into_iter().enumerate() {
+            let receiver = Receiver {
+                identity    : identity.clone(),
+                blocklist   : blocklist.clone(),
+                errors      : errors.clone(),
+                queue       : receivers.queue.clone(),
+                stop        : receivers.stop.clone(),
+                counters    : Arc::new(Counters::default()),
+            };
+            receivers.counters.push(receiver.counters.clone());
+
+            let thread = thread::Builder::new()
+                .name(format!("{name}-rx{i}"))
+                .spawn(move || receiver.run(socket))?;
+            receivers.threads.push(thread);
+        }
+        Ok((receivers, rx))
+    }
+
+    pub(crate) fn stats(&self) -> ReceiveStats {
+        ReceiveStats {
+            packets : self.counters.iter().map(|c| c.packets.load(Ordering::Relaxed)).collect(),
+            dropped : self.counters.iter().map(|c| c.dropped.load(Ordering::Relaxed)).collect(),
+            capacity: self.queue.max_capacity(),
+            depth   : self.queue.max_capacity() - self.queue.capacity(),
+        }
+    }
+
+    // Waits up to the read timeout for the threads to see the stop flag,
+    // the sockets close as they exit.
+    pub(crate) fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Receivers {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
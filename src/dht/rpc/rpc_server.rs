@@ -1,20 +1,24 @@
 use std::{
     fmt,
+    io,
     rc::{Rc, Weak},
     sync::Arc,
-    cell::RefCell,
-    collections::HashMap,
-    time::SystemTime,
-    net::{SocketAddr, UdpSocket as StdUdpSocket},
+    cell::{Cell, RefCell},
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime},
+    net::{IpAddr, SocketAddr, UdpSocket as StdUdpSocket},
 };
 use log::{info, warn, error, debug, trace};
-use tokio::net::UdpSocket;
+use tokio::{net::UdpSocket, sync::mpsc};
 use crate::{
     CryptoBox,
     CryptoIdentity,
     Id, Identity,
     NodeInfo,
+    EndpointPolicy,
+    Capabilities,
     cryptobox::Nonce,
+    core::version,
     errors::{
         Error,
         Result,
@@ -26,18 +30,57 @@ use crate::{
 use crate::dht::{
     timer_client::LocalTimerClient as TimerClient,
     suspicious_node_detector::SuspiciousNodeDetector,
+    blocklist::Blocklist,
     handler::{Handler, LocalHandler as AsyncHandler},
     rpc::RpcCall,
-    msg::{Message, msg::Method},
+    routing::RoutingTable,
+    session_ids::SessionIds,
+    msg::{Body, Message, msg::{self, Method}, error::PROTOCOL_ERROR},
+    node_event::{EventLog, NodeEventKind},
+    node_config::DEFAULT_MAX_INFLIGHT_CALLS,
+    stats::RpcCounters,
+    rpc::socket_health::{
+        SocketEvent,
+        SocketFault,
+        SocketHealth,
+        SocketHealthOptions,
+    },
+    rpc::send_shaper::{SendShaper, SendShaperOptions},
+    rpc::receivers::{self, Inbound, Receivers},
+    stats::ReceiveStats,
+    socket_event::SocketErrors,
+    utils,
 };
+#[cfg(feature = "testing")]
+use crate::testing::{LossyTransport, SimTransport};
+#[cfg(feature = "wsgateway")]
+use {
+    url::Url,
+    crate::dht::rpc::ws_gateway::GatewayClient,
+};
+
+// A packet held back by the send shaper, with the call it carries if any.
+struct QueuedPacket {
+    data    : Vec<u8>,
+    dest    : SocketAddr,
+    call    : Option<Rc<RefCell<RpcCall>>>,
+}
 
 #[allow(dead_code)]
 pub(crate) struct RpcServer {
     identity            : Arc<CryptoIdentity>,
     ni                  : NodeInfo,
+    // The throwaway identities of the lookups in privacy mode.
+    sessions            : Option<Rc<RefCell<SessionIds>>>,
+    // Advertised to the targets the routing table knows to decode them.
+    capabilities        : Option<(Capabilities, Rc<RefCell<RoutingTable>>)>,
 
     suspicious_node_detector: Option<Rc<RefCell<dyn SuspiciousNodeDetector>>>,
     pending_calls       : HashMap<i32, Rc<RefCell<RpcCall>>>,
+    // Calls beyond max_inflight_calls, sent as pending ones complete.
+    deferred_calls      : VecDeque<RpcCall>,
+    max_inflight_calls  : usize,
+    peak_inflight_calls : usize,
 
     recv_packets        : u32,
     recv_packets_at_last_reachable_check: u32,
@@ -58,13 +101,45 @@ pub(crate) struct RpcServer {
     tx_socket           : Option<Rc<StdUdpSocket>>,
     rx_socket           : Option<Rc<StdUdpSocket>>,
 
+    health              : SocketHealth,
+    socket_fault        : Option<SocketFault>,
+    rebind_pending      : bool,
+    socket_generation   : u64,
+    socket_handler      : Option<AsyncHandler<SocketEvent>>,
+    // Sockets sharing the port, read on threads of their own when above 1.
+    receive_sockets     : usize,
+    receivers           : Option<Receivers>,
+    inbound             : Option<mpsc::Receiver<Inbound>>,
+    if_addrs            : fn() -> Option<Vec<IpAddr>>,
+
+    events              : Option<EventLog>,
+    counters            : Cell<RpcCounters>,
+    endpoint_policy     : EndpointPolicy,
+    blocklist           : Option<Arc<Blocklist>>,
+    socket_errors       : Option<Arc<SocketErrors>>,
+
+    shaper              : RefCell<SendShaper>,
+    send_queue          : RefCell<VecDeque<QueuedPacket>>,
+    flush_timer         : Cell<Option<u64>>,
+    sent_handler        : Option<Handler<SocketAddr>>,
+
+    #[cfg(feature = "testing")]
+    transport           : Option<Arc<LossyTransport>>,
+    #[cfg(feature = "testing")]
+    sim                 : Option<Arc<SimTransport>>,
+    // Tunnels the packets over a WebSocket instead of a socket.
+    #[cfg(feature = "wsgateway")]
+    gateway_url         : Option<Url>,
+    #[cfg(feature = "wsgateway")]
+    gateway             : Option<GatewayClient>,
+
     cloned              : Weak<RefCell<RpcServer>>,
 }
 
 impl RpcServer {
-    const MAX_ACTIVE_CALLS              : usize = 64;
     const REACHABILITY_CHECK_INTERVAL   : u64 = 5_000;
     const REACHABILITY_TIMEOUT          : u64 = 60_000;
+    const MAX_QUEUED_PACKETS            : usize = 256;
 
     pub(crate) fn new(
         ni: NodeInfo,
@@ -76,8 +151,13 @@ impl RpcServer {
         Self {
             ni,
             identity,
+            sessions            : None,
+            capabilities        : None,
             suspicious_node_detector,
             pending_calls       : HashMap::new(),
+            deferred_calls      : VecDeque::new(),
+            max_inflight_calls  : DEFAULT_MAX_INFLIGHT_CALLS,
+            peak_inflight_calls : 0,
             recv_packets        : 0,
             recv_packets_at_last_reachable_check: 0,
             last_reachable_check: SystemTime::now(),
@@ -94,15 +174,64 @@ impl RpcServer {
             tx_socket           : None,
             rx_socket           : None,
 
+            health              : SocketHealth::new(SocketHealthOptions::default()),
+            socket_fault        : None,
+            rebind_pending      : false,
+            socket_generation   : 0,
+            socket_handler      : None,
+            receive_sockets     : 1,
+            receivers           : None,
+            inbound             : None,
+            if_addrs            : utils::local_addrs,
+
+            events              : None,
+            counters            : Cell::new(RpcCounters::default()),
+            endpoint_policy     : EndpointPolicy::default(),
+            blocklist           : None,
+            socket_errors       : None,
+
+            shaper              : RefCell::new(SendShaper::new(SendShaperOptions::default())),
+            send_queue          : RefCell::new(VecDeque::new()),
+            flush_timer         : Cell::new(None),
+            sent_handler        : None,
+
+            #[cfg(feature = "testing")]
+            transport           : None,
+            #[cfg(feature = "testing")]
+            sim                 : None,
+            #[cfg(feature = "wsgateway")]
+            gateway_url         : None,
+            #[cfg(feature = "wsgateway")]
+            gateway             : None,
+
             cloned              : Weak::new(),
         }
     }
 
+    pub(crate) fn set_session_ids(&mut self, sessions: Rc<RefCell<SessionIds>>) {
+        self.sessions = Some(sessions);
+    }
+
+    pub(crate) fn set_capabilities(&mut self, caps: Capabilities, rt: Rc<RefCell<RoutingTable>>) {
+        self.capabilities = Some((caps, rt));
+    }
+
     pub(crate) fn set_cloned(&mut self, cloned: Weak<RefCell<RpcServer>>) {
         self.cloned = cloned;
     }
 
     async fn check_reachability(&mut self) {
+        if self.check_socket_health().await {
+            return;
+        }
+
+        // Nothing gets through while the tunnel is down.
+        #[cfg(feature = "wsgateway")]
+        if self.gateway.as_ref().is_some_and(|g| !g.is_connected()) {
+            self.set_reachable(false).await;
+            return;
+        }
+
         let now = SystemTime::now();
 
         if self.recv_packets != self.recv_packets_at_last_reachable_check {
@@ -144,6 +273,23 @@ impl RpcServer {
         !self.pending_calls.is_empty()
     }
 
+    pub(crate) fn set_max_inflight_calls(&mut self, max: usize) {
+        self.max_inflight_calls = max;
+    }
+
+    pub(crate) fn inflight_calls(&self) -> usize {
+        self.pending_calls.len()
+    }
+
+    // The most calls ever awaiting a response at once.
+    pub(crate) fn peak_inflight_calls(&self) -> usize {
+        self.peak_inflight_calls
+    }
+
+    pub(crate) fn deferred_calls(&self) -> usize {
+        self.deferred_calls.len()
+    }
+
     pub(crate) fn message_handler(&mut self, consumer: AsyncHandler<Rc<Message>>) {
         self.message_handler = Some(consumer);
     }
@@ -156,6 +302,268 @@ impl RpcServer {
         self.calltimeout_handler = Some(consumer);
     }
 
+    pub(crate) fn set_event_log(&mut self, events: EventLog) {
+        self.events = Some(events);
+    }
+
+    pub(crate) fn set_blocklist(&mut self, blocklist: Arc<Blocklist>) {
+        self.blocklist = Some(blocklist);
+    }
+
+    pub(crate) fn set_socket_errors(&mut self, errors: Arc<SocketErrors>) {
+        self.socket_errors = Some(errors);
+    }
+
+    pub(crate) fn set_socket_health(&mut self, options: SocketHealthOptions) {
+        self.health = SocketHealth::new(options);
+    }
+
+    pub(crate) fn set_receive_sockets(&mut self, count: usize) {
+        self.receive_sockets = count.max(1);
+    }
+
+    pub(crate) fn receive_stats(&self) -> Option<ReceiveStats> {
+        self.receivers.as_ref().map(|r| r.stats())
+    }
+
+    // The packets of the receive threads, taken once by the owner of the
+    // receive loop. None with a single socket, read by the owner itself.
+    pub(crate) fn take_inbound(&mut self) -> Option<mpsc::Receiver<Inbound>> {
+        self.inbound.take()
+    }
+
+    pub(crate) fn set_send_shaper(&mut self, options: SendShaperOptions) {
+        self.shaper = RefCell::new(SendShaper::new(options));
+    }
+
+    // Called with the destination of every packet as it leaves the socket.
+    #[cfg(test)]
+    pub(crate) fn sent_handler(&mut self, consumer: Handler<SocketAddr>) {
+        self.sent_handler = Some(consumer);
+    }
+
+    // Passes the packets sent and received through the fault injector.
+    #[cfg(feature = "testing")]
+    pub(crate) fn set_transport(&mut self, transport: Arc<LossyTransport>) {
+        self.transport = Some(transport);
+    }
+
+    // Sends and receives through the simulated network instead of a socket.
+    #[cfg(feature = "testing")]
+    pub(crate) fn set_sim(&mut self, sim: Arc<SimTransport>) {
+        self.sim = Some(sim);
+    }
+
+    #[cfg(feature = "testing")]
+    fn is_simulated(&self) -> bool {
+        self.sim.is_some()
+    }
+
+    #[cfg(not(feature = "testing"))]
+    fn is_simulated(&self) -> bool {
+        false
+    }
+
+    // Sends and receives through the WebSocket gateway at the url instead
+    // of a socket, nothing is bound.
+    #[cfg(feature = "wsgateway")]
+    pub(crate) fn set_ws_gateway(&mut self, url: Url) {
+        self.gateway_url = Some(url);
+    }
+
+    #[cfg(feature = "wsgateway")]
+    fn is_tunneled(&self) -> bool {
+        self.gateway_url.is_some()
+    }
+
+    #[cfg(not(feature = "wsgateway"))]
+    fn is_tunneled(&self) -> bool {
+        false
+    }
+
+    #[cfg(test)]
+    pub(crate) fn queued_packets(&self) -> usize {
+        self.send_queue.borrow().len()
+    }
+
+    pub(crate) fn socket_handler(&mut self, consumer: AsyncHandler<SocketEvent>) {
+        self.socket_handler = Some(consumer);
+    }
+
+    pub(crate) fn set_endpoint_policy(&mut self, policy: EndpointPolicy) {
+        self.endpoint_policy = policy;
+    }
+
+    pub(crate) fn counters(&self) -> RpcCounters {
+        self.counters.get()
+    }
+
+    fn count(&self, update: impl FnOnce(&mut RpcCounters)) {
+        let mut counters = self.counters.get();
+        update(&mut counters);
+        self.counters.set(counters);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn socket_generation(&self) -> u64 {
+        self.socket_generation
+    }
+
+    pub(crate) fn rebind_pending(&self) -> bool {
+        self.rebind_pending
+    }
+
+    pub(crate) fn local_addr(&self) -> Option<SocketAddr> {
+        self.rx_socket.as_ref().and_then(|s| s.local_addr().ok())
+    }
+
+    fn record(&self, kind: NodeEventKind) {
+        if let Some(events) = self.events.as_ref() {
+            events.record(kind);
+        }
+    }
+
+    // Counts a failed read of the socket and tells the embedder.
+    pub(crate) fn receive_failed(&self, error: &io::Error) {
+        self.record(NodeEventKind::SocketError { kind: error.kind() });
+        if let Some(errors) = self.socket_errors.as_ref() {
+            errors.receive_failed(error);
+        }
+    }
+
+    fn bind_failed(&self, addr: SocketAddr, error: &io::Error) {
+        if let Some(errors) = self.socket_errors.as_ref() {
+            errors.bind_failed(addr, error);
+        }
+    }
+
+    async fn notify_socket_event(&mut self, event: SocketEvent) {
+        if let Some(h) = self.socket_handler.take() {
+            h.cb(event).await;
+            self.socket_handler = Some(h);
+        }
+    }
+
+    // Returns true while the socket is faulted and a rebind is wanted, the
+    // owner of the receiving socket then drops its clone and calls rebind().
+    pub(crate) async fn check_socket_health(&mut self) -> bool {
+        // The receive threads hold the sockets of a multi-socket server.
+        if !self.is_running || self.receivers.is_some() || self.is_simulated() || self.is_tunneled() {
+            return false;
+        }
+
+        if self.socket_fault.is_none() {
+            let addrs = (self.if_addrs)();
+            let Some(fault) = self.health.check(self.ni.ip(), addrs.as_deref()) else {
+                return false;
+            };
+
+            warn!("RPC server socket at {} is unhealthy: {}", self.ni.socket_addr(), fault.reason());
+            self.record(NodeEventKind::SocketUnhealthy { reason: fault.reason() });
+            self.socket_fault = Some(fault);
+            self.notify_socket_event(SocketEvent::Failed(fault)).await;
+        }
+
+        self.rebind_pending = true;
+        true
+    }
+
+    // The address to rebind to: the current one for a stalled socket, or an
+    // address of the same family still present on the interfaces.
+    fn rebind_addr(&self, fault: SocketFault) -> Option<SocketAddr> {
+        let current = *self.ni.socket_addr();
+        if fault == SocketFault::Stalled {
+            return Some(current);
+        }
+
+        let addrs = (self.if_addrs)()?;
+        let candidates = addrs.into_iter()
+            .filter(|ip| ip.is_ipv4() == current.is_ipv4() && !ip.is_unspecified())
+            .collect::<Vec<_>>();
+
+        candidates.iter()
+            .find(|ip| !ip.is_loopback())
+            .or(candidates.first())
+            .map(|ip| SocketAddr::new(*ip, current.port()))
+    }
+
+    fn rebind_socket(&mut self) -> Option<SocketAddr> {
+        self.rebind_pending = false;
+        let fault = self.socket_fault?;
+        let Some(addr) = self.rebind_addr(fault) else {
+            warn!("No usable local address to rebind the RPC server socket, will retry");
+            return None;
+        };
+
+        // The old socket must be released before binding the same port again
+        self.rx_socket = None;
+        self.tx_socket = None;
+
+        let socket = StdUdpSocket::bind(addr)
+            .or_else(|_| StdUdpSocket::bind(SocketAddr::new(addr.ip(), 0)))
+            .or_else(|_| StdUdpSocket::bind(self.ni.socket_addr()));
+
+        let socket = match socket {
+            Ok(socket) => socket,
+            Err(e) => {
+                error!("Rpc server failed to rebind udp socket at {}: {e}, will retry", addr);
+                self.record(NodeEventKind::SocketError { kind: e.kind() });
+                self.bind_failed(addr, &e);
+                return None;
+            }
+        };
+
+        let addr = match socket.local_addr() {
+            Ok(addr) => addr,
+            Err(_) => addr,
+        };
+        let socket = Rc::new(socket);
+        self.rx_socket = Some(socket.clone());
+        self.tx_socket = Some(socket);
+        self.socket_generation += 1;
+        self.socket_fault = None;
+        if let Some(errors) = self.socket_errors.as_ref() {
+            errors.rebound(*self.ni.socket_addr(), addr);
+        }
+        self.ni = NodeInfo::new(*self.ni.id(), addr);
+
+        // Start over as if freshly prepared
+        let now = SystemTime::now();
+        self.health.reset();
+        self.is_reachable = true;
+        self.recv_packets = 0;
+        self.recv_packets_at_last_reachable_check = 0;
+        self.last_reachable_check = now;
+
+        info!("RPC server socket rebound at {}", addr);
+        self.record(NodeEventKind::SocketRebound { addr });
+        Some(addr)
+    }
+
+    pub(crate) async fn rebind(server: Rc<RefCell<Self>>) {
+        let Some(addr) = server.borrow_mut().rebind_socket() else {
+            return;
+        };
+
+        let handler = server.borrow_mut().socket_handler.take();
+        if let Some(h) = handler {
+            h.cb(SocketEvent::Rebound(addr)).await;
+            server.borrow_mut().socket_handler = Some(h);
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn swap_socket(&mut self, socket: StdUdpSocket) {
+        let socket = Rc::new(socket);
+        self.rx_socket = Some(socket.clone());
+        self.tx_socket = Some(socket);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn set_if_addrs(&mut self, if_addrs: fn() -> Option<Vec<IpAddr>>) {
+        self.if_addrs = if_addrs;
+    }
+
     pub(crate) fn rx_tokio_socket(&self) -> Result<UdpSocket> {
         let std_socket = self.rx_socket.as_ref().ok_or_else(|| -> Error {
             NetworkError::new("RPC server socket not initialized")
@@ -172,9 +580,29 @@ impl RpcServer {
     }
 
     pub(crate) async fn start(&mut self) -> Result<()> {
-        let socket_addr = self.ni.socket_addr();
+        // The simulated network queues the packets like the receive threads.
+        #[cfg(feature = "testing")]
+        if let Some(sim) = self.sim.as_ref() {
+            self.inbound = Some(sim.attach(*self.ni.socket_addr()));
+            return Ok(());
+        }
+        // So does the gateway connection.
+        #[cfg(feature = "wsgateway")]
+        if let Some(url) = self.gateway_url.clone() {
+            info!("RPC server tunneling through the DHT gateway at {url}");
+            let (gateway, inbound) = GatewayClient::connect(url);
+            self.gateway = Some(gateway);
+            self.inbound = Some(inbound);
+            return Ok(());
+        }
+        if self.receive_sockets > 1 {
+            return self.start_receivers();
+        }
+
+        let socket_addr = *self.ni.socket_addr();
         let socket = StdUdpSocket::bind(socket_addr).map_err(|e| {
             error!("Rpc server failed to bind udp socket at {}: {e}", socket_addr);
+            self.bind_failed(socket_addr, &e);
             NetworkError::new(format!("{e}"))
         })?;
         let socket = Rc::new(socket);
@@ -184,6 +612,41 @@ impl RpcServer {
         Ok(())
     }
 
+    // Binds the receive sockets and starts a thread on each, packets are
+    // sent out of the first one.
+    fn start_receivers(&mut self) -> Result<()> {
+        let socket_addr = *self.ni.socket_addr();
+        let sockets = receivers::bind_reuse_port(socket_addr, self.receive_sockets).map_err(|e| {
+            error!("Rpc server failed to bind {} udp sockets at {}: {e}", self.receive_sockets, socket_addr);
+            self.bind_failed(socket_addr, &e);
+            NetworkError::new(format!("{e}"))
+        })?;
+
+        let socket = Rc::new(sockets[0].try_clone().map_err(|e| {
+            NetworkError::new(format!("Failed to clone UDP socket: {e}"))
+        })?);
+        let name = match socket_addr.is_ipv4() {
+            true  => "boson-dht4",
+            false => "boson-dht6",
+        };
+        let (receivers, inbound) = Receivers::spawn(
+            name,
+            sockets,
+            self.identity.clone(),
+            self.blocklist.clone(),
+            self.socket_errors.clone(),
+        ).map_err(|e| {
+            NetworkError::new(format!("Failed to start receive threads: {e}"))
+        })?;
+
+        info!("RPC server receiving on {} sockets at {}", self.receive_sockets, socket_addr);
+        self.rx_socket = Some(socket.clone());
+        self.tx_socket = Some(socket);
+        self.receivers = Some(receivers);
+        self.inbound = Some(inbound);
+        Ok(())
+    }
+
     pub(crate) fn prepare(&mut self) -> bool {
         let now = SystemTime::now();
         self.start_time = Some(now);
@@ -191,6 +654,7 @@ impl RpcServer {
 
         self.is_reachable   = true;
         self.last_reachable_check = now;
+        self.health.reset();
 
         let cloned = self.cloned.upgrade().expect("RpcServer weak reference not set");
         let result = self.timer_client.add_timer(
@@ -214,14 +678,32 @@ impl RpcServer {
 
     pub(crate) async fn stop(&mut self) {
         self.reachable_handler = None;
+        self.socket_handler = None;
+        if let Some(mut receivers) = self.receivers.take() {
+            receivers.stop();
+        }
+        self.inbound = None;
+        #[cfg(feature = "testing")]
+        if let Some(sim) = self.sim.as_ref() {
+            sim.detach(*self.ni.socket_addr());
+        }
+        #[cfg(feature = "wsgateway")]
+        drop(self.gateway.take());
         if !self.is_running {
             return;
         }
 
         self.pending_calls.clear();
+        self.deferred_calls.clear();
+        self.send_queue.borrow_mut().clear();
+        if let Some(timer_id) = self.flush_timer.take() {
+            let _ = self.timer_client.cancel_timer(timer_id);
+        }
 
         self.tx_socket  = None;
         self.rx_socket  = None;
+        self.socket_fault = None;
+        self.rebind_pending = false;
         self.start_time = None;
         self.is_running = false;
 
@@ -232,10 +714,27 @@ impl RpcServer {
     }
 
     pub(crate) fn send_call(&mut self, call: RpcCall) -> Result<()> {
-        if self.pending_calls.len() >= Self::MAX_ACTIVE_CALLS {
+        if self.pending_calls.len() >= self.max_inflight_calls {
+            debug!("Deferred call {} to {}, {} calls in flight",
+                call.txid(), call.target_id(), self.pending_calls.len());
+            self.deferred_calls.push_back(call);
             return Ok(());
         }
+        self.dispatch_call(call)
+    }
 
+    // Sends the deferred calls the freed slots allow, in order.
+    fn send_deferred(&mut self) {
+        while self.pending_calls.len() < self.max_inflight_calls {
+            let Some(call) = self.deferred_calls.pop_front() else {
+                break;
+            };
+            let _ = self.dispatch_call(call)
+                .map_err(|e| error!("{e}"));
+        }
+    }
+
+    fn dispatch_call(&mut self, call: RpcCall) -> Result<()> {
         let txid = call.txid();
         let target_id = call.target_id();
         let rs = self.cloned.upgrade().expect("RpcServer weak reference not set");
@@ -245,12 +744,14 @@ impl RpcServer {
             if exists.is_none() {
                 return;
             }
+            rs.borrow().count(|c| c.calls_timeout += 1);
 
             let handler = rs.borrow_mut().calltimeout_handler.take();
             if let Some(h) = handler {
                 h.cb(&target_id);
                 rs.borrow_mut().calltimeout_handler = Some(h);
             }
+            rs.borrow_mut().send_deferred();
         });
 
         let call = Rc::new(RefCell::new(call));
@@ -258,23 +759,34 @@ impl RpcServer {
         call.borrow_mut().set_timeout_handler(handler);
         call.borrow_mut().set_timer_client(self.timer_client.clone());
 
+        let session = call.borrow().sender();
+        let sender = session.clone().unwrap_or_else(|| self.identity.clone());
         let mut msg  = call.borrow_mut().take_transient();
-        msg.set_nodeid(self.identity.id().clone());
+        msg.set_nodeid(sender.id().clone());
+        // Session ids stay anonymous.
+        if let Some((caps, rt)) = self.capabilities.as_ref().filter(|_| session.is_none()) {
+            let ver = rt.borrow().bucket_entry(&target_id).map_or(0, |e| e.version());
+            if version::supports_capabilities(ver) {
+                msg.set_capabilities(*caps);
+            }
+        }
         msg.set_associated_call(call.clone());
 
         self.pending_calls.insert(txid, call.clone());
+        self.peak_inflight_calls = self.peak_inflight_calls.max(self.pending_calls.len());
 
         let msg = Rc::new(msg);
         call.borrow_mut().set_request(msg.clone());
 
-        match self.send_msg(&msg) {
-            Ok(_) => {
-                call.borrow_mut().sent();
-                if let Some(h) = self.callsent_handler.as_ref() {
-                    let target_id = call.borrow().target_id();
-                    h.cb(&target_id);
-                }
+        let result = self.encode_as(&sender, &msg).and_then(|data| {
+            self.dispatch(data, *msg.remote_addr(), Some(call.clone()))
+        });
+        match result {
+            Ok(Some(_)) => {
+                self.log_msg(&msg, "sent");
+                self.call_sent(&call);
             },
+            Ok(None) => self.log_msg(&msg, "queued"),
             Err(e) => {
                 let _ = self.pending_calls.remove(&txid);
                 call.borrow_mut().fail();
@@ -284,7 +796,31 @@ impl RpcServer {
         Ok(())
     }
 
+    // The request of the call left the socket, its timeout starts now.
+    fn call_sent(&mut self, call: &Rc<RefCell<RpcCall>>) {
+        self.health.on_call_sent();
+        self.count(|c| c.calls_sent += 1);
+        call.borrow_mut().sent();
+        if let Some(h) = self.callsent_handler.as_ref() {
+            let target_id = call.borrow().target_id();
+            h.cb(&target_id);
+        }
+    }
+
+    // Sends the message, returns the number of bytes sent or zero if the
+    // packet was queued by the send shaper.
     pub(crate) fn send_msg(&self, msg: &Message) -> Result<usize> {
+        let data = self.encode(msg)?;
+        let sent = self.dispatch(data, *msg.remote_addr(), None)?;
+        self.log_msg(msg, if sent.is_some() { "sent" } else { "queued" });
+        Ok(sent.unwrap_or(0))
+    }
+
+    fn encode(&self, msg: &Message) -> Result<Vec<u8>> {
+        self.encode_as(&self.identity, msg)
+    }
+
+    fn encode_as(&self, identity: &CryptoIdentity, msg: &Message) -> Result<Vec<u8>> {
         // Deserialize message to bytes
         let data = serde_cbor::to_vec(msg).map_err(|e| -> Error {
             ProtocolError::new(format!("Failed to serialize message: {e}"))
@@ -295,7 +831,7 @@ impl RpcServer {
         let mut buf = vec![0u8; cipher_len + Id::BYTES];
         buf[..Id::BYTES].copy_from_slice(msg.nodeid().as_bytes());
 
-        let rc = self.identity.encrypt(
+        let rc = identity.encrypt(
             msg.remote_id(), &data, &mut buf[Id::BYTES..]
         );
         let encrypted = match rc {
@@ -306,31 +842,187 @@ impl RpcServer {
             return Err(CryptoError::new(format!("Error: encrypted length {} does not match expected {}",
                 encrypted, cipher_len)));
         }
+        Ok(buf)
+    }
 
-        // Send message to remote node
-        let tx = self.tx_socket.as_ref().ok_or_else(|| -> Error {
-            NetworkError::new("RPC server socket not initialized")
-        })?;
-        let sent_len = tx.send_to(
-            &buf[..cipher_len + Id::BYTES], msg.remote_addr()
-        ).map_err(|e| -> Error {
+    // Sends the packet right away if the shaper lets it pass and nothing to
+    // the same destination is queued before it, otherwise queues it.
+    // None means queued.
+    fn dispatch(&self,
+        data: Vec<u8>,
+        dest: SocketAddr,
+        call: Option<Rc<RefCell<RpcCall>>>
+    ) -> Result<Option<usize>> {
+        let now = Instant::now();
+        let wait = self.shaper.borrow_mut().wait(&dest, now);
+        let ahead = self.send_queue.borrow().iter().any(|p| p.dest == dest);
+        if wait.is_zero() && !ahead {
+            self.shaper.borrow_mut().consume(dest, now);
+            return self.transmit(&data, dest).map(Some);
+        }
+
+        if self.send_queue.borrow().len() >= Self::MAX_QUEUED_PACKETS {
+            return Err(NetworkError::new("Send queue is full"));
+        }
+        self.send_queue.borrow_mut().push_back(QueuedPacket { data, dest, call });
+        self.count(|c| c.msgs_shaped += 1);
+        self.schedule_flush(wait);
+        Ok(None)
+    }
+
+    fn schedule_flush(&self, wait: Duration) {
+        if self.flush_timer.get().is_some() {
+            return;
+        }
+
+        let delay = (wait.as_millis() as u64).max(1);
+        let cloned = self.cloned.upgrade().expect("RpcServer weak reference not set");
+        let result = self.timer_client.add_timer(delay, None,
+            AsyncHandler::new(move |_| {
+                let server = cloned.clone();
+                Box::pin(async move {
+                    server.borrow_mut().flush_queue();
+                })
+            })
+        );
+        match result {
+            Ok(timer_id) => self.flush_timer.set(Some(timer_id)),
+            Err(e) => error!("Failed to set send queue timer: {e}"),
+        }
+    }
+
+    // Sends the queued packets the shaper lets pass, in order, skipping the
+    // ones whose destination is still paced.
+    pub(crate) fn flush_queue(&mut self) {
+        self.flush_timer.set(None);
+
+        let mut queue = self.send_queue.take();
+        let mut remaining = VecDeque::with_capacity(queue.len());
+        let mut next_wait: Option<Duration> = None;
+        while let Some(packet) = queue.pop_front() {
+            // Checked again before each send, the ones before may have
+            // used up what the shaper allowed.
+            let now = Instant::now();
+            let wait = self.shaper.borrow_mut().wait_queued(&packet.dest, now);
+            if !wait.is_zero() {
+                next_wait = Some(next_wait.map_or(wait, |w| w.min(wait)));
+                remaining.push_back(packet);
+                continue;
+            }
+
+            self.shaper.borrow_mut().consume_queued(packet.dest, now);
+            let result = self.transmit(&packet.data, packet.dest);
+            let Some(call) = packet.call else {
+                continue;
+            };
+            match result {
+                Ok(_) => self.call_sent(&call),
+                Err(_) => {
+                    let txid = call.borrow().txid();
+                    let _ = self.pending_calls.remove(&txid);
+                    call.borrow_mut().fail();
+                }
+            }
+        }
+
+        // Packets queued while flushing go behind the ones still waiting.
+        remaining.extend(self.send_queue.take());
+        *self.send_queue.borrow_mut() = remaining;
+        if let Some(wait) = next_wait {
+            self.schedule_flush(wait);
+        }
+        self.send_deferred();
+    }
+
+    fn transmit(&self, data: &[u8], dest: SocketAddr) -> Result<usize> {
+        let sent = self.send_packet(data, dest)?;
+        let sent_len = sent.map_err(|e| -> Error {
+            self.record(NodeEventKind::SocketError { kind: e.kind() });
+            if let Some(errors) = self.socket_errors.as_ref() {
+                errors.send_failed(dest, &e);
+            }
             NetworkError::new(format!("Failed to send message: {e}"))
         })?;
 
-        if sent_len != buf.len() {
+        if sent_len != data.len() {
             return Err(NetworkError::new(
-                format!("Error: sent length {} does not match expected {}", sent_len, buf.len())));
+                format!("Error: sent length {} does not match expected {}", sent_len, data.len())));
+        }
+        self.count(|c| {
+            c.msgs_sent += 1;
+            c.bytes_sent += sent_len as u64;
+        });
+        if let Some(h) = self.sent_handler.as_ref() {
+            h.cb(&dest);
         }
+        Ok(sent_len)
+    }
+
+    fn send_packet(&self, data: &[u8], dest: SocketAddr) -> Result<io::Result<usize>> {
+        #[cfg(feature = "wsgateway")]
+        if let Some(gateway) = self.gateway.as_ref() {
+            return Ok(gateway.send(dest, data));
+        }
+        #[cfg(feature = "testing")]
+        if let Some(sim) = self.sim.as_ref() {
+            sim.send(*self.ni.socket_addr(), dest, data);
+            return Ok(Ok(data.len()));
+        }
+        self.send_to(data, dest)
+    }
 
+    fn send_to(&self, data: &[u8], dest: SocketAddr) -> Result<io::Result<usize>> {
+        let tx = self.tx_socket.as_ref().ok_or_else(|| -> Error {
+            NetworkError::new("RPC server socket not initialized")
+        })?;
+        #[cfg(feature = "testing")]
+        let sent = match self.transport.as_ref() {
+            Some(transport) => self.send_through(transport, tx, data, dest),
+            None => tx.send_to(data, dest),
+        };
+        #[cfg(not(feature = "testing"))]
+        let sent = tx.send_to(data, dest);
+        Ok(sent)
+    }
+
+    // Sends the copies of the packet the fault injector lets through, the
+    // delayed ones from a timer. A lost packet counts as sent.
+    #[cfg(feature = "testing")]
+    fn send_through(&self,
+        transport: &LossyTransport,
+        tx: &Rc<StdUdpSocket>,
+        data: &[u8],
+        dest: SocketAddr
+    ) -> io::Result<usize> {
+        for delay in transport.outbound() {
+            if delay.is_zero() {
+                tx.send_to(data, dest)?;
+                continue;
+            }
+
+            let socket = tx.clone();
+            let data = data.to_vec();
+            let result = self.timer_client.add_timer((delay.as_millis() as u64).max(1), None,
+                AsyncHandler::new(move |_| {
+                    let _ = socket.send_to(&data, dest);
+                    Box::pin(async {})
+                })
+            );
+            if let Err(e) = result {
+                error!("Failed to set delayed packet timer: {e}");
+            }
+        }
+        Ok(data.len())
+    }
+
+    fn log_msg(&self, msg: &Message, what: &str) {
         if msg.method() == Method::Ping {
-            trace!("Message {}_{} to {}@{} was sent: {}",
+            trace!("Message {}_{} to {}@{} was {what}: {}",
                 msg.method(), msg.kind(), msg.remote_id(), msg.remote_addr(), msg);
         } else {
-            debug!("Message {}_{} to {}@{} was sent: {}",
+            debug!("Message {}_{} to {}@{} was {what}: {}",
                 msg.method(), msg.kind(), msg.remote_id(), msg.remote_addr(), msg);
         }
-
-        Ok(sent_len)
     }
 
     #[inline]
@@ -340,6 +1032,23 @@ impl RpcServer {
         }
     }
 
+    // Applies the endpoint policy to the peers carried by the message,
+    // returns false if the whole message has to be rejected.
+    pub(crate) fn screen_endpoints(policy: EndpointPolicy, msg: &mut Message) -> bool {
+        match msg.body_mut() {
+            Some(Body::AnnouncePeerRequest(req)) => policy.admits(req.peer().endpoint()),
+            Some(Body::FindPeerResponse(rsp)) => match policy {
+                EndpointPolicy::Reject => rsp.peers().unwrap_or_default().iter()
+                    .all(|peer| policy.admits(peer.endpoint())),
+                _ => {
+                    rsp.retain_peers(|peer| policy.admits(peer.endpoint()));
+                    true
+                }
+            },
+            _ => true,
+        }
+    }
+
     #[inline]
     fn observe_message(&self, from: SocketAddr, id: Id) {
         if let Some(detector) = &self.suspicious_node_detector {
@@ -354,7 +1063,29 @@ impl RpcServer {
         }
     }
 
+    // Decrypts a packet encrypted for one of the session ids, returning the
+    // session id along.
+    fn decrypt_session(&self, from_id: &Id, cipher: &[u8]) -> Option<(Vec<u8>, Option<Id>)> {
+        let sessions = self.sessions.as_ref()?;
+        let identities = sessions.borrow_mut().identities(Instant::now());
+        identities.iter().find_map(|identity| {
+            identity.decrypt_into(from_id, cipher).ok()
+                .map(|decrypted| (decrypted, Some(*identity.id())))
+        })
+    }
+
     pub(crate) async fn handle_packet(server: Rc<RefCell<Self>>, data: &[u8], from: SocketAddr) {
+        #[cfg(feature = "testing")]
+        if server.borrow().transport.as_ref().is_some_and(|t| !t.inbound()) {
+            return;
+        }
+
+        let blocklist = server.borrow().blocklist.clone();
+        if blocklist.as_ref().is_some_and(|b| b.drops_addr(&from.ip())) {
+            debug!("Dropped packet from blocked address {}", from);
+            return;
+        }
+
         let minimal_len = Id::BYTES + CryptoBox::MAC_BYTES + Message::MIN_BYTES;
         if data.len() < minimal_len {
             warn!("Ignored invalid packet from {}: too short", from);
@@ -372,19 +1103,55 @@ impl RpcServer {
             }
         };
 
-        // TODO: blacklist checking.
+        if blocklist.as_ref().is_some_and(|b| b.drops_id(&from_id)) {
+            debug!("Dropped packet from blocked node {}@{}", from_id, from);
+            return;
+        }
 
         // Decrypting message data.
         let identity = server.borrow().identity.clone();
-        let decrypted = match identity.decrypt_into(&from_id, &data[Id::BYTES..]) {
-            Ok(d) => d,
-            Err(e) => {
-                warn!("Ignored invalid packet from {}: decrypting error {e}", from);
-                server.borrow().malformed_message(from);
-                return;
+        let (decrypted, session) = match identity.decrypt_into(&from_id, &data[Id::BYTES..]) {
+            Ok(d) => (d, None),
+            Err(e) => match server.borrow().decrypt_session(&from_id, &data[Id::BYTES..]) {
+                Some(decrypted) => decrypted,
+                None => {
+                    warn!("Ignored invalid packet from {}: decrypting error {e}", from);
+                    server.borrow().malformed_message(from);
+                    return;
+                }
             }
         };
 
+        Self::handle_decrypted(server, data.len(), from, from_id, decrypted, session).await;
+    }
+
+    // Handles a packet from a receive thread, decrypted there unless it was
+    // encrypted for a session id or is malformed.
+    pub(crate) async fn handle_inbound(server: Rc<RefCell<Self>>, inbound: Inbound) {
+        let Some((from_id, decrypted)) = inbound.decrypted else {
+            return Self::handle_packet(server, &inbound.data, inbound.from).await;
+        };
+
+        #[cfg(feature = "testing")]
+        if server.borrow().transport.as_ref().is_some_and(|t| !t.inbound()) {
+            return;
+        }
+
+        if inbound.data.len() < Id::BYTES + CryptoBox::MAC_BYTES + Message::MIN_BYTES {
+            warn!("Ignored invalid packet from {}: too short", inbound.from);
+            server.borrow().malformed_message(inbound.from);
+            return;
+        }
+        Self::handle_decrypted(server, inbound.data.len(), inbound.from, from_id, decrypted, None).await;
+    }
+
+    async fn handle_decrypted(server: Rc<RefCell<Self>>,
+        len: usize,
+        from: SocketAddr,
+        from_id: Id,
+        decrypted: Vec<u8>,
+        session: Option<Id>
+    ) {
         // Deserializing message
         let mut msg = match serde_cbor::from_slice::<Message>(&decrypted) {
             Ok(m) => m,
@@ -397,6 +1164,40 @@ impl RpcServer {
         msg.set_nodeid(from_id);
         msg.set_remote(from_id, from);
 
+        // A session id only ever sends requests, a request to one comes
+        // from a node that picked it up from our lookups.
+        if session.is_some() && msg.is_req() {
+            debug!("Ignored request {} from {}@{} to a session id", msg.method(), from_id, from);
+            return;
+        }
+        server.borrow_mut().health.on_received();
+        server.borrow().count(|c| {
+            c.msgs_received += 1;
+            c.bytes_received += len as u64;
+        });
+
+        let policy = server.borrow().endpoint_policy;
+        if !Self::screen_endpoints(policy, &mut msg) {
+            warn!("Rejected message {}_{} from {}@{}: invalid peer endpoint",
+                msg.method(), msg.kind(), from_id, from);
+            server.borrow().malformed_message(from);
+
+            if msg.is_req() {
+                let mut err = msg::error_msg(msg.method(), msg.txid(),
+                    PROTOCOL_ERROR, "Invalid peer endpoint".into());
+                err.set_remote(from_id, from);
+                err.set_nodeid(*server.borrow().identity.id());
+                let _ = server.borrow().send_msg(&err);
+            } else {
+                let call = server.borrow_mut().pending_calls.remove(&msg.txid());
+                if let Some(call) = call {
+                    call.borrow_mut().fail();
+                    server.borrow_mut().send_deferred();
+                }
+            }
+            return;
+        }
+
         debug!("Received message {}_{} from {}@{}: {}",
             msg.method(), msg.kind(), from_id, from, msg);
 
@@ -413,6 +1214,14 @@ impl RpcServer {
 
         // Handle response or error message, matching with pending call.
         let msg_id = msg.txid();
+        let sent_as = server.borrow().pending_calls.get(&msg_id)
+            .map(|call| call.borrow().sender().map(|s| *s.id()));
+        if sent_as.is_some_and(|sender| sender != session) {
+            warn!("Ignored response {} from {}@{} encrypted for another identity than the call was sent from",
+                msg.method(), from_id, from);
+            server.borrow().malformed_message(from);
+            return;
+        }
         let call_opt = server.borrow_mut().pending_calls.remove(&msg_id);
         let Some(call) = call_opt else {
             server.borrow().observe_message(from, from_id);
@@ -421,6 +1230,7 @@ impl RpcServer {
                 msg.method(), msg.txid());
             return;
         };
+        server.borrow_mut().send_deferred();
         let msg = Rc::new({
             msg.set_associated_call(call.clone());
             msg
@@ -465,7 +1275,6 @@ impl RpcServer {
             handler.cb(msg).await;
             server.borrow_mut().message_handler = Some(handler);
         };
-        // TODO: handle metrics.
     }
 }
 
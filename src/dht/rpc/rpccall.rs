@@ -1,10 +1,11 @@
 use std::{
+    sync::Arc,
     rc::{Rc, Weak},
     cell::RefCell,
     time::SystemTime
 };
 use log::error;
-use crate::Id;
+use crate::{Id, CryptoIdentity};
 use crate::dht::{
     msg::{Message, msg::Kind},
     timer_client::LocalTimerClient as TimerClient,
@@ -42,6 +43,10 @@ pub(crate) struct RpcCall {
     // being nailed as Arc<Message> object.
     transient       : Option<Message>,
 
+    // The session identity the request is sent from in privacy mode,
+    // the node identity otherwise.
+    sender          : Option<Arc<CryptoIdentity>>,
+
     req             : Option<Rc<Message>>,
     rsp             : Option<Rc<Message>>,
 
@@ -49,6 +54,7 @@ pub(crate) struct RpcCall {
     rsp_time        : Option<SystemTime>,
 
     state           : State,
+    timeout         : u64,
 
     listener        : Option<CallListener>,
 
@@ -60,6 +66,8 @@ pub(crate) struct RpcCall {
 }
 
 impl RpcCall {
+    const DEFAULT_TIMEOUT: u64 = 10_000;
+
     pub(crate) fn new(target: impl Into<Target>, mut req: Message) -> Self {
         let target: Target = target.into();
         req.set_remote(target.id(),target.socket_addr());
@@ -68,11 +76,13 @@ impl RpcCall {
             txid            : req.txid(),
             target,
             transient       : Some(req),
+            sender          : None,
             req             : None,
             rsp             : None,
             sent_time       : None,
             rsp_time        : None,
             state           : State::Unsent,
+            timeout         : Self::DEFAULT_TIMEOUT,
             listener        : None,
             timer_id        : None,
             timer_client    : None,
@@ -98,6 +108,14 @@ impl RpcCall {
         self.transient.take().expect("Transient message not set")
     }
 
+    pub(crate) fn set_sender(&mut self, sender: Arc<CryptoIdentity>) {
+        self.sender = Some(sender);
+    }
+
+    pub(crate) fn sender(&self) -> Option<Arc<CryptoIdentity>> {
+        self.sender.clone()
+    }
+
     pub(crate) fn set_request(&mut self, req: Rc<Message>) {
         self.req = Some(req);
     }
@@ -133,6 +151,12 @@ impl RpcCall {
         self.sent_time
     }
 
+    // Milliseconds between sending the request and receiving its response.
+    pub(crate) fn rtt(&self) -> Option<u64> {
+        let sent = self.sent_time?;
+        self.rsp_time?.duration_since(sent).ok().map(|rtt| rtt.as_millis() as u64)
+    }
+
     pub(crate) fn set_listener(&mut self, listener: CallListener) {
         if self.state != State::Unsent {
             return;
@@ -140,6 +164,11 @@ impl RpcCall {
         self.listener = Some(listener);
     }
 
+    // Overrides the response timeout in milliseconds, before the call is sent.
+    pub(crate) fn set_timeout(&mut self, timeout: u64) {
+        self.timeout = timeout;
+    }
+
     pub(crate) fn set_timeout_handler(&mut self, handler: Handler<()>) {
         self.timeout_handler = Some(handler);
     }
@@ -223,7 +252,7 @@ impl RpcCall {
     pub(crate) fn sent(&mut self) {
         self.sent_time = Some(SystemTime::now());
         self.update_state(State::Sent);
-        self.set_timeout_timer(10_000);
+        self.set_timeout_timer(self.timeout);
     }
 
     pub(crate) fn respond(&mut self, rsp: Rc<Message>) {
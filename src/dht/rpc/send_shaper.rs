@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+use crate::dht::node_config::{
+    DEFAULT_SEND_RATE_INTERVAL,
+    DEFAULT_SEND_BURST,
+    DEFAULT_SEND_PACING,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SendShaperOptions {
+    // Packets allowed per interval, zero disables the shaping.
+    pub(crate) rate     : u32,
+    pub(crate) interval : Duration,
+    // Packets that may leave back-to-back after an idle period.
+    pub(crate) burst    : u32,
+    // Minimum gap between two packets to the same destination.
+    pub(crate) pacing   : Duration,
+}
+
+impl Default for SendShaperOptions {
+    fn default() -> Self {
+        Self {
+            rate    : 0,
+            interval: Duration::from_millis(DEFAULT_SEND_RATE_INTERVAL),
+            burst   : DEFAULT_SEND_BURST,
+            pacing  : Duration::from_millis(DEFAULT_SEND_PACING),
+        }
+    }
+}
+
+// Token bucket over all outgoing packets plus a per-destination gap.
+pub(crate) struct SendShaper {
+    options     : SendShaperOptions,
+    tokens      : f64,
+    refilled    : Instant,
+    last_sent   : HashMap<SocketAddr, Instant>,
+    // When the last queued packet left.
+    last_queued : Option<Instant>,
+}
+
+impl SendShaper {
+    pub(crate) fn new(options: SendShaperOptions) -> Self {
+        Self {
+            options,
+            tokens      : options.burst.max(1) as f64,
+            refilled    : Instant::now(),
+            last_sent   : HashMap::new(),
+            last_queued : None,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.options.rate > 0 && !self.options.interval.is_zero()
+    }
+
+    // How long a packet to `dest` has to wait at `now`, zero if it may leave.
+    pub(crate) fn wait(&mut self, dest: &SocketAddr, now: Instant) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+
+        self.refill(now);
+        let token = match self.tokens >= 1.0 {
+            true => Duration::ZERO,
+            false => self.options.interval.mul_f64((1.0 - self.tokens) / self.options.rate as f64),
+        };
+        let pace = self.last_sent.get(dest).map_or(Duration::ZERO, |sent| {
+            (*sent + self.options.pacing).saturating_duration_since(now)
+        });
+        token.max(pace)
+    }
+
+    // Like `wait`, for a packet taken from the queue. Queued packets leave
+    // one token apart at least, the tokens refilled while a flush was late
+    // do not send them back-to-back.
+    pub(crate) fn wait_queued(&mut self, dest: &SocketAddr, now: Instant) -> Duration {
+        if !self.is_enabled() {
+            return Duration::ZERO;
+        }
+
+        let wait = self.wait(dest, now);
+        let spacing = self.options.interval / self.options.rate;
+        let gap = self.last_queued.map_or(Duration::ZERO, |sent| {
+            (sent + spacing).saturating_duration_since(now)
+        });
+        wait.max(gap)
+    }
+
+    // Like `consume`, for a packet taken from the queue.
+    pub(crate) fn consume_queued(&mut self, dest: SocketAddr, now: Instant) {
+        self.consume(dest, now);
+        if self.is_enabled() {
+            self.last_queued = Some(now);
+        }
+    }
+
+    // Accounts a packet to `dest` leaving at `now`.
+    pub(crate) fn consume(&mut self, dest: SocketAddr, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.refill(now);
+        self.tokens -= 1.0;
+        self.last_sent.insert(dest, now);
+
+        let pacing = self.options.pacing;
+        if self.last_sent.len() > 256 {
+            self.last_sent.retain(|_, sent| now.saturating_duration_since(*sent) < pacing);
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled);
+        let added = elapsed.as_secs_f64() / self.options.interval.as_secs_f64() * self.options.rate as f64;
+        self.tokens = (self.tokens + added).min(self.options.burst.max(1) as f64);
+        self.refilled = self.refilled.max(now);
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    time::{Duration, Instant},
+    net::{IpAddr, SocketAddr},
+};
+
+use crate::dht::node_config::{
+    DEFAULT_SOCKET_RECV_TIMEOUT,
+    DEFAULT_SOCKET_STALL_CALLS,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SocketHealthOptions {
+    // No packet received for this long while calls were sent, zero disables the check.
+    pub(crate) recv_timeout : Duration,
+    // Minimum number of calls sent since the last received packet.
+    pub(crate) stall_calls  : u32,
+}
+
+impl Default for SocketHealthOptions {
+    fn default() -> Self {
+        Self {
+            recv_timeout: Duration::from_secs(DEFAULT_SOCKET_RECV_TIMEOUT),
+            stall_calls : DEFAULT_SOCKET_STALL_CALLS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SocketFault {
+    // Outgoing calls keep going out, but nothing comes back.
+    Stalled,
+    // The bound address is no longer assigned to any interface.
+    AddressLost,
+}
+
+impl SocketFault {
+    pub(crate) fn reason(&self) -> &'static str {
+        match self {
+            Self::Stalled => "no packets received",
+            Self::AddressLost => "bound address lost",
+        }
+    }
+}
+
+pub(crate) struct SocketHealth {
+    options         : SocketHealthOptions,
+    last_recv       : Instant,
+    calls_since_recv: u32,
+}
+
+impl SocketHealth {
+    pub(crate) fn new(options: SocketHealthOptions) -> Self {
+        Self {
+            options,
+            last_recv       : Instant::now(),
+            calls_since_recv: 0,
+        }
+    }
+
+    pub(crate) fn on_call_sent(&mut self) {
+        self.calls_since_recv = self.calls_since_recv.saturating_add(1);
+    }
+
+    pub(crate) fn on_received(&mut self) {
+        self.reset();
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.last_recv = Instant::now();
+        self.calls_since_recv = 0;
+    }
+
+    // `local_addrs` is the current interface address list, None when it
+    // could not be read, in which case only the stall check applies.
+    pub(crate) fn check(&self, bound: IpAddr, local_addrs: Option<&[IpAddr]>) -> Option<SocketFault> {
+        if let Some(addrs) = local_addrs {
+            if !bound.is_unspecified() && !addrs.contains(&bound) {
+                return Some(SocketFault::AddressLost);
+            }
+        }
+
+        if self.options.recv_timeout.is_zero() {
+            return None;
+        }
+
+        if self.calls_since_recv >= self.options.stall_calls.max(1) &&
+            self.last_recv.elapsed() >= self.options.recv_timeout {
+            return Some(SocketFault::Stalled);
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SocketEvent {
+    Failed(SocketFault),
+    Rebound(SocketAddr),
+}
@@ -0,0 +1,322 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+use futures::{SinkExt, StreamExt};
+use log::{debug, info, warn};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream, UdpSocket},
+    sync::mpsc::{self, error::TrySendError},
+    task::{JoinHandle, JoinSet},
+};
+use tokio_tungstenite::{
+    WebSocketStream,
+    tungstenite::{Error as WsError, Message},
+};
+use url::Url;
+
+use crate::dht::rpc::receivers::{Inbound, RECEIVE_QUEUE_SIZE};
+
+// The DHT datagrams of a node behind a network blocking UDP, tunneled over
+// a WebSocket to a gateway relaying them onto UDP. Every binary message
+// carries one datagram, untouched, framed as
+//
+//   [family: 4 | 6][ip: 4 | 16 bytes][port: 2][length: 2][datagram]
+//
+// the address being the destination towards the gateway and the source
+// back from it, all integers big-endian. The gateway sends the datagrams
+// of a connection from a UDP socket of its own, the other nodes see the
+// client at that address for as long as it stays connected.
+
+// Datagrams waiting for the connection to the gateway, also while it is
+// being established again, those beyond are refused like on a full socket
+// buffer.
+const SEND_QUEUE_SIZE: usize = 1024;
+
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+// Keeps the connection open through proxies closing idle ones.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+const MAX_DATAGRAM_SIZE: usize = u16::MAX as usize;
+
+// None if the datagram is too large for the length field.
+pub(crate) fn encode_frame(addr: SocketAddr, data: &[u8]) -> Option<Vec<u8>> {
+    let len = u16::try_from(data.len()).ok()?;
+    let mut frame = Vec::with_capacity(1 + 16 + 2 + 2 + data.len());
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            frame.push(4);
+            frame.extend_from_slice(&ip.octets());
+        },
+        IpAddr::V6(ip) => {
+            frame.push(6);
+            frame.extend_from_slice(&ip.octets());
+        },
+    }
+    frame.extend_from_slice(&addr.port().to_be_bytes());
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.extend_from_slice(data);
+    Some(frame)
+}
+
+// None for a frame of an unknown family or whose length does not match.
+pub(crate) fn decode_frame(frame: &[u8]) -> Option<(SocketAddr, &[u8])> {
+    let (&family, rest) = frame.split_first()?;
+    let (ip, rest) = match family {
+        4 => {
+            let (ip, rest) = rest.split_first_chunk::<4>()?;
+            (IpAddr::V4(Ipv4Addr::from(*ip)), rest)
+        },
+        6 => {
+            let (ip, rest) = rest.split_first_chunk::<16>()?;
+            (IpAddr::V6(Ipv6Addr::from(*ip)), rest)
+        },
+        _ => return None,
+    };
+    let (port, rest) = rest.split_first_chunk::<2>()?;
+    let (len, data) = rest.split_first_chunk::<2>()?;
+    if u16::from_be_bytes(*len) as usize != data.len() {
+        return None;
+    }
+    Some((SocketAddr::new(ip, u16::from_be_bytes(*port)), data))
+}
+
+// The node end of the tunnel, in place of the socket of the RPC server.
+// Connects to the gateway, and again whenever the connection drops.
+pub(crate) struct GatewayClient {
+    outbound    : mpsc::Sender<Vec<u8>>,
+    connected   : Arc<AtomicBool>,
+    task        : JoinHandle<()>,
+}
+
+impl GatewayClient {
+    // Returns the datagrams relayed back, queued like those of the receive
+    // threads. Must be called on the runtime of the DHT.
+    pub(crate) fn connect(url: Url) -> (Self, mpsc::Receiver<Inbound>) {
+        let (outbound, outbound_rx) = mpsc::channel(SEND_QUEUE_SIZE);
+        let (inbound_tx, inbound) = mpsc::channel(RECEIVE_QUEUE_SIZE);
+        let connected = Arc::new(AtomicBool::new(false));
+        let task = tokio::spawn(run_client(url, outbound_rx, inbound_tx, connected.clone()));
+
+        let client = Self {
+            outbound,
+            connected,
+            task,
+        };
+        (client, inbound)
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn send(&self, dest: SocketAddr, data: &[u8]) -> io::Result<usize> {
+        let Some(frame) = encode_frame(dest, data) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Datagram too large"));
+        };
+        match self.outbound.try_send(frame) {
+            Ok(()) => Ok(data.len()),
+            Err(TrySendError::Full(_)) => Err(io::ErrorKind::WouldBlock.into()),
+            Err(TrySendError::Closed(_)) => Err(io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+impl Drop for GatewayClient {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run_client(url: Url,
+    mut outbound: mpsc::Receiver<Vec<u8>>,
+    inbound: mpsc::Sender<Inbound>,
+    connected: Arc<AtomicBool>
+) {
+    let mut delay = RECONNECT_MIN_DELAY;
+    loop {
+        match tokio_tungstenite::connect_async(url.as_str()).await {
+            Ok((ws, _)) => {
+                info!("Connected to the DHT gateway at {url}");
+                delay = RECONNECT_MIN_DELAY;
+                connected.store(true, Ordering::Relaxed);
+                let result = relay_client(ws, &mut outbound, &inbound).await;
+                connected.store(false, Ordering::Relaxed);
+                match result {
+                    Ok(()) => return,
+                    Err(e) => warn!("Connection to the DHT gateway at {url} lost: {e}, reconnecting"),
+                }
+            },
+            Err(e) => warn!("Connecting to the DHT gateway at {url} failed: {e}, retrying in {delay:?}"),
+        }
+
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+}
+
+// Returns Ok once the RPC server is gone.
+async fn relay_client<S>(mut ws: WebSocketStream<S>,
+    outbound: &mut mpsc::Receiver<Vec<u8>>,
+    inbound: &mpsc::Sender<Inbound>
+) -> Result<(), WsError>
+where
+    S: AsyncRead + AsyncWrite + Unpin
+{
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    loop {
+        tokio::select! {
+            frame = outbound.recv() => match frame {
+                Some(frame) => ws.send(Message::binary(frame)).await?,
+                None => return Ok(()),
+            },
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Binary(frame))) => {
+                    let Some((from, data)) = decode_frame(&frame) else {
+                        debug!("Ignored malformed frame from the DHT gateway");
+                        continue;
+                    };
+                    let packet = Inbound {
+                        from,
+                        data: data.to_vec(),
+                        decrypted: None,
+                    };
+                    if let Err(TrySendError::Closed(_)) = inbound.try_send(packet) {
+                        return Ok(());
+                    }
+                },
+                Some(Ok(Message::Close(_))) | None => return Err(WsError::ConnectionClosed),
+                Some(Ok(_)) => {},
+                Some(Err(e)) => return Err(e),
+            },
+            _ = keepalive.tick() => ws.send(Message::Ping(Default::default())).await?,
+        }
+    }
+}
+
+// The gateway end, run by a full node. Relays the datagrams of every
+// client connected onto UDP and back.
+pub(crate) struct GatewayListener {
+    local_addr  : SocketAddr,
+    task        : JoinHandle<()>,
+}
+
+impl GatewayListener {
+    pub(crate) async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        info!("DHT gateway listening at {local_addr}");
+        Ok(Self {
+            local_addr,
+            task: tokio::spawn(accept_loop(listener)),
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for GatewayListener {
+    // The connections go with the accept loop holding them.
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn accept_loop(listener: TcpListener) {
+    let mut connections = JoinSet::new();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, from)) => {
+                    connections.spawn(serve(stream, from));
+                },
+                Err(e) => {
+                    warn!("DHT gateway accept error: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                },
+            },
+            Some(_) = connections.join_next(), if !connections.is_empty() => {},
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, from: SocketAddr) {
+    let ws = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            debug!("DHT gateway handshake with {from} failed: {e}");
+            return;
+        }
+    };
+
+    info!("DHT gateway client {from} connected");
+    match relay_gateway(ws).await {
+        Ok(()) => info!("DHT gateway client {from} disconnected"),
+        Err(e) => info!("DHT gateway client {from} disconnected: {e}"),
+    }
+}
+
+// The datagrams of the connection leave from a socket bound on its first
+// one, of the family of its destination. A connection carries the
+// datagrams of one DHT, those of the other family are dropped.
+async fn relay_gateway<S>(mut ws: WebSocketStream<S>) -> Result<(), WsError>
+where
+    S: AsyncRead + AsyncWrite + Unpin
+{
+    let mut socket: Option<UdpSocket> = None;
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    loop {
+        tokio::select! {
+            msg = ws.next() => match msg {
+                Some(Ok(Message::Binary(frame))) => {
+                    let Some((dest, data)) = decode_frame(&frame) else {
+                        debug!("DHT gateway ignored a malformed frame");
+                        continue;
+                    };
+                    if socket.is_none() {
+                        let unspecified: IpAddr = match dest {
+                            SocketAddr::V4(_) => Ipv4Addr::UNSPECIFIED.into(),
+                            SocketAddr::V6(_) => Ipv6Addr::UNSPECIFIED.into(),
+                        };
+                        socket = Some(UdpSocket::bind((unspecified, 0)).await?);
+                    }
+                    let Some(socket) = socket.as_ref() else {
+                        continue;
+                    };
+                    if socket.local_addr()?.is_ipv4() != dest.is_ipv4() {
+                        debug!("DHT gateway dropped a datagram to {dest} of the other family");
+                        continue;
+                    }
+                    if let Err(e) = socket.send_to(data, dest).await {
+                        debug!("DHT gateway failed to relay a datagram to {dest}: {e}");
+                    }
+                },
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {},
+                Some(Err(e)) => return Err(e),
+            },
+            received = async { socket.as_ref().unwrap().recv_from(&mut buf).await }, if socket.is_some() => {
+                let (len, from) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        debug!("DHT gateway receive error: {e}");
+                        continue;
+                    }
+                };
+                if let Some(frame) = encode_frame(from, &buf[..len]) {
+                    ws.send(Message::binary(frame)).await?;
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+};
+use serde::{Deserialize, Serialize};
+use serde_cbor::Value as CborValue;
+use log::{info, warn};
+
+use crate::{
+    Value,
+    PeerInfo,
+    errors::{Result, IOError},
+    dht::storage::data_storage::DataStorage,
+};
+
+/// A value or peer announcement bundled in a seed file, loaded into the
+/// storage of a node configured with it before the node starts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Record {
+    #[serde(rename = "v")]
+    Value(Value),
+    #[serde(rename = "p")]
+    Peer(PeerInfo),
+}
+
+impl From<Value> for Record {
+    fn from(value: Value) -> Self {
+        Self::Value(value)
+    }
+}
+
+impl From<PeerInfo> for Record {
+    fn from(peer: PeerInfo) -> Self {
+        Self::Peer(peer)
+    }
+}
+
+/// Writes the records as a seed file, a CBOR array of them.
+pub fn pack(records: &[Record], writer: &mut dyn Write) -> Result<()> {
+    serde_cbor::to_writer(writer, &records).map_err(|e| {
+        IOError::new(format!("Writing seed data error: {e}"))
+    })?;
+    Ok(())
+}
+
+// Records loaded from a seed file, and the ones skipped for not decoding
+// or not verifying, or for being older than the ones stored.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct SeedReport {
+    pub(crate) values   : usize,
+    pub(crate) peers    : usize,
+    pub(crate) invalid  : usize,
+    pub(crate) outdated : usize,
+}
+
+// Stores the records of the seed file as persistent, so they are served
+// right away and re-announced like the ones the node announced itself.
+pub(crate) fn load(path: &Path, storage: &mut dyn DataStorage) -> Result<SeedReport> {
+    let bytes = fs::read(path).map_err(|e| {
+        IOError::new(format!("Reading seed data {} error: {e}", path.display()))
+    })?;
+    let items = serde_cbor::from_slice::<Vec<CborValue>>(&bytes).map_err(|e| {
+        IOError::new(format!("Decoding seed data {} error: {e}", path.display()))
+    })?;
+
+    let mut report = SeedReport::default();
+    for item in items {
+        match serde_cbor::value::from_value::<Record>(item) {
+            Ok(Record::Value(value)) if value.is_valid() => {
+                let stored = storage.get_value(&value.id())?;
+                if stored.is_some_and(|v| v.sequence_number() > value.sequence_number()) {
+                    report.outdated += 1;
+                    continue;
+                }
+                storage.put_value(value, true)?;
+                report.values += 1;
+            },
+            Ok(Record::Peer(peer)) if peer.is_valid() => {
+                let stored = storage.get_peer(peer.id(), peer.fingerprint())?;
+                if stored.is_some_and(|p| p.sequence_number() > peer.sequence_number()) {
+                    report.outdated += 1;
+                    continue;
+                }
+                storage.put_peer(peer, true)?;
+                report.peers += 1;
+            },
+            _ => report.invalid += 1,
+        }
+    }
+
+    info!("Seeded {} values and {} peers from {}, {} records older than the stored ones",
+        report.values, report.peers, path.display(), report.outdated);
+    if report.invalid > 0 {
+        warn!("Skipped {} invalid records of seed data {}", report.invalid, path.display());
+    }
+    Ok(report)
+}
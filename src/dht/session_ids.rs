@@ -0,0 +1,74 @@
+use std::{
+    sync::Arc,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    CryptoIdentity,
+    signature::KeyPair,
+};
+
+// Throwaway identities the lookups of the application are sent from in
+// privacy mode, so the nodes answering them can not tell what the node
+// looks up. A lookup keeps its identity until it ends, a new one is taken
+// per lookup or once the current one was used for the rotation interval.
+//
+// Responses are encrypted for the identity a request was sent from, the
+// retired ones are kept a while for the late responses and the lookups
+// still running on them.
+pub(crate) struct SessionIds {
+    rotation: Duration,
+    current : Option<(Arc<CryptoIdentity>, Instant)>,
+    retired : VecDeque<(Arc<CryptoIdentity>, Instant)>,
+}
+
+impl SessionIds {
+    pub(crate) const RETENTION: Duration = Duration::from_secs(60);
+    pub(crate) const MAX_RETIRED: usize = 64;
+
+    // A zero rotation takes a new identity per lookup.
+    pub(crate) fn new(rotation: Duration) -> Self {
+        Self {
+            rotation,
+            current : None,
+            retired : VecDeque::new(),
+        }
+    }
+
+    // The identity for a lookup starting now.
+    pub(crate) fn next(&mut self, now: Instant) -> Arc<CryptoIdentity> {
+        if let Some((identity, since)) = self.current.as_ref() {
+            if !self.rotation.is_zero() && now.duration_since(*since) < self.rotation {
+                return identity.clone();
+            }
+        }
+
+        let identity = Arc::new(CryptoIdentity::from(KeyPair::random()));
+        if let Some((retired, _)) = self.current.replace((identity.clone(), now)) {
+            self.retired.push_back((retired, now));
+        }
+        self.expire(now);
+        identity
+    }
+
+    // The identities a packet may be encrypted for, the current one first.
+    pub(crate) fn identities(&mut self, now: Instant) -> Vec<Arc<CryptoIdentity>> {
+        self.expire(now);
+        self.current.iter()
+            .chain(self.retired.iter().rev())
+            .map(|(identity, _)| identity.clone())
+            .collect()
+    }
+
+    // Drops the identities retired for longer than the retention unless a
+    // lookup still runs on them, and the oldest beyond the limit anyway.
+    fn expire(&mut self, now: Instant) {
+        self.retired.retain(|(identity, retired)| {
+            now.duration_since(*retired) < Self::RETENTION || Arc::strong_count(identity) > 1
+        });
+        while self.retired.len() > Self::MAX_RETIRED {
+            self.retired.pop_front();
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::HashSet,
+    sync::Mutex,
+};
+
+use crate::{
+    Id,
+    Network,
+    NodeInfo,
+};
+
+// What one DHT instance shares with the other one of a dual-stack node.
+#[derive(Default)]
+struct Slot {
+    present     : bool,
+    // The nodes of its routing table, as last published.
+    nodes       : Vec<NodeInfo>,
+    // Contacts of its family learned by the other instance.
+    handed_over : Vec<NodeInfo>,
+}
+
+// Lets the IPv4 and IPv6 DHT instances of a dual-stack node answer the
+// wants of both families. Each instance runs on its own thread, so they do
+// not reach into each other's routing table: every instance publishes its
+// nodes here from time to time for the other to answer from, and the other
+// hands over the contacts of the family it learns from responses. It is
+// only set up by the node when both instances run.
+pub(crate) struct Siblings {
+    slots: Mutex<[Slot; 2]>,
+}
+
+impl Siblings {
+    // Contacts kept for an instance until it takes them.
+    pub(crate) const MAX_HANDED_OVER: usize = 64;
+    // An instance with fewer nodes than this asks for contacts of its
+    // family in the requests of the other.
+    pub(crate) const WANT_CONTACTS_IF_LESS_THAN: usize = 8;
+
+    pub(crate) fn new() -> Self {
+        Self {
+            slots: Mutex::new(Default::default()),
+        }
+    }
+
+    fn index(network: Network) -> usize {
+        match network {
+            Network::IPv4 => 0,
+            Network::IPv6 => 1,
+        }
+    }
+
+    pub(crate) fn attach(&self, network: Network) {
+        self.slots.lock().unwrap()[Self::index(network)].present = true;
+    }
+
+    pub(crate) fn detach(&self, network: Network) {
+        self.slots.lock().unwrap()[Self::index(network)] = Slot::default();
+    }
+
+    // Replaces the nodes published by the instance of `network`.
+    pub(crate) fn publish(&self, network: Network, nodes: Vec<NodeInfo>) {
+        let mut slots = self.slots.lock().unwrap();
+        let slot = &mut slots[Self::index(network)];
+        if slot.present {
+            slot.nodes = nodes;
+        }
+    }
+
+    // The `count` published nodes of `network` closest to `target`, `None`
+    // without an instance for the family.
+    pub(crate) fn closest(&self, network: Network, target: &Id, count: usize) -> Option<Vec<NodeInfo>> {
+        let slots = self.slots.lock().unwrap();
+        let slot = &slots[Self::index(network)];
+        if !slot.present {
+            return None;
+        }
+
+        let mut nodes = slot.nodes.clone();
+        nodes.sort_by(|a, b| target.three_way_compare(a.id(), b.id()));
+        nodes.truncate(count);
+        Some(nodes)
+    }
+
+    // Whether the instance of `network` runs but knows too few nodes.
+    pub(crate) fn wants_contacts(&self, network: Network) -> bool {
+        let slots = self.slots.lock().unwrap();
+        let slot = &slots[Self::index(network)];
+        slot.present && slot.nodes.len() < Self::WANT_CONTACTS_IF_LESS_THAN
+    }
+
+    // Keeps the contacts of `network` for its instance, the ones it has
+    // already or can not use are left out.
+    pub(crate) fn hand_over(&self, network: Network, nodes: &[NodeInfo]) {
+        let mut slots = self.slots.lock().unwrap();
+        let slot = &mut slots[Self::index(network)];
+        if !slot.present {
+            return;
+        }
+
+        let mut known = slot.nodes.iter()
+            .chain(slot.handed_over.iter())
+            .map(|n| *n.id())
+            .collect::<HashSet<_>>();
+        for node in nodes {
+            if slot.handed_over.len() >= Self::MAX_HANDED_OVER {
+                break;
+            }
+            if network.can_use_address(node.socket_addr()) && known.insert(*node.id()) {
+                slot.handed_over.push(node.clone());
+            }
+        }
+    }
+
+    pub(crate) fn take_handed_over(&self, network: Network) -> Vec<NodeInfo> {
+        let mut slots = self.slots.lock().unwrap();
+        std::mem::take(&mut slots[Self::index(network)].handed_over)
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    fmt,
+    io,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::dht::stats::SocketErrorStats;
+
+#[cfg(target_os = "linux")]
+const EAFNOSUPPORT: i32 = 97;
+#[cfg(windows)]
+const EAFNOSUPPORT: i32 = 10047;
+#[cfg(not(any(target_os = "linux", windows)))]
+const EAFNOSUPPORT: i32 = 47;
+
+// Socket level problems of the DHT sockets, as told to the handler set
+// with Node::set_socket_error_handler. The errno is the raw OS error when
+// there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SocketEvent {
+    BindFailed { addr: SocketAddr, errno: Option<i32> },
+    SendFailed { errno: Option<i32>, dest: SocketAddr },
+    ReceiveFailed { errno: Option<i32> },
+    Rebound { old: SocketAddr, new: SocketAddr },
+}
+
+impl fmt::Display for SocketEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errno = |errno: &Option<i32>| match errno {
+            Some(errno) => format!("os error {errno}"),
+            None => "no os error".into(),
+        };
+        match self {
+            Self::BindFailed { addr, errno: e } =>
+                write!(f, "Binding {} failed: {}", addr, errno(e)),
+            Self::SendFailed { errno: e, dest } =>
+                write!(f, "Sending to {} failed: {}", dest, errno(e)),
+            Self::ReceiveFailed { errno: e } =>
+                write!(f, "Receiving failed: {}", errno(e)),
+            Self::Rebound { old, new } =>
+                write!(f, "Socket rebound from {} to {}", old, new),
+        }
+    }
+}
+
+// Kinds of socket errors the counters are broken down by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrnoClass {
+    AddressInUse,
+    AddressUnavailable,
+    AddressFamily,
+    Unreachable,
+    PermissionDenied,
+    Refused,
+    NoBuffers,
+    Other,
+}
+
+impl ErrnoClass {
+    pub fn of(error: &io::Error) -> Self {
+        use io::ErrorKind::*;
+        match error.kind() {
+            AddrInUse => Self::AddressInUse,
+            AddrNotAvailable => Self::AddressUnavailable,
+            NetworkUnreachable | HostUnreachable | NetworkDown => Self::Unreachable,
+            PermissionDenied => Self::PermissionDenied,
+            ConnectionRefused | ConnectionReset => Self::Refused,
+            WouldBlock | OutOfMemory => Self::NoBuffers,
+            _ if error.raw_os_error() == Some(EAFNOSUPPORT) => Self::AddressFamily,
+            _ => Self::Other,
+        }
+    }
+}
+
+impl fmt::Display for ErrnoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::AddressInUse => "addressInUse",
+            Self::AddressUnavailable => "addressUnavailable",
+            Self::AddressFamily => "addressFamily",
+            Self::Unreachable => "unreachable",
+            Self::PermissionDenied => "permissionDenied",
+            Self::Refused => "refused",
+            Self::NoBuffers => "noBuffers",
+            Self::Other => "other",
+        })
+    }
+}
+
+pub type SocketErrorHandler = Box<dyn Fn(&SocketEvent) + Send + Sync>;
+
+#[derive(Default)]
+struct Inner {
+    handler     : Option<Arc<SocketErrorHandler>>,
+    stats       : SocketErrorStats,
+    window      : Option<Instant>,
+    delivered   : u32,
+}
+
+// Counts the socket errors of both DHT instances and tells the handler,
+// at most MAX_EVENTS send and receive failures per window, the others
+// only counted as suppressed. Bind failures and rebinds always get
+// through. The handler runs on the DHT thread and should return quickly.
+#[derive(Default)]
+pub(crate) struct SocketErrors {
+    inner: Mutex<Inner>,
+}
+
+impl SocketErrors {
+    pub(crate) const MAX_EVENTS: u32 = 10;
+    pub(crate) const WINDOW: Duration = Duration::from_secs(1);
+
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn set_handler(&self, handler: SocketErrorHandler) {
+        self.inner.lock().unwrap().handler = Some(Arc::new(handler));
+    }
+
+    pub(crate) fn stats(&self) -> SocketErrorStats {
+        self.inner.lock().unwrap().stats.clone()
+    }
+
+    pub(crate) fn bind_failed(&self, addr: SocketAddr, error: &io::Error) {
+        let handler = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.stats.bind_failures += 1;
+            inner.handler.clone()
+        };
+        if let Some(h) = handler {
+            h(&SocketEvent::BindFailed { addr, errno: error.raw_os_error() });
+        }
+    }
+
+    pub(crate) fn send_failed(&self, dest: SocketAddr, error: &io::Error) {
+        let handler = {
+            let mut inner = self.inner.lock().unwrap();
+            *inner.stats.send.entry(ErrnoClass::of(error)).or_default() += 1;
+            Self::limited(&mut inner)
+        };
+        if let Some(h) = handler {
+            h(&SocketEvent::SendFailed { errno: error.raw_os_error(), dest });
+        }
+    }
+
+    pub(crate) fn receive_failed(&self, error: &io::Error) {
+        let handler = {
+            let mut inner = self.inner.lock().unwrap();
+            *inner.stats.receive.entry(ErrnoClass::of(error)).or_default() += 1;
+            Self::limited(&mut inner)
+        };
+        if let Some(h) = handler {
+            h(&SocketEvent::ReceiveFailed { errno: error.raw_os_error() });
+        }
+    }
+
+    pub(crate) fn rebound(&self, old: SocketAddr, new: SocketAddr) {
+        let handler = self.inner.lock().unwrap().handler.clone();
+        if let Some(h) = handler {
+            h(&SocketEvent::Rebound { old, new });
+        }
+    }
+
+    // The handler if the event may go out in the current window.
+    fn limited(inner: &mut Inner) -> Option<Arc<SocketErrorHandler>> {
+        let handler = inner.handler.clone()?;
+        let now = Instant::now();
+        if inner.window.is_none_or(|start| now.duration_since(start) >= Self::WINDOW) {
+            inner.window = Some(now);
+            inner.delivered = 0;
+        }
+        if inner.delivered >= Self::MAX_EVENTS {
+            inner.stats.suppressed += 1;
+            return None;
+        }
+        inner.delivered += 1;
+        Some(handler)
+    }
+}
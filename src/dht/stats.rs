@@ -1,90 +1,727 @@
-use std::fmt;
-use crate::core::msg::msg;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use serde::{Deserialize, Serialize};
+use log::warn;
 
-pub(crate) struct Stats {
-    // TODO;
+use crate::{
+    Id,
+    Network,
+    Error,
+    errors::{Result, IOError},
+    dht::socket_event::ErrnoClass,
+};
+
+pub const STATS_JOURNAL_FILE: &str = "stats.log";
+
+// Cumulative RPC traffic counters of one DHT instance.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RpcCounters {
+    pub(crate) msgs_sent        : u64,
+    pub(crate) msgs_received    : u64,
+    pub(crate) bytes_sent       : u64,
+    pub(crate) bytes_received   : u64,
+    pub(crate) calls_sent       : u64,
+    pub(crate) calls_timeout    : u64,
+    // Packets held back by the send shaper before leaving.
+    pub(crate) msgs_shaped      : u64,
+}
+
+impl RpcCounters {
+    fn delta(&self, prev: &Self) -> Self {
+        Self {
+            msgs_sent       : self.msgs_sent.saturating_sub(prev.msgs_sent),
+            msgs_received   : self.msgs_received.saturating_sub(prev.msgs_received),
+            bytes_sent      : self.bytes_sent.saturating_sub(prev.bytes_sent),
+            bytes_received  : self.bytes_received.saturating_sub(prev.bytes_received),
+            calls_sent      : self.calls_sent.saturating_sub(prev.calls_sent),
+            calls_timeout   : self.calls_timeout.saturating_sub(prev.calls_timeout),
+            msgs_shaped     : self.msgs_shaped.saturating_sub(prev.msgs_shaped),
+        }
+    }
+}
+
+// Tasks and RPC calls of one DHT instance at the time of a sample, the
+// peaks are the highest values since the DHT started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Concurrency {
+    #[serde(rename = "tasks")]
+    pub(crate) running_tasks    : usize,
+    #[serde(rename = "peakTasks")]
+    pub(crate) peak_tasks       : usize,
+    #[serde(rename = "queuedTasks")]
+    pub(crate) queued_tasks     : usize,
+    #[serde(rename = "calls")]
+    pub(crate) inflight_calls   : usize,
+    #[serde(rename = "peakCalls")]
+    pub(crate) peak_calls       : usize,
+    #[serde(rename = "deferredCalls")]
+    pub(crate) deferred_calls   : usize,
+}
+
+impl Concurrency {
+    pub fn running_tasks(&self) -> usize {
+        self.running_tasks
+    }
+
+    pub fn peak_tasks(&self) -> usize {
+        self.peak_tasks
+    }
+
+    pub fn queued_tasks(&self) -> usize {
+        self.queued_tasks
+    }
+
+    pub fn inflight_calls(&self) -> usize {
+        self.inflight_calls
+    }
+
+    pub fn peak_calls(&self) -> usize {
+        self.peak_calls
+    }
+
+    // Calls waiting for a free slot below the in-flight limit.
+    pub fn deferred_calls(&self) -> usize {
+        self.deferred_calls
+    }
+}
+
+// Commands from the node to one DHT instance: those waiting in the queue
+// at the time of a sample, the most ever waiting, bounded by the capacity,
+// and how many of each kind were received since the DHT started.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CommandQueue {
+    pub(crate) capacity     : usize,
+    pub(crate) depth        : usize,
+    pub(crate) peak_depth   : usize,
+    pub(crate) counts       : BTreeMap<&'static str, u64>,
+}
+
+impl CommandQueue {
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn peak_depth(&self) -> usize {
+        self.peak_depth
+    }
+
+    // Commands received of the given kind, such as "findValue".
+    pub fn count(&self, kind: &str) -> u64 {
+        self.counts.get(kind).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum()
+    }
+}
+
+// Crypto contexts cached by the node: those kept at the time, and how
+// many lookups found one or derived it, and how many were evicted for
+// capacity or age or invalidated since the node started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CryptoCacheStats {
+    pub(crate) entries      : u64,
+    pub(crate) hits         : u64,
+    pub(crate) misses       : u64,
+    pub(crate) evictions    : u64,
+    pub(crate) invalidations: u64,
+}
+
+impl CryptoCacheStats {
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations
+    }
+
+    // Share of the lookups that found a cached context.
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            n => self.hits as f64 / n as f64,
+        }
+    }
+}
+
+// Write tokens of other nodes cached for announcing: those kept at the
+// time, and how many announces found a fresh one for their target or
+// looked it up since the DHT started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenCacheStats {
+    pub(crate) entries  : u64,
+    pub(crate) hits     : u64,
+    pub(crate) misses   : u64,
+}
+
+impl TokenCacheStats {
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+}
+
+// Blocked addresses and node ids in force, the packets dropped for coming
+// from them, the nodes kept out of or evicted from the routing tables for
+// them, and the nodes blocked for their invalid tokens or values since the
+// node started.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BlocklistStats {
+    pub(crate) entries              : u64,
+    pub(crate) dropped_by_address   : u64,
+    pub(crate) dropped_by_id        : u64,
+    pub(crate) refused_entries      : u64,
+    pub(crate) evicted_entries      : u64,
+    pub(crate) auto_blocks          : u64,
+}
+
+impl BlocklistStats {
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn dropped_by_address(&self) -> u64 {
+        self.dropped_by_address
+    }
+
+    pub fn dropped_by_id(&self) -> u64 {
+        self.dropped_by_id
+    }
+
+    pub fn refused_entries(&self) -> u64 {
+        self.refused_entries
+    }
+
+    pub fn evicted_entries(&self) -> u64 {
+        self.evicted_entries
+    }
+
+    pub fn auto_blocks(&self) -> u64 {
+        self.auto_blocks
+    }
+}
+
+// Origin nodes of the stored peers checked and found gone, the peers
+// given a shortened lifetime for it and those removed early since the node
+// started, and the peers waiting to be removed early now.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OriginCheckStats {
+    pub(crate) checks           : u64,
+    pub(crate) failures         : u64,
+    pub(crate) shortened_peers  : u64,
+    pub(crate) expired_peers    : u64,
+    pub(crate) pending_peers    : u64,
+}
+
+impl OriginCheckStats {
+    pub fn checks(&self) -> u64 {
+        self.checks
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    pub fn shortened_peers(&self) -> u64 {
+        self.shortened_peers
+    }
+
+    pub fn expired_peers(&self) -> u64 {
+        self.expired_peers
+    }
+
+    pub fn pending_peers(&self) -> u64 {
+        self.pending_peers
+    }
+}
+
+// The receive threads of a DHT in multi-socket mode: the packets read and
+// those dropped for a full queue on each socket, and the packets waiting
+// for the DHT thread at the time of a sample, bounded by the capacity.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReceiveStats {
+    pub(crate) packets      : Vec<u64>,
+    pub(crate) dropped      : Vec<u64>,
+    pub(crate) capacity     : usize,
+    pub(crate) depth        : usize,
 }
 
-impl Stats {
-    pub(crate) fn new() -> Self {
-        Stats {}
+impl ReceiveStats {
+    pub fn sockets(&self) -> usize {
+        self.packets.len()
     }
 
-    pub(crate) fn received_bytes(&self) -> usize {
-        unimplemented!()
+    pub fn packets(&self) -> &[u64] {
+        &self.packets
     }
 
-    pub(crate) fn sent_bytes(&self) -> usize {
-        unimplemented!()
+    pub fn dropped(&self) -> &[u64] {
+        &self.dropped
     }
 
-    pub(crate) fn received_bytes_in_sec(&self) -> usize {
-        unimplemented!()
+    pub fn total_packets(&self) -> u64 {
+        self.packets.iter().sum()
     }
 
-    pub(crate) fn sent_bytes_in_sec(&self) -> usize {
-        unimplemented!()
+    pub fn total_dropped(&self) -> u64 {
+        self.dropped.iter().sum()
     }
 
-    pub(crate) fn received_msgs(&self, _: msg::Method, _: msg::Kind) -> usize {
-        unimplemented!()
+    pub fn capacity(&self) -> usize {
+        self.capacity
     }
 
-    pub(crate) fn total_received_msgs(&self) -> usize {
-        unimplemented!()
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+// Socket errors of both DHT instances since the node was created: send and
+// receive failures by errno class, bind failures on start and rebind, and
+// the events held back from the error handler by its rate limit.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SocketErrorStats {
+    pub(crate) send             : BTreeMap<ErrnoClass, u64>,
+    pub(crate) receive          : BTreeMap<ErrnoClass, u64>,
+    pub(crate) bind_failures    : u64,
+    pub(crate) suppressed       : u64,
+}
+
+impl SocketErrorStats {
+    pub fn send_errors(&self) -> &BTreeMap<ErrnoClass, u64> {
+        &self.send
+    }
+
+    pub fn receive_errors(&self) -> &BTreeMap<ErrnoClass, u64> {
+        &self.receive
+    }
+
+    pub fn total_send_errors(&self) -> u64 {
+        self.send.values().sum()
+    }
+
+    pub fn total_receive_errors(&self) -> u64 {
+        self.receive.values().sum()
     }
 
-    pub(crate) fn sent_msgs(&self, _: msg::Method, _: msg::Kind) -> usize {
-        unimplemented!()
+    pub fn bind_failures(&self) -> u64 {
+        self.bind_failures
     }
 
-    pub(crate) fn total_sent_msgs(&self) -> usize {
-        unimplemented!()
+    pub fn suppressed_events(&self) -> u64 {
+        self.suppressed
     }
+}
+
+// Aggregates over the values or the peers stored under a prefix. The times
+// are the updated times in milliseconds since the Unix epoch, the median
+// of an even count the mean of the two middle ones. Publishers are the
+// distinct public keys of the values and the distinct origin nodes of the
+// peers, bytes the value data and the peer endpoints and extra data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyspaceStats {
+    pub(crate) count        : usize,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) oldest       : Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) newest       : Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) median       : Option<u64>,
+    pub(crate) publishers   : usize,
+    pub(crate) bytes        : u64,
+}
 
-    pub(crate) fn timeout_msgs(&self, _: msg::Method) -> usize {
-        unimplemented!()
+impl KeyspaceStats {
+    // Builds the stats from the updated times and sizes of the entries in
+    // the range and the number of their distinct publishers.
+    pub(crate) fn from(mut updated: Vec<u64>, publishers: usize, bytes: u64) -> Self {
+        updated.sort_unstable();
+        Self {
+            count   : updated.len(),
+            oldest  : updated.first().copied(),
+            newest  : updated.last().copied(),
+            median  : Self::median_of(&updated),
+            publishers,
+            bytes,
+        }
     }
 
-    pub(crate) fn total_timeout_msgs(&self) -> usize {
-        unimplemented!()
+    // The median of sorted times, the middle one or the two middle ones.
+    pub(crate) fn median_of(sorted: &[u64]) -> Option<u64> {
+        let mid = sorted.len() / 2;
+        match sorted.len() {
+            0 => None,
+            n if n % 2 == 1 => Some(sorted[mid]),
+            _ => Some(sorted[mid - 1] + (sorted[mid] - sorted[mid - 1]) / 2),
+        }
     }
 
-    pub(crate) fn dropped_packets(&self) -> usize {
-        unimplemented!()
+    pub fn count(&self) -> usize {
+        self.count
     }
 
-    pub(crate) fn dropped_bytes(&self) -> usize {
-        unimplemented!()
+    pub fn oldest(&self) -> Option<u64> {
+        self.oldest
     }
 
-    pub(crate) fn on_received_bytes(&mut self, _: usize) {
-        unimplemented!()
+    pub fn newest(&self) -> Option<u64> {
+        self.newest
     }
 
-    pub(crate) fn on_sent_bytes(&mut self, _: usize) {
-        unimplemented!()
+    pub fn median(&self) -> Option<u64> {
+        self.median
+    }
+
+    pub fn publishers(&self) -> usize {
+        self.publishers
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+// What the node keeps under a prefix of the keyspace: the values and peers
+// in storage and the routing table entries of both networks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyspaceReport {
+    pub(crate) prefix           : Id,
+    pub(crate) depth            : i32,
+    pub(crate) values           : KeyspaceStats,
+    pub(crate) peers            : KeyspaceStats,
+    pub(crate) routing_entries  : usize,
+}
+
+impl KeyspaceReport {
+    pub fn prefix(&self) -> &Id {
+        &self.prefix
     }
 
-    pub(crate) fn on_received_msg(&mut self, _: &Box<dyn msg::Msg>) {
-        unimplemented!()
+    pub fn depth(&self) -> i32 {
+        self.depth
     }
 
-    pub(crate) fn on_sent_msg(&mut self, _: &Box<dyn msg::Msg>) {
-        unimplemented!()
+    pub fn values(&self) -> &KeyspaceStats {
+        &self.values
     }
 
-    pub(crate) fn on_timeout_msg(&mut self, _: &Box<dyn msg::Msg>) {
-        unimplemented!()
+    pub fn peers(&self) -> &KeyspaceStats {
+        &self.peers
     }
 
-    pub(crate) fn on_dropped_packet(&mut self, _: usize) {
-        unimplemented!()
+    pub fn routing_entries(&self) -> usize {
+        self.routing_entries
     }
 }
 
-impl fmt::Display for Stats {
-    fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
-        unimplemented!()
+// Point-in-time view of one DHT instance, taken on its own thread.
+#[derive(Clone)]
+pub(crate) struct DhtStats {
+    pub(crate) network          : Network,
+    pub(crate) routing_entries  : usize,
+    pub(crate) versions         : BTreeMap<String, usize>,
+    pub(crate) reachable        : bool,
+    pub(crate) addr             : Option<SocketAddr>,
+    pub(crate) counters         : RpcCounters,
+    pub(crate) active_tasks     : usize,
+    pub(crate) concurrency      : Concurrency,
+    pub(crate) commands         : CommandQueue,
+    pub(crate) value_lookups    : u64,
+    pub(crate) token_cache      : TokenCacheStats,
+    pub(crate) receive          : Option<ReceiveStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSample {
+    #[serde(rename = "rt")]
+    routing_entries : usize,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    versions        : BTreeMap<String, usize>,
+    reachable       : bool,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    addr            : Option<SocketAddr>,
+    #[serde(rename = "sent")]
+    msgs_sent       : u64,
+    #[serde(rename = "received")]
+    msgs_received   : u64,
+    #[serde(rename = "sentBytes")]
+    bytes_sent      : u64,
+    #[serde(rename = "receivedBytes")]
+    bytes_received  : u64,
+    #[serde(rename = "calls")]
+    calls_sent      : u64,
+    #[serde(rename = "timeouts")]
+    calls_timeout   : u64,
+    #[serde(rename = "timeoutRate")]
+    timeout_rate    : f64,
+    #[serde(rename = "shaped", default)]
+    msgs_shaped     : u64,
+    #[serde(rename = "load", default)]
+    concurrency     : Concurrency,
+}
+
+impl NetworkSample {
+    fn new(stats: &DhtStats, delta: RpcCounters) -> Self {
+        let timeout_rate = match delta.calls_sent {
+            0 => 0.0,
+            n => delta.calls_timeout as f64 / n as f64,
+        };
+        Self {
+            routing_entries : stats.routing_entries,
+            versions        : stats.versions.clone(),
+            reachable       : stats.reachable,
+            addr            : stats.addr,
+            msgs_sent       : delta.msgs_sent,
+            msgs_received   : delta.msgs_received,
+            bytes_sent      : delta.bytes_sent,
+            bytes_received  : delta.bytes_received,
+            calls_sent      : delta.calls_sent,
+            calls_timeout   : delta.calls_timeout,
+            timeout_rate,
+            msgs_shaped     : delta.msgs_shaped,
+            concurrency     : stats.concurrency,
+        }
+    }
+
+    pub fn routing_entries(&self) -> usize {
+        self.routing_entries
+    }
+
+    // Number of routing table entries running each version, keyed by its
+    // normalized form such as "MK/4".
+    pub fn versions(&self) -> &BTreeMap<String, usize> {
+        &self.versions
+    }
+
+    pub fn is_reachable(&self) -> bool {
+        self.reachable
     }
+
+    // The address the DHT socket is bound to.
+    pub fn addr(&self) -> Option<&SocketAddr> {
+        self.addr.as_ref()
+    }
+
+    // RPC counters below are deltas since the previous sample.
+    pub fn msgs_sent(&self) -> u64 {
+        self.msgs_sent
+    }
+
+    pub fn msgs_received(&self) -> u64 {
+        self.msgs_received
+    }
+
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    pub fn calls_sent(&self) -> u64 {
+        self.calls_sent
+    }
+
+    pub fn calls_timeout(&self) -> u64 {
+        self.calls_timeout
+    }
+
+    pub fn timeout_rate(&self) -> f64 {
+        self.timeout_rate
+    }
+
+    // Packets queued by the outgoing rate shaper.
+    pub fn msgs_shaped(&self) -> u64 {
+        self.msgs_shaped
+    }
+
+    pub fn concurrency(&self) -> &Concurrency {
+        &self.concurrency
+    }
+}
+
+// One line of the stats journal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsSample {
+    #[serde(rename = "ts")]
+    timestamp   : u64,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    ipv4        : Option<NetworkSample>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    ipv6        : Option<NetworkSample>,
+    values      : usize,
+    peers       : usize,
+}
+
+impl StatsSample {
+    // Milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn network(&self, network: Network) -> Option<&NetworkSample> {
+        match network {
+            Network::IPv4 => self.ipv4.as_ref(),
+            Network::IPv6 => self.ipv6.as_ref(),
+        }
+    }
+
+    pub fn values(&self) -> usize {
+        self.values
+    }
+
+    pub fn peers(&self) -> usize {
+        self.peers
+    }
+}
+
+// Appends stats samples as JSON lines, rotating the file once it would
+// grow past `max_size`: stats.log -> stats.log.1 -> ... -> stats.log.<max_files>.
+pub(crate) struct StatsJournal {
+    path        : PathBuf,
+    max_size    : u64,
+    max_files   : usize,
+    last4       : RpcCounters,
+    last6       : RpcCounters,
+}
+
+impl StatsJournal {
+    pub(crate) fn new(path: PathBuf, max_size: u64, max_files: usize) -> Self {
+        Self {
+            path,
+            max_size,
+            max_files,
+            last4   : RpcCounters::default(),
+            last6   : RpcCounters::default(),
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn record(&mut self,
+        dht4: Option<DhtStats>,
+        dht6: Option<DhtStats>,
+        values: usize,
+        peers: usize
+    ) -> Result<StatsSample> {
+        let mut sample = StatsSample {
+            timestamp: crate::as_ms!(SystemTime::now()) as u64,
+            ipv4: None,
+            ipv6: None,
+            values,
+            peers,
+        };
+
+        for stats in [dht4, dht6].into_iter().flatten() {
+            let (last, slot) = match stats.network {
+                Network::IPv4 => (&mut self.last4, &mut sample.ipv4),
+                Network::IPv6 => (&mut self.last6, &mut sample.ipv6),
+            };
+            *slot = Some(NetworkSample::new(&stats, stats.counters.delta(last)));
+            *last = stats.counters;
+        }
+
+        self.append(&sample)?;
+        Ok(sample)
+    }
+
+    fn append(&self, sample: &StatsSample) -> Result<()> {
+        let mut line = serde_json::to_string(sample).map_err(|e|
+            IOError::new(format!("Serializing stats sample error: {e}"))
+        )?;
+        line.push('\n');
+
+        let size = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > 0 && size + line.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| -> Error {
+                IOError::new(format!("Writing stats journal {} error: {e}", self.path.display()))
+            })
+    }
+
+    fn rotate(&self) -> Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path).map_err(|e| -> Error {
+                IOError::new(format!("Truncating stats journal {} error: {e}", self.path.display()))
+            });
+        }
+
+        let _ = fs::remove_file(rotated_path(&self.path, self.max_files));
+        for n in (1..self.max_files).rev() {
+            let from = rotated_path(&self.path, n);
+            if from.exists() {
+                let _ = fs::rename(&from, rotated_path(&self.path, n + 1));
+            }
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1)).map_err(|e| -> Error {
+            IOError::new(format!("Rotating stats journal {} error: {e}", self.path.display()))
+        })
+    }
+}
+
+// Path of the n-th rotated journal file, n starts from 1.
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
+
+// Reads all well-formed samples of a journal file, malformed lines are skipped.
+pub fn read_journal(path: impl AsRef<Path>) -> Vec<StatsSample> {
+    let path = path.as_ref();
+    let Ok(file) = File::open(path) else {
+        return Vec::new();
+    };
+
+    BufReader::new(file).lines()
+        .map_while(|line| line.ok())
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str::<StatsSample>(&line)
+                .map_err(|e| warn!("Skipping malformed stats sample in {}: {e}", path.display()))
+                .ok()
+        })
+        .collect()
 }
@@ -1,11 +1,66 @@
-use std::time::Duration;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 use crate::{
     Id,
     Value,
     PeerInfo,
-    core::Result
+    core::Result,
+    errors::IOError,
+    dht::stats::KeyspaceStats,
 };
 
+// Rows sampled per table by an integrity check to verify their signatures.
+pub(crate) const SPOT_CHECK_SAMPLES: usize = 64;
+
+// Outcome of a storage integrity check.
+#[derive(Debug, Clone, Default)]
+pub struct IntegrityReport {
+    pub(crate) errors           : Vec<String>,
+    pub(crate) values_checked   : usize,
+    pub(crate) invalid_values   : Vec<Id>,
+    pub(crate) peers_checked    : usize,
+    pub(crate) invalid_peers    : Vec<Id>,
+}
+
+// Values and peers removed by a purge once expired.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Expired {
+    pub(crate) values   : Vec<Id>,
+    pub(crate) peers    : Vec<(Id, u64)>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty() && self.invalid_values.is_empty() && self.invalid_peers.is_empty()
+    }
+
+    // Problems reported by the database engine itself.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn values_checked(&self) -> usize {
+        self.values_checked
+    }
+
+    // Values whose stored row does not decode or verify.
+    pub fn invalid_values(&self) -> &[Id] {
+        &self.invalid_values
+    }
+
+    pub fn peers_checked(&self) -> usize {
+        self.peers_checked
+    }
+
+    // Peers whose stored row does not decode or verify.
+    pub fn invalid_peers(&self) -> &[Id] {
+        &self.invalid_peers
+    }
+}
+
 pub(crate) trait DataStorage: Send + Sync {
     fn open(&mut self,
         path: &str
@@ -17,7 +72,15 @@ pub(crate) trait DataStorage: Send + Sync {
     ) -> Result<()>;
 
     fn close(&mut self);
-    fn purge(&mut self);
+    fn purge(&mut self) -> Expired;
+
+    // Returns free pages to the file system and refreshes the query planner
+    // statistics, returns the number of pages freed.
+    fn maintain(&mut self) -> Result<u64>;
+
+    // Checks the database structure, a quick check skips the index
+    // consistency and the row spot checks.
+    fn check_integrity(&self, quick: bool) -> Result<IntegrityReport>;
 
     // parameters listed:
     // - value: Value;
@@ -28,11 +91,30 @@ pub(crate) trait DataStorage: Send + Sync {
         _persistent: bool,
     ) -> Result<()>;
 
+    // Stores a value put by the local node. It stays marked as such when
+    // other nodes store it again with put_value.
+    fn put_local_value(
+        &mut self,
+        _value: Value,
+        _persistent: bool,
+    ) -> Result<()>;
+
+    fn is_local_value(
+        &self,
+        _value_id: &Id
+    ) -> Result<bool>;
+
     fn get_value(
         &self,
         _value_id: &Id
     ) -> Result<Option<Value>>;
 
+    // The time in milliseconds the value was last stored or announced.
+    fn get_value_updated(
+        &self,
+        _value_id: &Id
+    ) -> Result<Option<u64>>;
+
     #[allow(unused)]
     fn get_values(&self) -> Result<Vec<Value>>;
 
@@ -57,6 +139,11 @@ pub(crate) trait DataStorage: Send + Sync {
 
     fn remove_value(&mut self, _: &Id) -> Result<()>;
 
+    fn count_values(&self) -> Result<usize>;
+
+    // Aggregates over the values with ids from first to last, both included.
+    fn value_range_stats(&self, _first: &Id, _last: &Id) -> Result<KeyspaceStats>;
+
     // methods related to peer(s)
     fn put_peer(&mut self,
         _peer: PeerInfo,
@@ -72,8 +159,16 @@ pub(crate) trait DataStorage: Send + Sync {
         _fingerprint: u64
     ) -> Result<Option<PeerInfo>>;
 
+    // The time in milliseconds the peer was last stored or announced.
+    fn get_peer_updated(&self,
+        _id: &Id,
+        _fingerprint: u64
+    ) -> Result<Option<u64>>;
+
     fn get_peers(&self, _: &Id) -> Result<Vec<PeerInfo>>;
 
+    // A random subset of up to `limit` peers each time, so every peer
+    // announced under the id gets its share of the responses.
     fn get_peers_with_expected_seq(&self,
         _peerid: &Id,
         _expected_seq: i32,
@@ -109,6 +204,15 @@ pub(crate) trait DataStorage: Send + Sync {
     #[allow(unused)]
     fn get_peers_all(&self) -> Result<Vec<PeerInfo>>;
 
+    fn count_peers(&self) -> Result<usize>;
+
+    // The number of peers announced under the id.
+    #[allow(unused)]
+    fn count_peers_by_id(&self, _: &Id) -> Result<usize>;
+
+    // Aggregates over the peers with ids from first to last, both included.
+    fn peer_range_stats(&self, _first: &Id, _last: &Id) -> Result<KeyspaceStats>;
+
     fn update_peer_announced_time(&mut self,
         _: &Id,
         _: u64
@@ -131,3 +235,27 @@ pub(crate) fn supports(database_uri: &str) -> bool {
 pub(crate) fn database_name(database_uri: &str) -> &str {
     database_uri.trim_start_matches("jdbc:sqlite:")
 }
+
+// Moves a corrupt database and its journal files aside so a fresh one can
+// be created in its place, returns where the database file was moved to.
+pub(crate) fn quarantine(path: &Path) -> Result<PathBuf> {
+    let suffix = format!(".corrupt-{}", crate::as_ms!(SystemTime::now()));
+    let with_suffix = |path: &Path, extra: &str| {
+        let mut name = path.as_os_str().to_os_string();
+        name.push(extra);
+        PathBuf::from(name)
+    };
+
+    let moved = with_suffix(path, &suffix);
+    fs::rename(path, &moved).map_err(|e| IOError::new(format!(
+        "Moving corrupt database {} aside error: {e}", path.display()
+    )))?;
+
+    for journal in ["-wal", "-shm", "-journal"] {
+        let from = with_suffix(path, journal);
+        if from.exists() {
+            let _ = fs::rename(&from, with_suffix(&moved, journal));
+        }
+    }
+    Ok(moved)
+}
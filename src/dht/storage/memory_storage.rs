@@ -0,0 +1,422 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use rand::seq::IndexedRandom;
+
+use crate::{
+    Id,
+    Clock,
+    SystemClock,
+    PeerInfo,
+    Value,
+    Result,
+    errors::{StateError, ArgumentError},
+};
+use crate::dht::storage::data_storage::{
+    DataStorage,
+    Expired,
+    IntegrityReport,
+    SPOT_CHECK_SAMPLES,
+};
+use crate::dht::stats::KeyspaceStats;
+
+struct ValueEntry {
+    value: Value,
+    persistent: bool,
+    updated: u64,
+    local: bool,
+}
+
+struct PeerEntry {
+    peer: PeerInfo,
+    persistent: bool,
+    updated: u64,
+}
+
+// In-memory DataStorage implementation, mirroring the semantics of the
+// sqlite-backed storage for ephemeral nodes and tests. Nothing survives
+// the process, but the data is kept across close/open of the same instance.
+pub(crate) struct MemoryStorage {
+    values: HashMap<Id, ValueEntry>,
+    peers: HashMap<(Id, u64), PeerEntry>,
+    value_expiry: Duration,
+    peer_expiry: Duration,
+    opened: bool,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryStorage {
+    #[allow(unused)]
+    pub(crate) fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub(crate) fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            values: HashMap::new(),
+            peers: HashMap::new(),
+            value_expiry: Duration::MAX,
+            peer_expiry: Duration::MAX,
+            opened: false,
+            clock,
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
+    }
+
+    fn insert_value(&mut self, value: Value, persistent: bool, local: bool) -> Result<()> {
+        self.check_opened()?;
+        self.values.insert(value.id(), ValueEntry {
+            value,
+            persistent,
+            updated: self.now_ms(),
+            local,
+        });
+        Ok(())
+    }
+
+    fn cutoff(&self, expiry: Duration) -> u64 {
+        self.now_ms().saturating_sub(expiry.as_millis().min(u64::MAX as u128) as u64)
+    }
+
+    fn check_opened(&self) -> Result<()> {
+        match self.opened {
+            true => Ok(()),
+            false => Err(StateError::new("Memory storage is not opened")),
+        }
+    }
+
+    fn sorted_values(&self) -> Vec<&ValueEntry> {
+        let mut entries = self.values.values().collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.value.id());
+        entries
+    }
+
+    fn sorted_peers(&self) -> Vec<&PeerEntry> {
+        let mut entries = self.peers.values().collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            (a.peer.id(), a.peer.fingerprint()).cmp(&(b.peer.id(), b.peer.fingerprint()))
+        });
+        entries
+    }
+}
+
+impl DataStorage for MemoryStorage {
+    fn open(&mut self, _path: &str) -> Result<()> {
+        self.opened = true;
+        Ok(())
+    }
+
+    fn initialize(&mut self,
+        value_expiry: Duration,
+        peer_expiry: Duration
+    ) -> Result<()> {
+        self.value_expiry = value_expiry;
+        self.peer_expiry  = peer_expiry;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.opened = false;
+    }
+
+    fn purge(&mut self) -> Expired {
+        let value_cutoff = self.cutoff(self.value_expiry);
+        let peer_cutoff  = self.cutoff(self.peer_expiry);
+        let mut expired = Expired::default();
+
+        self.values.retain(|id, e| {
+            let keep = e.persistent || e.updated > value_cutoff;
+            if !keep {
+                expired.values.push(*id);
+            }
+            keep
+        });
+        // By the publisher's clock like the sqlite storage, see remove_expired_peers.
+        self.peers.retain(|key, e| {
+            let keep = e.persistent || e.peer.announced().unwrap_or(e.updated) > peer_cutoff;
+            if !keep {
+                expired.peers.push(*key);
+            }
+            keep
+        });
+        expired
+    }
+
+    fn maintain(&mut self) -> Result<u64> {
+        self.check_opened()?;
+        Ok(0)
+    }
+
+    // Nothing is persisted, only the stored entries are spot checked.
+    fn check_integrity(&self, quick: bool) -> Result<IntegrityReport> {
+        self.check_opened()?;
+        let mut report = IntegrityReport::default();
+        if quick {
+            return Ok(report);
+        }
+
+        for entry in self.values.values().take(SPOT_CHECK_SAMPLES) {
+            report.values_checked += 1;
+            if !entry.value.is_valid() {
+                report.invalid_values.push(entry.value.id());
+            }
+        }
+        for entry in self.peers.values().take(SPOT_CHECK_SAMPLES) {
+            report.peers_checked += 1;
+            if !entry.peer.is_valid() {
+                report.invalid_peers.push(*entry.peer.id());
+            }
+        }
+        Ok(report)
+    }
+
+    // ── values ────
+    fn put_value(&mut self, value: Value, persistent: bool) -> Result<()> {
+        let local = self.is_local_value(&value.id())?;
+        self.insert_value(value, persistent, local)
+    }
+
+    fn put_local_value(&mut self, value: Value, persistent: bool) -> Result<()> {
+        self.insert_value(value, persistent, true)
+    }
+
+    fn is_local_value(&self, id: &Id) -> Result<bool> {
+        self.check_opened()?;
+        Ok(self.values.get(id).is_some_and(|e| e.local))
+    }
+
+    fn get_value(&self, id: &Id) -> Result<Option<Value>> {
+        self.check_opened()?;
+        Ok(self.values.get(id).map(|e| e.value.clone()))
+    }
+
+    fn get_value_updated(&self, id: &Id) -> Result<Option<u64>> {
+        self.check_opened()?;
+        Ok(self.values.get(id).map(|e| e.updated))
+    }
+
+    fn count_values(&self) -> Result<usize> {
+        self.check_opened()?;
+        Ok(self.values.len())
+    }
+
+    fn value_range_stats(&self, first: &Id, last: &Id) -> Result<KeyspaceStats> {
+        self.check_opened()?;
+        let entries = self.values.iter()
+            .filter(|(id, _)| *id >= first && *id <= last)
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>();
+        let publishers = entries.iter()
+            .filter_map(|e| e.value.public_key())
+            .collect::<HashSet<_>>();
+        Ok(KeyspaceStats::from(
+            entries.iter().map(|e| e.updated).collect(),
+            publishers.len(),
+            entries.iter().map(|e| e.value.data().len() as u64).sum(),
+        ))
+    }
+
+    fn get_values(&self) -> Result<Vec<Value>> {
+        self.check_opened()?;
+        Ok(self.values.values().map(|e| e.value.clone()).collect())
+    }
+
+    fn get_values_announced_before(
+        &self,
+        persistent: bool,
+        announced_before: u64
+    ) -> Result<Vec<Value>> {
+        self.check_opened()?;
+        Ok(self.values.values()
+            .filter(|e| e.persistent == persistent && e.updated <= announced_before)
+            .map(|e| e.value.clone())
+            .collect())
+    }
+
+    fn get_values_paginated(
+        &self,
+        offset: usize,
+        limit: usize
+    ) -> Result<Vec<Value>> {
+        self.check_opened()?;
+        Ok(self.sorted_values().into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|e| e.value.clone())
+            .collect())
+    }
+
+    fn update_value_announced_time(&mut self, id: &Id) -> Result<()> {
+        self.check_opened()?;
+        let now = self.now_ms();
+        if let Some(entry) = self.values.get_mut(id) {
+            entry.updated = now;
+        }
+        Ok(())
+    }
+
+    fn remove_value(&mut self, id: &Id) -> Result<()> {
+        self.check_opened()?;
+        self.values.remove(id);
+        Ok(())
+    }
+
+    // ── peers ────────────────────────────────────────────────────────────────
+
+    fn put_peer(&mut self, peer: PeerInfo, persistent: bool) -> Result<()> {
+        self.check_opened()?;
+        if !peer.is_valid() {
+            return Err(ArgumentError::new("peer signature validation failed"));
+        }
+        let key = (*peer.id(), peer.fingerprint());
+        if let Some(stored) = self.peers.get(&key) {
+            // Keep the newer announcement
+            if stored.peer.announcement_version() > peer.announcement_version() {
+                return Ok(());
+            }
+        }
+        self.peers.insert(key, PeerEntry {
+            peer,
+            persistent,
+            updated: self.now_ms(),
+        });
+        Ok(())
+    }
+
+    fn put_peers(&mut self, peers_in: Vec<PeerInfo>) -> Result<()> {
+        for peer in peers_in {
+            self.put_peer(peer, false)?;
+        }
+        Ok(())
+    }
+
+    fn get_peer(&self, id: &Id, fingerprint: u64) -> Result<Option<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.peers.get(&(*id, fingerprint)).map(|e| e.peer.clone()))
+    }
+
+    fn get_peer_updated(&self, id: &Id, fingerprint: u64) -> Result<Option<u64>> {
+        self.check_opened()?;
+        Ok(self.peers.get(&(*id, fingerprint)).map(|e| e.updated))
+    }
+
+    fn get_peers(&self, id: &Id) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.peers.values()
+            .filter(|e| e.peer.id() == id)
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn get_peers_with_expected_seq(&self, id: &Id, expected_seq: i32, limit: i32) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        let matched = self.peers.values()
+            .filter(|e| e.peer.id() == id && e.peer.sequence_number() >= expected_seq)
+            .collect::<Vec<_>>();
+        Ok(matched.sample(&mut rand::rng(), limit.max(0) as usize)
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn get_peers_authenticated_by(&self, id: &Id, node_id: &Id) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.peers.values()
+            .filter(|e| e.peer.id() == id && e.peer.nodeid() == Some(node_id))
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn get_peers_announced_before(&self,
+        persistent: bool,
+        announced_before: u64
+    ) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.peers.values()
+            .filter(|e| e.persistent == persistent && e.updated <= announced_before)
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn get_peers_paginated(&self,
+        offset: usize,
+        limit: usize
+    ) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.sorted_peers().into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn get_peers_paginated_and_announced_before(&self,
+        offset: usize,
+        limit: usize,
+        persistent: bool,
+        announced_before: u64
+    ) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.sorted_peers().into_iter()
+            .filter(|e| e.persistent == persistent && e.updated <= announced_before)
+            .skip(offset)
+            .take(limit)
+            .map(|e| e.peer.clone())
+            .collect())
+    }
+
+    fn count_peers(&self) -> Result<usize> {
+        self.check_opened()?;
+        Ok(self.peers.len())
+    }
+
+    fn count_peers_by_id(&self, id: &Id) -> Result<usize> {
+        self.check_opened()?;
+        Ok(self.peers.values().filter(|e| e.peer.id() == id).count())
+    }
+
+    fn peer_range_stats(&self, first: &Id, last: &Id) -> Result<KeyspaceStats> {
+        self.check_opened()?;
+        let entries = self.peers.values()
+            .filter(|e| e.peer.id() >= first && e.peer.id() <= last)
+            .collect::<Vec<_>>();
+        let publishers = entries.iter()
+            .filter_map(|e| e.peer.nodeid())
+            .collect::<HashSet<_>>();
+        Ok(KeyspaceStats::from(
+            entries.iter().map(|e| e.updated).collect(),
+            publishers.len(),
+            entries.iter().map(|e| {
+                (e.peer.endpoint().len() + e.peer.extra_data().map_or(0, |v| v.len())) as u64
+            }).sum(),
+        ))
+    }
+
+    fn get_peers_all(&self) -> Result<Vec<PeerInfo>> {
+        self.check_opened()?;
+        Ok(self.peers.values().map(|e| e.peer.clone()).collect())
+    }
+
+    fn update_peer_announced_time(&mut self, id: &Id, fingerprint: u64) -> Result<()> {
+        self.check_opened()?;
+        let now = self.now_ms();
+        if let Some(entry) = self.peers.get_mut(&(*id, fingerprint)) {
+            entry.updated = now;
+        }
+        Ok(())
+    }
+
+    fn remove_peer(&mut self, id: &Id, fingerprint: u64) -> Result<()> {
+        self.check_opened()?;
+        self.peers.remove(&(*id, fingerprint));
+        Ok(())
+    }
+
+    fn remove_peers(&mut self, id: &Id) -> Result<()> {
+        self.check_opened()?;
+        self.peers.retain(|(peer_id, _), _| peer_id != id);
+        Ok(())
+    }
+}
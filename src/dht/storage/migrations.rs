@@ -0,0 +1,77 @@
+use diesel::prelude::*;
+use diesel::connection::SimpleConnection;
+use log::info;
+
+// The steps taking a SQLite database from one schema version to the next,
+// shared by the storage of the node and the messaging repository. The
+// version is kept in the user_version pragma, 0 for a new database.
+
+// Upgrades a database older than `version`. The statements run first, then
+// the transform, which sees the schema they left and carries the existing
+// rows over to it.
+pub(crate) struct Migration<C: ?Sized = ()> {
+    pub(crate) version      : i32,
+    pub(crate) statements   : &'static [&'static str],
+    pub(crate) transform    : Option<fn(&mut SqliteConnection, &C) -> QueryResult<()>>,
+}
+
+#[derive(Debug)]
+pub(crate) enum MigrationError {
+    // Written by a newer build, which may have changed the meaning of
+    // the columns this one knows.
+    Newer(i32),
+    // The database stays at the version before `version`.
+    Failed { version: i32, error: diesel::result::Error },
+}
+
+#[derive(QueryableByName)]
+struct UserVersion {
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    user_version: i32,
+}
+
+pub(crate) fn user_version(conn: &mut SqliteConnection) -> QueryResult<i32> {
+    diesel::sql_query("PRAGMA user_version")
+        .load::<UserVersion>(conn)
+        .map(|rows| rows.first().map_or(0, |r| r.user_version))
+}
+
+// The version the migrations leave a database at.
+pub(crate) fn latest_version<C: ?Sized>(migrations: &[Migration<C>]) -> i32 {
+    migrations.last().map_or(0, |m| m.version)
+}
+
+// Applies the migrations newer than the database in order, each in a
+// transaction of its own together with the version it records, and returns
+// the version the database had before.
+pub(crate) fn migrate<C: ?Sized>(
+    conn: &mut SqliteConnection,
+    migrations: &[Migration<C>],
+    context: &C,
+) -> Result<i32, MigrationError> {
+    debug_assert!(migrations.windows(2).all(|w| w[0].version < w[1].version));
+
+    let found = user_version(conn)
+        .map_err(|error| MigrationError::Failed { version: 0, error })?;
+    if found > latest_version(migrations) {
+        return Err(MigrationError::Newer(found));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > found) {
+        conn.transaction(|conn| {
+            for stmt in migration.statements {
+                diesel::sql_query(*stmt).execute(conn)?;
+            }
+            if let Some(transform) = migration.transform {
+                transform(conn, context)?;
+            }
+            // Pragmas take no bound parameters.
+            conn.batch_execute(&format!("PRAGMA user_version = {}", migration.version))
+        }).map_err(|error| MigrationError::Failed { version: migration.version, error })?;
+    }
+
+    if found > 0 && found < latest_version(migrations) {
+        info!("Database upgraded from version {} to {}", found, latest_version(migrations));
+    }
+    Ok(found)
+}
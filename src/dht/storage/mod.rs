@@ -1,9 +1,12 @@
 pub(crate) mod data_storage;
 pub(crate) mod sqlite_storage;
+pub(crate) mod memory_storage;
 pub(crate) mod models;
+pub(crate) mod migrations;
 mod schema;
 mod sql;
 
+use crate::dht::storage::migrations::Migration;
 use crate::dht::storage::models::{
     Valore,
     NewValore,
@@ -16,6 +19,7 @@ use crate::dht::storage::schema::valores::{
     id              as val_id,
     persistent      as val_persistent,
     updated         as val_updated,
+    local           as val_local,
 };
 
 use crate::dht::storage::schema::peers::{
@@ -24,43 +28,145 @@ use crate::dht::storage::schema::peers::{
     fingerprint     as peer_fingerprint,
     persistent      as peer_persistent,
     updated         as peer_updated,
+    announced       as peer_announced,
     nodeId          as peer_node_id,
     sequenceNumber  as peer_seq,
 };
 
 use diesel::prelude::*;
+use diesel::connection::SimpleConnection;
 use diesel::result::Error;
 
+define_sql_function! {
+    fn random() -> BigInt;
+}
+
 #[derive(QueryableByName)]
-struct UserVersion {
+struct AutoVacuum {
     #[diesel(sql_type = diesel::sql_types::Integer)]
-    user_version: i32,
+    auto_vacuum: i32,
+}
+
+#[derive(QueryableByName)]
+struct PageCount {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    count: i64,
+}
+
+// Aggregates over a range of ids, see sql::VALUE_RANGE_STATS.
+#[derive(QueryableByName)]
+pub(crate) struct RangeStats {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub(crate) count: i64,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::BigInt>)]
+    pub(crate) oldest: Option<i64>,
+    #[diesel(sql_type = diesel::sql_types::Nullable<diesel::sql_types::BigInt>)]
+    pub(crate) newest: Option<i64>,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub(crate) publishers: i64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub(crate) bytes: i64,
+}
+
+#[derive(QueryableByName)]
+struct CheckResult {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    result: String,
+}
+
+// Switches the database to incremental auto vacuum, which takes a full
+// vacuum once for an existing database.
+fn enable_incremental_vacuum(conn: &mut SqliteConnection) -> bool {
+    const INCREMENTAL: i32 = 2;
+    let mode = diesel::sql_query(sql::GET_AUTO_VACUUM)
+        .load::<AutoVacuum>(conn)
+        .map(|rows| rows.first().map_or(0, |r| r.auto_vacuum))
+        .unwrap_or(0);
+
+    mode == INCREMENTAL || (
+        diesel::sql_query(sql::SET_AUTO_VACUUM_INCREMENTAL).execute(conn).is_ok() &&
+        diesel::sql_query(sql::VACUUM).execute(conn).is_ok()
+    )
 }
 
-fn user_version(conn: &mut SqliteConnection) -> i32 {
-    diesel::sql_query(sql::GET_USER_VERSION)
-        .load::<UserVersion>(conn)
-        .map(|rows| rows.first().map_or(0, |r| r.user_version))
-        .unwrap_or(0)
+fn freelist_count(conn: &mut SqliteConnection) -> Result<i64, Error> {
+    diesel::sql_query(sql::GET_FREELIST_COUNT)
+        .load::<PageCount>(conn)
+        .map(|rows| rows.first().map_or(0, |r| r.count))
 }
 
-fn drop_tbs(conn: &mut SqliteConnection) -> bool {
-    diesel::sql_query(sql::DROP_VALUES_TABLE).execute(conn).is_ok()     &&
-    diesel::sql_query(sql::DROP_VALUES_INDEX).execute(conn).is_ok()     &&
-    diesel::sql_query(sql::DROP_PEERS_TABLE).execute(conn).is_ok()      &&
-    diesel::sql_query(sql::DROP_PEERS_INDEX).execute(conn).is_ok()      &&
-    diesel::sql_query(sql::DROP_PEERS_ID_INDEX).execute(conn).is_ok()
+// Frees all unused pages and refreshes the planner statistics, returns the
+// number of pages returned to the file system.
+fn vacuum_and_optimize(conn: &mut SqliteConnection) -> Result<i64, Error> {
+    let before = freelist_count(conn)?;
+    // The pragma frees one page per step, batch execution runs it to the end.
+    conn.batch_execute(sql::INCREMENTAL_VACUUM)?;
+    conn.batch_execute(sql::OPTIMIZE)?;
+    Ok(before - freelist_count(conn)?)
 }
 
-fn create_tbs(conn: &mut SqliteConnection) -> bool {
-    diesel::sql_query(sql::SET_USER_VERSION).execute(conn).is_ok()      &&
-    diesel::sql_query(sql::CREATE_VALUES_TABLE).execute(conn).is_ok()   &&
-    diesel::sql_query(sql::CREATE_VALUES_INDEX).execute(conn).is_ok()   &&
-    diesel::sql_query(sql::CREATE_PEERS_TABLE).execute(conn).is_ok()    &&
-    diesel::sql_query(sql::CREATE_PEERS_INDEX).execute(conn).is_ok()    &&
-    diesel::sql_query(sql::CREATE_PEERS_ID_INDEX).execute(conn).is_ok()
+// Problems found by sqlite, empty if the database is fine.
+fn integrity_errors(conn: &mut SqliteConnection, quick: bool) -> Result<Vec<String>, Error> {
+    let query = if quick { sql::QUICK_CHECK } else { sql::INTEGRITY_CHECK };
+    diesel::sql_query(query)
+        .load::<CheckResult>(conn)
+        .map(|rows| rows.into_iter()
+            .map(|r| r.result)
+            .filter(|r| r != "ok")
+            .collect())
 }
 
+// The tables of databases older than version 5 are dropped, their layout
+// is not carried over.
+pub(crate) const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 5,
+        statements: &[
+            sql::DROP_VALUES_TABLE,
+            sql::DROP_VALUES_INDEX,
+            sql::DROP_PEERS_TABLE,
+            sql::DROP_PEERS_INDEX,
+            sql::DROP_PEERS_ID_INDEX,
+            sql::CREATE_VALUES_TABLE,
+            sql::CREATE_VALUES_INDEX,
+            sql::CREATE_PEERS_TABLE,
+            sql::CREATE_PEERS_INDEX,
+            sql::CREATE_PEERS_ID_INDEX,
+        ],
+        transform: None,
+    },
+    Migration {
+        version: 6,
+        statements: &[sql::ADD_PEERS_ANNOUNCED],
+        transform: None,
+    },
+    Migration {
+        version: 7,
+        statements: &[sql::ADD_PEERS_TAGS],
+        transform: None,
+    },
+    Migration {
+        version: 8,
+        statements: &[sql::ADD_VALUES_COUNTERSIGNER, sql::ADD_VALUES_COUNTERSIGNATURE],
+        transform: None,
+    },
+    Migration {
+        version: 9,
+        statements: &[sql::ADD_VALUES_LOCAL],
+        transform: None,
+    },
+    Migration {
+        version: 10,
+        statements: &[sql::ADD_PEERS_WEIGHT],
+        transform: None,
+    },
+    Migration {
+        version: 11,
+        statements: &[sql::ADD_PEERS_OBSERVED],
+        transform: None,
+    },
+];
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Value queries
 // ─────────────────────────────────────────────────────────────────────────────
@@ -88,6 +194,47 @@ pub(crate) fn get_value(
         .and_then(|mut v| Ok(v.pop()))
 }
 
+// SELECT local FROM valores WHERE id = ?
+pub(crate) fn is_local_value(
+    conn: &mut SqliteConnection,
+    id: &[u8],
+) -> Result<bool, Error> {
+    valores.find(id)
+        .select(val_local)
+        .first::<bool>(conn)
+        .optional()
+        .map(|v| v.unwrap_or(false))
+}
+
+// SELECT COUNT(*) FROM valores
+pub(crate) fn count_values(
+    conn: &mut SqliteConnection,
+) -> Result<i64, Error> {
+    valores.count().get_result(conn)
+}
+
+// SELECT COUNT(*), MIN(updated), ... FROM valores WHERE id BETWEEN ? AND ?
+// together with the middle one or two updated times of the range.
+pub(crate) fn value_range_stats(
+    conn: &mut SqliteConnection,
+    first: &[u8],
+    last: &[u8],
+) -> Result<(RangeStats, Vec<i64>), Error> {
+    let stats = diesel::sql_query(sql::VALUE_RANGE_STATS)
+        .bind::<diesel::sql_types::Binary, _>(first)
+        .bind::<diesel::sql_types::Binary, _>(last)
+        .get_result::<RangeStats>(conn)?;
+
+    let middle = valores
+        .filter(val_id.between(first, last))
+        .order(val_updated)
+        .select(val_updated)
+        .offset((stats.count - 1) / 2)
+        .limit(2 - stats.count % 2)
+        .load::<i64>(conn)?;
+    Ok((stats, middle))
+}
+
 // SELECT * FROM valores
 #[allow(unused)]
 pub(crate) fn get_values(
@@ -144,31 +291,48 @@ pub(crate) fn remove_value(
         .and_then(|deleted| Ok(deleted > 0))
 }
 
+// Removes the expired values, returns the ids of those removed.
 pub(crate) fn remove_expired_values(
     conn: &mut SqliteConnection,
     expired_before: i64,
-) -> Result<bool, Error> {
-    diesel::delete(
-        valores
+) -> Result<Vec<Vec<u8>>, Error> {
+    conn.transaction(|conn| {
+        let expired = valores
             .filter(val_persistent.eq(false))
-            .filter(val_updated.le(expired_before))
-    )
-    .execute(conn)
-    .and_then(|deleted| Ok(deleted > 0))
+            .filter(val_updated.le(expired_before));
+        let ids = expired.select(val_id).load::<Vec<u8>>(conn)?;
+        diesel::delete(expired).execute(conn)?;
+        Ok(ids)
+    })
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
 // Peer queries
 // ─────────────────────────────────────────────────────────────────────────────
+// INSERT OR REPLACE INTO peers(...) unless a newer announcement is stored,
+// by sequence number then signed announced time.
 pub(crate) fn put_peer(
     conn: &mut SqliteConnection,
     peer: NewPeer,
 ) -> Result<bool, Error> {
     use crate::dht::storage::schema::peers;
-    diesel::replace_into(peers::table)
-        .values(&peer)
-        .execute(conn)
-        .and_then(|num| Ok(num > 0))
+    conn.transaction(|conn| {
+        let stored = peers
+            .filter(peer_id.eq(peer.id))
+            .filter(peer_fingerprint.eq(peer.fingerprint))
+            .select((peer_seq, peer_announced))
+            .first::<(i32, Option<i64>)>(conn)
+            .optional()?;
+        let version = (peer.sequenceNumber, peer.announced.unwrap_or(0));
+        if stored.is_some_and(|(seq, announced)| (seq, announced.unwrap_or(0)) > version) {
+            return Ok(false);
+        }
+
+        diesel::replace_into(peers::table)
+            .values(&peer)
+            .execute(conn)
+            .and_then(|num| Ok(num > 0))
+    })
 }
 
 // INSERT OR REPLACE INTO peers(...)
@@ -209,7 +373,7 @@ pub(crate) fn get_peers_by_id(
         .load(conn)
 }
 
-// SELECT * FROM peers WHERE id = ? AND sequenceNumber >= ? LIMIT ?
+// SELECT * FROM peers WHERE id = ? AND sequenceNumber >= ? ORDER BY RANDOM() LIMIT ?
 pub(crate) fn get_peers_with_expected_seq(
     conn: &mut SqliteConnection,
     id: &[u8],
@@ -219,6 +383,7 @@ pub(crate) fn get_peers_with_expected_seq(
     peers
         .filter(peer_id.eq(id))
         .filter(peer_seq.ge(expected_seq))
+        .order(random())
         .limit(limit)
         .select(Peer::as_select())
         .load(conn)
@@ -281,6 +446,46 @@ pub(crate) fn get_peers_paginated_and_announced_before(
         .load(conn)
 }
 
+// SELECT COUNT(*) FROM peers
+pub(crate) fn count_peers(
+    conn: &mut SqliteConnection,
+) -> Result<i64, Error> {
+    peers.count().get_result(conn)
+}
+
+// SELECT COUNT(*) FROM peers WHERE id = ?
+pub(crate) fn count_peers_by_id(
+    conn: &mut SqliteConnection,
+    id: &[u8],
+) -> Result<i64, Error> {
+    peers
+        .filter(peer_id.eq(id))
+        .count()
+        .get_result(conn)
+}
+
+// SELECT COUNT(*), MIN(updated), ... FROM peers WHERE id BETWEEN ? AND ?
+// together with the middle one or two updated times of the range.
+pub(crate) fn peer_range_stats(
+    conn: &mut SqliteConnection,
+    first: &[u8],
+    last: &[u8],
+) -> Result<(RangeStats, Vec<i64>), Error> {
+    let stats = diesel::sql_query(sql::PEER_RANGE_STATS)
+        .bind::<diesel::sql_types::Binary, _>(first)
+        .bind::<diesel::sql_types::Binary, _>(last)
+        .get_result::<RangeStats>(conn)?;
+
+    let middle = peers
+        .filter(peer_id.between(first, last))
+        .order(peer_updated)
+        .select(peer_updated)
+        .offset((stats.count - 1) / 2)
+        .limit(2 - stats.count % 2)
+        .load::<i64>(conn)?;
+    Ok((stats, middle))
+}
+
 // SELECT * FROM peers
 #[allow(unused)]
 pub(crate) fn get_peers_all(
@@ -331,15 +536,21 @@ pub(crate) fn remove_peers_by_id(
         .and_then(|deleted| Ok(deleted > 0))
 }
 
+// Peers expire by the time their publisher announced them at, the time
+// they were stored for announcements without it.
+// Removes the expired peers, returns the ids and fingerprints of those removed.
 pub(crate) fn remove_expired_peers(
     conn: &mut SqliteConnection,
     expired_before: i64,
-) -> Result<bool, Error> {
-    diesel::delete(
-        peers
+) -> Result<Vec<(Vec<u8>, i64)>, Error> {
+    conn.transaction(|conn| {
+        let expired = peers
             .filter(peer_persistent.eq(false))
-            .filter(peer_updated.le(expired_before))
-    )
-    .execute(conn)
-    .and_then(|deleted| Ok(deleted > 0))
+            .filter(peer_announced.le(expired_before).or(
+                peer_announced.is_null().and(peer_updated.le(expired_before))
+            ));
+        let keys = expired.select((peer_id, peer_fingerprint)).load::<(Vec<u8>, i64)>(conn)?;
+        diesel::delete(expired).execute(conn)?;
+        Ok(keys)
+    })
 }
@@ -20,6 +20,9 @@ pub(crate) struct Valore {
     pub(crate) data:           Vec<u8>,
     pub(crate) persistent:     bool,
     pub(crate) updated:        i64,
+    pub(crate) countersigner:  Option<Vec<u8>>,
+    pub(crate) countersignature: Option<Vec<u8>>,
+    pub(crate) local:          bool,
 }
 
 #[allow(non_snake_case)]
@@ -36,6 +39,9 @@ pub(crate) struct NewValore<'a> {
     pub(crate) sequenceNumber: i32,
     pub(crate) persistent:     bool,
     pub(crate) updated:        i64,
+    pub(crate) countersigner:  Option<&'a [u8]>,
+    pub(crate) countersignature: Option<&'a [u8]>,
+    pub(crate) local:          bool,
 }
 
 #[allow(non_snake_case)]
@@ -56,6 +62,10 @@ pub(crate) struct Peer {
     pub(crate) extra:         Option<Vec<u8>>,
     pub(crate) persistent:    bool,
     pub(crate) updated:       i64,
+    pub(crate) announced:     Option<i64>,
+    pub(crate) tags:          Option<Vec<u8>>,
+    pub(crate) weight:        i32,
+    pub(crate) observed:      Option<String>,
 }
 
 #[allow(non_snake_case)]
@@ -74,4 +84,8 @@ pub(crate) struct NewPeer<'a> {
     pub(crate) extra:          Option<&'a [u8]>,
     pub(crate) persistent:     bool,
     pub(crate) updated:        i64,
+    pub(crate) announced:      Option<i64>,
+    pub(crate) tags:           Option<&'a [u8]>,
+    pub(crate) weight:         i32,
+    pub(crate) observed:       Option<&'a str>,
 }
@@ -10,6 +10,9 @@ diesel::table! {
         data -> Binary,
         persistent -> Bool,
         updated -> BigInt,
+        countersigner -> Nullable<Binary>,
+        countersignature -> Nullable<Binary>,
+        local -> Bool,
     }
 }
 
@@ -27,5 +30,9 @@ diesel::table! {
         extra -> Nullable<Binary>,
         persistent -> Bool,
         updated -> BigInt,
+        announced -> Nullable<BigInt>,
+        tags -> Nullable<Binary>,
+        weight -> Integer,
+        observed -> Nullable<Text>,
     }
 }
@@ -1,7 +1,26 @@
-// pub(crate) const CURRENT_VERSION: i32 = 5;
-pub(crate) const SET_USER_VERSION: &str = "PRAGMA user_version = 5";
-pub(crate) const GET_USER_VERSION: &str = "PRAGMA user_version";
+pub(crate) const GET_AUTO_VACUUM: &str = "PRAGMA auto_vacuum";
+pub(crate) const SET_AUTO_VACUUM_INCREMENTAL: &str = "PRAGMA auto_vacuum = INCREMENTAL";
+pub(crate) const VACUUM: &str = "VACUUM";
+pub(crate) const INCREMENTAL_VACUUM: &str = "PRAGMA incremental_vacuum";
+pub(crate) const OPTIMIZE: &str = "PRAGMA optimize";
+pub(crate) const GET_FREELIST_COUNT: &str = "SELECT freelist_count AS count FROM pragma_freelist_count";
+pub(crate) const INTEGRITY_CHECK: &str = "SELECT integrity_check AS result FROM pragma_integrity_check";
+pub(crate) const QUICK_CHECK: &str = "SELECT quick_check AS result FROM pragma_quick_check";
 
+pub(crate) const VALUE_RANGE_STATS: &str = "
+        SELECT COUNT(*) AS count, MIN(updated) AS oldest, MAX(updated) AS newest, \
+        COUNT(DISTINCT publicKey) AS publishers, \
+        COALESCE(SUM(LENGTH(data)), 0) AS bytes \
+        FROM valores WHERE id BETWEEN ? AND ?";
+
+pub(crate) const PEER_RANGE_STATS: &str = "
+        SELECT COUNT(*) AS count, MIN(updated) AS oldest, MAX(updated) AS newest, \
+        COUNT(DISTINCT nodeId) AS publishers, \
+        COALESCE(SUM(LENGTH(CAST(endpoint AS BLOB)) + COALESCE(LENGTH(extra), 0)), 0) AS bytes \
+        FROM peers WHERE id BETWEEN ? AND ?";
+
+// The tables as version 5 created them, the later columns are added by the
+// migrations.
 pub(crate) const CREATE_VALUES_TABLE: &str = "
         CREATE TABLE IF NOT EXISTS valores(\
         id BLOB NOT NULL PRIMARY KEY, \
@@ -39,6 +58,40 @@ pub(crate) const CREATE_PEERS_TABLE: &str = "
         ) WITHOUT ROWID
     ";
 
+// Version 5 databases lack the time peers were announced at.
+pub(crate) const ADD_PEERS_ANNOUNCED: &str = "
+        ALTER TABLE peers ADD COLUMN announced INTEGER
+    ";
+
+// Version 6 databases lack the capability tags of peers.
+pub(crate) const ADD_PEERS_TAGS: &str = "
+        ALTER TABLE peers ADD COLUMN tags BLOB
+    ";
+
+// Version 7 databases lack the counter-signatures of values.
+pub(crate) const ADD_VALUES_COUNTERSIGNER: &str = "
+        ALTER TABLE valores ADD COLUMN countersigner BLOB
+    ";
+
+pub(crate) const ADD_VALUES_COUNTERSIGNATURE: &str = "
+        ALTER TABLE valores ADD COLUMN countersignature BLOB
+    ";
+
+// Version 8 databases do not tell the values put by the local node.
+pub(crate) const ADD_VALUES_LOCAL: &str = "
+        ALTER TABLE valores ADD COLUMN local BOOLEAN NOT NULL DEFAULT FALSE
+    ";
+
+// Version 9 databases lack the weights of peers.
+pub(crate) const ADD_PEERS_WEIGHT: &str = "
+        ALTER TABLE peers ADD COLUMN weight INTEGER NOT NULL DEFAULT 1
+    ";
+
+// Version 10 databases lack the endpoints peers were observed at.
+pub(crate) const ADD_PEERS_OBSERVED: &str = "
+        ALTER TABLE peers ADD COLUMN observed TEXT
+    ";
+
 pub(crate) const CREATE_PEERS_INDEX: &str = "
         CREATE INDEX IF NOT EXISTS idx_peers_updated ON peers(updated)
     ";
@@ -1,27 +1,36 @@
 use std::cell::UnsafeCell;
-use std::time::{Duration, SystemTime};
+use std::sync::Arc;
+use std::time::Duration;
 use diesel::prelude::*;
 use log::warn;
 
 use crate::{
-    as_ms,
     Id,
+    Clock,
+    SystemClock,
     Error,
     PeerInfo,
     Value,
     Result,
-    errors::{StateError, ArgumentError},
+    errors::{StateError, ArgumentError, MalformedError, UnsupportedVersionError},
 };
 use crate::core::cryptobox::Nonce;
+use crate::core::signature::PrivateKey;
+use crate::core::peer_info::PeerFields;
 use crate::dht::storage::{
-    user_version,
-    drop_tbs,
-    create_tbs,
+    MIGRATIONS,
+    migrations::{self, MigrationError},
+    enable_incremental_vacuum,
+    vacuum_and_optimize,
+    integrity_errors,
     put_value,
     get_value,
+    is_local_value,
     get_values,
+    count_values,
+    value_range_stats,
     get_values_announced_before,
-    //get_values_paginated,
+    get_values_paginated,
     update_value_announced_time,
     remove_value,
     remove_expired_values,
@@ -31,40 +40,97 @@ use crate::dht::storage::{
     get_peers_with_expected_seq,
     get_peers_authenticated_by,
     get_peers_announced_before,
-    //get_peers_paginated,
+    get_peers_paginated,
     //get_peers_paginated_and_announced_before,
     get_peers_all,
+    count_peers,
+    count_peers_by_id,
+    peer_range_stats,
     update_peer_announced_time,
     remove_peer,
     remove_peers_by_id,
     remove_expired_peers,
 
-    data_storage::DataStorage,
+    RangeStats,
+    data_storage::{DataStorage, Expired, IntegrityReport, SPOT_CHECK_SAMPLES},
     models::{Valore, NewValore, Peer as DbPeer, NewPeer}
 };
+use crate::dht::stats::KeyspaceStats;
 
 fn db_err(e: impl std::fmt::Display) -> Error {
     StateError::new(e.to_string())
 }
 
+// Whether SQLite found the file corrupt (SQLITE_CORRUPT) or not a database
+// at all (SQLITE_NOTADB). Diesel keeps only the message of the error code.
+fn is_corrupt(e: &diesel::result::Error) -> bool {
+    let diesel::result::Error::DatabaseError(_, info) = e else {
+        return false;
+    };
+    ["database disk image is malformed", "not a database"].iter()
+        .any(|msg| info.message().contains(msg))
+}
+
+fn keyspace_stats((stats, middle): (RangeStats, Vec<i64>)) -> KeyspaceStats {
+    let middle = middle.into_iter().map(|v| v as u64).collect::<Vec<_>>();
+    KeyspaceStats {
+        count       : stats.count as usize,
+        oldest      : stats.oldest.map(|v| v as u64),
+        newest      : stats.newest.map(|v| v as u64),
+        median      : KeyspaceStats::median_of(&middle),
+        publishers  : stats.publishers as usize,
+        bytes       : stats.bytes as u64,
+    }
+}
+
 pub(crate) struct SqliteStorage {
     connection: UnsafeCell<Option<SqliteConnection>>,
     value_expiry: Duration,
     peer_expiry: Duration,
+    clock: Arc<dyn Clock>,
 }
 
 impl SqliteStorage {
+    #[allow(unused)]
     pub(crate) fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    pub(crate) fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             connection: UnsafeCell::new(None),
             value_expiry: Duration::MAX,
             peer_expiry:  Duration::MAX,
+            clock,
         }
     }
 
     fn conn(&self) -> &mut SqliteConnection {
         unsafe { (*self.connection.get()).as_mut().unwrap() }
     }
+
+    fn insert_value(&mut self, value: Value, persistent: bool, local: bool) -> Result<()> {
+        let now = self.clock.now_ms() as i64;
+        let value_id = value.id();
+        let v = NewValore {
+            id:             value_id.as_bytes(),
+            publicKey:      value.public_key().map(|pk| pk.as_bytes()),
+            privateKey:     value.private_key().map(|sk| sk.as_bytes()),
+            recipient:      value.recipient().map(|r| r.as_bytes()),
+            nonce:          value.nonce().map(|n| n.as_bytes()),
+            signature:      value.signature(),
+            data:           value.data(),
+            sequenceNumber: value.sequence_number(),
+            persistent,
+            updated:        now,
+            countersigner:  value.countersigner().map(|s| s.as_bytes()),
+            countersignature: value.countersignature(),
+            local,
+        };
+        put_value(self.conn(), v)
+            .map(|_| ())
+            .map_err(db_err)
+    }
 }
 
 impl Drop for SqliteStorage {
@@ -84,11 +150,83 @@ fn valore_to_value(v: Valore) -> Value {
         v.signature,
         v.data,
         v.sequenceNumber,
+    ).with_countersignature(
+        v.countersigner.as_ref().map(|s| Id::try_from(s.as_slice()).unwrap()),
+        v.countersignature,
     )
 }
 
+// Like valore_to_value, but tolerates rows with malformed keys.
+fn checked_value(v: Valore) -> Option<Value> {
+    let pk = match v.publicKey.as_deref() {
+        Some(pk) => Some(Id::try_from(pk).ok()?),
+        None => None,
+    };
+    let recipient = match v.recipient.as_deref() {
+        Some(r) => Some(Id::try_from(r).ok()?),
+        None => None,
+    };
+    let nonce = match v.nonce.as_deref() {
+        Some(n) => Some(Nonce::try_from(n).ok()?),
+        None => None,
+    };
+    let countersigner = match v.countersigner.as_deref() {
+        Some(s) => Some(Id::try_from(s).ok()?),
+        None => None,
+    };
+    let value = Value::packed(pk, recipient, nonce, v.signature, v.data, v.sequenceNumber)
+        .with_countersignature(countersigner, v.countersignature);
+    (value.id().as_bytes() == v.id.as_slice() && value.is_valid()).then_some(value)
+}
+
+fn checked_peer(p: DbPeer) -> Option<PeerInfo> {
+    let node_id = match p.nodeId.as_deref() {
+        Some(n) => Some(Id::try_from(n).ok()?),
+        None => None,
+    };
+    let peer = PeerInfo::packed(
+        Id::try_from(p.id.as_slice()).ok()?,
+        p.nonce,
+        p.sequenceNumber,
+        node_id,
+        p.nodeSignature,
+        p.signature,
+        p.fingerprint as u64,
+        p.endpoint,
+        PeerFields {
+            extra: p.extra,
+            tags: decode_tags(p.tags.as_deref())?,
+            weight: u8::try_from(p.weight).ok()?,
+            announced: p.announced.map(|v| v as u64),
+        },
+    ).with_observed(p.observed);
+    peer.is_valid().then_some(peer)
+}
+
+// The tags column holds the CBOR array of the tags, NULL without any.
+fn encode_tags(tags: &[String]) -> Option<Vec<u8>> {
+    (!tags.is_empty()).then(|| serde_cbor::to_vec(&tags).unwrap())
+}
+
+fn decode_tags(data: Option<&[u8]>) -> Option<Vec<String>> {
+    match data {
+        Some(v) => serde_cbor::from_slice(v).ok(),
+        None => Some(Vec::new()),
+    }
+}
+
+// Where a window of `SPOT_CHECK_SAMPLES` rows out of `total` starts.
+fn sample_offset(total: i64) -> usize {
+    match total as usize {
+        n if n > SPOT_CHECK_SAMPLES => (rand::random::<u64>() % (n - SPOT_CHECK_SAMPLES + 1) as u64) as usize,
+        _ => 0,
+    }
+}
+
+// The private key comes back with the peers this node owns, to sign them
+// again when they are re-announced.
 fn db_peer_to_info(p: DbPeer) -> PeerInfo {
-    PeerInfo::packed(
+    let mut peer = PeerInfo::packed(
         Id::try_from(p.id.as_slice()).unwrap(),
         p.nonce,
         p.sequenceNumber,
@@ -97,24 +235,42 @@ fn db_peer_to_info(p: DbPeer) -> PeerInfo {
         p.signature,
         p.fingerprint as u64,
         p.endpoint,
-        p.extra,
-    )
+        PeerFields {
+            extra: p.extra,
+            tags: decode_tags(p.tags.as_deref()).unwrap_or_default(),
+            weight: p.weight as u8,
+            announced: p.announced.map(|v| v as u64),
+        },
+    ).with_observed(p.observed);
+    if let Some(sk) = p.privateKey.and_then(|v| PrivateKey::try_from(v.as_slice()).ok()) {
+        let _ = peer.set_private_key(sk);
+    }
+    peer
 }
 
 impl DataStorage for SqliteStorage {
     fn open(&mut self, path: &str) -> Result<()> {
-        let conn = SqliteConnection::establish(path)
+        let mut conn = SqliteConnection::establish(path)
             .map_err(|e| StateError::new(format!("Failed to open SQLite at '{}': {}", path, e)))?;
 
-        unsafe { *self.connection.get() = Some(conn); }
-
-        let ver = user_version(self.conn());
-        if ver < 5 && !drop_tbs(self.conn()) {
-            return Err(StateError::new("Failed to drop old db tables"));
-        }
-        if !create_tbs(self.conn()) {
-            return Err(StateError::new("Failed to create db tables"));
+        // Left unopened unless the schema is one this build reads.
+        migrations::migrate(&mut conn, MIGRATIONS, &()).map_err(|e| match e {
+            MigrationError::Newer(version) => UnsupportedVersionError::new(version as u32, format!(
+                "Database '{}' is at version {}, newer than the supported {}",
+                path, version, migrations::latest_version(MIGRATIONS)
+            )) as Error,
+            MigrationError::Failed { error, .. } if is_corrupt(&error) => MalformedError::new(format!(
+                "Database '{}' is corrupt: {}", path, error
+            )) as Error,
+            MigrationError::Failed { version, error } => StateError::new(format!(
+                "Failed to upgrade database '{}' to version {}: {}", path, version, error
+            )),
+        })?;
+        if !enable_incremental_vacuum(&mut conn) {
+            warn!("Failed to enable incremental vacuum on '{}'", path);
         }
+
+        unsafe { *self.connection.get() = Some(conn); }
         Ok(())
     }
 
@@ -131,37 +287,80 @@ impl DataStorage for SqliteStorage {
         unsafe { *self.connection.get() = None; }
     }
 
-    fn purge(&mut self) {
-        let now          = as_ms!(SystemTime::now()) as i64;
+    fn purge(&mut self) -> Expired {
+        let now          = self.clock.now_ms() as i64;
         let value_cutoff = now - self.value_expiry.as_millis() as i64;
         let peer_cutoff  = now - self.peer_expiry.as_millis() as i64;
 
-        let _ = remove_expired_values(self.conn(), value_cutoff)
-            .map_err(|e| warn!("Purging expired values failed: {}", e));
+        let values = remove_expired_values(self.conn(), value_cutoff)
+            .map_err(|e| warn!("Purging expired values failed: {}", e))
+            .unwrap_or_default();
+
+        let peers = remove_expired_peers(self.conn(), peer_cutoff)
+            .map_err(|e| warn!("Purging expired peers failed: {}", e))
+            .unwrap_or_default();
+
+        Expired {
+            values: values.iter()
+                .filter_map(|id| Id::try_from(id.as_slice()).ok())
+                .collect(),
+            peers : peers.iter()
+                .filter_map(|(id, fp)| Id::try_from(id.as_slice()).ok().map(|id| (id, *fp as u64)))
+                .collect(),
+        }
+    }
+
+    fn maintain(&mut self) -> Result<u64> {
+        vacuum_and_optimize(self.conn())
+            .map(|freed| freed.max(0) as u64)
+            .map_err(db_err)
+    }
 
-        let _ = remove_expired_peers(self.conn(), peer_cutoff)
-            .map_err(|e| warn!("Purging expired peers failed: {}", e));
+    fn check_integrity(&self, quick: bool) -> Result<IntegrityReport> {
+        let errors = integrity_errors(self.conn(), quick).map_err(|e| match is_corrupt(&e) {
+            true => MalformedError::new(e.to_string()) as Error,
+            false => db_err(e),
+        })?;
+        let mut report = IntegrityReport {
+            errors,
+            ..Default::default()
+        };
+        if quick || !report.errors.is_empty() {
+            return Ok(report);
+        }
+
+        let offset = sample_offset(count_values(self.conn()).map_err(db_err)?);
+        for v in get_values_paginated(self.conn(), offset, SPOT_CHECK_SAMPLES).map_err(db_err)? {
+            report.values_checked += 1;
+            let id = Id::try_from(v.id.as_slice()).unwrap_or_default();
+            if checked_value(v).is_none() {
+                report.invalid_values.push(id);
+            }
+        }
+
+        let offset = sample_offset(count_peers(self.conn()).map_err(db_err)?);
+        for p in get_peers_paginated(self.conn(), offset, SPOT_CHECK_SAMPLES).map_err(db_err)? {
+            report.peers_checked += 1;
+            let id = Id::try_from(p.id.as_slice()).unwrap_or_default();
+            if checked_peer(p).is_none() {
+                report.invalid_peers.push(id);
+            }
+        }
+        Ok(report)
     }
 
     // ── values ────
     fn put_value(&mut self, value: Value, persistent: bool) -> Result<()> {
-        let now = as_ms!(SystemTime::now()) as i64;
-        let value_id = value.id();
-        let v = NewValore {
-            id:             value_id.as_bytes(),
-            publicKey:      value.public_key().map(|pk| pk.as_bytes()),
-            privateKey:     value.private_key().map(|sk| sk.as_bytes()),
-            recipient:      value.recipient().map(|r| r.as_bytes()),
-            nonce:          value.nonce().map(|n| n.as_bytes()),
-            signature:      value.signature(),
-            data:           value.data(),
-            sequenceNumber: value.sequence_number(),
-            persistent,
-            updated:        now,
-        };
-        put_value(self.conn(), v)
-            .map(|_| ())
-            .map_err(db_err)
+        let local = is_local_value(self.conn(), value.id().as_bytes()).map_err(db_err)?;
+        self.insert_value(value, persistent, local)
+    }
+
+    fn put_local_value(&mut self, value: Value, persistent: bool) -> Result<()> {
+        self.insert_value(value, persistent, true)
+    }
+
+    fn is_local_value(&self, id: &Id) -> Result<bool> {
+        is_local_value(self.conn(), id.as_bytes()).map_err(db_err)
     }
 
     fn get_value(&self, id: &Id) -> Result<Option<Value>> {
@@ -170,6 +369,24 @@ impl DataStorage for SqliteStorage {
             .map_err(db_err)
     }
 
+    fn get_value_updated(&self, id: &Id) -> Result<Option<u64>> {
+        get_value(self.conn(), id.as_bytes())
+            .map(|opt| opt.map(|v| v.updated as u64))
+            .map_err(db_err)
+    }
+
+    fn count_values(&self) -> Result<usize> {
+        count_values(self.conn())
+            .map(|n| n as usize)
+            .map_err(db_err)
+    }
+
+    fn value_range_stats(&self, first: &Id, last: &Id) -> Result<KeyspaceStats> {
+        value_range_stats(self.conn(), first.as_bytes(), last.as_bytes())
+            .map(keyspace_stats)
+            .map_err(db_err)
+    }
+
     fn get_values(&self) -> Result<Vec<Value>> {
         get_values(self.conn())
             .map(|vs| vs.into_iter().map(valore_to_value).collect())
@@ -195,7 +412,7 @@ impl DataStorage for SqliteStorage {
     }
 
     fn update_value_announced_time(&mut self, id: &Id) -> Result<()> {
-        let now = as_ms!(SystemTime::now()) as i64;
+        let now = self.clock.now_ms() as i64;
         update_value_announced_time(self.conn(), id.as_bytes(), now)
             .map(|_| ())
             .map_err(db_err)
@@ -213,7 +430,8 @@ impl DataStorage for SqliteStorage {
         if !peer.is_valid() {
             return Err(ArgumentError::new("peer signature validation failed"));
         }
-        let now = as_ms!(SystemTime::now()) as i64;
+        let now = self.clock.now_ms() as i64;
+        let tags = encode_tags(peer.tags());
         let p = NewPeer {
             id:             peer.id().as_bytes(),
             fingerprint:    peer.fingerprint() as i64,
@@ -227,6 +445,10 @@ impl DataStorage for SqliteStorage {
             extra:          peer.extra_data(),
             persistent,
             updated:        now,
+            announced:      peer.announced().map(|v| v as i64),
+            tags:           tags.as_deref(),
+            weight:         peer.weight() as i32,
+            observed:       peer.observed_endpoint(),
         };
         put_peer(self.conn(), p)
             .map(|_| ())
@@ -246,6 +468,12 @@ impl DataStorage for SqliteStorage {
             .map_err(db_err)
     }
 
+    fn get_peer_updated(&self, id: &Id, fingerprint: u64) -> Result<Option<u64>> {
+        get_peer(self.conn(), id.as_bytes(), fingerprint as i64)
+            .map(|opt| opt.map(|p| p.updated as u64))
+            .map_err(db_err)
+    }
+
     fn get_peers(&self, id: &Id) -> Result<Vec<PeerInfo>> {
         get_peers_by_id(self.conn(), id.as_bytes())
             .map(|ps| ps.into_iter().map(db_peer_to_info).collect())
@@ -289,6 +517,24 @@ impl DataStorage for SqliteStorage {
         unimplemented!()
     }
 
+    fn count_peers(&self) -> Result<usize> {
+        count_peers(self.conn())
+            .map(|n| n as usize)
+            .map_err(db_err)
+    }
+
+    fn count_peers_by_id(&self, id: &Id) -> Result<usize> {
+        count_peers_by_id(self.conn(), id.as_bytes())
+            .map(|n| n as usize)
+            .map_err(db_err)
+    }
+
+    fn peer_range_stats(&self, first: &Id, last: &Id) -> Result<KeyspaceStats> {
+        peer_range_stats(self.conn(), first.as_bytes(), last.as_bytes())
+            .map(keyspace_stats)
+            .map_err(db_err)
+    }
+
     fn get_peers_all(&self) -> Result<Vec<PeerInfo>> {
         get_peers_all(self.conn())
             .map(|ps| ps.into_iter().map(db_peer_to_info).collect())
@@ -296,7 +542,7 @@ impl DataStorage for SqliteStorage {
     }
 
     fn update_peer_announced_time(&mut self, id: &Id, fingerprint: u64) -> Result<()> {
-        let now = as_ms!(SystemTime::now()) as i64;
+        let now = self.clock.now_ms() as i64;
         update_peer_announced_time(self.conn(), id.as_bytes(), fingerprint as i64, now)
             .map(|_| ())
             .map_err(db_err)
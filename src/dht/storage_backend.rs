@@ -0,0 +1,32 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::{Error, ArgumentError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageBackend {
+    Memory,
+    #[default]
+    Sqlite,
+}
+
+impl FromStr for StorageBackend {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "memory" => Ok(StorageBackend::Memory),
+            "sqlite" => Ok(StorageBackend::Sqlite),
+            _ => Err(ArgumentError::new(format!("Unsupported storage backend: {input}"))),
+        }
+    }
+}
+
+impl fmt::Display for StorageBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            StorageBackend::Memory => "memory",
+            StorageBackend::Sqlite => "sqlite",
+        })
+    }
+}
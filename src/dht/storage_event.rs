@@ -0,0 +1,157 @@
+use std::{
+    thread,
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+};
+use log::warn;
+
+use crate::{Id, Value, PeerInfo};
+
+pub(crate) const DEFAULT_STORAGE_EVENT_CAPACITY: usize = 1024;
+
+// Changes of the values and peers stored by the node, for applications
+// reacting on what lands in their keyspace region. `local` tells the ones
+// made through this node's own API from those requested by other nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageEvent {
+    // A value stored for the first time (`new`) or in a newer version.
+    ValueStored { id: Id, seq: i32, new: bool, local: bool },
+    // A value stored again unchanged, extending its lifetime.
+    ValueUpdated { id: Id, seq: i32, local: bool },
+    // A peer announced by the `origin` node, the local node included.
+    PeerAnnounced { id: Id, fingerprint: u64, origin: Id, local: bool },
+    ValueExpired { id: Id },
+    PeerExpired { id: Id, fingerprint: u64 },
+}
+
+impl StorageEvent {
+    // The event for `value` stored over the version with `previous`
+    // sequence number, if any.
+    pub(crate) fn value_stored(value: &Value, previous: Option<i32>, local: bool) -> Self {
+        let id = value.id();
+        let seq = value.sequence_number();
+        match previous {
+            Some(prev) if prev >= seq => Self::ValueUpdated { id, seq, local },
+            _ => Self::ValueStored { id, seq, new: previous.is_none(), local },
+        }
+    }
+
+    pub(crate) fn peer_announced(peer: &PeerInfo, origin: Id, local: bool) -> Self {
+        Self::PeerAnnounced {
+            id: *peer.id(),
+            fingerprint: peer.fingerprint(),
+            origin,
+            local,
+        }
+    }
+}
+
+pub type StorageListener = Box<dyn Fn(StorageEvent) + Send>;
+
+struct State {
+    queue   : VecDeque<StorageEvent>,
+    dropped : u64,
+    // Handed to the delivery thread on its next round.
+    listener: Option<StorageListener>,
+    enabled : bool,
+    closed  : bool,
+}
+
+struct Shared {
+    state   : Mutex<State>,
+    ready   : Condvar,
+}
+
+// Bounded queue of storage events shared by the node and its DHT instances.
+// Events are delivered to the listener on a thread of their own so a slow
+// listener never holds up the DHT threads, when the queue is full the
+// oldest event is dropped and counted instead.
+pub(crate) struct StorageEvents {
+    capacity: usize,
+    shared  : Arc<Shared>,
+}
+
+impl StorageEvents {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    queue   : VecDeque::new(),
+                    dropped : 0,
+                    listener: None,
+                    enabled : false,
+                    closed  : false,
+                }),
+                ready: Condvar::new(),
+            }),
+        }
+    }
+
+    pub(crate) fn set_listener(&self, listener: StorageListener) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.listener = Some(listener);
+        if !state.enabled {
+            state.enabled = true;
+            let shared = self.shared.clone();
+            if let Err(e) = thread::Builder::new()
+                .name("boson-storage-events".into())
+                .spawn(move || Self::deliver(shared)) {
+                warn!("Spawning storage event thread error: {e}");
+                state.enabled = false;
+                state.listener = None;
+                return;
+            }
+        }
+        self.shared.ready.notify_one();
+    }
+
+    // Nothing is queued until a listener is set.
+    pub(crate) fn emit(&self, event: StorageEvent) {
+        let mut state = self.shared.state.lock().unwrap();
+        if !state.enabled || self.capacity == 0 {
+            return;
+        }
+        if state.queue.len() >= self.capacity {
+            state.queue.pop_front();
+            state.dropped += 1;
+        }
+        state.queue.push_back(event);
+        self.shared.ready.notify_one();
+    }
+
+    // Events dropped so far as the listener fell behind.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap().dropped
+    }
+
+    fn deliver(shared: Arc<Shared>) {
+        let mut listener: Option<StorageListener> = None;
+        loop {
+            let event = {
+                let mut state = shared.state.lock().unwrap();
+                while state.queue.is_empty() && state.listener.is_none() && !state.closed {
+                    state = shared.ready.wait(state).unwrap();
+                }
+                if state.closed {
+                    return;
+                }
+                if let Some(replaced) = state.listener.take() {
+                    listener = Some(replaced);
+                }
+                state.queue.pop_front()
+            };
+
+            if let (Some(event), Some(listener)) = (event, listener.as_ref()) {
+                listener(event);
+            }
+        }
+    }
+}
+
+impl Drop for StorageEvents {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.ready.notify_one();
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     time::SystemTime,
     net::SocketAddr
 };
-use crate::{Id, NodeInfo};
+use crate::{Id, NodeInfo, Capabilities};
 use crate::dht::{
     rpc::{Reachability, rpc_target::NodeInfoLike},
     routing::KBucketEntry
@@ -22,6 +22,7 @@ pub(crate) struct CandidateNode {
 
     reachable: bool,
     token: i32,
+    rtt: Option<f64>,
 }
 
 impl CandidateNode {
@@ -34,6 +35,7 @@ impl CandidateNode {
             pinged: 0,
             reachable,
             token: 0,
+            rtt: None,
         }
     }
 
@@ -41,6 +43,22 @@ impl CandidateNode {
         self.ni.id()
     }
 
+    pub(crate) fn capabilities(&self) -> Capabilities {
+        self.ni.capabilities()
+    }
+
+    pub(crate) fn set_capabilities(&mut self, caps: Capabilities) {
+        self.ni.set_capabilities(caps);
+    }
+
+    pub(crate) fn version(&self) -> i32 {
+        self.ni.version()
+    }
+
+    pub(crate) fn set_version(&mut self, ver: i32) {
+        self.ni.set_version(ver);
+    }
+
     pub(crate) fn set_sent(&mut self) {
         self.last_sent = Some(SystemTime::now());
         self.pinged += 1;
@@ -87,6 +105,15 @@ impl CandidateNode {
         self.acked
     }
 
+    // RTT estimate of the node from the routing table, if it has one.
+    pub(crate) fn rtt(&self) -> Option<f64> {
+        self.rtt
+    }
+
+    pub(crate) fn set_rtt(&mut self, rtt: Option<f64>) {
+        self.rtt = rtt;
+    }
+
     pub(crate) fn is_inflight(&self) -> bool {
         self.last_sent.is_some()
     }
@@ -104,7 +131,9 @@ impl Into<CandidateNode> for NodeInfo {
 
 impl Into<CandidateNode> for KBucketEntry {
     fn into(self) -> CandidateNode {
-        CandidateNode::new(self.ni(), self.is_reachable())
+        let mut cn = CandidateNode::new(self.ni(), self.is_reachable());
+        cn.rtt = self.avg_rtt();
+        cn
     }
 }
 
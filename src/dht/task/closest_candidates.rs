@@ -11,6 +11,7 @@ use indexmap::map::IndexMap;
 use crate::Id;
 use crate::dht::{
     rpc::rpc_target::NodeInfoLike,
+    routing::kbucket_entry::distance_order,
     task::candidate_node::CandidateNode,
 };
 
@@ -28,9 +29,11 @@ pub(crate) struct ClosestCandidates {
     closest: IndexMap<Id, Rc<RefCell<CandidateNode>>>,
 
     developer_mode: bool,
+    prefer_low_rtt: bool,
 }
 
 impl ClosestCandidates {
+    #[cfg(test)]
     pub(crate) fn new(target: Id, capacity: usize) -> Self {
         Self::with_developer_mode(target, capacity, false)
     }
@@ -43,9 +46,16 @@ impl ClosestCandidates {
             dedups_addrs    : HashSet::new(),
             closest         : IndexMap::new(),
             developer_mode,
+            prefer_low_rtt: false,
         }
     }
 
+    // Queries faster nodes first among the equally distant ones, the
+    // candidates themselves stay ordered by distance.
+    pub(crate) fn set_prefer_low_rtt(&mut self, enabled: bool) {
+        self.prefer_low_rtt = enabled;
+    }
+
     pub(crate) fn reached_capacity(&self) -> bool {
         self.closest.len() >= self.capacity
     }
@@ -148,9 +158,21 @@ impl ClosestCandidates {
     pub(crate) fn next(&self) -> Option<Rc<RefCell<CandidateNode>>> {
         self.closest.values().filter(|cn|
             cn.borrow().is_eligible()
-        ).min_by(|left, right|
-            Self::candidate_order(&self.target, left, right)
-        ).cloned()
+        ).min_by(|left, right| match self.prefer_low_rtt {
+            true => Self::rtt_order(&self.target, left, right),
+            false => Self::candidate_order(&self.target, left, right)
+        }).cloned()
+    }
+
+    fn rtt_order(
+        target: &Id,
+        a: &Rc<RefCell<CandidateNode>>,
+        b: &Rc<RefCell<CandidateNode>>
+    ) -> Ordering {
+        let a = a.borrow();
+        let b = b.borrow();
+        distance_order(target, (a.id(), a.rtt()), (b.id(), b.rtt()), true)
+            .then(a.pinged().cmp(&b.pinged()))
     }
 
     pub(crate) fn tail(&self) -> Id {
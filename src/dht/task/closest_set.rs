@@ -134,7 +134,6 @@ impl ClosestSet {
         self.closest.get(id).cloned()
     }
 
-    #[cfg(test)]
     pub(crate) fn remove(&mut self, id: &Id) -> Option<Rc<RefCell<CandidateNode>>> {
         if self.is_empty() {
             return None
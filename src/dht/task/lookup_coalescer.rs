@@ -0,0 +1,128 @@
+use std::{
+    hash::Hash,
+    sync::Arc,
+    time::SystemTime,
+    collections::HashMap,
+};
+
+use crate::{
+    Clock,
+    dht::{
+        promise::Promise,
+        task::task::TaskId,
+    },
+};
+
+// A caller waiting for the result of a shared lookup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Waiter<K> {
+    key: K,
+    id : u64,
+}
+
+pub(crate) enum Joined<K> {
+    // Answered from a recent result, no lookup needed.
+    Cached,
+    // Attached to the lookup in flight for the same key.
+    Attached(Waiter<K>),
+    // The first waiter, the caller has to start the lookup.
+    New(Waiter<K>),
+}
+
+struct Inflight<T> {
+    taskid  : Option<TaskId>,
+    waiters : HashMap<u64, Promise<T>>,
+}
+
+// Concurrent lookups for the same key share one task, its result is handed
+// to all the waiters. The task is only canceled when the last waiter leaves.
+// Completed results are kept for `ttl` milliseconds to answer rapid repeats,
+// 0 keeps none.
+pub(crate) struct LookupCoalescer<K, T> {
+    inflight    : HashMap<K, Inflight<T>>,
+    recent      : HashMap<K, (SystemTime, T)>,
+    ttl         : u64,
+    clock       : Arc<dyn Clock>,
+    next_waiter : u64,
+    started     : u64,
+}
+
+impl<K, T> LookupCoalescer<K, T>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+{
+    pub(crate) fn new(ttl: u64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inflight    : HashMap::new(),
+            recent      : HashMap::new(),
+            ttl,
+            clock,
+            next_waiter : 0,
+            started     : 0,
+        }
+    }
+
+    pub(crate) fn join(&mut self, key: &K, promise: Promise<T>) -> Joined<K> {
+        if let Some((time, result)) = self.recent.get(key) {
+            if self.clock.elapsed_ms(*time) < self.ttl as u128 {
+                promise.complete(Ok(result.clone()));
+                return Joined::Cached;
+            }
+            self.recent.remove(key);
+        }
+
+        self.next_waiter += 1;
+        let waiter = Waiter { key: key.clone(), id: self.next_waiter };
+
+        if let Some(inflight) = self.inflight.get_mut(key) {
+            inflight.waiters.insert(waiter.id, promise);
+            return Joined::Attached(waiter);
+        }
+
+        self.inflight.insert(key.clone(), Inflight {
+            taskid  : None,
+            waiters : HashMap::from([(waiter.id, promise)]),
+        });
+        Joined::New(waiter)
+    }
+
+    pub(crate) fn started(&mut self, key: &K, taskid: TaskId) {
+        if let Some(inflight) = self.inflight.get_mut(key) {
+            inflight.taskid = Some(taskid);
+        }
+        self.started += 1;
+    }
+
+    // Fans the result out to all the waiters, and keeps it for the
+    // rapid repeats if `cacheable`.
+    pub(crate) fn complete(&mut self, key: &K, result: T, cacheable: bool) {
+        if cacheable && self.ttl > 0 {
+            let ttl = self.ttl as u128;
+            let clock = self.clock.clone();
+            self.recent.retain(|_, (time, _)| clock.elapsed_ms(*time) < ttl);
+            self.recent.insert(key.clone(), (self.clock.now(), result.clone()));
+        }
+
+        if let Some(inflight) = self.inflight.remove(key) {
+            for promise in inflight.waiters.into_values() {
+                promise.complete(Ok(result.clone()));
+            }
+        }
+    }
+
+    // Returns the task to cancel if the waiter was the last one.
+    pub(crate) fn leave(&mut self, waiter: &Waiter<K>) -> Option<TaskId> {
+        let inflight = self.inflight.get_mut(&waiter.key)?;
+        inflight.waiters.remove(&waiter.id);
+        if !inflight.waiters.is_empty() {
+            return None;
+        }
+        self.inflight.remove(&waiter.key).and_then(|v| v.taskid)
+    }
+
+    // Number of lookups started, each shared by all its waiters.
+    pub(crate) fn started_count(&self) -> u64 {
+        self.started
+    }
+}
@@ -14,6 +14,7 @@ use crate::dht::{
     },
     msg::{Body,LookupResponse},
     routing::KBucket,
+    lookup_progress::{LookupProgress, ProgressReporter},
     task::{
         ClosestSet,
         ClosestCandidates,
@@ -33,17 +34,32 @@ pub(crate) struct LookupTaskData {
 
     done_on_eligible_result : bool,
     done_on_lookup          : bool,
+    // Looking for the nodes to store on, the others only route.
+    storage_only            : bool,
+
+    queried     : usize,
+    responded   : usize,
+    timed_out   : usize,
+    failed      : usize,
+    progress    : Option<ProgressReporter>,
 }
 
 impl LookupTaskData {
     pub(crate) fn new(target: Id, done_on_eligible_result: bool) -> Self {
         Self {
             closest     : ClosestSet::new(target, KBucket::MAX_ENTRIES),
-            candidates  : ClosestCandidates::new(target, MAX_ITERATIONS),
+            // Local test networks run all their nodes on one address.
+            candidates  : ClosestCandidates::with_developer_mode(target, MAX_ITERATIONS, cfg!(feature = "devp")),
             iteration_count : 0,
             target,
             done_on_eligible_result,
             done_on_lookup: false,
+            storage_only: false,
+            queried     : 0,
+            responded   : 0,
+            timed_out   : 0,
+            failed      : 0,
+            progress    : None,
         }
     }
 
@@ -51,9 +67,17 @@ impl LookupTaskData {
         self.done_on_eligible_result
     }
 
+    pub(crate) fn with_storage_only(&mut self, storage_only: bool) {
+        self.storage_only = storage_only;
+    }
+
     pub(crate) fn done_lookup(&mut self) {
         self.done_on_lookup = true;
     }
+
+    pub(crate) fn with_progress(&mut self, reporter: ProgressReporter) {
+        self.progress = Some(reporter);
+    }
 }
 
 pub(crate) trait LookupTask {
@@ -72,10 +96,17 @@ pub(crate) trait LookupTask {
     }
 
     fn add(&mut self, mut entries: Vec<impl Into<CandidateNode>>) {
-        let ni = self.dht().borrow().ni();
+        let (ni, prefer_low_rtt) = {
+            let dht = self.dht();
+            let dht = dht.borrow();
+            (dht.ni(), dht.prefer_low_rtt())
+        };
+        let rt = prefer_low_rtt.then(|| self.dht().borrow().rt());
+        self.data_mut().candidates.set_prefer_low_rtt(prefer_low_rtt);
+
         let mut todo: Vec<CandidateNode> = Vec::new();
         while let Some(entry) = entries.pop() {
-            let candidate: CandidateNode = entry.into();
+            let mut candidate: CandidateNode = entry.into();
             let bogon = if cfg!(feature = "devp") {
                 !is_any_unicast(&candidate.socket_addr().ip())
             } else {
@@ -87,6 +118,11 @@ pub(crate) trait LookupTask {
                 ni.socket_addr() == candidate.socket_addr() {
                 continue;
             }
+            if let (Some(rt), None) = (rt.as_ref(), candidate.rtt()) {
+                // Nodes learned from responses may be known to the routing table
+                let rtt = rt.borrow().bucket_entry(candidate.id()).and_then(|e| e.avg_rtt());
+                candidate.set_rtt(rtt);
+            }
             todo.push(candidate);
         }
 
@@ -99,14 +135,23 @@ pub(crate) trait LookupTask {
         self.data_mut().candidates.remove(id)
     }
 
+    // Every candidate taken is sent a request right away.
     fn next_candidate(&mut self) -> Option<Rc<RefCell<CandidateNode>>> {
-       self.data_mut().candidates.next()
+        let next = self.data_mut().candidates.next();
+        if next.is_some() {
+            self.data_mut().queried += 1;
+        }
+        next
     }
 
     fn add_closest(&mut self, cn: Rc<RefCell<CandidateNode>>) {
         self.data_mut().closest.add(cn)
     }
 
+    fn remove_closest(&mut self, id: &Id) -> Option<Rc<RefCell<CandidateNode>>> {
+        self.data_mut().closest.remove(id)
+    }
+
     fn closest(&self) -> &ClosestSet {
         &self.data().closest
     }
@@ -115,6 +160,31 @@ pub(crate) trait LookupTask {
         self.data_mut().iteration_count += 1;
     }
 
+    fn progress(&self) -> Option<LookupProgress> {
+        let data = self.data();
+        let reporter = data.progress.as_ref()?;
+        let best = (!data.closest.is_empty())
+            .then(|| data.target.common_prefix_len(&data.closest.head()));
+
+        Some(reporter.snapshot()
+            .with_counts(data.queried, data.responded, data.timed_out, data.failed)
+            .with_best_distance(best)
+            .with_candidates(data.candidates.size()))
+    }
+
+    fn report_progress(&self) {
+        if let (Some(reporter), Some(progress)) = (self.data().progress.as_ref(), self.progress()) {
+            reporter.update(progress);
+        }
+    }
+
+    // The last snapshot, once the task ended.
+    fn finish_progress(&self) {
+        if let (Some(reporter), Some(progress)) = (self.data().progress.as_ref(), self.progress()) {
+            reporter.finish(progress);
+        }
+    }
+
     fn is_done(&self) -> bool {
         let data = self.data();
         if data.done_on_lookup {
@@ -135,11 +205,13 @@ pub(crate) trait LookupTask {
     }
 
     fn call_error(&mut self, call: &RpcCall) {
+        self.data_mut().failed += 1;
         let id = call.target().id();
         let _  = self.remove_candidate(&id);
     }
 
     fn call_timeout(&mut self, call: &RpcCall) {
+        self.data_mut().timed_out += 1;
         let target = call.target();
         let id = target.id();
 
@@ -159,6 +231,7 @@ pub(crate) trait LookupTask {
     }
 
     fn call_responded(&mut self, call: &RpcCall) {
+        self.data_mut().responded += 1;
         let target_id = call.target_id();
         let Some(cn) = self.remove_candidate(&target_id) else {
             return;
@@ -167,6 +240,10 @@ pub(crate) trait LookupTask {
         cn.borrow_mut().set_replied();
 
         let rsp  = call.rsp().expect("no response set.");
+        cn.borrow_mut().set_version(rsp.ver());
+        if let Some(caps) = rsp.capabilities() {
+            cn.borrow_mut().set_capabilities(caps);
+        }
         let body = rsp.body().expect("no message body in response.");
         let token = match body {
             Body::FindNodeResponse(body) => body.token(),
@@ -175,7 +252,19 @@ pub(crate) trait LookupTask {
             _ => return,
         };
 
+        // Client-only nodes are never sent anything to store, nor count
+        // towards the closest nodes storing it.
+        if self.data().storage_only && !cn.borrow().capabilities().accepts_storage() {
+            return;
+        }
+
         cn.borrow_mut().set_token(token);
+        // Tokens are for the id asking, none of a session id is of use later.
+        if token != 0 && !self.base_data().has_session() {
+            let target = *self.target();
+            self.dht().borrow().token_cache().borrow_mut()
+                .put(&target, cn.borrow().ni(), token);
+        }
         self.add_closest(cn);
     }
 }
@@ -57,8 +57,10 @@ impl NodeLookupTask {
         self.bootstrap = bootstrap;
     }
 
+    // The tokens are to store on the closest nodes found.
     pub(crate) fn with_want_token(&mut self, token: bool) {
         self.want_token = token;
+        self.lookup_data.with_storage_only(token);
     }
 
     pub(crate) fn with_want_target(&mut self, want_target: bool) {
@@ -140,6 +142,7 @@ impl Task for NodeLookupTask {
                 KBucket::MAX_ENTRIES *3
             );
             kns.set_filter(|v| v.eligible_for_local_lookup());
+            kns.set_prefer_low_rtt(self.dht.borrow().prefer_low_rtt());
             kns.fill();
             kns.into()
         };
@@ -241,6 +244,10 @@ impl Task for NodeLookupTask {
         LookupTask::call_timeout(self, call);
     }
 
+    fn progressed(&self) {
+        LookupTask::report_progress(self);
+    }
+
     fn is_done(&self) -> bool {
         LookupTask::is_done(self)
     }
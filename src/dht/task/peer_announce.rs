@@ -2,13 +2,15 @@ use std::{
     any::Any,
     rc::Rc,
     cell::RefCell,
-    collections::VecDeque,
+    collections::{HashMap, HashSet, VecDeque},
 };
-use crate::PeerInfo;
+use crate::{Id, Network, PeerInfo};
+use crate::core::version;
 use crate::dht::{
     dht::DHT,
-    msg::msg,
+    msg::{LookupResponse, msg::{self, Body}, error::PROTOCOL_ERROR},
     handler::Handler,
+    rpc::{RpcCall, Target, rpc_target::NodeInfoLike},
     task::{ClosestSet, CandidateNode,Task, TaskData}
 };
 
@@ -17,7 +19,18 @@ pub(crate) struct PeerAnnounceTask {
 
     todo: Rc<RefCell<VecDeque<Rc<RefCell<CandidateNode>>>>>,
     peer: PeerInfo,
+    // The peer signed without its time for the nodes of older versions,
+    // none if not owned here.
+    untimed: Option<PeerInfo>,
     expected_seq: i32,
+    // Nodes to fetch a token from before announcing, and those that
+    // rejected a token once.
+    refetch: HashSet<Id>,
+    rejected: HashSet<Id>,
+    // The nodes the peer was announced to, and the endpoints those seeing
+    // its endpoint unroutable observed it at.
+    acked: usize,
+    observed: HashMap<Id, String>,
 
     dht: Rc<RefCell<DHT>>,
 }
@@ -33,13 +46,28 @@ impl PeerAnnounceTask {
         Self {
             dht,
             base_data: TaskData::new(),
+            untimed: peer.untimed().ok(),
             peer,
             todo: Rc::new(RefCell::new(
                 VecDeque::with_capacity(MAX_TODO_ENTRIES))),
-            expected_seq
+            expected_seq,
+            refetch: HashSet::new(),
+            rejected: HashSet::new(),
+            acked: 0,
+            observed: HashMap::new(),
         }
     }
 
+    // The closest nodes with their cached tokens, those without one are
+    // asked for a token first.
+    pub(crate) fn with_cached(&mut self, closest: ClosestSet) -> &Self {
+        self.refetch.extend(closest.entries().iter()
+            .filter(|cn| cn.borrow().token() == 0)
+            .map(|cn| *cn.borrow().id())
+        );
+        self.with_closest(closest)
+    }
+
     pub(crate) fn with_closest(&self, closest: ClosestSet) -> &Self {
         let mut borrowed_todo = self.todo.borrow_mut();
         let mut entries = closest.entries();
@@ -59,6 +87,31 @@ impl PeerAnnounceTask {
         );
         self
     }
+
+    pub(crate) fn peer(&self) -> &PeerInfo {
+        &self.peer
+    }
+
+    // The announcement as a node of the version can decode it, none for
+    // an older node if the peer can't be signed again here.
+    pub(crate) fn peer_for(&self, ver: i32) -> Option<&PeerInfo> {
+        match version::supports_peer_announced(ver) {
+            true => Some(&self.peer),
+            false => self.untimed.as_ref(),
+        }
+    }
+
+    // The endpoint the peer was observed at in place of its unroutable one
+    // by most of the nodes it was announced to.
+    pub(crate) fn observed_consensus(&self) -> Option<String> {
+        let mut votes = HashMap::<&str, usize>::new();
+        for endpoint in self.observed.values() {
+            *votes.entry(endpoint.as_str()).or_default() += 1;
+        }
+        votes.into_iter()
+            .find(|(_, count)| count * 2 > self.acked)
+            .map(|(endpoint, _)| endpoint.to_string())
+    }
 }
 
 impl Task for PeerAnnounceTask {
@@ -90,6 +143,17 @@ impl Task for PeerAnnounceTask {
             };
 
             let token = cn.borrow().token();
+            if token == 0 && self.refetch.remove(cn.borrow().id()) {
+                let want4 = self.network() == Network::IPv4;
+                let msg = msg::find_node_request(*self.peer.id(), want4, !want4, Some(true));
+
+                let cloned_todo = self.todo.clone();
+                let cb = Handler::new(move |_| {
+                    cloned_todo.borrow_mut().pop_front();
+                });
+                self.send_call(cn.into(), msg, Some(cb));
+                continue;
+            }
             if token == 0 {
                 log::warn!("{}#{} skip announcing to {} due to missing token",
                     self.task_name(),
@@ -100,8 +164,17 @@ impl Task for PeerAnnounceTask {
                 continue;
             }
 
+            let Some(peer) = self.peer_for(cn.borrow().version()) else {
+                log::debug!("{}#{} skip announcing to {} of an older version",
+                    self.task_name(),
+                    self.task_id(),
+                    cn.borrow().id(),
+                );
+                self.todo.borrow_mut().pop_front();
+                continue;
+            };
             let msg = msg::announce_peer_request(
-                self.peer.clone(), token, self.expected_seq,
+                peer.clone(), token, self.expected_seq,
             );
 
             let cloned_todo = self.todo.clone();
@@ -112,6 +185,63 @@ impl Task for PeerAnnounceTask {
         }
     }
 
+    fn call_responded(&mut self, call: &RpcCall) {
+        let Target::Candidate(cn) = call.target() else {
+            return;
+        };
+        let rsp = call.rsp();
+        let body = match rsp.as_ref().and_then(|m| m.body()) {
+            Some(Body::FindNodeResponse(body)) => body,
+            Some(Body::AnnouncePeerResponse(body)) => {
+                if let Some(observed) = body.observed() {
+                    self.observed.insert(*cn.borrow().id(), observed.to_string());
+                }
+                self.acked += 1;
+                return;
+            },
+            Some(_) => return,
+            // Announced to a node of an older version.
+            None => {
+                self.acked += rsp.is_some() as usize;
+                return;
+            },
+        };
+        if body.token() == 0 {
+            return;
+        }
+
+        cn.borrow_mut().set_version(rsp.as_ref().map_or(0, |m| m.ver()));
+        cn.borrow_mut().set_token(body.token());
+        self.dht.borrow().token_cache().borrow_mut()
+            .put(self.peer.id(), cn.borrow().ni(), body.token());
+        self.todo.borrow_mut().push_back(cn.clone());
+    }
+
+    fn call_error(&mut self, call: &RpcCall) {
+        let Target::Candidate(cn) = call.target() else {
+            return;
+        };
+        let rsp = call.rsp();
+        let Some(Body::Error(err)) = rsp.as_ref().and_then(|m| m.body()) else {
+            return;
+        };
+        let id = *cn.borrow().id();
+        if err.code() != PROTOCOL_ERROR || !self.rejected.insert(id) {
+            return;
+        }
+
+        // The token expired or the node restarted, fetch it once again.
+        log::debug!("{}#{} token rejected by {}, refreshing",
+            self.task_name(),
+            self.task_id(),
+            id
+        );
+        self.dht.borrow().token_cache().borrow_mut().invalidate(self.peer.id(), &id);
+        cn.borrow_mut().set_token(0);
+        self.refetch.insert(id);
+        self.todo.borrow_mut().push_back(cn.clone());
+    }
+
     fn is_done(&self) -> bool {
         self.todo.borrow().is_empty() &&
             self.data().is_done()
@@ -3,12 +3,14 @@ use std::{
     rc::Rc,
     cell::RefCell
 };
-use crate::{Id, PeerInfo};
+use crate::{Id, Network};
+use crate::core::version;
 use crate::dht::{
     dht::DHT,
     handler::Handler,
     eligible_peers::EligiblePeers,
-    rpc::RpcCall,
+    lookup_result::{Origins, PeerResult},
+    rpc::{RpcCall, rpc_target::NodeInfoLike},
     routing::{
         KBucket, KBucketEntry,
         KClosestNodes
@@ -34,18 +36,22 @@ impl PeerLookupTask {
         target: Id,
         expected_seq: i32,
         expected_count: usize,
+        tags: Vec<String>,
+        network: Option<Network>,
         done_on_eligible_result: bool
     ) -> Self {
         Self {
             base_data   : TaskData::new(),
             lookup_data : LookupTaskData::new(target, done_on_eligible_result),
-            result      : EligiblePeers::new(target, expected_seq, expected_count),
+            result      : EligiblePeers::new(target, expected_seq, expected_count)
+                .with_tags(tags)
+                .with_network(network),
             dht         : dht.clone()
         }
     }
 
-    pub(crate) fn result(&self) -> Vec<PeerInfo> {
-        self.result.peers()
+    pub(crate) fn result(&self) -> Vec<PeerResult> {
+        self.result.results()
     }
 }
 
@@ -97,6 +103,7 @@ impl Task for PeerLookupTask {
                 KBucket::MAX_ENTRIES *3
             );
             kns.set_filter(|v| v.eligible_for_local_lookup());
+            kns.set_prefer_low_rtt(self.dht.borrow().prefer_low_rtt());
             kns.fill();
             kns.into()
         };
@@ -120,6 +127,16 @@ impl Task for PeerLookupTask {
                 _ => break,
             };
 
+            // Older nodes reject requests with the tags, they are filtered
+            // here instead.
+            let tags = match version::supports_peer_tags(next.borrow().ni().version()) {
+                true => self.result.tags().to_vec(),
+                false => Vec::new(),
+            };
+            let wanted = match version::supports_peer_network(next.borrow().ni().version()) {
+                true => self.result.network(),
+                false => None,
+            };
             let target = next.clone().into();
             let msg = msg::find_peer_request(
                 self.target().clone(),
@@ -127,6 +144,8 @@ impl Task for PeerLookupTask {
                 network.is_ipv6(),
                 self.result.expected_seq(),
                 self.result.expected_count() as i32,
+                tags,
+                wanted,
             );
 
             let cb = Handler::new(move |_| {
@@ -159,7 +178,14 @@ impl Task for PeerLookupTask {
                 return;
             }
 
-            if !self.result.add(peers.to_vec(), false) {
+            let source = call.target_id();
+            let ages = body.ages();
+            let peers = peers.iter().enumerate().map(|(i, peer)| {
+                (peer.clone(), Origins::new(source, ages.map(|v| v[i])))
+            }).collect::<Vec<_>>();
+            let count = peers.len();
+
+            if !self.result.add(peers, false) {
                 log::warn!(
                     "{}#{} dropping peer response from {} due to ineligible peer data",
                     self.task_name(),
@@ -172,7 +198,7 @@ impl Task for PeerLookupTask {
             log::debug!("{}#{} received {} peers from response by {}",
                 self.task_name(),
                 self.task_id(),
-                peers.len(),
+                count,
                 call.target_id()
             );
 
@@ -212,6 +238,10 @@ impl Task for PeerLookupTask {
         LookupTask::call_timeout(self, call);
     }
 
+    fn progressed(&self) {
+        LookupTask::report_progress(self);
+    }
+
     fn is_done(&self) -> bool {
         LookupTask::is_done(self)
     }
@@ -2,14 +2,16 @@ use std::{
     fmt,
     any::Any,
     rc::{Rc, Weak},
+    sync::Arc,
     cell::RefCell,
     collections::HashSet,
     sync::atomic::{Ordering, AtomicI32},
 };
 use log::{warn, debug};
-use crate::core::Network;
+use crate::{CryptoIdentity, core::Network};
 use crate::dht::{
     dht::DHT,
+    node_config::DEFAULT_MAX_TASK_CALLS,
     msg::Message,
     handler::Handler,
     task::task_listener::TaskListener,
@@ -72,8 +74,11 @@ pub(crate) struct TaskData {
     //ended       : SystemTime,
 
     inflights   : HashSet<i32>,
+    max_calls   : usize,
     listener    : Option<TaskListener>,
     end_handler : Option<Handler<()>>,
+    // The session identity the requests are sent from in privacy mode.
+    session     : Option<Arc<CryptoIdentity>>,
 
     nested      : RefCell<Option<Box<dyn Task>>>,
     cloned      : Option<Weak<RefCell<Box<dyn Task>>>>,
@@ -86,8 +91,10 @@ impl TaskData {
             task_name   : String::new(),
             state       : State::Initialized,
             inflights   : HashSet::new(),
+            max_calls   : DEFAULT_MAX_TASK_CALLS,
             listener    : None,
             end_handler : None,
+            session     : None,
             nested      : RefCell::new(None),
             cloned      : None,
 
@@ -97,6 +104,10 @@ impl TaskData {
     pub(crate) fn is_done(&self) -> bool {
         self.inflights.is_empty()
     }
+
+    pub(crate) fn has_session(&self) -> bool {
+        self.session.is_some()
+    }
 }
 
 pub(crate) trait Task {
@@ -178,6 +189,15 @@ pub(crate) trait Task {
         self.data().inflights.len()
     }
 
+    // RPC calls of the task awaiting a response at once.
+    fn with_max_calls(&mut self, max_calls: usize) {
+        self.data_mut().max_calls = max_calls;
+    }
+
+    fn with_session(&mut self, session: Arc<CryptoIdentity>) {
+        self.data_mut().session = Some(session);
+    }
+
     fn with_ended_handler(&mut self, handler: Handler<()>) {
         self.data_mut().end_handler = Some(handler);
     }
@@ -346,7 +366,7 @@ pub(crate) trait Task {
 */
     fn can_dorequest(&self) -> bool {
         self.is_running() &&
-            self.inflight_size() < 16
+            self.inflight_size() < self.data().max_calls
     }
 
     fn prepare(&mut self) {}
@@ -357,7 +377,10 @@ pub(crate) trait Task {
     fn call_error(&mut self, _: &RpcCall) {}
     fn call_timeout(&mut self, _: &RpcCall) {}
 
-    fn send_call(&mut self, target: Target, msg: Message, handler: Option<Handler<()>>) {
+    // After a call changed the state of the task, for the progress reports.
+    fn progressed(&self) {}
+
+    fn send_call(&mut self, target: Target, mut msg: Message, handler: Option<Handler<()>>) {
         if !self.can_dorequest() {
             return;
         }
@@ -398,11 +421,21 @@ pub(crate) trait Task {
 
             if state >= rpccall::State::Stalled {
                 task.try_iterate();
+                if !task.is_ended() {
+                    task.progressed();
+                }
             }
         });
 
+        let session = self.data().session.clone();
+        if session.is_some() {
+            msg.set_transient();
+        }
         let mut call = RpcCall::new(target, msg);
         call.set_listener(listener);
+        if let Some(session) = session {
+            call.set_sender(session);
+        }
 
         handler.map(|v| v.cb(&()));
         self.data_mut().inflights.insert(call.txid());
@@ -1,38 +1,85 @@
 use std::{
-    rc::Rc,
-    cell::RefCell,
+    rc::{Rc, Weak},
+    cell::{Cell, RefCell},
     sync::atomic::{AtomicBool, Ordering},
-    collections::{VecDeque, HashSet},
+    collections::{VecDeque, HashMap},
 };
 use log::{debug, error};
 
 use crate::dht::{
     handler::Handler,
+    node_config::{
+        DEFAULT_MAX_ACTIVE_TASKS,
+        DEFAULT_MAX_INFLIGHT_CALLS,
+        DEFAULT_MAX_TASK_CALLS,
+    },
     task::{Task, task::{State, TaskId}}
 };
 
-const MAX_ACTIVE_TASKS: usize = 8;
+// Application tasks started ahead of a waiting maintenance task before it
+// gets the next free slot anyway.
+const MAX_MAINTENANCE_PASSES: usize = 4;
+
+type WeakTask = Weak<RefCell<Box<dyn Task>>>;
+type SharedTask = Rc<RefCell<Box<dyn Task>>>;
+
+// How many tasks and RPC calls of one DHT may be active at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ConcurrencyLimits {
+    pub(crate) max_active_tasks     : usize,
+    pub(crate) max_inflight_calls   : usize,
+    pub(crate) max_task_calls       : usize,
+}
+
+impl Default for ConcurrencyLimits {
+    fn default() -> Self {
+        Self {
+            max_active_tasks    : DEFAULT_MAX_ACTIVE_TASKS,
+            max_inflight_calls  : DEFAULT_MAX_INFLIGHT_CALLS,
+            max_task_calls      : DEFAULT_MAX_TASK_CALLS,
+        }
+    }
+}
 
 pub(crate) struct TaskManager {
-    queued      : RefCell<VecDeque<Rc<RefCell<Box<dyn Task>>>>>,
-    running     : RefCell<HashSet<TaskId>>,
+    queued      : RefCell<VecDeque<SharedTask>>,
+    maintenance : RefCell<VecDeque<SharedTask>>,
+    running     : RefCell<HashMap<TaskId, WeakTask>>,
     canceling   : AtomicBool,
+
+    limits      : ConcurrencyLimits,
+    passes      : Cell<usize>,
+    peak        : Cell<usize>,
+
+    weak        : Weak<TaskManager>,
 }
 
 impl TaskManager {
-    pub(crate) fn new() -> Self {
-        Self {
+    pub(crate) fn new(limits: ConcurrencyLimits) -> Rc<Self> {
+        Rc::new_cyclic(|weak| Self {
             queued      : RefCell::new(VecDeque::new()),
-            running     : RefCell::new(HashSet::new()),
+            maintenance : RefCell::new(VecDeque::new()),
+            running     : RefCell::new(HashMap::new()),
             canceling   : AtomicBool::new(false),
-        }
+            limits,
+            passes      : Cell::new(0),
+            peak        : Cell::new(0),
+            weak        : weak.clone(),
+        })
     }
 
+    // Adds a task requested by the application.
     pub(crate) fn add(&self, task: Box<dyn Task>) {
-        self.add_prior(task, false);
+        self.submit(task, false);
+    }
+
+    // Adds a routing table maintenance task, which waits for the tasks of
+    // the application when the slots are taken.
+    pub(crate) fn add_maintenance(&self, task: Box<dyn Task>) {
+        self.submit(task, true);
     }
 
-    pub(crate) fn add_prior(&self, mut task: Box<dyn Task>, priori: bool) {
+    fn submit(&self, mut task: Box<dyn Task>, maintenance: bool) {
         if self.canceling.load(Ordering::SeqCst) {
             return;
         }
@@ -40,11 +87,15 @@ impl TaskManager {
             return;
         }
 
+        // An ended task frees its slot for the next queued one.
         let taskid = task.task_id();
-        let running = self.running.clone();
+        let weak = self.weak.clone();
         task.with_ended_handler(
             Handler::new(move |_| {
-                running.borrow_mut().remove(&taskid);
+                if let Some(man) = weak.upgrade() {
+                    man.running.borrow_mut().remove(&taskid);
+                    man.dequeue();
+                }
             })
         );
 
@@ -55,29 +106,74 @@ impl TaskManager {
 			return;
         }
 
-        self.enqueue(task, priori);
+        task.with_max_calls(self.limits.max_task_calls);
+        self.enqueue(task, maintenance);
         self.dequeue();
     }
 
+    pub(crate) fn limits(&self) -> &ConcurrencyLimits {
+        &self.limits
+    }
+
+    // Tasks queued or running.
+    pub(crate) fn active(&self) -> usize {
+        self.queued() + self.running()
+    }
+
+    pub(crate) fn queued(&self) -> usize {
+        self.queued.borrow().len() + self.maintenance.borrow().len()
+    }
+
+    pub(crate) fn running(&self) -> usize {
+        self.running.borrow().len()
+    }
+
+    // The most tasks ever running at once.
+    pub(crate) fn peak_running(&self) -> usize {
+        self.peak.get()
+    }
+
     #[inline(always)]
     fn is_ready(&self) -> bool {
         !self.canceling.load(Ordering::SeqCst) &&
-            self.running.borrow().len() < MAX_ACTIVE_TASKS
+            self.running.borrow().len() < self.limits.max_active_tasks
     }
 
-    fn enqueue(&self, task: Box<dyn Task>, priori: bool) {
+    fn enqueue(&self, task: Box<dyn Task>, maintenance: bool) {
         let task = Rc::new(RefCell::new(task));
         task.borrow_mut().set_cloned(std::rc::Rc::downgrade(&task));
-        let mut queue = self.queued.borrow_mut();
-        match priori {
-            true => queue.push_front(task),
-            false => queue.push_back(task),
+        match maintenance {
+            true => self.maintenance.borrow_mut().push_back(task),
+            false => self.queued.borrow_mut().push_back(task),
         };
     }
 
+    // The tasks of the application go first, a waiting maintenance task
+    // only lets a few of them pass.
+    fn next(&self) -> Option<SharedTask> {
+        let mut maintenance = self.maintenance.borrow_mut();
+        if !maintenance.is_empty() && self.passes.get() >= MAX_MAINTENANCE_PASSES {
+            self.passes.set(0);
+            return maintenance.pop_front();
+        }
+
+        match self.queued.borrow_mut().pop_front() {
+            Some(task) => {
+                if !maintenance.is_empty() {
+                    self.passes.set(self.passes.get() + 1);
+                }
+                Some(task)
+            },
+            None => {
+                self.passes.set(0);
+                maintenance.pop_front()
+            }
+        }
+    }
+
     pub(crate) fn dequeue(&self) {
         while self.is_ready() {
-           let Some(task) = self.queued.borrow_mut().pop_front() else {
+            let Some(task) = self.next() else {
                 debug!("Queue drained.");
                 break;
             };
@@ -87,17 +183,42 @@ impl TaskManager {
             }
 
             let taskid = task.borrow().task_id();
-            let _ = self.running.borrow_mut().insert(taskid);
+            let running = {
+                let mut running = self.running.borrow_mut();
+                running.insert(taskid, Rc::downgrade(&task));
+                running.len()
+            };
+            self.peak.set(self.peak.get().max(running));
 
             task.borrow_mut().start();
         }
     }
 
+    // Cancels a queued or running task, returns false if it has ended already.
+    pub(crate) fn cancel(&self, taskid: TaskId) -> bool {
+        let queued = [&self.queued, &self.maintenance].into_iter().find_map(|queue| {
+            let mut queue = queue.borrow_mut();
+            queue.iter()
+                .position(|t| t.borrow().task_id() == taskid)
+                .and_then(|pos| queue.remove(pos))
+        });
+        let task = queued.or_else(|| {
+            self.running.borrow().get(&taskid).and_then(|t| t.upgrade())
+        });
+
+        let Some(task) = task else {
+            return false;
+        };
+        task.borrow_mut().cancel();
+        self.dequeue();
+        true
+    }
+
     pub(crate) fn stop(&self) {
         self.canceling.store(true, Ordering::SeqCst);
 
         let _ = self.running.borrow_mut().drain();
-        for t in self.queued.borrow_mut().drain(..) {
+        for t in self.queued.take().into_iter().chain(self.maintenance.take()) {
             t.borrow_mut().cancel();
         }
 
@@ -109,5 +230,6 @@ impl Drop for TaskManager {
     fn drop(&mut self) {
         self.running.borrow_mut().clear();
         self.queued.borrow_mut().clear();
+        self.maintenance.borrow_mut().clear();
     }
 }
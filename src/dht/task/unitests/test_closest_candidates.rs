@@ -1,9 +1,19 @@
 use crate::{Id, NodeInfo};
 use crate::dht::{
     rpc::rpc_target::NodeInfoLike,
-    task::closest_candidates::ClosestCandidates
+    task::{
+        candidate_node::CandidateNode,
+        closest_candidates::ClosestCandidates,
+    },
 };
 
+fn make_id(first_byte: u8, last_byte: u8) -> Id {
+    let mut bytes = [0u8; Id::BYTES];
+    bytes[0] = first_byte;
+    bytes[Id::BYTES - 1] = last_byte;
+    Id::from_bytes(bytes)
+}
+
 fn make_node(distance: usize, host: &str, port: u16) -> NodeInfo {
     NodeInfo::new(
         Id::try_from_bit_at(Id::BITS - distance).unwrap(),
@@ -144,6 +154,43 @@ mod tests {
         assert_eq!(next.borrow().id(), middle.id());
     }
 
+    #[test]
+    fn test_next_prefers_low_rtt() {
+        let target = Id::MIN_ID;
+        let mut candidates = ClosestCandidates::new(target, 8);
+
+        // Same common prefix with the target, closer by id first
+        let nodes = (1..=3).map(|i| NodeInfo::new(
+            make_id(0x40, i),
+            format!("1.1.1.{i}:39001").parse().unwrap()
+        )).collect::<Vec<_>>();
+        candidates.add(nodes.iter().cloned().map(|ni| ni.into()).collect());
+        for (ni, rtt) in nodes.iter().zip([None, Some(90.0), Some(15.0)]) {
+            candidates.candidate_node(ni.id()).unwrap().borrow_mut().set_rtt(rtt);
+        }
+        let farther = NodeInfo::new(make_id(0x80, 1), "1.1.1.9:39009".parse().unwrap());
+        let mut cn: CandidateNode = farther.clone().into();
+        cn.set_rtt(Some(1.0));
+        candidates.add(vec![cn]);
+
+        assert_eq!(candidates.next().unwrap().borrow().id(), nodes[0].id());
+
+        // The distance order of the candidates is kept
+        candidates.set_prefer_low_rtt(true);
+        assert_eq!(candidates.head(), nodes[0].id().clone());
+        let mut order = Vec::new();
+        while let Some(next) = candidates.next() {
+            next.borrow_mut().set_sent();
+            order.push(next.borrow().id().clone());
+        }
+        assert_eq!(order, vec![
+            nodes[2].id().clone(),
+            nodes[1].id().clone(),
+            nodes[0].id().clone(),
+            farther.id().clone(),
+        ]);
+    }
+
     #[test]
     fn test_empty() {
         let target = Id::MIN_ID;
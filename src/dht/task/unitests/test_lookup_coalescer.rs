@@ -0,0 +1,106 @@
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+use futures::FutureExt;
+
+use crate::{
+    Id,
+    ManualClock,
+};
+use crate::dht::{
+    promise::{Promise, PromiseFuture},
+    task::lookup_coalescer::{LookupCoalescer, Joined},
+};
+
+type Coalescer = LookupCoalescer<Id, Option<u32>>;
+
+fn join(lookups: &mut Coalescer, key: &Id) -> (Joined<Id>, PromiseFuture<Option<u32>>) {
+    let (promise, future) = Promise::pair();
+    (lookups.join(key, promise), future)
+}
+
+fn result(future: PromiseFuture<Option<u32>>) -> Option<Option<u32>> {
+    future.now_or_never().map(|v| v.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_lookup() {
+        let mut lookups = Coalescer::new(0, Arc::new(ManualClock::default()));
+        let key = Id::random();
+        let other = Id::random();
+
+        let (joined, f1) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::New(_)));
+        lookups.started(&key, 7);
+
+        let (joined, f2) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::Attached(_)));
+        let (joined, f3) = join(&mut lookups, &other);
+        assert!(matches!(joined, Joined::New(_)));
+        lookups.started(&other, 8);
+        assert_eq!(lookups.started_count(), 2);
+
+        lookups.complete(&key, Some(42), true);
+        assert_eq!(result(f1), Some(Some(42)));
+        assert_eq!(result(f2), Some(Some(42)));
+        assert_eq!(result(f3), None);
+
+        // Nothing kept without a ttl, the next lookup starts over.
+        let (joined, _) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::New(_)));
+    }
+
+    #[test]
+    fn test_leave() {
+        let mut lookups = Coalescer::new(0, Arc::new(ManualClock::default()));
+        let key = Id::random();
+
+        let (Joined::New(w1), _) = join(&mut lookups, &key) else { panic!() };
+        lookups.started(&key, 7);
+        let (Joined::Attached(w2), f2) = join(&mut lookups, &key) else { panic!() };
+
+        // The task goes on for the remaining waiter.
+        assert_eq!(lookups.leave(&w1), None);
+        assert_eq!(lookups.leave(&w1), None);
+
+        lookups.complete(&key, None, true);
+        assert_eq!(result(f2), Some(None));
+
+        // The last waiter leaving cancels the task.
+        let (Joined::New(w3), _) = join(&mut lookups, &key) else { panic!() };
+        lookups.started(&key, 9);
+        assert_eq!(lookups.leave(&w2), None);
+        assert_eq!(lookups.leave(&w3), Some(9));
+    }
+
+    #[test]
+    fn test_recent_results() {
+        let clock = Arc::new(ManualClock::default());
+        let mut lookups = Coalescer::new(3000, clock.clone());
+        let key = Id::random();
+
+        let _ = join(&mut lookups, &key);
+        lookups.started(&key, 7);
+        lookups.complete(&key, Some(42), true);
+
+        clock.advance(Duration::from_millis(2999));
+        let (joined, future) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::Cached));
+        assert_eq!(result(future), Some(Some(42)));
+        assert_eq!(lookups.started_count(), 1);
+
+        clock.advance(Duration::from_millis(1));
+        let (joined, _) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::New(_)));
+
+        // Results of canceled lookups are not kept.
+        lookups.complete(&key, None, false);
+        let (joined, _) = join(&mut lookups, &key);
+        assert!(matches!(joined, Joined::New(_)));
+    }
+}
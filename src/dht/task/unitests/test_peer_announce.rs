@@ -7,9 +7,12 @@ use crate::{
     Network,
     NodeInfo,
     PeerInfo,
+    core::version,
+    core::peer_info::PeerFields,
 };
 use crate::dht::{
     dht::DHT,
+    msg::announce_peer_req::AnnouncePeerRequest,
     task::{
         candidate_node::CandidateNode,
         closest_set::ClosestSet,
@@ -33,7 +36,11 @@ fn make_peer() -> PeerInfo {
         vec![9; 64],
         123456,
         "127.0.0.1:39001".to_string(),
-        Some(vec![1, 2, 3]),
+        PeerFields {
+            extra: Some(vec![1, 2, 3]),
+            announced: Some(1_700_000_000_000),
+            ..Default::default()
+        },
     )
 }
 
@@ -136,5 +143,34 @@ mod tests {
         assert!(task.is_done());
         assert!(task.is_completed());
     }
-}
 
+    #[test]
+    fn test_older_nodes() {
+        let peer = PeerInfo::builder("tcp://10.0.1.1:9200").build().unwrap();
+        assert!(peer.announced().is_some());
+        let task = PeerAnnounceTask::new(make_dht(), peer.clone(), -1);
+        assert_eq!(task.peer_for(version::ver()), Some(&peer));
+
+        // Nodes of the first version decode the peer as a 9-tuple and
+        // the announcement without its time.
+        let older = version::build("MK", 1);
+        let untimed = task.peer_for(older).expect("untimed peer");
+        assert!(untimed.announced().is_none());
+        assert!(untimed.is_valid());
+        let serde_cbor::Value::Array(fields) = serde_cbor::value::to_value(untimed).unwrap() else {
+            panic!("peer not encoded as a tuple");
+        };
+        assert_eq!(fields.len(), 9);
+
+        let req = AnnouncePeerRequest::new(untimed.clone(), 42, None);
+        let serde_cbor::Value::Map(fields) = serde_cbor::value::to_value(&req).unwrap() else {
+            panic!("request not encoded as a map");
+        };
+        assert!(!fields.contains_key(&serde_cbor::Value::Text("at".to_string())));
+
+        // The peers of others can't be signed again, older nodes are skipped.
+        let task = PeerAnnounceTask::new(make_dht(), make_peer(), -1);
+        assert!(task.peer_for(version::ver()).is_some());
+        assert!(task.peer_for(older).is_none());
+    }
+}
@@ -1,13 +1,19 @@
 use std::{
     rc::Rc,
     cell::RefCell,
+    time::Duration,
 };
 use crate::{
     Id,
     Network,
+    PeerInfo,
+    signature::KeyPair,
+    core::version,
 };
 use crate::dht::{
     dht::DHT,
+    eligible_peers::EligiblePeers,
+    lookup_result::Origins,
     task::{
         LookupTask,
         PeerLookupTask,
@@ -19,6 +25,10 @@ fn make_dht() -> Rc<RefCell<DHT>> {
     make_test_dht(Network::IPv4, "127.0.0.1")
 }
 
+fn from(node: &Id, age: Option<u64>, peer: &PeerInfo) -> Vec<(PeerInfo, Origins)> {
+    vec![(peer.without_private_key(), Origins::new(*node, age))]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,10 +37,199 @@ mod tests {
     fn test_default() {
         let target = Id::random();
         let dht    = make_dht();
-        let task   = PeerLookupTask::new(dht.clone(), target.clone(), 7, 3, true);
+        let task   = PeerLookupTask::new(dht.clone(), target.clone(), 7, 3, Vec::new(), None, true);
 
         assert_eq!(task.target(), &target);
         assert_eq!(task.candidate_size(), 0);
         assert!(task.result().is_empty());
     }
+
+    #[test]
+    fn test_newest_announcement_wins() {
+        let v1 = PeerInfo::builder("http://10.0.1.1:9200")
+            .with_fingerprint(7)
+            .build()
+            .unwrap();
+        let v2 = v1.update("http://10.0.1.1:9300", None, None).unwrap();
+        assert_eq!(v2.id(), v1.id());
+        assert_eq!(v2.sequence_number(), v1.sequence_number() + 1);
+
+        // Node A still holds the old announcement, node B the new one
+        let (a, b) = (Id::random(), Id::random());
+        let mut peers = EligiblePeers::new(v1.id().clone(), -1, 8);
+        assert!(peers.add(from(&b, None, &v2), false));
+        assert!(peers.add(from(&a, None, &v1), false));
+        assert_eq!(peers.peers(), vec![v2.without_private_key()]);
+
+        let mut peers = EligiblePeers::new(v1.id().clone(), -1, 8);
+        assert!(peers.add(from(&a, None, &v1), false));
+        assert!(peers.add(from(&b, None, &v2), false));
+        assert_eq!(peers.peers(), vec![v2.without_private_key()]);
+        assert_eq!(peers.results()[0].sources(), &[b]);
+    }
+
+    #[test]
+    fn test_newest_weight_wins() {
+        let kp = KeyPair::random();
+        let build = |seq: i32, weight: u8| PeerInfo::builder("http://10.0.1.1:9200")
+            .with_key(kp.clone())
+            .with_fingerprint(7)
+            .with_sequence_number(seq)
+            .with_weight(weight)
+            .build()
+            .unwrap();
+        let (v1, v2) = (build(1, 1), build(2, 4));
+
+        // Whichever responder comes first, the weight of the newer record is kept.
+        let (a, b) = (Id::random(), Id::random());
+        for (first, second) in [(&v1, &v2), (&v2, &v1)] {
+            let mut peers = EligiblePeers::new(v1.id().clone(), -1, 8);
+            assert!(peers.add(from(&a, None, first), false));
+            assert!(peers.add(from(&b, None, second), false));
+            let found = peers.peers();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].weight(), 4);
+        }
+    }
+
+    #[test]
+    fn test_peer_origins() {
+        let peer = PeerInfo::builder("http://10.0.1.1:9200").build().unwrap();
+        let (a, b, c) = (Id::random(), Id::random(), Id::random());
+
+        // The same announcement from several nodes, the youngest age wins.
+        let mut peers = EligiblePeers::new(peer.id().clone(), -1, 8);
+        assert!(peers.add(from(&a, Some(120), &peer), false));
+        assert!(peers.add(from(&b, None, &peer), false));
+        assert!(peers.add(from(&c, Some(30), &peer), false));
+        assert!(peers.add(from(&a, Some(10), &peer), false));
+
+        let results = peers.results();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].sources(), &[a, b, c]);
+        assert_eq!(results[0].min_age(), Some(Duration::from_secs(10)));
+        assert_eq!(results[0].peer(), &peer.without_private_key());
+
+        // Nodes running older versions report no age.
+        let mut peers = EligiblePeers::new(peer.id().clone(), -1, 8);
+        assert!(peers.add(from(&a, None, &peer), false));
+        assert_eq!(peers.results()[0].min_age(), None);
+    }
+
+    #[test]
+    fn test_observed_consensus() {
+        let peer = PeerInfo::builder("tcp://192.168.77.5:9200").build().unwrap();
+        let at = |endpoint: &str| peer.clone().with_observed(Some(endpoint.to_string()));
+        let (nat, forged) = ("tcp://203.0.113.7:9200", "tcp://198.51.100.9:9200");
+        let (a, b, c) = (Id::random(), Id::random(), Id::random());
+
+        // A lone responder is the majority of one.
+        let mut peers = EligiblePeers::new(peer.id().clone(), -1, 8);
+        assert!(peers.add(from(&a, None, &at(nat)), false));
+        assert_eq!(peers.peers()[0].observed_endpoint(), Some(nat));
+
+        // Outvoted, or without a majority, nothing is served.
+        assert!(peers.add(from(&b, None, &at(forged)), false));
+        assert_eq!(peers.peers()[0].observed_endpoint(), None);
+        assert!(peers.add(from(&c, None, &at(nat)), false));
+        assert_eq!(peers.results()[0].peer().observed_endpoint(), Some(nat));
+
+        // A responder votes once, however often it answers.
+        let mut peers = EligiblePeers::new(peer.id().clone(), -1, 8);
+        assert!(peers.add(from(&a, None, &at(nat)), false));
+        assert!(peers.add(from(&b, None, &at(forged)), false));
+        assert!(peers.add(from(&b, None, &at(forged)), false));
+        assert!(peers.add(from(&c, None, &peer), false));
+        assert_eq!(peers.peers()[0].observed_endpoint(), None);
+        assert!(peers.peers()[0].is_valid());
+    }
+
+    #[test]
+    fn test_tags_filtered() {
+        let kp = KeyPair::random();
+        let tls = PeerInfo::builder("https://10.0.1.1:8443")
+            .with_key(kp.clone())
+            .with_fingerprint(1)
+            .with_tags(&["proto/2", "tls"])
+            .build()
+            .unwrap();
+        let plain = PeerInfo::builder("http://10.0.1.1:8080")
+            .with_key(kp)
+            .with_fingerprint(2)
+            .with_tags(&["proto/1"])
+            .build()
+            .unwrap();
+        let node = Id::random();
+        let tags = vec!["tls".to_string()];
+
+        // Responders filtering by tags return the matching peer only
+        let mut peers = EligiblePeers::new(tls.id().clone(), -1, 2).with_tags(tags.clone());
+        assert!(peers.add(from(&node, None, &tls), false));
+        assert_eq!(peers.peers(), vec![tls.without_private_key()]);
+
+        // Older ones return all of them, filtered here
+        let mut peers = EligiblePeers::new(tls.id().clone(), -1, 2).with_tags(tags);
+        let mut all = from(&node, None, &tls);
+        all.extend(from(&node, None, &plain));
+        assert!(peers.add(all, false));
+        assert_eq!(peers.peers(), vec![tls.without_private_key()]);
+        assert!(!peers.reached_capacity());
+    }
+
+    #[test]
+    fn test_network_filtered() {
+        let kp = KeyPair::random();
+        let build = |endpoint: &str, fingerprint: u64| PeerInfo::builder(endpoint)
+            .with_key(kp.clone())
+            .with_fingerprint(fingerprint)
+            .build()
+            .unwrap();
+        let v4 = build("tcp://203.0.113.7:8080", 1);
+        let v6 = build("tcp://[2001:db8::7]:8080", 2);
+        let named = build("https://example.com", 3);
+        let node = Id::random();
+
+        let all = || {
+            let mut all = from(&node, None, &v4);
+            all.extend(from(&node, None, &v6));
+            all.extend(from(&node, None, &named));
+            all
+        };
+
+        // Older responders return all of them, filtered here.
+        for (network, expected) in [(Network::IPv4, &v4), (Network::IPv6, &v6)] {
+            let mut peers = EligiblePeers::new(v4.id().clone(), -1, 3).with_network(Some(network));
+            assert!(peers.add(all(), false));
+
+            let mut endpoints = peers.peers().iter().map(|p| p.endpoint().to_string()).collect::<Vec<_>>();
+            endpoints.sort();
+            assert_eq!(endpoints, vec![named.endpoint(), expected.endpoint()]);
+        }
+
+        let mut peers = EligiblePeers::new(v4.id().clone(), -1, 3);
+        assert!(peers.add(all(), false));
+        assert_eq!(peers.peers().len(), 3);
+    }
+
+    #[test]
+    fn test_decodable_by_older_nodes() {
+        let kp = KeyPair::random();
+        let builder = |fingerprint: u64| PeerInfo::builder("tcp://10.0.1.1:9200")
+            .with_key(kp.clone())
+            .with_fingerprint(fingerprint);
+        let timed = builder(1).build().unwrap();
+        let untimed = builder(2).build().unwrap().untimed().unwrap();
+        let tagged = builder(3).with_tags(&["tls"]).build().unwrap().untimed().unwrap();
+        let weighted = builder(4).with_weight(4).build().unwrap().untimed().unwrap();
+        let observed = builder(5).build().unwrap().untimed().unwrap()
+            .with_observed(Some("tcp://203.0.113.7:9200".to_string()));
+        let peers = vec![timed, untimed.clone(), tagged, weighted, observed.clone()];
+
+        assert_eq!(EligiblePeers::decodable_by(peers.clone(), version::ver()), peers);
+
+        // A requester of the first version only gets what it can decode.
+        let decodable = EligiblePeers::decodable_by(peers, version::build("MK", 1));
+        assert_eq!(decodable, vec![untimed, observed.with_observed(None)]);
+        assert!(decodable.iter().all(|p| p.is_valid()));
+    }
 }
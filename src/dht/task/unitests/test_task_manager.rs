@@ -0,0 +1,151 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    rc::Rc,
+    cell::RefCell,
+};
+
+use crate::Network;
+use crate::dht::{
+    dht::DHT,
+    task::{
+        Task,
+        TaskData,
+        task_manager::{TaskManager, ConcurrencyLimits},
+    },
+};
+use super::test_utils::make_test_dht;
+
+// Names of the started tasks, which are kept alive here as nothing else
+// holds them while they have no calls in flight.
+#[derive(Default)]
+struct Started {
+    names   : RefCell<Vec<String>>,
+    alive   : RefCell<Vec<Rc<RefCell<Box<dyn Task>>>>>,
+}
+
+// Stays running until canceled.
+struct IdleTask {
+    base_data   : TaskData,
+    dht         : Rc<RefCell<DHT>>,
+    started     : Rc<Started>,
+}
+
+impl Task for IdleTask {
+    fn data(&self) -> &TaskData {
+        &self.base_data
+    }
+
+    fn data_mut(&mut self) -> &mut TaskData {
+        &mut self.base_data
+    }
+
+    fn as_task(&self) -> &dyn Task {
+        self
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dht(&self) -> Rc<RefCell<DHT>> {
+        self.dht.clone()
+    }
+
+    fn prepare(&mut self) {
+        self.started.names.borrow_mut().push(self.task_name().to_string());
+        self.started.alive.borrow_mut().extend(self.cloned().upgrade());
+    }
+
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+fn idle_task(dht: &Rc<RefCell<DHT>>, started: &Rc<Started>, name: &str) -> Box<IdleTask> {
+    let mut task = Box::new(IdleTask {
+        base_data   : TaskData::new(),
+        dht         : dht.clone(),
+        started     : started.clone(),
+    });
+    task.with_name(name.into());
+    task
+}
+
+fn limits(max_active_tasks: usize) -> ConcurrencyLimits {
+    ConcurrencyLimits {
+        max_active_tasks,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_and_priority() {
+        let dht = make_test_dht(Network::IPv4, "127.0.0.1");
+        let started = Rc::new(Started::default());
+        let man = TaskManager::new(limits(2));
+
+        let mut ids = Vec::new();
+        for name in ["m1", "m2", "m3"] {
+            let task = idle_task(&dht, &started, name);
+            ids.push(task.task_id());
+            man.add_maintenance(task);
+        }
+        for name in ["u1", "u2"] {
+            let task = idle_task(&dht, &started, name);
+            ids.push(task.task_id());
+            man.add(task);
+        }
+        assert_eq!(*started.names.borrow(), ["m1", "m2"]);
+        assert_eq!(man.running(), 2);
+        assert_eq!(man.queued(), 3);
+        assert_eq!(man.active(), 5);
+
+        // Freed slots go to the application tasks first.
+        for id in [ids[0], ids[1], ids[3]] {
+            assert!(man.cancel(id));
+        }
+        assert_eq!(*started.names.borrow(), ["m1", "m2", "u1", "u2", "m3"]);
+        assert_eq!(man.running(), 2);
+        assert_eq!(man.queued(), 0);
+        assert_eq!(man.peak_running(), 2);
+
+        man.stop();
+    }
+
+    #[test]
+    fn test_maintenance_not_starved() {
+        let dht = make_test_dht(Network::IPv4, "127.0.0.1");
+        let started = Rc::new(Started::default());
+        let man = TaskManager::new(limits(1));
+        let mut ids = HashMap::new();
+
+        let mut add = |name: &str, maintenance: bool| {
+            let task = idle_task(&dht, &started, name);
+            ids.insert(name.to_string(), task.task_id());
+            match maintenance {
+                true => man.add_maintenance(task),
+                false => man.add(task),
+            }
+        };
+        add("u0", false);
+        add("m", true);
+        for i in 1..=6 {
+            add(&format!("u{i}"), false);
+        }
+
+        // Each cancel frees the only slot for the next task in line.
+        loop {
+            let last = started.names.borrow().last().cloned().unwrap();
+            if !man.cancel(ids[&last]) {
+                break;
+            }
+        }
+        assert_eq!(*started.names.borrow(), ["u0", "u1", "u2", "u3", "u4", "m", "u5", "u6"]);
+        assert_eq!(man.peak_running(), 1);
+    }
+}
@@ -2,16 +2,19 @@ use std::{
     rc::Rc,
     cell::RefCell,
     sync::{Arc, Mutex},
-    path::PathBuf,
+    path::Path,
 };
 use tokio::sync::mpsc;
 use crate::CryptoIdentity;
 use crate::Network;
+use crate::SystemClock;
 use crate::dht::{
     dht::DHT,
     dht_verticle::VerticleOptions,
     timer_client::{LocalTimerClient, LocalTimerCmd},
     token_manager::TokenManager,
+    data_layout::DataLayout,
+    node_event::EventLog,
     connection_status_listener::ConnectionStatusListener,
     storage::{
         data_storage::DataStorage,
@@ -28,7 +31,7 @@ impl ConnectionStatusListener for NoopConnectionStatusListener {}
 pub(super) fn make_test_dht(network: Network, host: &str) -> Rc<RefCell<DHT>> {
     let identity  = Arc::new(CryptoIdentity::new());
     let storage: Arc<Mutex<dyn DataStorage>> = Arc::new(Mutex::new(SqliteStorage::new()));
-    let token_man = Arc::new(TokenManager::new());
+    let token_man = Arc::new(TokenManager::new(Arc::new(SystemClock)));
     let listener: Arc<dyn ConnectionStatusListener> = Arc::new(NoopConnectionStatusListener);
 
     let options = VerticleOptions::default()
@@ -36,7 +39,8 @@ pub(super) fn make_test_dht(network: Network, host: &str) -> Rc<RefCell<DHT>> {
         .with_storage(storage)
         .with_tokenman(token_man)
         .with_listener(listener)
-        .with_datadir(PathBuf::from("."));
+        .with_layout(DataLayout::new(Path::new("."), "test"))
+        .with_event_log(EventLog::new(64));
 
     let (tx, _rx) = mpsc::unbounded_channel::<LocalTimerCmd>();
     let timer_client = Rc::new(LocalTimerClient::new(tx));
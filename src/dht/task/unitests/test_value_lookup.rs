@@ -5,10 +5,18 @@ use std::{
 use crate::{
     Id,
     Network,
+    NodeInfo,
+    Value,
+    signature::KeyPair,
+    core::SignedBuilder,
 };
 use crate::dht::{
+    NodeEventKind,
     dht::DHT,
+    msg::msg,
+    rpc::RpcCall,
     task::{
+        task::Task,
         lookup_task::LookupTask,
         value_lookup::ValueLookupTask,
     },
@@ -19,6 +27,33 @@ fn make_dht() -> Rc<RefCell<DHT>> {
     make_test_dht(Network::IPv4, "127.0.0.1")
 }
 
+fn make_node(port: u16) -> NodeInfo {
+    NodeInfo::new(Id::random(), format!("203.0.113.9:{port}").parse().unwrap())
+}
+
+// The value under the key of `value`, with a higher sequence number and
+// the signature of the original data.
+fn forge(value: &Value) -> Value {
+    Value::packed(
+        value.public_key().cloned(),
+        value.recipient().cloned(),
+        value.nonce().cloned(),
+        value.signature().map(|v| v.to_vec()),
+        b"forged value".to_vec(),
+        value.sequence_number() + 1,
+    )
+}
+
+// A call to `node` for the value of `target`, answered with `value`.
+fn answered(node: &NodeInfo, target: &Id, value: Value) -> RpcCall {
+    let mut call = RpcCall::new(node.clone(), msg::find_value_request(*target, true, false, -1));
+    let mut rsp = msg::find_value_response(call.txid(), value, None);
+    rsp.set_nodeid(*node.id());
+    rsp.set_remote(*node.id(), *node.socket_addr());
+    call.respond(Rc::new(rsp));
+    call
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -33,4 +68,34 @@ mod tests {
         assert_eq!(task.candidate_size(), 0);
         assert!(task.result().is_none());
     }
+
+    #[test]
+    fn test_forged_value() {
+        let kp = KeyPair::random();
+        let value = SignedBuilder::new(b"honest value")
+            .with_keypair(&kp)
+            .with_sequence_number(3)
+            .build()
+            .unwrap();
+        let target = value.id();
+        let (hostile, honest) = (make_node(39001), make_node(39002));
+
+        let dht = make_dht();
+        let mut task = ValueLookupTask::new(dht.clone(), target, -1, true);
+
+        // The forgery arrives first and outbids the honest value.
+        Task::call_responded(&mut task, &answered(&hostile, &target, forge(&value)));
+        assert!(task.result().is_none());
+        assert_eq!(task.strikes(hostile.id()), 1);
+
+        Task::call_responded(&mut task, &answered(&honest, &target, value.clone()));
+        let result = task.result().expect("Should have kept the honest value");
+        assert_eq!(result.value().data(), value.data());
+        assert_eq!(result.value().sequence_number(), 3);
+        assert_eq!(result.sources(), &[*honest.id()]);
+        assert_eq!(task.strikes(honest.id()), 0);
+
+        let rejected = NodeEventKind::ValueRejected { from: *hostile.id(), target };
+        assert!(dht.borrow().events().recent(16).iter().any(|e| e.kind() == &rejected));
+    }
 }
@@ -2,14 +2,15 @@ use std::{
     any::Any,
     rc::Rc,
     cell::RefCell,
-    collections::VecDeque,
+    collections::{HashSet, VecDeque},
 };
 
-use crate::Value;
+use crate::{Id, Network, Value};
 use crate::dht::{
     dht::DHT,
     handler::Handler,
-    msg::msg,
+    msg::{LookupResponse, msg::{self, Body}, error::PROTOCOL_ERROR},
+    rpc::{RpcCall, Target, rpc_target::NodeInfoLike},
     task::{
         Task, TaskData,
         ClosestSet,
@@ -25,6 +26,12 @@ pub(crate) struct ValueAnnounceTask {
     todo: Rc<RefCell<VecDeque<Rc<RefCell<CandidateNode>>>>>,
     value: Value,
     expected_seq: i32,
+    // Nodes that stored the value, the others rejected it or never answered.
+    acks: usize,
+    // Nodes to fetch a token from before storing, and those that
+    // rejected a token once.
+    refetch: HashSet<Id>,
+    rejected: HashSet<Id>,
 
     dht: Rc<RefCell<DHT>>
 }
@@ -41,10 +48,27 @@ impl ValueAnnounceTask {
                 VecDeque::with_capacity(MAX_TODO_ENTRIES))),
             value,
             expected_seq,
+            acks: 0,
+            refetch: HashSet::new(),
+            rejected: HashSet::new(),
             dht,
         }
     }
 
+    // The closest nodes with their cached tokens, those without one are
+    // asked for a token first.
+    pub(crate) fn with_cached(&mut self, closest: ClosestSet) -> &Self {
+        self.refetch.extend(closest.entries().iter()
+            .filter(|cn| cn.borrow().token() == 0)
+            .map(|cn| *cn.borrow().id())
+        );
+        self.with_closest(closest)
+    }
+
+    pub(crate) fn acks(&self) -> usize {
+        self.acks
+    }
+
     pub(crate) fn with_closest(&self, closest: ClosestSet) -> &Self {
         let mut borrowed_todo = self.todo.borrow_mut();
         let mut entries = closest.entries();
@@ -94,6 +118,17 @@ impl Task for ValueAnnounceTask {
             };
 
             let token = cn.borrow().token();
+            if token == 0 && self.refetch.remove(cn.borrow().id()) {
+                let want4 = self.network() == Network::IPv4;
+                let msg = msg::find_node_request(self.value.id(), want4, !want4, Some(true));
+
+                let cloned_todo = self.todo.clone();
+                let handler = Handler::new(move |_| {
+                    cloned_todo.borrow_mut().pop_front();
+                });
+                self.send_call(cn.into(), msg, Some(handler));
+                continue;
+            }
             if token == 0 {
                 self.todo.borrow_mut().pop_front();
                 continue;
@@ -113,6 +148,52 @@ impl Task for ValueAnnounceTask {
         }
     }
 
+    fn call_responded(&mut self, call: &RpcCall) {
+        let rsp = call.rsp();
+        let Some(Body::FindNodeResponse(body)) = rsp.as_ref().and_then(|m| m.body()) else {
+            if !call.nodeid_mismatched() {
+                self.acks += 1;
+            }
+            return;
+        };
+        let Target::Candidate(cn) = call.target() else {
+            return;
+        };
+        if body.token() == 0 {
+            return;
+        }
+
+        cn.borrow_mut().set_token(body.token());
+        self.dht.borrow().token_cache().borrow_mut()
+            .put(&self.value.id(), cn.borrow().ni(), body.token());
+        self.todo.borrow_mut().push_back(cn.clone());
+    }
+
+    fn call_error(&mut self, call: &RpcCall) {
+        let Target::Candidate(cn) = call.target() else {
+            return;
+        };
+        let rsp = call.rsp();
+        let Some(Body::Error(err)) = rsp.as_ref().and_then(|m| m.body()) else {
+            return;
+        };
+        let id = *cn.borrow().id();
+        if err.code() != PROTOCOL_ERROR || !self.rejected.insert(id) {
+            return;
+        }
+
+        // The token expired or the node restarted, fetch it once again.
+        log::debug!("{}#{} token rejected by {}, refreshing",
+            self.task_name(),
+            self.task_id(),
+            id
+        );
+        self.dht.borrow().token_cache().borrow_mut().invalidate(&self.value.id(), &id);
+        cn.borrow_mut().set_token(0);
+        self.refetch.insert(id);
+        self.todo.borrow_mut().push_back(cn.clone());
+    }
+
     fn is_done(&self) -> bool {
         self.todo.borrow().is_empty() &&
             self.data().is_done()
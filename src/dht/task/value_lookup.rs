@@ -2,12 +2,14 @@ use std::{
     any::Any,
     rc::Rc,
     cell::RefCell,
+    collections::HashMap,
 };
-use crate::{Id, Value};
+use crate::Id;
 use crate::dht::{
     dht::DHT,
     handler::Handler,
-    eligible_value::EligibleValue,
+    eligible_value::{self, EligibleValue},
+    lookup_result::{Origins, ValueResult},
     rpc::RpcCall,
     msg::{msg, LookupResponse, Body},
     routing::{
@@ -26,6 +28,7 @@ pub(crate) struct ValueLookupTask {
     lookup_data: LookupTaskData,
 
     result  : EligibleValue,
+    strikes : HashMap<Id, u32>,
     dht     : Rc<RefCell<DHT>>,
 }
 
@@ -40,12 +43,36 @@ impl ValueLookupTask {
             base_data   : TaskData::new(),
             lookup_data : LookupTaskData::new(target, done_on_eligible_result),
             result      : EligibleValue::new(target, expected_seq),
+            strikes     : HashMap::new(),
             dht         : dht.clone(),
         }
     }
 
-    pub(crate) fn result(&self) -> Option<Value> {
-        self.result.value()
+    pub(crate) fn result(&self) -> Option<ValueResult> {
+        self.result.result()
+    }
+
+    // The forged values a responder returned during this lookup.
+    pub(crate) fn strikes(&self, id: &Id) -> u32 {
+        self.strikes.get(id).copied().unwrap_or(0)
+    }
+
+    // The responder no longer counts among the closest nodes, so the
+    // lookup goes on to the next ones.
+    fn strike(&mut self, from: Id, reason: &str) {
+        LookupTask::remove_closest(self, &from);
+        let strikes = self.strikes.entry(from).or_default();
+        *strikes += 1;
+
+        log::warn!("{}#{} dropped a forged value of {} from {}: {}, strike {}",
+            self.task_name(),
+            self.task_id(),
+            self.target(),
+            from,
+            reason,
+            self.strikes(&from)
+        );
+        self.dht.borrow().on_forged_value(&from, self.target());
     }
 }
 
@@ -97,6 +124,7 @@ impl Task for ValueLookupTask {
                 KBucket::MAX_ENTRIES *3
             );
             kns.set_filter(|v| v.eligible_for_local_lookup());
+            kns.set_prefer_low_rtt(self.dht.borrow().prefer_low_rtt());
             kns.fill();
             kns.into()
         };
@@ -149,7 +177,15 @@ impl Task for ValueLookupTask {
         };
 
         if let Some(value) = body.value() {
-            if !self.result.update(value.clone(), false) {
+            // Checked before anything else sees it, a forged value neither
+            // competes for the result nor ends the lookup.
+            if let Err(reason) = eligible_value::verify(self.target(), value) {
+                self.strike(call.target_id(), reason);
+                return;
+            }
+
+            let origins = Origins::new(call.target_id(), body.age());
+            if !self.result.update(value.clone(), false, origins) {
                 return;
             }
             if self.result.is_empty() {
@@ -184,6 +220,10 @@ impl Task for ValueLookupTask {
         LookupTask::call_timeout(self, call);
     }
 
+    fn progressed(&self) {
+        LookupTask::report_progress(self);
+    }
+
     fn is_done(&self) -> bool {
         LookupTask::is_done(self)
     }
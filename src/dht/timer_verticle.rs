@@ -1,9 +1,10 @@
-use std::thread::JoinHandle;
+use std::thread::{self, JoinHandle};
 use tokio::{
-    runtime,
-    sync::mpsc::{self, UnboundedSender}
+    runtime::{self, Handle},
+    sync::mpsc::{self, UnboundedSender},
+    task,
 };
-use crate::errors::Result;
+use crate::errors::{Result, StateError};
 use crate::dht::{
     handler::AsyncHandler,
     timer_manager::AsyncTimerManager as TimerManager,
@@ -23,11 +24,11 @@ pub(crate) struct VerticleClient {
 impl VerticleClient {
     pub(crate) fn new(
         sender: UnboundedSender<TimerCmd>,
-        handle: JoinHandle<()>
+        handle: Option<JoinHandle<()>>
     ) -> Self {
         Self {
             timer_client: TimerClient::new(sender),
-            handle: Some(handle),
+            handle,
         }
     }
 
@@ -42,8 +43,9 @@ impl VerticleClient {
     pub(crate) async fn stop(&mut self) -> Result<()> {
         self.timer_client.stop().await?;
 
+        // Never block the caller's runtime threads on the join.
         if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
+            let _ = task::spawn_blocking(move || handle.join()).await;
         }
         Ok(())
     }
@@ -102,22 +104,59 @@ impl Verticle {
 }
 
 #[derive(Default)]
-pub(crate) struct VerticleOptions {}
+pub(crate) struct VerticleOptions {
+    runtime: Option<Handle>,
+    #[cfg(feature = "testing")]
+    local: bool,
+}
+
+impl VerticleOptions {
+    pub(crate) fn with_runtime(mut self, runtime: Option<Handle>) -> Self {
+        self.runtime = runtime;
+        self
+    }
+
+    // Runs the timers on the caller's LocalSet instead of a thread of their
+    // own, for the nodes of a simulation.
+    #[cfg(feature = "testing")]
+    pub(crate) fn with_local(mut self, local: bool) -> Self {
+        self.local = local;
+        self
+    }
+}
 
-pub(crate) fn deploy(option: VerticleOptions) -> Result<VerticleClient> {
+pub(crate) fn deploy(mut option: VerticleOptions) -> Result<VerticleClient> {
     let (sender, receiver) = mpsc::unbounded_channel::<TimerCmd>();
-    let handle = std::thread::spawn(move || {
-        let rt = runtime::Builder::new_current_thread()
-            .enable_time()
-            .enable_io()
-            .build()
-            .expect("dht verticle runtime should build");
+    #[cfg(feature = "testing")]
+    if option.local {
+        task::spawn_local(async move {
+            Verticle::new(option, receiver).run_loop().await;
+        });
+        return Ok(VerticleClient::new(sender, None));
+    }
 
-        let local = tokio::task::LocalSet::new();
-        rt.block_on(local.run_until(async move {
+    let runtime = option.runtime.take();
+    let handle = thread::Builder::new().name("boson-timer".into()).spawn(move || {
+        let local = task::LocalSet::new();
+        let run = local.run_until(async move {
             let mut vert = Verticle::new(option, receiver);
             vert.run_loop().await;
-        }));
-    });
-    Ok(VerticleClient::new(sender, handle))
+        });
+        block_on(runtime, run);
+    }).map_err(|e| StateError::new(format!("Spawning timer verticle thread error: {e}")))?;
+    Ok(VerticleClient::new(sender, Some(handle)))
+}
+
+// Drives a verticle on its dedicated thread, with the caller's runtime as
+// the reactor when given, otherwise with a runtime of its own.
+pub(crate) fn block_on<F: std::future::Future>(runtime: Option<Handle>, future: F) -> F::Output {
+    match runtime {
+        Some(handle) => handle.block_on(future),
+        None => runtime::Builder::new_current_thread()
+            .enable_time()
+            .enable_io()
+            .build()
+            .expect("dht verticle runtime should build")
+            .block_on(future),
+    }
 }
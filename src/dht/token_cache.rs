@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Id, NodeInfo, Clock};
+use crate::dht::{
+    stats::TokenCacheStats,
+    token_manager::TokenManager,
+};
+
+struct Entry {
+    node    : NodeInfo,
+    token   : i32,
+    expires : SystemTime,
+}
+
+// Write tokens other nodes handed out in their lookup responses, by the
+// target and the node, so announcing the same target again within their
+// validity needs no lookup to collect them.
+pub(crate) struct TokenCache {
+    clock   : Arc<dyn Clock>,
+    targets : HashMap<Id, HashMap<Id, Entry>>,
+    hits    : u64,
+    misses  : u64,
+}
+
+impl TokenCache {
+    // A token is accepted for one rotation period of the node at least,
+    // whenever in its current period the node handed it out.
+    pub(crate) const VALIDITY: Duration = Duration::from_millis(TokenManager::TOKEN_TIMEOUT);
+    // Tokens this close to expiry are fetched again before used.
+    pub(crate) const REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+    const MAX_TARGETS: usize = 1024;
+
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            targets : HashMap::new(),
+            hits    : 0,
+            misses  : 0,
+        }
+    }
+
+    pub(crate) fn put(&mut self, target: &Id, node: NodeInfo, token: i32) {
+        if token == 0 {
+            return;
+        }
+        if self.targets.len() >= Self::MAX_TARGETS && !self.targets.contains_key(target) {
+            self.prune();
+            if self.targets.len() >= Self::MAX_TARGETS {
+                return;
+            }
+        }
+
+        let expires = self.clock.now() + Self::VALIDITY;
+        self.targets.entry(*target)
+            .or_default()
+            .insert(*node.id(), Entry { node, token, expires });
+    }
+
+    pub(crate) fn invalidate(&mut self, target: &Id, node: &Id) {
+        if let Some(nodes) = self.targets.get_mut(target) {
+            nodes.remove(node);
+            if nodes.is_empty() {
+                self.targets.remove(target);
+            }
+        }
+    }
+
+    // The nodes with a token cached for the target, those to be fetched
+    // again with the token 0. None when no token is fresh, the target is
+    // to be looked up then.
+    pub(crate) fn lookup(&mut self, target: &Id) -> Option<Vec<(NodeInfo, i32)>> {
+        let now = self.clock.now();
+        let found = self.targets.get_mut(target).map(|nodes| {
+            nodes.retain(|_, entry| entry.expires > now);
+            nodes.values().map(|entry| match entry.expires > now + Self::REFRESH_MARGIN {
+                true  => (entry.node.clone(), entry.token),
+                false => (entry.node.clone(), 0),
+            }).collect::<Vec<_>>()
+        }).filter(|nodes| nodes.iter().any(|(_, token)| *token != 0));
+
+        match found.is_some() {
+            true  => self.hits += 1,
+            false => self.misses += 1,
+        }
+        found
+    }
+
+    pub(crate) fn stats(&self) -> TokenCacheStats {
+        TokenCacheStats {
+            entries : self.targets.values().map(|nodes| nodes.len() as u64).sum(),
+            hits    : self.hits,
+            misses  : self.misses,
+        }
+    }
+
+    fn prune(&mut self) {
+        let now = self.clock.now();
+        self.targets.retain(|_, nodes| {
+            nodes.retain(|_, entry| entry.expires > now);
+            !nodes.is_empty()
+        });
+    }
+}
@@ -1,13 +1,14 @@
 use std::{
     mem,
     net::{IpAddr, SocketAddr},
-    sync::Mutex,
+    sync::{Arc, Mutex},
     time::SystemTime
 };
 use sha2::{Digest, Sha256};
-use crate::Id;
+use crate::{Id, Clock};
 
 pub(crate) struct TokenManager {
+    clock: Arc<dyn Clock>,
     session_secret: [u8; 32],
     timestamp: Mutex<SystemTime>,
     previous_timestamp: Mutex<SystemTime>,
@@ -16,21 +17,23 @@ pub(crate) struct TokenManager {
 impl TokenManager {
     pub(crate) const TOKEN_TIMEOUT: u64 = 5 * 60 * 1000; // 5 minutes
 
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
         let session_secret = crate::random_array::<32>();
+        let now = clock.now();
 
         Self {
+            clock,
             session_secret,
-            timestamp: Mutex::new(SystemTime::now()),
-            previous_timestamp: Mutex::new(SystemTime::now()),
+            timestamp: Mutex::new(now),
+            previous_timestamp: Mutex::new(now),
         }
     }
 
     pub(crate) fn update_token_timestamp(&self) {
         let mut tm = crate::locked!(self.timestamp);
-        if crate::elapsed_ms!(tm) > Self::TOKEN_TIMEOUT as u128 {
+        if self.clock.elapsed_ms(*tm) > Self::TOKEN_TIMEOUT as u128 {
             *self.previous_timestamp.lock().unwrap() = *tm;
-            *tm = SystemTime::now();
+            *tm = self.clock.now();
         }
     }
 
@@ -0,0 +1,62 @@
+use std::time::{Duration, SystemTime};
+
+use crate::PeerInfo;
+use crate::dht::announcement::{AnnouncementPolicy, Verdict};
+
+const NOW: u64 = 1_700_000_000_000;
+const HOUR: u64 = 60 * 60 * 1000;
+
+fn announced_at(ms: u64) -> PeerInfo {
+    PeerInfo::builder("tcp://10.0.0.1:9000")
+        .with_announced_time(SystemTime::UNIX_EPOCH + Duration::from_millis(ms))
+        .build()
+        .unwrap()
+}
+
+fn policy(required: bool) -> AnnouncementPolicy {
+    AnnouncementPolicy::new(Duration::from_secs(2 * 60 * 60), required)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_announcement() {
+        // Captured a month ago, still carrying a valid signature.
+        let replayed = announced_at(NOW - 30 * 24 * HOUR).without_private_key();
+        assert!(replayed.is_valid());
+        assert!(matches!(policy(false).check(&replayed, NOW), Verdict::Rejected(_)));
+
+        assert_eq!(policy(false).check(&announced_at(NOW), NOW), Verdict::Accepted);
+    }
+
+    #[test]
+    fn test_skew_window() {
+        let policy = policy(false);
+        for (announced, accepted) in [
+            (NOW - 2 * HOUR, true),
+            (NOW - 2 * HOUR - 1, false),
+            (NOW + 2 * HOUR, true),
+            (NOW + 2 * HOUR + 1, false),
+            (NOW - HOUR, true),
+            (NOW + HOUR, true),
+        ] {
+            let verdict = policy.check(&announced_at(announced), NOW);
+            assert_eq!(verdict == Verdict::Accepted, accepted, "{}", announced as i64 - NOW as i64);
+        }
+
+        let narrow = AnnouncementPolicy::new(Duration::from_secs(60), false);
+        assert!(matches!(narrow.check(&announced_at(NOW - HOUR), NOW), Verdict::Rejected(_)));
+        assert_eq!(narrow.check(&announced_at(NOW - 60_000), NOW), Verdict::Accepted);
+    }
+
+    #[test]
+    fn test_untimed() {
+        let peer = announced_at(NOW).untimed().unwrap();
+        assert!(peer.is_valid());
+        assert_eq!(policy(false).check(&peer, NOW), Verdict::Untimed);
+        assert!(matches!(policy(true).check(&peer, NOW), Verdict::Rejected(_)));
+        assert_eq!(AnnouncementPolicy::default().check(&peer, NOW), Verdict::Untimed);
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    Id,
+    ManualClock,
+    dht::{
+        blocklist::{Blocklist, BlockTarget, BlockReason, StrikePolicy},
+        routing::{
+            kbucket_entry::KBucketEntry,
+            routing_table::RoutingTable,
+        },
+    },
+};
+
+fn policy() -> StrikePolicy {
+    StrikePolicy {
+        token_strikes   : 3,
+        value_strikes   : 2,
+        duration        : Duration::from_secs(60 * 60),
+    }
+}
+
+fn blocklist_path() -> PathBuf {
+    let dir = PathBuf::from(format!("/tmp/blocklist_{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("blocklist")
+}
+
+fn make_entry(id: Id, port: u16) -> KBucketEntry {
+    let addr = format!("10.0.0.{}:{port}", port % 200 + 1).parse::<SocketAddr>().unwrap();
+    let mut entry = KBucketEntry::new(id, addr);
+    entry.on_responded(20);
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_and_expire() {
+        let clock = Arc::new(ManualClock::default());
+        let blocklist = Blocklist::new(clock.clone(), policy());
+        let ip: IpAddr = "10.1.2.3".parse().unwrap();
+        let id = Id::random();
+
+        let entry = blocklist.block(BlockTarget::Address(ip), BlockReason::Manual, Some(Duration::from_secs(60)));
+        assert_eq!(entry.expires(), Some(entry.since() + Duration::from_secs(60)));
+        clock.advance(Duration::from_secs(1));
+        blocklist.block(BlockTarget::Id(id), BlockReason::Manual, None);
+
+        assert!(blocklist.drops_addr(&ip));
+        assert!(!blocklist.drops_addr(&"10.1.2.4".parse().unwrap()));
+        assert!(blocklist.drops_id(&id));
+        assert!(!blocklist.drops_id(&Id::random()));
+
+        let entries = blocklist.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].target(), &BlockTarget::Address(ip));
+        assert_eq!(entries[1].target(), &BlockTarget::Id(id));
+        assert_eq!(entries[1].expires(), None);
+
+        // Only the timed block runs out.
+        clock.advance(Duration::from_secs(60));
+        assert!(!blocklist.drops_addr(&ip));
+        assert!(blocklist.drops_id(&id));
+        assert_eq!(blocklist.entries().len(), 1);
+
+        assert!(blocklist.unblock(&BlockTarget::Id(id)));
+        assert!(!blocklist.unblock(&BlockTarget::Id(id)));
+        assert!(!blocklist.drops_id(&id));
+
+        let stats = blocklist.stats();
+        assert_eq!(stats.entries(), 0);
+        assert_eq!(stats.dropped_by_address(), 1);
+        assert_eq!(stats.dropped_by_id(), 2);
+    }
+
+    #[test]
+    fn test_strikes() {
+        let clock = Arc::new(ManualClock::default());
+        let blocklist = Blocklist::new(clock.clone(), policy());
+        let id = Id::random();
+
+        // Each reason counts on its own.
+        assert!(!blocklist.strike(&id, BlockReason::InvalidToken));
+        assert!(!blocklist.strike(&id, BlockReason::InvalidToken));
+        assert!(!blocklist.strike(&id, BlockReason::InvalidValue));
+        assert!(blocklist.strike(&id, BlockReason::InvalidToken));
+        assert!(blocklist.drops_id(&id));
+
+        let entry = &blocklist.entries()[0];
+        assert_eq!(entry.reason(), BlockReason::InvalidToken);
+        assert_eq!(entry.expires(), Some(entry.since() + policy().duration));
+
+        // Strikes further apart than the window start over.
+        let other = Id::random();
+        assert!(!blocklist.strike(&other, BlockReason::InvalidValue));
+        clock.advance(Blocklist::STRIKE_WINDOW + Duration::from_secs(1));
+        assert!(!blocklist.strike(&other, BlockReason::InvalidValue));
+        assert!(blocklist.strike(&other, BlockReason::InvalidValue));
+        assert_eq!(blocklist.stats().auto_blocks(), 2);
+
+        // The block runs out after the policy duration.
+        clock.advance(policy().duration - Blocklist::STRIKE_WINDOW);
+        assert!(!blocklist.drops_id(&id));
+        assert!(blocklist.drops_id(&other));
+
+        let disabled = Blocklist::new(clock.clone(), StrikePolicy {
+            token_strikes: 0,
+            ..policy()
+        });
+        for _ in 0..10 {
+            assert!(!disabled.strike(&id, BlockReason::InvalidToken));
+        }
+        assert!(!disabled.strike(&id, BlockReason::Manual));
+        assert!(disabled.entries().is_empty());
+    }
+
+    #[test]
+    fn test_persistence() {
+        let clock = Arc::new(ManualClock::default());
+        let path = blocklist_path();
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        let id = Id::random();
+
+        let blocklist = Blocklist::open(path.clone(), clock.clone(), policy());
+        assert!(blocklist.entries().is_empty());
+        blocklist.block(BlockTarget::Address(ip), BlockReason::Manual, None);
+        clock.advance(Duration::from_secs(1));
+        blocklist.block(BlockTarget::Id(id), BlockReason::InvalidValue, Some(Duration::from_secs(60)));
+        let saved = blocklist.entries();
+        drop(blocklist);
+
+        let reopened = Blocklist::open(path.clone(), clock.clone(), policy());
+        assert_eq!(reopened.entries(), saved);
+
+        // Blocks expired meanwhile are left behind.
+        clock.advance(Duration::from_secs(60));
+        let reopened = Blocklist::open(path.clone(), clock.clone(), policy());
+        assert_eq!(reopened.entries(), saved[..1]);
+
+        // An unreadable file starts empty.
+        fs::write(&path, b"garbage").unwrap();
+        assert!(Blocklist::open(path.clone(), clock, policy()).entries().is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_routing_table() {
+        let clock = Arc::new(ManualClock::default());
+        let blocklist = Arc::new(Blocklist::new(clock.clone(), policy()));
+        let mut rt = RoutingTable::new(Id::random());
+        rt.set_blocklist(blocklist.clone());
+
+        let entries = (0..4).map(|i| make_entry(Id::random(), 31000 + i)).collect::<Vec<_>>();
+        blocklist.block(BlockTarget::Id(*entries[0].id()), BlockReason::Manual, None);
+        for entry in entries.iter().cloned() {
+            rt.put(entry);
+        }
+        assert!(!rt.contains(entries[0].id()));
+        assert_eq!(rt.number_of_entries(), 3);
+
+        // Blocked after they got in, by id or by address.
+        blocklist.block(BlockTarget::Id(*entries[1].id()), BlockReason::Manual, None);
+        blocklist.block(BlockTarget::Address(entries[2].socket_addr().ip()), BlockReason::Manual, None);
+        assert_eq!(rt.evict_blocked(), 2);
+        assert_eq!(rt.evict_blocked(), 0);
+        assert!(rt.contains(entries[3].id()));
+        assert_eq!(rt.number_of_entries(), 1);
+
+        let stats = blocklist.stats();
+        assert_eq!(stats.refused_entries(), 1);
+        assert_eq!(stats.evicted_entries(), 2);
+
+        // Back in once the block is lifted.
+        blocklist.unblock(&BlockTarget::Id(*entries[0].id()));
+        rt.put(entries[0].clone());
+        assert!(rt.contains(entries[0].id()));
+    }
+}
@@ -1,10 +1,19 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::Duration,
+};
 use crate::{
     Identity,
     core::CryptoIdentity,
-    dht::cached_identity::CachedIdentity,
+    dht::{
+        cached_identity::CachedIdentity,
+        crypto_cache::CryptoCache,
+    },
 };
 
+fn cached(identity: CryptoIdentity) -> CachedIdentity {
+    CachedIdentity::new(identity, CryptoCache::new(16, Duration::ZERO))
+}
 
 #[cfg(test)]
 mod tests {
@@ -14,7 +23,7 @@ mod tests {
     fn test_identity() {
         let identity = CryptoIdentity::new();
         let expected_id = identity.id().clone();
-        let cached = CachedIdentity::new(identity);
+        let cached = cached(identity);
 
         assert_eq!(cached.id(), &expected_id);
 
@@ -25,7 +34,7 @@ mod tests {
 
     #[test]
     fn test_context_with_same_key() {
-        let cached = CachedIdentity::new(CryptoIdentity::new());
+        let cached = cached(CryptoIdentity::new());
         let peer_id = CryptoIdentity::new().id().clone();
 
         let first = cached.context(&peer_id);
@@ -37,7 +46,7 @@ mod tests {
 
     #[test]
     fn test_context_different_keys() {
-        let cached = CachedIdentity::new(CryptoIdentity::new());
+        let cached = cached(CryptoIdentity::new());
         let peer_a = CryptoIdentity::new().id().clone();
         let peer_b = CryptoIdentity::new().id().clone();
 
@@ -51,7 +60,7 @@ mod tests {
 
     #[test]
     fn test_clear_cache() {
-        let cached = CachedIdentity::new(CryptoIdentity::new());
+        let cached = cached(CryptoIdentity::new());
         let peer_id = CryptoIdentity::new().id().clone();
 
         let first = cached.context(&peer_id);
@@ -64,7 +73,7 @@ mod tests {
 
     #[test]
     fn test_sign_and_verify() {
-        let cached = CachedIdentity::new(CryptoIdentity::new());
+        let cached = cached(CryptoIdentity::new());
         let data = b"Hello, Boson!";
 
         let sig = cached.sign_into(data).unwrap();
@@ -74,7 +83,7 @@ mod tests {
 
     #[test]
     fn test_sign_tampered_data() {
-        let cached = CachedIdentity::new(CryptoIdentity::new());
+        let cached = cached(CryptoIdentity::new());
         let data = b"Hello, Boson!";
         let tampered = b"Hello, World!";
 
@@ -85,8 +94,8 @@ mod tests {
 
     #[test]
     fn test_sign_wrong_identity() {
-        let alice = CachedIdentity::new(CryptoIdentity::new());
-        let bob   = CachedIdentity::new(CryptoIdentity::new());
+        let alice = cached(CryptoIdentity::new());
+        let bob   = cached(CryptoIdentity::new());
         let data  = b"Hello, Boson!";
 
         let sig = alice.sign_into(data).unwrap();
@@ -97,8 +106,8 @@ mod tests {
 
     #[test]
     fn test_encrypt_decrypt_roundtrip() {
-        let alice = CachedIdentity::new(CryptoIdentity::new());
-        let bob   = CachedIdentity::new(CryptoIdentity::new());
+        let alice = cached(CryptoIdentity::new());
+        let bob   = cached(CryptoIdentity::new());
         let plain = b"Hello, Boson!";
 
         let cipher = alice.encrypt_into(bob.id(), plain).unwrap();
@@ -109,8 +118,8 @@ mod tests {
 
     #[test]
     fn test_encrypt_multiple_messages() {
-        let alice = CachedIdentity::new(CryptoIdentity::new());
-        let bob   = CachedIdentity::new(CryptoIdentity::new());
+        let alice = cached(CryptoIdentity::new());
+        let bob   = cached(CryptoIdentity::new());
 
         for i in 0u8..5 {
             let plain = vec![i; 32];
@@ -0,0 +1,109 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Id, Clock, ManualClock};
+use crate::dht::clock_skew::{ClockSkew, CompensatedClock, SkewChange};
+
+const NOW: u64 = 1_700_000_000_000;
+const MINUTE: i64 = 60 * 1000;
+
+// The time a node `offset` milliseconds ahead of the local clock tells.
+fn remote(offset: i64) -> u64 {
+    (NOW as i64 + offset) as u64 / 1000
+}
+
+fn skew(compensate: bool) -> ClockSkew {
+    ClockSkew::new(Duration::from_secs(5 * 60), compensate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let skew = skew(false);
+        assert_eq!(skew.record(&Id::random(), remote(-40 * MINUTE), NOW), None);
+        // A single node is not enough to tell.
+        assert_eq!(skew.estimate(), None);
+
+        assert!(matches!(skew.record(&Id::random(), remote(-40 * MINUTE), NOW),
+            Some(SkewChange::Exceeded(_))));
+        let estimate = skew.estimate().unwrap();
+        assert!((estimate + 40 * MINUTE).abs() <= 1000);
+        assert_eq!(skew.nodes(), 2);
+    }
+
+    #[test]
+    fn test_outliers() {
+        let skew = skew(false);
+        for _ in 0..3 {
+            skew.record(&Id::random(), remote(1000), NOW);
+        }
+        skew.record(&Id::random(), remote(3 * 60 * MINUTE), NOW);
+        skew.record(&Id::random(), remote(-3 * 60 * MINUTE), NOW);
+
+        assert!(skew.estimate().unwrap().abs() <= 2000);
+        assert_eq!(skew.compensation(), 0);
+    }
+
+    #[test]
+    fn test_latest_offset_per_node() {
+        let skew = skew(false);
+        let node = Id::random();
+        skew.record(&node, remote(60 * MINUTE), NOW);
+        skew.record(&node, remote(0), NOW);
+        skew.record(&Id::random(), remote(0), NOW);
+
+        assert_eq!(skew.nodes(), 2);
+        assert!(skew.estimate().unwrap().abs() <= 1000);
+    }
+
+    #[test]
+    fn test_threshold_crossing() {
+        let skew = skew(true);
+        let (node1, node2) = (Id::random(), Id::random());
+        assert_eq!(skew.record(&node1, remote(10 * MINUTE), NOW), None);
+        assert!(matches!(skew.record(&node2, remote(10 * MINUTE), NOW),
+            Some(SkewChange::Exceeded(_))));
+        // Told once only.
+        assert_eq!(skew.record(&node2, remote(10 * MINUTE), NOW), None);
+        assert!((skew.compensation() - 10 * MINUTE).abs() <= 1000);
+
+        // Of two nodes the one closer to the local clock counts.
+        assert_eq!(skew.record(&node1, remote(MINUTE), NOW), Some(SkewChange::Recovered));
+        assert_eq!(skew.compensation(), 0);
+        assert_eq!(skew.record(&node2, remote(MINUTE), NOW), None);
+    }
+
+    #[test]
+    fn test_disabled_threshold() {
+        let skew = ClockSkew::new(Duration::ZERO, true);
+        assert_eq!(skew.record(&Id::random(), remote(60 * MINUTE), NOW), None);
+        assert_eq!(skew.record(&Id::random(), remote(60 * MINUTE), NOW), None);
+        assert!(skew.estimate().is_some());
+        assert_eq!(skew.compensation(), 0);
+    }
+
+    #[test]
+    fn test_compensated_clock() {
+        let local = Arc::new(ManualClock::new(SystemTime::UNIX_EPOCH + Duration::from_millis(NOW)));
+        let skew = Arc::new(skew(true));
+        let clock = CompensatedClock::new(local.clone(), skew.clone());
+        assert_eq!(clock.now_ms(), NOW);
+
+        skew.record(&Id::random(), remote(-40 * MINUTE), NOW);
+        skew.record(&Id::random(), remote(-40 * MINUTE), NOW);
+        let expected = NOW as i64 - 40 * MINUTE;
+        assert!((clock.now_ms() as i64 - expected).abs() <= 1000);
+
+        // Without compensation the estimate is only told.
+        let skew = Arc::new(self::skew(false));
+        let clock = CompensatedClock::new(local, skew.clone());
+        skew.record(&Id::random(), remote(-40 * MINUTE), NOW);
+        skew.record(&Id::random(), remote(-40 * MINUTE), NOW);
+        assert_eq!(clock.now_ms(), NOW);
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    Identity,
+    CryptoIdentity,
+    dht::crypto_cache::CryptoCache,
+};
+
+// Encrypts from `sender` to the holder of `recipient` and decrypts it with
+// the context the cache gives to the recipient.
+fn roundtrip(cache: &CryptoCache, sender: &CryptoIdentity, recipient: &CryptoIdentity) -> bool {
+    let plain = b"Hello, Boson!";
+    let cipher = sender.create_crypto_context(recipient.id()).unwrap()
+        .encrypt_into(plain).unwrap();
+    let ctx = cache.context(sender.id(), recipient.encryption_keypair());
+    let decrypted = ctx.lock().unwrap().decrypt_into(&cipher);
+    decrypted.is_ok_and(|v| v == plain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let cache = CryptoCache::new(16, Duration::ZERO);
+        let me = CryptoIdentity::new();
+        let (alice, bob) = (CryptoIdentity::new(), CryptoIdentity::new());
+
+        assert!(roundtrip(&cache, &alice, &me));
+        assert!(roundtrip(&cache, &alice, &me));
+        assert!(roundtrip(&cache, &bob, &me));
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries(), 2);
+        assert_eq!(stats.misses(), 2);
+        assert_eq!(stats.hits(), 1);
+        assert_eq!(stats.evictions(), 0);
+        assert!((stats.hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+
+        // Clones share the contexts.
+        let shared = cache.clone();
+        let ctx = shared.context(alice.id(), me.encryption_keypair());
+        assert!(Arc::ptr_eq(&ctx, &cache.context(alice.id(), me.encryption_keypair())));
+        assert_eq!(cache.stats().hits(), 3);
+    }
+
+    #[test]
+    fn test_ttl_expiry() {
+        let cache = CryptoCache::new(16, Duration::from_millis(100));
+        let me = CryptoIdentity::new();
+        let alice = CryptoIdentity::new();
+
+        let first = cache.context(alice.id(), me.encryption_keypair());
+        assert!(Arc::ptr_eq(&first, &cache.context(alice.id(), me.encryption_keypair())));
+
+        thread::sleep(Duration::from_millis(250));
+        let second = cache.context(alice.id(), me.encryption_keypair());
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(roundtrip(&cache, &alice, &me));
+
+        let stats = cache.stats();
+        assert_eq!(stats.misses(), 2);
+        assert_eq!(stats.evictions(), 1);
+        assert_eq!(stats.entries(), 1);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let cache = CryptoCache::new(2, Duration::ZERO);
+        let me = CryptoIdentity::new();
+        for _ in 0..5 {
+            cache.context(CryptoIdentity::new().id(), me.encryption_keypair());
+        }
+
+        let stats = cache.stats();
+        assert!(stats.entries() <= 2);
+        assert_eq!(stats.entries() + stats.evictions(), 5);
+    }
+
+    #[test]
+    fn test_session_key_change() {
+        let cache = CryptoCache::new(16, Duration::ZERO);
+        let contact = CryptoIdentity::new();
+        let (session1, session2) = (CryptoIdentity::new(), CryptoIdentity::new());
+
+        let old = cache.context(contact.id(), session1.encryption_keypair());
+        assert!(roundtrip(&cache, &contact, &session1));
+
+        // A new session key never gets the context of the old one.
+        let new = cache.context(contact.id(), session2.encryption_keypair());
+        assert!(!Arc::ptr_eq(&old, &new));
+        assert!(roundtrip(&cache, &contact, &session2));
+
+        // Nor is the old one kept around once the contact is updated.
+        let other = CryptoIdentity::new();
+        cache.context(other.id(), session1.encryption_keypair());
+        cache.invalidate(contact.id());
+        let stats = cache.stats();
+        assert_eq!(stats.entries(), 1);
+        assert_eq!(stats.invalidations(), 2);
+
+        let misses = stats.misses();
+        assert!(!Arc::ptr_eq(&new, &cache.context(contact.id(), session2.encryption_keypair())));
+        assert!(Arc::ptr_eq(
+            &cache.context(other.id(), session1.encryption_keypair()),
+            &cache.context(other.id(), session1.encryption_keypair())
+        ));
+        assert_eq!(cache.stats().misses(), misses + 1);
+    }
+
+    #[test]
+    fn test_clear() {
+        let cache = CryptoCache::new(16, Duration::ZERO);
+        let me = CryptoIdentity::new();
+        cache.context(CryptoIdentity::new().id(), me.encryption_keypair());
+        cache.context(CryptoIdentity::new().id(), me.encryption_keypair());
+
+        cache.clear();
+        let stats = cache.stats();
+        assert_eq!(stats.entries(), 0);
+        assert_eq!(stats.invalidations(), 2);
+    }
+}
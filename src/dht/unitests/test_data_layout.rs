@@ -0,0 +1,88 @@
+use std::{
+    fs,
+    path::PathBuf,
+};
+
+use crate::{
+    Id,
+    Network,
+    dht::data_layout::DataLayout,
+};
+
+fn data_dir() -> PathBuf {
+    let dir = PathBuf::from(format!("/tmp/layout_{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_legacy(dir: &PathBuf, id: &Id) {
+    fs::write(dir.join("id"), id.to_base58()).unwrap();
+    for name in ["node.db", "node.db-wal", "dht4.cache", "stats.log", "stats.log.1"] {
+        fs::write(dir.join(name), name).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paths() {
+        let id = Id::random();
+        let dir = data_dir();
+
+        let layout = DataLayout::for_node(&dir, None, &id);
+        let short = id.to_base58()[..DataLayout::SHORT_ID_LEN].to_string();
+        assert_eq!(layout.root(), dir.join(&short));
+        assert_eq!(layout.database("node.db"), dir.join(&short).join("node.db"));
+        assert_eq!(layout.database("/var/db/node.db"), PathBuf::from("/var/db/node.db"));
+
+        let layout = DataLayout::for_node(&dir, Some("alpha"), &id);
+        assert_eq!(layout.routing_cache(Network::IPv4), dir.join("alpha/routing4.cache"));
+        assert_eq!(layout.routing_cache(Network::IPv6), dir.join("alpha/routing6.cache"));
+        assert_eq!(layout.id_file(), dir.join("alpha/id"));
+        assert_eq!(layout.stats_journal(), dir.join("alpha/stats.log"));
+
+        assert!(DataLayout::is_valid_instance("alpha"));
+        for name in ["", ".", "..", "a/b", "a\\b"] {
+            assert!(!DataLayout::is_valid_instance(name));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy() {
+        let id = Id::random();
+        let dir = data_dir();
+        write_legacy(&dir, &id);
+
+        let layout = DataLayout::new(&dir, "alpha");
+        layout.create().unwrap();
+        let moved = layout.migrate_legacy(&id, "node.db").unwrap();
+        assert_eq!(moved.len(), 6);
+
+        let root = dir.join("alpha");
+        assert_eq!(fs::read_to_string(root.join("routing4.cache")).unwrap(), "dht4.cache");
+        assert_eq!(fs::read_to_string(root.join("node.db-wal")).unwrap(), "node.db-wal");
+        assert_eq!(fs::read_to_string(root.join("stats.log.1")).unwrap(), "stats.log.1");
+        assert_eq!(fs::read_to_string(layout.id_file()).unwrap(), id.to_base58());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        // Done once only.
+        assert!(layout.migrate_legacy(&id, "node.db").unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy_of_other_node() {
+        let dir = data_dir();
+        write_legacy(&dir, &Id::random());
+
+        let layout = DataLayout::new(&dir, "beta");
+        layout.create().unwrap();
+        assert!(layout.migrate_legacy(&Id::random(), "node.db").unwrap().is_empty());
+        assert!(dir.join("node.db").is_file());
+        assert!(dir.join("dht4.cache").is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
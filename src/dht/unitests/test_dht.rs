@@ -1,6 +1,6 @@
 use std::{
     cell::RefCell,
-    path::PathBuf,
+    path::Path,
     rc::Rc,
     sync::{Arc, Mutex},
 };
@@ -9,6 +9,7 @@ use crate::{
     Network,
     Identity,
     CryptoIdentity,
+    SystemClock,
 };
 use crate::dht::{
     connection_status_listener::ConnectionStatusListener,
@@ -21,6 +22,7 @@ use crate::dht::{
     },
     timer_client::{LocalTimerClient, LocalTimerCmd},
     token_manager::TokenManager,
+    data_layout::DataLayout,
 };
 
 struct NoopConnectionStatusListener;
@@ -31,19 +33,18 @@ pub(super) fn make_dht(
     network: Network,
     host: &str,
 ) -> (Rc<RefCell<DHT>>, mpsc::UnboundedReceiver<LocalTimerCmd>) {
-    let tokenman = Arc::new(TokenManager::new());
+    let tokenman = Arc::new(TokenManager::new(Arc::new(SystemClock)));
     let storage: Arc<Mutex<dyn DataStorage>> = Arc::new(Mutex::new(SqliteStorage::new()));
     let listener: Arc<dyn ConnectionStatusListener> = Arc::new(NoopConnectionStatusListener);
     let (tx, rx) = mpsc::unbounded_channel::<LocalTimerCmd>();
     let timer_client = Rc::new(LocalTimerClient::new(tx));
-    let data_dir = PathBuf::from(".");
 
     let options = VerticleOptions::default()
         .with_identity(identity)
         .with_storage(storage)
         .with_tokenman(tokenman)
         .with_listener(listener)
-        .with_datadir(data_dir);
+        .with_layout(DataLayout::new(Path::new("."), "test"));
 
     let dht = DHT::new(options, network, host.to_string(), 0, None, timer_client)
         .expect("test DHT should build");
@@ -0,0 +1,88 @@
+use crate::{Id, Value};
+use crate::core::{
+    ImmutableBuilder,
+    SignedBuilder,
+    EncryptedBuilder,
+    signature::KeyPair,
+};
+use crate::dht::{
+    eligible_value::{self, EligibleValue},
+    lookup_result::Origins,
+};
+
+fn signed(kp: &KeyPair, seq: i32) -> Value {
+    SignedBuilder::new(b"honest value")
+        .with_keypair(kp)
+        .with_sequence_number(seq)
+        .build()
+        .unwrap()
+}
+
+// Same key and a higher sequence number, but signed by nobody.
+fn forged(value: &Value) -> Value {
+    Value::packed(
+        value.public_key().cloned(),
+        value.recipient().cloned(),
+        value.nonce().cloned(),
+        value.signature().map(|v| v.to_vec()),
+        b"forged value".to_vec(),
+        value.sequence_number() + 1,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let kp = KeyPair::random();
+        let value = signed(&kp, 3);
+        assert_eq!(eligible_value::verify(&value.id(), &value), Ok(()));
+        assert_eq!(eligible_value::verify(&Id::random(), &value), Err("not the value of the target"));
+        assert_eq!(eligible_value::verify(&value.id(), &forged(&value)), Err("invalid signature"));
+
+        let immutable = ImmutableBuilder::new(b"immutable").build().unwrap();
+        assert_eq!(eligible_value::verify(&immutable.id(), &immutable), Ok(()));
+
+        let signed_immutable = Value::packed(None, None, None,
+            value.signature().map(|v| v.to_vec()),
+            immutable.data().to_vec(), 0
+        );
+        assert_eq!(eligible_value::verify(&immutable.id(), &signed_immutable),
+            Err("immutable value with mutable fields"));
+    }
+
+    #[test]
+    fn test_verify_encrypted() {
+        let kp = KeyPair::random();
+        let recipient = Id::from(KeyPair::random().public_key());
+        let value = EncryptedBuilder::new(b"sealed", &recipient)
+            .with_keypair(&kp)
+            .build()
+            .unwrap();
+        assert_eq!(eligible_value::verify(&value.id(), &value), Ok(()));
+
+        let truncated = Value::packed(
+            value.public_key().cloned(),
+            value.recipient().cloned(),
+            value.nonce().cloned(),
+            value.signature().map(|v| v.to_vec()),
+            value.data()[..8].to_vec(),
+            value.sequence_number(),
+        );
+        assert_eq!(eligible_value::verify(&value.id(), &truncated), Err("encrypted data too short"));
+    }
+
+    #[test]
+    fn test_update_skips_forgery() {
+        let kp = KeyPair::random();
+        let value = signed(&kp, 3);
+        let origins = || Origins::new(Id::random(), None);
+
+        let mut result = EligibleValue::new(value.id(), -1);
+        assert!(result.update(value.clone(), false, origins()));
+        assert!(!result.update(forged(&value), false, origins()));
+        assert_eq!(result.result().unwrap().value(), &value);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    Id,
+    PeerInfo,
+    PeerBuilder,
+    EndpointPolicy,
+    core::peer_info::PeerFields,
+};
+use crate::dht::{
+    msg::{msg, Body, Message},
+    rpc::rpc_server::RpcServer,
+};
+
+// Peer records as they may arrive from the network, never built locally.
+fn received_peer(endpoint: &str) -> PeerInfo {
+    PeerInfo::packed(
+        Id::random(),
+        vec![7; PeerInfo::NONCE_BYTES],
+        1,
+        None,
+        None,
+        vec![9; 64],
+        0,
+        endpoint.to_string(),
+        PeerFields::default(),
+    )
+}
+
+fn peer_endpoints(msg: &Message) -> Vec<String> {
+    match msg.body() {
+        Some(Body::FindPeerResponse(rsp)) => rsp.peers().unwrap_or_default().iter()
+            .map(|p| p.endpoint().to_string())
+            .collect(),
+        _ => panic!("expected a find peer response"),
+    }
+}
+
+fn peer_ages(msg: &Message) -> Option<Vec<u64>> {
+    match msg.body() {
+        Some(Body::FindPeerResponse(rsp)) => rsp.ages().map(|v| v.to_vec()),
+        _ => panic!("expected a find peer response"),
+    }
+}
+
+fn find_peer_rsp() -> Message {
+    msg::find_peer_response(1, vec![
+        PeerBuilder::new("https://example.com").build().unwrap(),
+        received_peer("javascript:alert(1)"),
+        received_peer("HTTPS://Example.com:443/"),
+        received_peer(&format!("http://example.com/{}", "a".repeat(4096))),
+        received_peer("tcp://1.2.3.4:9000"),
+        // As announced by an older node
+        received_peer("1.2.3.4:9001"),
+    ], Some(vec![10, 11, 12, 13, 14, 15]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_peer_response() {
+        let mut rsp = find_peer_rsp();
+        assert!(!RpcServer::screen_endpoints(EndpointPolicy::Reject, &mut rsp));
+
+        let mut rsp = find_peer_rsp();
+        assert!(RpcServer::screen_endpoints(EndpointPolicy::Sanitize, &mut rsp));
+        assert_eq!(peer_endpoints(&rsp), vec![
+            "https://example.com",
+            "HTTPS://Example.com:443/",
+            "tcp://1.2.3.4:9000",
+            "1.2.3.4:9001",
+        ]);
+        assert_eq!(peer_ages(&rsp), Some(vec![10, 12, 14, 15]));
+
+        let mut rsp = find_peer_rsp();
+        assert!(RpcServer::screen_endpoints(EndpointPolicy::Accept, &mut rsp));
+        assert_eq!(peer_endpoints(&rsp).len(), 6);
+
+        let mut rsp = msg::find_peer_response(1, vec![
+            received_peer("https://example.com"),
+            received_peer("tcp://1.2.3.4:9000"),
+        ], None);
+        assert!(RpcServer::screen_endpoints(EndpointPolicy::Reject, &mut rsp));
+        assert_eq!(peer_endpoints(&rsp).len(), 2);
+    }
+
+    #[test]
+    fn test_announce_peer_request() {
+        let cases = [
+            ("https://example.com", [true, true, true]),
+            ("HTTPS://Example.com:443/", [false, true, true]),
+            ("javascript:alert(1)", [false, false, true]),
+            ("http://user:pw@example.com", [false, false, true]),
+            ("10.0.0.1:9000", [false, true, true]),
+            ("relay.example.com:9000", [false, true, true]),
+            ("tcp:1.2.3.4:9000", [false, false, true]),
+        ];
+
+        for (endpoint, expected) in cases {
+            let policies = [EndpointPolicy::Reject, EndpointPolicy::Sanitize, EndpointPolicy::Accept];
+            for (policy, admitted) in policies.into_iter().zip(expected) {
+                let mut req = msg::announce_peer_request(received_peer(endpoint), 1, -1);
+                assert_eq!(RpcServer::screen_endpoints(policy, &mut req), admitted,
+                    "{policy} on {endpoint}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_other_messages_pass() {
+        let mut req = msg::ping_request();
+        assert!(RpcServer::screen_endpoints(EndpointPolicy::Reject, &mut req));
+
+        let mut rsp = msg::find_peer_response_with_nodes(1, Some(Vec::new()), None);
+        assert!(RpcServer::screen_endpoints(EndpointPolicy::Reject, &mut rsp));
+    }
+}
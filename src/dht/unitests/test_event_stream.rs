@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+use futures::StreamExt;
+
+use crate::dht::event_stream::{EventBroadcast, StreamItem};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_ordered_delivery() {
+        let events = EventBroadcast::new(8);
+        // Nothing subscribed, dropped
+        events.emit(0);
+
+        let mut first = Box::pin(events.subscribe());
+        let mut second = Box::pin(events.subscribe());
+        events.emit(1);
+        events.emit(2);
+
+        assert_eq!(first.next().await, Some(StreamItem::Event(1)));
+        assert_eq!(first.next().await, Some(StreamItem::Event(2)));
+        assert_eq!(second.next().await, Some(StreamItem::Event(1)));
+
+        // Unsubscribed once dropped, the others keep their events.
+        drop(first);
+        events.emit(3);
+        assert_eq!(second.next().await, Some(StreamItem::Event(2)));
+        assert_eq!(second.next().await, Some(StreamItem::Event(3)));
+
+        drop(events);
+        assert_eq!(second.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer() {
+        let events = EventBroadcast::new(8);
+        let mut slow = Box::pin(events.subscribe());
+
+        // Never waits on the stream that is not polled.
+        let started = Instant::now();
+        for n in 0..100 {
+            events.emit(n);
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(slow.next().await, Some(StreamItem::Lagged(92)));
+        for n in 92..100 {
+            assert_eq!(slow.next().await, Some(StreamItem::Event(n)));
+        }
+    }
+}
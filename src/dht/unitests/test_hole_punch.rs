@@ -0,0 +1,133 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use tokio::net::UdpSocket;
+
+use crate::dht::hole_punch::{
+    self,
+    Probe,
+    ProbePattern,
+    MAX_CANDIDATES,
+};
+
+fn pattern() -> ProbePattern {
+    ProbePattern::default()
+        .with_rounds(20)
+        .with_interval(Duration::from_millis(20))
+}
+
+async fn socket() -> (UdpSocket, SocketAddr) {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    (socket, addr)
+}
+
+fn ip(v: &str) -> IpAddr {
+    v.parse().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_codec() {
+        for probe in [Probe::Probe(0), Probe::Probe(513), Probe::Ack(7)] {
+            let data = probe.encode(0xfeed);
+            assert_eq!(Probe::decode(&data, 0xfeed), Some(probe));
+            assert_eq!(Probe::decode(&data, 0xbeef), None);
+            assert_eq!(Probe::decode(&data[1..], 0xfeed), None);
+        }
+
+        let mut data = Probe::Ack(1).encode(1);
+        data[4] = 9;
+        assert_eq!(Probe::decode(&data, 1), None);
+        data = Probe::Ack(1).encode(1);
+        data[0] = b'X';
+        assert_eq!(Probe::decode(&data, 1), None);
+    }
+
+    #[test]
+    fn test_candidates() {
+        let pattern = ProbePattern::default().with_port_offsets(&[0, 1, -1]);
+        let hosts = [ip("192.168.1.2"), ip("fe80::2"), ip("0.0.0.0")];
+
+        let addrs = hole_punch::candidates(&hosts, Some(ip("203.0.113.9")), 5000, &pattern, true);
+        let expected: Vec<SocketAddr> = [
+            "192.168.1.2:5000", "192.168.1.2:5001", "192.168.1.2:4999",
+            "203.0.113.9:5000", "203.0.113.9:5001", "203.0.113.9:4999",
+        ].iter().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(addrs, expected);
+
+        // The observed address duplicating a reported one, and ports out
+        // of range.
+        let addrs = hole_punch::candidates(&hosts[..1], Some(ip("192.168.1.2")), 65535, &pattern, true);
+        assert_eq!(addrs, ["192.168.1.2:65535".parse().unwrap(), "192.168.1.2:65534".parse().unwrap()]);
+
+        let addrs = hole_punch::candidates(&hosts, None, 5000, &ProbePattern::default(), false);
+        assert_eq!(addrs, ["[fe80::2]:5000".parse::<SocketAddr>().unwrap()]);
+
+        let many = (0..40).map(|i| IpAddr::from([10, 0, 0, i])).collect::<Vec<_>>();
+        assert_eq!(hole_punch::candidates(&many, None, 5000, &pattern, true).len(), MAX_CANDIDATES);
+
+        // An empty offset list still probes the reported port.
+        assert_eq!(ProbePattern::default().with_port_offsets(&[]).port_offsets(), &[0]);
+    }
+
+    #[tokio::test]
+    async fn test_punch() {
+        let (a, addr_a) = socket().await;
+        let (b, addr_b) = socket().await;
+
+        // The candidates of b include ports nobody listens on.
+        let candidates_b = [addr_b, SocketAddr::new(addr_b.ip(), addr_b.port().wrapping_add(1))];
+        let candidates_a = [addr_a];
+        let pattern = pattern();
+        let (ra, rb) = tokio::join!(
+            hole_punch::punch(&a, 99, &candidates_b, &pattern),
+            hole_punch::punch(&b, 99, &candidates_a, &pattern)
+        );
+
+        let (remote_a, rtt) = ra.unwrap();
+        assert_eq!(remote_a, addr_b);
+        assert!(rtt < Duration::from_secs(1));
+        assert_eq!(rb.unwrap().0, addr_a);
+    }
+
+    #[tokio::test]
+    async fn test_punch_session_mismatch() {
+        let (a, addr_a) = socket().await;
+        let (b, addr_b) = socket().await;
+
+        let pattern = ProbePattern::default()
+            .with_rounds(3)
+            .with_interval(Duration::from_millis(20));
+        let (candidates_a, candidates_b) = ([addr_a], [addr_b]);
+        let (ra, rb) = tokio::join!(
+            hole_punch::punch(&a, 1, &candidates_b, &pattern),
+            hole_punch::punch(&b, 2, &candidates_a, &pattern)
+        );
+        assert!(ra.is_err());
+        assert!(rb.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_punch_one_way() {
+        let (a, _) = socket().await;
+        let (silent, addr) = socket().await;
+
+        // Probes arrive at a socket that never answers.
+        let pattern = ProbePattern::default()
+            .with_rounds(3)
+            .with_interval(Duration::from_millis(20));
+        let result = hole_punch::punch(&a, 5, &[addr], &pattern).await;
+        assert!(result.is_err());
+
+        let mut buf = [0u8; 64];
+        let (len, _) = silent.recv_from(&mut buf).await.unwrap();
+        assert_eq!(Probe::decode(&buf[..len], 5), Some(Probe::Probe(0)));
+
+        assert!(hole_punch::punch(&a, 5, &[], &pattern).await.is_err());
+    }
+}
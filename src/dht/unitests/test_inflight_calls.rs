@@ -0,0 +1,98 @@
+use std::{
+    cell::{Cell, RefCell},
+    net::{SocketAddr, UdpSocket},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+use crate::{CryptoIdentity, Identity, NodeInfo};
+use crate::dht::{
+    msg::msg,
+    handler::Handler,
+    rpc::{
+        RpcCall,
+        rpc_server::RpcServer,
+    },
+    timer_client::{GenericTimerCmd, LocalTimerClient, LocalTimerCmd},
+};
+
+fn sink() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    socket
+}
+
+struct TestServer {
+    server  : Rc<RefCell<RpcServer>>,
+    calls   : Rc<Cell<usize>>,
+    timers  : mpsc::UnboundedReceiver<LocalTimerCmd>,
+}
+
+impl TestServer {
+    async fn new(max_inflight_calls: usize) -> Self {
+        let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+        let identity = Arc::new(CryptoIdentity::new());
+        let ni = NodeInfo::new(identity.id().clone(), SocketAddr::from(([127, 0, 0, 1], 0)));
+
+        let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+        rs.set_max_inflight_calls(max_inflight_calls);
+
+        let calls = Rc::new(Cell::new(0));
+        let cloned = calls.clone();
+        rs.callsent_handler(Handler::new(move |_| cloned.set(cloned.get() + 1)));
+        rs.start().await.unwrap();
+
+        let server = Rc::new(RefCell::new(rs));
+        server.borrow_mut().set_cloned(Rc::downgrade(&server));
+        Self { server, calls, timers }
+    }
+
+    fn ping(&self, to: SocketAddr) {
+        let target = NodeInfo::new(CryptoIdentity::new().id().clone(), to);
+        self.server.borrow_mut().send_call(RpcCall::new(target, msg::ping_request())).unwrap();
+    }
+
+    // Times out the calls sent so far without waiting for their timers.
+    async fn time_out_calls(&mut self) {
+        let cmds = std::iter::from_fn(|| self.timers.try_recv().ok()).collect::<Vec<_>>();
+        for cmd in cmds {
+            if let GenericTimerCmd::Add { interval: None, cb, .. } = cmd {
+                cb.cb(()).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_calls_deferred_at_limit() {
+        let peer = sink();
+        let dest = peer.local_addr().unwrap();
+        let mut ts = TestServer::new(2).await;
+
+        for _ in 0..5 {
+            ts.ping(dest);
+        }
+        assert_eq!(ts.calls.get(), 2);
+        assert_eq!(ts.server.borrow().inflight_calls(), 2);
+        assert_eq!(ts.server.borrow().deferred_calls(), 3);
+
+        // Each completed call lets a deferred one go, none is dropped
+        ts.time_out_calls().await;
+        assert_eq!(ts.calls.get(), 4);
+        assert_eq!(ts.server.borrow().deferred_calls(), 1);
+
+        ts.time_out_calls().await;
+        assert_eq!(ts.calls.get(), 5);
+        ts.time_out_calls().await;
+        assert_eq!(ts.server.borrow().inflight_calls(), 0);
+        assert_eq!(ts.server.borrow().deferred_calls(), 0);
+        assert_eq!(ts.server.borrow().peak_inflight_calls(), 2);
+        assert_eq!(ts.server.borrow().counters().calls_timeout, 5);
+    }
+}
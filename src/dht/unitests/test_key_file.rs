@@ -0,0 +1,143 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::{
+    Id,
+    signature::KeyPair,
+    errors::CryptoError,
+};
+use crate::dht::{
+    Node,
+    key_file,
+    node_config::NodeConfig,
+    yaml_configuration::NodeConfiguration,
+};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("key-file-{name}-{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("node.key");
+        let kp = KeyPair::random();
+
+        key_file::write(&path, kp.private_key(), Some("secret")).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert!(key_file::is_encrypted(&data));
+        assert!(!String::from_utf8_lossy(&data).contains(&kp.private_key().to_string()[2..]));
+        assert_eq!(&key_file::read(&path, Some("secret")).unwrap(), kp.private_key());
+
+        key_file::write(&path, kp.private_key(), None).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), kp.private_key().to_string());
+        assert_eq!(&key_file::read(&path, None).unwrap(), kp.private_key());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let dir = temp_dir("wrong");
+        let path = dir.join("node.key");
+        key_file::write(&path, KeyPair::random().private_key(), Some("secret")).unwrap();
+
+        let e = key_file::read(&path, Some("guess")).unwrap_err();
+        assert!(e.downcast_ref::<CryptoError>().is_some(), "{e}");
+        assert!(e.to_string().contains("Wrong passphrase"));
+
+        let e = key_file::read(&path, None).unwrap_err();
+        assert!(e.to_string().contains("passphrase is required"), "{e}");
+
+        // Truncated or of another version.
+        let data = fs::read(&path).unwrap();
+        assert!(key_file::open(&data[..10], "secret").is_err());
+        let mut other = data.clone();
+        other[4] = 2;
+        assert!(key_file::open(&other, "secret").unwrap_err().to_string().contains("version 2"));
+        assert!(key_file::seal(KeyPair::random().private_key(), "").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_key_file() {
+        let dir = temp_dir("config");
+        let data_dir = dir.display();
+
+        // Created on the first start, then read back.
+        let yaml = format!("keyFile: keys/node.key\ndataDir: {data_dir}\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        let path = dir.join("keys").join("node.key");
+        assert_eq!(cfg.key_file(), Some(path.as_path()));
+        assert!(!key_file::is_encrypted(&fs::read(&path).unwrap()));
+        assert_eq!(NodeConfiguration::from(&yaml).unwrap().private_key(), cfg.private_key());
+
+        // The legacy plaintext file is encrypted once a passphrase is set.
+        let sealed = format!("{yaml}keyPassphrase: secret\n");
+        let migrated = NodeConfiguration::from(&sealed).unwrap();
+        assert_eq!(migrated.private_key(), cfg.private_key());
+        assert!(key_file::is_encrypted(&fs::read(&path).unwrap()));
+        assert_eq!(NodeConfiguration::from(&sealed).unwrap().private_key(), cfg.private_key());
+
+        let e = NodeConfiguration::from(&yaml).unwrap_err();
+        assert!(e.to_string().contains("passphrase is required"), "{e}");
+        let e = NodeConfiguration::from(&format!("{yaml}keyPassphrase: guess\n")).unwrap_err();
+        assert!(e.to_string().contains("Wrong passphrase"), "{e}");
+
+        // A new passphrase.
+        let cfg = migrated.with_key_passphrase("other").unwrap();
+        assert!(NodeConfiguration::from(&sealed).is_err());
+        let reopened = NodeConfiguration::from(&format!("{yaml}keyPassphrase: other\n")).unwrap();
+        assert_eq!(reopened.private_key(), cfg.private_key());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_config_rejections() {
+        let sk = KeyPair::random().private_key().to_string();
+
+        let e = NodeConfiguration::from("dataDir: /tmp\n").unwrap_err();
+        assert!(e.to_string().contains("privateKey or keyFile"), "{e}");
+
+        let both = format!("privateKey: \"{sk}\"\nkeyFile: node.key\n");
+        assert!(NodeConfiguration::from(&both).is_err());
+
+        let inline = format!("privateKey: \"{sk}\"\nkeyPassphrase: secret\n");
+        assert!(NodeConfiguration::from(&inline).is_err());
+
+        let cfg = NodeConfiguration::from(&format!("privateKey: \"{sk}\"\n")).unwrap();
+        assert!(cfg.key_file().is_none());
+        assert!(cfg.with_key_passphrase("secret").is_err());
+    }
+
+    #[test]
+    fn test_import_identity() {
+        let dir = temp_dir("import");
+        let path = dir.join("exported.key");
+        let kp = KeyPair::random();
+
+        // As written by Node::export_identity.
+        key_file::write(&path, kp.private_key(), Some("secret")).unwrap();
+        let imported = Node::import_identity(&path, Some("secret")).unwrap();
+        assert_eq!(Id::from(imported.public_key()), Id::from(kp.public_key()));
+        assert!(Node::import_identity(&path, Some("guess")).is_err());
+
+        // The exported file serves as the key file of a node elsewhere.
+        let yaml = format!("keyFile: {}\nkeyPassphrase: secret\n", path.display());
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(Id::from(KeyPair::from(cfg.private_key()).public_key()), Id::from(kp.public_key()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use tokio::sync::mpsc;
+
+use crate::core::Network;
+use crate::dht::lookup_progress::{
+    LookupProgress,
+    ProgressReporter,
+    PROGRESS_INTERVAL,
+    PROGRESS_QUEUE_SIZE,
+};
+
+// As the lookup task takes them.
+fn snapshot(reporter: &ProgressReporter, queried: usize, best: Option<usize>) -> LookupProgress {
+    reporter.snapshot()
+        .with_counts(queried, 0, 0, 0)
+        .with_best_distance(best)
+        .with_candidates(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_limited() {
+        let (tx, mut rx) = mpsc::channel(PROGRESS_QUEUE_SIZE);
+        let reporter = ProgressReporter::new(Network::IPv4, tx);
+
+        // The first one goes out, the next ones wait for the interval.
+        reporter.update(snapshot(&reporter, 1, None));
+        reporter.update(snapshot(&reporter, 2, None));
+        reporter.update(snapshot(&reporter, 3, Some(4)));
+        // Closer to the target, sent right away.
+        reporter.update(snapshot(&reporter, 4, Some(4)));
+        reporter.update(snapshot(&reporter, 5, Some(3)));
+
+        assert_eq!(rx.try_recv().unwrap().queried(), 1);
+        assert_eq!(rx.try_recv().unwrap().queried(), 3);
+        assert!(rx.try_recv().is_err());
+
+        std::thread::sleep(PROGRESS_INTERVAL);
+        reporter.update(snapshot(&reporter, 6, Some(4)));
+        assert_eq!(rx.try_recv().unwrap().queried(), 6);
+
+        // The last one always goes out.
+        reporter.finish(snapshot(&reporter, 7, Some(4)));
+        let last = rx.try_recv().unwrap();
+        assert!(last.is_finished());
+        assert_eq!(last.queried(), 7);
+        assert_eq!(last.candidates(), 0);
+    }
+
+    #[test]
+    fn test_never_waits() {
+        let (tx, mut rx) = mpsc::channel(1);
+        let reporter = ProgressReporter::new(Network::IPv6, tx);
+
+        // Nobody takes them, the ones finding the queue full are dropped.
+        for best in 0..10 {
+            reporter.update(snapshot(&reporter, best, Some(best)));
+        }
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.network(), Network::IPv6);
+        assert_eq!(first.best_distance(), Some(0));
+        assert!(rx.try_recv().is_err());
+
+        drop(rx);
+        reporter.update(snapshot(&reporter, 20, Some(20)));
+        reporter.finish(snapshot(&reporter, 20, Some(20)));
+    }
+}
@@ -0,0 +1,146 @@
+use std::cell::Cell;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+
+use crate::dht::storage::migrations::{
+    self,
+    Migration,
+    MigrationError,
+};
+
+// Counts the transforms run, and fails them when told to.
+#[derive(Default)]
+struct Context {
+    runs: Cell<usize>,
+    fail: Cell<bool>,
+}
+
+// Splits the names into the columns added by version 2.
+fn split_names(conn: &mut SqliteConnection, ctx: &Context) -> QueryResult<()> {
+    ctx.runs.set(ctx.runs.get() + 1);
+    if ctx.fail.get() {
+        return Err(diesel::result::Error::RollbackTransaction);
+    }
+    diesel::sql_query("UPDATE users SET first = substr(name, 1, instr(name, ' ') - 1), \
+        last = substr(name, instr(name, ' ') + 1)")
+        .execute(conn)
+        .map(|_| ())
+}
+
+const MIGRATIONS: &[Migration<Context>] = &[
+    Migration {
+        version: 1,
+        statements: &["CREATE TABLE users(id INTEGER PRIMARY KEY, name TEXT NOT NULL)"],
+        transform: None,
+    },
+    Migration {
+        version: 2,
+        statements: &[
+            "ALTER TABLE users ADD COLUMN first TEXT",
+            "ALTER TABLE users ADD COLUMN last TEXT",
+        ],
+        transform: Some(split_names),
+    },
+    Migration {
+        version: 3,
+        statements: &["ALTER TABLE users ADD COLUMN active INTEGER NOT NULL DEFAULT 1"],
+        transform: None,
+    },
+];
+
+#[derive(QueryableByName, Debug, PartialEq)]
+struct User {
+    #[diesel(sql_type = Text)]
+    first: String,
+    #[diesel(sql_type = Text)]
+    last: String,
+    #[diesel(sql_type = BigInt)]
+    active: i64,
+}
+
+#[derive(QueryableByName)]
+struct Column {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+fn columns(conn: &mut SqliteConnection) -> Vec<String> {
+    diesel::sql_query("SELECT name FROM pragma_table_info('users')")
+        .load::<Column>(conn)
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name)
+        .collect()
+}
+
+// A database left at version 1 with a row in it.
+fn v1_database() -> SqliteConnection {
+    let mut conn = SqliteConnection::establish(":memory:").unwrap();
+    assert_eq!(migrations::migrate(&mut conn, &MIGRATIONS[..1], &Context::default()).unwrap(), 0);
+    diesel::sql_query("INSERT INTO users(name) VALUES ('Ada Lovelace')")
+        .execute(&mut conn)
+        .unwrap();
+    conn
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_database() {
+        let mut conn = SqliteConnection::establish(":memory:").unwrap();
+        let ctx = Context::default();
+        assert_eq!(migrations::migrate(&mut conn, MIGRATIONS, &ctx).unwrap(), 0);
+        assert_eq!(migrations::user_version(&mut conn).unwrap(), 3);
+        assert_eq!(columns(&mut conn), ["id", "name", "first", "last", "active"]);
+        assert_eq!(ctx.runs.get(), 1);
+
+        // Nothing left to do on the next open.
+        assert_eq!(migrations::migrate(&mut conn, MIGRATIONS, &ctx).unwrap(), 3);
+        assert_eq!(ctx.runs.get(), 1);
+    }
+
+    #[test]
+    fn test_upgrade_keeps_rows() {
+        let mut conn = v1_database();
+        assert_eq!(migrations::migrate(&mut conn, MIGRATIONS, &Context::default()).unwrap(), 1);
+        assert_eq!(migrations::user_version(&mut conn).unwrap(), 3);
+
+        let users = diesel::sql_query("SELECT first, last, active FROM users")
+            .load::<User>(&mut conn)
+            .unwrap();
+        assert_eq!(users, [User { first: "Ada".into(), last: "Lovelace".into(), active: 1 }]);
+    }
+
+    #[test]
+    fn test_failed_migration_rolled_back() {
+        let mut conn = v1_database();
+        let ctx = Context::default();
+        ctx.fail.set(true);
+
+        let err = migrations::migrate(&mut conn, MIGRATIONS, &ctx).unwrap_err();
+        assert!(matches!(err, MigrationError::Failed { version: 2, .. }), "{err:?}");
+        // Neither the columns nor the version of the failed step remain.
+        assert_eq!(migrations::user_version(&mut conn).unwrap(), 1);
+        assert_eq!(columns(&mut conn), ["id", "name"]);
+
+        ctx.fail.set(false);
+        assert_eq!(migrations::migrate(&mut conn, MIGRATIONS, &ctx).unwrap(), 1);
+        assert_eq!(migrations::user_version(&mut conn).unwrap(), 3);
+        assert_eq!(ctx.runs.get(), 2);
+    }
+
+    #[test]
+    fn test_newer_version_refused() {
+        let mut conn = v1_database();
+        diesel::sql_query("PRAGMA user_version = 4").execute(&mut conn).unwrap();
+
+        let ctx = Context::default();
+        let err = migrations::migrate(&mut conn, MIGRATIONS, &ctx).unwrap_err();
+        assert!(matches!(err, MigrationError::Newer(4)), "{err:?}");
+        assert_eq!(columns(&mut conn), ["id", "name"]);
+        assert_eq!(ctx.runs.get(), 0);
+        assert_eq!(migrations::latest_version(MIGRATIONS), 3);
+    }
+}
@@ -2,9 +2,13 @@ use std::{env, fs};
 use log::LevelFilter;
 
 use crate::{
+    Id,
+    Capabilities,
     signature::{KeyPair, PrivateKey},
 };
 use crate::dht::{
+    StorageBackend,
+    KnownNetwork,
     node_config::NodeConfig,
     yaml_configuration::NodeConfiguration,
 };
@@ -74,4 +78,247 @@ mod tests {
         fs::remove_file(&path).unwrap();
         fs::remove_dir_all(&temp_dir).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_storage_backend() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\ndatabaseUri: jdbc:sqlite:node.db\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.storage_backend(), StorageBackend::Sqlite);
+
+        let cfg = cfg.with_storage_backend(StorageBackend::Memory);
+        assert_eq!(cfg.storage_backend(), StorageBackend::Memory);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nstorageBackend: memory\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.storage_backend(), StorageBackend::Memory);
+        assert_eq!(cfg.database_uri(), "");
+
+        let yaml = format!("privateKey: \"{private_key}\"\nstorageBackend: redis\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_listening_ports() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("ipv4: true\nport: 39001\nprivateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.port4(), 39001);
+        assert_eq!(cfg.port6(), 39001);
+        assert_eq!(cfg.addr4().map(|addr| addr.port()), Some(39001));
+        assert_eq!(cfg.addr6(), None);
+
+        let cfg = cfg.with_listening_port(39005).with_listening_port4(39003);
+        assert_eq!(cfg.port4(), 39003);
+        assert_eq!(cfg.port6(), 39005);
+        assert_eq!(cfg.addr4().map(|addr| addr.port()), Some(39003));
+
+        let yaml = format!("ipv4: true\nport: 39001\nport4: 39003\nprivateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.port4(), 39003);
+        assert_eq!(cfg.port6(), 39001);
+
+        let yaml = format!("ipv4: true\nport6: 39003\nprivateKey: \"{private_key}\"\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_concurrency_limits() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.max_active_tasks(), 8);
+        assert_eq!(cfg.max_inflight_calls(), 64);
+        assert_eq!(cfg.max_task_calls(), 16);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nmaxActiveTasks: 2\nmaxInflightCalls: 3\nmaxTaskCalls: 1\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.max_active_tasks(), 2);
+        assert_eq!(cfg.max_inflight_calls(), 3);
+        assert_eq!(cfg.max_task_calls(), 1);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nmaxInflightCalls: 0\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_routing_strategy() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.bucket_capacity(), 8);
+        assert_eq!(cfg.home_split_levels(), 0);
+        assert_eq!(cfg.max_routing_entries(), 0);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nbucketCapacity: 16\nhomeSplitLevels: 2\nmaxRoutingEntries: 512\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.bucket_capacity(), 16);
+        assert_eq!(cfg.home_split_levels(), 2);
+        assert_eq!(cfg.max_routing_entries(), 512);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nbucketCapacity: 0\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+        let yaml = format!("privateKey: \"{private_key}\"\nmaxRoutingEntries: 4\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_command_queue_size() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        assert_eq!(NodeConfiguration::from(&yaml).unwrap().command_queue_size(), 256);
+
+        let yaml = format!("privateKey: \"{private_key}\"\ncommandQueueSize: 4\n");
+        assert_eq!(NodeConfiguration::from(&yaml).unwrap().command_queue_size(), 4);
+
+        let yaml = format!("privateKey: \"{private_key}\"\ncommandQueueSize: 0\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_receive_sockets() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        assert_eq!(NodeConfiguration::from(&yaml).unwrap().receive_sockets(), 1);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nreceiveSockets: 4\n");
+        let result = NodeConfiguration::from(&yaml);
+        assert_eq!(result.is_ok(), cfg!(target_os = "linux"));
+
+        for count in [0, 65] {
+            let yaml = format!("privateKey: \"{private_key}\"\nreceiveSockets: {count}\n");
+            assert!(NodeConfiguration::from(&yaml).is_err());
+        }
+    }
+
+    #[test]
+    fn test_block_strikes() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!((cfg.block_token_strikes(), cfg.block_value_strikes(), cfg.block_duration()), (8, 4, 3600));
+
+        let yaml = format!("privateKey: \"{private_key}\"\nblockTokenStrikes: 0\nblockValueStrikes: 0\nblockDuration: 0\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!((cfg.block_token_strikes(), cfg.block_value_strikes()), (0, 0));
+
+        let yaml = format!("privateKey: \"{private_key}\"\nblockDuration: 0\n");
+        assert!(NodeConfiguration::from(&yaml).is_err());
+    }
+
+    #[test]
+    fn test_crypto_cache() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.crypto_cache_capacity(), 256);
+        assert_eq!(cfg.crypto_cache_ttl(), 30 * 60);
+
+        let yaml = format!("privateKey: \"{private_key}\"\ncryptoCacheCapacity: 16\ncryptoCacheTtl: 0\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.crypto_cache_capacity(), 16);
+        assert_eq!(cfg.crypto_cache_ttl(), 0);
+    }
+
+    #[test]
+    fn test_min_store_acks() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.min_store_acks(), 1);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nminStoreAcks: 3\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.min_store_acks(), 3);
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert!(!cfg.client_only());
+        assert_eq!(cfg.capabilities(), Capabilities::STORAGE);
+
+        let yaml = format!("privateKey: \"{private_key}\"\nclientOnly: true\nrelay: true\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.capabilities(), Capabilities::CLIENT_ONLY | Capabilities::RELAY);
+        assert!(!cfg.capabilities().accepts_storage());
+
+        let cfg = cfg.with_client_only(false).with_relay(false);
+        assert_eq!(cfg.capabilities(), Capabilities::STORAGE);
+    }
+
+    #[test]
+    fn test_bootstrap_entries() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\nbootstraps:\n  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113.5\n    - 39001\n  - id: 7jFV8w7eivjGEpaDu4V38EZ16CDhn4JutEdtGBWC67rF\n    address: \"2001:db8::8\"\n    port: 39002\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        let nodes = cfg.bootstrap_nodes();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].id(), &Id::try_from("7jFV8w7eivjGEpaDu4V38EZ16CDhn4JutEdtGBWC67rF").unwrap());
+        assert_eq!(nodes[1].host(), "2001:db8::8");
+        assert_eq!(nodes[1].port(), 39002);
+
+        let error = |entry: &str| {
+            let yaml = format!("privateKey: \"{private_key}\"\nbootstraps:\n  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113.5\n    - 39001\n{entry}");
+            NodeConfiguration::from(&yaml).unwrap_err().to_string()
+        };
+        let e = error("  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr60\n    - 203.0.113.5\n    - 39001\n");
+        assert!(e.contains("bootstraps[1]: invalid node id '2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr60'"), "{e}");
+        assert!(e.contains("at line 6"), "{e}");
+        let e = error("  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113\n    - 39001\n");
+        assert!(e.contains("bootstraps[1]: invalid address '203.0.113'"), "{e}");
+        let e = error("  - id: 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    address: 203.0.113.5\n    port: \"39001\"\n");
+        assert!(e.contains("bootstraps[1]: invalid port '39001', expected a number from 1 to 65535"), "{e}");
+        let e = error("  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113.5\n    - 0\n");
+        assert!(e.contains("bootstraps[1]: invalid port 0"), "{e}");
+        let e = error("  - id: 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    port: 39001\n");
+        assert!(e.contains("bootstraps[1]: missing field `address`"), "{e}");
+        let e = error("  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113.5\n");
+        assert!(e.contains("bootstraps[1]: invalid length 2"), "{e}");
+    }
+
+    #[test]
+    fn test_known_network() {
+        let private_key = KeyPair::random().private_key().to_string();
+        let yaml = format!("privateKey: \"{private_key}\"\nnetwork: mainnet\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.network(), Some(KnownNetwork::Mainnet));
+        assert_eq!(cfg.bootstrap_nodes(), KnownNetwork::Mainnet.bootstrap_nodes().as_slice());
+        let nodes = cfg.bootstrap_nodes().iter()
+            .map(|n| (n.id().to_base58(), n.host().to_string(), n.port()))
+            .collect::<Vec<_>>();
+        assert_eq!(nodes, vec![
+            ("HZXXs9LTfNQjrDKvvexRhuMk8TTJhYCfrHwaj3jUzuhZ".to_string(), "155.138.245.211".to_string(), 39001),
+            ("6o6LkHgLyD5sYyW9iN5LNRYnUoX29jiYauQ5cDjhCpWQ".to_string(), "45.32.138.246".to_string(), 39001),
+            ("8grFdb2f6LLJajHwARvXC95y73WXEanNS1rbBAZYbC5L".to_string(), "140.82.57.197".to_string(), 39001),
+        ]);
+        assert_eq!(cfg.messaging_peer_id(), KnownNetwork::Mainnet.messaging_peer_id().as_ref());
+        assert_eq!(cfg.activeproxy_peer_id(), KnownNetwork::Mainnet.activeproxy_peer_id().as_ref());
+
+        // Explicit entries take the place of the built-in ones.
+        let peer_id = Id::random();
+        let yaml = format!("privateKey: \"{private_key}\"\nnetwork: Mainnet\nbootstraps:\n  - - 2dLbPsaySh9EGWwpgreYiLEPG3NDhaojj7DBBfSsRr6k\n    - 203.0.113.5\n    - 39001\nmessagingPeerId: {peer_id}\n");
+        let cfg = NodeConfiguration::from(&yaml).unwrap();
+        assert_eq!(cfg.bootstrap_nodes().len(), 1);
+        assert_eq!(cfg.bootstrap_nodes()[0].host(), "203.0.113.5");
+        assert_eq!(cfg.messaging_peer_id(), Some(&peer_id));
+        assert_eq!(cfg.activeproxy_peer_id(), KnownNetwork::Mainnet.activeproxy_peer_id().as_ref());
+
+        // Nothing built in for the testnet yet.
+        let yaml = format!("privateKey: \"{private_key}\"\nnetwork: testnet\n");
+        let e = NodeConfiguration::from(&yaml).unwrap_err().to_string();
+        assert!(e.contains("network testnet has no built-in bootstrap nodes"), "{e}");
+
+        let yaml = format!("privateKey: \"{private_key}\"\nnetwork: devnet\n");
+        let e = NodeConfiguration::from(&yaml).unwrap_err().to_string();
+        assert!(e.contains("Unknown network: devnet"), "{e}");
+
+        let yaml = format!("privateKey: \"{private_key}\"\nactiveProxyPeerId: 5vVM1nrCwFh3QqAgbvF3bRgYQL5a2vpFjngwxkiS8Ja0\n");
+        let e = NodeConfiguration::from(&yaml).unwrap_err().to_string();
+        assert!(e.contains("activeProxyPeerId: ") && e.contains("at line 2"), "{e}");
+        let cfg = NodeConfiguration::from(&format!("privateKey: \"{private_key}\"\n")).unwrap();
+        assert_eq!((cfg.network(), cfg.messaging_peer_id()), (None, None));
+    }
+}
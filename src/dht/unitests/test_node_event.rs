@@ -0,0 +1,50 @@
+use crate::{
+    Id,
+    Network,
+    dht::node_event::{EventLog, NodeEventKind},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ring_eviction() {
+        let log = EventLog::new(3);
+        for depth in 0..5 {
+            log.record(NodeEventKind::BucketSplit { depth });
+        }
+
+        let events = log.recent(10);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].kind(), &NodeEventKind::BucketSplit { depth: 2 });
+        assert_eq!(events[2].kind(), &NodeEventKind::BucketSplit { depth: 4 });
+
+        let events = log.recent(1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), &NodeEventKind::BucketSplit { depth: 4 });
+    }
+
+    #[test]
+    fn test_shared_with_network() {
+        let log = EventLog::new(8);
+        let log4 = log.with_network(Network::IPv4);
+
+        let id = Id::random();
+        log4.record(NodeEventKind::CallTimeout { id });
+        log.record(NodeEventKind::StorageError { op: "get_value" });
+
+        let events = log.recent(8);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].network() == Some(Network::IPv4));
+        assert_eq!(events[0].kind(), &NodeEventKind::CallTimeout { id });
+        assert!(events[1].network().is_none());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let log = EventLog::new(0);
+        log.record(NodeEventKind::BootstrapFailed { nodes: 0 });
+        assert!(log.recent(8).is_empty());
+    }
+}
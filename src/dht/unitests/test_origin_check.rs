@@ -0,0 +1,150 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{
+    Id,
+    Clock,
+    ManualClock,
+    PeerInfo,
+    CryptoIdentity,
+    Identity,
+    dht::origin_check::{OriginChecks, CHECKS_PER_ROUND, MAX_FAILURES, SHORTENED_AGE},
+};
+
+fn make_peer(origin: &Arc<Mutex<CryptoIdentity>>, endpoint: &str) -> PeerInfo {
+    PeerInfo::builder(endpoint)
+        .with_node(origin.clone())
+        .build()
+        .expect("Failed to build peer")
+}
+
+fn keys(peers: &[PeerInfo]) -> Vec<(Id, u64)> {
+    peers.iter().map(|p| (*p.id(), p.fingerprint())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let clock = Arc::new(ManualClock::default());
+        let local = Arc::new(Mutex::new(CryptoIdentity::new()));
+        let local_id = *local.lock().unwrap().id();
+        let checks = OriginChecks::new(local_id, clock.clone());
+
+        let origins = (0..CHECKS_PER_ROUND + 2)
+            .map(|_| Arc::new(Mutex::new(CryptoIdentity::new())))
+            .collect::<Vec<_>>();
+        let mut peers = origins.iter()
+            .map(|o| make_peer(o, "https://example.com"))
+            .collect::<Vec<_>>();
+        peers.push(make_peer(&local, "https://local.com"));
+        peers.push(PeerInfo::builder("https://anonymous.com").build().unwrap());
+
+        // A few per round, never the local node or anonymous peers.
+        let first = checks.select(&peers);
+        assert_eq!(first.len(), CHECKS_PER_ROUND);
+        assert!(!first.contains(&local_id));
+        for origin in &first {
+            clock.advance(Duration::from_secs(1));
+            checks.record(origin, true, &[]);
+        }
+
+        // The ones not checked yet come next.
+        let second = checks.select(&peers);
+        assert_eq!(second.len(), CHECKS_PER_ROUND);
+        let unchecked = second.iter().filter(|id| !first.contains(id)).count();
+        assert_eq!(unchecked, 2);
+        assert_eq!(second[2..], first[..2]);
+    }
+
+    #[test]
+    fn test_shorten_and_expire() {
+        let clock = Arc::new(ManualClock::default());
+        let checks = OriginChecks::new(Id::random(), clock.clone());
+        let origin = Arc::new(Mutex::new(CryptoIdentity::new()));
+        let origin_id = *origin.lock().unwrap().id();
+        let peers = vec![
+            make_peer(&origin, "https://example.com:8001"),
+            make_peer(&origin, "https://example.com:8002"),
+        ];
+        let stored_ms = clock.now_ms();
+
+        for _ in 1..MAX_FAILURES {
+            assert_eq!(checks.record(&origin_id, false, &keys(&peers)), 0);
+        }
+        assert_eq!(checks.record(&origin_id, false, &keys(&peers)), 2);
+        // Shortened once only.
+        assert_eq!(checks.record(&origin_id, false, &keys(&peers)), 0);
+
+        let stats = checks.stats();
+        assert_eq!(stats.checks(), MAX_FAILURES as u64 + 1);
+        assert_eq!(stats.failures(), MAX_FAILURES as u64 + 1);
+        assert_eq!(stats.shortened_peers(), 2);
+        assert_eq!(stats.pending_peers(), 2);
+
+        clock.advance(SHORTENED_AGE - Duration::from_secs(1));
+        assert!(checks.take_expired(|_, _| Some(stored_ms)).is_empty());
+
+        clock.advance(Duration::from_secs(1));
+        let mut expired = checks.take_expired(|_, _| Some(stored_ms));
+        expired.sort();
+        let mut expected = keys(&peers);
+        expected.sort();
+        assert_eq!(expired, expected);
+
+        let stats = checks.stats();
+        assert_eq!(stats.expired_peers(), 2);
+        assert_eq!(stats.pending_peers(), 0);
+    }
+
+    #[test]
+    fn test_origin_recovers() {
+        let clock = Arc::new(ManualClock::default());
+        let checks = OriginChecks::new(Id::random(), clock.clone());
+        let origin = Arc::new(Mutex::new(CryptoIdentity::new()));
+        let origin_id = *origin.lock().unwrap().id();
+        let peers = vec![make_peer(&origin, "https://example.com")];
+
+        for _ in 0..MAX_FAILURES {
+            checks.record(&origin_id, false, &keys(&peers));
+        }
+        assert_eq!(checks.stats().pending_peers(), 1);
+
+        // Answering again keeps the normal lifetime, and starts the count over.
+        checks.record(&origin_id, true, &keys(&peers));
+        assert_eq!(checks.stats().pending_peers(), 0);
+        assert_eq!(checks.record(&origin_id, false, &keys(&peers)), 0);
+
+        clock.advance(SHORTENED_AGE);
+        assert!(checks.take_expired(|_, _| Some(0)).is_empty());
+    }
+
+    #[test]
+    fn test_reannounced() {
+        let clock = Arc::new(ManualClock::default());
+        let checks = OriginChecks::new(Id::random(), clock.clone());
+        let origin = Arc::new(Mutex::new(CryptoIdentity::new()));
+        let origin_id = *origin.lock().unwrap().id();
+        let peers = vec![
+            make_peer(&origin, "https://example.com:8001"),
+            make_peer(&origin, "https://example.com:8002"),
+        ];
+
+        for _ in 0..MAX_FAILURES {
+            checks.record(&origin_id, false, &keys(&peers));
+        }
+        let shortened_ms = clock.now_ms();
+        clock.advance(SHORTENED_AGE);
+
+        // The first got stored again since, the second is gone already.
+        let first = *peers[0].id();
+        let expired = checks.take_expired(|id, _| (*id == first).then_some(shortened_ms + 1));
+        assert!(expired.is_empty());
+        assert_eq!(checks.stats().pending_peers(), 0);
+        assert_eq!(checks.stats().expired_peers(), 0);
+    }
+}
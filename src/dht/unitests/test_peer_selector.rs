@@ -0,0 +1,68 @@
+use rand::{
+    rngs::StdRng,
+    SeedableRng,
+};
+
+use crate::{
+    PeerInfo,
+    signature::KeyPair,
+    dht::PeerSelector,
+};
+
+// Instances of the same service, one per weight.
+fn instances(weights: &[u8]) -> Vec<PeerInfo> {
+    let kp = KeyPair::random();
+    weights.iter().enumerate().map(|(i, weight)| {
+        PeerInfo::builder(&format!("tcp://10.0.8.{}:9900", i + 1))
+            .with_key(kp.clone())
+            .with_fingerprint(i as u64 + 1)
+            .with_weight(*weight)
+            .build()
+            .unwrap()
+            .without_private_key()
+    }).collect()
+}
+
+// How many times each instance is picked in `rounds` choices.
+fn picks(peers: &[PeerInfo], rounds: usize, seed: u64) -> Vec<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut counts = vec![0; peers.len()];
+    for _ in 0..rounds {
+        let peer = PeerSelector::weighted_choice(peers, &mut rng).unwrap();
+        let index = peers.iter().position(|p| p == peer).unwrap();
+        counts[index] += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_distribution() {
+        let peers = instances(&[1, 2, 4]);
+        let rounds = 70_000;
+        let counts = picks(&peers, rounds, 42);
+
+        for (count, weight) in counts.iter().zip([1, 2, 4]) {
+            let expected = rounds as f64 * weight as f64 / 7.0;
+            let error = (*count as f64 - expected).abs() / expected;
+            assert!(error < 0.05, "weight {weight}: {count} picks, {expected} expected");
+        }
+
+        // The same seed picks the same ones.
+        assert_eq!(picks(&peers, 1000, 7), picks(&peers, 1000, 7));
+    }
+
+    #[test]
+    fn test_zero_weight() {
+        // Drained instances take no load.
+        let peers = instances(&[0, 3, 0]);
+        assert_eq!(picks(&peers, 1000, 1), vec![0, 1000, 0]);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(PeerSelector::weighted_choice(&instances(&[0, 0]), &mut rng).is_none());
+        assert!(PeerSelector::weighted_choice(&[], &mut rng).is_none());
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    rc::Rc,
+    cell::RefCell,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicU64, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+use crate::{Id, Identity, CryptoIdentity, NodeInfo, Network, signature};
+use crate::dht::{
+    Node,
+    NodeConfiguration,
+    msg::{Message, msg},
+    rpc::{
+        RpcCall,
+        rpc_server::RpcServer,
+        receivers::{self, Receivers, RECEIVE_QUEUE_SIZE},
+    },
+    timer_client::{LocalTimerClient, LocalTimerCmd},
+};
+
+fn loopback() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+// A node sending packets by hand.
+struct Sender {
+    socket  : UdpSocket,
+    identity: CryptoIdentity,
+}
+
+impl Sender {
+    fn new() -> Self {
+        let socket = UdpSocket::bind(loopback()).unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        Self { socket, identity: CryptoIdentity::new() }
+    }
+
+    fn ni(&self) -> NodeInfo {
+        NodeInfo::new(*self.identity.id(), self.socket.local_addr().unwrap())
+    }
+
+    fn recv(&self) -> Message {
+        let mut buf = [0u8; 2048];
+        let (len, _) = self.socket.recv_from(&mut buf).unwrap();
+        let from = Id::try_from(&buf[..Id::BYTES]).unwrap();
+        let plain = self.identity.decrypt_into(&from, &buf[Id::BYTES..len]).unwrap();
+        serde_cbor::from_slice::<Message>(&plain).unwrap()
+    }
+
+    fn packet(&self, to: &Id, msg: &Message) -> Vec<u8> {
+        let data = serde_cbor::to_vec(msg).unwrap();
+        let mut packet = self.identity.id().as_bytes().to_vec();
+        packet.extend(self.identity.encrypt_into(to, &data).unwrap());
+        packet
+    }
+
+    fn send(&self, data: &[u8], to: SocketAddr) {
+        self.socket.send_to(data, to).unwrap();
+    }
+}
+
+async fn server(sockets: usize) -> (Rc<RefCell<RpcServer>>, Id, mpsc::UnboundedReceiver<LocalTimerCmd>) {
+    let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+    let identity = Arc::new(CryptoIdentity::new());
+    let ni = NodeInfo::new(*identity.id(), loopback());
+
+    let id = *identity.id();
+    let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+    rs.set_receive_sockets(sockets);
+    rs.start().await.unwrap();
+
+    let server = Rc::new(RefCell::new(rs));
+    server.borrow_mut().set_cloned(Rc::downgrade(&server));
+    (server, id, timers)
+}
+
+// Pings answered per second by a node reading the given number of sockets,
+// with clients sending as fast as they can for the duration.
+async fn ping_rate(sockets: usize, clients: usize, duration: Duration) -> f64 {
+    let dir = format!("/tmp/receivers_{:016x}", rand::random::<u64>());
+    fs::create_dir_all(&dir).unwrap();
+    let yaml = format!(
+        "ipv4: true\nport: 39020\nprivateKey: \"{}\"\ndataDir: {dir}\n\
+         databaseUri: jdbc:sqlite:storage.db\nstorageBackend: memory\nreceiveSockets: {sockets}\n",
+        signature::KeyPair::random().private_key()
+    );
+    let node = Node::new(Box::new(NodeConfiguration::from(&yaml).unwrap())).unwrap();
+    node.start().await.unwrap();
+    let ni = node.node_info();
+    let (to, to_id) = (*ni.socket_addr(), *ni.id());
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let answered = Arc::new(AtomicU64::new(0));
+    let threads = (0..clients).flat_map(|_| {
+        let client = Arc::new(Sender::new());
+        let sending = {
+            let (client, stop) = (client.clone(), stop.clone());
+            thread::spawn(move || while !stop.load(Ordering::Relaxed) {
+                let _ = client.socket.send_to(&client.packet(&to_id, &msg::ping_request()), to);
+            })
+        };
+        let receiving = {
+            let (stop, answered) = (stop.clone(), answered.clone());
+            thread::spawn(move || {
+                let mut buf = [0u8; 2048];
+                while !stop.load(Ordering::Relaxed) {
+                    if client.socket.recv_from(&mut buf).is_ok() {
+                        answered.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+        };
+        [sending, receiving]
+    }).collect::<Vec<_>>();
+
+    tokio::time::sleep(duration).await;
+    stop.store(true, Ordering::Relaxed);
+    threads.into_iter().for_each(|t| t.join().unwrap());
+
+    if let Some(stats) = node.receive_stats(Network::IPv4).await {
+        println!("{} sockets: packets {:?}, dropped {:?}", sockets, stats.packets(), stats.dropped());
+    }
+    let _ = node.stop().await;
+    let _ = fs::remove_dir_all(&dir);
+    answered.load(Ordering::Relaxed) as f64 / duration.as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bind_reuse_port() {
+        let sockets = receivers::bind_reuse_port(loopback(), 4).unwrap();
+        let port = sockets[0].local_addr().unwrap().port();
+        assert_ne!(port, 0);
+        assert!(sockets.iter().all(|s| s.local_addr().unwrap().port() == port));
+
+        // The port is taken for sockets without SO_REUSEPORT.
+        assert!(UdpSocket::bind(SocketAddr::from(([127, 0, 0, 1], port))).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_receive_and_decrypt() {
+        let identity = Arc::new(CryptoIdentity::new());
+        let sockets = receivers::bind_reuse_port(loopback(), 2).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        let (receivers, mut rx) = Receivers::spawn("test", sockets, identity.clone(), None, None).unwrap();
+
+        let sender = Sender::new();
+        let ping = msg::ping_request();
+        sender.send(&sender.packet(identity.id(), &ping), addr);
+        sender.send(b"too short", addr);
+
+        let inbound = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(inbound.from, sender.socket.local_addr().unwrap());
+        let (from_id, decrypted) = inbound.decrypted.unwrap();
+        assert_eq!(from_id, *sender.identity.id());
+        assert_eq!(serde_cbor::from_slice::<Message>(&decrypted).unwrap().txid(), ping.txid());
+
+        // Left as it arrived for the DHT thread to reject.
+        let inbound = tokio::time::timeout(Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        assert!(inbound.decrypted.is_none());
+        assert_eq!(inbound.data, b"too short");
+
+        let stats = receivers.stats();
+        assert_eq!(stats.sockets(), 2);
+        assert_eq!(stats.total_packets(), 2);
+        assert_eq!(stats.total_dropped(), 0);
+        assert_eq!(stats.capacity(), RECEIVE_QUEUE_SIZE);
+        assert_eq!(stats.depth(), 0);
+
+        drop(receivers);
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_queue_depth() {
+        let identity = Arc::new(CryptoIdentity::new());
+        let sockets = receivers::bind_reuse_port(loopback(), 2).unwrap();
+        let addr = sockets[0].local_addr().unwrap();
+        let (receivers, _rx) = Receivers::spawn("test", sockets, identity.clone(), None, None).unwrap();
+
+        let sender = Sender::new();
+        for _ in 0..3 {
+            sender.send(&sender.packet(identity.id(), &msg::ping_request()), addr);
+        }
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(receivers.stats().depth(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_multi_socket_server() {
+        let (server, id, _timers) = server(4).await;
+        let addr = server.borrow().local_addr().unwrap();
+        let mut inbound = server.borrow_mut().take_inbound().unwrap();
+        assert!(server.borrow_mut().take_inbound().is_none());
+
+        // A call goes out and its response comes back through a receive thread.
+        let responder = Sender::new();
+        server.borrow_mut().send_call(RpcCall::new(responder.ni(), msg::ping_request())).unwrap();
+        let req = responder.recv();
+        assert_eq!(server.borrow().inflight_calls(), 1);
+
+        responder.send(&responder.packet(&id, &msg::ping_response(req.txid())), addr);
+        let packet = tokio::time::timeout(Duration::from_secs(2), inbound.recv()).await.unwrap().unwrap();
+        RpcServer::handle_inbound(server.clone(), packet).await;
+        assert_eq!(server.borrow().inflight_calls(), 0);
+        assert_eq!(server.borrow().counters().msgs_received, 1);
+
+        let stats = server.borrow().receive_stats().unwrap();
+        assert_eq!(stats.sockets(), 4);
+        assert_eq!(stats.total_packets(), 1);
+
+        server.borrow_mut().stop().await;
+        assert!(server.borrow().receive_stats().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_single_socket_server() {
+        let (server, _, _timers) = server(1).await;
+        assert!(server.borrow_mut().take_inbound().is_none());
+        assert!(server.borrow().receive_stats().is_none());
+    }
+
+    // Compares the pings answered with one socket and four under a flood,
+    // run by hand on a multi-core machine:
+    // cargo test --lib test_receive_load -- --ignored --nocapture
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore]
+    async fn test_receive_load() {
+        let duration = Duration::from_secs(5);
+        let single = ping_rate(1, 8, duration).await;
+        let multi = ping_rate(4, 8, duration).await;
+        println!("Pings answered per second: {single:.0} with 1 socket, {multi:.0} with 4 sockets");
+        assert!(multi > single);
+    }
+}
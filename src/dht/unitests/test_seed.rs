@@ -0,0 +1,106 @@
+use std::path::PathBuf;
+use serde_cbor::Value as CborValue;
+
+use crate::{
+    random_bytes,
+    PeerInfo,
+    ImmutableBuilder as ValueBuilder,
+    SignedBuilder,
+    signature::KeyPair,
+};
+use crate::dht::{
+    seed::{self, Record, SeedReport},
+    storage::{
+        data_storage::DataStorage,
+        memory_storage::MemoryStorage,
+    },
+};
+
+fn seed_path() -> PathBuf {
+    std::env::temp_dir().join(format!("seed-{:016x}.cbor", rand::random::<u64>()))
+}
+
+fn open_storage() -> MemoryStorage {
+    let mut storage = MemoryStorage::new();
+    storage.open("").unwrap();
+    storage
+}
+
+#[test]
+fn test_pack_and_load() {
+    let immutable = ValueBuilder::new(&random_bytes(32)).build().unwrap();
+    let signed = SignedBuilder::new(&random_bytes(32))
+        .with_keypair(&KeyPair::random())
+        .with_sequence_number(2)
+        .build()
+        .unwrap();
+    let peer = PeerInfo::builder("tcp://127.0.0.1:8090").build().unwrap();
+
+    let records = vec![immutable.clone().into(), signed.clone().into(), peer.clone().into()];
+    let mut bytes = Vec::new();
+    seed::pack(&records, &mut bytes).unwrap();
+    let path = seed_path();
+    std::fs::write(&path, &bytes).unwrap();
+
+    let mut storage = open_storage();
+    let report = seed::load(&path, &mut storage).unwrap();
+    assert_eq!(report, SeedReport { values: 2, peers: 1, invalid: 0, outdated: 0 });
+    assert_eq!(storage.get_value(&immutable.id()).unwrap(), Some(immutable));
+    // Packed without the private keys.
+    let stored = storage.get_value(&signed.id()).unwrap().unwrap();
+    assert_eq!(stored.data(), signed.data());
+    assert_eq!(stored.signature(), signed.signature());
+    assert_eq!(stored.sequence_number(), 2);
+    let stored = storage.get_peer(peer.id(), peer.fingerprint()).unwrap().unwrap();
+    assert_eq!(stored.signature(), peer.signature());
+    assert!(stored.private_key().is_none());
+
+    // Seeded values and peers are re-announced like the node's own.
+    let now = crate::as_ms!(std::time::SystemTime::now()) as u64 + 1;
+    assert_eq!(storage.get_values_announced_before(true, now).unwrap().len(), 2);
+    assert_eq!(storage.get_peers_announced_before(true, now).unwrap().len(), 1);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_skip_invalid_and_outdated() {
+    let keypair = KeyPair::random();
+    let data = random_bytes(32);
+    let older = SignedBuilder::new(&data)
+        .with_keypair(&keypair)
+        .with_sequence_number(1)
+        .build()
+        .unwrap();
+    let newer = SignedBuilder::new(&data)
+        .with_keypair(&keypair)
+        .with_sequence_number(3)
+        .build()
+        .unwrap();
+
+    // A signed value with its data altered after signing.
+    let mut forged = serde_cbor::value::to_value(Record::from(newer.clone())).unwrap();
+    let CborValue::Map(record) = &mut forged else { panic!("record is not a map") };
+    let Some(CborValue::Map(value)) = record.values_mut().next() else { panic!("value is not a map") };
+    value.insert(CborValue::Text("v".into()), CborValue::Bytes(random_bytes(32)));
+
+    let items = vec![
+        serde_cbor::value::to_value(Record::from(older.clone())).unwrap(),
+        forged,
+        CborValue::Text("not a record".into()),
+    ];
+    let path = seed_path();
+    std::fs::write(&path, serde_cbor::to_vec(&items).unwrap()).unwrap();
+
+    // Never downgrades a newer value stored already.
+    let mut storage = open_storage();
+    storage.put_value(newer.clone(), false).unwrap();
+    let report = seed::load(&path, &mut storage).unwrap();
+    assert_eq!(report, SeedReport { values: 0, peers: 0, invalid: 2, outdated: 1 });
+    assert_eq!(storage.get_value(&newer.id()).unwrap().map(|v| v.sequence_number()), Some(3));
+
+    std::fs::write(&path, b"not cbor").unwrap();
+    assert!(seed::load(&path, &mut open_storage()).is_err());
+    std::fs::remove_file(&path).unwrap();
+    assert!(seed::load(&path, &mut open_storage()).is_err());
+}
@@ -0,0 +1,220 @@
+use std::{
+    cell::{Cell, RefCell},
+    net::{SocketAddr, UdpSocket},
+    rc::Rc,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+use crate::{CryptoIdentity, Identity, NodeInfo};
+use crate::dht::{
+    msg::msg,
+    handler::Handler,
+    rpc::{
+        RpcCall,
+        rpc_server::RpcServer,
+        send_shaper::{SendShaper, SendShaperOptions},
+    },
+    timer_client::{GenericTimerCmd, LocalTimerClient, LocalTimerCmd},
+};
+
+fn options(rate: u32, interval_ms: u64, burst: u32, pacing_ms: u64) -> SendShaperOptions {
+    SendShaperOptions {
+        rate,
+        interval: Duration::from_millis(interval_ms),
+        burst,
+        pacing: Duration::from_millis(pacing_ms),
+    }
+}
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+fn sink() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+    socket
+}
+
+fn drain(socket: &UdpSocket) -> usize {
+    let mut buf = [0u8; 2048];
+    let mut count = 0;
+    while socket.recv_from(&mut buf).is_ok() {
+        count += 1;
+    }
+    count
+}
+
+struct TestServer {
+    server  : Rc<RefCell<RpcServer>>,
+    sent    : Rc<RefCell<Vec<(SocketAddr, Instant)>>>,
+    calls   : Rc<Cell<usize>>,
+    timers  : mpsc::UnboundedReceiver<LocalTimerCmd>,
+}
+
+impl TestServer {
+    async fn new(options: SendShaperOptions) -> Self {
+        let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+        let identity = Arc::new(CryptoIdentity::new());
+        let ni = NodeInfo::new(identity.id().clone(), addr(0));
+
+        let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+        rs.set_send_shaper(options);
+
+        let sent = Rc::new(RefCell::new(Vec::new()));
+        let cloned = sent.clone();
+        rs.sent_handler(Handler::new(move |dest: &SocketAddr| {
+            cloned.borrow_mut().push((*dest, Instant::now()));
+        }));
+        let calls = Rc::new(Cell::new(0));
+        let cloned = calls.clone();
+        rs.callsent_handler(Handler::new(move |_| cloned.set(cloned.get() + 1)));
+        rs.start().await.unwrap();
+
+        let server = Rc::new(RefCell::new(rs));
+        server.borrow_mut().set_cloned(Rc::downgrade(&server));
+        Self { server, sent, calls, timers }
+    }
+
+    fn ping(&self, to: SocketAddr) {
+        let target = NodeInfo::new(CryptoIdentity::new().id().clone(), to);
+        self.server.borrow_mut().send_call(RpcCall::new(target, msg::ping_request())).unwrap();
+    }
+
+    // Fires the send queue timers until the queue is empty, call timeouts
+    // are left alone.
+    async fn run_queue(&mut self) {
+        while self.server.borrow().queued_packets() > 0 {
+            let cmd = tokio::time::timeout(Duration::from_secs(2), self.timers.recv())
+                .await.unwrap().unwrap();
+            if let GenericTimerCmd::Add { delay, interval: None, cb, .. } = cmd {
+                if delay < 1000 {
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    cb.cb(()).await;
+                }
+            }
+        }
+    }
+
+    fn gaps(&self, dest: SocketAddr) -> Vec<Duration> {
+        let sent = self.sent.borrow();
+        let times = sent.iter().filter(|(d, _)| *d == dest).map(|(_, t)| *t).collect::<Vec<_>>();
+        times.windows(2).map(|w| w[1] - w[0]).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled() {
+        let mut shaper = SendShaper::new(SendShaperOptions::default());
+        let now = Instant::now();
+        assert!(!shaper.is_enabled());
+        for _ in 0..1000 {
+            assert!(shaper.wait(&addr(1), now).is_zero());
+            shaper.consume(addr(1), now);
+        }
+    }
+
+    #[test]
+    fn test_rate_and_burst() {
+        // One packet per 10ms, three back-to-back
+        let mut shaper = SendShaper::new(options(10, 100, 3, 0));
+        let t0 = Instant::now();
+        for port in 1..=3 {
+            assert!(shaper.wait(&addr(port), t0).is_zero());
+            shaper.consume(addr(port), t0);
+        }
+
+        let wait = shaper.wait(&addr(4), t0);
+        assert!(wait > Duration::from_millis(9) && wait <= Duration::from_millis(10));
+        assert!(shaper.wait(&addr(4), t0 + wait).is_zero());
+
+        // Idle time refills the bucket up to the burst size only
+        let later = t0 + Duration::from_secs(10);
+        for port in 1..=3 {
+            assert!(shaper.wait(&addr(port), later).is_zero());
+            shaper.consume(addr(port), later);
+        }
+        assert!(!shaper.wait(&addr(4), later).is_zero());
+    }
+
+    #[test]
+    fn test_pacing() {
+        let mut shaper = SendShaper::new(options(1000, 100, 100, 50));
+        let t0 = Instant::now();
+        shaper.consume(addr(1), t0);
+
+        assert_eq!(shaper.wait(&addr(1), t0), Duration::from_millis(50));
+        assert_eq!(shaper.wait(&addr(1), t0 + Duration::from_millis(20)), Duration::from_millis(30));
+        assert!(shaper.wait(&addr(1), t0 + Duration::from_millis(50)).is_zero());
+        assert!(shaper.wait(&addr(2), t0).is_zero());
+    }
+
+    #[test]
+    fn test_queued_spacing() {
+        // One packet per 20ms, the bucket refilled by a late flush
+        let mut shaper = SendShaper::new(options(5, 100, 2, 0));
+        let t0 = Instant::now();
+        let late = t0 + Duration::from_millis(100);
+        assert!(shaper.wait_queued(&addr(1), late).is_zero());
+        shaper.consume_queued(addr(1), late);
+
+        assert!(shaper.wait(&addr(2), late).is_zero());
+        assert_eq!(shaper.wait_queued(&addr(2), late), Duration::from_millis(20));
+        assert!(shaper.wait_queued(&addr(2), late + Duration::from_millis(20)).is_zero());
+    }
+
+    #[tokio::test]
+    async fn test_queued_sends_are_spaced() {
+        let peer = sink();
+        let dest = peer.local_addr().unwrap();
+        let mut ts = TestServer::new(options(5, 100, 2, 0)).await;
+
+        for _ in 0..8 {
+            ts.ping(dest);
+        }
+        // Only the burst left, the queued calls are not sent yet
+        assert_eq!(ts.sent.borrow().len(), 2);
+        assert_eq!(ts.calls.get(), 2);
+        assert_eq!(ts.server.borrow().queued_packets(), 6);
+        assert_eq!(ts.server.borrow().counters().msgs_shaped, 6);
+
+        ts.run_queue().await;
+        assert_eq!(ts.sent.borrow().len(), 8);
+        assert_eq!(ts.calls.get(), 8);
+        assert_eq!(ts.server.borrow().counters().msgs_sent, 8);
+        assert_eq!(drain(&peer), 8);
+
+        let gaps = ts.gaps(dest);
+        assert!(gaps[0] < Duration::from_millis(5));
+        assert!(gaps[1..].iter().all(|gap| *gap >= Duration::from_millis(15)), "{gaps:?}");
+    }
+
+    #[tokio::test]
+    async fn test_pacing_per_destination() {
+        let (peer1, peer2) = (sink(), sink());
+        let (dest1, dest2) = (peer1.local_addr().unwrap(), peer2.local_addr().unwrap());
+        let mut ts = TestServer::new(options(1000, 100, 100, 40)).await;
+
+        ts.ping(dest1);
+        ts.ping(dest1);
+        ts.ping(dest1);
+        ts.ping(dest2);
+
+        // dest2 is not held back by the packets waiting for dest1
+        let order = ts.sent.borrow().iter().map(|(d, _)| *d).collect::<Vec<_>>();
+        assert_eq!(order, vec![dest1, dest2]);
+
+        ts.run_queue().await;
+        assert_eq!(drain(&peer1), 3);
+        assert_eq!(drain(&peer2), 1);
+        let gaps = ts.gaps(dest1);
+        assert_eq!(gaps.len(), 2);
+        assert!(gaps.iter().all(|gap| *gap >= Duration::from_millis(35)), "{gaps:?}");
+    }
+}
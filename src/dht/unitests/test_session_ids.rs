@@ -0,0 +1,185 @@
+use std::{
+    rc::Rc,
+    cell::RefCell,
+    net::{SocketAddr, UdpSocket},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc;
+
+use crate::{Id, Identity, CryptoIdentity, NodeInfo};
+use crate::dht::{
+    msg::{Message, msg},
+    rpc::{RpcCall, rpc_server::RpcServer},
+    session_ids::SessionIds,
+    timer_client::{LocalTimerClient, LocalTimerCmd},
+};
+
+fn ids(identities: &[Arc<CryptoIdentity>]) -> Vec<Id> {
+    identities.iter().map(|identity| *identity.id()).collect()
+}
+
+// A node answering by hand, seeing the requester id in the packet header.
+struct Responder {
+    socket  : UdpSocket,
+    identity: CryptoIdentity,
+}
+
+impl Responder {
+    fn new() -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+        Self { socket, identity: CryptoIdentity::new() }
+    }
+
+    fn ni(&self) -> NodeInfo {
+        NodeInfo::new(*self.identity.id(), self.socket.local_addr().unwrap())
+    }
+
+    fn recv(&self) -> (Id, Message) {
+        let mut buf = [0u8; 2048];
+        let (len, _) = self.socket.recv_from(&mut buf).unwrap();
+        let from = Id::try_from(&buf[..Id::BYTES]).unwrap();
+        let plain = self.identity.decrypt_into(&from, &buf[Id::BYTES..len]).unwrap();
+        (from, serde_cbor::from_slice::<Message>(&plain).unwrap())
+    }
+
+    fn packet(&self, to: &Id, msg: &Message) -> Vec<u8> {
+        let data = serde_cbor::to_vec(msg).unwrap();
+        let mut packet = self.identity.id().as_bytes().to_vec();
+        packet.extend(self.identity.encrypt_into(to, &data).unwrap());
+        packet
+    }
+}
+
+// Returns the node id along.
+async fn server(sessions: Rc<RefCell<SessionIds>>) -> (Rc<RefCell<RpcServer>>, Id, mpsc::UnboundedReceiver<LocalTimerCmd>) {
+    let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+    let identity = Arc::new(CryptoIdentity::new());
+    let ni = NodeInfo::new(*identity.id(), SocketAddr::from(([127, 0, 0, 1], 0)));
+
+    let id = *identity.id();
+    let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+    rs.set_session_ids(sessions);
+    rs.start().await.unwrap();
+
+    let server = Rc::new(RefCell::new(rs));
+    server.borrow_mut().set_cloned(Rc::downgrade(&server));
+    (server, id, timers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_lookup() {
+        let mut sessions = SessionIds::new(Duration::ZERO);
+        let now = Instant::now();
+
+        let first = sessions.next(now);
+        let second = sessions.next(now);
+        assert_ne!(first.id(), second.id());
+
+        // The current one first, then the retired newest first.
+        let third = sessions.next(now);
+        assert_eq!(ids(&sessions.identities(now)),
+            ids(&[third.clone(), second.clone(), first.clone()]));
+    }
+
+    #[test]
+    fn test_rotation() {
+        let rotation = Duration::from_secs(30);
+        let mut sessions = SessionIds::new(rotation);
+        let now = Instant::now();
+
+        let first = sessions.next(now);
+        let again = sessions.next(now + Duration::from_secs(29));
+        assert_eq!(first.id(), again.id());
+
+        let second = sessions.next(now + rotation);
+        assert_ne!(first.id(), second.id());
+        assert_eq!(ids(&sessions.identities(now + rotation)), ids(&[second, first]));
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut sessions = SessionIds::new(Duration::ZERO);
+        let now = Instant::now();
+
+        let running = sessions.next(now);
+        let done = *sessions.next(now).id();
+        let current = sessions.next(now);
+
+        // The lookup still holding its identity keeps it past the retention.
+        let later = now + SessionIds::RETENTION;
+        assert_eq!(ids(&sessions.identities(later)), vec![*current.id(), *running.id()]);
+        assert!(!ids(&sessions.identities(later)).contains(&done));
+
+        drop(running);
+        assert_eq!(ids(&sessions.identities(later)), vec![*current.id()]);
+    }
+
+    #[test]
+    fn test_max_retired() {
+        let mut sessions = SessionIds::new(Duration::ZERO);
+        let now = Instant::now();
+
+        let held = (0..SessionIds::MAX_RETIRED + 8)
+            .map(|_| sessions.next(now))
+            .collect::<Vec<_>>();
+        let identities = sessions.identities(now);
+        assert_eq!(identities.len(), SessionIds::MAX_RETIRED + 1);
+        assert_eq!(identities[0].id(), held.last().unwrap().id());
+    }
+
+    #[tokio::test]
+    async fn test_lookups_from_session_ids() {
+        let sessions = Rc::new(RefCell::new(SessionIds::new(Duration::ZERO)));
+        let (server, real, _timers) = server(sessions.clone()).await;
+        let responder = Responder::new();
+
+        for _ in 0..3 {
+            let mut req = msg::find_value_request(Id::random(), true, false, -1);
+            req.set_transient();
+            let mut call = RpcCall::new(responder.ni(), req);
+            call.set_sender(sessions.borrow_mut().next(Instant::now()));
+            server.borrow_mut().send_call(call).unwrap();
+        }
+
+        // A new requester id per lookup, none of them the node id.
+        let received = (0..3).map(|_| responder.recv()).collect::<Vec<_>>();
+        let observed = received.iter().map(|(from, _)| *from).collect::<Vec<_>>();
+        assert!(!observed.contains(&real));
+        assert_ne!(observed[0], observed[1]);
+        assert_ne!(observed[1], observed[2]);
+        assert_ne!(observed[0], observed[2]);
+        assert!(received.iter().all(|(_, req)| req.is_transient()));
+
+        let from = *responder.ni().socket_addr();
+        let response = |txid: i32| {
+            msg::find_value_response_with_nodes(txid, Some(vec![responder.ni()]), None)
+        };
+
+        assert_eq!(server.borrow().inflight_calls(), 3);
+
+        // The response to a session id matches the call sent from it.
+        let (session, req) = &received[0];
+        let packet = responder.packet(session, &response(req.txid()));
+        RpcServer::handle_packet(server.clone(), &packet, from).await;
+        assert_eq!(server.borrow().inflight_calls(), 2);
+
+        // Encrypted for the node id, it was not sent from there.
+        let (_, req) = &received[1];
+        let packet = responder.packet(&real, &response(req.txid()));
+        RpcServer::handle_packet(server.clone(), &packet, from).await;
+        assert_eq!(server.borrow().inflight_calls(), 2);
+
+        // A session id takes no requests.
+        let received_before = server.borrow().counters().msgs_received;
+        let (session, _) = &received[2];
+        let packet = responder.packet(session, &msg::ping_request());
+        RpcServer::handle_packet(server.clone(), &packet, from).await;
+        assert_eq!(server.borrow().counters().msgs_received, received_before);
+    }
+}
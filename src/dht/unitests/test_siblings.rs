@@ -0,0 +1,99 @@
+use std::{
+    cmp::Ordering,
+    net::SocketAddr,
+};
+
+use crate::{Id, Network, NodeInfo};
+use crate::dht::siblings::Siblings;
+
+fn node4(port: u16) -> NodeInfo {
+    NodeInfo::new(Id::random(), SocketAddr::from(([10, 0, 0, 1], port)))
+}
+
+fn node6(port: u16) -> NodeInfo {
+    NodeInfo::new(Id::random(), SocketAddr::from(([0x2001, 0xdb8, 0, 0, 0, 0, 0, 1], port)))
+}
+
+fn dual_stack() -> Siblings {
+    let siblings = Siblings::new();
+    siblings.attach(Network::IPv4);
+    siblings.attach(Network::IPv6);
+    siblings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_closest() {
+        let siblings = dual_stack();
+        let nodes = (1..=20).map(node6).collect::<Vec<_>>();
+        siblings.publish(Network::IPv6, nodes);
+
+        let target = Id::random();
+        let closest = siblings.closest(Network::IPv6, &target, 8).unwrap();
+        assert_eq!(closest.len(), 8);
+        assert!(closest.windows(2).all(|pair|
+            target.three_way_compare(pair[0].id(), pair[1].id()) != Ordering::Greater
+        ));
+
+        // Nothing published yet for the other family
+        assert_eq!(siblings.closest(Network::IPv4, &target, 8), Some(vec![]));
+    }
+
+    #[test]
+    fn test_absent_family() {
+        let siblings = Siblings::new();
+        siblings.attach(Network::IPv4);
+        siblings.publish(Network::IPv6, vec![node6(1)]);
+
+        assert_eq!(siblings.closest(Network::IPv6, &Id::random(), 8), None);
+        assert!(!siblings.wants_contacts(Network::IPv6));
+        siblings.hand_over(Network::IPv6, &[node6(2)]);
+        assert!(siblings.take_handed_over(Network::IPv6).is_empty());
+    }
+
+    #[test]
+    fn test_wants_contacts() {
+        let siblings = dual_stack();
+        assert!(siblings.wants_contacts(Network::IPv6));
+
+        let nodes = (1..=Siblings::WANT_CONTACTS_IF_LESS_THAN as u16).map(node6).collect();
+        siblings.publish(Network::IPv6, nodes);
+        assert!(!siblings.wants_contacts(Network::IPv6));
+        assert!(siblings.wants_contacts(Network::IPv4));
+    }
+
+    #[test]
+    fn test_hand_over() {
+        let siblings = dual_stack();
+        let known = node6(1);
+        siblings.publish(Network::IPv6, vec![known.clone()]);
+
+        let fresh = node6(2);
+        siblings.hand_over(Network::IPv6, &[known, fresh.clone(), fresh.clone(), node4(3)]);
+        assert_eq!(siblings.take_handed_over(Network::IPv6), vec![fresh]);
+        assert!(siblings.take_handed_over(Network::IPv6).is_empty());
+
+        let many = (1..=100).map(node6).collect::<Vec<_>>();
+        siblings.hand_over(Network::IPv6, &many);
+        assert_eq!(siblings.take_handed_over(Network::IPv6).len(), Siblings::MAX_HANDED_OVER);
+    }
+
+    #[test]
+    fn test_detach() {
+        let siblings = dual_stack();
+        siblings.publish(Network::IPv6, vec![node6(1)]);
+        siblings.hand_over(Network::IPv6, &[node6(2)]);
+
+        siblings.detach(Network::IPv6);
+        assert_eq!(siblings.closest(Network::IPv6, &Id::random(), 8), None);
+        assert!(siblings.take_handed_over(Network::IPv6).is_empty());
+
+        // Publishing after the instance stopped is ignored
+        siblings.publish(Network::IPv6, vec![node6(3)]);
+        siblings.attach(Network::IPv6);
+        assert_eq!(siblings.closest(Network::IPv6, &Id::random(), 8), Some(vec![]));
+    }
+}
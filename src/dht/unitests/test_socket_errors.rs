@@ -0,0 +1,129 @@
+use std::{
+    cell::RefCell,
+    io,
+    net::{SocketAddr, UdpSocket},
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::mpsc;
+
+use crate::{CryptoIdentity, Identity, NodeInfo};
+use crate::dht::{
+    msg::msg,
+    rpc::{RpcCall, rpc_server::RpcServer},
+    socket_event::{ErrnoClass, SocketErrors, SocketEvent},
+    timer_client::{LocalTimerClient, LocalTimerCmd},
+};
+
+fn loopback() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+// Socket errors with a handler keeping the events it is told.
+fn socket_errors() -> (Arc<SocketErrors>, Arc<Mutex<Vec<SocketEvent>>>) {
+    let errors = Arc::new(SocketErrors::new());
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let cloned = events.clone();
+    errors.set_handler(Box::new(move |event| cloned.lock().unwrap().push(event.clone())));
+    (errors, events)
+}
+
+fn server(addr: SocketAddr, errors: Arc<SocketErrors>) -> (RpcServer, mpsc::UnboundedReceiver<LocalTimerCmd>) {
+    let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+    let identity = Arc::new(CryptoIdentity::new());
+    let ni = NodeInfo::new(*identity.id(), addr);
+
+    let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+    rs.set_socket_errors(errors);
+    (rs, timers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_errno_class() {
+        let class = |kind| ErrnoClass::of(&io::Error::from(kind));
+        assert_eq!(class(io::ErrorKind::AddrInUse), ErrnoClass::AddressInUse);
+        assert_eq!(class(io::ErrorKind::AddrNotAvailable), ErrnoClass::AddressUnavailable);
+        assert_eq!(class(io::ErrorKind::NetworkUnreachable), ErrnoClass::Unreachable);
+        assert_eq!(class(io::ErrorKind::PermissionDenied), ErrnoClass::PermissionDenied);
+        assert_eq!(class(io::ErrorKind::ConnectionRefused), ErrnoClass::Refused);
+        assert_eq!(class(io::ErrorKind::InvalidData), ErrnoClass::Other);
+    }
+
+    #[tokio::test]
+    async fn test_send_failed() {
+        let (errors, events) = socket_errors();
+        let (rs, _timers) = server(loopback(), errors.clone());
+        let server = Rc::new(RefCell::new(rs));
+        server.borrow_mut().set_cloned(Rc::downgrade(&server));
+        server.borrow_mut().start().await.unwrap();
+
+        // An IPv6 destination is out of reach of an IPv4 socket.
+        let dest = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 39001));
+        let target = NodeInfo::new(*CryptoIdentity::new().id(), dest);
+        let _ = server.borrow_mut().send_call(RpcCall::new(target, msg::ping_request()));
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 1);
+        let SocketEvent::SendFailed { errno, dest: failed } = events[0] else {
+            panic!("Unexpected event {}", events[0]);
+        };
+        assert_eq!(failed, dest);
+        assert!(errno.is_some());
+
+        let stats = errors.stats();
+        assert_eq!(stats.send_errors().get(&ErrnoClass::AddressFamily), Some(&1));
+        assert_eq!(stats.total_send_errors(), 1);
+        assert_eq!(stats.total_receive_errors(), 0);
+        server.borrow_mut().stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_bind_failed() {
+        let taken = UdpSocket::bind(loopback()).unwrap();
+        let addr = taken.local_addr().unwrap();
+
+        let (errors, events) = socket_errors();
+        let (mut rs, _timers) = server(addr, errors.clone());
+        assert!(rs.start().await.is_err());
+
+        let events = events.lock().unwrap().clone();
+        assert!(matches!(events[..], [SocketEvent::BindFailed { addr: a, errno: Some(_) }] if a == addr));
+        assert_eq!(errors.stats().bind_failures(), 1);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let (errors, events) = socket_errors();
+        let refused = io::Error::from(io::ErrorKind::ConnectionRefused);
+        for _ in 0..25 {
+            errors.receive_failed(&refused);
+        }
+        // Bind failures and rebinds are never held back.
+        errors.bind_failed(loopback(), &refused);
+        errors.rebound(loopback(), loopback());
+
+        let events = events.lock().unwrap();
+        let limit = SocketErrors::MAX_EVENTS as usize;
+        assert_eq!(events.len(), limit + 2);
+        assert!(events[..limit].iter().all(|e| e == &SocketEvent::ReceiveFailed { errno: None }));
+
+        let stats = errors.stats();
+        assert_eq!(stats.receive_errors().get(&ErrnoClass::Refused), Some(&25));
+        assert_eq!(stats.suppressed_events(), 15);
+        assert_eq!(stats.bind_failures(), 1);
+    }
+
+    #[test]
+    fn test_no_handler() {
+        let errors = SocketErrors::new();
+        errors.send_failed(loopback(), &io::Error::from(io::ErrorKind::PermissionDenied));
+
+        let stats = errors.stats();
+        assert_eq!(stats.send_errors().get(&ErrnoClass::PermissionDenied), Some(&1));
+        assert_eq!(stats.suppressed_events(), 0);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    cell::RefCell,
+    net::{IpAddr, SocketAddr, UdpSocket},
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::mpsc;
+
+use crate::{CryptoIdentity, Identity, NodeInfo};
+use crate::dht::{
+    msg::msg,
+    handler::LocalHandler,
+    node_event::{EventLog, NodeEventKind},
+    rpc::{
+        RpcCall,
+        rpc_server::RpcServer,
+        socket_health::{SocketEvent, SocketFault, SocketHealth, SocketHealthOptions},
+    },
+    timer_client::{LocalTimerClient, LocalTimerCmd},
+};
+
+fn options(recv_timeout_ms: u64, stall_calls: u32) -> SocketHealthOptions {
+    SocketHealthOptions {
+        recv_timeout: Duration::from_millis(recv_timeout_ms),
+        stall_calls,
+    }
+}
+
+fn free_addr() -> SocketAddr {
+    UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+struct TestServer {
+    server  : Rc<RefCell<RpcServer>>,
+    socket_events: Rc<RefCell<Vec<SocketEvent>>>,
+    events  : EventLog,
+    _timers : mpsc::UnboundedReceiver<LocalTimerCmd>,
+}
+
+async fn make_server(addr: SocketAddr, options: SocketHealthOptions) -> TestServer {
+    let (tx, timers) = mpsc::unbounded_channel::<LocalTimerCmd>();
+    let identity = Arc::new(CryptoIdentity::new());
+    let ni = NodeInfo::new(identity.id().clone(), addr);
+
+    let mut rs = RpcServer::new(ni, identity, Rc::new(LocalTimerClient::new(tx)), None);
+    let events = EventLog::new(16);
+    rs.set_event_log(events.clone());
+    rs.set_socket_health(options);
+
+    let socket_events = Rc::new(RefCell::new(Vec::new()));
+    let cloned = socket_events.clone();
+    rs.socket_handler(LocalHandler::new(move |event| {
+        cloned.borrow_mut().push(event);
+        Box::pin(async {})
+    }));
+    rs.start().await.unwrap();
+
+    let server = Rc::new(RefCell::new(rs));
+    server.borrow_mut().set_cloned(Rc::downgrade(&server));
+    assert!(server.borrow_mut().prepare());
+
+    TestServer { server, socket_events, events, _timers: timers }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stall_detection() {
+        let ip = "127.0.0.1".parse::<IpAddr>().unwrap();
+        let mut health = SocketHealth::new(options(20, 2));
+        assert_eq!(health.check(ip, None), None);
+
+        health.on_call_sent();
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(health.check(ip, None), None);
+
+        health.on_call_sent();
+        assert_eq!(health.check(ip, None), Some(SocketFault::Stalled));
+
+        health.on_received();
+        assert_eq!(health.check(ip, None), None);
+    }
+
+    #[test]
+    fn test_stall_detection_disabled() {
+        let ip = "127.0.0.1".parse::<IpAddr>().unwrap();
+        let mut health = SocketHealth::new(options(0, 1));
+        (0..10).for_each(|_| health.on_call_sent());
+        assert_eq!(health.check(ip, None), None);
+    }
+
+    #[test]
+    fn test_address_lost() {
+        let ip = "192.168.1.2".parse::<IpAddr>().unwrap();
+        let other = "10.0.0.2".parse::<IpAddr>().unwrap();
+        let health = SocketHealth::new(SocketHealthOptions::default());
+
+        assert_eq!(health.check(ip, Some(&[other, ip])), None);
+        assert_eq!(health.check(ip, Some(&[other])), Some(SocketFault::AddressLost));
+        assert_eq!(health.check(ip, None), None);
+
+        let any = "0.0.0.0".parse::<IpAddr>().unwrap();
+        assert_eq!(health.check(any, Some(&[other])), None);
+    }
+
+    #[tokio::test]
+    async fn test_rebind_after_stall() {
+        let addr = free_addr();
+        let ts = make_server(addr, options(20, 1)).await;
+        let rs = ts.server.clone();
+
+        // Swap in a socket nobody reads from and send a call that never gets answered
+        rs.borrow_mut().swap_socket(UdpSocket::bind("127.0.0.1:0").unwrap());
+        let peer = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = NodeInfo::new(CryptoIdentity::new().id().clone(), peer.local_addr().unwrap());
+        rs.borrow_mut().send_call(RpcCall::new(target, msg::ping_request())).unwrap();
+
+        assert!(!rs.borrow_mut().check_socket_health().await);
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert!(rs.borrow_mut().check_socket_health().await);
+        assert!(rs.borrow().rebind_pending());
+        assert_eq!(*ts.socket_events.borrow(), vec![SocketEvent::Failed(SocketFault::Stalled)]);
+
+        RpcServer::rebind(rs.clone()).await;
+        assert!(!rs.borrow().rebind_pending());
+        assert_eq!(rs.borrow().socket_generation(), 1);
+        assert_eq!(rs.borrow().local_addr(), Some(addr));
+        assert!(rs.borrow().is_reachable());
+        assert_eq!(*ts.socket_events.borrow(), vec![
+            SocketEvent::Failed(SocketFault::Stalled),
+            SocketEvent::Rebound(addr),
+        ]);
+
+        // The rebound socket receives again
+        let rx = rs.borrow().rx_tokio_socket().unwrap();
+        peer.send_to(b"ping", addr).unwrap();
+        let mut buf = [0u8; 16];
+        let (len, from) = tokio::time::timeout(Duration::from_secs(2), rx.recv_from(&mut buf))
+            .await.unwrap().unwrap();
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(from, peer.local_addr().unwrap());
+
+        assert!(!rs.borrow_mut().check_socket_health().await);
+        assert_eq!(ts.socket_events.borrow().len(), 2);
+
+        let kinds = ts.events.recent(2).into_iter().map(|e| e.kind().clone()).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![
+            NodeEventKind::SocketUnhealthy { reason: SocketFault::Stalled.reason() },
+            NodeEventKind::SocketRebound { addr },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_rebind_on_address_change() {
+        let addr = free_addr();
+        let ts = make_server(addr, options(0, 1)).await;
+        let rs = ts.server.clone();
+
+        rs.borrow_mut().set_if_addrs(|| Some(vec![
+            "127.0.0.2".parse().unwrap(),
+            "::1".parse().unwrap(),
+        ]));
+        assert!(rs.borrow_mut().check_socket_health().await);
+        RpcServer::rebind(rs.clone()).await;
+
+        let rebound = rs.borrow().local_addr().unwrap();
+        assert_eq!(rebound.ip(), "127.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!(*ts.socket_events.borrow(), vec![
+            SocketEvent::Failed(SocketFault::AddressLost),
+            SocketEvent::Rebound(rebound),
+        ]);
+
+        // The new address is known, nothing left to recover
+        assert!(!rs.borrow_mut().check_socket_health().await);
+        assert_eq!(ts.socket_events.borrow().len(), 2);
+    }
+}
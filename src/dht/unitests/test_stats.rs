@@ -0,0 +1,137 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+};
+
+use crate::Network;
+use crate::dht::stats::{
+    self,
+    DhtStats,
+    RpcCounters,
+    Concurrency,
+    CommandQueue,
+    TokenCacheStats,
+    StatsJournal,
+};
+
+fn journal_dir() -> PathBuf {
+    let dir = PathBuf::from(format!("/tmp/stats_{:016x}", rand::random::<u64>()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn dht_stats(network: Network, calls_sent: u64, calls_timeout: u64) -> DhtStats {
+    DhtStats {
+        network,
+        routing_entries: 3,
+        versions: [("MK/4".to_string(), 3)].into(),
+        reachable: true,
+        addr: Some("127.0.0.1:39001".parse().unwrap()),
+        counters: RpcCounters {
+            msgs_sent: calls_sent * 2,
+            msgs_received: calls_sent,
+            bytes_sent: calls_sent * 200,
+            bytes_received: calls_sent * 100,
+            calls_sent,
+            calls_timeout,
+            msgs_shaped: 0,
+        },
+        active_tasks: 0,
+        concurrency: Concurrency {
+            running_tasks: 2,
+            peak_tasks: 4,
+            queued_tasks: 1,
+            inflight_calls: 3,
+            peak_calls: 9,
+            deferred_calls: 0,
+        },
+        commands: CommandQueue::default(),
+        value_lookups: 0,
+        token_cache: TokenCacheStats::default(),
+        receive: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_deltas() {
+        let dir = journal_dir();
+        let path = dir.join(stats::STATS_JOURNAL_FILE);
+        let mut journal = StatsJournal::new(path.clone(), 1024 * 1024, 2);
+
+        journal.record(Some(dht_stats(Network::IPv4, 10, 2)), None, 5, 7).unwrap();
+        journal.record(Some(dht_stats(Network::IPv4, 14, 4)), None, 6, 7).unwrap();
+
+        let samples = stats::read_journal(&path);
+        assert_eq!(samples.len(), 2);
+        assert!(samples[0].timestamp() <= samples[1].timestamp());
+        assert!(samples[1].network(Network::IPv6).is_none());
+        assert_eq!(samples[1].values(), 6);
+        assert_eq!(samples[1].peers(), 7);
+
+        let first = samples[0].network(Network::IPv4).unwrap();
+        assert_eq!(first.routing_entries(), 3);
+        assert_eq!(first.versions().get("MK/4"), Some(&3));
+        assert!(first.is_reachable());
+        assert_eq!(first.calls_sent(), 10);
+        assert_eq!(first.timeout_rate(), 0.2);
+
+        let second = samples[1].network(Network::IPv4).unwrap();
+        assert_eq!(second.calls_sent(), 4);
+        assert_eq!(second.calls_timeout(), 2);
+        assert_eq!(second.msgs_sent(), 8);
+        assert_eq!(second.bytes_received(), 400);
+        assert_eq!(second.timeout_rate(), 0.5);
+        assert_eq!(second.concurrency().running_tasks(), 2);
+        assert_eq!(second.concurrency().peak_tasks(), 4);
+        assert_eq!(second.concurrency().peak_calls(), 9);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = journal_dir();
+        let path = dir.join(stats::STATS_JOURNAL_FILE);
+        let mut journal = StatsJournal::new(path.clone(), 384, 2);
+
+        for i in 0..10 {
+            journal.record(Some(dht_stats(Network::IPv4, i, 0)), None, 0, 0).unwrap();
+            assert!(fs::metadata(&path).unwrap().len() <= 384);
+        }
+
+        assert!(stats::rotated_path(&path, 1).exists());
+        assert!(stats::rotated_path(&path, 2).exists());
+        assert!(!stats::rotated_path(&path, 3).exists());
+        assert_eq!(stats::read_journal(stats::rotated_path(&path, 1)).len(), 1);
+
+        let newest = stats::read_journal(&path);
+        assert_eq!(newest.len(), 1);
+        assert_eq!(newest[0].network(Network::IPv4).unwrap().calls_sent(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_read_skips_malformed_lines() {
+        let dir = journal_dir();
+        let path = dir.join(stats::STATS_JOURNAL_FILE);
+        let mut journal = StatsJournal::new(path.clone(), 1024 * 1024, 2);
+        journal.record(None, Some(dht_stats(Network::IPv6, 1, 1)), 0, 0).unwrap();
+
+        fs::OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(b"{\"ts\":\n\n").unwrap();
+        journal.record(None, Some(dht_stats(Network::IPv6, 2, 1)), 0, 0).unwrap();
+
+        let samples = stats::read_journal(&path);
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].network(Network::IPv6).unwrap().timeout_rate(), 1.0);
+
+        assert!(stats::read_journal(dir.join("missing.log")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,8 @@
 use std::fs;
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, SystemTime};
+use diesel::{Connection, RunQueryDsl, sqlite::SqliteConnection};
 use serial_test::serial;
 
 use crate::{
@@ -14,15 +16,38 @@ use crate::{
     SignedBuilder,
     EncryptedBuilder,
     signature::KeyPair,
+    Clock,
+    SystemClock,
+    ManualClock,
+    errors::UnsupportedVersionError,
 };
-use crate::dht::storage::{
-    data_storage::DataStorage,
-    sqlite_storage::SqliteStorage,
+use crate::dht::{
+    StorageBackend,
+    Prefix,
+    KeyspaceStats,
+    storage::{
+        data_storage::DataStorage,
+        sqlite_storage::SqliteStorage,
+        memory_storage::MemoryStorage,
+        migrations,
+    },
 };
 
-fn open_storage(path: &str) -> SqliteStorage {
-    let mut s = SqliteStorage::new();
-    s.open(path).unwrap_or_else(|e| panic!("Failed to open '{}': {}", path, e));
+const BACKENDS: [StorageBackend; 2] = [
+    StorageBackend::Sqlite,
+    StorageBackend::Memory,
+];
+
+fn open_storage(backend: StorageBackend, path: &str) -> Box<dyn DataStorage> {
+    open_storage_with_clock(backend, path, Arc::new(SystemClock))
+}
+
+fn open_storage_with_clock(backend: StorageBackend, path: &str, clock: Arc<dyn Clock>) -> Box<dyn DataStorage> {
+    let mut s: Box<dyn DataStorage> = match backend {
+        StorageBackend::Sqlite => Box::new(SqliteStorage::with_clock(clock)),
+        StorageBackend::Memory => Box::new(MemoryStorage::with_clock(clock)),
+    };
+    s.open(path).unwrap_or_else(|e| panic!("Failed to open {} '{}': {}", backend, path, e));
     s
 }
 
@@ -82,6 +107,14 @@ fn make_peer(endpoint: &str, fingerprint: u64) -> PeerInfo {
     rc.unwrap()
 }
 
+fn make_peer_announced_at(endpoint: &str, fingerprint: u64, time: SystemTime) -> PeerInfo {
+    PeerInfo::builder(endpoint)
+        .with_fingerprint(fingerprint)
+        .with_announced_time(time)
+        .build()
+        .unwrap()
+}
+
 fn make_peer_with_key(kp: KeyPair, endpoint: &str, fingerprint: u64, seq: i32) -> PeerInfo {
     let rc = PeerInfo::builder(endpoint)
         .with_key(kp)
@@ -115,6 +148,7 @@ fn assert_peer_roundtrip(actual: &PeerInfo, expected: &PeerInfo) {
     assert_eq!(actual.signature(), expected.signature());
     assert_eq!(actual.endpoint(), expected.endpoint());
     assert_eq!(actual.extra_data(), expected.extra_data());
+    assert_eq!(actual.tags(), expected.tags());
 }
 
 #[test]
@@ -138,13 +172,13 @@ fn test_retain() {
     remove_db(&path);
 
     let value = make_value();
-    let mut s = open_storage(&path);
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
     let rc = s.put_value(value.clone(), false);
     assert!(rc.is_ok());
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
     let rc = s.get_value(&value.id());
     assert!(rc.is_ok());
     let fetched = rc.unwrap();
@@ -155,13 +189,11 @@ fn test_retain() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_value() {
+fn check_value(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
@@ -205,13 +237,11 @@ fn test_value() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_values() {
+fn check_values(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
@@ -252,13 +282,11 @@ fn test_values() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_values_with_expected_seq() {
+fn check_values_with_expected_seq(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
@@ -302,18 +330,16 @@ fn test_values_with_expected_seq() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_peer() {
+fn check_peer(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
-    let peer = make_peer("10.0.0.1:9000", 100);
-    let (authenticated_peer, node_id) = make_authenticated_peer("10.0.0.2:9000", 200);
+    let peer = make_peer("tcp://10.0.0.1:9000", 100);
+    let (authenticated_peer, node_id) = make_authenticated_peer("tcp://10.0.0.2:9000", 200);
 
     assert!(s.put_peer(peer.clone(), false).is_ok());
     assert!(s.put_peer(authenticated_peer.clone(), true).is_ok());
@@ -352,20 +378,18 @@ fn test_peer() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_peers() {
+fn check_peers(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
     let keypair = KeyPair::random();
-    let p1 = make_peer_with_key(keypair.clone(), "10.0.0.1:9100", 1, 0);
-    let p2 = make_peer_with_key(keypair.clone(), "10.0.0.2:9100", 2, 5);
-    let p3 = make_peer("10.0.0.3:9100", 3);
+    let p1 = make_peer_with_key(keypair.clone(), "tcp://10.0.0.1:9100", 1, 0);
+    let p2 = make_peer_with_key(keypair.clone(), "tcp://10.0.0.2:9100", 2, 5);
+    let p3 = make_peer("tcp://10.0.0.3:9100", 3);
 
     let rc = s.put_peers(vec![p1.clone(), p2.clone(), p3.clone()]);
     assert!(rc.is_ok());
@@ -394,19 +418,17 @@ fn test_peers() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_peers_with_expected_seq() {
+fn check_peers_with_expected_seq(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
     assert!(rc.is_ok());
 
     let keypair = KeyPair::random();
-    let low_seq = make_peer_with_key(keypair.clone(), "10.0.1.1:9200", 11, 3);
-    let high_seq = make_peer_with_key(keypair, "10.0.1.2:9200", 22, 11);
+    let low_seq = make_peer_with_key(keypair.clone(), "tcp://10.0.1.1:9200", 11, 3);
+    let high_seq = make_peer_with_key(keypair, "tcp://10.0.1.2:9200", 22, 11);
 
     assert_eq!(low_seq.id(), high_seq.id(), "same keypair must produce same peer id");
 
@@ -425,20 +447,100 @@ fn test_peers_with_expected_seq() {
     remove_db(&path);
 }
 
-#[test]
-#[serial]
-fn test_purge() {
+fn check_peers_rotation(backend: StorageBackend) {
     let path = new_db_path();
     remove_db(&path);
 
-    let mut s = open_storage(&path);
+    let mut s = open_storage(backend, &path);
+    let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
+    assert!(rc.is_ok());
+
+    let keypair = KeyPair::random();
+    let announced = (1..=30u64)
+        .map(|fp| make_peer_with_key(keypair.clone(), &format!("tcp://10.0.2.{fp}:9400"), fp, 1))
+        .collect::<Vec<_>>();
+    let id = *announced[0].id();
+    assert!(s.put_peers(announced).is_ok());
+    assert!(s.put_peer(make_peer("tcp://10.0.2.100:9400", 1), false).is_ok());
+    assert_eq!(s.count_peers_by_id(&id).unwrap(), 30);
+    assert_eq!(s.count_peers_by_id(&Id::random()).unwrap(), 0);
+
+    // Each draw is a random subset, all peers show up before long.
+    let mut seen = std::collections::HashSet::new();
+    for _ in 0..100 {
+        let peers = s.get_peers_with_expected_seq(&id, 0, 8).unwrap();
+        assert_eq!(peers.len(), 8);
+        assert!(peers.iter().all(|p| p.id() == &id));
+        seen.extend(peers.iter().map(|p| p.fingerprint()));
+        if seen.len() == 30 {
+            break;
+        }
+    }
+    assert_eq!(seen.len(), 30, "{backend}: peers never returned");
+
+    // By origin it stays the same peer.
+    for _ in 0..3 {
+        assert_eq!(s.get_peer(&id, 7).unwrap().unwrap().fingerprint(), 7);
+    }
+
+    remove_db(&path);
+}
+
+fn check_peer_upsert_keeps_newest(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let mut s = open_storage(backend, &path);
+    let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
+    assert!(rc.is_ok());
+
+    // Same peer moved to another port
+    let keypair = KeyPair::random();
+    let v1 = make_peer_with_key(keypair.clone(), "tcp://10.0.1.1:9200", 7, 1);
+    let v2 = make_peer_with_key(keypair.clone(), "tcp://10.0.1.1:9300", 7, 2);
+
+    assert!(s.put_peer(v2.clone(), false).is_ok());
+    assert!(s.put_peer(v1.clone(), false).is_ok());
+
+    let peer = s.get_peer(v2.id(), 7).unwrap().unwrap();
+    assert_peer_roundtrip(&peer, &v2);
+    assert_eq!(s.get_peers(v2.id()).unwrap().len(), 1);
+
+    // Rebuilt with the same sequence number, the one signed later wins
+    let now = SystemTime::now();
+    let rebuilt = |endpoint: &str, time: SystemTime| PeerInfo::builder(endpoint)
+        .with_key(keypair.clone())
+        .with_fingerprint(8)
+        .with_sequence_number(2)
+        .with_announced_time(time)
+        .build()
+        .unwrap();
+    let older = rebuilt("tcp://10.0.1.1:9400", now - Duration::from_secs(60));
+    let newer = rebuilt("tcp://10.0.1.1:9500", now);
+
+    assert!(s.put_peer(newer.clone(), false).is_ok());
+    assert!(s.put_peer(older.clone(), false).is_ok());
+    assert_peer_roundtrip(&s.get_peer(newer.id(), 8).unwrap().unwrap(), &newer);
+
+    // Same version is a refresh
+    assert!(s.put_peers(vec![v1, v2.clone()]).is_ok());
+    assert_peer_roundtrip(&s.get_peer(v2.id(), 7).unwrap().unwrap(), &v2);
+
+    remove_db(&path);
+}
+
+fn check_purge(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let mut s = open_storage(backend, &path);
     let rc = s.initialize(Duration::ZERO, Duration::ZERO);
     assert!(rc.is_ok());
 
     let volatile_value = make_value();
     let persistent_value = make_signed_value(KeyPair::random(), 7);
-    let volatile_peer = make_peer("10.0.2.1:9300", 31);
-    let persistent_peer = make_peer("10.0.2.2:9300", 32);
+    let volatile_peer = make_peer("tcp://10.0.2.1:9300", 31);
+    let persistent_peer = make_peer("tcp://10.0.2.2:9300", 32);
 
     let rc = s.put_value(volatile_value.clone(), false);
     assert!(rc.is_ok());
@@ -476,3 +578,874 @@ fn test_purge() {
 
     remove_db(&path);
 }
+
+fn check_announced_before(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let clock = Arc::new(ManualClock::default());
+    let mut s = open_storage_with_clock(backend, &path, clock.clone());
+    let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
+    assert!(rc.is_ok());
+
+    let persistent_value = make_signed_value(KeyPair::random(), 1);
+    let volatile_value = make_value();
+    let persistent_peer = make_peer("tcp://10.0.3.1:9400", 41);
+
+    assert!(s.put_value(persistent_value.clone(), true).is_ok());
+    assert!(s.put_value(volatile_value.clone(), false).is_ok());
+    assert!(s.put_peer(persistent_peer.clone(), true).is_ok());
+
+    let before = clock.now_ms() + 1000;
+    let rc = s.get_values_announced_before(true, before);
+    assert!(rc.is_ok());
+    let values = rc.unwrap();
+    assert_eq!(values.len(), 1);
+    assert_value_roundtrip(&values[0], &persistent_value);
+
+    let rc = s.get_peers_announced_before(true, before);
+    assert!(rc.is_ok());
+    let peers = rc.unwrap();
+    assert_eq!(peers.len(), 1);
+    assert_peer_roundtrip(&peers[0], &persistent_peer);
+
+    // Nothing was announced before the epoch.
+    let rc = s.get_values_announced_before(true, 0);
+    assert!(rc.is_ok());
+    assert!(rc.unwrap().is_empty());
+
+    clock.advance(Duration::from_millis(5));
+    let checkpoint = clock.now_ms();
+    clock.advance(Duration::from_millis(5));
+
+    assert!(s.update_value_announced_time(&persistent_value.id()).is_ok());
+    assert!(s.update_peer_announced_time(persistent_peer.id(), persistent_peer.fingerprint()).is_ok());
+
+    let rc = s.get_values_announced_before(true, checkpoint);
+    assert!(rc.is_ok());
+    assert!(rc.unwrap().is_empty());
+    let rc = s.get_peers_announced_before(true, checkpoint);
+    assert!(rc.is_ok());
+    assert!(rc.unwrap().is_empty());
+
+    // The updated time follows the last announcement.
+    let now = clock.now_ms();
+    assert_eq!(s.get_value_updated(&persistent_value.id()).unwrap(), Some(now));
+    assert_eq!(s.get_value_updated(&volatile_value.id()).unwrap(), Some(now - 10));
+    assert_eq!(s.get_value_updated(&Id::random()).unwrap(), None);
+    assert_eq!(s.get_peer_updated(persistent_peer.id(), persistent_peer.fingerprint()).unwrap(), Some(now));
+    assert_eq!(s.get_peer_updated(persistent_peer.id(), 0).unwrap(), None);
+
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_value() {
+    for backend in BACKENDS {
+        check_value(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_values() {
+    for backend in BACKENDS {
+        check_values(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_values_with_expected_seq() {
+    for backend in BACKENDS {
+        check_values_with_expected_seq(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_peer() {
+    for backend in BACKENDS {
+        check_peer(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_peers() {
+    for backend in BACKENDS {
+        check_peers(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_peers_with_expected_seq() {
+    for backend in BACKENDS {
+        check_peers_with_expected_seq(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_peers_rotation() {
+    for backend in BACKENDS {
+        check_peers_rotation(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_peer_upsert_keeps_newest() {
+    for backend in BACKENDS {
+        check_peer_upsert_keeps_newest(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_purge() {
+    for backend in BACKENDS {
+        check_purge(backend);
+    }
+}
+
+fn check_expiry(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let clock = Arc::new(ManualClock::default());
+    let mut s = open_storage_with_clock(backend, &path, clock.clone());
+    let rc = s.initialize(Duration::from_secs(3600), Duration::from_secs(7200));
+    assert!(rc.is_ok());
+
+    let volatile_value = make_value();
+    let persistent_value = make_signed_value(KeyPair::random(), 3);
+    let volatile_peer = make_peer_announced_at("tcp://10.0.4.1:9500", 51, clock.now());
+    assert!(s.put_value(volatile_value.clone(), false).is_ok());
+    assert!(s.put_value(persistent_value.clone(), true).is_ok());
+    assert!(s.put_peer(volatile_peer.clone(), false).is_ok());
+
+    // Nothing expires before its time.
+    clock.advance(Duration::from_secs(3599));
+    s.purge();
+    assert!(s.get_value(&volatile_value.id()).unwrap().is_some());
+
+    // Re-announcing restarts the expiry.
+    assert!(s.update_value_announced_time(&volatile_value.id()).is_ok());
+    clock.advance(Duration::from_secs(3599));
+    s.purge();
+    assert!(s.get_value(&volatile_value.id()).unwrap().is_some());
+
+    clock.advance(Duration::from_secs(1));
+    s.purge();
+    assert!(s.get_value(&volatile_value.id()).unwrap().is_none());
+    assert!(s.get_value(&persistent_value.id()).unwrap().is_some());
+
+    // Peers are kept for twice as long.
+    assert!(s.get_peer(volatile_peer.id(), volatile_peer.fingerprint()).unwrap().is_some());
+    clock.advance(Duration::from_secs(1));
+    s.purge();
+    assert!(s.get_peer(volatile_peer.id(), volatile_peer.fingerprint()).unwrap().is_none());
+
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_expiry() {
+    for backend in BACKENDS {
+        check_expiry(backend);
+    }
+}
+
+fn check_publisher_expiry(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let clock = Arc::new(ManualClock::default());
+    let mut s = open_storage_with_clock(backend, &path, clock.clone());
+    assert!(s.initialize(Duration::from_secs(3600), Duration::from_secs(7200)).is_ok());
+
+    // Announced an hour before it arrived, by the publisher's clock.
+    let early = make_peer_announced_at("tcp://10.0.5.1:9600", 61, clock.now() - Duration::from_secs(3600));
+    let untimed = make_peer("tcp://10.0.5.2:9600", 62).untimed().unwrap();
+    assert!(s.put_peer(early.clone(), false).is_ok());
+    assert!(s.put_peer(untimed.clone(), false).is_ok());
+
+    let stored = s.get_peer(early.id(), early.fingerprint()).unwrap().unwrap();
+    assert_eq!(stored.announced_time(), early.announced_time());
+    assert!(stored.is_valid());
+
+    clock.advance(Duration::from_secs(3600));
+    s.purge();
+    assert!(s.get_peer(early.id(), early.fingerprint()).unwrap().is_none());
+    assert!(s.get_peer(untimed.id(), untimed.fingerprint()).unwrap().is_some());
+
+    // Without a time, a peer expires by the time it was stored.
+    clock.advance(Duration::from_secs(3600));
+    s.purge();
+    assert!(s.get_peer(untimed.id(), untimed.fingerprint()).unwrap().is_none());
+
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_publisher_expiry() {
+    for backend in BACKENDS {
+        check_publisher_expiry(backend);
+    }
+}
+
+// The peers table as version 5 created it, without the announced time.
+const PEERS_TABLE_V5: &str = "
+    CREATE TABLE peers(id BLOB NOT NULL, fingerprint INTEGER NOT NULL,
+        persistent BOOLEAN NOT NULL DEFAULT FALSE, privateKey BLOB, nonce BLOB NOT NULL,
+        sequenceNumber INTEGER NOT NULL DEFAULT 0, nodeId BLOB, nodeSignature BLOB,
+        signature BLOB NOT NULL, endpoint TEXT NOT NULL, extra BLOB,
+        updated INTEGER NOT NULL DEFAULT 0, PRIMARY KEY(id, fingerprint)) WITHOUT ROWID";
+
+// The peers table of version 7 to 9 databases.
+const PEERS_TABLE_V7: &str = "
+    CREATE TABLE peers(id BLOB NOT NULL, fingerprint INTEGER NOT NULL,
+        persistent BOOLEAN NOT NULL DEFAULT FALSE, privateKey BLOB, nonce BLOB NOT NULL,
+        sequenceNumber INTEGER NOT NULL DEFAULT 0, nodeId BLOB, nodeSignature BLOB,
+        signature BLOB NOT NULL, endpoint TEXT NOT NULL, extra BLOB,
+        updated INTEGER NOT NULL DEFAULT 0, announced INTEGER, tags BLOB,
+        PRIMARY KEY(id, fingerprint)) WITHOUT ROWID";
+
+// The values table as versions 5 to 7 created it, without the counter-signature.
+const VALUES_TABLE_V5: &str = "
+    CREATE TABLE valores(id BLOB NOT NULL PRIMARY KEY, publicKey BLOB, privateKey BLOB,
+        recipient BLOB, nonce BLOB, signature BLOB, sequenceNumber INTEGER NOT NULL DEFAULT 0,
+        data BLOB NOT NULL, persistent BOOLEAN NOT NULL DEFAULT FALSE,
+        updated INTEGER NOT NULL DEFAULT 0) WITHOUT ROWID";
+
+#[test]
+#[serial]
+fn test_upgrade_v5() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let peer = make_peer("tcp://10.0.6.1:9700", 71).untimed().unwrap();
+    {
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query(PEERS_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query(VALUES_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query(format!(
+            "INSERT INTO peers(id, fingerprint, nonce, signature, endpoint, updated) \
+             VALUES(x'{}', 71, x'{}', x'{}', '{}', 1)",
+            hex::encode(peer.id().as_bytes()),
+            hex::encode(peer.nonce()),
+            hex::encode(peer.signature()),
+            peer.endpoint()
+        )).execute(&mut conn).unwrap();
+        diesel::sql_query("PRAGMA user_version = 5").execute(&mut conn).unwrap();
+    }
+
+    // The stored peers survive the upgrade.
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    let stored = s.get_peer(peer.id(), 71).unwrap().unwrap();
+    assert_eq!(stored, peer.without_private_key());
+    assert!(stored.is_valid());
+
+    let timed = make_peer("tcp://10.0.6.2:9700", 72);
+    assert!(s.put_peer(timed.clone(), false).is_ok());
+    assert_eq!(s.get_peer(timed.id(), 72).unwrap().unwrap().announced_time(), timed.announced_time());
+    s.close();
+
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    assert_eq!(s.count_peers().unwrap(), 2);
+    remove_db(&path);
+}
+
+fn check_peer_tags(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let kp = KeyPair::random();
+    let tagged = PeerInfo::builder("tcp://10.0.7.1:9800")
+        .with_key(kp.clone())
+        .with_fingerprint(81)
+        .with_tags(&["proto/2", "tls"])
+        .build()
+        .unwrap();
+    let plain = make_peer_with_key(kp, "tcp://10.0.7.2:9800", 82, 0);
+
+    let mut s = open_storage(backend, &path);
+    assert!(s.put_peer(tagged.clone(), false).is_ok());
+    assert!(s.put_peer(plain.clone(), false).is_ok());
+
+    let stored = s.get_peer(tagged.id(), 81).unwrap().unwrap();
+    assert_peer_roundtrip(&stored, &tagged);
+    assert!(stored.is_valid());
+    assert!(s.get_peer(plain.id(), 82).unwrap().unwrap().tags().is_empty());
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_peer_tags() {
+    for backend in BACKENDS {
+        check_peer_tags(backend);
+    }
+}
+
+fn check_peer_weight(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let kp = KeyPair::random();
+    let weighted = PeerInfo::builder("tcp://10.0.7.3:9800")
+        .with_key(kp.clone())
+        .with_fingerprint(83)
+        .with_weight(4)
+        .build()
+        .unwrap();
+    let plain = make_peer_with_key(kp, "tcp://10.0.7.4:9800", 84, 0);
+
+    let mut s = open_storage(backend, &path);
+    assert!(s.put_peer(weighted.clone(), true).is_ok());
+    assert!(s.put_peer(plain.clone(), false).is_ok());
+
+    let stored = s.get_peer(weighted.id(), 83).unwrap().unwrap();
+    assert_peer_roundtrip(&stored, &weighted);
+    assert_eq!(stored.weight(), 4);
+    assert!(stored.is_valid());
+    assert_eq!(s.get_peer(plain.id(), 84).unwrap().unwrap().weight(), PeerInfo::DEFAULT_WEIGHT);
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_peer_weight() {
+    for backend in BACKENDS {
+        check_peer_weight(backend);
+    }
+}
+
+fn check_peer_observed(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let observed = make_peer("tcp://192.168.3.8:9900", 93)
+        .with_observed(Some("tcp://203.0.113.8:9900".to_string()));
+    let plain = make_peer("tcp://10.0.8.4:9900", 94);
+
+    let mut s = open_storage(backend, &path);
+    assert!(s.put_peer(observed.clone(), false).is_ok());
+    assert!(s.put_peer(plain.clone(), false).is_ok());
+
+    let stored = s.get_peer(observed.id(), 93).unwrap().unwrap();
+    assert_peer_roundtrip(&stored, &observed);
+    assert_eq!(stored.observed_endpoint(), Some("tcp://203.0.113.8:9900"));
+    assert!(stored.is_valid());
+    assert_eq!(s.get_peer(plain.id(), 94).unwrap().unwrap().observed_endpoint(), None);
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_peer_observed() {
+    for backend in BACKENDS {
+        check_peer_observed(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_upgrade_v9() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let peer = PeerInfo::builder("tcp://10.0.6.5:9700")
+        .with_fingerprint(75)
+        .with_tags(&["tls"])
+        .build()
+        .unwrap();
+    {
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query(PEERS_TABLE_V7).execute(&mut conn).unwrap();
+        diesel::sql_query(VALUES_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE valores ADD COLUMN countersigner BLOB").execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE valores ADD COLUMN countersignature BLOB").execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE valores ADD COLUMN local BOOLEAN NOT NULL DEFAULT FALSE").execute(&mut conn).unwrap();
+        diesel::sql_query(format!(
+            "INSERT INTO peers(id, fingerprint, nonce, signature, endpoint, updated, announced, tags) \
+             VALUES(x'{}', 75, x'{}', x'{}', '{}', 1, {}, x'{}')",
+            hex::encode(peer.id().as_bytes()),
+            hex::encode(peer.nonce()),
+            hex::encode(peer.signature()),
+            peer.endpoint(),
+            peer.announced().unwrap(),
+            hex::encode(serde_cbor::to_vec(&peer.tags()).unwrap())
+        )).execute(&mut conn).unwrap();
+        diesel::sql_query("PRAGMA user_version = 9").execute(&mut conn).unwrap();
+    }
+
+    // Peers stored before take the default weight.
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    let stored = s.get_peer(peer.id(), 75).unwrap().unwrap();
+    assert_eq!(stored, peer.without_private_key());
+    assert_eq!(stored.weight(), PeerInfo::DEFAULT_WEIGHT);
+
+    let weighted = PeerInfo::builder("tcp://10.0.6.6:9700")
+        .with_fingerprint(76)
+        .with_weight(2)
+        .build()
+        .unwrap();
+    assert!(s.put_peer(weighted.clone(), false).is_ok());
+    s.close();
+
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    assert_eq!(s.get_peer(weighted.id(), 76).unwrap().unwrap().weight(), 2);
+    assert_eq!(s.count_peers().unwrap(), 2);
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_upgrade_v6() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let peer = make_peer("tcp://10.0.6.3:9700", 73);
+    {
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query(PEERS_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query(VALUES_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE peers ADD COLUMN announced INTEGER").execute(&mut conn).unwrap();
+        diesel::sql_query(format!(
+            "INSERT INTO peers(id, fingerprint, nonce, signature, endpoint, updated, announced) \
+             VALUES(x'{}', 73, x'{}', x'{}', '{}', 1, {})",
+            hex::encode(peer.id().as_bytes()),
+            hex::encode(peer.nonce()),
+            hex::encode(peer.signature()),
+            peer.endpoint(),
+            peer.announced().unwrap()
+        )).execute(&mut conn).unwrap();
+        diesel::sql_query("PRAGMA user_version = 6").execute(&mut conn).unwrap();
+    }
+
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    let stored = s.get_peer(peer.id(), 73).unwrap().unwrap();
+    assert_eq!(stored, peer.without_private_key());
+
+    let tagged = PeerInfo::builder("tcp://10.0.6.4:9700")
+        .with_fingerprint(74)
+        .with_tags(&["tls"])
+        .build()
+        .unwrap();
+    assert!(s.put_peer(tagged.clone(), false).is_ok());
+    assert_eq!(s.get_peer(tagged.id(), 74).unwrap().unwrap().tags(), ["tls"]);
+    s.close();
+
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    assert_eq!(s.count_peers().unwrap(), 2);
+    remove_db(&path);
+}
+
+fn check_countersigned_value(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let service = KeyPair::random();
+    let service_id = Id::from(service.public_key());
+    let value = SignedBuilder::new(&random_bytes(32))
+        .with_countersigner_slot(&service_id)
+        .build()
+        .unwrap()
+        .countersign(&service)
+        .unwrap();
+    let plain = make_signed_value(KeyPair::random(), 0);
+
+    let mut s = open_storage(backend, &path);
+    assert!(s.put_value(value.clone(), false).is_ok());
+    assert!(s.put_value(plain.clone(), false).is_ok());
+
+    let stored = s.get_value(&value.id()).unwrap().unwrap();
+    assert_eq!(stored.countersigner(), Some(&service_id));
+    assert_eq!(stored.countersignature(), value.countersignature());
+    assert!(stored.verify_countersignature(&service_id));
+    assert!(!s.get_value(&plain.id()).unwrap().unwrap().is_countersigned());
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_countersigned_value() {
+    for backend in BACKENDS {
+        check_countersigned_value(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_upgrade_v7() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let value = make_signed_value(KeyPair::random(), 3);
+    {
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query(PEERS_TABLE_V7).execute(&mut conn).unwrap();
+        diesel::sql_query(VALUES_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query(format!(
+            "INSERT INTO valores(id, publicKey, nonce, signature, sequenceNumber, data, updated) \
+             VALUES(x'{}', x'{}', x'{}', x'{}', 3, x'{}', 1)",
+            hex::encode(value.id().as_bytes()),
+            hex::encode(value.public_key().unwrap().as_bytes()),
+            hex::encode(value.nonce().unwrap().as_bytes()),
+            hex::encode(value.signature().unwrap()),
+            hex::encode(value.data())
+        )).execute(&mut conn).unwrap();
+        diesel::sql_query("PRAGMA user_version = 7").execute(&mut conn).unwrap();
+    }
+
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    let stored = s.get_value(&value.id()).unwrap().unwrap();
+    assert_eq!(stored.data(), value.data());
+    assert!(stored.is_valid());
+    assert!(!stored.is_countersigned());
+
+    let service = KeyPair::random();
+    let countersigned = stored.countersign(&service).unwrap();
+    assert!(s.put_value(countersigned.clone(), false).is_ok());
+    s.close();
+
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    let stored = s.get_value(&value.id()).unwrap().unwrap();
+    assert!(stored.verify_countersignature(&Id::from(service.public_key())));
+    assert_eq!(s.count_values().unwrap(), 1);
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_upgrade_v8() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let value = make_signed_value(KeyPair::random(), 3);
+    {
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query(PEERS_TABLE_V7).execute(&mut conn).unwrap();
+        diesel::sql_query(VALUES_TABLE_V5).execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE valores ADD COLUMN countersigner BLOB").execute(&mut conn).unwrap();
+        diesel::sql_query("ALTER TABLE valores ADD COLUMN countersignature BLOB").execute(&mut conn).unwrap();
+        diesel::sql_query(format!(
+            "INSERT INTO valores(id, publicKey, nonce, signature, sequenceNumber, data, updated) \
+             VALUES(x'{}', x'{}', x'{}', x'{}', 3, x'{}', 1)",
+            hex::encode(value.id().as_bytes()),
+            hex::encode(value.public_key().unwrap().as_bytes()),
+            hex::encode(value.nonce().unwrap().as_bytes()),
+            hex::encode(value.signature().unwrap()),
+            hex::encode(value.data())
+        )).execute(&mut conn).unwrap();
+        diesel::sql_query("PRAGMA user_version = 8").execute(&mut conn).unwrap();
+    }
+
+    // Values stored before were put by others.
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    assert!(s.get_value(&value.id()).unwrap().unwrap().is_valid());
+    assert!(!s.is_local_value(&value.id()).unwrap());
+
+    assert!(s.put_local_value(value.clone(), false).is_ok());
+    s.close();
+
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    assert!(s.is_local_value(&value.id()).unwrap());
+    remove_db(&path);
+}
+
+fn check_local_value(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let mut s = open_storage(backend, &path);
+    let keypair = KeyPair::random();
+    let local = make_signed_value(keypair.clone(), 3);
+    let remote = make_value();
+
+    assert!(s.put_local_value(local.clone(), true).is_ok());
+    assert!(s.put_value(remote.clone(), false).is_ok());
+    assert!(s.is_local_value(&local.id()).unwrap());
+    assert!(!s.is_local_value(&remote.id()).unwrap());
+    assert!(!s.is_local_value(&Id::random()).unwrap());
+
+    // A newer copy coming back from the network is still the local one.
+    let updated = SignedBuilder::new(&random_bytes(32))
+        .with_keypair(&keypair)
+        .with_nonce(local.nonce().unwrap())
+        .with_sequence_number(4)
+        .build()
+        .unwrap();
+    assert_eq!(updated.id(), local.id());
+    assert!(s.put_value(updated.clone(), true).is_ok());
+    assert!(s.is_local_value(&local.id()).unwrap());
+    assert_value_roundtrip(&s.get_value(&local.id()).unwrap().unwrap(), &updated);
+
+    assert!(s.remove_value(&local.id()).is_ok());
+    assert!(!s.is_local_value(&local.id()).unwrap());
+
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_local_value() {
+    for backend in BACKENDS {
+        check_local_value(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_announced_before() {
+    for backend in BACKENDS {
+        check_announced_before(backend);
+    }
+}
+
+// Runs a statement on the database behind the storage's back.
+fn tamper(path: &str, stmt: &str) {
+    let mut conn = SqliteConnection::establish(path).unwrap();
+    assert!(diesel::sql_query(stmt).execute(&mut conn).unwrap() > 0);
+}
+
+#[test]
+#[serial]
+fn test_maintain() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let mut s = open_storage(StorageBackend::Sqlite, &path);
+    let values = (0..200)
+        .map(|_| ValueBuilder::new(&random_bytes(1024)).build().unwrap())
+        .collect::<Vec<_>>();
+    for value in values.iter() {
+        assert!(s.put_value(value.clone(), false).is_ok());
+    }
+    for value in values.iter().skip(1) {
+        assert!(s.remove_value(&value.id()).is_ok());
+    }
+
+    let size = fs::metadata(&path).unwrap().len();
+    assert!(s.maintain().unwrap() > 0);
+    assert!(fs::metadata(&path).unwrap().len() < size);
+    assert_eq!(s.maintain().unwrap(), 0);
+    assert!(s.get_value(&values[0].id()).unwrap().is_some());
+
+    s.close();
+    remove_db(&path);
+
+    let mut s = open_storage(StorageBackend::Memory, &path);
+    assert_eq!(s.maintain().unwrap(), 0);
+}
+
+#[test]
+#[serial]
+fn test_check_integrity() {
+    for backend in BACKENDS {
+        let path = new_db_path();
+        remove_db(&path);
+
+        let mut s = open_storage(backend, &path);
+        let value = make_signed_value(KeyPair::random(), 3);
+        let peer = make_peer("tcp://10.0.3.1:9300", 41);
+        assert!(s.put_value(value.clone(), false).is_ok());
+        assert!(s.put_value(make_value(), false).is_ok());
+        assert!(s.put_peer(peer.clone(), false).is_ok());
+
+        let report = s.check_integrity(false).unwrap();
+        assert!(report.is_ok(), "{backend}: {report:?}");
+        assert_eq!(report.values_checked(), 2);
+        assert_eq!(report.peers_checked(), 1);
+
+        if backend == StorageBackend::Sqlite {
+            tamper(&path, "UPDATE valores SET data = x'00' WHERE sequenceNumber = 3");
+            tamper(&path, "UPDATE peers SET endpoint = 'tcp://10.0.3.2:9300'");
+
+            // A quick check only looks at the database structure
+            assert!(s.check_integrity(true).unwrap().is_ok());
+
+            let report = s.check_integrity(false).unwrap();
+            assert!(!report.is_ok());
+            assert!(report.errors().is_empty());
+            assert_eq!(report.invalid_values(), &[value.id()]);
+            assert_eq!(report.invalid_peers(), &[*peer.id()]);
+        }
+        s.close();
+        remove_db(&path);
+    }
+}
+
+#[test]
+#[serial]
+fn test_open_corrupt() {
+    let path = new_db_path();
+    fs::write(&path, random_bytes(8192)).unwrap();
+
+    let mut s = SqliteStorage::new();
+    assert!(s.open(&path).is_err());
+    s.close();
+    remove_db(&path);
+}
+
+// Id, updated time, publisher and size of a stored value or peer.
+type Stored = (Id, u64, Option<Id>, u64);
+
+// The stats of the entries under the prefix, worked out the long way.
+fn expected_keyspace_stats(entries: &[Stored], prefix: &Prefix) -> KeyspaceStats {
+    let inside = entries.iter().filter(|e| prefix.is_prefix_of(&e.0)).collect::<Vec<_>>();
+    let mut times = inside.iter().map(|e| e.1).collect::<Vec<_>>();
+    times.sort();
+    let median = match times.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(times[n / 2]),
+        n => Some((times[n / 2 - 1] + times[n / 2]) / 2),
+    };
+    KeyspaceStats {
+        count       : inside.len(),
+        oldest      : times.first().copied(),
+        newest      : times.last().copied(),
+        median,
+        publishers  : inside.iter().filter_map(|e| e.2).collect::<HashSet<_>>().len(),
+        bytes       : inside.iter().map(|e| e.3).sum(),
+    }
+}
+
+fn check_keyspace_stats(backend: StorageBackend) {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let clock = Arc::new(ManualClock::default());
+    let mut s = open_storage_with_clock(backend, &path, clock.clone());
+    assert!(s.initialize(Duration::from_secs(3600), Duration::from_secs(7200)).is_ok());
+
+    let mut values = Vec::new();
+    for i in 0..24 {
+        clock.advance(Duration::from_millis(1000 + i * 7));
+        let data = random_bytes(16 + i as usize);
+        let kp = KeyPair::random();
+        let value = match i % 3 {
+            0 => ValueBuilder::new(&data).build().unwrap(),
+            _ => SignedBuilder::new(&data).with_keypair(&kp).build().unwrap(),
+        };
+        assert!(s.put_value(value.clone(), false).is_ok());
+        let updated = s.get_value_updated(&value.id()).unwrap().unwrap();
+        values.push((value.id(), updated, value.public_key().cloned(), data.len() as u64));
+    }
+
+    // Two origin nodes, and pairs of peers announced under the same key.
+    let nodes = [
+        Arc::new(Mutex::new(CryptoIdentity::new())),
+        Arc::new(Mutex::new(CryptoIdentity::new())),
+    ];
+    let mut peers = Vec::new();
+    let mut kp = KeyPair::random();
+    for i in 0..24u64 {
+        clock.advance(Duration::from_millis(500 + i * 11));
+        if i % 2 == 0 {
+            kp = KeyPair::random();
+        }
+        let endpoint = format!("tcp://10.0.6.{}:9600", i);
+        let mut builder = PeerInfo::builder(&endpoint)
+            .with_key(kp.clone())
+            .with_fingerprint(i);
+        if i % 4 != 0 {
+            builder = builder.with_node(nodes[i as usize % 2].clone());
+        }
+        if i % 3 == 1 {
+            builder = builder.with_extra(&random_bytes(i as usize));
+        }
+        let peer = builder.build().unwrap();
+        assert!(s.put_peer(peer.clone(), false).is_ok());
+        let updated = s.get_peer_updated(peer.id(), peer.fingerprint()).unwrap().unwrap();
+        let bytes = endpoint.len() + peer.extra_data().map_or(0, |v| v.len());
+        peers.push((*peer.id(), updated, peer.nodeid().cloned(), bytes as u64));
+    }
+
+    let half = Prefix::from(&values[0].0, 0);
+    let stats = s.value_range_stats(&half.first(), &half.last()).unwrap();
+    assert!(stats.count() > 0 && stats.count() < values.len());
+
+    let prefixes = [
+        half,
+        Prefix::from(&values[1].0, 1),
+        Prefix::from(&peers[0].0, 0),
+        Prefix::from(&peers[2].0, 2),
+        Prefix::from(&Id::random(), -1),
+        Prefix::from(&Id::random(), 64),
+    ];
+    for prefix in prefixes {
+        let (first, last) = (prefix.first(), prefix.last());
+        assert_eq!(s.value_range_stats(&first, &last).unwrap(), expected_keyspace_stats(&values, &prefix),
+            "values under {}/{} in {}", prefix.id(), prefix.depth(), backend);
+        assert_eq!(s.peer_range_stats(&first, &last).unwrap(), expected_keyspace_stats(&peers, &prefix),
+            "peers under {}/{} in {}", prefix.id(), prefix.depth(), backend);
+    }
+
+    // The whole keyspace holds everything.
+    let all = Prefix::from(&Id::random(), -1);
+    let stats = s.peer_range_stats(&all.first(), &all.last()).unwrap();
+    assert_eq!(stats.count(), 24);
+    assert_eq!(stats.publishers(), 2);
+    assert_eq!(stats.oldest(), Some(peers[0].1));
+    assert_eq!(stats.newest(), Some(peers[23].1));
+
+    let empty = Prefix::from(&Id::random(), 64);
+    let stats = s.value_range_stats(&empty.first(), &empty.last()).unwrap();
+    assert_eq!(stats, KeyspaceStats::default());
+
+    s.close();
+    remove_db(&path);
+}
+
+#[test]
+#[serial]
+fn test_keyspace_stats() {
+    for backend in BACKENDS {
+        check_keyspace_stats(backend);
+    }
+}
+
+#[test]
+#[serial]
+fn test_newer_version_refused() {
+    let path = new_db_path();
+    remove_db(&path);
+
+    let value = make_value();
+    {
+        let mut s = open_storage(StorageBackend::Sqlite, &path);
+        assert!(s.put_value(value.clone(), false).is_ok());
+        s.close();
+
+        let mut conn = SqliteConnection::establish(&path).unwrap();
+        diesel::sql_query("PRAGMA user_version = 12").execute(&mut conn).unwrap();
+    }
+
+    let mut s = SqliteStorage::new();
+    let err = s.open(&path).unwrap_err();
+    assert_eq!(err.downcast_ref::<UnsupportedVersionError>().map(|e| e.version()), Some(12));
+
+    // Left as the newer build wrote it.
+    let mut conn = SqliteConnection::establish(&path).unwrap();
+    assert_eq!(migrations::user_version(&mut conn).unwrap(), 12);
+    drop(conn);
+
+    diesel::sql_query("PRAGMA user_version = 11")
+        .execute(&mut SqliteConnection::establish(&path).unwrap())
+        .unwrap();
+    let s = open_storage(StorageBackend::Sqlite, &path);
+    assert_eq!(s.get_value(&value.id()).unwrap(), Some(value));
+    remove_db(&path);
+}
@@ -0,0 +1,90 @@
+use std::{
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::{Id, ImmutableBuilder};
+use crate::dht::storage_event::{StorageEvent, StorageEvents};
+
+fn expired(n: u8) -> StorageEvent {
+    StorageEvent::ValueExpired { id: Id::from_bytes([n; 32]) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_stored() {
+        let value = ImmutableBuilder::new(b"hello")
+            .build()
+            .expect("Failed to build value");
+        let id = value.id();
+        let seq = value.sequence_number();
+
+        assert_eq!(StorageEvent::value_stored(&value, None, true),
+            StorageEvent::ValueStored { id, seq, new: true, local: true });
+        assert_eq!(StorageEvent::value_stored(&value, Some(seq - 1), false),
+            StorageEvent::ValueStored { id, seq, new: false, local: false });
+        assert_eq!(StorageEvent::value_stored(&value, Some(seq), false),
+            StorageEvent::ValueUpdated { id, seq, local: false });
+    }
+
+    #[test]
+    fn test_delivery() {
+        let events = StorageEvents::new(8);
+        // Not queued without a listener
+        events.emit(expired(1));
+
+        let (tx, rx) = mpsc::channel();
+        events.set_listener(Box::new(move |event| {
+            let _ = tx.send(event);
+        }));
+        events.emit(expired(2));
+        events.emit(expired(3));
+
+        let timeout = Duration::from_secs(2);
+        assert_eq!(rx.recv_timeout(timeout).unwrap(), expired(2));
+        assert_eq!(rx.recv_timeout(timeout).unwrap(), expired(3));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+
+        // A new listener takes over
+        let (tx2, rx2) = mpsc::channel();
+        events.set_listener(Box::new(move |event| {
+            let _ = tx2.send(event);
+        }));
+        events.emit(expired(4));
+        assert_eq!(rx2.recv_timeout(timeout).unwrap(), expired(4));
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+        assert_eq!(events.dropped(), 0);
+    }
+
+    #[test]
+    fn test_overflow_drops_oldest() {
+        let events = StorageEvents::new(2);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let (tx, rx) = mpsc::channel();
+        events.set_listener(Box::new(move |event| {
+            let _ = started_tx.send(());
+            let _ = gate_rx.recv();
+            let _ = tx.send(event);
+        }));
+
+        // The listener is stuck on the first event while the others pile up
+        events.emit(expired(0));
+        started_rx.recv_timeout(Duration::from_secs(2)).unwrap();
+        for n in 1..=5 {
+            events.emit(expired(n));
+        }
+        assert_eq!(events.dropped(), 3);
+
+        for _ in 0..3 {
+            gate_tx.send(()).unwrap();
+        }
+        let delivered = (0..3)
+            .map(|_| rx.recv_timeout(Duration::from_secs(2)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(delivered, vec![expired(0), expired(4), expired(5)]);
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    Id,
+    NodeInfo,
+    ManualClock,
+    dht::token_cache::TokenCache,
+};
+
+fn make_node(port: u16) -> NodeInfo {
+    NodeInfo::new(Id::random(), format!("1.1.1.1:{port}").parse().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_within_validity() {
+        let clock = Arc::new(ManualClock::default());
+        let mut cache = TokenCache::new(clock.clone());
+        let target = Id::random();
+        let node1 = make_node(39001);
+        let node2 = make_node(39002);
+
+        assert!(cache.lookup(&target).is_none());
+        cache.put(&target, node1.clone(), 11);
+        cache.put(&target, node2.clone(), 22);
+        cache.put(&target, make_node(39003), 0);
+
+        for _ in 0..2 {
+            let mut found = cache.lookup(&target).unwrap();
+            found.sort_by_key(|(_, token)| *token);
+            assert_eq!(found, vec![(node1.clone(), 11), (node2.clone(), 22)]);
+            clock.advance(Duration::from_secs(60));
+        }
+        assert!(cache.lookup(&Id::random()).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.entries(), 2);
+        assert_eq!(stats.hits(), 2);
+        assert_eq!(stats.misses(), 2);
+    }
+
+    #[test]
+    fn test_refresh_near_expiry() {
+        let clock = Arc::new(ManualClock::default());
+        let mut cache = TokenCache::new(clock.clone());
+        let target = Id::random();
+        let node1 = make_node(39001);
+        let node2 = make_node(39002);
+
+        cache.put(&target, node1.clone(), 11);
+        clock.advance(TokenCache::VALIDITY - TokenCache::REFRESH_MARGIN);
+        cache.put(&target, node2.clone(), 22);
+
+        // The older token is handed back to be fetched again.
+        let mut found = cache.lookup(&target).unwrap();
+        found.sort_by_key(|(_, token)| *token);
+        assert_eq!(found, vec![(node1.clone(), 0), (node2.clone(), 22)]);
+
+        // Past the rotation nothing is fresh, the target is looked up.
+        clock.advance(TokenCache::VALIDITY);
+        assert!(cache.lookup(&target).is_none());
+        assert_eq!(cache.stats().entries(), 0);
+        assert_eq!(cache.stats().misses(), 1);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let clock = Arc::new(ManualClock::default());
+        let mut cache = TokenCache::new(clock.clone());
+        let target = Id::random();
+        let node1 = make_node(39001);
+        let node2 = make_node(39002);
+
+        cache.put(&target, node1.clone(), 11);
+        cache.put(&target, node2.clone(), 22);
+        cache.invalidate(&target, node1.id());
+        assert_eq!(cache.lookup(&target).unwrap(), vec![(node2.clone(), 22)]);
+
+        cache.invalidate(&target, node2.id());
+        assert!(cache.lookup(&target).is_none());
+        assert_eq!(cache.stats().entries(), 0);
+    }
+}
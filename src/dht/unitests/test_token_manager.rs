@@ -1,26 +1,30 @@
 use std::{
-    thread,
     net::SocketAddr,
+    sync::Arc,
     time::Duration,
 };
 
 use crate::{
     Id,
+    ManualClock,
     dht::token_manager::TokenManager,
 };
 
+const TOKEN_TIMEOUT: Duration = Duration::from_millis(TokenManager::TOKEN_TIMEOUT);
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_generate_token() {
-        let man = TokenManager::new();
+        let clock = Arc::new(ManualClock::default());
+        let man = TokenManager::new(clock.clone());
 
         let nodeid = Id::random();
         let target = Id::random();
         let addr = "192.168.1.123:32222".parse::<SocketAddr>().unwrap();
-        thread::sleep(Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
 
         let token1 = man.generate_token(&nodeid, &addr, &target);
         let token2 = man.generate_token(&nodeid, &addr, &target);
@@ -29,15 +33,46 @@ mod tests {
 
     #[test]
     fn test_verify_token() {
-        let man = TokenManager::new();
+        let clock = Arc::new(ManualClock::default());
+        let man = TokenManager::new(clock.clone());
 
         let nodeid = Id::random();
         let target = Id::random();
         let addr = "192.168.1.123:32222".parse::<SocketAddr>().unwrap();
-        thread::sleep(Duration::from_secs(1));
+        clock.advance(Duration::from_secs(1));
 
         let token = man.generate_token(&nodeid, &addr, &target);
         let result = man.verify_token(token, &nodeid, &addr, &target);
         assert_eq!(result, true);
     }
+
+    #[test]
+    fn test_token_rotation() {
+        let clock = Arc::new(ManualClock::default());
+        let man = TokenManager::new(clock.clone());
+
+        let nodeid = Id::random();
+        let target = Id::random();
+        let addr = "192.168.1.123:32222".parse::<SocketAddr>().unwrap();
+        let token = man.generate_token(&nodeid, &addr, &target);
+
+        // Not rotated before the timeout.
+        clock.advance(TOKEN_TIMEOUT);
+        man.update_token_timestamp();
+        assert_eq!(man.generate_token(&nodeid, &addr, &target), token);
+
+        // Rotated once, the previous token is still accepted.
+        clock.advance(Duration::from_millis(1));
+        man.update_token_timestamp();
+        assert_ne!(man.generate_token(&nodeid, &addr, &target), token);
+        assert!(man.verify_token(token, &nodeid, &addr, &target));
+
+        // Rotated twice, the token has expired.
+        clock.advance(TOKEN_TIMEOUT + Duration::from_millis(1));
+        assert!(!man.verify_token(token, &nodeid, &addr, &target));
+
+        let other = "192.168.1.124:32222".parse::<SocketAddr>().unwrap();
+        let token = man.generate_token(&nodeid, &addr, &target);
+        assert!(!man.verify_token(token, &nodeid, &other, &target));
+    }
 }
@@ -0,0 +1,146 @@
+use std::{
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::{net::UdpSocket, sync::mpsc, time::timeout};
+use url::Url;
+
+use crate::dht::rpc::{
+    receivers::Inbound,
+    ws_gateway::{self, GatewayClient, GatewayListener},
+};
+
+fn loopback() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 0))
+}
+
+fn gateway_url(listener: &GatewayListener) -> Url {
+    Url::parse(&format!("ws://{}", listener.local_addr())).unwrap()
+}
+
+// Answers every datagram with its bytes reversed.
+async fn echo_socket() -> SocketAddr {
+    let socket = UdpSocket::bind(loopback()).await.unwrap();
+    let addr = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = vec![0u8; 2048];
+        while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+            let reply = buf[..len].iter().rev().copied().collect::<Vec<_>>();
+            let _ = socket.send_to(&reply, from).await;
+        }
+    });
+    addr
+}
+
+async fn wait_connected(client: &GatewayClient) {
+    for _ in 0..100 {
+        if client.is_connected() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("Gateway not connected");
+}
+
+async fn roundtrip(client: &GatewayClient, inbound: &mut mpsc::Receiver<Inbound>, dest: SocketAddr) {
+    let data = b"ping datagram".to_vec();
+    assert_eq!(client.send(dest, &data).unwrap(), data.len());
+
+    let packet = timeout(Duration::from_secs(5), inbound.recv()).await
+        .expect("No datagram relayed back")
+        .unwrap();
+    assert_eq!(packet.from, dest);
+    assert_eq!(packet.data, data.iter().rev().copied().collect::<Vec<_>>());
+    assert!(packet.decrypted.is_none());
+}
+
+#[test]
+fn test_frame() {
+    let addrs = [
+        "203.0.113.7:39001".parse::<SocketAddr>().unwrap(),
+        "[2001:db8::7]:39001".parse::<SocketAddr>().unwrap(),
+    ];
+    for addr in addrs {
+        for data in [vec![], vec![0u8, 1, 2, 255], vec![7u8; 1500]] {
+            let frame = ws_gateway::encode_frame(addr, &data).unwrap();
+            assert_eq!(ws_gateway::decode_frame(&frame), Some((addr, data.as_slice())));
+        }
+    }
+
+    let frame = ws_gateway::encode_frame(addrs[0], b"datagram").unwrap();
+    assert_eq!(frame.len(), 1 + 4 + 2 + 2 + 8);
+    assert_eq!(&frame[..7], &[4, 203, 0, 113, 7, 0x98, 0x59]);
+    assert_eq!(&frame[7..9], &[0, 8]);
+
+    assert!(ws_gateway::encode_frame(addrs[0], &vec![0u8; 65536]).is_none());
+}
+
+#[test]
+fn test_malformed_frame() {
+    let addr = "203.0.113.7:39001".parse::<SocketAddr>().unwrap();
+    let frame = ws_gateway::encode_frame(addr, b"datagram").unwrap();
+
+    assert!(ws_gateway::decode_frame(&[]).is_none());
+    // Truncated, padded, or of an unknown family.
+    assert!(ws_gateway::decode_frame(&frame[..frame.len() - 1]).is_none());
+    assert!(ws_gateway::decode_frame(&[frame.as_slice(), &[0]].concat()).is_none());
+    assert!(ws_gateway::decode_frame(&frame[..5]).is_none());
+    let mut unknown = frame.clone();
+    unknown[0] = 5;
+    assert!(ws_gateway::decode_frame(&unknown).is_none());
+}
+
+#[tokio::test]
+async fn test_relay() {
+    let echo = echo_socket().await;
+    let listener = GatewayListener::bind(loopback()).await.unwrap();
+    let (client, mut inbound) = GatewayClient::connect(gateway_url(&listener));
+    wait_connected(&client).await;
+
+    roundtrip(&client, &mut inbound, echo).await;
+    roundtrip(&client, &mut inbound, echo).await;
+}
+
+#[tokio::test]
+async fn test_queued_until_connected() {
+    let echo = echo_socket().await;
+    // Nothing listens there yet.
+    let addr = std::net::TcpListener::bind(loopback()).unwrap().local_addr().unwrap();
+    let (client, mut inbound) = GatewayClient::connect(Url::parse(&format!("ws://{addr}")).unwrap());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!client.is_connected());
+
+    let data = b"early datagram".to_vec();
+    assert_eq!(client.send(echo, &data).unwrap(), data.len());
+
+    let _listener = GatewayListener::bind(addr).await.unwrap();
+    let packet = timeout(Duration::from_secs(5), inbound.recv()).await
+        .expect("Queued datagram not relayed")
+        .unwrap();
+    assert_eq!(packet.from, echo);
+    assert!(client.is_connected());
+}
+
+#[tokio::test]
+async fn test_reconnect() {
+    let echo = echo_socket().await;
+    let listener = GatewayListener::bind(loopback()).await.unwrap();
+    let addr = listener.local_addr();
+    let (client, mut inbound) = GatewayClient::connect(gateway_url(&listener));
+    wait_connected(&client).await;
+    roundtrip(&client, &mut inbound, echo).await;
+
+    // The gateway goes away with its connections, and comes back.
+    drop(listener);
+    for _ in 0..100 {
+        if !client.is_connected() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(!client.is_connected());
+
+    let _listener = GatewayListener::bind(addr).await.unwrap();
+    wait_connected(&client).await;
+    roundtrip(&client, &mut inbound, echo).await;
+}
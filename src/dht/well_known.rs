@@ -0,0 +1,76 @@
+use std::fmt;
+use std::str::FromStr;
+use std::net::SocketAddr;
+
+use crate::{Id, NodeInfo};
+use crate::errors::{Error, ArgumentError};
+
+// Node id, address and port of the bootstrap nodes run for the network.
+const MAINNET_BOOTSTRAPS: [(&str, &str, u16); 3] = [
+    ("HZXXs9LTfNQjrDKvvexRhuMk8TTJhYCfrHwaj3jUzuhZ", "155.138.245.211", 39001),
+    ("6o6LkHgLyD5sYyW9iN5LNRYnUoX29jiYauQ5cDjhCpWQ", "45.32.138.246",   39001),
+    ("8grFdb2f6LLJajHwARvXC95y73WXEanNS1rbBAZYbC5L", "140.82.57.197",   39001),
+];
+const MAINNET_MESSAGING_PEER: &str = "G5Q4WoLh1gfyiZQ4djRPAp6DxJBoUDY22dimtN2n6hFZ";
+const MAINNET_ACTIVEPROXY_PEER: &str = "5vVM1nrCwFh3QqAgbvF3bRgYQL5a2vpFjngwxkiS8Ja6";
+
+/// A public Boson network named by the `network` key of the node config,
+/// standing for its bootstrap nodes and the peer ids of its messaging and
+/// active proxy services.
+///
+/// No testnet nodes or services are run publicly yet, a testnet config
+/// names its bootstrap nodes and service peers itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KnownNetwork {
+    Mainnet,
+    Testnet,
+}
+
+impl KnownNetwork {
+    pub fn bootstrap_nodes(&self) -> Vec<NodeInfo> {
+        let entries: &[(&str, &str, u16)] = match self {
+            Self::Mainnet => &MAINNET_BOOTSTRAPS,
+            Self::Testnet => &[],
+        };
+        entries.iter().map(|(id, host, port)| {
+            let id = Id::try_from(*id).expect("Invalid well-known node id");
+            let ip = host.parse().expect("Invalid well-known node address");
+            NodeInfo::new(id, SocketAddr::new(ip, *port))
+        }).collect()
+    }
+
+    pub fn messaging_peer_id(&self) -> Option<Id> {
+        match self {
+            Self::Mainnet => Some(Id::try_from(MAINNET_MESSAGING_PEER).expect("Invalid well-known peer id")),
+            Self::Testnet => None,
+        }
+    }
+
+    pub fn activeproxy_peer_id(&self) -> Option<Id> {
+        match self {
+            Self::Mainnet => Some(Id::try_from(MAINNET_ACTIVEPROXY_PEER).expect("Invalid well-known peer id")),
+            Self::Testnet => None,
+        }
+    }
+}
+
+impl FromStr for KnownNetwork {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.to_ascii_lowercase().as_str() {
+            "mainnet" => Ok(Self::Mainnet),
+            "testnet" => Ok(Self::Testnet),
+            _ => Err(ArgumentError::new(format!("Unknown network: {input}, expected mainnet or testnet"))),
+        }
+    }
+}
+
+impl fmt::Display for KnownNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+        })
+    }
+}
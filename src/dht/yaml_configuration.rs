@@ -2,18 +2,56 @@ use std::{
     fmt,
     env,
     fs,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     path::{Path, PathBuf},
 };
 use log::LevelFilter;
-use serde::Deserialize;
+use serde::{
+    Deserialize, Deserializer,
+    de::{self, IgnoredAny, MapAccess, SeqAccess, Visitor},
+};
+use serde_yaml::Value as YamlValue;
+use url::Url;
 
 use crate::{
     Id,
     NodeInfo,
+    EndpointPolicy,
     signature,
     errors::{Result, IOError, ArgumentError},
-    dht::{NodeConfig, node_config::DEFAULT_DHT_PORT},
+    dht::{
+        NodeConfig,
+        key_file,
+        StorageBackend,
+        well_known::KnownNetwork,
+        node_config::{
+            DEFAULT_DHT_PORT,
+            DEFAULT_SOCKET_RECV_TIMEOUT,
+            DEFAULT_SOCKET_STALL_CALLS,
+            DEFAULT_STATS_MAX_FILE_SIZE,
+            DEFAULT_STATS_MAX_FILES,
+            DEFAULT_STORAGE_MAINTENANCE_INTERVAL,
+            DEFAULT_SEND_RATE_INTERVAL,
+            DEFAULT_SEND_BURST,
+            DEFAULT_SEND_PACING,
+            DEFAULT_BUCKET_REFRESH_INTERVAL,
+            DEFAULT_ANNOUNCEMENT_SKEW,
+            DEFAULT_CLOCK_SKEW_THRESHOLD,
+            DEFAULT_CRYPTO_CACHE_CAPACITY,
+            DEFAULT_CRYPTO_CACHE_TTL,
+            DEFAULT_MIN_STORE_ACKS,
+            DEFAULT_MAX_ACTIVE_TASKS,
+            DEFAULT_MAX_INFLIGHT_CALLS,
+            DEFAULT_MAX_TASK_CALLS,
+            DEFAULT_BUCKET_CAPACITY,
+            DEFAULT_COMMAND_QUEUE_SIZE,
+            MAX_RECEIVE_SOCKETS,
+            DEFAULT_BLOCK_TOKEN_STRIKES,
+            DEFAULT_BLOCK_VALUE_STRIKES,
+            DEFAULT_BLOCK_DURATION,
+        },
+        node_event::DEFAULT_EVENT_LOG_CAPACITY,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -23,13 +61,64 @@ pub struct NodeConfiguration {
     host4       : Option<String>,
     host6       : Option<String>,
     port        : u16,
+    port4       : Option<u16>,
+    port6       : Option<u16>,
     private_key : signature::PrivateKey,
+    key_file    : Option<PathBuf>,
     data_dir    : String,
+    instance_name: Option<String>,
     database_uri: String,
+    storage_backend: StorageBackend,
+    seed_data   : Option<String>,
+    network     : Option<KnownNetwork>,
     bootstrap_nodes: Vec<NodeInfo>,
+    messaging_peer_id: Option<Id>,
+    activeproxy_peer_id: Option<Id>,
     log_level   : LevelFilter,
     log_file    : Option<String>,
     devp        : bool,
+    event_log_capacity: usize,
+    crypto_cache_capacity: u64,
+    crypto_cache_ttl: u64,
+    min_store_acks: usize,
+    socket_recv_timeout: u64,
+    socket_stall_calls: u32,
+    endpoint_policy: EndpointPolicy,
+    stats_interval: u64,
+    stats_max_file_size: u64,
+    stats_max_files: usize,
+    storage_maintenance_interval: u64,
+    send_rate: u32,
+    send_rate_interval: u64,
+    send_burst: u32,
+    send_pacing: u64,
+    prefer_low_rtt: bool,
+    bucket_refresh_interval: u64,
+    lookup_cache_ttl: u64,
+    privacy_mode: bool,
+    privacy_rotation: u64,
+    announcement_skew: u64,
+    require_announcement_time: bool,
+    required_countersigner: Option<Id>,
+    reject_far_stores: bool,
+    pin_allowlist: Vec<Id>,
+    clock_skew_threshold: u64,
+    compensate_clock_skew: bool,
+    max_active_tasks: usize,
+    max_inflight_calls: usize,
+    max_task_calls: usize,
+    bucket_capacity: usize,
+    home_split_levels: usize,
+    max_routing_entries: usize,
+    command_queue_size: usize,
+    receive_sockets: usize,
+    ws_gateway: Option<Url>,
+    ws_gateway_listener: Option<SocketAddr>,
+    client_only: bool,
+    relay: bool,
+    block_token_strikes: u32,
+    block_value_strikes: u32,
+    block_duration: u64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -38,29 +127,173 @@ struct YamlNodeConfig {
     ipv6        : Option<bool>,
     #[serde(default = "default_port")]
     port        : u16,
+    port4       : Option<u16>,
+    port6       : Option<u16>,
     #[serde(rename = "privateKey")]
-    private_key : String,
+    private_key : Option<String>,
+    // The node key kept in a file instead, created on the first start, under
+    // the data directory unless absolute. With a passphrase the file is
+    // encrypted, `${VAR}` takes the passphrase from the environment.
+    #[serde(rename = "keyFile")]
+    key_file    : Option<String>,
+    #[serde(rename = "keyPassphrase")]
+    key_passphrase: Option<String>,
     #[serde(rename = "dataDir")]
     data_dir    : Option<String>,
-    #[serde(rename = "databaseUri")]
+    #[serde(rename = "instanceName")]
+    instance_name: Option<String>,
+    #[serde(rename = "databaseUri", default)]
     database_uri: String,
+    #[serde(rename = "storageBackend")]
+    storage_backend: Option<String>,
+    #[serde(rename = "seedData")]
+    seed_data   : Option<String>,
+    network     : Option<String>,
     #[serde(default)]
     bootstraps  : Vec<YamlNodeEntry>,
+    #[serde(rename = "messagingPeerId")]
+    messaging_peer_id: Option<Id>,
+    #[serde(rename = "activeProxyPeerId")]
+    activeproxy_peer_id: Option<Id>,
     #[serde(rename = "logLevel")]
     log_level   : Option<String>,
     #[serde(rename = "logFile")]
     log_file    : Option<String>,
     #[serde(rename = "enableDeveloperMode", default)]
     devp        : bool,
+    #[serde(rename = "eventLogCapacity", default = "default_event_log_capacity")]
+    event_log_capacity: usize,
+    #[serde(rename = "cryptoCacheCapacity", default = "default_crypto_cache_capacity")]
+    crypto_cache_capacity: u64,
+    #[serde(rename = "cryptoCacheTtl", default = "default_crypto_cache_ttl")]
+    crypto_cache_ttl: u64,
+    #[serde(rename = "minStoreAcks", default = "default_min_store_acks")]
+    min_store_acks: usize,
+    #[serde(rename = "socketRecvTimeout", default = "default_socket_recv_timeout")]
+    socket_recv_timeout: u64,
+    #[serde(rename = "socketStallCalls", default = "default_socket_stall_calls")]
+    socket_stall_calls: u32,
+    #[serde(rename = "peerEndpointPolicy")]
+    endpoint_policy: Option<String>,
+    #[serde(rename = "statsInterval", default)]
+    stats_interval: u64,
+    #[serde(rename = "statsMaxFileSize", default = "default_stats_max_file_size")]
+    stats_max_file_size: u64,
+    #[serde(rename = "statsMaxFiles", default = "default_stats_max_files")]
+    stats_max_files: usize,
+    #[serde(rename = "storageMaintenanceInterval", default = "default_storage_maintenance_interval")]
+    storage_maintenance_interval: u64,
+    #[serde(rename = "sendRate", default)]
+    send_rate: u32,
+    #[serde(rename = "sendRateInterval", default = "default_send_rate_interval")]
+    send_rate_interval: u64,
+    #[serde(rename = "sendBurst", default = "default_send_burst")]
+    send_burst: u32,
+    #[serde(rename = "sendPacing", default = "default_send_pacing")]
+    send_pacing: u64,
+    #[serde(rename = "preferLowRtt", default = "default_prefer_low_rtt")]
+    prefer_low_rtt: bool,
+    #[serde(rename = "bucketRefreshInterval", default = "default_bucket_refresh_interval")]
+    bucket_refresh_interval: u64,
+    #[serde(rename = "lookupCacheTtl", default)]
+    lookup_cache_ttl: u64,
+    #[serde(rename = "privacyMode", default)]
+    privacy_mode: bool,
+    #[serde(rename = "privacyRotation", default)]
+    privacy_rotation: u64,
+    #[serde(rename = "announcementSkew", default = "default_announcement_skew")]
+    announcement_skew: u64,
+    #[serde(rename = "requireAnnouncementTime", default)]
+    require_announcement_time: bool,
+    #[serde(rename = "requireCountersigner", default)]
+    required_countersigner: Option<Id>,
+    #[serde(rename = "rejectFarStores", default)]
+    reject_far_stores: bool,
+    #[serde(rename = "acceptPinRequestsFrom", default)]
+    pin_allowlist: Vec<Id>,
+    #[serde(rename = "clockSkewThreshold", default = "default_clock_skew_threshold")]
+    clock_skew_threshold: u64,
+    #[serde(rename = "compensateClockSkew", default)]
+    compensate_clock_skew: bool,
+    #[serde(rename = "maxActiveTasks", default = "default_max_active_tasks")]
+    max_active_tasks: usize,
+    #[serde(rename = "maxInflightCalls", default = "default_max_inflight_calls")]
+    max_inflight_calls: usize,
+    #[serde(rename = "maxTaskCalls", default = "default_max_task_calls")]
+    max_task_calls: usize,
+    #[serde(rename = "bucketCapacity", default = "default_bucket_capacity")]
+    bucket_capacity: usize,
+    #[serde(rename = "homeSplitLevels", default)]
+    home_split_levels: usize,
+    #[serde(rename = "maxRoutingEntries", default)]
+    max_routing_entries: usize,
+    #[serde(rename = "commandQueueSize", default = "default_command_queue_size")]
+    command_queue_size: usize,
+    #[serde(rename = "receiveSockets", default = "default_receive_sockets")]
+    receive_sockets: usize,
+    #[serde(rename = "wsGateway")]
+    ws_gateway: Option<String>,
+    #[serde(rename = "wsGatewayListener")]
+    ws_gateway_listener: Option<SocketAddr>,
+    #[serde(rename = "clientOnly", default)]
+    client_only: bool,
+    #[serde(rename = "relay", default)]
+    relay: bool,
+    #[serde(rename = "blockTokenStrikes", default = "default_block_token_strikes")]
+    block_token_strikes: u32,
+    #[serde(rename = "blockValueStrikes", default = "default_block_value_strikes")]
+    block_value_strikes: u32,
+    #[serde(rename = "blockDuration", default = "default_block_duration")]
+    block_duration: u64,
 }
 
 impl TryFrom<YamlNodeConfig> for NodeConfiguration {
     type Error = crate::Error;
     fn try_from(yaml: YamlNodeConfig) -> Result<Self> {
-        let sk = signature::PrivateKey::try_from(yaml.private_key.as_str())?;
-        let bootstrap_nodes = yaml.bootstraps.into_iter()
-            .map(|entry| NodeInfo::try_from(entry))
-            .collect::<Result<Vec<_>>>()?;
+        let data_dir = expand_datadir(yaml.data_dir);
+        let key_file = yaml.key_file.map(|v| key_file_path(&data_dir, v));
+        let passphrase = yaml.key_passphrase.as_deref().filter(|v| !v.is_empty());
+        let sk = match (yaml.private_key.as_deref(), key_file.as_ref()) {
+            (Some(_), Some(_)) => {
+                return Err(ArgumentError::new("privateKey and keyFile can not be both given"));
+            },
+            (Some(_), None) if passphrase.is_some() => {
+                return Err(ArgumentError::new("keyPassphrase is set without a keyFile"));
+            },
+            (Some(v), None) => signature::PrivateKey::try_from(v)?,
+            (None, Some(path)) => key_file::load_or_create(path, passphrase)?,
+            (None, None) => {
+                return Err(ArgumentError::new("privateKey or keyFile must be given"));
+            },
+        };
+        let storage_backend = match yaml.storage_backend.as_deref() {
+            Some(v) => v.parse::<StorageBackend>()?,
+            None => StorageBackend::default(),
+        };
+        let endpoint_policy = match yaml.endpoint_policy.as_deref() {
+            Some(v) => v.parse::<EndpointPolicy>()?,
+            None => EndpointPolicy::default(),
+        };
+        let network = match yaml.network.as_deref() {
+            Some(v) => Some(v.parse::<KnownNetwork>()?),
+            None => None,
+        };
+        // Explicit entries take the place of those of the network.
+        let bootstrap_nodes = match network {
+            Some(network) if yaml.bootstraps.is_empty() => {
+                let nodes = network.bootstrap_nodes();
+                if nodes.is_empty() {
+                    return Err(ArgumentError::new(format!(
+                        "network {network} has no built-in bootstrap nodes, bootstraps must be given")));
+                }
+                nodes
+            },
+            _ => yaml.bootstraps.into_iter().map(|entry| entry.0).collect(),
+        };
+        let messaging_peer_id = yaml.messaging_peer_id
+            .or_else(|| network.and_then(|v| v.messaging_peer_id()));
+        let activeproxy_peer_id = yaml.activeproxy_peer_id
+            .or_else(|| network.and_then(|v| v.activeproxy_peer_id()));
 
         let addr4 = if yaml.ipv4.unwrap_or(false) {
             use crate::local_addr;
@@ -74,36 +307,201 @@ impl TryFrom<YamlNodeConfig> for NodeConfiguration {
         } else {
             None
         };
+        if yaml.port4.is_some() && addr4.is_none() {
+            return Err(ArgumentError::new("port4 is set without an IPv4 address"));
+        }
+        if yaml.port6.is_some() && addr6.is_none() {
+            return Err(ArgumentError::new("port6 is set without an IPv6 address"));
+        }
+        if yaml.max_active_tasks == 0 || yaml.max_inflight_calls == 0 || yaml.max_task_calls == 0 {
+            return Err(ArgumentError::new("maxActiveTasks, maxInflightCalls and maxTaskCalls must be larger than 0"));
+        }
+        if yaml.bucket_capacity == 0 {
+            return Err(ArgumentError::new("bucketCapacity must be larger than 0"));
+        }
+        if yaml.max_routing_entries != 0 && yaml.max_routing_entries < yaml.bucket_capacity {
+            return Err(ArgumentError::new("maxRoutingEntries must be 0 or not less than bucketCapacity"));
+        }
+        if yaml.command_queue_size == 0 {
+            return Err(ArgumentError::new("commandQueueSize must be larger than 0"));
+        }
+        if yaml.receive_sockets == 0 || yaml.receive_sockets > MAX_RECEIVE_SOCKETS {
+            return Err(ArgumentError::new(format!("receiveSockets must be from 1 to {MAX_RECEIVE_SOCKETS}")));
+        }
+        if yaml.receive_sockets > 1 && !cfg!(target_os = "linux") {
+            return Err(ArgumentError::new("receiveSockets above 1 is only supported on Linux"));
+        }
+        let ws_gateway = match yaml.ws_gateway.as_deref() {
+            Some(v) => Some(Url::parse(v).map_err(|e| {
+                ArgumentError::new(format!("invalid wsGateway {v}: {e}"))
+            })?),
+            None => None,
+        };
+        if let Some(url) = ws_gateway.as_ref() {
+            if url.scheme() != "ws" || url.host().is_none() {
+                return Err(ArgumentError::new(format!("wsGateway must be a ws:// url, not {url}")));
+            }
+            if yaml.ws_gateway_listener.is_some() {
+                return Err(ArgumentError::new("wsGateway and wsGatewayListener can not be both given"));
+            }
+            if yaml.receive_sockets > 1 {
+                return Err(ArgumentError::new("receiveSockets above 1 can not be used with wsGateway"));
+            }
+        }
+        if (ws_gateway.is_some() || yaml.ws_gateway_listener.is_some()) && !cfg!(feature = "wsgateway") {
+            return Err(ArgumentError::new("wsGateway and wsGatewayListener require the wsgateway feature"));
+        }
+        if yaml.block_duration == 0 && (yaml.block_token_strikes > 0 || yaml.block_value_strikes > 0) {
+            return Err(ArgumentError::new("blockDuration must be larger than 0 with blockTokenStrikes or blockValueStrikes set"));
+        }
 
         Ok(NodeConfiguration {
             host4   : addr4,
             host6   : addr6,
             port    : yaml.port,
+            port4   : yaml.port4,
+            port6   : yaml.port6,
             private_key: sk,
-            data_dir: expand_datadir(yaml.data_dir),
+            key_file,
+            data_dir,
+            instance_name: yaml.instance_name,
             database_uri: yaml.database_uri,
+            storage_backend,
+            seed_data: yaml.seed_data,
+            network,
             bootstrap_nodes,
+            messaging_peer_id,
+            activeproxy_peer_id,
             log_level: log_level(yaml.log_level.as_deref()),
             log_file: yaml.log_file,
             devp    : yaml.devp,
+            event_log_capacity: yaml.event_log_capacity,
+            crypto_cache_capacity: yaml.crypto_cache_capacity,
+            crypto_cache_ttl: yaml.crypto_cache_ttl,
+            min_store_acks: yaml.min_store_acks,
+            socket_recv_timeout: yaml.socket_recv_timeout,
+            socket_stall_calls: yaml.socket_stall_calls,
+            endpoint_policy,
+            stats_interval: yaml.stats_interval,
+            stats_max_file_size: yaml.stats_max_file_size,
+            stats_max_files: yaml.stats_max_files,
+            storage_maintenance_interval: yaml.storage_maintenance_interval,
+            send_rate: yaml.send_rate,
+            send_rate_interval: yaml.send_rate_interval,
+            send_burst: yaml.send_burst,
+            send_pacing: yaml.send_pacing,
+            prefer_low_rtt: yaml.prefer_low_rtt,
+            bucket_refresh_interval: yaml.bucket_refresh_interval,
+            lookup_cache_ttl: yaml.lookup_cache_ttl,
+            privacy_mode: yaml.privacy_mode,
+            privacy_rotation: yaml.privacy_rotation,
+            announcement_skew: yaml.announcement_skew,
+            require_announcement_time: yaml.require_announcement_time,
+            required_countersigner: yaml.required_countersigner,
+            reject_far_stores: yaml.reject_far_stores,
+            pin_allowlist: yaml.pin_allowlist,
+            clock_skew_threshold: yaml.clock_skew_threshold,
+            compensate_clock_skew: yaml.compensate_clock_skew,
+            max_active_tasks: yaml.max_active_tasks,
+            max_inflight_calls: yaml.max_inflight_calls,
+            max_task_calls: yaml.max_task_calls,
+            bucket_capacity: yaml.bucket_capacity,
+            home_split_levels: yaml.home_split_levels,
+            max_routing_entries: yaml.max_routing_entries,
+            command_queue_size: yaml.command_queue_size,
+            receive_sockets: yaml.receive_sockets,
+            ws_gateway,
+            ws_gateway_listener: yaml.ws_gateway_listener,
+            client_only: yaml.client_only,
+            relay: yaml.relay,
+            block_token_strikes: yaml.block_token_strikes,
+            block_value_strikes: yaml.block_value_strikes,
+            block_duration: yaml.block_duration,
         })
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct YamlNodeEntry(Id, String, u16);
+// A bootstrap node, as `[id, address, port]` or `{id, address, port}`. The
+// fields are checked here, so the error tells the entry, the field and
+// the line.
+#[derive(Debug)]
+struct YamlNodeEntry(NodeInfo);
 
-impl TryFrom<YamlNodeEntry> for NodeInfo {
-    type Error = crate::Error;
+impl<'de> Deserialize<'de> for YamlNodeEntry {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NodeEntryVisitor)
+    }
+}
 
-    fn try_from(value: YamlNodeEntry) -> Result<NodeInfo> {
-        let YamlNodeEntry(id, host, port) = value;
-        let addr = format!("{host}:{port}")
-            .parse::<SocketAddr>()
-            .map_err(|e|ArgumentError::new(
-                format!("Invalid bootstrap node address {host}:{port}: {e}"))
-        )?;
-        Ok(NodeInfo::new(id, addr))
+struct NodeEntryVisitor;
+
+impl<'de> Visitor<'de> for NodeEntryVisitor {
+    type Value = YamlNodeEntry;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a bootstrap node as [id, address, port] or {id, address, port}")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<YamlNodeEntry, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut fields = Vec::with_capacity(3);
+        while fields.len() < 3 {
+            match seq.next_element::<YamlValue>()? {
+                Some(v) => fields.push(v),
+                None => return Err(de::Error::invalid_length(fields.len(), &self)),
+            }
+        }
+        if seq.next_element::<IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_length(4, &self));
+        }
+        node_entry(&fields[0], &fields[1], &fields[2]).map_err(de::Error::custom)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> std::result::Result<YamlNodeEntry, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        const FIELDS: &[&str] = &["id", "address", "port"];
+        let mut fields: [Option<YamlValue>; 3] = Default::default();
+        while let Some(key) = map.next_key::<String>()? {
+            let Some(pos) = FIELDS.iter().position(|v| *v == key) else {
+                return Err(de::Error::unknown_field(&key, FIELDS));
+            };
+            fields[pos] = Some(map.next_value()?);
+        }
+        let [Some(id), Some(address), Some(port)] = &fields else {
+            let pos = fields.iter().position(|v| v.is_none()).unwrap();
+            return Err(de::Error::missing_field(FIELDS[pos]));
+        };
+        node_entry(id, address, port).map_err(de::Error::custom)
+    }
+}
+
+fn node_entry(id: &YamlValue, address: &YamlValue, port: &YamlValue) -> std::result::Result<YamlNodeEntry, String> {
+    let id = match id.as_str() {
+        Some(v) => Id::try_from(v).map_err(|e| format!("invalid node id '{v}': {e}"))?,
+        None => return Err(format!("invalid node id {}, expected a base58 string", show(id))),
+    };
+    let ip = match address.as_str() {
+        Some(v) => v.parse::<IpAddr>().map_err(|e| format!("invalid address '{v}': {e}"))?,
+        None => return Err(format!("invalid address {}, expected an IP address", show(address))),
+    };
+    let port = match port.as_u64() {
+        Some(v @ 1..=65535) => v as u16,
+        _ => return Err(format!("invalid port {}, expected a number from 1 to 65535", show(port))),
+    };
+    Ok(YamlNodeEntry(NodeInfo::new(id, SocketAddr::new(ip, port))))
+}
+
+fn show(value: &YamlValue) -> String {
+    match value {
+        YamlValue::String(v) => format!("'{v}'"),
+        v => serde_yaml::to_string(v).map(|v| v.trim_end().to_string()).unwrap_or_default(),
     }
 }
 
@@ -111,6 +509,106 @@ fn default_port() -> u16 {
     DEFAULT_DHT_PORT
 }
 
+fn default_event_log_capacity() -> usize {
+    DEFAULT_EVENT_LOG_CAPACITY
+}
+
+fn default_crypto_cache_capacity() -> u64 {
+    DEFAULT_CRYPTO_CACHE_CAPACITY
+}
+
+fn default_crypto_cache_ttl() -> u64 {
+    DEFAULT_CRYPTO_CACHE_TTL
+}
+
+fn default_min_store_acks() -> usize {
+    DEFAULT_MIN_STORE_ACKS
+}
+
+fn default_socket_recv_timeout() -> u64 {
+    DEFAULT_SOCKET_RECV_TIMEOUT
+}
+
+fn default_socket_stall_calls() -> u32 {
+    DEFAULT_SOCKET_STALL_CALLS
+}
+
+fn default_stats_max_file_size() -> u64 {
+    DEFAULT_STATS_MAX_FILE_SIZE
+}
+
+fn default_stats_max_files() -> usize {
+    DEFAULT_STATS_MAX_FILES
+}
+
+fn default_storage_maintenance_interval() -> u64 {
+    DEFAULT_STORAGE_MAINTENANCE_INTERVAL
+}
+
+fn default_send_rate_interval() -> u64 {
+    DEFAULT_SEND_RATE_INTERVAL
+}
+
+fn default_send_burst() -> u32 {
+    DEFAULT_SEND_BURST
+}
+
+fn default_send_pacing() -> u64 {
+    DEFAULT_SEND_PACING
+}
+
+fn default_prefer_low_rtt() -> bool {
+    true
+}
+
+fn default_bucket_refresh_interval() -> u64 {
+    DEFAULT_BUCKET_REFRESH_INTERVAL
+}
+
+fn default_announcement_skew() -> u64 {
+    DEFAULT_ANNOUNCEMENT_SKEW
+}
+
+fn default_clock_skew_threshold() -> u64 {
+    DEFAULT_CLOCK_SKEW_THRESHOLD
+}
+
+fn default_max_active_tasks() -> usize {
+    DEFAULT_MAX_ACTIVE_TASKS
+}
+
+fn default_max_inflight_calls() -> usize {
+    DEFAULT_MAX_INFLIGHT_CALLS
+}
+
+fn default_max_task_calls() -> usize {
+    DEFAULT_MAX_TASK_CALLS
+}
+
+fn default_bucket_capacity() -> usize {
+    DEFAULT_BUCKET_CAPACITY
+}
+
+fn default_command_queue_size() -> usize {
+    DEFAULT_COMMAND_QUEUE_SIZE
+}
+
+fn default_receive_sockets() -> usize {
+    1
+}
+
+fn default_block_token_strikes() -> u32 {
+    DEFAULT_BLOCK_TOKEN_STRIKES
+}
+
+fn default_block_value_strikes() -> u32 {
+    DEFAULT_BLOCK_VALUE_STRIKES
+}
+
+fn default_block_duration() -> u64 {
+    DEFAULT_BLOCK_DURATION
+}
+
 impl NodeConfiguration {
     pub fn from(yaml: &str) -> Result<Self> {
         let expanded = expand_env(yaml)?;
@@ -140,6 +638,101 @@ impl NodeConfiguration {
 
         Self::load(path)
     }
+
+    /// The public network named by the `network` key, if any.
+    pub fn network(&self) -> Option<KnownNetwork> {
+        self.network
+    }
+
+    /// The peer id of the messaging service, as set or that of the network.
+    pub fn messaging_peer_id(&self) -> Option<&Id> {
+        self.messaging_peer_id.as_ref()
+    }
+
+    /// The peer id of the active proxy service, as set or that of the network.
+    pub fn activeproxy_peer_id(&self) -> Option<&Id> {
+        self.activeproxy_peer_id.as_ref()
+    }
+
+    pub fn with_listening_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn with_listening_port4(mut self, port: u16) -> Self {
+        self.port4 = Some(port);
+        self
+    }
+
+    pub fn with_listening_port6(mut self, port: u16) -> Self {
+        self.port6 = Some(port);
+        self
+    }
+
+    /// The file the node key is kept in, if not given inline.
+    pub fn key_file(&self) -> Option<&Path> {
+        self.key_file.as_deref()
+    }
+
+    /// Encrypts the key file under `passphrase`, or under a new passphrase
+    /// if it was encrypted already. The key file is required.
+    pub fn with_key_passphrase(self, passphrase: &str) -> Result<Self> {
+        let Some(path) = self.key_file.as_ref() else {
+            return Err(ArgumentError::new("A key passphrase requires the key in a keyFile"));
+        };
+        key_file::write(path, &self.private_key, Some(passphrase))?;
+        Ok(self)
+    }
+
+    pub fn with_instance_name(mut self, name: &str) -> Self {
+        self.instance_name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_storage_backend(mut self, backend: StorageBackend) -> Self {
+        self.storage_backend = backend;
+        self
+    }
+
+    pub fn with_seed_data(mut self, path: &str) -> Self {
+        self.seed_data = Some(path.to_string());
+        self
+    }
+
+    pub fn with_ws_gateway(mut self, url: Url) -> Self {
+        self.ws_gateway = Some(url);
+        self
+    }
+
+    pub fn with_ws_gateway_listener(mut self, addr: SocketAddr) -> Self {
+        self.ws_gateway_listener = Some(addr);
+        self
+    }
+
+    pub fn with_client_only(mut self, client_only: bool) -> Self {
+        self.client_only = client_only;
+        self
+    }
+
+    pub fn with_relay(mut self, relay: bool) -> Self {
+        self.relay = relay;
+        self
+    }
+
+    pub fn require_countersigner(mut self, signer: Option<Id>) -> Self {
+        self.required_countersigner = signer;
+        self
+    }
+
+    pub fn reject_far_stores(mut self, reject: bool) -> Self {
+        self.reject_far_stores = reject;
+        self
+    }
+
+    pub fn accept_pin_requests_from(mut self, allowlist: Vec<Id>) -> Self {
+        self.pin_allowlist = allowlist;
+        self
+    }
 }
 
 impl NodeConfig for NodeConfiguration {
@@ -155,6 +748,14 @@ impl NodeConfig for NodeConfiguration {
         self.port
     }
 
+    fn port4(&self) -> u16 {
+        self.port4.unwrap_or(self.port)
+    }
+
+    fn port6(&self) -> u16 {
+        self.port6.unwrap_or(self.port)
+    }
+
     fn private_key(&self) -> &signature::PrivateKey {
         &self.private_key
     }
@@ -167,6 +768,18 @@ impl NodeConfig for NodeConfiguration {
         &self.database_uri
     }
 
+    fn instance_name(&self) -> Option<&str> {
+        self.instance_name.as_deref()
+    }
+
+    fn storage_backend(&self) -> StorageBackend {
+        self.storage_backend
+    }
+
+    fn seed_data(&self) -> Option<&str> {
+        self.seed_data.as_deref()
+    }
+
     fn bootstrap_nodes(&self) -> &[NodeInfo] {
         &self.bootstrap_nodes
     }
@@ -183,6 +796,174 @@ impl NodeConfig for NodeConfiguration {
         self.devp
     }
 
+    fn event_log_capacity(&self) -> usize {
+        self.event_log_capacity
+    }
+
+    fn crypto_cache_capacity(&self) -> u64 {
+        self.crypto_cache_capacity
+    }
+
+    fn crypto_cache_ttl(&self) -> u64 {
+        self.crypto_cache_ttl
+    }
+
+    fn min_store_acks(&self) -> usize {
+        self.min_store_acks
+    }
+
+    fn socket_recv_timeout(&self) -> u64 {
+        self.socket_recv_timeout
+    }
+
+    fn socket_stall_calls(&self) -> u32 {
+        self.socket_stall_calls
+    }
+
+    fn endpoint_policy(&self) -> EndpointPolicy {
+        self.endpoint_policy
+    }
+
+    fn stats_interval(&self) -> u64 {
+        self.stats_interval
+    }
+
+    fn stats_max_file_size(&self) -> u64 {
+        self.stats_max_file_size
+    }
+
+    fn stats_max_files(&self) -> usize {
+        self.stats_max_files
+    }
+
+    fn storage_maintenance_interval(&self) -> u64 {
+        self.storage_maintenance_interval
+    }
+
+    fn send_rate(&self) -> u32 {
+        self.send_rate
+    }
+
+    fn send_rate_interval(&self) -> u64 {
+        self.send_rate_interval
+    }
+
+    fn send_burst(&self) -> u32 {
+        self.send_burst
+    }
+
+    fn send_pacing(&self) -> u64 {
+        self.send_pacing
+    }
+
+    fn prefer_low_rtt(&self) -> bool {
+        self.prefer_low_rtt
+    }
+
+    fn bucket_refresh_interval(&self) -> u64 {
+        self.bucket_refresh_interval
+    }
+
+    fn lookup_cache_ttl(&self) -> u64 {
+        self.lookup_cache_ttl
+    }
+
+    fn privacy_mode(&self) -> bool {
+        self.privacy_mode
+    }
+
+    fn privacy_rotation(&self) -> u64 {
+        self.privacy_rotation
+    }
+
+    fn announcement_skew(&self) -> u64 {
+        self.announcement_skew
+    }
+
+    fn require_announcement_time(&self) -> bool {
+        self.require_announcement_time
+    }
+
+    fn required_countersigner(&self) -> Option<&Id> {
+        self.required_countersigner.as_ref()
+    }
+
+    fn reject_far_stores(&self) -> bool {
+        self.reject_far_stores
+    }
+
+    fn pin_allowlist(&self) -> &[Id] {
+        &self.pin_allowlist
+    }
+
+    fn clock_skew_threshold(&self) -> u64 {
+        self.clock_skew_threshold
+    }
+
+    fn compensate_clock_skew(&self) -> bool {
+        self.compensate_clock_skew
+    }
+
+    fn max_active_tasks(&self) -> usize {
+        self.max_active_tasks
+    }
+
+    fn max_inflight_calls(&self) -> usize {
+        self.max_inflight_calls
+    }
+
+    fn max_task_calls(&self) -> usize {
+        self.max_task_calls
+    }
+
+    fn bucket_capacity(&self) -> usize {
+        self.bucket_capacity
+    }
+
+    fn home_split_levels(&self) -> usize {
+        self.home_split_levels
+    }
+
+    fn max_routing_entries(&self) -> usize {
+        self.max_routing_entries
+    }
+
+    fn command_queue_size(&self) -> usize {
+        self.command_queue_size
+    }
+
+    fn receive_sockets(&self) -> usize {
+        self.receive_sockets
+    }
+
+    fn ws_gateway(&self) -> Option<&Url> {
+        self.ws_gateway.as_ref()
+    }
+
+    fn ws_gateway_listener(&self) -> Option<SocketAddr> {
+        self.ws_gateway_listener
+    }
+
+    fn client_only(&self) -> bool {
+        self.client_only
+    }
+
+    fn relay(&self) -> bool {
+        self.relay
+    }
+
+    fn block_token_strikes(&self) -> u32 {
+        self.block_token_strikes
+    }
+
+    fn block_value_strikes(&self) -> u32 {
+        self.block_value_strikes
+    }
+
+    fn block_duration(&self) -> u64 {
+        self.block_duration
+    }
+
     fn dump(&self) {
         println!("{}", self);
     }
@@ -211,6 +992,14 @@ fn expand_datadir(data_dir: Option<String>) -> String {
     data_dir
 }
 
+fn key_file_path(data_dir: &str, key_file: String) -> PathBuf {
+    let path = PathBuf::from(expand_datadir(Some(key_file)));
+    match path.is_absolute() {
+        true => path,
+        false => Path::new(data_dir).join(path),
+    }
+}
+
 fn expand_env(input: &str) -> Result<String> {
     let mut expanded = String::with_capacity(input.len());
     let mut cursor = 0;
@@ -275,11 +1064,90 @@ impl fmt::Display for NodeConfiguration {
         write!(f, "\n\thost4: {}", self.host4.as_deref().unwrap_or("<none>"))?;
         write!(f, "\n\thost6: {}", self.host6.as_deref().unwrap_or("<none>"))?;
         write!(f, "\n\tport: {}", self.port)?;
-        write!(f, "\n\tprivateKey: {}", self.private_key)?;
+        if let Some(port) = self.port4 {
+            write!(f, "\n\tport4: {}", port)?;
+        }
+        if let Some(port) = self.port6 {
+            write!(f, "\n\tport6: {}", port)?;
+        }
+        match self.key_file.as_ref() {
+            Some(path) => write!(f, "\n\tkeyFile: {}", path.display())?,
+            None => write!(f, "\n\tprivateKey: {}", self.private_key)?,
+        }
         write!(f, "\n\tataDir: {}", self.data_dir)?;
+        if let Some(name) = self.instance_name.as_ref() {
+            write!(f, "\n\tinstanceName: {}", name)?;
+        }
+        write!(f, "\n\tstorageBackend: {}", self.storage_backend)?;
+        if let Some(path) = self.seed_data.as_ref() {
+            write!(f, "\n\tseedData: {}", path)?;
+        }
+        if let Some(network) = self.network {
+            write!(f, "\n\tnetwork: {}", network)?;
+        }
+        if let Some(id) = self.messaging_peer_id.as_ref() {
+            write!(f, "\n\tmessagingPeerId: {}", id)?;
+        }
+        if let Some(id) = self.activeproxy_peer_id.as_ref() {
+            write!(f, "\n\tactiveProxyPeerId: {}", id)?;
+        }
         write!(f, "\n\tlogLevel: {:?}", self.log_level)?;
         write!(f, "\n\tlogFile: {}", self.log_file.as_deref().unwrap_or("<none>"))?;
         write!(f, "\n\tenableDeveloperMode: {}", self.devp)?;
+        write!(f, "\n\teventLogCapacity: {}", self.event_log_capacity)?;
+        write!(f, "\n\tcryptoCacheCapacity: {}", self.crypto_cache_capacity)?;
+        write!(f, "\n\tcryptoCacheTtl: {}", self.crypto_cache_ttl)?;
+        write!(f, "\n\tminStoreAcks: {}", self.min_store_acks)?;
+        write!(f, "\n\tsocketRecvTimeout: {}", self.socket_recv_timeout)?;
+        write!(f, "\n\tsocketStallCalls: {}", self.socket_stall_calls)?;
+        write!(f, "\n\tpeerEndpointPolicy: {}", self.endpoint_policy)?;
+        write!(f, "\n\tstatsInterval: {}", self.stats_interval)?;
+        write!(f, "\n\tstatsMaxFileSize: {}", self.stats_max_file_size)?;
+        write!(f, "\n\tstatsMaxFiles: {}", self.stats_max_files)?;
+        write!(f, "\n\tstorageMaintenanceInterval: {}", self.storage_maintenance_interval)?;
+        write!(f, "\n\tsendRate: {}", self.send_rate)?;
+        write!(f, "\n\tsendRateInterval: {}", self.send_rate_interval)?;
+        write!(f, "\n\tsendBurst: {}", self.send_burst)?;
+        write!(f, "\n\tsendPacing: {}", self.send_pacing)?;
+        write!(f, "\n\tpreferLowRtt: {}", self.prefer_low_rtt)?;
+        write!(f, "\n\tbucketRefreshInterval: {}", self.bucket_refresh_interval)?;
+        write!(f, "\n\tlookupCacheTtl: {}", self.lookup_cache_ttl)?;
+        write!(f, "\n\tprivacyMode: {}", self.privacy_mode)?;
+        write!(f, "\n\tprivacyRotation: {}", self.privacy_rotation)?;
+        write!(f, "\n\tannouncementSkew: {}", self.announcement_skew)?;
+        write!(f, "\n\trequireAnnouncementTime: {}", self.require_announcement_time)?;
+        if let Some(signer) = self.required_countersigner.as_ref() {
+            write!(f, "\n\trequireCountersigner: {}", signer)?;
+        }
+        write!(f, "\n\trejectFarStores: {}", self.reject_far_stores)?;
+        if !self.pin_allowlist.is_empty() {
+            let ids = self.pin_allowlist.iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            write!(f, "\n\tacceptPinRequestsFrom: [{}]", ids)?;
+        }
+        write!(f, "\n\tclockSkewThreshold: {}", self.clock_skew_threshold)?;
+        write!(f, "\n\tcompensateClockSkew: {}", self.compensate_clock_skew)?;
+        write!(f, "\n\tmaxActiveTasks: {}", self.max_active_tasks)?;
+        write!(f, "\n\tmaxInflightCalls: {}", self.max_inflight_calls)?;
+        write!(f, "\n\tmaxTaskCalls: {}", self.max_task_calls)?;
+        write!(f, "\n\tbucketCapacity: {}", self.bucket_capacity)?;
+        write!(f, "\n\thomeSplitLevels: {}", self.home_split_levels)?;
+        write!(f, "\n\tmaxRoutingEntries: {}", self.max_routing_entries)?;
+        write!(f, "\n\tcommandQueueSize: {}", self.command_queue_size)?;
+        write!(f, "\n\treceiveSockets: {}", self.receive_sockets)?;
+        if let Some(url) = self.ws_gateway.as_ref() {
+            write!(f, "\n\twsGateway: {}", url)?;
+        }
+        if let Some(addr) = self.ws_gateway_listener {
+            write!(f, "\n\twsGatewayListener: {}", addr)?;
+        }
+        write!(f, "\n\tclientOnly: {}", self.client_only)?;
+        write!(f, "\n\trelay: {}", self.relay)?;
+        write!(f, "\n\tblockTokenStrikes: {}", self.block_token_strikes)?;
+        write!(f, "\n\tblockValueStrikes: {}", self.block_value_strikes)?;
+        write!(f, "\n\tblockDuration: {}", self.block_duration)?;
 
         if self.bootstrap_nodes.is_empty() {
             write!(f, "\n\tbootstraps: []")?;
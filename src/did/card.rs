@@ -153,7 +153,7 @@ impl Card {
             &self.to_sign_data(),
             &self.signature,
             &self.id.to_signature_key()
-        ).is_ok()
+        ).unwrap_or(false)
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -170,7 +170,7 @@ impl Credential {
             &self.to_sign_data(),
             &self.signature,
             &self.issuer().to_signature_key(),
-        ).is_ok()
+        ).unwrap_or(false)
     }
 
     pub fn validate(&self) -> Result<()> {
@@ -189,10 +189,16 @@ impl Credential {
     }
 
     pub(crate) fn to_sign_data(&self) -> Vec<u8> {
-        match self.signature.is_empty() {
+        let mut data = match self.signature.is_empty() {
             true    => Vec::from(self),
             false   => Vec::from(&Self::signed(self.clone(), None, None))
+        };
+        // The schema reference only exists in the W3C form, it is signed
+        // as a trailer so credentials without one keep their payload.
+        if let Some(schema) = self.vc.as_ref().and_then(|vc| vc.schema()) {
+            data.extend(serde_cbor::to_vec(schema).unwrap());
         }
+        data
     }
 
     pub fn vc(&self) -> Option<&VC> {
@@ -8,12 +8,14 @@ pub const BOSON_DID_CONTEXT     : &str = "https://bosonnetwork.io/ns/did/v1";
 pub const W3C_VC_CONTEXT        : &str = "https://www.w3.org/ns/credentials/v2";
 pub const BOSON_VC_CONTEXT      : &str = "https://bosonnetwork.io/ns/credentials/v1";
 pub const W3C_ED25519_CONTEXT   : &str = "https://w3id.org/security/suites/ed25519-2020/v1";
+pub const W3C_X25519_CONTEXT    : &str = "https://w3id.org/security/suites/x25519-2019/v1";
 
 pub const DEFAULT_VC_TYPE       : &str = "VerifiableCredential";
 pub const DEFAULT_VP_TYPE       : &str = "VerifiablePresentation";
 
 //#[allow(unused)]
 pub const DEFAULT_VERIFICATION_METHOD_FRAGMENT: &str = "default";
+pub const DEFAULT_KEY_AGREEMENT_FRAGMENT: &str = "key-agreement";
 
 //#[allow(unused)]
 pub const BOSON_ID_FORMAT_W3C   : &str = "boson.id.format.w3c-did";
@@ -11,9 +11,17 @@ pub mod w3c {
     mod vp_builder;
     mod diddoc;
     mod diddoc_builder;
+    mod schema;
 
     pub use self::{
         vc::VerifiableCredential,
+        schema::{
+            CredentialSchema,
+            ClaimSchema,
+            ClaimType,
+            SchemaViolation,
+            SchemaValidationError,
+        },
         vc_builder::VerifiableCredentialBuilder,
         vp::VerifiablePresentation,
         vp_builder::VerifiablePresentationBuilder,
@@ -27,6 +35,8 @@ pub mod credential;
 pub mod credential_builder;
 pub mod vouch;
 pub mod vouch_builder;
+pub mod vouch_chain;
+pub mod vouch_link_builder;
 pub mod card;
 pub mod card_builder;
 
@@ -39,7 +49,8 @@ pub use crate::did::{
     proof::Proof,
     verification_method::{
         VerificationMethod,
-        VerificationMethodType
+        VerificationMethodType,
+        Jwk,
     },
 
     card::Card,
@@ -48,6 +59,8 @@ pub use crate::did::{
     credential_builder::CredentialBuilder,
     vouch::Vouch,
     vouch_builder::VouchBuilder,
+    vouch_chain::VouchChain,
+    vouch_link_builder::VouchLinkBuilder,
 
     did_constants::{
         self as constants,
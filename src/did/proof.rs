@@ -126,7 +126,7 @@ impl Proof {
             data,
             &self.proof_value,
             &subject.to_signature_key()
-        ).is_ok()
+        ).unwrap_or(false)
     }
 }
 
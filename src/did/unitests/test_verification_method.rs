@@ -1,11 +1,22 @@
+use serde_json::json;
+
 use crate::{
     Id,
+    signature,
     did::{
         VerificationMethod,
         VerificationMethodType
     }
 };
 
+// The public key of RFC 8032 test vector 1, with its X25519 counterpart.
+const ED25519_KEY: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const X25519_KEY : &str = "d85e07ec22b0ad881537c2f44d662d1a143cf830c57aca4305d85c7a90f6b62e";
+
+fn fixed_id() -> Id {
+    Id::try_from(hex::decode(ED25519_KEY).unwrap().as_slice()).unwrap()
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -107,4 +118,110 @@ mod tests {
             assert_eq!(vmr, vmr2);
         }
     }
+
+    #[test]
+    fn test_ed25519_vectors() {
+        let did = fixed_id();
+        assert_eq!(did.to_base58(), "FVen3X669xLzsi6N2V91DoiyzHzg1uAgqiT8jZ9nS96Z");
+
+        let vm = VerificationMethod::ed25519(&did, "key-1", &did.to_signature_key());
+        assert_eq!(vm.id(), format!("{}#key-1", did.to_did_string()));
+        assert_eq!(vm.method_type(), Some(VerificationMethodType::Ed25519VerificationKey2020));
+        assert_eq!(vm.controller(), Some(&did));
+        assert_eq!(vm.public_key(), Some(did.as_bytes()));
+        assert_eq!(vm.public_key_multibase(), Some("z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw"));
+        assert_eq!(vm.public_key_jwk(), None);
+
+        let jwk = vm.to_jwk_form().unwrap();
+        assert_eq!(jwk.public_key_multibase(), None);
+        assert_eq!(serde_json::to_value(&jwk).unwrap(), json!({
+            "Entity": {
+                "id": vm.id(),
+                "type": "Ed25519VerificationKey2020",
+                "controller": did.to_base58(),
+                "publicKeyJwk": {
+                    "kty": "OKP",
+                    "crv": "Ed25519",
+                    "x": "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo"
+                }
+            }
+        }));
+        assert_eq!(jwk, vm);
+        assert_eq!(jwk.to_multibase_form().unwrap().public_key_multibase(), vm.public_key_multibase());
+    }
+
+    #[test]
+    fn test_x25519_vectors() {
+        let did = fixed_id();
+        let vm = VerificationMethod::x25519_from(&did).unwrap();
+        assert_eq!(vm.id(), format!("{}#key-agreement", did.to_did_string()));
+        assert_eq!(vm.method_type(), Some(VerificationMethodType::X25519KeyAgreementKey2019));
+        assert_eq!(vm.controller(), Some(&did));
+        assert_eq!(vm.public_key(), Some(hex::decode(X25519_KEY).unwrap().as_slice()));
+        assert_eq!(vm.public_key(), Some(did.to_encryption_key().as_bytes()));
+        assert_eq!(vm.public_key_multibase(), Some("z6LSrEnPXPcLyNLKJPhdJ1eWqyYKARWket5BbiN1rjdUsQ9b"));
+
+        let jwk = vm.to_jwk_form().unwrap();
+        let jwk = jwk.public_key_jwk().unwrap();
+        assert_eq!(jwk.kty(), "OKP");
+        assert_eq!(jwk.crv(), "X25519");
+        assert_eq!(jwk.x(), "2F4H7CKwrYgVN8L0TWYtGhQ8-DDFespDBdhcepD2ti4");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let did = fixed_id();
+        let methods = [
+            VerificationMethod::ed25519(&did, "key-1", &did.to_signature_key()),
+            VerificationMethod::x25519_from(&did).unwrap(),
+            VerificationMethod::default_entity(&did),
+        ];
+        for vm in methods {
+            for form in [vm.clone(), vm.to_jwk_form().unwrap(), vm.to_multibase_form().unwrap()] {
+                let json = serde_json::to_string(&form).unwrap();
+                let parsed = serde_json::from_str::<VerificationMethod>(&json).unwrap();
+                assert_eq!(parsed, vm);
+                assert_eq!(parsed.public_key(), vm.public_key());
+                assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+
+                let cbor = serde_cbor::to_vec(&form).unwrap();
+                let parsed = serde_cbor::from_slice::<VerificationMethod>(&cbor).unwrap();
+                assert_eq!(serde_cbor::to_vec(&parsed).unwrap(), cbor);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_keys() {
+        let did = fixed_id();
+        let entity = |key: serde_json::Value| {
+            let mut value = json!({
+                "id": format!("{}#key-1", did.to_did_string()),
+                "type": "X25519KeyAgreementKey2019",
+                "controller": did.to_base58(),
+            });
+            value.as_object_mut().unwrap().extend(key.as_object().unwrap().clone());
+            serde_json::from_value::<VerificationMethod>(json!({"Entity": value}))
+        };
+        let jwk = |crv: &str, x: &str| json!({"kty": "OKP", "crv": crv, "x": x});
+
+        let x = "2F4H7CKwrYgVN8L0TWYtGhQ8-DDFespDBdhcepD2ti4";
+        let multibase = "z6LSrEnPXPcLyNLKJPhdJ1eWqyYKARWket5BbiN1rjdUsQ9b";
+        assert!(entity(json!({"publicKeyJwk": jwk("X25519", x)})).is_ok());
+        assert!(entity(json!({"publicKeyJwk": jwk("X25519", x), "publicKeyMultibase": multibase})).is_ok());
+
+        // Curve not matching the method type
+        assert!(entity(json!({"publicKeyJwk": jwk("Ed25519", x)})).is_err());
+        // Truncated key
+        assert!(entity(json!({"publicKeyJwk": jwk("X25519", &x[..20])})).is_err());
+        // Ed25519 multicodec for an X25519 method
+        assert!(entity(json!({"publicKeyMultibase": "z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw"})).is_err());
+        // Both representations, of different keys
+        let other = signature::KeyPair::random().to_public_key();
+        let other = VerificationMethod::x25519_from(&Id::from(&other)).unwrap();
+        assert!(entity(json!({
+            "publicKeyJwk": jwk("X25519", x),
+            "publicKeyMultibase": other.public_key_multibase().unwrap(),
+        })).is_err());
+    }
 }
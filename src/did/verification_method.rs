@@ -2,10 +2,13 @@
 use std::fmt;
 use std::hash::{Hash, Hasher};
 use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose, Engine as _};
 
 use crate::{
     Id,
     Result,
+    signature,
+    cryptobox,
     errors::ArgumentError,
 };
 
@@ -18,16 +21,98 @@ use crate::did::{
 #[derive(Serialize, Deserialize)]
 pub enum VerificationMethodType {
     Ed25519VerificationKey2020,
+    X25519KeyAgreementKey2019,
+}
+
+impl VerificationMethodType {
+    // Multicodec prefix (unsigned varint) of the key in publicKeyMultibase.
+    fn multicodec(&self) -> &'static [u8] {
+        match self {
+            VerificationMethodType::Ed25519VerificationKey2020 => &[0xed, 0x01],
+            VerificationMethodType::X25519KeyAgreementKey2019 => &[0xec, 0x01],
+        }
+    }
+
+    fn curve(&self) -> &'static str {
+        match self {
+            VerificationMethodType::Ed25519VerificationKey2020 => "Ed25519",
+            VerificationMethodType::X25519KeyAgreementKey2019 => "X25519",
+        }
+    }
 }
 
 impl fmt::Display for VerificationMethodType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             VerificationMethodType::Ed25519VerificationKey2020 => write!(f, "Ed25519VerificationKey2020"),
+            VerificationMethodType::X25519KeyAgreementKey2019 => write!(f, "X25519KeyAgreementKey2019"),
+        }
+    }
+}
+
+// Public key as an OKP JSON Web Key (RFC 8037).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub struct Jwk {
+    #[serde(rename = "kty")]
+    kty : String,
+
+    #[serde(rename = "crv")]
+    crv : String,
+
+    #[serde(rename = "x")]
+    x   : String,
+}
+
+impl Jwk {
+    fn new(method_type: VerificationMethodType, public_key: &[u8]) -> Self {
+        Self {
+            kty : "OKP".into(),
+            crv : method_type.curve().into(),
+            x   : general_purpose::URL_SAFE_NO_PAD.encode(public_key),
+        }
+    }
+
+    pub fn kty(&self) -> &str {
+        &self.kty
+    }
+
+    pub fn crv(&self) -> &str {
+        &self.crv
+    }
+
+    pub fn x(&self) -> &str {
+        &self.x
+    }
+
+    fn decode(&self, method_type: VerificationMethodType) -> Result<Vec<u8>> {
+        if self.kty != "OKP" || self.crv != method_type.curve() {
+            return Err(ArgumentError::new(format!(
+                "Unsupported publicKeyJwk {}/{} for {}", self.kty, self.crv, method_type
+            )));
         }
+        general_purpose::URL_SAFE_NO_PAD.decode(&self.x).ok()
+            .filter(|v| v.len() == signature::PublicKey::BYTES)
+            .ok_or_else(|| ArgumentError::new("Invalid publicKeyJwk key").into())
     }
 }
 
+fn encode_multibase(method_type: VerificationMethodType, public_key: &[u8]) -> String {
+    let mut bytes = method_type.multicodec().to_vec();
+    bytes.extend_from_slice(public_key);
+    format!("z{}", bs58::encode(bytes).into_string())
+}
+
+fn decode_multibase(method_type: Option<VerificationMethodType>, value: &str) -> Option<Vec<u8>> {
+    let prefixed = method_type.and_then(|t| {
+        let bytes = bs58::decode(value.strip_prefix('z')?).into_vec().ok()?;
+        bytes.strip_prefix(t.multicodec()).map(|v| v.to_vec())
+    });
+    // Falls back to the bare base58 key written by earlier boson versions.
+    prefixed.or_else(|| bs58::decode(value).into_vec().ok())
+        .filter(|v| v.len() == signature::PublicKey::BYTES)
+}
+
 #[derive(Debug, Clone, Eq)]
 #[derive(Serialize, Deserialize)]
 pub enum VerificationMethod {
@@ -41,11 +126,49 @@ impl VerificationMethod {
         controller  : &Id,
         public_key_multibase: String
     ) -> Self {
+        let public_key = decode_multibase(Some(method_type), &public_key_multibase);
         Self::Entity(Entity {
             id          : id.into(),
             method_type : Some(method_type),
             controller  : Some(controller.clone()),
             public_key_multibase: Some(public_key_multibase),
+            public_key_jwk: None,
+            public_key,
+        })
+    }
+
+    pub fn ed25519(did: &Id, fragment: &str, public_key: &signature::PublicKey) -> Self {
+        Self::with_key(
+            &DIDUrl::new(did, None, None, Some(fragment)).to_string(),
+            VerificationMethodType::Ed25519VerificationKey2020,
+            did,
+            public_key.as_bytes(),
+        )
+    }
+
+    // The key agreement method with the cryptobox key derived from the id.
+    pub fn x25519_from(id: &Id) -> Result<Self> {
+        let public_key = cryptobox::PublicKey::try_from(&id.to_signature_key())?;
+        Ok(Self::with_key(
+            &DIDUrl::new(id, None, None, Some(did_constants::DEFAULT_KEY_AGREEMENT_FRAGMENT)).to_string(),
+            VerificationMethodType::X25519KeyAgreementKey2019,
+            id,
+            public_key.as_bytes(),
+        ))
+    }
+
+    fn with_key(id: &str,
+        method_type : VerificationMethodType,
+        controller  : &Id,
+        public_key  : &[u8]
+    ) -> Self {
+        Self::Entity(Entity {
+            id          : id.into(),
+            method_type : Some(method_type),
+            controller  : Some(*controller),
+            public_key_multibase: Some(encode_multibase(method_type, public_key)),
+            public_key_jwk: None,
+            public_key  : Some(public_key.to_vec()),
         })
     }
 
@@ -106,6 +229,38 @@ impl VerificationMethod {
         }
     }
 
+    pub fn public_key_jwk(&self) -> Option<&Jwk> {
+        self.as_entity().and_then(|v| v.public_key_jwk.as_ref())
+    }
+
+    // The raw public key, whichever representation it was given in.
+    pub fn public_key(&self) -> Option<&[u8]> {
+        self.as_entity().and_then(|v| v.public_key.as_deref())
+    }
+
+    // The same method with its key as publicKeyJwk only.
+    pub fn to_jwk_form(&self) -> Option<VerificationMethod> {
+        let mut entity = self.as_entity()?.clone();
+        entity.public_key_jwk = Some(Jwk::new(entity.method_type?, entity.public_key.as_deref()?));
+        entity.public_key_multibase = None;
+        Some(VerificationMethod::Entity(entity))
+    }
+
+    // The same method with its key as multicodec publicKeyMultibase only.
+    pub fn to_multibase_form(&self) -> Option<VerificationMethod> {
+        let mut entity = self.as_entity()?.clone();
+        entity.public_key_multibase = Some(encode_multibase(entity.method_type?, entity.public_key.as_deref()?));
+        entity.public_key_jwk = None;
+        Some(VerificationMethod::Entity(entity))
+    }
+
+    fn as_entity(&self) -> Option<&Entity> {
+        match self {
+            VerificationMethod::Entity(v) => Some(v),
+            VerificationMethod::Reference(v) => v.entity.as_ref(),
+        }
+    }
+
     pub fn is_reference(&self) -> bool {
         match self {
             VerificationMethod::Entity(v) => v.is_reference(),
@@ -154,18 +309,22 @@ impl fmt::Display for VerificationMethod {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "VerificationMethod{{id:{}\'", self.id())?;
         if !self.is_reference() {
-            write!(f, "type={},controller:{},publicKeyMultibase:{}}}",
+            write!(f, "type={},controller:{},",
                 self.method_type().unwrap(),
                 self.controller().unwrap().to_did_string(),
-                self.public_key_multibase().unwrap()
             )?;
+            match self.public_key_jwk() {
+                Some(jwk) => write!(f, "publicKeyJwk:{}}}", jwk.x())?,
+                None => write!(f, "publicKeyMultibase:{}}}", self.public_key_multibase().unwrap_or_default())?,
+            }
         }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Eq)]
 #[derive(Serialize, Deserialize)]
+#[serde(try_from = "EntityFields")]
 pub struct Entity {
     #[serde(rename = "id")]
     id: String,
@@ -178,7 +337,64 @@ pub struct Entity {
     controller: Option<Id>,
 
     #[serde(rename = "publicKeyMultibase", skip_serializing_if = "crate::is_default")]
-    public_key_multibase: Option<String>
+    public_key_multibase: Option<String>,
+
+    #[serde(rename = "publicKeyJwk", skip_serializing_if = "crate::is_default")]
+    public_key_jwk: Option<Jwk>,
+
+    // Normalized from either of the representations above.
+    #[serde(skip)]
+    public_key: Option<Vec<u8>>,
+}
+
+// The entity as it comes over the wire, before its key is normalized.
+#[derive(Deserialize)]
+struct EntityFields {
+    #[serde(rename = "id")]
+    id: String,
+
+    #[serde(rename = "type", default)]
+    method_type: Option<VerificationMethodType>,
+
+    #[serde(rename = "controller", default)]
+    #[serde(with="crate::serde_option_id_as_base58")]
+    controller: Option<Id>,
+
+    #[serde(rename = "publicKeyMultibase", default)]
+    public_key_multibase: Option<String>,
+
+    #[serde(rename = "publicKeyJwk", default)]
+    public_key_jwk: Option<Jwk>,
+}
+
+impl TryFrom<EntityFields> for Entity {
+    type Error = String;
+    fn try_from(v: EntityFields) -> std::result::Result<Self, Self::Error> {
+        let from_multibase = match v.public_key_multibase.as_deref() {
+            Some(value) => Some(decode_multibase(v.method_type, value)
+                .ok_or("Invalid publicKeyMultibase key")?),
+            None => None,
+        };
+        let from_jwk = match (v.public_key_jwk.as_ref(), v.method_type) {
+            (Some(jwk), Some(t)) => Some(jwk.decode(t).map_err(|e| e.to_string())?),
+            (Some(_), None) => return Err("publicKeyJwk requires the method type".into()),
+            (None, _) => None,
+        };
+        if let (Some(a), Some(b)) = (&from_multibase, &from_jwk) {
+            if a != b {
+                return Err("publicKeyMultibase and publicKeyJwk keys differ".into());
+            }
+        }
+
+        Ok(Self {
+            id          : v.id,
+            method_type : v.method_type,
+            controller  : v.controller,
+            public_key_multibase: v.public_key_multibase,
+            public_key_jwk: v.public_key_jwk,
+            public_key  : from_multibase.or(from_jwk),
+        })
+    }
 }
 
 impl Entity {
@@ -210,9 +426,22 @@ impl Hash for Entity {
         if let Some(controller) = &self.controller {
             controller.hash(state);
         }
-        if let Some(public_key) = &self.public_key_multibase {
-            public_key.hash(state);
-        }
+        self.public_key.hash(state);
+    }
+}
+
+// Entities holding the same key are equal whatever its representation.
+impl PartialEq for Entity {
+    fn eq(&self, other: &Self) -> bool {
+        let same_key = match (&self.public_key, &other.public_key) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.public_key_multibase == other.public_key_multibase &&
+                self.public_key_jwk == other.public_key_jwk,
+        };
+        self.id == other.id &&
+            self.method_type == other.method_type &&
+            self.controller == other.controller &&
+            same_key
     }
 }
 
@@ -125,7 +125,7 @@ impl Vouch {
             &self.to_sign_data(),
             &self.signature[..],
             &self.holder.to_signature_key()
-        ).is_ok()
+        ).unwrap_or(false)
     }
 
     pub fn validate(&self) -> Result<()> {
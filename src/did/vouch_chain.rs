@@ -0,0 +1,234 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::SystemTime;
+use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    as_secs,
+    Id,
+    CryptoIdentity,
+    errors::{
+        Error,
+        Result,
+        ArgumentError,
+        BeforeValidPeriodError,
+        ExpiredError,
+        SignatureError,
+    },
+};
+
+use crate::did::{
+    Credential,
+    Vouch,
+    VouchLinkBuilder,
+};
+
+pub const DELEGATION_TYPE       : &str = "BosonDelegation";
+pub(crate) const DELEGATION_ID  : &str = "delegation";
+pub(crate) const SCOPES_CLAIM   : &str = "scopes";
+
+/// Delegations from a root identity to a subject, each link a vouch by
+/// the subject of the link before, carrying a delegation credential for
+/// the next identity down the chain.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct VouchChain {
+    links: Vec<Vouch>,
+}
+
+impl VouchChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn links(&self) -> &[Vouch] {
+        &self.links
+    }
+
+    pub fn len(&self) -> usize {
+        self.links.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// The identity the chain starts from.
+    pub fn root(&self) -> Option<&Id> {
+        self.links.first().map(|link| link.holder())
+    }
+
+    /// The identity the chain delegates to in the end.
+    pub fn subject(&self) -> Option<&Id> {
+        self.links.last()
+            .and_then(Self::delegation)
+            .map(|cred| cred.subject().id())
+    }
+
+    /// A builder for a link from the identity to the subject.
+    pub fn link_builder(issuer: CryptoIdentity, subject: &Id) -> VouchLinkBuilder {
+        VouchLinkBuilder::new(issuer, subject)
+    }
+
+    /// Append a link, which must be issued by the subject of the chain.
+    /// The chain as a whole is checked by [`VouchChain::verify`].
+    pub fn push(&mut self, link: Vouch) -> Result<&mut Self> {
+        let Some(cred) = Self::delegation(&link) else {
+            return Err(ArgumentError::new("Vouch is not a delegation"));
+        };
+        if let Some(subject) = self.subject() {
+            if cred.issuer() != subject {
+                return Err(ArgumentError::new(format!(
+                    "Link issued by {} does not continue the chain to {}", cred.issuer(), subject
+                )));
+            }
+        }
+        self.links.push(link);
+        Ok(self)
+    }
+
+    /// Sign a link with the identity of the builder and append it.
+    pub fn append(&mut self, builder: &VouchLinkBuilder) -> Result<&mut Self> {
+        self.push(builder.build()?)
+    }
+
+    /// The scopes the chain delegates to its subject, None for all those
+    /// of the root.
+    pub fn scopes(&self) -> Option<Vec<String>> {
+        self.links.iter()
+            .filter_map(|link| Self::delegation(link).and_then(|c| Self::link_scopes(c).ok()))
+            .fold(None, |held, scopes| scopes.or(held))
+    }
+
+    /// Check the subject is vouched for by the root through at most
+    /// max_depth links, each signed by the subject of the one before,
+    /// valid at the given time and delegating no scope its issuer does
+    /// not hold. Issuers may appear only once in the chain.
+    pub fn verify(&self, root: &Id, subject: &Id, max_depth: usize, at: SystemTime) -> Result<()> {
+        if self.links.is_empty() {
+            return Err(ArgumentError::new("Vouch chain is empty"));
+        }
+        if self.links.len() > max_depth {
+            return Err(ArgumentError::new(format!(
+                "Vouch chain has {} links, more than the maximum depth {}", self.links.len(), max_depth
+            )));
+        }
+
+        let at = as_secs!(at);
+        let mut issuers = HashSet::new();
+        let mut expected = *root;
+        let mut held: Option<Vec<String>> = None;
+        for (i, link) in self.links.iter().enumerate() {
+            let Some(cred) = Self::delegation(link) else {
+                return Err(ArgumentError::new(format!("Link {i} is not a delegation")));
+            };
+            if cred.issuer() != link.holder() {
+                return Err(ArgumentError::new(format!(
+                    "Link {i} is vouched by {}, not its issuer {}", link.holder(), cred.issuer()
+                )));
+            }
+            if cred.issuer() != &expected {
+                return Err(ArgumentError::new(format!(
+                    "Link {i} is issued by {}, expected {}", cred.issuer(), expected
+                )));
+            }
+            if !issuers.insert(*cred.issuer()) {
+                return Err(ArgumentError::new(format!(
+                    "Link {i} repeats the issuer {}", cred.issuer()
+                )));
+            }
+
+            if !link.is_genuine() || !cred.is_genuine() {
+                return Err(SignatureError::new(format!("Link {i} signature is not valid")));
+            }
+            if cred.valid_from().is_some_and(|v| as_secs!(v) > at) {
+                return Err(BeforeValidPeriodError::new(format!("Link {i} is not yet valid")));
+            }
+            if cred.valid_until().is_some_and(|v| as_secs!(v) < at) {
+                return Err(ExpiredError::new(format!("Link {i} has expired")));
+            }
+
+            if let Some(scopes) = Self::link_scopes(cred)? {
+                if let Some(held) = held.as_ref() {
+                    if let Some(scope) = scopes.iter().find(|s| !held.contains(s)) {
+                        return Err(ArgumentError::new(format!(
+                            "Link {i} delegates the scope {scope} its issuer does not hold"
+                        )));
+                    }
+                }
+                held = Some(scopes);
+            }
+            expected = *cred.subject().id();
+        }
+
+        if issuers.contains(&expected) {
+            return Err(ArgumentError::new(format!("Vouch chain loops back to {}", expected)));
+        }
+        if &expected != subject {
+            return Err(ArgumentError::new(format!(
+                "Vouch chain ends at {}, not {}", expected, subject
+            )));
+        }
+        Ok(())
+    }
+
+    fn delegation(link: &Vouch) -> Option<&Credential> {
+        match link.credentials_by_type(DELEGATION_TYPE)[..] {
+            [cred] => Some(cred),
+            _ => None,
+        }
+    }
+
+    fn link_scopes(cred: &Credential) -> Result<Option<Vec<String>>> {
+        match cred.subject().claims_map().get(SCOPES_CLAIM) {
+            None | Some(Value::Null) => Ok(None),
+            Some(value) => serde_json::from_value(value.clone()).map(Some).map_err(|_| {
+                ArgumentError::new("Invalid scopes in delegation") as Error
+            }),
+        }
+    }
+}
+
+impl TryFrom<&str> for VouchChain {
+    type Error = Error;
+
+    fn try_from(input: &str) -> Result<Self> {
+        serde_json::from_str(input).map_err(|e| {
+            ArgumentError::new(format!("Failed to parse VouchChain from string: {}", e)).into()
+        })
+    }
+}
+
+impl FromStr for VouchChain {
+    type Err = Error;
+
+    fn from_str(data: &str) -> Result<Self> {
+        Self::try_from(data)
+    }
+}
+
+impl TryFrom<&[u8]> for VouchChain {
+    type Error = Error;
+
+    fn try_from(data: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice(data).map_err(|e| {
+            ArgumentError::new(format!("Failed to parse VouchChain from bytes: {}", e)).into()
+        })
+    }
+}
+
+impl From<&VouchChain> for Vec<u8> {
+    fn from(chain: &VouchChain) -> Self {
+        serde_cbor::to_vec(chain).unwrap()
+    }
+}
+
+impl fmt::Display for VouchChain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        serde_json::to_string(self)
+            .map_err(|_| std::fmt::Error)?
+            .fmt(f)
+    }
+}
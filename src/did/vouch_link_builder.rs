@@ -0,0 +1,102 @@
+use std::time::SystemTime;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    Id,
+    Result,
+    CryptoIdentity,
+};
+use crate::did::{
+    BosonIdentityObjectBuilder,
+    Credential,
+    Vouch,
+    vouch_chain::{DELEGATION_TYPE, DELEGATION_ID, SCOPES_CLAIM},
+};
+
+pub struct VouchLinkBuilder {
+    identity    : CryptoIdentity,
+    subject     : Id,
+    scopes      : Option<Vec<String>>,
+    valid_from  : Option<SystemTime>,
+    valid_until : Option<SystemTime>,
+}
+
+impl VouchLinkBuilder {
+    pub(crate) fn new(issuer: CryptoIdentity, subject: &Id) -> Self {
+        Self {
+            identity    : issuer,
+            subject     : *subject,
+            scopes      : None,
+            valid_from  : None,
+            valid_until : None,
+        }
+    }
+
+    /// Restrict the delegation to the scope, a link without scopes carries
+    /// all those its issuer holds.
+    pub fn with_scope(&mut self, scope: &str) -> &mut Self {
+        if scope.is_empty() {
+            return self;
+        }
+
+        let scope = scope.nfc().collect::<String>();
+        let scopes = self.scopes.get_or_insert_with(Vec::new);
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+        self
+    }
+
+    pub fn with_scopes(&mut self, scopes: Vec<&str>) -> &mut Self {
+        for scope in scopes {
+            self.with_scope(scope);
+        }
+        self
+    }
+
+    pub fn with_valid_from(&mut self, valid_from: SystemTime) -> &mut Self {
+        self.valid_from = Some(Self::trim_millis(valid_from));
+        self
+    }
+
+    pub fn with_valid_until(&mut self, valid_until: SystemTime) -> &mut Self {
+        self.valid_until = Some(Self::trim_millis(valid_until));
+        self
+    }
+
+    pub fn build(&self) -> Result<Vouch> {
+        BosonIdentityObjectBuilder::build(self)
+    }
+}
+
+impl BosonIdentityObjectBuilder for VouchLinkBuilder {
+    type BosonIdentityObject = Vouch;
+
+    fn identity(&self) -> &CryptoIdentity {
+        &self.identity
+    }
+
+    fn build(&self) -> Result<Self::BosonIdentityObject> {
+        let mut scopes = self.scopes.clone();
+        if let Some(scopes) = scopes.as_mut() {
+            scopes.sort();
+        }
+
+        let mut cred = Credential::builder(self.identity.clone());
+        cred.with_id(DELEGATION_ID)
+            .with_type(DELEGATION_TYPE)
+            .with_subject(self.subject)
+            .with_claim(SCOPES_CLAIM, scopes);
+        if let Some(valid_from) = self.valid_from {
+            cred.with_valid_from(valid_from);
+        }
+        if let Some(valid_until) = self.valid_until {
+            cred.with_valid_until(valid_until);
+        }
+
+        Vouch::builder(self.identity.clone())
+            .with_type(DELEGATION_TYPE)
+            .with_credential(cred.build()?)
+            .build()
+    }
+}
@@ -46,6 +46,10 @@ pub struct DIDDocument {
 	#[serde(skip_serializing_if = "crate::is_default")]
     assertions: Option<Vec<VM>>,
 
+    #[serde(rename = "keyAgreement")]
+	#[serde(skip_serializing_if = "crate::is_default")]
+    key_agreements: Option<Vec<VM>>,
+
     #[serde(rename = "verifiableCredential")]
 	#[serde(skip_serializing_if = "crate::is_default")]
     credentials: Option<Vec<VC>>,
@@ -60,12 +64,14 @@ pub struct DIDDocument {
 }
 
 impl DIDDocument {
+	#[allow(clippy::too_many_arguments)]
 	pub(crate) fn unsigned(
 		contexts	: Vec<String>,
 		id			: Id,
 		vms			: Vec<VM>,
 		auths		: Vec<VM>,
 		assertions	: Vec<VM>,
+		key_agreements: Vec<VM>,
 		credentials	: Vec<VC>,
 		services	: Vec<Service>
 	) -> Self {
@@ -85,6 +91,10 @@ impl DIDDocument {
 			true => Some(assertions),
 			false => None,
 		};
+		let key_agreements = match !key_agreements.is_empty() {
+			true => Some(key_agreements),
+			false => None,
+		};
 		let credentials = match !credentials.is_empty() {
 			true => Some(credentials),
 			false => None,
@@ -100,6 +110,7 @@ impl DIDDocument {
 			verification_methods,
 			authentications,
 			assertions,
+			key_agreements,
 			credentials,
 			services,
 			proof: None,
@@ -148,6 +159,7 @@ impl DIDDocument {
 			vec![default_method],
 			vec![default_method_ref.clone()],
 			vec![default_method_ref.clone()],
+			Vec::new(),
 			card.credentials().iter()
 				.map(|c| VC::from_cred_with_type_contexts(c, Some(vctype_contexts.clone())))
 				.collect(),
@@ -262,6 +274,28 @@ impl DIDDocument {
 		).flatten()
 	}
 
+	pub fn key_agreements(&self) -> Vec<&VM> {
+		self.key_agreements.as_ref().map_or(
+			Vec::new(),
+			|v| v.iter().collect()
+		)
+	}
+
+	pub fn key_agreement(&self, id: &str) -> Option<&VM> {
+		let didurl = match id.starts_with(constants::DID_SUFFIXED_SCHEME) {
+			true => DIDUrl::parse(id).unwrap(),
+			false => DIDUrl::new(&self.id, None, None, Some(id))
+		};
+		self.key_agreement_by_didurl(&didurl)
+	}
+
+	pub fn key_agreement_by_didurl(&self, id: &DIDUrl) -> Option<&VM> {
+		let id_str = id.to_string();
+		self.key_agreements.as_ref().and_then(|v|
+			v.iter().find(|v| v.id() == id_str)
+		)
+	}
+
 	pub fn credentials(&self) -> Vec<&VC> {
 		self.credentials.as_ref().map_or(
 			Vec::new(),
@@ -392,6 +426,7 @@ impl PartialEq<Self> for DIDDocument {
 		self.verification_methods == other.verification_methods &&
 		self.authentications == other.authentications &&
 		self.assertions == other.assertions &&
+		self.key_agreements == other.key_agreements &&
 		self.credentials == other.credentials &&
 		self.services == other.services &&
 		self.proof == other.proof
@@ -14,6 +14,7 @@ use crate::did::{
     did_constants as constants,
     BosonIdentityObjectBuilder,
     VerificationMethod as VM,
+    VerificationMethodType,
     proof::{Proof, ProofType, ProofPurpose},
     DIDUrl,
     w3c::{
@@ -29,6 +30,7 @@ pub struct DIDDocumentBuilder {
     verification_methods: HashMap<String, VM>,
     authentications     : Vec<VM>,
     assertions          : Vec<VM>,
+    key_agreements      : Vec<VM>,
     credentials         : Vec<VC>,
     services            : HashMap<String, Service>,
 
@@ -49,6 +51,7 @@ impl DIDDocumentBuilder {
             verification_methods: HashMap::new(),
             authentications     : Vec::new(),
             assertions          : Vec::new(),
+            key_agreements      : Vec::new(),
             credentials         : Vec::new(),
             services            : HashMap::new(),
             def_method_ref      : None,
@@ -126,6 +129,28 @@ impl DIDDocumentBuilder {
         self
     }
 
+    // Declares an X25519 key agreement method of the subject, such as the
+    // one from VerificationMethod::x25519_from().
+    pub fn with_key_agreement(&mut self, vm: VM) -> Result<&mut Self> {
+        if vm.is_reference() {
+            Err(ArgumentError::new("Key agreement method cannot be a reference"))?;
+        }
+        if vm.method_type() != Some(VerificationMethodType::X25519KeyAgreementKey2019) {
+            Err(ArgumentError::new("Key agreement method must be X25519KeyAgreementKey2019"))?;
+        }
+        if vm.controller() != Some(self.identity.id()) {
+            Err(ArgumentError::new("Key agreement method controller does not match identity"))?;
+        }
+        if self.verification_methods.contains_key(vm.id()) {
+            Err(ArgumentError::new("Verification method already exists"))?;
+        }
+
+        self.with_context(constants::W3C_X25519_CONTEXT)?;
+        self.key_agreements.push(vm.to_reference());
+        self.with_verification_method(vm);
+        Ok(self)
+    }
+
     pub fn with_credential(&mut self, vc: VC) -> Result<&mut Self> {
         if vc.subject().id() != self.identity.id() {
             Err(ArgumentError::new("VC subject does not match identity"))?;
@@ -236,6 +261,7 @@ impl BosonIdentityObjectBuilder for DIDDocumentBuilder {
             self.verification_methods.values().cloned().collect(),
             self.authentications.clone(),
             self.assertions.clone(),
+            self.key_agreements.clone(),
             self.credentials.clone(),
             self.services.values().cloned().collect(),
         );
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::error::Error;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::errors::{Result, ArgumentError};
+
+// Reference to the schema a credential claims to conform to,
+// the `credentialSchema` property of the W3C VC data model.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CredentialSchema {
+    #[serde(rename = "id")]
+    id: String,
+
+    #[serde(rename = "type")]
+    schema_type: String,
+}
+
+impl CredentialSchema {
+    pub fn new(id: &str, schema_type: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            schema_type: schema_type.to_string(),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn schema_type(&self) -> &str {
+        &self.schema_type
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Object,
+    Array,
+    Any,
+}
+
+impl ClaimType {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            Self::String    => value.is_string(),
+            Self::Number    => value.is_number(),
+            Self::Integer   => value.is_i64() || value.is_u64(),
+            Self::Boolean   => value.is_boolean(),
+            Self::Object    => value.is_object(),
+            Self::Array     => value.is_array(),
+            Self::Any       => true,
+        }
+    }
+
+    fn of(value: &Value) -> &'static str {
+        match value {
+            Value::Null         => "null",
+            Value::Bool(_)      => "boolean",
+            Value::Number(n)    => if n.is_f64() { "number" } else { "integer" },
+            Value::String(_)    => "string",
+            Value::Array(_)     => "array",
+            Value::Object(_)    => "object",
+        }
+    }
+}
+
+impl fmt::Display for ClaimType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::String    => "string",
+            Self::Number    => "number",
+            Self::Integer   => "integer",
+            Self::Boolean   => "boolean",
+            Self::Object    => "object",
+            Self::Array     => "array",
+            Self::Any       => "any",
+        };
+        f.write_str(name)
+    }
+}
+
+impl TryFrom<&str> for ClaimType {
+    type Error = crate::Error;
+
+    fn try_from(name: &str) -> Result<Self> {
+        match name {
+            "string"    => Ok(Self::String),
+            "number"    => Ok(Self::Number),
+            "integer"   => Ok(Self::Integer),
+            "boolean"   => Ok(Self::Boolean),
+            "object"    => Ok(Self::Object),
+            "array"     => Ok(Self::Array),
+            _ => Err(ArgumentError::new(format!("Unsupported claim type: {}", name))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ClaimRule {
+    name: String,
+    claim_type: ClaimType,
+    required: bool,
+}
+
+// Minimal claim schema: claim names, their primitive types and whether
+// they are required. Not a JSON Schema implementation, but the common
+// `{"required": [..], "properties": {"name": {"type": ".."}}}` subset
+// can be loaded with `TryFrom<&Value>`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClaimSchema {
+    rules: Vec<ClaimRule>,
+}
+
+impl ClaimSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn required(mut self, name: &str, claim_type: ClaimType) -> Self {
+        self.add(name, claim_type, true);
+        self
+    }
+
+    pub fn optional(mut self, name: &str, claim_type: ClaimType) -> Self {
+        self.add(name, claim_type, false);
+        self
+    }
+
+    fn add(&mut self, name: &str, claim_type: ClaimType, required: bool) {
+        self.rules.retain(|r| r.name != name);
+        self.rules.push(ClaimRule {
+            name: name.to_string(),
+            claim_type,
+            required,
+        });
+    }
+
+    pub(crate) fn validate(&self, claims: &Map<String, Value>) -> Result<()> {
+        for rule in &self.rules {
+            let Some(value) = claims.get(&rule.name) else {
+                if rule.required {
+                    return Err(SchemaValidationError::new(
+                        SchemaViolation::MissingClaim(rule.name.clone())
+                    ));
+                }
+                continue;
+            };
+
+            if !rule.claim_type.matches(value) {
+                return Err(SchemaValidationError::new(SchemaViolation::TypeMismatch {
+                    claim: rule.name.clone(),
+                    expected: rule.claim_type,
+                    found: ClaimType::of(value),
+                }));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&Value> for ClaimSchema {
+    type Error = crate::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        let mut schema = Self::new();
+        let required = value.get("required")
+            .and_then(|v| v.as_array())
+            .map(|v| v.iter().filter_map(|v| v.as_str()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        let Some(properties) = value.get("properties").and_then(|v| v.as_object()) else {
+            return Err(ArgumentError::new("Claim schema has no properties"));
+        };
+
+        for (name, property) in properties {
+            let claim_type = match property.get("type").and_then(|v| v.as_str()) {
+                Some(t) => ClaimType::try_from(t)?,
+                None => ClaimType::Any,
+            };
+            schema.add(name, claim_type, required.contains(&name.as_str()));
+        }
+
+        for name in required {
+            if !properties.contains_key(name) {
+                schema.add(name, ClaimType::Any, true);
+            }
+        }
+        Ok(schema)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaViolation {
+    MissingClaim(String),
+    TypeMismatch {
+        claim: String,
+        expected: ClaimType,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingClaim(claim) =>
+                write!(f, "missing required claim '{}'", claim),
+            Self::TypeMismatch { claim, expected, found } =>
+                write!(f, "claim '{}' should be {}, found {}", claim, expected, found),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SchemaValidationError {
+    violation: SchemaViolation,
+}
+
+impl SchemaValidationError {
+    pub fn new(violation: SchemaViolation) -> Box<Self> {
+        Box::new(Self { violation })
+    }
+
+    pub fn violation(&self) -> &SchemaViolation {
+        &self.violation
+    }
+}
+
+impl Error for SchemaValidationError {}
+
+impl fmt::Display for SchemaValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SchemaValidationError: {}", self.violation)
+    }
+}
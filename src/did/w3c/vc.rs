@@ -21,7 +21,11 @@ use crate::did::{
     Credential,
     VerificationMethod,
     DIDUrl,
-    w3c::VerifiableCredentialBuilder
+    w3c::{
+        VerifiableCredentialBuilder,
+        CredentialSchema,
+        ClaimSchema,
+    },
 };
 
 #[derive(Debug, Clone, Eq, Serialize, Deserialize)]
@@ -59,6 +63,10 @@ pub struct VerifiableCredential {
     #[serde(rename = "credentialSubject")]
     subject: CredentialSubject,
 
+    #[serde(rename = "credentialSchema")]
+    #[serde(skip_serializing_if = "crate::is_default", default)]
+    schema: Option<CredentialSchema>,
+
     #[serde(rename = "proof")]
     proof: Option<Proof>
 }
@@ -74,7 +82,8 @@ impl VerifiableCredential {
         valid_from  : Option<SystemTime>,
         valid_until : Option<SystemTime>,
         subject     : Option<Id>,
-        claims      : Map<String, Value>
+        claims      : Map<String, Value>,
+        schema      : Option<CredentialSchema>,
     ) -> Self {
         let contexts = match contexts.is_empty() {
             true => None,
@@ -96,6 +105,7 @@ impl VerifiableCredential {
             description,
             issuer,
             subject,
+            schema,
             valid_from  : valid_from.map(|v| as_secs!(v)),
             valid_until : valid_until.map(|v| as_secs!(v)),
             proof       : None
@@ -180,6 +190,7 @@ impl VerifiableCredential {
             valid_from  : credential.valid_from().map(|v| as_secs!(v)),
             valid_until : credential.valid_until().map(|v| as_secs!(v)),
             subject,
+            schema      : None,
             proof       : Some(proof)
         }
     }
@@ -228,6 +239,28 @@ impl VerifiableCredential {
         &self.subject
     }
 
+    pub fn schema(&self) -> Option<&CredentialSchema> {
+        self.schema.as_ref()
+    }
+
+    // Typed claim accessor, Ok(None) if the claim is absent and an error
+    // if it is present but can not be deserialized into T.
+    pub fn claim<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let Some(value) = self.subject.claims.get(name) else {
+            return Ok(None);
+        };
+        serde_json::from_value(value.clone()).map(Some).map_err(|e|
+            ArgumentError::new(format!("Invalid claim '{}': {}", name, e)).into()
+        )
+    }
+
+    pub fn validate_against(&self, schema: &ClaimSchema) -> Result<()> {
+        schema.validate(&self.subject.claims)
+    }
+
     pub fn proof(&self) -> &Proof {
         self.proof.as_ref().unwrap()
     }
@@ -388,6 +421,7 @@ impl Hash for VerifiableCredential {
         self.issuer.hash(state);
         self.valid_from.hash(state);
         self.valid_until.hash(state);
+        self.schema.hash(state);
         self.proof.hash(state);
     }
 }
@@ -403,6 +437,7 @@ impl PartialEq for VerifiableCredential {
         self.valid_from == other.valid_from &&
         self.valid_until == other.valid_until &&
         self.subject == other.subject &&
+        self.schema == other.schema &&
         self.proof == other.proof
     }
 }
@@ -18,7 +18,7 @@ use crate::did::{
     proof::{ProofType, ProofPurpose},
     VerificationMethod,
     BosonIdentityObjectBuilder,
-    w3c::VerifiableCredential,
+    w3c::{VerifiableCredential, CredentialSchema},
 };
 
 pub struct VerifiableCredentialBuilder {
@@ -32,6 +32,7 @@ pub struct VerifiableCredentialBuilder {
     valid_until : Option<SystemTime>,
     subject     : Option<Id>,
     claims      : Map<String, Value>,
+    schema      : Option<CredentialSchema>,
 }
 
 impl VerifiableCredentialBuilder {
@@ -61,6 +62,7 @@ impl VerifiableCredentialBuilder {
             valid_until : None,
             subject     : None,
             claims      : Map::new(),
+            schema      : None,
         }
     }
 
@@ -217,6 +219,21 @@ impl VerifiableCredentialBuilder {
         self
     }
 
+    pub fn with_schema(&mut self, id: &str, schema_type: &str) -> Result<&mut Self> {
+        if id.is_empty() {
+            Err(ArgumentError::new("Credential schema id cannot be empty"))?;
+        }
+        if schema_type.is_empty() {
+            Err(ArgumentError::new("Credential schema type cannot be empty"))?;
+        }
+
+        self.schema = Some(CredentialSchema::new(
+            &id.nfc().collect::<String>(),
+            &schema_type.nfc().collect::<String>()
+        ));
+        Ok(self)
+    }
+
     pub fn build(&self) -> Result<VerifiableCredential> {
         BosonIdentityObjectBuilder::build(self)
     }
@@ -266,6 +283,7 @@ impl BosonIdentityObjectBuilder for VerifiableCredentialBuilder {
             self.valid_until,
             self.subject.clone(),
             self.claims.clone(),
+            self.schema.clone(),
         );
 
         let signature = self.identity().sign_into(&unsigned.to_sign_data())?;
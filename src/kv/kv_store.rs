@@ -0,0 +1,398 @@
+use std::{
+    fs,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use log::{debug, info, warn};
+use tokio::{
+    task::{self, JoinHandle},
+    time::MissedTickBehavior,
+};
+
+use crate::{
+    Id,
+    Value,
+    SignedBuilder,
+    Error,
+    signature::KeyPair,
+    errors::{Result, IOError, StateError},
+    dht::{
+        Node,
+        LookupOption,
+        errors::{SeqNotExpected, SeqNotMonotonic},
+    },
+};
+
+const KV_DIR: &str = "kv";
+
+// Keeps the seeds of the keys apart from any other use of the namespace
+// key pair.
+const KV_DOMAIN: &[u8] = b"boson:kv:v1\0";
+
+// A put losing the race for the next sequence number to another device
+// sharing the namespace key pair is retried over the value found.
+const MAX_PUT_ATTEMPTS: usize = 3;
+
+const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The data of a key, with the sequence number of the value carrying it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(rename = "k")]
+    key     : String,
+    #[serde(rename = "d")]
+    data    : Vec<u8>,
+    #[serde(rename = "s")]
+    seq     : i32,
+}
+
+impl Entry {
+    pub(crate) fn new(key: &str, data: &[u8], seq: i32) -> Self {
+        Self {
+            key : key.to_string(),
+            data: data.to_vec(),
+            seq,
+        }
+    }
+
+    fn from_value(key: &str, value: &Value) -> Self {
+        Self::new(key, value.data(), value.sequence_number())
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn sequence_number(&self) -> i32 {
+        self.seq
+    }
+}
+
+/// Where [`KvStore::get`] reads a key from: the local cache only, or the
+/// network, refreshing the cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Freshness {
+    Cached,
+    Network,
+}
+
+/// A network copy of a key newer than the cached one and holding other
+/// data, put by another device sharing the namespace key pair.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    local   : Entry,
+    network : Entry,
+}
+
+impl Conflict {
+    pub fn key(&self) -> &str {
+        self.local.key()
+    }
+
+    pub fn local(&self) -> &Entry {
+        &self.local
+    }
+
+    pub fn network(&self) -> &Entry {
+        &self.network
+    }
+}
+
+/// How the application settles a [`Conflict`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// Takes the network copy, what happens without a conflict handler.
+    AcceptNetwork,
+    /// Puts the cached data again over the network copy.
+    KeepLocal,
+    /// Puts the given data over the network copy, merged from both.
+    Replace(Vec<u8>),
+}
+
+pub type ConflictHandler = Box<dyn Fn(&Conflict) -> Resolution + Send + Sync>;
+
+/// Stops the watch of a key when dropped.
+pub struct Watch {
+    task: JoinHandle<()>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+// The value of every key is signed with a key pair seeded from the
+// namespace key pair and the key, only those holding the namespace key
+// pair can put it.
+pub(crate) fn namespace_secret(keypair: &KeyPair) -> Id {
+    let mut sha256 = Sha256::new();
+    sha256.update(KV_DOMAIN);
+    sha256.update(keypair.private_key().as_bytes());
+    Id::try_from(sha256.finalize().as_slice()).unwrap()
+}
+
+pub(crate) fn keypair_for_key(secret: &Id, key: &str) -> KeyPair {
+    KeyPair::try_from_seed(Id::derive(secret, key).as_bytes()).unwrap()
+}
+
+pub(crate) fn value_id_for_key(secret: &Id, key: &str) -> Id {
+    let pk = Id::from(keypair_for_key(secret, key).public_key());
+    Id::try_from(Sha256::digest(pk.as_bytes()).as_slice()).unwrap()
+}
+
+// The last entry known of every key, saved to the data dir on each change.
+pub(crate) struct Cache {
+    path    : PathBuf,
+    entries : HashMap<String, Entry>,
+}
+
+impl Cache {
+    pub(crate) fn open(path: PathBuf) -> Self {
+        let mut entries = HashMap::new();
+        match Self::load(&path) {
+            Ok(loaded) => {
+                entries.extend(loaded.into_iter().map(|e| (e.key.clone(), e)));
+                if !entries.is_empty() {
+                    info!("Loaded {} cached keys from {}", entries.len(), path.display());
+                }
+            },
+            Err(e) => warn!("Loading key-value cache from {} error: {e}", path.display()),
+        }
+        Self { path, entries }
+    }
+
+    fn load(path: &Path) -> Result<Vec<Entry>> {
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+        let bytes = fs::read(path).map_err(|e| -> Error {
+            IOError::new(format!("Reading {} error: {e}", path.display()))
+        })?;
+        Ok(serde_cbor::from_slice(&bytes)?)
+    }
+
+    fn save(&self) {
+        let entries = self.entries.values().collect::<Vec<_>>();
+        let result = serde_cbor::to_vec(&entries).map_err(Error::from).and_then(|bytes| {
+            let tmp_path = self.path.with_extension("tmp");
+            self.path.parent().map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&tmp_path, bytes))
+                .and_then(|_| fs::rename(&tmp_path, &self.path))
+                .map_err(|e| -> Error {
+                    IOError::new(format!("Writing {} error: {e}", self.path.display()))
+                })
+        });
+        if let Err(e) = result {
+            warn!("Saving key-value cache error: {e}");
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<Entry> {
+        self.entries.get(key).cloned()
+    }
+
+    pub(crate) fn update(&mut self, entry: Entry) {
+        self.entries.insert(entry.key.clone(), entry);
+        self.save();
+    }
+}
+
+struct Inner {
+    node        : Arc<Node>,
+    secret      : Id,
+    cache       : Mutex<Cache>,
+    on_conflict : Mutex<Option<Arc<ConflictHandler>>>,
+}
+
+impl Inner {
+    fn cached(&self, key: &str) -> Option<Entry> {
+        self.cache.lock().unwrap().get(key)
+    }
+
+    fn update(&self, entry: Entry) {
+        self.cache.lock().unwrap().update(entry);
+    }
+
+    // The newest of the copies held by the node and found on the network.
+    async fn latest(&self, key: &str) -> Result<Option<Entry>> {
+        let value_id = value_id_for_key(&self.secret, key);
+        let local = self.node.value(value_id)?;
+        let found = self.node.find_value(&value_id, -1, Some(LookupOption::Conservative)).await?;
+        Ok([local, found].into_iter()
+            .flatten()
+            .max_by_key(|v| v.sequence_number())
+            .map(|v| Entry::from_value(key, &v)))
+    }
+
+    // Returns the entry after the refresh, and whether it changed.
+    async fn refresh(&self, key: &str) -> Result<(Option<Entry>, bool)> {
+        let cached = self.cached(key);
+        let Some(network) = self.latest(key).await? else {
+            return Ok((cached, false));
+        };
+        match cached {
+            Some(local) if network.seq <= local.seq => Ok((Some(local), false)),
+            Some(local) if network.data != local.data => {
+                let entry = self.resolve(Conflict { local, network }).await?;
+                Ok((Some(entry), true))
+            },
+            _ => {
+                self.update(network.clone());
+                Ok((Some(network), true))
+            },
+        }
+    }
+
+    async fn resolve(&self, conflict: Conflict) -> Result<Entry> {
+        let handler = self.on_conflict.lock().unwrap().clone();
+        let resolution = handler.map_or(Resolution::AcceptNetwork, |h| h(&conflict));
+        debug!("Conflict on '{}' at sequence number {} resolved with {resolution:?}",
+            conflict.key(), conflict.network.seq);
+
+        let Conflict { local, network } = conflict;
+        match resolution {
+            Resolution::AcceptNetwork => {
+                self.update(network.clone());
+                Ok(network)
+            },
+            Resolution::KeepLocal => self.put(&local.key, &local.data, Some(network)).await,
+            Resolution::Replace(data) => self.put(&local.key, &data, Some(network)).await,
+        }
+    }
+
+    // Puts the data over the base entry, the one over which the sequence
+    // number is bumped.
+    async fn put(&self, key: &str, data: &[u8], mut base: Option<Entry>) -> Result<Entry> {
+        let keypair = keypair_for_key(&self.secret, key);
+        for _ in 0..MAX_PUT_ATTEMPTS {
+            let expected_seq = base.as_ref().map_or(-1, |e| e.seq);
+            let value = SignedBuilder::new(data)
+                .with_keypair(&keypair)
+                .with_sequence_number(expected_seq + 1)
+                .build()?;
+
+            match self.node.store_value(&value, expected_seq, true).await {
+                Ok(()) => {
+                    let entry = Entry::new(key, data, expected_seq + 1);
+                    self.update(entry.clone());
+                    return Ok(entry);
+                },
+                Err(e) if !e.is::<SeqNotExpected>() && !e.is::<SeqNotMonotonic>() => return Err(e),
+                Err(_) => {},
+            }
+
+            debug!("Putting '{key}' at sequence number {} lost to another device, retrying",
+                expected_seq + 1);
+            base = self.latest(key).await?.or(base);
+        }
+        Err(StateError::new(format!(
+            "Putting '{key}' lost the sequence number to other devices {MAX_PUT_ATTEMPTS} times"
+        )))
+    }
+}
+
+/// A key-value store over the signed mutable values of the DHT, in the
+/// namespace of a key pair. Every key is a value of its own, its id derived
+/// from the namespace key pair and the key, so devices sharing the key pair
+/// share the store. The last entry known of every key is cached in the data
+/// dir of the node.
+pub struct KvStore {
+    inner           : Arc<Inner>,
+    namespace       : Id,
+    watch_interval  : Duration,
+}
+
+impl KvStore {
+    pub fn new(node: Arc<Node>, namespace_keypair: &KeyPair) -> Self {
+        let namespace = Id::from(namespace_keypair.public_key());
+        let path = node.storage_path().join(KV_DIR).join(namespace.to_string());
+        let inner = Inner {
+            node,
+            secret      : namespace_secret(namespace_keypair),
+            cache       : Mutex::new(Cache::open(path)),
+            on_conflict : Mutex::new(None),
+        };
+        Self {
+            inner: Arc::new(inner),
+            namespace,
+            watch_interval: DEFAULT_WATCH_INTERVAL,
+        }
+    }
+
+    /// The id of the namespace, the public key of its key pair.
+    pub fn namespace(&self) -> &Id {
+        &self.namespace
+    }
+
+    /// The id of the value carrying `key`.
+    pub fn value_id(&self, key: &str) -> Id {
+        value_id_for_key(&self.inner.secret, key)
+    }
+
+    /// Signs `data` for `key` with the sequence number after the cached
+    /// one, or after the one on the network for a key not put or read here
+    /// before, and stores it persistently. Retried over the newer value of
+    /// another device that won the sequence number.
+    pub async fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let base = match self.inner.cached(key) {
+            Some(entry) => Some(entry),
+            None => self.inner.latest(key).await?,
+        };
+        self.inner.put(key, data, base).await.map(|_| ())
+    }
+
+    /// Reads `key` from the cache, or from the network, settling a network
+    /// copy in conflict with the cached one through the conflict handler.
+    /// The cached entry is returned when the network has none newer.
+    pub async fn get(&self, key: &str, freshness: Freshness) -> Result<Option<Entry>> {
+        match freshness {
+            Freshness::Cached => Ok(self.inner.cached(key)),
+            Freshness::Network => self.inner.refresh(key).await.map(|(entry, _)| entry),
+        }
+    }
+
+    /// Calls `handler` with every newer entry of `key` found on the network,
+    /// those put through this store aside, until the returned [`Watch`] is
+    /// dropped. Must be called within a Tokio `LocalSet`, like the other
+    /// background tasks of the node.
+    pub fn watch(&self, key: &str, handler: impl Fn(&Entry) + 'static) -> Watch {
+        let inner = self.inner.clone();
+        let key = key.to_string();
+        let interval = self.watch_interval;
+        let task = task::spawn_local(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                match inner.refresh(&key).await {
+                    Ok((Some(entry), true)) => handler(&entry),
+                    Ok(_) => {},
+                    Err(e) => debug!("Watching '{key}' error: {e}"),
+                }
+            }
+        });
+        Watch { task }
+    }
+
+    /// How often watched keys are looked up, every minute by default. Applies
+    /// to the watches started afterwards.
+    pub fn set_watch_interval(&mut self, interval: Duration) {
+        self.watch_interval = interval;
+    }
+
+    /// Settles the network copies in conflict with the cached ones, which
+    /// are otherwise replaced by them.
+    pub fn on_conflict(&self, handler: impl Fn(&Conflict) -> Resolution + Send + Sync + 'static) {
+        let handler: ConflictHandler = Box::new(handler);
+        *self.inner.on_conflict.lock().unwrap() = Some(Arc::new(handler));
+    }
+}
@@ -0,0 +1,16 @@
+mod kv_store;
+
+#[cfg(test)]
+mod unitests {
+    mod test_kv_store;
+}
+
+pub use kv_store::{
+    KvStore,
+    Entry,
+    Freshness,
+    Conflict,
+    Resolution,
+    ConflictHandler,
+    Watch,
+};
@@ -0,0 +1,49 @@
+use crate::{
+    Id,
+    signature::KeyPair,
+    kv::kv_store::{self, Cache, Entry},
+};
+
+#[test]
+fn test_key_derivation() {
+    let keypair = KeyPair::random();
+    let secret = kv_store::namespace_secret(&keypair);
+    assert_eq!(secret, kv_store::namespace_secret(&keypair.clone()));
+    // Not derivable from the public namespace id.
+    assert_ne!(secret, Id::from(keypair.public_key()));
+
+    let id = kv_store::value_id_for_key(&secret, "profile");
+    assert_eq!(id, kv_store::value_id_for_key(&secret, "profile"));
+    assert_ne!(id, kv_store::value_id_for_key(&secret, "avatar"));
+
+    let other = kv_store::namespace_secret(&KeyPair::random());
+    assert_ne!(id, kv_store::value_id_for_key(&other, "profile"));
+
+    let value = crate::SignedBuilder::new(b"data")
+        .with_keypair(&kv_store::keypair_for_key(&secret, "profile"))
+        .build()
+        .unwrap();
+    assert_eq!(value.id(), id);
+}
+
+#[test]
+fn test_cache_persistence() {
+    let dir = std::env::temp_dir().join(format!("kv-cache-{:016x}", rand::random::<u64>()));
+    let path = dir.join("kv").join("namespace");
+
+    let mut cache = Cache::open(path.clone());
+    assert!(cache.get("profile").is_none());
+    cache.update(Entry::new("profile", b"alice", 0));
+    cache.update(Entry::new("avatar", b"png", 3));
+    cache.update(Entry::new("profile", b"alice v2", 1));
+
+    let cache = Cache::open(path.clone());
+    assert_eq!(cache.get("profile"), Some(Entry::new("profile", b"alice v2", 1)));
+    assert_eq!(cache.get("avatar").map(|e| e.sequence_number()), Some(3));
+
+    // A corrupted cache is started over.
+    std::fs::write(&path, b"not cbor").unwrap();
+    assert!(Cache::open(path).get("profile").is_none());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
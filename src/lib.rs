@@ -1,8 +1,20 @@
+// Crate internals shared with the DHT are unused in core-only builds.
+#![cfg_attr(not(feature = "dht"), allow(dead_code))]
+
 pub mod core;
 pub mod did;
+#[cfg(feature = "dht")]
 pub mod dht;
+#[cfg(feature = "activeproxy")]
 pub mod activeproxy;
+#[cfg(feature = "messaging")]
 pub mod messaging;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "crawler")]
+pub mod crawler;
+#[cfg(feature = "dht")]
+pub mod kv;
 
 pub use crate::core::{
     id::{
@@ -14,8 +26,10 @@ pub use crate::core::{
     errors::{self, Error, Result},
     signature::{self, Signature},
     cryptobox::{self, CryptoBox},
+    capabilities::{self, Capabilities},
     node_info::{self, NodeInfo},
     peer_info::{self, PeerInfo, PeerBuilder},
+    endpoint::{self, EndpointPolicy},
     value::{
         self,
         Value,
@@ -23,10 +37,13 @@ pub use crate::core::{
         SignedBuilder,
         EncryptedBuilder
     },
+    name_record::{self, NameRecord, NameClaim},
+    document::{self, Document},
     network::{self, Network},
     identity::{self, Identity, CryptoIdentity},
     crypto_context::{self, CryptoContext},
-    joint_result::{self, JointResult},
+    joint_result::{self, JointResult, ResultSource},
+    clock::{self, Clock, SystemClock, ManualClock},
 
     //node_config::{self, NodeConfig},
     //default_configuration as configuration,
@@ -34,6 +51,9 @@ pub use crate::core::{
 
 pub use crate::core::identity as crypto_identity;
 
+#[cfg(feature = "multiaddr")]
+pub use crate::core::multiaddr::{self, Multiaddr};
+
 pub use crate::did::{
     didurl,
     verification_method,
@@ -47,6 +67,7 @@ pub use crate::did::{
     card_builder,
 };
 
+#[cfg(feature = "dht")]
 pub use crate::dht::{
     node::{self, Node},
     connection_status::{self, ConnectionStatus},
@@ -101,8 +122,11 @@ macro_rules! unwrap_mut {
     }};
 }
 
+#[cfg(feature = "dht")]
 use std::net::IpAddr;
+#[cfg(feature = "dht")]
 use crate::errors::NetworkError;
+#[cfg(feature = "dht")]
 fn local_addr(ipv4: bool) -> Result<IpAddr>{
     let if_addrs = match get_if_addrs::get_if_addrs() {
         Ok(v) => v,
@@ -120,29 +144,40 @@ fn local_addr(ipv4: bool) -> Result<IpAddr>{
     Err(NetworkError::new("No working network interfaces"))
 }
 
+use crate::core::crypto::{Backend, Provider};
+
 fn random_array<const N: usize>() -> [u8; N] {
     let mut bytes = [0u8; N];
-    unsafe {
-        libsodium_sys::randombytes_buf(
-            bytes.as_mut_ptr() as *mut libc::c_void,
-            N
-        );
-    };
+    fill_random(&mut bytes);
     bytes
 }
 
 #[allow(unused)]
 fn random_bytes(len: usize) -> Vec<u8> {
     let mut bytes = vec![0u8; len];
-    unsafe {
-        libsodium_sys::randombytes_buf(
-            bytes.as_mut_ptr() as *mut libc::c_void,
-            len
-        );
-    };
+    fill_random(&mut bytes);
     bytes
 }
 
+// Drawn from the seed of the simulation running on this thread, if any.
+fn fill_random(bytes: &mut [u8]) {
+    #[cfg(feature = "testing")]
+    if crate::testing::sim_random(bytes) {
+        return;
+    }
+    Backend::random_bytes(bytes);
+}
+
+#[allow(unused)]
+fn random_u32() -> u32 {
+    Backend::random_u32()
+}
+
+#[allow(unused)]
+fn random_uniform(upper: u32) -> u32 {
+    Backend::random_uniform(upper)
+}
+
 #[allow(unused)]
 pub(crate) fn is_default<T: IsDefault>(v: &T) -> bool {
     v.is_default()
@@ -0,0 +1,354 @@
+use std::io::{Read, Write};
+use std::path::Path;
+use hkdf::Hkdf;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+
+use crate::{
+    Id,
+    signature::KeyPair,
+    cryptobox::{CryptoBox, Nonce},
+    core::crypto::{SealedHeader, HeaderError},
+};
+use crate::messaging::{
+    Error,
+    Result,
+    channel::Permission,
+    contact::ContactType,
+    persistence::database::{Database, ChannelRecord, ContactRecord},
+};
+
+// Repository config entry holding the registered device name.
+pub(crate) const DEVICE_NAME_KEY: &str = "deviceName";
+// Repository config entry holding the application the device registered with.
+pub(crate) const APP_NAME_KEY: &str = "appName";
+// Repository config entry holding the version id of the synced contact list.
+pub(crate) const CONTACTS_VERSION_KEY: &str = "contactsVersion";
+
+const MAGIC: &[u8; SealedHeader::MAGIC_BYTES] = b"BSNA";
+const FORMAT_VERSION: u8 = 1;
+
+const CHECK_BYTES: usize = 16;
+const MAC_BYTES: usize = 32;
+// The sealed header, then the password check value and the payload length.
+const HEADER_BYTES: usize = SealedHeader::BYTES + CHECK_BYTES + 4;
+
+const ENCRYPTION_KEY_CONTEXT: &[u8] = b"boson account backup encryption key";
+const MAC_KEY_CONTEXT: &[u8] = b"boson account backup mac key";
+const CHECK_CONTEXT: &[u8] = b"boson account backup password check";
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+struct ContactEntry {
+    #[serde(rename = "id")]
+    id: Id,
+    #[serde(rename = "t")]
+    contact_type: i32,
+    #[serde(rename = "h", skip_serializing_if = "Option::is_none", default)]
+    home_peer_id: Option<Id>,
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none", default)]
+    name: Option<String>,
+    #[serde(rename = "r", skip_serializing_if = "Option::is_none", default)]
+    remark: Option<String>,
+    #[serde_as(as = "Option<Bytes>")]
+    #[serde(rename = "k", skip_serializing_if = "Option::is_none", default)]
+    session_key: Option<Vec<u8>>,
+    #[serde(rename = "u")]
+    updated: u64,
+    #[serde(rename = "b", skip_serializing_if = "crate::is_default", default)]
+    blocked: bool,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+struct ChannelEntry {
+    #[serde(rename = "id")]
+    id: Id,
+    #[serde(rename = "o")]
+    owner: Id,
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none", default)]
+    name: Option<String>,
+    #[serde(rename = "p")]
+    permission: i32,
+    #[serde_as(as = "Option<Bytes>")]
+    #[serde(rename = "k", skip_serializing_if = "Option::is_none", default)]
+    session_key: Option<Vec<u8>>,
+    #[serde(rename = "e")]
+    key_epoch: u32,
+    #[serde(rename = "u")]
+    updated: u64,
+}
+
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    #[serde_as(as = "Bytes")]
+    #[serde(rename = "uk")]
+    user_key: Vec<u8>,
+    #[serde_as(as = "Bytes")]
+    #[serde(rename = "dk")]
+    device_key: Vec<u8>,
+    #[serde(rename = "dn", skip_serializing_if = "Option::is_none", default)]
+    device_name: Option<String>,
+    #[serde(rename = "an", skip_serializing_if = "Option::is_none", default)]
+    app_name: Option<String>,
+    #[serde(rename = "c")]
+    contacts: Vec<ContactEntry>,
+    #[serde(rename = "ch")]
+    channels: Vec<ChannelEntry>,
+    #[serde(rename = "cv", skip_serializing_if = "Option::is_none", default)]
+    contacts_version: Option<String>,
+}
+
+impl From<ContactRecord> for ContactEntry {
+    fn from(c: ContactRecord) -> Self {
+        Self {
+            id: c.id,
+            contact_type: c.contact_type.into(),
+            home_peer_id: c.home_peer_id,
+            name: c.name,
+            remark: c.remark,
+            session_key: c.session_key,
+            updated: c.updated,
+            blocked: c.blocked,
+        }
+    }
+}
+
+impl TryFrom<ContactEntry> for ContactRecord {
+    type Error = Error;
+
+    fn try_from(c: ContactEntry) -> Result<Self> {
+        Ok(Self {
+            id: c.id,
+            contact_type: ContactType::try_from(c.contact_type)
+                .map_err(|e| Error::Encoding(e.to_string()))?,
+            home_peer_id: c.home_peer_id,
+            name: c.name,
+            remark: c.remark,
+            session_key: c.session_key,
+            updated: c.updated,
+            blocked: c.blocked,
+        })
+    }
+}
+
+impl From<ChannelRecord> for ChannelEntry {
+    fn from(c: ChannelRecord) -> Self {
+        Self {
+            id: c.id,
+            owner: c.owner,
+            name: c.name,
+            permission: c.permission.into(),
+            session_key: c.session_key,
+            key_epoch: c.key_epoch,
+            updated: c.updated,
+        }
+    }
+}
+
+impl TryFrom<ChannelEntry> for ChannelRecord {
+    type Error = Error;
+
+    fn try_from(c: ChannelEntry) -> Result<Self> {
+        Ok(Self {
+            id: c.id,
+            owner: c.owner,
+            name: c.name,
+            permission: Permission::try_from(c.permission)
+                .map_err(|e| Error::Encoding(e.to_string()))?,
+            session_key: c.session_key,
+            key_epoch: c.key_epoch,
+            updated: c.updated,
+        })
+    }
+}
+
+// Keys derived from the backup password.
+struct BackupKeys {
+    cipher: CryptoBox,
+    mac:    [u8; 32],
+    check:  [u8; CHECK_BYTES],
+}
+
+impl BackupKeys {
+    fn derive(password: &str, header: &SealedHeader) -> Result<Self> {
+        let mut master = [0u8; 32];
+        if !header.derive(password, &mut master) {
+            return Err(Error::State("Deriving the account backup key failed".into()));
+        }
+
+        let hkdf = Hkdf::<Sha256>::new(Some(&header.salt), &master);
+        master.fill(0);
+
+        let mut key = [0u8; CryptoBox::SYMMETRIC_KEY_BYTES];
+        let mut mac = [0u8; 32];
+        let mut check = [0u8; CHECK_BYTES];
+        for (context, out) in [
+            (ENCRYPTION_KEY_CONTEXT, &mut key[..]),
+            (MAC_KEY_CONTEXT, &mut mac[..]),
+            (CHECK_CONTEXT, &mut check[..]),
+        ] {
+            hkdf.expand(context, out).expect("valid HKDF-SHA256 output length");
+        }
+
+        let cipher = CryptoBox::from_symmetric_key(key);
+        key.fill(0);
+        Ok(Self { cipher, mac, check })
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        <Hmac<Sha256> as KeyInit>::new_from_slice(&self.mac).expect("HMAC takes any key length")
+    }
+}
+
+impl Drop for BackupKeys {
+    fn drop(&mut self) {
+        self.mac.fill(0);
+    }
+}
+
+// Writes the account state held by `repository` as a password protected
+// bundle, laid out as header || sealed payload || HMAC-SHA256. The header
+// carries the Argon2id salt and cost, a password check value and the payload
+// length, so truncation and tampering are detected before anything is
+// decrypted.
+pub(crate) fn export_account(
+    repository: &Database,
+    user_key: &KeyPair,
+    device_key: &KeyPair,
+    password: &str,
+    mut writer: impl Write
+) -> Result<()> {
+    if password.is_empty() {
+        return Err(Error::Argument("Account backup password must not be empty".into()));
+    }
+
+    let config = |key: &str| -> Result<Option<String>> {
+        repository.get_config(key)?
+            .map(|value| String::from_utf8(value).map_err(|e| Error::Encoding(e.to_string())))
+            .transpose()
+    };
+
+    let bundle = Bundle {
+        user_key: user_key.private_key().as_bytes().to_vec(),
+        device_key: device_key.private_key().as_bytes().to_vec(),
+        device_name: config(DEVICE_NAME_KEY)?,
+        app_name: config(APP_NAME_KEY)?,
+        contacts: repository.contacts()?.into_iter().map(ContactEntry::from).collect(),
+        channels: repository.channels()?.into_iter().map(ChannelEntry::from).collect(),
+        contacts_version: config(CONTACTS_VERSION_KEY)?,
+    };
+    let plain = serde_cbor::to_vec(&bundle).map_err(|e| {
+        Error::Encoding(format!("Failed to CBOR-encode account backup: {e}"))
+    })?;
+
+    let header = SealedHeader::new();
+    let keys = BackupKeys::derive(password, &header)?;
+    let sealed = keys.cipher.encrypt_into(&plain, &Nonce::random()).map_err(|e| {
+        Error::Encoding(format!("Failed to encrypt account backup: {e}"))
+    })?;
+
+    let mut data = Vec::with_capacity(HEADER_BYTES + sealed.len() + MAC_BYTES);
+    header.write(MAGIC, FORMAT_VERSION, &mut data);
+    data.extend_from_slice(&keys.check);
+    data.extend_from_slice(&(sealed.len() as u32).to_be_bytes());
+    data.extend_from_slice(&sealed);
+
+    let mut mac = keys.mac();
+    mac.update(&data);
+    data.extend_from_slice(&mac.finalize().into_bytes());
+
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Restores an account bundle into a fresh repository under `data_dir` and
+// returns the user and device keypairs. Nothing is written to `data_dir`
+// unless the bundle was opened and verified.
+pub(crate) fn import_account(
+    mut reader: impl Read,
+    password: &str,
+    data_dir: &Path
+) -> Result<(KeyPair, KeyPair)> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let bundle = open_bundle(&data, password)?;
+
+    let key_pair = |bytes: &[u8]| KeyPair::try_from(bytes).map_err(|e| {
+        Error::Encoding(format!("Invalid key in account backup: {e}"))
+    });
+    let user_key = key_pair(&bundle.user_key)?;
+    let device_key = key_pair(&bundle.device_key)?;
+
+    let contacts = bundle.contacts.into_iter()
+        .map(ContactRecord::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    let channels = bundle.channels.into_iter()
+        .map(ChannelRecord::try_from)
+        .collect::<Result<Vec<_>>>()?;
+
+    if Database::exists(data_dir) {
+        return Err(Error::State(format!(
+            "{} already holds a messaging repository", data_dir.display()
+        )));
+    }
+
+    let repository = Database::open(data_dir, device_key.private_key())?;
+    for (key, value) in [
+        (DEVICE_NAME_KEY, bundle.device_name),
+        (APP_NAME_KEY, bundle.app_name),
+        (CONTACTS_VERSION_KEY, bundle.contacts_version),
+    ] {
+        if let Some(value) = value {
+            repository.put_config(key, value.as_bytes())?;
+        }
+    }
+    for contact in contacts.iter() {
+        repository.put_contact(contact)?;
+    }
+    for channel in channels.iter() {
+        repository.put_channel(channel)?;
+    }
+    Ok((user_key, device_key))
+}
+
+fn open_bundle(data: &[u8], password: &str) -> Result<Bundle> {
+    let corrupted = || Error::Encoding("Account backup is incomplete or corrupted".into());
+
+    if data.len() < HEADER_BYTES + MAC_BYTES {
+        return Err(corrupted());
+    }
+    let sealed_header = SealedHeader::parse(data, MAGIC, FORMAT_VERSION).map_err(|e| match e {
+        HeaderError::Incomplete => corrupted(),
+        HeaderError::UnsupportedVersion(v) => Error::Encoding(format!(
+            "Unsupported account backup version {v}"
+        )),
+        HeaderError::UnsupportedCost => Error::Encoding("Unsupported account backup key parameters".into()),
+    })?;
+
+    let (header, rest) = data.split_at(HEADER_BYTES);
+    let check = &header[SealedHeader::BYTES..SealedHeader::BYTES + CHECK_BYTES];
+    let length = u32::from_be_bytes(header[SealedHeader::BYTES + CHECK_BYTES..].try_into().unwrap()) as usize;
+    if rest.len() != length + MAC_BYTES {
+        return Err(corrupted());
+    }
+
+    let keys = BackupKeys::derive(password, &sealed_header)?;
+    if check != keys.check.as_slice() {
+        return Err(Error::Auth("Wrong password for the account backup".into()));
+    }
+
+    let (signed, tag) = data.split_at(HEADER_BYTES + length);
+    let mut mac = keys.mac();
+    mac.update(signed);
+    mac.verify_slice(tag).map_err(|_| corrupted())?;
+
+    let plain = keys.cipher.decrypt_into(&signed[HEADER_BYTES..]).map_err(|_| corrupted())?;
+    serde_cbor::from_slice::<Bundle>(&plain).map_err(|e| {
+        Error::Encoding(format!("Failed to CBOR-decode account backup: {e}"))
+    })
+}
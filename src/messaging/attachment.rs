@@ -0,0 +1,296 @@
+use std::fs;
+use std::path::PathBuf;
+use log::warn;
+use sha2::{Digest, Sha256};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+
+use crate::cryptobox::{CryptoBox, Nonce};
+use crate::messaging::{
+    Error,
+    Result,
+    client::BoxFuture,
+    message::{Content, ContentDisposition, content_type},
+};
+
+/// Largest attachment accepted for sending.
+pub const MAX_ATTACHMENT_SIZE: usize = 64 * 1024 * 1024;
+
+/// Ciphertext bytes sent per upload request, an interrupted upload resumes
+/// from the last part the service stored.
+pub const UPLOAD_PART_SIZE: usize = 1024 * 1024;
+
+const HASH_BYTES: usize = 32;
+
+/// What a message carrying an attachment holds in its body instead of the
+/// data itself.
+///
+/// Each attachment is encrypted with its own random key. The key only
+/// travels in the manifest, which is encrypted with the conversation or
+/// channel session key like any other message body. The service stores the
+/// ciphertext under its SHA-256 hash and never sees the key.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    // SHA-256 of the ciphertext, the address on the service.
+    #[serde_as(as = "Bytes")]
+    #[serde(rename = "h")]
+    hash: [u8; HASH_BYTES],
+    // SHA-256 of the plain data, checked after decryption.
+    #[serde_as(as = "Bytes")]
+    #[serde(rename = "d")]
+    digest: [u8; HASH_BYTES],
+    #[serde(rename = "s")]
+    size: u64,
+    #[serde(rename = "t")]
+    content_type: String,
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none", default)]
+    filename: Option<String>,
+    #[serde_as(as = "Bytes")]
+    #[serde(rename = "k")]
+    key: [u8; CryptoBox::SYMMETRIC_KEY_BYTES],
+}
+
+impl Manifest {
+    /// The manifest as carried in a message body.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self).map_err(|e| {
+            Error::Encoding(format!("Encoding attachment manifest failed: {e}"))
+        })
+    }
+
+    /// The manifest in a message body, refused when it announces an
+    /// attachment over [`MAX_ATTACHMENT_SIZE`].
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let manifest: Self = serde_cbor::from_slice(data).map_err(|e| {
+            Error::Encoding(format!("Invalid attachment manifest: {e}"))
+        })?;
+        if manifest.size > MAX_ATTACHMENT_SIZE as u64 {
+            return Err(Error::Argument(format!(
+                "Attachment size {} exceeds the maximum {}",
+                manifest.size, MAX_ATTACHMENT_SIZE
+            )));
+        }
+        Ok(manifest)
+    }
+
+    /// The manifest carried by a message body, an error for messages
+    /// without an attachment.
+    pub fn from_content(content: &Content) -> Result<Self> {
+        if content.content_type() != content_type::ATTACHMENT {
+            return Err(Error::Argument(format!(
+                "Message carries {}, not an attachment", content.content_type()
+            )));
+        }
+        Self::decode(content.body())
+    }
+
+    /// The hex encoded ciphertext hash the service stores the attachment
+    /// under.
+    pub fn address(&self) -> String {
+        hex::encode(self.hash)
+    }
+
+    /// The size of the plain data.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The content type of the plain data.
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The file name given by the sender, if any.
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    /// How a message carrying the attachment presents it.
+    pub fn disposition(&self) -> ContentDisposition {
+        ContentDisposition::Attachment { filename: self.filename.clone() }
+    }
+}
+
+/// An encrypted attachment ready for upload. Keep it to resume an
+/// interrupted upload, sealing the data again yields another ciphertext.
+pub struct Sealed {
+    manifest: Manifest,
+    cipher  : Vec<u8>,
+}
+
+impl Sealed {
+    /// The manifest to send once the upload completed.
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// The ciphertext to upload.
+    pub fn cipher(&self) -> &[u8] {
+        &self.cipher
+    }
+}
+
+/// Encrypt `data` with a fresh attachment key.
+pub fn seal(content_type: &str, filename: Option<&str>, data: &[u8]) -> Result<Sealed> {
+    if data.is_empty() {
+        return Err(Error::Argument("Attachment must not be empty".into()));
+    }
+    if data.len() > MAX_ATTACHMENT_SIZE {
+        return Err(Error::Argument(format!(
+            "Attachment size {} exceeds the maximum {}",
+            data.len(), MAX_ATTACHMENT_SIZE
+        )));
+    }
+    if content_type.is_empty() {
+        return Err(Error::Argument("Attachment content type must not be empty".into()));
+    }
+
+    let mut key = crate::random_array::<{ CryptoBox::SYMMETRIC_KEY_BYTES }>();
+    let cipher = CryptoBox::from_symmetric_key(key)
+        .encrypt_into(data, &Nonce::random())
+        .map_err(|e| Error::Encoding(format!("Encrypting attachment failed: {e}")))?;
+
+    let manifest = Manifest {
+        hash        : Sha256::digest(&cipher).into(),
+        digest      : Sha256::digest(data).into(),
+        size        : data.len() as u64,
+        content_type: content_type.into(),
+        filename    : filename.map(|v| v.into()),
+        key,
+    };
+    key.fill(0);
+    Ok(Sealed { manifest, cipher })
+}
+
+/// Check the ciphertext against the manifest, decrypt it and check the
+/// plain data too. Nothing is returned unless both hashes match.
+pub fn open(manifest: &Manifest, cipher: &[u8]) -> Result<Vec<u8>> {
+    let hash: [u8; HASH_BYTES] = Sha256::digest(cipher).into();
+    if hash != manifest.hash {
+        return Err(Error::Auth(format!(
+            "Attachment {} hash mismatch", manifest.address()
+        )));
+    }
+    if cipher.len() < CryptoBox::MAC_BYTES + Nonce::BYTES {
+        return Err(Error::Encoding(format!(
+            "Attachment {} is truncated", manifest.address()
+        )));
+    }
+
+    let data = CryptoBox::from_symmetric_key(manifest.key)
+        .decrypt_into(cipher)
+        .map_err(|e| Error::Auth(format!("Decrypting attachment failed: {e}")))?;
+
+    let digest: [u8; HASH_BYTES] = Sha256::digest(&data).into();
+    if data.len() as u64 != manifest.size || digest != manifest.digest {
+        return Err(Error::Auth(format!(
+            "Attachment {} content mismatch", manifest.address()
+        )));
+    }
+    Ok(data)
+}
+
+/// The attachment storage of the messaging service, implemented by the API
+/// client. Attachments are addressed by the hex encoded ciphertext hash.
+pub trait AttachmentStore: Send {
+    /// Number of bytes of the attachment the service already stored, 0 if
+    /// it has none.
+    fn uploaded<'a>(&'a mut self, address: &'a str) -> BoxFuture<'a, Result<u64>>;
+
+    /// Store `part` at `offset` of an attachment `total` bytes long.
+    fn upload_part<'a>(&'a mut self, address: &'a str, offset: u64, total: u64, part: &'a [u8]) -> BoxFuture<'a, Result<()>>;
+
+    /// The whole ciphertext of the attachment.
+    fn download<'a>(&'a mut self, address: &'a str) -> BoxFuture<'a, Result<Vec<u8>>>;
+}
+
+/// Upload the ciphertext in parts, starting after what the service already
+/// holds. Returns the number of bytes sent, 0 when the service had the
+/// whole attachment.
+pub async fn upload<S: AttachmentStore>(store: &mut S, sealed: &Sealed) -> Result<u64> {
+    let address = sealed.manifest.address();
+    let total = sealed.cipher.len() as u64;
+
+    let offset = store.uploaded(&address).await?;
+    if offset > total {
+        return Err(Error::State(format!(
+            "Service holds {offset} bytes of attachment {address}, expected at most {total}"
+        )));
+    }
+
+    let mut sent = 0u64;
+    for part in sealed.cipher[offset as usize..].chunks(UPLOAD_PART_SIZE) {
+        store.upload_part(&address, offset + sent, total, part).await?;
+        sent += part.len() as u64;
+    }
+    Ok(sent)
+}
+
+/// Fetch, verify and decrypt an attachment, from the cache when it was
+/// fetched before.
+pub async fn fetch<S: AttachmentStore>(
+    store: &mut S,
+    cache: &AttachmentCache,
+    manifest: &Manifest
+) -> Result<Vec<u8>> {
+    let address = manifest.address();
+    if let Some(cipher) = cache.get(&address) {
+        match open(manifest, &cipher) {
+            Ok(data) => return Ok(data),
+            Err(e) => {
+                warn!("Dropped cached attachment {address}: {e}");
+                cache.remove(&address);
+            }
+        }
+    }
+
+    let cipher = store.download(&address).await?;
+    let data = open(manifest, &cipher)?;
+    if let Err(e) = cache.put(&address, &cipher) {
+        warn!("Caching attachment {address} failed: {e}");
+    }
+    Ok(data)
+}
+
+/// Downloaded attachments kept on disk. Only the ciphertext is cached, so
+/// nothing readable is left on disk, and every hit is verified again.
+pub struct AttachmentCache {
+    dir: PathBuf,
+}
+
+impl AttachmentCache {
+    /// A cache kept under `dir`, created on the first entry.
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, address: &str) -> PathBuf {
+        self.dir.join(address)
+    }
+
+    /// The cached ciphertext of the attachment, unverified.
+    pub fn get(&self, address: &str) -> Option<Vec<u8>> {
+        fs::read(self.path(address)).ok()
+    }
+
+    /// Cache the ciphertext of the attachment.
+    pub fn put(&self, address: &str, cipher: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        // Written aside first, a crash must not leave a partial entry.
+        let tmp = self.dir.join(format!("{address}.part"));
+        fs::write(&tmp, cipher)?;
+        fs::rename(&tmp, self.path(address))?;
+        Ok(())
+    }
+
+    /// Drop the attachment from the cache.
+    pub fn remove(&self, address: &str) {
+        let _ = fs::remove_file(self.path(address));
+    }
+
+    /// Whether the attachment is cached.
+    pub fn contains(&self, address: &str) -> bool {
+        self.path(address).is_file()
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use crate::Id;
+use crate::messaging::rate_limit::Origin;
+
+/// The contacts blocked by the user, shared by the client changing it and
+/// the worker dropping their messages from the inbox before anything is
+/// decrypted or stored. Blocking keeps the contact and its history.
+#[derive(Clone, Default)]
+pub struct BlockList {
+    ids: Arc<RwLock<HashSet<Id>>>,
+}
+
+impl BlockList {
+    /// A list blocking `ids`.
+    pub fn new(ids: impl IntoIterator<Item = Id>) -> Self {
+        Self {
+            ids: Arc::new(RwLock::new(ids.into_iter().collect())),
+        }
+    }
+
+    /// Blocks the contact, returns whether it was not blocked before.
+    pub fn block(&self, id: &Id) -> bool {
+        self.ids.write().unwrap().insert(*id)
+    }
+
+    /// Unblocks the contact, returns whether it was blocked before.
+    pub fn unblock(&self, id: &Id) -> bool {
+        self.ids.write().unwrap().remove(id)
+    }
+
+    /// Whether the contact is blocked.
+    pub fn is_blocked(&self, id: &Id) -> bool {
+        self.ids.read().unwrap().contains(id)
+    }
+
+    /// The blocked contacts, sorted.
+    pub fn blocked(&self) -> Vec<Id> {
+        let mut ids: Vec<Id> = self.ids.read().unwrap().iter().copied().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Applies the blocked flags of the contacts in an update pushed by
+    /// another device of the user, returning the ones changed.
+    pub fn sync(&self, contacts: impl IntoIterator<Item = (Id, bool)>) -> Vec<(Id, bool)> {
+        let mut ids = self.ids.write().unwrap();
+        contacts.into_iter().filter(|(id, blocked)| match blocked {
+            true => ids.insert(*id),
+            false => ids.remove(id),
+        }).collect()
+    }
+
+    /// Whether a message is kept: the ones sent by a blocked contact are
+    /// dropped, directly or in a channel, as are the ones in a blocked
+    /// channel. The messaging service is never blocked.
+    pub fn admits(&self, origin: &Origin) -> bool {
+        let Origin::Sender { conversation, sender } = origin else {
+            return true;
+        };
+        let ids = self.ids.read().unwrap();
+        !ids.contains(sender) && !ids.contains(conversation)
+    }
+}
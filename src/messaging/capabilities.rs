@@ -0,0 +1,71 @@
+use serde::Deserialize;
+
+use crate::messaging::{Error, Result};
+
+/// The RPC protocol version this client speaks, sent with every request.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// The protocol version assumed for a service that does not tell its own.
+const LEGACY_PROTOCOL_VERSION: u32 = 1;
+
+/// What the messaging service advertises in its service info: the RPC
+/// protocol version it speaks and the methods it offers.
+///
+/// A service that advertises no capability list at all predates them, it
+/// offers every method of its protocol version.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerCapabilities {
+    #[serde(default)]
+    protocol_version: Option<u32>,
+    #[serde(default)]
+    capabilities: Option<Vec<String>>,
+}
+
+impl ServerCapabilities {
+    pub fn new(protocol_version: Option<u32>, capabilities: Option<&[&str]>) -> Self {
+        Self {
+            protocol_version,
+            capabilities: capabilities.map(|c| c.iter().map(|m| m.to_string()).collect()),
+        }
+    }
+
+    /// The protocol version of the service, 1 if it does not tell.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version.unwrap_or(LEGACY_PROTOCOL_VERSION)
+    }
+
+    /// The methods the service advertises, `None` for a service predating
+    /// capability lists.
+    pub fn capabilities(&self) -> Option<&[String]> {
+        self.capabilities.as_deref()
+    }
+
+    /// Whether the service offers the method, `true` for any method on a
+    /// service without a capability list.
+    pub fn supports(&self, method: &str) -> bool {
+        self.capabilities.as_ref()
+            .is_none_or(|c| c.iter().any(|m| m == method))
+    }
+
+    /// Fails the call of a method the service does not offer, or one added
+    /// by a later protocol version than the service speaks.
+    pub fn check(&self, method: &str, min_version: u32) -> Result<()> {
+        check_version(method, min_version, self.protocol_version())?;
+        match self.supports(method) {
+            true => Ok(()),
+            false => Err(Error::Unsupported(format!("{method} is not offered by the service"))),
+        }
+    }
+}
+
+/// Fails a method answered or offered at an older protocol version than the
+/// one that added it.
+pub fn check_version(method: &str, min_version: u32, version: u32) -> Result<()> {
+    match version >= min_version {
+        true => Ok(()),
+        false => Err(Error::Unsupported(format!(
+            "{method} needs protocol version {min_version}, the service speaks {version}"
+        ))),
+    }
+}
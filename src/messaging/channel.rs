@@ -96,6 +96,15 @@ impl fmt::Display for Role {
     }
 }
 
+/// How members leave a channel on the decision of the owner or a moderator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemberRemoval {
+    /// Removed, the members may join again.
+    Remove,
+    /// Banned, the members may not join again until unbanned.
+    Ban,
+}
+
 /// A single member of a channel, combining identity and role.
 pub trait ChannelMember: Send + Sync {
     /// The member's boson `Id`.
@@ -136,6 +145,12 @@ pub trait Channel: Contact {
 
     /// The current member count, if known.
     fn member_count(&self) -> Option<usize>;
+
+    /// The most members the channel takes, `None` for no bound.
+    fn max_members(&self) -> Option<usize>;
+
+    /// Whether joins wait for the approval of the owner or a moderator.
+    fn approval_required(&self) -> bool;
 }
 
 /// Mutable editing operations on a [`Channel`].
@@ -148,4 +163,10 @@ pub trait ChannelEditor {
 
     /// Update the channel announcement.
     fn set_announcement(&mut self, announcement: Option<String>);
+
+    /// Update the most members the channel takes.
+    fn set_max_members(&mut self, max_members: Option<usize>);
+
+    /// Update whether joins need approval.
+    fn set_approval_required(&mut self, approval_required: bool);
 }
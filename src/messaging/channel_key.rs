@@ -0,0 +1,218 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, Bytes};
+
+use crate::{
+    Id,
+    Identity,
+    CryptoIdentity,
+    cryptobox::{self, CryptoBox, Nonce},
+};
+use crate::messaging::{
+    errors::{Error, Result},
+    channel::ChannelMember,
+};
+
+/// Size of the key epoch prefix on channel message ciphertext.
+const EPOCH_BYTES: usize = 4;
+
+/// A channel session key rotation notice.
+///
+/// Carries the new session private key, encrypted separately for each
+/// remaining member with the rotator's identity key. It travels to the
+/// members as the CBOR payload of [`to_bytes`](Self::to_bytes).
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    #[serde(rename = "c")]
+    channel_id: Id,
+    #[serde(rename = "s")]
+    sender:     Id,
+    #[serde(rename = "e")]
+    epoch:      u32,
+    #[serde_as(as = "HashMap<_, Bytes>")]
+    #[serde(rename = "k")]
+    keys:       HashMap<Id, Vec<u8>>,
+}
+
+impl KeyRotation {
+    /// The channel whose session key was rotated.
+    pub fn channel_id(&self) -> &Id {
+        &self.channel_id
+    }
+
+    /// The owner or moderator who rotated the key.
+    pub fn sender(&self) -> &Id {
+        &self.sender
+    }
+
+    /// The key epoch introduced by this rotation.
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// The members this rotation was distributed to.
+    pub fn recipients(&self) -> impl Iterator<Item = &Id> {
+        self.keys.keys()
+    }
+
+    /// Encode the rotation as the CBOR payload sent to the members.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-encode key rotation: {}", e)))
+    }
+
+    /// Decode a rotation payload received from the owner or a moderator.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice::<KeyRotation>(bytes)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-decode key rotation: {}", e)))
+    }
+}
+
+/// The session keys of a channel, indexed by key epoch.
+///
+/// New messages are always encrypted with the key of the current epoch,
+/// older keys are kept so the message history stays readable.
+#[derive(Debug, Clone)]
+pub struct ChannelKeyRing {
+    channel_id: Id,
+    current:    u32,
+    keys:       BTreeMap<u32, cryptobox::KeyPair>,
+}
+
+impl ChannelKeyRing {
+    /// Create a key ring holding the initial channel session key at epoch 0.
+    pub fn new(channel_id: Id, session_key: cryptobox::KeyPair) -> Self {
+        Self {
+            channel_id,
+            current: 0,
+            keys: BTreeMap::from([(0, session_key)]),
+        }
+    }
+
+    /// The channel this key ring belongs to.
+    pub fn channel_id(&self) -> &Id {
+        &self.channel_id
+    }
+
+    /// The current key epoch.
+    pub fn epoch(&self) -> u32 {
+        self.current
+    }
+
+    /// The session keypair of the current epoch.
+    pub fn session_key(&self) -> &cryptobox::KeyPair {
+        &self.keys[&self.current]
+    }
+
+    /// Generate a new session key and distribute it to `members`.
+    ///
+    /// Called by the owner or a moderator after members were removed or
+    /// banned; the ousted members must not be listed in `members`.
+    pub fn rotate(&mut self, rotator: &CryptoIdentity, members: &[Id]) -> Result<KeyRotation> {
+        let session_key = cryptobox::KeyPair::random();
+        let plain = session_key.private_key().as_bytes();
+
+        let mut keys = HashMap::with_capacity(members.len());
+        for member in members.iter().filter(|id| *id != rotator.id()) {
+            let cipher = rotator.encrypt_into(member, plain).map_err(|e| {
+                Error::Auth(format!("Encrypting session key for {member} failed: {e}"))
+            })?;
+            keys.insert(*member, cipher);
+        }
+
+        let epoch = self.current + 1;
+        self.keys.insert(epoch, session_key);
+        self.current = epoch;
+
+        Ok(KeyRotation {
+            channel_id: self.channel_id,
+            sender: *rotator.id(),
+            epoch,
+            keys,
+        })
+    }
+
+    /// Install the session key carried by a rotation notice for `identity`.
+    ///
+    /// `sender` is the member record of the rotation sender as known
+    /// locally, only the owner and the moderators may rotate the key.
+    /// Stale or replayed rotations are ignored.
+    pub fn apply(&mut self, identity: &CryptoIdentity, rotation: &KeyRotation, sender: &dyn ChannelMember) -> Result<()> {
+        if rotation.channel_id != self.channel_id {
+            return Err(Error::Argument(format!(
+                "Key rotation for channel {} does not match channel {}",
+                rotation.channel_id, self.channel_id
+            )));
+        }
+        if sender.id() != &rotation.sender {
+            return Err(Error::Argument(format!(
+                "Key rotation from {} does not match member {}",
+                rotation.sender, sender.id()
+            )));
+        }
+        if !sender.is_owner() && !sender.is_moderator() {
+            return Err(Error::PermissionDenied(format!(
+                "Member {} may not rotate the key of channel {}",
+                rotation.sender, self.channel_id
+            )));
+        }
+        if self.keys.contains_key(&rotation.epoch) {
+            return Ok(());
+        }
+
+        let Some(cipher) = rotation.keys.get(identity.id()) else {
+            return Err(Error::NotFound(format!(
+                "No session key for {} in key rotation {}",
+                identity.id(), rotation.epoch
+            )));
+        };
+
+        let plain = identity.decrypt_into(&rotation.sender, cipher).map_err(|e| {
+            Error::Auth(format!("Decrypting session key failed: {e}"))
+        })?;
+        let session_key = cryptobox::KeyPair::try_from(plain.as_slice()).map_err(|e| {
+            Error::Encoding(format!("Invalid session key: {e}"))
+        })?;
+
+        self.keys.insert(rotation.epoch, session_key);
+        self.current = self.current.max(rotation.epoch);
+        Ok(())
+    }
+
+    /// Encrypt a channel message with the current session key.
+    ///
+    /// The ciphertext is prefixed with the big-endian key epoch.
+    pub fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        let cipher = Self::crypto_box(self.session_key())?
+            .encrypt_into(plain, &Nonce::random())
+            .map_err(|e| Error::Auth(format!("Encrypting channel message failed: {e}")))?;
+
+        let mut data = Vec::with_capacity(EPOCH_BYTES + cipher.len());
+        data.extend_from_slice(&self.current.to_be_bytes());
+        data.extend_from_slice(&cipher);
+        Ok(data)
+    }
+
+    /// Decrypt a channel message with the session key of its epoch.
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < EPOCH_BYTES + CryptoBox::MAC_BYTES + Nonce::BYTES {
+            return Err(Error::Encoding("Channel message is too short".into()));
+        }
+
+        let (epoch, cipher) = data.split_at(EPOCH_BYTES);
+        let epoch = u32::from_be_bytes(epoch.try_into().unwrap());
+        let Some(session_key) = self.keys.get(&epoch) else {
+            return Err(Error::NotFound(format!("No session key for epoch {epoch}")));
+        };
+
+        Self::crypto_box(session_key)?
+            .decrypt_into(cipher)
+            .map_err(|e| Error::Auth(format!("Decrypting channel message failed: {e}")))
+    }
+
+    fn crypto_box(session_key: &cryptobox::KeyPair) -> Result<CryptoBox> {
+        CryptoBox::try_from((session_key.public_key(), session_key.private_key()))
+            .map_err(|e| Error::Auth(format!("Invalid session key: {e}")))
+    }
+}
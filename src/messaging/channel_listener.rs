@@ -1,5 +1,6 @@
 use crate::Id;
 use crate::messaging::channel::{Channel, ChannelMember};
+use crate::messaging::join_request::JoinRequest;
 
 /// Receives channel lifecycle and membership events.
 pub trait ChannelListener: Send + Sync {
@@ -23,7 +24,9 @@ pub trait ChannelListener: Send + Sync {
         _new_owner: &Id,
     ) {}
 
-    /// Called when the channel session key was rotated.
+    /// Called when the channel session key was rotated, either locally or
+    /// after a [`KeyRotation`](crate::messaging::KeyRotation) from the owner
+    /// or a moderator was applied.
     fn on_channel_session_key_rotated(&self, _channel: &dyn Channel) {}
 
     /// Called when channel metadata (name, notice, etc.) was updated.
@@ -32,6 +35,11 @@ pub trait ChannelListener: Send + Sync {
     /// Called when a new member joined the channel.
     fn on_channel_member_joined(&self, _channel: &dyn Channel, _member: &dyn ChannelMember) {}
 
+    /// Called on the owner and moderators when a user asked to join a channel
+    /// requiring approval, see
+    /// [`MessagingClient::approve_join`](crate::messaging::MessagingClient::approve_join).
+    fn on_join_requested(&self, _channel: &dyn Channel, _request: &JoinRequest) {}
+
     /// Called when a member left the channel.
     fn on_channel_member_left(&self, _channel: &dyn Channel, _member: &dyn ChannelMember) {}
 
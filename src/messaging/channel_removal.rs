@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Id;
+use crate::messaging::{
+    Error,
+    Result,
+    contact::{Contact, ContactType},
+    channel::{Channel, Permission},
+};
+
+/// Only the owner may delete a channel. A channel without a local record,
+/// so without a known `owner`, is left to the service to decide on.
+pub fn check_owner(user: &Id, channel_id: &Id, owner: Option<&Id>) -> Result<()> {
+    match owner {
+        Some(owner) if owner != user => Err(Error::PermissionDenied(
+            format!("channel {} is owned by {}", channel_id, owner)
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Channel deletions requested by this client and not answered yet.
+///
+/// The CHANNEL_DELETED notification and the RPC response can arrive in
+/// either order. Whichever comes first purges the local state and reports
+/// the deletion, so the listeners hear about it exactly once.
+#[derive(Default)]
+pub struct ChannelRemovals {
+    // Channel id -> whether the notification reported it already.
+    pending: HashMap<Id, bool>,
+}
+
+impl ChannelRemovals {
+    /// No deletion pending.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the deletion of the channel was requested and not answered.
+    pub fn is_pending(&self, channel_id: &Id) -> bool {
+        self.pending.contains_key(channel_id)
+    }
+
+    /// Track the deletion of the channel, requested just now.
+    pub fn begin(&mut self, channel_id: &Id) {
+        self.pending.insert(*channel_id, false);
+    }
+
+    /// On the response to the request, returns whether the deletion still
+    /// has to be reported.
+    pub fn on_response(&mut self, channel_id: &Id) -> bool {
+        !self.pending.remove(channel_id).unwrap_or(false)
+    }
+
+    /// On a failed request, returns whether the channel is gone regardless,
+    /// as the notification confirmed the deletion before.
+    pub fn on_failure(&mut self, channel_id: &Id) -> bool {
+        self.pending.remove(channel_id).unwrap_or(false)
+    }
+
+    /// On the notification, returns whether the deletion has to be reported.
+    /// A channel deleted by someone else is only reported if it was known
+    /// locally.
+    pub fn on_notification(&mut self, channel_id: &Id, known: bool) -> bool {
+        match self.pending.get_mut(channel_id) {
+            Some(reported) => !std::mem::replace(reported, true),
+            None => known,
+        }
+    }
+}
+
+/// What is left of a deleted channel, handed to
+/// [`ChannelListener::on_channel_deleted`](crate::messaging::ChannelListener::on_channel_deleted).
+pub struct RemovedChannel {
+    id          : Id,
+    owner       : Id,
+    name        : Option<String>,
+    display_name: String,
+    permission  : Permission,
+    updated     : SystemTime,
+}
+
+impl RemovedChannel {
+    /// A minimal channel for a deletion without a local record.
+    pub fn from_id(id: &Id) -> Self {
+        Self {
+            id          : *id,
+            owner       : Id::zero(),
+            name        : None,
+            display_name: id.to_base58(),
+            permission  : Permission::Public,
+            updated     : SystemTime::now(),
+        }
+    }
+
+    /// The channel as it was known locally, `updated` in milliseconds
+    /// since the epoch.
+    pub fn new(id: &Id, owner: &Id, name: Option<&str>, permission: Permission, updated: u64) -> Self {
+        Self {
+            id          : *id,
+            owner       : *owner,
+            name        : name.map(|n| n.to_string()),
+            display_name: name.map(|n| n.to_string()).unwrap_or_else(|| id.to_base58()),
+            permission,
+            updated     : UNIX_EPOCH + Duration::from_millis(updated),
+        }
+    }
+}
+
+impl Contact for RemovedChannel {
+    fn id(&self) -> &Id                     { &self.id }
+    fn contact_type(&self) -> ContactType   { ContactType::Channel }
+    fn name(&self) -> Option<&str>          { self.name.as_deref() }
+    fn remark(&self) -> Option<&str>        { None }
+    fn tags(&self) -> Option<&str>          { None }
+    fn is_muted(&self) -> bool              { false }
+    fn is_blocked(&self) -> bool            { false }
+    fn created_at(&self) -> SystemTime      { self.updated }
+    fn updated_at(&self) -> SystemTime      { self.updated }
+    fn revision(&self) -> i32               { 0 }
+    fn avatar(&self) -> Option<&str>        { None }
+    fn display_name(&self) -> &str          { &self.display_name }
+}
+
+impl Channel for RemovedChannel {
+    fn permission(&self) -> Permission      { self.permission }
+    fn channel_name(&self) -> Option<&str>  { self.name.as_deref() }
+    fn notice(&self) -> Option<&str>        { None }
+    fn announcement(&self) -> Option<&str>  { None }
+    fn owner(&self) -> &Id                  { &self.owner }
+    fn session_id(&self) -> Option<&Id>     { None }
+    fn member_count(&self) -> Option<usize> { None }
+    fn max_members(&self) -> Option<usize>  { None }
+    fn approval_required(&self) -> bool     { false }
+}
@@ -0,0 +1,302 @@
+//! Chunked transport for message envelopes larger than an MQTT packet.
+//!
+//! The broker limits packets to [`MAX_PACKET_SIZE`]. Envelopes that fit in a
+//! single chunk are published unchanged, so they stay readable by peers
+//! without chunking support. Larger envelopes are split into chunk frames,
+//! CBOR maps carrying the `ck` extension (`v`, `s`, `id`, `i`, `n`: frame
+//! version, sender id, message id, chunk index and chunk count) next to the
+//! chunk data `d`. Every frame is then encrypted and published like a
+//! regular envelope.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use serde_cbor::Value;
+
+use crate::Id;
+use crate::messaging::errors::{Error, Result};
+
+/// Maximum MQTT packet size accepted by the messaging service.
+pub const MAX_PACKET_SIZE: usize = 16 * 1024;
+
+/// Maximum envelope bytes carried by one chunk, leaving room for the topic,
+/// the chunk extension and the transport encryption overhead.
+pub const MAX_CHUNK_SIZE: usize = 15 * 1024;
+
+/// Maximum number of chunks a message can be split into.
+pub const MAX_CHUNKS: usize = 64;
+
+/// Largest envelope that can be sent as a single message.
+pub const MAX_MESSAGE_SIZE: usize = MAX_CHUNK_SIZE * MAX_CHUNKS;
+
+/// Incomplete chunk sequences are dropped after this long.
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound of buffered chunk data awaiting reassembly.
+pub const DEFAULT_REASSEMBLY_CAPACITY: usize = 4 * 1024 * 1024;
+
+/// Incomplete chunk sequences kept per sender, the oldest one is dropped
+/// for a new one beyond it.
+pub const MAX_PENDING_PER_SENDER: usize = 4;
+
+/// Version of the chunk frames, a map is taken as a frame only with it.
+pub const FRAME_VERSION: u8 = 1;
+
+const KEY_CHUNK: &str = "ck";
+const KEY_VERSION: &str = "v";
+const KEY_DATA: &str = "d";
+
+/// Split an encoded envelope of `sender` into transport packets.
+///
+/// Envelopes up to [`MAX_CHUNK_SIZE`] are returned as-is in a single packet.
+pub fn split(sender: &Id, envelope: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+    if envelope.len() <= MAX_CHUNK_SIZE {
+        return Ok(vec![envelope]);
+    }
+    if envelope.len() > MAX_MESSAGE_SIZE {
+        return Err(Error::Argument(format!(
+            "Message size {} exceeds the maximum {}",
+            envelope.len(), MAX_MESSAGE_SIZE
+        )));
+    }
+
+    let id = u64::from_be_bytes(crate::random_array::<8>());
+    let total = envelope.len().div_ceil(MAX_CHUNK_SIZE);
+    envelope.chunks(MAX_CHUNK_SIZE).enumerate().map(|(index, data)| {
+        encode_frame(sender, id, index, total, data)
+    }).collect()
+}
+
+fn encode_frame(sender: &Id, id: u64, index: usize, total: usize, data: &[u8]) -> Result<Vec<u8>> {
+    let ext = BTreeMap::from([
+        (Value::Text(KEY_VERSION.into()), Value::Integer(FRAME_VERSION as i128)),
+        (Value::Text("s".into()),  Value::Bytes(sender.as_bytes().to_vec())),
+        (Value::Text("id".into()), Value::Integer(id as i128)),
+        (Value::Text("i".into()),  Value::Integer(index as i128)),
+        (Value::Text("n".into()),  Value::Integer(total as i128)),
+    ]);
+    let frame = Value::Map(BTreeMap::from([
+        (Value::Text(KEY_CHUNK.into()), Value::Map(ext)),
+        (Value::Text(KEY_DATA.into()),  Value::Bytes(data.to_vec())),
+    ]));
+
+    serde_cbor::to_vec(&frame).map_err(|e| {
+        Error::Encoding(format!("Encoding chunk frame failed: {e}"))
+    })
+}
+
+struct Frame {
+    sender: Id,
+    id: u64,
+    index: usize,
+    total: usize,
+    data: Vec<u8>,
+}
+
+// Returns None for regular envelopes, the ones without a versioned chunk
+// extension.
+fn decode_frame(packet: &[u8]) -> Result<Option<Frame>> {
+    let Ok(Value::Map(mut map)) = serde_cbor::from_slice::<Value>(packet) else {
+        return Ok(None);
+    };
+    let Some(Value::Map(ext)) = map.remove(&Value::Text(KEY_CHUNK.into())) else {
+        return Ok(None);
+    };
+    let Some(Value::Integer(version)) = ext.get(&Value::Text(KEY_VERSION.into())) else {
+        return Ok(None);
+    };
+    if *version != FRAME_VERSION as i128 {
+        return Err(Error::Encoding(format!("Unsupported chunk frame version {version}")));
+    }
+
+    let int = |key: &str| match ext.get(&Value::Text(key.into())) {
+        Some(Value::Integer(v)) => Ok(*v),
+        _ => Err(Error::Encoding(format!("Chunk extension misses '{key}'"))),
+    };
+    let sender = match ext.get(&Value::Text("s".into())) {
+        Some(Value::Bytes(v)) => Id::try_from_bytes(v).map_err(|_| {
+            Error::Encoding("Invalid chunk sender".into())
+        })?,
+        _ => return Err(Error::Encoding("Chunk extension misses 's'".into())),
+    };
+    let id = u64::try_from(int("id")?).map_err(|_| Error::Encoding("Invalid chunk message id".into()))?;
+    let index = int("i")?;
+    let total = int("n")?;
+
+    if total < 1 || total > MAX_CHUNKS as i128 || index < 0 || index >= total {
+        return Err(Error::Encoding(format!("Invalid chunk {index}/{total}")));
+    }
+
+    let Some(Value::Bytes(data)) = map.remove(&Value::Text(KEY_DATA.into())) else {
+        return Err(Error::Encoding("Chunk frame misses data".into()));
+    };
+    if data.len() > MAX_CHUNK_SIZE {
+        return Err(Error::Encoding(format!("Chunk data size {} too large", data.len())));
+    }
+
+    Ok(Some(Frame {
+        sender,
+        id,
+        index: index as usize,
+        total: total as usize,
+        data,
+    }))
+}
+
+struct Pending {
+    started: Instant,
+    chunks: Vec<Option<Vec<u8>>>,
+    received: usize,
+    size: usize,
+}
+
+/// Reassembles chunked envelopes received from the transport.
+///
+/// Sequences are told apart by the sender the transport authenticated and
+/// the message id. Buffered data is
+/// bounded: the oldest incomplete sequences are dropped when the capacity
+/// is exceeded or a sender has more than [`MAX_PENDING_PER_SENDER`] of
+/// them, and any sequence not completed within the timeout is discarded.
+pub struct Reassembler {
+    timeout: Duration,
+    capacity: usize,
+    buffered: usize,
+    pending: HashMap<(Id, u64), Pending>,
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_REASSEMBLY_TIMEOUT, DEFAULT_REASSEMBLY_CAPACITY)
+    }
+}
+
+impl Reassembler {
+    pub fn new(timeout: Duration, capacity: usize) -> Self {
+        Self {
+            timeout,
+            capacity,
+            buffered: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Feed a transport packet decrypted from `sender`.
+    ///
+    /// Returns the complete envelope once available: immediately for
+    /// regular envelopes, or when the last missing chunk arrives. Frames
+    /// claiming another sender than the one the packet was decrypted from
+    /// fail with [`Error::Auth`]. The envelope is not looked into, its
+    /// sender still has to be checked against `sender` like the one of a
+    /// regular envelope.
+    pub fn accept(&mut self, sender: &Id, packet: Vec<u8>) -> Result<Option<Vec<u8>>> {
+        let Some(frame) = decode_frame(&packet)? else {
+            return Ok(Some(packet));
+        };
+        if &frame.sender != sender {
+            return Err(Error::Auth(format!(
+                "Chunk frame of {} received from {}",
+                frame.sender, sender
+            )));
+        }
+
+        self.expire();
+
+        let key = (*sender, frame.id);
+        if !self.pending.contains_key(&key) {
+            self.make_room_for(sender);
+        }
+
+        let pending = self.pending.entry(key).or_insert_with(|| Pending {
+            started: Instant::now(),
+            chunks: vec![None; frame.total],
+            received: 0,
+            size: 0,
+        });
+        if pending.chunks.len() != frame.total {
+            return Err(Error::Encoding(format!(
+                "Chunk count mismatch for message {}: {} != {}",
+                frame.id, frame.total, pending.chunks.len()
+            )));
+        }
+
+        let slot = &mut pending.chunks[frame.index];
+        if slot.is_some() {
+            return Ok(None);    // duplicate delivery
+        }
+
+        let size = frame.data.len();
+        *slot = Some(frame.data);
+        pending.received += 1;
+        pending.size += size;
+        self.buffered += size;
+
+        if pending.received == pending.chunks.len() {
+            let pending = self.pending.remove(&key).unwrap();
+            self.buffered -= pending.size;
+
+            let mut envelope = Vec::with_capacity(pending.size);
+            pending.chunks.into_iter().flatten().for_each(|c| envelope.extend(c));
+            return Ok(Some(envelope));
+        }
+
+        self.shrink();
+        Ok(None)
+    }
+
+    /// Drop incomplete sequences older than the timeout, returning how
+    /// many were dropped.
+    pub fn expire(&mut self) -> usize {
+        let timeout = self.timeout;
+        let before = self.pending.len();
+        let mut released = 0;
+        self.pending.retain(|_, p| {
+            let keep = p.started.elapsed() < timeout;
+            if !keep {
+                released += p.size;
+            }
+            keep
+        });
+        self.buffered -= released;
+        before - self.pending.len()
+    }
+
+    /// Number of incomplete chunk sequences.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Bytes of chunk data buffered for incomplete sequences.
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    fn shrink(&mut self) {
+        while self.buffered > self.capacity {
+            let Some(oldest) = self.pending.iter()
+                .min_by_key(|(_, p)| p.started)
+                .map(|(key, _)| *key) else {
+                break;
+            };
+            self.drop_pending(&oldest);
+        }
+    }
+
+    // Drops the oldest sequences of the sender until a new one fits.
+    fn make_room_for(&mut self, sender: &Id) {
+        let mut of_sender = self.pending.iter()
+            .filter(|((from, _), _)| from == sender)
+            .map(|(key, p)| (*key, p.started))
+            .collect::<Vec<_>>();
+        of_sender.sort_by_key(|(_, started)| *started);
+
+        let excess = (of_sender.len() + 1).saturating_sub(MAX_PENDING_PER_SENDER);
+        for (key, _) in of_sender.into_iter().take(excess) {
+            self.drop_pending(&key);
+        }
+    }
+
+    fn drop_pending(&mut self, key: &(Id, u64)) {
+        if let Some(dropped) = self.pending.remove(key) {
+            self.buffered -= dropped.size;
+        }
+    }
+}
@@ -1,30 +1,52 @@
 use std::sync::Arc;
 use std::future::Future;
+use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::time::Duration;
+use futures::Stream;
+use log::warn;
+use url::Url;
 
-use crate::Id;
+use crate::{Id, PeerInfo};
+use crate::dht::event_stream::StreamItem;
 use crate::messaging::{
-    errors::Result,
-    channel::Permission,
+    errors::{Error, Result},
+    account_backup,
+    contact_transfer::{self, ContactFormat, ImportReport},
+    channel::{MemberRemoval, Permission},
     contact::Contact,
     channel::Channel,
     channel_listener::ChannelListener,
-    connection_listener::ConnectionListener,
+    connection_listener::{ConnectionListener, ConnectionEvent, ConnectionEvents},
     contact_listener::ContactListener,
     conversation::Conversation,
     friend_request::FriendRequest,
     friend_request_listener::FriendRequestListener,
     invite_ticket::InviteTicket,
+    join_request::JoinRequest,
     message::Message,
     message::MessageBuilder,
     message_listener::MessageListener,
+    message_search::MessageHit,
     session_info::SessionInfo,
     session_listener::SessionListener,
+    presence::Presence,
+    rate_limit::InboundRateLimit,
+    self_sync::ReadState,
+    notification::NotifyLevel,
+    service_ids::{ServiceIds, ServiceDiscovery},
+    capabilities::ServerCapabilities,
+    persistence::database::Database,
+    device_registry::{self, DeviceInfo, DeviceRegistration, DeviceRegistry, DeviceRequest, ServiceInfo},
 };
 
 /// Default maximum number of messages returned by a range query.
 pub const DEFAULT_MESSAGES_LIMIT: usize = 100;
 
+/// Default time a request to the messaging service waits for its response.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
 /// A boxed future returned by async methods on [`MessagingClient`].
 pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
 
@@ -43,6 +65,10 @@ pub trait MessagingClient: Send + Sync {
     /// The boson `Id` of the current device.
     fn device_id(&self) -> &Id;
 
+    /// The MQTT client id the current device connects with, derived from
+    /// the device id. Only meant for debugging broker sessions.
+    fn client_id(&self) -> &str;
+
     /// The boson `Id` of the messaging service peer.
     fn service_peer_id(&self) -> &Id;
 
@@ -60,6 +86,9 @@ pub trait MessagingClient: Send + Sync {
     fn start(&self) -> BoxFuture<'_, Result<()>>;
 
     /// Gracefully stop the client.
+    ///
+    /// Operations still awaiting the service fail with [`Error::Shutdown`],
+    /// new ones with [`Error::State`].
     fn stop(&self) -> BoxFuture<'_, Result<()>>;
 
     /// Whether the client background worker is running.
@@ -71,6 +100,27 @@ pub trait MessagingClient: Send + Sync {
     /// Whether the client is connected *and* fully initialised.
     fn is_ready(&self) -> bool;
 
+    /// Write the account as a password protected bundle for moving it to
+    /// another device: the user and device keys, the device registration,
+    /// contacts and joined channels with their session keys, and the
+    /// contacts version id.
+    ///
+    /// Restore it with [`MessagingClientBuilder::from_account_backup`].
+    fn export_account(&self, password: &str, writer: &mut dyn Write) -> Result<()>;
+
+    /// The largest encoded message accepted for sending.
+    ///
+    /// Messages above the MQTT packet limit are transparently sent in chunks.
+    fn max_message_size(&self) -> usize {
+        crate::messaging::chunking::MAX_MESSAGE_SIZE
+    }
+
+    /// The protocol version and methods the connected service advertised,
+    /// `None` until its service info was fetched after connecting.
+    ///
+    /// Calls the service does not offer fail with [`Error::Unsupported`].
+    fn server_capabilities(&self) -> Option<ServerCapabilities>;
+
     // -----------------------------------------------------------------
     // Listeners
     // -----------------------------------------------------------------
@@ -104,6 +154,11 @@ pub trait MessagingClient: Send + Sync {
     /// create a broadcast message.
     fn message(&self, recipient: Option<Id>) -> Box<dyn MessageBuilder>;
 
+    /// Create a message builder addressed to the contact or channel `to`.
+    fn message_to(&self, to: &Id) -> Box<dyn MessageBuilder> {
+        self.message(Some(*to))
+    }
+
     /// Retrieve a single conversation by the other party's `Id`.
     fn get_conversation(&self, id: &Id) -> BoxFuture<'_, Result<Option<Box<dyn Conversation>>>>;
 
@@ -143,6 +198,70 @@ pub trait MessagingClient: Send + Sync {
     /// Delete all messages within a conversation.
     fn remove_messages_in_conversation(&self, conversation_id: &Id) -> BoxFuture<'_, Result<()>>;
 
+    /// Search the text messages stored on this device for every word of
+    /// `query`, ignoring case and Unicode composition. Hits within
+    /// `scope`, or every conversation when `None`, come newest first.
+    fn search_messages(
+        &self,
+        query:  &str,
+        scope:  Option<&Id>,
+        limit:  usize,
+        offset: usize,
+    ) -> BoxFuture<'_, Result<Vec<MessageHit>>>;
+
+    /// Rebuild the local message search index, returns the number of
+    /// messages indexed.
+    fn reindex_messages(&self) -> BoxFuture<'_, Result<usize>>;
+
+    /// Mark `conversation_id` read up to the message created at `last_read`
+    /// (milliseconds), on every device of the user.
+    fn mark_read(&self, conversation_id: &Id, last_read: u64) -> BoxFuture<'_, Result<()>>;
+
+    /// How far `conversation_id` was read on any device of the user.
+    fn read_state(&self, conversation_id: &Id) -> Option<ReadState>;
+
+    /// Save the unsent text of `conversation_id` on every device of the
+    /// user, an empty text discards it.
+    fn save_draft(&self, conversation_id: &Id, text: &str) -> BoxFuture<'_, Result<()>>;
+
+    /// The unsent text of `conversation_id`, if any.
+    fn draft(&self, conversation_id: &Id) -> Option<String>;
+
+    /// Set which messages of `conversation_id` notify the user, on every
+    /// device of the user. The messages are stored whatever the level.
+    fn set_notify_level(&self, conversation_id: &Id, level: NotifyLevel) -> BoxFuture<'_, Result<()>>;
+
+    /// The notify level of `conversation_id`, [`NotifyLevel::All`] unless set.
+    fn notify_level(&self, conversation_id: &Id) -> NotifyLevel;
+
+    // -----------------------------------------------------------------
+    // Attachments
+    // -----------------------------------------------------------------
+
+    /// Encrypt `data` with a fresh attachment key, upload the ciphertext to
+    /// the messaging service and send `to` a message carrying the attachment
+    /// manifest. An interrupted upload is resumed where the service left off.
+    fn send_attachment(
+        &self,
+        to: &Id,
+        content_type: &str,
+        filename: Option<&str>,
+        data: Vec<u8>,
+    ) -> BoxFuture<'_, Result<Box<dyn Message>>>;
+
+    /// Like [`send_attachment`](Self::send_attachment), reading the data
+    /// from `path` and naming the attachment after the file.
+    fn send_attachment_from_file(
+        &self,
+        to: &Id,
+        content_type: &str,
+        path: &std::path::Path,
+    ) -> BoxFuture<'_, Result<Box<dyn Message>>>;
+
+    /// Download and decrypt the attachment carried by `message`. The data is
+    /// verified against the manifest hashes, downloads are cached on disk.
+    fn fetch_attachment(&self, message: &dyn Message) -> BoxFuture<'_, Result<Vec<u8>>>;
+
     // -----------------------------------------------------------------
     // Sessions
     // -----------------------------------------------------------------
@@ -200,9 +319,20 @@ pub trait MessagingClient: Send + Sync {
     ) -> BoxFuture<'_, Result<Box<dyn Channel>>>;
 
     /// Delete a channel (owner only).
+    ///
+    /// Fails with [`Error::PermissionDenied`](crate::messaging::Error::PermissionDenied)
+    /// if the channel is known locally and owned by someone else. On success
+    /// the channel record, its conversation history and session key are
+    /// removed locally, even if some of them were already gone.
     fn remove_channel(&self, channel_id: &Id) -> BoxFuture<'_, Result<()>>;
 
     /// Join a channel using an invite ticket.
+    ///
+    /// Fails with [`Error::ChannelFull`](crate::messaging::Error::ChannelFull)
+    /// if the channel has no room left. For a channel requiring approval the
+    /// join completes once the owner or a moderator approved it, and fails
+    /// with [`Error::JoinDenied`](crate::messaging::Error::JoinDenied) if the
+    /// request was turned down.
     fn join_channel(&self, ticket: InviteTicket) -> BoxFuture<'_, Result<Box<dyn Channel>>>;
 
     /// Leave a channel.
@@ -222,12 +352,31 @@ pub trait MessagingClient: Send + Sync {
         new_owner:  Id,
     ) -> BoxFuture<'_, Result<()>>;
 
-    /// Rotate the channel session key, optionally supplying a pre-generated keypair.
+    /// Rotate the channel session key (owner or moderators only): a new key
+    /// epoch starts and its key is sent as a
+    /// [`KeyRotation`](crate::messaging::KeyRotation) to the current members.
     fn rotate_channel_session_key(&self, channel_id: &Id) -> BoxFuture<'_, Result<()>>;
 
     /// Update channel metadata.
     fn update_channel_info(&self, channel: &dyn Channel) -> BoxFuture<'_, Result<()>>;
 
+    /// Bound the members of a channel and whether joins need approval
+    /// (owner only). Members beyond a lowered bound stay in the channel.
+    fn set_channel_join_policy(
+        &self,
+        channel_id:        &Id,
+        max_members:       Option<usize>,
+        approval_required: bool,
+    ) -> BoxFuture<'_, Result<()>>;
+
+    /// Approve or deny a pending request to join a channel (owner or
+    /// moderators only). An approved user completes the join, a denied one
+    /// gets [`Error::JoinDenied`](crate::messaging::Error::JoinDenied).
+    fn approve_join(&self, channel_id: &Id, user_id: &Id, approve: bool) -> BoxFuture<'_, Result<()>>;
+
+    /// The requests to join a channel waiting for a decision, oldest first.
+    fn join_requests(&self, channel_id: &Id) -> Vec<JoinRequest>;
+
     /// Update the roles of a set of channel members.
     fn set_channel_members_role(
         &self,
@@ -236,14 +385,34 @@ pub trait MessagingClient: Send + Sync {
         role:       crate::messaging::channel::Role,
     ) -> BoxFuture<'_, Result<()>>;
 
-    /// Ban a set of channel members.
-    fn ban_channel_members(&self, channel_id: &Id, members: &[Id]) -> BoxFuture<'_, Result<()>>;
+    /// Ban a set of channel members, then rotate the channel session key
+    /// among the remaining members so the banned ones cannot read the later
+    /// messages.
+    fn ban_channel_members(&self, channel_id: &Id, members: &[Id]) -> BoxFuture<'_, Result<()>> {
+        expel_and_rotate(self, channel_id, members, MemberRemoval::Ban)
+    }
 
     /// Unban a set of channel members.
     fn unban_channel_members(&self, channel_id: &Id, members: &[Id]) -> BoxFuture<'_, Result<()>>;
 
-    /// Remove a set of channel members.
-    fn remove_channel_members(&self, channel_id: &Id, members: &[Id]) -> BoxFuture<'_, Result<()>>;
+    /// Remove a set of channel members, then rotate the channel session key
+    /// among the remaining members so the removed ones cannot read the later
+    /// messages.
+    fn remove_channel_members(&self, channel_id: &Id, members: &[Id]) -> BoxFuture<'_, Result<()>> {
+        expel_and_rotate(self, channel_id, members, MemberRemoval::Remove)
+    }
+
+    /// Remove or ban a set of channel members on the messaging service,
+    /// leaving the channel session key as it is. Backs
+    /// [`remove_channel_members`](Self::remove_channel_members) and
+    /// [`ban_channel_members`](Self::ban_channel_members), which are the
+    /// ones to call.
+    fn expel_channel_members(
+        &self,
+        channel_id: &Id,
+        members:    &[Id],
+        removal:    MemberRemoval,
+    ) -> BoxFuture<'_, Result<()>>;
 
     // -----------------------------------------------------------------
     // Contacts
@@ -266,6 +435,64 @@ pub trait MessagingClient: Send + Sync {
 
     /// Delete all contacts.
     fn clear_contacts(&self) -> BoxFuture<'_, Result<()>>;
+
+    /// Block a contact, keeping it and its history. Its messages are
+    /// dropped before they are decrypted or stored, on every device of the
+    /// user, and the messaging service stops delivering them if it can.
+    fn block_contact(&self, id: &Id) -> BoxFuture<'_, Result<()>>;
+
+    /// Unblock a contact, its messages are delivered again.
+    fn unblock_contact(&self, id: &Id) -> BoxFuture<'_, Result<()>>;
+
+    /// The ids of the blocked contacts.
+    fn blocked_contacts(&self) -> Vec<Id>;
+
+    /// Write the contacts in `format`, never with their session keys.
+    fn export_contacts(&self, writer: &mut dyn Write, format: ContactFormat) -> Result<()>;
+
+    /// Add the contacts read in `format` that are not in the contact list
+    /// yet. They have no session key until the key exchange, completed by
+    /// [`add_friend`](Self::add_friend). Fails with
+    /// [`Error::Encoding`] on input not in `format` at all, invalid and
+    /// duplicate entries are skipped and reported.
+    fn import_contacts(&self, reader: &mut dyn Read, format: ContactFormat) -> Result<ImportReport>;
+
+    /// The last known presence of a contact, `None` if nothing was heard
+    /// from it since the client connected.
+    fn get_presence(&self, contact_id: &Id) -> Option<Presence>;
+
+    // -----------------------------------------------------------------
+    // Diagnostics
+    // -----------------------------------------------------------------
+
+    /// Packets received from the messaging server that a broker should
+    /// never send. They are ignored, a growing count hints at a broker
+    /// speaking another protocol version.
+    fn unexpected_packets(&self) -> u64;
+
+    /// Messages from contacts and channel members dropped over their
+    /// inbound rate limit, while their sender was muted, or because it is
+    /// blocked.
+    fn dropped_messages(&self) -> u64;
+
+    /// RPC responses and notifications ignored because they did not come
+    /// from the peer they must come from, such as a response from another
+    /// peer than the request was sent to.
+    fn spoofed_responses(&self) -> u64;
+}
+
+// The ousted members are gone from the service before the new key goes out,
+// so it only reaches the remaining members.
+fn expel_and_rotate<'a, C>(client: &'a C, channel_id: &Id, members: &[Id], removal: MemberRemoval) -> BoxFuture<'a, Result<()>>
+where
+    C: MessagingClient + ?Sized,
+{
+    let channel_id = *channel_id;
+    let members = members.to_vec();
+    Box::pin(async move {
+        client.expel_channel_members(&channel_id, &members, removal).await?;
+        client.rotate_channel_session_key(&channel_id).await
+    })
 }
 
 // ---------------------------------------------------------------------------
@@ -275,12 +502,19 @@ pub trait MessagingClient: Send + Sync {
 /// Fluent builder for constructing a [`MessagingClient`].
 pub struct MessagingClientBuilder {
     service_peer_id:  Option<Id>,
+    service_peer:     Option<PeerInfo>,
     service_endpoint: Option<url::Url>,
+    api_url:          Option<Url>,
+    service_ids:      Option<ServiceIds>,
     user_key:         Option<crate::signature::KeyPair>,
     device_key:       Option<crate::signature::KeyPair>,
+    device_name:      Option<String>,
     data_dir:         Option<std::path::PathBuf>,
+    request_timeout:  Option<Duration>,
+    inbound_limit:    InboundRateLimit,
 
     connection_listener:     Option<Arc<dyn ConnectionListener>>,
+    connection_events:       Option<Arc<ConnectionEvents>>,
     message_listener:        Option<Arc<dyn MessageListener>>,
     channel_listener:        Option<Arc<dyn ChannelListener>>,
     contact_listener:        Option<Arc<dyn ContactListener>>,
@@ -298,11 +532,18 @@ impl MessagingClientBuilder {
     pub fn new() -> Self {
         Self {
             service_peer_id:  None,
+            service_peer:     None,
             service_endpoint: None,
+            api_url:          None,
+            service_ids:      None,
             user_key:         None,
             device_key:       None,
+            device_name:      None,
             data_dir:         None,
+            request_timeout:  None,
+            inbound_limit:    InboundRateLimit::default(),
             connection_listener:     None,
+            connection_events:       None,
             message_listener:        None,
             channel_listener:        None,
             contact_listener:        None,
@@ -315,10 +556,27 @@ impl MessagingClientBuilder {
         self.service_peer_id = Some(id); self
     }
 
+    /// The messaging service peer, its endpoint is the default API base URL.
+    pub fn service_peer(mut self, peer: PeerInfo) -> Self {
+        self.service_peer_id = Some(*peer.id());
+        self.service_peer = Some(peer); self
+    }
+
     pub fn service_endpoint(mut self, url: url::Url) -> Self {
         self.service_endpoint = Some(url); self
     }
 
+    /// Override the API base URL derived from the service peer, mostly for
+    /// tests and staging environments.
+    pub fn api_url(mut self, url: Url) -> Self {
+        self.api_url = Some(url); self
+    }
+
+    /// Service ids cached by the caller, skips the discovery round-trip.
+    pub fn service_ids(mut self, ids: ServiceIds) -> Self {
+        self.service_ids = Some(ids); self
+    }
+
     pub fn user_key(mut self, kp: crate::signature::KeyPair) -> Self {
         self.user_key = Some(kp); self
     }
@@ -327,14 +585,103 @@ impl MessagingClientBuilder {
         self.device_key = Some(kp); self
     }
 
+    /// The human name of this device, NFC normalized and trimmed. Fails
+    /// with [`Error::Argument`] on an empty name, one longer than
+    /// [`MAX_DEVICE_NAME_LEN`](device_registry::MAX_DEVICE_NAME_LEN)
+    /// characters or one with control characters.
+    pub fn device_name(mut self, name: &str) -> Result<Self> {
+        self.device_name = Some(device_registry::check_device_name(name)?);
+        Ok(self)
+    }
+
     pub fn data_dir(mut self, dir: std::path::PathBuf) -> Self {
         self.data_dir = Some(dir); self
     }
 
+    /// How long a request to the messaging service waits for its response
+    /// before failing with [`Error::Timeout`], defaults to
+    /// [`DEFAULT_REQUEST_TIMEOUT`]. Zero keeps the default.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout).filter(|t| !t.is_zero()); self
+    }
+
+    /// Limits on the messages accepted from each contact, and from each
+    /// member of a channel. Responses and notifications of the messaging
+    /// service are never limited.
+    pub fn inbound_rate_limit(mut self, limit: InboundRateLimit) -> Self {
+        self.inbound_limit = limit; self
+    }
+
+    /// Restore an account bundle written by
+    /// [`MessagingClient::export_account`] into a fresh repository under
+    /// `data_dir` and return a builder set up with the restored keys.
+    ///
+    /// Fails with [`Error::Auth`] on a wrong password and with
+    /// [`Error::Encoding`] on an incomplete or tampered bundle, in both
+    /// cases before anything is written.
+    pub fn from_account_backup(reader: impl Read, password: &str, data_dir: PathBuf) -> Result<Self> {
+        let (user_key, device_key) = account_backup::import_account(reader, password, &data_dir)?;
+        Ok(Self::new()
+            .user_key(user_key)
+            .device_key(device_key)
+            .data_dir(data_dir))
+    }
+
+    /// The user keypair given by [`user_key`](Self::user_key), if any.
+    pub fn user_keypair(&self) -> Option<&crate::signature::KeyPair> {
+        self.user_key.as_ref()
+    }
+
+    /// The device keypair given by [`device_key`](Self::device_key), if any.
+    pub fn device_keypair(&self) -> Option<&crate::signature::KeyPair> {
+        self.device_key.as_ref()
+    }
+
+    /// The device name given by [`device_name`](Self::device_name), if any.
+    pub fn device_label(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// The data directory given by [`data_dir`](Self::data_dir), if any.
+    pub fn data_path(&self) -> Option<&std::path::Path> {
+        self.data_dir.as_deref()
+    }
+
+    /// The service request timeout in effect.
+    pub fn rpc_timeout(&self) -> Duration {
+        self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT)
+    }
+
+    /// The inbound rate limit in effect.
+    pub fn inbound_limit(&self) -> &InboundRateLimit {
+        &self.inbound_limit
+    }
+
     pub fn connection_listener(mut self, l: Arc<dyn ConnectionListener>) -> Self {
         self.connection_listener = Some(l); self
     }
 
+    /// Stream the connection events of the client, alongside the
+    /// [`connection_listener`](Self::connection_listener) if any. Every call
+    /// subscribes another stream.
+    pub fn connection_events(&mut self) -> impl Stream<Item = StreamItem<ConnectionEvent>> + Send + 'static {
+        self.connection_events
+            .get_or_insert_with(|| Arc::new(ConnectionEvents::new()))
+            .subscribe()
+    }
+
+    /// The listeners the client reports its connection events to.
+    pub fn connection_listeners(&self) -> Vec<Arc<dyn ConnectionListener>> {
+        let mut listeners = Vec::new();
+        if let Some(l) = self.connection_listener.as_ref() {
+            listeners.push(l.clone());
+        }
+        if let Some(events) = self.connection_events.as_ref() {
+            listeners.push(events.clone() as Arc<dyn ConnectionListener>);
+        }
+        listeners
+    }
+
     pub fn message_listener(mut self, l: Arc<dyn MessageListener>) -> Self {
         self.message_listener = Some(l); self
     }
@@ -355,3 +702,150 @@ impl MessagingClientBuilder {
         self.friend_request_listener = Some(l); self
     }
 }
+
+impl MessagingClientBuilder {
+    /// The API base URL: the explicit override if given, otherwise derived
+    /// from the service peer's endpoint.
+    pub fn resolve_api_url(&self) -> Result<Url> {
+        let derived = self.service_peer.as_ref().map(|peer| {
+            parse_api_url(peer.endpoint())
+        });
+
+        if let Some(url) = self.api_url.as_ref() {
+            check_api_url(url)?;
+            if let Some(Ok(derived)) = derived.as_ref() {
+                if derived != url {
+                    warn!("API url {url} overrides {derived} from the service peer");
+                }
+            }
+            return Ok(url.clone());
+        }
+
+        match derived {
+            Some(url) => url,
+            None => Err(Error::State(
+                "No API url: neither a service peer nor an API url was given".into()
+            )),
+        }
+    }
+
+    /// The service ids: the cached ones if given, otherwise looked up by
+    /// `discovery` from the API base URL.
+    pub async fn resolve_service_ids(&self, discovery: &dyn ServiceDiscovery) -> Result<ServiceIds> {
+        if let Some(ids) = self.service_ids.as_ref() {
+            return Ok(ids.clone());
+        }
+
+        let url = self.resolve_api_url()?;
+        discovery.service_ids(&url).await
+    }
+
+    /// What the service tells about itself, its device limit among others.
+    pub async fn service_info(&self, registry: &dyn DeviceRegistry) -> Result<ServiceInfo> {
+        let url = self.resolve_api_url()?;
+        registry.service_info(&url).await
+    }
+
+    /// The devices registered to the user, this one flagged
+    /// [`is_current`](DeviceInfo::is_current).
+    pub async fn devices(&self, registry: &dyn DeviceRegistry) -> Result<Vec<DeviceInfo>> {
+        let (user, device) = self.keypairs()?;
+        let url = self.resolve_api_url()?;
+        let current = Id::from(device.public_key());
+
+        let mut devices = registry.devices(&url, user).await?;
+        devices.iter_mut().for_each(|d| {
+            let is_current = d.id() == &current;
+            d.set_current(is_current);
+        });
+        Ok(devices)
+    }
+
+    /// Register this device under the [`device_name`](Self::device_name),
+    /// suffixed as `name-2`, `name-3`, ... when another device of the user
+    /// has it already. Fails with [`Error::DeviceLimitExceeded`] when the
+    /// user has as many devices as the service allows.
+    pub async fn register_device(&self, registry: &dyn DeviceRegistry, app_name: Option<&str>) -> Result<DeviceRegistration> {
+        let (user, device) = self.keypairs()?;
+        let Some(requested) = self.device_name.as_ref() else {
+            return Err(Error::State("No device name given".into()));
+        };
+
+        let url = self.resolve_api_url()?;
+        let device_id = Id::from(device.public_key());
+
+        // Registering again keeps the name of this device free for itself.
+        let devices = registry.devices(&url, user).await?;
+        let taken = devices.iter()
+            .filter(|d| d.id() != &device_id)
+            .map(|d| d.name());
+        let name = device_registry::unique_device_name(requested, taken);
+
+        let request = DeviceRequest {
+            user,
+            device,
+            name: &name,
+            app_name,
+        };
+        registry.register_device(&url, &request).await?;
+        Ok(DeviceRegistration::new(device_id, name, requested.clone()))
+    }
+
+    /// Write the account held in the [`data_dir`](Self::data_dir) as a
+    /// password protected bundle, the same [`MessagingClient::export_account`]
+    /// writes, restored by [`from_account_backup`](Self::from_account_backup).
+    /// Fails with [`Error::State`] without the keys or an existing repository.
+    pub fn export_account(&self, password: &str, writer: impl Write) -> Result<()> {
+        let (user, device) = self.keypairs()?;
+        let repository = self.repository()?;
+        account_backup::export_account(&repository, user, device, password, writer)
+    }
+
+    /// Write the contacts held in the [`data_dir`](Self::data_dir) in
+    /// `format`, as [`MessagingClient::export_contacts`] does.
+    pub fn export_contacts(&self, writer: impl Write, format: ContactFormat) -> Result<()> {
+        contact_transfer::export_contacts(&self.repository()?, writer, format)
+    }
+
+    /// Add the contacts read in `format` to the repository in the
+    /// [`data_dir`](Self::data_dir), as [`MessagingClient::import_contacts`]
+    /// does.
+    pub fn import_contacts(&self, reader: impl Read, format: ContactFormat) -> Result<ImportReport> {
+        contact_transfer::import_contacts(&self.repository()?, reader, format)
+    }
+
+    // The existing repository in the data directory, opened with the device key.
+    fn repository(&self) -> Result<Database> {
+        let (_, device) = self.keypairs()?;
+        let Some(data_dir) = self.data_dir.as_ref() else {
+            return Err(Error::State("No data directory given".into()));
+        };
+        if !Database::exists(data_dir) {
+            return Err(Error::State(format!(
+                "{} holds no messaging repository", data_dir.display()
+            )));
+        }
+        Database::open(data_dir, device.private_key())
+    }
+
+    fn keypairs(&self) -> Result<(&crate::signature::KeyPair, &crate::signature::KeyPair)> {
+        match (self.user_key.as_ref(), self.device_key.as_ref()) {
+            (Some(user), Some(device)) => Ok((user, device)),
+            _ => Err(Error::State("Both the user and the device key are required".into())),
+        }
+    }
+}
+
+fn parse_api_url(input: &str) -> Result<Url> {
+    let url = Url::parse(input).map_err(|e| {
+        Error::Argument(format!("Invalid API url {input}: {e}"))
+    })?;
+    check_api_url(&url).map(|_| url)
+}
+
+fn check_api_url(url: &Url) -> Result<()> {
+    if !matches!(url.scheme(), "http" | "https") || url.host().is_none() {
+        return Err(Error::Argument(format!("Invalid API url {url}: expected http(s) with a host")));
+    }
+    Ok(())
+}
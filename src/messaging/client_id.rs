@@ -0,0 +1,159 @@
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use log::warn;
+use sha2::{Digest, Sha256};
+use rumqttc::{ConnectionError, ConnectReturnCode};
+
+use crate::Id;
+use crate::messaging::{Error, Result};
+
+const V1_DOMAIN: &[u8] = b"boson-mqtt-client-id";
+const V1_BYTES: usize = 16;
+
+/// How the MQTT client id of a device is derived from its id.
+///
+/// The broker keys persistent sessions by client id, so a released scheme
+/// never changes: a device derives the same id with every crate version
+/// speaking that scheme. A new derivation gets a new variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    /// Base58 of the MD5 of the device id, used before schemes were
+    /// versioned. Only kept to resume the sessions opened with it.
+    Legacy,
+    /// Base58 of the first 16 bytes of
+    /// SHA-256("boson-mqtt-client-id" || device id).
+    V1,
+}
+
+impl Scheme {
+    /// The scheme new sessions are opened with.
+    pub const CURRENT: Scheme = Scheme::V1;
+
+    /// The client id of `device` in this scheme.
+    pub fn derive(&self, device: &Id) -> String {
+        match self {
+            Scheme::Legacy => {
+                bs58::encode(md5::compute(device.as_bytes()).0).into_string()
+            },
+            Scheme::V1 => {
+                let digest = Sha256::new()
+                    .chain_update(V1_DOMAIN)
+                    .chain_update(device.as_bytes())
+                    .finalize();
+                bs58::encode(&digest[..V1_BYTES]).into_string()
+            },
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        match self {
+            Scheme::Legacy  => "legacy",
+            Scheme::V1      => "v1",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "legacy"    => Some(Scheme::Legacy),
+            "v1"        => Some(Scheme::V1),
+            _           => None,
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.tag())
+    }
+}
+
+/// One way to connect to the broker: the client id and whether the
+/// session kept under it is dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attempt {
+    /// How the client id was derived.
+    pub scheme       : Scheme,
+    /// The MQTT client id.
+    pub client_id    : String,
+    /// Whether the broker drops the session kept under the id.
+    pub clean_session: bool,
+}
+
+impl Attempt {
+    fn new(scheme: Scheme, device: &Id, clean_session: bool) -> Self {
+        Self {
+            scheme,
+            client_id: scheme.derive(device),
+            clean_session,
+        }
+    }
+}
+
+/// The client ids to try in order, given the scheme of the last session
+/// the broker accepted, None if unknown.
+///
+/// Only the session of the last scheme is resumed. Connecting with any
+/// other id starts over with a clean session, the subscriptions are made
+/// again after every connect. Devices of crate versions before the session
+/// marker have no last scheme, their sessions were all opened with the
+/// legacy id.
+///
+/// Without compatibility only the current scheme is tried. With it, the
+/// legacy id is tried when the broker rejects the current one, and first
+/// when the last session was a legacy one.
+pub fn attempts(device: &Id, last: Option<Scheme>, compatible: bool) -> Vec<Attempt> {
+    let current = Attempt::new(Scheme::CURRENT, device, last != Some(Scheme::CURRENT));
+    if !compatible {
+        return vec![current];
+    }
+
+    let legacy = Attempt::new(Scheme::Legacy, device,
+        last.is_some() && last != Some(Scheme::Legacy)
+    );
+    match last {
+        Some(Scheme::Legacy) => vec![legacy, current],
+        _ => vec![current, legacy],
+    }
+}
+
+/// Whether the broker refused the client id itself, the next attempt may
+/// get through with another one.
+pub fn is_rejected(error: &ConnectionError) -> bool {
+    matches!(error, ConnectionError::ConnectionRefused(ConnectReturnCode::BadClientId))
+}
+
+/// Records the scheme of the last session the broker accepted.
+pub struct SessionMarker {
+    path: PathBuf,
+}
+
+impl SessionMarker {
+    /// A marker kept in the file at `path`.
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The scheme of the last accepted session, a missing or unreadable
+    /// marker counts as unknown.
+    pub fn load(&self) -> Option<Scheme> {
+        let tag = fs::read_to_string(&self.path).ok()?;
+        let scheme = Scheme::from_tag(tag.trim());
+        if scheme.is_none() {
+            warn!("Ignored unknown MQTT session marker {}", tag.trim());
+        }
+        scheme
+    }
+
+    /// Record `scheme` as the one of the last accepted session.
+    pub fn store(&self, scheme: Scheme) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| {
+                Error::State(format!("Creating session marker directory failed: {e}"))
+            })?;
+        }
+        fs::write(&self.path, scheme.tag()).map_err(|e| {
+            Error::State(format!("Writing session marker failed: {e}"))
+        })
+    }
+}
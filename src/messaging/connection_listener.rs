@@ -1,3 +1,11 @@
+use futures::Stream;
+
+use crate::dht::event_stream::{
+    EventBroadcast,
+    StreamItem,
+    DEFAULT_EVENT_STREAM_CAPACITY,
+};
+
 /// Receives connection lifecycle events from the messaging client.
 pub trait ConnectionListener: Send + Sync {
     /// Called when the client has started the connection attempt.
@@ -12,3 +20,57 @@ pub trait ConnectionListener: Send + Sync {
     /// Called when the connection drops or is closed.
     fn on_disconnected(&self) {}
 }
+
+/// A connection lifecycle event, as streamed by [`ConnectionEvents`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    Connecting,
+    Connected,
+    Ready,
+    Disconnected,
+}
+
+/// A [`ConnectionListener`] turning the events into streams, for async
+/// applications selecting over their event sources. A stream falling behind
+/// gets a [`StreamItem::Lagged`] item in place of the events it missed, the
+/// client never waits on it.
+pub struct ConnectionEvents {
+    events: EventBroadcast<ConnectionEvent>,
+}
+
+impl Default for ConnectionEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConnectionEvents {
+    pub fn new() -> Self {
+        Self {
+            events: EventBroadcast::new(DEFAULT_EVENT_STREAM_CAPACITY),
+        }
+    }
+
+    /// The events from now on, dropping the stream unsubscribes it.
+    pub fn subscribe(&self) -> impl Stream<Item = StreamItem<ConnectionEvent>> + Send + 'static {
+        self.events.subscribe()
+    }
+}
+
+impl ConnectionListener for ConnectionEvents {
+    fn on_connecting(&self) {
+        self.events.emit(ConnectionEvent::Connecting);
+    }
+
+    fn on_connected(&self) {
+        self.events.emit(ConnectionEvent::Connected);
+    }
+
+    fn on_ready(&self) {
+        self.events.emit(ConnectionEvent::Ready);
+    }
+
+    fn on_disconnected(&self) {
+        self.events.emit(ConnectionEvent::Disconnected);
+    }
+}
@@ -13,6 +13,23 @@ pub enum ContactType {
     Channel = 2,
 }
 
+impl TryFrom<i32> for ContactType {
+    type Error = &'static str;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ContactType::Auto),
+            1 => Ok(ContactType::Friend),
+            2 => Ok(ContactType::Channel),
+            _ => Err("Invalid ContactType value"),
+        }
+    }
+}
+
+impl From<ContactType> for i32 {
+    fn from(t: ContactType) -> i32 { t as i32 }
+}
+
 /// A contact entry in the local contact list.
 ///
 /// This trait mirrors the Java `Contact` interface and is implemented by both
@@ -1,4 +1,8 @@
+use std::time::Duration;
+
 use crate::messaging::contact::Contact;
+use crate::messaging::presence::Presence;
+use crate::messaging::self_sync::ReadState;
 use crate::Id;
 
 /// Receives events about changes to the local contact list.
@@ -14,4 +18,21 @@ pub trait ContactListener: Send + Sync {
 
     /// Called when every contact was cleared from the local list.
     fn on_contacts_cleared(&self) {}
+
+    /// Called when the presence of a contact has changed, including when an
+    /// online contact went silent and is now considered away.
+    fn on_presence_changed(&self, _contact_id: &Id, _presence: &Presence) {}
+
+    /// Called when a sender flooding a conversation was muted for the
+    /// duration, all its messages being dropped meanwhile. The conversation
+    /// is the sender itself or a channel, blocking it may be offered.
+    fn on_sender_muted(&self, _conversation_id: &Id, _sender: &Id, _duration: Duration) {}
+
+    /// Called when a contact was blocked or unblocked, on this device or on
+    /// another device of the user.
+    fn on_contact_blocked(&self, _contact_id: &Id, _blocked: bool) {}
+
+    /// Called when a conversation was marked read on another device of the
+    /// user, including the ones marked while this device was offline.
+    fn on_read_state_synced(&self, _state: &ReadState) {}
 }
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{as_ms, Id};
+use crate::messaging::{
+    Error,
+    Result,
+    contact::ContactType,
+    persistence::database::{Database, ContactRecord},
+};
+
+const NATIVE_FORMAT: &str = "boson-contacts";
+const NATIVE_VERSION: u32 = 1;
+
+const BOSON_ID_PROPERTY: &str = "X-BOSON-ID";
+const HOME_PEER_PROPERTY: &str = "X-BOSON-HOME-PEER";
+// Content lines longer than this many octets are folded, RFC 6350 3.2.
+const VCARD_LINE_OCTETS: usize = 75;
+
+/// The formats contacts are exported and imported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactFormat {
+    /// Boson JSON, keeping everything but the session keys: ids, type, home
+    /// peer, name, remark, block state and whether a session key is held.
+    Native,
+    /// vCard 4.0, one card per friend with its display name and the boson id
+    /// in an `X-BOSON-ID` property. Channels and the block state are lost.
+    VCard,
+}
+
+/// What became of one entry of an import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportOutcome {
+    /// Added as a contact waiting for a key exchange, see
+    /// [`ImportReport`].
+    Imported(Id),
+    /// Skipped, the contact list already holds it.
+    Duplicate(Id),
+    /// Skipped for the reason given.
+    Invalid(String),
+}
+
+/// The outcome of every entry of an import, in the order of the input.
+///
+/// Contacts are imported without session keys, pending a key exchange. A
+/// later `add_contact` with the key of the contact completes it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    outcomes: Vec<ImportOutcome>,
+}
+
+impl ImportReport {
+    pub fn outcomes(&self) -> &[ImportOutcome] {
+        &self.outcomes
+    }
+
+    pub fn imported(&self) -> Vec<Id> {
+        self.outcomes.iter().filter_map(|o| match o {
+            ImportOutcome::Imported(id) => Some(*id),
+            _ => None,
+        }).collect()
+    }
+
+    pub fn duplicates(&self) -> Vec<Id> {
+        self.outcomes.iter().filter_map(|o| match o {
+            ImportOutcome::Duplicate(id) => Some(*id),
+            _ => None,
+        }).collect()
+    }
+
+    /// The entries skipped as invalid, by their position in the input.
+    pub fn invalid(&self) -> Vec<(usize, &str)> {
+        self.outcomes.iter().enumerate().filter_map(|(i, o)| match o {
+            ImportOutcome::Invalid(reason) => Some((i, reason.as_str())),
+            _ => None,
+        }).collect()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct NativeContacts {
+    format: String,
+    version: u32,
+    contacts: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NativeContact {
+    id: String,
+    #[serde(rename = "type")]
+    contact_type: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    home_peer_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    remark: Option<String>,
+    #[serde(default)]
+    has_session_key: bool,
+    #[serde(default)]
+    blocked: bool,
+    #[serde(default)]
+    updated: u64,
+}
+
+// A contact read from the input, before it is checked against the list.
+struct Candidate {
+    id: Id,
+    contact_type: ContactType,
+    home_peer_id: Option<Id>,
+    name: Option<String>,
+    remark: Option<String>,
+    blocked: bool,
+}
+
+fn type_name(contact_type: ContactType) -> &'static str {
+    match contact_type {
+        ContactType::Auto => "auto",
+        ContactType::Friend => "friend",
+        ContactType::Channel => "channel",
+    }
+}
+
+fn parse_id(input: &str, what: &str) -> std::result::Result<Id, String> {
+    Id::try_from_base58(input.trim()).map_err(|_| format!("Invalid {what} {input}"))
+}
+
+// Writes the contacts of `repository` in the given format.
+pub(crate) fn export_contacts(repository: &Database, mut writer: impl Write, format: ContactFormat) -> Result<()> {
+    let contacts = repository.contacts()?;
+    let data = match format {
+        ContactFormat::Native => export_native(&contacts)?,
+        ContactFormat::VCard => export_vcard(&contacts),
+    };
+    writer.write_all(data.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Adds the contacts read in the given format to `repository`, without
+// session keys. Fails only on input that is not in the format at all, the
+// entries that cannot be imported are reported.
+pub(crate) fn import_contacts(repository: &Database, mut reader: impl Read, format: ContactFormat) -> Result<ImportReport> {
+    let mut data = String::new();
+    reader.read_to_string(&mut data)?;
+    let candidates = match format {
+        ContactFormat::Native => parse_native(&data)?,
+        ContactFormat::VCard => parse_vcard(&data),
+    };
+
+    let updated = as_ms!(SystemTime::now()) as u64;
+    let mut seen = HashSet::new();
+    let mut report = ImportReport::default();
+    for candidate in candidates {
+        let candidate = match candidate {
+            Ok(candidate) => candidate,
+            Err(reason) => {
+                report.outcomes.push(ImportOutcome::Invalid(reason));
+                continue;
+            }
+        };
+
+        let id = candidate.id;
+        if !seen.insert(id) || repository.contact(&id)?.is_some() {
+            report.outcomes.push(ImportOutcome::Duplicate(id));
+            continue;
+        }
+        repository.put_contact(&ContactRecord {
+            id,
+            contact_type: candidate.contact_type,
+            home_peer_id: candidate.home_peer_id,
+            name: candidate.name,
+            remark: candidate.remark,
+            session_key: None,
+            updated,
+            blocked: candidate.blocked,
+        })?;
+        report.outcomes.push(ImportOutcome::Imported(id));
+    }
+    Ok(report)
+}
+
+fn export_native(contacts: &[ContactRecord]) -> Result<String> {
+    let contacts = contacts.iter().map(|c| {
+        serde_json::to_value(NativeContact {
+            id: c.id.to_base58(),
+            contact_type: type_name(c.contact_type).into(),
+            home_peer_id: c.home_peer_id.map(|id| id.to_base58()),
+            name: c.name.clone(),
+            remark: c.remark.clone(),
+            has_session_key: c.session_key.is_some(),
+            blocked: c.blocked,
+            updated: c.updated,
+        })
+    }).collect::<std::result::Result<Vec<_>, _>>();
+
+    let contacts = NativeContacts {
+        format: NATIVE_FORMAT.into(),
+        version: NATIVE_VERSION,
+        contacts: contacts.map_err(|e| Error::Encoding(e.to_string()))?,
+    };
+    serde_json::to_string_pretty(&contacts).map_err(|e| {
+        Error::Encoding(format!("Failed to encode contacts: {e}"))
+    })
+}
+
+fn parse_native(data: &str) -> Result<Vec<std::result::Result<Candidate, String>>> {
+    let contacts = serde_json::from_str::<NativeContacts>(data).map_err(|e| {
+        Error::Encoding(format!("Failed to parse contacts: {e}"))
+    })?;
+    if contacts.format != NATIVE_FORMAT || contacts.version != NATIVE_VERSION {
+        return Err(Error::Encoding(format!(
+            "Unsupported contacts format {} version {}", contacts.format, contacts.version
+        )));
+    }
+
+    Ok(contacts.contacts.into_iter().map(|value| {
+        let c = serde_json::from_value::<NativeContact>(value)
+            .map_err(|e| format!("Invalid contact entry: {e}"))?;
+        let contact_type = match c.contact_type.as_str() {
+            "auto" => ContactType::Auto,
+            "friend" => ContactType::Friend,
+            "channel" => ContactType::Channel,
+            other => return Err(format!("Invalid contact type {other}")),
+        };
+        Ok(Candidate {
+            id: parse_id(&c.id, "contact id")?,
+            contact_type,
+            home_peer_id: c.home_peer_id.as_deref().map(|id| parse_id(id, "home peer id")).transpose()?,
+            name: c.name,
+            remark: c.remark,
+            blocked: c.blocked,
+        })
+    }).collect())
+}
+
+fn export_vcard(contacts: &[ContactRecord]) -> String {
+    let mut out = String::new();
+    for c in contacts.iter().filter(|c| c.contact_type != ContactType::Channel) {
+        let id = c.id.to_base58();
+        let name = c.remark.as_deref().or(c.name.as_deref()).unwrap_or(&id);
+
+        let mut lines = vec![
+            "BEGIN:VCARD".to_string(),
+            "VERSION:4.0".to_string(),
+            format!("FN:{}", escape(name)),
+        ];
+        if let (Some(_), Some(nickname)) = (c.remark.as_ref(), c.name.as_ref()) {
+            lines.push(format!("NICKNAME:{}", escape(nickname)));
+        }
+        lines.push(format!("{BOSON_ID_PROPERTY}:{id}"));
+        if let Some(peer) = c.home_peer_id.as_ref() {
+            lines.push(format!("{HOME_PEER_PROPERTY}:{}", peer.to_base58()));
+        }
+        lines.push("END:VCARD".to_string());
+
+        for line in lines {
+            fold(&line, &mut out);
+        }
+    }
+    out
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            ',' => escaped.push_str("\\,"),
+            ';' => escaped.push_str("\\;"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {},
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            unescaped.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push('\n'),
+            Some(ch) => unescaped.push(ch),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Appends the content line, folded at character boundaries and ended with
+// CRLF.
+fn fold(line: &str, out: &mut String) {
+    let mut octets = 0;
+    for ch in line.chars() {
+        if octets + ch.len_utf8() > VCARD_LINE_OCTETS {
+            out.push_str("\r\n ");
+            octets = 1;
+        }
+        out.push(ch);
+        octets += ch.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+// A content line: the property name without its group, the parameters and
+// the raw value.
+struct ContentLine {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl ContentLine {
+    fn parse(line: &str) -> Option<Self> {
+        let (head, value) = split_unquoted(line, ':')?;
+        let mut parts = head.split(';');
+        let name = parts.next()?.trim();
+        let name = name.rsplit('.').next().unwrap_or(name).to_ascii_uppercase();
+        let params = parts.map(|param| match param.split_once('=') {
+            Some((k, v)) => (k.trim().to_ascii_uppercase(), v.trim().trim_matches('"').to_string()),
+            // vCard 2.1 allows bare parameter values, such as QUOTED-PRINTABLE.
+            None => (String::new(), param.trim().to_string()),
+        }).collect();
+        Some(Self { name, params, value: value.to_string() })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params.iter()
+            .find(|(k, v)| k == key || (k.is_empty() && v.eq_ignore_ascii_case(key)))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn is_quoted_printable(&self) -> bool {
+        self.param("ENCODING").is_some_and(|v| v.eq_ignore_ascii_case("QUOTED-PRINTABLE"))
+            || self.param("QUOTED-PRINTABLE").is_some()
+    }
+
+    // The value as text. Only UTF-8 is understood, other charsets are read
+    // as far as they agree with it.
+    fn text(&self) -> String {
+        let value = match self.is_quoted_printable() {
+            true => decode_quoted_printable(&self.value),
+            false => self.value.clone(),
+        };
+        unescape(value.trim())
+    }
+}
+
+// Splits at the first separator outside a double quoted parameter value.
+fn split_unquoted(line: &str, separator: char) -> Option<(&str, &str)> {
+    let mut quoted = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            ch if ch == separator && !quoted => return Some((&line[..i], &line[i + 1..])),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'=' {
+            if let Some(byte) = value.get(i + 1..i + 3).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Joins folded lines: those starting with a space or tab continue the line
+// before, and a quoted-printable line ending in '=' goes on in the next.
+fn unfold(data: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut soft_break = false;
+    for raw in data.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if soft_break {
+            if let Some(last) = lines.last_mut() {
+                last.pop();
+                last.push_str(raw);
+            }
+        } else if let (Some(rest), Some(last)) = (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            last.push_str(rest);
+        } else if raw.trim().is_empty() {
+            continue;
+        } else {
+            lines.push(raw.to_string());
+        }
+
+        soft_break = lines.last().is_some_and(|line| {
+            line.ends_with('=') && line.to_ascii_uppercase().contains("QUOTED-PRINTABLE")
+        });
+    }
+    lines
+}
+
+fn parse_vcard(data: &str) -> Vec<std::result::Result<Candidate, String>> {
+    let mut cards = Vec::new();
+    let mut card: Option<Vec<ContentLine>> = None;
+    for line in unfold(data) {
+        let Some(line) = ContentLine::parse(&line) else {
+            continue;
+        };
+        match (line.name.as_str(), card.as_mut()) {
+            ("BEGIN", _) if line.value.trim().eq_ignore_ascii_case("VCARD") => {
+                if card.replace(Vec::new()).is_some() {
+                    cards.push(Err("vCard without END".to_string()));
+                }
+            },
+            ("END", Some(_)) if line.value.trim().eq_ignore_ascii_case("VCARD") => {
+                cards.push(vcard_contact(card.take().unwrap()));
+            },
+            (_, Some(lines)) => lines.push(line),
+            (_, None) => {},
+        }
+    }
+    if card.is_some() {
+        cards.push(Err("vCard without END".to_string()));
+    }
+    cards
+}
+
+fn vcard_contact(lines: Vec<ContentLine>) -> std::result::Result<Candidate, String> {
+    let property = |name: &str| lines.iter()
+        .find(|line| line.name == name)
+        .map(|line| line.text())
+        .filter(|text| !text.is_empty());
+
+    let id = property(BOSON_ID_PROPERTY).ok_or_else(|| format!("vCard without {BOSON_ID_PROPERTY}"))?;
+    // N is Family;Given;Additional;Prefixes;Suffixes, used without FN.
+    let name = property("N").map(|n| {
+        let parts = n.split(';').map(str::trim).collect::<Vec<_>>();
+        [parts.get(3), parts.get(1), parts.get(2), parts.first(), parts.get(4)]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }).filter(|name| !name.is_empty());
+
+    Ok(Candidate {
+        id: parse_id(&id, BOSON_ID_PROPERTY)?,
+        contact_type: ContactType::Friend,
+        home_peer_id: property(HOME_PEER_PROPERTY).map(|id| parse_id(&id, HOME_PEER_PROPERTY)).transpose()?,
+        name: property("NICKNAME"),
+        remark: property("FN").or(name),
+        blocked: false,
+    })
+}
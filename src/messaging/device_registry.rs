@@ -0,0 +1,380 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use reqwest::{Client, Response};
+use base64::{engine::general_purpose, Engine as _};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+use crate::{Id, signature};
+use crate::messaging::{
+    client::BoxFuture,
+    capabilities::ServerCapabilities,
+    errors::{Error, Result},
+};
+
+/// The longest device name accepted, in characters.
+pub const MAX_DEVICE_NAME_LEN: usize = 64;
+
+// Registration refused, the user has as many devices as the service allows.
+pub(crate) const DEVICE_LIMIT_EXCEEDED: i32 = -11;
+
+/// Checks a device name and returns it NFC normalized and trimmed.
+pub(crate) fn check_device_name(name: &str) -> Result<String> {
+    let name = name.trim().nfc().collect::<String>();
+    if name.is_empty() {
+        return Err(Error::Argument("Device name is empty".into()));
+    }
+    if name.chars().count() > MAX_DEVICE_NAME_LEN {
+        return Err(Error::Argument(format!(
+            "Device name is longer than {MAX_DEVICE_NAME_LEN} characters"
+        )));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(Error::Argument("Device name has control characters".into()));
+    }
+    Ok(name)
+}
+
+/// `name` if no other device is called so, otherwise the first of `name-2`,
+/// `name-3`, ... still free. Names are compared case-insensitively.
+pub(crate) fn unique_device_name<'a>(name: &str, taken: impl IntoIterator<Item = &'a str>) -> String {
+    let taken = taken.into_iter()
+        .map(|n| n.to_lowercase())
+        .collect::<std::collections::HashSet<_>>();
+
+    if !taken.contains(&name.to_lowercase()) {
+        return name.to_string();
+    }
+
+    (2..).map(|n| {
+        let suffix = format!("-{n}");
+        // Keep the suffixed name within the limit.
+        let keep = MAX_DEVICE_NAME_LEN.saturating_sub(suffix.len());
+        let base = name.chars().take(keep).collect::<String>();
+        format!("{base}{suffix}")
+    })
+    .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+    .unwrap()
+}
+
+/// The typed error of a refused registration, from the code of an RPC or
+/// HTTP error and the limit it carries, if any.
+pub(crate) fn device_error(code: i32, limit: Option<u32>) -> Option<Error> {
+    match code {
+        DEVICE_LIMIT_EXCEEDED => Some(Error::DeviceLimitExceeded { limit }),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonError {
+    code: i32,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+/// The error of a failed API request, from its status and its body.
+pub(crate) fn api_error(status: u16, body: &str) -> Error {
+    match serde_json::from_str::<JsonError>(body) {
+        Ok(e) => device_error(e.code, e.limit).unwrap_or(Error::Protocol {
+            code: e.code,
+            message: e.message,
+        }),
+        Err(_) => Error::State(format!("Http error: status {status}")),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(non_snake_case)]
+pub(crate) struct JsonDevice {
+    id: String,
+    name: String,
+    #[serde(default)]
+    appName: Option<String>,
+    #[serde(default)]
+    created: u64,
+}
+
+impl JsonDevice {
+    pub(crate) fn device(self) -> Result<DeviceInfo> {
+        let Ok(id) = Id::try_from(self.id.as_str()) else {
+            return Err(Error::State("Http error: invalid device id".into()));
+        };
+        Ok(DeviceInfo::new(id, &self.name, self.appName.as_deref(), self.created))
+    }
+}
+
+/// A device registered to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    id: Id,
+    name: String,
+    app_name: Option<String>,
+    created: u64,
+    is_current: bool,
+}
+
+impl DeviceInfo {
+    pub fn new(id: Id, name: &str, app_name: Option<&str>, created: u64) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            app_name: app_name.map(|v| v.to_string()),
+            created,
+            is_current: false,
+        }
+    }
+
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn app_name(&self) -> Option<&str> {
+        self.app_name.as_deref()
+    }
+
+    /// Registration time, in milliseconds since the epoch.
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Whether this is the device the client runs on.
+    pub fn is_current(&self) -> bool {
+        self.is_current
+    }
+
+    pub(crate) fn set_current(&mut self, current: bool) {
+        self.is_current = current;
+    }
+}
+
+/// What the messaging service tells about itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceInfo {
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    max_devices: Option<u32>,
+    #[serde(flatten)]
+    capabilities: ServerCapabilities,
+}
+
+impl ServiceInfo {
+    pub fn new(version: Option<&str>, max_devices: Option<u32>) -> Self {
+        Self {
+            version: version.map(|v| v.to_string()),
+            max_devices,
+            capabilities: ServerCapabilities::default(),
+        }
+    }
+
+    pub fn with_capabilities(mut self, capabilities: ServerCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// The most devices a user may register, `None` if the service does not
+    /// tell.
+    pub fn max_devices(&self) -> Option<u32> {
+        self.max_devices
+    }
+
+    /// The protocol version and methods the service advertises.
+    pub fn capabilities(&self) -> &ServerCapabilities {
+        &self.capabilities
+    }
+}
+
+/// A device registration to send to the service.
+#[derive(Debug, Clone)]
+pub struct DeviceRequest<'a> {
+    pub user: &'a signature::KeyPair,
+    pub device: &'a signature::KeyPair,
+    pub name: &'a str,
+    pub app_name: Option<&'a str>,
+}
+
+/// The outcome of a device registration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceRegistration {
+    device_id: Id,
+    name: String,
+    requested_name: String,
+}
+
+impl DeviceRegistration {
+    pub(crate) fn new(device_id: Id, name: String, requested_name: String) -> Self {
+        Self { device_id, name, requested_name }
+    }
+
+    pub fn device_id(&self) -> &Id {
+        &self.device_id
+    }
+
+    /// The name the device is registered under.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name asked for, differs from [`name`](Self::name) when another
+    /// device of the user had it already.
+    pub fn requested_name(&self) -> &str {
+        &self.requested_name
+    }
+
+    pub fn is_renamed(&self) -> bool {
+        self.name != self.requested_name
+    }
+}
+
+/// The device endpoints of the messaging service API.
+///
+/// Registrations refused over the user's device limit fail with
+/// [`Error::DeviceLimitExceeded`].
+pub trait DeviceRegistry: Send + Sync {
+    fn service_info<'a>(&'a self, api_url: &'a Url) -> BoxFuture<'a, Result<ServiceInfo>>;
+
+    fn devices<'a>(&'a self, api_url: &'a Url, user: &'a signature::KeyPair) -> BoxFuture<'a, Result<Vec<DeviceInfo>>>;
+
+    fn register_device<'a>(&'a self, api_url: &'a Url, request: &'a DeviceRequest<'a>) -> BoxFuture<'a, Result<()>>;
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonRegistration<'a> {
+    user_id: String,
+    device_id: String,
+    device_name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    app_name: Option<&'a str>,
+    nonce: String,
+    user_sig: String,
+    device_sig: String,
+}
+
+fn base64(bytes: &[u8]) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// [`DeviceRegistry`] over the service's HTTP API.
+#[derive(Debug, Default, Clone)]
+pub struct HttpDeviceRegistry;
+
+impl HttpDeviceRegistry {
+    fn client() -> Result<Client> {
+        Client::builder()
+            .user_agent("rboson")
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| Error::Argument(format!("Failed to create http client: {e}")))
+    }
+
+    fn join(api_url: &Url, path: &str) -> Result<Url> {
+        api_url.join(path).map_err(|e| {
+            Error::Argument(format!("Invalid API url {api_url}: {e}"))
+        })
+    }
+
+    async fn check(rsp: Response) -> Result<Response> {
+        let status = rsp.status();
+        if status.is_success() {
+            return Ok(rsp);
+        }
+        let body = rsp.text().await.unwrap_or_default();
+        Err(api_error(status.as_u16(), &body))
+    }
+
+    fn nonce() -> Vec<u8> {
+        crate::random_bytes(24)
+    }
+
+    fn sign(data: &[u8], kp: &signature::KeyPair) -> Result<Vec<u8>> {
+        signature::sign_into(data, kp.private_key()).map_err(|e| {
+            Error::Auth(format!("Signing error: {e}"))
+        })
+    }
+}
+
+impl DeviceRegistry for HttpDeviceRegistry {
+    fn service_info<'a>(&'a self, api_url: &'a Url) -> BoxFuture<'a, Result<ServiceInfo>> {
+        Box::pin(async move {
+            let url = Self::join(api_url, "/api/v1/service/info")?;
+            let rsp = Self::client()?.get(url)
+                .header("Accept", "application/json")
+                .send()
+                .await
+                .map_err(|e| Error::State(format!("Sending http request error {e}")))?;
+
+            Self::check(rsp).await?.json::<ServiceInfo>().await.map_err(|e| {
+                Error::Encoding(format!("Deserializing json error: {e}"))
+            })
+        })
+    }
+
+    fn devices<'a>(&'a self, api_url: &'a Url, user: &'a signature::KeyPair) -> BoxFuture<'a, Result<Vec<DeviceInfo>>> {
+        Box::pin(async move {
+            let user_id = Id::from(user.public_key());
+            let mut url = Self::join(api_url, &format!("/api/v1/users/{user_id}/devices"))?;
+
+            let nonce = Self::nonce();
+            let sig = Self::sign(&nonce, user)?;
+            url.query_pairs_mut()
+                .append_pair("nonce", &base64(&nonce))
+                .append_pair("sig", &base64(&sig));
+
+            let rsp = Self::client()?.get(url)
+                .header("Accept", "application/json")
+                .send()
+                .await
+                .map_err(|e| Error::State(format!("Sending http request error {e}")))?;
+
+            Self::check(rsp).await?.json::<Vec<JsonDevice>>().await.map_err(|e| {
+                Error::Encoding(format!("Deserializing json error: {e}"))
+            })?.into_iter().map(JsonDevice::device).collect()
+        })
+    }
+
+    fn register_device<'a>(&'a self, api_url: &'a Url, request: &'a DeviceRequest<'a>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let url = Self::join(api_url, "/api/v1/devices")?;
+
+            let user_id = Id::from(request.user.public_key());
+            let device_id = Id::from(request.device.public_key());
+            let nonce = Self::nonce();
+
+            // The user consents to this very device, the device proves its key.
+            let mut consent = nonce.clone();
+            consent.extend_from_slice(device_id.as_bytes());
+
+            let body = JsonRegistration {
+                user_id: user_id.to_base58(),
+                device_id: device_id.to_base58(),
+                device_name: request.name,
+                app_name: request.app_name,
+                nonce: base64(&nonce),
+                user_sig: base64(&Self::sign(&consent, request.user)?),
+                device_sig: base64(&Self::sign(&nonce, request.device)?),
+            };
+
+            let rsp = Self::client()?.post(url)
+                .header("Accept", "application/json")
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| Error::State(format!("Sending http request error {e}")))?;
+
+            Self::check(rsp).await.map(|_| ())
+        })
+    }
+}
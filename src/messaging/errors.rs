@@ -17,8 +17,25 @@ pub enum Error {
     Auth(String),
     /// The requested item was not found.
     NotFound(String),
+    /// The user is not allowed to perform the operation.
+    PermissionDenied(String),
+    /// The channel has as many members as its owner allows.
+    ChannelFull(String),
+    /// The owner or a moderator turned down the request to join a channel.
+    JoinDenied(String),
+    /// The user has as many devices registered as the service allows,
+    /// `limit` if the service tells it.
+    DeviceLimitExceeded { limit: Option<u32> },
+    /// The local repository was written by a newer version of the library,
+    /// at the schema version given.
+    UnsupportedVersion(u32),
+    /// The messaging service does not offer the method, or speaks an older
+    /// protocol version than the method needs.
+    Unsupported(String),
     /// Operation timed out.
     Timeout,
+    /// The client stopped before the operation completed.
+    Shutdown,
 }
 
 impl fmt::Display for Error {
@@ -31,7 +48,17 @@ impl fmt::Display for Error {
             Error::Encoding(m)                  => write!(f, "Encoding error: {}", m),
             Error::Auth(m)                      => write!(f, "Auth error: {}", m),
             Error::NotFound(m)                  => write!(f, "Not found: {}", m),
+            Error::PermissionDenied(m)          => write!(f, "Permission denied: {}", m),
+            Error::ChannelFull(m)               => write!(f, "Channel full: {}", m),
+            Error::JoinDenied(m)                => write!(f, "Join denied: {}", m),
+            Error::DeviceLimitExceeded { limit: Some(limit) }
+                                                => write!(f, "Device limit exceeded: at most {} devices", limit),
+            Error::DeviceLimitExceeded { limit: None }
+                                                => write!(f, "Device limit exceeded"),
+            Error::UnsupportedVersion(v)        => write!(f, "Unsupported repository version {}", v),
+            Error::Unsupported(m)               => write!(f, "Unsupported by the service: {}", m),
             Error::Timeout                      => write!(f, "Operation timed out"),
+            Error::Shutdown                     => write!(f, "Client is shut down"),
         }
     }
 }
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use log::{debug, warn};
+
+use crate::messaging::{
+    client::BoxFuture,
+    errors::Result,
+};
+
+/// Messages held back waiting for an earlier one, beyond which the earlier
+/// ones are given up on.
+pub const MAX_PENDING: usize = 1024;
+
+/// What the worker hands to the message listeners, in sequence order.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery<T> {
+    /// The next message.
+    Message(T),
+    /// The messages with the sequences from..=to are lost.
+    Gap(u64, u64),
+}
+
+/// The service side of the sync: the sequence the inbox reached, and the
+/// messages it still keeps.
+pub trait InboxService<T> {
+    /// The sequence of the last message the inbox received.
+    fn head(&mut self) -> BoxFuture<'_, Result<u64>>;
+
+    /// The messages with their sequences within from..=to, less the ones the
+    /// service no longer has.
+    fn history(&mut self, from: u64, to: u64) -> BoxFuture<'_, Result<Vec<(u64, T)>>>;
+}
+
+/// Delivers the inbox messages in the order of the per-inbox sequence the
+/// service stamps them with, dropping the ones replayed or backfilled twice.
+pub struct InboxSequencer<T> {
+    // Every message up to it was delivered or reported lost, None until the
+    // first sync of a fresh client.
+    cursor      : Option<u64>,
+    pending     : BTreeMap<u64, T>,
+    duplicates  : u64,
+}
+
+impl<T> InboxSequencer<T> {
+    /// A sequencer resuming after `cursor`, `None` for a fresh client.
+    pub fn new(cursor: Option<u64>) -> Self {
+        Self {
+            cursor,
+            pending: BTreeMap::new(),
+            duplicates: 0,
+        }
+    }
+
+    /// The highest sequence up to which every message was delivered or
+    /// reported lost, persisted by the worker.
+    pub fn cursor(&self) -> Option<u64> {
+        self.cursor
+    }
+
+    /// The number of messages dropped as delivered before.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// The sequences missing before the first message held back, the
+    /// worker syncs again to fetch them.
+    pub fn missing(&self) -> Option<(u64, u64)> {
+        let next = self.cursor? + 1;
+        self.pending.keys().next()
+            .filter(|&&seq| seq > next)
+            .map(|seq| (next, seq - 1))
+    }
+
+    /// Takes a message received live or backfilled, returning what can be
+    /// delivered now.
+    pub fn accept(&mut self, seq: u64, item: T) -> Vec<Delivery<T>> {
+        // A fresh client starts with the first message it gets.
+        let cursor = *self.cursor.get_or_insert(seq.saturating_sub(1));
+        if seq <= cursor || self.pending.contains_key(&seq) {
+            self.duplicates += 1;
+            debug!("Dropped duplicate inbox message {seq}");
+            return Vec::new();
+        }
+        self.pending.insert(seq, item);
+
+        let mut deliveries = Vec::new();
+        if self.pending.len() > MAX_PENDING {
+            if let Some((from, to)) = self.missing() {
+                warn!("Gave up on inbox messages {from}-{to} with {} held back", self.pending.len());
+                deliveries.push(Delivery::Gap(from, to));
+                self.cursor = Some(to);
+            }
+        }
+        self.release(&mut deliveries);
+        deliveries
+    }
+
+    /// Reports what is still missing up to the head once backfilled, the
+    /// service does not have it any longer.
+    pub fn give_up(&mut self, head: u64) -> Vec<Delivery<T>> {
+        let mut deliveries = Vec::new();
+        let Some(mut cursor) = self.cursor else {
+            return deliveries;
+        };
+        while cursor < head {
+            let to = self.pending.keys().next().map_or(head, |seq| (seq - 1).min(head));
+            warn!("Inbox messages {}-{to} are no longer on the service", cursor + 1);
+            deliveries.push(Delivery::Gap(cursor + 1, to));
+            self.cursor = Some(to);
+            self.release(&mut deliveries);
+            cursor = self.cursor.unwrap();
+        }
+        deliveries
+    }
+
+    fn release(&mut self, deliveries: &mut Vec<Delivery<T>>) {
+        while let Some(cursor) = self.cursor {
+            let Some(item) = self.pending.remove(&(cursor + 1)) else {
+                break;
+            };
+            self.cursor = Some(cursor + 1);
+            deliveries.push(Delivery::Message(item));
+        }
+    }
+}
+
+/// Catches up with the inbox on connect, before the live traffic: fetches
+/// what was missed since the cursor, and reports what the service lost.
+/// A fresh client starts at the head, without the history.
+pub async fn sync<T>(
+    sequencer: &mut InboxSequencer<T>,
+    service: &mut dyn InboxService<T>
+) -> Result<Vec<Delivery<T>>> {
+    let head = service.head().await?;
+    let Some(cursor) = sequencer.cursor else {
+        sequencer.cursor = Some(head);
+        return Ok(Vec::new());
+    };
+
+    let mut deliveries = Vec::new();
+    if head > cursor {
+        for (seq, item) in service.history(cursor + 1, head).await? {
+            deliveries.extend(sequencer.accept(seq, item));
+        }
+    }
+    deliveries.extend(sequencer.give_up(head));
+    Ok(deliveries)
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use log::{debug, warn};
+use rumqttc::{Packet, SubscribeReasonCode};
+
+use crate::Id;
+
+/// The MQTT version spoken with the messaging server.
+pub const PROTOCOL_VERSION: &str = "MQTT 3.1.1";
+
+/// What the worker does with a packet received from the broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Connected,
+    Publish,
+    PingResp,
+    Disconnected,
+    /// Nothing left to do, including the packets the eventloop already
+    /// answered itself.
+    Ignore,
+}
+
+/// The name of the packet type, for the logs.
+pub fn packet_type(packet: &Packet) -> &'static str {
+    match packet {
+        Packet::Connect(_)      => "CONNECT",
+        Packet::ConnAck(_)      => "CONNACK",
+        Packet::Publish(_)      => "PUBLISH",
+        Packet::PubAck(_)       => "PUBACK",
+        Packet::PubRec(_)       => "PUBREC",
+        Packet::PubRel(_)       => "PUBREL",
+        Packet::PubComp(_)      => "PUBCOMP",
+        Packet::Subscribe(_)    => "SUBSCRIBE",
+        Packet::SubAck(_)       => "SUBACK",
+        Packet::Unsubscribe(_)  => "UNSUBSCRIBE",
+        Packet::UnsubAck(_)     => "UNSUBACK",
+        Packet::PingReq         => "PINGREQ",
+        Packet::PingResp        => "PINGRESP",
+        Packet::Disconnect      => "DISCONNECT",
+    }
+}
+
+/// Whether a notification published to `to` may come from `from`. The
+/// events about the user come from the home peer only, the channel events
+/// from the home peer or from the channel they are about. Anything else is
+/// a spoofed notification and ignored.
+pub fn is_genuine_notification(from: &Id, to: &Id, home_peer: &Id, user_event: bool) -> bool {
+    match user_event {
+        true => from == home_peer,
+        false => from == home_peer || from == to,
+    }
+}
+
+/// Sorts the packets received from the broker for the worker. Packets a
+/// broker never sends are logged and counted rather than taken down the
+/// worker, the count is part of the client diagnostics.
+#[derive(Default)]
+pub struct IncomingPackets {
+    received    : u64,
+    unexpected  : Arc<AtomicU64>,
+}
+
+impl IncomingPackets {
+    /// Sorts the packets, counting the unexpected ones into `unexpected`.
+    pub fn new(unexpected: Arc<AtomicU64>) -> Self {
+        Self {
+            received: 0,
+            unexpected,
+        }
+    }
+
+    /// What to do with `packet`.
+    pub fn dispatch(&mut self, packet: &Packet) -> Action {
+        self.received += 1;
+
+        match packet {
+            Packet::ConnAck(_)      => Action::Connected,
+            Packet::Publish(_)      => Action::Publish,
+            Packet::PingResp        => Action::PingResp,
+            Packet::Disconnect      => Action::Disconnected,
+            Packet::PubAck(_)       => Action::Ignore,
+            Packet::UnsubAck(_)     => Action::Ignore,
+            Packet::SubAck(ack)     => {
+                let failures = ack.return_codes.iter()
+                    .filter(|v| matches!(v, SubscribeReasonCode::Failure))
+                    .count();
+                if failures > 0 {
+                    warn!("Broker refused {} of {} subscriptions in SUBACK {}",
+                        failures, ack.return_codes.len(), ack.pkid);
+                }
+                Action::Ignore
+            },
+            // Only QoS 1 is used, but the eventloop completes the QoS 2
+            // handshake on its own should the broker start one.
+            Packet::PubRec(_)
+            | Packet::PubRel(_)
+            | Packet::PubComp(_)    => {
+                debug!("QoS 2 {} handled by the eventloop", packet_type(packet));
+                Action::Ignore
+            },
+            Packet::Connect(_)
+            | Packet::Subscribe(_)
+            | Packet::Unsubscribe(_)
+            | Packet::PingReq       => {
+                self.unexpected.fetch_add(1, Ordering::Relaxed);
+                warn!("Ignored unexpected {} packet from the broker ({})",
+                    packet_type(packet), PROTOCOL_VERSION);
+                Action::Ignore
+            },
+        }
+    }
+
+    /// The number of packets dispatched so far.
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// The number of packets a broker should never send.
+    pub fn unexpected(&self) -> u64 {
+        self.unexpected.load(Ordering::Relaxed)
+    }
+}
@@ -1,4 +1,5 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
@@ -73,6 +74,11 @@ impl InviteTicket {
     /// A "bearer" ticket can be used by anyone (no specific invitee).
     pub fn is_bearer_ticket(&self)  -> bool { self.is_public.unwrap_or(false) }
 
+    /// The time after which the ticket is no longer accepted.
+    pub fn expiration(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.expire_ms)
+    }
+
     /// Returns `true` when the current time is past the expiry.
     pub fn is_expired(&self) -> bool {
         let now_ms = SystemTime::now()
@@ -101,7 +107,7 @@ impl InviteTicket {
         self.inviter
             .to_signature_key()
             .verify(&digest, &self.sig)
-            .is_ok()
+            .unwrap_or(false)
     }
 
     /// Return a copy of this ticket without the session key (suitable for public distribution).
@@ -166,3 +172,18 @@ impl InviteTicket {
             .map_err(|e| Error::Encoding(format!("Failed to CBOR-decode invite ticket: {}", e)))
     }
 }
+
+/// Parses a ticket in either of its text forms: hex, as produced by
+/// [`InviteTicket::to_hex`], or Base58.
+impl FromStr for InviteTicket {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let is_hex = s.len().is_multiple_of(2) && s.bytes().all(|b| b.is_ascii_hexdigit());
+        match is_hex {
+            true => Self::from_hex(s).or_else(|_| Self::from_base58(s)),
+            false => Self::from_base58(s),
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+use crate::Id;
+use crate::messaging::errors::Error;
+
+/// Error code of the service refusing a ChannelJoin to a full channel.
+pub const CHANNEL_FULL: i32  = -8;
+/// Error code of the service answering a ChannelJoin that was denied.
+pub const JOIN_DENIED: i32   = -9;
+/// Error code of the service holding a ChannelJoin for approval.
+pub const JOIN_PENDING: i32  = -10;
+
+/// A request to join a channel that requires approval, as seen by the
+/// owner and the moderators of the channel.
+///
+/// CBOR field names: `c` = channel_id, `u` = requester, `n` = name,
+/// `t` = requested time in milliseconds since UNIX epoch.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinRequest {
+    #[serde(rename = "c")]
+    channel_id: Id,
+
+    #[serde(rename = "u")]
+    requester: Id,
+
+    #[serde(rename = "n", default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    #[serde(rename = "t")]
+    requested_ms: u64,
+}
+
+impl JoinRequest {
+    /// A request by `requester`, reaching the service at `requested_ms`.
+    pub fn new(channel_id: Id, requester: Id, name: Option<String>, requested_ms: u64) -> Self {
+        Self { channel_id, requester, name, requested_ms }
+    }
+
+    /// The channel asked to be joined.
+    pub fn channel_id(&self) -> &Id { &self.channel_id }
+
+    /// The user asking to join.
+    pub fn requester(&self) -> &Id { &self.requester }
+
+    /// The display name of the requester, if it shared one.
+    pub fn name(&self) -> Option<&str> { self.name.as_deref() }
+
+    /// When the request reached the service.
+    pub fn requested_at(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.requested_ms)
+    }
+}
+
+/// Who gets into a channel holding a valid ticket, set by the owner.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinPolicy {
+    #[serde(rename = "mm", default, skip_serializing_if = "Option::is_none")]
+    max_members: Option<u32>,
+
+    #[serde(rename = "ar", default, skip_serializing_if = "crate::is_default")]
+    approval_required: bool,
+}
+
+impl JoinPolicy {
+    /// A policy admitting up to `max_members`, unlimited if `None`.
+    pub fn new(max_members: Option<usize>, approval_required: bool) -> Result<Self, Error> {
+        let max_members = match max_members {
+            Some(0) => Err(Error::Argument("A channel takes one member at least".into()))?,
+            Some(n) => Some(u32::try_from(n).map_err(|_|
+                Error::Argument(format!("Too many members: {n}"))
+            )?),
+            None => None,
+        };
+        Ok(Self { max_members, approval_required })
+    }
+
+    /// The most members the channel admits, unlimited if `None`.
+    pub fn max_members(&self) -> Option<usize> {
+        self.max_members.map(|n| n as usize)
+    }
+
+    /// Whether a join waits for the owner or a moderator to approve it.
+    pub fn approval_required(&self) -> bool {
+        self.approval_required
+    }
+}
+
+/// The decision of the owner or a moderator on a [`JoinRequest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JoinApproval {
+    #[serde(rename = "u")]
+    user: Id,
+
+    #[serde(rename = "a")]
+    approve: bool,
+}
+
+impl JoinApproval {
+    /// Approve or deny the request of `user`.
+    pub fn new(user: Id, approve: bool) -> Self {
+        Self { user, approve }
+    }
+
+    /// The user who asked to join.
+    pub fn user(&self) -> &Id {
+        &self.user
+    }
+
+    /// Whether the request is approved.
+    pub fn approve(&self) -> bool {
+        self.approve
+    }
+}
+
+/// The typed error for a refused join, None for any other error code.
+pub fn join_error(channel_id: &Id, code: i32) -> Option<Error> {
+    match code {
+        CHANNEL_FULL => Some(Error::ChannelFull(format!("channel {channel_id} has no room left"))),
+        JOIN_DENIED => Some(Error::JoinDenied(format!("request to join channel {channel_id} was denied"))),
+        _ => None,
+    }
+}
+
+/// What the worker does next about a join held for approval.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Decision<T> {
+    /// Nothing to do yet.
+    Wait,
+    /// Send the join once more, it goes through now.
+    Approved(T),
+    /// Fail the join with JoinDenied.
+    Denied(T),
+}
+
+enum State<T> {
+    Sent,
+    Held(T),
+    Decided(bool),
+}
+
+/// Joins requested by this client to channels requiring approval.
+///
+/// The service answers the ChannelJoin with JOIN_PENDING and later notifies
+/// the decision. The notification may overtake the response, so an early
+/// decision is kept until the response shows up. Decisions on channels
+/// not joined by this client are ignored.
+#[derive(Default)]
+pub struct PendingJoins<T> {
+    joins: HashMap<Id, State<T>>,
+}
+
+impl<T> PendingJoins<T> {
+    /// No join pending.
+    pub fn new() -> Self {
+        Self { joins: HashMap::new() }
+    }
+
+    /// Whether a join of the channel awaits its answer.
+    pub fn is_pending(&self, channel_id: &Id) -> bool {
+        self.joins.contains_key(channel_id)
+    }
+
+    /// Track the join of the channel, sent just now.
+    pub fn begin(&mut self, channel_id: &Id) {
+        self.joins.entry(*channel_id).or_insert(State::Sent);
+    }
+
+    /// The service holds the join for approval, the call is kept meanwhile.
+    pub fn on_pending(&mut self, channel_id: &Id, call: T) -> Decision<T> {
+        match self.joins.remove(channel_id) {
+            Some(State::Decided(true)) => Decision::Approved(call),
+            Some(State::Decided(false)) => Decision::Denied(call),
+            _ => {
+                self.joins.insert(*channel_id, State::Held(call));
+                Decision::Wait
+            },
+        }
+    }
+
+    /// The service notified the decision on the join.
+    pub fn on_decision(&mut self, channel_id: &Id, approved: bool) -> Decision<T> {
+        match self.joins.remove(channel_id) {
+            Some(State::Held(call)) => match approved {
+                true => Decision::Approved(call),
+                false => Decision::Denied(call),
+            },
+            Some(_) => {
+                self.joins.insert(*channel_id, State::Decided(approved));
+                Decision::Wait
+            },
+            None => Decision::Wait,
+        }
+    }
+
+    /// The join got its final answer, joined or failed.
+    pub fn finish(&mut self, channel_id: &Id) {
+        self.joins.remove(channel_id);
+    }
+}
+
+/// Join requests waiting for a decision in the channels the user owns or
+/// moderates, kept by the user agent for the owner and moderators to go
+/// through.
+#[derive(Default)]
+pub struct JoinRequests {
+    channels: HashMap<Id, Vec<JoinRequest>>,
+}
+
+impl JoinRequests {
+    /// No request waiting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the request, returns whether it is new. A repeated one replaces
+    /// the former.
+    pub fn add(&mut self, request: JoinRequest) -> bool {
+        let requests = self.channels.entry(request.channel_id).or_default();
+        match requests.iter_mut().find(|r| r.requester == request.requester) {
+            Some(existing) => {
+                *existing = request;
+                false
+            },
+            None => {
+                requests.push(request);
+                true
+            },
+        }
+    }
+
+    /// Drops the request decided here or by another moderator.
+    pub fn resolve(&mut self, channel_id: &Id, requester: &Id) -> Option<JoinRequest> {
+        let requests = self.channels.get_mut(channel_id)?;
+        let pos = requests.iter().position(|r| r.requester == *requester)?;
+        let request = requests.remove(pos);
+        if requests.is_empty() {
+            self.channels.remove(channel_id);
+        }
+        Some(request)
+    }
+
+    /// The requests waiting in the channel, oldest first.
+    pub fn pending(&self, channel_id: &Id) -> Vec<JoinRequest> {
+        let mut requests = self.channels.get(channel_id).cloned().unwrap_or_default();
+        requests.sort_by_key(|r| r.requested_ms);
+        requests
+    }
+
+    /// Drops the requests of a channel gone or left.
+    pub fn remove_channel(&mut self, channel_id: &Id) {
+        self.channels.remove(channel_id);
+    }
+}
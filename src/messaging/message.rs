@@ -1,9 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
 use std::time::SystemTime;
 
 use crate::Id;
-use crate::messaging::errors::{Error, Result};
+use crate::messaging::{
+    client::BoxFuture,
+    errors::{Error, Result},
+};
 
 // ---------------------------------------------------------------------------
 // ContentType
@@ -24,6 +27,9 @@ pub mod content_type {
     pub const VIDEO_MP4:   &str = "video/mp4";
     pub const VIDEO_WEBM:  &str = "video/webm";
     pub const BINARY:      &str = "application/octet-stream";
+    /// Body of a message carrying an attachment manifest, the attachment
+    /// itself is stored on the messaging service.
+    pub const ATTACHMENT:  &str = "application/x-boson-attachment";
 }
 
 // ---------------------------------------------------------------------------
@@ -206,6 +212,24 @@ pub trait Message: Send + Sync {
 
     /// The decoded content, if decryption succeeded.
     fn payload_as_content(&self) -> Option<&Content>;
+
+    /// The id the sender gave the message, the same on every device. `None`
+    /// for messages from clients without threading support.
+    fn message_id(&self) -> Option<&Id>;
+
+    /// The id of the message this one replies to.
+    fn in_reply_to(&self) -> Option<&Id>;
+
+    /// The id of the thread this message belongs to.
+    fn thread(&self) -> Option<&Id>;
+
+    /// The application properties set by the sender.
+    fn properties(&self) -> &BTreeMap<String, String>;
+
+    /// The position of an inbound message in the inbox, stamped by the
+    /// messaging service. `None` for outbound messages and for services
+    /// without inbox sequences.
+    fn inbox_sequence(&self) -> Option<u64>;
 }
 
 // ---------------------------------------------------------------------------
@@ -230,4 +254,19 @@ pub trait MessageBuilder: Send + Sync {
 
     /// Add an arbitrary header.
     fn header(self: Box<Self>, key: &str, value: &str) -> Box<dyn MessageBuilder>;
+
+    /// Reference the message being replied to by its
+    /// [`message_id`](Message::message_id).
+    fn in_reply_to(self: Box<Self>, message_id: &Id) -> Box<dyn MessageBuilder>;
+
+    /// Place the message in a thread.
+    fn thread(self: Box<Self>, thread_id: &Id) -> Box<dyn MessageBuilder>;
+
+    /// Set an application property. The properties are limited to
+    /// [`MAX_PROPERTIES_SIZE`](crate::messaging::message_meta::MAX_PROPERTIES_SIZE)
+    /// bytes in total, [`send`](Self::send) fails on more.
+    fn property(self: Box<Self>, key: &str, value: &str) -> Box<dyn MessageBuilder>;
+
+    /// Send the message, resolving to the message as sent.
+    fn send(self: Box<Self>) -> BoxFuture<'static, Result<Box<dyn Message>>>;
 }
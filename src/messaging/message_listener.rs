@@ -1,3 +1,4 @@
+use crate::Id;
 use crate::messaging::message::Message;
 
 /// Receives message delivery events.
@@ -5,6 +6,23 @@ pub trait MessageListener: Send + Sync {
     /// Called when a new inbound message arrives.
     fn on_message(&self, message: &dyn Message);
 
+    /// Called instead of [`on_message`](Self::on_message) for the inbound
+    /// messages the user is not to be notified of, by the
+    /// [`NotifyLevel`](crate::messaging::NotifyLevel) of their conversation.
+    /// They are stored like any other message.
+    fn on_silent_message(&self, _message: &dyn Message) {}
+
     /// Called when an outbound message was successfully delivered.
     fn on_sent(&self, _message: &dyn Message) {}
+
+    /// Called once in a while instead of [`on_message`](Self::on_message)
+    /// for the messages of a sender dropped over its inbound rate limit,
+    /// with their count since the last call.
+    fn on_messages_suppressed(&self, _conversation_id: &Id, _sender: &Id, _count: u64) {}
+
+    /// Called when inbound messages were lost while offline, the messaging
+    /// service no longer having them, with the first and last
+    /// [`inbox_sequence`](Message::inbox_sequence) missing. Some messages
+    /// may be missing from the conversations then.
+    fn on_gap(&self, _from: u64, _to: u64) {}
 }
@@ -0,0 +1,210 @@
+//! Threading and application properties carried by message envelopes.
+//!
+//! The fields travel in the `mx` extension of the CBOR envelope map, next
+//! to the regular envelope fields: `id` (the message id), `rt` (the id of
+//! the message replied to), `th` (the thread id) and `pp` (string
+//! properties). Clients without threading support ignore the extension, and
+//! envelopes without it read as messages with no thread or properties.
+
+use std::collections::BTreeMap;
+use serde_cbor::Value;
+
+use crate::Id;
+use crate::messaging::{
+    chunking::MAX_MESSAGE_SIZE,
+    errors::{Error, Result},
+};
+
+/// Maximum total bytes of the property keys and values of a message.
+pub const MAX_PROPERTIES_SIZE: usize = 4 * 1024;
+
+/// Maximum bytes of a property key.
+pub const MAX_PROPERTY_KEY_SIZE: usize = 64;
+
+const KEY_EXTENSION: &str = "mx";
+const KEY_MESSAGE_ID: &str = "id";
+const KEY_REPLY_TO: &str = "rt";
+const KEY_THREAD: &str = "th";
+const KEY_PROPERTIES: &str = "pp";
+
+/// Message id, reply reference, thread and properties of a message.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MessageMeta {
+    message_id:  Option<Id>,
+    in_reply_to: Option<Id>,
+    thread:      Option<Id>,
+    properties:  BTreeMap<String, String>,
+}
+
+impl MessageMeta {
+    /// Metadata for a new outgoing message, with a fresh message id.
+    pub fn new() -> Self {
+        Self {
+            message_id: Some(Id::random()),
+            ..Default::default()
+        }
+    }
+
+    /// Reference the message being replied to.
+    pub fn with_in_reply_to(mut self, message_id: &Id) -> Self {
+        self.in_reply_to = Some(*message_id);
+        self
+    }
+
+    /// Place the message in a thread.
+    pub fn with_thread(mut self, thread_id: &Id) -> Self {
+        self.thread = Some(*thread_id);
+        self
+    }
+
+    /// Set a property, replacing the value set before.
+    ///
+    /// Fails on empty or overlong keys and when the properties would exceed
+    /// [`MAX_PROPERTIES_SIZE`] bytes in total.
+    pub fn with_property(mut self, key: &str, value: &str) -> Result<Self> {
+        if key.is_empty() || key.len() > MAX_PROPERTY_KEY_SIZE {
+            return Err(Error::Argument(format!(
+                "Property key must have 1 to {MAX_PROPERTY_KEY_SIZE} bytes"
+            )));
+        }
+        let replaced = self.properties.get(key).map_or(0, |v| key.len() + v.len());
+        let size = self.properties_size() - replaced + key.len() + value.len();
+        if size > MAX_PROPERTIES_SIZE {
+            return Err(Error::Argument(format!(
+                "Properties size {size} exceeds the maximum {MAX_PROPERTIES_SIZE}"
+            )));
+        }
+        self.properties.insert(key.into(), value.into());
+        Ok(self)
+    }
+
+    /// The id the sender gave the message, the same on every device, for
+    /// detecting duplicates and referencing it in replies.
+    pub fn message_id(&self) -> Option<&Id> {
+        self.message_id.as_ref()
+    }
+
+    /// The id of the message this one replies to.
+    pub fn in_reply_to(&self) -> Option<&Id> {
+        self.in_reply_to.as_ref()
+    }
+
+    /// The id of the thread the message belongs to.
+    pub fn thread(&self) -> Option<&Id> {
+        self.thread.as_ref()
+    }
+
+    /// The thread of the message, or the message it replies to when it was
+    /// sent without one, as replies to a message make up its thread.
+    pub fn thread_root(&self) -> Option<&Id> {
+        self.thread.as_ref().or(self.in_reply_to.as_ref())
+    }
+
+    pub fn properties(&self) -> &BTreeMap<String, String> {
+        &self.properties
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(|v| v.as_str())
+    }
+
+    /// Whether there is nothing to carry in the envelope.
+    pub fn is_empty(&self) -> bool {
+        self.message_id.is_none()
+            && self.in_reply_to.is_none()
+            && self.thread.is_none()
+            && self.properties.is_empty()
+    }
+
+    fn properties_size(&self) -> usize {
+        self.properties.iter().map(|(k, v)| k.len() + v.len()).sum()
+    }
+
+    /// Add the metadata to an encoded envelope.
+    ///
+    /// Fails when the envelope is not a CBOR map, or when the metadata
+    /// pushes it over [`MAX_MESSAGE_SIZE`].
+    pub fn attach(&self, envelope: Vec<u8>) -> Result<Vec<u8>> {
+        if self.is_empty() {
+            return Ok(envelope);
+        }
+        let Ok(Value::Map(mut map)) = serde_cbor::from_slice::<Value>(&envelope) else {
+            return Err(Error::Encoding("Envelope is not a CBOR map".into()));
+        };
+
+        let mut ext = BTreeMap::new();
+        let id = |v: &Id| Value::Bytes(v.as_bytes().to_vec());
+        if let Some(v) = self.message_id.as_ref() {
+            ext.insert(Value::Text(KEY_MESSAGE_ID.into()), id(v));
+        }
+        if let Some(v) = self.in_reply_to.as_ref() {
+            ext.insert(Value::Text(KEY_REPLY_TO.into()), id(v));
+        }
+        if let Some(v) = self.thread.as_ref() {
+            ext.insert(Value::Text(KEY_THREAD.into()), id(v));
+        }
+        if !self.properties.is_empty() {
+            ext.insert(Value::Text(KEY_PROPERTIES.into()), Value::Map(
+                self.properties.iter()
+                    .map(|(k, v)| (Value::Text(k.clone()), Value::Text(v.clone())))
+                    .collect()
+            ));
+        }
+        map.insert(Value::Text(KEY_EXTENSION.into()), Value::Map(ext));
+
+        let envelope = serde_cbor::to_vec(&Value::Map(map)).map_err(|e| {
+            Error::Encoding(format!("Encoding message envelope failed: {e}"))
+        })?;
+        if envelope.len() > MAX_MESSAGE_SIZE {
+            return Err(Error::Argument(format!(
+                "Message size {} exceeds the maximum {}",
+                envelope.len(), MAX_MESSAGE_SIZE
+            )));
+        }
+        Ok(envelope)
+    }
+
+    /// Read the metadata from a received envelope, empty when it carries
+    /// none.
+    pub fn extract(envelope: &[u8]) -> Result<Self> {
+        let Ok(Value::Map(mut map)) = serde_cbor::from_slice::<Value>(envelope) else {
+            return Err(Error::Encoding("Envelope is not a CBOR map".into()));
+        };
+        let Some(ext) = map.remove(&Value::Text(KEY_EXTENSION.into())) else {
+            return Ok(Self::default());
+        };
+        let Value::Map(mut ext) = ext else {
+            return Err(Error::Encoding("Invalid message extension".into()));
+        };
+
+        let mut id = |key: &str| match ext.remove(&Value::Text(key.into())) {
+            None => Ok(None),
+            Some(Value::Bytes(v)) => Id::try_from_bytes(&v).map(Some).map_err(|_| {
+                Error::Encoding(format!("Invalid id '{key}' in message extension"))
+            }),
+            Some(_) => Err(Error::Encoding(format!("Invalid id '{key}' in message extension"))),
+        };
+        let message_id = id(KEY_MESSAGE_ID)?;
+        let in_reply_to = id(KEY_REPLY_TO)?;
+        let thread = id(KEY_THREAD)?;
+
+        let mut meta = Self {
+            message_id,
+            in_reply_to,
+            thread,
+            properties: BTreeMap::new(),
+        };
+        match ext.remove(&Value::Text(KEY_PROPERTIES.into())) {
+            None => {},
+            Some(Value::Map(props)) => for (k, v) in props {
+                let (Value::Text(k), Value::Text(v)) = (k, v) else {
+                    return Err(Error::Encoding("Invalid message property".into()));
+                };
+                meta = meta.with_property(&k, &v)
+                    .map_err(|e| Error::Encoding(format!("Invalid message properties: {e}")))?;
+            },
+            Some(_) => return Err(Error::Encoding("Invalid message properties".into())),
+        }
+        Ok(meta)
+    }
+}
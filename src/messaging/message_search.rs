@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::Id;
+use crate::messaging::message::{MessageType, content_type};
+
+// Words shown around the first match of a snippet.
+const SNIPPET_WORDS_BEFORE: usize = 4;
+const SNIPPET_WORDS_AFTER:  usize = 12;
+
+const ELLIPSIS: &str = "…";
+
+/// A persisted message matching a search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageHit {
+    conversation_id : Id,
+    message_id      : i64,
+    created         : u64,
+    snippet         : String,
+    highlights      : Vec<Range<usize>>,
+}
+
+impl MessageHit {
+    pub(crate) fn new(
+        conversation_id : Id,
+        message_id      : i64,
+        created         : u64,
+        text            : &str,
+        terms           : &HashSet<String>,
+    ) -> Self {
+        let (snippet, highlights) = snippet(text, terms);
+        Self { conversation_id, message_id, created, snippet, highlights }
+    }
+
+    /// The conversation the message belongs to.
+    pub fn conversation_id(&self) -> &Id {
+        &self.conversation_id
+    }
+
+    /// The local ID of the message.
+    pub fn message_id(&self) -> i64 {
+        self.message_id
+    }
+
+    /// Milliseconds since the Unix epoch when the message was created.
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// An excerpt of the message text around the first match.
+    pub fn snippet(&self) -> &str {
+        &self.snippet
+    }
+
+    /// Byte ranges of the matched words within [`snippet`](Self::snippet).
+    pub fn highlights(&self) -> &[Range<usize>] {
+        &self.highlights
+    }
+}
+
+// The text of the messages the search covers, only text content is indexed.
+pub(crate) fn searchable_text<'a>(
+    message_type: MessageType,
+    content_type: Option<&str>,
+    body: &'a [u8]
+) -> Option<&'a str> {
+    if message_type != MessageType::ContentMessage {
+        return None;
+    }
+    if !content_type.unwrap_or(content_type::TEXT).starts_with("text/") {
+        return None;
+    }
+    std::str::from_utf8(body).ok()
+}
+
+// Byte ranges of the words of `text`, runs of alphanumeric characters.
+fn words(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (pos, ch) in text.char_indices() {
+        match (ch.is_alphanumeric(), start) {
+            (true, None) => start = Some(pos),
+            (false, Some(begin)) => {
+                words.push(begin..pos);
+                start = None;
+            },
+            _ => {}
+        }
+    }
+    if let Some(begin) = start {
+        words.push(begin..text.len());
+    }
+    words
+}
+
+// The form a word is indexed and matched in, NFC normalized and lowercased
+// so matching ignores case and how the text was composed.
+fn normalize(word: &str) -> String {
+    word.nfc().collect::<String>().to_lowercase().nfc().collect()
+}
+
+// The distinct normalized words of `text`.
+pub(crate) fn tokenize(text: &str) -> HashSet<String> {
+    let text = text.nfc().collect::<String>();
+    words(&text).into_iter()
+        .map(|range| normalize(&text[range]))
+        .collect()
+}
+
+// An excerpt of `text` around the first word in `terms` with the byte ranges
+// of every matched word in it.
+pub(crate) fn snippet(text: &str, terms: &HashSet<String>) -> (String, Vec<Range<usize>>) {
+    let text = text.nfc().collect::<String>();
+    let words = words(&text);
+    if words.is_empty() {
+        return (String::new(), Vec::new());
+    }
+
+    let matched = |range: &Range<usize>| terms.contains(&normalize(&text[range.clone()]));
+    let first = words.iter().position(matched).unwrap_or(0);
+    let begin = first.saturating_sub(SNIPPET_WORDS_BEFORE);
+    let end = (first + SNIPPET_WORDS_AFTER).min(words.len());
+
+    let mut snippet = String::new();
+    if begin > 0 {
+        snippet.push_str(ELLIPSIS);
+    }
+    let offset = snippet.len();
+    let from = words[begin].start;
+    snippet.push_str(&text[from..words[end - 1].end]);
+    if end < words.len() {
+        snippet.push_str(ELLIPSIS);
+    }
+
+    let highlights = words[begin..end].iter()
+        .filter(|range| matched(range))
+        .map(|range| range.start - from + offset..range.end - from + offset)
+        .collect();
+    (snippet, highlights)
+}
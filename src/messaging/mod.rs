@@ -1,12 +1,35 @@
 pub mod errors;
 pub mod contact;
 pub mod channel;
+pub mod channel_key;
 pub mod message;
 pub mod conversation;
 pub mod friend_request;
 pub mod invite_ticket;
+pub mod join_request;
 pub mod session_info;
+pub mod service_ids;
+pub mod device_registry;
+pub mod capabilities;
 pub mod config;
+pub mod chunking;
+pub mod presence;
+pub mod message_search;
+pub mod message_meta;
+pub mod self_sync;
+pub mod notification;
+pub mod contact_transfer;
+pub(crate) mod account_backup;
+pub mod pending_calls;
+pub mod channel_removal;
+pub mod incoming;
+pub mod attachment;
+pub mod client_id;
+pub mod rate_limit;
+pub mod block_list;
+pub mod shutdown;
+pub mod inbox_sync;
+pub mod user_profile;
 
 pub mod connection_listener;
 pub mod contact_listener;
@@ -17,19 +40,64 @@ pub mod session_listener;
 
 pub mod client;
 
+// Not used by a compiled client yet.
+#[allow(unused)]
+pub(crate) mod persistence;
+#[allow(unused)]
+pub(crate) mod profile;
+
+#[cfg(test)]
+mod unitests {
+    mod test_persistence;
+    mod test_presence;
+    mod test_account_backup;
+    mod test_pending_calls;
+    mod test_channel_removal;
+    mod test_join_request;
+    mod test_incoming;
+    mod test_attachment;
+    mod test_client_id;
+    mod test_profile;
+    mod test_rate_limit;
+    mod test_block_list;
+    mod test_message_search;
+    mod test_self_sync;
+    mod test_contact_transfer;
+    mod test_device_registry;
+    mod test_shutdown;
+    mod test_notification;
+    mod test_capabilities;
+    mod test_inbox_sync;
+    mod test_service_ids;
+    mod test_utils;
+}
+
 pub use errors::{Error, Result};
 pub use contact::{Contact, ContactEditor, ContactType};
-pub use channel::{Channel, ChannelEditor, ChannelMember, Permission, Role};
+pub use contact_transfer::{ContactFormat, ImportReport, ImportOutcome};
+pub use channel::{Channel, ChannelEditor, ChannelMember, MemberRemoval, Permission, Role};
+pub use channel_key::{ChannelKeyRing, KeyRotation};
 pub use message::{Message, MessageBuilder, MessageType, Content, ContentDisposition, content_type};
 pub use conversation::Conversation;
 pub use friend_request::FriendRequest;
 pub use invite_ticket::InviteTicket;
+pub use join_request::JoinRequest;
 pub use session_info::SessionInfo;
+pub use service_ids::{ServiceIds, ServiceDiscovery, HttpServiceDiscovery};
+pub use device_registry::{DeviceInfo, DeviceRegistration, DeviceRegistry, DeviceRequest, HttpDeviceRegistry, ServiceInfo, MAX_DEVICE_NAME_LEN};
+pub use capabilities::{ServerCapabilities, PROTOCOL_VERSION};
 pub use config::Configuration;
-pub use connection_listener::ConnectionListener;
+pub use presence::{Presence, PresenceState};
+pub use message_search::MessageHit;
+pub use message_meta::MessageMeta;
+pub use self_sync::ReadState;
+pub use notification::{NotifyLevel, NotificationHint, NotifySetting, MENTIONS_PROPERTY};
+pub use rate_limit::InboundRateLimit;
+pub use user_profile::UserProfile;
+pub use connection_listener::{ConnectionListener, ConnectionEvent, ConnectionEvents};
 pub use contact_listener::ContactListener;
 pub use channel_listener::ChannelListener;
 pub use message_listener::MessageListener;
 pub use friend_request_listener::FriendRequestListener;
 pub use session_listener::SessionListener;
-pub use client::{MessagingClient, MessagingClientBuilder, DEFAULT_MESSAGES_LIMIT, BoxFuture};
+pub use client::{MessagingClient, MessagingClientBuilder, DEFAULT_MESSAGES_LIMIT, DEFAULT_REQUEST_TIMEOUT, BoxFuture};
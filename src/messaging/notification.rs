@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::Id;
+use crate::messaging::errors::{Error, Result};
+
+/// The message property listing the users mentioned, as base58 ids
+/// separated by commas or whitespace.
+pub const MENTIONS_PROPERTY: &str = "mentions";
+
+/// Which messages of a conversation the user wants to be notified of. The
+/// messages are synced and stored the same whatever the level.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum NotifyLevel {
+    #[default]
+    All      = 0,
+    /// Only the messages mentioning the user, every message of a direct
+    /// conversation is addressed to the user.
+    Mentions = 1,
+    /// Muted, no message notifies.
+    None     = 2,
+}
+
+impl TryFrom<i32> for NotifyLevel {
+    type Error = Error;
+
+    fn try_from(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(NotifyLevel::All),
+            1 => Ok(NotifyLevel::Mentions),
+            2 => Ok(NotifyLevel::None),
+            _ => Err(Error::Argument(format!("Unknown NotifyLevel value: {}", value))),
+        }
+    }
+}
+
+/// Whether an inbound message should notify the user, the silent ones are
+/// dispatched to
+/// [`on_silent_message`](crate::messaging::MessageListener::on_silent_message)
+/// instead of `on_message`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationHint {
+    Notify,
+    /// Stored like any other message, without notifying the user.
+    Silent,
+}
+
+impl NotificationHint {
+    /// The hint for a message in a conversation at `level`, `mentioned`
+    /// if the message mentions the user.
+    pub fn of(level: NotifyLevel, mentioned: bool) -> Self {
+        match level {
+            NotifyLevel::All => NotificationHint::Notify,
+            NotifyLevel::Mentions if mentioned => NotificationHint::Notify,
+            _ => NotificationHint::Silent,
+        }
+    }
+}
+
+/// The notify level of a conversation, kept in sync across the devices of
+/// the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifySetting {
+    #[serde(rename = "c")]
+    conversation: Id,
+    #[serde(rename = "l")]
+    level       : NotifyLevel,
+    #[serde(rename = "t")]
+    updated     : u64,
+}
+
+impl NotifySetting {
+    /// The level of the conversation, set at `updated`.
+    pub fn new(conversation: Id, level: NotifyLevel, updated: u64) -> Self {
+        Self { conversation, level, updated }
+    }
+
+    pub fn conversation_id(&self) -> &Id {
+        &self.conversation
+    }
+
+    pub fn level(&self) -> NotifyLevel {
+        self.level
+    }
+
+    /// Milliseconds since the Unix epoch when the level was set.
+    pub fn updated(&self) -> u64 {
+        self.updated
+    }
+
+    // Last writer wins, the tie broken by the quieter level.
+    pub(crate) fn supersedes(&self, other: &NotifySetting) -> bool {
+        (self.updated, self.level as u8) > (other.updated, other.level as u8)
+    }
+}
+
+/// Whether a channel message mentions the user: its text refers to the user
+/// as `@<id>`, or the id is listed in the mentions property.
+pub fn mentions(user: &Id, text: Option<&str>, properties: &BTreeMap<String, String>) -> bool {
+    let listed = properties.get(MENTIONS_PROPERTY).is_some_and(|v| {
+        v.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|s| !s.is_empty())
+            .any(|s| Id::try_from(s).is_ok_and(|id| &id == user))
+    });
+    listed || text.is_some_and(|t| t.contains(&format!("@{}", user)))
+}
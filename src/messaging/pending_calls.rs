@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::Id;
+
+// Ids of timed out requests remembered to recognize late responses.
+const MAX_TIMED_OUT_IDS: usize = 256;
+
+/// The ids of the requests of a session. They start at a random index, so
+/// the ids in use are hard to guess, and wrap around.
+pub struct RequestIds {
+    last: u32,
+}
+
+impl RequestIds {
+    /// Ids starting at a random index.
+    pub fn new() -> Self {
+        Self::starting_at(crate::random_u32())
+    }
+
+    /// Ids following `last`.
+    pub fn starting_at(last: u32) -> Self {
+        Self { last }
+    }
+
+    /// The id of the next request.
+    pub fn next_id(&mut self) -> u32 {
+        self.last = self.last.wrapping_add(1);
+        self.last
+    }
+}
+
+impl Default for RequestIds {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An expired request handed back by [`PendingCalls::expire`].
+pub enum Expired<T> {
+    /// Safe to send once more, re-arm it with [`PendingCalls::resend`].
+    Retry(u32, T),
+    /// Failed for good, its promise has to be completed with a timeout.
+    Timeout(u32, T),
+}
+
+/// What a response with a request id answers, see [`PendingCalls::take`].
+pub enum Answered<T> {
+    /// The request, sent to the peer the response came from.
+    Call(T),
+    /// A pending request sent to another peer, which stays pending.
+    Spoofed(Id),
+    /// A request that already timed out.
+    Late,
+    /// No request of this client.
+    Unknown,
+}
+
+struct Entry<T> {
+    call        : T,
+    to          : Id,
+    deadline    : Instant,
+    retryable   : bool,
+}
+
+/// RPC requests awaiting their responses, keyed by request id.
+///
+/// Every request gets a deadline when sent. Requests past their deadline
+/// are removed by [`expire`](Self::expire), idempotent ones are offered for
+/// a single retry first. The ids of timed out requests are remembered for a
+/// while, so a response arriving too late can be told apart from a bogus one.
+/// A response only answers a request when it comes from the peer the request
+/// was sent to, the ids alone are easily guessed.
+pub struct PendingCalls<T> {
+    timeout     : Duration,
+    entries     : HashMap<u32, Entry<T>>,
+    timed_out   : HashSet<u32>,
+    timed_out_order: VecDeque<u32>,
+}
+
+impl<T> PendingCalls<T> {
+    /// An empty table, a request fails `timeout` after it was sent.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            entries     : HashMap::new(),
+            timed_out   : HashSet::new(),
+            timed_out_order: VecDeque::new(),
+        }
+    }
+
+    /// How long a request waits for its response.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// How often [`expire`](Self::expire) should run, a request fails at
+    /// most this late.
+    pub fn sweep_interval(&self) -> Duration {
+        (self.timeout / 4).clamp(Duration::from_millis(10), Duration::from_secs(1))
+    }
+
+    /// The number of pending requests.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no request is pending.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Track a request sent to `to` at `now`, `retryable` if it may be sent
+    /// once more after timing out.
+    pub fn insert(&mut self, id: u32, to: Id, call: T, retryable: bool, now: Instant) {
+        self.entries.insert(id, Entry {
+            call,
+            to,
+            deadline: now + self.timeout,
+            retryable,
+        });
+    }
+
+    /// Track a retried request again, it is not retried another time.
+    pub fn resend(&mut self, id: u32, to: Id, call: T, now: Instant) {
+        self.insert(id, to, call, false, now);
+    }
+
+    /// Remove the request a response with `id` from `from` answers.
+    pub fn take(&mut self, id: u32, from: &Id) -> Answered<T> {
+        match self.entries.get(&id) {
+            Some(entry) if entry.to != *from => Answered::Spoofed(entry.to),
+            Some(_) => Answered::Call(self.entries.remove(&id).unwrap().call),
+            None if self.is_timed_out(id) => Answered::Late,
+            None => Answered::Unknown,
+        }
+    }
+
+    /// Remove the request a response with `id` answers.
+    pub fn remove(&mut self, id: u32) -> Option<T> {
+        self.entries.remove(&id).map(|e| e.call)
+    }
+
+    /// Remove all the requests, in the order of their deadlines. None of them
+    /// will get a response, their promises have to be failed.
+    pub fn drain(&mut self) -> Vec<(u32, T)> {
+        let mut entries = self.entries.drain()
+            .map(|(id, e)| (e.deadline, id, e.call))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|(deadline, id, _)| (*deadline, *id));
+        entries.into_iter().map(|(_, id, call)| (id, call)).collect()
+    }
+
+    /// Whether `id` belongs to a request that already timed out.
+    pub fn is_timed_out(&self, id: u32) -> bool {
+        self.timed_out.contains(&id)
+    }
+
+    /// The earliest deadline among the pending requests.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.entries.values().map(|e| e.deadline).min()
+    }
+
+    /// Remove the requests past their deadline at `now`.
+    pub fn expire(&mut self, now: Instant) -> Vec<Expired<T>> {
+        let mut ids = self.entries.iter()
+            .filter(|(_, e)| e.deadline <= now)
+            .map(|(id, e)| (e.deadline, *id))
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let mut expired = Vec::with_capacity(ids.len());
+        for (_, id) in ids {
+            let entry = self.entries.remove(&id).unwrap();
+            if entry.retryable {
+                expired.push(Expired::Retry(id, entry.call));
+                continue;
+            }
+
+            self.remember_timed_out(id);
+            expired.push(Expired::Timeout(id, entry.call));
+        }
+        expired
+    }
+
+    fn remember_timed_out(&mut self, id: u32) {
+        if !self.timed_out.insert(id) {
+            return;
+        }
+        self.timed_out_order.push_back(id);
+        if self.timed_out_order.len() > MAX_TIMED_OUT_IDS {
+            if let Some(oldest) = self.timed_out_order.pop_front() {
+                self.timed_out.remove(&oldest);
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use hkdf::Hkdf;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+use crate::{
+    signature,
+    cryptobox::{CryptoBox, Nonce},
+};
+use crate::messaging::{Error, Result};
+
+// Fixed HKDF context, changing it makes every encrypted column unreadable.
+const KEY_CONTEXT: &[u8] = b"boson messaging repository at-rest key v1";
+// Changing it requires the message search index to be rebuilt.
+const INDEX_KEY_CONTEXT: &[u8] = b"boson messaging repository search index key v1";
+
+// Bytes of the HMAC kept for a blinded search token.
+const BLIND_BYTES: usize = 16;
+
+// Symmetric cipher for the sensitive repository columns. The key is derived
+// from the device signature key, so the repository is only readable on the
+// device that wrote it. Sealed values are laid out as nonce || mac || cipher.
+// A second derived key blinds the message search tokens, so the search index
+// does not hold any plaintext either.
+pub(crate) struct AtRestCipher {
+    cipher:     CryptoBox,
+    index_key:  [u8; 32],
+}
+
+impl AtRestCipher {
+    pub(crate) fn new(device_key: &signature::PrivateKey) -> Self {
+        let mut key = [0u8; CryptoBox::SYMMETRIC_KEY_BYTES];
+        let mut index_key = [0u8; 32];
+        let hkdf = Hkdf::<Sha256>::new(None, device_key.as_bytes());
+        for (context, out) in [
+            (KEY_CONTEXT, &mut key),
+            (INDEX_KEY_CONTEXT, &mut index_key),
+        ] {
+            hkdf.expand(context, out).expect("32 bytes is a valid HKDF-SHA256 output length");
+        }
+
+        let cipher = CryptoBox::from_symmetric_key(key);
+        key.fill(0);
+        Self { cipher, index_key }
+    }
+
+    // A keyed, deterministic stand-in for a search token, equal tokens
+    // blind to the same hex string.
+    pub(crate) fn blind(&self, token: &str) -> String {
+        let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(&self.index_key)
+            .expect("HMAC takes any key length");
+        mac.update(token.as_bytes());
+        hex::encode(&mac.finalize().into_bytes()[..BLIND_BYTES])
+    }
+
+    pub(crate) fn seal(&self, plain: &[u8]) -> Result<Vec<u8>> {
+        self.cipher.encrypt_into(plain, &Nonce::random()).map_err(|e| {
+            Error::Encoding(format!("Failed to encrypt repository data: {e}"))
+        })
+    }
+
+    pub(crate) fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < Nonce::BYTES + CryptoBox::MAC_BYTES {
+            return Err(Error::Encoding("Encrypted repository data is truncated".into()));
+        }
+        self.cipher.decrypt_into(sealed).map_err(|e| {
+            Error::Encoding(format!("Failed to decrypt repository data: {e}"))
+        })
+    }
+}
+
+impl Drop for AtRestCipher {
+    fn drop(&mut self) {
+        self.index_key.fill(0);
+    }
+}
@@ -1,30 +1,245 @@
-
 use std::path::Path;
 use std::fs;
-use log::error;
+use std::sync::{Mutex, MutexGuard};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Binary, Integer, Nullable, Text};
+use log::{error, info, warn};
 
 use crate::{
     Id,
-    Error,
-    core::Result,
+    signature,
+    dht::storage::migrations::{self, Migration, MigrationError},
 };
 
 use crate::messaging::{
-    messaging_repository::MessagingRepository,
-    message::Message,
-    channel::Channel,
+    Error,
+    Result,
+    channel::Permission,
+    contact::ContactType,
+    message::MessageType,
+    message_search::{self, MessageHit},
+    self_sync::{Draft, ReadState},
+    notification::{NotifyLevel, NotifySetting},
 };
 
-#[allow(unused)]
-#[derive(Debug)]
-pub(crate) struct Database;
+use super::{
+    sql,
+    at_rest::AtRestCipher,
+    models::{ConfigEntry, DbChannel, DbContact, DbDraft, DbMessage, DbNotifyLevel, DbReadState, NewMessage},
+    schema::{config, channels, contacts, drafts, messages, notify_levels, read_states},
+};
+
+const DATABASE_FILE: &str = "messaging.db";
+// The config entry of the inbox sequence delivered up to.
+const INBOX_CURSOR: &str = ".inboxCursor";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ChannelRecord {
+    pub(crate) id:          Id,
+    pub(crate) owner:       Id,
+    pub(crate) name:        Option<String>,
+    pub(crate) permission:  Permission,
+    pub(crate) session_key: Option<Vec<u8>>,
+    pub(crate) updated:     u64,
+    pub(crate) key_epoch:   u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ContactRecord {
+    pub(crate) id:           Id,
+    pub(crate) contact_type: ContactType,
+    pub(crate) home_peer_id: Option<Id>,
+    pub(crate) name:         Option<String>,
+    pub(crate) remark:       Option<String>,
+    pub(crate) session_key:  Option<Vec<u8>>,
+    pub(crate) updated:      u64,
+    pub(crate) blocked:      bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MessageRecord {
+    pub(crate) rid:             i64,
+    pub(crate) conversation_id: Id,
+    pub(crate) sender:          Id,
+    pub(crate) message_type:    MessageType,
+    pub(crate) created:         u64,
+    pub(crate) body:            Vec<u8>,
+    // None for text/plain, the default content type of a message.
+    pub(crate) content_type:    Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct RowId {
+    #[diesel(sql_type = BigInt)]
+    rid: i64,
+}
+
+fn db_err(e: impl std::fmt::Display) -> Error {
+    Error::State(format!("Messaging database error: {e}"))
+}
+
+fn to_id(bytes: &[u8]) -> Result<Id> {
+    Id::try_from(bytes).map_err(|e| Error::Encoding(e.to_string()))
+}
+
+// Encrypts the sensitive columns of a plaintext (version 1) repository in place.
+fn encrypt_rows(conn: &mut SqliteConnection, cipher: &AtRestCipher) -> QueryResult<()> {
+    let seal = |plain: &[u8]| cipher.seal(plain).map_err(|e| {
+        error!("{e}");
+        diesel::result::Error::RollbackTransaction
+    });
+
+    // Only the columns of version 1 are there yet.
+    for entry in config::table.select(ConfigEntry::as_select()).load(conn)? {
+        diesel::update(config::table.find(&entry.key))
+            .set(config::value.eq(seal(&entry.value)?))
+            .execute(conn)?;
+    }
+
+    let keys = channels::table
+        .filter(channels::sessionKey.is_not_null())
+        .select((channels::id, channels::sessionKey.assume_not_null()))
+        .load::<(Vec<u8>, Vec<u8>)>(conn)?;
+    for (id, key) in keys {
+        diesel::update(channels::table.find(&id))
+            .set(channels::sessionKey.eq(seal(&key)?))
+            .execute(conn)?;
+    }
+
+    let bodies = messages::table
+        .select((messages::rid, messages::body))
+        .load::<(i64, Vec<u8>)>(conn)?;
+    for (rid, body) in bodies {
+        diesel::update(messages::table.find(rid))
+            .set(messages::body.eq(seal(&body)?))
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+// Adds the blinded words of a text message to the search index, other
+// messages are left out.
+fn index_message(
+    conn: &mut SqliteConnection,
+    cipher: &AtRestCipher,
+    rid: i64,
+    message_type: MessageType,
+    content_type: Option<&str>,
+    body: &[u8]
+) -> QueryResult<bool> {
+    let Some(text) = message_search::searchable_text(message_type, content_type, body) else {
+        return Ok(false);
+    };
+    let tokens = message_search::tokenize(text).iter()
+        .map(|token| cipher.blind(token))
+        .collect::<Vec<_>>();
+    if tokens.is_empty() {
+        return Ok(false);
+    }
+
+    diesel::sql_query(sql::INSERT_MESSAGE_TOKENS)
+        .bind::<BigInt, _>(rid)
+        .bind::<Text, _>(tokens.join(" "))
+        .execute(conn)
+        .map(|_| true)
+}
+
+// Rebuilds the search index from the stored messages, returns the number of
+// messages indexed.
+fn index_rows(conn: &mut SqliteConnection, cipher: &AtRestCipher) -> QueryResult<usize> {
+    diesel::sql_query(sql::CLEAR_MESSAGE_TOKENS).execute(conn)?;
+
+    let rows = messages::table
+        .select(DbMessage::as_select())
+        .load(conn)?;
+    let mut indexed = 0;
+    for row in rows {
+        let Ok(message_type) = MessageType::try_from(row.messageType) else {
+            continue;
+        };
+        let body = match cipher.open(&row.body) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Skipping unreadable message {} from the search index: {e}", row.rid);
+                continue;
+            }
+        };
+        if index_message(conn, cipher, row.rid, message_type, row.contentType.as_deref(), &body)? {
+            indexed += 1;
+        }
+    }
+    Ok(indexed)
+}
+
+const MIGRATIONS: &[Migration<AtRestCipher>] = &[
+    Migration {
+        version: sql::PLAINTEXT_VERSION,
+        statements: &[
+            sql::CREATE_CONFIG_TABLE,
+            sql::CREATE_CHANNELS_TABLE,
+            sql::CREATE_MESSAGES_TABLE,
+            sql::CREATE_MESSAGES_INDEX,
+        ],
+        transform: None,
+    },
+    Migration {
+        version: 2,
+        statements: &[],
+        transform: Some(encrypt_rows),
+    },
+    Migration {
+        version: 3,
+        statements: &[sql::CREATE_CONTACTS_TABLE, sql::ADD_CHANNELS_KEY_EPOCH],
+        transform: None,
+    },
+    Migration {
+        version: 4,
+        statements: &[sql::ADD_CONTACTS_BLOCKED],
+        transform: None,
+    },
+    // Messages stored before are all indexed as plain text.
+    Migration {
+        version: 5,
+        statements: &[sql::ADD_MESSAGES_CONTENT_TYPE, sql::CREATE_MESSAGES_FTS_TABLE],
+        transform: Some(|conn, cipher| index_rows(conn, cipher).map(|_| ())),
+    },
+    Migration {
+        version: 6,
+        statements: &[sql::CREATE_READ_STATES_TABLE, sql::CREATE_DRAFTS_TABLE],
+        transform: None,
+    },
+    Migration {
+        version: 7,
+        statements: &[sql::CREATE_NOTIFY_LEVELS_TABLE],
+        transform: None,
+    },
+];
+
+fn migrate(conn: &mut SqliteConnection, cipher: &AtRestCipher) -> Result<()> {
+    let version = migrations::migrate(conn, MIGRATIONS, cipher).map_err(|e| match e {
+        MigrationError::Newer(version) => Error::UnsupportedVersion(version as u32),
+        MigrationError::Failed { version, error } => db_err(format!(
+            "failed to upgrade to version {version}: {error}"
+        )),
+    })?;
+
+    if version == sql::PLAINTEXT_VERSION {
+        diesel::sql_query(sql::VACUUM).execute(conn).map_err(db_err)?;
+        info!("Messaging repository migrated to encrypted storage");
+    }
+    Ok(())
+}
+
+pub(crate) struct Database {
+    conn:   Mutex<SqliteConnection>,
+    cipher: AtRestCipher,
+}
 
-#[allow(unused)]
 impl Database {
-    pub(crate) fn open(path: &Path) -> Result<Self> {
+    pub(crate) fn open(path: &Path, device_key: &signature::PrivateKey) -> Result<Self> {
         let metadata = match fs::metadata(path) {
             Ok(metadata) => metadata,
-            Err(e) => {
+            Err(_) => {
                 fs::create_dir_all(path).map_err(|e| {
                     Error::Argument(format!("Failed to create directory {}: {e}", path.display()))
                 })?;
@@ -38,50 +253,435 @@ impl Database {
             Err(Error::Argument(format!("Path {} is not a directory", path.display())))?;
         }
 
-        let _path = fs::canonicalize(path).map_err(|e| {
+        let path = fs::canonicalize(path).map_err(|e| {
             error!("{e}, path: {}", path.display());
             Error::Argument(format!("Invalid persistent path {} with error: {e}", path.display()))
         })?;
 
-        Ok(Database {})
+        let file = path.join(DATABASE_FILE);
+        let mut conn = SqliteConnection::establish(&file.to_string_lossy()).map_err(|e| {
+            Error::State(format!("Failed to open messaging database {}: {e}", file.display()))
+        })?;
+
+        let cipher = AtRestCipher::new(device_key);
+        migrate(&mut conn, &cipher)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            cipher,
+        })
     }
-}
 
-impl MessagingRepository for Database {
-    fn put_config(&self, _key: &str, _val: Vec<u8>) -> Result<()>  {
-        unimplemented!()
+    // Whether `path` already holds a messaging repository.
+    pub(crate) fn exists(path: &Path) -> bool {
+        path.join(DATABASE_FILE).exists()
     }
 
-    fn get_config(&self, _key: &str) -> Result<Vec<u8>> {
-        unimplemented!()
+    fn conn(&self) -> MutexGuard<'_, SqliteConnection> {
+        self.conn.lock().unwrap()
     }
 
-    fn put_messages(&self, _messages: &[Message]) -> Result<()> {
-        unimplemented!()
+    pub(crate) fn put_config(&self, key: &str, value: &[u8]) -> Result<()> {
+        let entry = ConfigEntry {
+            key: key.to_string(),
+            value: self.cipher.seal(value)?,
+        };
+        diesel::replace_into(config::table)
+            .values(&entry)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
     }
 
-    fn messages_between(&self, _conversation_id: &Id, _begin: u64, _end: u64) -> Result<Vec<Message>> {
-        unimplemented!()
+    pub(crate) fn get_config(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let entry = config::table.find(key)
+            .select(ConfigEntry::as_select())
+            .first(&mut *self.conn())
+            .optional()
+            .map_err(db_err)?;
+
+        Ok(entry.and_then(|entry| {
+            self.cipher.open(&entry.value)
+                .map_err(|e| warn!("Skipping unreadable config entry {}: {e}", entry.key))
+                .ok()
+        }))
     }
 
-    fn messages_since(&self, _conversation_id: &Id, _since: u64, _limit: usize, _offset: usize) -> Result<Vec<Message>> {
-        unimplemented!()
+    pub(crate) fn put_channel(&self, channel: &ChannelRecord) -> Result<()> {
+        let row = DbChannel {
+            id: channel.id.as_bytes().to_vec(),
+            owner: channel.owner.as_bytes().to_vec(),
+            name: channel.name.clone(),
+            permission: channel.permission.into(),
+            sessionKey: channel.session_key.as_ref()
+                .map(|key| self.cipher.seal(key))
+                .transpose()?,
+            updated: channel.updated as i64,
+            keyEpoch: channel.key_epoch as i32,
+        };
+        diesel::replace_into(channels::table)
+            .values(&row)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
+    }
+
+    pub(crate) fn channel(&self, id: &Id) -> Result<Option<ChannelRecord>> {
+        let row = channels::table.find(id.as_bytes())
+            .select(DbChannel::as_select())
+            .first(&mut *self.conn())
+            .optional()
+            .map_err(db_err)?;
+
+        Ok(row.and_then(|row| {
+            self.to_channel(row)
+                .map_err(|e| warn!("Skipping unreadable channel {}: {e}", id))
+                .ok()
+        }))
+    }
+
+    pub(crate) fn channels(&self) -> Result<Vec<ChannelRecord>> {
+        let rows = channels::table
+            .select(DbChannel::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            self.to_channel(row)
+                .map_err(|e| warn!("Skipping unreadable channel record: {e}"))
+                .ok()
+        }).collect())
+    }
+
+    // Removes the channel record together with its cached session key.
+    pub(crate) fn remove_channel(&self, id: &Id) -> Result<bool> {
+        diesel::delete(channels::table.find(id.as_bytes()))
+            .execute(&mut *self.conn())
+            .map(|n| n > 0)
+            .map_err(db_err)
+    }
+
+    pub(crate) fn put_contact(&self, contact: &ContactRecord) -> Result<()> {
+        let row = DbContact {
+            id: contact.id.as_bytes().to_vec(),
+            contactType: contact.contact_type.into(),
+            homePeerId: contact.home_peer_id.map(|id| id.as_bytes().to_vec()),
+            name: contact.name.clone(),
+            remark: contact.remark.clone(),
+            sessionKey: contact.session_key.as_ref()
+                .map(|key| self.cipher.seal(key))
+                .transpose()?,
+            updated: contact.updated as i64,
+            blocked: contact.blocked,
+        };
+        diesel::replace_into(contacts::table)
+            .values(&row)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
+    }
+
+    pub(crate) fn contact(&self, id: &Id) -> Result<Option<ContactRecord>> {
+        let row = contacts::table.find(id.as_bytes())
+            .select(DbContact::as_select())
+            .first(&mut *self.conn())
+            .optional()
+            .map_err(db_err)?;
+
+        Ok(row.and_then(|row| {
+            self.to_contact(row)
+                .map_err(|e| warn!("Skipping unreadable contact {}: {e}", id))
+                .ok()
+        }))
+    }
+
+    pub(crate) fn contacts(&self) -> Result<Vec<ContactRecord>> {
+        let rows = contacts::table
+            .select(DbContact::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            self.to_contact(row)
+                .map_err(|e| warn!("Skipping unreadable contact record: {e}"))
+                .ok()
+        }).collect())
+    }
+
+    // The ids of the contacts blocked by the user.
+    pub(crate) fn blocked_contacts(&self) -> Result<Vec<Id>> {
+        let rows = contacts::table
+            .filter(contacts::blocked.eq(true))
+            .select(contacts::id)
+            .load::<Vec<u8>>(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.iter().filter_map(|id| {
+            to_id(id).map_err(|e| warn!("Skipping unreadable contact id: {e}")).ok()
+        }).collect())
+    }
+
+    // Returns the local storage id assigned to the message.
+    pub(crate) fn put_message(&self, msg: &MessageRecord) -> Result<i64> {
+        let body = self.cipher.seal(&msg.body)?;
+        let row = NewMessage {
+            conversationId: msg.conversation_id.as_bytes(),
+            sender: msg.sender.as_bytes(),
+            messageType: msg.message_type as i32,
+            created: msg.created as i64,
+            body: &body,
+            contentType: msg.content_type.as_deref(),
+        };
+
+        let mut conn = self.conn();
+        conn.transaction(|conn| {
+            diesel::insert_into(messages::table).values(&row).execute(conn)?;
+            let rid = diesel::sql_query(sql::LAST_INSERT_ROWID).get_result::<RowId>(conn)?.rid;
+            index_message(conn, &self.cipher, rid,
+                msg.message_type, msg.content_type.as_deref(), &msg.body)?;
+            QueryResult::Ok(rid)
+        }).map_err(db_err)
+    }
+
+    pub(crate) fn messages_since(&self,
+        conversation_id: &Id,
+        since: u64,
+        limit: usize,
+        offset: usize
+    ) -> Result<Vec<MessageRecord>> {
+        let rows = messages::table
+            .filter(messages::conversationId.eq(conversation_id.as_bytes()))
+            .filter(messages::created.ge(since as i64))
+            .order((messages::created.asc(), messages::rid.asc()))
+            .offset(offset as i64)
+            .limit(limit as i64)
+            .select(DbMessage::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            let rid = row.rid;
+            self.to_message(row)
+                .map_err(|e| warn!("Skipping unreadable message {rid}: {e}"))
+                .ok()
+        }).collect())
+    }
+
+    pub(crate) fn remove_messages_by_conversation(&self, conversation_id: &Id) -> Result<usize> {
+        self.conn().transaction(|conn| {
+            diesel::sql_query(sql::REMOVE_CONVERSATION_TOKENS)
+                .bind::<Binary, _>(conversation_id.as_bytes())
+                .execute(conn)?;
+            diesel::delete(messages::table
+                .filter(messages::conversationId.eq(conversation_id.as_bytes())))
+                .execute(conn)
+        }).map_err(db_err)
+    }
+
+    // Text messages containing every word of `query`, newest first, within
+    // the conversation `scope` or all of them.
+    pub(crate) fn search_messages(&self,
+        query: &str,
+        scope: Option<&Id>,
+        limit: usize,
+        offset: usize
+    ) -> Result<Vec<MessageHit>> {
+        let terms = message_search::tokenize(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let expr = terms.iter()
+            .map(|term| format!("\"{}\"", self.cipher.blind(term)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let mut conn = self.conn();
+        let rids = diesel::sql_query(sql::SEARCH_MESSAGES)
+            .bind::<Text, _>(expr)
+            .bind::<Nullable<Binary>, _>(scope.map(|id| id.as_bytes()))
+            .bind::<BigInt, _>(limit as i64)
+            .bind::<BigInt, _>(offset as i64)
+            .load::<RowId>(&mut *conn)
+            .map_err(db_err)?;
+        let rows = messages::table
+            .filter(messages::rid.eq_any(rids.iter().map(|row| row.rid)))
+            .order((messages::created.desc(), messages::rid.desc()))
+            .select(DbMessage::as_select())
+            .load(&mut *conn)
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            let rid = row.rid;
+            self.to_message(row)
+                .map_err(|e| warn!("Skipping unreadable message {rid}: {e}"))
+                .ok()
+        }).map(|msg| MessageHit::new(
+            msg.conversation_id,
+            msg.rid,
+            msg.created,
+            &String::from_utf8_lossy(&msg.body),
+            &terms,
+        )).collect())
+    }
+
+    // Rebuilds the message search index, returns the number of messages
+    // indexed.
+    pub(crate) fn reindex_messages(&self) -> Result<usize> {
+        self.conn().transaction(|conn| {
+            index_rows(conn, &self.cipher)
+        }).map_err(db_err)
+    }
+
+    pub(crate) fn put_inbox_cursor(&self, cursor: u64) -> Result<()> {
+        self.put_config(INBOX_CURSOR, &cursor.to_be_bytes())
+    }
+
+    // None until the first sync with the service.
+    pub(crate) fn inbox_cursor(&self) -> Result<Option<u64>> {
+        Ok(self.get_config(INBOX_CURSOR)?
+            .and_then(|v| v.try_into().ok())
+            .map(u64::from_be_bytes))
+    }
+
+    // Stores the read states synced from any device of the user.
+    pub(crate) fn put_read_states(&self, states: &[ReadState]) -> Result<()> {
+        let rows = states.iter().map(|state| DbReadState {
+            conversationId: state.conversation_id().as_bytes().to_vec(),
+            lastRead: state.last_read() as i64,
+            updated: state.updated() as i64,
+        }).collect::<Vec<_>>();
+
+        diesel::replace_into(read_states::table)
+            .values(&rows)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
+    }
+
+    pub(crate) fn read_states(&self) -> Result<Vec<ReadState>> {
+        let rows = read_states::table
+            .select(DbReadState::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            to_id(&row.conversationId)
+                .map(|id| ReadState::new(id, row.lastRead as u64, row.updated as u64))
+                .map_err(|e| warn!("Skipping unreadable read state: {e}"))
+                .ok()
+        }).collect())
+    }
+
+    pub(crate) fn put_drafts(&self, drafts: &[Draft]) -> Result<()> {
+        let rows = drafts.iter().map(|draft| Ok(DbDraft {
+            conversationId: draft.conversation.as_bytes().to_vec(),
+            body: self.cipher.seal(draft.text.as_bytes())?,
+            updated: draft.updated as i64,
+        })).collect::<Result<Vec<_>>>()?;
+
+        diesel::replace_into(drafts::table)
+            .values(&rows)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
+    }
+
+    // Includes the empty drafts, they still order the later updates.
+    pub(crate) fn drafts(&self) -> Result<Vec<Draft>> {
+        let rows = drafts::table
+            .select(DbDraft::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            self.to_draft(row)
+                .map_err(|e| warn!("Skipping unreadable draft: {e}"))
+                .ok()
+        }).collect())
+    }
+
+    // Stores the notify levels set on any device of the user.
+    pub(crate) fn put_notify_levels(&self, settings: &[NotifySetting]) -> Result<()> {
+        let rows = settings.iter().map(|setting| DbNotifyLevel {
+            conversationId: setting.conversation_id().as_bytes().to_vec(),
+            level: setting.level() as i32,
+            updated: setting.updated() as i64,
+        }).collect::<Vec<_>>();
+
+        diesel::replace_into(notify_levels::table)
+            .values(&rows)
+            .execute(&mut *self.conn())
+            .map(|_| ())
+            .map_err(db_err)
+    }
+
+    pub(crate) fn notify_levels(&self) -> Result<Vec<NotifySetting>> {
+        let rows = notify_levels::table
+            .select(DbNotifyLevel::as_select())
+            .load(&mut *self.conn())
+            .map_err(db_err)?;
+
+        Ok(rows.into_iter().filter_map(|row| {
+            to_id(&row.conversationId)
+                .and_then(|id| NotifyLevel::try_from(row.level)
+                    .map(|level| NotifySetting::new(id, level, row.updated as u64)))
+                .map_err(|e| warn!("Skipping unreadable notify level: {e}"))
+                .ok()
+        }).collect())
     }
 
-    fn remove_messages(&self, _rids: &[u32]) -> Result<()> {
-        unimplemented!()
+    fn to_draft(&self, row: DbDraft) -> Result<Draft> {
+        let text = String::from_utf8(self.cipher.open(&row.body)?)
+            .map_err(|e| Error::Encoding(e.to_string()))?;
+        Ok(Draft {
+            conversation: to_id(&row.conversationId)?,
+            text,
+            updated: row.updated as u64,
+        })
     }
 
-    fn remove_messages_by_conversation(&self, _conversation_id: &Id) -> Result<()> {
-        unimplemented!()
+    fn to_channel(&self, row: DbChannel) -> Result<ChannelRecord> {
+        Ok(ChannelRecord {
+            id: to_id(&row.id)?,
+            owner: to_id(&row.owner)?,
+            name: row.name,
+            permission: Permission::try_from(row.permission)
+                .map_err(|e| Error::Encoding(e.to_string()))?,
+            session_key: row.sessionKey
+                .map(|key| self.cipher.open(&key))
+                .transpose()?,
+            updated: row.updated as u64,
+            key_epoch: row.keyEpoch as u32,
+        })
     }
 
-    fn channel(&self, _channel_id: &Id) -> Result<Option<Channel>> {
-        // unimplemented!()
-        Ok(None)
+    fn to_contact(&self, row: DbContact) -> Result<ContactRecord> {
+        Ok(ContactRecord {
+            id: to_id(&row.id)?,
+            contact_type: ContactType::try_from(row.contactType)
+                .map_err(|e| Error::Encoding(e.to_string()))?,
+            home_peer_id: row.homePeerId
+                .map(|id| to_id(&id))
+                .transpose()?,
+            name: row.name,
+            remark: row.remark,
+            session_key: row.sessionKey
+                .map(|key| self.cipher.open(&key))
+                .transpose()?,
+            updated: row.updated as u64,
+            blocked: row.blocked,
+        })
     }
 
-    fn channels(&self) -> Result<Vec<Channel>> {
-        unimplemented!()
+    fn to_message(&self, row: DbMessage) -> Result<MessageRecord> {
+        Ok(MessageRecord {
+            rid: row.rid,
+            conversation_id: to_id(&row.conversationId)?,
+            sender: to_id(&row.sender)?,
+            message_type: MessageType::try_from(row.messageType)?,
+            created: row.created as u64,
+            body: self.cipher.open(&row.body)?,
+            content_type: row.contentType,
+        })
     }
 }
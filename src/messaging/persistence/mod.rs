@@ -0,0 +1,5 @@
+pub(crate) mod at_rest;
+pub(crate) mod database;
+mod models;
+mod schema;
+mod sql;
@@ -0,0 +1,103 @@
+use diesel::prelude::*;
+use super::schema::{
+    config,
+    channels,
+    contacts,
+    messages,
+    read_states,
+    drafts,
+    notify_levels,
+};
+
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = config)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct ConfigEntry {
+    pub(crate) key:     String,
+    pub(crate) value:   Vec<u8>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = channels)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbChannel {
+    pub(crate) id:          Vec<u8>,
+    pub(crate) owner:       Vec<u8>,
+    pub(crate) name:        Option<String>,
+    pub(crate) permission:  i32,
+    pub(crate) sessionKey:  Option<Vec<u8>>,
+    pub(crate) updated:     i64,
+    pub(crate) keyEpoch:    i32,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = contacts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbContact {
+    pub(crate) id:          Vec<u8>,
+    pub(crate) contactType: i32,
+    pub(crate) homePeerId:  Option<Vec<u8>>,
+    pub(crate) name:        Option<String>,
+    pub(crate) remark:      Option<String>,
+    pub(crate) sessionKey:  Option<Vec<u8>>,
+    pub(crate) updated:     i64,
+    pub(crate) blocked:     bool,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable)]
+#[diesel(table_name = messages)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbMessage {
+    pub(crate) rid:             i64,
+    pub(crate) conversationId:  Vec<u8>,
+    pub(crate) sender:          Vec<u8>,
+    pub(crate) messageType:     i32,
+    pub(crate) created:         i64,
+    pub(crate) body:            Vec<u8>,
+    pub(crate) contentType:     Option<String>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Insertable)]
+#[diesel(table_name = messages)]
+pub(crate) struct NewMessage<'a> {
+    pub(crate) conversationId:  &'a [u8],
+    pub(crate) sender:          &'a [u8],
+    pub(crate) messageType:     i32,
+    pub(crate) created:         i64,
+    pub(crate) body:            &'a [u8],
+    pub(crate) contentType:     Option<&'a str>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = read_states)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbReadState {
+    pub(crate) conversationId:  Vec<u8>,
+    pub(crate) lastRead:        i64,
+    pub(crate) updated:         i64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = drafts)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbDraft {
+    pub(crate) conversationId:  Vec<u8>,
+    pub(crate) body:            Vec<u8>,
+    pub(crate) updated:         i64,
+}
+
+#[allow(non_snake_case)]
+#[derive(Queryable, Selectable, Insertable)]
+#[diesel(table_name = notify_levels)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub(crate) struct DbNotifyLevel {
+    pub(crate) conversationId:  Vec<u8>,
+    pub(crate) level:           i32,
+    pub(crate) updated:         i64,
+}
@@ -0,0 +1,68 @@
+diesel::table! {
+    config (key) {
+        key -> Text,
+        value -> Binary,
+    }
+}
+
+diesel::table! {
+    channels (id) {
+        id -> Binary,
+        owner -> Binary,
+        name -> Nullable<Text>,
+        permission -> Integer,
+        sessionKey -> Nullable<Binary>,
+        updated -> BigInt,
+        keyEpoch -> Integer,
+    }
+}
+
+diesel::table! {
+    contacts (id) {
+        id -> Binary,
+        #[sql_name = "type"]
+        contactType -> Integer,
+        homePeerId -> Nullable<Binary>,
+        name -> Nullable<Text>,
+        remark -> Nullable<Text>,
+        sessionKey -> Nullable<Binary>,
+        updated -> BigInt,
+        blocked -> Bool,
+    }
+}
+
+diesel::table! {
+    messages (rid) {
+        rid -> BigInt,
+        conversationId -> Binary,
+        sender -> Binary,
+        messageType -> Integer,
+        created -> BigInt,
+        body -> Binary,
+        contentType -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    read_states (conversationId) {
+        conversationId -> Binary,
+        lastRead -> BigInt,
+        updated -> BigInt,
+    }
+}
+
+diesel::table! {
+    drafts (conversationId) {
+        conversationId -> Binary,
+        body -> Binary,
+        updated -> BigInt,
+    }
+}
+
+diesel::table! {
+    notify_levels (conversationId) {
+        conversationId -> Binary,
+        level -> Integer,
+        updated -> BigInt,
+    }
+}
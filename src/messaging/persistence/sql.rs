@@ -0,0 +1,123 @@
+// Version 1 kept every column in plaintext. Version 2 encrypts config values,
+// channel session keys and message bodies with the at-rest key. Version 3
+// adds the contacts table and the channel key epoch. Version 4 adds the blocked
+// flag of contacts. Version 5 adds the content type of messages and the message
+// search index. Version 6 adds the read state and drafts of conversations.
+// The tables are created in the layout of the version that added them, the
+// later columns are added by the migrations.
+pub(crate) const PLAINTEXT_VERSION: i32 = 1;
+
+pub(crate) const CREATE_CONFIG_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS config(\
+        key TEXT NOT NULL PRIMARY KEY, \
+        value BLOB NOT NULL\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const CREATE_CHANNELS_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS channels(\
+        id BLOB NOT NULL PRIMARY KEY, \
+        owner BLOB NOT NULL, \
+        name TEXT, \
+        permission INTEGER NOT NULL DEFAULT 0, \
+        sessionKey BLOB, \
+        updated INTEGER NOT NULL DEFAULT 0\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const ADD_CHANNELS_KEY_EPOCH: &str = "
+        ALTER TABLE channels ADD COLUMN keyEpoch INTEGER NOT NULL DEFAULT 0
+    ";
+
+pub(crate) const CREATE_CONTACTS_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS contacts(\
+        id BLOB NOT NULL PRIMARY KEY, \
+        type INTEGER NOT NULL DEFAULT 1, \
+        homePeerId BLOB, \
+        name TEXT, \
+        remark TEXT, \
+        sessionKey BLOB, \
+        updated INTEGER NOT NULL DEFAULT 0\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const ADD_CONTACTS_BLOCKED: &str = "
+        ALTER TABLE contacts ADD COLUMN blocked INTEGER NOT NULL DEFAULT 0
+    ";
+
+pub(crate) const CREATE_MESSAGES_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS messages(\
+        rid INTEGER PRIMARY KEY AUTOINCREMENT, \
+        conversationId BLOB NOT NULL, \
+        sender BLOB NOT NULL, \
+        messageType INTEGER NOT NULL DEFAULT 1, \
+        created INTEGER NOT NULL DEFAULT 0, \
+        body BLOB NOT NULL\
+        )
+    ";
+
+pub(crate) const ADD_MESSAGES_CONTENT_TYPE: &str = "
+        ALTER TABLE messages ADD COLUMN contentType TEXT
+    ";
+
+pub(crate) const CREATE_MESSAGES_INDEX: &str = "
+        CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages(conversationId, created)
+    ";
+
+// The words of text messages blinded with the index key, the rowid is the rid
+// of the message.
+pub(crate) const CREATE_MESSAGES_FTS_TABLE: &str = "
+        CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(tokens)
+    ";
+
+pub(crate) const INSERT_MESSAGE_TOKENS: &str = "
+        INSERT INTO messages_fts(rowid, tokens) VALUES (?, ?)
+    ";
+
+pub(crate) const CLEAR_MESSAGE_TOKENS: &str = "DELETE FROM messages_fts";
+
+pub(crate) const REMOVE_CONVERSATION_TOKENS: &str = "
+        DELETE FROM messages_fts WHERE rowid IN \
+        (SELECT rid FROM messages WHERE conversationId = ?)
+    ";
+
+// Binds the match expression, the conversation scope or NULL, limit and offset.
+pub(crate) const SEARCH_MESSAGES: &str = "
+        SELECT messages.rid AS rid FROM messages_fts \
+        JOIN messages ON messages.rid = messages_fts.rowid \
+        WHERE messages_fts MATCH ?1 \
+        AND (?2 IS NULL OR messages.conversationId = ?2) \
+        ORDER BY messages.created DESC, messages.rid DESC \
+        LIMIT ?3 OFFSET ?4
+    ";
+
+// Synced across the devices of the user, `lastRead` is the creation time of
+// the last message read.
+pub(crate) const CREATE_READ_STATES_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS read_states(\
+        conversationId BLOB NOT NULL PRIMARY KEY, \
+        lastRead INTEGER NOT NULL DEFAULT 0, \
+        updated INTEGER NOT NULL DEFAULT 0\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const CREATE_DRAFTS_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS drafts(\
+        conversationId BLOB NOT NULL PRIMARY KEY, \
+        body BLOB NOT NULL, \
+        updated INTEGER NOT NULL DEFAULT 0\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const CREATE_NOTIFY_LEVELS_TABLE: &str = "
+        CREATE TABLE IF NOT EXISTS notify_levels(\
+        conversationId BLOB NOT NULL PRIMARY KEY, \
+        level INTEGER NOT NULL DEFAULT 0, \
+        updated INTEGER NOT NULL DEFAULT 0\
+        ) WITHOUT ROWID
+    ";
+
+pub(crate) const LAST_INSERT_ROWID: &str = "SELECT last_insert_rowid() AS rid";
+
+// Rewrites the file so no freed page keeps plaintext from before the migration.
+pub(crate) const VACUUM: &str = "VACUUM";
@@ -0,0 +1,176 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::Id;
+use crate::messaging::errors::{Error, Result};
+
+/// Interval between presence heartbeats published while connected.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A contact announced online but silent for longer than this is shown as away.
+pub const STALE_TIMEOUT: Duration = Duration::from_secs(3 * 60);
+
+const PRESENCE_TOPIC_PREFIX: &str = "presence/";
+
+/// The presence state of a contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum PresenceState {
+    /// Offline, either announced or detected by the broker on a lost connection.
+    Offline = 0,
+    /// Connected and sending heartbeats.
+    Online  = 1,
+    /// Announced online, but no heartbeat was seen within [`STALE_TIMEOUT`].
+    Away    = 2,
+}
+
+/// The presence of a contact together with the last time it was seen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Presence {
+    #[serde(rename = "s")]
+    state       : PresenceState,
+    #[serde(rename = "t")]
+    last_seen   : u64,
+}
+
+impl Presence {
+    pub fn new(state: PresenceState, last_seen: u64) -> Self {
+        Self { state, last_seen }
+    }
+
+    /// The presence in `state`, seen now.
+    pub fn now(state: PresenceState) -> Self {
+        Self::new(state, crate::as_ms!(SystemTime::now()) as u64)
+    }
+
+    /// The presence state.
+    pub fn state(&self) -> PresenceState {
+        self.state
+    }
+
+    /// Milliseconds since the Unix epoch when the contact was last seen.
+    pub fn last_seen(&self) -> u64 {
+        self.last_seen
+    }
+
+    /// Whether the contact is currently online.
+    pub fn is_online(&self) -> bool {
+        self.state == PresenceState::Online
+    }
+
+    /// Encode the presence as the CBOR payload published on the presence topic.
+    pub fn to_bytes(self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(&self)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-encode presence: {}", e)))
+    }
+
+    /// Decode a presence payload received on a presence topic.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice::<Presence>(bytes)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-decode presence: {}", e)))
+    }
+}
+
+/// The MQTT topic a user publishes its presence on.
+pub fn presence_topic(user_id: &Id) -> String {
+    format!("{}{}", PRESENCE_TOPIC_PREFIX, user_id.to_base58())
+}
+
+/// The user id of a presence topic, `None` for any other topic.
+pub fn presence_topic_owner(topic: &str) -> Option<Id> {
+    topic.strip_prefix(PRESENCE_TOPIC_PREFIX)
+        .and_then(|id| Id::try_from_base58(id).ok())
+}
+
+/// Cached presence of the tracked contacts.
+///
+/// Only contacts in the local contact list are tracked, announcements from
+/// anybody else are ignored. An online contact without a heartbeat for
+/// longer than the stale timeout degrades to away, so a missed offline
+/// notification does not leave it shown as online forever.
+pub struct PresenceCache {
+    timeout     : u64,
+    tracked     : HashSet<Id>,
+    entries     : HashMap<Id, Presence>,
+}
+
+impl PresenceCache {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout     : timeout.as_millis() as u64,
+            tracked     : HashSet::new(),
+            entries     : HashMap::new(),
+        }
+    }
+
+    /// Replace the tracked contacts, returning the ids whose presence topics
+    /// have to be subscribed and unsubscribed respectively.
+    pub fn track(&mut self, contacts: &[Id]) -> (Vec<Id>, Vec<Id>) {
+        let contacts = contacts.iter().copied().collect::<HashSet<_>>();
+        let added = contacts.difference(&self.tracked).copied().collect::<Vec<_>>();
+        let removed = self.tracked.difference(&contacts).copied().collect::<Vec<_>>();
+
+        for id in removed.iter() {
+            self.entries.remove(id);
+        }
+        self.tracked = contacts;
+        (added, removed)
+    }
+
+    pub fn is_tracked(&self, id: &Id) -> bool {
+        self.tracked.contains(id)
+    }
+
+    /// The presence of a tracked contact as of `now`.
+    pub fn get(&self, id: &Id, now: u64) -> Option<Presence> {
+        self.entries.get(id).map(|p| self.effective(p, now))
+    }
+
+    /// Apply a presence announcement received at `now`, returning the new
+    /// presence if the visible state of the contact changed.
+    pub fn update(&mut self, id: &Id, presence: Presence, now: u64) -> Option<Presence> {
+        if !self.is_tracked(id) {
+            return None;
+        }
+
+        let prev = self.get(id, now);
+        // The last will is composed at connect time and carries a stale timestamp.
+        let last_seen = prev.map_or(presence.last_seen, |p| p.last_seen.max(presence.last_seen));
+        let current = Presence::new(presence.state, last_seen);
+        self.entries.insert(*id, current);
+
+        let current = self.effective(&current, now);
+        match prev {
+            Some(p) if p.state == current.state => None,
+            _ => Some(current),
+        }
+    }
+
+    /// Degrade online contacts that went silent, returning those that changed.
+    pub fn expire(&mut self, now: u64) -> Vec<(Id, Presence)> {
+        let mut changed = Vec::new();
+        for (id, presence) in self.entries.iter_mut() {
+            let effective = Self::degrade(presence, self.timeout, now);
+            if effective.state != presence.state {
+                *presence = effective;
+                changed.push((*id, effective));
+            }
+        }
+        changed
+    }
+
+    fn effective(&self, presence: &Presence, now: u64) -> Presence {
+        Self::degrade(presence, self.timeout, now)
+    }
+
+    fn degrade(presence: &Presence, timeout: u64, now: u64) -> Presence {
+        match presence.state {
+            PresenceState::Online if now.saturating_sub(presence.last_seen) > timeout => {
+                Presence::new(PresenceState::Away, presence.last_seen)
+            },
+            _ => *presence,
+        }
+    }
+}
@@ -3,16 +3,18 @@ use sha2::{Digest, Sha256};
 use unicode_normalization::UnicodeNormalization;
 use serde::Deserialize;
 
-use crate::Id;
+use crate::{Id, Identity, CryptoIdentity};
+use crate::messaging::{
+    UserProfile,
+    errors::{Error, Result},
+};
 
 #[derive(Debug, Clone, Deserialize, Hash)]
 pub struct Profile {
 	#[serde(rename = "id")]
-    #[serde(with = "crate::serde_id_as_base58")]
 	id: Id,
 
 	#[serde(rename = "p")]
-    #[serde(with = "crate::serde_id_as_base58")]
     home_peerid: Id,
 
     #[serde(rename = "ps")]
@@ -22,8 +24,7 @@ pub struct Profile {
 	#[serde(rename = "n")]
     name: String,
 
-	#[serde(rename = "a")]
-    #[serde(skip)]
+	#[serde(rename = "a", default)]
     avatar: bool,
 
 	#[serde(rename = "nt")]
@@ -60,16 +61,24 @@ impl Profile {
     }
 
     pub fn notice(&self) -> Option<&str> {
-        self.notice.as_ref().map(|v| v.as_str())
+        self.notice.as_deref()
     }
 
     pub fn sig(&self) -> &[u8] {
         &self.sig
     }
 
+    // Signed by the user over the digest of the profile fields.
     pub fn is_genuine(&self) -> bool {
-        //TODO: unimplemented!();
-        true
+        let digest = digest(&self.id,
+            &self.home_peerid,
+            Some(&self.name),
+            self.avatar,
+            self.notice.as_deref()
+        );
+        self.id.to_signature_key()
+            .verify(&digest, &self.sig)
+            .unwrap_or(false)
     }
 }
 
@@ -106,16 +115,43 @@ pub(crate) fn digest(id: &Id,
     sha256.update(id.as_bytes());
     sha256.update(peerid.as_bytes());
 
-    name.map(|v| {
+    if let Some(v) = name {
         sha256.update(v.nfc().collect::<String>().as_bytes());
-    });
+    }
 
     let avatar: u8 = avatar as u8;
-    sha256.update(&[avatar]);
-    notice.map(|v| {
+    sha256.update([avatar]);
+    if let Some(v) = notice {
         sha256.update(v.nfc().collect::<String>().as_bytes());
-    });
+    }
 
     sha256.finalize().to_vec()
 }
 
+
+/// The user profiles kept by the messaging service, implemented by the API
+/// client.
+pub(crate) trait ProfileStore {
+    /// The profile of the user, None if the user never set one.
+    async fn fetch_profile(&mut self, id: &Id) -> Result<Option<Profile>>;
+}
+
+/// Fetch the profile of the user from the service. A profile never set
+/// yields an empty one, a profile not signed by the user is rejected.
+pub(crate) async fn acquire<S: ProfileStore>(store: &mut S, user: &CryptoIdentity) -> Result<UserProfile> {
+    let Some(profile) = store.fetch_profile(user.id()).await? else {
+        return Ok(UserProfile::new(user.clone(), String::new(), false));
+    };
+
+    if profile.id() != user.id() {
+        return Err(Error::Auth(format!(
+            "Profile of {} returned for user {}", profile.id(), user.id()
+        )));
+    }
+    if !profile.is_genuine() {
+        return Err(Error::Auth(format!(
+            "Profile of user {} has an invalid signature", user.id()
+        )));
+    }
+    Ok(UserProfile::new(user.clone(), profile.name().to_string(), profile.has_avatar()))
+}
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::Id;
+
+/// Limits on the messages accepted from a single sender, applied to the
+/// inbox before a message is decrypted, stored or dispatched.
+///
+/// Every sender gets a token bucket refilled at `rate` messages per second
+/// and holding up to `burst` of them, messages beyond it are dropped. A
+/// sender going on after `mute_after` drops in a row is muted for
+/// `mute_duration`. Messages in a channel are limited per channel and sender.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InboundRateLimit {
+    rate            : f64,
+    burst           : u32,
+    mute_after      : u32,
+    mute_duration   : Duration,
+}
+
+impl InboundRateLimit {
+    pub const DEFAULT_RATE: f64 = 10.0;
+    pub const DEFAULT_BURST: u32 = 50;
+    pub const DEFAULT_MUTE_AFTER: u32 = 500;
+    pub const DEFAULT_MUTE_DURATION: Duration = Duration::from_secs(10 * 60);
+
+    /// Limits of `rate` messages per second with bursts of `burst`, and the
+    /// default mute threshold. A rate not above zero keeps the default rate,
+    /// the burst is at least 1.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate            : match rate.is_finite() && rate > 0.0 {
+                true => rate,
+                false => Self::DEFAULT_RATE,
+            },
+            burst           : burst.max(1),
+            mute_after      : Self::DEFAULT_MUTE_AFTER,
+            mute_duration   : Self::DEFAULT_MUTE_DURATION,
+        }
+    }
+
+    /// Mutes a sender for `duration` once `after` of its messages in a row
+    /// were dropped, zero never mutes.
+    pub fn with_mute(mut self, after: u32, duration: Duration) -> Self {
+        self.mute_after = after;
+        self.mute_duration = duration;
+        self
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    pub fn mute_after(&self) -> u32 {
+        self.mute_after
+    }
+
+    pub fn mute_duration(&self) -> Duration {
+        self.mute_duration
+    }
+}
+
+impl Default for InboundRateLimit {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_RATE, Self::DEFAULT_BURST)
+    }
+}
+
+/// Who a message arriving in the inbox counts against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Origin {
+    /// RPC responses and anything from the home peer, never limited.
+    Service,
+    /// A sender in a conversation: the contact itself for direct messages,
+    /// the channel for channel messages.
+    Sender {
+        conversation: Id,
+        sender      : Id,
+    },
+}
+
+impl Origin {
+    /// Classifies a message from the envelope, before its body is decrypted.
+    pub fn of(from: &Id, to: &Id, me: &Id, home_peer: &Id, is_call: bool) -> Self {
+        if is_call || from == home_peer {
+            return Origin::Service;
+        }
+        Origin::Sender {
+            conversation: match to == me {
+                true => *from,
+                false => *to,
+            },
+            sender: *from,
+        }
+    }
+}
+
+/// What to do with an incoming message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// Within the limit.
+    Accept,
+    /// Over the limit, dropped and counted.
+    Drop,
+    /// Dropped, and the sender is muted from now on for the duration. Only
+    /// returned once per mute, the application should be told.
+    Mute(Duration),
+}
+
+/// Messages of a sender dropped since the last [`InboundLimiter::take_suppressed`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Suppressed {
+    /// The conversation the messages were sent in.
+    pub conversation : Id,
+    /// The sender of the messages.
+    pub sender       : Id,
+    /// The number of messages dropped.
+    pub count        : u64,
+}
+
+struct Bucket {
+    tokens      : f64,
+    refilled    : Instant,
+    drops_in_row: u32,
+    muted_until : Option<Instant>,
+    suppressed  : u64,
+}
+
+/// The token buckets of the senders seen recently.
+pub struct InboundLimiter {
+    limits  : InboundRateLimit,
+    buckets : HashMap<(Id, Id), Bucket>,
+    dropped : u64,
+}
+
+impl InboundLimiter {
+    /// No sender seen yet, all of them held to `limits`.
+    pub fn new(limits: InboundRateLimit) -> Self {
+        Self {
+            limits,
+            buckets : HashMap::new(),
+            dropped : 0,
+        }
+    }
+
+    /// The limits the senders are held to.
+    pub fn limits(&self) -> &InboundRateLimit {
+        &self.limits
+    }
+
+    /// Takes a token from the bucket of the sender if there is one left.
+    pub fn admit(&mut self, origin: &Origin, now: Instant) -> Admission {
+        let Origin::Sender { conversation, sender } = origin else {
+            return Admission::Accept;
+        };
+
+        let limits = self.limits;
+        let bucket = self.buckets.entry((*conversation, *sender)).or_insert_with(|| Bucket {
+            tokens      : limits.burst as f64,
+            refilled    : now,
+            drops_in_row: 0,
+            muted_until : None,
+            suppressed  : 0,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limits.rate).min(limits.burst as f64);
+        bucket.refilled = now;
+
+        if bucket.muted_until.is_some_and(|until| now < until) {
+            bucket.suppressed += 1;
+            self.dropped += 1;
+            return Admission::Drop;
+        }
+        bucket.muted_until = None;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            bucket.drops_in_row = 0;
+            return Admission::Accept;
+        }
+
+        bucket.suppressed += 1;
+        bucket.drops_in_row += 1;
+        self.dropped += 1;
+        if limits.mute_after > 0 && bucket.drops_in_row >= limits.mute_after {
+            bucket.drops_in_row = 0;
+            bucket.muted_until = Some(now + limits.mute_duration);
+            return Admission::Mute(limits.mute_duration);
+        }
+        Admission::Drop
+    }
+
+    /// Whether the sender is muted in the conversation at `now`.
+    pub fn is_muted(&self, conversation: &Id, sender: &Id, now: Instant) -> bool {
+        self.buckets.get(&(*conversation, *sender))
+            .and_then(|b| b.muted_until)
+            .is_some_and(|until| now < until)
+    }
+
+    /// Lifts the mute of the sender, if the user chose not to block it.
+    pub fn unmute(&mut self, conversation: &Id, sender: &Id) {
+        if let Some(bucket) = self.buckets.get_mut(&(*conversation, *sender)) {
+            bucket.muted_until = None;
+            bucket.drops_in_row = 0;
+        }
+    }
+
+    /// The drop counts since the last call, one per sender, to be reported
+    /// as a single notice each. Forgets the senders back to a full bucket.
+    pub fn take_suppressed(&mut self, now: Instant) -> Vec<Suppressed> {
+        let limits = self.limits;
+        let mut suppressed = Vec::new();
+        self.buckets.retain(|(conversation, sender), bucket| {
+            if bucket.suppressed > 0 {
+                suppressed.push(Suppressed {
+                    conversation: *conversation,
+                    sender      : *sender,
+                    count       : bucket.suppressed,
+                });
+                bucket.suppressed = 0;
+            }
+
+            let elapsed = now.saturating_duration_since(bucket.refilled).as_secs_f64();
+            let full = bucket.tokens + elapsed * limits.rate >= limits.burst as f64;
+            let muted = bucket.muted_until.is_some_and(|until| now < until);
+            !full || muted
+        });
+        suppressed
+    }
+
+    /// Messages dropped since the limiter was created.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// The number of senders with a bucket.
+    pub fn senders(&self) -> usize {
+        self.buckets.len()
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::{Id, CryptoContext};
+use crate::messaging::{
+    errors::{Error, Result},
+    notification::{NotifyLevel, NotifySetting},
+};
+
+/// How far the user has read a conversation, kept in sync across the devices
+/// of the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadState {
+    #[serde(rename = "c")]
+    conversation: Id,
+    #[serde(rename = "r")]
+    last_read   : u64,
+    #[serde(rename = "t")]
+    updated     : u64,
+}
+
+impl ReadState {
+    /// The conversation read up to `last_read` at `updated`.
+    pub fn new(conversation: Id, last_read: u64, updated: u64) -> Self {
+        Self { conversation, last_read, updated }
+    }
+
+    /// The conversation read.
+    pub fn conversation_id(&self) -> &Id {
+        &self.conversation
+    }
+
+    /// Milliseconds since the Unix epoch when the last message read was
+    /// created, the message ids being local to each device.
+    pub fn last_read(&self) -> u64 {
+        self.last_read
+    }
+
+    /// Milliseconds since the Unix epoch when the conversation was marked read.
+    pub fn updated(&self) -> u64 {
+        self.updated
+    }
+
+    // Last writer wins, the tie broken by the furthest read.
+    fn supersedes(&self, other: &ReadState) -> bool {
+        (self.updated, self.last_read) > (other.updated, other.last_read)
+    }
+}
+
+/// The unsent text of a conversation, empty once sent or discarded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Draft {
+    /// The conversation of the draft.
+    #[serde(rename = "c")]
+    pub conversation: Id,
+    /// The unsent text.
+    #[serde(rename = "x")]
+    pub text        : String,
+    /// Milliseconds since the Unix epoch when the draft was saved.
+    #[serde(rename = "t")]
+    pub updated     : u64,
+}
+
+impl Draft {
+    fn supersedes(&self, other: &Draft) -> bool {
+        (self.updated, &self.text) > (other.updated, &other.text)
+    }
+}
+
+/// What a [`SyncMessage`] carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Kind {
+    /// Changes made on the sending device.
+    Update  = 0,
+    /// Sent on connect with the full state, asking the others for theirs.
+    Request = 1,
+    /// The full state, answering a request.
+    State   = 2,
+}
+
+/// A notification the devices of the user send to the user itself, encrypted
+/// with the self context.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncMessage {
+    /// What the message carries.
+    #[serde(rename = "k")]
+    pub kind       : Kind,
+    /// The sending device, for ignoring the echoes of our own messages.
+    #[serde(rename = "o")]
+    pub device     : Id,
+    /// The read states changed, or all of them.
+    #[serde(rename = "r", default, skip_serializing_if = "Vec::is_empty")]
+    pub read_states: Vec<ReadState>,
+    /// The drafts changed, or all of them.
+    #[serde(rename = "d", default, skip_serializing_if = "Vec::is_empty")]
+    pub drafts     : Vec<Draft>,
+    /// The notify levels changed, or all of them.
+    #[serde(rename = "n", default, skip_serializing_if = "Vec::is_empty")]
+    pub notify     : Vec<NotifySetting>,
+}
+
+impl SyncMessage {
+    /// The message encoded in CBOR.
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_cbor::to_vec(self)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-encode sync message: {}", e)))
+    }
+
+    /// The message encoded in `bytes` by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_cbor::from_slice::<SyncMessage>(bytes)
+            .map_err(|e| Error::Encoding(format!("Failed to CBOR-decode sync message: {}", e)))
+    }
+
+    /// The message encrypted with the self context.
+    pub fn seal(&self, ctx: &mut CryptoContext) -> Result<Vec<u8>> {
+        ctx.encrypt_into(&self.to_bytes()?)
+            .map_err(|e| Error::Encoding(format!("Failed to encrypt sync message: {}", e)))
+    }
+
+    /// The message [`seal`](Self::seal)ed into `cipher`.
+    pub fn open(ctx: &CryptoContext, cipher: &[u8]) -> Result<Self> {
+        let plain = ctx.decrypt_into(cipher)
+            .map_err(|e| Error::Encoding(format!("Failed to decrypt sync message: {}", e)))?;
+        Self::from_bytes(&plain)
+    }
+}
+
+/// What a sync message from another device changed locally.
+#[derive(Debug, Default)]
+pub struct Applied {
+    /// The read states taken over.
+    pub read_states: Vec<ReadState>,
+    /// The drafts taken over.
+    pub drafts     : Vec<Draft>,
+    /// The notify levels taken over.
+    pub notify     : Vec<NotifySetting>,
+    /// The full state to send back to a request.
+    pub reply      : Option<SyncMessage>,
+}
+
+#[derive(Default)]
+struct State {
+    read_states: HashMap<Id, ReadState>,
+    drafts     : HashMap<Id, Draft>,
+    notify     : HashMap<Id, NotifySetting>,
+}
+
+/// The read state, drafts and notify levels of the conversations, shared
+/// by the client setting them on this device and the worker applying the
+/// ones set on the other devices of the user. Every change is the last writer's, so the
+/// devices converge whatever order the messages arrive in.
+#[derive(Clone)]
+pub struct SelfSync {
+    device: Id,
+    state : Arc<Mutex<State>>,
+}
+
+impl SelfSync {
+    /// The state of `device` as stored locally.
+    pub fn new(device: Id,
+        read_states: impl IntoIterator<Item = ReadState>,
+        drafts: impl IntoIterator<Item = Draft>,
+        notify: impl IntoIterator<Item = NotifySetting>
+    ) -> Self {
+        let state = State {
+            read_states: read_states.into_iter().map(|s| (s.conversation, s)).collect(),
+            drafts: drafts.into_iter().map(|d| (d.conversation, d)).collect(),
+            notify: notify.into_iter().map(|n| (*n.conversation_id(), n)).collect(),
+        };
+        Self {
+            device,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// How far the conversation was read.
+    pub fn read_state(&self, conversation: &Id) -> Option<ReadState> {
+        self.state.lock().unwrap().read_states.get(conversation).copied()
+    }
+
+    /// The draft of the conversation, if any.
+    pub fn draft(&self, conversation: &Id) -> Option<String> {
+        self.state.lock().unwrap().drafts.get(conversation)
+            .filter(|d| !d.text.is_empty())
+            .map(|d| d.text.clone())
+    }
+
+    /// The notify level of the conversation.
+    pub fn notify_level(&self, conversation: &Id) -> NotifyLevel {
+        self.state.lock().unwrap().notify.get(conversation)
+            .map(|n| n.level())
+            .unwrap_or_default()
+    }
+
+    /// Marks the conversation read up to the message created at `last_read`,
+    /// returns the update for the other devices unless nothing moved.
+    pub fn mark_read(&self, conversation: &Id, last_read: u64, now: u64) -> Option<SyncMessage> {
+        let mut state = self.state.lock().unwrap();
+        if state.read_states.get(conversation).is_some_and(|s| s.last_read >= last_read) {
+            return None;
+        }
+
+        let updated = ReadState::new(*conversation, last_read, now);
+        state.read_states.insert(*conversation, updated);
+        Some(self.message(Kind::Update, vec![updated], vec![], vec![]))
+    }
+
+    /// Saves the draft of the conversation, returns the update for the
+    /// other devices unless it is unchanged.
+    pub fn save_draft(&self, conversation: &Id, text: &str, now: u64) -> Option<SyncMessage> {
+        let mut state = self.state.lock().unwrap();
+        let unchanged = match state.drafts.get(conversation) {
+            Some(draft) => draft.text == text,
+            None => text.is_empty(),
+        };
+        if unchanged {
+            return None;
+        }
+
+        let draft = Draft { conversation: *conversation, text: text.to_string(), updated: now };
+        state.drafts.insert(*conversation, draft.clone());
+        Some(self.message(Kind::Update, vec![], vec![draft], vec![]))
+    }
+
+    /// Sets the notify level of the conversation, returns the update for
+    /// the other devices unless it is unchanged.
+    pub fn set_notify_level(&self, conversation: &Id, level: NotifyLevel, now: u64) -> Option<SyncMessage> {
+        let mut state = self.state.lock().unwrap();
+        let current = state.notify.get(conversation).map(|n| n.level()).unwrap_or_default();
+        if current == level {
+            return None;
+        }
+
+        let setting = NotifySetting::new(*conversation, level, now);
+        state.notify.insert(*conversation, setting);
+        Some(self.message(Kind::Update, vec![], vec![], vec![setting]))
+    }
+
+    /// Sent on connect, to reconcile with the devices after being offline.
+    pub fn request(&self) -> SyncMessage {
+        self.full_state(Kind::Request)
+    }
+
+    /// Merges a message from another device, `None` for our own echoes.
+    pub fn apply(&self, msg: &SyncMessage) -> Option<Applied> {
+        if msg.device == self.device {
+            return None;
+        }
+
+        let mut applied = Applied::default();
+        {
+            let mut state = self.state.lock().unwrap();
+            for incoming in msg.read_states.iter() {
+                let newer = state.read_states.get(&incoming.conversation)
+                    .is_none_or(|s| incoming.supersedes(s));
+                if newer {
+                    state.read_states.insert(incoming.conversation, *incoming);
+                    applied.read_states.push(*incoming);
+                }
+            }
+            for incoming in msg.drafts.iter() {
+                let newer = state.drafts.get(&incoming.conversation)
+                    .is_none_or(|d| incoming.supersedes(d));
+                if newer {
+                    state.drafts.insert(incoming.conversation, incoming.clone());
+                    applied.drafts.push(incoming.clone());
+                }
+            }
+            for incoming in msg.notify.iter() {
+                let newer = state.notify.get(incoming.conversation_id())
+                    .is_none_or(|n| incoming.supersedes(n));
+                if newer {
+                    state.notify.insert(*incoming.conversation_id(), *incoming);
+                    applied.notify.push(*incoming);
+                }
+            }
+        }
+
+        if msg.kind == Kind::Request {
+            applied.reply = Some(self.full_state(Kind::State));
+        }
+        Some(applied)
+    }
+
+    fn full_state(&self, kind: Kind) -> SyncMessage {
+        let state = self.state.lock().unwrap();
+        self.message(kind,
+            state.read_states.values().copied().collect(),
+            state.drafts.values().cloned().collect(),
+            state.notify.values().copied().collect(),
+        )
+    }
+
+    fn message(&self,
+        kind: Kind,
+        read_states: Vec<ReadState>,
+        drafts: Vec<Draft>,
+        notify: Vec<NotifySetting>
+    ) -> SyncMessage {
+        SyncMessage {
+            kind,
+            device: self.device,
+            read_states,
+            drafts,
+            notify,
+        }
+    }
+}
@@ -1,9 +1,12 @@
+use std::time::Duration;
 use serde::Deserialize;
+use reqwest::Client;
+use url::Url;
 
-use crate::{
-    Id,
-    Error,
-    error::Result,
+use crate::{Id, Value};
+use crate::messaging::{
+    client::BoxFuture,
+    errors::{Error, Result},
 };
 
 #[derive(Debug, Deserialize)]
@@ -29,13 +32,18 @@ impl JsonServiceIds {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The peer and node ids of a messaging service.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ServiceIds {
     peerid: Id,
     nodeid: Id
 }
 
 impl ServiceIds {
+    pub fn new(peerid: Id, nodeid: Id) -> Self {
+        Self { peerid, nodeid }
+    }
+
     pub fn peerid(&self) -> &Id {
         &self.peerid
     }
@@ -43,4 +51,49 @@ impl ServiceIds {
     pub fn nodeid(&self) -> &Id {
         &self.nodeid
     }
-}
\ No newline at end of file
+
+    /// The id of the value of `key` published for the service, its peer id
+    /// as the namespace: the same [`Id::derive`] derivation as
+    /// [`Node::find_value_by_key`](crate::dht::Node::find_value_by_key), in
+    /// place of hashing the peer id and the key by hand.
+    pub fn value_id(&self, key: &str) -> Id {
+        Value::id_for_key(&self.peerid, key)
+    }
+}
+
+/// Looks up the [`ServiceIds`] of a messaging service from its API endpoint.
+pub trait ServiceDiscovery: Send + Sync {
+    fn service_ids<'a>(&'a self, api_url: &'a Url) -> BoxFuture<'a, Result<ServiceIds>>;
+}
+
+/// [`ServiceDiscovery`] over the service's HTTP API.
+#[derive(Debug, Default, Clone)]
+pub struct HttpServiceDiscovery;
+
+impl ServiceDiscovery for HttpServiceDiscovery {
+    fn service_ids<'a>(&'a self, api_url: &'a Url) -> BoxFuture<'a, Result<ServiceIds>> {
+        Box::pin(async move {
+            let url = api_url.join("/api/v1/service/id").map_err(|e| {
+                Error::Argument(format!("Invalid API url {api_url}: {e}"))
+            })?;
+
+            let client = Client::builder()
+                .user_agent("rboson")
+                .timeout(Duration::from_secs(30))
+                .build()
+                .map_err(|e| Error::Argument(format!("Failed to create http client: {e}")))?;
+
+            let rsp = client.get(url)
+                .header("Accept", "application/json")
+                .send()
+                .await
+                .map_err(|e| Error::State(format!("Sending http request error {e}")))?
+                .error_for_status()
+                .map_err(|e| Error::State(format!("{e}")))?;
+
+            rsp.json::<JsonServiceIds>().await.map_err(|e| {
+                Error::Encoding(format!("Deserializing json error: {e}"))
+            })?.ids()
+        })
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::LinkedList;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::Duration;
+use log::warn;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::messaging::{
+    errors::{Error, Result},
+    pending_calls::PendingCalls,
+};
+
+/// How long `stop` waits for the worker to wind down before aborting it.
+pub const STOP_GRACE: Duration = Duration::from_secs(5);
+
+const RUNNING: u8 = 0;
+const STOPPING: u8 = 1;
+const STOPPED: u8 = 2;
+
+/// The lifecycle shared by the client and its worker.
+///
+/// Once a stop is requested the client takes no new operations, the worker
+/// leaves its loop, fails every request still queued or awaiting a response
+/// with [`Error::Shutdown`] and, unless the stop is forced, disconnects from
+/// the broker cleanly. The user agent learns about the disconnection once,
+/// from whichever of the worker and `stop` gets there first.
+#[derive(Default)]
+pub struct Shutdown {
+    state       : AtomicU8,
+    forced      : AtomicBool,
+    disconnected: AtomicBool,
+    notify      : Notify,
+}
+
+impl Shutdown {
+    /// A running lifecycle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether no stop was requested yet.
+    pub fn is_running(&self) -> bool {
+        self.state.load(Ordering::Acquire) == RUNNING
+    }
+
+    /// Whether the worker wound down.
+    pub fn is_stopped(&self) -> bool {
+        self.state.load(Ordering::Acquire) == STOPPED
+    }
+
+    /// Whether a forced stop was requested.
+    pub fn is_forced(&self) -> bool {
+        self.forced.load(Ordering::Acquire)
+    }
+
+    /// Fails once a stop was requested, for the operations of the client.
+    pub fn check(&self) -> Result<()> {
+        match self.is_running() {
+            true => Ok(()),
+            false => Err(Error::State("Client is stopping".into())),
+        }
+    }
+
+    /// Request the stop, false if it was requested already. A forced stop
+    /// skips the clean disconnect, a later forced request still makes it so.
+    pub fn begin(&self, forced: bool) -> bool {
+        if forced {
+            self.forced.store(true, Ordering::Release);
+        }
+        let first = self.state.compare_exchange(RUNNING, STOPPING,
+            Ordering::AcqRel, Ordering::Acquire
+        ).is_ok();
+        self.notify.notify_waiters();
+        first
+    }
+
+    /// Resolves once a stop was requested, for the worker loop to select on.
+    pub async fn requested(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if !self.is_running() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Whether on_disconnected is due. While running every lost connection
+    /// counts, once stopping only the first.
+    pub fn take_disconnected(&self) -> bool {
+        if self.is_running() {
+            return true;
+        }
+        !self.disconnected.swap(true, Ordering::AcqRel)
+    }
+
+    /// The worker wound down.
+    pub fn finish(&self) {
+        self.state.store(STOPPED, Ordering::Release);
+    }
+
+    /// Queue `item` unless a stop was requested. The check is made under the
+    /// queue lock, so an item is either drained by the worker or refused.
+    pub fn enqueue<T>(&self, queue: &Mutex<LinkedList<T>>, item: T) -> Result<()> {
+        let mut queue = crate::locked!(queue);
+        self.check()?;
+        queue.push_back(item);
+        Ok(())
+    }
+}
+
+/// Hand the requests still queued, then those awaiting a response, to `fail`.
+/// Called by the worker after the stop was requested, nothing is answered
+/// any more. Returns how many were failed.
+pub fn fail_pending<T>(
+    queued: &Mutex<LinkedList<T>>,
+    pending: &mut PendingCalls<T>,
+    mut fail: impl FnMut(T)
+) -> usize {
+    let queued = std::mem::take(&mut *crate::locked!(queued));
+    let mut count = queued.len();
+    queued.into_iter().for_each(&mut fail);
+
+    let pending = pending.drain();
+    count += pending.len();
+    pending.into_iter().for_each(|(_, call)| fail(call));
+    count
+}
+
+/// Wait up to `grace` for the worker to end, then abort it. The task is
+/// awaited either way, it does not outlive the call. Returns whether it
+/// ended on its own.
+pub async fn join_worker(mut task: JoinHandle<()>, grace: Duration) -> bool {
+    if tokio::time::timeout(grace, &mut task).await.is_ok() {
+        return true;
+    }
+
+    warn!("Messaging worker did not stop within {:?}, aborting it", grace);
+    task.abort();
+    _ = task.await;
+    false
+}
@@ -0,0 +1,203 @@
+use crate::{
+    Id,
+    signature::{self, KeyPair},
+};
+use crate::messaging::{
+    Error,
+    MessagingClientBuilder,
+    channel::Permission,
+    contact::ContactType,
+    account_backup::{self, APP_NAME_KEY, CONTACTS_VERSION_KEY, DEVICE_NAME_KEY},
+    persistence::database::{Database, ChannelRecord, ContactRecord},
+};
+use super::test_utils::{RepoDir, new_repo_dir, make_contact};
+
+const PASSWORD: &str = "correct horse battery staple";
+const CONTACTS_VERSION: &str = "v-8c41f0";
+
+fn make_channel(key_epoch: u32) -> ChannelRecord {
+    ChannelRecord {
+        id: Id::random(),
+        owner: Id::random(),
+        name: Some("rust-users".into()),
+        permission: Permission::ModeratorInvite,
+        session_key: Some(crate::random_bytes(32)),
+        updated: 1700000001000,
+        key_epoch,
+    }
+}
+
+// The state a running agent keeps: its keys and a populated repository.
+struct Account {
+    dir:        RepoDir,
+    user:       KeyPair,
+    device:     KeyPair,
+    repository: Database,
+    contacts:   Vec<ContactRecord>,
+    channels:   Vec<ChannelRecord>,
+}
+
+impl Account {
+    fn populated() -> Self {
+        let dir = new_repo_dir();
+        let user = KeyPair::random();
+        let device = KeyPair::random();
+        let repository = Database::open(&dir, device.private_key()).unwrap();
+
+        let contacts = vec![
+            ContactRecord {
+                remark: Some("work".into()),
+                ..make_contact(ContactType::Friend, Some("alice"))
+            },
+            ContactRecord {
+                blocked: true,
+                ..make_contact(ContactType::Auto, Some("alice"))
+            },
+        ];
+        let channels = vec![make_channel(0), make_channel(3)];
+        for contact in contacts.iter() {
+            repository.put_contact(contact).unwrap();
+        }
+        for channel in channels.iter() {
+            repository.put_channel(channel).unwrap();
+        }
+        repository.put_config(DEVICE_NAME_KEY, b"laptop").unwrap();
+        repository.put_config(APP_NAME_KEY, b"boson-im").unwrap();
+        repository.put_config(CONTACTS_VERSION_KEY, CONTACTS_VERSION.as_bytes()).unwrap();
+
+        Self { dir, user, device, repository, contacts, channels }
+    }
+
+    fn export(&self, password: &str) -> Vec<u8> {
+        let mut bundle = Vec::new();
+        account_backup::export_account(&self.repository, &self.user, &self.device, password, &mut bundle).unwrap();
+        bundle
+    }
+}
+
+fn sorted<T: Clone, F: Fn(&T) -> Id>(items: &[T], key: F) -> Vec<T> {
+    let mut items = items.to_vec();
+    items.sort_by_key(|item| key(item));
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_restore() {
+        let account = Account::populated();
+        let bundle = account.export(PASSWORD);
+        assert!(!bundle.windows(6).any(|w| w == b"laptop"));
+
+        let dir = new_repo_dir();
+        let builder = MessagingClientBuilder::from_account_backup(bundle.as_slice(), PASSWORD, dir.to_path_buf()).unwrap();
+        assert_eq!(builder.data_path(), Some(&*dir));
+
+        // The restored identity signs identically
+        let user = builder.user_keypair().unwrap();
+        let device = builder.device_keypair().unwrap();
+        assert_eq!(user.private_key().as_bytes(), account.user.private_key().as_bytes());
+        assert_eq!(device.public_key().as_bytes(), account.device.public_key().as_bytes());
+        let data = b"identity check";
+        assert_eq!(
+            signature::sign_into(data, user.private_key()).unwrap(),
+            signature::sign_into(data, account.user.private_key()).unwrap()
+        );
+
+        let restored = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(
+            sorted(&restored.contacts().unwrap(), |c| c.id),
+            sorted(&account.contacts, |c| c.id)
+        );
+        assert_eq!(
+            sorted(&restored.channels().unwrap(), |c| c.id),
+            sorted(&account.channels, |c| c.id)
+        );
+        assert_eq!(restored.channel(&account.channels[1].id).unwrap().unwrap().key_epoch, 3);
+        assert_eq!(restored.get_config(DEVICE_NAME_KEY).unwrap(), Some(b"laptop".to_vec()));
+        assert_eq!(restored.get_config(APP_NAME_KEY).unwrap(), Some(b"boson-im".to_vec()));
+        assert_eq!(restored.get_config(CONTACTS_VERSION_KEY).unwrap(), Some(CONTACTS_VERSION.as_bytes().to_vec()));
+        drop(restored);
+
+        // Only a fresh repository is restored into
+        let err = MessagingClientBuilder::from_account_backup(bundle.as_slice(), PASSWORD, dir.to_path_buf());
+        assert!(matches!(err, Err(Error::State(_))));
+    }
+
+    #[test]
+    fn test_wrong_password() {
+        let account = Account::populated();
+        let bundle = account.export(PASSWORD);
+
+        let dir = new_repo_dir();
+        let err = MessagingClientBuilder::from_account_backup(bundle.as_slice(), "wrong password", dir.to_path_buf());
+        assert!(matches!(err, Err(Error::Auth(_))));
+        assert!(!dir.exists());
+
+        let mut empty = Vec::new();
+        assert!(matches!(
+            account_backup::export_account(&account.repository, &account.user, &account.device, "", &mut empty),
+            Err(Error::Argument(_))
+        ));
+    }
+
+    #[test]
+    fn test_incomplete_or_tampered() {
+        let account = Account::populated();
+        let bundle = account.export(PASSWORD);
+        let dir = new_repo_dir();
+
+        let restore = |data: &[u8]| {
+            MessagingClientBuilder::from_account_backup(data, PASSWORD, dir.to_path_buf())
+        };
+
+        // Partially written bundles
+        for len in [0, 10, 60, bundle.len() / 2, bundle.len() - 1] {
+            assert!(matches!(restore(&bundle[..len]), Err(Error::Encoding(_))), "accepted {len} bytes");
+        }
+
+        // A flipped bit in the payload or in the MAC
+        for pos in [bundle.len() / 2, bundle.len() - 1] {
+            let mut tampered = bundle.clone();
+            tampered[pos] ^= 0x01;
+            assert!(matches!(restore(&tampered), Err(Error::Encoding(_))));
+        }
+
+        let mut trailing = bundle.clone();
+        trailing.push(0);
+        assert!(matches!(restore(&trailing), Err(Error::Encoding(_))));
+        assert!(!dir.exists());
+
+        assert!(restore(&bundle).is_ok());
+    }
+
+    #[test]
+    fn test_export_from_builder() {
+        let account = Account::populated();
+        let builder = MessagingClientBuilder::new()
+            .user_key(account.user.clone())
+            .device_key(account.device.clone())
+            .data_dir(account.dir.to_path_buf());
+        let mut bundle = Vec::new();
+        builder.export_account(PASSWORD, &mut bundle).unwrap();
+
+        let dir = new_repo_dir();
+        let restored = MessagingClientBuilder::from_account_backup(bundle.as_slice(), PASSWORD, dir.to_path_buf()).unwrap();
+        assert_eq!(restored.user_keypair().unwrap().private_key().as_bytes(), account.user.private_key().as_bytes());
+        let repository = Database::open(&dir, account.device.private_key()).unwrap();
+        assert_eq!(
+            sorted(&repository.contacts().unwrap(), |c| c.id),
+            sorted(&account.contacts, |c| c.id)
+        );
+
+        // Nothing to export without the keys or the repository
+        let keyless = MessagingClientBuilder::new().data_dir(account.dir.to_path_buf());
+        assert!(matches!(keyless.export_account(PASSWORD, &mut Vec::new()), Err(Error::State(_))));
+        let empty_dir = new_repo_dir();
+        let empty = builder.data_dir(empty_dir.to_path_buf());
+        assert!(matches!(empty.export_account(PASSWORD, &mut Vec::new()), Err(Error::State(_))));
+        assert!(!empty.data_path().unwrap().exists());
+    }
+}
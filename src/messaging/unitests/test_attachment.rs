@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use crate::messaging::{
+    Error,
+    Result,
+    client::BoxFuture,
+    message::{Content, ContentDisposition, content_type},
+    attachment::{self, AttachmentCache, AttachmentStore, Manifest, UPLOAD_PART_SIZE, MAX_ATTACHMENT_SIZE},
+};
+
+// The attachment storage of the service, kept in memory.
+#[derive(Default)]
+struct MockStore {
+    files       : HashMap<String, Vec<u8>>,
+    parts       : usize,
+    downloads   : usize,
+    // Parts accepted before the next upload fails, unlimited if None.
+    fail_after  : Option<usize>,
+}
+
+impl AttachmentStore for MockStore {
+    fn uploaded<'a>(&'a mut self, address: &'a str) -> BoxFuture<'a, Result<u64>> {
+        Box::pin(async move {
+            Ok(self.files.get(address).map(|v| v.len() as u64).unwrap_or(0))
+        })
+    }
+
+    fn upload_part<'a>(&'a mut self, address: &'a str, offset: u64, _total: u64, part: &'a [u8]) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            if let Some(n) = self.fail_after.as_mut() {
+                if *n == 0 {
+                    return Err(Error::State("connection reset".into()));
+                }
+                *n -= 1;
+            }
+            let file = self.files.entry(address.into()).or_default();
+            assert_eq!(file.len() as u64, offset);
+            file.extend_from_slice(part);
+            self.parts += 1;
+            Ok(())
+        })
+    }
+
+    fn download<'a>(&'a mut self, address: &'a str) -> BoxFuture<'a, Result<Vec<u8>>> {
+        Box::pin(async move {
+            self.downloads += 1;
+            self.files.get(address).cloned().ok_or(Error::NotFound(address.into()))
+        })
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    let dir = format!("/tmp/ta_{:016x}", rand::random::<u64>());
+    let _ = fs::remove_dir_all(&dir);
+    PathBuf::from(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let data = crate::random_bytes(UPLOAD_PART_SIZE * 2 + 100);
+        let sealed = attachment::seal(content_type::IMAGE_PNG, Some("cat.png"), &data).unwrap();
+        assert_ne!(sealed.cipher(), data.as_slice());
+
+        let mut store = MockStore::default();
+        let sent = attachment::upload(&mut store, &sealed).await.unwrap();
+        assert_eq!(sent, sealed.cipher().len() as u64);
+        assert_eq!(store.parts, 3);
+
+        // The message body carries the manifest.
+        let manifest = sealed.manifest();
+        let content = Content::_new(
+            HashMap::new(),
+            Some(content_type::ATTACHMENT.into()),
+            Some(manifest.disposition()),
+            manifest.encode().unwrap(),
+        );
+        let received = Manifest::from_content(&content).unwrap();
+        assert_eq!(&received, manifest);
+        assert_eq!(received.size(), data.len() as u64);
+        assert_eq!(received.content_type(), content_type::IMAGE_PNG);
+        assert_eq!(received.filename(), Some("cat.png"));
+        assert_eq!(content.content_disposition(), ContentDisposition::attachment("cat.png"));
+
+        let dir = cache_dir();
+        let cache = AttachmentCache::new(dir.clone());
+        let fetched = attachment::fetch(&mut store, &cache, &received).await.unwrap();
+        assert_eq!(fetched, data);
+
+        // Plain message bodies are not attachments.
+        let text = Content::_new(HashMap::new(), None, None, b"hello".to_vec());
+        assert!(matches!(Manifest::from_content(&text), Err(Error::Argument(_))));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_hash_mismatch() {
+        let data = crate::random_bytes(4096);
+        let sealed = attachment::seal(content_type::BINARY, None, &data).unwrap();
+        let manifest = sealed.manifest().clone();
+
+        let mut store = MockStore::default();
+        attachment::upload(&mut store, &sealed).await.unwrap();
+        store.files.get_mut(&manifest.address()).unwrap()[40] ^= 0x01;
+
+        let dir = cache_dir();
+        let cache = AttachmentCache::new(dir.clone());
+        let result = attachment::fetch(&mut store, &cache, &manifest).await;
+        assert!(matches!(result, Err(Error::Auth(_))));
+        assert!(!cache.contains(&manifest.address()));
+
+        // A manifest from another attachment does not open this one.
+        let other = attachment::seal(content_type::BINARY, None, &data).unwrap();
+        assert!(matches!(attachment::open(other.manifest(), sealed.cipher()), Err(Error::Auth(_))));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit() {
+        let data = crate::random_bytes(10000);
+        let sealed = attachment::seal(content_type::BINARY, None, &data).unwrap();
+        let manifest = sealed.manifest().clone();
+
+        let mut store = MockStore::default();
+        attachment::upload(&mut store, &sealed).await.unwrap();
+
+        let dir = cache_dir();
+        let cache = AttachmentCache::new(dir.clone());
+        for _ in 0..3 {
+            let fetched = attachment::fetch(&mut store, &cache, &manifest).await.unwrap();
+            assert_eq!(fetched, data);
+        }
+        assert_eq!(store.downloads, 1);
+
+        // Only the ciphertext is kept on disk.
+        let cached = cache.get(&manifest.address()).unwrap();
+        assert_eq!(cached, sealed.cipher());
+
+        // A damaged cache entry is dropped and downloaded again.
+        let mut damaged = cached.clone();
+        damaged[30] ^= 0x80;
+        cache.put(&manifest.address(), &damaged).unwrap();
+        let fetched = attachment::fetch(&mut store, &cache, &manifest).await.unwrap();
+        assert_eq!(fetched, data);
+        assert_eq!(store.downloads, 2);
+        assert_eq!(cache.get(&manifest.address()).unwrap(), sealed.cipher());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_resume_upload() {
+        let data = crate::random_bytes(UPLOAD_PART_SIZE * 3);
+        let sealed = attachment::seal(content_type::VIDEO_MP4, None, &data).unwrap();
+        let total = sealed.cipher().len() as u64;
+
+        let mut store = MockStore {
+            fail_after: Some(2),
+            ..Default::default()
+        };
+        let result = attachment::upload(&mut store, &sealed).await;
+        assert!(matches!(result, Err(Error::State(_))));
+        assert_eq!(store.parts, 2);
+
+        store.fail_after = None;
+        let sent = attachment::upload(&mut store, &sealed).await.unwrap();
+        assert_eq!(sent, total - 2 * UPLOAD_PART_SIZE as u64);
+        assert_eq!(store.files[&sealed.manifest().address()], sealed.cipher());
+
+        // Nothing left to send once the service holds it all.
+        assert_eq!(attachment::upload(&mut store, &sealed).await.unwrap(), 0);
+        assert_eq!(store.parts, 4);
+    }
+
+    #[test]
+    fn test_limits() {
+        assert!(matches!(attachment::seal(content_type::BINARY, None, &[]), Err(Error::Argument(_))));
+        assert!(matches!(attachment::seal("", None, b"data"), Err(Error::Argument(_))));
+
+        let data = vec![0u8; MAX_ATTACHMENT_SIZE + 1];
+        assert!(matches!(attachment::seal(content_type::BINARY, None, &data), Err(Error::Argument(_))));
+
+        let sealed = attachment::seal(content_type::BINARY, None, b"data").unwrap();
+        let mut value: serde_cbor::Value = serde_cbor::from_slice(&sealed.manifest().encode().unwrap()).unwrap();
+        if let serde_cbor::Value::Map(map) = &mut value {
+            map.insert(serde_cbor::Value::Text("s".into()), serde_cbor::Value::Integer(MAX_ATTACHMENT_SIZE as i128 + 1));
+        }
+        let encoded = serde_cbor::to_vec(&value).unwrap();
+        assert!(matches!(Manifest::decode(&encoded), Err(Error::Argument(_))));
+        assert!(matches!(Manifest::decode(b"garbage"), Err(Error::Encoding(_))));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::Id;
+use crate::messaging::{
+    block_list::BlockList,
+    rate_limit::Origin,
+};
+
+// The inbox of the client worker: drops the messages of blocked senders
+// from their envelope, and keeps the others.
+struct Inbox {
+    me: Id,
+    peer: Id,
+    blocked: BlockList,
+    accepted: Vec<Id>,
+    dropped: u64,
+}
+
+impl Inbox {
+    fn new(blocked: BlockList) -> Self {
+        Self {
+            me: Id::random(),
+            peer: Id::random(),
+            blocked,
+            accepted: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    fn on_inbox_msg(&mut self, from: &Id, to: &Id, is_call: bool) {
+        let origin = Origin::of(from, to, &self.me, &self.peer, is_call);
+        match self.blocked.admits(&origin) {
+            true => self.accepted.push(*from),
+            false => self.dropped += 1,
+        }
+    }
+
+    fn direct(&mut self, from: &Id) {
+        let me = self.me;
+        self.on_inbox_msg(from, &me, false);
+    }
+
+    fn accepted_from(&self, from: &Id) -> usize {
+        self.accepted.iter().filter(|v| *v == from).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_and_unblock() {
+        // The client changes the list the worker reads.
+        let blocked = BlockList::default();
+        let mut inbox = Inbox::new(blocked.clone());
+        let abuser = Id::random();
+        let friend = Id::random();
+
+        assert!(blocked.block(&abuser));
+        assert!(!blocked.block(&abuser));
+        inbox.direct(&abuser);
+        inbox.direct(&friend);
+        assert_eq!(inbox.accepted_from(&abuser), 0);
+        assert_eq!(inbox.accepted_from(&friend), 1);
+        assert_eq!(inbox.dropped, 1);
+        assert_eq!(blocked.blocked(), vec![abuser]);
+
+        assert!(blocked.unblock(&abuser));
+        assert!(!blocked.unblock(&abuser));
+        inbox.direct(&abuser);
+        assert_eq!(inbox.accepted_from(&abuser), 1);
+        assert_eq!(inbox.dropped, 1);
+        assert!(blocked.blocked().is_empty());
+    }
+
+    #[test]
+    fn test_channels() {
+        let blocked = BlockList::default();
+        let mut inbox = Inbox::new(blocked.clone());
+        let channel = Id::random();
+        let abuser = Id::random();
+        let member = Id::random();
+
+        // A blocked contact is not heard in channels either.
+        blocked.block(&abuser);
+        inbox.on_inbox_msg(&abuser, &channel, false);
+        inbox.on_inbox_msg(&member, &channel, false);
+        assert_eq!(inbox.accepted_from(&abuser), 0);
+        assert_eq!(inbox.accepted_from(&member), 1);
+
+        // Nor anyone in a blocked channel.
+        blocked.block(&channel);
+        inbox.on_inbox_msg(&member, &channel, false);
+        inbox.direct(&member);
+        assert_eq!(inbox.accepted_from(&member), 2);
+        assert_eq!(inbox.dropped, 2);
+    }
+
+    #[test]
+    fn test_service_never_blocked() {
+        let blocked = BlockList::default();
+        let mut inbox = Inbox::new(blocked.clone());
+        let peer = inbox.peer;
+        let me = inbox.me;
+        let abuser = Id::random();
+
+        blocked.block(&peer);
+        blocked.block(&abuser);
+        inbox.on_inbox_msg(&peer, &me, false);
+        // RPC responses are relayed by the service on behalf of the sender
+        inbox.on_inbox_msg(&abuser, &me, true);
+        assert_eq!(inbox.accepted_from(&peer), 1);
+        assert_eq!(inbox.accepted_from(&abuser), 1);
+        assert_eq!(inbox.dropped, 0);
+    }
+
+    #[test]
+    fn test_sync() {
+        let (a, b, c) = (Id::random(), Id::random(), Id::random());
+        let blocked = BlockList::new([a]);
+
+        // Pushed by another device: b blocked, a unblocked, c unchanged.
+        let changed = blocked.sync([(a, false), (b, true), (c, false)]);
+        assert_eq!(changed, vec![(a, false), (b, true)]);
+        assert_eq!(blocked.blocked(), vec![b]);
+
+        assert!(blocked.sync([(b, true)]).is_empty());
+    }
+}
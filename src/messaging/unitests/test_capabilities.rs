@@ -0,0 +1,93 @@
+use crate::messaging::{
+    Error,
+    ServiceInfo,
+    capabilities::{self, ServerCapabilities, PROTOCOL_VERSION},
+};
+
+// The methods as the client worker gates them: name and the protocol
+// version that added it.
+const CHANNEL_INFO: (&str, u32) = ("channel_info", 1);
+const CHANNEL_JOIN_POLICY: (&str, u32) = ("channel_join_policy", 2);
+
+fn check(caps: &ServerCapabilities, method: (&str, u32)) -> Result<(), Error> {
+    caps.check(method.0, method.1)
+}
+
+fn service_info(json: &str) -> ServiceInfo {
+    serde_json::from_str(json).unwrap()
+}
+
+#[test]
+fn test_legacy_service() {
+    let info = service_info(r#"{"version":"1.0","maxDevices":5}"#);
+    let caps = info.capabilities();
+    assert_eq!(caps.protocol_version(), 1);
+    assert!(caps.capabilities().is_none());
+    assert!(caps.supports(CHANNEL_INFO.0));
+
+    assert!(check(caps, CHANNEL_INFO).is_ok());
+    assert!(matches!(check(caps, CHANNEL_JOIN_POLICY), Err(Error::Unsupported(_))));
+}
+
+#[test]
+fn test_advertised_methods() {
+    let info = service_info(r#"{
+        "version": "2.1",
+        "protocolVersion": 2,
+        "capabilities": ["channel_info", "channel_join_policy"]
+    }"#);
+    let caps = info.capabilities();
+    assert_eq!(caps.protocol_version(), 2);
+    assert_eq!(caps.capabilities().unwrap().len(), 2);
+
+    assert!(check(caps, CHANNEL_INFO).is_ok());
+    assert!(check(caps, CHANNEL_JOIN_POLICY).is_ok());
+
+    let rc = check(caps, ("channel_ban", 1));
+    assert!(matches!(rc, Err(Error::Unsupported(ref m)) if m.contains("channel_ban")));
+}
+
+#[test]
+fn test_newer_version_without_method() {
+    let caps = ServerCapabilities::new(Some(PROTOCOL_VERSION), Some(&["channel_info"]));
+    assert!(check(&caps, CHANNEL_INFO).is_ok());
+    assert!(!caps.supports(CHANNEL_JOIN_POLICY.0));
+    assert!(matches!(check(&caps, CHANNEL_JOIN_POLICY), Err(Error::Unsupported(_))));
+}
+
+#[test]
+fn test_older_version_with_method() {
+    // Advertising the method does not make up for the protocol version.
+    let caps = ServerCapabilities::new(Some(1), Some(&["channel_join_policy"]));
+    let rc = check(&caps, CHANNEL_JOIN_POLICY);
+    assert!(matches!(rc, Err(Error::Unsupported(ref m)) if m.contains("protocol version 2")));
+}
+
+#[test]
+fn test_unknown_fields() {
+    let info = service_info(r#"{
+        "version": "3.0",
+        "maxDevices": 3,
+        "protocolVersion": 3,
+        "capabilities": ["channel_info"],
+        "capabilityFlags": {"compression": true},
+        "region": "eu"
+    }"#);
+    assert_eq!(info.version(), Some("3.0"));
+    assert_eq!(info.max_devices(), Some(3));
+    assert_eq!(info.capabilities().protocol_version(), 3);
+    assert!(info.capabilities().supports(CHANNEL_INFO.0));
+
+    let caps: ServerCapabilities = serde_json::from_str(r#"{"protocolVersion":2,"extra":[1,2]}"#).unwrap();
+    assert_eq!(caps.protocol_version(), 2);
+}
+
+#[test]
+fn test_response_version() {
+    assert!(capabilities::check_version(CHANNEL_INFO.0, CHANNEL_INFO.1, 1).is_ok());
+    assert!(capabilities::check_version(CHANNEL_JOIN_POLICY.0, CHANNEL_JOIN_POLICY.1, 2).is_ok());
+    assert!(matches!(
+        capabilities::check_version(CHANNEL_JOIN_POLICY.0, CHANNEL_JOIN_POLICY.1, 1),
+        Err(Error::Unsupported(_))
+    ));
+}
@@ -0,0 +1,211 @@
+use crate::{
+    Id,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    Error,
+    Result,
+    contact::Contact,
+    channel::{Channel, Permission},
+    message::MessageType,
+    persistence::database::{Database, ChannelRecord, MessageRecord},
+    channel_removal::{self, ChannelRemovals, RemovedChannel},
+};
+use super::test_utils::{RepoDir, new_repo_dir};
+
+// Drives the removal the way the client worker does, recording the
+// channels reported to the listeners.
+struct Worker {
+    user: Id,
+    db: Database,
+    removals: ChannelRemovals,
+    deleted: Vec<(Id, Id)>,
+    _dir: RepoDir,
+}
+
+impl Worker {
+    fn new() -> Self {
+        let dir = new_repo_dir();
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        Self {
+            user: Id::random(),
+            db,
+            removals: ChannelRemovals::new(),
+            deleted: Vec::new(),
+            _dir: dir,
+        }
+    }
+
+    fn add_channel(&self, owner: &Id) -> Id {
+        let record = ChannelRecord {
+            id: Id::random(),
+            owner: owner.clone(),
+            name: Some("rust-users".into()),
+            permission: Permission::MemberInvite,
+            session_key: Some(crate::random_bytes(32)),
+            updated: 1700000000000,
+            key_epoch: 0,
+        };
+        self.db.put_channel(&record).unwrap();
+        self.db.put_message(&MessageRecord {
+            rid: 0,
+            conversation_id: record.id.clone(),
+            sender: owner.clone(),
+            message_type: MessageType::ContentMessage,
+            created: 1000,
+            body: b"hello".to_vec(),
+            content_type: None,
+        }).unwrap();
+        record.id
+    }
+
+    fn remove_channel(&mut self, id: &Id) -> Result<()> {
+        let record = self.db.channel(id).unwrap();
+        channel_removal::check_owner(&self.user, id, record.as_ref().map(|r| &r.owner))?;
+        self.removals.begin(id);
+        Ok(())
+    }
+
+    fn report(&mut self, id: &Id, record: Option<ChannelRecord>) {
+        let channel = match record {
+            Some(record) => RemovedChannel::new(
+                &record.id, &record.owner, record.name.as_deref(), record.permission, record.updated
+            ),
+            None => RemovedChannel::from_id(id),
+        };
+        self.deleted.push((channel.id().clone(), channel.owner().clone()));
+    }
+
+    fn on_response(&mut self, id: &Id) {
+        let record = self.purge(id);
+        if self.removals.on_response(id) {
+            self.report(id, record);
+        }
+    }
+
+    fn on_notification(&mut self, id: &Id) {
+        let record = self.purge(id);
+        if self.removals.on_notification(id, record.is_some()) {
+            self.report(id, record);
+        }
+    }
+
+    // Drops the channel record and its history, as the worker does.
+    fn purge(&self, id: &Id) -> Option<ChannelRecord> {
+        let record = self.db.channel(id).unwrap();
+        self.db.remove_channel(id).unwrap();
+        self.db.remove_messages_by_conversation(id).unwrap();
+        record
+    }
+
+    fn is_purged(&self, id: &Id) -> bool {
+        self.db.channel(id).unwrap().is_none() &&
+            self.db.messages_since(id, 0, 10, 0).unwrap().is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_removes_channel() {
+        let mut w = Worker::new();
+        let user = w.user.clone();
+        let id = w.add_channel(&user);
+
+        w.remove_channel(&id).unwrap();
+        assert!(w.removals.is_pending(&id));
+        assert!(!w.is_purged(&id));
+
+        w.on_response(&id);
+        assert!(!w.removals.is_pending(&id));
+        assert!(w.is_purged(&id));
+        assert_eq!(w.deleted, vec![(id, user)]);
+    }
+
+    #[test]
+    fn test_non_owner_is_denied() {
+        let mut w = Worker::new();
+        let id = w.add_channel(&Id::random());
+
+        let rc = w.remove_channel(&id);
+        assert!(matches!(rc, Err(Error::PermissionDenied(_))));
+        assert!(!w.removals.is_pending(&id));
+        assert!(!w.is_purged(&id));
+        assert!(w.deleted.is_empty());
+    }
+
+    #[test]
+    fn test_missing_local_record() {
+        let mut w = Worker::new();
+        let id = Id::random();
+
+        w.remove_channel(&id).unwrap();
+        w.on_response(&id);
+        assert!(w.is_purged(&id));
+        assert_eq!(w.deleted, vec![(id, Id::zero())]);
+
+        let channel = RemovedChannel::from_id(&id);
+        assert_eq!(channel.display_name(), id.to_base58());
+        assert_eq!(channel.channel_name(), None);
+    }
+
+    #[test]
+    fn test_notification_before_response() {
+        let mut w = Worker::new();
+        let user = w.user.clone();
+        let id = w.add_channel(&user);
+
+        w.remove_channel(&id).unwrap();
+        w.on_notification(&id);
+        assert!(w.is_purged(&id));
+        assert_eq!(w.deleted, vec![(id.clone(), user.clone())]);
+
+        w.on_response(&id);
+        assert!(!w.removals.is_pending(&id));
+        assert_eq!(w.deleted, vec![(id, user)]);
+    }
+
+    #[test]
+    fn test_response_before_notification() {
+        let mut w = Worker::new();
+        let user = w.user.clone();
+        let id = w.add_channel(&user);
+
+        w.remove_channel(&id).unwrap();
+        w.on_response(&id);
+        w.on_notification(&id);
+        assert!(w.is_purged(&id));
+        assert_eq!(w.deleted, vec![(id, user)]);
+    }
+
+    #[test]
+    fn test_failure_after_notification() {
+        let mut removals = ChannelRemovals::new();
+        let id = Id::random();
+
+        removals.begin(&id);
+        assert!(!removals.on_failure(&id));
+
+        removals.begin(&id);
+        assert!(removals.on_notification(&id, false));
+        assert!(removals.on_failure(&id));
+        assert!(!removals.is_pending(&id));
+    }
+
+    #[test]
+    fn test_deleted_by_others() {
+        let mut w = Worker::new();
+        let owner = Id::random();
+        let id = w.add_channel(&owner);
+
+        w.on_notification(&id);
+        assert!(w.is_purged(&id));
+        assert_eq!(w.deleted, vec![(id, owner)]);
+
+        // Unknown channels are not reported.
+        w.on_notification(&Id::random());
+        assert_eq!(w.deleted.len(), 1);
+    }
+}
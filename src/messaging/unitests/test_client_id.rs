@@ -0,0 +1,82 @@
+use std::{fs, path::PathBuf};
+use rumqttc::{ConnectionError, ConnectReturnCode};
+
+use crate::Id;
+use crate::messaging::client_id::{self, Attempt, Scheme, SessionMarker};
+
+fn device(byte: u8) -> Id {
+    Id::from_bytes([byte; Id::BYTES])
+}
+
+fn marker_path() -> PathBuf {
+    PathBuf::from(format!("/tmp/tcid_{:016x}/mqtt_session", rand::random::<u64>()))
+}
+
+fn ids(attempts: &[Attempt]) -> Vec<(Scheme, bool)> {
+    attempts.iter().map(|v| (v.scheme, v.clean_session)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The derived ids are keys of broker sessions, they must never change.
+    #[test]
+    fn test_derive() {
+        for (dev, legacy, v1) in [
+            (device(0x00), "EvRtTwYQMDj3ANV1sQaMRz", "FDcV8vu5skEqDZPqgvKvBW"),
+            (device(0x5a), "rEzuhhJjg5HTkjeynHrPP", "NZjmUvbJrqZPx7fQ2jzg1Y"),
+        ] {
+            assert_eq!(Scheme::Legacy.derive(&dev), legacy);
+            assert_eq!(Scheme::V1.derive(&dev), v1);
+        }
+        assert_eq!(Scheme::CURRENT, Scheme::V1);
+    }
+
+    #[test]
+    fn test_attempts() {
+        let dev = device(0x5a);
+
+        let attempts = client_id::attempts(&dev, None, false);
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].client_id, "NZjmUvbJrqZPx7fQ2jzg1Y");
+        assert!(attempts[0].clean_session);
+
+        assert_eq!(ids(&client_id::attempts(&dev, Some(Scheme::V1), false)), [(Scheme::V1, false)]);
+        assert_eq!(ids(&client_id::attempts(&dev, Some(Scheme::Legacy), false)), [(Scheme::V1, true)]);
+
+        // Devices without a marker may hold a legacy session.
+        let attempts = client_id::attempts(&dev, None, true);
+        assert_eq!(ids(&attempts), [(Scheme::V1, true), (Scheme::Legacy, false)]);
+        assert_eq!(attempts[1].client_id, "rEzuhhJjg5HTkjeynHrPP");
+
+        assert_eq!(ids(&client_id::attempts(&dev, Some(Scheme::Legacy), true)),
+            [(Scheme::Legacy, false), (Scheme::V1, true)]);
+        assert_eq!(ids(&client_id::attempts(&dev, Some(Scheme::V1), true)),
+            [(Scheme::V1, false), (Scheme::Legacy, true)]);
+    }
+
+    #[test]
+    fn test_rejected() {
+        assert!(client_id::is_rejected(&ConnectionError::ConnectionRefused(ConnectReturnCode::BadClientId)));
+        assert!(!client_id::is_rejected(&ConnectionError::ConnectionRefused(ConnectReturnCode::BadUserNamePassword)));
+        assert!(!client_id::is_rejected(&ConnectionError::RequestsDone));
+    }
+
+    #[test]
+    fn test_session_marker() {
+        let path = marker_path();
+        let marker = SessionMarker::new(path.clone());
+        assert_eq!(marker.load(), None);
+
+        marker.store(Scheme::Legacy).unwrap();
+        assert_eq!(marker.load(), Some(Scheme::Legacy));
+        marker.store(Scheme::V1).unwrap();
+        assert_eq!(SessionMarker::new(path.clone()).load(), Some(Scheme::V1));
+
+        fs::write(&path, "v9").unwrap();
+        assert_eq!(marker.load(), None);
+
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{
+    Id,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    Error,
+    MessagingClientBuilder,
+    contact::ContactType,
+    contact_transfer::{self, ContactFormat, ImportOutcome},
+    persistence::database::{Database, ContactRecord},
+};
+use super::test_utils::{RepoDir, new_repo_dir, make_contact};
+
+// A repository in a directory removed when dropped.
+struct Repo {
+    repository: Database,
+    _dir:       RepoDir,
+}
+
+impl Repo {
+    fn new() -> Self {
+        let dir = new_repo_dir();
+        let repository = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        Self { repository, _dir: dir }
+    }
+
+    fn with(contacts: &[ContactRecord]) -> Self {
+        let repo = Self::new();
+        for contact in contacts {
+            repo.repository.put_contact(contact).unwrap();
+        }
+        repo
+    }
+
+    fn export(&self, format: ContactFormat) -> String {
+        let mut data = Vec::new();
+        contact_transfer::export_contacts(&self.repository, &mut data, format).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn import(&self, data: &str, format: ContactFormat) -> contact_transfer::ImportReport {
+        contact_transfer::import_contacts(&self.repository, data.as_bytes(), format).unwrap()
+    }
+}
+
+fn vcard_of(id: &Id, extra: &str) -> String {
+    format!("BEGIN:VCARD\r\nVERSION:4.0\r\n{extra}X-BOSON-ID:{id}\r\nEND:VCARD\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_round_trip() {
+        let mut blocked = make_contact(ContactType::Auto, None);
+        blocked.blocked = true;
+        blocked.home_peer_id = None;
+        let contacts = vec![
+            ContactRecord {
+                remark: Some("Alice, at work".into()),
+                ..make_contact(ContactType::Friend, Some("alice"))
+            },
+            blocked,
+            make_contact(ContactType::Channel, Some("rust-users")),
+        ];
+        let source = Repo::with(&contacts);
+        let data = source.export(ContactFormat::Native);
+        assert!(data.contains("\"hasSessionKey\": true"));
+
+        // The keys never leave the device.
+        let key = &contacts[0].session_key.as_ref().unwrap()[..8];
+        assert!(!data.as_bytes().windows(key.len()).any(|w| w == key));
+
+        let target = Repo::new();
+        let report = target.import(&data, ContactFormat::Native);
+        assert_eq!(report.imported().len(), 3);
+        assert!(report.duplicates().is_empty() && report.invalid().is_empty());
+
+        for contact in contacts.iter() {
+            let imported = target.repository.contact(&contact.id).unwrap().unwrap();
+            assert_eq!(imported.contact_type, contact.contact_type);
+            assert_eq!(imported.home_peer_id, contact.home_peer_id);
+            assert_eq!(imported.name, contact.name);
+            assert_eq!(imported.remark, contact.remark);
+            assert_eq!(imported.blocked, contact.blocked);
+            // Pending the key exchange.
+            assert_eq!(imported.session_key, None);
+        }
+
+        // Exported again it reads the same, but for the session keys.
+        let again = target.export(ContactFormat::Native);
+        assert_eq!(again.matches("\"hasSessionKey\": false").count(), 3);
+    }
+
+    #[test]
+    fn test_native_invalid_entries() {
+        let good = Id::random();
+        let data = format!(r#"{{
+            "format": "boson-contacts",
+            "version": 1,
+            "contacts": [
+                {{ "id": "{good}", "type": "friend" }},
+                {{ "id": "not-an-id", "type": "friend" }},
+                {{ "id": "{}", "type": "stranger" }},
+                {{ "type": "friend" }},
+                {{ "id": "{}", "type": "friend", "homePeerId": "0x00" }}
+            ]
+        }}"#, Id::random(), Id::random());
+
+        let repo = Repo::new();
+        let report = repo.import(&data, ContactFormat::Native);
+        assert_eq!(report.imported(), vec![good]);
+        let invalid = report.invalid().into_iter().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(invalid, vec![1, 2, 3, 4]);
+        assert_eq!(repo.repository.contacts().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_native_not_contacts() {
+        let repo = Repo::new();
+        for data in ["", "[]", r#"{"format": "other", "version": 1, "contacts": []}"#] {
+            let result = contact_transfer::import_contacts(&repo.repository, data.as_bytes(), ContactFormat::Native);
+            assert!(matches!(result, Err(Error::Encoding(_))), "{data}");
+        }
+    }
+
+    #[test]
+    fn test_import_dedup() {
+        let existing = ContactRecord {
+            remark: Some("Bobby".into()),
+            ..make_contact(ContactType::Friend, Some("bob"))
+        };
+        let repo = Repo::with(std::slice::from_ref(&existing));
+        let fresh = Id::random();
+
+        let data = [
+            vcard_of(&existing.id, "FN:Someone else\r\n"),
+            vcard_of(&fresh, "FN:Carol\r\n"),
+            vcard_of(&fresh, "FN:Carol again\r\n"),
+        ].concat();
+        let report = repo.import(&data, ContactFormat::VCard);
+        assert_eq!(report.outcomes(), &[
+            ImportOutcome::Duplicate(existing.id),
+            ImportOutcome::Imported(fresh),
+            ImportOutcome::Duplicate(fresh),
+        ]);
+
+        // The contact list keeps its own record, key and all.
+        let kept = repo.repository.contact(&existing.id).unwrap().unwrap();
+        assert_eq!(kept, existing);
+        let imported = repo.repository.contact(&fresh).unwrap().unwrap();
+        assert_eq!(imported.remark.as_deref(), Some("Carol"));
+
+        // Importing the same again changes nothing.
+        let report = repo.import(&data, ContactFormat::VCard);
+        assert!(report.imported().is_empty());
+        assert_eq!(report.duplicates().len(), 3);
+        assert_eq!(repo.repository.contacts().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_vcard_export() {
+        let long = "A remark long enough to be folded over more than one content line, ünïcödé included";
+        let contacts = vec![
+            ContactRecord {
+                remark: Some(long.into()),
+                ..make_contact(ContactType::Friend, Some("dave"))
+            },
+            make_contact(ContactType::Auto, Some("erin; the 2nd")),
+            make_contact(ContactType::Channel, Some("rust-users")),
+        ];
+        let data = Repo::with(&contacts).export(ContactFormat::VCard);
+
+        assert_eq!(data.matches("BEGIN:VCARD\r\n").count(), 2);
+        assert!(data.contains("VERSION:4.0\r\n"));
+        assert!(data.contains("FN:erin\\; the 2nd\r\n"));
+        assert!(data.contains("NICKNAME:dave\r\n"));
+        assert!(data.contains(&format!("X-BOSON-ID:{}\r\n", contacts[1].id)));
+        assert!(!data.contains(&contacts[2].id.to_base58()));
+        assert!(data.split("\r\n").all(|line| line.len() <= 75));
+
+        // Read back, less what vCard cannot carry.
+        let target = Repo::new();
+        let report = target.import(&data, ContactFormat::VCard);
+        assert_eq!(report.imported().len(), 2);
+        let dave = target.repository.contact(&contacts[0].id).unwrap().unwrap();
+        assert_eq!(dave.remark.as_deref(), Some(long));
+        assert_eq!(dave.name.as_deref(), Some("dave"));
+        assert_eq!(dave.home_peer_id, contacts[0].home_peer_id);
+        assert_eq!(dave.contact_type, ContactType::Friend);
+        let erin = target.repository.contact(&contacts[1].id).unwrap().unwrap();
+        assert_eq!(erin.remark.as_deref(), Some("erin; the 2nd"));
+    }
+
+    #[test]
+    fn test_vcard_messy_input() {
+        let ids = (0..5).map(|_| Id::random()).collect::<Vec<_>>();
+        let folded = ids[0].to_base58();
+        let (a, b) = folded.split_at(10);
+        let data = format!(concat!(
+            // Bare LF line endings, lower case names, a folded id and a group.
+            "begin:vcard\n",
+            "version:3.0\n",
+            "fn;charset=UTF-8:Zoë\n",
+            "  Müller\n",
+            "item1.x-boson-id:{a}\n",
+            " {b}\n",
+            "end:vcard\n",
+            "\n",
+            // vCard 2.1 with quoted-printable over a soft line break.
+            "BEGIN:VCARD\r\n",
+            "VERSION:2.1\r\n",
+            "N;CHARSET=UTF-8;ENCODING=QUOTED-PRINTABLE:M=C3=BCller;J=C3=\r\n",
+            "=BCrgen;;;\r\n",
+            "X-BOSON-ID:{id1}\r\n",
+            "END:VCARD\r\n",
+            // Quoted parameter values with separators, escapes and a tab fold.
+            "BEGIN:VCARD\r\n",
+            "VERSION:4.0\r\n",
+            "FN;TYPE=\"home,work\";X-NOTE=\"a:b\":Smith\\, Anna\\nPhD\r\n",
+            "X-BOSON-ID;VALUE=text:{id2}\r\n",
+            "X-BOSON-HOME-PEER:{peer}\r\n",
+            "END:VCARD\r\n",
+            // No boson id, a bad one and a card cut short.
+            "BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Nobody\r\nEND:VCARD\r\n",
+            "BEGIN:VCARD\r\nFN:Bad\r\nX-BOSON-ID:0OIl\r\nEND:VCARD\r\n",
+            "BEGIN:VCARD\r\nFN:Cut\r\nX-BOSON-ID:{id3}\r\n",
+        ), a = a, b = b, id1 = ids[1], id2 = ids[2], peer = ids[4], id3 = ids[3]);
+
+        let repo = Repo::new();
+        let report = repo.import(&data, ContactFormat::VCard);
+        assert_eq!(report.imported(), vec![ids[0], ids[1], ids[2]]);
+        let invalid = report.invalid().into_iter().map(|(i, _)| i).collect::<Vec<_>>();
+        assert_eq!(invalid, vec![3, 4, 5]);
+
+        let remark = |id: &Id| repo.repository.contact(id).unwrap().unwrap().remark;
+        assert_eq!(remark(&ids[0]).as_deref(), Some("Zoë Müller"));
+        assert_eq!(remark(&ids[1]).as_deref(), Some("Jürgen Müller"));
+        assert_eq!(remark(&ids[2]).as_deref(), Some("Smith, Anna\nPhD"));
+        let anna = repo.repository.contact(&ids[2]).unwrap().unwrap();
+        assert_eq!(anna.home_peer_id, Some(ids[4]));
+        assert_eq!(anna.session_key, None);
+    }
+
+    #[test]
+    fn test_transfer_from_builder() {
+        let device = KeyPair::random();
+        let dir = new_repo_dir();
+        let builder = MessagingClientBuilder::new()
+            .user_key(KeyPair::random())
+            .device_key(device.clone())
+            .data_dir(dir.to_path_buf());
+
+        // Nothing to transfer before the repository exists
+        let result = builder.import_contacts("[]".as_bytes(), ContactFormat::Native);
+        assert!(matches!(result, Err(Error::State(_))));
+        assert!(!dir.exists());
+
+        let contact = make_contact(ContactType::Friend, Some("alice"));
+        Database::open(&dir, device.private_key()).unwrap().put_contact(&contact).unwrap();
+
+        let mut data = Vec::new();
+        builder.export_contacts(&mut data, ContactFormat::VCard).unwrap();
+        let repo = Repo::new();
+        let report = repo.import(std::str::from_utf8(&data).unwrap(), ContactFormat::VCard);
+        assert_eq!(report.imported(), &[contact.id]);
+
+        let fresh = Id::random();
+        let report = builder.import_contacts(vcard_of(&fresh, "FN:Carol\r\n").as_bytes(), ContactFormat::VCard).unwrap();
+        assert_eq!(report.imported(), &[fresh]);
+        let mut data = Vec::new();
+        builder.export_contacts(&mut data, ContactFormat::Native).unwrap();
+        assert!(String::from_utf8(data).unwrap().contains(&fresh.to_base58()));
+    }
+}
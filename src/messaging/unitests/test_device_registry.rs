@@ -0,0 +1,178 @@
+use std::sync::Mutex;
+use url::Url;
+
+use crate::{Id, signature};
+use crate::messaging::{
+    BoxFuture,
+    Error,
+    Result,
+    MessagingClientBuilder,
+    device_registry::{
+        self,
+        DeviceInfo,
+        DeviceRegistry,
+        DeviceRequest,
+        ServiceInfo,
+        MAX_DEVICE_NAME_LEN,
+    },
+};
+
+// The service API as seen by the client, holding the devices of one user.
+struct MockRegistry {
+    max_devices: Option<u32>,
+    devices: Mutex<Vec<DeviceInfo>>,
+}
+
+impl MockRegistry {
+    fn new(max_devices: Option<u32>, names: &[&str]) -> Self {
+        let devices = names.iter()
+            .map(|name| DeviceInfo::new(Id::random(), name, None, 0))
+            .collect();
+        Self {
+            max_devices,
+            devices: Mutex::new(devices),
+        }
+    }
+}
+
+impl DeviceRegistry for MockRegistry {
+    fn service_info<'a>(&'a self, _: &'a Url) -> BoxFuture<'a, Result<ServiceInfo>> {
+        Box::pin(async move {
+            Ok(ServiceInfo::new(Some("1.0"), self.max_devices))
+        })
+    }
+
+    fn devices<'a>(&'a self, _: &'a Url, _: &'a signature::KeyPair) -> BoxFuture<'a, Result<Vec<DeviceInfo>>> {
+        Box::pin(async move {
+            Ok(self.devices.lock().unwrap().clone())
+        })
+    }
+
+    fn register_device<'a>(&'a self, _: &'a Url, request: &'a DeviceRequest<'a>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut devices = self.devices.lock().unwrap();
+            let id = Id::from(request.device.public_key());
+            devices.retain(|d| d.id() != &id);
+
+            if let Some(max) = self.max_devices {
+                if devices.len() as u32 >= max {
+                    let body = format!(r#"{{"code":-11,"message":"Too many devices","limit":{max}}}"#);
+                    return Err(device_registry::api_error(403, &body));
+                }
+            }
+            devices.push(DeviceInfo::new(id, request.name, request.app_name, 0));
+            Ok(())
+        })
+    }
+}
+
+fn builder(name: &str) -> MessagingClientBuilder {
+    MessagingClientBuilder::new()
+        .api_url(Url::parse("https://example.com").unwrap())
+        .user_key(signature::KeyPair::random())
+        .device_key(signature::KeyPair::random())
+        .device_name(name).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_name() {
+        let b = MessagingClientBuilder::new().device_name("  Laptop\u{0065}\u{0301} ").unwrap();
+        assert_eq!(b.device_label(), Some("Laptop\u{00e9}"));
+
+        let longest = "x".repeat(MAX_DEVICE_NAME_LEN);
+        assert!(MessagingClientBuilder::new().device_name(&longest).is_ok());
+
+        for name in ["", "   ", "a\nb", &format!("{longest}x")] {
+            let e = MessagingClientBuilder::new().device_name(name).err().unwrap();
+            assert!(matches!(e, Error::Argument(_)), "{name:?}: {e}");
+        }
+    }
+
+    #[test]
+    fn test_unique_name() {
+        assert_eq!(device_registry::unique_device_name("phone", ["laptop"]), "phone");
+        assert_eq!(device_registry::unique_device_name("phone", ["Phone"]), "phone-2");
+        assert_eq!(device_registry::unique_device_name("phone", ["phone", "phone-2", "PHONE-3"]), "phone-4");
+
+        // The suffix stays within the limit.
+        let longest = "x".repeat(MAX_DEVICE_NAME_LEN);
+        let name = device_registry::unique_device_name(&longest, [longest.as_str()]);
+        assert_eq!(name.chars().count(), MAX_DEVICE_NAME_LEN);
+        assert!(name.ends_with("x-2"));
+    }
+
+    #[test]
+    fn test_api_errors() {
+        let e = device_registry::api_error(403, r#"{"code":-11,"message":"Too many devices","limit":5}"#);
+        assert!(matches!(e, Error::DeviceLimitExceeded { limit: Some(5) }));
+        assert_eq!(e.to_string(), "Device limit exceeded: at most 5 devices");
+
+        let e = device_registry::api_error(403, r#"{"code":-11}"#);
+        assert!(matches!(e, Error::DeviceLimitExceeded { limit: None }));
+
+        let e = device_registry::api_error(403, r#"{"code":-4,"message":"Forbidden"}"#);
+        assert!(matches!(e, Error::Protocol { code: -4, ref message } if message == "Forbidden"));
+
+        let e = device_registry::api_error(502, "<html>Bad gateway</html>");
+        assert!(matches!(e, Error::State(_)));
+
+        assert!(device_registry::device_error(-8, Some(5)).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_register_suffixed() {
+        let registry = MockRegistry::new(None, &["Phone", "phone-2"]);
+        let b = builder("phone");
+
+        let reg = b.register_device(&registry, Some("chat")).await.unwrap();
+        assert_eq!(reg.name(), "phone-3");
+        assert_eq!(reg.requested_name(), "phone");
+        assert!(reg.is_renamed());
+        assert_eq!(reg.device_id(), &Id::from(b.device_keypair().unwrap().public_key()));
+
+        // Registering again keeps the name.
+        let reg = b.register_device(&registry, Some("chat")).await.unwrap();
+        assert_eq!(reg.name(), "phone-3");
+
+        let reg = builder("tablet").register_device(&registry, None).await.unwrap();
+        assert_eq!(reg.name(), "tablet");
+        assert!(!reg.is_renamed());
+    }
+
+    #[tokio::test]
+    async fn test_register_limit() {
+        let registry = MockRegistry::new(Some(2), &["phone", "laptop"]);
+        let b = builder("tablet");
+
+        let info = b.service_info(&registry).await.unwrap();
+        assert_eq!(info.max_devices(), Some(2));
+
+        let e = b.register_device(&registry, None).await.err().unwrap();
+        assert!(matches!(e, Error::DeviceLimitExceeded { limit: Some(2) }));
+        assert_eq!(b.devices(&registry).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_devices_current() {
+        let registry = MockRegistry::new(None, &["phone", "laptop"]);
+        let b = builder("tablet");
+
+        let devices = b.devices(&registry).await.unwrap();
+        assert!(devices.iter().all(|d| !d.is_current()));
+
+        b.register_device(&registry, None).await.unwrap();
+        let devices = b.devices(&registry).await.unwrap();
+        let current = devices.iter().filter(|d| d.is_current()).collect::<Vec<_>>();
+        assert_eq!(current.len(), 1);
+        assert_eq!(current[0].name(), "tablet");
+
+        let e = MessagingClientBuilder::new()
+            .api_url(Url::parse("https://example.com").unwrap())
+            .devices(&registry).await.err().unwrap();
+        assert!(matches!(e, Error::State(_)));
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use crate::messaging::{
+    client::BoxFuture,
+    errors::Result,
+    inbox_sync::{self, Delivery, InboxSequencer, InboxService, MAX_PENDING},
+};
+
+// The service side, keeping the messages it did not lose.
+struct MockInbox {
+    head        : u64,
+    kept        : BTreeMap<u64, String>,
+    // Replays the history backwards, each message twice.
+    shuffled    : bool,
+    requested   : Vec<(u64, u64)>,
+}
+
+impl MockInbox {
+    fn new(head: u64, kept: &[u64]) -> Self {
+        Self {
+            head,
+            kept: kept.iter().map(|seq| (*seq, format!("m{seq}"))).collect(),
+            shuffled: false,
+            requested: Vec::new(),
+        }
+    }
+}
+
+impl InboxService<String> for MockInbox {
+    fn head(&mut self) -> BoxFuture<'_, Result<u64>> {
+        let head = self.head;
+        Box::pin(async move { Ok(head) })
+    }
+
+    fn history(&mut self, from: u64, to: u64) -> BoxFuture<'_, Result<Vec<(u64, String)>>> {
+        self.requested.push((from, to));
+        let mut items = self.kept.range(from..=to)
+            .map(|(seq, v)| (*seq, v.clone()))
+            .collect::<Vec<_>>();
+        if self.shuffled {
+            items.reverse();
+            items.extend(items.clone());
+        }
+        Box::pin(async move { Ok(items) })
+    }
+}
+
+fn msg(seq: u64) -> Delivery<String> {
+    Delivery::Message(format!("m{seq}"))
+}
+
+fn accept_all(sequencer: &mut InboxSequencer<String>, seqs: &[u64]) -> Vec<Delivery<String>> {
+    seqs.iter().flat_map(|seq| sequencer.accept(*seq, format!("m{seq}"))).collect()
+}
+
+#[test]
+fn test_out_of_order_replay() {
+    let mut sequencer = InboxSequencer::new(Some(3));
+    assert_eq!(accept_all(&mut sequencer, &[5]), vec![]);
+    assert_eq!(sequencer.missing(), Some((4, 4)));
+
+    assert_eq!(accept_all(&mut sequencer, &[4, 6, 4, 3, 5]), vec![msg(4), msg(5), msg(6)]);
+    assert_eq!(sequencer.cursor(), Some(6));
+    assert_eq!(sequencer.missing(), None);
+    assert_eq!(sequencer.duplicates(), 3);
+}
+
+#[tokio::test]
+async fn test_backfill_with_gap() {
+    // Offline from 3 on, the service lost 5 and 6 over its queue limits.
+    let mut sequencer = InboxSequencer::new(Some(2));
+    let mut service = MockInbox::new(8, &[3, 4, 7, 8]);
+    service.shuffled = true;
+
+    let deliveries = inbox_sync::sync(&mut sequencer, &mut service).await.unwrap();
+    assert_eq!(service.requested, vec![(3, 8)]);
+    assert_eq!(deliveries, vec![msg(3), msg(4), Delivery::Gap(5, 6), msg(7), msg(8)]);
+    assert_eq!(sequencer.cursor(), Some(8));
+
+    // The broker replays its queue once the live traffic is processed.
+    assert_eq!(accept_all(&mut sequencer, &[7, 9, 8]), vec![msg(9)]);
+    assert_eq!(sequencer.duplicates(), 4 + 2);
+}
+
+#[tokio::test]
+async fn test_lost_up_to_head() {
+    let mut sequencer = InboxSequencer::new(Some(2));
+    let mut service = MockInbox::new(6, &[3]);
+    let deliveries = inbox_sync::sync(&mut sequencer, &mut service).await.unwrap();
+    assert_eq!(deliveries, vec![msg(3), Delivery::Gap(4, 6)]);
+    assert_eq!(sequencer.cursor(), Some(6));
+
+    // Nothing more to fetch.
+    let deliveries = inbox_sync::sync(&mut sequencer, &mut service).await.unwrap();
+    assert_eq!(deliveries, vec![]);
+    assert_eq!(service.requested, vec![(3, 6)]);
+}
+
+#[tokio::test]
+async fn test_fill_live_hole() {
+    let mut sequencer = InboxSequencer::new(Some(8));
+    assert_eq!(accept_all(&mut sequencer, &[10, 11]), vec![]);
+    assert_eq!(sequencer.missing(), Some((9, 9)));
+
+    let mut service = MockInbox::new(11, &[9, 10, 11]);
+    let deliveries = inbox_sync::sync(&mut sequencer, &mut service).await.unwrap();
+    assert_eq!(deliveries, vec![msg(9), msg(10), msg(11)]);
+    assert_eq!(sequencer.missing(), None);
+}
+
+#[tokio::test]
+async fn test_fresh_client() {
+    // Starts at the head rather than reporting the history as lost.
+    let mut sequencer = InboxSequencer::new(None);
+    let mut service = MockInbox::new(40, &[39, 40]);
+    let deliveries = inbox_sync::sync(&mut sequencer, &mut service).await.unwrap();
+    assert_eq!(deliveries, vec![]);
+    assert!(service.requested.is_empty());
+    assert_eq!(sequencer.cursor(), Some(40));
+
+    // Or with the first live message, before any sync.
+    let mut sequencer = InboxSequencer::new(None);
+    assert_eq!(accept_all(&mut sequencer, &[17, 18]), vec![msg(17), msg(18)]);
+}
+
+#[test]
+fn test_too_many_held_back() {
+    let mut sequencer = InboxSequencer::new(Some(0));
+    let seqs = (2..=MAX_PENDING as u64 + 1).collect::<Vec<_>>();
+    assert_eq!(accept_all(&mut sequencer, &seqs), vec![]);
+
+    let deliveries = accept_all(&mut sequencer, &[MAX_PENDING as u64 + 2]);
+    assert_eq!(deliveries.len(), MAX_PENDING + 2);
+    assert_eq!(deliveries[0], Delivery::Gap(1, 1));
+    assert_eq!(deliveries[1], msg(2));
+    assert_eq!(sequencer.cursor(), Some(MAX_PENDING as u64 + 2));
+}
@@ -0,0 +1,97 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use rumqttc::{
+    Packet, QoS,
+    ConnAck, ConnectReturnCode, Connect,
+    Publish, PubAck, PubRec, PubRel, PubComp,
+    Subscribe, SubAck, SubscribeReasonCode,
+    Unsubscribe, UnsubAck,
+};
+
+use crate::Id;
+use crate::messaging::incoming::{self, IncomingPackets, Action, packet_type};
+
+fn all_packets() -> Vec<Packet> {
+    vec![
+        Packet::Connect(Connect::new("client")),
+        Packet::ConnAck(ConnAck::new(ConnectReturnCode::Success, true)),
+        Packet::Publish(Publish::new("inbox/user", QoS::AtLeastOnce, vec![1u8, 2, 3])),
+        Packet::PubAck(PubAck::new(1)),
+        Packet::PubRec(PubRec::new(2)),
+        Packet::PubRel(PubRel::new(3)),
+        Packet::PubComp(PubComp::new(4)),
+        Packet::Subscribe(Subscribe::new("inbox/user", QoS::AtLeastOnce)),
+        Packet::SubAck(SubAck::new(5, vec![
+            SubscribeReasonCode::Success(QoS::AtLeastOnce),
+            SubscribeReasonCode::Failure,
+        ])),
+        Packet::Unsubscribe(Unsubscribe::new("inbox/user")),
+        Packet::UnsubAck(UnsubAck::new(6)),
+        Packet::PingReq,
+        Packet::PingResp,
+        Packet::Disconnect,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_packet() {
+        let counter = Arc::new(AtomicU64::new(0));
+        let mut incoming = IncomingPackets::new(counter.clone());
+
+        let mut unexpected = Vec::new();
+        for packet in all_packets() {
+            let action = incoming.dispatch(&packet);
+            let expected = match packet {
+                Packet::ConnAck(_)  => Action::Connected,
+                Packet::Publish(_)  => Action::Publish,
+                Packet::PingResp    => Action::PingResp,
+                Packet::Disconnect  => Action::Disconnected,
+                _ => Action::Ignore,
+            };
+            assert_eq!(action, expected, "{}", packet_type(&packet));
+
+            if matches!(packet, Packet::Connect(_) | Packet::Subscribe(_) | Packet::Unsubscribe(_) | Packet::PingReq) {
+                unexpected.push(packet_type(&packet));
+            }
+        }
+
+        assert_eq!(unexpected, ["CONNECT", "SUBSCRIBE", "UNSUBSCRIBE", "PINGREQ"]);
+        assert_eq!(incoming.received(), 14);
+        assert_eq!(incoming.unexpected(), 4);
+
+        // Shared with the client diagnostics.
+        assert_eq!(counter.load(std::sync::atomic::Ordering::Relaxed), 4);
+    }
+
+    #[test]
+    fn test_expected_packets() {
+        let mut incoming = IncomingPackets::default();
+        for _ in 0..3 {
+            incoming.dispatch(&Packet::PingResp);
+            incoming.dispatch(&Packet::PubRel(PubRel::new(7)));
+            incoming.dispatch(&Packet::SubAck(SubAck::new(8, vec![SubscribeReasonCode::Failure])));
+        }
+        assert_eq!(incoming.received(), 9);
+        assert_eq!(incoming.unexpected(), 0);
+    }
+
+    #[test]
+    fn test_genuine_notifications() {
+        let home = Id::random();
+        let channel = Id::random();
+        let user = Id::random();
+        let other = Id::random();
+
+        assert!(incoming::is_genuine_notification(&home, &user, &home, true));
+        assert!(!incoming::is_genuine_notification(&other, &user, &home, true));
+        assert!(!incoming::is_genuine_notification(&channel, &channel, &home, true));
+
+        assert!(incoming::is_genuine_notification(&home, &channel, &home, false));
+        assert!(incoming::is_genuine_notification(&channel, &channel, &home, false));
+        assert!(!incoming::is_genuine_notification(&other, &channel, &home, false));
+    }
+}
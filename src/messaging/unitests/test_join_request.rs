@@ -0,0 +1,330 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, UNIX_EPOCH};
+use serde_cbor::Value;
+
+use crate::Id;
+use crate::messaging::{
+    Error,
+    Result,
+    join_request::{
+        self,
+        JoinRequest,
+        JoinPolicy,
+        JoinApproval,
+        JoinRequests,
+        PendingJoins,
+        Decision,
+    },
+};
+
+// Carried through the notification data or the RPC parameters.
+fn round_trip<T>(v: &T) -> T
+where
+    T: serde::Serialize + serde::de::DeserializeOwned
+{
+    let value = serde_cbor::value::to_value(v).unwrap();
+    let bytes = serde_cbor::to_vec(&value).unwrap();
+    serde_cbor::from_slice::<T>(&bytes).unwrap()
+}
+
+fn keys<T: serde::Serialize>(v: &T) -> Vec<String> {
+    match serde_cbor::value::to_value(v).unwrap() {
+        Value::Map(map) => map.keys().map(|k| match k {
+            Value::Text(k) => k.clone(),
+            _ => panic!("non-text key"),
+        }).collect(),
+        _ => panic!("not a map"),
+    }
+}
+
+// A ChannelJoin in flight, as kept by the client worker.
+#[derive(Debug, PartialEq, Eq)]
+struct Call {
+    channel_id: Id,
+    attempt: u32,
+}
+
+// Answers the worker gets for a ChannelJoin.
+enum Response {
+    Joined,
+    Error(i32),
+}
+
+// Drives a join the way the client worker does, recording what was sent
+// and what the caller of join_channel got.
+struct Worker {
+    joins: PendingJoins<Call>,
+    sent: Vec<Call>,
+    outcomes: Vec<(Id, Result<()>)>,
+}
+
+impl Worker {
+    fn new() -> Self {
+        Self {
+            joins: PendingJoins::new(),
+            sent: Vec::new(),
+            outcomes: Vec::new(),
+        }
+    }
+
+    fn join(&mut self, channel_id: &Id) {
+        self.send(Call { channel_id: *channel_id, attempt: 1 });
+    }
+
+    fn send(&mut self, call: Call) {
+        self.joins.begin(&call.channel_id);
+        self.sent.push(call);
+    }
+
+    fn decide(&mut self, decision: Decision<Call>) {
+        match decision {
+            Decision::Wait => {},
+            Decision::Approved(call) => {
+                let attempt = call.attempt + 1;
+                self.send(Call { channel_id: call.channel_id, attempt });
+            },
+            Decision::Denied(call) => {
+                let denied = join_request::join_error(&call.channel_id, join_request::JOIN_DENIED);
+                self.outcomes.push((call.channel_id, Err(denied.unwrap())));
+            },
+        }
+    }
+
+    fn on_response(&mut self, response: Response) {
+        let call = self.sent.pop().unwrap();
+        let channel_id = call.channel_id;
+        match response {
+            Response::Error(join_request::JOIN_PENDING) => {
+                let decision = self.joins.on_pending(&channel_id, call);
+                self.decide(decision);
+            },
+            Response::Error(code) => {
+                self.joins.finish(&channel_id);
+                let e = join_request::join_error(&channel_id, code)
+                    .unwrap_or(Error::Protocol { code, message: "refused".into() });
+                self.outcomes.push((channel_id, Err(e)));
+            },
+            Response::Joined => {
+                self.joins.finish(&channel_id);
+                self.outcomes.push((channel_id, Ok(())));
+            },
+        }
+    }
+
+    // The CHANNEL_JOIN_APPROVED or CHANNEL_JOIN_DENIED notification.
+    fn on_notification(&mut self, event: u32, data: Value) {
+        let channel_id: Id = serde_cbor::value::from_value(data).unwrap();
+        let approved = event == 11;
+        let decision = self.joins.on_decision(&channel_id, approved);
+        self.decide(decision);
+    }
+}
+
+fn decision_data(channel_id: &Id) -> Value {
+    serde_cbor::value::to_value(channel_id).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_request_serialization() {
+        let channel_id = Id::random();
+        let requester = Id::random();
+        let request = JoinRequest::new(channel_id, requester, Some("alice".into()), 1700000000000);
+        assert_eq!(keys(&request), vec!["c", "n", "t", "u"]);
+
+        let decoded = round_trip(&request);
+        assert_eq!(decoded, request);
+        assert_eq!(decoded.channel_id(), &channel_id);
+        assert_eq!(decoded.requester(), &requester);
+        assert_eq!(decoded.name(), Some("alice"));
+        assert_eq!(decoded.requested_at(), UNIX_EPOCH + Duration::from_millis(1700000000000));
+
+        // The name is left out when not shared.
+        let anonymous = JoinRequest::new(channel_id, requester, None, 1);
+        assert_eq!(keys(&anonymous), vec!["c", "t", "u"]);
+        assert_eq!(round_trip(&anonymous).name(), None);
+    }
+
+    #[test]
+    fn test_join_policy_serialization() {
+        let unbounded = JoinPolicy::new(None, false).unwrap();
+        assert_eq!(unbounded, JoinPolicy::default());
+        assert!(keys(&unbounded).is_empty());
+
+        let policy = JoinPolicy::new(Some(50), true).unwrap();
+        assert_eq!(keys(&policy), vec!["ar", "mm"]);
+        let decoded = round_trip(&policy);
+        assert_eq!(decoded.max_members(), Some(50));
+        assert!(decoded.approval_required());
+
+        // Fields missing on the wire take their defaults.
+        let empty: JoinPolicy = serde_cbor::value::from_value(Value::Map(BTreeMap::new())).unwrap();
+        assert_eq!(empty.max_members(), None);
+        assert!(!empty.approval_required());
+
+        assert!(matches!(JoinPolicy::new(Some(0), false), Err(Error::Argument(_))));
+        assert!(matches!(JoinPolicy::new(Some(u32::MAX as usize + 1), false), Err(Error::Argument(_))));
+    }
+
+    #[test]
+    fn test_join_approval_serialization() {
+        let user = Id::random();
+        let approval = JoinApproval::new(user, true);
+        assert_eq!(keys(&approval), vec!["a", "u"]);
+
+        let decoded = round_trip(&approval);
+        assert_eq!(decoded.user(), &user);
+        assert!(decoded.approve());
+        assert!(!round_trip(&JoinApproval::new(user, false)).approve());
+    }
+
+    #[test]
+    fn test_join_errors() {
+        let channel_id = Id::random();
+        let full = join_request::join_error(&channel_id, join_request::CHANNEL_FULL).unwrap();
+        assert!(matches!(full, Error::ChannelFull(_)));
+        assert!(full.to_string().starts_with("Channel full"));
+
+        let denied = join_request::join_error(&channel_id, join_request::JOIN_DENIED).unwrap();
+        assert!(matches!(denied, Error::JoinDenied(_)));
+        assert!(denied.to_string().contains(&channel_id.to_string()));
+
+        assert!(join_request::join_error(&channel_id, join_request::JOIN_PENDING).is_none());
+        assert!(join_request::join_error(&channel_id, -4).is_none());
+    }
+
+    #[test]
+    fn test_join_without_approval() {
+        let mut worker = Worker::new();
+        let channel_id = Id::random();
+
+        worker.join(&channel_id);
+        assert!(worker.joins.is_pending(&channel_id));
+        worker.on_response(Response::Joined);
+
+        assert!(!worker.joins.is_pending(&channel_id));
+        assert_eq!(worker.outcomes.len(), 1);
+        assert!(worker.outcomes[0].1.is_ok());
+    }
+
+    #[test]
+    fn test_join_full_channel() {
+        let mut worker = Worker::new();
+        let channel_id = Id::random();
+
+        worker.join(&channel_id);
+        worker.on_response(Response::Error(join_request::CHANNEL_FULL));
+
+        assert!(!worker.joins.is_pending(&channel_id));
+        assert!(matches!(worker.outcomes[0].1, Err(Error::ChannelFull(_))));
+    }
+
+    #[test]
+    fn test_join_approved() {
+        let mut worker = Worker::new();
+        let channel_id = Id::random();
+
+        worker.join(&channel_id);
+        worker.on_response(Response::Error(join_request::JOIN_PENDING));
+        assert!(worker.sent.is_empty());
+        assert!(worker.outcomes.is_empty());
+        assert!(worker.joins.is_pending(&channel_id));
+
+        // The approval sends the join once more, completed as usual.
+        worker.on_notification(11, decision_data(&channel_id));
+        assert_eq!(worker.sent, vec![Call { channel_id, attempt: 2 }]);
+        worker.on_response(Response::Joined);
+
+        assert_eq!(worker.outcomes.len(), 1);
+        assert!(worker.outcomes[0].1.is_ok());
+        assert!(!worker.joins.is_pending(&channel_id));
+    }
+
+    #[test]
+    fn test_join_denied() {
+        let mut worker = Worker::new();
+        let channel_id = Id::random();
+
+        worker.join(&channel_id);
+        worker.on_response(Response::Error(join_request::JOIN_PENDING));
+        worker.on_notification(12, decision_data(&channel_id));
+
+        assert!(worker.sent.is_empty());
+        assert!(matches!(worker.outcomes[0].1, Err(Error::JoinDenied(_))));
+        assert!(!worker.joins.is_pending(&channel_id));
+    }
+
+    #[test]
+    fn test_decision_before_pending_response() {
+        let mut worker = Worker::new();
+        let approved = Id::random();
+        let denied = Id::random();
+
+        worker.join(&approved);
+        worker.on_notification(11, decision_data(&approved));
+        assert!(worker.joins.is_pending(&approved));
+        worker.on_response(Response::Error(join_request::JOIN_PENDING));
+        assert_eq!(worker.sent, vec![Call { channel_id: approved, attempt: 2 }]);
+        worker.on_response(Response::Joined);
+
+        worker.join(&denied);
+        worker.on_notification(12, decision_data(&denied));
+        worker.on_response(Response::Error(join_request::JOIN_PENDING));
+
+        assert_eq!(worker.outcomes.len(), 2);
+        assert_eq!(worker.outcomes[0].0, approved);
+        assert!(worker.outcomes[0].1.is_ok());
+        assert_eq!(worker.outcomes[1].0, denied);
+        assert!(matches!(worker.outcomes[1].1, Err(Error::JoinDenied(_))));
+    }
+
+    #[test]
+    fn test_decision_on_unknown_join_ignored() {
+        let mut worker = Worker::new();
+        let channel_id = Id::random();
+
+        worker.on_notification(11, decision_data(&channel_id));
+        worker.on_notification(12, decision_data(&channel_id));
+        assert!(worker.sent.is_empty());
+        assert!(worker.outcomes.is_empty());
+        assert!(!worker.joins.is_pending(&channel_id));
+    }
+
+    #[test]
+    fn test_join_requests_cache() {
+        let mut cache = JoinRequests::new();
+        let channel_id = Id::random();
+        let alice = Id::random();
+        let bob = Id::random();
+
+        // Through the CHANNEL_JOIN_REQUESTED notification data.
+        let request = |user: &Id, name: &str, at: u64| {
+            let request = JoinRequest::new(channel_id, *user, Some(name.into()), at);
+            round_trip(&request)
+        };
+
+        assert!(cache.add(request(&bob, "bob", 2000)));
+        assert!(cache.add(request(&alice, "alice", 1000)));
+        assert!(!cache.add(request(&bob, "bobby", 3000)));
+
+        let pending = cache.pending(&channel_id);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].requester(), &alice);
+        assert_eq!(pending[1].requester(), &bob);
+        assert_eq!(pending[1].name(), Some("bobby"));
+        assert!(cache.pending(&Id::random()).is_empty());
+
+        // Resolved by another moderator, through CHANNEL_JOIN_RESOLVED.
+        let resolved: JoinApproval = round_trip(&JoinApproval::new(alice, true));
+        assert_eq!(cache.resolve(&channel_id, resolved.user()).unwrap().requester(), &alice);
+        assert!(cache.resolve(&channel_id, &alice).is_none());
+        assert_eq!(cache.pending(&channel_id).len(), 1);
+
+        cache.remove_channel(&channel_id);
+        assert!(cache.pending(&channel_id).is_empty());
+        assert!(cache.resolve(&channel_id, &bob).is_none());
+    }
+}
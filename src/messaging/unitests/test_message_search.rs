@@ -0,0 +1,214 @@
+use std::{
+    fs,
+    path::Path,
+};
+use diesel::prelude::*;
+
+use crate::{
+    Id,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    message::{MessageType, content_type},
+    message_search,
+    persistence::database::{Database, MessageRecord},
+};
+use super::test_utils::new_repo_dir;
+
+fn raw_conn(dir: &Path) -> SqliteConnection {
+    SqliteConnection::establish(&dir.join("messaging.db").to_string_lossy()).unwrap()
+}
+
+fn make_message(conversation_id: &Id, created: u64, body: &str) -> MessageRecord {
+    MessageRecord {
+        rid: 0,
+        conversation_id: *conversation_id,
+        sender: Id::random(),
+        message_type: MessageType::ContentMessage,
+        created,
+        body: body.as_bytes().to_vec(),
+        content_type: None,
+    }
+}
+
+// Text messages in two conversations together with messages the search
+// leaves out, returns the rids of the text messages.
+fn populate(db: &Database, alice: &Id, bob: &Id) -> Vec<i64> {
+    let rids = [
+        (alice, 1000, "Rust 2024 edition is out"),
+        (bob,   2000, "Did you try the new rust analyzer?"),
+        (alice, 3000, "lunch at noon, then some RUST"),
+        (bob,   4000, "no crabs today"),
+    ].into_iter().map(|(conv, created, body)| {
+        db.put_message(&make_message(conv, created, body)).unwrap()
+    }).collect();
+
+    db.put_message(&MessageRecord {
+        content_type: Some(content_type::IMAGE_PNG.into()),
+        ..make_message(alice, 5000, "rust")
+    }).unwrap();
+    db.put_message(&MessageRecord {
+        message_type: MessageType::ControlMessage,
+        ..make_message(bob, 6000, "rust")
+    }).unwrap();
+    rids
+}
+
+fn message_ids(hits: &[crate::messaging::MessageHit]) -> Vec<i64> {
+    hits.iter().map(|hit| hit.message_id()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scoped_and_unscoped() {
+        let dir = new_repo_dir();
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        let alice = Id::random();
+        let bob = Id::random();
+        let rids = populate(&db, &alice, &bob);
+
+        // Newest first, non-text content is not indexed
+        let hits = db.search_messages("rust", None, 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[2], rids[1], rids[0]]);
+        assert_eq!(hits[0].conversation_id(), &alice);
+        assert_eq!(hits[0].created(), 3000);
+        assert_eq!(hits[1].conversation_id(), &bob);
+
+        let hits = db.search_messages("rust", Some(&alice), 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[2], rids[0]]);
+
+        let hits = db.search_messages("rust", None, 1, 1).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[1]]);
+
+        // Every word has to match
+        let hits = db.search_messages("rust edition", None, 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[0]]);
+        assert!(db.search_messages("rust crabs", None, 10, 0).unwrap().is_empty());
+        assert!(db.search_messages("  ?! ", None, 10, 0).unwrap().is_empty());
+
+        // Removed conversations drop out of the index
+        db.remove_messages_by_conversation(&alice).unwrap();
+        let hits = db.search_messages("rust", None, 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[1]]);
+
+    }
+
+    #[test]
+    fn test_case_and_normalization() {
+        let dir = new_repo_dir();
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        let conversation = Id::random();
+
+        // Decomposed e followed by a combining acute accent
+        let rid = db.put_message(&make_message(&conversation, 1000, "Cafe\u{301} Ølhus tonight")).unwrap();
+        for query in ["café", "CAFÉ", "cafe\u{301}", "ølhus"] {
+            let hits = db.search_messages(query, None, 10, 0).unwrap();
+            assert_eq!(message_ids(&hits), vec![rid], "query {query}");
+        }
+        assert!(db.search_messages("cafe", None, 10, 0).unwrap().is_empty());
+
+    }
+
+    #[test]
+    fn test_snippet() {
+        let dir = new_repo_dir();
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        let conversation = Id::random();
+        let body = "one two three four five six seven Boson eight nine ten eleven \
+            twelve thirteen fourteen boson fifteen sixteen seventeen eighteen";
+        db.put_message(&make_message(&conversation, 1000, body)).unwrap();
+
+        let hits = db.search_messages("BOSON", None, 10, 0).unwrap();
+        assert_eq!(hits.len(), 1);
+        let hit = &hits[0];
+        assert_eq!(hit.snippet(),
+            "…four five six seven Boson eight nine ten eleven twelve thirteen fourteen boson fifteen sixteen seventeen…");
+        let words = hit.highlights().iter()
+            .map(|range| &hit.snippet()[range.clone()])
+            .collect::<Vec<_>>();
+        assert_eq!(words, vec!["Boson", "boson"]);
+
+        // Short texts are shown whole
+        let terms = message_search::tokenize("noon");
+        let (snippet, highlights) = message_search::snippet("lunch at noon", &terms);
+        assert_eq!(snippet, "lunch at noon");
+        assert_eq!(highlights, vec![9..13]);
+
+    }
+
+    #[test]
+    fn test_reindex() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let alice = Id::random();
+        let bob = Id::random();
+
+        let rids = {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            populate(&db, &alice, &bob)
+        };
+
+        let mut conn = raw_conn(&dir);
+        diesel::sql_query("DELETE FROM messages_fts").execute(&mut conn).unwrap();
+        drop(conn);
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert!(db.search_messages("rust", None, 10, 0).unwrap().is_empty());
+        assert_eq!(db.reindex_messages().unwrap(), rids.len());
+        let hits = db.search_messages("rust", None, 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rids[2], rids[1], rids[0]]);
+
+        // Rebuilding twice does not duplicate hits
+        assert_eq!(db.reindex_messages().unwrap(), rids.len());
+        assert_eq!(db.search_messages("rust", None, 10, 0).unwrap().len(), 3);
+
+    }
+
+    #[test]
+    fn test_index_not_plaintext() {
+        let dir = new_repo_dir();
+        let conversation = Id::random();
+        let word = "xylophonically";
+
+        {
+            let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+            db.put_message(&make_message(&conversation, 1000, word)).unwrap();
+            assert_eq!(db.search_messages(word, None, 10, 0).unwrap().len(), 1);
+        }
+
+        let bytes = fs::read(dir.join("messaging.db")).unwrap();
+        assert!(!bytes.windows(word.len()).any(|w| w == word.as_bytes()));
+    }
+
+    #[test]
+    fn test_migrate_v4_index() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let conversation = Id::random();
+
+        let rid = {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_message(&make_message(&conversation, 1000, "see you at the harbour")).unwrap()
+        };
+
+        // Back to the version 4 layout, without the content type and index.
+        let mut conn = raw_conn(&dir);
+        for stmt in [
+            "ALTER TABLE messages DROP COLUMN contentType",
+            "DROP TABLE messages_fts",
+            "PRAGMA user_version = 4",
+        ] {
+            diesel::sql_query(stmt).execute(&mut conn).unwrap();
+        }
+        drop(conn);
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        let hits = db.search_messages("harbour", Some(&conversation), 10, 0).unwrap();
+        assert_eq!(message_ids(&hits), vec![rid]);
+        assert_eq!(db.messages_since(&conversation, 0, 10, 0).unwrap()[0].content_type, None);
+
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+};
+
+use crate::{
+    Id,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    message::MessageType,
+    persistence::database::{Database, MessageRecord},
+    self_sync::SelfSync,
+    notification::{self, NotificationHint, NotifyLevel, MENTIONS_PROPERTY},
+};
+
+// A device of the user, with the worker storing the messages of a channel
+// and telling whether each one notifies.
+struct Device {
+    user    : Id,
+    sync    : SelfSync,
+    db      : Database,
+    dir     : PathBuf,
+}
+
+impl Device {
+    fn new(user: &Id) -> Self {
+        let dir = PathBuf::from(format!("/tmp/tnt_{:016x}", rand::random::<u64>()));
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        Self {
+            user    : *user,
+            sync    : SelfSync::new(Id::random(), vec![], vec![], vec![]),
+            db,
+            dir,
+        }
+    }
+
+    fn receive(&self,
+        channel: &Id,
+        text: &str,
+        properties: &BTreeMap<String, String>,
+        created: u64
+    ) -> NotificationHint {
+        let mentioned = notification::mentions(&self.user, Some(text), properties);
+        let hint = NotificationHint::of(self.sync.notify_level(channel), mentioned);
+        self.db.put_message(&MessageRecord {
+            rid: 0,
+            conversation_id: *channel,
+            sender: Id::random(),
+            message_type: MessageType::ContentMessage,
+            created,
+            body: text.as_bytes().to_vec(),
+            content_type: None,
+        }).unwrap();
+        hint
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn props(mentions: &str) -> BTreeMap<String, String> {
+    BTreeMap::from([(MENTIONS_PROPERTY.to_string(), mentions.to_string())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mentions() {
+        let me = Id::random();
+        let other = Id::random();
+        let none = BTreeMap::new();
+
+        assert!(notification::mentions(&me, Some(&format!("hi @{me}, look")), &none));
+        assert!(!notification::mentions(&me, Some(&format!("hi @{other}")), &none));
+        // The id alone is no mention, nor a message without text.
+        assert!(!notification::mentions(&me, Some(&format!("{me}")), &none));
+        assert!(!notification::mentions(&me, None, &none));
+
+        assert!(notification::mentions(&me, None, &props(&format!("{other}, {me}"))));
+        assert!(notification::mentions(&me, None, &props(&format!("{other} {me}"))));
+        assert!(!notification::mentions(&me, None, &props(&format!("{other},not-an-id"))));
+    }
+
+    #[test]
+    fn test_hint_by_level() {
+        use NotificationHint::*;
+        assert_eq!(NotificationHint::of(NotifyLevel::All, false), Notify);
+        assert_eq!(NotificationHint::of(NotifyLevel::Mentions, false), Silent);
+        assert_eq!(NotificationHint::of(NotifyLevel::Mentions, true), Notify);
+        assert_eq!(NotificationHint::of(NotifyLevel::None, true), Silent);
+    }
+
+    #[test]
+    fn test_mentions_only_channel() {
+        let user = Id::random();
+        let device = Device::new(&user);
+        let channel = Id::random();
+        let none = BTreeMap::new();
+
+        assert_eq!(device.sync.notify_level(&channel), NotifyLevel::All);
+        assert!(device.sync.set_notify_level(&channel, NotifyLevel::Mentions, 10).is_some());
+        // Setting the same level again sends nothing.
+        assert!(device.sync.set_notify_level(&channel, NotifyLevel::Mentions, 20).is_none());
+
+        let plain = device.receive(&channel, "lunch anyone?", &none, 1_000);
+        let mentioning = device.receive(&channel, &format!("@{user} are you in?"), &none, 2_000);
+        let listed = device.receive(&channel, "see the minutes", &props(&user.to_string()), 3_000);
+        assert_eq!(plain, NotificationHint::Silent);
+        assert_eq!(mentioning, NotificationHint::Notify);
+        assert_eq!(listed, NotificationHint::Notify);
+
+        // Every message was stored, the silent one included.
+        let stored = device.db.messages_since(&channel, 0, 10, 0).unwrap();
+        let bodies = stored.iter().map(|m| m.body.as_slice()).collect::<Vec<_>>();
+        assert_eq!(bodies.len(), 3);
+        assert_eq!(bodies[0], b"lunch anyone?");
+
+        // Other conversations keep notifying.
+        let other = Id::random();
+        assert_eq!(device.receive(&other, "lunch anyone?", &none, 4_000), NotificationHint::Notify);
+    }
+
+    #[test]
+    fn test_level_synced_across_devices() {
+        let user = Id::random();
+        let phone = Device::new(&user);
+        let laptop = Device::new(&user);
+        let channel = Id::random();
+        let none = BTreeMap::new();
+
+        let update = phone.sync.set_notify_level(&channel, NotifyLevel::None, 10).unwrap();
+        phone.db.put_notify_levels(&update.notify).unwrap();
+
+        let applied = laptop.sync.apply(&update).unwrap();
+        assert_eq!(applied.notify, update.notify);
+        laptop.db.put_notify_levels(&applied.notify).unwrap();
+        assert_eq!(laptop.receive(&channel, &format!("@{user}"), &none, 1_000), NotificationHint::Silent);
+
+        // An older change from the laptop loses to the phone's.
+        let stale = SelfSync::new(Id::random(), vec![], vec![], vec![])
+            .set_notify_level(&channel, NotifyLevel::Mentions, 5)
+            .unwrap();
+        assert!(phone.sync.apply(&stale).unwrap().notify.is_empty());
+        assert_eq!(phone.sync.notify_level(&channel), NotifyLevel::None);
+
+        // Kept by the repository, to resume from on the next start.
+        let restored = laptop.db.notify_levels().unwrap();
+        assert_eq!(restored, update.notify);
+        let restarted = SelfSync::new(Id::random(), vec![], vec![], restored);
+        assert_eq!(restarted.notify_level(&channel), NotifyLevel::None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::{
+    Id,
+    messaging::{
+        Error,
+        Result,
+        pending_calls::{Answered, Expired, PendingCalls, RequestIds},
+    },
+};
+
+const TIMEOUT: Duration = Duration::from_millis(200);
+
+type Reply = oneshot::Sender<Result<()>>;
+
+// Drives the table the way the client worker does, against a service that
+// never answers.
+struct SilentService {
+    peer: Id,
+    pending: PendingCalls<Reply>,
+    sent: Vec<u32>,
+    spoofed: u64,
+}
+
+impl SilentService {
+    fn new() -> Self {
+        Self {
+            peer: Id::random(),
+            pending: PendingCalls::new(TIMEOUT),
+            sent: Vec::new(),
+            spoofed: 0,
+        }
+    }
+
+    fn call(&mut self, id: u32, retryable: bool) -> oneshot::Receiver<Result<()>> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.insert(id, self.peer, tx, retryable, Instant::now());
+        self.sent.push(id);
+        rx
+    }
+
+    // A response with the request id as the worker receives it.
+    fn respond(&mut self, id: u32, from: &Id) {
+        match self.pending.take(id, from) {
+            Answered::Call(reply) => _ = reply.send(Ok(())),
+            Answered::Spoofed(_) => self.spoofed += 1,
+            Answered::Late | Answered::Unknown => {},
+        }
+    }
+
+    fn sweep(&mut self) {
+        for expired in self.pending.expire(Instant::now()) {
+            match expired {
+                Expired::Retry(id, reply) => {
+                    self.pending.resend(id, self.peer, reply, Instant::now());
+                    self.sent.push(id);
+                },
+                Expired::Timeout(_, reply) => {
+                    _ = reply.send(Err(Error::Timeout));
+                },
+            }
+        }
+    }
+
+    async fn wait(&mut self, mut rx: oneshot::Receiver<Result<()>>) -> Result<()> {
+        let mut sweeper = tokio::time::interval(self.pending.sweep_interval());
+        loop {
+            tokio::select! {
+                result = &mut rx => return result.unwrap(),
+                _ = sweeper.tick() => self.sweep(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_times_out_without_response() {
+        let mut service = SilentService::new();
+        let started = Instant::now();
+        let rx = service.call(1, false);
+
+        let result = service.wait(rx).await;
+        let elapsed = started.elapsed();
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(elapsed >= TIMEOUT);
+        assert!(elapsed < TIMEOUT + service.pending.sweep_interval() * 2, "{elapsed:?}");
+        assert!(service.pending.is_empty());
+        assert_eq!(service.sent, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn test_idempotent_retried_once() {
+        let mut service = SilentService::new();
+        let started = Instant::now();
+        let rx = service.call(7, true);
+
+        let result = service.wait(rx).await;
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(started.elapsed() >= TIMEOUT * 2);
+        assert!(service.pending.is_empty());
+        assert_eq!(service.sent, vec![7, 7]);
+    }
+
+    #[test]
+    fn test_late_response() {
+        let peer = Id::random();
+        let mut pending = PendingCalls::new(TIMEOUT);
+        let now = Instant::now();
+        pending.insert(1, peer, "answered", false, now);
+        pending.insert(2, peer, "silent", false, now);
+        assert_eq!(pending.next_deadline(), Some(now + TIMEOUT));
+
+        assert_eq!(pending.remove(1), Some("answered"));
+        assert!(pending.expire(now + TIMEOUT / 2).is_empty());
+
+        let expired = pending.expire(now + TIMEOUT);
+        assert!(matches!(expired.as_slice(), [Expired::Timeout(2, "silent")]));
+        assert!(pending.is_empty());
+        assert_eq!(pending.next_deadline(), None);
+
+        // The response after the deadline is recognized and dropped
+        assert_eq!(pending.remove(2), None);
+        assert!(pending.is_timed_out(2));
+        assert!(!pending.is_timed_out(1));
+        assert!(!pending.is_timed_out(3));
+        assert!(matches!(pending.take(2, &peer), Answered::Late));
+        assert!(matches!(pending.take(3, &peer), Answered::Unknown));
+    }
+
+    #[test]
+    fn test_timed_out_ids_bounded() {
+        let mut pending = PendingCalls::new(TIMEOUT);
+        let now = Instant::now();
+        for id in 0..1000 {
+            pending.insert(id, Id::random(), (), false, now);
+        }
+        assert_eq!(pending.expire(now + TIMEOUT).len(), 1000);
+        assert!(pending.is_timed_out(999));
+        assert!(!pending.is_timed_out(0));
+    }
+
+    #[test]
+    fn test_spoofed_response() {
+        let mut service = SilentService::new();
+        let mut rx = service.call(1, false);
+
+        // Another contact guessing the id leaves the call pending.
+        service.respond(1, &Id::random());
+        assert_eq!(service.spoofed, 1);
+        assert_eq!(service.pending.len(), 1);
+        assert!(matches!(rx.try_recv(), Err(oneshot::error::TryRecvError::Empty)));
+
+        let peer = service.peer;
+        service.respond(1, &peer);
+        assert_eq!(service.spoofed, 1);
+        assert!(service.pending.is_empty());
+        assert!(matches!(rx.try_recv(), Ok(Ok(()))));
+    }
+
+    #[test]
+    fn test_request_ids() {
+        // Sessions start apart, so the ids of one do not tell the other's.
+        let starts = (0..8).map(|_| RequestIds::new().next_id()).collect::<std::collections::HashSet<_>>();
+        assert!(starts.len() > 1);
+
+        let mut ids = RequestIds::starting_at(u32::MAX - 1);
+        assert_eq!(ids.next_id(), u32::MAX);
+        assert_eq!(ids.next_id(), 0);
+        assert_eq!(ids.next_id(), 1);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{
+    fs,
+    path::Path,
+};
+use diesel::prelude::*;
+
+use crate::{
+    Id,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    Error,
+    channel::Permission,
+    contact::ContactType,
+    message::MessageType,
+    persistence::database::{
+        Database,
+        ChannelRecord,
+        ContactRecord,
+        MessageRecord,
+    },
+    self_sync::{Draft, ReadState},
+};
+use super::test_utils::{new_repo_dir, make_contact};
+
+const ACCESS_TOKEN: &[u8] = b"access-token-0f3c9a2e7d51b8aa";
+const MESSAGE_BODY: &[u8] = b"meet me at the usual place at noon";
+
+fn db_bytes(dir: &Path) -> Vec<u8> {
+    fs::read(dir.join("messaging.db")).unwrap()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn make_channel(session_key: &[u8]) -> ChannelRecord {
+    ChannelRecord {
+        id: Id::random(),
+        owner: Id::random(),
+        name: Some("rust-users".into()),
+        permission: Permission::MemberInvite,
+        session_key: Some(session_key.to_vec()),
+        updated: 1700000000000,
+        key_epoch: 0,
+    }
+}
+
+fn make_message(conversation_id: &Id, created: u64, body: &[u8]) -> MessageRecord {
+    MessageRecord {
+        rid: 0,
+        conversation_id: conversation_id.clone(),
+        sender: Id::random(),
+        message_type: MessageType::ContentMessage,
+        created,
+        body: body.to_vec(),
+        content_type: None,
+    }
+}
+
+fn raw_conn(dir: &Path) -> SqliteConnection {
+    SqliteConnection::establish(&dir.join("messaging.db").to_string_lossy()).unwrap()
+}
+
+// The version 1 layout, where every column was stored in plaintext.
+fn create_plaintext_repo(dir: &Path, channel: &ChannelRecord, msgs: &[MessageRecord]) {
+    fs::create_dir_all(dir).unwrap();
+    let mut conn = raw_conn(dir);
+    for stmt in [
+        "CREATE TABLE config(key TEXT NOT NULL PRIMARY KEY, value BLOB NOT NULL) WITHOUT ROWID",
+        "CREATE TABLE channels(id BLOB NOT NULL PRIMARY KEY, owner BLOB NOT NULL, name TEXT, \
+            permission INTEGER NOT NULL DEFAULT 0, sessionKey BLOB, updated INTEGER NOT NULL DEFAULT 0) WITHOUT ROWID",
+        "CREATE TABLE messages(rid INTEGER PRIMARY KEY AUTOINCREMENT, conversationId BLOB NOT NULL, \
+            sender BLOB NOT NULL, messageType INTEGER NOT NULL DEFAULT 1, created INTEGER NOT NULL DEFAULT 0, \
+            body BLOB NOT NULL)",
+        "PRAGMA user_version = 1",
+    ] {
+        diesel::sql_query(stmt).execute(&mut conn).unwrap();
+    }
+
+    use diesel::sql_types::{Binary, BigInt, Integer, Nullable, Text};
+    diesel::sql_query("INSERT INTO config(key, value) VALUES (?, ?)")
+        .bind::<Text, _>("accessToken")
+        .bind::<Binary, _>(ACCESS_TOKEN)
+        .execute(&mut conn).unwrap();
+    diesel::sql_query("INSERT INTO channels VALUES (?, ?, ?, ?, ?, ?)")
+        .bind::<Binary, _>(channel.id.as_bytes())
+        .bind::<Binary, _>(channel.owner.as_bytes())
+        .bind::<Nullable<Text>, _>(channel.name.as_deref())
+        .bind::<Integer, _>(i32::from(channel.permission))
+        .bind::<Nullable<Binary>, _>(channel.session_key.as_deref())
+        .bind::<BigInt, _>(channel.updated as i64)
+        .execute(&mut conn).unwrap();
+    for msg in msgs {
+        diesel::sql_query("INSERT INTO messages(conversationId, sender, messageType, created, body) VALUES (?, ?, ?, ?, ?)")
+            .bind::<Binary, _>(msg.conversation_id.as_bytes())
+            .bind::<Binary, _>(msg.sender.as_bytes())
+            .bind::<Integer, _>(msg.message_type as i32)
+            .bind::<BigInt, _>(msg.created as i64)
+            .bind::<Binary, _>(msg.body.as_slice())
+            .execute(&mut conn).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let channel = make_channel(&crate::random_bytes(32));
+        let conversation = Id::random();
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_config("accessToken", ACCESS_TOKEN).unwrap();
+            db.put_channel(&channel).unwrap();
+            let rid1 = db.put_message(&make_message(&conversation, 1000, b"first")).unwrap();
+            let rid2 = db.put_message(&make_message(&conversation, 2000, MESSAGE_BODY)).unwrap();
+            assert!(rid2 > rid1);
+        }
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.get_config("accessToken").unwrap(), Some(ACCESS_TOKEN.to_vec()));
+        assert_eq!(db.get_config("missing").unwrap(), None);
+        assert_eq!(db.channel(&channel.id).unwrap(), Some(channel.clone()));
+        assert_eq!(db.channels().unwrap(), vec![channel]);
+
+        let msgs = db.messages_since(&conversation, 1500, 10, 0).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].body, MESSAGE_BODY);
+        assert_eq!(db.messages_since(&conversation, 0, 10, 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_no_plaintext_on_disk() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let session_key = crate::random_bytes(32);
+        let conversation = Id::random();
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_config("accessToken", ACCESS_TOKEN).unwrap();
+            db.put_channel(&make_channel(&session_key)).unwrap();
+            db.put_message(&make_message(&conversation, 1000, MESSAGE_BODY)).unwrap();
+        }
+
+        let bytes = db_bytes(&dir);
+        assert!(contains(&bytes, conversation.as_bytes()));
+        assert!(!contains(&bytes, ACCESS_TOKEN));
+        assert!(!contains(&bytes, &session_key));
+        assert!(!contains(&bytes, MESSAGE_BODY));
+    }
+
+    #[test]
+    fn test_migrate_plaintext_repo() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let session_key = crate::random_bytes(32);
+        let channel = make_channel(&session_key);
+        let conversation = Id::random();
+        let msgs = vec![
+            make_message(&conversation, 1000, b"hello"),
+            make_message(&conversation, 2000, MESSAGE_BODY),
+        ];
+
+        create_plaintext_repo(&dir, &channel, &msgs);
+        let bytes = db_bytes(&dir);
+        assert!(contains(&bytes, ACCESS_TOKEN));
+        assert!(contains(&bytes, &session_key));
+        assert!(contains(&bytes, MESSAGE_BODY));
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            assert_eq!(db.get_config("accessToken").unwrap(), Some(ACCESS_TOKEN.to_vec()));
+            assert_eq!(db.channels().unwrap(), vec![channel.clone()]);
+
+            let loaded = db.messages_since(&conversation, 0, 10, 0).unwrap();
+            assert_eq!(loaded.len(), msgs.len());
+            for (loaded, msg) in loaded.iter().zip(msgs.iter()) {
+                assert_eq!(loaded.sender, msg.sender);
+                assert_eq!(loaded.created, msg.created);
+                assert_eq!(loaded.body, msg.body);
+            }
+        }
+
+        let bytes = db_bytes(&dir);
+        assert!(!contains(&bytes, ACCESS_TOKEN));
+        assert!(!contains(&bytes, &session_key));
+        assert!(!contains(&bytes, MESSAGE_BODY));
+
+        // Reopening does not encrypt twice
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.channel(&channel.id).unwrap(), Some(channel));
+        assert_eq!(db.messages_since(&conversation, 0, 10, 0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_skip_undecryptable_rows() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let good = make_channel(&crate::random_bytes(32));
+        let bad = make_channel(&crate::random_bytes(32));
+        let conversation = Id::random();
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_config("accessToken", ACCESS_TOKEN).unwrap();
+            db.put_channel(&good).unwrap();
+            db.put_channel(&bad).unwrap();
+            db.put_message(&make_message(&conversation, 1000, b"truncated")).unwrap();
+            db.put_message(&make_message(&conversation, 2000, MESSAGE_BODY)).unwrap();
+        }
+
+        let mut conn = raw_conn(&dir);
+        diesel::sql_query("UPDATE channels SET sessionKey = randomblob(72) WHERE id = ?")
+            .bind::<diesel::sql_types::Binary, _>(bad.id.as_bytes())
+            .execute(&mut conn).unwrap();
+        diesel::sql_query("UPDATE messages SET body = x'00' WHERE created = 1000")
+            .execute(&mut conn).unwrap();
+        drop(conn);
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.channels().unwrap(), vec![good.clone()]);
+        assert_eq!(db.channel(&bad.id).unwrap(), None);
+
+        let msgs = db.messages_since(&conversation, 0, 10, 0).unwrap();
+        assert_eq!(msgs.len(), 1);
+        assert_eq!(msgs[0].body, MESSAGE_BODY);
+        drop(db);
+
+        // Another device key cannot read anything, but loading still succeeds
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        assert_eq!(db.get_config("accessToken").unwrap(), None);
+        assert!(db.channels().unwrap().is_empty());
+        assert!(db.messages_since(&conversation, 0, 10, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_blocked_contacts() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let friend = make_contact(ContactType::Friend, Some("bob"));
+        let mut blocked = ContactRecord {
+            blocked: true,
+            ..make_contact(ContactType::Friend, Some("bob"))
+        };
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_contact(&friend).unwrap();
+            db.put_contact(&blocked).unwrap();
+        }
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.contact(&blocked.id).unwrap(), Some(blocked.clone()));
+        assert_eq!(db.blocked_contacts().unwrap(), vec![blocked.id]);
+
+        // Unblocking keeps the contact.
+        blocked.blocked = false;
+        db.put_contact(&blocked).unwrap();
+        assert!(db.blocked_contacts().unwrap().is_empty());
+        assert_eq!(db.contacts().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_migrate_v3_contacts() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let contact = make_contact(ContactType::Friend, Some("bob"));
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_contact(&contact).unwrap();
+        }
+
+        // Back to the version 3 layout, without the blocked column.
+        let mut conn = raw_conn(&dir);
+        for stmt in [
+            "ALTER TABLE contacts DROP COLUMN blocked",
+            "ALTER TABLE messages DROP COLUMN contentType",
+            "DROP TABLE messages_fts",
+            "PRAGMA user_version = 3",
+        ] {
+            diesel::sql_query(stmt).execute(&mut conn).unwrap();
+        }
+        drop(conn);
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.contact(&contact.id).unwrap(), Some(contact.clone()));
+        db.put_contact(&ContactRecord { blocked: true, ..contact.clone() }).unwrap();
+        assert_eq!(db.blocked_contacts().unwrap(), vec![contact.id]);
+    }
+
+    #[test]
+    fn test_newer_version_refused() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let contact = make_contact(ContactType::Friend, Some("bob"));
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_contact(&contact).unwrap();
+        }
+        diesel::sql_query("PRAGMA user_version = 8").execute(&mut raw_conn(&dir)).unwrap();
+
+        let err = Database::open(&dir, device.private_key()).err().unwrap();
+        assert!(matches!(err, Error::UnsupportedVersion(8)), "{err}");
+
+        // Untouched, a build that knows the version still reads it.
+        diesel::sql_query("PRAGMA user_version = 7").execute(&mut raw_conn(&dir)).unwrap();
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.contact(&contact.id).unwrap(), Some(contact));
+    }
+
+    #[test]
+    fn test_read_states_and_drafts() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        let conversation = Id::random();
+        let draft = Draft {
+            conversation,
+            text: String::from_utf8(MESSAGE_BODY.to_vec()).unwrap(),
+            updated: 1700000000000,
+        };
+
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            db.put_read_states(&[ReadState::new(conversation, 1700000000000, 1)]).unwrap();
+            db.put_read_states(&[ReadState::new(conversation, 1700000005000, 2)]).unwrap();
+            db.put_drafts(&[draft.clone()]).unwrap();
+        }
+        assert!(!contains(&db_bytes(&dir), MESSAGE_BODY));
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.read_states().unwrap(), vec![ReadState::new(conversation, 1700000005000, 2)]);
+        assert_eq!(db.drafts().unwrap(), vec![draft]);
+    }
+
+    #[test]
+    fn test_inbox_cursor() {
+        let dir = new_repo_dir();
+        let device = KeyPair::random();
+        {
+            let db = Database::open(&dir, device.private_key()).unwrap();
+            assert_eq!(db.inbox_cursor().unwrap(), None);
+            db.put_inbox_cursor(41).unwrap();
+            db.put_inbox_cursor(42).unwrap();
+        }
+
+        let db = Database::open(&dir, device.private_key()).unwrap();
+        assert_eq!(db.inbox_cursor().unwrap(), Some(42));
+    }
+}
@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use crate::Id;
+use crate::messaging::presence::{
+    self,
+    Presence,
+    PresenceCache,
+    PresenceState,
+};
+
+const TIMEOUT: Duration = Duration::from_secs(180);
+const T0: u64 = 1_700_000_000_000;
+
+fn online(at: u64) -> Presence {
+    Presence::new(PresenceState::Online, at)
+}
+
+fn offline(at: u64) -> Presence {
+    Presence::new(PresenceState::Offline, at)
+}
+
+fn tracking(contacts: &[Id]) -> PresenceCache {
+    let mut cache = PresenceCache::new(TIMEOUT);
+    cache.track(contacts);
+    cache
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wire_format() {
+        let presence = online(T0);
+        let decoded = Presence::from_bytes(&presence.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded, presence);
+        assert!(decoded.is_online());
+        assert!(Presence::from_bytes(b"\xff\x00").is_err());
+
+        let id = Id::random();
+        let topic = presence::presence_topic(&id);
+        assert_eq!(presence::presence_topic_owner(&topic), Some(id));
+        assert_eq!(presence::presence_topic_owner("inbox/abc"), None);
+        assert_eq!(presence::presence_topic_owner("presence/not-an-id"), None);
+    }
+
+    #[test]
+    fn test_track_contacts() {
+        let alice = Id::random();
+        let bob = Id::random();
+        let carol = Id::random();
+
+        let mut cache = PresenceCache::new(TIMEOUT);
+        let (added, removed) = cache.track(&[alice.clone(), bob.clone()]);
+        assert_eq!(added.len(), 2);
+        assert!(removed.is_empty());
+
+        // Strangers are never cached
+        assert!(cache.update(&carol, online(T0), T0).is_none());
+        assert!(cache.get(&carol, T0).is_none());
+
+        assert!(cache.update(&bob, online(T0), T0).is_some());
+        let (added, removed) = cache.track(&[alice.clone(), carol.clone()]);
+        assert_eq!(added, vec![carol]);
+        assert_eq!(removed, vec![bob.clone()]);
+        assert!(cache.get(&bob, T0).is_none());
+        assert!(!cache.is_tracked(&bob));
+    }
+
+    #[test]
+    fn test_state_changes() {
+        let alice = Id::random();
+        let mut cache = tracking(&[alice.clone()]);
+        assert!(cache.get(&alice, T0).is_none());
+
+        assert_eq!(cache.update(&alice, online(T0), T0), Some(online(T0)));
+
+        // Heartbeats refresh last seen without reporting a change
+        assert!(cache.update(&alice, online(T0 + 60_000), T0 + 60_000).is_none());
+        assert_eq!(cache.get(&alice, T0 + 60_000), Some(online(T0 + 60_000)));
+
+        // The last will carries the connect time, last seen is kept
+        let changed = cache.update(&alice, offline(T0), T0 + 90_000).unwrap();
+        assert_eq!(changed, offline(T0 + 60_000));
+        assert_eq!(cache.get(&alice, T0 + 90_000).unwrap().state(), PresenceState::Offline);
+    }
+
+    #[test]
+    fn test_stale_degrades_to_away() {
+        let alice = Id::random();
+        let bob = Id::random();
+        let mut cache = tracking(&[alice.clone(), bob.clone()]);
+        cache.update(&alice, online(T0), T0);
+        cache.update(&bob, offline(T0), T0);
+
+        let timeout = TIMEOUT.as_millis() as u64;
+        assert!(cache.expire(T0 + timeout).is_empty());
+        assert!(cache.get(&alice, T0 + timeout).unwrap().is_online());
+
+        // The offline notification got lost, alice is shown away
+        let now = T0 + timeout + 1;
+        assert_eq!(cache.get(&alice, now).unwrap().state(), PresenceState::Away);
+        let changed = cache.expire(now);
+        assert_eq!(changed, vec![(alice.clone(), Presence::new(PresenceState::Away, T0))]);
+        assert!(cache.expire(now + 1).is_empty());
+        assert_eq!(cache.get(&bob, now).unwrap().state(), PresenceState::Offline);
+
+        // A later heartbeat brings her back online
+        assert_eq!(cache.update(&alice, online(now), now), Some(online(now)));
+    }
+}
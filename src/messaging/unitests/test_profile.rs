@@ -0,0 +1,101 @@
+use base64::{engine::general_purpose, Engine as _};
+use serde_json::json;
+
+use crate::{Id, Identity, CryptoIdentity};
+use crate::messaging::{
+    Error,
+    Result,
+    profile::{self, Profile, ProfileStore},
+};
+
+// The profiles of the service, kept in memory.
+#[derive(Default)]
+struct MockStore {
+    profile : Option<Profile>,
+    fetches : usize,
+    down    : bool,
+}
+
+impl ProfileStore for MockStore {
+    async fn fetch_profile(&mut self, id: &Id) -> Result<Option<Profile>> {
+        self.fetches += 1;
+        if self.down {
+            return Err(Error::State("connection refused".into()));
+        }
+        Ok(self.profile.clone().filter(|p| p.id() == id))
+    }
+}
+
+// The profile as the service returns it, signed by `signer`.
+fn profile(user: &Id, signer: &CryptoIdentity, name: &str, avatar: bool) -> Profile {
+    let peerid = Id::random();
+    let digest = profile::digest(user, &peerid, Some(name), avatar, None);
+    let sig = signer.sign_into(&digest).unwrap();
+    serde_json::from_value(json!({
+        "id": user.to_base58(),
+        "p" : peerid.to_base58(),
+        "ps": general_purpose::URL_SAFE_NO_PAD.encode([0u8; 64]),
+        "n" : name,
+        "a" : avatar,
+        "s" : general_purpose::URL_SAFE_NO_PAD.encode(sig),
+    })).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_present() {
+        let user = CryptoIdentity::new();
+        let mut store = MockStore {
+            profile: Some(profile(user.id(), &user, "Alice", true)),
+            ..Default::default()
+        };
+        assert!(store.profile.as_ref().unwrap().is_genuine());
+
+        let acquired = profile::acquire(&mut store, &user).await.unwrap();
+        assert_eq!(acquired.id(), user.id());
+        assert_eq!(acquired.name(), "Alice");
+        assert!(acquired.has_avatar());
+        assert_eq!(store.fetches, 1);
+    }
+
+    #[tokio::test]
+    async fn test_acquire_absent() {
+        let user = CryptoIdentity::new();
+        let mut store = MockStore::default();
+
+        // Never set, a default profile of the user still comes back
+        let acquired = profile::acquire(&mut store, &user).await.unwrap();
+        assert_eq!(acquired.id(), user.id());
+        assert_eq!(acquired.name(), "");
+        assert!(!acquired.has_avatar());
+
+        store.down = true;
+        assert!(matches!(profile::acquire(&mut store, &user).await, Err(Error::State(_))));
+    }
+
+    #[tokio::test]
+    async fn test_acquire_invalid_signature() {
+        let user = CryptoIdentity::new();
+        let mut store = MockStore {
+            profile: Some(profile(user.id(), &CryptoIdentity::new(), "Mallory", false)),
+            ..Default::default()
+        };
+        assert!(!store.profile.as_ref().unwrap().is_genuine());
+        assert!(matches!(profile::acquire(&mut store, &user).await, Err(Error::Auth(_))));
+
+        // Signed by the user, but not over the name the service returns
+        let genuine = profile(user.id(), &user, "Alice", false);
+        let forged = json!({
+            "id": user.id().to_base58(),
+            "p" : genuine.home_peerid().to_base58(),
+            "ps": general_purpose::URL_SAFE_NO_PAD.encode(genuine.home_peer_sig()),
+            "n" : "Mallory",
+            "s" : general_purpose::URL_SAFE_NO_PAD.encode(genuine.sig()),
+        });
+        store.profile = Some(serde_json::from_value(forged).unwrap());
+        assert!(matches!(profile::acquire(&mut store, &user).await, Err(Error::Auth(_))));
+    }
+}
@@ -0,0 +1,229 @@
+use std::time::{Duration, Instant};
+
+use crate::Id;
+use crate::messaging::rate_limit::{
+    Admission,
+    InboundLimiter,
+    InboundRateLimit,
+    Origin,
+};
+
+// The inbox of the client worker: classifies every message from its envelope
+// and keeps only the admitted ones.
+struct Inbox {
+    me: Id,
+    peer: Id,
+    limiter: InboundLimiter,
+    accepted: Vec<Id>,
+    muted: Vec<(Id, Id, Duration)>,
+}
+
+impl Inbox {
+    fn new(limits: InboundRateLimit) -> Self {
+        Self {
+            me: Id::random(),
+            peer: Id::random(),
+            limiter: InboundLimiter::new(limits),
+            accepted: Vec::new(),
+            muted: Vec::new(),
+        }
+    }
+
+    fn receive(&mut self, from: &Id, to: &Id, is_call: bool, now: Instant) {
+        let origin = Origin::of(from, to, &self.me, &self.peer, is_call);
+        match self.limiter.admit(&origin, now) {
+            Admission::Accept => self.accepted.push(*from),
+            Admission::Drop => {},
+            Admission::Mute(duration) => {
+                if let Origin::Sender { conversation, sender } = origin {
+                    self.muted.push((conversation, sender, duration));
+                }
+            },
+        }
+    }
+
+    fn direct(&mut self, from: &Id, count: usize, now: Instant) {
+        let me = self.me;
+        (0..count).for_each(|_| self.receive(from, &me, false, now));
+    }
+
+    fn accepted_from(&self, from: &Id) -> usize {
+        self.accepted.iter().filter(|v| *v == from).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_burst_dropped_and_reported_once() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 10));
+        let flooder = Id::random();
+        let friend = Id::random();
+        let now = Instant::now();
+
+        inbox.direct(&flooder, 100, now);
+        inbox.direct(&friend, 5, now);
+        assert_eq!(inbox.accepted_from(&flooder), 10);
+        assert_eq!(inbox.accepted_from(&friend), 5);
+        assert_eq!(inbox.limiter.dropped(), 90);
+
+        let suppressed = inbox.limiter.take_suppressed(now);
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].conversation, flooder);
+        assert_eq!(suppressed[0].sender, flooder);
+        assert_eq!(suppressed[0].count, 90);
+
+        // Reported once, the total is kept
+        assert!(inbox.limiter.take_suppressed(now).is_empty());
+        assert_eq!(inbox.limiter.dropped(), 90);
+        assert!(inbox.muted.is_empty());
+    }
+
+    #[test]
+    fn test_service_never_limited() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 1));
+        let flooder = Id::random();
+        let peer = inbox.peer;
+        let me = inbox.me;
+        let now = Instant::now();
+
+        for _ in 0..50 {
+            inbox.receive(&flooder, &me, false, now);
+            inbox.receive(&peer, &me, false, now);
+            // RPC responses are relayed by the service on behalf of the sender
+            inbox.receive(&flooder, &me, true, now);
+        }
+        assert_eq!(inbox.accepted_from(&peer), 50);
+        assert_eq!(inbox.accepted_from(&flooder), 51);
+        assert_eq!(inbox.limiter.dropped(), 49);
+        assert_eq!(inbox.limiter.senders(), 1);
+    }
+
+    #[test]
+    fn test_channel_limited_per_sender() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 3));
+        let channel = Id::random();
+        let flooder = Id::random();
+        let member = Id::random();
+        let now = Instant::now();
+
+        (0..10).for_each(|_| inbox.receive(&flooder, &channel, false, now));
+        (0..3).for_each(|_| inbox.receive(&member, &channel, false, now));
+        // The direct messages of the flooder have a bucket of their own
+        inbox.direct(&flooder, 3, now);
+
+        assert_eq!(inbox.accepted_from(&flooder), 6);
+        assert_eq!(inbox.accepted_from(&member), 3);
+
+        let suppressed = inbox.limiter.take_suppressed(now);
+        assert_eq!(suppressed.len(), 1);
+        assert_eq!(suppressed[0].conversation, channel);
+        assert_eq!(suppressed[0].sender, flooder);
+        assert_eq!(suppressed[0].count, 7);
+    }
+
+    #[test]
+    fn test_refilled_over_time() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(10.0, 5));
+        let sender = Id::random();
+        let now = Instant::now();
+
+        inbox.direct(&sender, 10, now);
+        assert_eq!(inbox.accepted_from(&sender), 5);
+
+        inbox.direct(&sender, 10, now + Duration::from_millis(300));
+        assert_eq!(inbox.accepted_from(&sender), 8);
+
+        // Never above the burst however long the sender was quiet
+        inbox.direct(&sender, 10, now + Duration::from_secs(60));
+        assert_eq!(inbox.accepted_from(&sender), 13);
+        assert!(inbox.muted.is_empty());
+    }
+
+    #[test]
+    fn test_muted_after_threshold() {
+        let mute = Duration::from_secs(60);
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 2).with_mute(5, mute));
+        let sender = Id::random();
+        let now = Instant::now();
+
+        inbox.direct(&sender, 7, now);
+        assert_eq!(inbox.accepted_from(&sender), 2);
+        assert_eq!(inbox.muted, vec![(sender, sender, mute)]);
+        assert!(inbox.limiter.is_muted(&sender, &sender, now));
+
+        // Dropped while muted even with tokens back, the mute told only once
+        inbox.direct(&sender, 20, now + Duration::from_secs(30));
+        assert_eq!(inbox.accepted_from(&sender), 2);
+        assert_eq!(inbox.muted.len(), 1);
+        assert_eq!(inbox.limiter.dropped(), 25);
+
+        // Kept through the sweeps until the mute expires
+        assert_eq!(inbox.limiter.take_suppressed(now + Duration::from_secs(59))[0].count, 25);
+        assert_eq!(inbox.limiter.senders(), 1);
+
+        let later = now + mute;
+        assert!(!inbox.limiter.is_muted(&sender, &sender, later));
+        inbox.direct(&sender, 1, later);
+        assert_eq!(inbox.accepted_from(&sender), 3);
+    }
+
+    #[test]
+    fn test_unmute() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 1).with_mute(1, Duration::from_secs(600)));
+        let sender = Id::random();
+        let now = Instant::now();
+
+        inbox.direct(&sender, 2, now);
+        assert_eq!(inbox.muted.len(), 1);
+
+        inbox.limiter.unmute(&sender, &sender);
+        assert!(!inbox.limiter.is_muted(&sender, &sender, now));
+        inbox.direct(&sender, 1, now + Duration::from_secs(1));
+        assert_eq!(inbox.accepted_from(&sender), 2);
+    }
+
+    #[test]
+    fn test_never_muted() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(1.0, 1).with_mute(0, Duration::from_secs(600)));
+        let sender = Id::random();
+        let now = Instant::now();
+
+        inbox.direct(&sender, 1000, now);
+        assert!(inbox.muted.is_empty());
+        assert!(!inbox.limiter.is_muted(&sender, &sender, now));
+    }
+
+    #[test]
+    fn test_quiet_senders_forgotten() {
+        let mut inbox = Inbox::new(InboundRateLimit::new(10.0, 10));
+        let now = Instant::now();
+        for _ in 0..100 {
+            inbox.direct(&Id::random(), 1, now);
+        }
+        assert_eq!(inbox.limiter.senders(), 100);
+        assert!(inbox.limiter.take_suppressed(now).is_empty());
+        assert_eq!(inbox.limiter.senders(), 100);
+
+        assert!(inbox.limiter.take_suppressed(now + Duration::from_secs(1)).is_empty());
+        assert_eq!(inbox.limiter.senders(), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = InboundRateLimit::default();
+        assert_eq!(limits.rate(), InboundRateLimit::DEFAULT_RATE);
+        assert_eq!(limits.burst(), InboundRateLimit::DEFAULT_BURST);
+        assert_eq!(limits.mute_after(), InboundRateLimit::DEFAULT_MUTE_AFTER);
+        assert_eq!(limits.mute_duration(), InboundRateLimit::DEFAULT_MUTE_DURATION);
+
+        let limits = InboundRateLimit::new(0.0, 0);
+        assert_eq!(limits.rate(), InboundRateLimit::DEFAULT_RATE);
+        assert_eq!(limits.burst(), 1);
+        assert_eq!(InboundRateLimit::new(f64::NAN, 5).rate(), InboundRateLimit::DEFAULT_RATE);
+        assert_eq!(InboundRateLimit::new(-1.0, 5).rate(), InboundRateLimit::DEFAULT_RATE);
+        assert_eq!(InboundRateLimit::new(0.5, 5).rate(), 0.5);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    fs,
+    path::PathBuf,
+};
+
+use crate::{
+    Id,
+    CryptoContext,
+    CryptoIdentity,
+    Identity,
+    signature::KeyPair,
+};
+use crate::messaging::{
+    persistence::database::Database,
+    self_sync::{ReadState, SelfSync, SyncMessage},
+};
+
+// One of the devices of the user, with the worker applying what arrives on
+// the inbox to its repository.
+struct Device {
+    sync    : SelfSync,
+    context : CryptoContext,
+    db      : Database,
+    dir     : PathBuf,
+    online  : bool,
+    synced  : Vec<ReadState>,
+}
+
+impl Device {
+    fn new(user: &CryptoIdentity) -> Self {
+        let dir = PathBuf::from(format!("/tmp/tss_{:016x}", rand::random::<u64>()));
+        let db = Database::open(&dir, KeyPair::random().private_key()).unwrap();
+        Self {
+            sync    : SelfSync::new(Id::random(), vec![], vec![], vec![]),
+            context : user.create_crypto_context(user.id()).unwrap(),
+            db,
+            dir,
+            online  : true,
+            synced  : Vec::new(),
+        }
+    }
+
+    fn publish(&mut self, msg: Option<SyncMessage>, wire: &mut Vec<Vec<u8>>) {
+        if let Some(msg) = msg {
+            wire.push(msg.seal(&mut self.context).unwrap());
+        }
+    }
+
+    fn mark_read(&mut self, conversation: &Id, last_read: u64, now: u64, wire: &mut Vec<Vec<u8>>) {
+        let msg = self.sync.mark_read(conversation, last_read, now);
+        if let Some(msg) = msg.as_ref() {
+            self.db.put_read_states(&msg.read_states).unwrap();
+        }
+        self.publish(msg, wire);
+    }
+
+    fn save_draft(&mut self, conversation: &Id, text: &str, now: u64, wire: &mut Vec<Vec<u8>>) {
+        let msg = self.sync.save_draft(conversation, text, now);
+        if let Some(msg) = msg.as_ref() {
+            self.db.put_drafts(&msg.drafts).unwrap();
+        }
+        self.publish(msg, wire);
+    }
+
+    fn connect(&mut self, wire: &mut Vec<Vec<u8>>) {
+        self.online = true;
+        let request = self.sync.request();
+        self.publish(Some(request), wire);
+    }
+
+    // Returns whether the message came from another device.
+    fn receive(&mut self, packet: &[u8], wire: &mut Vec<Vec<u8>>) -> bool {
+        let msg = SyncMessage::open(&self.context, packet).unwrap();
+        let Some(applied) = self.sync.apply(&msg) else {
+            return false;
+        };
+
+        self.db.put_read_states(&applied.read_states).unwrap();
+        self.db.put_drafts(&applied.drafts).unwrap();
+        self.synced.extend(applied.read_states);
+        self.publish(applied.reply, wire);
+        true
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// The broker hands every packet to every online device, the sender included.
+fn deliver(wire: &mut Vec<Vec<u8>>, devices: &mut [&mut Device]) -> usize {
+    let mut echoes = 0;
+    while !wire.is_empty() {
+        let packets = std::mem::take(wire);
+        for packet in packets.iter() {
+            for device in devices.iter_mut().filter(|d| d.online) {
+                if !device.receive(packet, wire) {
+                    echoes += 1;
+                }
+            }
+        }
+    }
+    echoes
+}
+
+fn sorted(mut states: Vec<ReadState>) -> Vec<ReadState> {
+    states.sort_by_key(|s| *s.conversation_id());
+    states
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interleaved_updates_converge() {
+        let user = CryptoIdentity::from(KeyPair::random());
+        let mut phone = Device::new(&user);
+        let mut laptop = Device::new(&user);
+        let alice = Id::random();
+        let bob = Id::random();
+        let mut wire = Vec::new();
+
+        // Both read alice before hearing of each other, the laptop last.
+        phone.mark_read(&alice, 1_000, 10, &mut wire);
+        laptop.mark_read(&alice, 900, 20, &mut wire);
+        laptop.mark_read(&bob, 2_000, 30, &mut wire);
+        phone.mark_read(&bob, 2_500, 40, &mut wire);
+        phone.save_draft(&bob, "see you", 50, &mut wire);
+
+        // Delivered in reverse, the newest first.
+        wire.reverse();
+        let echoes = deliver(&mut wire, &mut [&mut phone, &mut laptop]);
+        assert_eq!(echoes, 5);
+
+        for device in [&phone, &laptop] {
+            assert_eq!(device.sync.read_state(&alice).unwrap().last_read(), 900);
+            assert_eq!(device.sync.read_state(&bob).unwrap().last_read(), 2_500);
+            assert_eq!(device.sync.draft(&bob).as_deref(), Some("see you"));
+        }
+        assert_eq!(sorted(phone.db.read_states().unwrap()), sorted(laptop.db.read_states().unwrap()));
+        assert_eq!(phone.db.drafts().unwrap(), laptop.db.drafts().unwrap());
+
+        // Only the updates winning over the local state were reported.
+        assert_eq!(phone.synced, vec![ReadState::new(alice, 900, 20)]);
+        assert_eq!(laptop.synced, vec![ReadState::new(bob, 2_500, 40)]);
+    }
+
+    #[test]
+    fn test_echoes_ignored() {
+        let user = CryptoIdentity::from(KeyPair::random());
+        let mut phone = Device::new(&user);
+        let conversation = Id::random();
+        let mut wire = Vec::new();
+
+        phone.mark_read(&conversation, 1_000, 10, &mut wire);
+        // Reading back to an older message sends nothing.
+        phone.mark_read(&conversation, 500, 20, &mut wire);
+        assert_eq!(wire.len(), 1);
+
+        assert_eq!(deliver(&mut wire, &mut [&mut phone]), 1);
+        assert!(wire.is_empty());
+        assert!(phone.synced.is_empty());
+        assert_eq!(phone.sync.read_state(&conversation).unwrap().last_read(), 1_000);
+    }
+
+    #[test]
+    fn test_reconcile_on_connect() {
+        let user = CryptoIdentity::from(KeyPair::random());
+        let mut phone = Device::new(&user);
+        let mut laptop = Device::new(&user);
+        let alice = Id::random();
+        let bob = Id::random();
+        let mut wire = Vec::new();
+
+        // The laptop misses what the phone did, and the other way round.
+        laptop.online = false;
+        phone.mark_read(&alice, 1_000, 10, &mut wire);
+        phone.save_draft(&alice, "on my way", 20, &mut wire);
+        deliver(&mut wire, &mut [&mut phone, &mut laptop]);
+        laptop.mark_read(&bob, 3_000, 30, &mut wire);
+        wire.clear();
+        assert!(laptop.sync.read_state(&alice).is_none());
+
+        laptop.connect(&mut wire);
+        deliver(&mut wire, &mut [&mut phone, &mut laptop]);
+
+        assert_eq!(laptop.synced, vec![ReadState::new(alice, 1_000, 10)]);
+        assert_eq!(laptop.sync.draft(&alice).as_deref(), Some("on my way"));
+        assert_eq!(phone.synced, vec![ReadState::new(bob, 3_000, 30)]);
+
+        // Applied to the repository of the laptop as well.
+        let states = sorted(laptop.db.read_states().unwrap());
+        assert_eq!(states, sorted(vec![
+            ReadState::new(alice, 1_000, 10),
+            ReadState::new(bob, 3_000, 30),
+        ]));
+
+        // Clearing the draft on the laptop clears it on the phone.
+        laptop.save_draft(&alice, "", 40, &mut wire);
+        deliver(&mut wire, &mut [&mut phone, &mut laptop]);
+        assert!(phone.sync.draft(&alice).is_none());
+    }
+
+    #[test]
+    fn test_other_user_cannot_open() {
+        let user = CryptoIdentity::from(KeyPair::random());
+        let other = CryptoIdentity::from(KeyPair::random());
+        let mut phone = Device::new(&user);
+        let mut wire = Vec::new();
+        phone.mark_read(&Id::random(), 1_000, 10, &mut wire);
+
+        let context = other.create_crypto_context(other.id()).unwrap();
+        assert!(SyncMessage::open(&context, &wire[0]).is_err());
+    }
+}
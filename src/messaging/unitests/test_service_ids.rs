@@ -0,0 +1,22 @@
+use crate::{
+    Id,
+    Value,
+    SignedBuilder,
+    messaging::ServiceIds,
+};
+
+#[test]
+fn test_value_id() {
+    let peerid = Id::try_from("0x000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+    let ids = ServiceIds::new(peerid, Id::random());
+
+    // Fixed like the derivation itself.
+    assert_eq!(ids.value_id("profile").to_hexstr(),
+        "0x5b254d6f0c5c8e35ba291be6e163e05aff996d908e19f3de26e45181e52d612b");
+    assert_eq!(ids.value_id("profile"), Value::id_for_key(&peerid, "profile"));
+    assert_ne!(ids.value_id("profile"), ids.value_id("other"));
+
+    let value = SignedBuilder::world_writable(&peerid, "profile", b"data").build().unwrap();
+    assert_eq!(value.id(), ids.value_id("profile"));
+    assert_ne!(ServiceIds::new(Id::random(), *ids.nodeid()).value_id("profile"), value.id());
+}
@@ -0,0 +1,201 @@
+use std::collections::LinkedList;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::{
+    Id,
+    messaging::{
+        Error,
+        Result,
+        pending_calls::PendingCalls,
+        shutdown::{self, Shutdown},
+    },
+};
+
+type Reply = oneshot::Sender<Result<()>>;
+
+const GRACE: Duration = Duration::from_millis(500);
+
+// A worker the way the client runs it, against a service that never answers:
+// it sends the queued requests until a stop is requested, then fails what is
+// left and tells about the disconnection.
+struct Worker {
+    shutdown: Arc<Shutdown>,
+    queue: Arc<Mutex<LinkedList<Reply>>>,
+    pending: PendingCalls<Reply>,
+    next_id: u32,
+    disconnected: Arc<AtomicUsize>,
+}
+
+impl Worker {
+    async fn run(mut self) {
+        let mut ticker = tokio::time::interval(Duration::from_millis(10));
+        loop {
+            tokio::select! {
+                _ = self.shutdown.requested() => break,
+                _ = ticker.tick() => self.send_queued(),
+            }
+        }
+
+        shutdown::fail_pending(&self.queue, &mut self.pending, |reply| {
+            _ = reply.send(Err(Error::Shutdown));
+        });
+        if self.shutdown.take_disconnected() {
+            self.disconnected.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn send_queued(&mut self) {
+        while let Some(reply) = crate::locked!(self.queue).pop_front() {
+            self.next_id += 1;
+            self.pending.insert(self.next_id, Id::random(), reply, false, Instant::now());
+        }
+    }
+}
+
+struct Client {
+    shutdown: Arc<Shutdown>,
+    queue: Arc<Mutex<LinkedList<Reply>>>,
+    disconnected: Arc<AtomicUsize>,
+    worker: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Client {
+    fn start() -> Self {
+        let shutdown = Arc::new(Shutdown::new());
+        let queue = Arc::new(Mutex::new(LinkedList::new()));
+        let disconnected = Arc::new(AtomicUsize::new(0));
+        let worker = Worker {
+            shutdown: shutdown.clone(),
+            queue: queue.clone(),
+            // Long enough that no request times out during the test.
+            pending: PendingCalls::new(Duration::from_secs(60)),
+            next_id: 0,
+            disconnected: disconnected.clone(),
+        };
+        Self {
+            shutdown,
+            queue,
+            disconnected,
+            worker: Some(tokio::spawn(worker.run())),
+        }
+    }
+
+    fn call(&self) -> Result<oneshot::Receiver<Result<()>>> {
+        let (tx, rx) = oneshot::channel();
+        self.shutdown.enqueue(&self.queue, tx)?;
+        Ok(rx)
+    }
+
+    async fn stop(&mut self, forced: bool) -> bool {
+        self.shutdown.begin(forced);
+        let joined = match self.worker.take() {
+            Some(task) => shutdown::join_worker(task, GRACE).await,
+            None => true,
+        };
+        if self.shutdown.take_disconnected() {
+            self.disconnected.fetch_add(1, Ordering::SeqCst);
+        }
+        self.shutdown.finish();
+        joined
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_fails_awaiting_call() {
+        let mut client = Client::start();
+        let rx = client.call().unwrap();
+        let queued = client.call().unwrap();
+
+        // Awaited in another task while the worker holds it as pending.
+        let caller = tokio::spawn(async move { rx.await.unwrap() });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let started = Instant::now();
+        let (result, joined) = tokio::join!(caller, client.stop(false));
+        assert!(matches!(result.unwrap(), Err(Error::Shutdown)));
+        assert!(matches!(queued.await.unwrap(), Err(Error::Shutdown)));
+        assert!(joined);
+        assert!(started.elapsed() < GRACE, "{:?}", started.elapsed());
+
+        assert!(client.shutdown.is_stopped());
+        assert_eq!(client.disconnected.load(Ordering::SeqCst), 1);
+
+        // No new operations once stopped.
+        assert!(matches!(client.call(), Err(Error::State(_))));
+    }
+
+    #[tokio::test]
+    async fn test_forced_stop() {
+        let mut client = Client::start();
+        let rx = client.call().unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(client.stop(true).await);
+        assert!(client.shutdown.is_forced());
+        assert!(matches!(rx.await.unwrap(), Err(Error::Shutdown)));
+        assert_eq!(client.disconnected.load(Ordering::SeqCst), 1);
+
+        // Stopping again changes nothing.
+        assert!(!client.shutdown.begin(false));
+        assert!(client.stop(false).await);
+        assert_eq!(client.disconnected.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_worker_aborted() {
+        let dropped = Arc::new(AtomicUsize::new(0));
+        struct Guard(Arc<AtomicUsize>);
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        // A worker that never looks at the shutdown.
+        let guard = Guard(dropped.clone());
+        let task = tokio::spawn(async move {
+            let _guard = guard;
+            std::future::pending::<()>().await;
+        });
+
+        let started = Instant::now();
+        assert!(!shutdown::join_worker(task, Duration::from_millis(100)).await);
+        assert!(started.elapsed() >= Duration::from_millis(100));
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_disconnected_once() {
+        let shutdown = Shutdown::new();
+        // Every lost connection counts while running.
+        assert!(shutdown.take_disconnected());
+        assert!(shutdown.take_disconnected());
+        assert!(shutdown.check().is_ok());
+
+        assert!(shutdown.begin(false));
+        assert!(!shutdown.is_forced());
+        assert!(shutdown.take_disconnected());
+        assert!(!shutdown.take_disconnected());
+        assert!(matches!(shutdown.check(), Err(Error::State(_))));
+    }
+
+    #[test]
+    fn test_drain_in_deadline_order() {
+        let mut pending = PendingCalls::new(Duration::from_secs(1));
+        let now = Instant::now();
+        pending.insert(3, Id::random(), "c", false, now + Duration::from_millis(2));
+        pending.insert(1, Id::random(), "a", true, now);
+        pending.insert(2, Id::random(), "b", false, now + Duration::from_millis(1));
+
+        assert_eq!(pending.drain(), vec![(1, "a"), (2, "b"), (3, "c")]);
+        assert!(pending.is_empty());
+        assert!(!pending.is_timed_out(1));
+    }
+}
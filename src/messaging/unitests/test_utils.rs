@@ -0,0 +1,46 @@
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::Id;
+use crate::messaging::{
+    contact::ContactType,
+    persistence::database::ContactRecord,
+};
+
+/// A fresh repository directory, removed with its contents when dropped.
+pub(super) struct RepoDir(PathBuf);
+
+impl Deref for RepoDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for RepoDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub(super) fn new_repo_dir() -> RepoDir {
+    RepoDir(PathBuf::from(format!("/tmp/tm_{:016x}", rand::random::<u64>())))
+}
+
+/// A contact record carrying a session key, with no remark and not blocked.
+pub(super) fn make_contact(contact_type: ContactType, name: Option<&str>) -> ContactRecord {
+    ContactRecord {
+        id: Id::random(),
+        contact_type,
+        home_peer_id: Some(Id::random()),
+        name: name.map(|n| n.into()),
+        remark: None,
+        session_key: Some(crate::random_bytes(64)),
+        updated: 1700000000000,
+        blocked: false,
+    }
+}
@@ -1,9 +1,8 @@
-use crate::Id;
-use crate::core::{
-    crypto_identity::CryptoIdentity,
-};
+use std::fmt;
 
-#[derive(Debug, Clone)]
+use crate::{Id, Identity, CryptoIdentity};
+
+#[derive(Clone)]
 pub struct UserProfile {
     identity: CryptoIdentity,
     name    : String,
@@ -35,3 +34,14 @@ impl UserProfile {
         self.avatar
     }
 }
+
+// Leaves the private key of the identity out.
+impl fmt::Debug for UserProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserProfile")
+            .field("id", self.id())
+            .field("name", &self.name)
+            .field("avatar", &self.avatar)
+            .finish()
+    }
+}
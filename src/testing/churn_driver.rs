@@ -0,0 +1,88 @@
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use rand::{
+    rngs::SmallRng,
+    seq::SliceRandom,
+    SeedableRng,
+};
+
+use crate::testing::TestNetwork;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChurnStats {
+    pub rounds          : u64,
+    pub stops           : u64,
+    pub restarts        : u64,
+    pub failed_restarts : u64,
+}
+
+/// Stops and starts again a fraction of the nodes of a [`TestNetwork`] on a
+/// schedule. The first node, which the others bootstrap from, stays up.
+pub struct ChurnDriver {
+    interval    : Duration,
+    fraction    : f64,
+    seed        : Option<u64>,
+}
+
+impl ChurnDriver {
+    // Every interval, the given fraction of the nodes leaves the network and
+    // those that left the round before join it again.
+    pub fn new(interval: Duration, fraction: f64) -> Self {
+        Self {
+            interval,
+            fraction: fraction.clamp(0.0, 1.0),
+            seed    : None,
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Churns the network for the duration, then starts all stopped nodes.
+    pub async fn run(&self, network: &TestNetwork, duration: Duration) -> ChurnStats {
+        let mut rng = SmallRng::seed_from_u64(self.seed.unwrap_or_else(rand::random));
+        let mut stats = ChurnStats::default();
+        let mut stopped = Vec::new();
+        let count = ((network.size() - 1) as f64 * self.fraction).ceil() as usize;
+        let deadline = Instant::now() + duration;
+
+        while Instant::now() + self.interval <= deadline {
+            tokio::time::sleep(self.interval).await;
+            stats.rounds += 1;
+
+            self.restart(network, &mut stopped, &mut stats).await;
+
+            let mut candidates = network.running().into_iter()
+                .filter(|i| *i != 0)
+                .collect::<Vec<_>>();
+            candidates.shuffle(&mut rng);
+            for i in candidates.into_iter().take(count) {
+                match network.stop_node(i).await {
+                    Ok(_) => {
+                        stats.stops += 1;
+                        stopped.push(i);
+                    }
+                    Err(e) => warn!("Churn failed to stop test node {i}: {e}"),
+                }
+            }
+            info!("Churn round {}: {} nodes running", stats.rounds, network.running().len());
+        }
+
+        self.restart(network, &mut stopped, &mut stats).await;
+        stats
+    }
+
+    async fn restart(&self, network: &TestNetwork, stopped: &mut Vec<usize>, stats: &mut ChurnStats) {
+        for i in stopped.drain(..) {
+            match network.restart_node(i).await {
+                Ok(_) => stats.restarts += 1,
+                Err(e) => {
+                    warn!("Churn failed to restart test node {i}: {e}");
+                    stats.failed_restarts += 1;
+                }
+            }
+        }
+    }
+}
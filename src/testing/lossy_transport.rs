@@ -0,0 +1,165 @@
+use std::{
+    sync::Mutex,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use rand::{
+    rngs::SmallRng,
+    RngExt,
+    SeedableRng,
+};
+
+// What happens to the datagrams passing a LossyTransport, no fault at all by
+// default. Probabilities are between 0 and 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FaultProfile {
+    // Outgoing datagrams dropped.
+    pub loss            : f64,
+    // Incoming datagrams dropped, on top of the outgoing loss of the sender.
+    pub inbound_loss    : f64,
+    // Outgoing datagrams sent twice.
+    pub duplication     : f64,
+    // Outgoing datagrams, and duplicates, held back for a delay picked
+    // uniformly between min_delay and max_delay, so they arrive out of order.
+    pub delay           : f64,
+    pub min_delay       : Duration,
+    pub max_delay       : Duration,
+}
+
+impl FaultProfile {
+    // Drops the given fraction of the outgoing datagrams.
+    pub fn lossy(loss: f64) -> Self {
+        Self { loss, ..Default::default() }
+    }
+
+    pub fn with_duplication(mut self, duplication: f64) -> Self {
+        self.duplication = duplication;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: f64, min_delay: Duration, max_delay: Duration) -> Self {
+        self.delay = delay;
+        self.min_delay = min_delay;
+        self.max_delay = max_delay.max(min_delay);
+        self
+    }
+
+    pub fn with_inbound_loss(mut self, inbound_loss: f64) -> Self {
+        self.inbound_loss = inbound_loss;
+        self
+    }
+}
+
+// Counts of the datagrams the transport let through or tampered with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TransportStats {
+    pub sent            : u64,
+    pub dropped         : u64,
+    pub duplicated      : u64,
+    pub delayed         : u64,
+    pub received        : u64,
+    pub inbound_dropped : u64,
+}
+
+// Fault injector on the send and receive path of the RPC servers of the nodes
+// it is given to, see Node::with_faults. One transport is usually shared by
+// all nodes of a TestNetwork, its profile can be changed while they run.
+pub struct LossyTransport {
+    profile         : Mutex<FaultProfile>,
+    rng             : Mutex<SmallRng>,
+
+    sent            : AtomicU64,
+    dropped         : AtomicU64,
+    duplicated      : AtomicU64,
+    delayed         : AtomicU64,
+    received        : AtomicU64,
+    inbound_dropped : AtomicU64,
+}
+
+impl LossyTransport {
+    pub fn new(profile: FaultProfile) -> Self {
+        Self::with_seed(profile, rand::random())
+    }
+
+    // Same seed, same faults for the same sequence of datagrams.
+    pub fn with_seed(profile: FaultProfile, seed: u64) -> Self {
+        Self {
+            profile         : Mutex::new(profile),
+            rng             : Mutex::new(SmallRng::seed_from_u64(seed)),
+            sent            : AtomicU64::new(0),
+            dropped         : AtomicU64::new(0),
+            duplicated      : AtomicU64::new(0),
+            delayed         : AtomicU64::new(0),
+            received        : AtomicU64::new(0),
+            inbound_dropped : AtomicU64::new(0),
+        }
+    }
+
+    pub fn profile(&self) -> FaultProfile {
+        *self.profile.lock().unwrap()
+    }
+
+    pub fn set_profile(&self, profile: FaultProfile) {
+        *self.profile.lock().unwrap() = profile;
+    }
+
+    pub fn stats(&self) -> TransportStats {
+        TransportStats {
+            sent            : self.sent.load(Ordering::Relaxed),
+            dropped         : self.dropped.load(Ordering::Relaxed),
+            duplicated      : self.duplicated.load(Ordering::Relaxed),
+            delayed         : self.delayed.load(Ordering::Relaxed),
+            received        : self.received.load(Ordering::Relaxed),
+            inbound_dropped : self.inbound_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    // The delays of the copies of an outgoing datagram to send, zero for
+    // right away, none when it is lost.
+    pub(crate) fn outbound(&self) -> Vec<Duration> {
+        let profile = self.profile();
+        let mut rng = self.rng.lock().unwrap();
+        if rng.random_bool(profile.loss.clamp(0.0, 1.0)) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Vec::new();
+        }
+
+        let copies = match rng.random_bool(profile.duplication.clamp(0.0, 1.0)) {
+            true => {
+                self.duplicated.fetch_add(1, Ordering::Relaxed);
+                2
+            },
+            false => 1,
+        };
+        self.sent.fetch_add(1, Ordering::Relaxed);
+
+        (0..copies).map(|_| {
+            if !rng.random_bool(profile.delay.clamp(0.0, 1.0)) {
+                return Duration::ZERO;
+            }
+            self.delayed.fetch_add(1, Ordering::Relaxed);
+            rng.random_range(profile.min_delay..=profile.max_delay)
+        }).collect()
+    }
+
+    // Whether an incoming datagram gets through.
+    pub(crate) fn inbound(&self) -> bool {
+        let loss = self.profile().inbound_loss.clamp(0.0, 1.0);
+        match self.rng.lock().unwrap().random_bool(loss) {
+            true => {
+                self.inbound_dropped.fetch_add(1, Ordering::Relaxed);
+                false
+            },
+            false => {
+                self.received.fetch_add(1, Ordering::Relaxed);
+                true
+            }
+        }
+    }
+}
+
+impl Default for LossyTransport {
+    fn default() -> Self {
+        Self::new(FaultProfile::default())
+    }
+}
@@ -0,0 +1,29 @@
+//! Fault injection and a local multi-node network for soak testing the DHT
+//! under churn, packet loss, duplication, delay and clock skew, and a
+//! simulated network running the DHT on virtual time for reproducible
+//! protocol tests. Only built with the `testing` feature.
+
+mod lossy_transport;
+mod offset_clock;
+mod test_network;
+mod churn_driver;
+mod soak;
+mod sim_transport;
+mod sim_network;
+
+pub use crate::testing::{
+    lossy_transport::{LossyTransport, FaultProfile, TransportStats},
+    offset_clock::OffsetClock,
+    test_network::{TestNetwork, TestNetworkBuilder},
+    churn_driver::{ChurnDriver, ChurnStats},
+    soak::{Soak, SoakReport, Workload, WorkloadStats},
+    sim_transport::{SimTransport, LatencyModel, SimStats},
+    sim_network::SimNetwork,
+};
+pub(crate) use crate::testing::sim_network::sim_random;
+
+#[cfg(test)]
+mod unitests {
+    mod test_lossy_transport;
+    mod test_sim_transport;
+}
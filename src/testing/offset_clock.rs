@@ -0,0 +1,39 @@
+use std::{
+    sync::atomic::{AtomicI64, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use crate::Clock;
+
+// The system time shifted by an offset, so the nodes of a TestNetwork can run
+// with skewed clocks. The offset can be changed while the node runs.
+#[derive(Debug, Default)]
+pub struct OffsetClock {
+    offset_ms: AtomicI64,
+}
+
+impl OffsetClock {
+    // Positive offsets put the clock ahead, negative ones behind.
+    pub fn new(offset_ms: i64) -> Self {
+        Self { offset_ms: AtomicI64::new(offset_ms) }
+    }
+
+    pub fn offset_ms(&self) -> i64 {
+        self.offset_ms.load(Ordering::Relaxed)
+    }
+
+    pub fn set_offset_ms(&self, offset_ms: i64) {
+        self.offset_ms.store(offset_ms, Ordering::Relaxed);
+    }
+}
+
+impl Clock for OffsetClock {
+    fn now(&self) -> SystemTime {
+        let offset = self.offset_ms();
+        let now = SystemTime::now();
+        match offset >= 0 {
+            true => now + Duration::from_millis(offset as u64),
+            false => now - Duration::from_millis(offset.unsigned_abs()),
+        }
+    }
+}
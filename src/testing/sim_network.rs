@@ -0,0 +1,274 @@
+use std::{
+    cell::RefCell,
+    fs,
+    future::Future,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use rand::{
+    rngs::SmallRng,
+    Rng,
+    RngExt,
+    SeedableRng,
+};
+use tokio::{
+    runtime::{self, Runtime},
+    task::LocalSet,
+    time::{self, Instant},
+};
+
+use crate::{
+    signature::KeyPair,
+    ManualClock,
+    Node,
+    errors::{Result, ArgumentError, IOError},
+    dht::NodeConfiguration,
+};
+use crate::testing::{LatencyModel, SimTransport};
+
+// How often the driver of a simulation brings the clock of the nodes up to
+// the virtual time when no datagram arrives in between.
+const DEFAULT_RESOLUTION: Duration = Duration::from_millis(100);
+
+thread_local! {
+    // The generator of the simulation running on this thread.
+    static RANDOM: RefCell<Option<SmallRng>> = const { RefCell::new(None) };
+}
+
+// Fills the bytes from the generator of the simulation running on this
+// thread, returns false when none runs.
+pub(crate) fn sim_random(bytes: &mut [u8]) -> bool {
+    RANDOM.with_borrow_mut(|rng| match rng.as_mut() {
+        Some(rng) => {
+            rng.fill_bytes(bytes);
+            true
+        },
+        None => false,
+    })
+}
+
+// Hands the generator of a simulation to the thread while it runs.
+struct RandomGuard<'a> {
+    random: &'a RefCell<Option<SmallRng>>,
+}
+
+impl<'a> RandomGuard<'a> {
+    fn install(random: &'a RefCell<Option<SmallRng>>) -> Self {
+        RANDOM.set(random.take());
+        Self { random }
+    }
+}
+
+impl Drop for RandomGuard<'_> {
+    fn drop(&mut self) {
+        *self.random.borrow_mut() = RANDOM.take();
+    }
+}
+
+/// A network of DHT nodes running on virtual time in the calling thread.
+/// The datagrams between the nodes go through the event queue of a
+/// [`SimTransport`] instead of sockets, and the time only passes while the
+/// simulation runs, jumping straight to the next timer or datagram due, so
+/// hours of a scenario take seconds. The nodes read the time from one
+/// [`ManualClock`] kept in step with the virtual time.
+///
+/// The node keys, the latency and loss of every datagram and the random ids
+/// the DHT picks while the simulation runs are drawn from the seed. The tasks
+/// of a node still interleave in an order of their own, so two runs with the
+/// same seed agree on the outcome of a scenario rather than packet by packet.
+///
+/// Driving its own runtime, a simulation can not be created or run from
+/// within an async context.
+pub struct SimNetwork {
+    seed        : u64,
+    rng         : SmallRng,
+    random      : RefCell<Option<SmallRng>>,
+    dir         : PathBuf,
+    base_port   : u16,
+    config      : String,
+
+    nodes       : Vec<Arc<Node>>,
+    clock       : Arc<ManualClock>,
+    transport   : Arc<SimTransport>,
+    epoch       : Instant,
+
+    // Dropped before the runtime it runs on.
+    local       : LocalSet,
+    runtime     : Runtime,
+}
+
+impl SimNetwork {
+    pub fn new(seed: u64) -> Self {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .expect("simulation runtime should build");
+
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let start = SystemTime::now();
+        let epoch = runtime.block_on(async { Instant::now() });
+        let transport = Arc::new(SimTransport::new(rng.random(), LatencyModel::default()));
+        let random = RefCell::new(Some(SmallRng::seed_from_u64(rng.random())));
+        let clock = Arc::new(ManualClock::new(start));
+
+        let local = LocalSet::new();
+        local.spawn_local(drive(transport.clone(), clock.clone(), start, epoch));
+
+        Self {
+            seed,
+            rng,
+            random,
+            dir         : std::env::temp_dir().join(format!("boson-sim-{:016x}", rand::random::<u64>())),
+            base_port   : 39001,
+            config      : String::new(),
+            nodes       : Vec::new(),
+            clock,
+            transport,
+            epoch,
+            local,
+            runtime,
+        }
+    }
+
+    pub fn with_latency(self, model: LatencyModel) -> Self {
+        self.transport.set_model(model);
+        self
+    }
+
+    // Extra yaml lines appended to the configuration of the nodes added
+    // afterwards.
+    pub fn with_config(mut self, yaml: &str) -> Self {
+        self.config = yaml.to_string();
+        self
+    }
+
+    // Creates and starts the nodes, at the current virtual time. The first
+    // node of the network is the bootstrap node of the others, each joins
+    // on its own as it starts.
+    pub fn add_nodes(mut self, count: usize) -> Result<Self> {
+        if self.base_port as usize + self.nodes.len() + count > u16::MAX as usize {
+            return Err(ArgumentError::new("Not enough ports for the simulated nodes"));
+        }
+
+        for _ in 0..count {
+            let index = self.nodes.len();
+            let data_dir = self.dir.join(format!("node-{index}"));
+            fs::create_dir_all(&data_dir).map_err(|e| IOError::new(
+                format!("Creating {} failed: {e}", data_dir.display())))?;
+
+            let keypair = KeyPair::try_from_seed(&self.rng.random::<[u8; KeyPair::SEED_BYTES]>())?;
+            let bootstraps = self.nodes.first().map(|first| {
+                let ni = first.node_info();
+                let addr = ni.socket_addr();
+                format!("bootstraps:\n  - [\"{}\", \"{}\", {}]\n", ni.id(), addr.ip(), addr.port())
+            }).unwrap_or_default();
+
+            let yaml = format!(
+                "ipv4: true\nport: {}\nprivateKey: \"{}\"\ndataDir: {}\ndatabaseUri: jdbc:sqlite:node.db\nlogLevel: \"warn\"\n{}{}",
+                self.base_port as usize + index,
+                keypair.private_key(),
+                data_dir.display(),
+                bootstraps,
+                self.config,
+            );
+            let cfg = NodeConfiguration::from(&yaml)?;
+            let node = Node::with_sim(Box::new(cfg), self.clock.clone(), self.transport.clone())?;
+            self.block_on(node.start())?;
+            self.nodes.push(node);
+        }
+        Ok(self)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn node(&self, index: usize) -> Option<&Arc<Node>> {
+        self.nodes.get(index)
+    }
+
+    pub fn nodes(&self) -> &[Arc<Node>] {
+        &self.nodes
+    }
+
+    pub fn clock(&self) -> &Arc<ManualClock> {
+        &self.clock
+    }
+
+    pub fn transport(&self) -> &Arc<SimTransport> {
+        &self.transport
+    }
+
+    // The virtual time elapsed since the simulation was created.
+    pub fn now(&self) -> Duration {
+        let _guard = self.runtime.enter();
+        Instant::now() - self.epoch
+    }
+
+    // Runs the simulation until the virtual time, does nothing once past it.
+    pub fn run_until(&self, virtual_time: Duration) {
+        let deadline = self.epoch + virtual_time;
+        self.block_on(async move {
+            time::sleep_until(deadline).await
+        });
+    }
+
+    pub fn run_for(&self, duration: Duration) {
+        self.run_until(self.now() + duration);
+    }
+
+    // Runs the simulation until the future completes, the future usually
+    // being a call on one of the nodes.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        let _random = RandomGuard::install(&self.random);
+        self.runtime.block_on(self.local.run_until(future))
+    }
+}
+
+impl Drop for SimNetwork {
+    // Stops the nodes and removes their data, leaving the nodes be when
+    // unwinding from a failed run.
+    fn drop(&mut self) {
+        let nodes = std::mem::take(&mut self.nodes);
+        if !std::thread::panicking() {
+            self.block_on(async {
+                for node in nodes {
+                    let _ = node.stop().await;
+                }
+            });
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+// Delivers the datagrams of the simulation as they fall due, and brings the
+// clock up to the virtual time at least every DEFAULT_RESOLUTION.
+async fn drive(
+    transport: Arc<SimTransport>,
+    clock: Arc<ManualClock>,
+    start: SystemTime,
+    epoch: Instant,
+) {
+    let mut next_tick = epoch + DEFAULT_RESOLUTION;
+    loop {
+        let wake = transport.next_due().map_or(next_tick, |due| due.min(next_tick));
+        tokio::select! {
+            biased;
+            _ = transport.woken() => continue,
+            _ = time::sleep_until(wake) => {}
+        }
+
+        let now = Instant::now();
+        clock.set(start + (now - epoch));
+        while next_tick <= now {
+            next_tick += DEFAULT_RESOLUTION;
+        }
+        transport.deliver_due(now);
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    cmp::{Ordering as CmpOrdering, Reverse},
+    collections::{BinaryHeap, HashMap},
+    net::SocketAddr,
+    sync::Mutex,
+    time::Duration,
+};
+use rand::{
+    rngs::SmallRng,
+    RngExt,
+    SeedableRng,
+};
+use tokio::{
+    sync::{mpsc, Notify},
+    time::Instant,
+};
+
+use crate::dht::rpc::receivers::{Inbound, RECEIVE_QUEUE_SIZE};
+
+// How long the datagrams between the simulated nodes take and how many of
+// them get lost. Each link draws from a generator of its own, seeded from the
+// network seed and its two ends, so the fate of a datagram depends neither
+// on the traffic of the other links nor on the order the nodes ran in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LatencyModel {
+    // Picked uniformly between min_latency and max_latency per datagram.
+    pub min_latency : Duration,
+    pub max_latency : Duration,
+    // Fraction of the datagrams lost, between 0 and 1.
+    pub loss        : f64,
+}
+
+impl LatencyModel {
+    pub fn fixed(latency: Duration) -> Self {
+        Self::uniform(latency, latency)
+    }
+
+    pub fn uniform(min_latency: Duration, max_latency: Duration) -> Self {
+        Self {
+            min_latency,
+            max_latency : max_latency.max(min_latency),
+            loss        : 0.0,
+        }
+    }
+
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        self.loss = loss;
+        self
+    }
+}
+
+impl Default for LatencyModel {
+    fn default() -> Self {
+        Self::uniform(Duration::from_millis(5), Duration::from_millis(50))
+    }
+}
+
+// Counts of the datagrams passing the simulated network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SimStats {
+    pub sent        : u64,
+    pub delivered   : u64,
+    pub lost        : u64,
+    // Addressed to no running node, or to one too far behind to take them.
+    pub unroutable  : u64,
+}
+
+// A datagram in flight, due at the virtual time it arrives. Datagrams due at
+// the same time arrive in the order they were sent.
+struct Datagram {
+    due     : Instant,
+    seq     : u64,
+    from    : SocketAddr,
+    to      : SocketAddr,
+    data    : Vec<u8>,
+}
+
+impl Datagram {
+    fn key(&self) -> (Instant, u64) {
+        (self.due, self.seq)
+    }
+}
+
+impl PartialEq for Datagram {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Datagram {}
+
+impl PartialOrd for Datagram {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Datagram {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.key().cmp(&other.key())
+    }
+}
+
+struct EventQueue {
+    seed        : u64,
+    model       : LatencyModel,
+    links       : HashMap<(SocketAddr, SocketAddr), SmallRng>,
+    ports       : HashMap<SocketAddr, mpsc::Sender<Inbound>>,
+    pending     : BinaryHeap<Reverse<Datagram>>,
+    seq         : u64,
+    stats       : SimStats,
+}
+
+impl EventQueue {
+    fn link(&mut self, from: SocketAddr, to: SocketAddr) -> &mut SmallRng {
+        let seed = self.seed;
+        self.links.entry((from, to)).or_insert_with(|| {
+            SmallRng::seed_from_u64(link_seed(seed, from, to))
+        })
+    }
+}
+
+// The network between the nodes of a SimNetwork, standing in for the sockets
+// of their RPC servers, see Node::with_sim. Datagrams wait in a central queue
+// ordered by virtual time and are handed to the receiving node by the driver
+// of the network once due.
+pub struct SimTransport {
+    queue   : Mutex<EventQueue>,
+    // Wakes the driver up for a datagram due before it planned to look again.
+    wake    : Notify,
+}
+
+impl SimTransport {
+    pub(crate) fn new(seed: u64, model: LatencyModel) -> Self {
+        Self {
+            queue: Mutex::new(EventQueue {
+                seed,
+                model,
+                links   : HashMap::new(),
+                ports   : HashMap::new(),
+                pending : BinaryHeap::new(),
+                seq     : 0,
+                stats   : SimStats::default(),
+            }),
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn model(&self) -> LatencyModel {
+        self.queue.lock().unwrap().model
+    }
+
+    // Applies to the datagrams sent from now on.
+    pub fn set_model(&self, model: LatencyModel) {
+        self.queue.lock().unwrap().model = model;
+    }
+
+    pub fn stats(&self) -> SimStats {
+        self.queue.lock().unwrap().stats
+    }
+
+    // Datagrams in flight.
+    pub fn pending(&self) -> usize {
+        self.queue.lock().unwrap().pending.len()
+    }
+
+    // The datagrams to the address from now on, read by the RPC server bound
+    // to it instead of a socket.
+    pub(crate) fn attach(&self, addr: SocketAddr) -> mpsc::Receiver<Inbound> {
+        let (tx, rx) = mpsc::channel(RECEIVE_QUEUE_SIZE);
+        self.queue.lock().unwrap().ports.insert(addr, tx);
+        rx
+    }
+
+    pub(crate) fn detach(&self, addr: SocketAddr) {
+        self.queue.lock().unwrap().ports.remove(&addr);
+    }
+
+    // Queues the datagram to arrive after the latency of its link, a lost
+    // datagram counts as sent.
+    pub(crate) fn send(&self, from: SocketAddr, to: SocketAddr, data: &[u8]) {
+        let mut queue = self.queue.lock().unwrap();
+        let model = queue.model;
+        queue.stats.sent += 1;
+
+        let rng = queue.link(from, to);
+        if rng.random_bool(model.loss.clamp(0.0, 1.0)) {
+            queue.stats.lost += 1;
+            return;
+        }
+        let latency = rng.random_range(model.min_latency..=model.max_latency);
+
+        queue.seq += 1;
+        let datagram = Datagram {
+            due     : Instant::now() + latency,
+            seq     : queue.seq,
+            from,
+            to,
+            data    : data.to_vec(),
+        };
+        let earliest = queue.pending.peek().is_none_or(|Reverse(d)| datagram < *d);
+        queue.pending.push(Reverse(datagram));
+        drop(queue);
+
+        if earliest {
+            self.wake.notify_one();
+        }
+    }
+
+    // When the next datagram arrives, if any is in flight.
+    pub(crate) fn next_due(&self) -> Option<Instant> {
+        self.queue.lock().unwrap().pending.peek().map(|Reverse(d)| d.due)
+    }
+
+    pub(crate) async fn woken(&self) {
+        self.wake.notified().await
+    }
+
+    // Hands the datagrams due by now to their nodes, in the order they
+    // arrive.
+    pub(crate) fn deliver_due(&self, now: Instant) {
+        let mut queue = self.queue.lock().unwrap();
+        while queue.pending.peek().is_some_and(|Reverse(d)| d.due <= now) {
+            let Reverse(datagram) = queue.pending.pop().unwrap();
+            let inbound = Inbound {
+                from        : datagram.from,
+                data        : datagram.data,
+                decrypted   : None,
+            };
+            let delivered = queue.ports.get(&datagram.to)
+                .is_some_and(|port| port.try_send(inbound).is_ok());
+            match delivered {
+                true  => queue.stats.delivered += 1,
+                false => queue.stats.unroutable += 1,
+            }
+        }
+    }
+}
+
+// Mixes the network seed with the two ends of a link (splitmix64), the
+// same on every run unlike the hashers of the std collections.
+fn link_seed(seed: u64, from: SocketAddr, to: SocketAddr) -> u64 {
+    let mix = |mut z: u64| {
+        z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let end = |addr: SocketAddr| {
+        let ip = match addr.ip() {
+            std::net::IpAddr::V4(ip) => u32::from(ip) as u128,
+            std::net::IpAddr::V6(ip) => u128::from(ip),
+        };
+        mix((ip as u64) ^ ((ip >> 64) as u64) ^ ((addr.port() as u64) << 48))
+    };
+    mix(seed ^ end(from)) ^ mix(end(to).rotate_left(17))
+}
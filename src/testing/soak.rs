@@ -0,0 +1,262 @@
+use std::{
+    fmt,
+    panic::{self, PanicHookInfo},
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use rand::{
+    rngs::SmallRng,
+    RngExt,
+    SeedableRng,
+};
+
+use crate::{
+    Id,
+    Node,
+    ImmutableBuilder,
+    errors::{Result, StateError},
+};
+use crate::testing::{
+    ChurnDriver,
+    ChurnStats,
+    TestNetwork,
+    TransportStats,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorkloadStats {
+    pub stores              : u64,
+    pub failed_stores       : u64,
+    pub lookups             : u64,
+    pub found               : u64,
+    // Operations cut short by the churn stopping the node running them.
+    pub interrupted_stores  : u64,
+    pub interrupted_lookups : u64,
+}
+
+impl WorkloadStats {
+    // Found values of the lookups not interrupted, 1 without any.
+    pub fn lookup_success_rate(&self) -> f64 {
+        match self.lookups.saturating_sub(self.interrupted_lookups) {
+            0 => 1.0,
+            n => self.found as f64 / n as f64,
+        }
+    }
+}
+
+/// Stores values through random running nodes of a [`TestNetwork`] and looks
+/// up the ones stored so far through others, from a number of workers.
+pub struct Workload {
+    interval    : Duration,
+    workers     : usize,
+    seed        : Option<u64>,
+}
+
+impl Workload {
+    // Each worker does one store and one lookup, then waits for the interval.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            workers : 4,
+            seed    : None,
+        }
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub async fn run(&self, network: &TestNetwork, duration: Duration) -> WorkloadStats {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let stored = Mutex::new(Vec::new());
+        let deadline = Instant::now() + duration;
+
+        let workers = (0..self.workers as u64).map(|i| {
+            self.work(network, &stored, deadline, seed.wrapping_add(i))
+        });
+        futures::future::join_all(workers).await.into_iter()
+            .fold(WorkloadStats::default(), |total, stats| WorkloadStats {
+                stores              : total.stores + stats.stores,
+                failed_stores       : total.failed_stores + stats.failed_stores,
+                lookups             : total.lookups + stats.lookups,
+                found               : total.found + stats.found,
+                interrupted_stores  : total.interrupted_stores + stats.interrupted_stores,
+                interrupted_lookups : total.interrupted_lookups + stats.interrupted_lookups,
+            })
+    }
+
+    async fn work(&self,
+        network: &TestNetwork,
+        stored: &Mutex<Vec<Id>>,
+        deadline: Instant,
+        seed: u64
+    ) -> WorkloadStats {
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let mut stats = WorkloadStats::default();
+
+        while Instant::now() < deadline {
+            if let Some(node) = Self::pick(network, &mut rng) {
+                let data = rng.random::<[u8; 32]>();
+                let value = ImmutableBuilder::new(&data).build().unwrap();
+                match node.store_value(&value, -1, false).await {
+                    Ok(_) => stored.lock().unwrap().push(value.id()),
+                    Err(_) if !node.is_running() => stats.interrupted_stores += 1,
+                    Err(_) => stats.failed_stores += 1,
+                }
+                stats.stores += 1;
+            }
+
+            let value_id = {
+                let stored = stored.lock().unwrap();
+                (!stored.is_empty()).then(|| stored[rng.random_range(0..stored.len())])
+            };
+            if let (Some(value_id), Some(node)) = (value_id, Self::pick(network, &mut rng)) {
+                match node.find_value(&value_id, -1, None).await {
+                    Ok(Some(_)) => stats.found += 1,
+                    _ if !node.is_running() => stats.interrupted_lookups += 1,
+                    _ => {},
+                }
+                stats.lookups += 1;
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+        stats
+    }
+
+    fn pick(network: &TestNetwork, rng: &mut SmallRng) -> Option<Arc<Node>> {
+        let running = network.running();
+        match running.is_empty() {
+            true => None,
+            false => network.node(running[rng.random_range(0..running.len())]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SoakReport {
+    pub churn       : ChurnStats,
+    pub workload    : WorkloadStats,
+    pub transport   : TransportStats,
+    pub panics      : usize,
+    pub converged   : bool,
+}
+
+impl SoakReport {
+    // Fails with all broken invariants: panics on any thread, a lookup
+    // success rate below the minimum, nodes not restarted, or routing tables
+    // not converged once the churn stopped.
+    pub fn check(&self, min_success_rate: f64) -> Result<()> {
+        let mut broken = Vec::new();
+        if self.panics > 0 {
+            broken.push(format!("{} panics", self.panics));
+        }
+        if self.workload.lookup_success_rate() < min_success_rate {
+            broken.push(format!("lookup success rate {:.3} below {:.3}",
+                self.workload.lookup_success_rate(), min_success_rate));
+        }
+        if self.churn.failed_restarts > 0 {
+            broken.push(format!("{} nodes failed to restart", self.churn.failed_restarts));
+        }
+        if !self.converged {
+            broken.push("routing tables did not converge".into());
+        }
+        match broken.is_empty() {
+            true => Ok(()),
+            false => Err(StateError::new(format!("{}: {self}", broken.join(", ")))),
+        }
+    }
+}
+
+impl fmt::Display for SoakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "churn {:?}, workload {:?}, transport {:?}, panics {}, converged {}",
+            self.churn, self.workload, self.transport, self.panics, self.converged)
+    }
+}
+
+/// A soak run over a [`TestNetwork`]: the churn and the workload go on
+/// together for the duration, then the network is given the settle time to
+/// converge.
+pub struct Soak {
+    duration    : Duration,
+    settle      : Duration,
+    churn       : ChurnDriver,
+    workload    : Workload,
+}
+
+impl Soak {
+    pub fn new(duration: Duration, churn: ChurnDriver, workload: Workload) -> Self {
+        Self {
+            duration,
+            settle: Duration::from_secs(30),
+            churn,
+            workload,
+        }
+    }
+
+    pub fn with_settle(mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self
+    }
+
+    pub async fn run(&self, network: &TestNetwork) -> SoakReport {
+        let watch = PanicWatch::install();
+        let (churn, workload) = tokio::join!(
+            self.churn.run(network, self.duration),
+            self.workload.run(network, self.duration)
+        );
+        let converged = network.wait_converged(self.settle).await;
+
+        SoakReport {
+            churn,
+            workload,
+            transport   : network.transport().stats(),
+            panics      : watch.panics(),
+            converged,
+        }
+    }
+}
+
+type PanicHook = Box<dyn Fn(&PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+// Counts the panics of all threads, the DHT ones included, while installed.
+struct PanicWatch {
+    panics  : Arc<AtomicUsize>,
+    previous: Arc<PanicHook>,
+}
+
+impl PanicWatch {
+    fn install() -> Self {
+        let panics = Arc::new(AtomicUsize::new(0));
+        let previous: Arc<PanicHook> = Arc::new(panic::take_hook());
+
+        let counter = panics.clone();
+        let hook = previous.clone();
+        panic::set_hook(Box::new(move |info| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            hook(info);
+        }));
+        Self { panics, previous }
+    }
+
+    fn panics(&self) -> usize {
+        self.panics.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for PanicWatch {
+    fn drop(&mut self) {
+        let previous = self.previous.clone();
+        panic::set_hook(Box::new(move |info| previous(info)));
+    }
+}
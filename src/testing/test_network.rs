@@ -0,0 +1,317 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use log::warn;
+use rand::{
+    rngs::SmallRng,
+    RngExt,
+    SeedableRng,
+};
+
+use crate::{
+    signature,
+    Node,
+    Network,
+    errors::{Result, ArgumentError, IOError, StateError},
+    dht::{LookupOption, NodeConfiguration},
+};
+use crate::testing::{
+    FaultProfile,
+    LossyTransport,
+    OffsetClock,
+};
+
+// Attempts to start a node again, its port may be held a little longer by
+// the socket of the stopped instance, and to bootstrap it.
+const START_ATTEMPTS: usize = 5;
+
+// A node of the network, with what it needs to be started again.
+struct TestNode {
+    yaml    : String,
+    clock   : Arc<OffsetClock>,
+    node    : Mutex<Option<Arc<Node>>>,
+}
+
+/// A network of DHT nodes on the local host sharing one fault injector, each
+/// with its own skewed clock. The first node is the bootstrap node of the
+/// others and is never stopped by a [`ChurnDriver`](crate::testing::ChurnDriver).
+pub struct TestNetwork {
+    dir         : PathBuf,
+    transport   : Arc<LossyTransport>,
+    nodes       : Vec<TestNode>,
+}
+
+pub struct TestNetworkBuilder {
+    size        : usize,
+    base_port   : u16,
+    dir         : Option<PathBuf>,
+    faults      : FaultProfile,
+    clock_skew  : Duration,
+    seed        : Option<u64>,
+    config      : String,
+}
+
+impl TestNetworkBuilder {
+    fn new(size: usize) -> Self {
+        Self {
+            size,
+            base_port   : 33001,
+            dir         : None,
+            faults      : FaultProfile::default(),
+            clock_skew  : Duration::ZERO,
+            seed        : None,
+            config      : String::new(),
+        }
+    }
+
+    // Node i listens on base_port + i.
+    pub fn with_base_port(mut self, port: u16) -> Self {
+        self.base_port = port;
+        self
+    }
+
+    // Directory for the data of the nodes, removed on shutdown. A new one in
+    // the temporary directory by default.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    pub fn with_faults(mut self, faults: FaultProfile) -> Self {
+        self.faults = faults;
+        self
+    }
+
+    // Each node gets a clock offset picked uniformly within +/- skew.
+    pub fn with_clock_skew(mut self, skew: Duration) -> Self {
+        self.clock_skew = skew;
+        self
+    }
+
+    // Seeds the faults and the clock offsets.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    // Extra yaml lines appended to the configuration of every node.
+    pub fn with_config(mut self, yaml: &str) -> Self {
+        self.config = yaml.to_string();
+        self
+    }
+
+    // Creates and starts the nodes, then has them join through the first one.
+    pub async fn start(self) -> Result<TestNetwork> {
+        if self.size < 2 {
+            return Err(ArgumentError::new("A test network needs at least 2 nodes"));
+        }
+        if self.base_port as usize + self.size > u16::MAX as usize {
+            return Err(ArgumentError::new("Not enough ports above the base port"));
+        }
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = SmallRng::seed_from_u64(seed);
+        let dir = self.dir.clone().unwrap_or_else(|| {
+            std::env::temp_dir().join(format!("boson-testnet-{:016x}", rand::random::<u64>()))
+        });
+
+        let skew = self.clock_skew.as_millis() as i64;
+        let mut nodes = Vec::with_capacity(self.size);
+        for i in 0..self.size {
+            let data_dir = dir.join(format!("node-{i}"));
+            fs::create_dir_all(&data_dir).map_err(|e| IOError::new(
+                format!("Creating {} failed: {e}", data_dir.display())))?;
+
+            let yaml = format!(
+                "ipv4: true\nport: {}\nprivateKey: \"{}\"\ndataDir: {}\ndatabaseUri: jdbc:sqlite:node.db\nlogLevel: \"warn\"\n{}",
+                self.base_port as usize + i,
+                signature::KeyPair::random().private_key(),
+                data_dir.display(),
+                self.config,
+            );
+            nodes.push(TestNode {
+                yaml,
+                clock   : Arc::new(OffsetClock::new(rng.random_range(-skew..=skew))),
+                node    : Mutex::new(None),
+            });
+        }
+
+        let network = TestNetwork {
+            dir,
+            transport   : Arc::new(LossyTransport::with_seed(self.faults, rng.random())),
+            nodes,
+        };
+
+        let started = futures::future::join_all(
+            (0..network.size()).map(|i| network.start_node(i))
+        ).await;
+        if let Some(Err(e)) = started.into_iter().find(|r| r.is_err()) {
+            network.shutdown().await;
+            return Err(e);
+        }
+
+        let joined = futures::future::join_all(
+            (1..network.size()).map(|i| network.join(i))
+        ).await;
+        for (i, result) in joined.into_iter().enumerate() {
+            if let Err(e) = result {
+                warn!("Test node {} failed to join the network: {e}", i + 1);
+            }
+        }
+        Ok(network)
+    }
+}
+
+impl TestNetwork {
+    pub fn builder(size: usize) -> TestNetworkBuilder {
+        TestNetworkBuilder::new(size)
+    }
+
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // The node at the index, None while it is stopped.
+    pub fn node(&self, index: usize) -> Option<Arc<Node>> {
+        self.nodes.get(index)?.node.lock().unwrap().clone()
+    }
+
+    // Indexes of the nodes currently running.
+    pub fn running(&self) -> Vec<usize> {
+        (0..self.size()).filter(|i| self.node(*i).is_some()).collect()
+    }
+
+    pub fn clock(&self, index: usize) -> Option<&Arc<OffsetClock>> {
+        self.nodes.get(index).map(|n| &n.clock)
+    }
+
+    pub fn transport(&self) -> &Arc<LossyTransport> {
+        &self.transport
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    pub async fn stop_node(&self, index: usize) -> Result<()> {
+        let slot = self.nodes.get(index).ok_or_else(|| ArgumentError::new(
+            format!("No test node {index}")))?;
+        let node = slot.node.lock().unwrap().take();
+        if let Some(node) = node {
+            node.stop().await?;
+        }
+        Ok(())
+    }
+
+    // Starts the stopped node as a new process would, from its configuration
+    // and data directory, and has it join the network again.
+    pub async fn restart_node(&self, index: usize) -> Result<()> {
+        self.start_node(index).await?;
+        self.join(index).await
+    }
+
+    // Bootstraps the node from all the other running ones, as a node does
+    // from its list of bootstrap nodes, until its routing table gets entries.
+    // The DHT itself does not bootstrap again for minutes once an attempt
+    // went lost to the faults.
+    async fn join(&self, index: usize) -> Result<()> {
+        let node = self.node(index).ok_or_else(|| StateError::new(
+            format!("Test node {index} is not running")))?;
+        let seeds = self.running().into_iter()
+            .filter(|i| *i != index)
+            .filter_map(|i| self.node(i).map(|n| n.node_info()))
+            .collect::<Vec<_>>();
+        if seeds.is_empty() {
+            return Ok(());
+        }
+
+        for _ in 0..START_ATTEMPTS {
+            node.bootstrap(&seeds).await?;
+            let buckets = node.routing_table_snapshot(Network::IPv4).await?;
+            if buckets.iter().any(|b| b.entries() > 0) {
+                return Ok(());
+            }
+        }
+        Err(StateError::new(format!("Test node {index} found no other node to join")))
+    }
+
+    async fn start_node(&self, index: usize) -> Result<()> {
+        let slot = self.nodes.get(index).ok_or_else(|| ArgumentError::new(
+            format!("No test node {index}")))?;
+        if slot.node.lock().unwrap().is_some() {
+            return Err(StateError::new(format!("Test node {index} is already running")));
+        }
+
+        let mut attempts = 0;
+        let node = loop {
+            let cfg = NodeConfiguration::from(&slot.yaml)?;
+            let node = Node::with_faults(Box::new(cfg), slot.clock.clone(), self.transport.clone())?;
+            attempts += 1;
+            match node.start().await {
+                Ok(_) => break node,
+                Err(e) if attempts < START_ATTEMPTS => {
+                    warn!("Starting test node {index} failed: {e}, will retry");
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        *slot.node.lock().unwrap() = Some(node);
+        Ok(())
+    }
+
+    // Every running node knows the next running one, or finds it with a full
+    // lookup.
+    pub async fn converged(&self) -> bool {
+        let running = self.running();
+        let lookups = (0..running.len()).map(|pos| self.finds_next(&running, pos));
+        futures::future::join_all(lookups).await.into_iter().all(|found| found)
+    }
+
+    // Checks convergence until it is reached or the timeout expires. A node
+    // that found the next one once is not asked again, a lookup now and then
+    // lost to the faults does not hold the whole network back.
+    pub async fn wait_converged(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let running = self.running();
+        let mut pending = (0..running.len()).collect::<Vec<_>>();
+        loop {
+            let found = futures::future::join_all(
+                pending.iter().map(|pos| self.finds_next(&running, *pos))
+            ).await;
+            let mut found = found.into_iter();
+            pending.retain(|_| !found.next().unwrap_or(false));
+
+            if pending.is_empty() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn finds_next(&self, running: &[usize], pos: usize) -> bool {
+        let from = self.node(running[pos]);
+        let target = self.node(running[(pos + 1) % running.len()]);
+        let (Some(from), Some(target)) = (from, target) else {
+            return false;
+        };
+        from.find_node(target.id(), Some(LookupOption::Conservative)).await
+            .is_ok_and(|found| found.has_value())
+    }
+
+    // Stops all nodes and removes their data.
+    pub async fn shutdown(self) {
+        for i in 0..self.size() {
+            if let Err(e) = self.stop_node(i).await {
+                warn!("Stopping test node {i} failed: {e}");
+            }
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    Clock,
+    testing::{FaultProfile, LossyTransport, OffsetClock},
+};
+
+fn send(transport: &LossyTransport, packets: usize) -> Vec<Vec<Duration>> {
+    (0..packets).map(|_| transport.outbound()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_faults() {
+        let transport = LossyTransport::default();
+        let sent = send(&transport, 100);
+        assert!(sent.iter().all(|copies| copies == &[Duration::ZERO]));
+        assert!((0..100).all(|_| transport.inbound()));
+
+        let stats = transport.stats();
+        assert_eq!(stats.sent, 100);
+        assert_eq!(stats.received, 100);
+        assert_eq!(stats.dropped + stats.duplicated + stats.delayed + stats.inbound_dropped, 0);
+    }
+
+    #[test]
+    fn test_faults() {
+        let profile = FaultProfile::lossy(0.2)
+            .with_duplication(0.1)
+            .with_delay(0.5, Duration::from_millis(10), Duration::from_millis(50))
+            .with_inbound_loss(0.1);
+        let transport = LossyTransport::with_seed(profile, 7);
+        let sent = send(&transport, 10_000);
+
+        let stats = transport.stats();
+        assert_eq!(stats.sent + stats.dropped, 10_000);
+        assert!((1_700..2_300).contains(&stats.dropped), "{stats:?}");
+        assert!((600..1_000).contains(&stats.duplicated), "{stats:?}");
+        assert_eq!(sent.iter().filter(|copies| copies.len() == 2).count() as u64, stats.duplicated);
+
+        let delays = sent.iter().flatten().filter(|d| !d.is_zero()).collect::<Vec<_>>();
+        assert_eq!(delays.len() as u64, stats.delayed);
+        assert!(delays.iter().all(|d| (10..=50).contains(&d.as_millis())));
+
+        // Same seed, same faults
+        let again = LossyTransport::with_seed(profile, 7);
+        assert_eq!(send(&again, 10_000), sent);
+
+        transport.set_profile(FaultProfile::default());
+        assert!(send(&transport, 100).iter().all(|copies| copies.len() == 1));
+    }
+
+    #[test]
+    fn test_offset_clock() {
+        let ahead = OffsetClock::new(60_000);
+        let behind = OffsetClock::new(-60_000);
+        let now = SystemTime::now();
+        assert!(ahead.now().duration_since(now).unwrap() >= Duration::from_secs(59));
+        assert!(now.duration_since(behind.now()).unwrap() >= Duration::from_secs(59));
+
+        behind.set_offset_ms(0);
+        assert_eq!(behind.offset_ms(), 0);
+        assert!(behind.now().duration_since(now).unwrap() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::{
+    sync::mpsc,
+    time::{self, Instant},
+};
+
+use crate::{
+    dht::rpc::receivers::Inbound,
+    testing::{LatencyModel, SimTransport},
+};
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([10, 0, 0, 1], port))
+}
+
+// Sends the numbered datagrams from the port, then delivers them all and
+// returns their numbers in the order they arrived, with the arrival times.
+async fn exchange(transport: &SimTransport, from: u16, rx: &mut mpsc::Receiver<Inbound>, count: u8) -> Vec<(u8, Duration)> {
+    let start = Instant::now();
+    for i in 0..count {
+        transport.send(addr(from), addr(1), &[i]);
+    }
+
+    let mut arrived = Vec::new();
+    while let Some(due) = transport.next_due() {
+        time::sleep_until(due).await;
+        transport.deliver_due(Instant::now());
+        while let Ok(inbound) = rx.try_recv() {
+            arrived.push((inbound.data[0], Instant::now() - start));
+        }
+    }
+    arrived
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ordered_by_arrival() {
+        let transport = SimTransport::new(7, LatencyModel::uniform(
+            Duration::from_millis(10), Duration::from_millis(90)
+        ));
+        let mut rx = transport.attach(addr(1));
+        let arrived = exchange(&transport, 2, &mut rx, 50).await;
+
+        assert_eq!(arrived.len(), 50);
+        assert!(arrived.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert!(arrived.iter().all(|(_, at)| (10..=90).contains(&at.as_millis())));
+        // Reordered by the latencies
+        assert!(arrived.windows(2).any(|w| w[0].0 > w[1].0));
+
+        // The same instant keeps the sending order.
+        transport.set_model(LatencyModel::fixed(Duration::from_millis(20)));
+        let arrived = exchange(&transport, 2, &mut rx, 10).await;
+        assert_eq!(arrived.iter().map(|(i, _)| *i).collect::<Vec<_>>(), (0..10).collect::<Vec<_>>());
+
+        let stats = transport.stats();
+        assert_eq!(stats.sent, 60);
+        assert_eq!(stats.delivered, 60);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_same_seed_same_fate() {
+        let model = LatencyModel::default().with_loss(0.3);
+        let mut runs = Vec::new();
+        for _ in 0..2 {
+            let transport = SimTransport::new(42, model);
+            let mut rx = transport.attach(addr(1));
+            // Traffic on another link does not change the fate of this one.
+            transport.send(addr(3), addr(1), &[255]);
+            let arrived = exchange(&transport, 2, &mut rx, 100).await;
+            runs.push(arrived);
+
+            let stats = transport.stats();
+            assert_eq!(stats.sent, 101);
+            assert_eq!(stats.lost + stats.delivered, 101);
+            assert!((15..=45).contains(&stats.lost), "{stats:?}");
+        }
+        let from2 = |run: &Vec<(u8, Duration)>| run.iter().filter(|(i, _)| *i != 255).cloned().collect::<Vec<_>>();
+        assert_eq!(from2(&runs[0]), from2(&runs[1]));
+
+        let other = SimTransport::new(43, model);
+        let mut rx = other.attach(addr(1));
+        assert_ne!(exchange(&other, 2, &mut rx, 100).await, from2(&runs[0]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unroutable() {
+        let transport = SimTransport::new(1, LatencyModel::fixed(Duration::from_millis(5)));
+        let rx = transport.attach(addr(1));
+        transport.send(addr(2), addr(9), &[0]);
+        transport.send(addr(2), addr(1), &[1]);
+        transport.detach(addr(1));
+        drop(rx);
+
+        time::sleep(Duration::from_millis(5)).await;
+        transport.deliver_due(Instant::now());
+        let stats = transport.stats();
+        assert_eq!(stats.unroutable, 2);
+        assert_eq!(stats.delivered, 0);
+        assert_eq!(transport.pending(), 0);
+    }
+}
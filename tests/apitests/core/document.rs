@@ -0,0 +1,217 @@
+use std::sync::{Arc, Mutex};
+use std::net::SocketAddr;
+use boson::{
+    signature,
+    Id,
+    Value,
+    NodeInfo,
+    PeerInfo,
+    PeerBuilder,
+    SignedBuilder,
+    ImmutableBuilder,
+    CryptoIdentity,
+    document::{Document, WithPrivate},
+};
+use crate::create_random_bytes;
+
+fn authenticated_peer() -> PeerInfo {
+    let node = CryptoIdentity::from(signature::KeyPair::random());
+    PeerBuilder::new("https://example.com:8443")
+        .with_node(Arc::new(Mutex::new(node)))
+        .with_sequence_number(7)
+        .with_fingerprint(42)
+        .with_extra(b"extra data")
+        .build()
+        .unwrap()
+}
+
+fn signed_value() -> Value {
+    SignedBuilder::new(&create_random_bytes(48))
+        .with_sequence_number(3)
+        .build()
+        .unwrap()
+}
+
+fn assert_value_eq(a: &Value, b: &Value) {
+    assert_eq!(a.id(), b.id());
+    assert_eq!(a.public_key(), b.public_key());
+    assert_eq!(a.recipient(), b.recipient());
+    assert_eq!(a.nonce(), b.nonce());
+    assert_eq!(a.signature(), b.signature());
+    assert_eq!(a.sequence_number(), b.sequence_number());
+    assert_eq!(a.data(), b.data());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_json() {
+        let peer = authenticated_peer();
+        let json = serde_json::to_string(&Document::new(peer.clone())).unwrap();
+
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["id"], peer.id().to_base58());
+        assert_eq!(v["nodeId"], peer.nodeid().unwrap().to_base58());
+        assert_eq!(v["endpoint"], peer.endpoint());
+        assert_eq!(v["seq"], 7);
+        assert_eq!(v["fingerprint"], 42);
+        assert!(v.get("privateKey").is_none());
+
+        let doc: Document<PeerInfo> = serde_json::from_str(&json).unwrap();
+        let decoded = doc.into_inner();
+        assert!(decoded.is_valid());
+        assert!(!decoded.has_private_key());
+        assert_eq!(decoded, peer.without_private_key());
+    }
+
+    #[test]
+    fn test_peer_cbor() {
+        let peer = authenticated_peer();
+        let cbor = serde_cbor::to_vec(&Document::new(peer.clone())).unwrap();
+
+        let doc: Document<PeerInfo> = serde_cbor::from_slice(&cbor).unwrap();
+        let decoded = doc.into_inner();
+        assert!(decoded.is_valid());
+        assert_eq!(decoded, peer.without_private_key());
+
+        // Binary formats carry ids as raw bytes
+        let v: serde_cbor::Value = serde_cbor::from_slice(&cbor).unwrap();
+        let serde_cbor::Value::Map(map) = v else {
+            panic!("expected a cbor map");
+        };
+        let id = map.get(&serde_cbor::Value::Text("id".into())).unwrap();
+        assert_eq!(id, &serde_cbor::Value::Bytes(peer.id().as_bytes().to_vec()));
+    }
+
+    #[test]
+    fn test_peer_private_key() {
+        let peer = authenticated_peer();
+        assert!(peer.has_private_key());
+
+        let json = serde_json::to_string(&Document::new(peer.clone()).with_private()).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(v.get("privateKey").is_some());
+
+        let doc: Document<PeerInfo, WithPrivate> = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc.inner(), &peer);
+
+        // Without opting in, the private key is dropped on read as well
+        let doc: Document<PeerInfo> = serde_json::from_str(&json).unwrap();
+        assert!(!doc.inner().has_private_key());
+    }
+
+    #[test]
+    fn test_peer_tampered() {
+        let peer = authenticated_peer();
+        let mut v = serde_json::to_value(Document::new(peer)).unwrap();
+        v["endpoint"] = "https://attacker.example.com".into();
+
+        let rc = serde_json::from_value::<Document<PeerInfo>>(v);
+        assert!(rc.is_err());
+    }
+
+    #[test]
+    fn test_peer_mismatched_private_key() {
+        let peer = authenticated_peer();
+        let other = signature::KeyPair::random();
+        let mut v = serde_json::to_value(Document::new(peer)).unwrap();
+        let other_doc = serde_json::to_value(Document::new(
+            PeerBuilder::new("http://localhost").with_key(other).build().unwrap()
+        ).with_private()).unwrap();
+        v["privateKey"] = other_doc["privateKey"].clone();
+
+        let rc = serde_json::from_value::<Document<PeerInfo, WithPrivate>>(v);
+        assert!(rc.is_err());
+    }
+
+    #[test]
+    fn test_node_info() {
+        let addr: SocketAddr = "192.168.1.10:39001".parse().unwrap();
+        let mut node = NodeInfo::new(Id::random(), addr);
+        node.set_version(5);
+
+        let json = serde_json::to_string(&Document::new(node.clone())).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["id"], node.id().to_base58());
+        assert_eq!(v["host"], "192.168.1.10");
+        assert_eq!(v["port"], 39001);
+
+        let decoded = serde_json::from_str::<Document<NodeInfo>>(&json).unwrap().into_inner();
+        assert_eq!(decoded, node);
+        assert_eq!(decoded.version(), 5);
+
+        let cbor = serde_cbor::to_vec(&Document::new(node.clone())).unwrap();
+        let decoded = serde_cbor::from_slice::<Document<NodeInfo>>(&cbor).unwrap().into_inner();
+        assert_eq!(decoded, node);
+        assert_eq!(decoded.version(), 5);
+    }
+
+    #[test]
+    fn test_value() {
+        let value = signed_value();
+
+        let json = serde_json::to_string(&Document::new(value.clone())).unwrap();
+        let v: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(v["publicKey"], value.public_key().unwrap().to_base58());
+        assert!(v.get("privateKey").is_none());
+        assert!(v.get("recipient").is_none());
+
+        let decoded = serde_json::from_str::<Document<Value>>(&json).unwrap().into_inner();
+        assert!(decoded.is_valid());
+        assert!(!decoded.has_private_key());
+        assert_value_eq(&decoded, &value);
+
+        let cbor = serde_cbor::to_vec(&Document::new(value.clone())).unwrap();
+        let decoded = serde_cbor::from_slice::<Document<Value>>(&cbor).unwrap().into_inner();
+        assert!(decoded.is_valid());
+        assert_value_eq(&decoded, &value);
+    }
+
+    #[test]
+    fn test_value_private_key() {
+        let value = signed_value();
+
+        let cbor = serde_cbor::to_vec(&Document::new(value.clone()).with_private()).unwrap();
+        let decoded = serde_cbor::from_slice::<Document<Value, WithPrivate>>(&cbor)
+            .unwrap()
+            .into_inner();
+        assert!(decoded.is_valid());
+        assert_eq!(decoded.private_key(), value.private_key());
+        assert_value_eq(&decoded, &value);
+    }
+
+    #[test]
+    fn test_value_tampered() {
+        let value = signed_value();
+        let mut v = serde_json::to_value(Document::new(value)).unwrap();
+        v["seq"] = 4.into();
+
+        let rc = serde_json::from_value::<Document<Value>>(v);
+        assert!(rc.is_err());
+    }
+
+    #[test]
+    fn test_immutable_value() {
+        let value = ImmutableBuilder::new(&create_random_bytes(32)).build().unwrap();
+
+        let cbor = serde_cbor::to_vec(&Document::new(value.clone())).unwrap();
+        let decoded = serde_cbor::from_slice::<Document<Value>>(&cbor).unwrap().into_inner();
+        assert!(decoded.is_valid());
+        assert!(!decoded.is_mutable());
+        assert_value_eq(&decoded, &value);
+    }
+
+    #[test]
+    fn test_wire_format_unchanged() {
+        // The document representation is separate from the DHT encoding
+        let peer = authenticated_peer();
+        let wire = serde_cbor::to_vec(&peer).unwrap();
+        let doc = serde_cbor::to_vec(&Document::new(peer.clone())).unwrap();
+        assert_ne!(wire, doc);
+
+        let decoded: PeerInfo = serde_cbor::from_slice(&wire).unwrap();
+        assert_eq!(decoded, peer.without_private_key());
+    }
+}
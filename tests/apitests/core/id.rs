@@ -209,6 +209,18 @@ mod tests {
         assert_eq!(id1, id4);
     }
 
+    #[test]
+    fn test_try_from_did_string() {
+        let base58 = "HZXXs9LTfNQjrDKvvexRhuMk8TTJhYCfrHwaj3jUzuhZ";
+        let id = Id::try_from(base58).unwrap();
+        let result = Id::try_from_did_string(&id.to_did_string());
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), id);
+
+        assert_eq!(Id::try_from_did_string(base58).is_err(), true);
+        assert_eq!(Id::try_from_did_string("did:boson:0x00").is_err(), true);
+    }
+
     #[test]
     fn test_try_from_signature_publickey() {
         let kp = signature::KeyPair::random();
@@ -1,4 +1,5 @@
 use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 use boson::{
     Id,
     PeerInfo,
@@ -128,15 +129,18 @@ mod tests {
         let kp = signature::KeyPair::random();
         let mut nonce = vec![0u8; PeerInfo::NONCE_BYTES];
         rand::fill(&mut nonce);
+        let announced = SystemTime::now();
         let rc1 = PeerBuilder::new(endpoint)
             .with_key(kp.clone())
             .with_nonce(&nonce)
+            .with_announced_time(announced)
             .build();
         let peer1 = rc1.unwrap();
 
         let rc2 = PeerBuilder::new(endpoint)
             .with_key(kp)
             .with_nonce(&nonce)
+            .with_announced_time(announced)
             .build();
         let peer2 = rc2.unwrap();
 
@@ -161,12 +165,14 @@ mod tests {
         let peer_kp = signature::KeyPair::random();
         let mut nonce = vec![0u8; PeerInfo::NONCE_BYTES];
         rand::fill(&mut nonce);
+        let announced = SystemTime::now();
         let rc = PeerBuilder::new(endpoint)
             .with_key(peer_kp.clone())
             .with_nonce(&nonce)
             .with_node(node.clone())
             .with_sequence_number(101)
             .with_fingerprint(100)
+            .with_announced_time(announced)
             .build();
         let peer1 = rc.unwrap();
 
@@ -176,12 +182,33 @@ mod tests {
             .with_node(node.clone())
             .with_sequence_number(101)
             .with_fingerprint(100)
+            .with_announced_time(announced)
             .build();
         let peer2 = rc.unwrap();
 
         assert_eq!(peer1, peer2);
     }
 
+    #[test]
+    fn test_bytes() {
+        let peer = PeerBuilder::new("http://localhost:8080")
+            .with_fingerprint(100)
+            .with_extra(b"extra")
+            .build()
+            .unwrap();
+
+        let bytes = Vec::from(&peer);
+        let result = PeerInfo::try_from(bytes.as_slice());
+        assert_eq!(result.is_ok(), true);
+
+        let decoded = result.unwrap();
+        assert_eq!(decoded, peer.without_private_key());
+        assert_eq!(decoded.announced_time(), peer.announced_time());
+        assert_eq!(decoded.is_valid(), true);
+
+        assert_eq!(PeerInfo::try_from(&bytes[..bytes.len() - 1]).is_err(), true);
+    }
+
     #[test] // case9
     fn test_equal_partial() {
         let endpoint = "http://localhost:8080";
@@ -305,4 +305,46 @@ mod tests {
         assert_eq!(val.private_key(), Some(kp.private_key()));
         assert_eq!(val.id(), value::value_id(&val));
     }
+
+    #[test]
+    fn test_decrypt() {
+        let data = create_random_bytes(32);
+        let kp = signature::KeyPair::random();
+        let rec_kp = signature::KeyPair::random();
+        let rec: Id = rec_kp.public_key().into();
+        let val = EncryptedBuilder::new(&data, &rec)
+            .with_keypair(&kp)
+            .build()
+            .unwrap();
+
+        let result = val.decrypt(&rec_kp);
+        assert_eq!(result.is_ok(), true);
+        assert_eq!(result.unwrap(), data);
+
+        assert_eq!(val.decrypt(&signature::KeyPair::random()).is_err(), true);
+        let signed = SignedBuilder::new(&data).build().unwrap();
+        assert_eq!(signed.decrypt(&rec_kp).is_err(), true);
+    }
+
+    #[test]
+    fn test_bytes() {
+        let data = create_random_bytes(32);
+        let val = SignedBuilder::new(&data)
+            .with_sequence_number(7)
+            .build()
+            .unwrap();
+
+        let bytes = Vec::from(&val);
+        let result = Value::try_from(bytes.as_slice());
+        assert_eq!(result.is_ok(), true);
+
+        let decoded = result.unwrap();
+        assert_eq!(decoded.id(), val.id());
+        assert_eq!(decoded.sequence_number(), 7);
+        assert_eq!(decoded.data(), val.data());
+        assert_eq!(decoded.is_valid(), true);
+        assert_eq!(decoded.private_key().is_none(), true);
+
+        assert_eq!(Value::try_from(&bytes[1..]).is_err(), true);
+    }
 }
@@ -1,19 +1,46 @@
 use std::{
     fs,
-    sync::Arc,
-    time::Duration,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
 };
 use serial_test::serial;
+use futures::{Stream, StreamExt};
 use boson::{
+    Id,
+    ConnectionStatus,
+    Clock,
+    ManualClock,
+    CryptoIdentity,
+    Network,
+    NodeInfo,
+    ResultSource,
     signature,
     cryptobox::{Nonce, CryptoBox},
     core::{
         PeerBuilder, Result,
         ImmutableBuilder as ValueBuilder,
+        SignedBuilder,
+        NAME_NAMESPACE,
     },
+    kv::{KvStore, Freshness, Conflict, Resolution},
     dht::{
+        stats,
+        seed,
+        NodeConfig,
         NodeConfiguration,
+        NodeEventKind,
+        StorageEvent,
+        LookupOption,
+        LookupOptionsEx,
         Node,
+        MAX_EXTENSION_PAYLOAD,
+        ProbePattern,
+        NodeStatusEvent,
+        StreamItem,
+        BlockTarget,
+        BlockReason,
+        SocketEvent,
+        Prefix,
     },
 };
 use crate::{
@@ -21,10 +48,46 @@ use crate::{
     remove_working_path,
 };
 
-fn working_path(input: &str) -> String {
+// The working directory of a test node under the system temp directory,
+// removed when dropped, so a failed test leaves nothing behind either.
+struct WorkingPath(String);
+
+impl std::ops::Deref for WorkingPath {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<std::ffi::OsStr> for WorkingPath {
+    fn as_ref(&self) -> &std::ffi::OsStr {
+        self.0.as_ref()
+    }
+}
+
+impl AsRef<std::path::Path> for WorkingPath {
+    fn as_ref(&self) -> &std::path::Path {
+        self.0.as_ref()
+    }
+}
+
+impl std::fmt::Display for WorkingPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Drop for WorkingPath {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn working_path(input: &str) -> WorkingPath {
     let random_suffix = format!("{:016x}", rand::random::<u64>());
 
-    let path = std::env::current_dir().unwrap().join(format!("{input}-{random_suffix}"));
+    let path = std::env::temp_dir().join(format!("boson-{input}-{random_suffix}"));
     if !std::fs::metadata(&path).is_ok() {
         match std::fs::create_dir(&path) {
             Ok(_) => {}
@@ -33,7 +96,7 @@ fn working_path(input: &str) -> String {
             }
         }
     }
-    path.display().to_string()
+    WorkingPath(path.display().to_string())
 }
 
 fn cleanup_path(input: &str) {
@@ -41,19 +104,39 @@ fn cleanup_path(input: &str) {
 }
 
 fn create_node(port: u16, path: &str) -> Result<Arc<Node>> {
+    create_node_with(port, path, "")
+}
+
+fn create_node_with(port: u16, path: &str, extra: &str) -> Result<Arc<Node>> {
+    Ok(Node::new(Box::new(node_config(port, path, extra)?))?)
+}
+
+fn node_config(port: u16, path: &str, extra: &str) -> Result<NodeConfiguration> {
     let private_key = signature::KeyPair::random().private_key().to_string();
     let config_path = format!("{path}/node.yaml");
     let yaml = format!(
-        "ipv4: true\nport: {}\nprivateKey: \"{}\"\ndataDir: {}\ndatabaseUri: {}\nlogLevel: \"debug\"\n",
+        "ipv4: true\nport: {}\nprivateKey: \"{}\"\ndataDir: {}\ndatabaseUri: {}\nlogLevel: \"debug\"\n{}",
         port,
         private_key,
         path,
         format!("jdbc:sqlite:node.db"),
+        extra,
     );
 
     fs::write(&config_path, yaml)?;
-    let cfg = NodeConfiguration::load(&config_path).unwrap();
-    Ok(Node::new(Box::new(cfg))?)
+    Ok(NodeConfiguration::load(&config_path).unwrap())
+}
+
+// Names of all threads of this process.
+fn thread_names() -> Vec<String> {
+    fs::read_dir("/proc/self/task").unwrap()
+        .filter_map(|entry| fs::read_to_string(entry.ok()?.path().join("comm")).ok())
+        .map(|name| name.trim_end().to_string())
+        .collect()
+}
+
+fn count_threads(names: &[String], prefix: &str) -> usize {
+    names.iter().filter(|name| name.starts_with(prefix)).count()
 }
 
 #[cfg(test)]
@@ -290,6 +373,7 @@ mod tests {
 
                 found.v4().map(|ni| {
                     assert!(ni.id() == node2.id());
+                    assert_eq!(ni.normalized_version(), node2.version());
                     println!("\x1b[31mfound target {} on node {}\x1b[0m",
                         node2.id(), node1.id());
                 });
@@ -844,4 +928,2607 @@ mod tests {
         remove_working_path(&path2);
         remove_working_path(&path3);
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_recent_events() {
+        use diesel::{Connection, RunQueryDsl, SqliteConnection};
+
+        let path = working_path("node1");
+        let node = create_node(32240, &path).unwrap();
+        if let Err(e) = node.start().await {
+            panic!("Failed to start node: {e}");
+        }
+
+        // break the storage underneath the running node.
+        let db_path = node.storage_path().join("node.db");
+        let mut conn = SqliteConnection::establish(db_path.to_str().unwrap()).unwrap();
+        diesel::sql_query("DROP TABLE valores").execute(&mut conn).unwrap();
+
+        let data = create_random_bytes(32);
+        let value = ValueBuilder::new(&data)
+            .build()
+            .expect("Failed to build value");
+        let result = node.value(value.id());
+        assert!(result.is_err());
+
+        let events = node.recent_events(64);
+        let failed = events.iter().position(|e| {
+            matches!(e.kind(), NodeEventKind::BootstrapFailed { .. })
+        });
+        let storage = events.iter().position(|e| {
+            matches!(e.kind(), NodeEventKind::StorageError { op: "get_value" })
+        });
+        assert!(failed.is_some());
+        assert!(storage.is_some());
+        assert!(failed < storage);
+        assert!(events[failed.unwrap()].network().is_some());
+        assert!(events[storage.unwrap()].network().is_none());
+
+        let events = node.recent_events(1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind(), &NodeEventKind::StorageError { op: "get_value" });
+
+        _ = node.stop().await;
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_closest_nodes() {
+        let path = working_path("node1");
+        let node = create_node(32242, &path).unwrap();
+        if let Err(e) = node.start().await {
+            panic!("Failed to start node: {e}");
+        }
+
+        let target = Id::random();
+        let nodes = node.closest_nodes(&target, 8, None, false).await.unwrap();
+        assert!(nodes.is_empty());
+
+        let nodes = node.closest_nodes(&target, 8, None, true).await.unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id(), node.id());
+
+        let nodes = node.closest_nodes(&target, 8, Some(Network::IPv4), true).await.unwrap();
+        assert_eq!(nodes.len(), 1);
+
+        assert!(node.closest_nodes(&target, 8, Some(Network::IPv6), true).await.is_err());
+        assert!(node.closest_nodes(&target, 0, None, true).await.is_err());
+
+        _ = node.stop().await;
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_routing_table_snapshot() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32244, &path1).unwrap();
+        let node2 = create_node_with(32246, &path2, "bucketRefreshInterval: 1\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let buckets = node2.routing_table_snapshot(Network::IPv4).await.unwrap();
+        assert!(!buckets.is_empty());
+        assert_eq!(buckets.iter().map(|b| b.entries()).sum::<usize>(), 1);
+        assert!(buckets.iter().any(|b| b.versions().get(&node1.version()) == Some(&1)));
+        assert!(buckets.iter().any(|b| b.is_home_bucket()));
+        assert!(buckets.iter().all(|b| b.last_activity() <= std::time::SystemTime::now()));
+        assert!(node2.routing_table_snapshot(Network::IPv6).await.is_err());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_extension_rpc() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32244, &path1).unwrap();
+        let node2 = create_node(32246, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        let sender = node1.id().clone();
+        node2.set_extension_handler(Box::new(move |from, data| {
+            assert_eq!(from.id(), &sender);
+            match data {
+                b"reject" => None,
+                _ => Some([b"echo:".as_slice(), data].concat()),
+            }
+        }));
+
+        let timeout = Duration::from_secs(5);
+        let target = node2.node_info();
+        let rsp = node1.send_extension(&target, b"hello".to_vec(), timeout).await.unwrap();
+        assert_eq!(rsp, b"echo:hello");
+
+        let rsp = node1.send_extension(&target, Vec::new(), timeout).await.unwrap();
+        assert_eq!(rsp, b"echo:");
+
+        // Rejected by the handler
+        let result = node1.send_extension(&target, b"reject".to_vec(), timeout).await;
+        assert!(result.is_err());
+
+        // No handler registered on node1
+        let result = node2.send_extension(&node1.node_info(), b"hello".to_vec(), timeout).await;
+        assert!(result.is_err());
+        assert!(node1.is_running());
+
+        let payload = vec![0u8; MAX_EXTENSION_PAYLOAD + 1];
+        assert!(node1.send_extension(&target, payload, timeout).await.is_err());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_stats_journal() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node_with(32248, &path1, "statsInterval: 1\n").unwrap();
+        let node2 = create_node_with(32250, &path2, "statsInterval: 1\nstatsMaxFileSize: 200\nstatsMaxFiles: 2\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        _ = node1.find_node(node2.id(), None).await;
+
+        tokio::time::sleep(Duration::from_millis(3500)).await;
+
+        let journal = node1.stats_journal_path().unwrap();
+        assert_eq!(journal, node1.storage_path().join("stats.log"));
+        let samples = stats::read_journal(&journal);
+        assert!(samples.len() >= 2);
+        assert!(samples.windows(2).all(|w| w[0].timestamp() <= w[1].timestamp()));
+        assert!(samples.iter().all(|s| s.network(Network::IPv6).is_none()));
+
+        let ipv4 = samples.iter()
+            .filter_map(|s| s.network(Network::IPv4))
+            .collect::<Vec<_>>();
+        assert_eq!(ipv4.len(), samples.len());
+        assert!(ipv4.iter().map(|s| s.msgs_received()).sum::<u64>() > 0);
+        assert!(ipv4.iter().map(|s| s.msgs_sent()).sum::<u64>() > 0);
+        assert!(ipv4.last().unwrap().routing_entries() >= 1);
+        assert!(ipv4.last().unwrap().versions().contains_key(&node2.version()));
+
+        // Every sample overflows the tiny cap, so node2 rotates on each write
+        let journal = node2.stats_journal_path().unwrap();
+        assert_eq!(stats::read_journal(&journal).len(), 1);
+        assert_eq!(stats::read_journal(stats::rotated_path(&journal, 1)).len(), 1);
+        assert!(stats::rotated_path(&journal, 2).exists());
+        assert!(!stats::rotated_path(&journal, 3).exists());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_caller_runtime() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let before = thread_names();
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("caller-rt")
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let node1 = Node::with_runtime(Box::new(node_config(32252, &path1, "").unwrap()), rt.handle().clone()).unwrap();
+        let node2 = Node::with_runtime(Box::new(node_config(32254, &path2, "").unwrap()), rt.handle().clone()).unwrap();
+        assert!(node1.runtime().is_some());
+
+        // Reports where the DHT thread of node2 is driven from
+        node2.set_extension_handler(Box::new(|_, _| {
+            let thread = std::thread::current().name().unwrap_or_default().to_string();
+            let flavor = tokio::runtime::Handle::try_current()
+                .map(|h| format!("{:?}", h.runtime_flavor()))
+                .unwrap_or_default();
+            Some(format!("{thread}:{flavor}").into_bytes())
+        }));
+
+        let during = rt.block_on(async {
+            let (rc1, rc2) = tokio::join!(
+                node1.start(),
+                node2.start()
+            );
+            _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+            _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+            _ = node2.bootstrap_one(&node1.node_info()).await
+                .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+
+            let found = node1.find_node(node2.id(), None).await.unwrap();
+            assert_eq!(found.v4().map(|ni| ni.id()), Some(node2.id()));
+
+            let rsp = node1.send_extension(&node2.node_info(), Vec::new(), Duration::from_secs(5)).await.unwrap();
+            assert_eq!(String::from_utf8(rsp).unwrap(), "boson-dht4:MultiThread");
+
+            let during = thread_names();
+            let _ = tokio::join!(
+                node1.stop(),
+                node2.stop()
+            );
+            during
+        });
+        drop(rt);
+
+        // Only the dedicated DHT and timer threads, no runtime threads of their own
+        assert_eq!(count_threads(&during, "caller-rt"), count_threads(&before, "caller-rt") + 2);
+        assert_eq!(count_threads(&during, "boson-dht4"), count_threads(&before, "boson-dht4") + 2);
+        assert_eq!(count_threads(&during, "boson-timer"), count_threads(&before, "boson-timer") + 2);
+        assert_eq!(count_threads(&during, "tokio-"), count_threads(&before, "tokio-"));
+
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_storage_recovery() {
+        let path = working_path("node1");
+        let db_path = std::path::Path::new(&path).join("node1").join("node.db");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        fs::write(&db_path, create_random_bytes(8192)).unwrap();
+
+        let node = create_node_with(32256, &path, "instanceName: node1").unwrap();
+        if let Err(e) = node.start().await {
+            panic!("Failed to start node on a corrupt database: {e}");
+        }
+
+        let events = node.recent_events(64);
+        let moved_to = events.iter().find_map(|e| match e.kind() {
+            NodeEventKind::StorageRecovered { moved_to } => Some(moved_to.clone()),
+            _ => None,
+        }).expect("No storage recovery recorded");
+        assert_eq!(moved_to.parent(), db_path.parent());
+        assert!(moved_to.file_name().unwrap().to_str().unwrap().starts_with("node.db.corrupt-"));
+        assert_eq!(fs::metadata(&moved_to).unwrap().len(), 8192);
+
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build value");
+        _ = node.store_value(&value, -1, false).await;
+        assert!(node.value(value.id()).unwrap().is_some());
+
+        let report = node.check_storage_integrity().unwrap();
+        assert!(report.is_ok(), "{report:?}");
+        assert_eq!(report.values_checked(), 1);
+
+        _ = node.stop().await;
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_storage_newer_version() {
+        let path = working_path("node1");
+        let db_path = std::path::Path::new(&path).join("node1").join("node.db");
+        let node = create_node_with(32422, &path, "instanceName: node1").unwrap();
+        if let Err(e) = node.start().await {
+            panic!("Failed to start node: {e}");
+        }
+        _ = node.stop().await;
+        drop(node);
+
+        // A schema version no build knows, the user_version at offset 60 of
+        // the header.
+        let mut data = fs::read(&db_path).unwrap();
+        data[60..64].copy_from_slice(&999u32.to_be_bytes());
+        fs::write(&db_path, &data).unwrap();
+
+        let node = create_node_with(32422, &path, "instanceName: node1").unwrap();
+        assert!(node.start().await.is_err());
+        assert!(!node.recent_events(64).iter()
+            .any(|e| matches!(e.kind(), NodeEventKind::StorageRecovered { .. })));
+
+        // Left in place, not moved aside.
+        assert_eq!(fs::read(&db_path).unwrap(), data);
+        let moved = fs::read_dir(db_path.parent().unwrap()).unwrap()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name().to_string_lossy().starts_with("node.db.corrupt-"));
+        assert!(!moved);
+
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_storage_maintenance() {
+        let path = working_path("node1");
+        let node = create_node_with(32258, &path, "storageMaintenanceInterval: 1\n").unwrap();
+        if let Err(e) = node.start().await {
+            panic!("Failed to start node: {e}");
+        }
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+
+        let maintained = node.recent_events(64).iter()
+            .filter(|e| matches!(e.kind(), NodeEventKind::StorageMaintained { .. }))
+            .count();
+        assert!(maintained >= 2);
+
+        _ = node.stop().await;
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_node_with_hint() {
+        let paths = ["node1", "node2", "node3", "node4"].map(working_path);
+        let nodes = [32260, 32262, 32264, 32266].iter().zip(paths.iter())
+            .map(|(port, path)| create_node(*port, path).unwrap())
+            .collect::<Vec<_>>();
+        let [node1, node2, node3, node4] = [0, 1, 2, 3].map(|i| nodes[i].clone());
+        for node in nodes.iter() {
+            _ = node.start().await.map_err(|e| panic!("Failed to start node: {e}"));
+        }
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        // node3 is known to node1 only
+        _ = node1.bootstrap_one(&node3.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node3 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // node1 is fresh in the routing table of node2, no lookup task is created
+        let active = node2.active_tasks().await;
+        let found = node2.find_node_with_hint(node1.id(), None, Some(LookupOption::Optimistic)).await.unwrap();
+        assert_eq!(found.v4().map(|ni| *ni.id()), Some(*node1.id()));
+        assert_eq!(found.source(Network::IPv4), Some(ResultSource::Cache));
+        assert!(node2.active_tasks().await <= active);
+
+        // The hinted address belongs to node1 now, the ping times out
+        let stale = NodeInfo::new(*node3.id(), *node1.node_info().socket_addr());
+        let found = node2.find_node_with_hint(node3.id(), Some(&stale), Some(LookupOption::Optimistic)).await.unwrap();
+        assert_eq!(found.source(Network::IPv4), Some(ResultSource::Lookup));
+        assert_eq!(found.v4().unwrap().socket_addr(), node3.node_info().socket_addr());
+
+        // node4 is in nobody's routing table, only the hint can find it
+        let hint = node4.node_info();
+        let found = node2.find_node_with_hint(node4.id(), Some(&hint), Some(LookupOption::Optimistic)).await.unwrap();
+        assert_eq!(found.v4(), Some(&hint));
+        assert_eq!(found.source(Network::IPv4), Some(ResultSource::Hint));
+
+        let wrong = NodeInfo::new(Id::random(), *hint.socket_addr());
+        assert!(node2.find_node_with_hint(node4.id(), Some(&wrong), None).await.is_err());
+
+        for node in nodes.iter() {
+            _ = node.stop().await;
+        }
+        for path in paths.iter() {
+            cleanup_path(path);
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_send_shaping() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32268, &path1).unwrap();
+        let node2 = create_node_with(32270, &path2,
+            "sendRate: 10\nsendRateInterval: 1000\nsendBurst: 2\nstatsInterval: 1\n"
+        ).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        // The lookups still complete, just more slowly
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        let found = node2.find_node(node1.id(), None).await.unwrap();
+        assert_eq!(found.v4().map(|ni| *ni.id()), Some(*node1.id()));
+
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+
+        let samples = stats::read_journal(node2.stats_journal_path().unwrap());
+        let shaped = samples.iter()
+            .filter_map(|s| s.network(Network::IPv4))
+            .map(|s| s.msgs_shaped())
+            .sum::<u64>();
+        assert!(shaped > 0);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_listening_port_per_network() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+
+        // A distinct IPv6 port without any IPv6 address is rejected
+        let cfg = node_config(32272, &path1, "").unwrap().with_listening_port6(32273);
+        assert!(Node::new(Box::new(cfg)).is_err());
+
+        let node1 = create_node_with(32272, &path1, "ipv6: true\nport6: 32273\n").unwrap();
+        let node2 = create_node_with(32274, &path2, "ipv6: true\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        let ni4 = node1.node_info_of(Network::IPv4).unwrap();
+        let ni6 = node1.node_info_of(Network::IPv6).unwrap();
+        assert_eq!(ni4.port(), 32272);
+        assert_eq!(ni6.port(), 32273);
+        assert_eq!(ni6.network(), Network::IPv6);
+
+        _ = node2.bootstrap(&[ni4.clone(), ni6.clone()]).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // Each routing table of node2 holds node1 with its own family's port
+        let nodes4 = node2.closest_nodes(node1.id(), 1, Some(Network::IPv4), false).await.unwrap();
+        let nodes6 = node2.closest_nodes(node1.id(), 1, Some(Network::IPv6), false).await.unwrap();
+        assert_eq!(nodes4, vec![ni4]);
+        assert_eq!(nodes6, vec![ni6]);
+
+        let found = node2.find_node(node1.id(), None).await.unwrap();
+        assert!(found.v4().is_some() || found.v6().is_some());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_coalesced_find_value() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32276, &path1).unwrap();
+        let node2 = create_node_with(32278, &path2, "lookupCacheTtl: 3000\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build immutable value");
+        _ = node1.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+
+        // Only node1 keeps the value, node2 has to look it up.
+        let value_id = value.id();
+        _ = node2.remove_value(value_id.clone());
+
+        let lookups = node2.value_lookups().await;
+        let results = tokio::join!(
+            node2.find_value(&value_id, -1, None),
+            node2.find_value(&value_id, -1, None),
+            node2.find_value(&value_id, -1, None),
+            node2.find_value(&value_id, -1, None),
+            node2.find_value(&value_id, -1, None)
+        );
+        for result in [results.0, results.1, results.2, results.3, results.4] {
+            let found = result.unwrap().expect("Should have found the value");
+            assert_eq!(found.id(), value_id);
+            assert_eq!(found.data(), value.data());
+        }
+        assert_eq!(node2.value_lookups().await, lookups + 1);
+
+        // A repeated lookup within the ttl reuses the result.
+        let missing = Id::random();
+        assert!(node2.find_value(&missing, -1, None).await.unwrap().is_none());
+        assert!(node2.find_value(&missing, -1, None).await.unwrap().is_none());
+        assert_eq!(node2.value_lookups().await, lookups + 2);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_value_by_key() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32280, &path1).unwrap();
+        let node2 = create_node(32282, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let app = Id::random();
+        let data = create_random_bytes(32);
+        let value = SignedBuilder::world_writable(&app, "settings", &data)
+            .build()
+            .expect("Failed to build signed value");
+        _ = node1.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        _ = node2.remove_value(value.id());
+
+        let found = node2.find_value_by_key(&app, "settings", -1, None).await
+            .expect("Failed to find value")
+            .expect("Should have found the value");
+        assert_eq!(found.id(), value.id());
+        assert_eq!(found.data(), data.as_slice());
+
+        let result = node2.find_value_by_key(&app, "other", -1, None).await;
+        assert!(result.unwrap().is_none());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_lookup_age() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let clock = Arc::new(ManualClock::default());
+        let node1 = Node::with_clock(Box::new(node_config(32284, &path1, "").unwrap()), clock.clone()).unwrap();
+        let node2 = create_node(32286, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build immutable value");
+        let peer = PeerBuilder::new("https://example.com")
+            .build()
+            .expect("Failed to build peer");
+        _ = node1.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        _ = node1.announce_peer(&peer, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+
+        // Only node1 keeps them, node2 has to look them up.
+        _ = node2.remove_value(value.id());
+        _ = node2.remove_peer(peer.id().clone(), peer.fingerprint()).await;
+
+        clock.advance(Duration::from_secs(90));
+
+        let result = node2.find_value_detailed(&value.id(), -1, None).await
+            .expect("Failed to find value")
+            .expect("Should have found the value");
+        assert_eq!(result.value().data(), value.data());
+        assert_eq!(result.sources(), &[*node1.id()]);
+        let age = result.min_age().expect("Should have an age").as_secs();
+        assert!((90..=92).contains(&age), "age {age}");
+
+        let results = node2.find_peer_detailed(peer.id(), -1, 1, None).await
+            .expect("Failed to find peer");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].peer().endpoint(), peer.endpoint());
+        assert_eq!(results[0].sources(), &[*node1.id()]);
+        let age = results[0].min_age().expect("Should have an age").as_secs();
+        assert!((90..=92).contains(&age), "age {age}");
+
+        // Kept by node2 now, answered from its own storage.
+        let result = node2.find_value_detailed(&value.id(), -1, None).await
+            .expect("Failed to find value")
+            .expect("Should have found the value");
+        assert_eq!(result.sources(), &[*node2.id()]);
+        assert!(result.min_age().unwrap() < Duration::from_secs(5));
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_peer_filtered() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32390, &path1).unwrap();
+        let node2 = create_node(32392, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // One service reachable over IPv4 and another over IPv6, one id.
+        let kp = signature::KeyPair::random();
+        let v4 = PeerBuilder::new("tcp://203.0.113.7:8080")
+            .with_key(kp.clone())
+            .with_fingerprint(1)
+            .build()
+            .expect("Failed to build peer");
+        let v6 = PeerBuilder::new("tcp://[2001:db8::7]:8080")
+            .with_key(kp)
+            .with_fingerprint(2)
+            .build()
+            .expect("Failed to build peer");
+        for peer in [&v4, &v6] {
+            _ = node1.announce_peer(peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+
+        // Only node1 keeps them, node2 has to look them up.
+        for peer in [&v4, &v6] {
+            _ = node2.remove_peer(peer.id().clone(), peer.fingerprint()).await;
+        }
+
+        for (network, expected) in [(Network::IPv4, &v4), (Network::IPv6, &v6)] {
+            let peers = node2.find_peer_filtered(v4.id(), -1, 2, None, Some(network)).await
+                .expect("Failed to find peer");
+            assert_eq!(peers.len(), 1);
+            assert_eq!(peers[0].endpoint(), expected.endpoint());
+            assert_eq!(peers[0].network(), Some(network));
+        }
+
+        let peers = node2.find_peer_filtered(v4.id(), -1, 2, None, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 2);
+
+        // Answered from the storage of node2 now, filtered the same.
+        let peers = node2.find_peer_filtered(v4.id(), -1, 2, Some(LookupOption::Local), Some(Network::IPv6)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].endpoint(), v6.endpoint());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_peer_tagged() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32302, &path1).unwrap();
+        let node2 = create_node(32304, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // Two services under one id, only one of them speaks TLS.
+        let kp = signature::KeyPair::random();
+        let tls = PeerBuilder::new("https://example.com:8443")
+            .with_key(kp.clone())
+            .with_fingerprint(1)
+            .with_tags(&["proto/2", "tls"])
+            .build()
+            .expect("Failed to build peer");
+        let plain = PeerBuilder::new("http://example.com:8080")
+            .with_key(kp)
+            .with_fingerprint(2)
+            .with_tags(&["proto/1"])
+            .build()
+            .expect("Failed to build peer");
+        for peer in [&tls, &plain] {
+            _ = node1.announce_peer(peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+
+        // Only node1 keeps them, node2 has to look them up.
+        for peer in [&tls, &plain] {
+            _ = node2.remove_peer(peer.id().clone(), peer.fingerprint()).await;
+        }
+
+        let peers = node2.find_peer_tagged(tls.id(), &["tls"], -1, 2, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].endpoint(), tls.endpoint());
+        assert_eq!(peers[0].tags(), tls.tags());
+
+        let peers = node2.find_peer_tagged(tls.id(), &["tls", "proto/1"], -1, 2, None).await
+            .expect("Failed to find peer");
+        assert!(peers.is_empty());
+
+        let peers = node2.find_peer(tls.id(), -1, 2, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 2);
+
+        // Answered from the storage of node2 now, filtered the same.
+        let peers = node2.find_peer_tagged(tls.id(), &["proto/1"], -1, 2, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].endpoint(), plain.endpoint());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_peer_one() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32324, &path1).unwrap();
+        let node2 = create_node(32326, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // Three instances of one service, the last one drained.
+        let kp = signature::KeyPair::random();
+        let id = Id::from(kp.public_key());
+        let instances = [(1, 2), (2, 4), (3, 0)].map(|(i, weight)| {
+            PeerBuilder::new(&format!("https://example.com:844{i}"))
+                .with_key(kp.clone())
+                .with_fingerprint(i)
+                .with_sequence_number(1)
+                .with_weight(weight)
+                .build()
+                .expect("Failed to build peer")
+        });
+        for peer in instances.iter() {
+            _ = node1.announce_peer(peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+        for peer in instances.iter() {
+            _ = node2.remove_peer(peer.id().clone(), peer.fingerprint()).await;
+        }
+
+        // The weights come along with the lookup.
+        let peers = node2.find_peer(&id, -1, 3, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 3);
+        for peer in peers.iter() {
+            let announced = instances.iter().find(|v| v.fingerprint() == peer.fingerprint()).unwrap();
+            assert_eq!(peer.weight(), announced.weight());
+        }
+
+        for _ in 0..8 {
+            let peer = node2.find_peer_one(&id, Some(LookupOption::Local)).await
+                .expect("Failed to find peer")
+                .expect("No peer chosen");
+            assert_ne!(peer.weight(), 0);
+        }
+        assert!(node2.find_peer_one(&Id::random(), Some(LookupOption::Local)).await
+            .expect("Failed to find peer")
+            .is_none());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_find_peer_newest_announcement() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32424, &path1).unwrap();
+        let node2 = create_node(32426, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // The service restarted on another port, its announcement rebuilt
+        // with the same sequence number.
+        let kp = signature::KeyPair::random();
+        let id = Id::from(kp.public_key());
+        let now = SystemTime::now();
+        let announcement = |port: u16, time: SystemTime| PeerBuilder::new(&format!("https://example.com:{port}"))
+            .with_key(kp.clone())
+            .with_fingerprint(7)
+            .with_announced_time(time)
+            .build()
+            .expect("Failed to build peer");
+        let v1 = announcement(8441, now - Duration::from_secs(60));
+        let v2 = announcement(8442, now);
+        assert!(v2.announcement_version() > v1.announcement_version());
+
+        _ = node1.announce_peer(&v1, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        _ = node2.announce_peer(&v2, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+
+        for node in [&node1, &node2] {
+            let peers = node.find_peer(&id, -1, 4, None).await
+                .expect("Failed to find peer");
+            assert_eq!(peers.len(), 1);
+            assert_eq!(peers[0].endpoint(), v2.endpoint());
+        }
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_store_value_countersigned() {
+        let service = signature::KeyPair::random();
+        let service_id = Id::from(service.public_key());
+
+        // Only node1 requires the values to be counter-signed.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node_with(32306, &path1, &format!("requireCountersigner: {}\n", service_id)).unwrap();
+        let node2 = create_node(32308, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let plain = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build value");
+        let approved = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .with_countersigner_slot(&service_id)
+            .build()
+            .and_then(|v| v.countersign(&service))
+            .expect("Failed to countersign value");
+        let forged = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .and_then(|v| v.countersign(&signature::KeyPair::random()))
+            .expect("Failed to countersign value");
+
+        assert!(node1.store_value(&plain, -1, false).await.is_err());
+        assert!(node1.store_value(&forged, -1, false).await.is_err());
+        assert!(node1.store_value(&approved, -1, false).await.is_ok());
+
+        // node2 passes them through, node1 only keeps the approved ones.
+        let remote = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .with_countersigner_slot(&service_id)
+            .build()
+            .and_then(|v| v.countersign(&service))
+            .expect("Failed to countersign value");
+        for value in [&plain, &forged, &remote] {
+            _ = node2.store_value(value, -1, false).await
+                .map_err(|e| panic!("Failed to store value: {e}"));
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        for value in [&plain, &forged] {
+            assert!(node1.value(value.id()).unwrap().is_none());
+            assert!(node2.value(value.id()).unwrap().is_some());
+
+            // Found on node2 for the application, still not cached.
+            let found = node1.find_value(&value.id(), -1, None).await
+                .expect("Failed to find value");
+            assert_eq!(found.map(|v| v.id()), Some(value.id()));
+            assert!(node1.value(value.id()).unwrap().is_none());
+        }
+        let found = node1.value(remote.id()).unwrap()
+            .expect("Value not stored");
+        assert!(found.verify_countersignature(&service_id));
+
+        // Looked up from node1 with the counter-signature.
+        _ = node2.remove_value(approved.id());
+        let found = node2.find_value(&approved.id(), -1, None).await
+            .expect("Failed to find value")
+            .expect("Value not found");
+        assert!(found.verify_countersignature(&service_id));
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_clock_skew() {
+        // node1 runs three hours ahead, beyond the announcement skew.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let clock = Arc::new(ManualClock::new(SystemTime::now() + Duration::from_secs(3 * 60 * 60)));
+        let node1 = Node::with_clock(
+            Box::new(node_config(32310, &path1, "compensateClockSkew: true\n").unwrap()),
+            clock.clone()
+        ).unwrap();
+        let node2 = create_node(32312, &path2).unwrap();
+        let node3 = create_node(32314, &path3).unwrap();
+
+        let (rc1, rc2, rc3) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+
+        assert_eq!(node1.estimated_clock_skew(), None);
+        _ = node1.bootstrap(&[node2.node_info(), node3.node_info()]).await
+            .map_err(|e| panic!("Failed to bootstrap node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        _ = node1.find_node(&Id::random(), None).await
+            .map_err(|e| panic!("Failed to find node: {e}"));
+
+        let skew = node1.estimated_clock_skew().expect("Should have estimated the skew");
+        let expected = -3 * 60 * 60 * 1000;
+        assert!((skew - expected).abs() < 5000, "skew {skew}");
+        assert!(node1.recent_events(64).iter().any(|e| {
+            matches!(e.kind(), NodeEventKind::ClockSkewDetected { offset_secs, nodes }
+                if (offset_secs + 3 * 60 * 60).abs() < 5 && *nodes >= 2)
+        }));
+        // The others tell theirs in line.
+        if let Some(skew) = node2.estimated_clock_skew() {
+            assert!(skew.abs() < 5000, "skew {skew}");
+        }
+
+        // node1 verifies the token it gave and takes the announcement of
+        // node2 by the compensated time.
+        let peer2 = PeerBuilder::new("https://example.com:8002")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        _ = node2.announce_peer(&peer2, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let peers = node1.find_peer(peer2.id(), -1, 1, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+
+        // And stamps its own announcements in line with the others.
+        let peer1 = PeerBuilder::new("https://example.com:8001")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        _ = node1.announce_peer(&peer1, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        let peers = node2.find_peer(peer1.id(), -1, 1, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_store_value_unconfirmed() {
+        // All the others turn down the values of node1, none counter-signed.
+        let required = format!("requireCountersigner: {}\n", Id::random());
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let path4 = working_path("node4");
+        let node1 = create_node(32316, &path1).unwrap();
+        let node2 = create_node_with(32318, &path2, &required).unwrap();
+        let node3 = create_node_with(32320, &path3, &required).unwrap();
+        let node4 = create_node_with(32322, &path4, &required).unwrap();
+
+        let (rc1, rc2, rc3, rc4) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start(),
+            node4.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+        _ = rc4.map_err(|e| panic!("Failed to start node4: {e}"));
+
+        let others = [node2.node_info(), node3.node_info(), node4.node_info()];
+        _ = node1.bootstrap(&others).await
+            .map_err(|e| panic!("Failed to bootstrap node1: {e}"));
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let value = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build value");
+        _ = node1.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        assert!(node1.recent_events(64).iter().any(|e| {
+            matches!(e.kind(), NodeEventKind::StoreUnconfirmed { value_id, acks }
+                if *value_id == value.id() && *acks == 0)
+        }));
+        for node in [&node2, &node3, &node4] {
+            assert!(node.value(value.id()).unwrap().is_none());
+        }
+
+        // node1 answers for the value meanwhile.
+        let found = node2.find_value(&value.id(), -1, None).await
+            .expect("Failed to find value")
+            .expect("Value not found");
+        assert_eq!(found.id(), value.id());
+        assert_eq!(found.data(), value.data());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop(),
+            node4.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+        cleanup_path(&path4);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_connect_direct() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32288, &path1).unwrap();
+        let node2 = create_node(32290, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node1.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // Refused until node2 takes direct connections.
+        let timeout = Duration::from_secs(10);
+        assert!(node1.connect_direct(node2.id(), timeout).await.is_err());
+        assert!(node1.connect_direct(node1.id(), timeout).await.is_err());
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        node2.set_direct_connection_handler(Box::new(move |result| {
+            _ = tx.send(result);
+        }));
+        // A guessed neighbour port on each side, nobody answers there.
+        let pattern = ProbePattern::default()
+            .with_interval(Duration::from_millis(50))
+            .with_port_offsets(&[0, 1]);
+        node1.set_probe_pattern(pattern.clone());
+        node2.set_probe_pattern(pattern);
+
+        let result1 = node1.connect_direct(node2.id(), timeout).await
+            .expect("Failed to connect node2 directly");
+        let result2 = rx.recv_timeout(timeout)
+            .expect("node2 should have been handed the connection");
+
+        assert_eq!(result1.peer(), node2.id());
+        assert_eq!(result2.peer(), node1.id());
+        assert_eq!(result1.remote_addr(), result2.local_addr().unwrap());
+        assert_eq!(result2.remote_addr(), result1.local_addr().unwrap());
+        assert!(result1.rtt() < timeout);
+
+        // The verified 5-tuple carries datagrams both ways.
+        let remote1 = result1.remote_addr();
+        let remote2 = result2.remote_addr();
+        let socket1 = tokio::net::UdpSocket::from_std(result1.into_socket()).unwrap();
+        let socket2 = tokio::net::UdpSocket::from_std(result2.into_socket()).unwrap();
+        let mut buf = [0u8; 64];
+
+        socket1.send_to(b"ping", remote1).await.unwrap();
+        let (len, from) = loop {
+            let (len, from) = socket2.recv_from(&mut buf).await.unwrap();
+            if &buf[..len] == b"ping" {
+                break (len, from);
+            }
+        };
+        assert_eq!(&buf[..len], b"ping");
+        assert_eq!(from, remote2);
+
+        socket2.send_to(b"pong", remote2).await.unwrap();
+        let len = loop {
+            let (len, _) = socket1.recv_from(&mut buf).await.unwrap();
+            if &buf[..len] == b"pong" {
+                break len;
+            }
+        };
+        assert_eq!(&buf[..len], b"pong");
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_concurrency_limits() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+
+        let node1 = create_node(32292, &path1).unwrap();
+        let node2 = create_node_with(32294, &path2,
+            "maxActiveTasks: 2\nmaxInflightCalls: 3\nmaxTaskCalls: 1\n"
+        ).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+
+        let value = ValueBuilder::new(&create_random_bytes(32)).build().unwrap();
+        node1.store_value(&value, -1, false).await.unwrap();
+
+        // Queued beyond the limits, but every lookup completes
+        let ids = (0..12).map(|_| Id::random()).chain([value.id()]).collect::<Vec<_>>();
+        let lookups = ids.iter().map(|id| node2.find_value(id, -1, None));
+        let results = futures::future::join_all(lookups).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+        let found = results.last().unwrap().as_ref().unwrap();
+        assert_eq!(found.as_ref().map(|v| v.id()), Some(value.id()));
+
+        let load = node2.concurrency(Network::IPv4).await.unwrap();
+        assert_eq!(load.peak_tasks(), 2);
+        assert!(load.peak_calls() <= 3);
+        assert!(node2.concurrency(Network::IPv6).await.is_none());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_command_queue_backpressure() {
+        let path = working_path("node1");
+        let node = create_node_with(32296, &path, "commandQueueSize: 4\n").unwrap();
+        _ = node.start().await.map_err(|e| panic!("Failed to start node: {e}"));
+
+        // Far more lookups than the queue holds, every one completes
+        let targets = (0..2000).map(|_| Id::random()).collect::<Vec<_>>();
+        let lookups = targets.iter().map(|id| node.find_node(id, Some(LookupOption::Local)));
+        let results = futures::future::join_all(lookups).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+
+        let queue = node.command_queue(Network::IPv4).await.unwrap();
+        assert_eq!(queue.capacity(), 4);
+        assert!(queue.peak_depth() <= 4);
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.count("findNode"), 2000);
+        assert!(node.command_queue(Network::IPv6).await.is_none());
+
+        _ = node.stop().await;
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_command_queue_stop() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32298, &path1).unwrap();
+        let node2 = create_node_with(32300, &path2, "commandQueueSize: 4\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+
+        // Lookups of node2 now wait for node1 to time out
+        _ = node1.stop().await;
+        let lookups = (0..500).map(|_| {
+            let node = node2.clone();
+            tokio::spawn(async move {
+                node.find_node(&Id::random(), None).await
+                    .map(|_| ())
+                    .map_err(|e| e.to_string())
+            })
+        }).collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        _ = node2.stop().await;
+
+        // Queued and running lookups are turned away rather than left hanging
+        let results = tokio::time::timeout(Duration::from_secs(2),
+            futures::future::join_all(lookups)
+        ).await.expect("lookups left hanging after stop");
+        let errors = results.into_iter().filter_map(|r| r.unwrap().err()).collect::<Vec<_>>();
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|e| e.contains("node stopped")), "{errors:?}");
+
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_dual_stack_responses() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let node1 = create_node_with(32328, &path1, "ipv6: true\n").unwrap();
+        let node2 = create_node_with(32330, &path2, "ipv6: true\n").unwrap();
+        let node3 = create_node_with(32332, &path3, "ipv6: true\n").unwrap();
+
+        let (rc1, rc2, rc3) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+
+        let ni4 = node1.node_info_of(Network::IPv4).unwrap();
+        let ni6 = node1.node_info_of(Network::IPv6).unwrap();
+        _ = node3.bootstrap(&[ni4.clone(), ni6]).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node3: {e}"));
+        // Long enough for the IPv6 instance of node1 to publish node3.
+        tokio::time::sleep(Duration::from_millis(6000)).await;
+
+        // node2 only knows node1 over IPv4, its IPv6 instance bootstraps
+        // from the nodes6 node1 answers with.
+        _ = node2.bootstrap_one(&ni4).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+
+        let ni3 = node3.node_info_of(Network::IPv6).unwrap();
+        let mut found = false;
+        for _ in 0..15 {
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            let nodes6 = node2.closest_nodes(node3.id(), 1, Some(Network::IPv6), false).await.unwrap();
+            if nodes6 == vec![ni3.clone()] {
+                found = true;
+                break;
+            }
+        }
+        assert!(found, "node3 was not learned over IPv6");
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_storage_events() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let clock = Arc::new(ManualClock::default());
+        let node1 = create_node(32334, &path1).unwrap();
+        let node2 = Node::with_clock(Box::new(node_config(32336, &path2, "").unwrap()), clock.clone()).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = events.clone();
+        node2.set_storage_listener(Box::new(move |event| {
+            received.lock().unwrap().push(event);
+        }));
+
+        let value1 = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build signed value");
+        let value2 = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build signed value");
+        let peer = PeerBuilder::new("https://example.com")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+
+        // Stored by node1, stored and persisted by node2 itself, then a peer
+        // announced by node1.
+        _ = node1.store_value(&value1, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        _ = node2.store_value(&value2, -1, true).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        _ = node1.announce_peer(&peer, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        // Only what node2 does not persist expires.
+        clock.advance(Duration::from_secs(3 * 60 * 60));
+        node2.expire_storage();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let expected = vec![
+            StorageEvent::ValueStored { id: value1.id(), seq: 1, new: true, local: false },
+            StorageEvent::ValueStored { id: value2.id(), seq: 1, new: true, local: true },
+            StorageEvent::PeerAnnounced {
+                id: *peer.id(),
+                fingerprint: peer.fingerprint(),
+                origin: *node1.id(),
+                local: false
+            },
+            StorageEvent::ValueExpired { id: value1.id() },
+            StorageEvent::PeerExpired { id: *peer.id(), fingerprint: peer.fingerprint() },
+        ];
+        assert_eq!(*events.lock().unwrap(), expected);
+        assert_eq!(node2.dropped_storage_events(), 0);
+        assert!(node2.value(value2.id()).unwrap().is_some());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_privacy_mode_lookups() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32338, &path1).unwrap();
+        let node2 = create_node_with(32340, &path2, "privacyMode: true\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        // Only node1 holds them, node2 has to look them up.
+        let value = SignedBuilder::new(&create_random_bytes(32))
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build signed value");
+        let peer = PeerBuilder::new("https://example.com")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        let _ = node1.store_value(&value, -1, false).await;
+        let _ = node1.announce_peer(&peer, -1, false).await;
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(node2.value(value.id()).unwrap().is_none());
+
+        // Answered to the session ids of the lookups.
+        let found = node2.find_value(&value.id(), -1, None).await
+            .expect("Failed to find value");
+        assert_eq!(found.map(|v| v.id()), Some(value.id()));
+        let peers = node2.find_peer(peer.id(), -1, 1, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.iter().map(|p| *p.id()).collect::<Vec<_>>(), vec![*peer.id()]);
+
+        // node1 only learned node2 under its node id.
+        let closest = node1.closest_nodes(&Id::random(), 8, None, false).await.unwrap();
+        assert_eq!(closest.iter().map(|ni| *ni.id()).collect::<Vec<_>>(), vec![*node2.id()]);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    // A value that nodeC leaves to a node it knows closer to the value.
+    async fn far_value(node: &Node) -> boson::Value {
+        loop {
+            let value = SignedBuilder::new(&create_random_bytes(32))
+                .with_sequence_number(1)
+                .build()
+                .expect("Failed to build signed value");
+            let value_id = value.id();
+            let closest = node.closest_nodes(&value_id, 1, None, false).await.unwrap();
+            if closest.iter().any(|ni| value_id.three_way_compare(ni.id(), node.id()).is_lt()) {
+                return value;
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_pin_value_on() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let node1 = create_node(32342, &path1).unwrap();
+        let node2 = create_node(32344, &path2).unwrap();
+        // Only node1 may have nodeC pin its values.
+        let node3 = create_node_with(32346, &path3, &format!(
+            "rejectFarStores: true\nacceptPinRequestsFrom:\n  - {}\n", node1.id()
+        )).unwrap();
+
+        let (rc1, rc2, rc3) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        _ = node3.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node3: {e}"));
+        _ = node3.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node3: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let value = far_value(&node3).await;
+        let results = node1.pin_value_on(&value, &[node3.node_info()]).await
+            .expect("Failed to pin value");
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, *node3.id());
+        assert!(results[0].1.is_ok(), "Pin refused: {:?}", results[0].1);
+        assert_eq!(node3.value(value.id()).unwrap().map(|v| v.id()), Some(value.id()));
+
+        // Two nodes are short of the bucket nodeC refuses far values for,
+        // the refusal is in the pinning soak test.
+        let value = far_value(&node3).await;
+        let results = node2.pin_value_on(&value, &[node3.node_info()]).await
+            .expect("Failed to pin value");
+        assert!(results[0].1.is_ok(), "Pin refused: {:?}", results[0].1);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    // The events the stream already has, without waiting for more.
+    async fn drain<T>(stream: &mut (impl Stream<Item = T> + Unpin)) -> Vec<T> {
+        let mut items = Vec::new();
+        while let Ok(Some(item)) = tokio::time::timeout(Duration::from_millis(200), stream.next()).await {
+            items.push(item);
+        }
+        items
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_event_streams() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32348, &path1).unwrap();
+        let node2 = create_node(32350, &path2).unwrap();
+
+        let mut status = Box::pin(node2.status_events());
+        let mut connection = Box::pin(node2.connection_events(Network::IPv4));
+        let mut connection6 = Box::pin(node2.connection_events(Network::IPv6));
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        _ = node2.stop().await;
+
+        // Restarted with node1 in the routing table it saved.
+        _ = node2.start().await.map_err(|e| panic!("Failed to restart node2: {e}"));
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+
+        assert_eq!(drain(&mut status).await, vec![
+            StreamItem::Event(NodeStatusEvent::Started),
+            StreamItem::Event(NodeStatusEvent::Stopped),
+            StreamItem::Event(NodeStatusEvent::Started),
+            StreamItem::Event(NodeStatusEvent::Stopped),
+        ]);
+        assert_eq!(drain(&mut connection).await, vec![
+            // Nothing to bootstrap from on the first start.
+            StreamItem::Event(ConnectionStatus::Connecting),
+            StreamItem::Event(ConnectionStatus::Disconnected),
+            StreamItem::Event(ConnectionStatus::Connecting),
+            StreamItem::Event(ConnectionStatus::Connected),
+            StreamItem::Event(ConnectionStatus::Disconnected),
+        ]);
+        assert!(drain(&mut connection6).await.is_empty());
+
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_token_cache() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let clock1 = Arc::new(ManualClock::default());
+        let clock2 = Arc::new(ManualClock::default());
+        let node1 = Node::with_clock(Box::new(node_config(32352, &path1, "").unwrap()), clock1.clone()).unwrap();
+        let node2 = Node::with_clock(Box::new(node_config(32354, &path2, "").unwrap()), clock2.clone()).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node1.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let announced = Arc::new(std::sync::Mutex::new(0));
+        let counted = announced.clone();
+        node2.set_storage_listener(Box::new(move |event| {
+            if matches!(event, StorageEvent::PeerAnnounced { .. }) {
+                *counted.lock().unwrap() += 1;
+            }
+        }));
+
+        let peer = PeerBuilder::new("https://example.com")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        let rotation = Duration::from_secs(5 * 60 + 1);
+
+        // The second announce uses the token of the first lookup.
+        for _ in 0..2 {
+            _ = node1.announce_peer(&peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+        let stats = node1.token_cache_stats().await;
+        assert_eq!((stats.misses(), stats.hits()), (1, 1));
+        assert_eq!(stats.entries(), 1);
+
+        // node2 rotates its token twice, the cached one is rejected and
+        // fetched again once.
+        for _ in 0..2 {
+            clock2.advance(rotation);
+            _ = node1.announce_peer(&peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+        let stats = node1.token_cache_stats().await;
+        assert_eq!((stats.misses(), stats.hits()), (1, 3));
+        let rejected = node2.recent_events(64).iter()
+            .filter(|e| matches!(e.kind(), NodeEventKind::TokenRejected { .. }))
+            .count();
+        assert_eq!(rejected, 1);
+
+        // Past the rotation the closest nodes are looked up again.
+        clock1.advance(rotation);
+        _ = node1.announce_peer(&peer, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        let stats = node1.token_cache_stats().await;
+        assert_eq!((stats.misses(), stats.hits()), (2, 3));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(*announced.lock().unwrap(), 5);
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_shared_data_dir() {
+        let path = working_path("shared");
+        let node1 = create_node_with(32356, &path, "instanceName: alpha").unwrap();
+        let node2 = create_node_with(32358, &path, "instanceName: beta").unwrap();
+        assert_eq!(node1.storage_path(), std::path::Path::new(&path).join("alpha"));
+        assert_eq!(node2.storage_path(), std::path::Path::new(&path).join("beta"));
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+
+        // Nothing of either node is left in the shared directory itself.
+        let mut names = fs::read_dir(&path).unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["alpha", "beta", "node.yaml"]);
+        for node in [&node1, &node2] {
+            let dir = node.storage_path();
+            assert_eq!(fs::read_to_string(dir.join("id")).unwrap(), node.id().to_base58());
+            assert!(dir.join("node.db").is_file());
+            assert!(dir.join("routing4.cache").is_file());
+        }
+
+        // Each restores its own routing table, knowing the other.
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to restart node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to restart node2: {e}"));
+        for node in [&node1, &node2] {
+            let buckets = node.routing_table_snapshot(Network::IPv4).await.unwrap();
+            assert_eq!(buckets.iter().map(|b| b.entries()).sum::<usize>(), 1);
+        }
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_block_address() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32360, &path1).unwrap();
+        let node2 = create_node_with(32362, &path2, "instanceName: beta\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        let ip = node1.node_info().ip();
+        let entry = node2.block_address(ip, None);
+        assert_eq!(entry.target(), &BlockTarget::Address(ip));
+        assert_eq!(node2.blocklist(), vec![entry.clone()]);
+
+        // Nothing from node1 gets through, neither knows the other.
+        _ = node1.bootstrap_one(&node2.node_info()).await;
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        assert!(node2.blocklist_stats().dropped_by_address() > 0);
+        for node in [&node1, &node2] {
+            let buckets = node.routing_table_snapshot(Network::IPv4).await.unwrap();
+            assert_eq!(buckets.iter().map(|b| b.entries()).sum::<usize>(), 0);
+        }
+        _ = node2.stop().await;
+
+        // The block outlives the node.
+        let node3 = create_node_with(32362, &path2, "instanceName: beta\n").unwrap();
+        assert_eq!(node3.blocklist(), vec![entry]);
+        assert!(node3.unblock(&BlockTarget::Address(ip)));
+        assert!(node3.blocklist().is_empty());
+
+        _ = node1.stop().await;
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_block_on_invalid_tokens() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let clock2 = Arc::new(ManualClock::default());
+        let node1 = create_node(32364, &path1).unwrap();
+        let config2 = node_config(32366, &path2, "blockTokenStrikes: 2\nblockDuration: 600\n").unwrap();
+        let node2 = Node::with_clock(Box::new(config2), clock2.clone()).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node1.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        let buckets = node2.routing_table_snapshot(Network::IPv4).await.unwrap();
+        assert_eq!(buckets.iter().map(|b| b.entries()).sum::<usize>(), 1);
+
+        let peers = (0..2).map(|_| PeerBuilder::new("https://example.com")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer")
+        ).collect::<Vec<_>>();
+        for peer in peers.iter() {
+            _ = node1.announce_peer(peer, -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+
+        // node2 rotates its token twice, both cached tokens are rejected.
+        let rotation = Duration::from_secs(5 * 60 + 1);
+        for _ in 0..2 {
+            clock2.advance(rotation);
+            _ = node1.announce_peer(&peers[0], -1, false).await
+                .map_err(|e| panic!("Failed to announce peer: {e}"));
+        }
+        _ = node1.announce_peer(&peers[1], -1, false).await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let blocked = node2.blocklist();
+        assert_eq!(blocked.len(), 1);
+        assert_eq!(blocked[0].target(), &BlockTarget::Id(*node1.id()));
+        assert_eq!(blocked[0].reason(), BlockReason::InvalidToken);
+        assert!(node2.recent_events(64).iter()
+            .any(|e| matches!(e.kind(), NodeEventKind::NodeBlocked { .. })));
+
+        let buckets = node2.routing_table_snapshot(Network::IPv4).await.unwrap();
+        assert_eq!(buckets.iter().map(|b| b.entries()).sum::<usize>(), 0);
+        let stats = node2.blocklist_stats();
+        assert_eq!(stats.auto_blocks(), 1);
+        assert_eq!(stats.evicted_entries(), 1);
+
+        // Lifted after the block duration.
+        clock2.advance(Duration::from_secs(600));
+        assert!(node2.blocklist().is_empty());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_lookup_deadline() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32368, &path1).unwrap();
+        let node2 = create_node(32370, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+
+        _ = node1.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let options = LookupOptionsEx::default().with_timeout(Duration::from_secs(2));
+        let rc = node1.find_value_ex(&Id::random(), -1, LookupOptionsEx::default()).await
+            .expect("Failed to find value");
+        assert!(rc.completed());
+        let rc = node1.find_value_ex(&Id::random(), -1, options).await
+            .expect("Failed to find value");
+        assert!(rc.completed());
+        assert!(rc.result().is_none());
+        let rc = node1.find_peer_ex(&Id::random(), -1, 4, options).await
+            .expect("Failed to find peer");
+        assert!(rc.completed());
+
+        // node2 is still in the routing table of node1, but never answers.
+        _ = node2.stop().await;
+        let started = Instant::now();
+        let rc = node1.find_value_ex(&Id::random(), -1, options).await
+            .expect("Failed to find value");
+        let elapsed = started.elapsed();
+        assert!(!rc.completed());
+        assert!(rc.result().is_none());
+        assert!(elapsed >= Duration::from_millis(1900) && elapsed < Duration::from_secs(4), "{elapsed:?}");
+
+        let started = Instant::now();
+        let rc = node1.find_peer_ex(&Id::random(), -1, 4, options).await
+            .expect("Failed to find peer");
+        let elapsed = started.elapsed();
+        assert!(!rc.completed());
+        assert!(rc.result().is_empty());
+        assert!(elapsed >= Duration::from_millis(1900) && elapsed < Duration::from_secs(4), "{elapsed:?}");
+
+        assert!(node1.find_value_ex(&Id::random(), -1, LookupOptionsEx::default()
+            .with_timeout(Duration::ZERO)).await.is_err());
+
+        _ = node1.stop().await;
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_peer_origin_gone() {
+        // node2 keeps the peers node1 and node3 announced, and stops
+        // answering for those of node1 once it is gone.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let cfg1 = node_config(32372, &path1, "").unwrap();
+        let origin1 = Arc::new(Mutex::new(CryptoIdentity::from(signature::KeyPair::from(cfg1.private_key()))));
+        let node1 = Node::new(Box::new(cfg1)).unwrap();
+        let clock2 = Arc::new(ManualClock::default());
+        let node2 = Node::with_clock(Box::new(node_config(32374, &path2, "").unwrap()), clock2.clone()).unwrap();
+        let cfg3 = node_config(32376, &path3, "").unwrap();
+        let origin3 = Arc::new(Mutex::new(CryptoIdentity::from(signature::KeyPair::from(cfg3.private_key()))));
+        let node3 = Node::new(Box::new(cfg3)).unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = node1.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node1: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let peer1 = PeerBuilder::new("https://example.com:8001")
+            .with_node(origin1)
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        _ = node1.announce_peer(&peer1, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+        _ = node1.stop().await;
+
+        _ = node3.start().await
+            .map_err(|e| panic!("Failed to start node3: {e}"));
+        _ = node3.bootstrap_one(&node2.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node2 on node3: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let peer3 = PeerBuilder::new("https://example.com:8003")
+            .with_node(origin3)
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        _ = node3.announce_peer(&peer3, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+
+        // Checked only past half their lifetime.
+        node2.check_peer_origins().await;
+        assert_eq!(node2.origin_check_stats().checks(), 0);
+
+        clock2.advance(Duration::from_secs(61 * 60));
+        for _ in 0..3 {
+            node2.check_peer_origins().await;
+        }
+        let stats = node2.origin_check_stats();
+        assert_eq!(stats.checks(), 6);
+        assert_eq!(stats.failures(), 3);
+        assert_eq!(stats.shortened_peers(), 1);
+
+        // Still an hour short of their normal expiry.
+        clock2.advance(Duration::from_secs(11 * 60));
+        node2.expire_storage();
+        assert_eq!(node2.origin_check_stats().expired_peers(), 1);
+
+        let peers = node2.find_peer(peer1.id(), -1, 1, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert!(peers.is_empty());
+        let peers = node2.find_peer(peer3.id(), -1, 1, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+        let peers = node3.find_peer(peer1.id(), -1, 1, None).await
+            .expect("Failed to find peer");
+        assert!(peers.is_empty());
+
+        let _ = tokio::join!(
+            node2.stop(),
+            node3.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_receive_sockets() {
+        // node1 reads four sockets on its port, the others talk to it as
+        // to any node.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let node1 = create_node_with(32378, &path1, "receiveSockets: 4\n").unwrap();
+        let node2 = create_node(32380, &path2).unwrap();
+        let node3 = create_node(32382, &path3).unwrap();
+
+        let (rc1, rc2, rc3) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+        assert_eq!(count_threads(&thread_names(), "boson-dht4-rx"), 4);
+
+        let ni = node1.node_info();
+        let (rc1, rc2) = tokio::join!(
+            node2.bootstrap_one(&ni),
+            node3.bootstrap_one(&ni)
+        );
+        _ = rc1.map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to bootstrapping node1 on node3: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build immutable value");
+        _ = node1.store_value(&value, -1, false).await
+            .map_err(|e| panic!("store value error: {e}"));
+        let found = node3.find_value(&value.id(), -1, None).await
+            .expect("Find value error")
+            .expect("Should have found the value");
+        assert_eq!(found.data(), value.data());
+
+        let peer = PeerBuilder::new("https://example.com")
+            .build()
+            .expect("Failed to build peer");
+        _ = node1.announce_peer(&peer, -1, false).await
+            .map_err(|e| panic!("announce peer error: {e}"));
+        let peers = node2.find_peer(peer.id(), -1, 1, None).await
+            .expect("Find peer error");
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].id(), peer.id());
+
+        let stats = node1.receive_stats(Network::IPv4).await.unwrap();
+        assert_eq!(stats.sockets(), 4);
+        assert!(stats.total_packets() > 0);
+        assert_eq!(stats.total_dropped(), 0);
+        assert!(node2.receive_stats(Network::IPv4).await.is_none());
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        assert_eq!(count_threads(&thread_names(), "boson-dht4-rx"), 0);
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_socket_bind_failed() {
+        // node2 is set up on the port node1 already holds.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32384, &path1).unwrap();
+        let node2 = create_node(32384, &path2).unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let cloned = events.clone();
+        node2.set_socket_error_handler(Box::new(move |event| {
+            cloned.lock().unwrap().push(event.clone());
+        }));
+
+        node1.start().await.expect("Failed to start node1");
+        assert!(node2.start().await.is_err());
+
+        let events = events.lock().unwrap().clone();
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            SocketEvent::BindFailed { addr, errno } => {
+                assert_eq!(addr.port(), 32384);
+                assert!(errno.is_some());
+            }
+            event => panic!("Unexpected socket event {event}"),
+        }
+        assert_eq!(node2.socket_error_stats().bind_failures(), 1);
+        assert_eq!(node1.socket_error_stats().bind_failures(), 0);
+
+        let _ = node1.stop().await;
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_keyspace_report() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(32386, &path1).unwrap();
+        let node2 = create_node(32388, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(node1.start(), node2.start());
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(2 * 1000)).await;
+
+        let mut ids = Vec::new();
+        for _ in 0..8 {
+            let value = ValueBuilder::new(&create_random_bytes(40))
+                .build()
+                .expect("Failed to build immutable value");
+            node1.store_value(&value, -1, false).await.expect("Failed to store value");
+            ids.push(value.id());
+        }
+
+        let all = Prefix::from(&Id::random(), -1);
+        let report = node1.keyspace_report(&all).await.expect("Failed to get keyspace report");
+        assert_eq!(report.depth(), -1);
+        assert_eq!(report.values().count(), 8);
+        assert_eq!(report.values().publishers(), 0);
+        assert_eq!(report.values().bytes(), 8 * 40);
+        assert!(report.values().oldest() <= report.values().median());
+        assert!(report.values().median() <= report.values().newest());
+        assert_eq!(report.peers().count(), 0);
+        assert_eq!(report.routing_entries(), 1);
+
+        // Half the keyspace, by the first bit of the first value.
+        let half = Prefix::from(&ids[0], 0);
+        let inside = ids.iter().filter(|id| half.is_prefix_of(id)).count();
+        let report = node1.keyspace_report(&half).await.unwrap();
+        assert_eq!(report.values().count(), inside);
+        assert_eq!(report.values().bytes(), inside as u64 * 40);
+        let narrow = Prefix::from(&node2.id(), 64);
+        let report = node1.keyspace_report(&narrow).await.unwrap();
+        assert_eq!(report.routing_entries(), 1);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["prefix"], serde_json::json!(report.prefix().to_base58()));
+        assert_eq!(json["depth"], 64);
+        assert_eq!(json["routingEntries"], 1);
+        assert_eq!(json["values"]["count"], report.values().count());
+        assert!(json["peers"].get("oldest").is_none());
+
+        let _ = tokio::join!(node1.stop(), node2.stop());
+        assert!(node1.keyspace_report(&all).await.is_err());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_name_claims() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let clock = Arc::new(ManualClock::new(SystemTime::now()));
+        let node1 = create_node(32394, &path1).unwrap();
+        let node2 = Node::with_clock(Box::new(node_config(32396, &path2, "").unwrap()), clock.clone()).unwrap();
+
+        let (rc1, rc2) = tokio::join!(node1.start(), node2.start());
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let alice = signature::KeyPair::random();
+        let bob = signature::KeyPair::random();
+        let carol = signature::KeyPair::random();
+        let targets = (0..4).map(|_| Id::random()).collect::<Vec<_>>();
+
+        let record = node1.register_name("Alice", &targets[0], &alice).await
+            .expect("Failed to register name");
+        assert_eq!(record.name(), "alice");
+        assert_eq!(record.sequence_number(), 0);
+        assert!(record.is_valid());
+
+        let claims = node2.resolve_name("  ALICE ").await.expect("Failed to resolve name");
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].owner(), &Id::from(alice.public_key()));
+        assert_eq!(claims[0].target(), &targets[0]);
+
+        // A conflicting claim is kept next to the first one.
+        node2.register_name("alice", &targets[1], &bob).await.expect("Failed to register name");
+        let claims = node1.resolve_name("alice").await.unwrap();
+        let owners = claims.iter().map(|c| *c.owner()).collect::<Vec<_>>();
+        assert_eq!(owners, vec![Id::from(alice.public_key()), Id::from(bob.public_key())]);
+
+        // Anyone can sign the value of the name, the claims are kept all the same.
+        let wiped = SignedBuilder::world_writable(&NAME_NAMESPACE, "alice", b"wiped")
+            .with_sequence_number(100)
+            .build()
+            .unwrap();
+        node2.store_value(&wiped, -1, false).await.expect("Failed to store value");
+        let claims = node1.resolve_name("alice").await.unwrap();
+        assert_eq!(claims.iter().map(|c| *c.owner()).collect::<Vec<_>>(), owners);
+
+        // Claimed again, the claim is bumped in place.
+        let record = node1.register_name("alice", &targets[2], &alice).await.unwrap();
+        assert_eq!(record.sequence_number(), 1);
+        let claims = node2.resolve_name("alice").await.unwrap();
+        assert_eq!(claims.len(), 2);
+        assert_eq!(claims[0].owner(), &Id::from(alice.public_key()));
+        assert_eq!(claims[0].target(), &targets[2]);
+        assert_eq!(claims[0].sequence_number(), 1);
+        assert_eq!(claims[1].target(), &targets[1]);
+
+        // Expired claims are left out.
+        let expires = clock.now_ms() + 60 * 60 * 1000;
+        node2.register_name_until("alice", &targets[3], &carol, expires).await.unwrap();
+        assert_eq!(node2.resolve_name("alice").await.unwrap().len(), 3);
+        clock.advance(Duration::from_secs(90 * 60));
+        let claims = node2.resolve_name("alice").await.unwrap();
+        assert_eq!(claims.len(), 2);
+        assert!(claims.iter().all(|c| c.owner() != &Id::from(carol.public_key())));
+        assert!(node2.register_name_until("alice", &targets[3], &carol, expires).await.is_err());
+
+        assert!(node2.resolve_name("bob").await.unwrap().is_empty());
+        assert!(node2.resolve_name(" ").await.is_err());
+        assert!(node1.register_name(&"x".repeat(65), &targets[0], &alice).await.is_err());
+
+        let _ = tokio::join!(node1.stop(), node2.stop());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_peer_observed_endpoint() {
+        // node1 announces a peer at a private address it is not sending
+        // from, as from behind a NAT.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let node1 = create_node(32398, &path1).unwrap();
+        let node2 = create_node(32400, &path2).unwrap();
+        let node3 = create_node(32402, &path3).unwrap();
+
+        let (rc1, rc2, rc3) = tokio::join!(
+            node1.start(),
+            node2.start(),
+            node3.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to start node3: {e}"));
+
+        let ni = node1.node_info();
+        let (rc2, rc3) = tokio::join!(
+            node2.bootstrap_one(&ni),
+            node3.bootstrap_one(&ni)
+        );
+        _ = rc2.map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to bootstrapping node1 on node3: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let source = ni.socket_addr().ip();
+        let peer = PeerBuilder::new("tcp://192.168.77.5:7443")
+            .with_sequence_number(1)
+            .build()
+            .expect("Failed to build peer");
+        _ = node1.announce_peer(&peer, -1, false).await
+            .map_err(|e| panic!("Failed to announce peer: {e}"));
+
+        let observed = format!("tcp://{source}:7443");
+        let peers = node3.find_peer(peer.id(), -1, 1, None).await
+            .expect("Failed to find peer");
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].endpoint(), peer.endpoint());
+        assert_eq!(peers[0].observed_endpoint(), Some(observed.as_str()));
+        assert!(peers[0].is_valid());
+
+        let warned = node1.recent_events(64).iter().any(|e| matches!(e.kind(),
+            NodeEventKind::EndpointUnroutable { peer: id, observed: at } if id == peer.id() && at == &observed
+        ));
+        assert!(warned);
+        // The announcer serves it too.
+        let peers = node1.find_peer(peer.id(), -1, 1, Some(LookupOption::Local)).await
+            .expect("Failed to find peer");
+        assert_eq!(peers[0].observed_endpoint(), Some(observed.as_str()));
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    #[cfg(feature = "wsgateway")]
+    #[tokio::test]
+    #[serial]
+    async fn test_ws_gateway() {
+        // node3 is on a network without UDP, tunneling through node1.
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let path3 = working_path("node3");
+        let node1 = create_node_with(32404, &path1, "wsGatewayListener: 127.0.0.1:32405\n").unwrap();
+        let node2 = create_node(32406, &path2).unwrap();
+        let node3 = create_node_with(32408, &path3, "wsGateway: ws://127.0.0.1:32405\n").unwrap();
+
+        let (rc1, rc2) = tokio::join!(
+            node1.start(),
+            node2.start()
+        );
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        assert_eq!(node1.ws_gateway_addr(), Some("127.0.0.1:32405".parse().unwrap()));
+        _ = node3.start().await.map_err(|e| panic!("Failed to start node3: {e}"));
+
+        // node3 binds nothing.
+        let ni3 = node3.node_info();
+        assert!(std::net::UdpSocket::bind(ni3.socket_addr()).is_ok());
+
+        let ni = node1.node_info();
+        let (rc2, rc3) = tokio::join!(
+            node2.bootstrap_one(&ni),
+            node3.bootstrap_one(&ni)
+        );
+        _ = rc2.map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        _ = rc3.map_err(|e| panic!("Failed to bootstrapping node1 on node3: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        let found = node3.find_node(node2.id(), None).await
+            .expect("Failed to find node2");
+        assert!(found.v4().is_some_and(|ni| ni.id() == node2.id()));
+
+        // Stored through the gateway, found by the others.
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build immutable value");
+        _ = node3.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        let found = node2.find_value(&value.id(), -1, None).await
+            .expect("Failed to find value");
+        assert_eq!(found.map(|v| v.data().to_vec()), Some(value.data().to_vec()));
+
+        // And the other way round.
+        let value = ValueBuilder::new(&create_random_bytes(32))
+            .build()
+            .expect("Failed to build immutable value");
+        _ = node2.store_value(&value, -1, false).await
+            .map_err(|e| panic!("Failed to store value: {e}"));
+        let found = node3.find_value(&value.id(), -1, None).await
+            .expect("Failed to find value");
+        assert_eq!(found.map(|v| v.data().to_vec()), Some(value.data().to_vec()));
+
+        let _ = tokio::join!(
+            node1.stop(),
+            node2.stop(),
+            node3.stop()
+        );
+        assert!(node1.ws_gateway_addr().is_none());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+        cleanup_path(&path3);
+    }
+
+    async fn start_pair(port1: u16, port2: u16) -> (Arc<Node>, Arc<Node>, WorkingPath, WorkingPath) {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+        let node1 = create_node(port1, &path1).unwrap();
+        let node2 = create_node(port2, &path2).unwrap();
+
+        let (rc1, rc2) = tokio::join!(node1.start(), node2.start());
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        (node1, node2, path1, path2)
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_kv_store() {
+        let (node1, node2, path1, path2) = start_pair(32410, 32412).await;
+
+        // Two devices sharing the namespace key pair.
+        let namespace = signature::KeyPair::random();
+        let kv1 = KvStore::new(node1.clone(), &namespace);
+        let kv2 = KvStore::new(node2.clone(), &namespace);
+        assert_eq!(kv1.value_id("profile"), kv2.value_id("profile"));
+
+        kv1.put("profile", b"alice v1").await.expect("Failed to put");
+        let entry = kv1.get("profile", Freshness::Cached).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"alice v1");
+        assert_eq!(entry.sequence_number(), 0);
+
+        // Cache-only reads stay off the network.
+        assert!(kv2.get("profile", Freshness::Cached).await.unwrap().is_none());
+        let entry = kv2.get("profile", Freshness::Network).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"alice v1");
+        assert_eq!(entry.sequence_number(), 0);
+        assert_eq!(kv2.get("profile", Freshness::Cached).await.unwrap(), Some(entry));
+
+        // kv1 puts over its stale cache, losing sequence number 1 to kv2.
+        kv2.put("profile", b"from device 2").await.expect("Failed to put");
+        kv1.put("profile", b"from device 1").await.expect("Failed to put with a stale cache");
+        let entry = kv1.get("profile", Freshness::Cached).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"from device 1");
+        assert_eq!(entry.sequence_number(), 2);
+
+        let entry = kv2.get("profile", Freshness::Network).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"from device 1");
+        assert_eq!(entry.sequence_number(), 2);
+
+        // The cache outlives the store.
+        drop(kv1);
+        let kv1 = KvStore::new(node1.clone(), &namespace);
+        let entry = kv1.get("profile", Freshness::Cached).await.unwrap().unwrap();
+        assert_eq!(entry.sequence_number(), 2);
+
+        let other = KvStore::new(node2.clone(), &signature::KeyPair::random());
+        assert_ne!(other.value_id("profile"), kv1.value_id("profile"));
+        assert!(other.get("profile", Freshness::Network).await.unwrap().is_none());
+
+        let _ = tokio::join!(node1.stop(), node2.stop());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_kv_store_conflict() {
+        let (node1, node2, path1, path2) = start_pair(32414, 32416).await;
+
+        let namespace = signature::KeyPair::random();
+        let kv1 = KvStore::new(node1.clone(), &namespace);
+        let mut kv2 = KvStore::new(node2.clone(), &namespace);
+
+        kv1.put("notes", b"a").await.unwrap();
+        assert!(kv2.get("notes", Freshness::Network).await.unwrap().is_some());
+        kv1.put("notes", b"b").await.unwrap();
+
+        // kv2 merges the newer copy with its own.
+        let conflicts = Arc::new(Mutex::new(Vec::new()));
+        kv2.on_conflict({
+            let conflicts = conflicts.clone();
+            move |c: &Conflict| {
+                conflicts.lock().unwrap().push(c.clone());
+                Resolution::Replace([c.local().data(), b"+", c.network().data()].concat())
+            }
+        });
+        let entry = kv2.get("notes", Freshness::Network).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"a+b");
+        assert_eq!(entry.sequence_number(), 2);
+        {
+            let conflicts = conflicts.lock().unwrap();
+            assert_eq!(conflicts.len(), 1);
+            assert_eq!(conflicts[0].key(), "notes");
+            assert_eq!(conflicts[0].local().data(), b"a");
+            assert_eq!(conflicts[0].network().sequence_number(), 1);
+        }
+
+        // Without a handler the network copy is taken.
+        let entry = kv1.get("notes", Freshness::Network).await.unwrap().unwrap();
+        assert_eq!(entry.data(), b"a+b");
+
+        // Watched, the changes of the other device are seen, not its own.
+        kv2.on_conflict(|_| Resolution::AcceptNetwork);
+        kv2.set_watch_interval(Duration::from_millis(200));
+        let local = tokio::task::LocalSet::new();
+        local.run_until(async {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let watch = kv2.watch("notes", {
+                let seen = seen.clone();
+                move |e| seen.lock().unwrap().push(e.clone())
+            });
+
+            kv1.put("notes", b"c").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            let data = seen.lock().unwrap().iter().map(|e| e.data().to_vec()).collect::<Vec<_>>();
+            assert_eq!(data, vec![b"c".to_vec()]);
+
+            kv2.put("notes", b"d").await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1000)).await;
+            assert_eq!(seen.lock().unwrap().len(), 1);
+            drop(watch);
+        }).await;
+
+        let _ = tokio::join!(node1.stop(), node2.stop());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn test_seed_data() {
+        let path1 = working_path("node1");
+        let path2 = working_path("node2");
+
+        let immutable = ValueBuilder::new(&create_random_bytes(32)).build().unwrap();
+        let signed = SignedBuilder::new(&create_random_bytes(32))
+            .with_keypair(&signature::KeyPair::random())
+            .with_sequence_number(5)
+            .build()
+            .unwrap();
+        let peer = PeerBuilder::new("tcp://192.168.1.10:8090").build().unwrap();
+
+        let seed_path = format!("{path1}/seed.cbor");
+        let mut file = fs::File::create(&seed_path).unwrap();
+        let records = [immutable.clone().into(), signed.clone().into(), peer.clone().into()];
+        seed::pack(&records, &mut file).expect("Failed to pack seed data");
+        drop(file);
+
+        let node1 = create_node_with(32418, &path1, &format!("seedData: {seed_path}\n")).unwrap();
+        let node2 = create_node(32420, &path2).unwrap();
+        let (rc1, rc2) = tokio::join!(node1.start(), node2.start());
+        _ = rc1.map_err(|e| panic!("Failed to start node1: {e}"));
+        _ = rc2.map_err(|e| panic!("Failed to start node2: {e}"));
+        _ = node2.bootstrap_one(&node1.node_info()).await
+            .map_err(|e| panic!("Failed to bootstrapping node1 on node2: {e}"));
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+
+        // Served by node1 without being announced.
+        for round in 0..2 {
+            let found = node2.find_value(&immutable.id(), -1, None).await.unwrap();
+            assert_eq!(found.map(|v| v.data().to_vec()), Some(immutable.data().to_vec()), "round {round}");
+            let found = node2.find_value(&signed.id(), -1, None).await.unwrap().unwrap();
+            assert_eq!(found.sequence_number(), 5);
+            assert_eq!(found.data(), signed.data());
+            let found = node2.find_peer(peer.id(), -1, 1, None).await.unwrap();
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].endpoint(), peer.endpoint());
+
+            // Still served once node1 restarted.
+            if round == 0 {
+                _ = node1.stop().await;
+                _ = node1.start().await.map_err(|e| panic!("Failed to restart node1: {e}"));
+                _ = node2.bootstrap_one(&node1.node_info()).await;
+                tokio::time::sleep(Duration::from_millis(1000)).await;
+            }
+        }
+
+        let _ = tokio::join!(node1.stop(), node2.stop());
+        cleanup_path(&path1);
+        cleanup_path(&path2);
+    }
 }
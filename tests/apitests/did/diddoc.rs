@@ -8,6 +8,7 @@ use boson::{
         constants,
         w3c::DIDDocument as DIDDoc,
         w3c::VerifiableCredential as VC,
+        VerificationMethod,
         VerificationMethodType,
         Card,
         DIDUrl,
@@ -469,4 +470,40 @@ mod tests {
         assert_eq!(doc, doc_new);
         assert_eq!(doc.to_string(), doc_new.to_string());
     }
+
+    #[test]
+    fn test_key_agreement() {
+        let identity = CryptoIdentity::new();
+        let vm = VerificationMethod::x25519_from(identity.id()).unwrap();
+        let doc = DIDDoc::builder(identity.clone())
+            .with_key_agreement(vm.clone()).unwrap()
+            .build().unwrap();
+
+        assert!(doc.contexts().contains(&constants::W3C_X25519_CONTEXT));
+        assert_eq!(doc.verification_methods().len(), 2);
+        assert_eq!(doc.verification_method("key-agreement"), Some(&vm));
+        assert_eq!(doc.key_agreements().len(), 1);
+
+        let agreement = doc.key_agreement("key-agreement").unwrap();
+        assert!(agreement.is_reference());
+        assert_eq!(agreement.method_type(), Some(VerificationMethodType::X25519KeyAgreementKey2019));
+        assert_eq!(agreement.public_key(), Some(identity.id().to_encryption_key().as_bytes()));
+        assert!(doc.is_genuine());
+
+        let json = serde_json::to_string(&doc).unwrap();
+        assert!(json.contains("\"keyAgreement\""));
+        let doc_new: DIDDoc = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc, doc_new);
+        assert_eq!(doc.key_agreements(), doc_new.key_agreements());
+
+        // Only the subject's own X25519 methods are accepted
+        let mut builder = DIDDoc::builder(identity.clone());
+        let other = VerificationMethod::x25519_from(CryptoIdentity::new().id()).unwrap();
+        assert!(builder.with_key_agreement(other).is_err());
+        let signing = VerificationMethod::ed25519(identity.id(), "key-1", &identity.id().to_signature_key());
+        assert!(builder.with_key_agreement(signing).is_err());
+        assert!(builder.with_key_agreement(vm.to_reference()).is_err());
+        assert!(builder.with_key_agreement(vm.clone()).is_ok());
+        assert!(builder.with_key_agreement(vm).is_err());
+    }
 }
@@ -7,10 +7,31 @@ use boson::{
     did::{
 		constants,
         DIDUrl,
-        w3c::VerifiableCredential as VC,
+        w3c::{
+			VerifiableCredential as VC,
+			ClaimSchema,
+			ClaimType,
+			SchemaViolation,
+			SchemaValidationError,
+		},
     }
 };
 
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+struct Address {
+	street: String,
+	city: String,
+	zip: u32,
+}
+
+fn passport_schema() -> ClaimSchema {
+	ClaimSchema::new()
+		.required("name", ClaimType::String)
+		.required("age", ClaimType::Integer)
+		.required("verified", ClaimType::Boolean)
+		.optional("address", ClaimType::Object)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -242,4 +263,100 @@ mod tests {
 
 		// TODO:
 	}
+
+	#[test]
+	fn test_vc_with_schema() {
+		let identity = CryptoIdentity::new();
+		let address = Address {
+			street: "123 Main St".to_string(),
+			city: "Anytown".to_string(),
+			zip: 12345,
+		};
+		let vc = VC::builder(identity.clone())
+			.with_id("passport").unwrap()
+			.with_type("Passport", "https://example.com/credentials/passport/v1").unwrap()
+			.with_schema("https://example.com/schemas/passport.json", "JsonSchema").unwrap()
+			.with_claim("name", "John Doe")
+			.with_claim("age", 42)
+			.with_claim("score", 9.5)
+			.with_claim("verified", true)
+			.with_claim("address", address.clone())
+			.build()
+			.unwrap();
+
+		let schema = vc.schema().unwrap();
+		assert_eq!(schema.id(), "https://example.com/schemas/passport.json");
+		assert_eq!(schema.schema_type(), "JsonSchema");
+		assert!(vc.is_genuine());
+		assert!(vc.validate_against(&passport_schema()).is_ok());
+
+		// Typed accessors
+		assert_eq!(vc.claim::<String>("name").unwrap(), Some("John Doe".to_string()));
+		assert_eq!(vc.claim::<u32>("age").unwrap(), Some(42));
+		assert_eq!(vc.claim::<f64>("score").unwrap(), Some(9.5));
+		assert_eq!(vc.claim::<bool>("verified").unwrap(), Some(true));
+		assert_eq!(vc.claim::<Address>("address").unwrap(), Some(address));
+		assert_eq!(vc.claim::<String>("nickname").unwrap(), None);
+		assert!(vc.claim::<bool>("name").is_err());
+
+		// The schema reference is covered by the proof
+		let json = vc.to_string();
+		let tampered = json.replace("passport.json", "visa.json");
+		let vc_tampered = tampered.parse::<VC>().unwrap();
+		assert_eq!(vc_tampered.schema().unwrap().id(), "https://example.com/schemas/visa.json");
+		assert!(!vc_tampered.is_genuine());
+
+		let vc_new = json.parse::<VC>().unwrap();
+		assert_eq!(vc, vc_new);
+		assert!(vc_new.is_genuine());
+
+		let cred = vc.to_boson_credential();
+		assert!(cred.is_genuine());
+		assert_eq!(VC::from(&cred), vc);
+	}
+
+	#[test]
+	fn test_vc_schema_violation() {
+		let identity = CryptoIdentity::new();
+		let vc = VC::builder(identity.clone())
+			.with_id("passport").unwrap()
+			.with_schema("https://example.com/schemas/passport.json", "JsonSchema").unwrap()
+			.with_claim("name", "John Doe")
+			.with_claim("verified", "yes")
+			.build()
+			.unwrap();
+
+		let err = vc.validate_against(&passport_schema()).unwrap_err();
+		let err = err.downcast_ref::<SchemaValidationError>().unwrap();
+		assert_eq!(err.violation(), &SchemaViolation::MissingClaim("age".to_string()));
+
+		let schema = ClaimSchema::new()
+			.required("name", ClaimType::String)
+			.required("verified", ClaimType::Boolean);
+		let err = vc.validate_against(&schema).unwrap_err();
+		let err = err.downcast_ref::<SchemaValidationError>().unwrap();
+		assert_eq!(err.violation(), &SchemaViolation::TypeMismatch {
+			claim: "verified".to_string(),
+			expected: ClaimType::Boolean,
+			found: "string",
+		});
+
+		// JSON Schema subset
+		let schema = ClaimSchema::try_from(&serde_json::json!({
+			"type": "object",
+			"required": ["name", "age"],
+			"properties": {
+				"name": { "type": "string" },
+				"age": { "type": "integer" },
+			}
+		})).unwrap();
+		assert_eq!(schema, ClaimSchema::new()
+			.required("age", ClaimType::Integer)
+			.required("name", ClaimType::String));
+		let err = vc.validate_against(&schema).unwrap_err();
+		let err = err.downcast_ref::<SchemaValidationError>().unwrap();
+		assert_eq!(err.violation(), &SchemaViolation::MissingClaim("age".to_string()));
+
+		assert!(VC::builder(identity).with_schema("", "JsonSchema").is_err());
+	}
 }
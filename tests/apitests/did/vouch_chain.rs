@@ -0,0 +1,117 @@
+use std::time::{SystemTime, Duration};
+use boson::{
+	CryptoIdentity,
+	Identity,
+	did::{Vouch, VouchChain, VouchLinkBuilder},
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn link(issuer: &CryptoIdentity, subject: &CryptoIdentity) -> VouchLinkBuilder {
+	VouchChain::link_builder(issuer.clone(), subject.id())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_three_links() {
+		let (a, b, c, d) = (CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new());
+		let mut chain = VouchChain::new();
+		chain.append(&link(&a, &b)).unwrap()
+			.append(&link(&b, &c)).unwrap()
+			.append(&link(&c, &d)).unwrap();
+
+		assert_eq!(chain.len(), 3);
+		assert_eq!(chain.root(), Some(a.id()));
+		assert_eq!(chain.subject(), Some(d.id()));
+		assert_eq!(chain.scopes(), None);
+
+		let now = SystemTime::now();
+		assert!(chain.verify(a.id(), d.id(), 3, now).is_ok());
+		assert!(chain.verify(a.id(), d.id(), 2, now).is_err());
+		assert!(chain.verify(b.id(), d.id(), 3, now).is_err());
+		assert!(chain.verify(a.id(), c.id(), 3, now).is_err());
+		assert!(VouchChain::new().verify(a.id(), a.id(), 3, now).is_err());
+
+		let json = chain.to_string();
+		let chain2 = json.parse::<VouchChain>().unwrap();
+		assert_eq!(chain, chain2);
+		assert!(chain2.verify(a.id(), d.id(), 3, now).is_ok());
+
+		let cbor = Vec::from(&chain);
+		let chain3 = VouchChain::try_from(&cbor[..]).unwrap();
+		assert_eq!(chain, chain3);
+		assert!(chain3.verify(a.id(), d.id(), 3, now).is_ok());
+	}
+
+	#[test]
+	fn test_broken_continuity() {
+		let (a, b, c, d) = (CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new());
+		let mut chain = VouchChain::new();
+		chain.append(&link(&a, &b)).unwrap();
+		assert!(chain.append(&link(&c, &d)).is_err());
+		assert_eq!(chain.len(), 1);
+
+		// Put together from links off the wire, past the checks of push().
+		let json = format!("[{},{}]",
+			link(&a, &b).build().unwrap(),
+			link(&c, &d).build().unwrap()
+		);
+		let chain = json.parse::<VouchChain>().unwrap();
+		assert!(chain.verify(a.id(), d.id(), 4, SystemTime::now()).is_err());
+
+		// A vouch that is not a delegation is no link.
+		let other = Vouch::builder(b.clone())
+			.with_credential_by_claims("profile", "BosonProfile", [("name", "Bob")].into()).unwrap()
+			.build().unwrap();
+		assert!(VouchChain::new().push(other).is_err());
+	}
+
+	#[test]
+	fn test_cycles() {
+		let (a, b, c) = (CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new());
+		let mut chain = VouchChain::new();
+		chain.append(&link(&a, &b)).unwrap()
+			.append(&link(&b, &a)).unwrap();
+		assert!(chain.verify(a.id(), a.id(), 4, SystemTime::now()).is_err());
+
+		chain.append(&link(&a, &c)).unwrap();
+		assert!(chain.verify(a.id(), c.id(), 4, SystemTime::now()).is_err());
+	}
+
+	#[test]
+	fn test_scope_narrowing() {
+		let (a, b, c, d) = (CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new());
+		let now = SystemTime::now();
+
+		let mut chain = VouchChain::new();
+		chain.append(link(&a, &b).with_scopes(vec!["read", "write"])).unwrap()
+			.append(&link(&b, &c)).unwrap()
+			.append(link(&c, &d).with_scope("read")).unwrap();
+		assert!(chain.verify(a.id(), d.id(), 3, now).is_ok());
+		assert_eq!(chain.scopes(), Some(vec!["read".to_string()]));
+
+		let mut chain = VouchChain::new();
+		chain.append(link(&a, &b).with_scope("read")).unwrap()
+			.append(&link(&b, &c)).unwrap()
+			.append(link(&c, &d).with_scopes(vec!["read", "write"])).unwrap();
+		assert!(chain.verify(a.id(), d.id(), 3, now).is_err());
+	}
+
+	#[test]
+	fn test_expired_middle_link() {
+		let (a, b, c, d) = (CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new(), CryptoIdentity::new());
+		let now = SystemTime::now();
+
+		let mut chain = VouchChain::new();
+		chain.append(link(&a, &b).with_valid_from(now).with_valid_until(now + DAY * 30)).unwrap()
+			.append(link(&b, &c).with_valid_from(now).with_valid_until(now + DAY)).unwrap()
+			.append(link(&c, &d).with_valid_from(now).with_valid_until(now + DAY * 30)).unwrap();
+
+		assert!(chain.verify(a.id(), d.id(), 3, now + DAY / 2).is_ok());
+		assert!(chain.verify(a.id(), d.id(), 3, now + DAY * 2).is_err());
+		assert!(chain.verify(a.id(), d.id(), 3, now - DAY).is_err());
+	}
+}
@@ -6,6 +6,7 @@ mod core {
     mod node_info;
     mod peer_info;
     mod value;
+    mod document;
 
 }
 
@@ -21,18 +22,21 @@ mod did {
     mod verification_method;
     mod credential;
     mod vouch;
+    mod vouch_chain;
     mod card;
     mod vc;
     mod vp;
     mod diddoc;
 }
 
-/*
 #[cfg(test)]
 mod messaging {
-    mod client;
+    // mod client;
+    mod channel_key;
+    mod builder;
+    mod chunking;
+    mod message_meta;
 }
-*/
 
 // helper function
 fn randomize_bytes<const N: usize>(array: &mut [u8; N]) {
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use futures::StreamExt;
+use url::Url;
+use boson::{
+    Id,
+    core::PeerBuilder,
+    dht::StreamItem,
+    messaging::{
+        BoxFuture,
+        ConnectionEvent,
+        ConnectionListener,
+        Error,
+        MessagingClientBuilder,
+        Result,
+        ServiceDiscovery,
+        ServiceIds,
+    },
+};
+
+struct MockDiscovery {
+    ids: ServiceIds,
+    calls: AtomicUsize,
+}
+
+impl MockDiscovery {
+    fn new() -> Self {
+        Self {
+            ids: ServiceIds::new(Id::random(), Id::random()),
+            calls: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl ServiceDiscovery for MockDiscovery {
+    fn service_ids<'a>(&'a self, api_url: &'a Url) -> BoxFuture<'a, Result<ServiceIds>> {
+        assert_eq!(api_url.host_str(), Some("api.example.com"));
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(self.ids.clone()) })
+    }
+}
+
+#[test]
+fn test_api_url_derived() {
+    let peer = PeerBuilder::new("https://api.example.com").build().unwrap();
+    let builder = MessagingClientBuilder::new().service_peer(peer);
+    let url = builder.resolve_api_url().unwrap();
+    assert_eq!(url.as_str(), "https://api.example.com/");
+
+    let peer = PeerBuilder::new("tcp://api.example.com:1883").build().unwrap();
+    let builder = MessagingClientBuilder::new().service_peer(peer);
+    assert!(matches!(builder.resolve_api_url(), Err(Error::Argument(_))));
+
+    let builder = MessagingClientBuilder::new();
+    assert!(matches!(builder.resolve_api_url(), Err(Error::State(_))));
+}
+
+#[test]
+fn test_api_url_override() {
+    let staging = Url::parse("https://staging.example.com").unwrap();
+
+    let peer = PeerBuilder::new("https://api.example.com").build().unwrap();
+    let builder = MessagingClientBuilder::new()
+        .service_peer(peer)
+        .api_url(staging.clone());
+    assert_eq!(builder.resolve_api_url().unwrap(), staging);
+
+    // the override applies even when the peer carries no usable url.
+    let peer = PeerBuilder::new("tcp://api.example.com:1883").build().unwrap();
+    let builder = MessagingClientBuilder::new()
+        .service_peer(peer)
+        .api_url(staging.clone());
+    assert_eq!(builder.resolve_api_url().unwrap(), staging);
+}
+
+#[tokio::test]
+async fn test_service_ids() {
+    let discovery = MockDiscovery::new();
+    let peer = PeerBuilder::new("https://api.example.com").build().unwrap();
+    let builder = MessagingClientBuilder::new().service_peer(peer);
+
+    let ids = builder.resolve_service_ids(&discovery).await.unwrap();
+    assert_eq!(ids, discovery.ids);
+    assert_eq!(discovery.calls.load(Ordering::SeqCst), 1);
+
+    let cached = ServiceIds::new(Id::random(), Id::random());
+    let builder = builder.service_ids(cached.clone());
+    let ids = builder.resolve_service_ids(&discovery).await.unwrap();
+    assert_eq!(ids, cached);
+    assert_eq!(discovery.calls.load(Ordering::SeqCst), 1);
+}
+
+struct ReadyListener(AtomicUsize);
+
+impl ConnectionListener for ReadyListener {
+    fn on_ready(&self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn test_connection_events() {
+    let listener = Arc::new(ReadyListener(AtomicUsize::new(0)));
+    let mut builder = MessagingClientBuilder::new()
+        .connection_listener(listener.clone());
+    let mut events = Box::pin(builder.connection_events());
+    let mut slow = Box::pin(builder.connection_events());
+
+    // The listener and the streams see the same events.
+    let listeners = builder.connection_listeners();
+    assert_eq!(listeners.len(), 2);
+    for l in listeners.iter() {
+        l.on_connecting();
+        l.on_connected();
+        l.on_ready();
+    }
+    assert_eq!(listener.0.load(Ordering::SeqCst), 1);
+    assert_eq!(events.next().await, Some(StreamItem::Event(ConnectionEvent::Connecting)));
+    assert_eq!(events.next().await, Some(StreamItem::Event(ConnectionEvent::Connected)));
+    assert_eq!(events.next().await, Some(StreamItem::Event(ConnectionEvent::Ready)));
+
+    // A stream never polled falls behind, the client goes on.
+    for _ in 0..100 {
+        listeners[1].on_disconnected();
+    }
+    assert!(matches!(slow.next().await, Some(StreamItem::Lagged(n)) if n > 0));
+    assert_eq!(slow.next().await, Some(StreamItem::Event(ConnectionEvent::Disconnected)));
+}
@@ -0,0 +1,141 @@
+use boson::{
+    Id,
+    Identity,
+    CryptoIdentity,
+    cryptobox::{self, CryptoBox},
+    messaging::{ChannelKeyRing, ChannelMember, Error, KeyRotation, Role},
+};
+
+struct Member {
+    identity: CryptoIdentity,
+    ring: ChannelKeyRing,
+}
+
+impl Member {
+    // The member record others keep of this member.
+    fn record(&self, role: Role) -> Record {
+        Record(*self.identity.id(), role)
+    }
+}
+
+struct Record(Id, Role);
+
+impl ChannelMember for Record {
+    fn id(&self) -> &Id { &self.0 }
+    fn role(&self) -> Role { self.1 }
+}
+
+// A three-member channel sharing the initial session key.
+fn setup_channel() -> (Member, Member, Member) {
+    let channel_id = Id::random();
+    let session_key = cryptobox::KeyPair::random();
+
+    let member = || Member {
+        identity: CryptoIdentity::new(),
+        ring: ChannelKeyRing::new(channel_id, session_key.clone()),
+    };
+    (member(), member(), member())
+}
+
+#[test]
+fn test_rotate_on_remove() {
+    let (mut owner, mut bob, mut carol) = setup_channel();
+    let history = owner.ring.encrypt(b"before removal").unwrap();
+    let old_key = carol.ring.session_key().clone();
+
+    // carol gets removed, the key goes to the remaining members only.
+    let rotation = owner.ring.rotate(&owner.identity, &[
+        *owner.identity.id(),
+        *bob.identity.id(),
+    ]).unwrap();
+    assert_eq!(rotation.epoch(), 1);
+    assert_eq!(owner.ring.epoch(), 1);
+    assert_eq!(rotation.recipients().collect::<Vec<_>>(), vec![bob.identity.id()]);
+
+    // the rotation reaches the members over the wire.
+    let rotation = KeyRotation::from_bytes(&rotation.to_bytes().unwrap()).unwrap();
+    let sender = owner.record(Role::Owner);
+
+    bob.ring.apply(&bob.identity, &rotation, &sender).unwrap();
+    assert_eq!(bob.ring.epoch(), 1);
+    assert_eq!(
+        bob.ring.session_key().private_key().as_bytes(),
+        owner.ring.session_key().private_key().as_bytes()
+    );
+
+    let result = carol.ring.apply(&carol.identity, &rotation, &sender);
+    assert!(matches!(result, Err(Error::NotFound(_))));
+    assert_eq!(carol.ring.epoch(), 0);
+
+    let message = owner.ring.encrypt(b"after removal").unwrap();
+    assert_eq!(bob.ring.decrypt(&message).unwrap(), b"after removal");
+    assert!(carol.ring.decrypt(&message).is_err());
+
+    // the old session key itself can not open the new message either.
+    let old_box = CryptoBox::try_from((old_key.public_key(), old_key.private_key())).unwrap();
+    assert!(old_box.decrypt_into(&message[4..]).is_err());
+
+    // history encrypted under the old key stays readable.
+    assert_eq!(bob.ring.decrypt(&history).unwrap(), b"before removal");
+    assert_eq!(owner.ring.decrypt(&history).unwrap(), b"before removal");
+}
+
+#[test]
+fn test_apply_replayed() {
+    let (mut owner, mut bob, _) = setup_channel();
+
+    let rotation = owner.ring.rotate(&owner.identity, &[*bob.identity.id()]).unwrap();
+    let sender = owner.record(Role::Owner);
+    bob.ring.apply(&bob.identity, &rotation, &sender).unwrap();
+    bob.ring.apply(&bob.identity, &rotation, &sender).unwrap();
+    assert_eq!(bob.ring.epoch(), 1);
+
+    let (other, _, _) = setup_channel();
+    let result = other.ring.clone().apply(&bob.identity, &rotation, &sender);
+    assert!(matches!(result, Err(Error::Argument(_))));
+}
+
+#[test]
+fn test_apply_from_moderator() {
+    let (mut moderator, mut bob, _) = setup_channel();
+
+    let rotation = moderator.ring.rotate(&moderator.identity, &[*bob.identity.id()]).unwrap();
+    bob.ring.apply(&bob.identity, &rotation, &moderator.record(Role::Moderator)).unwrap();
+    assert_eq!(bob.ring.epoch(), 1);
+}
+
+#[test]
+fn test_apply_from_member() {
+    let (owner, mut bob, mut carol) = setup_channel();
+
+    // carol is a plain member and pushes a key of her own.
+    let rotation = carol.ring.rotate(&carol.identity, &[
+        *owner.identity.id(),
+        *bob.identity.id(),
+    ]).unwrap();
+
+    let result = bob.ring.apply(&bob.identity, &rotation, &carol.record(Role::Member));
+    assert!(matches!(result, Err(Error::PermissionDenied(_))));
+    let result = bob.ring.apply(&bob.identity, &rotation, &carol.record(Role::Banned));
+    assert!(matches!(result, Err(Error::PermissionDenied(_))));
+
+    // nor can she pass the rotation off as the owner's.
+    let result = bob.ring.apply(&bob.identity, &rotation, &owner.record(Role::Owner));
+    assert!(matches!(result, Err(Error::Argument(_))));
+    assert_eq!(bob.ring.epoch(), 0);
+}
+
+#[test]
+fn test_rotation_bytes() {
+    let (mut owner, bob, carol) = setup_channel();
+
+    let rotation = owner.ring.rotate(&owner.identity, &[
+        *bob.identity.id(),
+        *carol.identity.id(),
+    ]).unwrap();
+    let decoded = KeyRotation::from_bytes(&rotation.to_bytes().unwrap()).unwrap();
+    assert_eq!(decoded, rotation);
+
+    let result = KeyRotation::from_bytes(b"not a rotation");
+    assert!(matches!(result, Err(Error::Encoding(_))));
+}
@@ -0,0 +1,271 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use serde_cbor::Value;
+use boson::{
+    Id,
+    Identity,
+    CryptoIdentity,
+    messaging::{
+        Error,
+        chunking::{
+            self,
+            Reassembler,
+            MAX_PACKET_SIZE,
+            MAX_CHUNK_SIZE,
+            MAX_MESSAGE_SIZE,
+            MAX_PENDING_PER_SENDER,
+        },
+    },
+};
+use crate::create_random_bytes;
+
+// A client/service pair standing in for the MQTT publish/receive path:
+// each packet is encrypted for the peer exactly like the transport does.
+struct Loopback {
+    client: CryptoIdentity,
+    service: CryptoIdentity,
+}
+
+impl Loopback {
+    fn new() -> Self {
+        Self {
+            client: CryptoIdentity::new(),
+            service: CryptoIdentity::new(),
+        }
+    }
+
+    fn publish(&self, envelope: Vec<u8>) -> Vec<Vec<u8>> {
+        chunking::split(self.client.id(), envelope).unwrap().iter().map(|packet| {
+            let payload = self.client.encrypt_into(self.service.id(), packet).unwrap();
+            assert!(payload.len() < MAX_PACKET_SIZE);
+            payload
+        }).collect()
+    }
+
+    fn receive(&self, reassembler: &mut Reassembler, payload: &[u8]) -> Option<Vec<u8>> {
+        let packet = self.service.decrypt_into(self.client.id(), payload).unwrap();
+        reassembler.accept(self.client.id(), packet).unwrap()
+    }
+}
+
+#[test]
+fn test_round_trip_100kb() {
+    let loopback = Loopback::new();
+    let mut reassembler = Reassembler::default();
+    let body = create_random_bytes(100 * 1024);
+
+    let payloads = loopback.publish(body.clone());
+    assert_eq!(payloads.len(), (100 * 1024usize).div_ceil(MAX_CHUNK_SIZE));
+
+    // Deliver out of order, the last packet completes the message
+    let (last, rest) = payloads.split_last().unwrap();
+    for payload in rest.iter().rev() {
+        assert_eq!(loopback.receive(&mut reassembler, payload), None);
+    }
+    assert_eq!(reassembler.pending(), 1);
+
+    let received = loopback.receive(&mut reassembler, last).unwrap();
+    assert_eq!(received, body);
+    assert_eq!(reassembler.pending(), 0);
+    assert_eq!(reassembler.buffered(), 0);
+}
+
+#[test]
+fn test_single_chunk_unchanged() {
+    let envelope = create_random_bytes(MAX_CHUNK_SIZE);
+    let packets = chunking::split(&Id::random(), envelope.clone()).unwrap();
+    assert_eq!(packets, vec![envelope.clone()]);
+
+    // Regular envelopes pass straight through
+    let mut reassembler = Reassembler::default();
+    let sender = Id::random();
+    assert_eq!(reassembler.accept(&sender, envelope.clone()).unwrap(), Some(envelope));
+
+    let cbor = serde_cbor::to_vec(&serde_json::json!({"t": 1, "b": "hello"})).unwrap();
+    assert_eq!(reassembler.accept(&sender, cbor.clone()).unwrap(), Some(cbor));
+}
+
+#[test]
+fn test_duplicate_chunks() {
+    let mut reassembler = Reassembler::default();
+    let sender = Id::random();
+    let body = create_random_bytes(3 * MAX_CHUNK_SIZE);
+    let packets = chunking::split(&sender, body.clone()).unwrap();
+    assert_eq!(packets.len(), 3);
+
+    assert_eq!(reassembler.accept(&sender, packets[0].clone()).unwrap(), None);
+    assert_eq!(reassembler.accept(&sender, packets[0].clone()).unwrap(), None);
+    assert_eq!(reassembler.accept(&sender, packets[1].clone()).unwrap(), None);
+    assert_eq!(reassembler.accept(&sender, packets[2].clone()).unwrap(), Some(body));
+}
+
+#[test]
+fn test_lost_chunk_timeout() {
+    let loopback = Loopback::new();
+    let mut reassembler = Reassembler::new(Duration::from_millis(50), chunking::DEFAULT_REASSEMBLY_CAPACITY);
+
+    let payloads = loopback.publish(create_random_bytes(100 * 1024));
+    for payload in payloads.iter().skip(1) {
+        assert_eq!(loopback.receive(&mut reassembler, payload), None);
+    }
+    assert_eq!(reassembler.pending(), 1);
+    assert!(reassembler.buffered() > 0);
+
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(reassembler.expire(), 1);
+    assert_eq!(reassembler.pending(), 0);
+    assert_eq!(reassembler.buffered(), 0);
+
+    // The late first chunk starts a new sequence that never completes
+    assert_eq!(loopback.receive(&mut reassembler, &payloads[0]), None);
+    assert_eq!(reassembler.pending(), 1);
+}
+
+#[test]
+fn test_bounded_buffer() {
+    let mut reassembler = Reassembler::new(Duration::from_secs(60), 4 * MAX_CHUNK_SIZE);
+
+    let (alice, bob) = (Id::random(), Id::random());
+    let first = chunking::split(&alice, create_random_bytes(3 * MAX_CHUNK_SIZE)).unwrap();
+    let second = chunking::split(&bob, create_random_bytes(4 * MAX_CHUNK_SIZE)).unwrap();
+
+    reassembler.accept(&alice, first[0].clone()).unwrap();
+    reassembler.accept(&alice, first[1].clone()).unwrap();
+    std::thread::sleep(Duration::from_millis(5));
+    for packet in &second[..3] {
+        reassembler.accept(&bob, packet.clone()).unwrap();
+    }
+
+    // The oldest incomplete sequence was evicted to stay within capacity
+    assert_eq!(reassembler.pending(), 1);
+    assert!(reassembler.buffered() <= 4 * MAX_CHUNK_SIZE);
+    assert_eq!(reassembler.accept(&alice, first[2].clone()).unwrap(), None);
+}
+
+#[test]
+fn test_oversized_message() {
+    let sender = Id::random();
+    let rc = chunking::split(&sender, vec![0u8; MAX_MESSAGE_SIZE + 1]);
+    assert!(matches!(rc, Err(Error::Argument(_))));
+    assert_eq!(chunking::split(&sender, vec![0u8; MAX_MESSAGE_SIZE]).unwrap().len(), chunking::MAX_CHUNKS);
+}
+
+fn ext(frame: &mut BTreeMap<Value, Value>) -> &mut BTreeMap<Value, Value> {
+    match frame.get_mut(&Value::Text("ck".into())) {
+        Some(Value::Map(ext)) => ext,
+        _ => panic!("No chunk extension"),
+    }
+}
+
+fn frame_of(packet: &[u8]) -> BTreeMap<Value, Value> {
+    match serde_cbor::from_slice::<Value>(packet).unwrap() {
+        Value::Map(frame) => frame,
+        _ => panic!("Not a chunk frame"),
+    }
+}
+
+// The value of `key` in the chunk extension of a frame.
+fn ext_of(packet: &[u8], key: &str) -> Value {
+    ext(&mut frame_of(packet))[&Value::Text(key.into())].clone()
+}
+
+// Rewrites `key` in the chunk extension of a frame.
+fn with_ext(packet: &[u8], key: &str, value: Value) -> Vec<u8> {
+    let mut frame = frame_of(packet);
+    ext(&mut frame).insert(Value::Text(key.into()), value);
+    serde_cbor::to_vec(&Value::Map(frame)).unwrap()
+}
+
+#[test]
+fn test_same_message_id_of_two_senders() {
+    let mut reassembler = Reassembler::default();
+    let (alice, bob) = (Id::random(), Id::random());
+    let body1 = create_random_bytes(2 * MAX_CHUNK_SIZE);
+    let body2 = create_random_bytes(2 * MAX_CHUNK_SIZE);
+
+    // bob reuses the message id of alice, the sequences stay apart
+    let packets1 = chunking::split(&alice, body1.clone()).unwrap();
+    let id = ext_of(&packets1[0], "id");
+    let packets2 = chunking::split(&bob, body2.clone()).unwrap().iter().map(|packet| {
+        with_ext(packet, "id", id.clone())
+    }).collect::<Vec<_>>();
+
+    assert_eq!(reassembler.accept(&alice, packets1[0].clone()).unwrap(), None);
+    assert_eq!(reassembler.accept(&bob, packets2[0].clone()).unwrap(), None);
+    assert_eq!(reassembler.pending(), 2);
+
+    assert_eq!(reassembler.accept(&bob, packets2[1].clone()).unwrap(), Some(body2));
+    assert_eq!(reassembler.accept(&alice, packets1[1].clone()).unwrap(), Some(body1));
+    assert_eq!(reassembler.pending(), 0);
+}
+
+#[test]
+fn test_pending_per_sender() {
+    let mut reassembler = Reassembler::default();
+    let (alice, bob) = (Id::random(), Id::random());
+
+    let bobs = chunking::split(&bob, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+    assert_eq!(reassembler.accept(&bob, bobs[0].clone()).unwrap(), None);
+
+    let mut alices = Vec::new();
+    for _ in 0..MAX_PENDING_PER_SENDER + 2 {
+        let packets = chunking::split(&alice, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+        assert_eq!(reassembler.accept(&alice, packets[0].clone()).unwrap(), None);
+        alices.push(packets);
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    // The oldest sequences of alice were dropped, the one of bob is kept
+    assert_eq!(reassembler.pending(), MAX_PENDING_PER_SENDER + 1);
+    assert_eq!(reassembler.accept(&alice, alices[0][1].clone()).unwrap(), None);
+    assert!(reassembler.accept(&bob, bobs[1].clone()).unwrap().is_some());
+    assert!(reassembler.accept(&alice, alices.last().unwrap()[1].clone()).unwrap().is_some());
+}
+
+#[test]
+fn test_frame_version() {
+    let mut reassembler = Reassembler::default();
+
+    // A regular envelope carrying a 'ck' key is not taken for a frame
+    let cbor = serde_cbor::to_vec(&serde_json::json!({
+        "ck": {"id": 1, "i": 0, "n": 2},
+        "d": [1, 2, 3],
+    })).unwrap();
+    let sender = Id::random();
+    assert_eq!(reassembler.accept(&sender, cbor.clone()).unwrap(), Some(cbor));
+    assert_eq!(reassembler.pending(), 0);
+
+    let packets = chunking::split(&sender, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+    let unknown = with_ext(&packets[0], "v", Value::Integer(2));
+    assert!(matches!(reassembler.accept(&sender, unknown), Err(Error::Encoding(_))));
+    assert_eq!(reassembler.pending(), 0);
+}
+
+#[test]
+fn test_spoofed_sender() {
+    let mut reassembler = Reassembler::default();
+    let (alice, mallory) = (Id::random(), Id::random());
+
+    let alices = chunking::split(&alice, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+    assert_eq!(reassembler.accept(&alice, alices[0].clone()).unwrap(), None);
+
+    // mallory claims to be alice, to push her sequences out or to slip a
+    // chunk into one of them
+    for _ in 0..MAX_PENDING_PER_SENDER {
+        let packets = chunking::split(&alice, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+        assert!(matches!(reassembler.accept(&mallory, packets[0].clone()), Err(Error::Auth(_))));
+    }
+    assert!(matches!(reassembler.accept(&mallory, alices[1].clone()), Err(Error::Auth(_))));
+    assert_eq!(reassembler.pending(), 1);
+
+    // a frame of mallory declaring herself is not taken for alice's either
+    let mallorys = chunking::split(&mallory, create_random_bytes(2 * MAX_CHUNK_SIZE)).unwrap();
+    let mallorys = mallorys.iter().map(|packet| {
+        with_ext(packet, "id", ext_of(&alices[0], "id"))
+    }).collect::<Vec<_>>();
+    assert_eq!(reassembler.accept(&mallory, mallorys[1].clone()).unwrap(), None);
+    assert_eq!(reassembler.pending(), 2);
+
+    assert!(reassembler.accept(&alice, alices[1].clone()).unwrap().is_some());
+    assert_eq!(reassembler.pending(), 1);
+}
@@ -0,0 +1,167 @@
+use boson::{
+    Id,
+    Identity,
+    CryptoIdentity,
+    messaging::{
+        Error,
+        MessageMeta,
+        chunking::{self, Reassembler, MAX_MESSAGE_SIZE},
+        message_meta::{MAX_PROPERTIES_SIZE, MAX_PROPERTY_KEY_SIZE},
+    },
+};
+use crate::create_random_bytes;
+
+// The envelope fields a client without threading support reads, any other
+// key is skipped.
+#[serde_with::serde_as]
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct Envelope {
+    #[serde(rename = "f")]
+    from: Id,
+    #[serde(rename = "t")]
+    to: Id,
+    #[serde(rename = "b")]
+    #[serde_as(as = "serde_with::Bytes")]
+    body: Vec<u8>,
+}
+
+// Sender, recipient and messaging service, each packet encrypted by the
+// sender for the service and the body for the recipient, like the worker
+// does.
+struct Loopback {
+    sender: CryptoIdentity,
+    recipient: CryptoIdentity,
+    service: CryptoIdentity,
+}
+
+impl Loopback {
+    fn new() -> Self {
+        Self {
+            sender: CryptoIdentity::new(),
+            recipient: CryptoIdentity::new(),
+            service: CryptoIdentity::new(),
+        }
+    }
+
+    fn publish(&self, body: &[u8], meta: &MessageMeta) -> boson::messaging::Result<Vec<Vec<u8>>> {
+        let envelope = Envelope {
+            from: self.sender.id().clone(),
+            to: self.recipient.id().clone(),
+            body: self.sender.encrypt_into(self.recipient.id(), body).unwrap(),
+        };
+        let envelope = meta.attach(serde_cbor::to_vec(&envelope).unwrap())?;
+        Ok(chunking::split(self.sender.id(), envelope)?.iter().map(|packet| {
+            self.sender.encrypt_into(self.service.id(), packet).unwrap()
+        }).collect())
+    }
+
+    fn receive(&self, payloads: &[Vec<u8>]) -> (Envelope, Vec<u8>, MessageMeta) {
+        let mut reassembler = Reassembler::default();
+        let mut received = None;
+        for payload in payloads {
+            let packet = self.service.decrypt_into(self.sender.id(), payload).unwrap();
+            received = reassembler.accept(self.sender.id(), packet).unwrap();
+        }
+        let received = received.expect("Incomplete message");
+
+        let envelope = serde_cbor::from_slice::<Envelope>(&received).unwrap();
+        let body = self.recipient.decrypt_into(self.sender.id(), &envelope.body).unwrap();
+        let meta = MessageMeta::extract(&received).unwrap();
+        (envelope, body, meta)
+    }
+}
+
+#[test]
+fn test_round_trip() {
+    let loopback = Loopback::new();
+    let original = MessageMeta::new()
+        .with_property("kind", "poll").unwrap()
+        .with_property("lang", "en").unwrap();
+    let reply = MessageMeta::new()
+        .with_in_reply_to(original.message_id().unwrap())
+        .with_property("kind", "vote").unwrap();
+    let later = MessageMeta::new()
+        .with_in_reply_to(reply.message_id().unwrap())
+        .with_thread(original.message_id().unwrap());
+
+    for meta in [&original, &reply, &later] {
+        let payloads = loopback.publish(b"hello", meta).unwrap();
+        let (envelope, body, received) = loopback.receive(&payloads);
+        assert_eq!(envelope.from, *loopback.sender.id());
+        assert_eq!(body, b"hello");
+        assert_eq!(&received, meta);
+    }
+
+    // Each message has an id of its own, replies land in the thread of the
+    // message they answer.
+    assert_ne!(original.message_id(), reply.message_id());
+    assert_eq!(original.thread_root(), None);
+    assert_eq!(reply.thread_root(), original.message_id());
+    assert_eq!(later.thread_root(), original.message_id());
+    assert_eq!(later.in_reply_to(), reply.message_id());
+    assert_eq!(original.property("lang"), Some("en"));
+    assert_eq!(reply.properties().len(), 1);
+}
+
+#[test]
+fn test_chunked_round_trip() {
+    let loopback = Loopback::new();
+    let body = create_random_bytes(100 * 1024);
+    let meta = MessageMeta::new()
+        .with_thread(&Id::random())
+        .with_property("name", "photo.jpg").unwrap();
+
+    let payloads = loopback.publish(&body, &meta).unwrap();
+    assert!(payloads.len() > 1);
+    let (_, received_body, received) = loopback.receive(&payloads);
+    assert_eq!(received_body, body);
+    assert_eq!(received, meta);
+}
+
+#[test]
+fn test_without_meta() {
+    let loopback = Loopback::new();
+
+    // Envelopes of clients without threading support.
+    let payloads = loopback.publish(b"hello", &MessageMeta::default()).unwrap();
+    let (_, body, meta) = loopback.receive(&payloads);
+    assert_eq!(body, b"hello");
+    assert!(meta.is_empty());
+    assert_eq!(meta.message_id(), None);
+    assert_eq!(meta.thread_root(), None);
+
+    // Not an envelope.
+    let rc = MessageMeta::default().with_thread(&Id::random()).attach(vec![1, 2, 3]);
+    assert!(matches!(rc, Err(Error::Encoding(_))));
+    assert!(matches!(MessageMeta::extract(&[1, 2, 3]), Err(Error::Encoding(_))));
+}
+
+#[test]
+fn test_property_limits() {
+    let rc = MessageMeta::new().with_property("", "v");
+    assert!(matches!(rc, Err(Error::Argument(_))));
+    let rc = MessageMeta::new().with_property(&"k".repeat(MAX_PROPERTY_KEY_SIZE + 1), "v");
+    assert!(matches!(rc, Err(Error::Argument(_))));
+
+    let value = "v".repeat(MAX_PROPERTIES_SIZE / 2 - 1);
+    let meta = MessageMeta::new()
+        .with_property("a", &value).unwrap()
+        .with_property("b", &value).unwrap();
+    assert!(matches!(meta.clone().with_property("c", "vv"), Err(Error::Argument(_))));
+    // Replacing a value counts the new one only.
+    let meta = meta.with_property("b", "v").unwrap();
+    assert_eq!(meta.property("b"), Some("v"));
+}
+
+#[test]
+fn test_oversized_envelope() {
+    let loopback = Loopback::new();
+    let meta = MessageMeta::new()
+        .with_property("note", &"v".repeat(MAX_PROPERTIES_SIZE - 4)).unwrap();
+
+    // A body that fits on its own, pushed over the limit by the properties.
+    let body = create_random_bytes(MAX_MESSAGE_SIZE - 1024);
+    let rc = loopback.publish(&body, &meta);
+    assert!(matches!(rc, Err(Error::Argument(_))));
+    assert!(loopback.publish(&body, &MessageMeta::default()).is_ok());
+}
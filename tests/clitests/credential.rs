@@ -0,0 +1,95 @@
+use std::fs;
+use std::collections::HashMap;
+use boson::{
+    CryptoIdentity,
+    did::{Credential, Vouch},
+};
+
+use crate::{run, run_json, WorkDir};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_issue_verify() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        let cred = dir.file("cred.json");
+        let issuer = run_json(&["keygen", "-o", &key], None);
+        let subject = run_json(&["keygen"], None);
+
+        let output = run(&[
+            "credential", "issue", "-k", &key,
+            "--id", "profile",
+            "--subject", subject["did"].as_str().unwrap(),
+            "--type", "ProfileCredential",
+            "--name", "Profile",
+            "--claim", "name=Alice",
+            "--claim", "age=30",
+            "--valid-days", "30",
+            "-o", &cred,
+        ], None);
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+        let credential = Credential::try_from(fs::read_to_string(&cred).unwrap().as_str()).unwrap();
+        assert_eq!(credential.id(), "profile");
+        assert_eq!(credential.issuer().to_base58(), issuer["id"].as_str().unwrap());
+        assert_eq!(credential.subject().id().to_base58(), subject["id"].as_str().unwrap());
+        assert_eq!(credential.types(), vec!["ProfileCredential"]);
+        assert_eq!(credential.subject().claim::<String>("name").unwrap(), "Alice");
+        assert_eq!(credential.subject().claim::<u32>("age"), Some(30));
+        assert!(credential.valid_until().is_some());
+        assert!(credential.validate().is_ok());
+
+        let report = run_json(&["credential", "verify", &cred], None);
+        assert_eq!(report["type"], "credential");
+        assert_eq!(report["issuer"], issuer["id"]);
+        assert_eq!(report["valid"], true);
+
+        // Altered claims break the signature.
+        let forged = fs::read_to_string(&cred).unwrap().replace("Alice", "Mallory");
+        let output = run(&["credential", "verify", "--json"], Some(forged.as_bytes()));
+        assert!(!output.status.success());
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(report["genuine"], false);
+        assert_eq!(report["valid"], false);
+    }
+
+    #[test]
+    fn test_issue_to_stdout() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        run_json(&["keygen", "-o", &key], None);
+
+        let output = run(&["credential", "issue", "-k", &key, "--id", "email", "--claim", "email=a@b.c"], None);
+        assert!(output.status.success());
+        let report = run_json(&["credential", "verify"], Some(&output.stdout));
+        assert_eq!(report["valid"], true);
+        assert_eq!(report["issuer"], report["subject"]);
+
+        // Credentials need claims.
+        let output = run(&["credential", "issue", "-k", &key, "--id", "empty"], None);
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_verify_vouch() {
+        let identity = CryptoIdentity::new();
+        let vouch = Vouch::builder(identity)
+            .with_id("vouch")
+            .with_credential_by_claims("profile", "ProfileCredential",
+                HashMap::from([("name", "Alice")]))
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let report = run_json(&["credential", "verify"], Some(vouch.to_string().as_bytes()));
+        assert_eq!(report["type"], "vouch");
+        assert_eq!(report["credentials"], 1);
+        assert_eq!(report["valid"], true);
+
+        let output = run(&["credential", "verify"], Some(b"{}"));
+        assert!(!output.status.success());
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+use boson::{
+    signature,
+    Id,
+    Identity,
+    CryptoIdentity,
+    PeerBuilder,
+    SignedBuilder,
+    EncryptedBuilder,
+    messaging::InviteTicket,
+};
+
+use crate::{run, run_json, WorkDir};
+
+fn ticket(inviter: &CryptoIdentity, invitee: Option<&Id>, expire_ms: u64) -> InviteTicket {
+    let channel = Id::random();
+    let is_public = invitee.is_none();
+    let digest = InviteTicket::digest(&channel, inviter.id(), invitee.unwrap_or(&Id::max()), is_public, expire_ms);
+    let sig = inviter.sign_into(&digest).unwrap();
+    let session_key = invitee.map(|_| vec![7u8; 48]);
+    InviteTicket::new(channel, inviter.id().clone(), is_public, expire_ms, sig, session_key)
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_decode() {
+        let inviter = CryptoIdentity::new();
+        let expire_ms = now_ms() + InviteTicket::DEFAULT_EXPIRATION_MS;
+        let bearer = ticket(&inviter, None, expire_ms);
+
+        let report = run_json(&["ticket", "decode"], Some(bearer.to_base58().unwrap().as_bytes()));
+        assert_eq!(report["channel"], bearer.channel_id().to_base58());
+        assert_eq!(report["inviter"], inviter.id().to_base58());
+        assert_eq!(report["bearer"], true);
+        assert_eq!(report["expiration"], expire_ms / 1000);
+        assert_eq!(report["expired"], false);
+        assert_eq!(report["sessionKey"], false);
+        assert_eq!(report["valid"], true);
+
+        // Named tickets are only valid for their invitee.
+        let invitee = Id::random();
+        let named = ticket(&inviter, Some(&invitee), now_ms() - 1000);
+        let dir = WorkDir::new();
+        let file = dir.file("ticket");
+        fs::write(&file, named.to_hex().unwrap()).unwrap();
+
+        let report = run_json(&["ticket", "decode", &file], None);
+        assert_eq!(report["bearer"], false);
+        assert_eq!(report["expired"], true);
+        assert_eq!(report["sessionKey"], true);
+        assert!(report["valid"].is_null());
+
+        let did = invitee.to_did_string();
+        let report = run_json(&["ticket", "decode", &file, "--invitee", &did], None);
+        assert_eq!(report["valid"], true);
+        let other = Id::random().to_base58();
+        let report = run_json(&["ticket", "decode", &file, "--invitee", &other], None);
+        assert_eq!(report["valid"], false);
+
+        let output = run(&["ticket", "decode"], Some(b"zzzz"));
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_value_decode() {
+        let keypair = signature::KeyPair::random();
+        let value = SignedBuilder::new(b"hello boson")
+            .with_keypair(&keypair)
+            .with_sequence_number(3)
+            .build()
+            .unwrap();
+        let bytes = Vec::from(&value);
+
+        let report = run_json(&["value", "decode"], Some(bs58::encode(&bytes).into_string().as_bytes()));
+        assert_eq!(report["id"], value.id().to_base58());
+        assert_eq!(report["publicKey"], Id::from(keypair.public_key()).to_base58());
+        assert_eq!(report["sequence"], 3);
+        assert_eq!(report["mutable"], true);
+        assert_eq!(report["encrypted"], false);
+        assert_eq!(report["valid"], true);
+        assert_eq!(report["text"], "hello boson");
+
+        // Hex and raw CBOR decode the same.
+        let hex = run_json(&["value", "decode"], Some(hex::encode(&bytes).as_bytes()));
+        assert_eq!(hex, report);
+        let dir = WorkDir::new();
+        let file = dir.file("value.cbor");
+        fs::write(&file, &bytes).unwrap();
+        assert_eq!(run_json(&["value", "decode", &file], None), report);
+
+        let output = run(&["value", "decode"], Some(b"0x00"));
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_value_decrypt() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        let recipient = run_json(&["keygen", "-o", &key], None);
+        let recipient = Id::try_from(recipient["id"].as_str().unwrap()).unwrap();
+
+        let value = EncryptedBuilder::new(b"for your eyes only", &recipient).build().unwrap();
+        let encoded = bs58::encode(Vec::from(&value)).into_string();
+
+        let report = run_json(&["value", "decode"], Some(encoded.as_bytes()));
+        assert_eq!(report["encrypted"], true);
+        assert_eq!(report["recipient"], recipient.to_base58());
+        assert!(report["plain"].is_null());
+        assert!(report["text"].is_null());
+
+        let report = run_json(&["value", "decode", "-k", &key], Some(encoded.as_bytes()));
+        assert_eq!(report["text"], "for your eyes only");
+        assert_eq!(report["plain"], hex::encode(b"for your eyes only"));
+
+        let other = dir.file("other");
+        run_json(&["keygen", "-o", &other], None);
+        let output = run(&["value", "decode", "-k", &other], Some(encoded.as_bytes()));
+        assert!(!output.status.success());
+    }
+
+    #[test]
+    fn test_peer_decode() {
+        let peer = PeerBuilder::new("tcp://192.168.1.10:9000")
+            .with_fingerprint(42)
+            .with_extra(b"extra")
+            .build()
+            .unwrap();
+        let encoded = bs58::encode(Vec::from(&peer)).into_string();
+
+        let report = run_json(&["peer", "decode"], Some(encoded.as_bytes()));
+        assert_eq!(report["id"], peer.id().to_base58());
+        assert_eq!(report["endpoint"], "tcp://192.168.1.10:9000");
+        assert_eq!(report["fingerprint"], 42);
+        assert_eq!(report["extra"], hex::encode(b"extra"));
+        assert_eq!(report["authenticated"], false);
+        assert_eq!(report["valid"], true);
+        let announced = peer.announced_time().unwrap().duration_since(UNIX_EPOCH).unwrap();
+        assert_eq!(report["announced"], announced.as_secs());
+
+        let output = run(&["peer", "decode"], Some(bs58::encode(b"garbage").into_string().as_bytes()));
+        assert!(!output.status.success());
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use boson::{signature, Id};
+
+use crate::{run, run_json, WorkDir};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keygen() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        let report = run_json(&["keygen", "-o", &key], None);
+        assert_eq!(report["privateKeyFile"], key.as_str());
+        assert!(report.get("privateKey").is_none());
+
+        let sk = fs::read_to_string(&key).unwrap();
+        let keypair = signature::KeyPair::from(signature::PrivateKey::try_from(sk.as_str()).unwrap());
+        let id = Id::from(keypair.public_key());
+        assert_eq!(report["id"], id.to_base58());
+        assert_eq!(report["did"], id.to_did_string());
+
+        // Without a file the private key is printed.
+        let report = run_json(&["keygen"], None);
+        assert!(signature::PrivateKey::try_from(report["privateKey"].as_str().unwrap()).is_ok());
+    }
+
+    #[test]
+    fn test_id() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        let keygen = run_json(&["keygen", "-o", &key], None);
+
+        let report = run_json(&["id", &key], None);
+        assert_eq!(report["id"], keygen["id"]);
+        assert_eq!(report["publicKey"], keygen["publicKey"]);
+
+        // Ids and DIDs are read from stdin as well.
+        let did = keygen["did"].as_str().unwrap();
+        let report = run_json(&["id"], Some(did.as_bytes()));
+        assert_eq!(report["id"], keygen["id"]);
+        assert!(report.get("publicKey").is_none());
+        let report = run_json(&["id", "-"], Some(report["hex"].as_str().unwrap().as_bytes()));
+        assert_eq!(report["did"], did);
+
+        let output = run(&["id"], Some(b"not a key"));
+        assert!(!output.status.success());
+        assert!(!output.stderr.is_empty());
+    }
+
+    #[test]
+    fn test_sign_verify() {
+        let dir = WorkDir::new();
+        let key = dir.file("key");
+        let file = dir.file("data");
+        let keygen = run_json(&["keygen", "-o", &key], None);
+        fs::write(&file, b"The quick brown fox").unwrap();
+
+        let report = run_json(&["sign", "-k", &key, &file], None);
+        assert_eq!(report["signer"], keygen["id"]);
+        let sig = report["signature"].as_str().unwrap().to_string();
+
+        let did = keygen["did"].as_str().unwrap();
+        let report = run_json(&["verify", "--signer", did, "--signature", &sig, &file], None);
+        assert_eq!(report["valid"], true);
+
+        // The same signature over stdin, and in hex.
+        let hex = format!("0x{}", hex::encode(bs58::decode(&sig).into_vec().unwrap()));
+        let report = run_json(&["verify", "--signer", did, "--signature", &hex],
+            Some(b"The quick brown fox"));
+        assert_eq!(report["valid"], true);
+
+        // Tampered data and other signers fail with a non-zero status.
+        let output = run(&["verify", "--signer", did, "--signature", &sig, "--json"],
+            Some(b"The quick brown fox!"));
+        assert!(!output.status.success());
+        let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(report["valid"], false);
+
+        let other = run_json(&["keygen"], None);
+        let output = run(&["verify", "--signer", other["id"].as_str().unwrap(), "--signature", &sig, &file], None);
+        assert!(!output.status.success());
+    }
+}
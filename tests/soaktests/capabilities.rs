@@ -0,0 +1,80 @@
+use std::time::Duration;
+use boson::{
+    Id,
+    Network,
+    Capabilities,
+    core::ImmutableBuilder as ValueBuilder,
+    dht::LookupOption,
+    testing::{LatencyModel, SimNetwork},
+};
+
+const FULL_NODES: usize = 8;
+// Nodes 1 to 4 only route.
+const CLIENTS: std::ops::RangeInclusive<usize> = 1..=4;
+
+fn network(seed: u64) -> SimNetwork {
+    SimNetwork::new(seed)
+        .with_latency(LatencyModel::uniform(Duration::from_millis(5), Duration::from_millis(80)))
+        .add_nodes(1)
+        .and_then(|sim| sim.with_config("clientOnly: true\n").add_nodes(CLIENTS.count()))
+        .and_then(|sim| sim.with_config("").add_nodes(FULL_NODES - 1))
+        .expect("Failed to start the simulated nodes")
+}
+
+// The nodes holding the value in their own storage, by index.
+fn holders(sim: &SimNetwork, value_id: &Id) -> Vec<usize> {
+    sim.nodes().iter().enumerate()
+        .filter(|(_, node)| node.value(*value_id).unwrap().is_some())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_only_nodes() {
+        let sim = network(13);
+        sim.run_until(Duration::from_secs(5 * 60));
+
+        // Known to the others for what they are.
+        let full = sim.node(0).unwrap();
+        let client = sim.node(*CLIENTS.start()).unwrap();
+        let found = sim.block_on(full.find_node(client.id(), Some(LookupOption::Conservative))).unwrap();
+        assert_eq!(found.v4().expect("client node not found").capabilities(), Capabilities::CLIENT_ONLY);
+
+        let buckets = sim.block_on(full.routing_table_snapshot(Network::IPv4)).unwrap();
+        let tally = |name: &str| buckets.iter()
+            .map(|b| b.capabilities().get(name).copied().unwrap_or(0))
+            .sum::<usize>();
+        let entries = buckets.iter().map(|b| b.entries()).sum::<usize>();
+        assert!(tally("client-only") > 0, "{buckets:?}");
+        assert_eq!(tally("client-only") + tally("storage"), entries, "{buckets:?}");
+
+        // Stored on the full nodes only, whoever stores it.
+        let values = [
+            (0, ValueBuilder::new(b"stored by a full node").build().unwrap()),
+            (*CLIENTS.start(), ValueBuilder::new(b"stored by a client node").build().unwrap()),
+        ];
+        for (from, value) in values.iter() {
+            sim.block_on(sim.node(*from).unwrap().store_value(value, -1, false)).unwrap();
+
+            let holders = holders(&sim, &value.id());
+            let remote = holders.iter().filter(|i| *i != from).collect::<Vec<_>>();
+            assert!(!remote.is_empty(), "{holders:?}");
+            assert!(remote.iter().all(|i| !CLIENTS.contains(*i)), "{holders:?}");
+        }
+
+        // The client nodes still route the lookups, their own and those of
+        // the others.
+        for i in CLIENTS {
+            let found = sim.block_on(sim.node(i).unwrap()
+                .find_value(&values[0].1.id(), -1, Some(LookupOption::Conservative))).unwrap();
+            assert_eq!(found.as_ref(), Some(&values[0].1));
+        }
+        let last = sim.node(sim.size() - 1).unwrap();
+        let found = sim.block_on(last.find_node(client.id(), Some(LookupOption::Conservative))).unwrap();
+        assert!(found.has_value());
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashSet;
+use std::time::Duration;
+use serial_test::serial;
+use boson::{
+    crawler::Crawler,
+    testing::TestNetwork,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_crawl() {
+        let network = TestNetwork::builder(10)
+            .with_base_port(33401)
+            .start().await
+            .unwrap();
+        assert!(network.wait_converged(Duration::from_secs(30)).await);
+        let node = network.node(0).unwrap();
+
+        let report = Crawler::new(&node)
+            .with_rate(100)
+            .with_politeness(Duration::from_millis(50))
+            .run(Duration::from_secs(60)).await
+            .unwrap();
+
+        // Every node, the crawling one as the others know it.
+        let all = (0..network.size())
+            .map(|i| *network.node(i).unwrap().id())
+            .collect::<HashSet<_>>();
+        let found = report.nodes().iter()
+            .map(|n| *n.node().id())
+            .collect::<HashSet<_>>();
+        assert_eq!(found, all);
+        assert_eq!(report.unique_nodes(), 10);
+
+        // Everyone but the crawling node was asked, and answered.
+        assert_eq!(report.queried(), 9);
+        assert_eq!(report.responsive(), 9);
+        assert_eq!(report.reachability(), 1.0);
+        assert_eq!(report.versions().values().sum::<usize>(), 9);
+        assert_eq!(report.versions().len(), 1, "{:?}", report.versions());
+        assert_eq!(report.capabilities().get("storage"), Some(&9), "{:?}", report.capabilities());
+        assert_eq!(report.capabilities().len(), 1, "{:?}", report.capabilities());
+        assert_eq!(report.address_distribution().values().sum::<usize>(), 10);
+
+        let birthday = report.birthday_estimate().unwrap();
+        assert!((5.0..=30.0).contains(&birthday), "{report}");
+        assert!((10..=30).contains(&report.estimated_size()), "{report}");
+
+        // The sweep of each node, its own id and the buckets around it.
+        assert!(report.requests() >= 9 * 5);
+
+        network.shutdown().await;
+    }
+}
@@ -0,0 +1,14 @@
+#[cfg(test)]
+mod soak;
+#[cfg(test)]
+mod lookup_progress;
+#[cfg(test)]
+mod sim;
+#[cfg(test)]
+mod capabilities;
+#[cfg(test)]
+mod pinning;
+#[cfg(all(test, feature = "crawler"))]
+mod crawler;
+
+fn main() {}
@@ -0,0 +1,83 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serial_test::serial;
+use boson::{
+    Id,
+    dht::{LookupOption, LookupProgress},
+    testing::TestNetwork,
+};
+
+// Collects the snapshots handed to the callback.
+fn collector() -> (Arc<Mutex<Vec<LookupProgress>>>, impl Fn(LookupProgress) + Send) {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let sink = seen.clone();
+    (seen, move |progress| sink.lock().unwrap().push(progress))
+}
+
+// Counts never go down, and the lookup ends with a finished snapshot.
+fn check(seen: &[LookupProgress]) {
+    assert!(!seen.is_empty());
+    for pair in seen.windows(2) {
+        assert_eq!(pair[0].network(), pair[1].network());
+        assert!(!pair[0].is_finished(), "{seen:?}");
+        assert!(pair[0].queried() <= pair[1].queried(), "{seen:?}");
+        assert!(pair[0].responded() <= pair[1].responded(), "{seen:?}");
+        assert!(pair[0].elapsed() <= pair[1].elapsed(), "{seen:?}");
+    }
+
+    let last = seen.last().unwrap();
+    assert!(last.is_finished());
+    assert!(last.queried() > 0, "{last:?}");
+    assert!(last.responded() > 0, "{last:?}");
+    assert!(last.responded() + last.timed_out() + last.failed() <= last.queried(), "{last:?}");
+    assert!(last.best_distance().is_some());
+    assert_eq!(last.candidates(), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_lookup_progress() {
+        let network = TestNetwork::builder(6)
+            .with_base_port(33301)
+            .start().await
+            .unwrap();
+        assert!(network.wait_converged(Duration::from_secs(30)).await);
+        let node = network.node(0).unwrap();
+
+        // Nothing stored under the id, the lookup runs to its end.
+        let (seen, on_progress) = collector();
+        let value = node.find_value_with_progress(&Id::random(), -1,
+            Some(LookupOption::Conservative), on_progress).await.unwrap();
+        assert!(value.is_none());
+        check(&seen.lock().unwrap());
+
+        let (seen, on_progress) = collector();
+        let found = node.find_node_with_progress(&Id::random(),
+            Some(LookupOption::Conservative), on_progress).await.unwrap();
+        assert!(!found.has_value());
+        check(&seen.lock().unwrap());
+
+        // Known to the routing table, no lookup runs at all.
+        let (seen, on_progress) = collector();
+        let target = network.node(3).unwrap().id().clone();
+        let found = node.find_node_with_progress(&target,
+            Some(LookupOption::Conservative), on_progress).await.unwrap();
+        assert!(found.has_value());
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(seen[0].is_finished());
+        assert_eq!(seen[0].queried(), 0);
+
+        let (seen, on_progress) = collector();
+        let peers = node.find_peer_with_progress(&Id::random(), -1, 4,
+            Some(LookupOption::Conservative), on_progress).await.unwrap();
+        assert!(peers.is_empty());
+        check(&seen.lock().unwrap());
+
+        network.shutdown().await;
+    }
+}
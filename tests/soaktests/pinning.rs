@@ -0,0 +1,106 @@
+use std::time::Duration;
+use boson::{
+    Id,
+    Value,
+    core::ImmutableBuilder as ValueBuilder,
+    testing::{LatencyModel, SimNetwork},
+};
+
+const NODES: usize = 16;
+// Refuse far stores, the first ones with a small bucket capacity.
+const STRICT: std::ops::Range<usize> = 8..NODES;
+const SMALL_BUCKETS: std::ops::Range<usize> = 8..12;
+// The storers pick the nodes closest to the value up to a full bucket.
+const STORE_SET: usize = 8;
+
+// Node 0 may have the strict nodes pin its values.
+fn network(seed: u64) -> SimNetwork {
+    let sim = SimNetwork::new(seed)
+        .with_latency(LatencyModel::uniform(Duration::from_millis(5), Duration::from_millis(80)))
+        .add_nodes(STRICT.start)
+        .expect("Failed to start the simulated nodes");
+
+    let pinner = *sim.node(0).unwrap().id();
+    let strict = format!("rejectFarStores: true\nacceptPinRequestsFrom:\n  - {pinner}\n");
+    sim.with_config(&format!("bucketCapacity: 2\n{strict}"))
+        .add_nodes(SMALL_BUCKETS.len())
+        .and_then(|sim| sim.with_config(&strict).add_nodes(STRICT.end - SMALL_BUCKETS.end))
+        .expect("Failed to start the simulated nodes")
+}
+
+// The nodes holding the value in their own storage, by index.
+fn holders(sim: &SimNetwork, value_id: &Id) -> Vec<usize> {
+    sim.nodes().iter().enumerate()
+        .filter(|(_, node)| node.value(*value_id).unwrap().is_some())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// The nodes of the network closest to the target, by index.
+fn closest(sim: &SimNetwork, target: &Id, count: usize) -> Vec<usize> {
+    let mut indexes = (0..sim.size()).collect::<Vec<_>>();
+    indexes.sort_by(|a, b| {
+        target.three_way_compare(sim.node(*a).unwrap().id(), sim.node(*b).unwrap().id())
+    });
+    indexes.truncate(count);
+    indexes
+}
+
+// A value a full bucket of the nodes the node knows is closer to.
+fn far_value(sim: &SimNetwork, index: usize) -> Value {
+    let node = sim.node(index).unwrap();
+    for i in 0u32.. {
+        let value = ValueBuilder::new(format!("far value {i}").as_bytes()).build().unwrap();
+        let value_id = value.id();
+        let known = sim.block_on(node.closest_nodes(&value_id, STORE_SET, None, false)).unwrap();
+        if known.len() == STORE_SET && known.iter().all(|ni| value_id.three_way_compare(ni.id(), node.id()).is_lt()) {
+            return value;
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stores_across_bucket_capacities() {
+        let sim = network(17);
+        sim.run_until(Duration::from_secs(10 * 60));
+
+        // Stored by nodes of either bucket capacity, strict or not, the
+        // strict nodes of the store set keep the value.
+        for from in [0, 3, SMALL_BUCKETS.start, STRICT.end - 1] {
+            let value = ValueBuilder::new(format!("stored by node {from}").as_bytes()).build().unwrap();
+            sim.block_on(sim.node(from).unwrap().store_value(&value, -1, false)).unwrap();
+            sim.run_for(Duration::from_secs(10));
+
+            let holders = holders(&sim, &value.id());
+            let expected = closest(&sim, &value.id(), STORE_SET).into_iter()
+                .filter(|i| *i != from)
+                .collect::<Vec<_>>();
+            assert!(expected.iter().any(|i| STRICT.contains(i)), "{expected:?}");
+            assert!(expected.iter().all(|i| holders.contains(i)), "{holders:?} {expected:?}");
+        }
+    }
+
+    #[test]
+    fn test_far_pins() {
+        let sim = network(19);
+        sim.run_until(Duration::from_secs(10 * 60));
+
+        let target = STRICT.end - 1;
+        let target_info = sim.node(target).unwrap().node_info();
+
+        // Not on the allowlist, the strict node keeps to the values close to it.
+        let value = far_value(&sim, target);
+        let results = sim.block_on(sim.node(1).unwrap().pin_value_on(&value, &[target_info.clone()])).unwrap();
+        assert!(results[0].1.is_err());
+        assert!(!holders(&sim, &value.id()).contains(&target));
+
+        let results = sim.block_on(sim.node(0).unwrap().pin_value_on(&value, &[target_info])).unwrap();
+        assert!(results[0].1.is_ok(), "Pin refused: {:?}", results[0].1);
+        assert!(holders(&sim, &value.id()).contains(&target));
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+use boson::{
+    Id,
+    core::ImmutableBuilder as ValueBuilder,
+    dht::LookupOption,
+    testing::{LatencyModel, SimNetwork},
+};
+
+const NODES: usize = 12;
+
+fn network(seed: u64) -> SimNetwork {
+    SimNetwork::new(seed)
+        .with_latency(LatencyModel::uniform(Duration::from_millis(5), Duration::from_millis(80)))
+        .add_nodes(NODES)
+        .expect("Failed to start the simulated nodes")
+}
+
+// The nodes holding the value in their own storage, by index.
+fn holders(sim: &SimNetwork, value_id: &Id) -> Vec<usize> {
+    sim.nodes().iter().enumerate()
+        .filter(|(_, node)| node.value(*value_id).unwrap().is_some())
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Lets the nodes join through the first one, then has every node look up
+// every other until all are found, as a node does now and then. Returns the
+// nodes found by each node.
+fn converge(seed: u64) -> Vec<Vec<Id>> {
+    let sim = network(seed);
+    sim.run_until(Duration::from_secs(5 * 60));
+
+    let mut found = vec![Vec::new(); NODES];
+    while sim.now() < Duration::from_secs(60 * 60) {
+        for (i, from) in sim.nodes().iter().enumerate() {
+            for to in sim.nodes().iter().filter(|n| n.id() != from.id()) {
+                if found[i].contains(to.id()) {
+                    continue;
+                }
+                let result = sim.block_on(from.find_node(to.id(), Some(LookupOption::Conservative))).unwrap();
+                if result.has_value() {
+                    found[i].push(*to.id());
+                }
+            }
+        }
+        if found.iter().all(|f| f.len() == NODES - 1) {
+            break;
+        }
+        sim.run_for(Duration::from_secs(60));
+    }
+
+    found.iter_mut().for_each(|f| f.sort());
+    found
+}
+
+// Stores a persistent value and an ephemeral one, then runs past the age of
+// the values. Returns whether the owner still holds the persistent value, and
+// the nodes still holding the ephemeral one.
+fn refresh(seed: u64) -> (bool, Vec<usize>) {
+    let sim = network(seed);
+    sim.run_until(Duration::from_secs(5 * 60));
+
+    let persistent = ValueBuilder::new(b"kept alive by its owner").build().unwrap();
+    let ephemeral = ValueBuilder::new(b"left to expire").build().unwrap();
+    sim.block_on(sim.node(1).unwrap().store_value(&persistent, -1, true)).unwrap();
+    sim.block_on(sim.node(2).unwrap().store_value(&ephemeral, -1, false)).unwrap();
+
+    sim.run_for(Duration::from_secs(60));
+    assert!(holders(&sim, &ephemeral.id()).contains(&2));
+
+    sim.run_until(Duration::from_secs(150 * 60));
+    (holders(&sim, &persistent.id()).contains(&1), holders(&sim, &ephemeral.id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sim_bootstrap_converges() {
+        let found = converge(7);
+        assert!(found.iter().all(|f| f.len() == NODES - 1));
+        assert_eq!(converge(7), found);
+    }
+
+    #[test]
+    fn test_sim_value_refresh() {
+        let result = refresh(11);
+        assert_eq!(result, (true, vec![]));
+        assert_eq!(refresh(11), result);
+    }
+}
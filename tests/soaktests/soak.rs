@@ -0,0 +1,72 @@
+use std::time::Duration;
+use serial_test::serial;
+use boson::testing::{
+    ChurnDriver,
+    FaultProfile,
+    Soak,
+    TestNetwork,
+    Workload,
+};
+
+fn faults(loss: f64) -> FaultProfile {
+    FaultProfile::lossy(loss)
+        .with_duplication(0.05)
+        .with_delay(0.1, Duration::from_millis(5), Duration::from_millis(50))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Short enough for CI: 6 nodes, 5% loss, one node churned every 3s.
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_soak_short() {
+        let network = TestNetwork::builder(6)
+            .with_base_port(33101)
+            .with_faults(faults(0.05))
+            .with_clock_skew(Duration::from_secs(30))
+            .start().await
+            .unwrap();
+
+        let soak = Soak::new(
+            Duration::from_secs(20),
+            ChurnDriver::new(Duration::from_secs(3), 0.2),
+            Workload::new(Duration::from_millis(200)),
+        ).with_settle(Duration::from_secs(60));
+        let report = soak.run(&network).await;
+        network.shutdown().await;
+
+        println!("soak report: {report}");
+        assert!(report.churn.stops > 0);
+        assert!(report.workload.lookups > 0);
+        assert!(report.transport.dropped > 0);
+        // A lookup racing a churned node and lost packets misses now and
+        // then, the bar is set to catch regressions rather than that noise.
+        report.check(0.5).unwrap();
+    }
+
+    // Run with: cargo test --features testing --test soaktests -- --ignored
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    #[ignore]
+    async fn test_soak_long() {
+        let network = TestNetwork::builder(24)
+            .with_base_port(33201)
+            .with_faults(faults(0.2).with_inbound_loss(0.05))
+            .with_clock_skew(Duration::from_secs(120))
+            .start().await
+            .unwrap();
+
+        let soak = Soak::new(
+            Duration::from_secs(30 * 60),
+            ChurnDriver::new(Duration::from_secs(5), 0.2),
+            Workload::new(Duration::from_millis(100)),
+        ).with_settle(Duration::from_secs(120));
+        let report = soak.run(&network).await;
+        network.shutdown().await;
+
+        println!("soak report: {report}");
+        report.check(0.5).unwrap();
+    }
+}